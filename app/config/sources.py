@@ -0,0 +1,238 @@
+"""
+Layered configuration sources
+
+Every setting is read from the first layer that sets it, from the highest precedence:
+
+1. PAMP_<NAME> environment variables
+2. <NAME> environment variables, the names read before the prefix was introduced
+3. the .env file
+4. the profile selected by APP_ENV, app/config/profiles/<APP_ENV>.toml or the same file in APP_PROFILES_DIR
+5. the defaults of Settings
+
+In the variable layers, <NAME>_FILE names a file holding the value instead, for secrets mounted as files; setting
+both is an error. The layered values are validated together into Settings and every mistake is reported with the
+setting and the source it came from: unknown profile keys and PAMP_ variables, unreadable secret files and invalid
+values. Startup fails with all of them.
+"""
+
+import difflib
+import os
+import tomllib
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Any, Dict, List, Mapping, Optional, Tuple
+from urllib.parse import urlsplit, urlunsplit
+
+from dotenv import dotenv_values
+from pydantic import SecretStr, ValidationError
+
+from app.config.config import Settings
+
+ENV_PREFIX = "PAMP_"
+FILE_SUFFIX = "_FILE"
+ENV_FILE = Settings.model_config.get("env_file") or ".env"
+PROFILES_DIRECTORY = Path(__file__).with_name("profiles")
+# Settings choosing the profile, read from the variable layers only
+PROFILE_SETTINGS = ("app_env", "app_profiles_dir")
+
+# Settings redacted when the configuration is printed, besides the SecretStr ones
+SECRET_SETTINGS = ("postgres_password", "aws_access_key_id", "aws_secret_access_key")
+# Settings holding URLs whose password is redacted
+URL_SETTINGS = ("database_url",)
+REDACTED = "********"
+
+
+@dataclass(frozen=True)
+class SettingValue:
+    """Raw value of a setting and where it was read"""
+
+    value: Any
+    source: str  # e.g. "default", "profile app/config/profiles/prod.toml" or "environment PAMP_PORT"
+
+
+class ConfigurationError(ValueError):
+    """Invalid configuration, with every setting at fault and its source"""
+
+    def __init__(self, errors: List[str]):
+        self.errors = errors
+        super().__init__("Invalid configuration:\n" + "\n".join(f"  - {error}" for error in errors))
+
+
+def closest(name: str, candidates) -> str:
+    """Suffix of an error naming the closest candidate, empty when none is close"""
+    matches = difflib.get_close_matches(name, list(candidates), n=1)
+    return f", did you mean {matches[0]}?" if matches else ""
+
+
+def variable_layer(
+    variables: Mapping[str, Optional[str]], label: str, prefix: str, errors: List[str]
+) -> Dict[str, SettingValue]:
+    """
+    Settings of a layer of variables, names matched case-insensitively like the settings always were
+
+    Args:
+        variables: Variables of the layer, the environment or the .env file
+        label: Label of the layer in the sources, "environment" or the file
+        prefix: Prefix of the setting names in the variables
+        errors: Where the mistakes are appended
+    """
+    by_name = {name.upper(): (name, value) for name, value in variables.items() if value is not None}
+    values: Dict[str, SettingValue] = {}
+    for field in Settings.model_fields:
+        key = prefix + field.upper()
+        direct, indirect = by_name.get(key), by_name.get(key + FILE_SUFFIX)
+        if direct and indirect:
+            errors.append(f"{field} ({label}): both {direct[0]} and {indirect[0]} are set, set only one of them")
+        elif indirect:
+            name, path = indirect
+            try:
+                value = Path(path).read_text(encoding="utf-8").rstrip("\r\n")
+            except OSError as e:
+                errors.append(f"{field} ({label} {name}): cannot read {path}: {e.strerror or str(e)}")
+                continue
+            values[field] = SettingValue(value, f"file {path} ({label} {name})")
+        elif direct:
+            values[field] = SettingValue(direct[1], f"{label} {direct[0]}")
+    return values
+
+
+def unknown_prefixed_variables(environ: Mapping[str, str], errors: List[str]) -> None:
+    """Report PAMP_ variables naming no setting, except the unprefixed names of the pamp_ settings"""
+    fields = [field.upper() for field in Settings.model_fields]
+    known = set(fields) | {ENV_PREFIX + field for field in fields}
+    known |= {name + FILE_SUFFIX for name in known}
+    for name in sorted(environ):
+        if name.upper().startswith(ENV_PREFIX) and name.upper() not in known:
+            errors.append(f"{name} (environment): unknown setting{closest(name.upper(), known)}")
+
+
+def profile_path(app_env: str, profiles_dir: Optional[str] = None) -> Path:
+    """File of a profile"""
+    return Path(profiles_dir or PROFILES_DIRECTORY) / f"{app_env}.toml"
+
+
+def profile_layer(path: Path, errors: List[str]) -> Dict[str, SettingValue]:
+    """Settings of a profile file, keyed by setting name"""
+    label = f"profile {path}"
+    try:
+        with open(path, "rb") as f:
+            document = tomllib.load(f)
+    except (OSError, tomllib.TOMLDecodeError) as e:
+        errors.append(f"app_env ({label}): cannot be read: {str(e)}")
+        return {}
+
+    values: Dict[str, SettingValue] = {}
+    for key, value in document.items():
+        if key in PROFILE_SETTINGS:
+            errors.append(f"{key} ({label}): chooses the profile, it can only be set by a variable")
+        elif key not in Settings.model_fields:
+            errors.append(f"{key} ({label}): unknown setting{closest(key, Settings.model_fields)}")
+        elif isinstance(value, dict):
+            errors.append(f"{key} ({label}): expected a value, got a table")
+        else:
+            values[key] = SettingValue(value, label)
+    return values
+
+
+def load_layers(
+    environ: Optional[Mapping[str, str]] = None, env_file: Optional[str] = ENV_FILE
+) -> Tuple[Dict[str, SettingValue], List[str]]:
+    """
+    Value and source of every setting, with the mistakes found while reading the layers
+
+    Args:
+        environ: Environment variables, those of the process by default
+        env_file: The .env file, read when it exists, None to ignore it
+    """
+    environ = os.environ if environ is None else environ
+    errors: List[str] = []
+    variable_layers = [
+        variable_layer(environ, "environment", ENV_PREFIX, errors),
+        variable_layer(environ, "environment", "", errors),
+    ]
+    if env_file and Path(env_file).is_file():
+        variable_layers.append(variable_layer(dotenv_values(env_file), str(env_file), "", errors))
+    unknown_prefixed_variables(environ, errors)
+
+    def first(field: str) -> Optional[SettingValue]:
+        return next((layer[field] for layer in variable_layers if field in layer), None)
+
+    layers = list(variable_layers)
+    app_env = first("app_env")
+    if app_env is not None and app_env.value:
+        directory = first("app_profiles_dir")
+        path = profile_path(app_env.value, directory.value if directory else None)
+        if path.is_file():
+            layers.append(profile_layer(path, errors))
+        else:
+            profiles = [profile.stem for profile in path.parent.glob("*.toml")]
+            errors.append(f"app_env ({app_env.source}): no profile file {path}{closest(app_env.value, profiles)}")
+
+    values = {}
+    for name, field in Settings.model_fields.items():
+        value = next((layer[name] for layer in layers if name in layer), None)
+        values[name] = value or SettingValue(field.get_default(call_default_factory=True), "default")
+    return values, errors
+
+
+def load_settings_with_sources(
+    environ: Optional[Mapping[str, str]] = None, env_file: Optional[str] = ENV_FILE
+) -> Tuple[Settings, Dict[str, SettingValue]]:
+    """
+    Settings of the layered sources, validated together, with the value and source of each
+
+    Raises:
+        ConfigurationError: Naming every setting at fault with its source
+    """
+    values, errors = load_layers(environ, env_file)
+    try:
+        # Every setting is given, Settings reads neither the environment nor .env itself
+        settings = Settings(_env_file=None, **{name: value.value for name, value in values.items()})
+    except ValidationError as e:
+        for error in e.errors():
+            name = str(error["loc"][0]) if error["loc"] else ""
+            source = values[name].source if name in values else "settings"
+            errors.append(f"{name} ({source}): {error['msg']}")
+    if errors:
+        raise ConfigurationError(errors)
+    return settings, values
+
+
+def load_settings(environ: Optional[Mapping[str, str]] = None, env_file: Optional[str] = ENV_FILE) -> Settings:
+    """
+    Settings of the layered sources, validated together
+
+    Raises:
+        ConfigurationError: Naming every setting at fault with its source
+    """
+    return load_settings_with_sources(environ, env_file)[0]
+
+
+def redact(name: str, value: Any) -> Any:
+    """Value of a setting as printed: secrets hidden once set, the password of URLs hidden"""
+    if value is None or value == "":
+        return value
+    if isinstance(value, SecretStr) or name in SECRET_SETTINGS:
+        return REDACTED
+    if name in URL_SETTINGS:
+        parts = urlsplit(str(value))
+        if parts.password:
+            netloc = parts.netloc.replace(f":{parts.password}@", f":{REDACTED}@", 1)
+            return urlunsplit(parts._replace(netloc=netloc))
+    return value
+
+
+def redacted_config(
+    environ: Optional[Mapping[str, str]] = None, env_file: Optional[str] = ENV_FILE
+) -> Dict[str, Dict[str, Any]]:
+    """
+    Effective value and source of every setting, secrets redacted
+
+    Raises:
+        ConfigurationError: Like load_settings
+    """
+    settings, values = load_settings_with_sources(environ, env_file)
+    return {
+        name: {"value": redact(name, getattr(settings, name)), "source": values[name].source}
+        for name in Settings.model_fields
+    }
@@ -0,0 +1,219 @@
+import logging
+from typing import List
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, HTTPException, Query
+from sqlmodel import Session
+
+from app.domains.admin.admin_stats_service import AdminStatsService
+from app.domains.admin.dto.admin_stats_dto import AdminStatsDto
+from app.domains.admin.dto.config_reload_dto import ConfigReloadDto
+from app.domains.admin.dto.log_filter_dto import LogFilterDto, LogFilterResponseDto
+from app.domains.admin.dto.tokenizer_config_dto import TokenizerConfigDto
+from app.domains.audit.audit_hook import audited
+from app.domains.audit.audit_models import AuditAction
+from app.domains.quotas.dto.quota_dto import ProjectQuotaDto
+from app.domains.quotas.quota_service import QuotaService
+from app.domains.reports.dto.report_dto import PseudonymMappingDto
+from app.domains.reports.report_service import ReportService
+from app.domains.reports.reports_controller import get_report_service
+from app.domains.tenants.dto.tenant_dto import TenantProjectDto
+from app.domains.tenants.tenants_service import TenantService
+from app.shared.config_reload import CONFIG_RELOADER, ConfigChangeRejected, ConfigReloadError
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException, ValidationException
+from app.shared.security import require_admin_scope
+from app.shared.tracing import LOG_FILTER, configured_log_filter
+
+router = APIRouter(prefix="/admin", tags=["admin"], dependencies=[Depends(require_admin_scope)])
+
+
+def get_admin_stats_service(session: Session = Depends(get_session)) -> AdminStatsService:
+    """Dependency to get admin statistics service"""
+    return AdminStatsService(session)
+
+
+@router.get("/stats", response_model=AdminStatsDto)
+async def get_admin_stats(
+    refresh: bool = Query(False, description="Recompute the statistics instead of serving the last snapshot"),
+    service: AdminStatsService = Depends(get_admin_stats_service),
+):
+    """
+    Get per-project and total counts of submissions, stored files and bytes, fingerprint cache entries and
+    detection runs by status
+
+    Statistics are served from a snapshot recomputed every ADMIN_STATS_MAX_AGE_SECONDS.
+    """
+    try:
+        return service.get_stats(refresh=refresh)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=f"Failed to compute statistics: {str(e)}")
+
+
+def get_quota_service(session: Session = Depends(get_session)) -> QuotaService:
+    """Dependency to get quota service"""
+    return QuotaService(session)
+
+
+@router.get("/quotas", response_model=List[ProjectQuotaDto])
+async def list_project_quotas(service: QuotaService = Depends(get_quota_service)):
+    """
+    Get the usage against their quotas of the projects with stored submissions, runs over the last 24 hours or
+    quotas set by PROJECT_QUOTAS
+    """
+    try:
+        return service.list_project_quotas()
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/quotas/{project_uuid}", response_model=ProjectQuotaDto)
+async def get_project_quota(project_uuid: UUID, service: QuotaService = Depends(get_quota_service)):
+    """Get the usage of a project against each of its quotas, a limit of 0 being unlimited"""
+    try:
+        return service.get_project_quota(project_uuid)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get(
+    "/runs/{run_id}/pseudonyms",
+    response_model=PseudonymMappingDto,
+    dependencies=[Depends(audited(AuditAction.PSEUDONYM_ACCESS, "run", "run_id"))],
+)
+async def get_run_pseudonyms(run_id: UUID, service: ReportService = Depends(get_report_service)):
+    """Get the submitter, submission or group behind each pseudonym of the anonymized reports of a run"""
+    try:
+        return service.get_pseudonym_mapping(run_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+def get_tenant_service(session: Session = Depends(get_session)) -> TenantService:
+    """Dependency to get tenant service"""
+    return TenantService(session)
+
+
+@router.get("/tenants/{tenant_id}/projects", response_model=List[TenantProjectDto])
+async def list_tenant_projects(tenant_id: str, service: TenantService = Depends(get_tenant_service)):
+    """Get the projects assigned to a tenant, the only ones its requests reach while TENANCY_ENABLED is set"""
+    try:
+        return service.list_projects(tenant_id)
+    except ValidationException as e:
+        raise HTTPException(status_code=422, detail=str(e.detail))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put(
+    "/tenants/{tenant_id}/projects/{project_uuid}",
+    response_model=TenantProjectDto,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "tenant_project", "project_uuid"))],
+)
+async def assign_tenant_project(
+    tenant_id: str, project_uuid: UUID, service: TenantService = Depends(get_tenant_service)
+):
+    """
+    Assign a project to a tenant, moving it from its previous tenant if any
+
+    The submissions, runs, reports and configurations of the project are then only found by the requests of the
+    tenant. Requests of a tenant cannot create anything in a project that is not assigned to it.
+    """
+    try:
+        return service.assign_project(tenant_id, project_uuid)
+    except ValidationException as e:
+        raise HTTPException(status_code=422, detail=str(e.detail))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+def log_filter_response() -> LogFilterResponseDto:
+    root_level, levels = LOG_FILTER.levels()
+    levels = {"root": root_level, **levels}
+    return LogFilterResponseDto(
+        filter=LOG_FILTER.spec, levels={name: logging.getLevelName(level) for name, level in levels.items()}
+    )
+
+
+@router.get("/logging", response_model=LogFilterResponseDto)
+async def get_log_filter():
+    """Get the log filter applied to the loggers"""
+    return log_filter_response()
+
+
+@router.put(
+    "/logging",
+    response_model=LogFilterResponseDto,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "log_filter"))],
+)
+async def set_log_filter(log_filter: LogFilterDto):
+    """Replace the log filter until the next reload or restart, loggers it no longer names follow the default level"""
+    try:
+        LOG_FILTER.apply(log_filter.filter)
+    except ValueError as e:
+        raise HTTPException(status_code=422, detail=str(e))
+    return log_filter_response()
+
+
+@router.post(
+    "/logging/reload",
+    response_model=LogFilterResponseDto,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "log_filter"))],
+)
+async def reload_log_filter():
+    """Apply the log filter of the configuration again, LOG_FILTER as currently set by its layered sources"""
+    from app.config.sources import load_settings
+
+    try:
+        LOG_FILTER.apply(configured_log_filter(load_settings()))
+    except ValueError as e:
+        raise HTTPException(status_code=422, detail=f"Invalid LOG_FILTER: {str(e)}")
+    return log_filter_response()
+
+
+@router.get("/tokenizer-config", response_model=TokenizerConfigDto)
+async def get_effective_tokenizer_config():
+    """
+    Get the effective per-language tokenizer configuration, the built-in one merged with TOKENIZER_CONFIG_PATH
+
+    The tokenizer version of each language is the one its cached fingerprints are keyed with.
+    """
+    from app.domains.tokenization.tokenization_service import TOKENIZER_VERSION
+    from app.domains.tokenization.tokenizer_config import get_tokenizer_config
+
+    return get_tokenizer_config().to_dict(TOKENIZER_VERSION)
+
+
+@router.post(
+    "/config/reload",
+    response_model=ConfigReloadDto,
+    responses={
+        409: {"description": "A setting that cannot change at runtime differs, nothing was reloaded"},
+        422: {"description": "The configuration is invalid, nothing was reloaded"},
+    },
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "settings"))],
+)
+async def reload_config():
+    """
+    Read the environment, .env and TOKENIZER_CONFIG_PATH again and apply the settings that can change at runtime
+
+    Thresholds, limits, worker counts, the log filter and the tokenizer configuration are swapped as a whole, runs
+    in flight keep the configuration they started with. Changes to any other setting, e.g. DATABASE_URL or
+    STORAGE_BACKEND, reject the whole reload.
+    """
+    try:
+        reload = CONFIG_RELOADER.reload("POST /admin/config/reload")
+    except ConfigChangeRejected as e:
+        raise HTTPException(status_code=409, detail=str(e))
+    except ConfigReloadError as e:
+        raise HTTPException(status_code=422, detail=str(e))
+    return ConfigReloadDto(
+        changed=list(reload.changed),
+        tokenizer_config_reloaded=reload.tokenizer_config_reloaded,
+        tokenizer_config_revision=reload.tokenizer_config_revision,
+        message=f"{len(reload.changed)} settings changed" if reload.changed else "No setting changed",
+    )
@@ -0,0 +1,22 @@
+from datetime import datetime
+
+from sqlmodel import JSON, Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+GLOBAL_SNAPSHOT = "global"
+
+
+class AdminStatsSnapshot(SQLModel, table=True):
+    """Database model for the last computed usage statistics, shared by every instance of the service"""
+
+    __tablename__ = "admin_stats_snapshot"
+
+    name: str = Field(default=GLOBAL_SNAPSHOT, primary_key=True, max_length=50, description="Name of the snapshot")
+    computed_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the statistics were computed",
+    )
+    duration_seconds: float = Field(default=0.0, description="Time taken to compute the statistics")
+    data: dict = Field(default_factory=dict, sa_column=Column(JSON), description="Computed statistics")
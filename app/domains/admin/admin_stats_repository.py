@@ -0,0 +1,76 @@
+import logging
+from typing import Dict, Optional
+
+from sqlalchemy import func
+from sqlmodel import Session, select
+
+from app.domains.admin.admin_stats_models import GLOBAL_SNAPSHOT, AdminStatsSnapshot
+from app.domains.runs.runs_models import DetectionRun
+from app.domains.submissions.submissions_models import Submission
+from app.shared.exceptions import DatabaseException
+
+logger = logging.getLogger(__name__)
+
+
+class AdminStatsRepository:
+    """Repository aggregating database resources per project and holding the statistics snapshot"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def count_submissions_by_project(self) -> Dict[str, dict]:
+        """Submission count and oldest/newest creation time of each project"""
+        try:
+            statement = select(
+                Submission.project_uuid,
+                func.count(Submission.id),
+                func.min(Submission.created_at),
+                func.max(Submission.created_at),
+            ).group_by(Submission.project_uuid)
+            return {
+                str(project_uuid): {"submissions": count, "oldest": oldest, "newest": newest}
+                for project_uuid, count, oldest, newest in self.session.exec(statement).all()
+            }
+        except Exception as e:
+            raise DatabaseException(f"Failed to count submissions by project: {str(e)}")
+
+    def count_runs_by_project(self) -> Dict[str, dict]:
+        """Run count per status and oldest/newest start time of each project"""
+        try:
+            statement = select(
+                DetectionRun.project_uuid,
+                DetectionRun.status,
+                func.count(DetectionRun.id),
+                func.min(DetectionRun.started_at),
+                func.max(DetectionRun.started_at),
+            ).group_by(DetectionRun.project_uuid, DetectionRun.status)
+            rows = self.session.exec(statement).all()
+        except Exception as e:
+            raise DatabaseException(f"Failed to count detection runs by project: {str(e)}")
+
+        projects = {}
+        for project_uuid, status, count, oldest, newest in rows:
+            project = projects.setdefault(str(project_uuid), {"runs_by_status": {}, "oldest": oldest, "newest": newest})
+            status = status.value if hasattr(status, "value") else str(status)
+            project["runs_by_status"][status] = count
+            project["oldest"] = min(project["oldest"], oldest)
+            project["newest"] = max(project["newest"], newest)
+        return projects
+
+    def get_snapshot(self, name: str = GLOBAL_SNAPSHOT) -> Optional[AdminStatsSnapshot]:
+        """Get the last computed statistics, None if they were never computed"""
+        try:
+            return self.session.get(AdminStatsSnapshot, name)
+        except Exception as e:
+            raise DatabaseException(f"Failed to get statistics snapshot: {str(e)}")
+
+    def save_snapshot(self, snapshot: AdminStatsSnapshot) -> AdminStatsSnapshot:
+        """Replace the stored statistics snapshot"""
+        try:
+            snapshot = self.session.merge(snapshot)
+            self.session.commit()
+            self.session.refresh(snapshot)
+            return snapshot
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save statistics snapshot: {str(e)}")
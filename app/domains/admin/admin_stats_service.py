@@ -0,0 +1,127 @@
+import logging
+import time
+from typing import Optional
+
+from sqlmodel import Session
+
+from app.domains.admin.admin_stats_models import AdminStatsSnapshot
+from app.domains.admin.admin_stats_repository import AdminStatsRepository
+from app.domains.admin.dto.admin_stats_dto import (
+    AdminStatsDto,
+    FingerprintCacheStatsDto,
+    ProjectStatsDto,
+    StorageUsageDto,
+)
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.shared.timestamps import as_utc, utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class AdminStatsService:
+    """
+    Service computing the usage statistics of the service for capacity planning
+
+    Statistics are aggregated from the database and the storage backend at most once per max_age_seconds and
+    kept as a snapshot in the database, so requests read the snapshot rather than scanning the store.
+    """
+
+    def __init__(
+        self,
+        session: Session,
+        storage_service: Optional[SubmissionStorageService] = None,
+        fingerprint_service=None,
+        max_age_seconds: Optional[float] = None,
+    ):
+        self.repository = AdminStatsRepository(session)
+        self.storage_service = storage_service or SubmissionStorageService()
+        if fingerprint_service is None:
+            from app.shared.services import get_fingerprint_service
+
+            fingerprint_service = get_fingerprint_service()
+        self.fingerprint_service = fingerprint_service
+        if max_age_seconds is None:
+            from app.config.config import get_settings
+
+            max_age_seconds = get_settings().admin_stats_max_age_seconds
+        self.max_age_seconds = max_age_seconds
+
+    def get_stats(self, refresh: bool = False) -> AdminStatsDto:
+        """
+        Get the usage statistics, recomputing them when forced or when the snapshot is older than the max age
+        """
+        snapshot = self.repository.get_snapshot()
+        if refresh or snapshot is None or self._age_seconds(snapshot) > self.max_age_seconds:
+            snapshot = self.refresh()
+
+        return AdminStatsDto(
+            **snapshot.data,
+            computed_at=self._computed_at(snapshot),
+            age_seconds=self._age_seconds(snapshot),
+            duration_seconds=snapshot.duration_seconds,
+        )
+
+    def refresh(self) -> AdminStatsSnapshot:
+        """Recompute the statistics and replace the snapshot"""
+        started = time.monotonic()
+        submissions = self.repository.count_submissions_by_project()
+        runs = self.repository.count_runs_by_project()
+        usage = self.storage_service.get_usage()
+        cache = self.fingerprint_service.get_cache_info()
+
+        projects = []
+        for project_uuid in sorted(set(submissions) | set(runs) | set(usage["projects"])):
+            project_submissions = submissions.get(project_uuid, {})
+            project_runs = runs.get(project_uuid, {})
+            timestamps = [
+                value
+                for value in (
+                    project_submissions.get("oldest"),
+                    project_submissions.get("newest"),
+                    project_runs.get("oldest"),
+                    project_runs.get("newest"),
+                )
+                if value is not None
+            ]
+            projects.append(
+                ProjectStatsDto(
+                    project_uuid=project_uuid,
+                    submissions=project_submissions.get("submissions", 0),
+                    storage=StorageUsageDto(**usage["projects"].get(project_uuid, {})),
+                    runs_by_status=project_runs.get("runs_by_status", {}),
+                    oldest_resource_at=min(timestamps) if timestamps else None,
+                    newest_resource_at=max(timestamps) if timestamps else None,
+                )
+            )
+
+        runs_by_status = {}
+        for project in projects:
+            for status, count in project.runs_by_status.items():
+                runs_by_status[status] = runs_by_status.get(status, 0) + count
+        oldest = [p.oldest_resource_at for p in projects if p.oldest_resource_at]
+        newest = [p.newest_resource_at for p in projects if p.newest_resource_at]
+
+        data = {
+            "submissions": sum(p.submissions for p in projects),
+            "storage": StorageUsageDto(
+                files=usage["files"], logical_bytes=usage["logical_bytes"], physical_bytes=usage["physical_bytes"]
+            ).model_dump(mode="json"),
+            "blobs": usage["blobs"],
+            "fingerprint_cache": FingerprintCacheStatsDto(
+                enabled=cache["enabled"],
+                backend=cache["backend"],
+                entries=cache["entries"],
+                size_bytes=cache.get("size_bytes", 0),
+            ).model_dump(mode="json"),
+            "runs_by_status": runs_by_status,
+            "oldest_resource_at": to_rfc3339(min(oldest)) if oldest else None,
+            "newest_resource_at": to_rfc3339(max(newest)) if newest else None,
+            "projects": [project.model_dump(mode="json") for project in projects],
+        }
+        duration = time.monotonic() - started
+        snapshot = self.repository.save_snapshot(AdminStatsSnapshot(data=data, duration_seconds=duration))
+        logger.info(f"Computed admin statistics for {len(projects)} projects in {duration:.2f}s")
+        return snapshot
+
+    def _age_seconds(self, snapshot: AdminStatsSnapshot) -> float:
+        return max(0.0, (utc_now() - as_utc(snapshot.computed_at)).total_seconds())
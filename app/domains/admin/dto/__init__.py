@@ -0,0 +1,8 @@
+from .admin_stats_dto import AdminStatsDto, FingerprintCacheStatsDto, ProjectStatsDto, StorageUsageDto
+
+__all__ = [
+    "AdminStatsDto",
+    "FingerprintCacheStatsDto",
+    "ProjectStatsDto",
+    "StorageUsageDto",
+]
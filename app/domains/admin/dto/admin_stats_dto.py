@@ -0,0 +1,77 @@
+from typing import Dict, List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class StorageUsageDto(BaseModel):
+    """DTO for the stored files of a project or of the whole service"""
+
+    files: int = 0
+    logical_bytes: int = 0
+    physical_bytes: int = 0
+
+
+class ProjectStatsDto(BaseModel):
+    """DTO for the resource counts of one project"""
+
+    project_uuid: UUID
+    submissions: int = 0
+    storage: StorageUsageDto
+    runs_by_status: Dict[str, int] = {}
+    oldest_resource_at: Optional[UtcTimestamp] = None
+    newest_resource_at: Optional[UtcTimestamp] = None
+
+
+class FingerprintCacheStatsDto(BaseModel):
+    """DTO for the size of the fingerprint cache"""
+
+    enabled: bool
+    backend: Optional[str] = None
+    entries: int = 0
+    size_bytes: int = 0
+
+
+class AdminStatsDto(BaseModel):
+    """DTO for the usage statistics of the service"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "computed_at": "2024-01-15T10:30:00+01:00",
+                "age_seconds": 120.5,
+                "duration_seconds": 3.2,
+                "submissions": 240,
+                "storage": {"files": 9600, "logical_bytes": 1048576000, "physical_bytes": 157286400},
+                "blobs": 2100,
+                "fingerprint_cache": {"enabled": True, "backend": "lmdb", "entries": 2050, "size_bytes": 48234496},
+                "runs_by_status": {"completed": 230, "failed": 2},
+                "oldest_resource_at": "2023-09-01T08:00:00+02:00",
+                "newest_resource_at": "2024-01-15T10:25:00+01:00",
+                "projects": [
+                    {
+                        "project_uuid": "550e8400-e29b-41d4-a716-446655440000",
+                        "submissions": 120,
+                        "storage": {"files": 4800, "logical_bytes": 524288000, "physical_bytes": 83886080},
+                        "runs_by_status": {"completed": 118},
+                        "oldest_resource_at": "2023-09-01T08:00:00+02:00",
+                        "newest_resource_at": "2024-01-15T10:25:00+01:00",
+                    }
+                ],
+            }
+        }
+    )
+
+    computed_at: UtcTimestamp
+    age_seconds: float
+    duration_seconds: float
+    submissions: int
+    storage: StorageUsageDto
+    blobs: int
+    fingerprint_cache: FingerprintCacheStatsDto
+    runs_by_status: Dict[str, int]
+    oldest_resource_at: Optional[UtcTimestamp] = None
+    newest_resource_at: Optional[UtcTimestamp] = None
+    projects: List[ProjectStatsDto]
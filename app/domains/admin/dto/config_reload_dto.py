@@ -0,0 +1,12 @@
+from typing import List, Optional
+
+from pydantic import BaseModel
+
+
+class ConfigReloadDto(BaseModel):
+    """DTO for a reload of the runtime configuration"""
+
+    changed: List[str]  # names of the settings that changed
+    tokenizer_config_reloaded: bool
+    tokenizer_config_revision: Optional[str] = None  # None for the built-in tokenizer configuration
+    message: str
@@ -0,0 +1,15 @@
+from typing import Dict
+
+from pydantic import BaseModel, Field
+
+
+class LogFilterDto(BaseModel):
+    """DTO for the log filter applied to the loggers"""
+
+    filter: str = Field(description='Default level then per logger levels, e.g. "info,app.domains.detection=debug"')
+
+
+class LogFilterResponseDto(LogFilterDto):
+    """DTO for the applied log filter with the resulting level of each named logger"""
+
+    levels: Dict[str, str] = {}
@@ -0,0 +1,47 @@
+from typing import Dict, List, Optional
+
+from pydantic import BaseModel, ConfigDict
+
+
+class LanguageTokenizerConfigDto(BaseModel):
+    """DTO for the effective tokenizer configuration of one language"""
+
+    extensions: List[str] = []
+    filenames: List[str] = []
+    stop_tokens: List[str] = []
+    normalization: Optional[str] = None
+    generated_markers: List[str] = []
+    generated_marker_lines: int
+    token_weights: Dict[str, int] = {}
+    tokenizer_version: str
+
+
+class TokenizerConfigDto(BaseModel):
+    """DTO for the effective tokenizer configuration, the built-in one merged with the deployment file"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "sources": ["app/domains/tokenization/tokenizer_config.toml", "/etc/pamp/tokenizer.toml"],
+                "token_classes": {"identifier": ["identifier", "name"], "comment": ["comment"]},
+                "ignored_extensions": [".gz", ".zip"],
+                "languages": {
+                    "python": {
+                        "extensions": [".py", ".pyi"],
+                        "filenames": [],
+                        "stop_tokens": ["comment"],
+                        "normalization": None,
+                        "generated_markers": ["@generated", "Generated by Django"],
+                        "generated_marker_lines": 5,
+                        "token_weights": {"identifier": 1, "literal": 1, "comment": 1, "other": 1},
+                        "tokenizer_version": "1+3fa2c81d",
+                    }
+                },
+            }
+        }
+    )
+
+    sources: List[str]
+    token_classes: Dict[str, List[str]]
+    ignored_extensions: List[str] = []
+    languages: Dict[str, LanguageTokenizerConfigDto]
@@ -0,0 +1,69 @@
+"""
+Hash chain of the audit log
+
+Each entry stores the SHA-256 of its canonical JSON form, which includes the hash of the previous entry. Editing a
+field of an entry changes its hash, and replacing its hash breaks the link of the next entry, so tampering is
+detected by recomputing the chain from the first entry. Removing the most recent entries is only detected against
+an external copy of the last hash, as returned by GET /audit/verify.
+"""
+
+import hashlib
+import json
+from dataclasses import dataclass
+from typing import Iterable, Optional
+
+from app.domains.audit.audit_models import AuditEntry
+from app.shared.timestamps import to_rfc3339
+
+GENESIS_HASH = "0" * 64
+
+
+def entry_digest(entry: AuditEntry, previous_hash: str) -> str:
+    """Hash of an entry chained after the given hash"""
+    fields = {
+        "sequence": entry.sequence,
+        "occurred_at": to_rfc3339(entry.occurred_at),
+        "action": entry.action.value if hasattr(entry.action, "value") else entry.action,
+        "actor": entry.actor,
+        "project_uuid": str(entry.project_uuid) if entry.project_uuid else None,
+        "resource_type": entry.resource_type,
+        "resource_id": entry.resource_id,
+        "details": entry.details,
+        "ip_address": entry.ip_address,
+        "user_agent": entry.user_agent,
+        "request_id": entry.request_id,
+        "previous_hash": previous_hash,
+    }
+    canonical = json.dumps(fields, sort_keys=True, separators=(",", ":"), ensure_ascii=False, default=str)
+    return hashlib.sha256(canonical.encode("utf-8")).hexdigest()
+
+
+@dataclass
+class ChainVerification:
+    """Outcome of recomputing the chain, broken_at being the sequence of the first entry that does not verify"""
+
+    valid: bool
+    checked: int
+    last_sequence: Optional[int] = None
+    last_hash: Optional[str] = None
+    broken_at: Optional[int] = None
+    reason: Optional[str] = None
+
+
+def verify_chain(entries: Iterable[AuditEntry]) -> ChainVerification:
+    """Recompute the chain of entries given in sequence order, stopping at the first one that does not verify"""
+    previous_hash = GENESIS_HASH
+    checked = 0
+    for entry in entries:
+        reason = None
+        if entry.sequence != checked + 1:
+            reason = f"expected entry {checked + 1}, an entry was removed or inserted"
+        elif entry.previous_hash != previous_hash:
+            reason = "previous hash does not match the hash of the previous entry"
+        elif entry_digest(entry, previous_hash) != entry.entry_hash:
+            reason = "entry hash does not match its fields"
+        if reason is not None:
+            return ChainVerification(False, checked, checked or None, previous_hash, entry.sequence, reason)
+        previous_hash = entry.entry_hash
+        checked += 1
+    return ChainVerification(True, checked, checked or None, previous_hash)
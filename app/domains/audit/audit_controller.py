@@ -0,0 +1,47 @@
+from typing import List, Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, HTTPException, Query
+from sqlmodel import Session
+
+from app.domains.audit.audit_models import AuditAction
+from app.domains.audit.audit_service import AuditService
+from app.domains.audit.dto import AuditChainVerificationDto, AuditEntryDto
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException
+from app.shared.security import require_audit_scope
+from app.shared.timestamps import OffsetTimestamp
+
+router = APIRouter(prefix="/audit", tags=["audit"], dependencies=[Depends(require_audit_scope)])
+
+
+def get_audit_service(session: Session = Depends(get_session)) -> AuditService:
+    """Dependency to get audit service"""
+    return AuditService(session)
+
+
+@router.get("/entries", response_model=List[AuditEntryDto])
+async def list_audit_entries(
+    project_uuid: Optional[UUID] = Query(None, description="Only the entries of a project"),
+    actor: Optional[str] = Query(None, description="Only the entries of an actor, e.g. user:<uuid> or admin"),
+    action: Optional[AuditAction] = Query(None, description="Only the entries of an action"),
+    since: Optional[OffsetTimestamp] = Query(None, description="Only the entries from this time, with its offset"),
+    until: Optional[OffsetTimestamp] = Query(None, description="Only the entries before this time, with its offset"),
+    skip: int = Query(0, ge=0, description="Number of entries to skip"),
+    limit: int = Query(100, ge=1, le=1000, description="Maximum number of entries to return"),
+    service: AuditService = Depends(get_audit_service),
+):
+    """List the entries of the audit log, most recent first"""
+    try:
+        return service.list_entries(project_uuid, actor, action, since, until, skip, limit)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/verify", response_model=AuditChainVerificationDto)
+async def verify_audit_chain(service: AuditService = Depends(get_audit_service)):
+    """Recompute the hash chain of the audit log, reporting the first entry that was modified, removed or inserted"""
+    try:
+        return service.verify()
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
@@ -0,0 +1,79 @@
+"""
+Dependencies recording the sensitive operations of endpoints in the audit log
+
+An audited endpoint declares `dependencies=[Depends(audited(action, resource_type, resource_param))]`. The entry is
+appended before the endpoint runs, so a deletion or configuration change whose entry cannot be written is answered
+with 503 without being performed. The actor is the scope of the bearer token a request carries, else the user the
+PAMP gateway authenticated, from the AUDIT_ACTOR_HEADER header.
+"""
+
+from typing import Callable, Mapping, Optional
+from uuid import UUID
+
+from fastapi import Depends, Request
+from sqlmodel import Session
+
+from app.config.config import get_settings
+from app.domains.audit.audit_models import AuditAction
+from app.domains.audit.audit_service import AuditService
+from app.shared.database import get_session
+from app.shared.security import get_client_info, granted_scope
+from app.shared.tracing import request_id_of
+
+ANONYMOUS_ACTOR = "anonymous"
+
+
+def actor_of(headers: Mapping[str, str]) -> str:
+    """
+    Authenticated identity behind the headers of a request or the metadata of a call, with lowercase names: admin or
+    audit, user:<id>, or anonymous
+    """
+    scope = granted_scope(headers.get("authorization"))
+    if scope is not None:
+        return scope
+    user = (headers.get(get_settings().audit_actor_header.lower()) or "").strip()
+    return f"user:{user[:200]}" if user else ANONYMOUS_ACTOR
+
+
+def _uuid_or_none(value) -> Optional[UUID]:
+    try:
+        return UUID(str(value)) if value is not None else None
+    except ValueError:
+        return None
+
+
+def audited(
+    action: AuditAction,
+    resource_type: str,
+    resource_param: Optional[str] = None,
+    when: Optional[Callable[[Request], bool]] = None,
+) -> Callable:
+    """
+    Dependency recording a request in the audit log, the resource ID taken from the path parameter resource_param;
+    the other path parameters, the method and the path are kept in the details of the entry
+    """
+
+    def record_audit_entry(request: Request, session: Session = Depends(get_session)) -> None:
+        if when is not None and not when(request):
+            return
+        path_params = dict(request.path_params)
+        resource_id = path_params.pop(resource_param, None) if resource_param else None
+        details = {"method": request.method, "path": request.url.path}
+        details.update({name: str(value) for name, value in path_params.items()})
+        if request.query_params:
+            details["query"] = str(request.query_params)
+        ip_address, user_agent = get_client_info(request)
+
+        AuditService(session).record(
+            action,
+            actor_of(request.headers),
+            resource_type,
+            resource_id=str(resource_id) if resource_id is not None else None,
+            project_uuid=_uuid_or_none(path_params.get("project_uuid")),
+            details=details,
+            ip_address=ip_address,
+            user_agent=user_agent,
+            request_id=request_id_of(request),
+        )
+
+    return record_audit_entry
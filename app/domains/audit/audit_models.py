@@ -0,0 +1,57 @@
+from datetime import datetime
+from enum import Enum
+from typing import Optional
+from uuid import UUID, uuid4
+
+from sqlmodel import JSON, Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class AuditAction(str, Enum):
+    """Enumeration for the sensitive operations recorded in the audit log"""
+
+    REPORT_VIEW = "report_view"  # a run report, its HTML page or one of its exports
+    FRAGMENT_VIEW = "fragment_view"  # the shared fragments or code of a compared pair
+    FILE_DOWNLOAD = "file_download"  # the raw content of a submitted file
+    SUBMISSION_DELETE = "submission_delete"
+    CONFIG_CHANGE = "config_change"  # step configurations, retention policies, legal holds, schedules, log levels
+    PSEUDONYM_ACCESS = "pseudonym_access"  # the identities behind the pseudonyms of anonymized reports
+
+
+# Deleting and changing configuration fail when their entry cannot be written, views are logged on a best-effort basis
+STRICT_AUDIT_ACTIONS = frozenset({AuditAction.SUBMISSION_DELETE, AuditAction.CONFIG_CHANGE})
+
+
+class AuditEntry(SQLModel, table=True):
+    """
+    Database model for the append-only audit log, each entry hashing its fields with the hash of the previous one so
+    that a modified, deleted or inserted row breaks the chain
+    """
+
+    __tablename__ = "audit_entry"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    sequence: int = Field(unique=True, index=True, description="Position in the chain, from 1")
+    occurred_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False, index=True),
+        description="When the operation was performed",
+    )
+
+    # Who did what to which resource
+    action: AuditAction = Field(index=True, description="Operation performed")
+    actor: str = Field(index=True, max_length=255, description="Authenticated identity, e.g. user:<uuid> or admin")
+    project_uuid: Optional[UUID] = Field(default=None, index=True, description="Project of the resource")
+    resource_type: str = Field(max_length=64, description="Kind of resource, e.g. run, pair, submission")
+    resource_id: Optional[str] = Field(default=None, max_length=255, description="ID of the resource")
+    details: Optional[dict] = Field(
+        default=None, sa_column=Column(JSON), description="Method, path and other resource IDs of the request"
+    )
+    ip_address: Optional[str] = Field(default=None, max_length=45)
+    user_agent: Optional[str] = Field(default=None, max_length=500)
+    request_id: Optional[str] = Field(default=None, max_length=128)
+
+    # Chain
+    previous_hash: str = Field(max_length=64, description="Hash of the previous entry, zeros for the first one")
+    entry_hash: str = Field(max_length=64, description="SHA-256 of the fields of the entry and previous_hash")
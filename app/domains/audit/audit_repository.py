@@ -0,0 +1,109 @@
+from datetime import datetime
+from typing import Iterator, List, Optional
+from uuid import UUID
+
+from sqlmodel import Session, select
+
+from app.domains.audit.audit_models import AuditAction, AuditEntry
+from app.domains.runs.runs_models import DetectionPair, DetectionRun
+from app.domains.submissions.submissions_models import Submission, SubmissionSimilarity
+from app.shared.exceptions import DatabaseException
+
+
+class AuditRepository:
+    """Repository for the audit log, which it only appends to"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def last_entry(self) -> Optional[AuditEntry]:
+        """Most recent entry of the chain"""
+        try:
+            statement = select(AuditEntry).order_by(AuditEntry.sequence.desc()).limit(1)
+            return self.session.exec(statement).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get the last audit entry: {str(e)}")
+
+    def append(self, entry: AuditEntry) -> AuditEntry:
+        """
+        Insert an entry, the unique sequence refusing it when another writer appended first
+
+        Raises:
+            DatabaseException: If the entry could not be inserted
+        """
+        try:
+            self.session.add(entry)
+            self.session.commit()
+            self.session.refresh(entry)
+            return entry
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to append audit entry: {str(e)}")
+
+    def project_of(self, resource_type: str, resource_id: UUID) -> Optional[UUID]:
+        """Project of a run, pair, submission or similarity, None when it does not exist"""
+        try:
+            if resource_type == "run":
+                statement = select(DetectionRun.project_uuid).where(DetectionRun.id == resource_id)
+            elif resource_type == "pair":
+                statement = select(DetectionPair.project_uuid).where(DetectionPair.id == resource_id)
+            elif resource_type == "submission":
+                statement = select(Submission.project_uuid).where(Submission.id == resource_id)
+            elif resource_type == "similarity":
+                statement = (
+                    select(Submission.project_uuid)
+                    .join(SubmissionSimilarity, SubmissionSimilarity.submission_id == Submission.id)
+                    .where(SubmissionSimilarity.id == resource_id)
+                )
+            else:
+                return None
+            return self.session.exec(statement).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get the project of the audited {resource_type}: {str(e)}")
+
+    def list_entries(
+        self,
+        project_uuid: Optional[UUID] = None,
+        actor: Optional[str] = None,
+        action: Optional[AuditAction] = None,
+        since: Optional[datetime] = None,
+        until: Optional[datetime] = None,
+        skip: int = 0,
+        limit: int = 100,
+    ) -> List[AuditEntry]:
+        """Entries matching the filters, most recent first"""
+        try:
+            statement = select(AuditEntry)
+            if project_uuid is not None:
+                statement = statement.where(AuditEntry.project_uuid == project_uuid)
+            if actor is not None:
+                statement = statement.where(AuditEntry.actor == actor)
+            if action is not None:
+                statement = statement.where(AuditEntry.action == action)
+            if since is not None:
+                statement = statement.where(AuditEntry.occurred_at >= since)
+            if until is not None:
+                statement = statement.where(AuditEntry.occurred_at < until)
+            statement = statement.order_by(AuditEntry.sequence.desc()).offset(skip).limit(limit)
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to list audit entries: {str(e)}")
+
+    def iterate_chain(self, batch_size: int = 1000) -> Iterator[AuditEntry]:
+        """Every entry in sequence order, read in batches"""
+        after = 0
+        while True:
+            try:
+                statement = (
+                    select(AuditEntry)
+                    .where(AuditEntry.sequence > after)
+                    .order_by(AuditEntry.sequence)
+                    .limit(batch_size)
+                )
+                batch = list(self.session.exec(statement).all())
+            except Exception as e:
+                raise DatabaseException(f"Failed to read the audit chain: {str(e)}")
+            yield from batch
+            if len(batch) < batch_size:
+                return
+            after = batch[-1].sequence
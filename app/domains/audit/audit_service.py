@@ -0,0 +1,112 @@
+import logging
+import threading
+from datetime import datetime
+from typing import List, Optional
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.audit.audit_chain import GENESIS_HASH, entry_digest, verify_chain
+from app.domains.audit.audit_models import STRICT_AUDIT_ACTIONS, AuditAction, AuditEntry
+from app.domains.audit.audit_repository import AuditRepository
+from app.domains.audit.dto.audit_dto import AuditChainVerificationDto, AuditEntryDto
+from app.shared.exceptions import AuditWriteException, DatabaseException
+from app.shared.metrics import AUDIT_WRITE_FAILURES
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+# Appends of this process are serialized, another instance appending first is retried against its entry
+APPEND_ATTEMPTS = 5
+_APPEND_LOCK = threading.Lock()
+
+
+class AuditService:
+    """Service appending to the audit log, querying it and verifying its hash chain"""
+
+    def __init__(self, session: Session):
+        self.session = session
+        self.repository = AuditRepository(session)
+
+    def record(
+        self,
+        action: AuditAction,
+        actor: str,
+        resource_type: str,
+        resource_id: Optional[str] = None,
+        project_uuid: Optional[UUID] = None,
+        details: Optional[dict] = None,
+        ip_address: Optional[str] = None,
+        user_agent: Optional[str] = None,
+        request_id: Optional[str] = None,
+    ) -> Optional[AuditEntry]:
+        """
+        Append an entry to the chain, its project resolved from the resource when not given
+
+        Views are recorded on a best-effort basis, a failure is logged and counted. Deletions and configuration
+        changes are recorded before they are performed and refused when their entry cannot be written.
+
+        Raises:
+            AuditWriteException: If the entry of a deletion or configuration change could not be written
+        """
+        error = None
+        for _ in range(APPEND_ATTEMPTS):
+            try:
+                with _APPEND_LOCK:
+                    if project_uuid is None and resource_id is not None:
+                        project_uuid = self._project_of(resource_type, resource_id)
+                    last = self.repository.last_entry()
+                    entry = AuditEntry(
+                        sequence=last.sequence + 1 if last else 1,
+                        occurred_at=utc_now(),
+                        action=action,
+                        actor=actor,
+                        project_uuid=project_uuid,
+                        resource_type=resource_type,
+                        resource_id=resource_id,
+                        details=details,
+                        ip_address=ip_address,
+                        user_agent=user_agent[:500] if user_agent else None,
+                        request_id=request_id,
+                        previous_hash=last.entry_hash if last else GENESIS_HASH,
+                    )
+                    entry.entry_hash = entry_digest(entry, entry.previous_hash)
+                    return self.repository.append(entry)
+            except DatabaseException as e:
+                error = e
+
+        AUDIT_WRITE_FAILURES.labels(action.value).inc()
+        logger.error(f"Audit entry of {action.value} on {resource_type} {resource_id} by {actor} lost: {error.detail}")
+        if action in STRICT_AUDIT_ACTIONS:
+            raise AuditWriteException(action.value, error.detail)
+        return None
+
+    def _project_of(self, resource_type: str, resource_id: str) -> Optional[UUID]:
+        try:
+            return self.repository.project_of(resource_type, UUID(resource_id))
+        except ValueError:
+            return None
+
+    def list_entries(
+        self,
+        project_uuid: Optional[UUID] = None,
+        actor: Optional[str] = None,
+        action: Optional[AuditAction] = None,
+        since: Optional[datetime] = None,
+        until: Optional[datetime] = None,
+        skip: int = 0,
+        limit: int = 100,
+    ) -> List[AuditEntryDto]:
+        """Entries matching the filters, most recent first"""
+        entries = self.repository.list_entries(project_uuid, actor, action, since, until, skip, limit)
+        return [AuditEntryDto.model_validate(entry) for entry in entries]
+
+    def verify(self) -> AuditChainVerificationDto:
+        """Recompute the whole chain from its first entry"""
+        verification = verify_chain(self.repository.iterate_chain())
+        if not verification.valid:
+            logger.error(
+                f"Audit chain broken at entry {verification.broken_at}: {verification.reason}, "
+                f"{verification.checked} entries verified before it"
+            )
+        return AuditChainVerificationDto.model_validate(verification)
@@ -0,0 +1,3 @@
+from .audit_dto import AuditChainVerificationDto, AuditEntryDto
+
+__all__ = ["AuditChainVerificationDto", "AuditEntryDto"]
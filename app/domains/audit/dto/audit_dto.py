@@ -0,0 +1,43 @@
+from typing import Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field
+
+from app.domains.audit.audit_models import AuditAction
+from app.shared.timestamps import UtcTimestamp
+
+
+class AuditEntryDto(BaseModel):
+    """DTO for reading an entry of the audit log"""
+
+    model_config = ConfigDict(from_attributes=True, use_enum_values=True)
+
+    id: UUID
+    sequence: int
+    occurred_at: UtcTimestamp
+    action: AuditAction
+    actor: str
+    project_uuid: Optional[UUID] = None
+    resource_type: str
+    resource_id: Optional[str] = None
+    details: Optional[dict] = None
+    ip_address: Optional[str] = None
+    user_agent: Optional[str] = None
+    request_id: Optional[str] = None
+    previous_hash: str
+    entry_hash: str
+
+
+class AuditChainVerificationDto(BaseModel):
+    """DTO for the verification of the hash chain of the audit log"""
+
+    model_config = ConfigDict(from_attributes=True)
+
+    valid: bool
+    checked: int = Field(description="Entries verified, from the first one")
+    last_sequence: Optional[int] = Field(default=None, description="Last entry verified")
+    last_hash: Optional[str] = Field(
+        default=None, description="Hash of the last entry verified, kept elsewhere it shows the log was not truncated"
+    )
+    broken_at: Optional[int] = Field(default=None, description="First entry that does not verify")
+    reason: Optional[str] = None
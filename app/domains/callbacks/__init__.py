@@ -0,0 +1 @@
+# Callbacks domain package
@@ -0,0 +1,167 @@
+import json
+import logging
+import threading
+import urllib.error
+import urllib.request
+from datetime import timedelta
+from typing import Callable, Mapping, Optional
+
+from app.config.config import Settings
+from app.domains.callbacks.callbacks_models import CallbackDelivery, CallbackStatus
+from app.domains.callbacks.callbacks_repository import CallbackDeliveryRepository
+from app.domains.callbacks.signing import signed_headers
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+# Posts a body with headers to a URL within a timeout, returns the HTTP status
+Sender = Callable[[str, bytes, Mapping[str, str], float], int]
+
+
+def post_json(url: str, body: bytes, headers: Mapping[str, str], timeout: float) -> int:
+    headers = {"Content-Type": "application/json", **headers}
+    request = urllib.request.Request(url, data=body, headers=headers, method="POST")
+    try:
+        with urllib.request.urlopen(request, timeout=timeout) as response:
+            return response.status
+    except urllib.error.HTTPError as e:
+        return e.code
+
+
+def callback_body(delivery: CallbackDelivery) -> bytes:
+    """Bytes of the summary of a delivery, the same on every attempt"""
+    return json.dumps(delivery.payload, sort_keys=True, separators=(",", ":")).encode()
+
+
+class CallbackDispatcher:
+    """
+    Background thread posting the due callbacks to the main PAMP service
+
+    A delivery failing, by a non-2xx answer or no answer, is attempted again after a backoff doubling with each
+    attempt, up to max_attempts; then it is failed and only resent by a redelivery.
+    """
+
+    def __init__(
+        self,
+        secret: str,
+        sender: Sender = post_json,
+        timeout_seconds: float = 10.0,
+        max_attempts: int = 8,
+        backoff_seconds: float = 30.0,
+        backoff_max_seconds: float = 3600.0,
+        interval_seconds: float = 5.0,
+        batch_size: int = 20,
+        session_factory=None,
+    ):
+        self.secret = secret
+        self.sender = sender
+        self.timeout_seconds = timeout_seconds
+        self.max_attempts = max(max_attempts, 1)
+        self.backoff_seconds = backoff_seconds
+        self.backoff_max_seconds = backoff_max_seconds
+        self.interval_seconds = interval_seconds
+        self.batch_size = max(batch_size, 1)
+        self._session_factory = session_factory
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def _new_session(self):
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from sqlmodel import Session
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    def backoff(self, attempts: int) -> float:
+        """Wait before the attempt following the given number of failed ones"""
+        return min(self.backoff_seconds * 2 ** (attempts - 1), self.backoff_max_seconds)
+
+    def attempt(self, delivery: CallbackDelivery) -> bool:
+        """Post a delivery once, signed anew, and record the outcome on it without committing, returns success"""
+        now = utc_now()
+        body = callback_body(delivery)
+        delivery.attempts += 1
+        delivery.last_attempt_at = now
+        try:
+            status_code = self.sender(delivery.url, body, signed_headers(self.secret, body), self.timeout_seconds)
+            error = None if 200 <= status_code < 300 else f"HTTP {status_code}"
+        except Exception as e:
+            status_code, error = None, str(e) or type(e).__name__
+        delivery.last_status_code = status_code
+
+        if error is None:
+            delivery.status = CallbackStatus.DELIVERED
+            delivery.delivered_at = now
+            delivery.next_attempt_at = None
+            delivery.last_error = None
+            return True
+
+        delivery.last_error = error[:1000]
+        if delivery.attempts >= self.max_attempts:
+            delivery.status = CallbackStatus.FAILED
+            delivery.next_attempt_at = None
+            logger.error(f"Callback of run {delivery.run_id} failed after {delivery.attempts} attempts: {error}")
+        else:
+            delivery.next_attempt_at = now + timedelta(seconds=self.backoff(delivery.attempts))
+            logger.warning(f"Callback of run {delivery.run_id} failed, attempt {delivery.attempts}: {error}")
+        return False
+
+    def deliver_due(self) -> int:
+        """Attempt every due delivery once, returns how many were delivered"""
+        delivered = 0
+        while True:
+            with self._new_session() as session:
+                repository = CallbackDeliveryRepository(session)
+                deliveries = repository.lock_due(utc_now(), self.batch_size)
+                for delivery in deliveries:
+                    delivered += self.attempt(delivery)
+                    session.add(delivery)
+                repository.commit()
+            if len(deliveries) < self.batch_size:
+                return delivered
+
+    def run_once(self) -> int:
+        """Deliver the due callbacks, errors are logged and never stop the dispatcher"""
+        try:
+            return self.deliver_due()
+        except Exception as e:
+            logger.error(f"Callback dispatcher failed: {str(e)}")
+            return 0
+
+    def _run(self) -> None:
+        while not self._stop_event.wait(self.interval_seconds):
+            self.run_once()
+
+    def start(self) -> None:
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="callback-dispatcher", daemon=True)
+        self._thread.start()
+        logger.info(f"Callbacks dispatched every {self.interval_seconds:g} seconds")
+
+    def stop(self, timeout: float = 5.0) -> None:
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+
+
+def create_callback_dispatcher(settings: Settings) -> Optional[CallbackDispatcher]:
+    """Build the callback dispatcher from the settings, None when callbacks are disabled or have no secret"""
+    if not settings.pamp_callback_enabled:
+        return None
+    if not settings.pamp_callback_secret or not settings.pamp_callback_url:
+        logger.warning("PAMP callbacks are enabled without PAMP_CALLBACK_URL and PAMP_CALLBACK_SECRET, none is sent")
+        return None
+    return CallbackDispatcher(
+        settings.pamp_callback_secret.get_secret_value(),
+        timeout_seconds=settings.pamp_callback_timeout_seconds,
+        max_attempts=settings.pamp_callback_max_attempts,
+        backoff_seconds=settings.pamp_callback_backoff_seconds,
+        backoff_max_seconds=settings.pamp_callback_backoff_max_seconds,
+        interval_seconds=settings.pamp_callback_interval_seconds,
+    )
@@ -0,0 +1,78 @@
+"""
+Summaries of finished runs posted to the main PAMP service
+
+The summary of a run is written with the run it summarizes, in the transaction finishing it, and posted by the
+callback dispatcher until PAMP accepts it. It carries IDs and counts, never identities or source code: PAMP reads
+the details from the report it links to.
+"""
+
+from typing import Any, Dict, List, Optional
+
+from sqlalchemy import func
+from sqlmodel import Session, select
+
+from app.domains.callbacks.callbacks_models import CallbackDelivery
+from app.domains.callbacks.dto.callback_dto import RunSummaryCallbackDto, ThresholdCountDto
+from app.domains.runs.runs_models import DetectionPair
+from app.domains.submissions.submissions_models import SimilarityStatus
+
+CALLBACK_TYPE = "detection.finished"
+CALLBACK_VERSION = 1
+
+
+def report_url(public_url: str, run_id) -> str:
+    """URL of the HTML report of a run, relative when the public URL of the service is not set"""
+    return f"{public_url.rstrip('/')}/runs/{run_id}/report.html"
+
+
+def count_pairs_above(session: Session, run_id, thresholds: List[float]) -> List[ThresholdCountDto]:
+    """Completed pairs of a run at or above each threshold"""
+    counts = []
+    for threshold in thresholds:
+        statement = (
+            select(func.count())
+            .select_from(DetectionPair)
+            .where(DetectionPair.run_id == run_id)
+            .where(DetectionPair.status == SimilarityStatus.COMPLETED)
+            .where(DetectionPair.overall_similarity >= threshold)
+        )
+        counts.append(ThresholdCountDto(threshold=threshold, pairs=session.exec(statement).one()))
+    return counts
+
+
+def run_summary(session: Session, run, thresholds: List[float], public_url: str = "") -> Dict[str, Any]:
+    """JSON summary of a finished run"""
+    return RunSummaryCallbackDto(
+        type=CALLBACK_TYPE,
+        version=CALLBACK_VERSION,
+        run_id=run.id,
+        project_uuid=run.project_uuid,
+        project_step_uuid=run.project_step_uuid,
+        status=getattr(run.status, "value", run.status),
+        total_pairs=run.total_pairs,
+        completed_pairs=run.completed_pairs,
+        failed_pairs=run.failed_pairs,
+        pairs_above_thresholds=count_pairs_above(session, run.id, thresholds),
+        finished_at=run.finished_at,
+        report_url=report_url(public_url, run.id),
+    ).model_dump(mode="json")
+
+
+def callback_delivery(session: Session, run, settings) -> Optional[CallbackDelivery]:
+    """Delivery of the summary of a run, None when callbacks are disabled or not configured"""
+    if not settings.pamp_callback_enabled or not settings.pamp_callback_url or not settings.pamp_callback_secret:
+        return None
+    return CallbackDelivery(
+        run_id=run.id,
+        url=settings.pamp_callback_url,
+        payload=run_summary(session, run, settings.pamp_callback_threshold_values, settings.pamp_callback_public_url),
+    )
+
+
+def record_callback(session: Session, run) -> None:
+    """Add the summary of a finished run to the deliveries, in the transaction of the session"""
+    from app.config.config import get_settings
+
+    delivery = callback_delivery(session, run, get_settings())
+    if delivery is not None:
+        session.add(delivery)
@@ -0,0 +1,54 @@
+from typing import List
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, HTTPException
+from sqlmodel import Session
+
+from app.domains.callbacks.callbacks_service import CallbackService
+from app.domains.callbacks.dto.callback_dto import CallbackDeliveryDto
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException, ValidationException
+from app.shared.security import require_admin_scope
+
+router = APIRouter(prefix="/runs", tags=["callbacks"])
+
+
+def get_callback_service(session: Session = Depends(get_session)) -> CallbackService:
+    """Dependency to get callback service"""
+    return CallbackService(session)
+
+
+@router.get("/{run_id}/callbacks", response_model=List[CallbackDeliveryDto])
+async def list_run_callbacks(run_id: UUID, service: CallbackService = Depends(get_callback_service)):
+    """Get the delivery state of the summary of a run posted to the main PAMP service"""
+    try:
+        return service.list_run_callbacks(run_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post(
+    "/{run_id}/callbacks/redeliver",
+    response_model=CallbackDeliveryDto,
+    status_code=202,
+    dependencies=[Depends(require_admin_scope)],
+)
+async def redeliver_run_callback(run_id: UUID, service: CallbackService = Depends(get_callback_service)):
+    """
+    Send the summary of a finished run to the main PAMP service again, with a new retry budget, for when PAMP was
+    down longer than the retries. Returns 409 when callbacks are not configured
+    """
+    try:
+        delivery = service.redeliver(run_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except ValidationException as e:
+        raise HTTPException(status_code=409, detail=str(e.detail))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    if delivery is None:
+        raise HTTPException(status_code=409, detail="PAMP callbacks are not configured")
+    return delivery
@@ -0,0 +1,41 @@
+from datetime import datetime
+from enum import Enum
+from typing import Optional
+from uuid import UUID, uuid4
+
+from sqlmodel import JSON, Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class CallbackStatus(str, Enum):
+    """Enumeration for the delivery status of a callback"""
+
+    PENDING = "pending"  # waiting for its next attempt
+    DELIVERED = "delivered"
+    FAILED = "failed"  # retry budget spent, only resent by a redelivery
+
+
+class CallbackDelivery(SQLModel, table=True):
+    """Database model for the summary of a finished run posted to the main PAMP service"""
+
+    __tablename__ = "callback_delivery"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    run_id: UUID = Field(foreign_key="detection_run.id", index=True, description="ID of the summarized run")
+    url: str = Field(max_length=2048, description="Endpoint the summary is posted to")
+    payload: dict = Field(default_factory=dict, sa_column=Column(JSON), description="Summary of the run")
+
+    # Delivery state
+    status: CallbackStatus = Field(default=CallbackStatus.PENDING, index=True, description="Status of the delivery")
+    attempts: int = Field(default=0, description="Attempts since the delivery was created or redelivered")
+    next_attempt_at: Optional[datetime] = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), index=True),
+        description="When the next attempt is due, None once delivered or failed",
+    )
+    last_attempt_at: Optional[datetime] = Field(default=None, sa_column=Column(UtcDateTime()))
+    last_status_code: Optional[int] = Field(default=None, description="HTTP status of the last attempt")
+    last_error: Optional[str] = Field(default=None, description="Error of the last failed attempt")
+    delivered_at: Optional[datetime] = Field(default=None, sa_column=Column(UtcDateTime()))
+    created_at: datetime = Field(default_factory=utc_now, sa_column=Column(UtcDateTime(), nullable=False))
@@ -0,0 +1,69 @@
+from datetime import datetime
+from typing import List, Optional
+from uuid import UUID
+
+from sqlmodel import Session, select
+
+from app.domains.callbacks.callbacks_models import CallbackDelivery, CallbackStatus
+from app.shared.exceptions import DatabaseException
+
+
+class CallbackDeliveryRepository:
+    """Repository for the callbacks delivered or waiting to be delivered to the main PAMP service"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def lock_due(self, now: datetime, limit: int) -> List[CallbackDelivery]:
+        """
+        Pending deliveries whose next attempt is due, oldest first, locked until the transaction ends
+
+        Rows locked by the dispatcher of another instance are skipped, so each attempt is made by one of them.
+        """
+        try:
+            statement = (
+                select(CallbackDelivery)
+                .where(CallbackDelivery.status == CallbackStatus.PENDING)
+                .where(CallbackDelivery.next_attempt_at <= now)
+                .order_by(CallbackDelivery.next_attempt_at)
+                .limit(limit)
+                .with_for_update(skip_locked=True)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to read due callbacks: {str(e)}")
+
+    def get(self, delivery_id: UUID) -> Optional[CallbackDelivery]:
+        try:
+            return self.session.get(CallbackDelivery, delivery_id)
+        except Exception as e:
+            raise DatabaseException(f"Failed to get callback delivery: {str(e)}")
+
+    def list_by_run(self, run_id: UUID) -> List[CallbackDelivery]:
+        try:
+            statement = (
+                select(CallbackDelivery)
+                .where(CallbackDelivery.run_id == run_id)
+                .order_by(CallbackDelivery.created_at.desc())
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to list callback deliveries: {str(e)}")
+
+    def save(self, delivery: CallbackDelivery) -> CallbackDelivery:
+        try:
+            self.session.add(delivery)
+            self.session.commit()
+            self.session.refresh(delivery)
+            return delivery
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save callback delivery: {str(e)}")
+
+    def commit(self) -> None:
+        try:
+            self.session.commit()
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to update callback deliveries: {str(e)}")
@@ -0,0 +1,72 @@
+import logging
+from typing import List, Optional
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.callbacks.callbacks import callback_delivery
+from app.domains.callbacks.callbacks_models import CallbackStatus
+from app.domains.callbacks.callbacks_repository import CallbackDeliveryRepository
+from app.domains.callbacks.dto.callback_dto import CallbackDeliveryDto
+from app.domains.runs.runs_models import UNFINISHED_RUN_STATUSES
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.shared.exceptions import NotFoundException, ValidationException
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class CallbackService:
+    """Service for the callbacks of finished runs to the main PAMP service"""
+
+    def __init__(self, session: Session):
+        self.repository = CallbackDeliveryRepository(session)
+        self.run_repository = DetectionRunRepository(session)
+
+    def _get_run_or_raise(self, run_id: UUID):
+        run = self.run_repository.get_run(run_id)
+        if not run:
+            raise NotFoundException(f"Detection run with ID {run_id} not found")
+        return run
+
+    def list_run_callbacks(self, run_id: UUID) -> List[CallbackDeliveryDto]:
+        """Deliveries of the summary of a run, newest first"""
+        self._get_run_or_raise(run_id)
+        return [CallbackDeliveryDto.model_validate(delivery) for delivery in self.repository.list_by_run(run_id)]
+
+    def redeliver(self, run_id: UUID) -> Optional[CallbackDeliveryDto]:
+        """
+        Send the summary of a finished run again, with a new retry budget, to the endpoint currently configured
+
+        The latest delivery of the run is due again with a summary built anew, a delivery is created for runs
+        that finished while callbacks were disabled.
+
+        Returns:
+            The pending delivery, None when callbacks are disabled or not configured
+
+        Raises:
+            NotFoundException: If the run does not exist
+            ValidationException: If the run is still running
+        """
+        from app.config.config import get_settings
+
+        run = self._get_run_or_raise(run_id)
+        if run.status in UNFINISHED_RUN_STATUSES:
+            raise ValidationException(f"Detection run {run_id} is still running")
+
+        fresh = callback_delivery(self.repository.session, run, get_settings())
+        if fresh is None:
+            return None
+        deliveries = self.repository.list_by_run(run_id)
+        if not deliveries:
+            delivery = fresh
+        else:
+            delivery = deliveries[0]
+            delivery.url = fresh.url
+            delivery.payload = fresh.payload
+            delivery.status = CallbackStatus.PENDING
+            delivery.attempts = 0
+            delivery.next_attempt_at = utc_now()
+
+        logger.info(f"Callback of run {run_id} queued for redelivery to {delivery.url}")
+        return CallbackDeliveryDto.model_validate(self.repository.save(delivery))
@@ -0,0 +1,7 @@
+from .callback_dto import CallbackDeliveryDto, RunSummaryCallbackDto, ThresholdCountDto
+
+__all__ = [
+    "RunSummaryCallbackDto",
+    "ThresholdCountDto",
+    "CallbackDeliveryDto",
+]
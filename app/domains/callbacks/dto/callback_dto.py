@@ -0,0 +1,69 @@
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field
+
+from app.domains.callbacks.callbacks_models import CallbackStatus
+from app.shared.timestamps import UtcTimestamp
+
+
+class ThresholdCountDto(BaseModel):
+    """DTO for the number of completed pairs of a run at or above a similarity"""
+
+    threshold: float
+    pairs: int
+
+
+class RunSummaryCallbackDto(BaseModel):
+    """DTO for the summary of a finished run posted to the main PAMP service"""
+
+    model_config = ConfigDict(
+        extra="forbid",
+        json_schema_extra={
+            "example": {
+                "type": "detection.finished",
+                "version": 1,
+                "run_id": "550e8400-e29b-41d4-a716-446655440010",
+                "project_uuid": "550e8400-e29b-41d4-a716-446655440000",
+                "project_step_uuid": "550e8400-e29b-41d4-a716-446655440003",
+                "status": "completed",
+                "total_pairs": 12,
+                "completed_pairs": 12,
+                "failed_pairs": 0,
+                "pairs_above_thresholds": [{"threshold": 0.5, "pairs": 3}, {"threshold": 0.9, "pairs": 1}],
+                "finished_at": "2024-01-15T10:32:10Z",
+                "report_url": "https://submissions.pamp.example/runs/550e8400-e29b-41d4-a716-446655440010/report.html",
+            }
+        },
+    )
+
+    type: str = Field(description="Always detection.finished")
+    version: int = Field(description="Version of the summary schema, incremented on breaking changes")
+    run_id: UUID
+    project_uuid: UUID
+    project_step_uuid: UUID
+    status: str = Field(description="completed, incomplete, partial or failed")
+    total_pairs: int
+    completed_pairs: int
+    failed_pairs: int
+    pairs_above_thresholds: List[ThresholdCountDto] = Field(description="Ascending thresholds")
+    finished_at: Optional[UtcTimestamp] = None
+    report_url: str = Field(description="HTML report of the run")
+
+
+class CallbackDeliveryDto(BaseModel):
+    """DTO for the delivery state of a callback"""
+
+    model_config = ConfigDict(from_attributes=True, use_enum_values=True)
+
+    id: UUID
+    run_id: UUID
+    url: str
+    status: CallbackStatus
+    attempts: int
+    next_attempt_at: Optional[UtcTimestamp] = None
+    last_attempt_at: Optional[UtcTimestamp] = None
+    last_status_code: Optional[int] = None
+    last_error: Optional[str] = None
+    delivered_at: Optional[UtcTimestamp] = None
+    created_at: UtcTimestamp
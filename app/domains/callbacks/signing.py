@@ -0,0 +1,86 @@
+"""
+HMAC signatures of the callbacks posted to the main PAMP service
+
+Each attempt is signed with the shared secret over its timestamp, a random nonce and the exact body bytes:
+
+    X-PAMP-Timestamp: 1705314672
+    X-PAMP-Nonce: 9f86d081884c7d659a2feaa0c55ad015
+    X-PAMP-Signature: sha256=hex(HMAC-SHA256(secret, "{timestamp}.{nonce}." + body))
+
+A receiver recomputes the signature, refuses timestamps further than its tolerance from its clock and nonces it
+already accepted within that tolerance: a captured request cannot be replayed, even during the tolerance window.
+Retries are signed again with a new timestamp and nonce, so a receiver never refuses a retry as a replay.
+"""
+
+import hashlib
+import hmac
+import secrets
+import threading
+import time
+from typing import Dict, Mapping, Optional
+
+TIMESTAMP_HEADER = "X-PAMP-Timestamp"
+NONCE_HEADER = "X-PAMP-Nonce"
+SIGNATURE_HEADER = "X-PAMP-Signature"
+SIGNATURE_PREFIX = "sha256="
+
+DEFAULT_TOLERANCE_SECONDS = 300
+
+
+def compute_signature(secret: str, timestamp: int, nonce: str, body: bytes) -> str:
+    """Signature of a body sent at a timestamp with a nonce, as sent in X-PAMP-Signature"""
+    message = f"{timestamp}.{nonce}.".encode() + body
+    return SIGNATURE_PREFIX + hmac.new(secret.encode(), message, hashlib.sha256).hexdigest()
+
+
+def new_nonce() -> str:
+    return secrets.token_hex(16)
+
+
+def signed_headers(secret: str, body: bytes, timestamp: Optional[int] = None, nonce: Optional[str] = None) -> Dict:
+    """Headers of a signed attempt, with the current time and a new nonce unless given"""
+    timestamp = int(time.time()) if timestamp is None else timestamp
+    nonce = new_nonce() if nonce is None else nonce
+    return {
+        TIMESTAMP_HEADER: str(timestamp),
+        NONCE_HEADER: nonce,
+        SIGNATURE_HEADER: compute_signature(secret, timestamp, nonce, body),
+    }
+
+
+class ReplayGuard:
+    """
+    Verification of signed callbacks on the receiving side, remembering the nonces accepted within the tolerance
+
+    The main PAMP service mirrors it, and tests use it as the reference receiver.
+    """
+
+    def __init__(self, secret: str, tolerance_seconds: int = DEFAULT_TOLERANCE_SECONDS, clock=time.time):
+        self.secret = secret
+        self.tolerance_seconds = tolerance_seconds
+        self._clock = clock
+        self._seen: Dict[str, float] = {}
+        self._lock = threading.Lock()
+
+    def verify(self, headers: Mapping[str, str], body: bytes) -> bool:
+        """Whether a request is signed with the secret, recent and not seen before; accepted nonces are remembered"""
+        headers = {name.lower(): value for name, value in headers.items()}
+        nonce = headers.get(NONCE_HEADER.lower(), "")
+        signature = headers.get(SIGNATURE_HEADER.lower(), "")
+        try:
+            timestamp = int(headers.get(TIMESTAMP_HEADER.lower(), ""))
+        except ValueError:
+            return False
+
+        now = self._clock()
+        if not nonce or abs(now - timestamp) > self.tolerance_seconds:
+            return False
+        if not hmac.compare_digest(compute_signature(self.secret, timestamp, nonce, body), signature):
+            return False
+        with self._lock:
+            # Nonces older than the tolerance cannot come back, their timestamps would be refused
+            self._seen = {seen: at for seen, at in self._seen.items() if now - at <= self.tolerance_seconds}
+            if nonce in self._seen:
+                return False
+            self._seen[nonce] = now
+        return True
@@ -0,0 +1 @@
+# Corpus domain package
@@ -0,0 +1,200 @@
+"""
+Portable corpus archive format.
+
+An archive is a gzipped tar stream whose members come in a fixed order so that it can be written and read
+sequentially without holding it in memory:
+
+    archive.json                         header: format, schema version, content mode, source project
+    blobs/{algorithm}/{digest}           file contents (content mode "blobs" only), each stored once
+    submissions/{id}.json                submission metadata and the manifest of its latest stored version
+    similarities/{id}.json               submission similarity records
+    runs/{id}/run.json                   detection run with its participants
+    runs/{id}/pairs-{n}.json             chunks of pairs of the run, each with its fragments
+
+IDs inside an archive are only used to resolve cross-references, an import creates new ones.
+Schema version 1 archives named blobs blobs/{sha256}, they are still imported.
+"""
+
+import io
+import json
+import os
+import tarfile
+import threading
+import time
+from enum import Enum
+from typing import Callable, Iterable, Iterator, Optional
+
+from app.domains.storage.exceptions import StoredObjectNotFoundException
+from app.domains.storage.submission_store import StoredObject, SubmissionStore
+from app.shared.exceptions import ValidationException
+from app.shared.tenancy import current_tenancy, project_accessible
+from app.shared.tracing import in_current_context
+
+ARCHIVE_FORMAT = "pamp-corpus"
+ARCHIVE_SCHEMA_VERSION = 2
+# Schema versions this build can import
+SUPPORTED_SCHEMA_VERSIONS = {1, 2}
+
+ARCHIVE_CONTENT_TYPE = "application/gzip"
+HEADER_MEMBER = "archive.json"
+PAIRS_PER_MEMBER = 500
+
+
+class ArchiveContent(str, Enum):
+    """What an archive holds for each submission file"""
+
+    BLOBS = "blobs"
+    FINGERPRINTS = "fingerprints"
+
+
+class IncompatibleArchiveException(ValidationException):
+    """Raised when an archive is not a corpus archive or uses an unsupported schema version"""
+
+    def __init__(self, reason: str):
+        super().__init__(
+            f"Incompatible corpus archive: {reason}",
+            details={"error_type": "incompatible_archive", "reason": reason},
+        )
+        self.reason = reason
+
+
+def export_key(project_uuid, export_id) -> str:
+    """Build the storage key of an export archive, not found for work of another tenant"""
+    prefix = f"exports/projects/{project_uuid}/"
+    if not project_accessible(project_uuid):
+        raise StoredObjectNotFoundException(prefix)
+    return f"{prefix}{export_id}.tar.gz"
+
+
+def export_accessible(archive_key: str) -> bool:
+    """Whether the current work may read an archive, a tenant only reads the exports of its projects"""
+    parts = archive_key.split("/")
+    if len(parts) >= 4 and parts[:2] == ["exports", "projects"]:
+        return project_accessible(parts[2])
+    return not current_tenancy().restricted
+
+
+def check_header(header: dict) -> int:
+    """
+    Validate an archive header, returns its schema version
+
+    Raises:
+        IncompatibleArchiveException: If the format is unknown or the schema version is not supported
+    """
+    if header.get("format") != ARCHIVE_FORMAT:
+        raise IncompatibleArchiveException(f"unknown archive format '{header.get('format')}'")
+    schema_version = header.get("schema_version")
+    if schema_version not in SUPPORTED_SCHEMA_VERSIONS:
+        raise IncompatibleArchiveException(
+            f"schema version {schema_version} is not supported, this build imports versions "
+            f"{', '.join(str(v) for v in sorted(SUPPORTED_SCHEMA_VERSIONS))}"
+        )
+    try:
+        ArchiveContent(header.get("content"))
+    except ValueError:
+        raise IncompatibleArchiveException(f"unknown content mode '{header.get('content')}'")
+    return schema_version
+
+
+class ChunkReader(io.RawIOBase):
+    """Readable file object over an iterator of byte chunks"""
+
+    def __init__(self, chunks: Iterable[bytes]):
+        self._chunks = iter(chunks)
+        self._buffer = b""
+
+    def readable(self) -> bool:
+        return True
+
+    def readinto(self, target) -> int:
+        while not self._buffer:
+            try:
+                self._buffer = next(self._chunks)
+            except StopIteration:
+                return 0
+        size = min(len(target), len(self._buffer))
+        target[:size] = self._buffer[:size]
+        self._buffer = self._buffer[size:]
+        return size
+
+
+def add_json_member(tar: tarfile.TarFile, name: str, data) -> None:
+    """Add a JSON document as a member of a tar stream"""
+    content = json.dumps(data, sort_keys=True).encode("utf-8")
+    add_stream_member(tar, name, len(content), [content])
+
+
+def add_stream_member(tar: tarfile.TarFile, name: str, size: int, chunks: Iterable[bytes]) -> None:
+    """Add a member of known size from an iterator of chunks"""
+    info = tarfile.TarInfo(name)
+    info.size = size
+    info.mtime = int(time.time())
+    tar.addfile(info, io.BufferedReader(ChunkReader(chunks)))
+
+
+def write_archive_to_store(
+    store: SubmissionStore, key: str, write_members: Callable[[tarfile.TarFile], None]
+) -> StoredObject:
+    """
+    Stream an archive into the store while it is being written
+
+    The members are written by a producer thread into a pipe the store reads from, so the archive never
+    lives in memory as a whole. A failed producer deletes the partially uploaded object.
+    """
+    read_fd, write_fd = os.pipe()
+    reader = os.fdopen(read_fd, "rb")
+    writer = os.fdopen(write_fd, "wb")
+    errors = []
+
+    def produce():
+        try:
+            with tarfile.open(fileobj=writer, mode="w|gz") as tar:
+                write_members(tar)
+        except BaseException as e:
+            errors.append(e)
+        finally:
+            try:
+                writer.close()
+            except OSError:
+                pass
+
+    producer = threading.Thread(target=in_current_context(produce), name="corpus-export", daemon=True)
+    producer.start()
+    try:
+        stored = store.put(key, reader, ARCHIVE_CONTENT_TYPE)
+    finally:
+        # Unblocks the producer if the upload stopped reading early
+        reader.close()
+        producer.join()
+
+    if errors:
+        store.delete(key)
+        raise errors[0]
+    return stored
+
+
+def read_archive_members(chunks: Iterable[bytes]) -> Iterator[tuple]:
+    """
+    Iterate over the members of an archive stream as (name, file object) pairs, header first
+
+    Raises:
+        IncompatibleArchiveException: If the stream is not a readable archive or does not start with a header
+    """
+    try:
+        with tarfile.open(fileobj=io.BufferedReader(ChunkReader(chunks)), mode="r|gz") as tar:
+            first = True
+            for member in tar:
+                if first and member.name != HEADER_MEMBER:
+                    raise IncompatibleArchiveException(f"archive must start with {HEADER_MEMBER}")
+                first = False
+                if not member.isfile():
+                    continue
+                yield member.name, tar.extractfile(member)
+            if first:
+                raise IncompatibleArchiveException("archive is empty")
+    except (tarfile.TarError, EOFError, OSError) as e:
+        raise IncompatibleArchiveException(f"unreadable archive: {str(e)}")
+
+
+def read_json_member(fileobj) -> Optional[dict]:
+    return json.loads(fileobj.read().decode("utf-8"))
@@ -0,0 +1,82 @@
+from typing import Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, HTTPException
+from fastapi.responses import StreamingResponse
+from sqlmodel import Session
+
+from app.domains.corpus.corpus_archive import ARCHIVE_CONTENT_TYPE
+from app.domains.corpus.corpus_service import CorpusService
+from app.domains.corpus.dto.corpus_dto import (
+    CorpusExportDto,
+    CorpusExportResponseDto,
+    CorpusImportDto,
+    CorpusImportResponseDto,
+)
+from app.domains.storage.exceptions import StorageException
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException, ValidationException
+
+router = APIRouter(prefix="/projects", tags=["corpus"])
+
+
+def get_corpus_service(session: Session = Depends(get_session)) -> CorpusService:
+    """Dependency to get corpus service"""
+    return CorpusService(session)
+
+
+@router.post("/{project_uuid}/export", response_model=CorpusExportResponseDto, status_code=201)
+async def export_corpus(
+    project_uuid: UUID,
+    options: Optional[CorpusExportDto] = None,
+    service: CorpusService = Depends(get_corpus_service),
+):
+    """
+    Export the submissions, similarities and detection runs of a project as an archive in the storage backend
+
+    - **content**: `blobs` to include file contents, `fingerprints` to only include their fingerprints
+    - **anonymize**: Replace author and record identifiers with pseudonyms and drop links and client metadata
+    """
+    try:
+        return service.export_project(project_uuid, options or CorpusExportDto())
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except (DatabaseException, StorageException) as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/{project_uuid}/exports/{export_id}")
+async def download_corpus_export(
+    project_uuid: UUID, export_id: UUID, service: CorpusService = Depends(get_corpus_service)
+):
+    """Download an export archive"""
+    try:
+        content = service.open_export(project_uuid, export_id)
+        return StreamingResponse(
+            content,
+            media_type=ARCHIVE_CONTENT_TYPE,
+            headers={"Content-Disposition": f'attachment; filename="{export_id}.tar.gz"'},
+        )
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except StorageException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/{project_uuid}/import", response_model=CorpusImportResponseDto, status_code=201)
+async def import_corpus(
+    project_uuid: UUID, import_data: CorpusImportDto, service: CorpusService = Depends(get_corpus_service)
+):
+    """
+    Import a corpus archive into a project, recreating its resources with new IDs
+
+    - **archive_key**: Storage key of the archive, under `exports/`
+    """
+    try:
+        return service.import_archive(project_uuid, import_data)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except ValidationException as e:
+        raise HTTPException(status_code=422, detail=e.detail)
+    except (DatabaseException, StorageException) as e:
+        raise HTTPException(status_code=500, detail=str(e))
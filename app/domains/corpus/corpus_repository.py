@@ -0,0 +1,125 @@
+import logging
+from itertools import groupby
+from typing import Iterator, List
+from uuid import UUID
+
+from sqlmodel import Session, SQLModel, select
+
+from app.domains.quotas.quota_repository import record_usage
+from app.domains.runs.runs_models import (
+    UNFINISHED_RUN_STATUSES,
+    DetectionFragment,
+    DetectionPair,
+    DetectionRun,
+    DetectionRunParticipant,
+)
+from app.domains.submissions.submissions_models import Submission, SubmissionSimilarity
+from app.shared.exceptions import DatabaseException
+from app.shared.tenancy import require_project_access
+
+logger = logging.getLogger(__name__)
+
+
+class CorpusRepository:
+    """Repository reading a whole project for export and writing imported resources"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def get_submissions(self, project_uuid: UUID) -> List[Submission]:
+        """Get every submission of a project, oldest first"""
+        try:
+            statement = (
+                select(Submission).where(Submission.project_uuid == project_uuid).order_by(Submission.created_at)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get submissions of project: {str(e)}")
+
+    def iter_similarity_batches(self, project_uuid: UUID, batch_size: int) -> Iterator[List[SubmissionSimilarity]]:
+        """Iterate over the similarity records of a project in batches"""
+        offset = 0
+        while True:
+            try:
+                statement = (
+                    select(SubmissionSimilarity)
+                    .where(SubmissionSimilarity.project_uuid == project_uuid)
+                    .order_by(SubmissionSimilarity.created_at, SubmissionSimilarity.id)
+                    .offset(offset)
+                    .limit(batch_size)
+                )
+                similarities = list(self.session.exec(statement).all())
+            except Exception as e:
+                raise DatabaseException(f"Failed to get similarities of project: {str(e)}")
+            if not similarities:
+                return
+            yield similarities
+            offset += batch_size
+
+    def get_finished_runs(self, project_uuid: UUID) -> List[DetectionRun]:
+        """Get the runs of a project that are no longer in progress, oldest first"""
+        try:
+            statement = (
+                select(DetectionRun)
+                .where(DetectionRun.project_uuid == project_uuid, DetectionRun.status.notin_(UNFINISHED_RUN_STATUSES))
+                .order_by(DetectionRun.started_at)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection runs of project: {str(e)}")
+
+    def get_participants(self, run_id: UUID) -> List[DetectionRunParticipant]:
+        """Get the participants of a run"""
+        try:
+            statement = select(DetectionRunParticipant).where(DetectionRunParticipant.run_id == run_id)
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection run participants: {str(e)}")
+
+    def iter_pair_batches(self, run_id: UUID, batch_size: int) -> Iterator[List[tuple]]:
+        """Iterate over the pairs of a run with their fragments, as batches of (pair, fragments)"""
+        offset = 0
+        while True:
+            try:
+                pairs = list(
+                    self.session.exec(
+                        select(DetectionPair)
+                        .where(DetectionPair.run_id == run_id)
+                        .order_by(DetectionPair.created_at, DetectionPair.id)
+                        .offset(offset)
+                        .limit(batch_size)
+                    ).all()
+                )
+                if not pairs:
+                    return
+                fragments = self.session.exec(
+                    select(DetectionFragment).where(DetectionFragment.pair_id.in_([pair.id for pair in pairs]))
+                ).all()
+            except Exception as e:
+                raise DatabaseException(f"Failed to get detection pairs of run: {str(e)}")
+
+            by_pair = {}
+            for fragment in fragments:
+                by_pair.setdefault(fragment.pair_id, []).append(fragment)
+            yield [(pair, by_pair.get(pair.id, [])) for pair in pairs]
+            offset += batch_size
+
+    def save_all(self, records: List[SQLModel]) -> None:
+        """
+        Insert imported records in one transaction, with the usage their submissions count against quotas
+
+        Records are flushed in their order, by runs of one model, so that each can reference those before it.
+        """
+        for project_uuid in {record.project_uuid for record in records if isinstance(record, Submission)}:
+            require_project_access(project_uuid)
+        try:
+            for _, group in groupby(records, key=type):
+                self.session.add_all(list(group))
+                self.session.flush()
+            for record in records:
+                if isinstance(record, Submission):
+                    record_usage(self.session, record.project_uuid, submissions=1, stored_bytes=record.stored_bytes)
+            self.session.commit()
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save imported records: {str(e)}")
@@ -0,0 +1,467 @@
+import logging
+import re
+import shutil
+import tempfile
+from pathlib import Path
+from typing import Dict, Iterator, List, Optional
+from uuid import UUID, uuid4, uuid5
+
+from sqlmodel import Session, SQLModel
+
+from app.domains.corpus.corpus_archive import (
+    ARCHIVE_FORMAT,
+    ARCHIVE_SCHEMA_VERSION,
+    HEADER_MEMBER,
+    PAIRS_PER_MEMBER,
+    ArchiveContent,
+    IncompatibleArchiveException,
+    add_json_member,
+    add_stream_member,
+    check_header,
+    export_accessible,
+    export_key,
+    read_archive_members,
+    read_json_member,
+    write_archive_to_store,
+)
+from app.domains.corpus.corpus_repository import CorpusRepository
+from app.domains.corpus.dto.corpus_dto import (
+    CorpusExportDto,
+    CorpusExportResponseDto,
+    CorpusImportDto,
+    CorpusImportResponseDto,
+)
+from app.domains.runs.dto.run_response_dto import (
+    DetectionFragmentDto,
+    DetectionPairDto,
+    DetectionRunDto,
+    DetectionRunParticipantDto,
+)
+from app.domains.runs.runs_models import DetectionFragment, DetectionPair, DetectionRun, DetectionRunParticipant
+from app.domains.storage.exceptions import StoredObjectNotFoundException
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.storage.submission_store import normalize_key
+from app.domains.submissions.submissions_models import Submission, SubmissionSimilarity
+from app.domains.tokenization.streaming_source import decode_source
+from app.shared.content_hash import parse_hash_algorithm
+from app.shared.exceptions import NotFoundException, ValidationException
+from app.shared.timestamps import to_rfc3339, utc_now
+
+logger = logging.getLogger(__name__)
+
+_BLOB_MEMBER = re.compile(r"^blobs/(?:(sha256|blake3)/)?([0-9a-f]{64})$")
+_SUBMISSION_MEMBER = re.compile(r"^submissions/([^/]+)\.json$")
+_SIMILARITY_MEMBER = re.compile(r"^similarities/([^/]+)\.json$")
+_RUN_MEMBER = re.compile(r"^runs/([^/]+)/run\.json$")
+_PAIRS_MEMBER = re.compile(r"^runs/([^/]+)/pairs-\d+\.json$")
+
+# Similarity records read per query while exporting
+SIMILARITY_BATCH_SIZE = 500
+
+
+class Pseudonymizer:
+    """Maps identifiers to pseudonyms that are stable within one export, or keeps them when disabled"""
+
+    def __init__(self, enabled: bool):
+        self.enabled = enabled
+        self._namespace = uuid4()
+
+    def __call__(self, value: Optional[UUID]) -> Optional[str]:
+        if value is None:
+            return None
+        return str(uuid5(self._namespace, str(value)) if self.enabled else value)
+
+
+class CorpusService:
+    """
+    Service exporting a project as a portable corpus archive and importing such archives
+
+    Anonymized exports replace submission, group, submitter and record identifiers with pseudonyms, so
+    cross-references survive, and drop links, descriptions, client metadata and legal hold reasons.
+    """
+
+    def __init__(
+        self,
+        session: Session,
+        storage_service: Optional[SubmissionStorageService] = None,
+        fingerprint_service=None,
+    ):
+        self.session = session
+        self.repository = CorpusRepository(session)
+        self.storage_service = storage_service or SubmissionStorageService()
+        self._fingerprint_service = fingerprint_service
+
+    @property
+    def fingerprint_service(self):
+        """Fingerprint service of fingerprint-only exports, created on first use"""
+        if self._fingerprint_service is None:
+            from app.shared.services import get_fingerprint_service
+
+            self._fingerprint_service = get_fingerprint_service()
+        return self._fingerprint_service
+
+    def export_project(self, project_uuid: UUID, options: CorpusExportDto) -> CorpusExportResponseDto:
+        """
+        Export the submissions, similarities and finished runs of a project into the storage backend
+
+        Raises:
+            NotFoundException: If the project has no submissions
+        """
+        submissions = self.repository.get_submissions(project_uuid)
+        if not submissions:
+            raise NotFoundException(f"No submissions found for project {project_uuid}")
+
+        content = ArchiveContent(options.content)
+        pseudonym = Pseudonymizer(options.anonymize)
+        export_id = uuid4()
+        exported_at = utc_now()
+        result = CorpusExportResponseDto(
+            export_id=export_id,
+            project_uuid=project_uuid,
+            archive_key=export_key(project_uuid, export_id),
+            schema_version=ARCHIVE_SCHEMA_VERSION,
+            content=content.value,
+            anonymized=options.anonymize,
+            exported_at=exported_at,
+            size_bytes=0,
+            submissions=0,
+            blobs=0,
+            similarities=0,
+            runs=0,
+            pairs=0,
+        )
+
+        header = {
+            "format": ARCHIVE_FORMAT,
+            "schema_version": ARCHIVE_SCHEMA_VERSION,
+            "content": content.value,
+            "anonymized": options.anonymize,
+            "exported_at": to_rfc3339(exported_at),
+            "source_project_uuid": None if options.anonymize else str(project_uuid),
+        }
+        if content == ArchiveContent.FINGERPRINTS:
+            header["fingerprint_parameters"] = self.fingerprint_service.parameters
+
+        def write_members(tar):
+            manifests = {submission.id: self.storage_service.get_manifest(submission) for submission in submissions}
+            add_json_member(tar, HEADER_MEMBER, header)
+
+            if content == ArchiveContent.BLOBS:
+                blob_sizes = {
+                    self.storage_service.entry_blob(entry): entry["size"]
+                    for manifest in manifests.values()
+                    if manifest
+                    for entry in manifest["files"].values()
+                }
+                for digest, algorithm in sorted(blob_sizes):
+                    chunks = self.storage_service.blobs.stream(digest, algorithm)
+                    size = blob_sizes[(digest, algorithm)]
+                    add_stream_member(tar, f"blobs/{algorithm.value}/{digest}", size, chunks)
+                    result.blobs += 1
+
+            for submission in submissions:
+                if manifests[submission.id] is None:
+                    result.submissions_without_files.append(submission.id)
+                document = self._submission_document(submission, manifests[submission.id], content, pseudonym)
+                add_json_member(tar, f"submissions/{document['submission']['id']}.json", document)
+                result.submissions += 1
+
+            for batch in self.repository.iter_similarity_batches(project_uuid, SIMILARITY_BATCH_SIZE):
+                for similarity in batch:
+                    document = self._similarity_document(similarity, content, pseudonym)
+                    add_json_member(tar, f"similarities/{document['id']}.json", document)
+                    result.similarities += 1
+
+            for run in self.repository.get_finished_runs(project_uuid):
+                run_ref = pseudonym(run.id)
+                document = self._run_document(run, pseudonym, options.anonymize)
+                add_json_member(tar, f"runs/{run_ref}/run.json", document)
+                for index, batch in enumerate(self.repository.iter_pair_batches(run.id, PAIRS_PER_MEMBER)):
+                    pairs = [self._pair_document(pair, fragments, pseudonym) for pair, fragments in batch]
+                    document = {"run_id": run_ref, "pairs": pairs}
+                    add_json_member(tar, f"runs/{run_ref}/pairs-{index:05d}.json", document)
+                    result.pairs += len(pairs)
+                result.runs += 1
+
+        stored = write_archive_to_store(self.storage_service.store, result.archive_key, write_members)
+        result.size_bytes = stored.size
+
+        logger.info(
+            f"Exported project {project_uuid} to {result.archive_key}: {result.submissions} submissions, "
+            f"{result.blobs} blobs, {result.runs} runs, {result.pairs} pairs"
+            f"{' (anonymized)' if options.anonymize else ''}"
+        )
+        return result
+
+    def _submission_document(self, submission: Submission, manifest: Optional[dict], content, pseudonym) -> dict:
+        data = submission.model_dump(mode="json", exclude={"project_uuid"})
+        data["id"] = pseudonym(submission.id)
+        data["group_uuid"] = pseudonym(submission.group_uuid)
+        data["submitted_by_uuid"] = pseudonym(submission.submitted_by_uuid)
+        if pseudonym.enabled:
+            data.update(
+                link=f"anonymized://submissions/{data['id']}",
+                description=None,
+                ip_address=None,
+                user_agent=None,
+                legal_hold_reason=None,
+            )
+
+        files = None
+        if manifest is not None:
+            files = {}
+            for path, entry in manifest["files"].items():
+                if content == ArchiveContent.BLOBS:
+                    files[path] = dict(entry)
+                    continue
+                content = self.storage_service.blobs.get(*self.storage_service.entry_blob(entry))
+                text = decode_source(content)
+                fingerprint_set = self.fingerprint_service.get_fingerprints(text, Path(path))
+                files[path] = {
+                    "size": entry["size"],
+                    "content_type": entry.get("content_type"),
+                    "fingerprints": [list(fingerprint) for fingerprint in fingerprint_set.fingerprints],
+                }
+        return {"submission": data, "files": files}
+
+    @staticmethod
+    def _similarity_document(similarity: SubmissionSimilarity, content, pseudonym) -> dict:
+        data = similarity.model_dump(mode="json", exclude={"project_uuid"})
+        data["id"] = pseudonym(similarity.id)
+        data["submission_id"] = pseudonym(similarity.submission_id)
+        data["compared_submission_id"] = pseudonym(similarity.compared_submission_id)
+        if content == ArchiveContent.FINGERPRINTS:
+            # Both hold code excerpts, which fingerprint-only archives must not carry
+            data.update(shared_blocks=None, visualization_data=None)
+        return data
+
+    def _run_document(self, run: DetectionRun, pseudonym, anonymize: bool) -> dict:
+        data = DetectionRunDto.model_validate(run).model_dump(mode="json")
+        data["id"] = pseudonym(run.id)
+        data["project_uuid"] = pseudonym(run.project_uuid)
+        data["trigger_submission_id"] = pseudonym(run.trigger_submission_id)
+        data["schedule_id"] = pseudonym(run.schedule_id)
+        if anonymize:
+            data["legal_hold_reason"] = None
+
+        participants = []
+        for participant in self.repository.get_participants(run.id):
+            participant_data = DetectionRunParticipantDto.model_validate(participant).model_dump(mode="json")
+            participant_data["submission_id"] = pseudonym(participant.submission_id)
+            participant_data["group_uuid"] = pseudonym(participant.group_uuid)
+            participant_data["submitted_by_uuid"] = pseudonym(participant.submitted_by_uuid)
+            participants.append(participant_data)
+        return {"run": data, "participants": participants}
+
+    @staticmethod
+    def _pair_document(pair: DetectionPair, fragments, pseudonym) -> dict:
+        data = DetectionPairDto.model_validate(pair).model_dump(mode="json")
+        for field in (
+            "id",
+            "run_id",
+            "project_uuid",
+            "submission_id",
+            "compared_submission_id",
+            "similarity_id",
+            "submitted_by_uuid",
+            "compared_submitted_by_uuid",
+        ):
+            data[field] = pseudonym(getattr(pair, field))
+        return {
+            "pair": data,
+            "fragments": [DetectionFragmentDto.model_validate(f).model_dump(mode="json") for f in fragments],
+        }
+
+    def open_export(self, project_uuid: UUID, export_id: UUID) -> Iterator[bytes]:
+        """Stream an export archive from the storage backend"""
+        return self.storage_service.store.stream(export_key(project_uuid, export_id))
+
+    def import_archive(self, project_uuid: UUID, import_data: CorpusImportDto) -> CorpusImportResponseDto:
+        """
+        Recreate the resources of an archive under a project, with new IDs and the same cross-references
+
+        Records are staged while the archive is read and saved in one transaction at its end. When the archive
+        fails part way, nothing is saved and the files already stored for its submissions are deleted.
+
+        Raises:
+            ValidationException: If the archive key is outside the exports area
+            IncompatibleArchiveException: If the archive is unreadable or uses an unsupported schema version
+        """
+        archive_key = normalize_key(import_data.archive_key)
+        if not archive_key.startswith("exports/"):
+            raise ValidationException("Corpus archives are imported from the exports/ area of the storage backend")
+        if not export_accessible(archive_key):
+            raise StoredObjectNotFoundException(archive_key)
+
+        state = _ImportState(project_uuid)
+        blob_directory = Path(tempfile.mkdtemp(prefix="corpus_import_"))
+        try:
+            for name, fileobj in read_archive_members(self.storage_service.store.stream(archive_key)):
+                if name == HEADER_MEMBER:
+                    state.header = read_json_member(fileobj)
+                    state.schema_version = check_header(state.header)
+                elif name.startswith("blobs/"):
+                    match = _BLOB_MEMBER.match(name)
+                    if not match:
+                        raise IncompatibleArchiveException(f"invalid blob member {name}")
+                    # Schema version 1 archives name SHA-256 blobs by their digest only
+                    algorithm = parse_hash_algorithm(match.group(1))
+                    (blob_directory / algorithm.value).mkdir(exist_ok=True)
+                    with open(blob_directory / algorithm.value / match.group(2), "wb") as target:
+                        shutil.copyfileobj(fileobj, target)
+                elif _SUBMISSION_MEMBER.match(name):
+                    self._import_submission(state, read_json_member(fileobj), blob_directory)
+                elif _SIMILARITY_MEMBER.match(name):
+                    self._import_similarity(state, read_json_member(fileobj))
+                elif _RUN_MEMBER.match(name):
+                    self._import_run(state, read_json_member(fileobj))
+                elif _PAIRS_MEMBER.match(name):
+                    self._import_pairs(state, read_json_member(fileobj))
+                else:
+                    logger.warning(f"Ignoring unknown member {name} of corpus archive {archive_key}")
+            self.repository.save_all(state.records)
+        except Exception:
+            self._discard_files(state)
+            raise
+        finally:
+            shutil.rmtree(blob_directory, ignore_errors=True)
+
+        header = state.header
+        source_project = header.get("source_project_uuid")
+        result = CorpusImportResponseDto(
+            project_uuid=project_uuid,
+            source_project_uuid=UUID(source_project) if source_project else None,
+            schema_version=state.schema_version,
+            content=header["content"],
+            anonymized=bool(header.get("anonymized")),
+            submission_ids=state.submission_ids,
+            **state.counts,
+        )
+        logger.info(
+            f"Imported {archive_key} into project {project_uuid}: {result.submissions} submissions, "
+            f"{result.files} files, {result.runs} runs, {result.pairs} pairs"
+        )
+        return result
+
+    def _discard_files(self, state: "_ImportState") -> None:
+        """Delete the files stored for the submissions of a failed import"""
+        for submission in state.submissions:
+            try:
+                self.storage_service.delete_submission_files(submission)
+            except Exception as e:
+                logger.error(f"Failed to delete stored files of discarded imported submission {submission.id}: {e}")
+
+    def _import_submission(self, state: "_ImportState", document: dict, blob_directory: Path) -> None:
+        data = dict(document["submission"])
+        archive_id = data.pop("id")
+        # Stored bytes are counted again as the files of the archive are stored
+        submission = Submission.model_validate(
+            {**data, "id": uuid4(), "project_uuid": state.project_uuid, "stored_bytes": 0}
+        )
+        state.records.append(submission)
+        state.submissions.append(submission)
+        state.submission_ids[archive_id] = submission.id
+        state.counts["submissions"] += 1
+
+        files = document.get("files")
+        if files and state.header["content"] == ArchiveContent.BLOBS.value:
+            summary = self.storage_service.ingest_manifest(submission, files, blob_directory)
+            submission.stored_bytes = summary["total_bytes"]
+            state.counts["files"] += summary["file_count"]
+
+    def _import_similarity(self, state: "_ImportState", data: dict) -> None:
+        data = dict(data)
+        archive_id = data.pop("id")
+        similarity = SubmissionSimilarity.model_validate(
+            {
+                **data,
+                "id": uuid4(),
+                "project_uuid": state.project_uuid,
+                "submission_id": state.submission(data["submission_id"]),
+                "compared_submission_id": state.submission(data["compared_submission_id"]),
+            }
+        )
+        state.records.append(similarity)
+        state.similarity_ids[archive_id] = similarity.id
+        state.counts["similarities"] += 1
+
+    def _import_run(self, state: "_ImportState", document: dict) -> None:
+        run_data = DetectionRunDto.model_validate(document["run"]).model_dump(exclude={"id"})
+        trigger_submission = run_data.get("trigger_submission_id")
+        run = DetectionRun.model_validate(
+            {
+                **run_data,
+                "id": uuid4(),
+                "project_uuid": state.project_uuid,
+                "trigger_submission_id": state.submission(trigger_submission) if trigger_submission else None,
+                # Schedules are not exported, the imported project has none of them
+                "schedule_id": None,
+            }
+        )
+        participants = []
+        for participant_data in document.get("participants", []):
+            participant = DetectionRunParticipantDto.model_validate(participant_data)
+            participants.append(
+                DetectionRunParticipant(
+                    run_id=run.id,
+                    submission_id=state.submission(participant.submission_id),
+                    group_uuid=participant.group_uuid,
+                    submitted_by_uuid=participant.submitted_by_uuid,
+                )
+            )
+        state.records.append(run)
+        state.records.extend(participants)
+        state.run_ids[document["run"]["id"]] = run.id
+        state.counts["runs"] += 1
+
+    def _import_pairs(self, state: "_ImportState", document: dict) -> None:
+        run_id = state.run_ids.get(document["run_id"])
+        if run_id is None:
+            raise IncompatibleArchiveException(f"pairs of unknown run {document['run_id']}")
+
+        records = []
+        for entry in document["pairs"]:
+            pair_data = DetectionPairDto.model_validate(entry["pair"]).model_dump(exclude={"id"})
+            similarity_id = pair_data.get("similarity_id")
+            pair = DetectionPair.model_validate(
+                {
+                    **pair_data,
+                    "id": uuid4(),
+                    "run_id": run_id,
+                    "project_uuid": state.project_uuid,
+                    "submission_id": state.submission(pair_data["submission_id"]),
+                    "compared_submission_id": state.submission(pair_data["compared_submission_id"]),
+                    "similarity_id": state.similarity_ids.get(str(similarity_id)) if similarity_id else None,
+                }
+            )
+            records.append(pair)
+            for fragment_data in entry.get("fragments", []):
+                fragment = DetectionFragmentDto.model_validate(fragment_data).model_dump(exclude={"id"})
+                records.append(DetectionFragment.model_validate({**fragment, "pair_id": pair.id, "run_id": run_id}))
+
+        # Pairs are flushed before their fragments reference them
+        state.records.extend(record for record in records if isinstance(record, DetectionPair))
+        state.records.extend(record for record in records if isinstance(record, DetectionFragment))
+        state.counts["pairs"] += len(document["pairs"])
+
+
+class _ImportState:
+    """Staged records, ID mappings and counters of one import"""
+
+    def __init__(self, project_uuid: UUID):
+        self.project_uuid = project_uuid
+        self.records: List[SQLModel] = []
+        self.submissions: List[Submission] = []
+        self.header: Dict = {}
+        self.schema_version: Optional[int] = None
+        self.submission_ids: Dict[str, UUID] = {}
+        self.similarity_ids: Dict[str, UUID] = {}
+        self.run_ids: Dict[str, UUID] = {}
+        self.counts = {"submissions": 0, "files": 0, "similarities": 0, "runs": 0, "pairs": 0}
+
+    def submission(self, archive_id) -> UUID:
+        """New ID of a submission of the archive"""
+        new_id = self.submission_ids.get(str(archive_id))
+        if new_id is None:
+            raise IncompatibleArchiveException(f"reference to unknown submission {archive_id}")
+        return new_id
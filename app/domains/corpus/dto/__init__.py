@@ -0,0 +1,8 @@
+from .corpus_dto import CorpusExportDto, CorpusExportResponseDto, CorpusImportDto, CorpusImportResponseDto
+
+__all__ = [
+    "CorpusExportDto",
+    "CorpusExportResponseDto",
+    "CorpusImportDto",
+    "CorpusImportResponseDto",
+]
@@ -0,0 +1,94 @@
+from typing import Dict, List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field
+
+from app.domains.corpus.corpus_archive import ArchiveContent
+from app.shared.timestamps import UtcTimestamp
+
+
+class CorpusExportDto(BaseModel):
+    """DTO for the options of a corpus export"""
+
+    model_config = ConfigDict(
+        use_enum_values=True, json_schema_extra={"example": {"content": "blobs", "anonymize": False}}
+    )
+
+    content: ArchiveContent = Field(
+        default=ArchiveContent.BLOBS, description="Export file contents ('blobs') or only their fingerprints"
+    )
+    anonymize: bool = Field(
+        default=False, description="Replace author, group and record identifiers and drop links and client metadata"
+    )
+
+
+class CorpusExportResponseDto(BaseModel):
+    """DTO for a finished corpus export"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "export_id": "550e8400-e29b-41d4-a716-446655440050",
+                "project_uuid": "550e8400-e29b-41d4-a716-446655440000",
+                "archive_key": "exports/projects/550e8400-e29b-41d4-a716-446655440000/"
+                "550e8400-e29b-41d4-a716-446655440050.tar.gz",
+                "schema_version": 1,
+                "content": "blobs",
+                "anonymized": False,
+                "size_bytes": 1048576,
+                "submissions": 300,
+                "blobs": 412,
+                "similarities": 44850,
+                "runs": 300,
+                "pairs": 44850,
+            }
+        }
+    )
+
+    export_id: UUID
+    project_uuid: UUID
+    archive_key: str
+    schema_version: int
+    content: str
+    anonymized: bool
+    exported_at: UtcTimestamp
+    size_bytes: int
+    submissions: int
+    blobs: int
+    similarities: int
+    runs: int
+    pairs: int
+    submissions_without_files: List[UUID] = []
+
+
+class CorpusImportDto(BaseModel):
+    """DTO for importing a corpus archive held by the storage backend"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "archive_key": "exports/projects/550e8400-e29b-41d4-a716-446655440000/"
+                "550e8400-e29b-41d4-a716-446655440050.tar.gz"
+            }
+        }
+    )
+
+    archive_key: str = Field(description="Storage key of the archive, as returned by the export")
+
+
+class CorpusImportResponseDto(BaseModel):
+    """DTO for the outcome of a corpus import"""
+
+    project_uuid: UUID
+    source_project_uuid: Optional[UUID] = None
+    schema_version: int
+    content: str
+    anonymized: bool
+    submissions: int
+    files: int
+    similarities: int
+    runs: int
+    pairs: int
+    submission_ids: Dict[str, UUID] = Field(
+        default_factory=dict, description="New submission ID of each submission ID found in the archive"
+    )
@@ -0,0 +1,136 @@
+"""
+Comparability
+Decides which submissions carry enough code to be scored against each other.
+
+A token is comparable when it says something about the code: comments, parse errors and the root node spanning
+a whole file are not. Submissions without a single comparable token (no supported file, empty files, comments
+only) are never scored, their pairs are recorded as not comparable with the reason. Pairs where a side has fewer
+comparable tokens than the configured minimum are scored but flagged as low confidence, a handful of tokens
+matching by chance.
+"""
+
+from dataclasses import dataclass, field
+from enum import Enum
+from typing import Any, Dict, Iterable, List, Optional, Tuple
+
+# Default comparable tokens below which the pairs of a submission are flagged as low confidence
+DEFAULT_MIN_COMPARABLE_TOKENS = 20
+
+# Types of the root node of the tree-sitter grammars, spanning a whole file whatever it holds
+ROOT_NODE_TYPES = frozenset(
+    {
+        "chunk",
+        "compilation_unit",
+        "document",
+        "module",
+        "program",
+        "source",
+        "source_file",
+        "stylesheet",
+        "translation_unit",
+    }
+)
+# Node types of parse errors, which tell nothing about the code
+ERROR_NODE_TYPES = frozenset({"ERROR"})
+
+
+class NotComparableReason(str, Enum):
+    """Enumeration for why a submission is not compared"""
+
+    NO_SUPPORTED_FILES = "no_supported_files"  # no file in a supported language
+    EMPTY = "empty"  # supported files, but no token at all
+    COMMENTS_ONLY = "comments_only"  # tokens, but only comments and root nodes
+
+
+# Sentence shown in reports for each reason
+REASON_DESCRIPTIONS = {
+    NotComparableReason.NO_SUPPORTED_FILES: "no file in a supported language",
+    NotComparableReason.EMPTY: "no code in its files",
+    NotComparableReason.COMMENTS_ONLY: "only comments in its files",
+}
+
+
+def is_comment(token: Dict[str, Any]) -> bool:
+    return "comment" in token.get("type", "")
+
+
+def is_comparable(token: Dict[str, Any]) -> bool:
+    """Whether a token counts towards the code of a submission"""
+    token_type = token.get("type", "")
+    return token_type not in ROOT_NODE_TYPES and token_type not in ERROR_NODE_TYPES and not is_comment(token)
+
+
+def comparable_token_count(tokens: Iterable[Dict[str, Any]]) -> int:
+    return sum(1 for token in tokens if is_comparable(token))
+
+
+def not_comparable_reason(files_count: int, tokens: Iterable[Dict[str, Any]]) -> Optional[NotComparableReason]:
+    """Why a submission of files_count supported files and these tokens is not compared, None when it is"""
+    if files_count == 0:
+        return NotComparableReason.NO_SUPPORTED_FILES
+    has_tokens = has_comments = False
+    for token in tokens:
+        if is_comparable(token):
+            return None
+        has_tokens = True
+        has_comments = has_comments or is_comment(token)
+    return NotComparableReason.COMMENTS_ONLY if has_tokens and has_comments else NotComparableReason.EMPTY
+
+
+def describe_reason(reason) -> str:
+    """Sentence of a reason, its raw value when unknown"""
+    try:
+        return REASON_DESCRIPTIONS[NotComparableReason(reason)]
+    except ValueError:
+        return str(reason)
+
+
+def is_low_confidence(comparable_tokens1: int, comparable_tokens2: int, minimum: int) -> bool:
+    """Whether the score of a pair rests on too few tokens on either side, a minimum of 0 never flags"""
+    return min(comparable_tokens1, comparable_tokens2) < minimum
+
+
+@dataclass
+class PairComparability:
+    """Comparability of both sides of a pair, sides keyed submission1 and submission2 like the similarity details"""
+
+    comparable_tokens: Dict[str, int]
+    reasons: Dict[str, Optional[NotComparableReason]] = field(default_factory=dict)
+    low_confidence: bool = False
+
+    @property
+    def comparable(self) -> bool:
+        return not any(self.reasons.values())
+
+    @property
+    def error_message(self) -> Optional[str]:
+        """Reasons of the sides not compared, None when both are"""
+        if self.comparable:
+            return None
+        return "Not comparable: " + "; ".join(
+            f"{side} has {describe_reason(reason)}" for side, reason in self.reasons.items() if reason
+        )
+
+    def to_details(self) -> Dict[str, Any]:
+        """Entries of the similarity details of the pair"""
+        return {
+            "comparable_tokens": dict(self.comparable_tokens),
+            "not_comparable": {side: reason.value for side, reason in self.reasons.items() if reason},
+            "low_confidence": self.low_confidence,
+        }
+
+
+def assess_pair(
+    sides: List[Tuple[int, List[Dict[str, Any]]]], minimum: int = DEFAULT_MIN_COMPARABLE_TOKENS
+) -> PairComparability:
+    """
+    Comparability of a pair from the supported files count and the tokens of each side
+
+    Pairs with a side not comparable are never low confidence: they are not scored at all.
+    """
+    names = ("submission1", "submission2")
+    counts = {name: comparable_token_count(tokens) for name, (_, tokens) in zip(names, sides)}
+    reasons = {name: not_comparable_reason(files_count, tokens) for name, (files_count, tokens) in zip(names, sides)}
+    comparability = PairComparability(counts, reasons)
+    comparability.low_confidence = comparability.comparable and is_low_confidence(*counts.values(), minimum)
+    return comparability
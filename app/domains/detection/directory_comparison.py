@@ -0,0 +1,353 @@
+"""
+Directory Comparison
+Compares the supported files of two directories, shared by detection runs and the pamp_detect.py CLI.
+
+Nothing is read from or written to the database: runs fetch the submissions and store the results, the CLI
+compares directories of the filesystem and writes a report from the same results, so both score pairs alike.
+"""
+
+import logging
+import time
+from pathlib import Path
+from typing import Any, Callable, Dict, List, Optional
+
+from app.domains.detection.comparability import PairComparability, assess_pair
+from app.domains.detection.file_errors import FileErrorLog, FileStage, is_systemic
+from app.domains.fingerprints.fingerprint_models import FingerprintCacheStats
+from app.domains.fingerprints.fingerprinting import fingerprint_similarity
+from app.domains.runs.run_progress import NULL_RUN_PROGRESS, RunProgress
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.domains.tokenization.code_metrics import CodeMetricsCollector
+from app.domains.tokenization.streaming_source import decode_source
+from app.shared.profiling import NULL_PROFILER, StageProfiler
+from app.shared.timeouts import checkpoint
+
+logger = logging.getLogger(__name__)
+
+
+def relative_path(file_path: Path, root: Path) -> str:
+    """Identity of a submission file everywhere: its path from the submission root, like the stored keys"""
+    try:
+        return file_path.relative_to(root).as_posix()
+    except ValueError:
+        return file_path.name
+
+
+def read_source(file_path) -> str:
+    """Read and decode a source file whatever its encoding, raising the errors of reading the file"""
+    with open(file_path, "rb") as f:
+        content = decode_source(f.read())
+    logger.debug(f"Successfully read {file_path}")
+    return content
+
+
+def assess_comparability(
+    repo1_files: List[Path], tokens1: List[dict], repo2_files: List[Path], tokens2: List[dict]
+) -> PairComparability:
+    """Comparability of the tokens of both submissions, flagged low confidence below the configured minimum"""
+    from app.config.config import get_settings
+
+    return assess_pair(
+        [(len(repo1_files), tokens1), (len(repo2_files), tokens2)],
+        get_settings().detection_min_comparable_tokens,
+    )
+
+
+def not_comparable_results(comparability: PairComparability, processing_time: float, details: Dict[str, Any]) -> dict:
+    """Results of a pair that is not scored, every metric at zero and the reasons in its details"""
+    return {
+        "jaccard_similarity": 0.0,
+        "type_similarity": 0.0,
+        "overall_similarity": 0.0,
+        "structural_similarity": 0.0,
+        "type_sequence_similarity": 0.0,
+        "flow_similarity": 0.0,
+        "operation_similarity": 0.0,
+        "processing_time_seconds": processing_time,
+        "status": SimilarityStatus.NOT_COMPARABLE,
+        "error_message": comparability.error_message,
+        "similarity_details": {"algorithm": "ast_similarity", **details, **comparability.to_details()},
+        "visualization_data": [],
+    }
+
+
+def visualize_file_pairs(
+    visualization_service,
+    repo1_files: List[Path],
+    repo2_files: List[Path],
+    repo1_path: Path,
+    repo2_path: Path,
+    file_errors: Optional[FileErrorLog] = None,
+    read: Callable[[Path], str] = read_source,
+) -> List[dict]:
+    """
+    Visualization of every pair of files sharing code, most similar first, files named by their path
+
+    Files that cannot be read and file pairs that cannot be visualized are skipped, and recorded in
+    file_errors when given. Systemic failures are raised.
+    """
+    files_with_similarities_visualization = []
+    file_errors = file_errors if file_errors is not None else FileErrorLog()
+
+    def read_side(side: str, file_path: Path, name: str) -> Optional[str]:
+        try:
+            return read(file_path)
+        except Exception as e:
+            if is_systemic(e):
+                raise
+            file_errors.record(side, name, FileStage.DECODING, e)
+            return None
+
+    for file_path in repo1_files:
+        name1 = relative_path(file_path, repo1_path)
+        content1 = read_side("submission1", file_path, name1)
+
+        for file_path2 in repo2_files:
+            checkpoint()
+            name2 = relative_path(file_path2, repo2_path)
+            content2 = read_side("submission2", file_path2, name2)
+
+            if content1 is None or content2 is None:
+                continue
+
+            try:
+                react_flow_data = visualization_service.generate_react_flow_ast(content1, content2, name1, name2, "elk")
+            except Exception as e:
+                if is_systemic(e):
+                    raise
+                logger.warning(f"Skipping the fragments of {name1} and {name2}: {e}")
+                file_errors.record("submission1", name1, FileStage.FRAGMENT_EXTRACTION, e)
+                continue
+
+            if react_flow_data.get("has_similarity", False):
+                files_with_similarities_visualization.append(
+                    {
+                        "file_pair": {
+                            "file_from_submission1": name1,
+                            "file_from_submission2": name2,
+                        },
+                        "react_flow": react_flow_data,
+                    }
+                )
+
+    # Sort by similarity, pairs of equal similarity by their paths
+    files_with_similarities_visualization.sort(
+        key=lambda x: (
+            -x["react_flow"].get("analysis_metadata", {}).get("average_similarity", 0.0),
+            x["file_pair"]["file_from_submission1"],
+            x["file_pair"]["file_from_submission2"],
+        )
+    )
+    return files_with_similarities_visualization
+
+
+class DirectoryComparator:
+    """
+    Compares two directories of source files: collects their supported files, fingerprints them through the
+    fingerprint service, scores the tokens of both sides and visualizes the file pairs sharing code
+    """
+
+    def __init__(
+        self,
+        tokenization_service,
+        fingerprint_service,
+        similarity_service,
+        visualization_service,
+        language: Optional[str] = None,
+        file_timeout_seconds: float = 0,
+    ):
+        self.tokenization_service = tokenization_service
+        self.fingerprint_service = fingerprint_service
+        self.similarity_service = similarity_service
+        self.visualization_service = visualization_service
+        # Only the files of this language are compared, every supported one when None
+        self.language = language
+        # Files taking longer to tokenize are recorded as timed out and skipped, 0 for no limit
+        self.file_timeout_seconds = file_timeout_seconds
+
+    def collect_files(self, root: Path) -> List[Path]:
+        """Supported files of a directory, only those of the language of the comparator when it has one"""
+        files = self.tokenization_service.extract_supported_files_from_directory(root)
+        if self.language is None:
+            return files
+        detect_language = self.tokenization_service._detect_language
+        return [file_path for file_path in files if detect_language(file_path) == self.language]
+
+    def measure_file(self, collector: CodeMetricsCollector, file_path: Path, tokens: List[dict]) -> None:
+        """Add the code metrics of a tokenized file, those of a file that cannot be read again being null"""
+        language = self.tokenization_service._detect_language(file_path)
+        try:
+            text = read_source(file_path)
+        except Exception as e:
+            if is_systemic(e):
+                raise
+            logger.warning(f"Code metrics of {file_path} not computed: {e}")
+            collector.add_failed(language)
+            return
+        collector.add(text, tokens, language)
+
+    def measure(self, root: Path) -> dict:
+        """Code metrics of the supported files of a directory, tokenized through the fingerprint service"""
+        collector = CodeMetricsCollector(self.tokenization_service.tokenizer_config)
+
+        def failed(path: Path, stage: FileStage, error: BaseException) -> None:
+            collector.add_failed(self.tokenization_service._detect_language(path))
+
+        for file_path, fingerprint_set in self.fingerprint_service.fingerprint_files(
+            [file_path for file_path in self.collect_files(root) if file_path.is_file()],
+            read_source,
+            on_error=failed,
+            timeout_seconds=self.file_timeout_seconds,
+        ):
+            self.measure_file(collector, file_path, fingerprint_set.tokens)
+        return collector.to_dict()
+
+    def compare(
+        self,
+        repo1_path: Path,
+        repo2_path: Path,
+        cache_stats: Optional[FingerprintCacheStats] = None,
+        profiler: StageProfiler = NULL_PROFILER,
+        run_progress: RunProgress = NULL_RUN_PROGRESS,
+        start_time: Optional[float] = None,
+        metrics: Optional[Dict[str, CodeMetricsCollector]] = None,
+    ) -> dict:
+        """
+        Results of the comparison of two directories, as stored for a submission similarity
+
+        Args:
+            repo1_path: Root of the first submission
+            repo2_path: Root of the second submission
+            cache_stats: Fingerprint cache statistics of the run, updated with the lookups
+            profiler: Profiler timing the stages of the run
+            run_progress: Progress of the run, told of each tokenized and skipped file
+            start_time: Time the comparison started at, counted in its processing time, now by default
+            metrics: Collectors of the code metrics of the sides to measure, "submission1" or "submission2",
+                given the files of the side as they are tokenized
+        """
+        start_time = time.time() if start_time is None else start_time
+
+        with profiler.stage("file_collection"):
+            repo1_compatible_files = self.collect_files(repo1_path)
+            repo2_compatible_files = self.collect_files(repo2_path)
+
+        # Tokenize all files, reusing cached fingerprints of unchanged files
+        tokens1 = []
+        tokens2 = []
+        fingerprints1 = set()
+        fingerprints2 = set()
+        # Token count of each tokenized file by path, like the file pairs of the visualization
+        files_tokens1: Dict[str, int] = {}
+        files_tokens2: Dict[str, int] = {}
+        # Files that failed are skipped, the comparison goes on with the others
+        file_errors = FileErrorLog()
+        metrics = metrics or {}
+
+        def file_failed(side: str, repo_path: Path):
+            def record(path: Path, stage: FileStage, error: BaseException) -> None:
+                file_errors.record(side, relative_path(path, repo_path), stage, error)
+                if side in metrics:
+                    metrics[side].add_failed(self.tokenization_service._detect_language(path))
+                run_progress.warn(
+                    f"File skipped at {stage.value}", submission=side, path=relative_path(path, repo_path)
+                )
+
+            return record
+
+        for file_path, fingerprint_set in self.fingerprint_service.fingerprint_files(
+            [file_path for file_path in repo1_compatible_files if file_path.is_file()],
+            read_source,
+            cache_stats,
+            profiler,
+            on_error=file_failed("submission1", repo1_path),
+            timeout_seconds=self.file_timeout_seconds,
+        ):
+            run_progress.file_tokenized()
+            tokens1.extend(fingerprint_set.tokens)
+            fingerprints1 |= fingerprint_set.hashes
+            name = relative_path(file_path, repo1_path)
+            files_tokens1[name] = files_tokens1.get(name, 0) + len(fingerprint_set.tokens)
+            if "submission1" in metrics:
+                self.measure_file(metrics["submission1"], file_path, fingerprint_set.tokens)
+
+        for file_path, fingerprint_set in self.fingerprint_service.fingerprint_files(
+            [file_path for file_path in repo2_compatible_files if file_path.is_file()],
+            read_source,
+            cache_stats,
+            profiler,
+            on_error=file_failed("submission2", repo2_path),
+            timeout_seconds=self.file_timeout_seconds,
+        ):
+            run_progress.file_tokenized()
+            tokens2.extend(fingerprint_set.tokens)
+            fingerprints2 |= fingerprint_set.hashes
+            name = relative_path(file_path, repo2_path)
+            files_tokens2[name] = files_tokens2.get(name, 0) + len(fingerprint_set.tokens)
+            if "submission2" in metrics:
+                self.measure_file(metrics["submission2"], file_path, fingerprint_set.tokens)
+
+        # Submissions without comparable tokens are never scored
+        comparability = assess_comparability(repo1_compatible_files, tokens1, repo2_compatible_files, tokens2)
+        if not comparability.comparable:
+            return not_comparable_results(
+                comparability,
+                time.time() - start_time,
+                {
+                    "tokens_count": {"submission1": len(tokens1), "submission2": len(tokens2)},
+                    "files_tokens": {"submission1": files_tokens1, "submission2": files_tokens2},
+                    "files_count": {
+                        "submission1": len(repo1_compatible_files),
+                        "submission2": len(repo2_compatible_files),
+                    },
+                    "partial": bool(file_errors),
+                    "file_errors": file_errors.to_dict(),
+                },
+            )
+
+        # Perform similarity analysis
+        with profiler.stage("pairwise_comparison"):
+            similarity_result = self.similarity_service.compare_similarity(tokens1, tokens2)
+
+        with profiler.stage("fragment_extraction"):
+            files_with_similarities_visualization = visualize_file_pairs(
+                self.visualization_service,
+                repo1_compatible_files,
+                repo2_compatible_files,
+                repo1_path,
+                repo2_path,
+                file_errors,
+            )
+
+        return {
+            "jaccard_similarity": similarity_result["jaccard_similarity"],
+            "type_similarity": similarity_result["type_similarity"],
+            "overall_similarity": similarity_result["overall_similarity"],
+            "structural_similarity": similarity_result["structural_similarity"],
+            "type_sequence_similarity": similarity_result["type_sequence_similarity"],
+            "flow_similarity": similarity_result["flow_similarity"],
+            "operation_similarity": similarity_result["operation_similarity"],
+            "processing_time_seconds": time.time() - start_time,
+            "similarity_details": {
+                "algorithm": "ast_similarity",
+                "common_elements": similarity_result["common_elements"],
+                "total_unique_elements": similarity_result["total_unique_elements"],
+                "length_ratio": similarity_result["length_ratio"],
+                "length_penalty": similarity_result["length_penalty"],
+                "tokens_count": {"submission1": len(tokens1), "submission2": len(tokens2)},
+                "files_tokens": {"submission1": files_tokens1, "submission2": files_tokens2},
+                **comparability.to_details(),
+                "fingerprint_similarity": fingerprint_similarity(fingerprints1, fingerprints2),
+                "processed_tokens_count": {
+                    "submission1": similarity_result["tokens1_length"],
+                    "submission2": similarity_result["tokens2_length"],
+                },
+                "files_count": {
+                    "submission1": len(repo1_compatible_files),
+                    "submission2": len(repo2_compatible_files),
+                },
+                # Scores may be understated when files were skipped
+                "partial": bool(file_errors),
+                "file_errors": file_errors.to_dict(),
+            },
+            "visualization_data": files_with_similarities_visualization,
+        }
@@ -0,0 +1,105 @@
+"""
+Per-file failures of detection runs
+
+A file that cannot be decoded, tokenized, fingerprinted or visualized is skipped and recorded, and the comparison
+goes on with the other files of its submission: its results are flagged partial, as scores may be understated.
+Systemic failures, of the database, the storage or the memory, are not specific to a file: they stop the run,
+which fails.
+
+A file taking longer than the file timeout is recorded the same way, flagged timed out.
+"""
+
+from dataclasses import dataclass
+from enum import Enum
+from typing import Any, Dict, List, Optional
+
+from app.domains.storage.exceptions import StorageException
+from app.shared.exceptions import DatabaseException
+from app.shared.timeouts import TimedOut
+
+# Errors no other file would escape, repositories raise database errors as DatabaseException
+SYSTEMIC_ERRORS = (DatabaseException, StorageException, MemoryError)
+
+
+class FileStage(str, Enum):
+    """Stage of the detection pipeline a file failed at"""
+
+    DECODING = "decoding"
+    TOKENIZATION = "tokenization"
+    FINGERPRINTING = "fingerprinting"
+    FRAGMENT_EXTRACTION = "fragment_extraction"
+
+
+class FileStageError(Exception):
+    """Failure of a file at a stage after its decoding, wrapping the original error"""
+
+    def __init__(self, stage: FileStage, cause: BaseException):
+        super().__init__(str(cause))
+        self.stage = stage
+        self.cause = cause
+
+
+@dataclass(frozen=True)
+class FileError:
+    """Failure of one file: its path from the submission root, the stage, the error message and if it timed out"""
+
+    path: str
+    stage: FileStage
+    message: str
+    submission_id: Optional[str] = None
+    timed_out: bool = False
+
+    def to_dict(self) -> Dict[str, Any]:
+        data = {"path": self.path, "stage": self.stage.value, "message": self.message}
+        if self.submission_id is not None:
+            data["submission_id"] = self.submission_id
+        if self.timed_out:
+            data["timed_out"] = True
+        return data
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "FileError":
+        return cls(
+            data["path"],
+            FileStage(data["stage"]),
+            data["message"],
+            data.get("submission_id"),
+            bool(data.get("timed_out")),
+        )
+
+
+def is_systemic(error: BaseException) -> bool:
+    """Whether an error comes from the database, the storage or the process rather than from the file processed"""
+    cause = error.cause if isinstance(error, FileStageError) else error
+    return isinstance(cause, SYSTEMIC_ERRORS)
+
+
+def describe_error(error: BaseException) -> str:
+    """Message of an error with its type, for errors without message like RecursionError"""
+    cause = error.cause if isinstance(error, FileStageError) else error
+    if isinstance(cause, TimedOut):
+        return str(cause)
+    return f"{type(cause).__name__}: {cause}" if str(cause) else type(cause).__name__
+
+
+class FileErrorLog:
+    """Failures of the files of both sides of a comparison, each file recorded once per stage"""
+
+    SIDES = ("submission1", "submission2")
+
+    def __init__(self):
+        self._errors: Dict[str, Dict[tuple, FileError]] = {side: {} for side in self.SIDES}
+
+    def record(self, side: str, path: str, stage: FileStage, error: BaseException) -> None:
+        timed_out = isinstance(error, TimedOut)
+        self._errors[side].setdefault((path, stage), FileError(path, stage, describe_error(error), timed_out=timed_out))
+
+    def has(self, side: str, path: str) -> bool:
+        """Whether a file of a side already failed at some stage"""
+        return any(error_path == path for error_path, _ in self._errors[side])
+
+    def __bool__(self) -> bool:
+        return any(self._errors.values())
+
+    def to_dict(self) -> Dict[str, List[Dict[str, Any]]]:
+        return {side: [error.to_dict() for error in errors.values()] for side, errors in self._errors.items()}
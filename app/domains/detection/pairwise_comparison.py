@@ -0,0 +1,159 @@
+"""
+Pairwise Comparison
+Runs the comparisons of a detection run on a worker pool, yielding results in a canonical order.
+"""
+
+import logging
+import threading
+from collections import deque
+from concurrent.futures import ThreadPoolExecutor
+from typing import Any, Callable, Dict, FrozenSet, Hashable, Iterable, Iterator, List, Mapping, Optional, Tuple
+
+from app.domains.fingerprints.fingerprinting import fingerprint_similarity
+from app.shared.tracing import in_current_context
+
+logger = logging.getLogger(__name__)
+
+Pair = Tuple[Hashable, Hashable]
+
+
+def canonical_pairs(pairs: Iterable[Pair]) -> List[Pair]:
+    """Sort pairs by their IDs, the order results are yielded and reported in"""
+    return sorted(pairs, key=lambda pair: (str(pair[0]), str(pair[1])))
+
+
+class PairwiseProgress:
+    """Progress of a pairwise comparison, updated by every worker"""
+
+    def __init__(self, total: int = 0):
+        self.total = total
+        self.completed = 0
+        self.failed = 0
+        self.cancelled = False
+        self._lock = threading.Lock()
+
+    def record(self, failed: bool = False) -> None:
+        with self._lock:
+            if failed:
+                self.failed += 1
+            else:
+                self.completed += 1
+
+    @property
+    def processed(self) -> int:
+        return self.completed + self.failed
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "total": self.total,
+            "completed": self.completed,
+            "failed": self.failed,
+            "cancelled": self.cancelled,
+        }
+
+
+class ParallelPairwiseComparator:
+    """
+    Compares pairs in chunks on a bounded thread pool
+
+    Chunks are small and pulled from a shared queue by whichever worker is idle, so a few expensive pairs never
+    leave the other workers waiting. Results are yielded in canonical pair order whatever the number of workers,
+    so reports built from them are identical at any parallelism.
+    """
+
+    def __init__(self, workers: int = 1, chunk_size: int = 16):
+        self.workers = max(workers, 1)
+        self.chunk_size = max(chunk_size, 1)
+
+    def compare(
+        self,
+        pairs: Iterable[Pair],
+        compare_pair: Callable[[Pair], Any],
+        progress: Optional[PairwiseProgress] = None,
+        should_cancel: Optional[Callable[[], bool]] = None,
+    ) -> Iterator[Tuple[Pair, Any]]:
+        """
+        Compare every pair, yielding (pair, result) in canonical order
+
+        Args:
+            pairs: Pairs to compare
+            compare_pair: Compares one pair, a None result or an exception counts as a failed pair
+            progress: Updated as each pair finishes, its total is set to the number of pairs
+            should_cancel: Checked before each pair, once it returns True no new pair is started and only the
+                pairs already compared are yielded
+        """
+        pairs = canonical_pairs(pairs)
+        progress = progress if progress is not None else PairwiseProgress()
+        progress.total = len(pairs)
+        chunks = [pairs[start : start + self.chunk_size] for start in range(0, len(pairs), self.chunk_size)]
+
+        def run_chunk(chunk: List[Pair]) -> List[Tuple[Pair, Any]]:
+            results = []
+            for pair in chunk:
+                if progress.cancelled or (should_cancel is not None and should_cancel()):
+                    progress.cancelled = True
+                    break
+                try:
+                    result = compare_pair(pair)
+                except Exception as e:
+                    logger.error(f"Comparison of {pair[0]} and {pair[1]} failed: {str(e)}")
+                    result = None
+                progress.record(failed=result is None)
+                results.append((pair, result))
+            return results
+
+        if self.workers == 1:
+            for chunk in chunks:
+                yield from run_chunk(chunk)
+                if progress.cancelled:
+                    return
+            return
+
+        with ThreadPoolExecutor(max_workers=self.workers, thread_name_prefix="comparison") as executor:
+            # Enough chunks queued to keep every worker busy without holding all results in memory
+            pending = deque()
+            try:
+                for chunk in chunks:
+                    if progress.cancelled:
+                        break
+                    if len(pending) >= 2 * self.workers:
+                        yield from pending.popleft().result()
+                    pending.append(executor.submit(in_current_context(run_chunk), chunk))
+                while pending:
+                    yield from pending.popleft().result()
+            finally:
+                progress.cancelled = progress.cancelled or bool(pending)
+                for future in pending:
+                    future.cancel()
+
+
+def fingerprint_similarities(
+    index: Mapping[Hashable, FrozenSet[int]],
+    comparator: Optional[ParallelPairwiseComparator] = None,
+    pairs: Optional[Iterable[Pair]] = None,
+    progress: Optional[PairwiseProgress] = None,
+    should_cancel: Optional[Callable[[], bool]] = None,
+) -> List[Tuple[Pair, float]]:
+    """
+    Fingerprint similarity of pairs of an index mapping submissions to their fingerprint hashes
+
+    Workers read the index in place, it must not change while the comparison runs.
+
+    Args:
+        index: Fingerprint hashes of each submission
+        comparator: Comparator to run on, sequential by default
+        pairs: Pairs to compare, every pair of the index by default
+    """
+    comparator = comparator or ParallelPairwiseComparator()
+    if pairs is None:
+        keys = list(index)
+        pairs = [(first, second) for position, first in enumerate(keys) for second in keys[position + 1 :]]
+
+    return list(
+        comparator.compare(
+            pairs,
+            lambda pair: fingerprint_similarity(index[pair[0]], index[pair[1]]),
+            progress=progress,
+            should_cancel=should_cancel,
+        )
+    )
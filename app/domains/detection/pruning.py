@@ -0,0 +1,78 @@
+"""
+Pair Pruning
+Skips the detailed comparison of pairs whose overall similarity, bounded from the similarity profiles of the
+comparison index, cannot reach the reporting threshold.
+"""
+
+import threading
+from dataclasses import dataclass
+from typing import Any, Dict, Hashable, Optional
+
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.fingerprints.comparison_index import ComparisonIndex
+
+
+@dataclass(frozen=True)
+class PrunedPair:
+    """Result of a pair that was not compared in detail"""
+
+    submission_id: Hashable
+    compared_submission_id: Hashable
+    estimated_similarity: float
+
+
+class PairPruner:
+    """
+    Decides which pairs of a run are compared in detail
+
+    The estimate of a pair is an upper bound of the overall similarity its detailed comparison would report,
+    computed from the similarity profiles of both submissions in the step index. The index profiles the files a
+    detailed comparison reads, decoded the same way, so a pair whose estimate is below the threshold cannot be
+    reported at it; the margin also keeps pairs reported at a slightly lower threshold. Pairs whose profile is
+    missing from the index are always compared.
+    """
+
+    def __init__(
+        self,
+        index: ComparisonIndex,
+        threshold: float,
+        margin: float,
+        similarity_service: Optional[SimilarityDetectionService] = None,
+    ):
+        self.index = index
+        self.threshold = threshold
+        self.margin = max(margin, 0.0)
+        self.similarity_service = similarity_service or SimilarityDetectionService()
+        self._lock = threading.Lock()
+        self.evaluated = 0
+        self.pruned = 0
+
+    @property
+    def cutoff(self) -> float:
+        return self.threshold - self.margin
+
+    def estimate(self, submission_id, compared_submission_id) -> Optional[float]:
+        """Upper bound of the overall similarity of a pair, None when either submission has no indexed profile"""
+        profile1 = self.index.profile_of(submission_id)
+        profile2 = self.index.profile_of(compared_submission_id)
+        if profile1 is None or profile2 is None:
+            return None
+        return self.similarity_service.overall_similarity_bound(profile1, profile2)
+
+    def prune(self, submission_id, compared_submission_id) -> Optional[PrunedPair]:
+        """The pruned result of a pair whose estimate is below the cutoff, None when it must be compared"""
+        estimate = self.estimate(submission_id, compared_submission_id)
+        pruned = estimate is not None and estimate < self.cutoff
+        with self._lock:
+            self.evaluated += 1
+            self.pruned += pruned
+        return PrunedPair(submission_id, compared_submission_id, estimate) if pruned else None
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "threshold": self.threshold,
+            "margin": self.margin,
+            "evaluated": self.evaluated,
+            "pruned": self.pruned,
+            "ratio": round(self.pruned / self.evaluated, 4) if self.evaluated else 0.0,
+        }
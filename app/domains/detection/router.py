@@ -1,4 +1,3 @@
-from datetime import datetime
 from pathlib import Path
 from typing import Any, Dict
 
@@ -8,6 +7,7 @@ from app.domains.detection.similarity_detection_service import SimilarityDetecti
 from app.domains.detection.visualization import VisualizationService
 from app.domains.tokenization.tokenization_service import TokenizationService
 from app.shared.services import get_tokenization_service as get_singleton_tokenization_service
+from app.shared.timestamps import to_rfc3339, utc_now
 
 router = APIRouter(prefix="/detection", tags=["detection"])
 
@@ -127,7 +127,7 @@ async def test_project_similarity(tokenization_service: TokenizationService = De
 
         # Build comprehensive response
         response = {
-            "analysis_timestamp": datetime.utcnow().isoformat(),
+            "analysis_timestamp": to_rfc3339(utc_now()),
             "projects": {
                 "calculator": {
                     "files": calc_file_details,
@@ -233,7 +233,7 @@ async def test_project_similarity_simple(tokenization_service: TokenizationServi
         )
 
         return {
-            "timestamp": datetime.utcnow().isoformat(),
+            "timestamp": to_rfc3339(utc_now()),
             "calculator_tokens": len(calc_all_tokens),
             "game_tokens": len(game_all_tokens),
             "jaccard_similarity": overall_similarity["jaccard_similarity"],
@@ -295,7 +295,7 @@ async def compare_specific_files(
         )
 
         return {
-            "timestamp": datetime.utcnow().isoformat(),
+            "timestamp": to_rfc3339(utc_now()),
             "files": {"calculator": file1, "game": file2},
             "tokens": {"calculator": len(calc_tokens), "game": len(game_tokens)},
             "similarity": {
@@ -357,7 +357,7 @@ async def get_react_flow_ast_for_files(file1: str, file2: str):
         )
 
         return {
-            "timestamp": datetime.utcnow().isoformat(),
+            "timestamp": to_rfc3339(utc_now()),
             "files_compared": {"file1": file1, "file2": file2},
             "layout_used": "elk_layered",
             "react_flow": react_flow_data,
@@ -420,7 +420,7 @@ async def get_react_flow_ast_for_projects():
                     )
 
         return {
-            "timestamp": datetime.utcnow().isoformat(),
+            "timestamp": to_rfc3339(utc_now()),
             "total_file_pairs_with_similarity": len(files_with_similarities),
             "layout_used": "elk_layered",
             "file_pairs": files_with_similarities,
@@ -519,7 +519,7 @@ async def get_combined_react_flow_ast_for_projects(layout: str = "elk"):
                         y_offset += 700
 
         return {
-            "timestamp": datetime.utcnow().isoformat(),
+            "timestamp": to_rfc3339(utc_now()),
             "total_file_pairs_with_similarity": len(files_with_similarities),
             "files_analyzed": files_with_similarities,
             "layout_used": layout,
@@ -599,7 +599,7 @@ async def get_animated_react_flow_ast_for_files(file1: str, file2: str, layout:
                     edge["style"]["animation"] = "dash 3s linear infinite"
 
         return {
-            "timestamp": datetime.utcnow().isoformat(),
+            "timestamp": to_rfc3339(utc_now()),
             "files_compared": {"file1": file1, "file2": file2},
             "layout_used": layout,
             "react_flow": react_flow_data,
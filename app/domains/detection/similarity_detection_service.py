@@ -3,19 +3,66 @@ Similarity Detection Service
 Handles code similarity analysis, comparison, and shared code block detection.
 """
 
+import itertools
 import logging
+import math
 import re
+from collections import Counter
 from difflib import SequenceMatcher
 from pathlib import Path
 from typing import Any, Dict, List
 
+from app.domains.tokenization.line_index import DEFAULT_EXCERPT_MAX_BYTES, EQUIVALENT_LINE_BYTES, clamp_excerpt
+from app.shared.timeouts import checkpoint
+
 logger = logging.getLogger(__name__)
 
+# Weights of the metrics in the overall similarity, skipped metrics share theirs among the others
+OVERALL_WEIGHTS = {
+    "jaccard": 0.25,
+    "structural": 0.30,
+    "type_sequence": 0.20,
+    "flow": 0.15,
+    "operation": 0.05,
+    "type": 0.05,
+}
+# Longer sequences are not compared, their similarity is 0
+MAX_SEQUENCE_LENGTH = 10000
+# A sequence metric at 0 with a side longer than this is skipped
+SKIPPED_SEQUENCE_LENGTH = 1000
+# Greatest weight of the fuzzy matches of signature parts in the enhanced Jaccard similarity
+FUZZY_MATCH_WEIGHT = 0.3
+
 
 class SimilarityDetectionService:
-    def __init__(self):
-        """Initialize the similarity detection service."""
-        pass
+    def __init__(self, excerpt_max_bytes: int = DEFAULT_EXCERPT_MAX_BYTES):
+        """
+        Initialize the similarity detection service.
+
+        Args:
+            excerpt_max_bytes: Bytes of the code excerpt of each side of a shared block, longer code is cut
+        """
+        self.excerpt_max_bytes = excerpt_max_bytes
+
+    @staticmethod
+    def _line_count(function: Dict[str, Any]) -> int:
+        """Lines of a function, counted from its bytes when it is located by byte offsets"""
+        lines = function["end_line"] - function["start_line"] + 1
+        if "start_byte" in function:
+            lines = max(lines, math.ceil((function["end_byte"] - function["start_byte"]) / EQUIVALENT_LINE_BYTES))
+        return lines
+
+    def _block_code(self, function1: Dict[str, Any], function2: Dict[str, Any]) -> Dict[str, Any]:
+        """Clamped code excerpts of a shared block, with byte offsets of the sides located by them"""
+        code = {
+            "file1_code_block": clamp_excerpt(function1["code_block"], self.excerpt_max_bytes),
+            "file2_code_block": clamp_excerpt(function2["code_block"], self.excerpt_max_bytes),
+        }
+        for side, function in (("file1", function1), ("file2", function2)):
+            if "start_byte" in function:
+                code[f"{side}_start_byte"] = function["start_byte"]
+                code[f"{side}_end_byte"] = function["end_byte"]
+        return code
 
     def prepare_for_similarity(self, tokens: List[Dict[str, Any]]) -> List[Dict[str, Any]]:
         """
@@ -182,18 +229,10 @@ class SimilarityDetectionService:
 
         This combines exact matching with fuzzy matching to provide more granular similarity scores.
         """
-        # Early exit for edge cases
-        if not sig1_parts and not sig2_parts:
-            return 1.0
-        if not sig1_parts or not sig2_parts:
-            return 0.0
-
-        # Filter empty parts once
+        # Filter empty parts once, nothing to compare on either side shares nothing
         sig1_clean = [part for part in sig1_parts if part.strip()]
         sig2_clean = [part for part in sig2_parts if part.strip()]
 
-        if not sig1_clean and not sig2_clean:
-            return 1.0
         if not sig1_clean or not sig2_clean:
             return 0.0
 
@@ -227,6 +266,7 @@ class SimilarityDetectionService:
         unmatched_list2 = list(unmatched_sig2)
 
         for part1 in unmatched_list1:
+            checkpoint()
             best_match = 0.0
             len1 = len(part1)
 
@@ -257,7 +297,7 @@ class SimilarityDetectionService:
         if fuzzy_matches > 0:
             # Calculate fuzzy contribution
             avg_fuzzy = fuzzy_matches / len(unmatched_list1)
-            fuzzy_weight = 0.3 * (len(unmatched_list1) / max(len(sig1_clean), len(sig2_clean)))
+            fuzzy_weight = FUZZY_MATCH_WEIGHT * (len(unmatched_list1) / max(len(sig1_clean), len(sig2_clean)))
             combined_score = exact_jaccard * (1 - fuzzy_weight) + avg_fuzzy * fuzzy_weight
             return min(1.0, combined_score)
 
@@ -272,12 +312,17 @@ class SimilarityDetectionService:
         sim_tokens1 = self.prepare_for_similarity(tokens1)
         sim_tokens2 = self.prepare_for_similarity(tokens2)
 
+        # A side without tokens is not similar to anything, even to another empty side
+        if not sim_tokens1 or not sim_tokens2:
+            return self._unscored_comparison(len(sim_tokens1), len(sim_tokens2))
+
         # Generate signatures
         signature1 = self.get_similarity_signature(tokens1)
         signature2 = self.get_similarity_signature(tokens2)
 
-        sig1_parts = signature1.split(" | ")
-        sig2_parts = signature2.split(" | ")
+        # An empty signature splits to one empty part, which is no element
+        sig1_parts = [part for part in signature1.split(" | ") if part]
+        sig2_parts = [part for part in signature2.split(" | ") if part]
 
         # Calculate enhanced Jaccard similarity with fuzzy matching
         jaccard_similarity = self._calculate_enhanced_jaccard_similarity(sig1_parts, sig2_parts)
@@ -316,45 +361,28 @@ class SimilarityDetectionService:
         # 5. LENGTH PENALTY for very different file sizes
         len1, len2 = len(sim_tokens1), len(sim_tokens2)
         length_ratio = min(len1, len2) / max(len1, len2) if max(len1, len2) > 0 else 0.0
-        length_penalty = 1.0 if length_ratio > 0.5 else (0.9 if length_ratio > 0.3 else 0.8)
+        length_penalty = self._length_penalty(len1, len2)
 
         # 6. CALCULATE OVERALL SIMILARITY (weighted combination)
-        # Dynamically adjust weights based on available metrics (skip heavy calculations for large sequences)
-        base_weights = {
-            "jaccard": 0.25,
-            "structural": 0.30,
-            "type_sequence": 0.20,
-            "flow": 0.15,
-            "operation": 0.05,
-            "type": 0.05,
-        }
-
         # Check which heavy metrics were skipped (return 0.0)
         skipped_metrics = []
-        if structural_similarity == 0.0 and (len(seq1) > 1000 or len(seq2) > 1000):
+        if structural_similarity == 0.0 and max(len(seq1), len(seq2)) > SKIPPED_SEQUENCE_LENGTH:
             skipped_metrics.append("structural")
-        if type_sequence_similarity == 0.0 and (len(types1) > 1000 or len(types2) > 1000):
+        if type_sequence_similarity == 0.0 and max(len(types1), len(types2)) > SKIPPED_SEQUENCE_LENGTH:
             skipped_metrics.append("type_sequence")
         if flow_similarity == 0.0:
             flow1 = self._extract_logical_flow(sim_tokens1)
             flow2 = self._extract_logical_flow(sim_tokens2)
-            if len(flow1) > 1000 or len(flow2) > 1000:
+            if max(len(flow1), len(flow2)) > SKIPPED_SEQUENCE_LENGTH:
                 skipped_metrics.append("flow")
         if operation_similarity == 0.0:
             ops1 = self._extract_operations(sim_tokens1)
             ops2 = self._extract_operations(sim_tokens2)
-            if len(ops1) > 1000 or len(ops2) > 1000:
+            if max(len(ops1), len(ops2)) > SKIPPED_SEQUENCE_LENGTH:
                 skipped_metrics.append("operation")
 
-        # Redistribute weights of skipped metrics to remaining metrics
-        total_skipped_weight = sum(base_weights[metric] for metric in skipped_metrics)
-        remaining_metrics = [k for k in base_weights.keys() if k not in skipped_metrics]
-
-        if total_skipped_weight > 0 and remaining_metrics:
-            # Distribute skipped weight proportionally among remaining metrics
-            weight_bonus = total_skipped_weight / len(remaining_metrics)
-            for metric in remaining_metrics:
-                base_weights[metric] += weight_bonus
+        # Dynamically adjust weights based on available metrics (skip heavy calculations for large sequences)
+        base_weights = self._overall_weights(skipped_metrics)
 
         overall_similarity = (
             jaccard_similarity * base_weights["jaccard"]
@@ -388,6 +416,130 @@ class SimilarityDetectionService:
             },
         }
 
+    @staticmethod
+    def _length_penalty(len1: int, len2: int) -> float:
+        length_ratio = min(len1, len2) / max(len1, len2) if max(len1, len2) > 0 else 0.0
+        return 1.0 if length_ratio > 0.5 else (0.9 if length_ratio > 0.3 else 0.8)
+
+    @staticmethod
+    def _overall_weights(skipped_metrics: List[str]) -> Dict[str, float]:
+        """Weights of the overall similarity, those of the skipped metrics redistributed to the remaining ones"""
+        base_weights = dict(OVERALL_WEIGHTS)
+        total_skipped_weight = sum(base_weights[metric] for metric in skipped_metrics)
+        remaining_metrics = [k for k in base_weights.keys() if k not in skipped_metrics]
+
+        if total_skipped_weight > 0 and remaining_metrics:
+            # Distribute skipped weight proportionally among remaining metrics
+            weight_bonus = total_skipped_weight / len(remaining_metrics)
+            for metric in remaining_metrics:
+                base_weights[metric] += weight_bonus
+        return base_weights
+
+    def similarity_profile(self, tokens: List[Dict[str, Any]]) -> Dict[str, Any]:
+        """
+        Counts of the elements compare_similarity reads from the tokens of a submission, whatever their order, from
+        which overall_similarity_bound bounds its overall similarity with any other submission
+        """
+        sim_tokens = self.prepare_for_similarity(tokens)
+        signature = self.get_similarity_signature(tokens)
+        return {
+            "tokens": len(sim_tokens),
+            "parts": len({part for part in signature.split(" | ") if part.strip()}),
+            "types": dict(Counter(token["type"] for token in sim_tokens)),
+            "structure": dict(Counter(self._create_structural_sequence(sim_tokens))),
+            "flow": dict(Counter(self._extract_logical_flow(sim_tokens))),
+            "operations": dict(Counter(self._extract_operations(sim_tokens))),
+        }
+
+    def overall_similarity_bound(self, profile1: Dict[str, Any], profile2: Dict[str, Any]) -> float:
+        """
+        Upper bound of the overall similarity compare_similarity gives two submissions, from their profiles
+
+        A sequence shares at most the elements both sides hold whatever their order, the exact Jaccard similarity of
+        the signatures is at most the ratio of their distinct part counts and fuzzy matches add at most their weight
+        of the rest. The type similarity and the length penalty are exact. A long sequence bounded above 0 may still
+        score 0 and be skipped, the bound is the highest over both outcomes.
+        """
+        len1, len2 = profile1["tokens"], profile2["tokens"]
+        if not len1 or not len2:
+            return 0.0
+
+        parts1, parts2 = profile1["parts"], profile2["parts"]
+        jaccard_similarity = 0.0
+        if parts1 and parts2:
+            exact_jaccard = min(parts1, parts2) / max(parts1, parts2)
+            jaccard_similarity = min(1.0, exact_jaccard + FUZZY_MATCH_WEIGHT * (1 - exact_jaccard))
+        types1, types2 = set(profile1["types"]), set(profile2["types"])
+        bounds = {"jaccard": jaccard_similarity, "type": len(types1 & types2) / len(types1 | types2)}
+
+        skipped, undecided = [], []
+        for metric, counts in (
+            ("structural", "structure"),
+            ("type_sequence", "types"),
+            ("flow", "flow"),
+            ("operation", "operations"),
+        ):
+            counts1, counts2 = profile1[counts], profile2[counts]
+            bounds[metric] = self._sequence_similarity_bound(counts1, counts2)
+            if max(sum(counts1.values()), sum(counts2.values())) > SKIPPED_SEQUENCE_LENGTH:
+                (undecided if bounds[metric] else skipped).append(metric)
+
+        overall_similarity = 0.0
+        for outcomes in itertools.product((False, True), repeat=len(undecided)):
+            skipped_metrics = skipped + [metric for metric, skip in zip(undecided, outcomes) if skip]
+            weights = self._overall_weights(skipped_metrics)
+            scores = {metric: 0.0 if metric in skipped_metrics else bound for metric, bound in bounds.items()}
+            # Summed in the order of compare_similarity, so that rounding cannot lift the score over its bound
+            overall_similarity = max(
+                overall_similarity,
+                (
+                    scores["jaccard"] * weights["jaccard"]
+                    + scores["structural"] * weights["structural"]
+                    + scores["type_sequence"] * weights["type_sequence"]
+                    + scores["flow"] * weights["flow"]
+                    + scores["operation"] * weights["operation"]
+                    + scores["type"] * weights["type"]
+                )
+                * self._length_penalty(len1, len2),
+            )
+        return round(overall_similarity, 4)
+
+    @staticmethod
+    def _sequence_similarity_bound(counts1: Dict[str, int], counts2: Dict[str, int]) -> float:
+        """Upper bound of _sequence_similarity_optimized from the element counts of both sequences"""
+        len1, len2 = sum(counts1.values()), sum(counts2.values())
+        if len1 > MAX_SEQUENCE_LENGTH or len2 > MAX_SEQUENCE_LENGTH:
+            return 0.0
+        if not len1 and not len2:
+            return 1.0
+        if not len1 or not len2:
+            return 0.0
+        # The longest common subsequence is made of elements both sides hold
+        shared = sum(min(count, counts2.get(element, 0)) for element, count in counts1.items())
+        return shared / max(len1, len2)
+
+    def _unscored_comparison(self, tokens1_length: int, tokens2_length: int) -> Dict[str, Any]:
+        """Result of compare_similarity when a side has no token, every score at 0"""
+        return {
+            "jaccard_similarity": 0.0,
+            "type_similarity": 0.0,
+            "overall_similarity": 0.0,
+            "structural_similarity": 0.0,
+            "type_sequence_similarity": 0.0,
+            "flow_similarity": 0.0,
+            "operation_similarity": 0.0,
+            "length_penalty": 1.0,
+            "common_elements": 0,
+            "total_unique_elements": 0,
+            "signature1_length": 0,
+            "signature2_length": 0,
+            "tokens1_length": tokens1_length,
+            "tokens2_length": tokens2_length,
+            "length_ratio": 0.0,
+            "common_types": [],
+            "signatures": {"file1": "", "file2": ""},
+        }
+
     def detect_shared_code_blocks(
         self,
         source1: str,
@@ -455,10 +607,11 @@ class SimilarityDetectionService:
 
         # Compare all function pairs using pre-tokenized data
         for func1_id, func1_data in functions1.items():
+            checkpoint()
             for func2_id, func2_data in functions2.items():
                 # Skip comparison for functions with less than 5 lines (too trivial for meaningful comparison)
-                func1_line_count = func1_data["end_line"] - func1_data["start_line"] + 1
-                func2_line_count = func2_data["end_line"] - func2_data["start_line"] + 1
+                func1_line_count = self._line_count(func1_data)
+                func2_line_count = self._line_count(func2_data)
 
                 if func1_line_count < 5 or func2_line_count < 5:
                     logger.debug(
@@ -486,12 +639,12 @@ class SimilarityDetectionService:
                         "file2_filename": file2_name,
                         "similarity_score": func_similarity["similarity_score"],
                         "common_patterns": func_similarity["common_patterns"],
-                        "file1_code_block": func1_data["code_block"],
-                        "file2_code_block": func2_data["code_block"],
+                        **self._block_code(func1_data, func2_data),
                         "file1_start_line": func1_data["start_line"],
                         "file1_end_line": func1_data["end_line"],
                         "file2_start_line": func2_data["start_line"],
                         "file2_end_line": func2_data["end_line"],
+                        "matched_tokens": min(len(func1_tokens), len(func2_tokens)),
                         "file1_language": func1_data.get("language", "unknown"),
                         "file2_language": func2_data.get("language", "unknown"),
                         "file1_node_type": func1_data.get("node_type", "unknown"),
@@ -503,7 +656,7 @@ class SimilarityDetectionService:
         return {
             "shared_blocks": shared_blocks,
             "total_shared_blocks": len(shared_blocks),
-            "average_similarity": sum(similarity_scores) / len(similarity_scores) if similarity_scores else 0.0,
+            "average_similarity": math.fsum(similarity_scores) / len(similarity_scores) if similarity_scores else 0.0,
             "functions_file1": len(functions1),
             "functions_file2": len(functions2),
             "shared_percentage": (
@@ -608,10 +761,11 @@ class SimilarityDetectionService:
 
         # Compare all function pairs using pre-tokenized data
         for func1_id, func1_data in functions1.items():
+            checkpoint()
             for func2_id, func2_data in functions2.items():
                 # Skip comparison for functions with less than 5 lines (too trivial for meaningful comparison)
-                func1_line_count = func1_data["end_line"] - func1_data["start_line"] + 1
-                func2_line_count = func2_data["end_line"] - func2_data["start_line"] + 1
+                func1_line_count = self._line_count(func1_data)
+                func2_line_count = self._line_count(func2_data)
 
                 if func1_line_count < 5 or func2_line_count < 5:
                     logger.debug(
@@ -639,9 +793,9 @@ class SimilarityDetectionService:
                         "file1_end_line": func1_data["end_line"],
                         "file2_start_line": func2_data["start_line"],
                         "file2_end_line": func2_data["end_line"],
-                        "file1_code_block": func1_data["code_block"],
-                        "file2_code_block": func2_data["code_block"],
+                        **self._block_code(func1_data, func2_data),
                         "similarity_score": func_similarity["similarity_score"],
+                        "matched_tokens": min(len(func1_tokens), len(func2_tokens)),
                         "structural_similarity": func_similarity["structural_similarity"],
                         "common_elements": func_similarity["common_patterns"],
                     }
@@ -650,7 +804,7 @@ class SimilarityDetectionService:
 
         # Calculate statistics
         total_shared_blocks = len(shared_blocks)
-        average_similarity = sum(similarity_scores) / len(similarity_scores) if similarity_scores else 0.0
+        average_similarity = math.fsum(similarity_scores) / len(similarity_scores) if similarity_scores else 0.0
 
         result = {
             "shared_blocks": shared_blocks,
@@ -933,7 +1087,7 @@ class SimilarityDetectionService:
     def _sequence_similarity_optimized(self, seq1: List[str], seq2: List[str]) -> float:
         """Calculate similarity between two sequences, skipping heavy calculations for large sequences."""
         # For small sequences, use the regular method
-        if len(seq1) <= 10000 and len(seq2) <= 10000:
+        if len(seq1) <= MAX_SEQUENCE_LENGTH and len(seq2) <= MAX_SEQUENCE_LENGTH:
             return self._sequence_similarity(seq1, seq2)
 
         # For large sequences, skip calculation to avoid performance issues
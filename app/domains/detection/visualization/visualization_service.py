@@ -4,10 +4,13 @@ Handles conversion of code tokens into interactive graph structures.
 """
 
 import logging
+import math
 import re
 from pathlib import Path
 from typing import Any, Dict, List, Optional
 
+from app.domains.tokenization.line_index import BYTE_OFFSET_KEYS
+
 logger = logging.getLogger(__name__)
 
 
@@ -95,7 +98,8 @@ class VisualizationService:
             total_similarities = len(
                 [edge for edge in similarity_edges if edge.get("data", {}).get("type") == "similarity"]
             )
-            average_similarity = sum(
+            # fsum is exact, the average does not depend on the order of the edges
+            average_similarity = math.fsum(
                 edge.get("data", {}).get("similarity_score", 0)
                 for edge in similarity_edges
                 if edge.get("data", {}).get("type") == "similarity"
@@ -117,6 +121,7 @@ class VisualizationService:
                     "file1": {"name": file1_name, "functions": len(file1_functions.get("functions", []))},
                     "file2": {"name": file2_name, "functions": len(file2_functions.get("functions", []))},
                 },
+                "shared_blocks": self._summarize_shared_blocks(shared_blocks),
             }
 
         except Exception as e:
@@ -210,7 +215,8 @@ class VisualizationService:
             total_similarities = len(
                 [edge for edge in similarity_edges if edge.get("data", {}).get("type") == "similarity"]
             )
-            average_similarity = sum(
+            # fsum is exact, the average does not depend on the order of the edges
+            average_similarity = math.fsum(
                 edge.get("data", {}).get("similarity_score", 0)
                 for edge in similarity_edges
                 if edge.get("data", {}).get("type") == "similarity"
@@ -232,6 +238,7 @@ class VisualizationService:
                     "file1": {"name": file1_name, "functions": len(file1_functions.get("functions", []))},
                     "file2": {"name": file2_name, "functions": len(file2_functions.get("functions", []))},
                 },
+                "shared_blocks": self._summarize_shared_blocks(shared_blocks),
             }
 
         except Exception as e:
@@ -239,6 +246,23 @@ class VisualizationService:
             logger.warning(f"Cache-aware visualization failed, falling back to standard method: {e}")
             return self.generate_react_flow_ast(source1, source2, file1_name, file2_name, layout_engine)
 
+    def _summarize_shared_blocks(self, shared_blocks: List[Dict]) -> List[Dict[str, Any]]:
+        """Keep the location and score of each shared block, without the code"""
+        return [
+            {
+                **{key: block[key] for key in BYTE_OFFSET_KEYS if key in block},
+                "file1_function": block.get("file1_function"),
+                "file2_function": block.get("file2_function"),
+                "file1_start_line": block.get("file1_start_line"),
+                "file1_end_line": block.get("file1_end_line"),
+                "file2_start_line": block.get("file2_start_line"),
+                "file2_end_line": block.get("file2_end_line"),
+                "similarity_score": block.get("similarity_score", 0.0),
+                "matched_tokens": block.get("matched_tokens"),
+            }
+            for block in shared_blocks
+        ]
+
     def _extract_functions_with_imports(self, source_code: str, filename: str) -> Dict[str, Any]:
         """Extract functions and imports from source code."""
         if not source_code:
@@ -0,0 +1 @@
+# Events domain package
@@ -0,0 +1,13 @@
+from .event_dto import (
+    DetectionEventDataDto,
+    EventDto,
+    SubmissionCreatedDataDto,
+    SubmissionDeletedDataDto,
+)
+
+__all__ = [
+    "EventDto",
+    "SubmissionCreatedDataDto",
+    "SubmissionDeletedDataDto",
+    "DetectionEventDataDto",
+]
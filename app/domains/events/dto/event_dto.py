@@ -0,0 +1,80 @@
+from typing import Any, Dict, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class EventDto(BaseModel):
+    """DTO for a published lifecycle event, the envelope around the data of its type"""
+
+    model_config = ConfigDict(
+        extra="forbid",
+        json_schema_extra={
+            "example": {
+                "id": "1b4e28ba-2fa1-11d2-883f-0016d3cca427",
+                "type": "detection.completed",
+                "version": 1,
+                "occurred_at": "2024-01-15T10:31:12Z",
+                "data": {"run_id": "550e8400-e29b-41d4-a716-446655440003", "status": "completed"},
+            }
+        },
+    )
+
+    id: UUID = Field(description="ID of the event, the same on every delivery")
+    type: str = Field(description="submission.created, submission.deleted, detection.completed or detection.failed")
+    version: int = Field(description="Version of the data schema of the type, incremented on breaking changes")
+    occurred_at: UtcTimestamp = Field(description="When the change happened")
+    data: Dict[str, Any] = Field(description="Resource IDs and summary fields, never source code")
+
+
+class SubmissionCreatedDataDto(BaseModel):
+    """DTO for the data of submission.created"""
+
+    model_config = ConfigDict(extra="forbid")
+
+    submission_id: UUID
+    project_uuid: UUID
+    group_uuid: UUID
+    project_step_uuid: UUID
+    submitted_by_uuid: Optional[UUID] = None
+    link_type: Optional[str] = None
+    upload_date_time: UtcTimestamp
+    is_late: bool
+    minutes_late: Optional[int] = None
+    file_count: Optional[int] = None
+    file_size_bytes: Optional[int] = None
+
+
+class SubmissionDeletedDataDto(BaseModel):
+    """DTO for the data of submission.deleted"""
+
+    model_config = ConfigDict(extra="forbid")
+
+    submission_id: UUID
+    project_uuid: UUID
+    group_uuid: UUID
+    project_step_uuid: UUID
+
+
+class DetectionEventDataDto(BaseModel):
+    """DTO for the data of detection.completed and detection.failed"""
+
+    model_config = ConfigDict(extra="forbid")
+
+    run_id: UUID
+    project_uuid: UUID
+    project_step_uuid: UUID
+    trigger: str
+    trigger_submission_id: Optional[UUID] = None
+    status: str = Field(
+        description="completed, incomplete or partial for detection.completed, failed for detection.failed"
+    )
+    total_pairs: int
+    completed_pairs: int
+    failed_pairs: int
+    file_error_count: int = Field(description="Files skipped because they failed, see the run for their paths")
+    started_at: UtcTimestamp
+    finished_at: Optional[UtcTimestamp] = None
+    error_message: Optional[str] = None
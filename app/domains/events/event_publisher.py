@@ -0,0 +1,181 @@
+"""
+Brokers lifecycle events are published to
+
+A publisher only returns once the broker accepted an event, and raises EventPublishException otherwise: the outbox
+relay keeps the event and publishes it again later. Events are published at least once, consumers discard
+redeliveries by event ID.
+"""
+
+import json
+import logging
+from typing import Any, Dict, List, Optional
+
+try:
+    import pika
+except ImportError:
+    pika = None
+
+from app.config.config import Settings
+
+logger = logging.getLogger(__name__)
+
+SUPPORTED_BACKENDS = ("amqp", "kafka", "memory")
+
+
+class EventPublishException(Exception):
+    """Raised when the broker did not accept an event"""
+
+
+def event_key(event: Dict[str, Any]) -> Optional[str]:
+    """ID of the resource of an event, events of one resource keep their order on partitioned brokers"""
+    data = event.get("data") or {}
+    return data.get("run_id") or data.get("submission_id")
+
+
+class EventPublisher:
+    """Broker publishing lifecycle events"""
+
+    backend_name = "none"
+
+    def publish(self, event: Dict[str, Any]) -> None:
+        """Publish an event, once the broker accepted it"""
+        raise NotImplementedError
+
+    def close(self) -> None:
+        pass
+
+
+class InMemoryEventPublisher(EventPublisher):
+    """Publisher keeping the events in memory, for tests and local development"""
+
+    backend_name = "memory"
+
+    def __init__(self):
+        self.events: List[Dict[str, Any]] = []
+        self.available = True
+
+    def publish(self, event: Dict[str, Any]) -> None:
+        if not self.available:
+            raise EventPublishException("Broker unavailable")
+        self.events.append(event)
+
+
+class AmqpEventPublisher(EventPublisher):
+    """Publisher to a RabbitMQ topic exchange, routed by event type, with publisher confirms"""
+
+    backend_name = "amqp"
+
+    def __init__(self, url: str, exchange: str, connect=None):
+        self.url = url
+        self.exchange = exchange
+        self._connect = connect or self._connect_to_broker
+        self._connection = None
+        self._channel = None
+
+    def _connect_to_broker(self):
+        if pika is None:
+            raise EventPublishException("pika is required to publish events to RabbitMQ")
+        return pika.BlockingConnection(pika.URLParameters(self.url))
+
+    def _open_channel(self):
+        if self._channel is None or not self._channel.is_open:
+            self.close()
+            self._connection = self._connect()
+            self._channel = self._connection.channel()
+            self._channel.exchange_declare(self.exchange, exchange_type="topic", durable=True)
+            self._channel.confirm_delivery()
+        return self._channel
+
+    def publish(self, event: Dict[str, Any]) -> None:
+        try:
+            self._open_channel().basic_publish(
+                self.exchange,
+                event["type"],
+                json.dumps(event).encode(),
+                properties=pika.BasicProperties(
+                    content_type="application/json",
+                    message_id=str(event["id"]),
+                    type=event["type"],
+                    headers={"version": event["version"]},
+                    delivery_mode=2,
+                ),
+            )
+        except EventPublishException:
+            raise
+        except Exception as e:
+            # The connection may be broken, a new one is opened for the next event
+            self.close()
+            raise EventPublishException(f"Failed to publish {event['type']} to {self.exchange}: {str(e)}")
+
+    def close(self) -> None:
+        connection, self._connection, self._channel = self._connection, None, None
+        if connection is not None:
+            try:
+                connection.close()
+            except Exception:
+                pass
+
+
+class KafkaEventPublisher(EventPublisher):
+    """Publisher to a Kafka topic keyed by resource, waiting for every in-sync replica, needs kafka-python"""
+
+    backend_name = "kafka"
+
+    def __init__(self, bootstrap_servers: str, topic: str, timeout_seconds: float = 10.0, producer=None):
+        self.bootstrap_servers = bootstrap_servers
+        self.topic = topic
+        self.timeout_seconds = timeout_seconds
+        self._producer = producer
+
+    def _get_producer(self):
+        if self._producer is None:
+            try:
+                from kafka import KafkaProducer
+            except ImportError:
+                raise EventPublishException("kafka-python is required to publish events to Kafka")
+            self._producer = KafkaProducer(
+                bootstrap_servers=[server.strip() for server in self.bootstrap_servers.split(",")], acks="all"
+            )
+        return self._producer
+
+    def publish(self, event: Dict[str, Any]) -> None:
+        key = event_key(event)
+        try:
+            self._get_producer().send(
+                self.topic,
+                key=key.encode() if key else None,
+                value=json.dumps(event).encode(),
+                headers=[("type", event["type"].encode()), ("version", str(event["version"]).encode())],
+            ).get(timeout=self.timeout_seconds)
+        except EventPublishException:
+            raise
+        except Exception as e:
+            raise EventPublishException(f"Failed to publish {event['type']} to {self.topic}: {str(e)}")
+
+    def close(self) -> None:
+        if self._producer is not None:
+            self._producer.close()
+            self._producer = None
+
+
+def create_event_publisher(settings: Settings) -> EventPublisher:
+    """
+    Build the event publisher selected by the events_backend setting
+
+    Raises:
+        ValueError: If the backend is unknown
+    """
+    backend = (settings.events_backend or "amqp").lower()
+    logger.info(f"Creating event publisher with backend '{backend}'")
+
+    if backend == "amqp":
+        url = settings.events_amqp_url or settings.amqp_url
+        return AmqpEventPublisher(url.get_secret_value(), settings.events_amqp_exchange)
+    if backend == "kafka":
+        return KafkaEventPublisher(settings.events_kafka_bootstrap_servers, settings.events_kafka_topic)
+    if backend == "memory":
+        return InMemoryEventPublisher()
+
+    raise ValueError(
+        f"Unknown events backend '{settings.events_backend}'. Supported backends: {', '.join(SUPPORTED_BACKENDS)}"
+    )
@@ -0,0 +1,121 @@
+"""
+Lifecycle events of submissions and detection runs
+
+Events are versioned JSON documents carrying resource IDs and summary fields, never source code, links or free
+text written by students. They are written to the outbox in the transaction of the change they describe, so an
+event exists exactly when its change was committed, and published by the outbox relay once the broker accepts them.
+"""
+
+import logging
+from typing import Any, Callable, Dict, Tuple, Type
+from uuid import uuid4
+
+from pydantic import BaseModel
+from sqlmodel import Session
+
+from app.domains.events.dto.event_dto import (
+    DetectionEventDataDto,
+    EventDto,
+    SubmissionCreatedDataDto,
+    SubmissionDeletedDataDto,
+)
+from app.domains.events.events_models import OutboxEvent
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+SUBMISSION_CREATED = "submission.created"
+SUBMISSION_DELETED = "submission.deleted"
+DETECTION_COMPLETED = "detection.completed"
+DETECTION_FAILED = "detection.failed"
+
+# Data schema and version of each event type
+EVENT_SCHEMAS: Dict[str, Tuple[Type[BaseModel], int]] = {
+    SUBMISSION_CREATED: (SubmissionCreatedDataDto, 1),
+    SUBMISSION_DELETED: (SubmissionDeletedDataDto, 1),
+    DETECTION_COMPLETED: (DetectionEventDataDto, 1),
+    DETECTION_FAILED: (DetectionEventDataDto, 1),
+}
+
+
+def build_event(event_type: str, data: BaseModel) -> OutboxEvent:
+    """Outbox entry of an event of a type, with the data of the schema of the type"""
+    schema, version = EVENT_SCHEMAS[event_type]
+    if not isinstance(data, schema):
+        raise TypeError(f"{event_type} events carry {schema.__name__}, not {type(data).__name__}")
+    occurred_at = utc_now()
+    envelope = EventDto(
+        id=uuid4(),
+        type=event_type,
+        version=version,
+        occurred_at=occurred_at,
+        data=data.model_dump(mode="json"),
+    )
+    return OutboxEvent(
+        event_id=envelope.id,
+        event_type=event_type,
+        payload=envelope.model_dump(mode="json"),
+        occurred_at=occurred_at,
+    )
+
+
+def submission_created(submission) -> OutboxEvent:
+    return build_event(
+        SUBMISSION_CREATED,
+        SubmissionCreatedDataDto(
+            submission_id=submission.id,
+            project_uuid=submission.project_uuid,
+            group_uuid=submission.group_uuid,
+            project_step_uuid=submission.project_step_uuid,
+            submitted_by_uuid=submission.submitted_by_uuid,
+            link_type=getattr(submission.link_type, "value", submission.link_type),
+            upload_date_time=submission.upload_date_time,
+            is_late=submission.is_late,
+            minutes_late=submission.minutes_late,
+            file_count=submission.file_count,
+            file_size_bytes=submission.file_size_bytes,
+        ),
+    )
+
+
+def submission_deleted(submission) -> OutboxEvent:
+    return build_event(
+        SUBMISSION_DELETED,
+        SubmissionDeletedDataDto(
+            submission_id=submission.id,
+            project_uuid=submission.project_uuid,
+            group_uuid=submission.group_uuid,
+            project_step_uuid=submission.project_step_uuid,
+        ),
+    )
+
+
+def detection_finished(run) -> OutboxEvent:
+    """detection.failed for failed runs, detection.completed for completed, incomplete and partial ones"""
+    status = getattr(run.status, "value", run.status)
+    return build_event(
+        DETECTION_FAILED if status == "failed" else DETECTION_COMPLETED,
+        DetectionEventDataDto(
+            run_id=run.id,
+            project_uuid=run.project_uuid,
+            project_step_uuid=run.project_step_uuid,
+            trigger=getattr(run.trigger, "value", run.trigger),
+            trigger_submission_id=run.trigger_submission_id,
+            status=status,
+            total_pairs=run.total_pairs,
+            completed_pairs=run.completed_pairs,
+            failed_pairs=run.failed_pairs,
+            file_error_count=len(run.file_errors or []),
+            started_at=run.started_at,
+            finished_at=run.finished_at,
+            error_message=run.error_message,
+        ),
+    )
+
+
+def record_event(session: Session, build: Callable[[Any], OutboxEvent], resource: Any) -> None:
+    """Add the event of a resource to the outbox, in the transaction of the session, unless events are disabled"""
+    from app.config.config import get_settings
+
+    if get_settings().events_enabled:
+        session.add(build(resource))
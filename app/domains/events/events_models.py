@@ -0,0 +1,29 @@
+from datetime import datetime
+from typing import Optional
+from uuid import UUID
+
+from sqlmodel import JSON, Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class OutboxEvent(SQLModel, table=True):
+    """Database model for a lifecycle event, written with the change it describes and published afterwards"""
+
+    __tablename__ = "event_outbox"
+
+    # Increasing with insertion, the order events are published in
+    sequence: Optional[int] = Field(default=None, primary_key=True)
+    event_id: UUID = Field(unique=True, description="ID of the event, for consumers to discard redeliveries")
+    event_type: str = Field(max_length=100, index=True, description="Type of the event, e.g. submission.created")
+    payload: dict = Field(default_factory=dict, sa_column=Column(JSON), description="Published JSON event")
+    occurred_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the change happened",
+    )
+    published_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime(), index=True), description="When the broker accepted the event"
+    )
+    attempts: int = Field(default=0, description="Failed publication attempts")
+    last_error: Optional[str] = Field(default=None, description="Error of the last failed publication")
@@ -0,0 +1,123 @@
+import logging
+import threading
+from datetime import timedelta
+from typing import Optional
+
+from app.config.config import Settings
+from app.domains.events.event_publisher import EventPublisher, create_event_publisher
+from app.domains.events.outbox_repository import EventOutboxRepository
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class OutboxRelay:
+    """
+    Background thread publishing the events of the outbox, oldest first
+
+    A batch stops at the first event the broker refuses, which is kept with its error and published first by a
+    later drain, so consumers see the events of a resource in order even across broker outages.
+    """
+
+    def __init__(
+        self,
+        publisher: EventPublisher,
+        interval_seconds: float = 2.0,
+        batch_size: int = 100,
+        retention_seconds: Optional[float] = None,
+        session_factory=None,
+    ):
+        self.publisher = publisher
+        self.interval_seconds = interval_seconds
+        self.batch_size = max(batch_size, 1)
+        self.retention_seconds = retention_seconds
+        self._session_factory = session_factory
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def _new_session(self):
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from sqlmodel import Session
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    def drain_once(self) -> int:
+        """Publish the pending events until none is left or the broker refuses one, returns how many were published"""
+        published = 0
+        while True:
+            with self._new_session() as session:
+                repository = EventOutboxRepository(session)
+                events = repository.lock_pending(self.batch_size)
+                sent, failure = [], None
+                for event in events:
+                    try:
+                        self.publisher.publish(event.payload)
+                    except Exception as e:
+                        failure = str(e)
+                        repository.mark_failed(event, failure)
+                        break
+                    sent.append(event)
+                repository.mark_published(sent)
+                repository.commit()
+
+            published += len(sent)
+            if failure is not None:
+                logger.warning(f"Event publication stopped, {published} published before the failure: {failure}")
+                return published
+            if len(events) < self.batch_size:
+                return published
+
+    def purge_published(self) -> int:
+        """Delete the events published longer than the retention ago"""
+        if self.retention_seconds is None:
+            return 0
+        with self._new_session() as session:
+            cutoff = utc_now() - timedelta(seconds=self.retention_seconds)
+            return EventOutboxRepository(session).delete_published_before(cutoff)
+
+    def run_once(self) -> int:
+        """Drain and purge the outbox, errors are logged and never stop the relay"""
+        published = 0
+        try:
+            published = self.drain_once()
+            self.purge_published()
+        except Exception as e:
+            logger.error(f"Event outbox relay failed: {str(e)}")
+        return published
+
+    def _run(self) -> None:
+        while not self._stop_event.wait(self.interval_seconds):
+            self.run_once()
+
+    def start(self) -> None:
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="event-outbox-relay", daemon=True)
+        self._thread.start()
+        logger.info(f"Event outbox relayed to {self.publisher.backend_name} every {self.interval_seconds:g} seconds")
+
+    def stop(self, timeout: float = 5.0) -> None:
+        """Stop the relay, then publish the events written since its last drain"""
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+        self.run_once()
+        self.publisher.close()
+
+
+def create_outbox_relay(settings: Settings) -> Optional[OutboxRelay]:
+    """Build the outbox relay from the settings, None when events are disabled"""
+    if not settings.events_enabled:
+        return None
+    return OutboxRelay(
+        create_event_publisher(settings),
+        settings.events_relay_interval_seconds,
+        settings.events_relay_batch_size,
+        retention_seconds=settings.events_outbox_retention_hours * 3600,
+    )
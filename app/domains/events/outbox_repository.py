@@ -0,0 +1,74 @@
+from datetime import datetime
+from typing import List
+
+from sqlalchemy import delete, func
+from sqlmodel import Session, select
+
+from app.domains.events.events_models import OutboxEvent
+from app.shared.exceptions import DatabaseException
+from app.shared.timestamps import utc_now
+
+
+class EventOutboxRepository:
+    """Repository for the events waiting in the outbox"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def lock_pending(self, limit: int) -> List[OutboxEvent]:
+        """
+        Oldest unpublished events, locked until the transaction ends
+
+        Rows locked by the relay of another instance are skipped, so each event is published by one of them.
+        """
+        try:
+            statement = (
+                select(OutboxEvent)
+                .where(OutboxEvent.published_at.is_(None))
+                .order_by(OutboxEvent.sequence)
+                .limit(limit)
+                .with_for_update(skip_locked=True)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to read pending events: {str(e)}")
+
+    def count_pending(self) -> int:
+        try:
+            statement = select(func.count()).select_from(OutboxEvent).where(OutboxEvent.published_at.is_(None))
+            return self.session.exec(statement).one()
+        except Exception as e:
+            raise DatabaseException(f"Failed to count pending events: {str(e)}")
+
+    def mark_published(self, events: List[OutboxEvent]) -> None:
+        """Mark events as accepted by the broker, add them to the transaction without committing it"""
+        published_at = utc_now()
+        for event in events:
+            event.published_at = published_at
+            event.last_error = None
+            self.session.add(event)
+
+    def mark_failed(self, event: OutboxEvent, error: str) -> None:
+        """Record a failed publication of an event, add it to the transaction without committing it"""
+        event.attempts += 1
+        event.last_error = error[:1000]
+        self.session.add(event)
+
+    def commit(self) -> None:
+        try:
+            self.session.commit()
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to update the event outbox: {str(e)}")
+
+    def delete_published_before(self, cutoff: datetime) -> int:
+        """Delete the events published before a cutoff, returns how many"""
+        try:
+            statement = delete(OutboxEvent).where(OutboxEvent.published_at < cutoff)
+            deleted = self.session.execute(statement).rowcount
+            self.session.commit()
+            return deleted
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to delete published events: {str(e)}")
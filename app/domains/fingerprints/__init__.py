@@ -0,0 +1 @@
+# Fingerprints domain package
@@ -0,0 +1,224 @@
+"""
+Automatic tuning of the fingerprinting parameters of a detection run
+
+Short scripts share most of their k-grams at the k fitting large projects, and large projects drown in boilerplate
+matches at the k fitting short scripts. Before comparing, the corpus of a run is analyzed: the language holding
+most of its tokens and the median number of tokens of its files in that language give a row of the tuning table
+of the language, and the background similarity, the median fingerprint similarity of sampled pairs of submissions
+of different groups, which are independent work, must stay under a target. While it does not, the larger rows of
+the table are tried in turn, and the largest is kept when none reaches the target.
+"""
+
+from dataclasses import dataclass, field
+from itertools import combinations
+from statistics import median
+from typing import Any, Dict, List, Optional, Sequence, Tuple
+
+from app.domains.fingerprints.fingerprint_models import DEFAULT_KGRAM_HASH_SCHEME, KgramHashScheme, NormalizationLevel
+from app.domains.fingerprints.fingerprinting import compute_fingerprints, fingerprint_similarity
+from app.domains.tokenization.tokenizer_config import TokenizerConfig
+
+# Rows of (minimum median tokens per file, k, window), in increasing order, the last row the median reaches applies
+TuningRow = Tuple[int, int, int]
+
+DEFAULT_TUNING_ROWS: Tuple[TuningRow, ...] = ((0, 4, 3), (250, 5, 4), (1200, 7, 5), (5000, 9, 6))
+
+TUNING_TABLE: Dict[str, Tuple[TuningRow, ...]] = {
+    # Terse, short scripts are common, a small k still tells them apart
+    "python": ((0, 4, 3), (200, 5, 4), (1000, 6, 5), (5000, 8, 6)),
+    "javascript": ((0, 4, 3), (250, 5, 4), (1200, 7, 5), (5000, 9, 6)),
+    "typescript": ((0, 4, 3), (250, 5, 4), (1200, 7, 5), (5000, 9, 6)),
+    # Verbose languages whose declarations, accessors and imports are alike in every project
+    "java": ((0, 5, 4), (300, 7, 5), (1500, 9, 6), (6000, 12, 8)),
+    "csharp": ((0, 5, 4), (300, 7, 5), (1500, 9, 6), (6000, 12, 8)),
+    "c": ((0, 5, 4), (300, 6, 5), (1500, 8, 6), (6000, 10, 7)),
+    "cpp": ((0, 5, 4), (300, 6, 5), (1500, 8, 6), (6000, 10, 7)),
+}
+
+DEFAULT_TARGET = 0.1
+DEFAULT_MAX_PAIRS = 50
+
+
+@dataclass
+class TuningFile:
+    """Tokens of a file of the corpus, with the language and normalization they are fingerprinted with"""
+
+    language: str
+    tokens: List[Dict[str, Any]]
+    normalization: str = NormalizationLevel.IDENTIFIERS.value
+
+
+@dataclass
+class TuningSubmission:
+    """Submission of the corpus, submissions of the same group are never paired"""
+
+    submission_id: int
+    group: Any
+    files: List[TuningFile] = field(default_factory=list)
+
+
+@dataclass
+class AutoTuningResult:
+    """Parameters chosen for a corpus and why"""
+
+    k: int
+    window: int
+    language: Optional[str]
+    median_tokens: float
+    languages: Dict[str, float]
+    sampled_submissions: int
+    sampled_pairs: int
+    target: float
+    background_similarity: Optional[float]
+    target_reached: bool
+    candidates: List[Dict[str, Any]]
+    rationale: str
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Record of the tuning in the parameters of a run"""
+        return {
+            "k": self.k,
+            "window": self.window,
+            "language": self.language,
+            "median_tokens": self.median_tokens,
+            "languages": self.languages,
+            "sampled_submissions": self.sampled_submissions,
+            "sampled_pairs": self.sampled_pairs,
+            "target": self.target,
+            "background_similarity": self.background_similarity,
+            "target_reached": self.target_reached,
+            "candidates": self.candidates,
+            "rationale": self.rationale,
+        }
+
+
+def tuning_rows(language: Optional[str]) -> Tuple[TuningRow, ...]:
+    """Rows of the tuning table of a language"""
+    return TUNING_TABLE.get(language or "", DEFAULT_TUNING_ROWS)
+
+
+def independent_pairs(corpus: Sequence[TuningSubmission], max_pairs: int) -> List[Tuple[int, int]]:
+    """Indexes of up to max_pairs pairs of submissions of different groups, in a stable order"""
+    pairs = []
+    for first, second in combinations(range(len(corpus)), 2):
+        if corpus[first].group is not None and corpus[first].group == corpus[second].group:
+            continue
+        pairs.append((first, second))
+        if len(pairs) >= max_pairs:
+            break
+    return pairs
+
+
+def background_similarity(
+    corpus: Sequence[TuningSubmission],
+    pairs: Sequence[Tuple[int, int]],
+    k: int,
+    window: int,
+    scheme: KgramHashScheme = DEFAULT_KGRAM_HASH_SCHEME,
+    tokenizer_config: Optional[TokenizerConfig] = None,
+) -> Optional[float]:
+    """Median fingerprint similarity of pairs of submissions at k and window, None without pairs"""
+    needed = sorted({index for pair in pairs for index in pair})
+    hashes = {
+        index: {
+            fingerprint
+            for tuning_file in corpus[index].files
+            for fingerprint, _ in compute_fingerprints(
+                tuning_file.tokens,
+                k,
+                window,
+                NormalizationLevel(tuning_file.normalization),
+                scheme,
+                tokenizer_config,
+                tuning_file.language,
+            )
+        }
+        for index in needed
+    }
+    if not pairs:
+        return None
+    return round(median(fingerprint_similarity(hashes[first], hashes[second]) for first, second in pairs), 4)
+
+
+def tune_fingerprinting(
+    corpus: Sequence[TuningSubmission],
+    target: float = DEFAULT_TARGET,
+    max_pairs: int = DEFAULT_MAX_PAIRS,
+    scheme: KgramHashScheme = DEFAULT_KGRAM_HASH_SCHEME,
+    tokenizer_config: Optional[TokenizerConfig] = None,
+) -> AutoTuningResult:
+    """
+    Choose k and window for a corpus
+
+    Args:
+        corpus: Sampled submissions of the run with the tokens of their files
+        target: Background similarity the parameters must stay under
+        max_pairs: Most pairs of submissions of different groups compared for the background similarity
+        scheme: Hash scheme of the k-grams, that of the fingerprint service
+        tokenizer_config: Tokenizer configuration giving the weight of token classes by language
+    """
+    tokens_by_language: Dict[str, int] = {}
+    files_by_language: Dict[str, List[int]] = {}
+    for submission in corpus:
+        for tuning_file in submission.files:
+            if not tuning_file.tokens:
+                continue
+            tokens_by_language[tuning_file.language] = (
+                tokens_by_language.get(tuning_file.language, 0) + len(tuning_file.tokens)
+            )
+            files_by_language.setdefault(tuning_file.language, []).append(len(tuning_file.tokens))
+    total_tokens = sum(tokens_by_language.values())
+    languages = {
+        language: round(count / total_tokens, 4) for language, count in sorted(tokens_by_language.items())
+    }
+    # Most tokens first, then by name so that ties are stable
+    language = min(tokens_by_language, key=lambda name: (-tokens_by_language[name], name), default=None)
+    median_tokens = float(median(files_by_language[language])) if language else 0.0
+
+    rows = tuning_rows(language)
+    base = max(index for index, row in enumerate(rows) if row[0] <= median_tokens)
+    pairs = independent_pairs(corpus, max_pairs)
+
+    candidates = []
+    for _, k, window in rows[base:]:
+        similarity = background_similarity(corpus, pairs, k, window, scheme, tokenizer_config)
+        candidates.append({"k": k, "window": window, "background_similarity": similarity})
+        if similarity is None or similarity < target:
+            break
+    chosen = candidates[-1]
+    similarity = chosen["background_similarity"]
+    target_reached = similarity is None or similarity < target
+
+    rationale = (
+        f"{language or 'no language'} holds most tokens, {median_tokens:g} per file at the median, "
+        f"the tuning table gives k={rows[base][1]} and window={rows[base][2]}"
+    )
+    if similarity is None:
+        rationale += ", no pair of submissions of different groups to measure the background similarity"
+    elif len(candidates) == 1:
+        rationale += f", the background similarity of {len(pairs)} sampled pairs is {similarity:g}, under {target:g}"
+    elif target_reached:
+        rationale += (
+            f", the background similarity of {len(pairs)} sampled pairs was {candidates[0]['background_similarity']:g},"
+            f" k={chosen['k']} and window={chosen['window']} bring it to {similarity:g}, under {target:g}"
+        )
+    else:
+        rationale += (
+            f", no row of the table brings the background similarity of {len(pairs)} sampled pairs under {target:g},"
+            f" the largest, k={chosen['k']} and window={chosen['window']}, leaves it at {similarity:g}"
+        )
+
+    return AutoTuningResult(
+        k=chosen["k"],
+        window=chosen["window"],
+        language=language,
+        median_tokens=median_tokens,
+        languages=languages,
+        sampled_submissions=len(corpus),
+        sampled_pairs=len(pairs),
+        target=target,
+        background_similarity=similarity,
+        target_reached=target_reached,
+        candidates=candidates,
+        rationale=rationale,
+    )
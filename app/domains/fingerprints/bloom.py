@@ -0,0 +1,164 @@
+"""
+Bloom filters of the fingerprint sets of a project step
+
+Each submission gets a filter sized from its number of fingerprints n. The configured false positive rate is the
+probability that a submission sharing none of the fingerprints of a query of q = max(n, 256) fingerprints still
+passes, so each fingerprint is tested at rate / q. Filters have m bits, the power of two fitting that rate for n
+fingerprints, and k = log2(q / rate) bit positions drawn from the high bits of a 64-bit linear congruential sequence
+seeded with the fingerprint hash (double hashing correlates positions on power of two sizes and misses the rate).
+
+Filters of the same shape are stored bit-sliced: slice i holds bit i of every filter of that shape, one bit per
+submission slot, so probing a fingerprint against all the submissions of a shape is at most k integer ANDs
+whatever the number of submissions.
+
+The filters only ever answer "may contain": a submission sharing a fingerprint always passes, so candidates are a
+superset of the submissions sharing fingerprints and false positives only cost an exact check.
+"""
+
+import math
+from typing import Dict, Iterable, List, Set, Tuple
+
+DEFAULT_FALSE_POSITIVE_RATE = 0.01
+MIN_FILTER_BITS = 64
+# Queries are the fingerprints of a whole submission, hardly ever fewer than this
+MIN_QUERY_FINGERPRINTS = 256
+MASK_64 = 0xFFFFFFFFFFFFFFFF
+LCG_MULTIPLIER = 0x9E3779B97F4A7C15
+LCG_INCREMENT = 0x632BE59BD9B4E019
+
+
+def filter_shape(cardinality: int, false_positive_rate: float) -> Tuple[int, int]:
+    """(bits, hash functions) of the filter of a set, keeping the false positive rate of a query of its size"""
+    cardinality = max(cardinality, 1)
+    element_rate = min(max(false_positive_rate, 1e-9), 0.5) / max(cardinality, MIN_QUERY_FINGERPRINTS)
+    bits = math.ceil(-cardinality * math.log(element_rate) / math.log(2) ** 2)
+    return max(MIN_FILTER_BITS, 1 << (bits - 1).bit_length()), max(1, round(-math.log2(element_rate)))
+
+
+def bit_positions(fingerprint_hash: int, bits: int, hashes: int) -> List[int]:
+    """Bit positions of a fingerprint in a filter of bits bits, a power of two"""
+    shift = 64 - (bits.bit_length() - 1)
+    positions = []
+    state = fingerprint_hash
+    for _ in range(hashes):
+        state = (state * LCG_MULTIPLIER + LCG_INCREMENT) & MASK_64
+        positions.append(state >> shift)
+    return positions
+
+
+class FilterGroup:
+    """Bit-sliced filters of one size and number of hash functions"""
+
+    def __init__(self, bits: int, hashes: int):
+        self.bits = bits
+        self.hashes = hashes
+        self.slots: Dict[str, int] = {}
+        self.slices: List[int] = [0] * bits
+        self.everyone = 0
+
+    def add(self, document_id: str, fingerprint_hashes: Iterable[int]) -> None:
+        # Lowest free slot, slots of removed submissions are reused
+        slot = (~self.everyone & (self.everyone + 1)).bit_length() - 1
+        self.slots[document_id] = slot
+        self.everyone |= 1 << slot
+        column = 1 << slot
+        for fingerprint_hash in fingerprint_hashes:
+            for position in bit_positions(fingerprint_hash, self.bits, self.hashes):
+                self.slices[position] |= column
+
+    def remove(self, document_id: str) -> None:
+        keep = ~(1 << self.slots.pop(document_id))
+        self.everyone &= keep
+        self.slices = [bit_slice & keep for bit_slice in self.slices]
+
+    def candidates(self, fingerprint_hashes: Iterable[int]) -> int:
+        """Slots of the filters that may contain at least one of the fingerprints, as a bit mask"""
+        everyone = self.everyone
+        found = 0
+        for fingerprint_hash in fingerprint_hashes:
+            matching = everyone & ~found
+            for position in bit_positions(fingerprint_hash, self.bits, self.hashes):
+                matching &= self.slices[position]
+                if not matching:
+                    break
+            found |= matching
+            if found == everyone:
+                break
+        return found
+
+
+class BloomFilterIndex:
+    """Per-submission Bloom filters of a step, grouped by size for probing them all at once"""
+
+    def __init__(self, false_positive_rate: float = DEFAULT_FALSE_POSITIVE_RATE):
+        self.false_positive_rate = false_positive_rate
+        self.groups: Dict[Tuple[int, int], FilterGroup] = {}
+        self._group_of: Dict[str, Tuple[int, int]] = {}
+
+    def __contains__(self, document_id) -> bool:
+        return str(document_id) in self._group_of
+
+    def __len__(self) -> int:
+        return len(self._group_of)
+
+    def add(self, document_id, fingerprint_hashes: Iterable[int]) -> None:
+        """Build the filter of a submission, replacing its previous one"""
+        document_id = str(document_id)
+        fingerprint_hashes = list(fingerprint_hashes)
+        self.remove(document_id)
+        group_key = filter_shape(len(fingerprint_hashes), self.false_positive_rate)
+        group = self.groups.get(group_key)
+        if group is None:
+            group = self.groups[group_key] = FilterGroup(*group_key)
+        group.add(document_id, fingerprint_hashes)
+        self._group_of[document_id] = group_key
+
+    def remove(self, document_id) -> bool:
+        group_key = self._group_of.pop(str(document_id), None)
+        if group_key is None:
+            return False
+        group = self.groups[group_key]
+        group.remove(str(document_id))
+        if not group.slots:
+            del self.groups[group_key]
+        return True
+
+    def candidates(self, fingerprint_hashes: Iterable[int]) -> Set[str]:
+        """Submissions whose filter may contain one of the fingerprints, a superset of those sharing one"""
+        fingerprint_hashes = list(fingerprint_hashes)
+        found = set()
+        for group in self.groups.values():
+            mask = group.candidates(fingerprint_hashes)
+            found.update(document_id for document_id, slot in group.slots.items() if mask >> slot & 1)
+        return found
+
+    def serialize(self) -> Tuple[Dict[str, object], bytes]:
+        """Layout of the groups and their slices, one fixed width record per slice"""
+        layout, data = [], bytearray()
+        for (bits, hashes), group in sorted(self.groups.items()):
+            width = (max(group.slots.values()) + 8) // 8
+            layout.append({"bits": bits, "hashes": hashes, "width": width, "slots": group.slots})
+            for bit_slice in group.slices:
+                data += bit_slice.to_bytes(width, "little")
+        return {"false_positive_rate": self.false_positive_rate, "groups": layout}, bytes(data)
+
+    @classmethod
+    def deserialize(cls, layout: Dict[str, object], data) -> "BloomFilterIndex":
+        index = cls(float(layout["false_positive_rate"]))
+        offset = 0
+        for group_layout in layout["groups"]:
+            bits, hashes, width = int(group_layout["bits"]), int(group_layout["hashes"]), int(group_layout["width"])
+            group = FilterGroup(bits, hashes)
+            group.slots = {str(document_id): int(slot) for document_id, slot in group_layout["slots"].items()}
+            group.everyone = sum(1 << slot for slot in group.slots.values())
+            end = offset + bits * width
+            if end > len(data):
+                raise ValueError("Truncated Bloom filter slices")
+            group.slices = [
+                int.from_bytes(data[start : start + width], "little") for start in range(offset, end, width)
+            ]
+            offset = end
+            index.groups[(bits, hashes)] = group
+            index._group_of.update((document_id, (bits, hashes)) for document_id in group.slots)
+        return index
+
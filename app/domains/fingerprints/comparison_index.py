@@ -0,0 +1,254 @@
+import hashlib
+import json
+import struct
+from collections import defaultdict
+from typing import Any, Dict, FrozenSet, Iterable, Optional, Set
+
+from app.domains.fingerprints.bloom import DEFAULT_FALSE_POSITIVE_RATE, BloomFilterIndex
+
+INDEX_MAGIC = b"PAMPIDX"
+# 2: files selected and decoded like the detailed comparisons, 3: binary hashes and Bloom filters,
+# 4: similarity profiles
+INDEX_FORMAT_VERSION = 4
+
+
+class ComparisonIndexCorruptedException(Exception):
+    """Raised when a persisted index cannot be trusted: bad header, version, checksum or content"""
+
+
+def pack_hashes(hashes: Iterable[int]) -> bytes:
+    """Sorted fingerprint hashes as 64-bit big-endian records"""
+    ordered = sorted(hashes)
+    return struct.pack(f">{len(ordered)}Q", *ordered)
+
+
+class IndexedDocument:
+    """
+    Fingerprint hashes of one version of a submission, decoded from the persisted index when first read, with its
+    similarity profile (see SimilarityDetectionService.similarity_profile), None when it was indexed without
+    """
+
+    __slots__ = ("version", "count", "profile", "_hashes", "_packed")
+
+    def __init__(
+        self,
+        version: int,
+        hashes: Optional[Iterable[int]] = None,
+        packed=None,
+        profile: Optional[Dict[str, Any]] = None,
+    ):
+        self.version = version
+        self.profile = profile
+        self._hashes: Optional[FrozenSet[int]] = frozenset(hashes) if hashes is not None else None
+        self._packed = packed
+        self.count = len(self._hashes) if self._hashes is not None else len(packed) // 8
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, IndexedDocument):
+            return NotImplemented
+        return (self.version, self.hashes, self.profile) == (other.version, other.hashes, other.profile)
+
+    def __repr__(self) -> str:
+        return f"IndexedDocument(version={self.version}, count={self.count})"
+
+    @property
+    def hashes(self) -> FrozenSet[int]:
+        if self._hashes is None:
+            self._hashes = frozenset(struct.unpack(f">{self.count}Q", self._packed))
+        return self._hashes
+
+    def packed(self) -> bytes:
+        if self._packed is None:
+            self._packed = pack_hashes(self._hashes)
+        return bytes(self._packed)
+
+
+class ComparisonIndex:
+    """
+    Fingerprints of the submissions of a project step with a Bloom filter per submission
+
+    Finding the submissions sharing fingerprints with one probes the Bloom filters first and only reads the exact
+    fingerprints of the candidates, so neither the fingerprints of the other submissions nor the inverted index
+    (fingerprint hash -> submissions containing it, built on first use of postings) are needed.
+
+    The index is only valid for the fingerprinting parameters it was built with. postings_processed counts the
+    postings added or removed since the index was loaded, which is the whole work of an incremental update.
+    """
+
+    def __init__(self, parameters: Dict[str, Any], bloom_false_positive_rate: float = DEFAULT_FALSE_POSITIVE_RATE):
+        self.parameters = dict(parameters)
+        self.documents: Dict[str, IndexedDocument] = {}
+        self.filters = BloomFilterIndex(bloom_false_positive_rate)
+        self._postings: Optional[Dict[int, Set[str]]] = None
+        self.postings_processed = 0
+
+    def __contains__(self, document_id) -> bool:
+        return str(document_id) in self.documents
+
+    def __len__(self) -> int:
+        return len(self.documents)
+
+    @property
+    def postings(self) -> Dict[int, Set[str]]:
+        """Inverted index of the fingerprints, built from the documents the first time it is read"""
+        if self._postings is None:
+            postings = defaultdict(set)
+            for document_id, document in self.documents.items():
+                for fingerprint_hash in document.hashes:
+                    postings[fingerprint_hash].add(document_id)
+            self._postings = postings
+        return self._postings
+
+    def version_of(self, document_id) -> Optional[int]:
+        document = self.documents.get(str(document_id))
+        return document.version if document else None
+
+    def profile_of(self, document_id) -> Optional[Dict[str, Any]]:
+        document = self.documents.get(str(document_id))
+        return document.profile if document else None
+
+    def add(self, document_id, version: int, hashes: Iterable[int], profile: Optional[Dict[str, Any]] = None) -> None:
+        """Index a submission version, replacing the version indexed before"""
+        document_id = str(document_id)
+        self.remove(document_id)
+        document = IndexedDocument(version, hashes, profile=profile)
+        self.documents[document_id] = document
+        self.filters.add(document_id, document.hashes)
+        if self._postings is not None:
+            for fingerprint_hash in document.hashes:
+                self._postings[fingerprint_hash].add(document_id)
+        self.postings_processed += document.count
+
+    def remove(self, document_id) -> bool:
+        document = self.documents.pop(str(document_id), None)
+        if document is None:
+            return False
+        self.filters.remove(document_id)
+        if self._postings is not None:
+            for fingerprint_hash in document.hashes:
+                holders = self._postings.get(fingerprint_hash)
+                if holders is not None:
+                    holders.discard(str(document_id))
+                    if not holders:
+                        del self._postings[fingerprint_hash]
+        self.postings_processed += document.count
+        return True
+
+    def candidates(self, document_id) -> Set[str]:
+        """Other submissions whose Bloom filter may hold a fingerprint of a submission, a superset of those sharing"""
+        document = self.documents.get(str(document_id))
+        if document is None:
+            return set()
+        return self.filters.candidates(document.hashes) - {str(document_id)}
+
+    def shared_counts(self, document_id) -> Dict[str, int]:
+        """Number of fingerprints each other submission shares with a submission, submissions sharing none omitted"""
+        document = self.documents.get(str(document_id))
+        if document is None:
+            return {}
+        counts = {}
+        for candidate in self.candidates(document_id):
+            shared = len(document.hashes & self.documents[candidate].hashes)
+            if shared:
+                counts[candidate] = shared
+        return counts
+
+    def probe_shared_counts(self, document_id) -> Dict[str, int]:
+        """shared_counts from the inverted index, probing the postings of every fingerprint of the submission"""
+        document = self.documents.get(str(document_id))
+        counts: Dict[str, int] = defaultdict(int)
+        if document is None:
+            return {}
+        for fingerprint_hash in document.hashes:
+            for holder in self.postings.get(fingerprint_hash, ()):
+                counts[holder] += 1
+        counts.pop(str(document_id), None)
+        return dict(counts)
+
+    def similarities(self, document_id) -> Dict[str, float]:
+        """Fingerprint (Jaccard) similarity of a submission with every other submission sharing fingerprints"""
+        size = self.documents[str(document_id)].count if document_id in self else 0
+        return {
+            other: shared / (size + self.documents[other].count - shared)
+            for other, shared in self.shared_counts(document_id).items()
+        }
+
+    def serialize(self) -> bytes:
+        """
+        Header line with format version, parameters, documents, filter layout and checksum of the payload, followed
+        by the payload: the sorted hashes of every document, then the Bloom filter slices
+        """
+        document_ids = sorted(self.documents)
+        filter_layout, filter_data = self.filters.serialize()
+        payload = b"".join([self.documents[document_id].packed() for document_id in document_ids] + [filter_data])
+        header = {
+            "format": INDEX_FORMAT_VERSION,
+            "parameters": self.parameters,
+            "documents": [
+                [
+                    document_id,
+                    self.documents[document_id].version,
+                    self.documents[document_id].count,
+                    self.documents[document_id].profile,
+                ]
+                for document_id in document_ids
+            ],
+            "filters": filter_layout,
+            "length": len(payload),
+            "checksum": hashlib.sha256(payload).hexdigest(),
+        }
+        return INDEX_MAGIC + json.dumps(header, sort_keys=True).encode("utf-8") + b"\n" + payload
+
+    @classmethod
+    def deserialize(
+        cls, data: bytes, parameters: Dict[str, Any], bloom_false_positive_rate: Optional[float] = None
+    ) -> "ComparisonIndex":
+        """
+        Load a serialized index built with the given parameters, the hashes of each document are decoded when read
+
+        Filters of the loaded documents keep their size, those of documents added afterwards are sized with
+        bloom_false_positive_rate when given.
+
+        Raises:
+            ComparisonIndexCorruptedException: If the data is not an index of the current format built with these
+                parameters, or does not match its checksum
+        """
+        if not data.startswith(INDEX_MAGIC) or b"\n" not in data:
+            raise ComparisonIndexCorruptedException("Missing index header")
+        header_line, payload = data[len(INDEX_MAGIC) :].split(b"\n", 1)
+        try:
+            header = json.loads(header_line)
+        except ValueError:
+            raise ComparisonIndexCorruptedException("Unreadable index header")
+
+        if header.get("format") != INDEX_FORMAT_VERSION:
+            raise ComparisonIndexCorruptedException(f"Unsupported index format {header.get('format')}")
+        if header.get("parameters") != parameters:
+            raise ComparisonIndexCorruptedException("Index built with other fingerprinting parameters")
+        if header.get("length") != len(payload) or header.get("checksum") != hashlib.sha256(payload).hexdigest():
+            raise ComparisonIndexCorruptedException("Index checksum mismatch")
+
+        try:
+            view = memoryview(payload)
+            index = cls(parameters)
+            offset = 0
+            for document_id, version, count, profile in header["documents"]:
+                end = offset + 8 * int(count)
+                if end > len(view):
+                    raise ValueError("Truncated document hashes")
+                index.documents[str(document_id)] = IndexedDocument(
+                    int(version), packed=view[offset:end], profile=profile
+                )
+                offset = end
+            index.filters = BloomFilterIndex.deserialize(header["filters"], view[offset:])
+            # A document without filter would never be a candidate
+            unfiltered = [document_id for document_id in index.documents if document_id not in index.filters]
+            if unfiltered or len(index.filters) != len(index):
+                raise ValueError("Bloom filters do not match the documents")
+            if bloom_false_positive_rate is not None:
+                index.filters.false_positive_rate = bloom_false_positive_rate
+        except (ValueError, KeyError, TypeError) as e:
+            raise ComparisonIndexCorruptedException(f"Unreadable index payload: {str(e)}")
+
+        index.postings_processed = 0
+        return index
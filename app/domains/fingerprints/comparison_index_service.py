@@ -0,0 +1,163 @@
+import hashlib
+import json
+import logging
+from pathlib import Path
+from typing import Any, Dict, Iterable, Optional, Tuple
+
+from app.domains.fingerprints.bloom import DEFAULT_FALSE_POSITIVE_RATE
+from app.domains.fingerprints.comparison_index import ComparisonIndex, ComparisonIndexCorruptedException
+from app.domains.storage.exceptions import StoredObjectNotFoundException
+from app.domains.tokenization.streaming_source import decode_source
+from app.shared.tenancy import project_accessible
+
+logger = logging.getLogger(__name__)
+
+INDEXES_PREFIX = "indexes/"
+
+
+def index_key(project_uuid, project_step_uuid, parameters: Dict[str, Any]) -> str:
+    """Key of the index of a project step, one per set of fingerprinting parameters"""
+    digest = hashlib.sha256(json.dumps(parameters, sort_keys=True).encode("utf-8")).hexdigest()[:16]
+    return f"{index_prefix(project_uuid, project_step_uuid)}comparison-{digest}.idx"
+
+
+def index_prefix(project_uuid, project_step_uuid) -> str:
+    """Key prefix of the indexes of a project step, not found for work of another tenant"""
+    prefix = f"{INDEXES_PREFIX}{project_uuid}/{project_step_uuid}/"
+    if not project_accessible(project_uuid):
+        raise StoredObjectNotFoundException(prefix)
+    return prefix
+
+
+class ComparisonIndexService:
+    """
+    Keeps a persisted comparison index per project step in the submission store
+
+    Runs load the index and update it incrementally: submissions stored since the last run, or resubmitted with
+    a new version, are fingerprinted and indexed, submissions gone from the step are removed. A missing, corrupted
+    or outdated index is rebuilt from scratch.
+    """
+
+    def __init__(
+        self,
+        storage_service=None,
+        fingerprint_service=None,
+        bloom_false_positive_rate: float = DEFAULT_FALSE_POSITIVE_RATE,
+        similarity_service=None,
+    ):
+        if storage_service is None:
+            from app.domains.storage.submission_storage_service import SubmissionStorageService
+
+            storage_service = SubmissionStorageService()
+        self.storage_service = storage_service
+
+        if fingerprint_service is None:
+            from app.shared.services import get_fingerprint_service
+
+            fingerprint_service = get_fingerprint_service()
+        self.fingerprint_service = fingerprint_service
+        # Chance that a submission sharing no fingerprint with another still passes its Bloom filter
+        self.bloom_false_positive_rate = bloom_false_positive_rate
+
+        if similarity_service is None:
+            from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+
+            similarity_service = SimilarityDetectionService()
+        self.similarity_service = similarity_service
+
+    @property
+    def parameters(self) -> Dict[str, Any]:
+        return self.fingerprint_service.parameters
+
+    def load(self, project_uuid, project_step_uuid) -> Tuple[Optional[ComparisonIndex], Optional[str]]:
+        """Load the index of a project step, returns (None, reason) when it must be rebuilt"""
+        key = index_key(project_uuid, project_step_uuid, self.parameters)
+        try:
+            data = self.storage_service.store.get(key)
+        except StoredObjectNotFoundException:
+            return None, "missing"
+
+        try:
+            return ComparisonIndex.deserialize(data, self.parameters, self.bloom_false_positive_rate), None
+        except ComparisonIndexCorruptedException as e:
+            logger.warning(f"Rebuilding comparison index of step {project_step_uuid}: {str(e)}")
+            return None, str(e)
+
+    def save(self, project_uuid, project_step_uuid, index: ComparisonIndex) -> None:
+        self.storage_service.store.put(
+            index_key(project_uuid, project_step_uuid, index.parameters), index.serialize(), "application/octet-stream"
+        )
+
+    def delete(self, project_uuid, project_step_uuid) -> int:
+        """Delete every index of a project step, returns the number of deleted objects"""
+        return self.storage_service.store.delete_prefix(index_prefix(project_uuid, project_step_uuid))
+
+    def _fingerprint_document(self, manifest: dict) -> Tuple[set, Dict[str, Any]]:
+        """
+        Fingerprints and similarity profile of a stored submission, from the files a detailed comparison reads in
+        the order it reads them, decoded the way it decodes them, so that the index holds what the comparison
+        computes
+
+        Returns:
+            (fingerprint hashes, similarity profile of the tokens of every file)
+        """
+        tokenization_service = self.fingerprint_service.tokenization_service
+        hashes, tokens = set(), []
+        for path, entry in sorted(manifest["files"].items()):
+            if not tokenization_service.is_supported_file(Path(path)):
+                continue
+            # Decoded straight from the stored file, without reading it into a buffer first
+            with self.storage_service.blobs.view(*self.storage_service.entry_blob(entry)) as content:
+                text = decode_source(content)
+            # Left out of comparisons, see TokenizationService.extract_supported_files_from_directory
+            if self.fingerprint_service.is_generated(text, Path(path)):
+                continue
+            fingerprint_set = self.fingerprint_service.get_fingerprints(text, Path(path))
+            hashes |= fingerprint_set.hashes
+            tokens.extend(fingerprint_set.tokens)
+        return hashes, self.similarity_service.similarity_profile(tokens)
+
+    def sync(self, project_uuid, project_step_uuid, submissions: Iterable) -> Tuple[ComparisonIndex, Dict[str, Any]]:
+        """
+        Bring the index of a project step up to date with its submissions and persist it
+
+        Submissions not stored in the submission store are not indexed.
+
+        Returns:
+            (index, statistics of the update)
+        """
+        index, rebuild_reason = self.load(project_uuid, project_step_uuid)
+        rebuilt = index is None
+        if index is None:
+            index = ComparisonIndex(self.parameters, self.bloom_false_positive_rate)
+
+        added = removed = 0
+        current = set()
+        for submission in submissions:
+            version = self.storage_service.get_latest_version(submission)
+            manifest = self.storage_service.get_manifest(submission, version) if version is not None else None
+            if manifest is None:
+                continue
+            current.add(str(submission.id))
+            if index.version_of(submission.id) != version:
+                index.add(submission.id, version, *self._fingerprint_document(manifest))
+                added += 1
+
+        for document_id in [document_id for document_id in index.documents if document_id not in current]:
+            index.remove(document_id)
+            removed += 1
+
+        stats = {
+            "rebuilt": rebuilt,
+            "rebuild_reason": rebuild_reason,
+            "documents": len(index),
+            "added": added,
+            "removed": removed,
+            "postings_processed": index.postings_processed,
+        }
+        if rebuilt or added or removed:
+            try:
+                self.save(project_uuid, project_step_uuid, index)
+            except Exception as e:
+                logger.error(f"Failed to persist comparison index of step {project_step_uuid}: {str(e)}")
+        return index, stats
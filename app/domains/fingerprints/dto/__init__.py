@@ -0,0 +1,6 @@
+from .fingerprint_cache_dto import FingerprintCacheInfoDto, FingerprintCacheInvalidationDto
+
+__all__ = [
+    "FingerprintCacheInfoDto",
+    "FingerprintCacheInvalidationDto",
+]
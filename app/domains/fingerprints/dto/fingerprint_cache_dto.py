@@ -0,0 +1,52 @@
+from typing import List, Optional
+
+from pydantic import BaseModel, ConfigDict
+
+
+class FingerprintCacheInfoDto(BaseModel):
+    """DTO for the state of the fingerprint cache"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "enabled": True,
+                "backend": "lmdb",
+                "entries": 1520,
+                "size_bytes": 48234496,
+                "tokenizer_version": "1",
+                "hash_algorithm": "blake3",
+                "accepted_hash_algorithms": ["sha256"],
+                "hash_scheme": "rolling-xxh3",
+                "normalization": "identifiers",
+                "k": 5,
+                "window": 4,
+            }
+        }
+    )
+
+    enabled: bool
+    backend: Optional[str] = None
+    entries: int
+    size_bytes: int = 0
+    tokenizer_version: str
+    hash_algorithm: str
+    accepted_hash_algorithms: List[str] = []
+    hash_scheme: str
+    normalization: str
+    k: int
+    window: int
+
+
+class FingerprintCacheInvalidationDto(BaseModel):
+    """DTO for the result of a fingerprint cache invalidation"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {"language": "python", "tokenizer_version": None, "hash_algorithm": None, "invalidated": 312}
+        }
+    )
+
+    language: Optional[str] = None
+    tokenizer_version: Optional[str] = None
+    hash_algorithm: Optional[str] = None
+    invalidated: int
@@ -0,0 +1,99 @@
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict
+
+
+class DebugTokenDto(BaseModel):
+    """DTO for a token of a file as fingerprinting sees it"""
+
+    index: int
+    type: str
+    token_class: str  # class of the type in the tokenizer configuration, e.g. "identifier"
+    text: str
+    normalized: str  # what is hashed into k-grams at the normalization level
+    weight: int  # times the token is repeated in the k-grams, 0 leaves it out
+    line: int  # 1-based, tokens do not record columns
+    end_line: int
+
+
+class DebugKgramDto(BaseModel):
+    """DTO for a k-gram of normalized tokens and its hash"""
+
+    index: int
+    token: int  # index of its first token
+    hash: str  # 64-bit hash in hexadecimal
+    parts: List[str]
+
+
+class DebugFingerprintDto(BaseModel):
+    """DTO for a fingerprint selected by winnowing"""
+
+    hash: str
+    token: int  # index of the first token of its k-gram
+
+
+class FileFingerprintDebugDto(BaseModel):
+    """DTO for the token stream, k-grams and fingerprints of a stored file, computed like a detection run does"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "submission_id": "550e8400-e29b-41d4-a716-446655440000",
+                "version": 1,
+                "path": "src/main.py",
+                "language": "python",
+                "generated": False,
+                "content_hash": "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+                "tokenizer_version": "1",
+                "hash_algorithm": "blake3",
+                "hash_scheme": "rolling-xxh3",
+                "normalization": "identifiers",
+                "k": 5,
+                "window": 4,
+                "token_count": 2,
+                "kgram_count": 1,
+                "fingerprint_count": 1,
+                "skip": 0,
+                "limit": 1000,
+                "tokens": [
+                    {
+                        "index": 0,
+                        "type": "identifier",
+                        "token_class": "identifier",
+                        "text": "total",
+                        "normalized": "identifier",
+                        "weight": 1,
+                        "line": 1,
+                        "end_line": 1,
+                    }
+                ],
+                "kgrams": [{"index": 0, "token": 0, "hash": "3f2b9c1d0e4a5b68", "parts": ["identifier", "integer"]}],
+                "fingerprints": [{"hash": "3f2b9c1d0e4a5b68", "token": 0}],
+                "tokenization_error": None,
+            }
+        }
+    )
+
+    submission_id: UUID
+    version: Optional[int] = None
+    path: str
+    language: str
+    generated: bool  # detection runs leave generated files out
+    content_hash: str
+    tokenizer_version: str
+    hash_algorithm: str
+    hash_scheme: str
+    normalization: str
+    k: int
+    window: int
+    token_count: int
+    kgram_count: int
+    fingerprint_count: int
+    # Page of the tokens, with the k-grams and fingerprints starting at one of them
+    skip: int
+    limit: int
+    tokens: List[DebugTokenDto]
+    kgrams: List[DebugKgramDto]
+    fingerprints: List[DebugFingerprintDto]
+    tokenization_error: Optional[str] = None
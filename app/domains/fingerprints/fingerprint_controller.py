@@ -0,0 +1,49 @@
+from typing import Optional
+
+from fastapi import APIRouter, Depends, HTTPException, Query
+
+from app.domains.fingerprints.dto.fingerprint_cache_dto import FingerprintCacheInfoDto, FingerprintCacheInvalidationDto
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.shared.security import require_admin_scope
+from app.shared.services import get_fingerprint_service
+
+router = APIRouter(prefix="/admin/fingerprint-cache", tags=["admin"], dependencies=[Depends(require_admin_scope)])
+
+
+@router.get("", response_model=FingerprintCacheInfoDto)
+async def get_fingerprint_cache_info(service: FingerprintService = Depends(get_fingerprint_service)):
+    """Get the configuration and size of the fingerprint cache"""
+    try:
+        return service.get_cache_info()
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=f"Failed to read fingerprint cache: {str(e)}")
+
+
+@router.delete("", response_model=FingerprintCacheInvalidationDto)
+async def invalidate_fingerprint_cache(
+    language: Optional[str] = Query(None, description="Only invalidate entries of this language"),
+    tokenizer_version: Optional[str] = Query(None, description="Only invalidate entries of this tokenizer version"),
+    hash_algorithm: Optional[str] = Query(None, description="Only invalidate entries keyed by this hash algorithm"),
+    service: FingerprintService = Depends(get_fingerprint_service),
+):
+    """
+    Invalidate cached fingerprints by language, tokenizer version and/or content hash algorithm
+
+    At least one filter is required; several filters together only delete entries matching all of them.
+    Invalidating hash_algorithm=sha256 ends a migration to BLAKE3 once the entries in use were copied.
+    """
+    if language is None and tokenizer_version is None and hash_algorithm is None:
+        raise HTTPException(
+            status_code=400, detail="Provide a language, a tokenizer_version and/or a hash_algorithm to invalidate"
+        )
+
+    try:
+        invalidated = service.invalidate(
+            language=language, tokenizer_version=tokenizer_version, hash_algorithm=hash_algorithm
+        )
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=f"Failed to invalidate fingerprint cache: {str(e)}")
+
+    return FingerprintCacheInvalidationDto(
+        language=language, tokenizer_version=tokenizer_version, hash_algorithm=hash_algorithm, invalidated=invalidated
+    )
@@ -0,0 +1,101 @@
+"""
+Pages of fingerprint traces
+
+A trace of a huge file holds hundreds of thousands of tokens, responses hold a page of its tokens with the k-grams
+and fingerprints starting at one of them, so consecutive pages cover each k-gram and fingerprint once.
+"""
+
+from bisect import bisect_left
+from typing import Optional
+from uuid import UUID
+
+from app.domains.fingerprints.dto.fingerprint_debug_dto import (
+    DebugFingerprintDto,
+    DebugKgramDto,
+    DebugTokenDto,
+    FileFingerprintDebugDto,
+)
+from app.domains.fingerprints.fingerprint_models import FingerprintTrace, NormalizationLevel
+from app.domains.fingerprints.fingerprinting import normalize_token
+from app.domains.tokenization.tokenizer_config import TokenizerConfig
+
+
+def hex_hash(value: int) -> str:
+    """64-bit hash in hexadecimal, exact for JSON clients whose numbers are doubles"""
+    return f"{value:016x}"
+
+
+def trace_page(
+    trace: FingerprintTrace,
+    tokenizer_config: TokenizerConfig,
+    submission_id: UUID,
+    version: Optional[int],
+    path: str,
+    generated: bool,
+    skip: int,
+    limit: int,
+) -> FileFingerprintDebugDto:
+    """Page of a trace: tokens skip to skip + limit, the k-grams and the fingerprints starting at one of them"""
+    key = trace.key
+    language_config = tokenizer_config.language(key.language)
+    normalization = NormalizationLevel(key.normalization)
+    page = range(skip, min(skip + limit, len(trace.tokens)))
+
+    tokens = []
+    for index in page:
+        token = trace.tokens[index]
+        token_class = tokenizer_config.token_class(token.get("type", ""))
+        tokens.append(
+            DebugTokenDto(
+                index=index,
+                type=token.get("type", ""),
+                token_class=token_class,
+                text=token.get("text", ""),
+                normalized=normalize_token(token, normalization, tokenizer_config),
+                weight=language_config.token_weights[token_class] if language_config.weighted else 1,
+                line=token.get("start", 0) + 1,
+                end_line=token.get("end", token.get("start", 0)) + 1,
+            )
+        )
+
+    # Parts are in token order, the k-grams of the page are a contiguous range of them
+    token_positions = trace.positions if trace.positions is not None else range(len(trace.parts))
+    first, last = bisect_left(token_positions, page.start), bisect_left(token_positions, page.stop)
+    kgrams = [
+        DebugKgramDto(
+            index=index,
+            token=trace.token_index(index),
+            hash=hex_hash(trace.hashes[index]),
+            parts=trace.parts[index : index + key.k],
+        )
+        for index in range(first, min(last, len(trace.hashes)))
+    ]
+    fingerprints = [
+        DebugFingerprintDto(hash=hex_hash(fingerprint_hash), token=token)
+        for fingerprint_hash, token in trace.fingerprints
+        if token in page
+    ]
+
+    return FileFingerprintDebugDto(
+        submission_id=submission_id,
+        version=version,
+        path=path,
+        language=key.language,
+        generated=generated,
+        content_hash=key.content_hash,
+        tokenizer_version=key.tokenizer_version,
+        hash_algorithm=key.hash_algorithm,
+        hash_scheme=key.hash_scheme,
+        normalization=key.normalization,
+        k=key.k,
+        window=key.window,
+        token_count=len(trace.tokens),
+        kgram_count=len(trace.hashes),
+        fingerprint_count=len(trace.fingerprints),
+        skip=skip,
+        limit=limit,
+        tokens=tokens,
+        kgrams=kgrams,
+        fingerprints=fingerprints,
+        tokenization_error=trace.error,
+    )
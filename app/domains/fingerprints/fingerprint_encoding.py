@@ -0,0 +1,221 @@
+"""
+Compact encoding of stored fingerprint sets
+
+Layout of an entry: magic, encoding version and codec, the token and fingerprint counts, the fingerprints
+(token positions delta + varint encoded in token order, then the 64-bit hashes in the same order), and the token
+stream as consecutive pickled chunks of tokens compressed with the codec. Consecutive fingerprints are at most a
+window apart so their deltas fit in one byte, while hashes are uniformly random and delta coding cannot shrink them.
+
+Fingerprints are read without touching the token stream, which is only decompressed once the tokens are used,
+chunk by chunk when they are iterated once. Entries written before this encoding (plain pickles) stay readable.
+"""
+
+import io
+import pickle
+import struct
+import zlib
+from collections.abc import Sequence
+from enum import IntEnum
+from typing import Any, Callable, Dict, Iterator, List, Optional, Tuple
+
+from app.domains.fingerprints.fingerprint_models import FingerprintSet
+
+try:
+    import zstandard
+except ImportError:  # pragma: no cover - zlib is used instead
+    zstandard = None
+
+ENCODING_MAGIC = b"PFS"
+ENCODING_VERSION = 1
+TOKEN_CHUNK_SIZE = 1024
+ZLIB_LEVEL = 6
+ZSTD_LEVEL = 3
+READ_BUFFER_SIZE = 64 * 1024
+INPUT_SLICE_SIZE = 16 * 1024
+
+
+class Codec(IntEnum):
+    """Compressor of the token stream, recorded in every entry"""
+
+    ZLIB = 1
+    ZSTD = 2
+
+
+DEFAULT_CODEC = Codec.ZSTD if zstandard is not None else Codec.ZLIB
+
+
+class FingerprintEncodingException(ValueError):
+    """Entry that cannot be decoded by this version"""
+
+
+def encode_varint(value: int, out: bytearray) -> None:
+    while value > 0x7F:
+        out.append((value & 0x7F) | 0x80)
+        value >>= 7
+    out.append(value)
+
+
+def zigzag(value: int) -> int:
+    """Map signed deltas to unsigned varints, small magnitudes to small values"""
+    return value << 1 if value >= 0 else ((-value) << 1) - 1
+
+
+def unzigzag(value: int) -> int:
+    return value >> 1 if not value & 1 else -((value + 1) >> 1)
+
+
+def decode_varint(data, offset: int) -> Tuple[int, int]:
+    """Decode the varint at offset, returns (value, offset after it)"""
+    value = shift = 0
+    while True:
+        byte = data[offset]
+        offset += 1
+        value |= (byte & 0x7F) << shift
+        if byte < 0x80:
+            return value, offset
+        shift += 7
+
+
+class _DecompressingReader(io.RawIOBase):
+    """Readable stream decompressing its input slice by slice as it is read"""
+
+    def __init__(self, data, decompressor):
+        self._data = memoryview(data)
+        self._offset = 0
+        self._decompressor = decompressor
+        self._output = memoryview(b"")
+
+    def readable(self) -> bool:
+        return True
+
+    def readinto(self, buffer) -> int:
+        while not self._output and self._offset < len(self._data):
+            self._output = memoryview(
+                self._decompressor.decompress(self._data[self._offset : self._offset + INPUT_SLICE_SIZE])
+            )
+            self._offset += INPUT_SLICE_SIZE
+        size = min(len(buffer), len(self._output))
+        buffer[:size] = self._output[:size]
+        self._output = self._output[size:]
+        return size
+
+
+def _compress(data: bytes, codec: Codec) -> bytes:
+    if codec == Codec.ZSTD:
+        if zstandard is None:
+            raise FingerprintEncodingException("The zstandard package is required for the zstd codec")
+        return zstandard.ZstdCompressor(level=ZSTD_LEVEL).compress(data)
+    return zlib.compress(data, ZLIB_LEVEL)
+
+
+def _open_stream(data, codec: Codec) -> io.BufferedReader:
+    if codec == Codec.ZSTD:
+        if zstandard is None:
+            raise FingerprintEncodingException("The zstandard package is required to read zstd entries")
+        decompressor = zstandard.ZstdDecompressor().decompressobj()
+    else:
+        decompressor = zlib.decompressobj()
+    return io.BufferedReader(_DecompressingReader(data, decompressor), READ_BUFFER_SIZE)
+
+
+def iter_token_stream(data, codec: Codec) -> Iterator[Dict[str, Any]]:
+    """Decompress and unpickle a token stream one chunk at a time"""
+    with _open_stream(data, codec) as stream:
+        while stream.peek(1):
+            yield from pickle.load(stream)
+
+
+class LazyTokens(Sequence):
+    """
+    Tokens of a stored entry, decompressed when first used
+
+    Iterating streams the tokens without keeping them, any other access decodes and keeps the whole list.
+    """
+
+    __hash__ = None
+
+    def __init__(self, count: int, load: Callable[[], Iterator[Dict[str, Any]]]):
+        self._count = count
+        self._load = load
+        self._tokens: Optional[List[Dict[str, Any]]] = None
+
+    def _materialize(self) -> List[Dict[str, Any]]:
+        if self._tokens is None:
+            self._tokens = list(self._load())
+        return self._tokens
+
+    def __len__(self) -> int:
+        return self._count
+
+    def __iter__(self) -> Iterator[Dict[str, Any]]:
+        return iter(self._tokens) if self._tokens is not None else self._load()
+
+    def __getitem__(self, index):
+        return self._materialize()[index]
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, Sequence) or isinstance(other, (str, bytes)):
+            return NotImplemented
+        return len(self) == len(other) and list(self) == list(other)
+
+    def __repr__(self) -> str:
+        return f"LazyTokens({self._count} tokens)"
+
+
+def encode_fingerprint_set(fingerprint_set: FingerprintSet, codec: Codec = DEFAULT_CODEC) -> bytes:
+    tokens = list(fingerprint_set.tokens)
+    fingerprints = list(fingerprint_set.fingerprints)
+
+    out = bytearray(ENCODING_MAGIC)
+    out += bytes((ENCODING_VERSION, codec))
+    encode_varint(len(tokens), out)
+    encode_varint(len(fingerprints), out)
+    previous = 0
+    for _, position in fingerprints:
+        encode_varint(zigzag(position - previous), out)
+        previous = position
+    out += struct.pack(f">{len(fingerprints)}Q", *(fingerprint_hash for fingerprint_hash, _ in fingerprints))
+
+    stream = io.BytesIO()
+    for start in range(0, len(tokens), TOKEN_CHUNK_SIZE):
+        pickle.dump(tokens[start : start + TOKEN_CHUNK_SIZE], stream, protocol=pickle.HIGHEST_PROTOCOL)
+    out += _compress(stream.getvalue(), codec)
+    return bytes(out)
+
+
+def decode_fingerprint_set(data: bytes) -> FingerprintSet:
+    """
+    Decode a stored entry, the tokens lazily
+
+    Raises:
+        FingerprintEncodingException: If the entry has an unknown version or codec, or is truncated
+    """
+    if not data.startswith(ENCODING_MAGIC):
+        # Plain pickle written before the encoding was introduced
+        legacy = pickle.loads(data)
+        return FingerprintSet(tokens=legacy["tokens"], fingerprints=legacy["fingerprints"])
+
+    try:
+        version, codec = data[len(ENCODING_MAGIC)], Codec(data[len(ENCODING_MAGIC) + 1])
+        if version != ENCODING_VERSION:
+            raise FingerprintEncodingException(f"Unsupported fingerprint encoding version {version}")
+        token_count, offset = decode_varint(data, len(ENCODING_MAGIC) + 2)
+        fingerprint_count, offset = decode_varint(data, offset)
+
+        positions = []
+        position = 0
+        for _ in range(fingerprint_count):
+            delta, offset = decode_varint(data, offset)
+            position += unzigzag(delta)
+            positions.append(position)
+        hashes = struct.unpack_from(f">{fingerprint_count}Q", data, offset)
+    except FingerprintEncodingException:
+        raise
+    except (IndexError, ValueError, struct.error) as e:
+        raise FingerprintEncodingException(f"Unreadable fingerprint entry: {str(e)}")
+
+    token_stream = memoryview(data)[offset + 8 * fingerprint_count :]
+    return FingerprintSet(
+        tokens=LazyTokens(token_count, lambda: iter_token_stream(token_stream, codec)),
+        fingerprints=[(fingerprint_hash, position) for fingerprint_hash, position in zip(hashes, positions)],
+    )
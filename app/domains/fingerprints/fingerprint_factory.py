@@ -0,0 +1,59 @@
+import logging
+from typing import Optional
+
+from app.config.config import Settings
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import FingerprintStore, InMemoryFingerprintStore
+from app.shared.exceptions import ValidationException
+
+logger = logging.getLogger(__name__)
+
+SUPPORTED_BACKENDS = ("lmdb", "memory")
+
+
+def create_fingerprint_store(settings: Settings) -> Optional[FingerprintStore]:
+    """
+    Build the fingerprint store selected by the fingerprint_cache_backend setting, None when the cache is disabled
+
+    Raises:
+        ValidationException: If the backend is unknown
+    """
+    if not settings.fingerprint_cache_enabled:
+        logger.info("Fingerprint cache disabled")
+        return None
+
+    backend = (settings.fingerprint_cache_backend or "lmdb").lower()
+    logger.info(f"Creating fingerprint store with backend '{backend}'")
+
+    if backend == "lmdb":
+        from app.domains.fingerprints.lmdb_fingerprint_store import LmdbFingerprintStore
+
+        return LmdbFingerprintStore(settings.fingerprint_cache_path, settings.fingerprint_cache_max_size_gb)
+
+    if backend == "memory":
+        return InMemoryFingerprintStore()
+
+    raise ValidationException(
+        f"Unknown fingerprint cache backend '{settings.fingerprint_cache_backend}'. "
+        f"Supported backends: {', '.join(SUPPORTED_BACKENDS)}"
+    )
+
+
+def create_fingerprint_service(
+    settings: Settings, tokenization_service, store: Optional[FingerprintStore] = None
+) -> FingerprintService:
+    """Build the fingerprint service with the fingerprinting parameters, on a store when given or the configured one"""
+    return FingerprintService(
+        tokenization_service,
+        store=store if store is not None else create_fingerprint_store(settings),
+        k=settings.fingerprint_k,
+        window=settings.fingerprint_window,
+        normalization=settings.fingerprint_normalization,
+        hash_scheme=settings.fingerprint_hash_scheme,
+        hash_algorithm=settings.content_hash_algorithm,
+        accepted_hash_algorithms=settings.accepted_content_hash_algorithms,
+        workers=settings.tokenization_workers,
+        max_files_in_memory=settings.tokenization_max_files_in_memory,
+        streaming_threshold_bytes=settings.tokenization_streaming_threshold_mb * 1024 * 1024,
+        tokenizer_config=getattr(tokenization_service, "tokenizer_config", None),
+    )
@@ -0,0 +1,170 @@
+import threading
+from dataclasses import dataclass, field
+from enum import Enum
+from typing import Any, Dict, List, Optional, Set, Tuple
+
+from app.shared.content_hash import LEGACY_HASH_ALGORITHM
+
+KEY_SEPARATOR = "|"
+# Between the tenant and the content hash of a key, tenant IDs contain neither separator
+TENANT_SEPARATOR = "@"
+
+
+class NormalizationLevel(str, Enum):
+    """How much of the token text is kept before hashing k-grams"""
+
+    NONE = "none"  # token type and full text
+    IDENTIFIERS = "identifiers"  # identifiers and literals reduced to their type
+    TYPES = "types"  # token types only
+
+
+class KgramHashScheme(str, Enum):
+    """How k-grams of normalized tokens are hashed"""
+
+    BLAKE2B = "blake2b"  # digest of every joined k-gram, the scheme before rolling hashes
+    ROLLING_XXH3 = "rolling-xxh3"  # rolling polynomial hash of token hashes, mixed by the XXH3 avalanche
+
+
+LEGACY_KGRAM_HASH_SCHEME = KgramHashScheme.BLAKE2B
+DEFAULT_KGRAM_HASH_SCHEME = KgramHashScheme.ROLLING_XXH3
+
+
+@dataclass(frozen=True)
+class FingerprintKey:
+    """Identity of a fingerprint set: same content tokenized and fingerprinted with the same parameters"""
+
+    content_hash: str
+    language: str
+    tokenizer_version: str
+    normalization: str
+    k: int
+    window: int
+    # Algorithm of content_hash, part of the key so that hashes of two algorithms never collide
+    hash_algorithm: str = LEGACY_HASH_ALGORITHM.value
+    # K-gram hash scheme, fingerprints of two schemes never match so switching schemes misses every entry
+    hash_scheme: str = LEGACY_KGRAM_HASH_SCHEME.value
+    # Tenant the fingerprints were computed for, entries are never shared between tenants, None when unrestricted
+    tenant: Optional[str] = None
+
+    def to_cache_key(self) -> str:
+        """Serialize the key, starting with the fields invalidation filters on"""
+        parts = [self.tokenizer_version, self.language, self.normalization, str(self.k), str(self.window)]
+        # SHA-256 keys keep the layout they had before the algorithm was recorded, so existing entries stay valid,
+        # and likewise keys of the legacy k-gram hash scheme
+        if self.hash_scheme != LEGACY_KGRAM_HASH_SCHEME.value:
+            parts += [self.hash_algorithm, self.hash_scheme]
+        elif self.hash_algorithm != LEGACY_HASH_ALGORITHM.value:
+            parts.append(self.hash_algorithm)
+        content = f"{self.tenant}{TENANT_SEPARATOR}{self.content_hash}" if self.tenant else self.content_hash
+        return KEY_SEPARATOR.join(parts + [content])
+
+    @classmethod
+    def from_cache_key(cls, cache_key: str) -> "FingerprintKey":
+        parts = cache_key.split(KEY_SEPARATOR)
+        if len(parts) == 6:
+            # SHA-256 key, see to_cache_key
+            parts.insert(5, LEGACY_HASH_ALGORITHM.value)
+        if len(parts) == 7:
+            parts.insert(6, LEGACY_KGRAM_HASH_SCHEME.value)
+        tokenizer_version, language, normalization, k, window, hash_algorithm, hash_scheme, content = parts
+        tenant, _, content_hash = content.rpartition(TENANT_SEPARATOR)
+        return cls(
+            content_hash=content_hash,
+            language=language,
+            tokenizer_version=tokenizer_version,
+            normalization=normalization,
+            k=int(k),
+            window=int(window),
+            hash_algorithm=hash_algorithm,
+            hash_scheme=hash_scheme,
+            tenant=tenant or None,
+        )
+
+    def matches(
+        self,
+        language: Optional[str] = None,
+        tokenizer_version: Optional[str] = None,
+        hash_algorithm: Optional[str] = None,
+    ) -> bool:
+        """Check whether the key is selected by an invalidation filter"""
+        if language is not None and self.language != language:
+            return False
+        if tokenizer_version is not None and self.tokenizer_version != tokenizer_version:
+            return False
+        if hash_algorithm is not None and self.hash_algorithm != hash_algorithm:
+            return False
+        return True
+
+
+@dataclass
+class FingerprintSet:
+    """Tokens of a file with their winnowed k-gram fingerprints as (hash, token index) pairs"""
+
+    tokens: List[Dict[str, Any]]
+    fingerprints: List[Tuple[int, int]]
+
+    @property
+    def hashes(self) -> Set[int]:
+        return {fingerprint_hash for fingerprint_hash, _ in self.fingerprints}
+
+
+@dataclass
+class FingerprintTrace:
+    """What fingerprinting a file went through, from its tokens to its selected fingerprints"""
+
+    key: FingerprintKey
+    tokens: List[Dict[str, Any]]
+    parts: List[str]  # normalized tokens, repeated by the weight of their class
+    hashes: List[int]  # hash of the k-gram starting at each part
+    # Index of the token of each part, None when no class is weighted and parts are the tokens
+    positions: Optional[List[int]]
+    fingerprints: List[Tuple[int, int]]
+    error: Optional[str] = None  # why tokenization failed, with no token
+
+    def token_index(self, part: int) -> int:
+        """Index of the token of a part"""
+        return part if self.positions is None else self.positions[part]
+
+
+@dataclass
+class FingerprintCacheStats:
+    """Fingerprint cache usage of one detection run"""
+
+    hits: int = 0
+    misses: int = 0
+    writes: int = 0
+    errors: int = 0
+    parameters: Dict[str, Any] = field(default_factory=dict)
+    # Counters are updated by every tokenization worker of the run
+    _lock: threading.Lock = field(default_factory=threading.Lock, init=False, repr=False, compare=False)
+
+    def record(self, **increments: int) -> None:
+        """Atomically add to the counters, e.g. record(misses=1, writes=1)"""
+        with self._lock:
+            for counter, increment in increments.items():
+                setattr(self, counter, getattr(self, counter) + increment)
+
+    @property
+    def lookups(self) -> int:
+        return self.hits + self.misses
+
+    @property
+    def tokenizations(self) -> int:
+        """Every miss is tokenized and fingerprinted"""
+        return self.misses
+
+    @property
+    def hit_rate(self) -> float:
+        """Calculate hit rate percentage"""
+        return (self.hits / self.lookups * 100) if self.lookups > 0 else 0.0
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "hits": self.hits,
+            "misses": self.misses,
+            "writes": self.writes,
+            "errors": self.errors,
+            "tokenizations": self.tokenizations,
+            "hit_rate": round(self.hit_rate, 2),
+            "parameters": self.parameters,
+        }
@@ -0,0 +1,437 @@
+import copy
+import logging
+import threading
+import time
+from collections import deque
+from concurrent.futures import ThreadPoolExecutor
+from contextlib import ExitStack
+from dataclasses import replace
+from pathlib import Path
+from typing import Any, Callable, Dict, Iterable, Iterator, Optional, Tuple
+
+from app.domains.detection.file_errors import FileStage, FileStageError, is_systemic
+from app.domains.fingerprints.fingerprint_models import (
+    DEFAULT_KGRAM_HASH_SCHEME,
+    FingerprintCacheStats,
+    FingerprintKey,
+    FingerprintSet,
+    FingerprintTrace,
+    KgramHashScheme,
+    NormalizationLevel,
+)
+from app.domains.fingerprints.fingerprint_store import FingerprintStore
+from app.domains.fingerprints.fingerprinting import (
+    compute_fingerprints,
+    content_hash,
+    weighted_kgram_hashes,
+    winnow,
+)
+from app.domains.tokenization.streaming_source import StreamingSource, normalize_source
+from app.domains.tokenization.tokenization_service import TOKENIZER_VERSION
+from app.domains.tokenization.tokenizer_config import TokenizerConfig, get_tokenizer_config
+from app.shared.concurrency import resolve_workers
+from app.shared.content_hash import DEFAULT_HASH_ALGORITHM, HashAlgorithm, parse_hash_algorithm
+from app.shared.metrics import (
+    DETECTION_TIMEOUTS,
+    FINGERPRINT_CACHE_ERRORS,
+    FINGERPRINT_CACHE_HITS,
+    FINGERPRINT_CACHE_MISSES,
+    TOKENIZATION_SECONDS,
+    TOKENIZED_FILES,
+    TOKENS,
+)
+from app.shared.profiling import NULL_PROFILER, StageProfiler
+from app.shared.tenancy import tenant_namespace
+from app.shared.timeouts import FILE, TimedOut, Timeout
+from app.shared.tracing import in_current_context
+
+logger = logging.getLogger(__name__)
+
+
+class FingerprintService:
+    """
+    Tokenizes and fingerprints files through the fingerprint store:
+    hits load the stored fingerprint set, misses tokenize, fingerprint and write back
+
+    While the content hash algorithm is being migrated, entries keyed by one of the accepted previous
+    algorithms are still hits and are copied under the current algorithm.
+    """
+
+    def __init__(
+        self,
+        tokenization_service,
+        store: Optional[FingerprintStore] = None,
+        k: int = 5,
+        window: int = 4,
+        normalization: str = NormalizationLevel.IDENTIFIERS.value,
+        tokenizer_version: str = TOKENIZER_VERSION,
+        hash_algorithm: str = DEFAULT_HASH_ALGORITHM.value,
+        accepted_hash_algorithms: Iterable[str] = (),
+        workers: int = 1,
+        max_files_in_memory: int = 64,
+        streaming_threshold_bytes: int = 0,
+        hash_scheme: str = DEFAULT_KGRAM_HASH_SCHEME.value,
+        tokenizer_config: Optional[TokenizerConfig] = None,
+    ):
+        self.tokenization_service = tokenization_service
+        self.store = store
+        self.k = k
+        self.window = window
+        self.normalization = NormalizationLevel(normalization)
+        self.tokenizer_version = tokenizer_version
+        # Languages configured otherwise than built in get their own tokenizer version and may set a normalization
+        self.tokenizer_config = tokenizer_config or get_tokenizer_config()
+        self.hash_scheme = KgramHashScheme(hash_scheme)
+        self.hash_algorithm = parse_hash_algorithm(hash_algorithm)
+        self.accepted_hash_algorithms = [
+            algorithm
+            for algorithm in (parse_hash_algorithm(name) for name in accepted_hash_algorithms)
+            if algorithm != self.hash_algorithm
+        ]
+        # 0 uses every available CPU
+        self.workers = resolve_workers(workers, lambda cpus: cpus)
+        self.max_files_in_memory = max(max_files_in_memory, 1)
+        # Files larger than this are streamed from disk instead of read in memory, 0 never streams
+        self.streaming_threshold_bytes = streaming_threshold_bytes
+        self._executor: Optional[ThreadPoolExecutor] = None
+        self._executor_lock = threading.Lock()
+
+    @property
+    def parameters(self) -> Dict[str, Any]:
+        """Parameters every cached entry depends on"""
+        parameters = {
+            "tokenizer_version": self.tokenizer_version,
+            "hash_algorithm": self.hash_algorithm.value,
+            "hash_scheme": self.hash_scheme.value,
+            "normalization": self.normalization.value,
+            "k": self.k,
+            "window": self.window,
+        }
+        # Only set once configured otherwise than built in, indexes built with the built-in configuration stay valid
+        if self.tokenizer_config.revision:
+            parameters["tokenizer_config"] = self.tokenizer_config.revision
+        return parameters
+
+    def is_generated(self, content: str, file_path: Path) -> bool:
+        """Whether a decoded file holds a generated file marker of its language, such files are not compared"""
+        language = self.tokenization_service._detect_language(file_path)
+        return self.tokenizer_config.language(language).is_generated(content)
+
+    def new_stats(self) -> FingerprintCacheStats:
+        """Create the statistics of a run using this service"""
+        return FingerprintCacheStats(parameters=self.parameters)
+
+    def with_parameters(self, k: Optional[int] = None, window: Optional[int] = None) -> "FingerprintService":
+        """Service fingerprinting with other k-gram and window sizes, on the same store and tokenization pool"""
+        k, window = k or self.k, window or self.window
+        if (k, window) == (self.k, self.window):
+            return self
+        service = copy.copy(self)
+        service.k, service.window = k, window
+        service._get_executor = self._get_executor
+        return service
+
+    def build_key(self, content: str, file_path: Optional[Path] = None, hash_algorithm=None) -> FingerprintKey:
+        hash_algorithm = parse_hash_algorithm(hash_algorithm) if hash_algorithm else self.hash_algorithm
+        return self._key(content_hash(content, hash_algorithm), file_path, hash_algorithm)
+
+    def _key(self, hash_value: str, file_path: Optional[Path], hash_algorithm: HashAlgorithm) -> FingerprintKey:
+        language = self.tokenization_service._detect_language(file_path)
+        return FingerprintKey(
+            content_hash=hash_value,
+            language=language,
+            tokenizer_version=self.tokenizer_config.tokenizer_version(language, self.tokenizer_version),
+            normalization=self.tokenizer_config.language(language).normalization or self.normalization.value,
+            k=self.k,
+            window=self.window,
+            hash_algorithm=hash_algorithm.value,
+            hash_scheme=self.hash_scheme.value,
+            tenant=tenant_namespace(),
+        )
+
+    def _get_cached(
+        self, key: FingerprintKey, previous_key: Callable[[HashAlgorithm], FingerprintKey]
+    ) -> Optional[FingerprintSet]:
+        cached = self.store.get(key)
+        if cached is not None:
+            return cached
+
+        for previous_algorithm in self.accepted_hash_algorithms:
+            cached = self.store.get(previous_key(previous_algorithm))
+            if cached is not None:
+                # Migrated on read, the previous entry goes away with the invalidation ending the transition
+                self.store.put(key, cached)
+                return cached
+        return None
+
+    def get_fingerprints(
+        self,
+        content: str,
+        file_path: Optional[Path] = None,
+        stats: Optional[FingerprintCacheStats] = None,
+        profiler: StageProfiler = NULL_PROFILER,
+        raise_errors: bool = False,
+    ) -> FingerprintSet:
+        """
+        Get the tokens and fingerprints of a file, tokenizing it only if they are not stored yet
+
+        Args:
+            content: File content, with or without a BOM and in any line endings
+            file_path: Path of the file, used to detect its language
+            stats: Statistics of the current run, updated with the outcome of the lookup
+            profiler: Records the tokenization and fingerprinting time of the run
+            raise_errors: Raise tokenization and fingerprinting failures as FileStageError, tokenization
+                failures otherwise give no token
+        """
+        content = normalize_source(content)
+        options = {"raise_errors": True} if raise_errors else {}
+        return self._fingerprint(
+            self.build_key(content, file_path),
+            lambda algorithm: self.build_key(content, file_path, algorithm),
+            lambda: self.tokenization_service.tokenize(content, file_path, **options),
+            file_path,
+            stats,
+            profiler,
+            raise_errors,
+        )
+
+    def get_file_fingerprints(
+        self,
+        file_path: Path,
+        stats: Optional[FingerprintCacheStats] = None,
+        profiler: StageProfiler = NULL_PROFILER,
+        raise_errors: bool = False,
+    ) -> FingerprintSet:
+        """
+        Same as get_fingerprints on the content of a file, streamed from disk instead of read in memory
+
+        The cache key is identical, so entries are shared with files fingerprinted from their content.
+        """
+        options = {"raise_errors": True} if raise_errors else {}
+        with ExitStack() as stack:
+            with profiler.stage("decoding"):
+                source = stack.enter_context(StreamingSource(file_path))
+                key = self._key(source.hash(self.hash_algorithm), file_path, self.hash_algorithm)
+            return self._fingerprint(
+                key,
+                lambda algorithm: self._key(source.hash(algorithm), file_path, algorithm),
+                lambda: self.tokenization_service.tokenize_source(source, file_path, **options),
+                file_path,
+                stats,
+                profiler,
+                raise_errors,
+            )
+
+    def _fingerprint(
+        self,
+        key: FingerprintKey,
+        previous_key: Callable[[HashAlgorithm], FingerprintKey],
+        tokenize: Callable[[], list],
+        file_path: Optional[Path],
+        stats: Optional[FingerprintCacheStats],
+        profiler: StageProfiler = NULL_PROFILER,
+        raise_errors: bool = False,
+    ) -> FingerprintSet:
+        stats = stats if stats is not None else self.new_stats()
+
+        if self.store is not None:
+            try:
+                cached = self._get_cached(key, previous_key)
+                if cached is not None:
+                    stats.record(hits=1)
+                    FINGERPRINT_CACHE_HITS.inc()
+                    return cached
+                FINGERPRINT_CACHE_MISSES.inc()
+            except Exception as e:
+                stats.record(errors=1)
+                FINGERPRINT_CACHE_ERRORS.inc()
+                logger.warning(f"Failed to read fingerprints of {file_path} from cache: {e}")
+
+        stats.record(misses=1)
+        stage = FileStage.TOKENIZATION
+        try:
+            started = time.perf_counter()
+            with profiler.stage(f"tokenization.{key.language}"):
+                tokens = tokenize()
+            language = key.language or "unknown"
+            TOKENIZATION_SECONDS.labels(language).inc(time.perf_counter() - started)
+            TOKENIZED_FILES.labels(language).inc()
+            TOKENS.labels(language).inc(len(tokens))
+            stage = FileStage.FINGERPRINTING
+            with profiler.stage("fingerprinting"):
+                fingerprints = compute_fingerprints(
+                    tokens,
+                    self.k,
+                    self.window,
+                    NormalizationLevel(key.normalization),
+                    self.hash_scheme,
+                    self.tokenizer_config,
+                    key.language,
+                )
+                fingerprint_set = FingerprintSet(tokens=tokens, fingerprints=fingerprints)
+        except Exception as e:
+            if raise_errors and not is_systemic(e):
+                raise FileStageError(stage, e) from e
+            raise
+
+        # Empty token lists are also returned on tokenization failures, never cache them
+        if self.store is not None and tokens:
+            try:
+                self.store.put(key, fingerprint_set)
+                stats.record(writes=1)
+            except Exception as e:
+                stats.record(errors=1)
+                logger.warning(f"Failed to write fingerprints of {file_path} to cache: {e}")
+
+        return fingerprint_set
+
+    def trace_fingerprints(
+        self,
+        content: str,
+        file_path: Optional[Path] = None,
+        k: Optional[int] = None,
+        window: Optional[int] = None,
+        normalization: Optional[NormalizationLevel] = None,
+    ) -> FingerprintTrace:
+        """
+        Tokenize and fingerprint a file like get_fingerprints, keeping every intermediate step, for debugging
+
+        The store is neither read nor written. Without overrides the key and the fingerprints are those
+        get_fingerprints caches for the same content.
+
+        Args:
+            content: File content, with or without a BOM and in any line endings
+            file_path: Path of the file, used to detect its language
+            k: K-gram size instead of the configured one
+            window: Winnowing window instead of the configured one
+            normalization: Normalization level instead of that of the language or the configured one
+        """
+        content = normalize_source(content)
+        key = self.build_key(content, file_path)
+        key = replace(
+            key,
+            k=k or key.k,
+            window=window or key.window,
+            normalization=normalization.value if normalization else key.normalization,
+        )
+        error = None
+        try:
+            tokens = self.tokenization_service.tokenize(content, file_path, raise_errors=True)
+        except Exception as e:
+            tokens, error = [], str(e)
+        parts, hashes, positions = weighted_kgram_hashes(
+            tokens, key.k, NormalizationLevel(key.normalization), self.hash_scheme, self.tokenizer_config, key.language
+        )
+        fingerprints = winnow(hashes, key.window, positions) if tokens else []
+        return FingerprintTrace(key, tokens, parts, hashes, positions, fingerprints, error)
+
+    def _get_executor(self) -> ThreadPoolExecutor:
+        with self._executor_lock:
+            if self._executor is None:
+                self._executor = ThreadPoolExecutor(max_workers=self.workers, thread_name_prefix="tokenization")
+            return self._executor
+
+    def fingerprint_files(
+        self,
+        file_paths: Iterable[Path],
+        read_file: Callable[[Path], Optional[str]],
+        stats: Optional[FingerprintCacheStats] = None,
+        profiler: StageProfiler = NULL_PROFILER,
+        on_error: Optional[Callable[[Path, FileStage, BaseException], None]] = None,
+        timeout_seconds: float = 0,
+    ) -> Iterator[Tuple[Path, FingerprintSet]]:
+        """
+        Read and fingerprint files on the tokenization pool, yielding the results in the order of file_paths
+
+        At most max_files_in_memory files are being read, tokenized or waiting to be consumed at any time,
+        so the decoded contents held in memory stay bounded whatever the size of the submission. Files larger
+        than streaming_threshold_bytes are streamed from disk instead of passed to read_file.
+
+        Args:
+            file_paths: Files to fingerprint
+            read_file: Returns the decoded content of a file, None to skip it
+            stats: Statistics of the current run, updated by every worker
+            profiler: Records the decoding, tokenization and fingerprinting time of the run
+            on_error: Called in the order of file_paths with each file that failed, its stage and the error,
+                the file being skipped; failures are raised without it, and systemic ones in any case
+            timeout_seconds: Time each file may be read and tokenized in, 0 for no limit; a file taking longer
+                fails its tokenization with TimedOut at its next checkpoint
+        """
+        stats = stats if stats is not None else self.new_stats()
+        raise_errors = on_error is not None
+
+        def fingerprint(file_path: Path) -> Any:
+            timeout = Timeout(FILE, timeout_seconds)
+            try:
+                with timeout:
+                    if self.streaming_threshold_bytes and file_path.stat().st_size > self.streaming_threshold_bytes:
+                        return self.get_file_fingerprints(file_path, stats, profiler, raise_errors)
+                    with profiler.stage("decoding"):
+                        content = read_file(file_path)
+                    if content is None:
+                        return None
+                    return self.get_fingerprints(content, file_path, stats, profiler, raise_errors)
+            except TimedOut as e:
+                # Timeouts of the pair or the run stop the comparison, not only this file
+                if not raise_errors or e.timeout is not timeout:
+                    raise
+                DETECTION_TIMEOUTS.labels(FILE).inc()
+                return FileStageError(FileStage.TOKENIZATION, e)
+            except Exception as e:
+                if not raise_errors or is_systemic(e):
+                    raise
+                # Handed to on_error by the consumer, in the order of the files
+                return e if isinstance(e, FileStageError) else FileStageError(FileStage.DECODING, e)
+
+        if self.workers <= 1:
+            for file_path in file_paths:
+                yield from self._yield_result(file_path, fingerprint(file_path), on_error)
+            return
+
+        executor = self._get_executor()
+        pending = deque()
+        try:
+            for file_path in file_paths:
+                if len(pending) >= self.max_files_in_memory:
+                    yield from self._completed(pending.popleft(), on_error)
+                pending.append((file_path, executor.submit(in_current_context(fingerprint), file_path)))
+            while pending:
+                yield from self._completed(pending.popleft(), on_error)
+        finally:
+            for _, future in pending:
+                future.cancel()
+
+    @classmethod
+    def _completed(cls, item: Tuple[Path, Any], on_error: Optional[Callable] = None) -> Iterator[Tuple[Path, Any]]:
+        file_path, future = item
+        yield from cls._yield_result(file_path, future.result(), on_error)
+
+    @staticmethod
+    def _yield_result(file_path: Path, result: Any, on_error: Optional[Callable]) -> Iterator[Tuple[Path, Any]]:
+        if isinstance(result, FileStageError):
+            logger.warning(f"Skipping {file_path}, {result.stage.value} failed: {result.cause}")
+            on_error(file_path, result.stage, result.cause)
+        elif result is not None:
+            yield file_path, result
+
+    def invalidate(
+        self,
+        language: Optional[str] = None,
+        tokenizer_version: Optional[str] = None,
+        hash_algorithm: Optional[str] = None,
+    ) -> int:
+        """Delete the cached entries matching the filters, returns the number deleted"""
+        if self.store is None:
+            return 0
+        return self.store.invalidate(language, tokenizer_version, hash_algorithm)
+
+    def get_cache_info(self) -> Dict[str, Any]:
+        """Get the configuration and size of the fingerprint cache"""
+        return {
+            "enabled": self.store is not None,
+            "backend": self.store.backend_name if self.store is not None else None,
+            "entries": self.store.count() if self.store is not None else 0,
+            "size_bytes": self.store.size_bytes() if self.store is not None else 0,
+            "accepted_hash_algorithms": [algorithm.value for algorithm in self.accepted_hash_algorithms],
+            **self.parameters,
+        }
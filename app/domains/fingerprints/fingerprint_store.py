@@ -0,0 +1,84 @@
+import threading
+from abc import ABC, abstractmethod
+from typing import Dict, Optional
+
+from app.domains.fingerprints.fingerprint_encoding import decode_fingerprint_set, encode_fingerprint_set
+from app.domains.fingerprints.fingerprint_models import FingerprintKey, FingerprintSet
+
+
+class FingerprintStore(ABC):
+    """Persistent mapping from fingerprint keys to fingerprint sets"""
+
+    backend_name = "abstract"
+
+    @abstractmethod
+    def get(self, key: FingerprintKey) -> Optional[FingerprintSet]:
+        """Get the fingerprint set of a key, None if it was never stored"""
+
+    @abstractmethod
+    def put(self, key: FingerprintKey, fingerprint_set: FingerprintSet) -> None:
+        """Store the fingerprint set of a key, replacing any previous one"""
+
+    @abstractmethod
+    def invalidate(
+        self,
+        language: Optional[str] = None,
+        tokenizer_version: Optional[str] = None,
+        hash_algorithm: Optional[str] = None,
+    ) -> int:
+        """Delete the entries matching the filters (all entries without filters), returns the number deleted"""
+
+    @abstractmethod
+    def count(self) -> int:
+        """Number of stored entries"""
+
+    def size_bytes(self) -> int:
+        """Approximate space used by the stored entries"""
+        return 0
+
+    def close(self) -> None:
+        """Release the resources held by the store"""
+
+
+class InMemoryFingerprintStore(FingerprintStore):
+    """
+    Fingerprint store kept in process memory, used by tests and when no cache directory is available
+
+    Entries are held encoded like on disk, so the store takes the space a persisted cache would.
+    """
+
+    backend_name = "memory"
+
+    def __init__(self):
+        self._entries: Dict[FingerprintKey, bytes] = {}
+        self._lock = threading.Lock()
+
+    def get(self, key: FingerprintKey) -> Optional[FingerprintSet]:
+        with self._lock:
+            value = self._entries.get(key)
+        return decode_fingerprint_set(value) if value is not None else None
+
+    def put(self, key: FingerprintKey, fingerprint_set: FingerprintSet) -> None:
+        value = encode_fingerprint_set(fingerprint_set)
+        with self._lock:
+            self._entries[key] = value
+
+    def invalidate(
+        self,
+        language: Optional[str] = None,
+        tokenizer_version: Optional[str] = None,
+        hash_algorithm: Optional[str] = None,
+    ) -> int:
+        with self._lock:
+            matching = [key for key in self._entries if key.matches(language, tokenizer_version, hash_algorithm)]
+            for key in matching:
+                del self._entries[key]
+            return len(matching)
+
+    def count(self) -> int:
+        with self._lock:
+            return len(self._entries)
+
+    def size_bytes(self) -> int:
+        with self._lock:
+            return sum(len(value) for value in self._entries.values())
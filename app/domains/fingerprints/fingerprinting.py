@@ -0,0 +1,202 @@
+import hashlib
+from typing import Any, Dict, List, Optional, Tuple
+
+from app.domains.fingerprints.fingerprint_models import DEFAULT_KGRAM_HASH_SCHEME, KgramHashScheme, NormalizationLevel
+from app.domains.tokenization.streaming_source import normalize_source
+from app.domains.tokenization.tokenizer_config import TokenizerConfig, builtin_tokenizer_config
+from app.shared.content_hash import DEFAULT_HASH_ALGORITHM, HashAlgorithm, hash_bytes
+
+# Token classes whose text is replaced by the type name from the 'identifiers' normalization level on
+RENAMED_TOKEN_CLASSES = ("identifier", "literal")
+
+# Rolling hash modulo the Mersenne prime 2^61 - 1 with a fixed base, so hashes are the same in every process
+ROLLING_HASH_MODULUS = (1 << 61) - 1
+ROLLING_HASH_BASE = 0x1F3D5B79A2C4E687 % ROLLING_HASH_MODULUS
+XXH3_AVALANCHE_MULTIPLIER = 0x165667919E3779F9
+MASK_64 = 0xFFFFFFFFFFFFFFFF
+
+
+def content_hash(content: str, algorithm: HashAlgorithm = DEFAULT_HASH_ALGORITHM) -> str:
+    """Hash of the file content the fingerprints are computed from, after BOM and line ending normalization"""
+    return hash_bytes(normalize_source(content).encode("utf-8", errors="ignore"), algorithm)
+
+
+def normalize_token(
+    token: Dict[str, Any], normalization: NormalizationLevel, tokenizer_config: Optional[TokenizerConfig] = None
+) -> str:
+    """Reduce a token to the representation hashed into k-grams, its class given by the tokenizer configuration"""
+    token_type = token.get("type", "")
+    if normalization == NormalizationLevel.TYPES:
+        return token_type
+
+    text = token.get("text", "")
+    if normalization == NormalizationLevel.NONE:
+        return f"{token_type}:{text}"
+
+    # Identifiers and literals are renamed freely, multi-line nodes are covered by their children
+    token_class = (tokenizer_config or builtin_tokenizer_config()).token_class(token_type)
+    if token_class in RENAMED_TOKEN_CLASSES or "\n" in text:
+        return token_type
+    return f"{token_type}:{text}"
+
+
+def weigh_tokens(
+    tokens: List[Dict[str, Any]], parts: List[str], weights: Dict[str, int], tokenizer_config: TokenizerConfig
+) -> Tuple[List[str], List[int]]:
+    """Repeat each normalized token by the weight of its class, with the index of the token of each repeated part"""
+    weighted_parts, positions = [], []
+    for index, (token, part) in enumerate(zip(tokens, parts)):
+        weight = weights[tokenizer_config.token_class(token.get("type", ""))]
+        weighted_parts.extend([part] * weight)
+        positions.extend([index] * weight)
+    return weighted_parts, positions
+
+
+def hash_kgram(parts: List[str]) -> int:
+    """Stable 64-bit hash of a k-gram, independent of PYTHONHASHSEED"""
+    digest = hashlib.blake2b("\x1f".join(parts).encode("utf-8", errors="ignore"), digest_size=8).digest()
+    return int.from_bytes(digest, "big")
+
+
+def xxh3_avalanche(value: int) -> int:
+    """Final mix of XXH3, a bijection of 64-bit values spreading every input bit over the whole output"""
+    value ^= value >> 37
+    value = (value * XXH3_AVALANCHE_MULTIPLIER) & MASK_64
+    return value ^ (value >> 32)
+
+
+def hash_token(part: str) -> int:
+    """Stable hash of a normalized token, reduced to the field of the rolling hash"""
+    digest = hashlib.blake2b(part.encode("utf-8", errors="ignore"), digest_size=8).digest()
+    return int.from_bytes(digest, "big") % ROLLING_HASH_MODULUS
+
+
+def rolling_kgram_hashes(parts: List[str], k: int) -> List[int]:
+    """
+    Hashes of every k-gram of normalized tokens, sliding a polynomial hash over the token hashes
+
+    Each distinct token is hashed once, each k-gram then costs a few integer operations instead of a digest of the
+    joined k-gram. The polynomial value alone is linear in the tokens and clusters similar k-grams, the XXH3
+    avalanche decorrelates it before winnowing compares hashes.
+    """
+    token_hashes: Dict[str, int] = {}
+    values = []
+    for part in parts:
+        value = token_hashes.get(part)
+        if value is None:
+            value = token_hashes[part] = hash_token(part)
+        values.append(value)
+
+    k = min(k, len(values))
+    base, modulus = ROLLING_HASH_BASE, ROLLING_HASH_MODULUS
+    # Weight of the token leaving the window
+    leaving = pow(base, k - 1, modulus)
+    state = 0
+    for value in values[:k]:
+        state = (state * base + value) % modulus
+
+    hashes = [xxh3_avalanche(state)]
+    append = hashes.append
+    # xxh3_avalanche inlined, the call costs as much as the hash
+    for outgoing, incoming in zip(values, values[k:]):
+        state = ((state - outgoing * leaving) * base + incoming) % modulus
+        mixed = (((state >> 37) ^ state) * XXH3_AVALANCHE_MULTIPLIER) & MASK_64
+        append(mixed ^ (mixed >> 32))
+    return hashes
+
+
+def kgram_hashes(parts: List[str], k: int, scheme: KgramHashScheme = DEFAULT_KGRAM_HASH_SCHEME) -> List[int]:
+    """Hashes of every k-gram of normalized tokens in order, a single hash of all of them when fewer than k"""
+    if not parts:
+        return []
+    if scheme == KgramHashScheme.BLAKE2B:
+        if len(parts) < k:
+            return [hash_kgram(parts)]
+        return [hash_kgram(parts[i : i + k]) for i in range(len(parts) - k + 1)]
+    return rolling_kgram_hashes(parts, k)
+
+
+def weighted_kgram_hashes(
+    tokens: List[Dict[str, Any]],
+    k: int,
+    normalization: NormalizationLevel,
+    scheme: KgramHashScheme = DEFAULT_KGRAM_HASH_SCHEME,
+    tokenizer_config: Optional[TokenizerConfig] = None,
+    language: Optional[str] = None,
+) -> Tuple[List[str], List[int], Optional[List[int]]]:
+    """
+    Normalized tokens of a token stream repeated by the weight of their class, and the hashes of their k-grams
+
+    Returns:
+        The normalized parts, the hash of the k-gram starting at each part and the index of the token of each part,
+        None when the language weighs no class so parts and tokens are the same
+    """
+    tokenizer_config = tokenizer_config or builtin_tokenizer_config()
+    parts = [normalize_token(token, normalization, tokenizer_config) for token in tokens]
+    positions = None
+    language_config = tokenizer_config.language(language)
+    if language_config.weighted:
+        parts, positions = weigh_tokens(tokens, parts, language_config.token_weights, tokenizer_config)
+    return parts, kgram_hashes(parts, max(1, k), KgramHashScheme(scheme)), positions
+
+
+def winnow(hashes: List[int], window: int, positions: Optional[List[int]] = None) -> List[Tuple[int, int]]:
+    """
+    Minimum hash of every window of `window` consecutive k-gram hashes, rightmost on ties, each selected once
+
+    Returns:
+        List of (hash, index of the k-gram, or of its first token through positions) pairs in order
+    """
+    if not hashes:
+        return []
+
+    window = max(1, window)
+    if len(hashes) <= window:
+        minimum = min(hashes)
+        position = len(hashes) - 1 - hashes[::-1].index(minimum)
+        return [(minimum, position if positions is None else positions[position])]
+
+    fingerprints = []
+    last_position = -1
+    for start in range(len(hashes) - window + 1):
+        window_hashes = hashes[start : start + window]
+        minimum = min(window_hashes)
+        position = start + window - 1 - window_hashes[::-1].index(minimum)
+        if position != last_position:
+            fingerprints.append((minimum, position if positions is None else positions[position]))
+            last_position = position
+
+    return fingerprints
+
+
+def compute_fingerprints(
+    tokens: List[Dict[str, Any]],
+    k: int,
+    window: int,
+    normalization: NormalizationLevel,
+    scheme: KgramHashScheme = DEFAULT_KGRAM_HASH_SCHEME,
+    tokenizer_config: Optional[TokenizerConfig] = None,
+    language: Optional[str] = None,
+) -> List[Tuple[int, int]]:
+    """
+    Winnow the k-gram hashes of a token stream (Schleimer et al., 2003)
+
+    Every window of `window` consecutive k-gram hashes contributes its minimum hash (rightmost on ties),
+    so any match of at least window + k - 1 tokens shares at least one fingerprint. Tokens are repeated by
+    the weight of their class in the tokenizer configuration of the language, a weight of 0 leaves them out.
+
+    Returns:
+        List of (hash, index of the first token of the k-gram) pairs in token order
+    """
+    if not tokens:
+        return []
+
+    _, hashes, positions = weighted_kgram_hashes(tokens, k, normalization, scheme, tokenizer_config, language)
+    return winnow(hashes, window, positions)
+
+
+def fingerprint_similarity(hashes1: set, hashes2: set) -> float:
+    """Jaccard similarity of two fingerprint hash sets"""
+    if not hashes1 and not hashes2:
+        return 0.0
+    return len(hashes1 & hashes2) / len(hashes1 | hashes2)
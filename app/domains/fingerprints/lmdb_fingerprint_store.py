@@ -0,0 +1,87 @@
+import logging
+import threading
+from pathlib import Path
+from typing import Optional
+
+import lmdb
+
+from app.domains.fingerprints.fingerprint_encoding import decode_fingerprint_set, encode_fingerprint_set
+from app.domains.fingerprints.fingerprint_models import FingerprintKey, FingerprintSet
+from app.domains.fingerprints.fingerprint_store import FingerprintStore
+
+logger = logging.getLogger(__name__)
+
+
+class LmdbFingerprintStore(FingerprintStore):
+    """Fingerprint store persisted in an LMDB database, surviving restarts of the service"""
+
+    backend_name = "lmdb"
+
+    def __init__(self, db_path: str, max_size_gb: int = 2):
+        self.db_path = Path(db_path)
+        self.db_path.mkdir(parents=True, exist_ok=True)
+        self._lock = threading.Lock()
+
+        self._env = lmdb.open(
+            str(self.db_path),
+            map_size=max_size_gb * 1024 * 1024 * 1024,
+            max_dbs=1,
+            lock=True,
+        )
+        with self._env.begin(write=True) as txn:
+            self._db = self._env.open_db(b"fingerprints", txn=txn, create=True)
+
+        logger.info(f"LMDB fingerprint store initialized at {self.db_path}")
+
+    def get(self, key: FingerprintKey) -> Optional[FingerprintSet]:
+        with self._env.begin(write=False) as txn:
+            value = txn.get(key.to_cache_key().encode("utf-8"), db=self._db)
+
+        # Entries written before the compact encoding are plain pickles, decoded as such
+        return decode_fingerprint_set(value) if value is not None else None
+
+    def put(self, key: FingerprintKey, fingerprint_set: FingerprintSet) -> None:
+        value = encode_fingerprint_set(fingerprint_set)
+        with self._lock, self._env.begin(write=True) as txn:
+            txn.put(key.to_cache_key().encode("utf-8"), value, db=self._db)
+
+    def invalidate(
+        self,
+        language: Optional[str] = None,
+        tokenizer_version: Optional[str] = None,
+        hash_algorithm: Optional[str] = None,
+    ) -> int:
+        deleted = 0
+        with self._lock, self._env.begin(write=True) as txn:
+            cursor = txn.cursor(self._db)
+            for raw_key in list(cursor.iternext(keys=True, values=False)):
+                try:
+                    key = FingerprintKey.from_cache_key(raw_key.decode("utf-8"))
+                except ValueError:
+                    # Unreadable keys were written by an incompatible version, drop them too
+                    key = None
+
+                if key is None or key.matches(language, tokenizer_version, hash_algorithm):
+                    txn.delete(raw_key, db=self._db)
+                    deleted += 1
+
+        logger.info(
+            f"Invalidated {deleted} fingerprint entries "
+            f"(language={language}, version={tokenizer_version}, hash_algorithm={hash_algorithm})"
+        )
+        return deleted
+
+    def count(self) -> int:
+        with self._env.begin(write=False) as txn:
+            return txn.stat(self._db)["entries"]
+
+    def size_bytes(self) -> int:
+        with self._env.begin(write=False) as txn:
+            stat = txn.stat(self._db)
+        return stat["psize"] * (stat["branch_pages"] + stat["leaf_pages"] + stat["overflow_pages"])
+
+    def close(self) -> None:
+        try:
+            self._env.close()
+        except Exception as e:
+            logger.error(f"Error closing fingerprint store: {e}")
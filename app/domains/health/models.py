@@ -1,13 +1,13 @@
-from datetime import datetime
-
 from sqlmodel import SQLModel
 
+from app.shared.timestamps import UtcTimestamp
+
 
 class HealthCheck(SQLModel):
     """Health check response model"""
 
     status: str
-    timestamp: datetime
+    timestamp: UtcTimestamp
     version: str
     database_status: str
 
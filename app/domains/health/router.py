@@ -1,23 +1,14 @@
 import time
-from datetime import datetime
 
 import psutil
-import pytz
 from fastapi import APIRouter, Depends, HTTPException
 from sqlmodel import Session, text
 
 from app.config.config import get_settings
 from app.domains.health.models import DatabaseHealth, HealthCheck, ServiceHealth
 from app.shared.database import get_session
-
-# Paris timezone
-PARIS_TZ = pytz.timezone("Europe/Paris")
-
-
-def get_paris_time() -> datetime:
-    """Get current time in Paris timezone"""
-    return datetime.now(PARIS_TZ)
-
+from app.shared.shutdown import SHUTDOWN
+from app.shared.timestamps import utc_now
 
 router = APIRouter(prefix="/health", tags=["health"])
 settings = get_settings()
@@ -40,7 +31,7 @@ async def health_check(session: Session = Depends(get_session)):
 
     return HealthCheck(
         status="healthy" if database_status == "healthy" else "degraded",
-        timestamp=get_paris_time(),
+        timestamp=utc_now(),
         version=settings.app_version,
         database_status=database_status,
     )
@@ -81,12 +72,14 @@ async def detailed_health_check(session: Session = Depends(get_session)):
 @router.get("/readiness")
 async def readiness_check(session: Session = Depends(get_session)):
     """
-    Readiness check for Kubernetes/container orchestration
+    Readiness check for Kubernetes/container orchestration, failing once the instance drains for a shutdown
     """
+    if SHUTDOWN.draining:
+        raise HTTPException(status_code=503, detail="Service not ready: shutting down")
     try:
         # Test if we can execute a simple query
         session.exec(text("SELECT 1")).first()
-        return {"status": "ready", "timestamp": get_paris_time()}
+        return {"status": "ready", "timestamp": utc_now()}
     except Exception as e:
         raise HTTPException(status_code=503, detail=f"Service not ready: {str(e)}")
 
@@ -96,7 +89,7 @@ async def liveness_check():
     """
     Liveness check for Kubernetes/container orchestration
     """
-    return {"status": "alive", "timestamp": get_paris_time()}
+    return {"status": "alive", "timestamp": utc_now()}
 
 
 @router.get("/database")
@@ -127,7 +120,7 @@ async def database_health(session: Session = Depends(get_session)):
             "connection_time_ms": connection_time,
             "basic_query_success": basic_result == 1,
             "required_tables_exist": table_result == 2,
-            "timestamp": get_paris_time(),
+            "timestamp": utc_now(),
         }
     except Exception as e:
-        return {"status": "unhealthy", "error": str(e), "timestamp": get_paris_time()}
+        return {"status": "unhealthy", "error": str(e), "timestamp": utc_now()}
@@ -0,0 +1 @@
+# Messaging domain package
@@ -0,0 +1,298 @@
+"""
+Detection requests consumed from a RabbitMQ queue
+
+The PAMP platform may enqueue detection requests instead of calling POST /submissions. Each message is validated
+against the same DTO as the HTTP handler and runs through the same submission pipeline, its detection run queued on
+the detection scheduler. A message is acknowledged once its submission is created, requeued when it failed for a
+reason that may pass, like the database being down, and moved to the dead-letter queue when it is malformed, refused
+like an HTTP 4xx, or failed max_attempts times.
+
+Deliveries are counted by the broker in the x-delivery-count header of quorum queues, the request queue is declared
+as one. Messages are handled on a pool of prefetch_count workers while the consumer thread keeps the connection,
+and its heartbeats, alive; workers settle their delivery through the connection thread as pika is not thread safe.
+"""
+
+import logging
+import threading
+from concurrent.futures import ThreadPoolExecutor
+from dataclasses import dataclass
+from enum import Enum
+from typing import Any, Callable, Mapping, Optional
+
+from fastapi import HTTPException
+from pydantic import ValidationError
+
+try:
+    import pika
+except ImportError:
+    pika = None
+
+from app.config.config import Settings
+from app.domains.messaging.dto.detection_request_dto import DetectionRequestDto
+from app.shared.tracing import new_request_id, span
+
+logger = logging.getLogger(__name__)
+
+DELIVERY_COUNT_HEADER = "x-delivery-count"
+DEAD_LETTER_REASON_HEADER = "x-dead-letter-reason"
+ORIGINAL_QUEUE_HEADER = "x-original-queue"
+
+
+class DeliveryOutcome(str, Enum):
+    """What becomes of a delivered message"""
+
+    ACK = "ack"
+    REQUEUE = "requeue"
+    DEAD_LETTER = "dead_letter"
+
+
+@dataclass(frozen=True)
+class Disposition:
+    """Outcome of a delivery, with the reason of dead-lettered ones"""
+
+    outcome: DeliveryOutcome
+    reason: Optional[str] = None
+
+
+class DetectionRequestHandler:
+    """Runs detection request messages through the submission pipeline and decides the outcome of each delivery"""
+
+    def __init__(self, max_attempts: int = 3, session_factory=None):
+        self.max_attempts = max(max_attempts, 1)
+        self._session_factory = session_factory
+
+    def _new_session(self):
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from sqlmodel import Session
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    def create_submission(self, request: DetectionRequestDto):
+        """Create the submission of a request and queue its detection run, like POST /submissions"""
+        from app.domains.submissions.submissions_service import SubmissionService
+
+        with self._new_session() as session:
+            return SubmissionService(session).create_submission(
+                submission_data=request.submission,
+                allow_duplicates=request.allow_duplicates,
+                profile=request.profile,
+                priority=request.priority,
+            )
+
+    def handle(self, body: bytes, attempt: int = 1) -> Disposition:
+        """
+        Handle one delivery of a message
+
+        Args:
+            body: JSON body of the message
+            attempt: Number of this delivery, from 1
+        """
+        try:
+            request = DetectionRequestDto.model_validate_json(body)
+        except ValidationError as e:
+            logger.warning(f"Malformed detection request: {e.error_count()} errors")
+            return Disposition(DeliveryOutcome.DEAD_LETTER, f"Malformed message: {e}")
+
+        try:
+            response = self.create_submission(request)
+        except HTTPException as e:
+            if e.status_code < 500:
+                logger.warning(f"Detection request refused: {e.detail}")
+                return Disposition(DeliveryOutcome.DEAD_LETTER, f"Refused with status {e.status_code}: {e.detail}")
+            return self._failed(attempt, str(e.detail))
+        except Exception as e:
+            return self._failed(attempt, f"{type(e).__name__}: {str(e)}")
+
+        logger.info(f"Detection request created submission {response.submission_id}")
+        return Disposition(DeliveryOutcome.ACK)
+
+    def _failed(self, attempt: int, error: str) -> Disposition:
+        if attempt >= self.max_attempts:
+            logger.error(f"Detection request failed {attempt} times, dead-lettering it: {error}")
+            return Disposition(DeliveryOutcome.DEAD_LETTER, f"Failed after {attempt} attempts: {error}")
+        logger.warning(f"Detection request failed on attempt {attempt} of {self.max_attempts}, requeued: {error}")
+        return Disposition(DeliveryOutcome.REQUEUE)
+
+
+def delivery_attempt(headers: Optional[Mapping[str, Any]]) -> int:
+    """Number of a delivery from the broker count of the previous ones, 1 when the queue does not count them"""
+    return int((headers or {}).get(DELIVERY_COUNT_HEADER) or 0) + 1
+
+
+class DetectionRequestConsumer:
+    """
+    Background thread consuming a queue of detection requests
+
+    The thread reconnects after broker failures. Stopping cancels the consumer first, so no new message is started
+    and the prefetched ones are requeued by the broker, then waits for the messages being handled to be settled.
+    """
+
+    def __init__(
+        self,
+        url: str,
+        queue: str,
+        dead_letter_queue: str,
+        handler: DetectionRequestHandler,
+        prefetch_count: int = 4,
+        declare_queues: bool = True,
+        reconnect_delay_seconds: float = 5.0,
+        connect: Optional[Callable[[], Any]] = None,
+    ):
+        self.url = url
+        self.queue = queue
+        self.dead_letter_queue = dead_letter_queue
+        self.handler = handler
+        self.prefetch_count = max(prefetch_count, 1)
+        self.declare_queues = declare_queues
+        self.reconnect_delay_seconds = reconnect_delay_seconds
+        self._connect = connect or self._connect_to_broker
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+        self._in_flight = 0
+        self._in_flight_lock = threading.Lock()
+        self.consuming = threading.Event()
+
+    def _connect_to_broker(self):
+        if pika is None:
+            raise RuntimeError("pika is required to consume detection requests from RabbitMQ")
+        return pika.BlockingConnection(pika.URLParameters(self.url))
+
+    def _setup(self, channel) -> None:
+        if self.declare_queues:
+            channel.queue_declare(self.dead_letter_queue, durable=True)
+            channel.queue_declare(self.queue, durable=True, arguments={"x-queue-type": "quorum"})
+        # Dead letters are only acknowledged once the broker confirmed their publication
+        channel.confirm_delivery()
+        channel.basic_qos(prefetch_count=self.prefetch_count)
+
+    def _handle(self, connection, channel, method, properties, body: bytes) -> None:
+        headers = getattr(properties, "headers", None)
+        incoming_id = getattr(properties, "correlation_id", None) or getattr(properties, "message_id", None)
+        with span(request_id=new_request_id(incoming_id)):
+            try:
+                disposition = self.handler.handle(body, delivery_attempt(headers))
+            except Exception as e:
+                logger.error(f"Detection request handler failed: {str(e)}")
+                disposition = Disposition(DeliveryOutcome.REQUEUE)
+        try:
+            connection.add_callback_threadsafe(lambda: self._settle(channel, method, properties, body, disposition))
+        except Exception as e:
+            logger.warning(f"Connection lost before settling detection request, it will be redelivered: {str(e)}")
+            with self._in_flight_lock:
+                self._in_flight -= 1
+
+    def _settle(self, channel, method, properties, body: bytes, disposition: Disposition) -> None:
+        try:
+            if disposition.outcome == DeliveryOutcome.ACK:
+                channel.basic_ack(method.delivery_tag)
+            elif disposition.outcome == DeliveryOutcome.REQUEUE:
+                channel.basic_nack(method.delivery_tag, requeue=True)
+            else:
+                self._dead_letter(channel, method, properties, body, disposition.reason)
+        except Exception as e:
+            # The broker redelivers deliveries left unsettled by a lost channel
+            logger.error(f"Failed to settle detection request {method.delivery_tag}: {str(e)}")
+        finally:
+            with self._in_flight_lock:
+                self._in_flight -= 1
+
+    def _dead_letter(self, channel, method, properties, body: bytes, reason: Optional[str]) -> None:
+        headers = {
+            **(getattr(properties, "headers", None) or {}),
+            DEAD_LETTER_REASON_HEADER: reason,
+            ORIGINAL_QUEUE_HEADER: self.queue,
+        }
+        try:
+            channel.basic_publish(
+                "",
+                self.dead_letter_queue,
+                body,
+                properties=self._properties(properties, headers),
+                mandatory=True,
+            )
+        except Exception as e:
+            logger.error(f"Failed to dead-letter detection request, requeued: {str(e)}")
+            channel.basic_nack(method.delivery_tag, requeue=True)
+            return
+        channel.basic_ack(method.delivery_tag)
+        logger.warning(f"Detection request moved to {self.dead_letter_queue}: {reason}")
+
+    def _properties(self, properties, headers: Mapping[str, Any]):
+        values = {
+            name: getattr(properties, name, None)
+            for name in ("content_type", "content_encoding", "correlation_id", "message_id", "timestamp")
+        }
+        return pika.BasicProperties(headers=dict(headers), delivery_mode=2, **values)
+
+    def _consume(self, connection, executor: ThreadPoolExecutor) -> None:
+        channel = connection.channel()
+        self._setup(channel)
+
+        def on_message(channel, method, properties, body):
+            with self._in_flight_lock:
+                self._in_flight += 1
+            executor.submit(self._handle, connection, channel, method, properties, body)
+
+        consumer_tag = channel.basic_consume(self.queue, on_message_callback=on_message)
+        self.consuming.set()
+        logger.info(f"Consuming detection requests from {self.queue}")
+        try:
+            while not self._stop_event.is_set():
+                connection.process_data_events(time_limit=1)
+        finally:
+            self.consuming.clear()
+
+        # Stop consuming before draining: prefetched messages not started yet go back to the queue
+        channel.basic_cancel(consumer_tag)
+        while True:
+            with self._in_flight_lock:
+                if self._in_flight <= 0:
+                    break
+            connection.process_data_events(time_limit=0.1)
+        connection.close()
+
+    def _run(self) -> None:
+        while not self._stop_event.is_set():
+            executor = ThreadPoolExecutor(max_workers=self.prefetch_count, thread_name_prefix="amqp-request")
+            try:
+                self._consume(self._connect(), executor)
+            except Exception as e:
+                logger.error(f"Detection request consumer failed, reconnecting: {str(e)}")
+                self._stop_event.wait(self.reconnect_delay_seconds)
+            finally:
+                # Deliveries of a lost connection are redelivered, their handlers can finish and settle nothing
+                executor.shutdown(wait=True)
+                with self._in_flight_lock:
+                    self._in_flight = 0
+
+    def start(self) -> None:
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="amqp-consumer", daemon=True)
+        self._thread.start()
+
+    def stop(self, timeout: Optional[float] = 60.0) -> None:
+        """Stop consuming, then wait up to timeout for the messages being handled to be settled"""
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+
+
+def create_detection_request_consumer(settings: Settings) -> Optional[DetectionRequestConsumer]:
+    """Build the detection request consumer from the settings, None when the AMQP consumer is disabled"""
+    if not settings.amqp_enabled:
+        return None
+    return DetectionRequestConsumer(
+        settings.amqp_url.get_secret_value(),
+        settings.amqp_queue,
+        settings.amqp_dead_letter_queue,
+        DetectionRequestHandler(settings.amqp_max_attempts),
+        prefetch_count=settings.amqp_prefetch_count,
+        declare_queues=settings.amqp_declare_queues,
+    )
@@ -0,0 +1,3 @@
+from .detection_request_dto import DetectionRequestDto
+
+__all__ = ["DetectionRequestDto"]
@@ -0,0 +1,28 @@
+from pydantic import BaseModel, ConfigDict, Field
+
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.shared.concurrency import JobPriority
+
+
+class DetectionRequestDto(BaseModel):
+    """DTO for a detection request message, the body and query parameters of POST /submissions"""
+
+    model_config = ConfigDict(
+        extra="forbid",
+        json_schema_extra={
+            "example": {
+                "submission": {
+                    "link": "https://github.com/user/repository.git",
+                    "project_uuid": "550e8400-e29b-41d4-a716-446655440000",
+                    "group_uuid": "550e8400-e29b-41d4-a716-446655440001",
+                    "project_step_uuid": "550e8400-e29b-41d4-a716-446655440002",
+                },
+                "priority": "normal",
+            }
+        },
+    )
+
+    submission: CreateSubmissionDto
+    allow_duplicates: bool = Field(default=False, description="Allow duplicate submissions")
+    profile: bool = Field(default=False, description="Record per-stage timings of the detection run")
+    priority: JobPriority = Field(default=JobPriority.NORMAL, description="Priority of the detection run")
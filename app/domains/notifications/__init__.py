@@ -0,0 +1 @@
+# Notifications domain package
@@ -0,0 +1,7 @@
+from .object_ingestion_dto import NotificationResultDto, NotifiedObjectDto, ObjectIngestionDto
+
+__all__ = [
+    "ObjectIngestionDto",
+    "NotifiedObjectDto",
+    "NotificationResultDto",
+]
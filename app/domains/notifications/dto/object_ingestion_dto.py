@@ -0,0 +1,42 @@
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field
+
+from app.domains.notifications.notifications_models import ObjectIngestionStatus
+from app.shared.timestamps import UtcTimestamp
+
+
+class ObjectIngestionDto(BaseModel):
+    """DTO for an object version notified by a bucket and what became of it"""
+
+    model_config = ConfigDict(from_attributes=True, use_enum_values=True)
+
+    id: UUID
+    bucket: str
+    object_key: str
+    version: str
+    status: ObjectIngestionStatus
+    submission_id: Optional[UUID] = None
+    reason: Optional[str] = None
+    notifications: int
+    retries: Optional[List[dict]] = Field(
+        default=None, description="Attempts of fetches that failed transiently and were retried: client, host, error"
+    )
+    created_at: UtcTimestamp
+    updated_at: UtcTimestamp
+
+
+class NotifiedObjectDto(BaseModel):
+    """DTO for the outcome of an object of a notification"""
+
+    bucket: str
+    key: str
+    version: str
+    outcome: str = Field(description="ingested, duplicate, quarantined, refused or failed")
+
+
+class NotificationResultDto(BaseModel):
+    """DTO for the outcomes of the objects of a notification"""
+
+    objects: List[NotifiedObjectDto]
@@ -0,0 +1,66 @@
+"""
+Object Key Templates
+Map the key of an object dropped in a bucket to the submission it holds.
+
+A template is a key whose {placeholders} each match a non-empty part of one path segment. {project_uuid},
+{project_step_uuid} and {group_uuid} are required, {submitted_by_uuid} is optional, and any other name, like
+{filename} or {course}, matches without being kept. Text outside the placeholders must be in the key as written.
+"""
+
+import re
+from typing import Dict
+from uuid import UUID
+
+# Placeholders kept as fields of the submission, the first three required
+SUBMISSION_FIELDS = ("project_uuid", "project_step_uuid", "group_uuid", "submitted_by_uuid")
+REQUIRED_FIELDS = SUBMISSION_FIELDS[:3]
+
+_PLACEHOLDER = re.compile(r"\{([A-Za-z_][A-Za-z0-9_]*)\}")
+
+
+class KeyMismatch(ValueError):
+    """A key the template cannot map to a submission"""
+
+
+class KeyTemplate:
+    """Compiled key template"""
+
+    def __init__(self, template: str):
+        names = _PLACEHOLDER.findall(template)
+        missing = [name for name in REQUIRED_FIELDS if name not in names]
+        if missing:
+            raise ValueError(f"Key template '{template}' lacks {', '.join('{' + name + '}' for name in missing)}")
+        repeated = sorted({name for name in names if names.count(name) > 1})
+        if repeated:
+            raise ValueError(f"Key template '{template}' repeats {', '.join('{' + name + '}' for name in repeated)}")
+
+        pattern, position = "", 0
+        for match in _PLACEHOLDER.finditer(template):
+            pattern += re.escape(template[position : match.start()]) + f"(?P<{match.group(1)}>[^/]+)"
+            position = match.end()
+        pattern += re.escape(template[position:])
+
+        self.template = template
+        self._pattern = re.compile(pattern)
+
+    def parse(self, key: str) -> Dict[str, UUID]:
+        """
+        Submission fields of a key
+
+        Raises:
+            KeyMismatch: If the key does not match the template or a kept placeholder is not a UUID
+        """
+        match = self._pattern.fullmatch(key)
+        if match is None:
+            raise KeyMismatch(f"Key does not match the template {self.template}")
+
+        fields = {}
+        for name in SUBMISSION_FIELDS:
+            value = match.groupdict().get(name)
+            if value is None:
+                continue
+            try:
+                fields[name] = UUID(value)
+            except ValueError:
+                raise KeyMismatch(f"{{{name}}} of the key is not a UUID: {value}")
+        return fields
@@ -0,0 +1,182 @@
+"""
+Submissions ingested from the objects notified by a bucket
+
+Each object created in the bucket is mapped to its project, step and group by the key template, then runs through
+the submission pipeline like POST /submissions with its s3:// link, which downloads and ingests the archive.
+Ingestions are recorded per object version before the submission is created, so a notification of a version
+already known is a duplicate and creates nothing; a version whose ingestion failed for a reason that may pass, like
+the database being down, is forgotten so that the redelivery of its notification retries it. Keys that do not
+match the template are quarantined and listed by GET /notifications/s3/quarantine.
+"""
+
+import logging
+from datetime import timedelta
+from enum import Enum
+from typing import List, Optional, Tuple, Union
+
+from fastapi import HTTPException
+from pydantic import ValidationError
+
+from app.config.config import Settings
+from app.domains.notifications.key_template import KeyMismatch, KeyTemplate
+from app.domains.notifications.notifications_models import ObjectIngestion, ObjectIngestionStatus
+from app.domains.notifications.notifications_repository import ObjectIngestionRepository
+from app.domains.notifications.s3_notifications import ObjectCreated, parse_notification
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.shared.exceptions import DatabaseException
+from app.shared.metrics import NOTIFIED_OBJECTS
+from app.shared.retries import recorded_retries
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class IngestionOutcome(str, Enum):
+    """What became of a notified object"""
+
+    INGESTED = "ingested"
+    DUPLICATE = "duplicate"
+    QUARANTINED = "quarantined"
+    REFUSED = "refused"
+    FAILED = "failed"  # may pass, the notification should be delivered again
+
+
+class ObjectNotificationHandler:
+    """Creates the submissions of the objects of S3 event notifications, once per object version"""
+
+    def __init__(self, key_template: KeyTemplate, claim_timeout_seconds: float = 900.0, session_factory=None):
+        self.key_template = key_template
+        self.claim_timeout_seconds = claim_timeout_seconds
+        self._session_factory = session_factory
+
+    def _new_session(self):
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from sqlmodel import Session
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    def create_submission(self, submission: CreateSubmissionDto):
+        """Create the submission of an object and queue its detection run, like POST /submissions"""
+        from app.domains.submissions.submissions_service import SubmissionService
+
+        # Every version of an object is a new submission of the group, duplicate versions never get here
+        with self._new_session() as session:
+            return SubmissionService(session).create_submission(submission_data=submission, allow_duplicates=True)
+
+    def handle(self, body: Union[bytes, str, dict]) -> List[Tuple[ObjectCreated, IngestionOutcome]]:
+        """
+        Ingest the objects of a notification, returns them with their outcomes
+
+        Raises:
+            ValueError: If the body is not an S3 event notification
+        """
+        return [(created, self.ingest(created)) for created in parse_notification(body)]
+
+    def ingest(self, created: ObjectCreated) -> IngestionOutcome:
+        """Create the submission of a notified object version, unless a notification of it was already handled"""
+        outcome = self._ingest(created)
+        NOTIFIED_OBJECTS.labels(outcome.value).inc()
+        return outcome
+
+    def _ingest(self, created: ObjectCreated) -> IngestionOutcome:
+        with self._new_session() as session:
+            repository = ObjectIngestionRepository(session)
+            ingestion = self._claim(repository, created)
+            if ingestion is None:
+                return IngestionOutcome.DUPLICATE
+
+            try:
+                fields = self.key_template.parse(created.key)
+            except KeyMismatch as e:
+                logger.warning(f"Object {created.link} quarantined: {str(e)}")
+                return self._settle(repository, ingestion, ObjectIngestionStatus.QUARANTINED, str(e))
+
+            retries: List[dict] = []
+            try:
+                submission = CreateSubmissionDto(
+                    link=created.link,
+                    file_size_bytes=created.size,
+                    upload_date_time=created.event_time,
+                    **fields,
+                )
+                with recorded_retries() as retries:
+                    response = self.create_submission(submission)
+            except ValidationError as e:
+                logger.warning(f"Object {created.link} refused: {e.error_count()} invalid fields")
+                return self._settle(repository, ingestion, ObjectIngestionStatus.REFUSED, str(e))
+            except HTTPException as e:
+                ingestion.retries = retries or None
+                if e.status_code < 500:
+                    logger.warning(f"Object {created.link} refused with status {e.status_code}: {e.detail}")
+                    return self._settle(repository, ingestion, ObjectIngestionStatus.REFUSED, str(e.detail))
+                return self._failed(repository, ingestion, str(e.detail))
+            except Exception as e:
+                ingestion.retries = retries or None
+                return self._failed(repository, ingestion, f"{type(e).__name__}: {str(e)}")
+
+            ingestion.retries = retries or None
+            ingestion.submission_id = response.submission_id
+            self._settle(repository, ingestion, ObjectIngestionStatus.INGESTED)
+            logger.info(f"Object {created.link} version {created.version} created submission {response.submission_id}")
+            return IngestionOutcome.INGESTED
+
+    def _claim(self, repository: ObjectIngestionRepository, created: ObjectCreated) -> Optional[ObjectIngestion]:
+        """The ingestion of a new or abandoned object version, None for a duplicate notification"""
+        existing = repository.get_by_digest(created.digest)
+        if existing is None:
+            ingestion = ObjectIngestion(
+                object_digest=created.digest, bucket=created.bucket, object_key=created.key, version=created.version
+            )
+            if repository.claim(ingestion):
+                return ingestion
+            existing = repository.get_by_digest(created.digest)
+            if existing is None:
+                raise DatabaseException(f"Ingestion of {created.link} was released by a concurrent notification")
+
+        # A pending ingestion not settled in time was left by a stopped instance, the notification takes it over
+        abandoned_before = utc_now() - timedelta(seconds=self.claim_timeout_seconds)
+        existing.notifications += 1
+        if existing.status == ObjectIngestionStatus.PENDING and existing.updated_at < abandoned_before:
+            logger.warning(f"Ingestion of {created.link} version {created.version} was abandoned, retrying it")
+            return repository.save(existing)
+        repository.save(existing)
+        logger.info(f"Duplicate notification of {created.link} version {created.version} ignored")
+        return None
+
+    def _settle(
+        self,
+        repository: ObjectIngestionRepository,
+        ingestion: ObjectIngestion,
+        status: ObjectIngestionStatus,
+        reason: Optional[str] = None,
+    ) -> IngestionOutcome:
+        ingestion.status = status
+        ingestion.reason = reason[:2000] if reason else None
+        repository.save(ingestion)
+        return IngestionOutcome(status.value)
+
+    def _failed(
+        self, repository: ObjectIngestionRepository, ingestion: ObjectIngestion, error: str
+    ) -> IngestionOutcome:
+        retried = f" after {len(ingestion.retries)} retried fetches" if ingestion.retries else ""
+        logger.error(
+            f"Ingestion of s3://{ingestion.bucket}/{ingestion.object_key} failed{retried}, to be retried: {error}"
+        )
+        try:
+            repository.delete(ingestion)
+        except Exception as e:
+            # Left pending, the ingestion is taken over by a notification once the claim times out
+            logger.error(f"Failed to release the ingestion of s3://{ingestion.bucket}/{ingestion.object_key}: {e}")
+        return IngestionOutcome.FAILED
+
+
+def create_object_notification_handler(settings: Settings) -> ObjectNotificationHandler:
+    """Build the notification handler from the settings, raises ValueError for an invalid key template"""
+    return ObjectNotificationHandler(
+        KeyTemplate(settings.s3_notifications_key_template),
+        claim_timeout_seconds=settings.s3_notifications_claim_timeout_seconds,
+    )
@@ -0,0 +1,110 @@
+from typing import List, Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Body, Depends, Header, HTTPException, Query
+from sqlmodel import Session
+
+from app.config.config import get_settings
+from app.domains.notifications.dto.object_ingestion_dto import (
+    NotificationResultDto,
+    NotifiedObjectDto,
+    ObjectIngestionDto,
+)
+from app.domains.notifications.notification_handler import (
+    IngestionOutcome,
+    ObjectNotificationHandler,
+    create_object_notification_handler,
+)
+from app.domains.notifications.notifications_models import ObjectIngestionStatus
+from app.domains.notifications.notifications_service import NotificationService
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException
+from app.shared.security import WEBHOOK_SCOPE, require_admin_scope, require_bearer_token
+
+router = APIRouter(prefix="/notifications", tags=["notifications"])
+
+
+def get_notification_service(session: Session = Depends(get_session)) -> NotificationService:
+    """Dependency to get notification service"""
+    return NotificationService(session)
+
+
+def require_webhook_token(authorization: Optional[str] = Header(None)) -> None:
+    """Dependency rejecting webhook calls that do not carry the configured S3_NOTIFICATIONS_WEBHOOK_TOKEN"""
+    settings = get_settings()
+    token = settings.s3_notifications_webhook_token if settings.s3_notifications_enabled else None
+    require_bearer_token(token, WEBHOOK_SCOPE, "The S3 notification webhook is disabled", authorization)
+
+
+def get_notification_handler() -> ObjectNotificationHandler:
+    """Dependency to get the notification handler"""
+    try:
+        return create_object_notification_handler(get_settings())
+    except ValueError as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/s3", response_model=NotificationResultDto, dependencies=[Depends(require_webhook_token)])
+def receive_s3_notification(
+    notification: dict = Body(..., description="S3 event notification, as posted by a MinIO webhook"),
+    handler: ObjectNotificationHandler = Depends(get_notification_handler),
+):
+    """
+    Create the submissions of the objects of an S3 event notification, once per object version. Answers 503 when
+    an object failed for a reason that may pass, so that the notification is posted again
+    """
+    try:
+        results = handler.handle(notification)
+    except ValueError as e:
+        raise HTTPException(status_code=422, detail=str(e))
+
+    if any(outcome == IngestionOutcome.FAILED for _, outcome in results):
+        raise HTTPException(status_code=503, detail="Ingestion failed, post the notification again")
+    return NotificationResultDto(
+        objects=[
+            NotifiedObjectDto(bucket=created.bucket, key=created.key, version=created.version, outcome=outcome.value)
+            for created, outcome in results
+        ]
+    )
+
+
+@router.get("/s3/ingestions", response_model=List[ObjectIngestionDto], dependencies=[Depends(require_admin_scope)])
+async def list_ingestions(
+    status: ObjectIngestionStatus = Query(ObjectIngestionStatus.INGESTED, description="Status of the ingestions"),
+    skip: int = Query(0, ge=0, description="Number of objects to skip"),
+    limit: int = Query(100, ge=1, le=1000, description="Number of objects to return"),
+    service: NotificationService = Depends(get_notification_service),
+):
+    """Get the notified objects in a status, newest first, with the fetches retried while ingesting them"""
+    try:
+        return service.list_ingestions(status, skip, limit)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/s3/quarantine", response_model=List[ObjectIngestionDto], dependencies=[Depends(require_admin_scope)])
+async def list_quarantined_objects(
+    skip: int = Query(0, ge=0, description="Number of objects to skip"),
+    limit: int = Query(100, ge=1, le=1000, description="Number of objects to return"),
+    service: NotificationService = Depends(get_notification_service),
+):
+    """Get the notified objects whose key does not match S3_NOTIFICATIONS_KEY_TEMPLATE, newest first"""
+    try:
+        return service.list_quarantined(skip, limit)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.delete(
+    "/s3/quarantine/{ingestion_id}", response_model=ObjectIngestionDto, dependencies=[Depends(require_admin_scope)]
+)
+async def dismiss_quarantined_object(
+    ingestion_id: UUID, service: NotificationService = Depends(get_notification_service)
+):
+    """Remove an object from the quarantine, a later notification of the same version is then ingested again"""
+    try:
+        return service.dismiss_quarantined(ingestion_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e.detail))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
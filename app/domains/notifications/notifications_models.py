@@ -0,0 +1,42 @@
+from datetime import datetime
+from enum import Enum
+from typing import List, Optional
+from uuid import UUID, uuid4
+
+from sqlmodel import JSON, Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class ObjectIngestionStatus(str, Enum):
+    """Enumeration for the ingestion status of an object notified by a bucket"""
+
+    PENDING = "pending"  # its submission is being created
+    INGESTED = "ingested"
+    QUARANTINED = "quarantined"  # key not matching the template, listed for an operator
+    REFUSED = "refused"  # refused by the submission pipeline, like POST /submissions answering a 4xx
+
+
+class ObjectIngestion(SQLModel, table=True):
+    """Database model for an object version notified by a bucket, notifications of a known version are duplicates"""
+
+    __tablename__ = "object_ingestion"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    object_digest: str = Field(max_length=64, unique=True, description="SHA-256 of bucket, key and version")
+    bucket: str = Field(max_length=255, description="Bucket of the object")
+    object_key: str = Field(max_length=1024, description="Key of the object")
+    version: str = Field(max_length=1024, description="Version ID of the object, etag:<ETag> in unversioned buckets")
+
+    # Ingestion state
+    status: ObjectIngestionStatus = Field(
+        default=ObjectIngestionStatus.PENDING, index=True, description="Status of the ingestion"
+    )
+    submission_id: Optional[UUID] = Field(default=None, index=True, description="Submission created from it")
+    reason: Optional[str] = Field(default=None, description="Why it was quarantined or refused")
+    notifications: int = Field(default=1, description="Notifications received for this version, duplicates included")
+    retries: Optional[List[dict]] = Field(
+        default=None, sa_column=Column(JSON), description="Fetches retried while creating its submission"
+    )
+    created_at: datetime = Field(default_factory=utc_now, sa_column=Column(UtcDateTime(), nullable=False))
+    updated_at: datetime = Field(default_factory=utc_now, sa_column=Column(UtcDateTime(), nullable=False))
@@ -0,0 +1,79 @@
+from typing import List, Optional
+from uuid import UUID
+
+from sqlalchemy.exc import IntegrityError
+from sqlmodel import Session, select
+
+from app.domains.notifications.notifications_models import ObjectIngestion, ObjectIngestionStatus
+from app.shared.exceptions import DatabaseException
+from app.shared.timestamps import utc_now
+
+
+class ObjectIngestionRepository:
+    """Repository for the objects notified by buckets and what became of them"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def get(self, ingestion_id: UUID) -> Optional[ObjectIngestion]:
+        try:
+            return self.session.get(ObjectIngestion, ingestion_id)
+        except Exception as e:
+            raise DatabaseException(f"Failed to get object ingestion: {str(e)}")
+
+    def get_by_digest(self, object_digest: str) -> Optional[ObjectIngestion]:
+        try:
+            statement = select(ObjectIngestion).where(ObjectIngestion.object_digest == object_digest)
+            return self.session.exec(statement).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get object ingestion: {str(e)}")
+
+    def claim(self, ingestion: ObjectIngestion) -> bool:
+        """
+        Insert the ingestion of an object version, False when a concurrent notification of the same version already
+        inserted it
+        """
+        try:
+            self.session.add(ingestion)
+            self.session.commit()
+            self.session.refresh(ingestion)
+            return True
+        except IntegrityError:
+            self.session.rollback()
+            return False
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save object ingestion: {str(e)}")
+
+    def list_by_status(self, status: ObjectIngestionStatus, skip: int = 0, limit: int = 100) -> List[ObjectIngestion]:
+        """Ingestions in a status, newest first"""
+        try:
+            statement = (
+                select(ObjectIngestion)
+                .where(ObjectIngestion.status == status)
+                .order_by(ObjectIngestion.created_at.desc())
+                .offset(skip)
+                .limit(limit)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to list object ingestions: {str(e)}")
+
+    def save(self, ingestion: ObjectIngestion) -> ObjectIngestion:
+        try:
+            ingestion.updated_at = utc_now()
+            self.session.add(ingestion)
+            self.session.commit()
+            self.session.refresh(ingestion)
+            return ingestion
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save object ingestion: {str(e)}")
+
+    def delete(self, ingestion: ObjectIngestion) -> None:
+        try:
+            self.session.delete(ingestion)
+            self.session.commit()
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to delete object ingestion: {str(e)}")
@@ -0,0 +1,42 @@
+from typing import List
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.notifications.dto.object_ingestion_dto import ObjectIngestionDto
+from app.domains.notifications.notifications_models import ObjectIngestionStatus
+from app.domains.notifications.notifications_repository import ObjectIngestionRepository
+from app.shared.exceptions import NotFoundException
+
+
+class NotificationService:
+    """Service for the objects notified by buckets"""
+
+    def __init__(self, session: Session):
+        self.repository = ObjectIngestionRepository(session)
+
+    def list_ingestions(
+        self, status: ObjectIngestionStatus, skip: int = 0, limit: int = 100
+    ) -> List[ObjectIngestionDto]:
+        """Ingestions of notified objects in a status, newest first, with the fetches retried for them"""
+        ingestions = self.repository.list_by_status(status, skip, limit)
+        return [ObjectIngestionDto.model_validate(ingestion) for ingestion in ingestions]
+
+    def list_quarantined(self, skip: int = 0, limit: int = 100) -> List[ObjectIngestionDto]:
+        """Objects whose key does not match the template, newest first"""
+        ingestions = self.repository.list_by_status(ObjectIngestionStatus.QUARANTINED, skip, limit)
+        return [ObjectIngestionDto.model_validate(ingestion) for ingestion in ingestions]
+
+    def dismiss_quarantined(self, ingestion_id: UUID) -> ObjectIngestionDto:
+        """
+        Remove an object from the quarantine, a later notification of the same version is then ingested again
+
+        Raises:
+            NotFoundException: If no such object is quarantined
+        """
+        ingestion = self.repository.get(ingestion_id)
+        if ingestion is None or ingestion.status != ObjectIngestionStatus.QUARANTINED:
+            raise NotFoundException("Quarantined object", str(ingestion_id))
+        dismissed = ObjectIngestionDto.model_validate(ingestion)
+        self.repository.delete(ingestion)
+        return dismissed
@@ -0,0 +1,87 @@
+"""
+S3 Event Notifications
+Objects created in the event notifications of a bucket.
+
+The records are those of S3 event notifications, whether delivered to SQS directly, wrapped in an SNS notification
+or posted by a MinIO webhook, which uses the same records. Keys are URL-encoded in the records and decoded here.
+Test events, sent when a notification is configured, and events other than object creations give no object.
+"""
+
+import hashlib
+import json
+from dataclasses import dataclass
+from typing import List, Optional, Union
+from urllib.parse import unquote_plus
+
+
+@dataclass(frozen=True)
+class ObjectCreated:
+    """An object created in a bucket"""
+
+    bucket: str
+    key: str
+    version_id: Optional[str] = None  # None in unversioned buckets
+    etag: Optional[str] = None
+    size: Optional[int] = None
+    event_time: Optional[str] = None
+
+    @property
+    def version(self) -> str:
+        """Version the ingestion is idempotent on, the ETag of the content in unversioned buckets"""
+        return self.version_id or f"etag:{self.etag or ''}"
+
+    @property
+    def digest(self) -> str:
+        """SHA-256 of bucket, key and version, the same for every notification of this version"""
+        return hashlib.sha256("\0".join((self.bucket, self.key, self.version)).encode()).hexdigest()
+
+    @property
+    def link(self) -> str:
+        return f"s3://{self.bucket}/{self.key}"
+
+
+def parse_notification(body: Union[bytes, str, dict]) -> List[ObjectCreated]:
+    """
+    Objects created in the records of an S3 event notification
+
+    Raises:
+        ValueError: If the body is not an S3 event notification
+    """
+    document = body if isinstance(body, dict) else json.loads(body)
+    if isinstance(document, dict) and document.get("Type") == "Notification":
+        # Delivered through SNS, the notification is the message
+        document = json.loads(document.get("Message") or "null")
+    if not isinstance(document, dict):
+        raise ValueError("Not an S3 event notification")
+
+    records = document.get("Records")
+    if records is None:
+        if document.get("Event") == "s3:TestEvent":
+            return []
+        raise ValueError("Not an S3 event notification, it has no Records")
+    if not isinstance(records, list):
+        raise ValueError("Records of an S3 event notification must be a list")
+
+    objects = []
+    for record in records:
+        if not isinstance(record, dict):
+            raise ValueError("Record of an S3 event notification must be an object")
+        event_name = str(record.get("eventName") or "")
+        if not event_name.startswith(("ObjectCreated:", "s3:ObjectCreated:")):
+            continue
+        s3 = record.get("s3") or {}
+        bucket = (s3.get("bucket") or {}).get("name")
+        created = s3.get("object") or {}
+        if not bucket or not created.get("key"):
+            raise ValueError("Record of an S3 event notification has no bucket or key")
+        objects.append(
+            ObjectCreated(
+                bucket=bucket,
+                key=unquote_plus(created["key"]),
+                version_id=created.get("versionId") or None,
+                etag=created.get("eTag") or None,
+                size=created.get("size"),
+                event_time=record.get("eventTime"),
+            )
+        )
+    return objects
@@ -0,0 +1,119 @@
+import logging
+import threading
+from typing import Any, Mapping, Optional
+
+try:
+    import boto3
+except ImportError:
+    boto3 = None
+
+from app.config.config import Settings
+from app.domains.notifications.notification_handler import (
+    IngestionOutcome,
+    ObjectNotificationHandler,
+    create_object_notification_handler,
+)
+
+logger = logging.getLogger(__name__)
+
+
+class SqsNotificationPoller:
+    """
+    Background thread long polling an SQS queue of S3 event notifications
+
+    A message is deleted once each of its objects is ingested, quarantined, refused or a duplicate. A message with
+    an object that failed is left in the queue, SQS delivers it again once its visibility timeout expired and the
+    objects already ingested are then duplicates. Messages that are not S3 event notifications are deleted.
+    """
+
+    def __init__(
+        self,
+        queue_url: str,
+        handler: ObjectNotificationHandler,
+        wait_seconds: int = 20,
+        batch_size: int = 10,
+        error_delay_seconds: float = 5.0,
+        client=None,
+        region_name: Optional[str] = None,
+        endpoint_url: Optional[str] = None,
+    ):
+        self.queue_url = queue_url
+        self.handler = handler
+        self.wait_seconds = min(max(wait_seconds, 0), 20)
+        self.batch_size = min(max(batch_size, 1), 10)
+        self.error_delay_seconds = error_delay_seconds
+        self.region_name = region_name
+        self.endpoint_url = endpoint_url
+        self._client = client
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    @property
+    def client(self):
+        if self._client is None:
+            if boto3 is None:
+                raise RuntimeError("boto3 is required to poll S3 event notifications from SQS")
+            self._client = boto3.client("sqs", region_name=self.region_name, endpoint_url=self.endpoint_url)
+        return self._client
+
+    def handle_message(self, message: Mapping[str, Any]) -> bool:
+        """Ingest the objects of a message, returns whether it is done with and can be deleted"""
+        try:
+            results = self.handler.handle(message.get("Body") or "")
+        except ValueError as e:
+            logger.error(f"SQS message {message.get('MessageId')} is not an S3 event notification, deleted: {e}")
+            return True
+        return all(outcome != IngestionOutcome.FAILED for _, outcome in results)
+
+    def poll_once(self) -> int:
+        """Receive one batch of messages and handle them, returns how many were received"""
+        response = self.client.receive_message(
+            QueueUrl=self.queue_url, MaxNumberOfMessages=self.batch_size, WaitTimeSeconds=self.wait_seconds
+        )
+        messages = response.get("Messages") or []
+        for message in messages:
+            try:
+                done = self.handle_message(message)
+            except Exception as e:
+                logger.error(f"Failed to handle SQS message {message.get('MessageId')}, left for redelivery: {e}")
+                done = False
+            if done:
+                self.client.delete_message(QueueUrl=self.queue_url, ReceiptHandle=message["ReceiptHandle"])
+        return len(messages)
+
+    def _run(self) -> None:
+        while not self._stop_event.is_set():
+            try:
+                self.poll_once()
+            except Exception as e:
+                logger.error(f"SQS notification poller failed: {str(e)}")
+                self._stop_event.wait(self.error_delay_seconds)
+
+    def start(self) -> None:
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="sqs-notification-poller", daemon=True)
+        self._thread.start()
+        logger.info(f"Polling S3 event notifications from {self.queue_url}")
+
+    def stop(self, timeout: Optional[float] = None) -> None:
+        """Stop polling once the current receive returns, waiting at most its long polling wait by default"""
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(self.wait_seconds + 5 if timeout is None else timeout)
+            self._thread = None
+
+
+def create_sqs_notification_poller(settings: Settings) -> Optional[SqsNotificationPoller]:
+    """Build the SQS notification poller from the settings, None when notifications are disabled or have no queue"""
+    if not settings.s3_notifications_enabled or not settings.s3_notifications_sqs_queue_url:
+        return None
+    return SqsNotificationPoller(
+        settings.s3_notifications_sqs_queue_url,
+        create_object_notification_handler(settings),
+        wait_seconds=settings.s3_notifications_sqs_wait_seconds,
+        batch_size=settings.s3_notifications_sqs_batch_size,
+        region_name=settings.aws_default_region,
+        endpoint_url=settings.s3_notifications_sqs_endpoint_url,
+    )
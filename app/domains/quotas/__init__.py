@@ -0,0 +1 @@
+# Quotas domain package
@@ -0,0 +1,3 @@
+from .quota_dto import ProjectQuotaDto, QuotaUsageDto
+
+__all__ = ["ProjectQuotaDto", "QuotaUsageDto"]
@@ -0,0 +1,25 @@
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class QuotaUsageDto(BaseModel):
+    """DTO for the usage of a project against one of its quotas"""
+
+    quota: str
+    limit: int  # 0 for unlimited
+    usage: Optional[int] = None  # None for run_submissions, checked against each run when it starts
+    remaining: Optional[int] = None  # None when unlimited or not counted
+    exceeded: bool = False
+
+
+class ProjectQuotaDto(BaseModel):
+    """DTO for the usage of a project against each of its quotas"""
+
+    project_uuid: UUID
+    overridden: bool  # whether PROJECT_QUOTAS sets quotas of the project
+    quotas: List[QuotaUsageDto]
+    updated_at: Optional[UtcTimestamp] = None  # when stored bytes or submissions last changed
@@ -0,0 +1,26 @@
+from datetime import datetime
+from uuid import UUID
+
+from sqlalchemy import BigInteger
+from sqlmodel import Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class ProjectUsage(SQLModel, table=True):
+    """Database model for the usage counted against the quotas of a project, updated with each change it counts"""
+
+    __tablename__ = "project_usage"
+
+    project_uuid: UUID = Field(primary_key=True, description="UUID of the project")
+    submissions: int = Field(default=0, description="Submissions the project keeps")
+    stored_bytes: int = Field(
+        default=0,
+        sa_column=Column(BigInteger(), nullable=False, default=0),
+        description="Bytes of the stored files of its submissions",
+    )
+    updated_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the usage last changed",
+    )
@@ -0,0 +1,89 @@
+from datetime import datetime
+from typing import Dict, List, Optional
+from uuid import UUID
+
+from sqlalchemy import func, update
+from sqlalchemy.exc import IntegrityError
+from sqlmodel import Session, select
+
+from app.domains.quotas.quota_models import ProjectUsage
+from app.domains.runs.runs_models import DetectionRun
+from app.shared.exceptions import DatabaseException
+from app.shared.timestamps import utc_now
+
+
+def record_usage(session: Session, project_uuid: UUID, submissions: int = 0, stored_bytes: int = 0) -> None:
+    """
+    Add to the usage of a project in the transaction of the session, committed with the change it counts
+
+    Counters are incremented in the database, so concurrent changes of a project add up; the first change of a
+    project inserts its row in a savepoint, one inserted concurrently is incremented instead.
+    """
+    if not submissions and not stored_bytes:
+        return
+
+    def increment() -> int:
+        return session.execute(
+            update(ProjectUsage)
+            .where(ProjectUsage.project_uuid == project_uuid)
+            .values(
+                submissions=ProjectUsage.submissions + submissions,
+                stored_bytes=ProjectUsage.stored_bytes + stored_bytes,
+                updated_at=utc_now(),
+            )
+        ).rowcount
+
+    if increment():
+        return
+    try:
+        with session.begin_nested():
+            session.add(
+                ProjectUsage(
+                    project_uuid=project_uuid, submissions=max(submissions, 0), stored_bytes=max(stored_bytes, 0)
+                )
+            )
+    except IntegrityError:
+        increment()
+
+
+class QuotaRepository:
+    """Repository for the usage of projects counted against their quotas"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def get_usage(self, project_uuid: UUID) -> Optional[ProjectUsage]:
+        """Usage of a project, None when nothing of it was ever counted"""
+        try:
+            return self.session.get(ProjectUsage, project_uuid)
+        except Exception as e:
+            raise DatabaseException(f"Failed to get project usage: {str(e)}")
+
+    def list_usage(self) -> List[ProjectUsage]:
+        """Usage of every project counted"""
+        try:
+            return list(self.session.exec(select(ProjectUsage).order_by(ProjectUsage.project_uuid)).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to list project usage: {str(e)}")
+
+    def count_runs_since(self, project_uuid: UUID, since: datetime) -> int:
+        """Detection runs of a project started since a time"""
+        try:
+            statement = select(func.count(DetectionRun.id)).where(
+                DetectionRun.project_uuid == project_uuid, DetectionRun.started_at >= since
+            )
+            return self.session.exec(statement).one()
+        except Exception as e:
+            raise DatabaseException(f"Failed to count detection runs: {str(e)}")
+
+    def count_runs_by_project_since(self, since: datetime) -> Dict[str, int]:
+        """Detection runs started since a time, by project"""
+        try:
+            statement = (
+                select(DetectionRun.project_uuid, func.count(DetectionRun.id))
+                .where(DetectionRun.started_at >= since)
+                .group_by(DetectionRun.project_uuid)
+            )
+            return {str(project_uuid): count for project_uuid, count in self.session.exec(statement).all()}
+        except Exception as e:
+            raise DatabaseException(f"Failed to count detection runs by project: {str(e)}")
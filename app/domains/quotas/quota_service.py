@@ -0,0 +1,115 @@
+import logging
+from datetime import timedelta
+from typing import Dict, List, Optional
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.quotas.dto.quota_dto import ProjectQuotaDto, QuotaUsageDto
+from app.domains.quotas.quota_models import ProjectUsage
+from app.domains.quotas.quota_repository import QuotaRepository
+from app.domains.quotas.quotas import (
+    QUOTA_NAMES,
+    RUN_SUBMISSIONS,
+    RUNS_PER_DAY,
+    STORED_BYTES,
+    SUBMISSIONS,
+    exceeds,
+    parse_project_quotas,
+    project_quotas,
+)
+from app.shared.exceptions import QuotaExceededException
+from app.shared.metrics import QUOTA_REJECTIONS
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+RUN_RATE_WINDOW = timedelta(days=1)
+
+
+class QuotaService:
+    """Service checking requests against the quotas of their project and reporting usage"""
+
+    def __init__(self, session: Session):
+        self.repository = QuotaRepository(session)
+
+    def check_submission(self, project_uuid: UUID, file_size_bytes: Optional[int] = None) -> None:
+        """
+        Check that a project can keep one more submission of a size, at least one byte when it is not declared
+
+        Raises:
+            QuotaExceededException: 403 if the submissions or stored bytes quota of the project would be exceeded
+        """
+        quotas = project_quotas(project_uuid)
+        if not quotas.submissions and not quotas.stored_bytes:
+            return
+        usage = self.repository.get_usage(project_uuid) or ProjectUsage(project_uuid=project_uuid)
+        self._check(project_uuid, SUBMISSIONS, quotas.submissions, usage.submissions, 1)
+        self._check(project_uuid, STORED_BYTES, quotas.stored_bytes, usage.stored_bytes, max(file_size_bytes or 0, 1))
+
+    def check_run(self, project_uuid: UUID, run_submissions: int) -> None:
+        """
+        Check that a project can start a detection run comparing a number of submissions, the submission included
+
+        Raises:
+            QuotaExceededException: 403 if the run compares more submissions than allowed, 429 if the project
+                started its quota of runs over the last 24 hours
+        """
+        quotas = project_quotas(project_uuid)
+        self._check(project_uuid, RUN_SUBMISSIONS, quotas.run_submissions, 0, run_submissions)
+        if quotas.runs_per_day:
+            started = self.repository.count_runs_since(project_uuid, utc_now() - RUN_RATE_WINDOW)
+            self._check(project_uuid, RUNS_PER_DAY, quotas.runs_per_day, started, 1, rate=True)
+
+    def get_project_quota(self, project_uuid: UUID) -> ProjectQuotaDto:
+        """Usage of a project against each of its quotas"""
+        runs = self.repository.count_runs_since(project_uuid, utc_now() - RUN_RATE_WINDOW)
+        return self._project_quota(project_uuid, self.repository.get_usage(project_uuid), runs)
+
+    def list_project_quotas(self) -> List[ProjectQuotaDto]:
+        """Usage against their quotas of the projects with counted usage, recent runs or overridden quotas"""
+        from app.config.config import get_settings
+
+        usage = {str(row.project_uuid): row for row in self.repository.list_usage()}
+        runs = self.repository.count_runs_by_project_since(utc_now() - RUN_RATE_WINDOW)
+        overridden = parse_project_quotas(get_settings().project_quotas)
+        return [
+            self._project_quota(UUID(project_uuid), usage.get(project_uuid), runs.get(project_uuid, 0))
+            for project_uuid in sorted(set(usage) | set(runs) | set(overridden))
+        ]
+
+    def _project_quota(self, project_uuid: UUID, usage: Optional[ProjectUsage], runs: int) -> ProjectQuotaDto:
+        from app.config.config import get_settings
+
+        quotas = project_quotas(project_uuid)
+        used: Dict[str, Optional[int]] = {
+            STORED_BYTES: usage.stored_bytes if usage else 0,
+            SUBMISSIONS: usage.submissions if usage else 0,
+            RUNS_PER_DAY: runs,
+            RUN_SUBMISSIONS: None,
+        }
+        rows = []
+        for name in QUOTA_NAMES:
+            limit, current = quotas.limit(name), used[name]
+            counted = limit > 0 and current is not None
+            rows.append(
+                QuotaUsageDto(
+                    quota=name,
+                    limit=limit,
+                    usage=current,
+                    remaining=max(limit - current, 0) if counted else None,
+                    exceeded=counted and exceeds(limit, current, 0),
+                )
+            )
+        return ProjectQuotaDto(
+            project_uuid=project_uuid,
+            overridden=str(project_uuid) in parse_project_quotas(get_settings().project_quotas),
+            quotas=rows,
+            updated_at=usage.updated_at if usage else None,
+        )
+
+    def _check(self, project_uuid, quota: str, limit: int, usage: int, requested: int, rate: bool = False) -> None:
+        if exceeds(limit, usage, requested):
+            QUOTA_REJECTIONS.labels(quota).inc()
+            logger.warning(f"Quota {quota} of project {project_uuid} exceeded: {usage} used of {limit}")
+            raise QuotaExceededException(quota, project_uuid, limit, usage, requested, rate=rate)
@@ -0,0 +1,90 @@
+"""
+Per-project quotas
+
+Each project is limited in the bytes of its stored submission files, the submissions it keeps, the detection runs
+it starts over the last 24 hours and the submissions a run compares. The limits are the QUOTA_MAX_<NAME> settings,
+0 for unlimited, and PROJECT_QUOTAS overrides them for some projects with a JSON object keyed by project UUID:
+
+    {"550e8400-e29b-41d4-a716-446655440000": {"submissions": 500, "runs_per_day": 0}}
+
+Stored bytes and submissions are counted in project_usage in the transaction of each ingestion and deletion, runs
+are counted from the detection runs themselves.
+"""
+
+import json
+from dataclasses import asdict, dataclass
+from typing import Dict
+from uuid import UUID
+
+STORED_BYTES = "stored_bytes"
+SUBMISSIONS = "submissions"
+RUNS_PER_DAY = "runs_per_day"
+RUN_SUBMISSIONS = "run_submissions"
+
+QUOTA_NAMES = (STORED_BYTES, SUBMISSIONS, RUNS_PER_DAY, RUN_SUBMISSIONS)
+
+
+@dataclass(frozen=True)
+class ProjectQuotas:
+    """Limits of a project by quota name, 0 for unlimited"""
+
+    stored_bytes: int = 0
+    submissions: int = 0
+    runs_per_day: int = 0
+    run_submissions: int = 0
+
+    def limit(self, name: str) -> int:
+        return getattr(self, name)
+
+    def to_dict(self) -> Dict[str, int]:
+        return asdict(self)
+
+
+def parse_project_quotas(value: str) -> Dict[str, Dict[str, int]]:
+    """
+    Overrides of PROJECT_QUOTAS by project UUID in its canonical form
+
+    Raises:
+        ValueError: If the value is not such a JSON object, names an unknown quota or a negative limit
+    """
+    if not value or not value.strip():
+        return {}
+    try:
+        document = json.loads(value)
+    except json.JSONDecodeError as e:
+        raise ValueError(f"project_quotas is not valid JSON: {e}")
+    if not isinstance(document, dict):
+        raise ValueError("project_quotas must be a JSON object keyed by project UUID")
+
+    overrides = {}
+    for project, quotas in document.items():
+        try:
+            project_uuid = str(UUID(project))
+        except ValueError:
+            raise ValueError(f"project_quotas: {project} is not a project UUID")
+        if not isinstance(quotas, dict):
+            raise ValueError(f"project_quotas: the quotas of {project} must be an object")
+        for name, limit in quotas.items():
+            if name not in QUOTA_NAMES:
+                raise ValueError(f"project_quotas: unknown quota {name}, expected one of {', '.join(QUOTA_NAMES)}")
+            if isinstance(limit, bool) or not isinstance(limit, int) or limit < 0:
+                raise ValueError(f"project_quotas: {name} of {project} must be a non-negative integer")
+        overrides[project_uuid] = quotas
+    return overrides
+
+
+def project_quotas(project_uuid: UUID, settings=None) -> ProjectQuotas:
+    """Limits of a project, the configured ones with its overrides"""
+    if settings is None:
+        from app.config.config import get_settings
+
+        settings = get_settings()
+
+    defaults = {name: getattr(settings, f"quota_max_{name}") for name in QUOTA_NAMES}
+    overrides = parse_project_quotas(settings.project_quotas).get(str(project_uuid), {})
+    return ProjectQuotas(**{**defaults, **overrides})
+
+
+def exceeds(limit: int, usage: int, requested: int) -> bool:
+    """Whether requesting more on top of a usage goes over a limit, never with 0 for unlimited"""
+    return limit > 0 and usage + requested > limit
@@ -0,0 +1,95 @@
+"""
+Pseudonyms of the identities of a detection run for anonymized reports
+
+Submitters become "Student 017", submissions "Submission 003" and groups "Group 002". Numbers are assigned in the
+order of a digest of the run and the identifier, so the pseudonyms of a run are the same in every report and export
+of it, while the numbering of two runs cannot be matched up. Anonymized documents replace every known identifier
+wherever it appears, blank the fields marked as PII and scrub, on a best-effort basis, the user names of home
+directories in paths and code ("/home/jdupont/..." becomes "/home/user/...").
+"""
+
+import hashlib
+import re
+from typing import Any, Dict, Iterable, List, Optional, Tuple
+
+from app.domains.reports.dto.report_dto import PseudonymDto, PseudonymKind
+
+# Fields of DTOs and documents blanked in anonymized outputs, they may hold names, links or free text
+PII_FIELDS = frozenset({"link", "description", "ip_address", "user_agent", "legal_hold_reason", "team_name"})
+SCRUBBED_USER = "user"
+UNKNOWN_PSEUDONYM = "Unknown"
+
+# User name of a home directory, /home/<name>, /Users/<name>, C:\Users\<name> or C:/Users/<name>, not inside a path
+_HOME_DIRECTORY = re.compile(r"(?i)(?<![\w.-])(/home/|/Users/|\b[A-Z]:[\\/]+Users[\\/]+)([^/\\\s\"'<>:;,&]+)")
+
+
+def scrub_home_directories(text: str) -> str:
+    """Replace the user name of home directories in a text"""
+    return _HOME_DIRECTORY.sub(lambda match: match.group(1) + SCRUBBED_USER, text)
+
+
+class RunPseudonyms:
+    """Pseudonyms of the submissions, students and groups of the participants of a run, team members are students"""
+
+    def __init__(self, run_id, participants: Iterable):
+        self.run_id = run_id
+        participants = list(participants)
+        self._identities: Dict[str, Tuple[PseudonymKind, str]] = {}
+        for kind, values in (
+            (
+                PseudonymKind.STUDENT,
+                [getattr(p, "submitted_by_uuid", None) for p in participants]
+                + [member for p in participants for member in getattr(p, "team_members", None) or []],
+            ),
+            (PseudonymKind.SUBMISSION, [p.submission_id for p in participants]),
+            (PseudonymKind.GROUP, [getattr(p, "group_uuid", None) for p in participants]),
+        ):
+            identifiers = sorted({str(v) for v in values if v is not None}, key=self._order)
+            width = max(3, len(str(len(identifiers))))
+            for number, identifier in enumerate(identifiers, 1):
+                # An identifier of several kinds keeps the pseudonym of the first one
+                self._identities.setdefault(identifier, (kind, f"{kind.label} {number:0{width}d}"))
+        self._pattern = (
+            re.compile("|".join(re.escape(i) for i in sorted(self._identities, key=len, reverse=True)))
+            if self._identities
+            else None
+        )
+
+    def _order(self, identifier: str) -> str:
+        return hashlib.sha256(f"{self.run_id}:{identifier}".encode("utf-8")).hexdigest()
+
+    def __call__(self, value) -> Optional[str]:
+        """Pseudonym of an identifier, None for None"""
+        if value is None:
+            return None
+        identity = self._identities.get(str(value))
+        return identity[1] if identity else UNKNOWN_PSEUDONYM
+
+    def scrub(self, text: str) -> str:
+        """Replace the known identifiers and the home directory user names in a text"""
+        if self._pattern is not None:
+            text = self._pattern.sub(lambda match: self._identities[match.group(0)][1], text)
+        return scrub_home_directories(text)
+
+    def anonymize(self, document: Any) -> Any:
+        """Copy of a JSON-compatible document with its strings scrubbed and its PII fields blanked"""
+        if isinstance(document, dict):
+            return {
+                self.scrub(key) if isinstance(key, str) else key: None if key in PII_FIELDS else self.anonymize(value)
+                for key, value in document.items()
+            }
+        if isinstance(document, list):
+            return [self.anonymize(value) for value in document]
+        if isinstance(document, str):
+            return self.scrub(document)
+        return document
+
+    def mapping(self) -> List[PseudonymDto]:
+        """Identity behind each pseudonym, by kind then number"""
+        return sorted(
+            (
+                PseudonymDto(pseudonym=pseudonym, kind=kind, identifier=identifier)
+                for identifier, (kind, pseudonym) in self._identities.items()
+            ),
+            key=lambda dto: (list(PseudonymKind).index(dto.kind), dto.pseudonym),
+        )
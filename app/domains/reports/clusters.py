@@ -0,0 +1,82 @@
+"""
+Clusters of submissions linked by flagged pairs
+
+A cluster is a connected component of the graph whose nodes are submissions and whose edges are the pairs at or
+above the flagging threshold: two submissions are in the same cluster when a chain of flagged pairs links them,
+even if they were not flagged together.
+"""
+
+from dataclasses import dataclass
+from typing import Dict, Iterable, List
+
+
+@dataclass
+class Cluster:
+    """Submissions of a cluster, sorted, with the number and highest similarity of the flagged pairs inside it"""
+
+    members: List[str]
+    pair_count: int
+    max_similarity: float
+
+
+class ClusterFinder:
+    """
+    Clusters of pairs added one at a time, holding only the submissions seen and the counters of each cluster
+
+    Args:
+        threshold: Similarity at or above which added pairs link their submissions
+    """
+
+    def __init__(self, threshold: float):
+        self.threshold = threshold
+        self._parent: Dict[str, str] = {}
+        # Number and highest similarity of the flagged pairs of each cluster, by root
+        self._pair_counts: Dict[str, int] = {}
+        self._max_similarities: Dict[str, float] = {}
+
+    def _root(self, node: str) -> str:
+        parent = self._parent
+        parent.setdefault(node, node)
+        while parent[node] != node:
+            parent[node] = parent[parent[node]]
+            node = parent[node]
+        return node
+
+    def add(self, pair) -> None:
+        """Add a pair with submission_id, compared_submission_id and overall_similarity, ignored below the threshold"""
+        if pair.overall_similarity < self.threshold:
+            return
+        first, second = self._root(str(pair.submission_id)), self._root(str(pair.compared_submission_id))
+        root = min(first, second)
+        if first != second:
+            other = max(first, second)
+            self._parent[other] = root
+            self._pair_counts[root] = self._pair_counts.get(root, 0) + self._pair_counts.pop(other, 0)
+            self._max_similarities[root] = max(
+                self._max_similarities.get(root, 0.0), self._max_similarities.pop(other, 0.0)
+            )
+        self._pair_counts[root] = self._pair_counts.get(root, 0) + 1
+        self._max_similarities[root] = max(self._max_similarities.get(root, 0.0), pair.overall_similarity)
+
+    def clusters(self) -> List[Cluster]:
+        """Clusters of the pairs added so far, largest first then by highest similarity, ties by first member"""
+        clusters: Dict[str, Cluster] = {}
+        for node in sorted(self._parent):
+            root = self._root(node)
+            if root not in clusters:
+                clusters[root] = Cluster([], self._pair_counts[root], self._max_similarities[root])
+            clusters[root].members.append(node)
+        return sorted(clusters.values(), key=lambda c: (-len(c.members), -c.max_similarity, c.members[0]))
+
+
+def find_clusters(pairs: Iterable, threshold: float) -> List[Cluster]:
+    """
+    Clusters of the pairs at or above a threshold, largest first then by highest similarity, ties by first member
+
+    Args:
+        pairs: Objects with submission_id, compared_submission_id and overall_similarity
+    """
+    finder = ClusterFinder(threshold)
+    for pair in pairs:
+        finder.add(pair)
+    return finder.clusters()
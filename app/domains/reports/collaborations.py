@@ -0,0 +1,77 @@
+"""
+Declared collaborations of a project step
+
+Some assignments allow students to share code, pair programming for instance. A step declares it as pairs of
+parties, "student:<uuid>", "team:<uuid>" or "group:<uuid>", every student of the group, with "*" instead of the
+UUID for any student, team or group and "*" alone for anyone. A pair of a run is a declared collaboration when one
+of its submissions matches a party of a declaration and the other the other party, so ["group:A", "group:A"] covers
+everyone in group A with everyone in group A. Such pairs are still compared and kept, but reported apart and left
+out of the statistics and summaries.
+
+Runs classify their pairs with the declarations their step had when they started, reclassifying a run replaces
+them with the current ones without comparing anything again.
+"""
+
+from typing import Dict, Iterable, List, Optional, Set, Tuple
+
+from app.domains.reports.run_stats import participant_members
+
+WILDCARD = "*"
+
+# (kind, identifier) of a party, (WILDCARD, WILDCARD) for anyone
+Party = Tuple[str, str]
+
+
+def parse_party(party: str) -> Party:
+    """Kind and identifier of a party as stored, see DeclaredCollaborationDto"""
+    if party == WILDCARD:
+        return WILDCARD, WILDCARD
+    kind, _, identifier = party.partition(":")
+    return kind, identifier
+
+
+def participant_identities(participant) -> Set[Party]:
+    """Parties a participating submission matches: its students, team and group, and the wildcards of each"""
+    identities = {(WILDCARD, WILDCARD)}
+    for kind, values in (
+        ("student", participant_members(participant)),
+        ("team", [getattr(participant, "team_id", None)]),
+        ("group", [getattr(participant, "group_uuid", None)]),
+    ):
+        for value in values:
+            if value is not None:
+                identities.update({(kind, str(value)), (kind, WILDCARD)})
+    return identities
+
+
+class DeclaredCollaborations:
+    """Classifier of the pairs of a run against declared collaborations, a pair matches when it is called with it"""
+
+    def __init__(self, declarations: Optional[Iterable[dict]], participants: Iterable):
+        self.declarations: List[Tuple[Party, Party]] = [
+            (parse_party(declaration["parties"][0]), parse_party(declaration["parties"][1]))
+            for declaration in declarations or []
+        ]
+        self._identities: Dict[str, Set[Party]] = (
+            {str(p.submission_id): participant_identities(p) for p in participants} if self.declarations else {}
+        )
+
+    def __bool__(self) -> bool:
+        return bool(self.declarations)
+
+    def _side(self, submission_id, submitted_by) -> Set[Party]:
+        identities = self._identities.get(str(submission_id), {(WILDCARD, WILDCARD)})
+        if submitted_by is not None:
+            identities = identities | {("student", str(submitted_by)), ("student", WILDCARD)}
+        return identities
+
+    def __call__(self, pair) -> bool:
+        """Whether a pair is a declared collaboration"""
+        if not self.declarations:
+            return False
+        first = self._side(pair.submission_id, pair.submitted_by_uuid)
+        second = self._side(pair.compared_submission_id, pair.compared_submitted_by_uuid)
+        return any(
+            (one in first and other in second) or (other in first and one in second)
+            for one, other in self.declarations
+        )
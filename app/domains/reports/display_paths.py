@@ -0,0 +1,35 @@
+"""
+Short display names of submission file paths
+
+Files are identified by their full path relative to the submission root everywhere: pairing, fragments, heatmaps
+and file content. Presentation only shortens them to their file name, prefixed with as many parent directories as
+needed to tell apart the paths shown together: `pkg/utils.py` and `tests/utils.py` rather than `utils.py` twice.
+"""
+
+from pathlib import PurePosixPath
+from typing import Dict, Iterable
+
+
+def short_paths(paths: Iterable[str]) -> Dict[str, str]:
+    """Shortest unique trailing part of each path, at least its file name, the full path when nothing shorter is"""
+    parts = {path: PurePosixPath(path).parts or (path,) for path in set(paths)}
+    names: Dict[str, str] = {}
+    depth = 1
+    pending = set(parts)
+    while pending:
+        suffixes: Dict[str, list] = {}
+        for path in pending:
+            suffixes.setdefault("/".join(parts[path][-depth:]), []).append(path)
+        for suffix, shared in suffixes.items():
+            if len(shared) == 1 or all(len(parts[path]) <= depth for path in shared):
+                for path in shared:
+                    names[path] = path if len(parts[path]) <= depth else suffix
+                    pending.discard(path)
+            else:
+                # Paths as long as the suffix cannot grow, the others need one more parent
+                for path in shared:
+                    if len(parts[path]) <= depth:
+                        names[path] = path
+                        pending.discard(path)
+        depth += 1
+    return names
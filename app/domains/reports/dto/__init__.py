@@ -0,0 +1,39 @@
+from .report_dto import (
+    ClusterStatsDto,
+    EvidenceDto,
+    FilePairDto,
+    GraphFormat,
+    HistogramBucketDto,
+    LanguageStatsDto,
+    PseudonymDto,
+    PseudonymKind,
+    PseudonymMappingDto,
+    ReportPendingDto,
+    RunStatsDto,
+    RunSummaryDto,
+    SnippetDto,
+    SubmissionMaxScoreDto,
+    SummaryMetric,
+    SuspiciousPairDto,
+    ThresholdCountDto,
+)
+
+__all__ = [
+    "GraphFormat",
+    "ReportPendingDto",
+    "HistogramBucketDto",
+    "ThresholdCountDto",
+    "ClusterStatsDto",
+    "LanguageStatsDto",
+    "SubmissionMaxScoreDto",
+    "RunStatsDto",
+    "SummaryMetric",
+    "FilePairDto",
+    "SnippetDto",
+    "EvidenceDto",
+    "SuspiciousPairDto",
+    "RunSummaryDto",
+    "PseudonymKind",
+    "PseudonymDto",
+    "PseudonymMappingDto",
+]
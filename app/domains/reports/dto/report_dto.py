@@ -0,0 +1,262 @@
+from enum import Enum
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class GraphFormat(str, Enum):
+    """Formats of the similarity graph export"""
+
+    DOT = "dot"
+    GRAPHML = "graphml"
+
+
+class SummaryMetric(str, Enum):
+    """Pair metrics the suspicious pairs summary can rank by"""
+
+    OVERALL_SIMILARITY = "overall_similarity"
+    STRUCTURAL_SIMILARITY = "structural_similarity"
+    JACCARD_SIMILARITY = "jaccard_similarity"
+    MATCHED_TOKENS = "matched_tokens"
+    FRAGMENTS_COUNT = "fragments_count"
+
+
+class PseudonymKind(str, Enum):
+    """Kinds of identities replaced in anonymized reports"""
+
+    STUDENT = "student"
+    SUBMISSION = "submission"
+    GROUP = "group"
+
+    @property
+    def label(self) -> str:
+        return self.value.capitalize()
+
+
+class ReportPendingDto(BaseModel):
+    """DTO answering a report request while the report is rendered in the background"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "run_id": "550e8400-e29b-41d4-a716-446655440010",
+                "status": "queued",
+                "position": 2,
+                "retry_after_seconds": 10,
+            }
+        }
+    )
+
+    run_id: UUID
+    status: str  # "queued" or "rendering"
+    position: Optional[int] = None  # position in the report queue while queued
+    retry_after_seconds: int
+
+
+class HistogramBucketDto(BaseModel):
+    """DTO for a bucket of the score histogram, lower bound included, upper bound excluded except for the last one"""
+
+    lower: float
+    upper: float
+    count: int
+
+
+class ThresholdCountDto(BaseModel):
+    """DTO for the number of pairs at or above a similarity"""
+
+    threshold: float
+    count: int
+
+
+class ClusterStatsDto(BaseModel):
+    """DTO for the clusters of the submissions linked by pairs at or above a similarity"""
+
+    threshold: float
+    count: int
+    sizes: List[int]  # largest first
+
+
+class LanguageStatsDto(BaseModel):
+    """DTO for the compared files of a language"""
+
+    language: str
+    label: Optional[str] = None  # name of the language in reports, e.g. C#
+    submissions: int
+    files: int
+    tokens: int
+
+
+class SubmissionMaxScoreDto(BaseModel):
+    """DTO for the highest similarity of a participating submission, self-matches excluded"""
+
+    submission_id: UUID
+    submitted_by_uuid: Optional[UUID] = None
+    max_similarity: Optional[float] = None  # None when no pair of the submission was compared
+    most_similar_submission_id: Optional[UUID] = None
+    pairs: int
+
+
+class RunStatsDto(BaseModel):
+    """DTO for the aggregated statistics of a run"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "run_id": "550e8400-e29b-41d4-a716-446655440010",
+                "computed_at": "2024-01-15T10:35:00+01:00",
+                "pair_count": 3,
+                "suppressed_pairs": 1,
+                "declared_collaboration_pairs": 0,
+                "histogram": [
+                    {"lower": 0.0, "upper": 0.5, "count": 1},
+                    {"lower": 0.5, "upper": 1.0, "count": 2},
+                ],
+                "thresholds": [{"threshold": 0.8, "count": 1}],
+                "clusters": {"threshold": 0.5, "count": 1, "sizes": [3]},
+                "languages": [{"language": "python", "label": "Python", "submissions": 3, "files": 7, "tokens": 2310}],
+                "submissions": [
+                    {
+                        "submission_id": "550e8400-e29b-41d4-a716-446655440020",
+                        "max_similarity": 0.91,
+                        "most_similar_submission_id": "550e8400-e29b-41d4-a716-446655440021",
+                        "pairs": 2,
+                    }
+                ],
+            }
+        }
+    )
+
+    run_id: UUID
+    computed_at: UtcTimestamp
+    pair_count: int  # completed pairs, self-matches and declared collaborations excluded
+    suppressed_pairs: int  # completed pairs of a submission with itself, its group or one of its students
+    declared_collaboration_pairs: int = 0  # completed pairs the declared collaborations of the run allow
+    histogram: List[HistogramBucketDto]
+    thresholds: List[ThresholdCountDto]
+    clusters: ClusterStatsDto
+    languages: List[LanguageStatsDto]  # most files first
+    submissions: List[SubmissionMaxScoreDto]  # highest max_similarity first
+
+
+class FilePairDto(BaseModel):
+    """DTO for the most similar file pair of a pair"""
+
+    file1_path: str
+    file2_path: str
+    similarity: float
+
+
+class SnippetDto(BaseModel):
+    """DTO for the source text of one side of a fragment"""
+
+    path: str
+    start_line: Optional[int] = None  # 1-based, None when the file is not available
+    end_line: Optional[int] = None  # 1-based and inclusive, the last line shown
+    text: Optional[str] = None
+    truncated: bool = False  # the fragment runs past end_line
+    available: bool = True  # False when the file is no longer stored
+
+
+class EvidenceDto(BaseModel):
+    """DTO for the longest matched fragment of a pair, with the source text of both sides"""
+
+    similarity: float
+    lines: int  # lines of the fragment, both sides added
+    file1_function: Optional[str] = None
+    file2_function: Optional[str] = None
+    left: SnippetDto
+    right: SnippetDto
+
+
+class SuspiciousPairDto(BaseModel):
+    """DTO for a pair of the suspicious pairs summary"""
+
+    rank: int
+    pair_id: UUID
+    submission_id: UUID
+    compared_submission_id: UUID
+    submitted_by_uuid: Optional[UUID] = None
+    compared_submitted_by_uuid: Optional[UUID] = None
+    score: Optional[float] = None  # value of the ranking metric
+    overall_similarity: float
+    best_file_pair: Optional[FilePairDto] = None
+    evidence: Optional[EvidenceDto] = None  # None when the pair has no located fragment
+
+
+class RunSummaryDto(BaseModel):
+    """DTO for the most suspicious pairs of a run"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "run_id": "550e8400-e29b-41d4-a716-446655440010",
+                "metric": "overall_similarity",
+                "min_similarity": 0.5,
+                "top": 10,
+                "flagged_pairs": 1,
+                "pairs": [
+                    {
+                        "rank": 1,
+                        "pair_id": "550e8400-e29b-41d4-a716-446655440030",
+                        "submission_id": "550e8400-e29b-41d4-a716-446655440020",
+                        "compared_submission_id": "550e8400-e29b-41d4-a716-446655440021",
+                        "score": 0.93,
+                        "overall_similarity": 0.93,
+                        "best_file_pair": {"file1_path": "main.py", "file2_path": "app.py", "similarity": 0.95},
+                        "evidence": {
+                            "similarity": 0.97,
+                            "lines": 8,
+                            "left": {"path": "main.py", "start_line": 3, "end_line": 6, "text": "def fib(n):\n..."},
+                            "right": {"path": "app.py", "start_line": 10, "end_line": 13, "text": "def f(x):\n..."},
+                        },
+                    }
+                ],
+            }
+        }
+    )
+
+    run_id: UUID
+    metric: SummaryMetric
+    min_similarity: float
+    top: int
+    flagged_pairs: int  # completed pairs at or above min_similarity, declared collaborations excluded
+    declared_collaboration_pairs: int = 0  # completed pairs at or above min_similarity the run allows
+    pairs: List[SuspiciousPairDto]  # at most top, highest score first
+
+
+class PseudonymDto(BaseModel):
+    """DTO for the identity behind a pseudonym of anonymized reports"""
+
+    pseudonym: str
+    kind: PseudonymKind
+    identifier: str
+
+
+class PseudonymMappingDto(BaseModel):
+    """DTO for the pseudonyms of the anonymized reports of a run"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "run_id": "550e8400-e29b-41d4-a716-446655440010",
+                "pseudonyms": [
+                    {
+                        "pseudonym": "Student 001",
+                        "kind": "student",
+                        "identifier": "550e8400-e29b-41d4-a716-446655440040",
+                    },
+                    {
+                        "pseudonym": "Submission 001",
+                        "kind": "submission",
+                        "identifier": "550e8400-e29b-41d4-a716-446655440020",
+                    },
+                ],
+            }
+        }
+    )
+
+    run_id: UUID
+    pseudonyms: List[PseudonymDto]
@@ -0,0 +1,190 @@
+"""
+Similarity graph of a detection run as DOT or GraphML
+
+Nodes are the participating submissions, with the cluster they belong to as attribute, and edges the completed
+pairs at or above a threshold, with their overall similarity and matched token count. Every identifier and label is
+quoted and escaped for its format: DOT strings escape backslashes, double quotes and line breaks, and GraphML goes
+through XML escaping with the characters XML cannot represent removed. Exports are produced as a stream of text
+chunks, so large graphs are never held as a single string.
+"""
+
+import re
+from dataclasses import dataclass
+from typing import Dict, Iterable, Iterator, List, Optional
+from xml.sax.saxutils import escape, quoteattr
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.clusters import find_clusters
+from app.domains.reports.dto.report_dto import GraphFormat
+
+GRAPH_MEDIA_TYPES = {
+    GraphFormat.DOT: "text/vnd.graphviz; charset=utf-8",
+    GraphFormat.GRAPHML: "application/graphml+xml; charset=utf-8",
+}
+GRAPH_FILE_EXTENSIONS = {GraphFormat.DOT: "dot", GraphFormat.GRAPHML: "graphml"}
+# Characters per chunk of the stream
+CHUNK_SIZE = 64 * 1024
+
+GRAPHML_NAMESPACE = "http://graphml.graphdrawing.org/xmlns"
+GRAPHML_SCHEMA = "http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd"
+# (key, element, type) of the GraphML attributes
+GRAPHML_KEYS = (
+    ("label", "node", "string"),
+    ("submitter", "node", "string"),
+    ("cluster", "node", "int"),
+    ("similarity", "edge", "double"),
+    ("matched_tokens", "edge", "int"),
+)
+
+# Characters not allowed in XML 1.0 documents, even escaped
+_XML_INVALID = re.compile("[\x00-\x08\x0b\x0c\x0e-\x1f\ud800-\udfff\ufffe\uffff]")
+
+
+@dataclass
+class GraphNode:
+    id: str
+    label: str
+    submitter: Optional[str]
+    cluster: Optional[int]
+
+
+@dataclass
+class GraphEdge:
+    source: str
+    target: str
+    similarity: float
+    matched_tokens: Optional[int]
+
+
+def build_graph(
+    participants: Iterable, pairs: Iterable, threshold: float, pseudonyms: Optional[RunPseudonyms] = None
+):
+    """
+    Nodes and edges of the similarity graph of a run
+
+    Args:
+        participants: Participants of the run, one node each
+        pairs: Completed pairs, with submission_id, compared_submission_id, overall_similarity and matched_tokens
+        threshold: Similarity at or above which pairs become edges
+        pseudonyms: Pseudonyms of the run replacing submission and submitter identifiers, None to keep them
+
+    Returns:
+        Tuple of the nodes and the edges, most similar first
+    """
+    flagged = [p for p in pairs if p.overall_similarity >= threshold]
+    clusters = find_clusters(flagged, threshold)
+    cluster_of = {member: index for index, cluster in enumerate(clusters, 1) for member in cluster.members}
+
+    nodes: Dict[str, GraphNode] = {}
+
+    def node_id(submission_id) -> str:
+        key = str(submission_id)
+        if key not in nodes:
+            if pseudonyms is None:
+                nodes[key] = GraphNode(key, key[:8], None, cluster_of.get(key))
+            else:
+                nodes[key] = GraphNode(pseudonyms(key), pseudonyms(key), None, cluster_of.get(key))
+        return nodes[key].id
+
+    for participant in participants:
+        node_id(participant.submission_id)
+        submitter = participant.submitted_by_uuid
+        if submitter is not None:
+            nodes[str(participant.submission_id)].submitter = pseudonyms(submitter) if pseudonyms else str(submitter)
+
+    edges = [
+        GraphEdge(
+            node_id(pair.submission_id),
+            node_id(pair.compared_submission_id),
+            pair.overall_similarity,
+            pair.matched_tokens,
+        )
+        for pair in flagged
+    ]
+    return list(nodes.values()), edges
+
+
+def dot_quote(value) -> str:
+    """DOT double-quoted string of a value"""
+    text = str(value).replace("\\", "\\\\").replace('"', '\\"')
+    return '"' + text.replace("\r\n", "\n").replace("\r", "\n").replace("\n", "\\n") + '"'
+
+
+def _xml_text(value) -> str:
+    return _XML_INVALID.sub("", str(value))
+
+
+def iter_dot(name: str, nodes: List[GraphNode], edges: List[GraphEdge]) -> Iterator[str]:
+    """Lines of the DOT document of an undirected graph"""
+    yield f"graph {dot_quote(name)} {{\n"
+    yield "  node [shape=ellipse];\n"
+    for node in nodes:
+        attributes = [f"label={dot_quote(node.label)}"]
+        if node.submitter is not None:
+            attributes.append(f"submitter={dot_quote(node.submitter)}")
+        if node.cluster is not None:
+            attributes.append(f"cluster={node.cluster}")
+        yield f"  {dot_quote(node.id)} [{', '.join(attributes)}];\n"
+    for edge in edges:
+        attributes = [f"similarity={edge.similarity:.4f}", f'label="{edge.similarity:.3f}"']
+        if edge.matched_tokens is not None:
+            attributes.append(f"matched_tokens={edge.matched_tokens}")
+        yield f"  {dot_quote(edge.source)} -- {dot_quote(edge.target)} [{', '.join(attributes)}];\n"
+    yield "}\n"
+
+
+def iter_graphml(name: str, nodes: List[GraphNode], edges: List[GraphEdge]) -> Iterator[str]:
+    """Lines of the GraphML document of an undirected graph"""
+    yield '<?xml version="1.0" encoding="UTF-8"?>\n'
+    yield (
+        f'<graphml xmlns="{GRAPHML_NAMESPACE}" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" '
+        f'xsi:schemaLocation="{GRAPHML_NAMESPACE} {GRAPHML_SCHEMA}">\n'
+    )
+    for key, element, attribute_type in GRAPHML_KEYS:
+        yield f'  <key id="{key}" for="{element}" attr.name="{key}" attr.type="{attribute_type}"/>\n'
+    yield f'  <graph id={quoteattr(_xml_text(name))} edgedefault="undirected">\n'
+    for node in nodes:
+        data = {"label": node.label, "submitter": node.submitter, "cluster": node.cluster}
+        yield f"    <node id={quoteattr(_xml_text(node.id))}>{_graphml_data(data)}</node>\n"
+    for edge in edges:
+        data = {"similarity": f"{edge.similarity:.4f}", "matched_tokens": edge.matched_tokens}
+        yield (
+            f"    <edge source={quoteattr(_xml_text(edge.source))} target={quoteattr(_xml_text(edge.target))}>"
+            f"{_graphml_data(data)}</edge>\n"
+        )
+    yield "  </graph>\n"
+    yield "</graphml>\n"
+
+
+def _graphml_data(values: dict) -> str:
+    return "".join(
+        f'<data key="{key}">{escape(_xml_text(value))}</data>' for key, value in values.items() if value is not None
+    )
+
+
+def export_graph(
+    graph_format: GraphFormat,
+    name: str,
+    participants: Iterable,
+    pairs: Iterable,
+    threshold: float,
+    pseudonyms: Optional[RunPseudonyms] = None,
+) -> Iterator[str]:
+    """
+    Stream the similarity graph of a run in a format, in chunks of about CHUNK_SIZE characters
+
+    Pairs are only read once the stream is consumed.
+    """
+    nodes, edges = build_graph(participants, pairs, threshold, pseudonyms)
+    lines = iter_dot if graph_format == GraphFormat.DOT else iter_graphml
+
+    chunk: List[str] = []
+    size = 0
+    for line in lines(name, nodes, edges):
+        chunk.append(line)
+        size += len(line)
+        if size >= CHUNK_SIZE:
+            yield "".join(chunk)
+            chunk, size = [], 0
+    if chunk:
+        yield "".join(chunk)
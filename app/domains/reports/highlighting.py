@@ -0,0 +1,190 @@
+"""
+Lexical syntax highlighting of source files for HTML reports
+
+One regular expression per language family classifies comments, strings, numbers and keywords, everything else is
+plain text. The result is the escaped HTML of each source line: the spans of tokens running over several lines
+(block comments, multi-line strings) are closed at the end of a line and reopened on the next, so line i of the
+output is always line i of the source. Files of languages without a lexer come out as escaped plain text.
+
+The pair view highlights the regions of its fragments the same way, wrapping each matched line in a mark element.
+"""
+
+import html
+import re
+from pathlib import PurePosixPath
+from typing import FrozenSet, Iterator, List, Optional, Tuple
+
+from app.domains.tokenization.streaming_source import normalize_source
+
+_NUMBER = r"(?P<num>\b\d[\w.]*)"
+_WORD = r"(?P<word>[A-Za-z_]\w*)"
+_QUOTED = r"\"(?:\\.|[^\"\\\n])*\"?|'(?:\\.|[^'\\\n])*'?"
+
+PYTHON_LEXER = re.compile(
+    r"(?P<com>#[^\n]*)"
+    r"|(?P<str>[rbuRBUfF]{0,2}(?:\"\"\"(?:\\.|.)*?(?:\"\"\"|\Z)|'''(?:\\.|.)*?(?:'''|\Z)|" + _QUOTED + "))"
+    rf"|{_NUMBER}|{_WORD}",
+    re.S,
+)
+C_LEXER = re.compile(
+    r"(?P<com>//[^\n]*|/\*.*?(?:\*/|\Z))" r"|(?P<str>" + _QUOTED + r"|`(?:\\.|[^`\\])*`?)" rf"|{_NUMBER}|{_WORD}",
+    re.S,
+)
+HASH_LEXER = re.compile(r"(?P<com>#[^\n]*)" r"|(?P<str>" + _QUOTED + ")" rf"|{_NUMBER}|{_WORD}", re.S)
+
+
+def _keywords(words: str) -> FrozenSet[str]:
+    return frozenset(words.split())
+
+
+PYTHON_KEYWORDS = _keywords(
+    "False None True and as assert async await break class continue def del elif else except finally for from "
+    "global if import in is lambda nonlocal not or pass raise return try while with yield match case self"
+)
+C_KEYWORDS = _keywords(
+    "auto break case char const continue default do double else enum extern float for goto if inline int long "
+    "register return short signed sizeof static struct switch typedef union unsigned void volatile while bool true "
+    "false NULL"
+)
+CPP_KEYWORDS = C_KEYWORDS | _keywords(
+    "class namespace template typename public private protected virtual override using new delete this throw try "
+    "catch operator friend nullptr constexpr explicit mutable noexcept std"
+)
+JAVA_KEYWORDS = _keywords(
+    "abstract assert boolean break byte case catch char class const continue default do double else enum extends "
+    "final finally float for if implements import instanceof int interface long native new null package private "
+    "protected public record return short static super switch synchronized this throw throws try var void "
+    "volatile while true false"
+)
+JAVASCRIPT_KEYWORDS = _keywords(
+    "async await break case catch class const continue debugger default delete do else export extends false "
+    "finally for function if import in instanceof let new null return static super switch this throw true try "
+    "typeof undefined var void while with yield interface type enum implements private public protected readonly"
+)
+CSHARP_KEYWORDS = JAVA_KEYWORDS | _keywords("using namespace struct string bool readonly override virtual async await")
+GO_KEYWORDS = _keywords(
+    "break case chan const continue default defer else fallthrough for func go goto if import interface map "
+    "package range return select struct switch type var nil true false"
+)
+RUST_KEYWORDS = _keywords(
+    "as async await break const continue crate dyn else enum extern false fn for if impl in let loop match mod move "
+    "mut pub ref return self Self static struct super trait true type unsafe use where while"
+)
+KOTLIN_KEYWORDS = JAVA_KEYWORDS | _keywords("fun val when object data companion is in out sealed open")
+PHP_KEYWORDS = _keywords(
+    "abstract and array as break case catch class const continue declare default do echo else elseif empty "
+    "extends false final finally for foreach function global if implements include interface isset namespace new "
+    "null or print private protected public require return static switch throw trait true try use var while"
+)
+RUBY_KEYWORDS = _keywords(
+    "alias and begin break case class def defined do else elsif end ensure false for if in module next nil not or "
+    "redo rescue retry return self super then true undef unless until when while yield"
+)
+SHELL_KEYWORDS = _keywords("if then else elif fi case esac for while until do done in function return local export")
+
+# Extension -> (language name, lexer, keywords)
+LANGUAGES = {
+    ".py": ("python", PYTHON_LEXER, PYTHON_KEYWORDS),
+    ".c": ("c", C_LEXER, C_KEYWORDS),
+    ".h": ("c", C_LEXER, C_KEYWORDS),
+    ".cpp": ("cpp", C_LEXER, CPP_KEYWORDS),
+    ".cc": ("cpp", C_LEXER, CPP_KEYWORDS),
+    ".cxx": ("cpp", C_LEXER, CPP_KEYWORDS),
+    ".hpp": ("cpp", C_LEXER, CPP_KEYWORDS),
+    ".java": ("java", C_LEXER, JAVA_KEYWORDS),
+    ".js": ("javascript", C_LEXER, JAVASCRIPT_KEYWORDS),
+    ".jsx": ("javascript", C_LEXER, JAVASCRIPT_KEYWORDS),
+    ".ts": ("typescript", C_LEXER, JAVASCRIPT_KEYWORDS),
+    ".tsx": ("typescript", C_LEXER, JAVASCRIPT_KEYWORDS),
+    ".cs": ("csharp", C_LEXER, CSHARP_KEYWORDS),
+    ".go": ("go", C_LEXER, GO_KEYWORDS),
+    ".rs": ("rust", C_LEXER, RUST_KEYWORDS),
+    ".kt": ("kotlin", C_LEXER, KOTLIN_KEYWORDS),
+    ".php": ("php", C_LEXER, PHP_KEYWORDS),
+    ".rb": ("ruby", HASH_LEXER, RUBY_KEYWORDS),
+    ".sh": ("shell", HASH_LEXER, SHELL_KEYWORDS),
+}
+
+# CSS class of each token kind
+TOKEN_CLASSES = {"com": "c", "str": "s", "num": "n", "word": "k"}
+# Element wrapping the matched lines of a highlighted region, for CSS to emphasize them
+MATCH_START = '<mark class="match">'
+MATCH_END = "</mark>"
+
+
+def _segments(text: str, language) -> Iterator[Tuple[Optional[str], str]]:
+    """(CSS class or None, text) pieces covering the whole text in order"""
+    if language is None:
+        yield None, text
+        return
+
+    _, lexer, keywords = language
+    position = 0
+    for match in lexer.finditer(text):
+        kind = match.lastgroup
+        if kind == "word" and match.group() not in keywords:
+            continue
+        if match.start() > position:
+            yield None, text[position : match.start()]
+        yield TOKEN_CLASSES[kind], match.group()
+        position = match.end()
+    if position < len(text):
+        yield None, text[position:]
+
+
+def highlight_lines(text: str, file_path: Optional[str] = None) -> List[str]:
+    """
+    Escaped HTML of each line of a source file, highlighted when the language of its extension is known
+
+    A leading BOM is dropped and line endings are normalized first, the result has as many entries as
+    text.split("\\n") after normalization.
+    """
+    text = normalize_source(text)
+    language = LANGUAGES.get(PurePosixPath(file_path or "").suffix.lower())
+
+    lines: List[str] = []
+    current: List[str] = []
+    for css_class, value in _segments(text, language):
+        for index, part in enumerate(value.split("\n")):
+            if index:
+                lines.append("".join(current))
+                current = []
+            if part:
+                escaped = html.escape(part)
+                current.append(f'<span class="{css_class}">{escaped}</span>' if css_class else escaped)
+    lines.append("".join(current))
+    return lines
+
+
+def language_name(file_path: Optional[str]) -> Optional[str]:
+    """Name of the language highlighted for a file, None when its extension has no lexer"""
+    language = LANGUAGES.get(PurePosixPath(file_path or "").suffix.lower())
+    return language[0] if language else None
+
+
+def highlight_region(
+    text: str, file_path: Optional[str], start: int, end: int, context: int = 0
+) -> List[Tuple[int, str, bool]]:
+    """
+    Highlighted lines of a matched region of a file and of the context lines around it
+
+    Args:
+        text: Content of the file
+        file_path: Path of the file, its extension selects the lexer
+        start: First matched line, 0-based
+        end: Last matched line, 0-based and inclusive
+        context: Lines kept before and after the region
+
+    Returns:
+        (1-based line number, HTML, matched) of each line, matched lines wrapped in MATCH_START and MATCH_END. Spans
+        never cross lines, so the markers always enclose whole, balanced lines.
+    """
+    lines = highlight_lines(text, file_path)
+    end = max(start, end)
+    first, last = max(0, start - context), min(len(lines) - 1, end + context)
+    region = []
+    for number in range(first, last + 1):
+        matched = start <= number <= end
+        line = f"{MATCH_START}{lines[number]}{MATCH_END}" if matched else lines[number]
+        region.append((number + 1, line, matched))
+    return region
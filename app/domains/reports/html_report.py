@@ -0,0 +1,338 @@
+"""
+Self-contained HTML report of a detection run
+
+The report is a single file without external requests: CSS and JavaScript are inlined by the template, code is
+highlighted on the server. Everything coming from submissions (code, file paths, function names) is escaped by the
+template engine, highlighted code is escaped by the highlighter before it is marked safe, and the data embedded for
+scripts is serialized with the tojson filter, which escapes <, > and & so that a "</script>" in a submission cannot
+close the script element.
+
+Text is rendered in the locale of the report, while everything machine-readable, the data embedded for scripts,
+identifiers, anchors and scores, is the same in every locale.
+
+Self-matches, pairs of submissions of the same group or sharing a student through their teams, and pairs of declared
+collaborations are not flagged: they are listed apart, without fragments, and never link submissions into clusters.
+"""
+
+from dataclasses import dataclass, field
+from datetime import datetime
+from pathlib import Path
+from typing import Callable, Dict, Iterable, List, Optional, Tuple
+
+from jinja2 import Environment, FileSystemLoader, select_autoescape
+from markupsafe import Markup
+
+from app.domains.detection.comparability import describe_reason
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.clusters import find_clusters
+from app.domains.reports.display_paths import short_paths
+from app.domains.reports.highlighting import highlight_lines
+from app.domains.reports.run_stats import is_self_match, participant_members
+from app.domains.runs.runs_models import FragmentType
+from app.domains.tokenization.streaming_source import normalize_source
+from app.shared.i18n import DEFAULT_LOCALE, Locale, translator
+from app.shared.timestamps import utc_now
+
+# Part of the cache key of stored reports, to bump whenever the template or the rendered content changes
+REPORT_FORMAT_VERSION = 9
+REPORT_TEMPLATE = "run_report.html"
+# Lines shown per side of a fragment, longer fragments are cut
+MAX_FRAGMENT_LINES = 200
+# Code blocks shown per pair, most similar first
+MAX_BLOCKS_PER_PAIR = 20
+
+_environment = Environment(
+    loader=FileSystemLoader(Path(__file__).parent / "templates"),
+    autoescape=select_autoescape(["html"]),
+    trim_blocks=True,
+    lstrip_blocks=True,
+)
+
+# (submission ID, file path) -> decoded content, None when the file cannot be read
+SourceReader = Callable[[str, str], Optional[str]]
+# (decoded content, file path) -> lines of the content as shown
+LineSplitter = Callable[[str, str], List[str]]
+
+
+@dataclass
+class CodeExcerpt:
+    """Lines of one side of a fragment, as (1-based line number, line as split by the excerpts)"""
+
+    path: str
+    lines: List[Tuple[int, str]] = field(default_factory=list)
+    truncated: bool = False
+    available: bool = True
+
+
+@dataclass
+class FragmentView:
+    similarity: float
+    left: CodeExcerpt
+    right: CodeExcerpt
+    left_function: Optional[str] = None
+    right_function: Optional[str] = None
+
+
+@dataclass
+class PairView:
+    rank: int
+    pair: object
+    left_label: str
+    right_label: str
+    cluster: Optional[int]
+    files: List[object] = field(default_factory=list)
+    blocks: List[FragmentView] = field(default_factory=list)
+    # Short name shown for each file path of either side, the full path staying in the title
+    left_names: Dict[str, str] = field(default_factory=dict)
+    right_names: Dict[str, str] = field(default_factory=dict)
+
+
+def highlighted_lines(content: str, path: str) -> List[str]:
+    """Highlighted HTML of each line of a file, marked safe for the template"""
+    return [Markup(line) for line in highlight_lines(content, path)]
+
+
+def plain_lines(content: str, path: str) -> List[str]:
+    """Source text of each line of a file, BOM and line endings normalized like the highlighter does"""
+    return normalize_source(content).split("\n")
+
+
+class SourceExcerpts:
+    """Lines of the files fragments point to, each file read and split once"""
+
+    def __init__(self, read_source: SourceReader, split_lines: LineSplitter = highlighted_lines):
+        self.read_source = read_source
+        self.split_lines = split_lines
+        self._lines: Dict[Tuple[str, str], Optional[List[str]]] = {}
+
+    def excerpt(
+        self,
+        submission_id,
+        path: str,
+        start: Optional[int],
+        end: Optional[int],
+        max_lines: int = MAX_FRAGMENT_LINES,
+    ) -> CodeExcerpt:
+        """Lines start to end of a file, both 0-based and inclusive like the fragment rows, cut after max_lines"""
+        key = (str(submission_id), path)
+        if key not in self._lines:
+            content = self.read_source(*key)
+            self._lines[key] = self.split_lines(content, path) if content is not None else None
+        lines = self._lines[key]
+        if lines is None or start is None:
+            return CodeExcerpt(path, available=False)
+
+        end = max(start, end if end is not None else start)
+        last = min(end, start + max_lines - 1, len(lines) - 1)
+        return CodeExcerpt(
+            path, [(number + 1, lines[number]) for number in range(start, last + 1)], truncated=last < end
+        )
+
+
+def participant_labels(participants: Iterable) -> Dict[str, str]:
+    """Short display label of each participating submission"""
+    return {str(p.submission_id): str(p.submission_id)[:8] for p in participants}
+
+
+def team_labels(participants: Iterable, pseudonyms: Optional[RunPseudonyms], t) -> Dict[str, str]:
+    """Team name and members of each participating submission of a team, names are left out of anonymized reports"""
+    teams = {}
+    for p in participants:
+        members = getattr(p, "team_members", None)
+        if not members:
+            continue
+        shown = ", ".join(pseudonyms(member) if pseudonyms is not None else str(member) for member in members)
+        name = getattr(p, "team_name", None)
+        teams[str(p.submission_id)] = (
+            t("report.team", name=name, members=shown)
+            if name and pseudonyms is None
+            else t("report.team_members", members=shown)
+        )
+    return teams
+
+
+def file_names(pair, fragments: List, side: str, compared_side: str) -> Dict[str, str]:
+    """Short names of the files of one side of a pair, telling them apart from every compared file of it"""
+    compared_files = (getattr(pair, "compared_files", None) or {}).get(compared_side) or {}
+    return short_paths([getattr(fragment, f"{side}_path") for fragment in fragments] + list(compared_files))
+
+
+def render_run_report(
+    run,
+    participants: List,
+    pairs: List,
+    fragments: Dict[str, List],
+    read_source: SourceReader,
+    threshold: float,
+    omitted_pairs: int = 0,
+    generated_at: Optional[datetime] = None,
+    pseudonyms: Optional[RunPseudonyms] = None,
+    late: Optional[Dict[str, int]] = None,
+    labels: Optional[Dict[str, str]] = None,
+    locale: Locale = DEFAULT_LOCALE,
+    declared: Optional[Callable[[object], bool]] = None,
+    timed_out_pairs: Optional[List] = None,
+    metrics: Optional[Dict[str, dict]] = None,
+) -> str:
+    """
+    Render the HTML report of a run
+
+    Args:
+        run: DetectionRun of the report
+        participants: Participants of the run
+        pairs: Pairs at or above the threshold, most similar first, self-matches are listed apart
+        fragments: Fragments of each pair by pair ID, most similar first
+        read_source: Reader of the submission files the fragments point to
+        threshold: Similarity at or above which pairs are flagged
+        omitted_pairs: Flagged pairs left out of the report
+        pseudonyms: Pseudonyms of the run replacing identities and home directories everywhere, None to keep them
+        late: Minutes after the deadline of the participating submissions uploaded late, by submission ID
+        labels: Display label of each participating submission by ID, the start of the ID by default
+        locale: Language of the text of the report
+        declared: Whether a pair is a declared collaboration, see DeclaredCollaborations, none is without it
+        timed_out_pairs: Pairs stopped by the pair or the run timeout, listed apart without scores
+        metrics: Code metrics of the participating submissions computed so far, by submission ID
+    """
+    t = translator(locale)
+    labels = {**participant_labels(participants), **(labels or {})}
+    if pseudonyms is not None:
+        labels = {submission_id: pseudonyms(submission_id) for submission_id in labels}
+    submitters = {str(p.submission_id): p.submitted_by_uuid for p in participants}
+    teams = team_labels(participants, pseudonyms, t)
+    groups = {str(p.submission_id): getattr(p, "group_uuid", None) for p in participants}
+    members = {str(p.submission_id): participant_members(p) for p in participants}
+    self_matches = [pair for pair in pairs if is_self_match(pair, groups, members)]
+    pairs = [pair for pair in pairs if not is_self_match(pair, groups, members)]
+    collaborations = [pair for pair in pairs if declared is not None and declared(pair)]
+    pairs = [pair for pair in pairs if not (declared is not None and declared(pair))]
+
+    def label(submission_id) -> str:
+        return labels.get(str(submission_id), str(submission_id)[:8])
+
+    def reason(value) -> str:
+        return t(f"reason.{getattr(value, 'value', value)}", default=describe_reason(value))
+
+    def listed(apart: List) -> List[tuple]:
+        return [
+            (
+                label(pair.submission_id),
+                str(pair.submission_id),
+                label(pair.compared_submission_id),
+                str(pair.compared_submission_id),
+                pair.overall_similarity,
+            )
+            for pair in apart
+        ]
+
+    clusters = find_clusters(pairs, threshold)
+    cluster_of = {member: index for index, cluster in enumerate(clusters, 1) for member in cluster.members}
+
+    sources = SourceExcerpts(read_source)
+    pair_views = []
+    for rank, pair in enumerate(pairs, 1):
+        view = PairView(
+            rank,
+            pair,
+            label(pair.submission_id),
+            label(pair.compared_submission_id),
+            cluster_of.get(str(pair.submission_id)),
+        )
+        for fragment in fragments.get(str(pair.id), []):
+            if fragment.fragment_type == FragmentType.FILE:
+                view.files.append(fragment)
+            elif len(view.blocks) < MAX_BLOCKS_PER_PAIR:
+                details = fragment.details or {}
+                view.blocks.append(
+                    FragmentView(
+                        fragment.similarity,
+                        sources.excerpt(
+                            pair.submission_id, fragment.file1_path, fragment.file1_start_line, fragment.file1_end_line
+                        ),
+                        sources.excerpt(
+                            pair.compared_submission_id,
+                            fragment.file2_path,
+                            fragment.file2_start_line,
+                            fragment.file2_end_line,
+                        ),
+                        details.get("file1_function"),
+                        details.get("file2_function"),
+                    )
+                )
+        view.left_names = file_names(pair, fragments.get(str(pair.id), []), "file1", "submission1")
+        view.right_names = file_names(pair, fragments.get(str(pair.id), []), "file2", "submission2")
+        pair_views.append(view)
+
+    data = {
+        "run_id": str(run.id),
+        "threshold": threshold,
+        "pairs": [
+            {
+                "id": str(view.pair.id),
+                "submission_id": str(view.pair.submission_id),
+                "compared_submission_id": str(view.pair.compared_submission_id),
+                "overall_similarity": view.pair.overall_similarity,
+                "cluster": view.cluster,
+                "files": [[f.file1_path, f.file2_path, f.similarity] for f in view.files],
+            }
+            for view in pair_views
+        ],
+        "participants": [
+            {"submission_id": str(p.submission_id), "metrics": metrics[str(p.submission_id)]}
+            for p in participants
+            if metrics and str(p.submission_id) in metrics
+        ],
+    }
+    document = _environment.get_template(REPORT_TEMPLATE).render(
+        t=t,
+        lang=locale.value,
+        run=run,
+        status=getattr(run.status, "value", run.status),
+        threshold=threshold,
+        pairs=pair_views,
+        teams=teams,
+        self_matches=listed(self_matches),
+        collaborations=listed(collaborations),
+        clusters=[
+            {
+                "index": index,
+                "members": [(label(member), member, submitters.get(member)) for member in cluster.members],
+                "pair_count": cluster.pair_count,
+                "max_similarity": cluster.max_similarity,
+            }
+            for index, cluster in enumerate(clusters, 1)
+        ],
+        not_comparable=[
+            (label(p.submission_id), str(p.submission_id), reason(p.not_comparable_reason))
+            for p in participants
+            if getattr(p, "not_comparable_reason", None)
+        ],
+        file_errors=[
+            (label(error["submission_id"]), error["submission_id"], error["path"], error["stage"], error["message"])
+            for error in getattr(run, "file_errors", None) or []
+        ],
+        timed_out=[
+            (
+                label(pair.submission_id),
+                str(pair.submission_id),
+                label(pair.compared_submission_id),
+                str(pair.compared_submission_id),
+                pair.error_message or "",
+            )
+            for pair in timed_out_pairs or []
+        ],
+        late=late or {},
+        late_submissions=[
+            (label(p.submission_id), str(p.submission_id), late[str(p.submission_id)])
+            for p in participants
+            if late and str(p.submission_id) in late
+        ],
+        code_metrics=[
+            (label(p.submission_id), str(p.submission_id), metrics[str(p.submission_id)])
+            for p in participants
+            if metrics and str(p.submission_id) in metrics
+        ],
+        omitted_pairs=omitted_pairs,
+        generated_at=generated_at or utc_now(),
+        data=data,
+    )
+    return pseudonyms.scrub(document) if pseudonyms is not None else document
@@ -0,0 +1,225 @@
+"""
+JPlag-compatible result archive of a detection run
+
+The archive follows the result format of JPlag 4 so that it can be opened by the JPlag report viewer:
+overview.json with the pair scores and clusters, one comparison file per exported pair with its matched line
+ranges, submissionFileIndex.json with the token count of each file, and the exported sources under submissions/.
+Fields our results cannot fill faithfully get the defaults below instead of being left out, since the viewer
+expects them:
+
+- language: DEFAULT_LANGUAGE, a run compares files of several languages
+- match_sensitivity: DEFAULT_MATCH_SENSITIVITY, the minimum token match of JPlag, which our detection does not have
+- failed_submission_names and excluded_files: empty
+- AVG is our overall similarity, MAX the larger share of the tokens of a side matched by the shared blocks
+- the AVG distribution counts every completed pair of the run, the MAX one the exported pairs only
+- tokens of a match: 0 for blocks recorded without token counts
+- cluster strength: the average similarity of the flagged pairs inside the cluster
+- sources that are no longer stored are written as empty files
+
+Distributions have 100 buckets of 1%, highest first like JPlag writes them. Entries are sorted and carry a fixed
+date, so the same run always gives the same bytes.
+"""
+
+import io
+import json
+import math
+import zipfile
+from pathlib import PurePosixPath
+from typing import Dict, Iterable, List, Optional, Set, Tuple
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.clusters import find_clusters
+from app.domains.reports.html_report import SourceReader, participant_labels
+from app.domains.runs.match_stats import block_tokens, side_coverage
+from app.domains.runs.runs_models import FragmentType
+from app.shared.timestamps import to_rfc3339
+
+JPLAG_VERSION = {"major": 4, "minor": 3, "patch": 0}
+DEFAULT_LANGUAGE = "multi-language"
+DEFAULT_MATCH_SENSITIVITY = 9
+SUBMISSIONS_FOLDER = "submissions"
+DISTRIBUTION_BUCKETS = 100
+METRIC_DESCRIPTIONS = {
+    "AVG": "Overall similarity of the pair as computed by the detection.",
+    "MAX": "Larger share of the tokens of one side matched by the shared blocks of the pair.",
+}
+# Date of the archive entries, fixed so that exports are reproducible
+_ENTRY_DATE = (1980, 1, 1, 0, 0, 0)
+
+
+def distribution(scores: Iterable[float]) -> List[int]:
+    """Counts of the scores in 100 buckets of 1%, highest first, 1.0 counted in the highest bucket"""
+    buckets = [0] * DISTRIBUTION_BUCKETS
+    for score in scores:
+        buckets[DISTRIBUTION_BUCKETS - 1 - min(int(score * DISTRIBUTION_BUCKETS), DISTRIBUTION_BUCKETS - 1)] += 1
+    return buckets
+
+
+def _file_name(submission: str, path: str) -> str:
+    return str(PurePosixPath(submission) / path)
+
+
+def _comparison_name(first: str, second: str) -> str:
+    return f"{first}-{second}.json"
+
+
+def _execution_time(run) -> int:
+    if run.finished_at is None or run.started_at is None:
+        return 0
+    return max(0, round((run.finished_at - run.started_at).total_seconds() * 1000))
+
+
+def export_jplag(
+    run,
+    participants: List,
+    pairs: List,
+    fragments: Dict[str, List],
+    scores: Iterable[float],
+    read_source: SourceReader,
+    threshold: float,
+    pseudonyms: Optional[RunPseudonyms] = None,
+) -> bytes:
+    """
+    JPlag result archive of the flagged pairs of a run
+
+    Args:
+        run: DetectionRun of the export
+        participants: Participants of the run
+        pairs: Exported pairs, most similar first
+        fragments: Fragments of each pair by pair ID
+        scores: Overall similarity of every completed pair of the run, for the AVG distribution
+        read_source: Reader of the submission files the pairs compared
+        threshold: Similarity at or above which pairs are flagged
+        pseudonyms: Pseudonyms of the run replacing identities and home directories everywhere, None to keep them
+    """
+    labels = participant_labels(participants)
+    if pseudonyms is not None:
+        labels = {submission_id: pseudonyms(submission_id) for submission_id in labels}
+
+    def jplag_id(submission_id) -> str:
+        """Folder of a submission in the archive, its pseudonym when anonymized"""
+        return pseudonyms(submission_id) if pseudonyms is not None else str(submission_id)
+
+    def scrub(text: str) -> str:
+        return pseudonyms.scrub(text) if pseudonyms is not None else text
+
+    entries: Dict[str, str] = {}
+    comparison_names: Dict[str, Dict[str, str]] = {}
+    top_comparisons: Dict[str, List[dict]] = {"AVG": [], "MAX": []}
+    max_scores: List[float] = []
+    # Token count of the files to export by (submission ID, path)
+    files: Dict[Tuple[str, str], int] = {}
+
+    for pair in pairs:
+        first, second = jplag_id(pair.submission_id), jplag_id(pair.compared_submission_id)
+        pair_fragments = fragments.get(str(pair.id), [])
+        first_similarity, second_similarity = side_coverage(pair, pair_fragments)
+        similarities = {"AVG": pair.overall_similarity, "MAX": max(first_similarity, second_similarity)}
+        max_scores.append(similarities["MAX"])
+
+        for side, submission in (("submission1", pair.submission_id), ("submission2", pair.compared_submission_id)):
+            for path, tokens in ((pair.compared_files or {}).get(side) or {}).items():
+                files[(str(submission), path)] = tokens or 0
+
+        matches = []
+        for fragment in pair_fragments:
+            if (
+                fragment.fragment_type != FragmentType.BLOCK
+                or fragment.file1_start_line is None
+                or fragment.file2_start_line is None
+            ):
+                continue
+            files.setdefault((str(pair.submission_id), fragment.file1_path), 0)
+            files.setdefault((str(pair.compared_submission_id), fragment.file2_path), 0)
+            matches.append(
+                {
+                    "file1": scrub(_file_name(first, fragment.file1_path)),
+                    "file2": scrub(_file_name(second, fragment.file2_path)),
+                    "start1": fragment.file1_start_line + 1,
+                    "end1": max(fragment.file1_start_line, fragment.file1_end_line or 0) + 1,
+                    "start2": fragment.file2_start_line + 1,
+                    "end2": max(fragment.file2_start_line, fragment.file2_end_line or 0) + 1,
+                    "tokens": block_tokens(fragment) or 0,
+                }
+            )
+        matches.sort(key=lambda m: (m["file1"], m["start1"], m["file2"], m["start2"], m["end1"], m["end2"]))
+
+        name = _comparison_name(first, second)
+        comparison_names.setdefault(first, {})[second] = name
+        comparison_names.setdefault(second, {})[first] = name
+        for metric, similarity in similarities.items():
+            top_comparisons[metric].append(
+                {"first_submission": first, "second_submission": second, "similarity": similarity}
+            )
+        entries[name] = json.dumps(
+            {
+                "id1": first,
+                "id2": second,
+                "similarities": similarities,
+                "matches": matches,
+                "first_similarity": first_similarity,
+                "second_similarity": second_similarity,
+            },
+            sort_keys=True,
+        )
+
+    clusters = []
+    for cluster in find_clusters(pairs, threshold):
+        members: Set[str] = set(cluster.members)
+        inside = [
+            p.overall_similarity for p in pairs if p.overall_similarity >= threshold and str(p.submission_id) in members
+        ]
+        average = math.fsum(inside) / len(inside) if inside else 0.0
+        clusters.append(
+            {"average_similarity": average, "strength": average, "members": [jplag_id(m) for m in cluster.members]}
+        )
+
+    file_indexes: Dict[str, Dict[str, dict]] = {}
+    for (submission, path), tokens in sorted(files.items()):
+        name = scrub(_file_name(jplag_id(submission), path))
+        file_indexes.setdefault(jplag_id(submission), {})[name] = {"token_count": tokens}
+        content = read_source(submission, path)
+        entries[f"{SUBMISSIONS_FOLDER}/{name}"] = scrub(content) if content is not None else ""
+    extensions = {PurePosixPath(path).suffix for _, path in files} - {""}
+
+    all_scores = list(scores)
+    entries["overview.json"] = json.dumps(
+        {
+            "jplag_version": JPLAG_VERSION,
+            "submission_folder_path": [SUBMISSIONS_FOLDER],
+            "base_code_folder_path": "",
+            "language": DEFAULT_LANGUAGE,
+            "file_extensions": sorted(extensions),
+            "submission_id_to_display_name": {jplag_id(key): labels[key] for key in sorted(labels)},
+            "submission_ids_to_comparison_file_name": comparison_names,
+            "failed_submission_names": [],
+            "excluded_files": [],
+            "match_sensitivity": DEFAULT_MATCH_SENSITIVITY,
+            "date_of_execution": to_rfc3339(run.started_at) or "",
+            "execution_time": _execution_time(run),
+            "metrics": [
+                {
+                    "name": metric,
+                    "distribution": distribution(all_scores if metric == "AVG" else max_scores),
+                    "topComparisons": sorted(
+                        comparisons,
+                        key=lambda c: (-c["similarity"], c["first_submission"], c["second_submission"]),
+                    ),
+                    "description": METRIC_DESCRIPTIONS[metric],
+                }
+                for metric, comparisons in top_comparisons.items()
+            ],
+            "clusters": clusters,
+            "total_comparisons": len(all_scores),
+        },
+        sort_keys=True,
+    )
+    entries["submissionFileIndex.json"] = json.dumps({"submission_file_indexes": file_indexes}, sort_keys=True)
+
+    archive = io.BytesIO()
+    with zipfile.ZipFile(archive, "w") as zip_file:
+        for name in sorted(entries):
+            entry = zipfile.ZipInfo(name, date_time=_ENTRY_DATE)
+            entry.compress_type = zipfile.ZIP_DEFLATED
+            zip_file.writestr(entry, entries[name].encode("utf-8"))
+    return archive.getvalue()
@@ -0,0 +1,175 @@
+"""
+Moss-compatible export of a detection run
+
+The export is a ZIP archive laid out like a Moss result page: index.html lists the flagged pairs with the percent
+matched of each side and the lines matched, and match<N>.html lists the matched line ranges of pair N with their
+file names. Our detection is token-based where Moss is line-based, the mapping is written in MAPPING_NOTES at the
+top of the index:
+
+- the percent of a side is the matched tokens of the shared blocks over the tokens of the compared files of that
+  side, capped at 100, so the two sides differ when one submission is larger; pairs recorded without token counts
+  show their overall similarity on both sides
+- the lines matched are the distinct lines covered by the shared blocks on the side covering more of them
+- line ranges are 1-based and inclusive like Moss, our fragment rows are 0-based
+
+Pairs are listed most similar first and matches by file and line, and the archive entries carry a fixed date, so
+the same run always gives the same bytes.
+"""
+
+import io
+import zipfile
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Dict, Iterable, List, Optional, Set, Tuple
+
+from jinja2 import Environment, FileSystemLoader, select_autoescape
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.html_report import participant_labels
+from app.domains.runs.match_stats import side_coverage
+from app.domains.runs.runs_models import FragmentType
+
+MOSS_INDEX_TEMPLATE = "moss_index.html"
+MOSS_MATCH_TEMPLATE = "moss_match.html"
+MAPPING_NOTES = [
+    "Percent matched: matched tokens of the shared blocks over the tokens of the compared files of each side, "
+    "capped at 100; overall similarity on both sides for pairs recorded without token counts.",
+    "Lines matched: distinct lines covered by the shared blocks, on the side covering more of them.",
+    "Line ranges: 1-based and inclusive.",
+]
+# Date of the archive entries, fixed so that exports are reproducible
+_ENTRY_DATE = (1980, 1, 1, 0, 0, 0)
+
+_environment = Environment(
+    loader=FileSystemLoader(Path(__file__).parent / "templates"),
+    autoescape=select_autoescape(["html"]),
+    trim_blocks=True,
+    lstrip_blocks=True,
+)
+
+
+@dataclass
+class MossMatch:
+    """Matched line ranges of a shared block, 1-based and inclusive"""
+
+    left_path: str
+    left_start: int
+    left_end: int
+    right_path: str
+    right_start: int
+    right_end: int
+    similarity: float
+
+
+@dataclass
+class MossPair:
+    number: int
+    similarity: float
+    left_label: str
+    right_label: str
+    left_percent: int
+    right_percent: int
+    lines_matched: int
+    matches: List[MossMatch] = field(default_factory=list)
+
+
+def _range(start: int, end: Optional[int]) -> Tuple[int, int]:
+    return start + 1, max(start, end if end is not None else start) + 1
+
+
+def covered_lines(ranges: Iterable[Tuple[str, int, int]]) -> int:
+    """Distinct lines covered by (path, first line, last line) ranges"""
+    lines: Set[Tuple[str, int]] = set()
+    for path, first, last in ranges:
+        lines.update((path, number) for number in range(first, last + 1))
+    return len(lines)
+
+
+def moss_pair(number: int, pair, fragments: List, left_label: str, right_label: str) -> MossPair:
+    """Moss view of a pair from its block fragments, the located ones only"""
+    left_coverage, right_coverage = side_coverage(pair, fragments)
+    blocks = [
+        f
+        for f in fragments
+        if f.fragment_type == FragmentType.BLOCK and f.file1_start_line is not None and f.file2_start_line is not None
+    ]
+    matches = sorted(
+        (
+            MossMatch(
+                f.file1_path,
+                *_range(f.file1_start_line, f.file1_end_line),
+                f.file2_path,
+                *_range(f.file2_start_line, f.file2_end_line),
+                f.similarity,
+            )
+            for f in blocks
+        ),
+        key=lambda m: (m.left_path, m.left_start, m.left_end, m.right_path, m.right_start, m.right_end),
+    )
+    return MossPair(
+        number,
+        pair.overall_similarity,
+        left_label,
+        right_label,
+        round(left_coverage * 100),
+        round(right_coverage * 100),
+        max(
+            covered_lines((m.left_path, m.left_start, m.left_end) for m in matches),
+            covered_lines((m.right_path, m.right_start, m.right_end) for m in matches),
+        ),
+        matches,
+    )
+
+
+def export_moss(
+    run,
+    participants: List,
+    pairs: List,
+    fragments: Dict[str, List],
+    threshold: float,
+    omitted_pairs: int = 0,
+    pseudonyms: Optional[RunPseudonyms] = None,
+) -> bytes:
+    """
+    Moss-style ZIP archive of the flagged pairs of a run
+
+    Args:
+        run: DetectionRun of the export
+        participants: Participants of the run
+        pairs: Flagged pairs, most similar first
+        fragments: Fragments of each pair by pair ID
+        threshold: Similarity at or above which pairs are flagged
+        omitted_pairs: Flagged pairs left out of the export
+        pseudonyms: Pseudonyms of the run replacing identities and home directories everywhere, None to keep them
+    """
+    labels = participant_labels(participants)
+    if pseudonyms is not None:
+        labels = {submission_id: pseudonyms(submission_id) for submission_id in labels}
+
+    def label(submission_id) -> str:
+        return labels.get(str(submission_id), str(submission_id)[:8])
+
+    moss_pairs = [
+        moss_pair(
+            number, pair, fragments.get(str(pair.id), []), label(pair.submission_id), label(pair.compared_submission_id)
+        )
+        for number, pair in enumerate(pairs)
+    ]
+    pages = {
+        "index.html": _environment.get_template(MOSS_INDEX_TEMPLATE).render(
+            run_id=run.id, threshold=threshold, pairs=moss_pairs, omitted_pairs=omitted_pairs, notes=MAPPING_NOTES
+        )
+    }
+    match_template = _environment.get_template(MOSS_MATCH_TEMPLATE)
+    for moss in moss_pairs:
+        pages[f"match{moss.number}.html"] = match_template.render(pair=moss)
+
+    archive = io.BytesIO()
+    with zipfile.ZipFile(archive, "w") as zip_file:
+        for name, page in pages.items():
+            if pseudonyms is not None:
+                page = pseudonyms.scrub(page)
+            entry = zipfile.ZipInfo(name, date_time=_ENTRY_DATE)
+            entry.compress_type = zipfile.ZIP_DEFLATED
+            zip_file.writestr(entry, page.encode("utf-8"))
+    return archive.getvalue()
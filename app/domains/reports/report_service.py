@@ -0,0 +1,587 @@
+import hashlib
+import json
+import logging
+import threading
+from concurrent.futures import Future
+from pathlib import Path, PurePosixPath
+from typing import Callable, Dict, Iterator, List, Optional, Union
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.reports.dto.report_dto import (
+    GraphFormat,
+    PseudonymMappingDto,
+    ReportPendingDto,
+    RunStatsDto,
+    RunSummaryDto,
+    SummaryMetric,
+)
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.collaborations import DeclaredCollaborations
+from app.domains.reports.graph_export import export_graph
+from app.domains.reports.html_report import REPORT_FORMAT_VERSION, SourceReader, render_run_report
+from app.domains.reports.jplag_export import export_jplag
+from app.domains.reports.moss_export import export_moss
+from app.domains.reports.sarif_export import export_sarif
+from app.domains.reports.run_stats import STATS_FORMAT_VERSION, LanguageDetector, compute_run_stats, histogram_edges
+from app.domains.reports.summary import DEFAULT_SUMMARY_SIZE, summarize_pairs, top_pairs
+from app.domains.runs.runs_models import DetectionRun
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.storage.exceptions import (
+    InvalidStorageKeyException,
+    StorageException,
+    StoredObjectNotFoundException,
+)
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.submissions_models import Submission
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.domains.tokenization.languages import get_language_registry
+from app.domains.tokenization.streaming_source import decode_source
+from app.shared.exceptions import NotFoundException, ValidationException
+from app.shared.i18n import DEFAULT_LOCALE, Locale
+from app.shared.tenancy import project_accessible
+from app.shared.timestamps import to_rfc3339, utc_now
+from app.shared.tracing import in_span
+
+logger = logging.getLogger(__name__)
+
+REPORTS_PREFIX = "reports/"
+HTML_CONTENT_TYPE = "text/html; charset=utf-8"
+JSON_CONTENT_TYPE = "application/json"
+# Suggested delay before asking again for a report rendered in the background
+RETRY_AFTER_SECONDS = 10
+
+
+def run_reports_prefix(run) -> str:
+    """Prefix of the stored reports of a run, not found for work of another tenant"""
+    prefix = f"{REPORTS_PREFIX}{run.project_uuid}/{run.id}/"
+    if not project_accessible(run.project_uuid):
+        raise StoredObjectNotFoundException(prefix)
+    return prefix
+
+
+def delete_run_reports(storage_service: SubmissionStorageService, run) -> int:
+    """Delete the stored reports of a run, returns the number of deleted objects"""
+    return storage_service.store.delete_prefix(run_reports_prefix(run))
+
+
+def stored_source_reader(storage_service: SubmissionStorageService, submissions: Dict[str, Submission]) -> SourceReader:
+    """Reader of the latest stored version of the files of submissions by ID, None for files it cannot read"""
+    stored_paths: Dict[str, Dict[str, Optional[str]]] = {}
+
+    def read(submission_id: str, path: str) -> Optional[str]:
+        submission = submissions.get(submission_id)
+        if submission is None:
+            return None
+        try:
+            try:
+                return decode_source(storage_service.read_file(submission, path))
+            except StoredObjectNotFoundException:
+                pass
+            # Comparisons of earlier versions recorded file names without their directory, only resolved when a
+            # single stored file has the name rather than reading whichever comes first
+            if submission_id not in stored_paths:
+                names: Dict[str, Optional[str]] = {}
+                for stored in storage_service.list_files(submission):
+                    name = PurePosixPath(stored.key).name
+                    names[name] = None if name in names else stored.key
+                stored_paths[submission_id] = names
+            stored_path = stored_paths[submission_id].get(path) if "/" not in path else None
+            if stored_path is None:
+                return None
+            return decode_source(storage_service.read_file(submission, stored_path))
+        except (StorageException, StoredObjectNotFoundException, InvalidStorageKeyException) as e:
+            logger.warning(f"Cannot read {path} of submission {submission_id} for a report: {str(e)}")
+            return None
+
+    return read
+
+
+class ReportGenerationException(Exception):
+    """Raised when the background rendering of a report failed, the next request renders it again"""
+
+
+class ReportService:
+    """
+    Service rendering the self-contained HTML reports of detection runs
+
+    Reports flagging fewer than async_min_pairs pairs are rendered on request. Larger ones are rendered by the
+    report scheduler and stored in the submission store under a key covering everything the report depends on:
+    the state of the run, the threshold and the participating submissions still present. Requests get the stored
+    report once it exists and the queue position of its rendering until then. Storing a report deletes the
+    reports of previous states of the run, and the reports of a run are deleted with it.
+
+    Run statistics are computed on the first request and stored next to the reports the same way.
+    """
+
+    # Background renderings by report key, shared by the services of all requests
+    _pending: Dict[str, Future] = {}
+    _pending_lock = threading.RLock()
+
+    def __init__(
+        self,
+        session: Session,
+        storage_service: Optional[SubmissionStorageService] = None,
+        job_scheduler=None,
+        session_factory: Optional[Callable[[], Session]] = None,
+        min_similarity: Optional[float] = None,
+        max_pairs: Optional[int] = None,
+        async_min_pairs: Optional[int] = None,
+        detect_language: Optional[LanguageDetector] = None,
+    ):
+        from app.config.config import get_settings
+
+        settings = get_settings()
+        self.session = session
+        self.repository = DetectionRunRepository(session)
+        self.submission_repository = SubmissionRepository(session)
+        self.storage_service = storage_service or SubmissionStorageService()
+        if job_scheduler is None:
+            from app.shared.services import get_report_scheduler
+
+            job_scheduler = get_report_scheduler()
+        self.job_scheduler = job_scheduler
+        # Sessions of the background renderings, the request session is closed by then
+        self._session_factory = session_factory
+        self.min_similarity = settings.report_min_similarity if min_similarity is None else min_similarity
+        self.max_pairs = settings.report_max_pairs if max_pairs is None else max_pairs
+        self.async_min_pairs = settings.report_async_min_pairs if async_min_pairs is None else async_min_pairs
+        self._detect_language = detect_language
+
+    def _new_session(self) -> Session:
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    def _get_run_or_raise(self, run_id: UUID) -> DetectionRun:
+        run = self.repository.get_run(run_id)
+        if not run:
+            raise NotFoundException(f"Detection run with ID {run_id} not found")
+        return run
+
+    def get_html_report(
+        self,
+        run_id: UUID,
+        min_similarity: Optional[float] = None,
+        anonymize: bool = False,
+        locale: Locale = DEFAULT_LOCALE,
+    ) -> Union[str, ReportPendingDto]:
+        """
+        Get the HTML report of a run, or the state of its rendering while it is rendered in the background
+
+        Anonymized reports replace the identities of the run with its pseudonyms, they are stored apart like the
+        reports of each locale.
+
+        Raises:
+            NotFoundException: If the run does not exist
+            ReportGenerationException: If the background rendering of the report failed
+        """
+        run = self._get_run_or_raise(run_id)
+        threshold = self.min_similarity if min_similarity is None else min_similarity
+        submissions = self._present_submissions(run)
+
+        if self.repository.count_pairs(run_id, threshold, completed_only=True) < self.async_min_pairs:
+            return self._render(run, threshold, submissions, anonymize, locale)
+
+        key = self._report_key(run, threshold, submissions, anonymize, locale)
+        try:
+            return self.storage_service.store.get(key).decode("utf-8")
+        except StoredObjectNotFoundException:
+            return self._schedule(run, threshold, key, anonymize, locale)
+
+    def export_graph(
+        self, run_id: UUID, graph_format: GraphFormat, min_similarity: Optional[float] = None, anonymize: bool = False
+    ) -> Iterator[str]:
+        """
+        Stream the similarity graph of a run, pairs are read from the database while the stream is consumed
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        run = self._get_run_or_raise(run_id)
+        threshold = self.min_similarity if min_similarity is None else min_similarity
+        return export_graph(
+            graph_format,
+            f"run {run.id}",
+            self.repository.get_participants(run.id),
+            self.repository.iter_pair_edges(run.id, threshold),
+            threshold,
+            self._pseudonyms(run) if anonymize else None,
+        )
+
+    def export_moss(self, run_id: UUID, min_similarity: Optional[float] = None, anonymize: bool = False) -> bytes:
+        """
+        Export the flagged pairs of a run as a Moss-style ZIP archive, the most similar max_pairs of them
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        run = self._get_run_or_raise(run_id)
+        threshold = self.min_similarity if min_similarity is None else min_similarity
+        participants = self.repository.get_participants(run.id)
+        pairs, total = self.repository.get_pairs(run.id, threshold, 0, self.max_pairs, completed_only=True)
+        return export_moss(
+            run,
+            participants,
+            pairs,
+            self.repository.get_fragments_by_pairs([p.id for p in pairs]),
+            threshold,
+            omitted_pairs=total - len(pairs),
+            pseudonyms=RunPseudonyms(run.id, participants) if anonymize else None,
+        )
+
+    def export_jplag(self, run_id: UUID, min_similarity: Optional[float] = None, anonymize: bool = False) -> bytes:
+        """
+        Export the flagged pairs of a run as a JPlag result archive, the most similar max_pairs of them with the
+        sources they compared
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        run = self._get_run_or_raise(run_id)
+        threshold = self.min_similarity if min_similarity is None else min_similarity
+        participants = self.repository.get_participants(run.id)
+        pairs, _ = self.repository.get_pairs(run.id, threshold, 0, self.max_pairs, completed_only=True)
+        return export_jplag(
+            run,
+            participants,
+            pairs,
+            self.repository.get_fragments_by_pairs([p.id for p in pairs]),
+            (pair.overall_similarity for pair in self.repository.iter_completed_pairs(run.id)),
+            self._source_reader(self._present_submissions(run)),
+            threshold,
+            pseudonyms=RunPseudonyms(run.id, participants) if anonymize else None,
+        )
+
+    def export_sarif(
+        self, run_id: UUID, submission_id: UUID, min_similarity: Optional[float] = None, anonymize: bool = False
+    ) -> dict:
+        """
+        Export the flagged pairs of a submission in a run as a SARIF log located on the files of the submission
+
+        Raises:
+            NotFoundException: If the run does not exist or the submission did not take part in it
+        """
+        run = self._get_run_or_raise(run_id)
+        threshold = self.min_similarity if min_similarity is None else min_similarity
+        participants = self.repository.get_participants(run.id)
+        if str(submission_id) not in {str(p.submission_id) for p in participants}:
+            raise NotFoundException(f"Submission {submission_id} did not take part in detection run {run_id}")
+        pairs = self.repository.get_pairs_by_submission(run.id, submission_id, threshold)
+        return export_sarif(
+            run,
+            submission_id,
+            pairs,
+            self.repository.get_fragments_by_pairs([p.id for p in pairs]),
+            threshold,
+            pseudonyms=RunPseudonyms(run.id, participants) if anonymize else None,
+        )
+
+    def get_pseudonyms(self, run_id: UUID) -> RunPseudonyms:
+        """
+        Get the pseudonyms of the anonymized reports of a run
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        return self._pseudonyms(self._get_run_or_raise(run_id))
+
+    def get_pseudonym_mapping(self, run_id: UUID) -> PseudonymMappingDto:
+        """
+        Get the identity behind each pseudonym of the anonymized reports of a run
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        pseudonyms = self.get_pseudonyms(run_id)
+        return PseudonymMappingDto(run_id=run_id, pseudonyms=pseudonyms.mapping())
+
+    def _pseudonyms(self, run: DetectionRun) -> RunPseudonyms:
+        return RunPseudonyms(run.id, self.repository.get_participants(run.id))
+
+    def get_run_stats(
+        self,
+        run_id: UUID,
+        edges: Optional[List[float]] = None,
+        thresholds: Optional[List[float]] = None,
+        cluster_threshold: Optional[float] = None,
+    ) -> RunStatsDto:
+        """
+        Get the aggregated statistics of a run, computed once per state of the run and parameters
+
+        Raises:
+            NotFoundException: If the run does not exist
+            ValidationException: If a histogram edge is outside [0, 1]
+        """
+        run = self._get_run_or_raise(run_id)
+        try:
+            edges = histogram_edges(edges)
+        except ValueError as e:
+            raise ValidationException(str(e))
+        thresholds = sorted(set(thresholds)) if thresholds else None
+        cluster_threshold = self.min_similarity if cluster_threshold is None else cluster_threshold
+
+        state = self._state_digest(run, {"format": STATS_FORMAT_VERSION})
+        parameters = {"edges": edges, "thresholds": thresholds, "cluster_threshold": cluster_threshold}
+        key = f"{run_reports_prefix(run)}stats-{state}-{self._digest(parameters)}.json"
+        try:
+            return RunStatsDto.model_validate_json(self.storage_service.store.get(key))
+        except StoredObjectNotFoundException:
+            pass
+
+        participants = self.repository.get_participants(run.id)
+        stats = RunStatsDto(
+            run_id=run.id,
+            computed_at=utc_now(),
+            **compute_run_stats(
+                participants,
+                self.repository.iter_completed_pairs(run.id),
+                self._language_detector(),
+                edges,
+                thresholds,
+                cluster_threshold,
+                get_language_registry().label,
+                DeclaredCollaborations(run.declared_collaborations, participants),
+            ),
+        )
+        # Statistics of previous states of the run are never served again
+        for stored in self.storage_service.store.list(f"{run_reports_prefix(run)}stats-"):
+            if not stored.key.startswith(f"{run_reports_prefix(run)}stats-{state}-"):
+                self.storage_service.store.delete(stored.key)
+        self.storage_service.store.put(key, stats.model_dump_json().encode("utf-8"), JSON_CONTENT_TYPE)
+        return stats
+
+    def get_summary(
+        self,
+        run_id: UUID,
+        top: int = DEFAULT_SUMMARY_SIZE,
+        metric: SummaryMetric = SummaryMetric.OVERALL_SIMILARITY,
+        min_similarity: Optional[float] = None,
+    ) -> RunSummaryDto:
+        """
+        Get the top flagged pairs of a run by a metric, each with its longest matched fragment on both sides
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        run = self._get_run_or_raise(run_id)
+        threshold = self.min_similarity if min_similarity is None else min_similarity
+        declared = DeclaredCollaborations(run.declared_collaborations, self.repository.get_participants(run.id))
+        flagged = collaborations = 0
+
+        def flagged_pairs() -> Iterator:
+            nonlocal flagged, collaborations
+            for pair in self.repository.iter_completed_pairs(run.id):
+                # Pairs come most similar first, none of the next ones is flagged
+                if pair.overall_similarity < threshold:
+                    return
+                if declared(pair):
+                    collaborations += 1
+                    continue
+                flagged += 1
+                yield pair
+
+        pairs = top_pairs(flagged_pairs(), metric, top)
+        return RunSummaryDto(
+            run_id=run.id,
+            metric=metric,
+            min_similarity=threshold,
+            top=top,
+            flagged_pairs=flagged,
+            declared_collaboration_pairs=collaborations,
+            pairs=summarize_pairs(
+                pairs,
+                self.repository.get_fragments_by_pairs([p.id for p in pairs]),
+                self._source_reader(self._present_submissions(run)),
+                metric,
+            ),
+        )
+
+    def _language_detector(self) -> LanguageDetector:
+        if self._detect_language is None:
+            from app.shared.services import get_tokenization_service
+
+            tokenization_service = get_tokenization_service()
+            self._detect_language = lambda name: tokenization_service._detect_language(Path(name))
+        return self._detect_language
+
+    def _present_submissions(self, run: DetectionRun) -> Dict[str, Submission]:
+        """Participating submissions of a run that were not deleted since, by ID"""
+        participants = {str(p.submission_id) for p in self.repository.get_participants(run.id)}
+        return {
+            str(submission.id): submission
+            for submission in self.submission_repository.get_by_project_step(run.project_uuid, run.project_step_uuid)
+            if str(submission.id) in participants
+        }
+
+    @staticmethod
+    def _digest(values: dict) -> str:
+        return hashlib.sha256(json.dumps(values, sort_keys=True).encode("utf-8")).hexdigest()[:16]
+
+    def _state_digest(self, run: DetectionRun, values: dict) -> str:
+        """Digest of the state of a run and of values its stored content depends on"""
+        state = {
+            "status": getattr(run.status, "value", run.status),
+            "completed_pairs": run.completed_pairs,
+            "failed_pairs": run.failed_pairs,
+            "finished_at": to_rfc3339(run.finished_at),
+            "declared_collaborations": run.declared_collaborations or [],
+            **values,
+        }
+        return self._digest(state)
+
+    def _report_state(self, run: DetectionRun, submissions: Dict[str, Submission]) -> str:
+        """Digest of what every report of a run depends on, its threshold and options aside"""
+        return self._state_digest(
+            run,
+            {
+                "format": REPORT_FORMAT_VERSION,
+                "submissions": sorted(submissions),
+                "late": self._late(submissions),
+                "metrics": self._metrics(submissions),
+            },
+        )
+
+    def _report_key(
+        self,
+        run: DetectionRun,
+        threshold: float,
+        submissions: Dict[str, Submission],
+        anonymize: bool = False,
+        locale: Locale = DEFAULT_LOCALE,
+    ) -> str:
+        state = self._report_state(run, submissions)
+        options = self._digest(
+            {"threshold": threshold, "max_pairs": self.max_pairs, "anonymize": anonymize, "locale": locale.value}
+        )
+        return f"{run_reports_prefix(run)}report-{state}-{options}.html"
+
+    def _render(
+        self,
+        run: DetectionRun,
+        threshold: float,
+        submissions: Dict[str, Submission],
+        anonymize: bool = False,
+        locale: Locale = DEFAULT_LOCALE,
+    ) -> str:
+        participants = self.repository.get_participants(run.id)
+        pairs, total = self.repository.get_pairs(run.id, threshold, 0, self.max_pairs, completed_only=True)
+        return render_run_report(
+            run,
+            participants,
+            pairs,
+            self.repository.get_fragments_by_pairs([p.id for p in pairs]),
+            self._source_reader(submissions),
+            threshold,
+            omitted_pairs=total - len(pairs),
+            pseudonyms=RunPseudonyms(run.id, participants) if anonymize else None,
+            late=self._late(submissions),
+            locale=locale,
+            declared=DeclaredCollaborations(run.declared_collaborations, participants),
+            timed_out_pairs=self.repository.get_timed_out_pairs(run.id),
+            metrics=self._metrics(submissions),
+        )
+
+    def _metrics(self, submissions: Dict[str, Submission]) -> Dict[str, dict]:
+        """Code metrics of the submissions computed so far by ID, none when they cannot be read"""
+        try:
+            records = self.submission_repository.get_metrics_by_submissions(
+                [submission.id for submission in submissions.values()]
+            )
+        except Exception as e:
+            logger.warning(f"Code metrics left out of the report: {e}")
+            return {}
+        return {str(submission_id): record.metrics for submission_id, record in records.items()}
+
+    @staticmethod
+    def _late(submissions: Dict[str, Submission]) -> Dict[str, int]:
+        """Minutes after the deadline of the late submissions, by ID"""
+        return {
+            submission_id: submission.minutes_late
+            for submission_id, submission in submissions.items()
+            if getattr(submission, "is_late", False) and submission.minutes_late is not None
+        }
+
+    def _source_reader(self, submissions: Dict[str, Submission]) -> SourceReader:
+        return stored_source_reader(self.storage_service, submissions)
+
+    def _schedule(
+        self,
+        run: DetectionRun,
+        threshold: float,
+        key: str,
+        anonymize: bool = False,
+        locale: Locale = DEFAULT_LOCALE,
+    ) -> ReportPendingDto:
+        """Queue the rendering of a report unless it is already queued or rendering"""
+        with self._pending_lock:
+            future = self._pending.get(key)
+            if future is not None and future.done():
+                # Only failures stay pending once done, reported once then retried
+                del self._pending[key]
+                error = future.exception()
+                if error is not None:
+                    raise ReportGenerationException(f"Failed to render the report of run {run.id}: {str(error)}")
+                future = None
+            if future is None:
+                future = self.job_scheduler.submit(
+                    in_span(self._render_in_background, run_id=run.id),
+                    run.id,
+                    threshold,
+                    key,
+                    anonymize,
+                    locale,
+                    job_id=key,
+                )
+                self._pending[key] = future
+                future.add_done_callback(lambda done: self._forget(key, done))
+
+        queued = self.job_scheduler.status(key)
+        return ReportPendingDto(
+            run_id=run.id,
+            status="queued" if queued else "rendering",
+            position=queued.position if queued else None,
+            retry_after_seconds=RETRY_AFTER_SECONDS,
+        )
+
+    @classmethod
+    def _forget(cls, key: str, future: Future) -> None:
+        if future.cancelled() or future.exception() is None:
+            with cls._pending_lock:
+                if cls._pending.get(key) is future:
+                    del cls._pending[key]
+
+    def _render_in_background(
+        self, run_id: UUID, threshold: float, key: str, anonymize: bool = False, locale: Locale = DEFAULT_LOCALE
+    ) -> None:
+        try:
+            with self._new_session() as session:
+                service = ReportService(
+                    session,
+                    self.storage_service,
+                    self.job_scheduler,
+                    self._session_factory,
+                    self.min_similarity,
+                    self.max_pairs,
+                    self.async_min_pairs,
+                    self._detect_language,
+                )
+                run = service._get_run_or_raise(run_id)
+                submissions = service._present_submissions(run)
+                html = service._render(run, threshold, submissions, anonymize, locale)
+                prefix = run_reports_prefix(run)
+                state = service._report_state(run, submissions)
+
+            # Reports of previous states of the run are never served again
+            for stored in self.storage_service.store.list(f"{prefix}report-"):
+                if not stored.key.startswith(f"{prefix}report-{state}-"):
+                    self.storage_service.store.delete(stored.key)
+            self.storage_service.store.put(key, html.encode("utf-8"), HTML_CONTENT_TYPE)
+            logger.info(f"Stored HTML report of run {run_id}, {len(html)} characters")
+        except Exception as e:
+            logger.error(f"Failed to render the report of run {run_id}: {str(e)}")
+            raise
@@ -0,0 +1,239 @@
+from typing import List, Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, HTTPException, Query
+from fastapi.responses import HTMLResponse, JSONResponse, Response, StreamingResponse
+from sqlmodel import Session
+
+from app.domains.audit.audit_hook import audited
+from app.domains.audit.audit_models import AuditAction
+from app.domains.reports.dto.report_dto import GraphFormat, ReportPendingDto, RunStatsDto, RunSummaryDto, SummaryMetric
+from app.domains.reports.graph_export import GRAPH_FILE_EXTENSIONS, GRAPH_MEDIA_TYPES
+from app.domains.reports.report_service import ReportGenerationException, ReportService
+from app.domains.reports.summary import DEFAULT_SUMMARY_SIZE
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException, ValidationException
+from app.shared.i18n import Locale, get_locale
+
+router = APIRouter(prefix="/runs", tags=["reports"])
+
+
+def get_report_service(session: Session = Depends(get_session)) -> ReportService:
+    """Dependency to get report service"""
+    return ReportService(session)
+
+
+@router.get(
+    "/{run_id}/report.html",
+    response_class=HTMLResponse,
+    responses={202: {"model": ReportPendingDto}},
+    dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))],
+)
+async def get_run_html_report(
+    run_id: UUID,
+    min_similarity: Optional[float] = Query(
+        None, ge=0.0, le=1.0, description="Flag pairs at or above this similarity, REPORT_MIN_SIMILARITY by default"
+    ),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    locale: Locale = Depends(get_locale),
+    service: ReportService = Depends(get_report_service),
+):
+    """
+    Get a single-file HTML report of a run, to open without the frontend: sortable table of the flagged pairs,
+    clusters of submissions linked by flagged pairs and side-by-side highlighted views of the shared fragments
+
+    Reports flagging REPORT_ASYNC_MIN_PAIRS pairs or more are rendered in the background and stored, until then
+    the request answers 202 with the queue position of the rendering and a Retry-After header.
+
+    With **anonymize**, submitters, submissions and groups are shown as "Student 017", "Submission 003" and
+    "Group 002", and user names of home directories in paths and code are scrubbed.
+
+    The text of the report is in English or French, from the **locale** parameter or the Accept-Language header.
+    """
+    try:
+        report = service.get_html_report(run_id, min_similarity, anonymize, locale)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except (DatabaseException, ReportGenerationException) as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    if isinstance(report, ReportPendingDto):
+        return JSONResponse(
+            status_code=202,
+            content=report.model_dump(mode="json"),
+            headers={"Retry-After": str(report.retry_after_seconds)},
+        )
+    return HTMLResponse(report, headers={"Content-Language": locale.value, "Vary": "Accept-Language"})
+
+
+@router.get("/{run_id}/graph", dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))])
+async def export_run_graph(
+    run_id: UUID,
+    format: GraphFormat = Query(GraphFormat.DOT, description="Export format, dot or graphml"),
+    min_similarity: Optional[float] = Query(
+        None, ge=0.0, le=1.0, description="Keep pairs at or above this similarity, REPORT_MIN_SIMILARITY by default"
+    ),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: ReportService = Depends(get_report_service),
+):
+    """
+    Export the similarity graph of a run for graph tools: one node per participating submission with its cluster,
+    one edge per completed pair at or above the threshold with its similarity and matched token count
+    """
+    try:
+        content = service.export_graph(run_id, format, min_similarity, anonymize)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    return StreamingResponse(
+        content,
+        media_type=GRAPH_MEDIA_TYPES[format],
+        headers={"Content-Disposition": f'attachment; filename="run-{run_id}.{GRAPH_FILE_EXTENSIONS[format]}"'},
+    )
+
+
+@router.get("/{run_id}/moss.zip", dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))])
+async def export_run_moss(
+    run_id: UUID,
+    min_similarity: Optional[float] = Query(
+        None, ge=0.0, le=1.0, description="Keep pairs at or above this similarity, REPORT_MIN_SIMILARITY by default"
+    ),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: ReportService = Depends(get_report_service),
+):
+    """
+    Export the flagged pairs of a run as a ZIP archive of Moss-style result pages: index.html with the percent
+    matched of each side and the lines matched of each pair, match<N>.html with the matched line ranges of pair N
+
+    Percentages are token-based, the mapping to the line-based percentages of Moss is noted at the top of the index.
+    """
+    try:
+        archive = service.export_moss(run_id, min_similarity, anonymize)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    return Response(
+        archive,
+        media_type="application/zip",
+        headers={"Content-Disposition": f'attachment; filename="run-{run_id}-moss.zip"'},
+    )
+
+
+@router.get("/{run_id}/export/jplag", dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))])
+async def export_run_jplag(
+    run_id: UUID,
+    min_similarity: Optional[float] = Query(
+        None, ge=0.0, le=1.0, description="Keep pairs at or above this similarity, REPORT_MIN_SIMILARITY by default"
+    ),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: ReportService = Depends(get_report_service),
+):
+    """
+    Export the flagged pairs of a run as a JPlag result archive for the JPlag report viewer: overview.json with the
+    pair scores and clusters, a comparison file per pair with its matched line ranges and the compared sources
+
+    Fields JPlag has and our results do not, like the match sensitivity, get documented defaults.
+    """
+    try:
+        archive = service.export_jplag(run_id, min_similarity, anonymize)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    return Response(
+        archive,
+        media_type="application/zip",
+        headers={"Content-Disposition": f'attachment; filename="run-{run_id}-jplag.zip"'},
+    )
+
+
+@router.get("/{run_id}/export/sarif", dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))])
+async def export_run_sarif(
+    run_id: UUID,
+    submission: UUID = Query(..., description="Submission whose files the findings are located on"),
+    min_similarity: Optional[float] = Query(
+        None, ge=0.0, le=1.0, description="Keep pairs at or above this similarity, REPORT_MIN_SIMILARITY by default"
+    ),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: ReportService = Depends(get_report_service),
+):
+    """
+    Export the flagged pairs of a submission as a SARIF 2.1.0 log for code review tools: a "high-similarity-pair"
+    result per pair and a "matched-fragment" result per shared block, located on the files of the submission with
+    1-based line regions
+    """
+    try:
+        log = service.export_sarif(run_id, submission, min_similarity, anonymize)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    return JSONResponse(
+        log,
+        media_type="application/sarif+json",
+        headers={"Content-Disposition": f'attachment; filename="run-{run_id}-{submission}.sarif"'},
+    )
+
+
+@router.get("/{run_id}/stats", response_model=RunStatsDto)
+async def get_run_stats(
+    run_id: UUID,
+    edges: Optional[List[float]] = Query(
+        None, description="Edges of the score histogram buckets, repeated; 0 and 1 are added when missing"
+    ),
+    thresholds: Optional[List[float]] = Query(None, description="Similarities to count the pairs at or above of"),
+    min_similarity: Optional[float] = Query(
+        None, ge=0.0, le=1.0, description="Link clusters at or above this similarity, REPORT_MIN_SIMILARITY by default"
+    ),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: ReportService = Depends(get_report_service),
+):
+    """
+    Get the aggregated statistics of a run for dashboards: score histogram, pairs at or above thresholds, clusters,
+    languages of the compared files and the highest similarity of each submission, self-matches excluded
+
+    Statistics are computed on the first request and stored until the run changes.
+    """
+    try:
+        stats = service.get_run_stats(run_id, edges, thresholds, min_similarity)
+        if anonymize:
+            return JSONResponse(service.get_pseudonyms(run_id).anonymize(stats.model_dump(mode="json")))
+        return stats
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except ValidationException as e:
+        raise HTTPException(status_code=422, detail=e.detail)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/{run_id}/summary", response_model=RunSummaryDto)
+async def get_run_summary(
+    run_id: UUID,
+    top: int = Query(DEFAULT_SUMMARY_SIZE, ge=1, le=100, description="Number of pairs in the summary"),
+    metric: SummaryMetric = Query(SummaryMetric.OVERALL_SIMILARITY, description="Metric the pairs are ranked by"),
+    min_similarity: Optional[float] = Query(
+        None, ge=0.0, le=1.0, description="Overall similarity of flagged pairs, REPORT_MIN_SIMILARITY by default"
+    ),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: ReportService = Depends(get_report_service),
+):
+    """
+    Get the most suspicious flagged pairs of a run with their submitters, their most similar file pair and the
+    source text of their longest matched fragment on both sides
+    """
+    try:
+        summary = service.get_summary(run_id, top, metric, min_similarity)
+        if anonymize:
+            return JSONResponse(service.get_pseudonyms(run_id).anonymize(summary.model_dump(mode="json")))
+        return summary
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
@@ -0,0 +1,187 @@
+"""
+Aggregated statistics of a detection run
+
+Statistics are computed from the completed pairs of the run. Self-matches, pairs of a submission with itself, with
+another submission of its group or with another submission of one of its students, are suppressed: they are counted
+apart and left out of every other statistic, so a student resubmitting their own work never tops the dashboard. The
+students of a submission are its submitter and the members its team had when it was submitted, so two teams of a
+student are never compared against each other either. Pairs of declared collaborations are counted apart and left
+out the same way.
+"""
+
+from bisect import bisect_right
+from typing import Callable, Dict, Iterable, List, Optional, Sequence, Set
+
+from app.domains.reports.clusters import find_clusters
+from app.domains.reports.dto.report_dto import (
+    ClusterStatsDto,
+    HistogramBucketDto,
+    LanguageStatsDto,
+    SubmissionMaxScoreDto,
+    ThresholdCountDto,
+)
+
+# Part of the cache key of stored statistics, to bump whenever the computed content changes
+STATS_FORMAT_VERSION = 4
+DEFAULT_HISTOGRAM_EDGES = (0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0)
+DEFAULT_THRESHOLDS = (0.5, 0.7, 0.8, 0.9)
+
+# File name -> language
+LanguageDetector = Callable[[str], str]
+
+
+def histogram_edges(edges: Optional[Iterable[float]] = None) -> List[float]:
+    """
+    Sorted bucket edges between 0 and 1, both added when missing so that the buckets cover every score
+
+    Raises:
+        ValueError: If an edge is outside [0, 1]
+    """
+    values = sorted(set(edges)) if edges else list(DEFAULT_HISTOGRAM_EDGES)
+    if values[0] < 0.0 or values[-1] > 1.0:
+        raise ValueError("Histogram edges must be between 0 and 1")
+    if values[0] > 0.0:
+        values.insert(0, 0.0)
+    if values[-1] < 1.0:
+        values.append(1.0)
+    return values
+
+
+def participant_members(participant) -> Set[str]:
+    """Students of a participating submission, its submitter and the members of its team when it was submitted"""
+    members = {str(member) for member in getattr(participant, "team_members", None) or []}
+    if participant.submitted_by_uuid is not None:
+        members.add(str(participant.submitted_by_uuid))
+    return members
+
+
+def is_self_match(pair, groups: Dict[str, object], members: Optional[Dict[str, Set[str]]] = None) -> bool:
+    """
+    Whether a pair compares a submission with itself, another of its group or another of one of its students
+
+    Args:
+        pair: The pair
+        groups: Group of each participating submission by ID
+        members: Students of each participating submission by ID, see participant_members, only their submitters
+            are compared without it
+    """
+    first, second = str(pair.submission_id), str(pair.compared_submission_id)
+    if first == second:
+        return True
+    group = groups.get(first)
+    if group is not None and group == groups.get(second):
+        return True
+    if pair.submitted_by_uuid is not None and pair.submitted_by_uuid == pair.compared_submitted_by_uuid:
+        return True
+    return bool(members and members.get(first, set()) & members.get(second, set()))
+
+
+def compute_run_stats(
+    participants: Sequence,
+    pairs: Iterable,
+    detect_language: LanguageDetector,
+    edges: Optional[Iterable[float]] = None,
+    thresholds: Optional[Iterable[float]] = None,
+    cluster_threshold: float = 0.5,
+    language_label: Optional[Callable[[str], str]] = None,
+    declared: Optional[Callable[[object], bool]] = None,
+) -> dict:
+    """
+    Statistics of the pairs of a run, as the fields of RunStatsDto without the run ones
+
+    Args:
+        participants: Participants of the run
+        pairs: Completed pairs of the run
+        detect_language: Language of a compared file from its name
+        edges: Edges of the histogram buckets, DEFAULT_HISTOGRAM_EDGES by default
+        thresholds: Similarities to count the pairs at or above of, DEFAULT_THRESHOLDS by default
+        cluster_threshold: Similarity at or above which pairs link submissions into clusters
+        language_label: Name of a language in reports, languages have no label without it
+        declared: Whether a pair is a declared collaboration, see DeclaredCollaborations, none is without it
+    """
+    bucket_edges = histogram_edges(edges)
+    threshold_values = sorted(set(thresholds)) if thresholds else list(DEFAULT_THRESHOLDS)
+    groups = {str(p.submission_id): p.group_uuid for p in participants}
+    members = {str(p.submission_id): participant_members(p) for p in participants}
+
+    counts = [0] * (len(bucket_edges) - 1)
+    kept = []
+    suppressed = 0
+    collaborations = 0
+    best: Dict[str, tuple] = {}
+    pair_counts: Dict[str, int] = {}
+    files: Dict[str, Dict[str, int]] = {}
+
+    for pair in pairs:
+        compared = pair.compared_files or {}
+        for submission_id, side in ((pair.submission_id, "submission1"), (pair.compared_submission_id, "submission2")):
+            files.setdefault(str(submission_id), {}).update(compared.get(side) or {})
+
+        if is_self_match(pair, groups, members):
+            suppressed += 1
+            continue
+        if declared is not None and declared(pair):
+            collaborations += 1
+            continue
+        kept.append(pair)
+        counts[min(bisect_right(bucket_edges, pair.overall_similarity) - 1, len(counts) - 1)] += 1
+        for own, other in (
+            (pair.submission_id, pair.compared_submission_id),
+            (pair.compared_submission_id, pair.submission_id),
+        ):
+            key = str(own)
+            pair_counts[key] = pair_counts.get(key, 0) + 1
+            if key not in best or pair.overall_similarity > best[key][0]:
+                best[key] = (pair.overall_similarity, other)
+
+    languages: Dict[str, dict] = {}
+    for submission_id, submission_files in files.items():
+        for name, tokens in submission_files.items():
+            language = languages.setdefault(detect_language(name), {"submissions": set(), "files": 0, "tokens": 0})
+            language["submissions"].add(submission_id)
+            language["files"] += 1
+            language["tokens"] += tokens or 0
+
+    clusters = find_clusters(kept, cluster_threshold)
+    submissions = [
+        SubmissionMaxScoreDto(
+            submission_id=participant.submission_id,
+            submitted_by_uuid=participant.submitted_by_uuid,
+            max_similarity=best[str(participant.submission_id)][0] if str(participant.submission_id) in best else None,
+            most_similar_submission_id=best.get(str(participant.submission_id), (None, None))[1],
+            pairs=pair_counts.get(str(participant.submission_id), 0),
+        )
+        for participant in participants
+    ]
+    submissions.sort(key=lambda s: (s.max_similarity is None, -(s.max_similarity or 0.0), str(s.submission_id)))
+
+    return {
+        "pair_count": len(kept),
+        "suppressed_pairs": suppressed,
+        "declared_collaboration_pairs": collaborations,
+        "histogram": [
+            HistogramBucketDto(lower=bucket_edges[i], upper=bucket_edges[i + 1], count=count)
+            for i, count in enumerate(counts)
+        ],
+        "thresholds": [
+            ThresholdCountDto(threshold=t, count=sum(1 for p in kept if p.overall_similarity >= t))
+            for t in threshold_values
+        ],
+        "clusters": ClusterStatsDto(
+            threshold=cluster_threshold, count=len(clusters), sizes=[len(c.members) for c in clusters]
+        ),
+        "languages": sorted(
+            (
+                LanguageStatsDto(
+                    language=name,
+                    label=language_label(name) if language_label else None,
+                    submissions=len(value["submissions"]),
+                    files=value["files"],
+                    tokens=value["tokens"],
+                )
+                for name, value in languages.items()
+            ),
+            key=lambda language: (-language.files, language.language),
+        ),
+        "submissions": submissions,
+    }
@@ -0,0 +1,159 @@
+"""
+SARIF 2.1.0 export of the findings of a run for one submission
+
+The log is written from the perspective of one submission so that its locations map onto the files of one
+repository: each flagged pair of the submission is a "high-similarity-pair" result located on its files of the
+most similar file pairs, and each shared block a "matched-fragment" result whose region is the matched lines on
+its side. The other submission of a pair only appears in messages and properties, never as a location. Regions
+are 1-based as SARIF requires, while our fragment rows are 0-based. Results are sorted, so the same run always
+gives the same log.
+"""
+
+from pathlib import PurePosixPath
+from typing import Any, Dict, List, Optional
+from urllib.parse import quote
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.runs.runs_models import FragmentType
+
+SARIF_VERSION = "2.1.0"
+SARIF_SCHEMA = "https://docs.oasis-open.org/sarif/sarif/v2.1.0/errata01/os/schemas/sarif-schema-2.1.0.json"
+TOOL_NAME = "PAMP similarity detection"
+# Base of the artifact URIs, the root of the repository of the submission
+SOURCE_ROOT = "SRCROOT"
+PAIR_RULE = "high-similarity-pair"
+FRAGMENT_RULE = "matched-fragment"
+RULES = [
+    {
+        "id": PAIR_RULE,
+        "name": "HighSimilarityPair",
+        "shortDescription": {"text": "Submission highly similar to another submission"},
+        "fullDescription": {
+            "text": "The overall similarity of the submission with another submission of the same project step is at "
+            "or above the flagging threshold."
+        },
+        "defaultConfiguration": {"level": "warning"},
+    },
+    {
+        "id": FRAGMENT_RULE,
+        "name": "MatchedFragment",
+        "shortDescription": {"text": "Code block matching a block of another submission"},
+        "fullDescription": {"text": "Lines of the submission matched by a shared block of a flagged pair."},
+        "defaultConfiguration": {"level": "note"},
+    },
+]
+
+
+def artifact_location(path: str) -> Dict[str, str]:
+    """Artifact location of a file of the submission, relative to its repository root"""
+    return {"uri": quote(str(PurePosixPath(path.replace("\\", "/"))).lstrip("/")), "uriBaseId": SOURCE_ROOT}
+
+
+def _region(start: int, end: Optional[int]) -> Dict[str, int]:
+    return {"startLine": start + 1, "endLine": max(start, end if end is not None else start) + 1}
+
+
+def export_sarif(
+    run,
+    submission_id,
+    pairs: List,
+    fragments: Dict[str, List],
+    threshold: float,
+    pseudonyms: Optional[RunPseudonyms] = None,
+) -> Dict[str, Any]:
+    """
+    SARIF log of the flagged pairs of a submission
+
+    Args:
+        run: DetectionRun of the export
+        submission_id: Submission whose files the results are located on
+        pairs: Flagged pairs involving the submission, on either side
+        fragments: Fragments of each pair by pair ID
+        threshold: Similarity at or above which pairs are flagged
+        pseudonyms: Pseudonyms of the run replacing identities and home directories everywhere, None to keep them
+    """
+
+    def identity(value) -> Optional[str]:
+        if value is None:
+            return None
+        return pseudonyms(value) if pseudonyms is not None else str(value)
+
+    def scrub(text: str) -> str:
+        return pseudonyms.scrub(text) if pseudonyms is not None else text
+
+    results = []
+    for pair in sorted(pairs, key=lambda p: (-p.overall_similarity, str(p.id))):
+        # Whether the submission is the first side of the pair
+        own_first = str(pair.submission_id) == str(submission_id)
+        other = identity(pair.compared_submission_id if own_first else pair.submission_id)
+        pair_fragments = fragments.get(str(pair.id), [])
+        properties = {"pairId": str(pair.id), "comparedSubmission": other, "similarity": pair.overall_similarity}
+
+        file_fragments = [f for f in pair_fragments if f.fragment_type == FragmentType.FILE]
+        files = sorted({f.file1_path if own_first else f.file2_path for f in file_fragments})
+        results.append(
+            {
+                "ruleId": PAIR_RULE,
+                "ruleIndex": 0,
+                "level": "warning",
+                "message": {
+                    "text": scrub(f"Overall similarity of {pair.overall_similarity:.0%} with submission {other}.")
+                },
+                "locations": [
+                    {"physicalLocation": {"artifactLocation": artifact_location(scrub(path))}} for path in files
+                ],
+                "partialFingerprints": {"pairId/v1": str(pair.id)},
+                "properties": properties,
+            }
+        )
+
+        blocks = []
+        for fragment in pair_fragments:
+            own = (fragment.file1_path, fragment.file1_start_line, fragment.file1_end_line)
+            theirs = (fragment.file2_path, fragment.file2_start_line, fragment.file2_end_line)
+            if not own_first:
+                own, theirs = theirs, own
+            if fragment.fragment_type != FragmentType.BLOCK or own[1] is None:
+                continue
+            blocks.append((own, theirs, fragment))
+        for own, theirs, fragment in sorted(blocks, key=lambda b: (b[0][0], b[0][1], b[1][0], b[1][1] or 0)):
+            region = _region(own[1], own[2])
+            matched = "code"
+            if theirs[1] is not None:
+                their_region = _region(theirs[1], theirs[2])
+                matched = f"lines {their_region['startLine']}-{their_region['endLine']}"
+            results.append(
+                {
+                    "ruleId": FRAGMENT_RULE,
+                    "ruleIndex": 1,
+                    "level": "note",
+                    "message": {
+                        "text": scrub(
+                            f"Lines {region['startLine']}-{region['endLine']} match {matched} of {theirs[0]} in "
+                            f"submission {other} ({fragment.similarity:.0%})."
+                        )
+                    },
+                    "locations": [
+                        {"physicalLocation": {"artifactLocation": artifact_location(scrub(own[0])), "region": region}}
+                    ],
+                    "partialFingerprints": {"fragmentId/v1": str(fragment.id)},
+                    "properties": {
+                        **properties,
+                        "fragmentSimilarity": fragment.similarity,
+                        "matchedTokens": (fragment.details or {}).get("matched_tokens"),
+                    },
+                }
+            )
+
+    return {
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [
+            {
+                "tool": {"driver": {"name": TOOL_NAME, "version": run.detection_version, "rules": RULES}},
+                "automationDetails": {"id": f"similarity/{run.id}/{identity(submission_id)}"},
+                "results": results,
+                "properties": {"runId": str(run.id), "submission": identity(submission_id), "threshold": threshold},
+            }
+        ],
+    }
@@ -0,0 +1,155 @@
+"""
+Summary of the most suspicious pairs of a detection run
+
+The flagged pairs are ranked by a metric, and each pair of the top comes with its most similar file pair and the
+source text of its longest matched fragment on both sides, read with the excerpts of the HTML report. Ties are
+broken by overall similarity, then by submission IDs and pair ID, so the same run always gives the same summary.
+"""
+
+import heapq
+from typing import Dict, Iterable, List, Optional
+
+from app.domains.reports.dto.report_dto import EvidenceDto, FilePairDto, SnippetDto, SummaryMetric, SuspiciousPairDto
+from app.domains.reports.html_report import CodeExcerpt, SourceExcerpts, SourceReader, plain_lines
+from app.domains.runs.runs_models import FragmentType
+
+DEFAULT_SUMMARY_SIZE = 10
+# Lines of source text kept per side of an evidence fragment
+MAX_EVIDENCE_LINES = 40
+
+
+def metric_value(pair, metric: SummaryMetric) -> Optional[float]:
+    """Value of a ranking metric for a pair, None when it was not recorded"""
+    return getattr(pair, metric.value, None)
+
+
+def _rank_key(pair, metric: SummaryMetric) -> tuple:
+    score = metric_value(pair, metric)
+    return (
+        score is None,
+        -(score or 0),
+        -pair.overall_similarity,
+        str(pair.submission_id),
+        str(pair.compared_submission_id),
+        str(pair.id),
+    )
+
+
+def top_pairs(pairs: Iterable, metric: SummaryMetric, top: int) -> List:
+    """The top pairs by a metric, highest first, pairs without a value for it last"""
+    return heapq.nsmallest(top, pairs, key=lambda pair: _rank_key(pair, metric))
+
+
+def line_span(start: Optional[int], end: Optional[int]) -> int:
+    """Lines of a 0-based inclusive range of one side of a fragment, 0 when it has no location"""
+    if start is None:
+        return 0
+    return max(start, end if end is not None else start) - start + 1
+
+
+def fragment_lines(fragment) -> int:
+    """Lines of a fragment, both sides added"""
+    return line_span(fragment.file1_start_line, fragment.file1_end_line) + line_span(
+        fragment.file2_start_line, fragment.file2_end_line
+    )
+
+
+def longest_fragment(fragments: Iterable):
+    """The block fragment spanning the most lines, the most similar one among equals, None without located block"""
+    blocks = [f for f in fragments if f.fragment_type != FragmentType.FILE and fragment_lines(f) > 0]
+    if not blocks:
+        return None
+    return min(
+        blocks,
+        key=lambda f: (
+            -fragment_lines(f),
+            -f.similarity,
+            f.file1_path,
+            f.file1_start_line if f.file1_start_line is not None else -1,
+            f.file2_path,
+            f.file2_start_line if f.file2_start_line is not None else -1,
+            str(f.id),
+        ),
+    )
+
+
+def best_file_pair(fragments: Iterable) -> Optional[FilePairDto]:
+    """The most similar file fragment of a pair, by path among equals"""
+    files = [f for f in fragments if f.fragment_type == FragmentType.FILE]
+    if not files:
+        return None
+    best = min(files, key=lambda f: (-f.similarity, f.file1_path, f.file2_path))
+    return FilePairDto(file1_path=best.file1_path, file2_path=best.file2_path, similarity=best.similarity)
+
+
+def _snippet(excerpt: CodeExcerpt) -> SnippetDto:
+    if not excerpt.available or not excerpt.lines:
+        return SnippetDto(path=excerpt.path, available=False)
+    return SnippetDto(
+        path=excerpt.path,
+        start_line=excerpt.lines[0][0],
+        end_line=excerpt.lines[-1][0],
+        text="\n".join(line for _, line in excerpt.lines),
+        truncated=excerpt.truncated,
+    )
+
+
+def summarize_pairs(
+    pairs: List, fragments: Dict[str, List], read_source: SourceReader, metric: SummaryMetric
+) -> List[SuspiciousPairDto]:
+    """
+    Summary entries of ranked pairs
+
+    Args:
+        pairs: Pairs of the summary, highest ranked first
+        fragments: Fragments of each pair by pair ID
+        read_source: Reader of the submission files the fragments point to
+        metric: Metric the pairs were ranked by
+    """
+    sources = SourceExcerpts(read_source, plain_lines)
+    summary = []
+    for rank, pair in enumerate(pairs, 1):
+        pair_fragments = fragments.get(str(pair.id), [])
+        evidence = None
+        fragment = longest_fragment(pair_fragments)
+        if fragment is not None:
+            details = fragment.details or {}
+            evidence = EvidenceDto(
+                similarity=fragment.similarity,
+                lines=fragment_lines(fragment),
+                file1_function=details.get("file1_function"),
+                file2_function=details.get("file2_function"),
+                left=_snippet(
+                    sources.excerpt(
+                        pair.submission_id,
+                        fragment.file1_path,
+                        fragment.file1_start_line,
+                        fragment.file1_end_line,
+                        MAX_EVIDENCE_LINES,
+                    )
+                ),
+                right=_snippet(
+                    sources.excerpt(
+                        pair.compared_submission_id,
+                        fragment.file2_path,
+                        fragment.file2_start_line,
+                        fragment.file2_end_line,
+                        MAX_EVIDENCE_LINES,
+                    )
+                ),
+            )
+        summary.append(
+            SuspiciousPairDto(
+                rank=rank,
+                pair_id=pair.id,
+                submission_id=pair.submission_id,
+                compared_submission_id=pair.compared_submission_id,
+                submitted_by_uuid=pair.submitted_by_uuid,
+                compared_submitted_by_uuid=pair.compared_submitted_by_uuid,
+                score=metric_value(pair, metric),
+                overall_similarity=pair.overall_similarity,
+                best_file_pair=best_file_pair(pair_fragments),
+                evidence=evidence,
+            )
+        )
+    return summary
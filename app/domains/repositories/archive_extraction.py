@@ -0,0 +1,224 @@
+"""
+Archive Extraction
+Extracts submission archives whose entry names were written by any system or tool.
+
+Names are decoded then sanitized to names every file system and store can address, a lossy mapping:
+- names without the UTF-8 flag are decoded as UTF-8 when valid, else as Windows-1252 when that gives Latin
+  letters only, else as CP437, the encoding of the ZIP specification; decoded names are NFC normalized
+- backslashes are separators, control characters and <>:"|?* are replaced by _
+- trailing spaces and dots are stripped and segments left empty become _
+- reserved device names (CON, PRN, AUX, NUL, COM1 to COM9, LPT1 to LPT9) get a _ prefix, with or without extension
+- segments longer than MAX_SEGMENT_BYTES are cut, keeping their extension
+- a path taken by an earlier entry gets a ~N suffix before its extension, ARCHIVE_NAMES_FILE is always taken
+
+Entries with '..' segments, paths still longer than MAX_PATH_BYTES and entries that cannot be read are skipped
+with a warning, the rest of the archive is extracted. The raw name of every renamed entry is kept in
+ARCHIVE_NAMES_FILE at the root of the extracted project, which storage records in the manifest of the version.
+Only archives extracted here have one, storage reads it from no other directory.
+"""
+
+import json
+import logging
+import re
+import shutil
+import unicodedata
+import zipfile
+import zlib
+from dataclasses import dataclass, field
+from pathlib import Path, PurePosixPath
+from typing import Dict, List, Set
+from urllib.parse import quote
+
+logger = logging.getLogger(__name__)
+
+# Names of the extracted entries that were renamed, and of the skipped ones, relative to the project root
+ARCHIVE_NAMES_FILE = ".pamp-archive-names"
+# Bytes of a path segment, the limit of most file systems
+MAX_SEGMENT_BYTES = 255
+# Bytes of a sanitized path, the key length limit of S3
+MAX_PATH_BYTES = 1024
+# Extensions longer than this are cut with the rest of a long segment
+MAX_EXTENSION_BYTES = 16
+
+# Bit 11 of the general purpose flags, set when the entry name is UTF-8
+UTF8_FLAG = 0x800
+_INVALID_CHARACTERS = re.compile(r'[\x00-\x1f\x7f<>:"|?*]')
+_RESERVED_NAMES = {"CON", "PRN", "AUX", "NUL"} | {f"{device}{n}" for device in ("COM", "LPT") for n in range(1, 10)}
+
+
+class UnusableEntryName(Exception):
+    """An entry name that no sanitization can turn into a safe path"""
+
+    def __init__(self, name: str, reason: str):
+        self.name = name
+        self.reason = reason
+        super().__init__(f"Unusable archive entry '{name}': {reason}")
+
+
+@dataclass(frozen=True)
+class ArchiveEntryName:
+    """Original name of an extracted entry"""
+
+    name: str  # decoded, before sanitization
+    raw: bytes  # as stored in the archive
+    encoding: str  # encoding the raw name was decoded with
+
+    def to_metadata(self) -> dict:
+        """Entry of the file metadata, the raw name percent-encoded so it survives JSON"""
+        return {"name": self.name, "raw": quote(self.raw, safe="/ "), "encoding": self.encoding}
+
+
+@dataclass
+class ExtractionReport:
+    """Entries of an extracted archive by sanitized path"""
+
+    extracted: Dict[str, ArchiveEntryName] = field(default_factory=dict)
+    skipped: List[dict] = field(default_factory=list)
+
+    @property
+    def renamed(self) -> Dict[str, ArchiveEntryName]:
+        """Entries whose path is not their raw name"""
+        return {path: entry for path, entry in self.extracted.items() if path.encode("utf-8") != entry.raw}
+
+    def write(self, root: Path, extract_path: Path) -> None:
+        """Write the renamed and skipped entries under root, paths relative to it, nothing if there are none"""
+        prefix = root.relative_to(extract_path).as_posix() + "/" if root != extract_path else ""
+        renamed = {
+            path[len(prefix) :]: entry.to_metadata()
+            for path, entry in self.renamed.items()
+            if path.startswith(prefix)
+        }
+        if renamed or self.skipped:
+            names = {"renamed": renamed, "skipped": self.skipped}
+            (root / ARCHIVE_NAMES_FILE).write_text(json.dumps(names, sort_keys=True), encoding="utf-8")
+
+
+def read_archive_names(directory: Path) -> dict:
+    """Renamed and skipped entries written at the root of an extracted project, empty for other directories"""
+    try:
+        names = json.loads((directory / ARCHIVE_NAMES_FILE).read_text(encoding="utf-8"))
+    except (OSError, ValueError):
+        return {"renamed": {}, "skipped": []}
+    return {"renamed": names.get("renamed") or {}, "skipped": names.get("skipped") or []}
+
+
+def decode_entry_name(info: zipfile.ZipInfo) -> ArchiveEntryName:
+    """Decode the name of an entry, from its raw bytes unless flagged UTF-8"""
+    if info.flag_bits & UTF8_FLAG:
+        return ArchiveEntryName(unicodedata.normalize("NFC", info.orig_filename), info.orig_filename.encode(), "utf-8")
+
+    # zipfile decodes unflagged names as CP437, which maps every byte and gives the raw name back
+    raw = info.orig_filename.encode("cp437")
+    for encoding in ("utf-8", "cp1252"):
+        try:
+            name = raw.decode(encoding)
+        except UnicodeDecodeError:
+            continue
+        if encoding == "utf-8" or all(c.isascii() or "LATIN" in unicodedata.name(c, "") for c in name):
+            return ArchiveEntryName(unicodedata.normalize("NFC", name), raw, encoding)
+    return ArchiveEntryName(raw.decode("cp437"), raw, "cp437")
+
+
+def sanitize_segment(segment: str) -> str:
+    """Name of one path segment that every file system accepts"""
+    segment = _INVALID_CHARACTERS.sub("_", segment).rstrip(" .")
+    if len(segment.encode("utf-8")) > MAX_SEGMENT_BYTES:
+        suffix = PurePosixPath(segment).suffix
+        if len(suffix.encode("utf-8")) > MAX_EXTENSION_BYTES:
+            suffix = ""
+        stem = segment[: len(segment) - len(suffix)].encode("utf-8")
+        stem = stem[: MAX_SEGMENT_BYTES - len(suffix.encode("utf-8"))].decode("utf-8", errors="ignore")
+        segment = (stem.rstrip(" .") or "_") + suffix
+    if not segment:
+        return "_"
+    if segment.split(".")[0].rstrip(" ").upper() in _RESERVED_NAMES:
+        return "_" + segment
+    return segment
+
+
+def sanitize_path(name: str) -> str:
+    """
+    Safe relative path of an entry name
+
+    Raises:
+        UnusableEntryName: If the name escapes the archive or is too long even once sanitized
+    """
+    segments = [segment for segment in name.replace("\\", "/").split("/") if segment not in ("", ".")]
+    if any(segment == ".." for segment in segments):
+        raise UnusableEntryName(name, "parent directory segments are not allowed")
+    if not segments:
+        raise UnusableEntryName(name, "name is empty")
+    path = reserve_names_file("/".join(sanitize_segment(segment) for segment in segments))
+    if len(path.encode("utf-8")) > MAX_PATH_BYTES:
+        raise UnusableEntryName(name, f"path is longer than {MAX_PATH_BYTES} bytes")
+    return path
+
+
+def reserve_names_file(path: str) -> str:
+    """Path, or path~N when named ARCHIVE_NAMES_FILE, which only the extractor writes"""
+    if path.rpartition("/")[2] != ARCHIVE_NAMES_FILE:
+        return path
+    return unique_path(path, {path})
+
+
+def unique_path(path: str, taken: Set[str]) -> str:
+    """Path, or the first path~N not taken, the suffix before the extension"""
+    if path not in taken:
+        return path
+    parent, _, segment = path.rpartition("/")
+    suffix = PurePosixPath(segment).suffix
+    stem = segment[: len(segment) - len(suffix)]
+    number = 1
+    while True:
+        candidate = f"{parent + '/' if parent else ''}{stem}~{number}{suffix}"
+        if candidate not in taken:
+            return candidate
+        number += 1
+
+
+def _parents(path: str) -> List[str]:
+    parts = path.split("/")
+    return ["/".join(parts[:index]) for index in range(1, len(parts))]
+
+
+def extract_zip(zip_path, extract_path: Path) -> ExtractionReport:
+    """
+    Extract the files of a ZIP archive under sanitized names
+
+    Raises:
+        zipfile.BadZipFile: If the archive itself cannot be read
+    """
+    report = ExtractionReport()
+    files: Set[str] = set()
+    directories: Set[str] = set()
+    with zipfile.ZipFile(zip_path, "r") as zip_ref:
+        for info in zip_ref.infolist():
+            if info.is_dir():
+                continue
+            entry = decode_entry_name(info)
+            try:
+                path = sanitize_path(entry.name)
+                if any(parent in files for parent in _parents(path)):
+                    raise UnusableEntryName(entry.name, "a parent directory has the name of a file")
+                path = unique_path(path, files | directories)
+            except UnusableEntryName as e:
+                logger.warning(str(e))
+                report.skipped.append({"name": entry.name, "raw": entry.to_metadata()["raw"], "reason": e.reason})
+                continue
+
+            target = extract_path / path
+            try:
+                target.parent.mkdir(parents=True, exist_ok=True)
+                with zip_ref.open(info) as source, open(target, "wb") as destination:
+                    shutil.copyfileobj(source, destination)
+            except (zipfile.BadZipFile, zlib.error, RuntimeError, NotImplementedError, OSError) as e:
+                logger.warning(f"Skipping unreadable archive entry '{entry.name}': {str(e)}")
+                target.unlink(missing_ok=True)
+                report.skipped.append({"name": entry.name, "raw": entry.to_metadata()["raw"], "reason": str(e)})
+                continue
+
+            files.add(path)
+            directories.update(_parents(path))
+            report.extracted[path] = entry
+    return report
+
@@ -1,5 +1,7 @@
 """Repository-specific exceptions for better error handling"""
 
+from fastapi import status
+
 from app.shared.exceptions import ValidationException
 
 
@@ -25,18 +27,56 @@ class UnsupportedRepositoryException(RepositoryFetchException):
         )
 
 
-class GitRepositoryException(RepositoryFetchException):
-    """Base exception for Git repository operations"""
+# Git errors of a network or a server that may answer a moment later, retried
+TRANSIENT_GIT_ERRORS = (
+    "could not resolve host",
+    "connection reset",
+    "connection refused",
+    "network is unreachable",
+    "connection timed out",
+    "operation timed out",
+    "failed to connect",
+    "couldn't connect",
+    "early eof",
+    "rpc failed",
+    "unexpected disconnect",
+    "remote end hung up unexpectedly",
+    "the requested url returned error: 5",
+    "the requested url returned error: 429",
+    "internal server error",
+    "bad gateway",
+    "service unavailable",
+    "gateway timeout",
+)
+
+
+def is_transient_git_error(git_error: str) -> bool:
+    """Whether the output of a failed git command is that of a failure that may pass"""
+    output = (git_error or "").lower()
+    return any(error in output for error in TRANSIENT_GIT_ERRORS)
 
-    def __init__(self, message: str, source_type: str, source_url: str, git_error: str = None, details: dict = None):
+
+class GitRepositoryException(RepositoryFetchException):
+    """Base exception for Git repository operations, transient when attempting it again may succeed"""
+
+    def __init__(
+        self,
+        message: str,
+        source_type: str,
+        source_url: str,
+        git_error: str = None,
+        details: dict = None,
+        transient: bool = False,
+    ):
         super().__init__(message, source_type, source_url, details)
         self.git_error = git_error
+        self.transient = transient
 
 
 class GitCloneException(GitRepositoryException):
     """Raised when git clone operation fails"""
 
-    def __init__(self, url: str, source_type: str, git_error: str, return_code: int = None):
+    def __init__(self, url: str, source_type: str, git_error: str, return_code: int = None, transient: bool = False):
         message = f"Failed to clone {source_type} repository: {url}"
         if git_error:
             message += f". Git error: {git_error}"
@@ -53,6 +93,7 @@ class GitCloneException(GitRepositoryException):
                 "git_error": git_error,
                 "return_code": return_code,
             },
+            transient=transient,
         )
 
 
@@ -71,6 +112,7 @@ class GitTimeoutException(GitRepositoryException):
                 "url": url,
                 "timeout_seconds": timeout_seconds,
             },
+            transient=True,
         )
 
 
@@ -83,6 +125,30 @@ class S3FetchException(RepositoryFetchException):
         self.key = key
 
 
+class FetchSuspendedException(RepositoryFetchException):
+    """
+    Raised instead of fetching from a host whose circuit breaker is open after repeated transient failures, answered
+    with 503 and Retry-After since the same submission should succeed later
+    """
+
+    def __init__(self, source_url: str, source_type: str, host: str, retry_after_seconds: float):
+        message = f"Fetches from {host} are suspended after repeated failures, retry in {retry_after_seconds:.0f}s"
+        super().__init__(
+            message,
+            source_type,
+            source_url,
+            details={
+                "error_type": "fetch_suspended",
+                "url": source_url,
+                "host": host,
+                "retry_after_seconds": round(retry_after_seconds),
+            },
+        )
+        self.status_code = status.HTTP_503_SERVICE_UNAVAILABLE
+        self.headers = {"Retry-After": str(max(round(retry_after_seconds), 1))}
+        self.host = host
+
+
 class S3ConfigurationException(S3FetchException):
     """Raised when S3 configuration is invalid"""
 
@@ -173,6 +239,32 @@ class S3ExtractionException(S3FetchException):
         )
 
 
+class MalwareDetectedException(RepositoryFetchException):
+    """Raised when the malware scanner finds a signature in an upload or one of its files"""
+
+    def __init__(self, source_url: str, signature: str, file_name: str = None):
+        message = f"Upload rejected, malware found in {file_name or source_url}: {signature}"
+        super().__init__(
+            message,
+            source_url=source_url,
+            details={"error_type": "malware_detected", "url": source_url, "file": file_name, "signature": signature},
+        )
+        self.signature = signature
+        self.file_name = file_name
+
+
+class MalwareScannerUnavailableException(RepositoryFetchException):
+    """Raised when an upload cannot be scanned and the scanner fails closed"""
+
+    def __init__(self, source_url: str, scanner_error: str):
+        message = f"Upload rejected, it could not be scanned for malware: {scanner_error}"
+        super().__init__(
+            message,
+            source_url=source_url,
+            details={"error_type": "malware_scanner_unavailable", "url": source_url, "scanner_error": scanner_error},
+        )
+
+
 class SubmissionValidationException(ValidationException):
     """Raised when submission validation fails"""
 
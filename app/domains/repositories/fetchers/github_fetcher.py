@@ -2,7 +2,12 @@ import logging
 import subprocess
 from pathlib import Path
 
-from app.domains.repositories.exceptions import GitCloneException, GitTimeoutException, UnsupportedRepositoryException
+from app.domains.repositories.exceptions import (
+    GitCloneException,
+    GitTimeoutException,
+    UnsupportedRepositoryException,
+    is_transient_git_error,
+)
 
 logger = logging.getLogger(__name__)
 
@@ -139,22 +144,23 @@ class GithubFetcher:
             if result.returncode != 0:
                 error_output = result.stderr.strip() if result.stderr else "Unknown error"
                 logger.error(f"Git clone failed for {repo_url}: {error_output}")
+                transient = is_transient_git_error(error_output)
 
                 # Analyze error for more specific messages
                 if "not found" in error_output.lower() or "repository not found" in error_output.lower():
                     raise GitCloneException(
-                        repo_url, "github", "Repository not found or access denied", result.returncode
+                        repo_url, "github", "Repository not found or access denied", result.returncode, transient
                     )
                 elif "authentication" in error_output.lower() or "permission denied" in error_output.lower():
                     raise GitCloneException(
-                        repo_url, "github", "Authentication failed or access denied", result.returncode
+                        repo_url, "github", "Authentication failed or access denied", result.returncode, transient
                     )
                 elif "network" in error_output.lower() or "could not resolve" in error_output.lower():
                     raise GitCloneException(
-                        repo_url, "github", "Network error or DNS resolution failed", result.returncode
+                        repo_url, "github", "Network error or DNS resolution failed", result.returncode, transient
                     )
                 else:
-                    raise GitCloneException(repo_url, "github", error_output, result.returncode)
+                    raise GitCloneException(repo_url, "github", error_output, result.returncode, transient)
 
             # Verify the clone was successful
             if not repo_path.exists():
@@ -11,7 +11,11 @@ except ImportError:
     boto3 = None
 
 from app.config.config import get_settings
+from app.domains.repositories.archive_extraction import ExtractionReport, extract_zip, reserve_names_file
 from app.domains.repositories.exceptions import (
+    FetchSuspendedException,
+    MalwareDetectedException,
+    MalwareScannerUnavailableException,
     S3BucketException,
     S3ConfigurationException,
     S3CredentialsException,
@@ -19,6 +23,8 @@ from app.domains.repositories.exceptions import (
     S3FetchException,
     S3ObjectException,
 )
+from app.domains.repositories.malware_scanner import create_upload_scanner
+from app.shared.retries import CircuitOpenError, retrier
 
 logger = logging.getLogger(__name__)
 
@@ -39,6 +45,7 @@ class S3Fetcher:
             self.aws_access_key_id = settings.aws_access_key_id
             self.aws_secret_access_key = settings.aws_secret_access_key
             self.aws_default_region = settings.aws_default_region
+            self.upload_scanner = create_upload_scanner(settings)
 
             logger.debug(f"S3Fetcher initialized successfully with region: {self.aws_default_region}")
         except Exception as e:
@@ -58,6 +65,9 @@ class S3Fetcher:
 
         Raises:
             S3FetchException: If download/extraction fails
+            FetchSuspendedException: If downloads from S3 are suspended by its circuit breaker
+            MalwareDetectedException: If the upload, or one of its files, is infected
+            MalwareScannerUnavailableException: If the upload could not be scanned and scanning fails closed
         """
         temp_file_path = None
         extract_path = None
@@ -75,17 +85,25 @@ class S3Fetcher:
 
                 logger.info(f"Downloading from S3: {s3_url}")
 
-                # Download file from S3
+                # Download file from S3, again while it fails transiently
                 try:
-                    s3_client.download_file(bucket_name, object_key, temp_file_path)
+                    retrier("s3").call(
+                        self._endpoint_host(), lambda: s3_client.download_file(bucket_name, object_key, temp_file_path)
+                    )
                     logger.debug(f"Download completed: {temp_file_path}")
                 except ClientError as e:
                     self._handle_s3_client_error(e, bucket_name, object_key, s3_url)
+                except CircuitOpenError as e:
+                    raise FetchSuspendedException(s3_url, "s3", e.host, e.retry_after_seconds)
 
                 # Verify download
                 if not Path(temp_file_path).exists() or Path(temp_file_path).stat().st_size == 0:
                     raise S3ObjectException(bucket_name, object_key, s3_url, "Downloaded file is empty or missing")
 
+                # Scan the upload before anything is extracted from it
+                if self.upload_scanner is not None:
+                    self.upload_scanner.scan_file(Path(temp_file_path), s3_url, Path(object_key).name)
+
                 # Extract the content
                 extract_path = Path(temp_dir) / self._get_extract_folder_name(object_key)
                 try:
@@ -99,42 +117,44 @@ class S3Fetcher:
                 # Check if it's a zip file and extract
                 try:
                     if self._is_zip_file(temp_file_path):
-                        self._extract_zip(temp_file_path, extract_path)
+                        report = self._extract_zip(temp_file_path, extract_path)
                         logger.debug(f"Extracted ZIP file to: {extract_path}")
 
                         # Check if we need to use a nested directory as the project root
                         project_root = self._get_project_root_path(extract_path)
+                        report.write(project_root, extract_path)
                         if project_root != extract_path:
                             logger.info(f"Using nested directory as project root: {project_root}")
                             extract_path = project_root
                     else:
                         # If not a zip, just copy the file
-                        target_file = extract_path / Path(object_key).name
+                        target_file = extract_path / reserve_names_file(Path(object_key).name)
                         target_file.write_bytes(Path(temp_file_path).read_bytes())
                         logger.debug(f"Copied file to: {target_file}")
                 except Exception as e:
                     raise S3ExtractionException(s3_url, str(e), bucket_name, object_key)
 
+                # Scan the extracted files too, for archives the daemon cannot look into
+                if self.upload_scanner is not None and self.upload_scanner.scan_extracted_files:
+                    self.upload_scanner.scan_directory(extract_path, s3_url)
+
                 logger.info(f"Successfully downloaded and extracted S3 content to: {extract_path}")
                 return extract_path
 
         except NoCredentialsError as e:
             logger.error(f"AWS credentials error for {s3_url}: {str(e)}")
             raise S3CredentialsException(s3_url)
-        except (S3FetchException, S3ConfigurationException, S3CredentialsException):
+        except (MalwareDetectedException, MalwareScannerUnavailableException):
+            # Nothing of a refused upload is kept
+            self._remove_extraction(extract_path)
+            raise
+        except (S3FetchException, S3ConfigurationException, S3CredentialsException, FetchSuspendedException):
             # Re-raise our custom exceptions
             raise
         except Exception as e:
             logger.error(f"Unexpected error fetching S3 content from {s3_url}: {str(e)}")
             # Clean up on unexpected errors
-            if extract_path and extract_path.exists():
-                try:
-                    import shutil
-
-                    shutil.rmtree(extract_path, ignore_errors=True)
-                    logger.debug(f"Cleaned up partial extraction: {extract_path}")
-                except Exception:
-                    pass
+            self._remove_extraction(extract_path)
             raise S3FetchException(f"Unexpected error fetching S3 content: {str(e)}", s3_url)
         finally:
             # Clean up temporary file
@@ -145,6 +165,17 @@ class S3Fetcher:
                 except Exception as e:
                     logger.warning(f"Failed to clean up temporary file {temp_file_path}: {str(e)}")
 
+    def _remove_extraction(self, extract_path: Path) -> None:
+        """Remove a partial or refused extraction"""
+        if extract_path and extract_path.exists():
+            try:
+                import shutil
+
+                shutil.rmtree(extract_path, ignore_errors=True)
+                logger.debug(f"Cleaned up partial extraction: {extract_path}")
+            except Exception:
+                pass
+
     def _parse_s3_url(self, s3_url: str) -> tuple[str, str]:
         """Parse S3 URL into bucket and object key"""
         try:
@@ -168,6 +199,10 @@ class S3Fetcher:
                 raise
             raise S3ConfigurationException(f"Failed to parse S3 URL: {str(e)}", s3_url)
 
+    def _endpoint_host(self) -> str:
+        """Host of the S3 endpoint of the configured region, guarded by its circuit breaker"""
+        return f"s3.{getattr(self, 'aws_default_region', 'us-east-1')}.amazonaws.com"
+
     def _get_s3_client(self, s3_url: str = None):
         """Get configured S3 client"""
         try:
@@ -226,22 +261,19 @@ class S3Fetcher:
         except zipfile.BadZipFile:
             return False
 
-    def _extract_zip(self, zip_path: str, extract_path: Path):
-        """Extract ZIP file to specified path"""
+    def _extract_zip(self, zip_path: str, extract_path: Path) -> ExtractionReport:
+        """Extract ZIP file to specified path, entries under sanitized names and unusable ones skipped"""
         try:
-            with zipfile.ZipFile(zip_path, "r") as zip_ref:
-                # Validate ZIP file structure
-                zip_ref.testzip()
-
-                # Extract all contents
-                zip_ref.extractall(extract_path)
+            report = extract_zip(zip_path, extract_path)
 
-                # Verify extraction
-                extracted_files = list(extract_path.rglob("*"))
-                if not extracted_files:
-                    raise Exception("No files were extracted from ZIP")
+            # Verify extraction
+            if not report.extracted:
+                raise Exception("No files were extracted from ZIP")
 
-                logger.debug(f"Extracted {len(extracted_files)} items from ZIP to: {extract_path}")
+            logger.debug(f"Extracted {len(report.extracted)} files from ZIP to: {extract_path}")
+            if report.skipped:
+                logger.warning(f"Skipped {len(report.skipped)} unusable entries of {zip_path}")
+            return report
 
         except zipfile.BadZipFile as e:
             logger.error(f"Invalid ZIP file {zip_path}: {str(e)}")
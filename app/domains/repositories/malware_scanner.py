@@ -0,0 +1,172 @@
+"""
+Malware Scanning
+Streams uploads to a ClamAV daemon with the clamd INSTREAM command, before they are extracted or stored.
+
+The command is sent as zINSTREAM followed by a null byte, then the content in chunks each prefixed by its length
+as 4 bytes in network order, ended by a chunk of length 0. clamd answers "stream: OK", "stream: <signature> FOUND"
+or "<reason> ERROR", terminated by a null byte as the command was prefixed by z. Uploads larger than the
+StreamMaxLength of the daemon are answered with an error, which the policy of the deployment handles like an
+unreachable daemon.
+"""
+
+import logging
+import socket
+import struct
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Iterable, Iterator, Optional
+
+from app.config.config import Settings
+from app.domains.repositories.exceptions import MalwareDetectedException, MalwareScannerUnavailableException
+from app.shared.metrics import MALWARE_SCANS
+
+logger = logging.getLogger(__name__)
+
+# Bytes sent per INSTREAM chunk
+CHUNK_SIZE = 64 * 1024
+# Longest reply read from the daemon
+MAX_REPLY_BYTES = 4096
+
+
+class ScannerError(Exception):
+    """The daemon could not be reached or gave no verdict"""
+
+
+@dataclass(frozen=True)
+class ScanResult:
+    """Verdict of the daemon, the signature found or None when clean"""
+
+    signature: Optional[str] = None
+
+    @property
+    def infected(self) -> bool:
+        return self.signature is not None
+
+
+def parse_reply(reply: bytes) -> ScanResult:
+    """Verdict of an INSTREAM reply, raises ScannerError for an error or an unknown reply"""
+    text = reply.rstrip(b"\0").decode("utf-8", errors="replace").strip()
+    if text.startswith("stream: "):
+        text = text[len("stream: ") :]
+    if text == "OK":
+        return ScanResult()
+    if text.endswith(" FOUND"):
+        return ScanResult(text[: -len(" FOUND")].strip())
+    raise ScannerError(f"clamd answered: {text or 'nothing'}")
+
+
+class ClamdScanner:
+    """Client of the INSTREAM command of clamd over TCP, one connection per scan"""
+
+    def __init__(
+        self, host: str = "localhost", port: int = 3310, timeout_seconds: float = 30.0, chunk_size: int = CHUNK_SIZE
+    ):
+        self.host = host
+        self.port = port
+        self.timeout_seconds = timeout_seconds
+        self.chunk_size = max(chunk_size, 1)
+
+    def scan_stream(self, chunks: Iterable[bytes]) -> ScanResult:
+        """Stream content to the daemon and return its verdict, raises ScannerError when it gives none"""
+        try:
+            with socket.create_connection((self.host, self.port), timeout=self.timeout_seconds) as connection:
+                connection.sendall(b"zINSTREAM\0")
+                for chunk in chunks:
+                    if chunk:
+                        connection.sendall(struct.pack("!L", len(chunk)) + chunk)
+                connection.sendall(struct.pack("!L", 0))
+                reply = self._read_reply(connection)
+        except OSError as e:
+            raise ScannerError(f"clamd at {self.host}:{self.port} cannot be reached: {str(e) or type(e).__name__}")
+        return parse_reply(reply)
+
+    def scan_file(self, path: Path) -> ScanResult:
+        return self.scan_stream(self._read_chunks(path))
+
+    def _read_chunks(self, path: Path) -> Iterator[bytes]:
+        with open(path, "rb") as file:
+            while chunk := file.read(self.chunk_size):
+                yield chunk
+
+    @staticmethod
+    def _read_reply(connection: socket.socket) -> bytes:
+        reply = b""
+        while b"\0" not in reply and len(reply) < MAX_REPLY_BYTES:
+            data = connection.recv(MAX_REPLY_BYTES)
+            if not data:
+                break
+            reply += data
+        return reply
+
+
+class UploadScanner:
+    """
+    Scanning of uploads under the policy of the deployment
+
+    An infected upload is always refused. One that cannot be scanned is refused when failing closed, the default,
+    and accepted with a warning when failing open. Every verdict is counted in pamp_malware_scans_total.
+    """
+
+    def __init__(self, scanner: ClamdScanner, fail_open: bool = False, scan_extracted_files: bool = False):
+        self.scanner = scanner
+        self.fail_open = fail_open
+        self.scan_extracted_files = scan_extracted_files
+
+    def scan_file(self, path: Path, source_url: str, name: Optional[str] = None) -> Optional[ScanResult]:
+        """
+        Scan one file of an upload
+
+        Returns:
+            The clean verdict, None when the file could not be scanned and the scanner fails open
+
+        Raises:
+            MalwareDetectedException: If a signature is found
+            MalwareScannerUnavailableException: If the file could not be scanned and the scanner fails closed
+        """
+        name = name or path.name
+        try:
+            result = self.scanner.scan_file(path)
+        except ScannerError as e:
+            MALWARE_SCANS.labels("error").inc()
+            if self.fail_open:
+                logger.warning(f"Malware scan of {name} from {source_url} skipped, failing open: {str(e)}")
+                return None
+            logger.error(f"Malware scan of {name} from {source_url} failed, upload refused: {str(e)}")
+            raise MalwareScannerUnavailableException(source_url, str(e))
+
+        if result.infected:
+            MALWARE_SCANS.labels("infected").inc()
+            logger.warning(f"Malware {result.signature} found in {name} from {source_url}, upload refused")
+            raise MalwareDetectedException(source_url, result.signature, name)
+
+        MALWARE_SCANS.labels("clean").inc()
+        logger.debug(f"Malware scan of {name} from {source_url}: clean")
+        return result
+
+    def scan_directory(self, root: Path, source_url: str) -> int:
+        """
+        Scan every file of an extracted upload, named relative to its root, returns how many were scanned
+
+        Files that could not be scanned when failing open are skipped with a warning each, the others still scanned.
+        """
+        scanned = 0
+        for path in sorted(root.rglob("*")):
+            if not path.is_file() or path.is_symlink():
+                continue
+            if self.scan_file(path, source_url, path.relative_to(root).as_posix()) is not None:
+                scanned += 1
+        return scanned
+
+
+def create_upload_scanner(settings: Settings) -> Optional[UploadScanner]:
+    """Build the upload scanner from the settings, None when scanning is disabled"""
+    if not settings.malware_scan_enabled:
+        return None
+    scanner = ClamdScanner(
+        settings.malware_scan_clamd_host,
+        settings.malware_scan_clamd_port,
+        timeout_seconds=settings.malware_scan_timeout_seconds,
+    )
+    return UploadScanner(
+        scanner, fail_open=settings.malware_scan_fail_open, scan_extracted_files=settings.malware_scan_extracted_files
+    )
@@ -1,8 +1,11 @@
 import logging
 import tempfile
 from pathlib import Path
+from typing import Optional
+from urllib.parse import urlparse
 
 from app.domains.repositories.exceptions import (
+    FetchSuspendedException,
     RepositoryFetchException,
     SubmissionValidationException,
     TemporaryDirectoryException,
@@ -12,6 +15,7 @@ from app.domains.repositories.fetchers.github_fetcher import GithubFetcher
 from app.domains.repositories.fetchers.gitlab_fetcher import GitlabFetcher
 from app.domains.repositories.fetchers.s3_fetcher import S3Fetcher
 from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.shared.retries import CircuitOpenError, retrier
 
 logger = logging.getLogger(__name__)
 
@@ -38,6 +42,16 @@ def cleanup_temp_directory(repo_path: Path) -> None:
         raise TemporaryDirectoryException("cleanup", str(e), str(repo_path))
 
 
+def git_host(project_url: str) -> str:
+    """Host a repository is cloned from: of its URL, of git@host:path, github.com for user/repo"""
+    url = project_url.strip()
+    if url.startswith("git@"):
+        return url[len("git@") :].split(":", 1)[0].lower()
+    if "://" not in url:
+        return "github.com"
+    return (urlparse(url).hostname or "").lower()
+
+
 class SubmissionFetcher:
     """Unified fetcher for submissions from different sources"""
 
@@ -103,12 +117,12 @@ class SubmissionFetcher:
                 f"Fetching {project_type} submission: {project_url} (Project: {submission.project_uuid}, Group: {submission.group_uuid})"
             )
 
-            # Fetch from appropriate source
+            # Fetch from appropriate source, clones failing transiently are attempted again
             try:
                 if project_type == "github":
-                    result_path = self.github_fetcher.clone_github_repo(project_url, temp_dir)
+                    result_path = self._clone(self.github_fetcher.clone_github_repo, project_url, temp_dir)
                 elif project_type == "gitlab":
-                    result_path = self.gitlab_fetcher.clone_gitlab_repo(project_url, temp_dir)
+                    result_path = self._clone(self.gitlab_fetcher.clone_gitlab_repo, project_url, temp_dir)
                 elif project_type == "s3":
                     result_path = self.s3_fetcher.fetch_s3_content(project_url, temp_dir)
                 else:
@@ -152,6 +166,15 @@ class SubmissionFetcher:
                 source_url=project_url if "project_url" in locals() else submission.link if submission else None,
             )
 
+    def _clone(self, clone, project_url: str, temp_dir: str) -> Path:
+        """Clone a repository with the retry policy and the circuit breaker of its host"""
+        host = git_host(project_url)
+        try:
+            return retrier("git").call(host, lambda: clone(project_url, temp_dir))
+        except CircuitOpenError as e:
+            project_type = self._determine_project_type(project_url)
+            raise FetchSuspendedException(project_url, project_type, host, e.retry_after_seconds)
+
     def _create_temp_directory(self, project_url: str) -> str:
         """Create a temporary directory for submission fetching"""
         # Create a safe prefix from the URL
@@ -229,3 +252,42 @@ class SubmissionFetcher:
             List of supported project types
         """
         return ["github", "gitlab", "s3"]
+
+
+class FetchedSubmission:
+    """
+    Files of a submission being uploaded, fetched on first use and shared by its rules, malware scan, language policy
+    and storage until closed
+
+    A failed fetch is not attempted again, later uses raise its exception.
+    """
+
+    def __init__(self, fetcher: SubmissionFetcher, submission: CreateSubmissionDto):
+        self.fetcher = fetcher
+        self.submission = submission
+        self._path: Optional[Path] = None
+        self._error: Optional[Exception] = None
+
+    def path(self) -> Path:
+        """Directory of the fetched files"""
+        if self._error is not None:
+            raise self._error
+        if self._path is None:
+            try:
+                self._path = self.fetcher.fetch_submission(self.submission)
+            except Exception as e:
+                self._error = e
+                raise
+        return self._path
+
+    def close(self) -> None:
+        """Remove the fetched files"""
+        if self._path is not None and self._path.exists():
+            cleanup_temp_directory(self._path)
+        self._path = None
+
+    def __enter__(self) -> "FetchedSubmission":
+        return self
+
+    def __exit__(self, *exc_info) -> None:
+        self.close()
@@ -0,0 +1 @@
+# Retention domain package
@@ -0,0 +1,17 @@
+from .retention_dto import (
+    LegalHoldDto,
+    LegalHoldResponseDto,
+    ProjectPurgeDto,
+    RetentionPolicyDto,
+    RetentionPolicyResponseDto,
+    RetentionPurgeReportDto,
+)
+
+__all__ = [
+    "RetentionPolicyDto",
+    "RetentionPolicyResponseDto",
+    "LegalHoldDto",
+    "LegalHoldResponseDto",
+    "ProjectPurgeDto",
+    "RetentionPurgeReportDto",
+]
@@ -0,0 +1,86 @@
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class RetentionPolicyDto(BaseModel):
+    """DTO for creating or replacing the retention policy of a project"""
+
+    model_config = ConfigDict(
+        json_schema_extra={"example": {"submission_retention_days": 548, "report_retention_days": 1826}}
+    )
+
+    submission_retention_days: Optional[int] = Field(
+        default=None, ge=1, description="Days submissions are kept, forever if omitted"
+    )
+    report_retention_days: Optional[int] = Field(
+        default=None, ge=1, description="Days detection runs are kept, forever if omitted"
+    )
+
+
+class RetentionPolicyResponseDto(RetentionPolicyDto):
+    """DTO for reading the retention policy of a project"""
+
+    model_config = ConfigDict(from_attributes=True)
+
+    project_uuid: UUID
+    created_at: UtcTimestamp
+    updated_at: Optional[UtcTimestamp] = None
+
+
+class LegalHoldDto(BaseModel):
+    """DTO for placing or lifting a legal hold"""
+
+    model_config = ConfigDict(
+        json_schema_extra={"example": {"legal_hold": True, "reason": "Disciplinary case 2024-017"}}
+    )
+
+    legal_hold: bool = Field(description="Whether the resource is exempt from retention purges")
+    reason: Optional[str] = Field(default=None, max_length=500, description="Reason of the legal hold")
+
+
+class LegalHoldResponseDto(BaseModel):
+    """DTO for the legal hold state of a resource"""
+
+    resource_type: str
+    resource_id: UUID
+    legal_hold: bool
+    reason: Optional[str] = None
+
+
+class ProjectPurgeDto(BaseModel):
+    """DTO for what a purge removed (or would remove) from one project"""
+
+    project_uuid: UUID
+    submission_cutoff: Optional[UtcTimestamp] = None
+    report_cutoff: Optional[UtcTimestamp] = None
+    deleted_submissions: List[UUID] = []
+    deleted_runs: List[UUID] = []
+    held_submissions: List[UUID] = []
+    held_runs: List[UUID] = []
+    errors: List[str] = []
+
+
+class RetentionPurgeReportDto(BaseModel):
+    """DTO for the outcome of a retention purge"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "dry_run": True,
+                "executed_at": "2026-01-15T03:00:00+01:00",
+                "total_deleted_submissions": 42,
+                "total_deleted_runs": 7,
+                "projects": [],
+            }
+        }
+    )
+
+    dry_run: bool
+    executed_at: UtcTimestamp
+    total_deleted_submissions: int
+    total_deleted_runs: int
+    projects: List[ProjectPurgeDto]
@@ -0,0 +1,134 @@
+from typing import Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, HTTPException, Query, Request
+from sqlmodel import Session
+
+from app.domains.audit.audit_hook import audited
+from app.domains.audit.audit_models import AuditAction
+from app.domains.retention.dto.retention_dto import (
+    LegalHoldDto,
+    LegalHoldResponseDto,
+    RetentionPolicyDto,
+    RetentionPolicyResponseDto,
+    RetentionPurgeReportDto,
+)
+from app.domains.retention.retention_service import RetentionService
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException
+from app.shared.security import require_admin_scope
+
+router = APIRouter(prefix="/retention", tags=["retention"])
+
+
+def get_retention_service(session: Session = Depends(get_session)) -> RetentionService:
+    """Dependency to get retention service"""
+    return RetentionService(session)
+
+
+def is_performed_purge(request: Request) -> bool:
+    """Whether a purge request deletes, dry runs are not audited"""
+    return request.query_params.get("dry_run", "true").lower() in ("false", "0", "no", "off")
+
+
+@router.get("/projects/{project_uuid}/policy", response_model=RetentionPolicyResponseDto)
+async def get_retention_policy(project_uuid: UUID, service: RetentionService = Depends(get_retention_service)):
+    """Get the retention policy of a project"""
+    try:
+        return service.get_policy(project_uuid)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put(
+    "/projects/{project_uuid}/policy",
+    response_model=RetentionPolicyResponseDto,
+    dependencies=[Depends(require_admin_scope), Depends(audited(AuditAction.CONFIG_CHANGE, "retention_policy"))],
+)
+async def set_retention_policy(
+    project_uuid: UUID, policy_data: RetentionPolicyDto, service: RetentionService = Depends(get_retention_service)
+):
+    """
+    Create or replace the retention policy of a project
+
+    - **submission_retention_days**: Days submissions and their files are kept (forever if omitted)
+    - **report_retention_days**: Days detection runs and their pairs are kept (forever if omitted)
+    """
+    try:
+        return service.set_policy(project_uuid, policy_data)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.delete(
+    "/projects/{project_uuid}/policy",
+    status_code=204,
+    dependencies=[Depends(require_admin_scope), Depends(audited(AuditAction.CONFIG_CHANGE, "retention_policy"))],
+)
+async def delete_retention_policy(project_uuid: UUID, service: RetentionService = Depends(get_retention_service)):
+    """Delete the retention policy of a project, its resources are then kept forever"""
+    try:
+        service.delete_policy(project_uuid)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post(
+    "/purge",
+    response_model=RetentionPurgeReportDto,
+    dependencies=[
+        Depends(require_admin_scope),
+        Depends(audited(AuditAction.SUBMISSION_DELETE, "retention_purge", when=is_performed_purge)),
+    ],
+)
+async def purge_expired_resources(
+    dry_run: bool = Query(True, description="Only report what would be deleted"),
+    project_uuid: Optional[UUID] = Query(None, description="Restrict the purge to one project"),
+    service: RetentionService = Depends(get_retention_service),
+):
+    """Run the retention purge now, in dry-run mode unless dry_run=false"""
+    try:
+        return service.purge(dry_run=dry_run, project_uuid=project_uuid)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put(
+    "/submissions/{submission_id}/legal-hold",
+    response_model=LegalHoldResponseDto,
+    dependencies=[
+        Depends(require_admin_scope),
+        Depends(audited(AuditAction.CONFIG_CHANGE, "submission", "submission_id")),
+    ],
+)
+async def set_submission_legal_hold(
+    submission_id: UUID, hold: LegalHoldDto, service: RetentionService = Depends(get_retention_service)
+):
+    """Place or lift a legal hold on a submission, exempting it and its runs from purges"""
+    try:
+        return service.set_submission_legal_hold(submission_id, hold)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put(
+    "/runs/{run_id}/legal-hold",
+    response_model=LegalHoldResponseDto,
+    dependencies=[Depends(require_admin_scope), Depends(audited(AuditAction.CONFIG_CHANGE, "run", "run_id"))],
+)
+async def set_run_legal_hold(
+    run_id: UUID, hold: LegalHoldDto, service: RetentionService = Depends(get_retention_service)
+):
+    """Place or lift a legal hold on a detection run, exempting it and its participants from purges"""
+    try:
+        return service.set_run_legal_hold(run_id, hold)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
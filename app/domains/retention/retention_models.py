@@ -0,0 +1,33 @@
+from datetime import datetime
+from typing import Optional
+from uuid import UUID, uuid4
+
+from sqlmodel import Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class ProjectRetentionPolicy(SQLModel, table=True):
+    """Database model for the retention configuration of a project"""
+
+    __tablename__ = "project_retention_policy"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    project_uuid: UUID = Field(index=True, unique=True, description="UUID of the project the policy applies to")
+
+    # Retention windows, None keeps the resources forever
+    submission_retention_days: Optional[int] = Field(
+        default=None, ge=1, description="Days a submission and its files are kept after creation"
+    )
+    report_retention_days: Optional[int] = Field(
+        default=None, ge=1, description="Days a detection run and its pairs are kept after it started"
+    )
+
+    created_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the policy was created",
+    )
+    updated_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When the policy was last updated"
+    )
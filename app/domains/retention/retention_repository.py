@@ -0,0 +1,188 @@
+import logging
+from datetime import datetime
+from typing import List, Optional, Set
+from uuid import UUID
+
+from sqlmodel import Session, select
+
+from app.domains.retention.retention_models import ProjectRetentionPolicy
+from app.domains.runs.runs_models import UNFINISHED_RUN_STATUSES, DetectionRun, DetectionRunParticipant
+from app.domains.submissions.submissions_models import Submission
+from app.shared.exceptions import DatabaseException, NotFoundException
+from app.shared.tenancy import require_project_access
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class RetentionRepository:
+    """Repository for retention policies, legal holds and expired resource lookups"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def get_policy(self, project_uuid: UUID) -> Optional[ProjectRetentionPolicy]:
+        """Get the retention policy of a project"""
+        try:
+            statement = select(ProjectRetentionPolicy).where(ProjectRetentionPolicy.project_uuid == project_uuid)
+            return self.session.exec(statement).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get retention policy: {str(e)}")
+
+    def list_policies(self) -> List[ProjectRetentionPolicy]:
+        """Get every project retention policy"""
+        try:
+            return list(self.session.exec(select(ProjectRetentionPolicy)).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to list retention policies: {str(e)}")
+
+    def upsert_policy(self, project_uuid: UUID, policy_data: dict) -> ProjectRetentionPolicy:
+        """Create or replace the retention policy of a project"""
+        require_project_access(project_uuid)
+        try:
+            policy = self.get_policy(project_uuid)
+            if policy is None:
+                policy = ProjectRetentionPolicy(project_uuid=project_uuid, **policy_data)
+            else:
+                for field, value in policy_data.items():
+                    setattr(policy, field, value)
+                policy.updated_at = utc_now()
+
+            self.session.add(policy)
+            self.session.commit()
+            self.session.refresh(policy)
+            return policy
+        except DatabaseException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save retention policy: {str(e)}")
+
+    def delete_policy(self, project_uuid: UUID) -> bool:
+        """Delete the retention policy of a project"""
+        try:
+            policy = self.get_policy(project_uuid)
+            if not policy:
+                raise NotFoundException(f"No retention policy for project {project_uuid}")
+
+            self.session.delete(policy)
+            self.session.commit()
+            return True
+        except (NotFoundException, DatabaseException):
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to delete retention policy: {str(e)}")
+
+    def get_submissions_created_before(self, project_uuid: UUID, cutoff: datetime) -> List[Submission]:
+        """Get the submissions of a project created before a date, holds included"""
+        try:
+            statement = (
+                select(Submission)
+                .where(Submission.project_uuid == project_uuid, Submission.created_at < cutoff)
+                .order_by(Submission.created_at)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get expired submissions: {str(e)}")
+
+    def get_runs_started_before(self, project_uuid: UUID, cutoff: datetime) -> List[DetectionRun]:
+        """Get the finished runs of a project started before a date, holds included"""
+        try:
+            statement = (
+                select(DetectionRun)
+                .where(
+                    DetectionRun.project_uuid == project_uuid,
+                    DetectionRun.started_at < cutoff,
+                    DetectionRun.status.notin_(UNFINISHED_RUN_STATUSES),
+                )
+                .order_by(DetectionRun.started_at)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get expired detection runs: {str(e)}")
+
+    def get_participant_submission_ids(self, run_ids: List[UUID]) -> Set[UUID]:
+        """Get the submissions taking part in any of the given runs"""
+        if not run_ids:
+            return set()
+        try:
+            statement = select(DetectionRunParticipant.submission_id).where(
+                DetectionRunParticipant.run_id.in_(run_ids)
+            )
+            return set(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get run participants: {str(e)}")
+
+    def get_runs_with_participants(self, submission_ids: List[UUID]) -> Set[UUID]:
+        """Get the runs any of the given submissions took part in"""
+        if not submission_ids:
+            return set()
+        try:
+            statement = select(DetectionRunParticipant.run_id).where(
+                DetectionRunParticipant.submission_id.in_(submission_ids)
+            )
+            return set(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get runs of submissions: {str(e)}")
+
+    def get_held_submission_ids(self, project_uuid: UUID) -> Set[UUID]:
+        """Get the submissions of a project under legal hold"""
+        try:
+            statement = select(Submission.id).where(
+                Submission.project_uuid == project_uuid, Submission.legal_hold.is_(True)
+            )
+            return set(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get held submissions: {str(e)}")
+
+    def get_held_run_ids(self, project_uuid: UUID) -> Set[UUID]:
+        """Get the runs of a project under legal hold"""
+        try:
+            statement = select(DetectionRun.id).where(
+                DetectionRun.project_uuid == project_uuid, DetectionRun.legal_hold.is_(True)
+            )
+            return set(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get held detection runs: {str(e)}")
+
+    def set_submission_legal_hold(self, submission_id: UUID, legal_hold: bool, reason: Optional[str]) -> Submission:
+        """Place or lift a legal hold on a submission"""
+        try:
+            submission = self.session.get(Submission, submission_id)
+            if not submission:
+                raise NotFoundException(f"Submission with ID {submission_id} not found")
+
+            submission.legal_hold = legal_hold
+            submission.legal_hold_reason = reason if legal_hold else None
+            submission.updated_at = utc_now()
+
+            self.session.add(submission)
+            self.session.commit()
+            self.session.refresh(submission)
+            return submission
+        except NotFoundException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to update submission legal hold: {str(e)}")
+
+    def set_run_legal_hold(self, run_id: UUID, legal_hold: bool, reason: Optional[str]) -> DetectionRun:
+        """Place or lift a legal hold on a detection run"""
+        try:
+            run = self.session.get(DetectionRun, run_id)
+            if not run:
+                raise NotFoundException(f"Detection run with ID {run_id} not found")
+
+            run.legal_hold = legal_hold
+            run.legal_hold_reason = reason if legal_hold else None
+
+            self.session.add(run)
+            self.session.commit()
+            self.session.refresh(run)
+            return run
+        except NotFoundException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to update detection run legal hold: {str(e)}")
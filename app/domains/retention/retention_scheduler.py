@@ -0,0 +1,93 @@
+import logging
+import threading
+from typing import Optional
+
+from app.config.config import Settings
+
+logger = logging.getLogger(__name__)
+
+
+class RetentionScheduler:
+    """Background thread running the retention purge, then the blob garbage collection, at a fixed interval"""
+
+    def __init__(
+        self,
+        interval_seconds: float,
+        dry_run: bool = False,
+        session_factory=None,
+        gc_grace_seconds: Optional[float] = None,
+    ):
+        self.interval_seconds = interval_seconds
+        self.dry_run = dry_run
+        self.gc_grace_seconds = gc_grace_seconds
+        self._session_factory = session_factory
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def _new_session(self):
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from sqlmodel import Session
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    def run_once(self):
+        """Run one purge in a dedicated session, errors are logged and never stop the scheduler"""
+        from app.domains.retention.retention_service import RetentionService
+
+        report = None
+        try:
+            with self._new_session() as session:
+                report = RetentionService(session).purge(dry_run=self.dry_run)
+        except Exception as e:
+            logger.error(f"Scheduled retention purge failed: {str(e)}")
+
+        if self.gc_grace_seconds is not None and not self.dry_run:
+            self.collect_garbage()
+        return report
+
+    def collect_garbage(self):
+        """Sweep the blobs no submission references anymore, errors are logged"""
+        from app.domains.storage.submission_storage_service import SubmissionStorageService
+
+        try:
+            return SubmissionStorageService().collect_garbage(self.gc_grace_seconds)
+        except Exception as e:
+            logger.error(f"Scheduled blob garbage collection failed: {str(e)}")
+            return None
+
+    def _run(self) -> None:
+        while not self._stop_event.wait(self.interval_seconds):
+            self.run_once()
+
+    def start(self) -> None:
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="retention-purge", daemon=True)
+        self._thread.start()
+        logger.info(
+            f"Retention purge scheduled every {self.interval_seconds / 3600:g} hours"
+            f"{' (dry run)' if self.dry_run else ''}"
+        )
+
+    def stop(self, timeout: float = 5.0) -> None:
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+
+
+def create_retention_scheduler(settings: Settings) -> Optional[RetentionScheduler]:
+    """Build the retention scheduler from the settings, None when scheduled purges are disabled"""
+    if not settings.retention_purge_enabled:
+        logger.info("Scheduled retention purge disabled")
+        return None
+    return RetentionScheduler(
+        settings.retention_purge_interval_hours * 3600,
+        dry_run=settings.retention_purge_dry_run,
+        gc_grace_seconds=settings.storage_gc_grace_seconds if settings.storage_gc_enabled else None,
+    )
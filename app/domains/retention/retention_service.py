@@ -0,0 +1,179 @@
+import logging
+from datetime import datetime, timedelta
+from typing import Callable, Optional
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.retention.dto.retention_dto import (
+    LegalHoldDto,
+    LegalHoldResponseDto,
+    ProjectPurgeDto,
+    RetentionPolicyDto,
+    RetentionPolicyResponseDto,
+    RetentionPurgeReportDto,
+)
+from app.domains.retention.retention_models import ProjectRetentionPolicy
+from app.domains.reports.report_service import delete_run_reports
+from app.domains.retention.retention_repository import RetentionRepository
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.submissions.submissions_similarity_repository import SubmissionSimilarityRepository
+from app.shared.exceptions import NotFoundException
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class RetentionService:
+    """
+    Service enforcing project retention policies
+
+    Legal holds extend across links: a held submission protects the runs it took part in,
+    and a held run protects its participating submissions.
+    """
+
+    def __init__(
+        self,
+        session: Session,
+        clock: Optional[Callable[[], datetime]] = None,
+        submission_service=None,
+        storage_service=None,
+    ):
+        self.session = session
+        self.repository = RetentionRepository(session)
+        self.run_repository = DetectionRunRepository(session)
+        self.similarity_repository = SubmissionSimilarityRepository(session)
+        self.clock = clock or utc_now
+        self._submission_service = submission_service
+        self._storage_service = storage_service
+
+    @property
+    def submission_service(self):
+        """Submission service used for deletions, created on first use as it loads the detection stack"""
+        if self._submission_service is None:
+            from app.domains.submissions.submissions_service import SubmissionService
+
+            self._submission_service = SubmissionService(self.session)
+        return self._submission_service
+
+    @property
+    def storage_service(self):
+        """Storage service holding the stored reports of the runs, created on first use"""
+        if self._storage_service is None:
+            from app.domains.storage.submission_storage_service import SubmissionStorageService
+
+            self._storage_service = SubmissionStorageService()
+        return self._storage_service
+
+    def get_policy(self, project_uuid: UUID) -> RetentionPolicyResponseDto:
+        policy = self.repository.get_policy(project_uuid)
+        if not policy:
+            raise NotFoundException(f"No retention policy for project {project_uuid}")
+        return RetentionPolicyResponseDto.model_validate(policy)
+
+    def set_policy(self, project_uuid: UUID, policy_data: RetentionPolicyDto) -> RetentionPolicyResponseDto:
+        policy = self.repository.upsert_policy(project_uuid, policy_data.model_dump())
+        logger.info(
+            f"Retention policy of project {project_uuid} set: submissions {policy.submission_retention_days} days, "
+            f"reports {policy.report_retention_days} days"
+        )
+        return RetentionPolicyResponseDto.model_validate(policy)
+
+    def delete_policy(self, project_uuid: UUID) -> bool:
+        return self.repository.delete_policy(project_uuid)
+
+    def set_submission_legal_hold(self, submission_id: UUID, hold: LegalHoldDto) -> LegalHoldResponseDto:
+        submission = self.repository.set_submission_legal_hold(submission_id, hold.legal_hold, hold.reason)
+        logger.info(f"Legal hold {'placed on' if hold.legal_hold else 'lifted from'} submission {submission_id}")
+        return LegalHoldResponseDto(
+            resource_type="submission",
+            resource_id=submission.id,
+            legal_hold=submission.legal_hold,
+            reason=submission.legal_hold_reason,
+        )
+
+    def set_run_legal_hold(self, run_id: UUID, hold: LegalHoldDto) -> LegalHoldResponseDto:
+        run = self.repository.set_run_legal_hold(run_id, hold.legal_hold, hold.reason)
+        logger.info(f"Legal hold {'placed on' if hold.legal_hold else 'lifted from'} detection run {run_id}")
+        return LegalHoldResponseDto(
+            resource_type="run", resource_id=run.id, legal_hold=run.legal_hold, reason=run.legal_hold_reason
+        )
+
+    def purge(self, dry_run: bool = False, project_uuid: Optional[UUID] = None) -> RetentionPurgeReportDto:
+        """
+        Delete the resources older than their project retention window
+
+        Args:
+            dry_run: Only report what would be deleted
+            project_uuid: Restrict the purge to one project
+        """
+        now = self.clock()
+        if project_uuid is not None:
+            policy = self.repository.get_policy(project_uuid)
+            policies = [policy] if policy else []
+        else:
+            policies = self.repository.list_policies()
+
+        projects = [self._purge_project(policy, now, dry_run) for policy in policies]
+        report = RetentionPurgeReportDto(
+            dry_run=dry_run,
+            executed_at=now,
+            total_deleted_submissions=sum(len(p.deleted_submissions) for p in projects),
+            total_deleted_runs=sum(len(p.deleted_runs) for p in projects),
+            projects=projects,
+        )
+
+        logger.info(
+            f"Retention purge{' (dry run)' if dry_run else ''}: "
+            f"{report.total_deleted_submissions} submissions and {report.total_deleted_runs} runs "
+            f"{'would be ' if dry_run else ''}deleted across {len(projects)} projects"
+        )
+        return report
+
+    def _purge_project(self, policy: ProjectRetentionPolicy, now: datetime, dry_run: bool) -> ProjectPurgeDto:
+        project_uuid = policy.project_uuid
+        result = ProjectPurgeDto(project_uuid=project_uuid)
+        action = "Would delete" if dry_run else "Deleted"
+
+        held_submissions = self.repository.get_held_submission_ids(project_uuid)
+        held_runs = self.repository.get_held_run_ids(project_uuid)
+        protected_runs = held_runs | self.repository.get_runs_with_participants(list(held_submissions))
+        protected_submissions = held_submissions | self.repository.get_participant_submission_ids(list(held_runs))
+
+        if policy.report_retention_days:
+            result.report_cutoff = now - timedelta(days=policy.report_retention_days)
+            for run in self.repository.get_runs_started_before(project_uuid, result.report_cutoff):
+                if run.id in protected_runs:
+                    result.held_runs.append(run.id)
+                    continue
+                try:
+                    if not dry_run:
+                        delete_run_reports(self.storage_service, run)
+                        self.run_repository.delete_run(run.id)
+                    result.deleted_runs.append(run.id)
+                    logger.info(f"{action} expired detection run {run.id} of project {project_uuid}")
+                except Exception as e:
+                    result.errors.append(f"Run {run.id}: {str(e)}")
+                    logger.error(f"Failed to purge detection run {run.id}: {str(e)}")
+
+        if policy.submission_retention_days:
+            result.submission_cutoff = now - timedelta(days=policy.submission_retention_days)
+            for submission in self.repository.get_submissions_created_before(project_uuid, result.submission_cutoff):
+                if submission.id in protected_submissions:
+                    result.held_submissions.append(submission.id)
+                    continue
+                try:
+                    if not dry_run:
+                        self._delete_submission(submission.id)
+                    result.deleted_submissions.append(submission.id)
+                    logger.info(f"{action} expired submission {submission.id} of project {project_uuid}")
+                except Exception as e:
+                    result.errors.append(f"Submission {submission.id}: {str(e)}")
+                    logger.error(f"Failed to purge submission {submission.id}: {str(e)}")
+
+        return result
+
+    def _delete_submission(self, submission_id: UUID) -> None:
+        """Delete a submission through the same cascade as the delete endpoint"""
+        self.similarity_repository.delete_by_submission_id(submission_id)
+        self.submission_service.delete_submission(submission_id)
@@ -0,0 +1 @@
+# RPC domain package
@@ -0,0 +1,181 @@
+"""
+Conversions between the gRPC messages of proto/pamp/v1/pamp.proto and the DTOs of the service layer
+
+Requests are turned into the DTOs the HTTP handlers receive, invalid ones raise the ValidationException the HTTP
+API answers with 422. Failures of the service layer are HTTP exceptions, answered with the gRPC status of their
+HTTP status.
+"""
+
+import json
+from datetime import datetime, timezone
+from typing import Any, Dict, Optional, Tuple
+from uuid import UUID
+
+from fastapi import HTTPException
+from pydantic import ValidationError
+
+from app.domains.runs.dto.run_response_dto import DetectionFragmentDto, DetectionPairDto, DetectionRunReportDto
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.shared.concurrency import JobPriority
+from app.shared.exceptions import ValidationException
+
+# Priority enum of the proto by number, unspecified is normal
+PRIORITIES = {
+    0: JobPriority.NORMAL,
+    1: JobPriority.LOW,
+    2: JobPriority.NORMAL,
+    3: JobPriority.HIGH,
+    4: JobPriority.URGENT,
+}
+
+# gRPC status codes of the HTTP statuses of the service layer, others are INTERNAL from 500 and INVALID_ARGUMENT below
+HTTP_STATUS_CODES = {
+    400: "INVALID_ARGUMENT",
+    401: "UNAUTHENTICATED",
+    403: "PERMISSION_DENIED",
+    404: "NOT_FOUND",
+    409: "FAILED_PRECONDITION",
+    422: "INVALID_ARGUMENT",
+    429: "RESOURCE_EXHAUSTED",
+    503: "UNAVAILABLE",
+}
+
+
+def grpc_status(error: Exception) -> Tuple[str, str]:
+    """Name of the gRPC status code answering an error, with its message"""
+    if isinstance(error, HTTPException):
+        detail = error.detail if isinstance(error.detail, str) else json.dumps(error.detail, default=str)
+        default = "INTERNAL" if error.status_code >= 500 else "INVALID_ARGUMENT"
+        return HTTP_STATUS_CODES.get(error.status_code, default), detail
+    return "INTERNAL", f"Internal server error: {str(error)}"
+
+
+def parse_uuid(value: str, field: str, required: bool = True) -> Optional[UUID]:
+    """UUID of a string field, None when it is empty and optional"""
+    if not value:
+        if required:
+            raise ValidationException(f"{field} is required")
+        return None
+    try:
+        return UUID(value)
+    except ValueError:
+        raise ValidationException(f"{field} is not a UUID: {value}")
+
+
+def parse_priority(value: int) -> JobPriority:
+    if value not in PRIORITIES:
+        raise ValidationException(f"Unknown priority: {value}")
+    return PRIORITIES[value]
+
+
+def create_submission_dto(request) -> CreateSubmissionDto:
+    """DTO of a CreateSubmissionRequest, the body POST /submissions receives"""
+    source = request.WhichOneof("source")
+    if source is None:
+        raise ValidationException("git_url or storage_reference is required")
+    if source == "storage_reference" and not request.storage_reference.lower().startswith("s3://"):
+        raise ValidationException(f"storage_reference must be an s3:// URL: {request.storage_reference}")
+
+    fields: Dict[str, Any] = {
+        "link": getattr(request, source),
+        "project_uuid": parse_uuid(request.project_uuid, "project_uuid"),
+        "group_uuid": parse_uuid(request.group_uuid, "group_uuid"),
+        "project_step_uuid": parse_uuid(request.project_step_uuid, "project_step_uuid"),
+        "submitted_by_uuid": parse_uuid(request.submitted_by_uuid, "submitted_by_uuid", required=False),
+        "description": request.description or None,
+    }
+    if request.HasField("upload_date_time"):
+        fields["upload_date_time"] = request.upload_date_time.ToDatetime(tzinfo=timezone.utc)
+    try:
+        return CreateSubmissionDto(**fields)
+    except ValidationError as e:
+        raise ValidationException(f"Invalid submission: {e}")
+
+
+def message(message_class, **fields):
+    """Message with the given fields, None ones left unset"""
+    return message_class(**{name: value for name, value in fields.items() if value is not None})
+
+
+def timestamp(value: Optional[datetime]):
+    if value is None:
+        return None
+    from google.protobuf.timestamp_pb2 import Timestamp
+
+    result = Timestamp()
+    result.FromDatetime(value)
+    return result
+
+
+def text(value: Any) -> Optional[str]:
+    """String field of a UUID or enum value, None when absent"""
+    return None if value is None else str(getattr(value, "value", value))
+
+
+def run_message(protos, report: DetectionRunReportDto):
+    run = report.run
+    return message(
+        protos.Run,
+        id=text(run.id),
+        project_uuid=text(run.project_uuid),
+        project_step_uuid=text(run.project_step_uuid),
+        trigger=text(run.trigger),
+        trigger_submission_id=text(run.trigger_submission_id),
+        priority=text(run.priority),
+        status=text(run.status),
+        total_pairs=run.total_pairs,
+        completed_pairs=run.completed_pairs,
+        failed_pairs=run.failed_pairs,
+        persisted_pairs=report.persisted_pairs,
+        started_at=timestamp(run.started_at),
+        finished_at=timestamp(run.finished_at),
+        error_message=run.error_message,
+        queue_position=report.queue.position if report.queue else None,
+    )
+
+
+def pair_message(protos, pair: DetectionPairDto):
+    return message(
+        protos.PairResult,
+        id=text(pair.id),
+        run_id=text(pair.run_id),
+        submission_id=text(pair.submission_id),
+        compared_submission_id=text(pair.compared_submission_id),
+        submitted_by_uuid=text(pair.submitted_by_uuid),
+        compared_submitted_by_uuid=text(pair.compared_submitted_by_uuid),
+        overall_similarity=pair.overall_similarity,
+        jaccard_similarity=pair.jaccard_similarity,
+        type_similarity=pair.type_similarity,
+        structural_similarity=pair.structural_similarity,
+        type_sequence_similarity=pair.type_sequence_similarity,
+        flow_similarity=pair.flow_similarity,
+        operation_similarity=pair.operation_similarity,
+        fragments_count=pair.fragments_count,
+        low_confidence=pair.low_confidence,
+        partial=pair.partial,
+        status=text(pair.status),
+        error_message=pair.error_message,
+        created_at=timestamp(pair.created_at),
+    )
+
+
+def fragment_message(protos, fragment: DetectionFragmentDto):
+    details = None
+    if fragment.details is not None:
+        from google.protobuf.struct_pb2 import Struct
+
+        details = Struct()
+        details.update(fragment.model_dump(mode="json", include={"details"})["details"])
+    return message(
+        protos.Fragment,
+        id=text(fragment.id),
+        fragment_type=text(fragment.fragment_type),
+        file1_path=fragment.file1_path,
+        file2_path=fragment.file2_path,
+        file1_start_line=fragment.file1_start_line,
+        file1_end_line=fragment.file1_end_line,
+        file2_start_line=fragment.file2_start_line,
+        file2_end_line=fragment.file2_end_line,
+        similarity=fragment.similarity,
+        details=details,
+    )
@@ -0,0 +1,290 @@
+"""
+gRPC server of the submission and detection APIs
+
+The servicer calls the same services as the HTTP handlers, so both interfaces create submissions, queue runs and
+refuse requests alike. The messages and services are compiled from proto/pamp/v1/pamp.proto when the server is
+built, which needs grpcio-tools next to grpcio; clients generate their stubs from the same file.
+"""
+
+import functools
+import logging
+import sys
+import threading
+from concurrent import futures
+from pathlib import Path
+from typing import Mapping, Optional, Tuple
+
+from fastapi import HTTPException
+
+try:
+    import grpc
+except ImportError:
+    grpc = None
+
+from app.config.config import Settings
+from app.domains.audit.audit_models import AuditAction
+from app.domains.rpc.grpc_messages import (
+    create_submission_dto,
+    fragment_message,
+    grpc_status,
+    message,
+    pair_message,
+    parse_priority,
+    parse_uuid,
+    run_message,
+    text,
+)
+from app.shared.concurrency import JobPriority
+from app.shared.security import require_admin_scope
+from app.shared.tenancy import tenancy_of, tenancy_scope
+from app.shared.tracing import current_request_id, new_request_id, span
+
+logger = logging.getLogger(__name__)
+
+PROTO_ROOT = Path(__file__).resolve().parents[3] / "proto"
+PROTO_PATH = "pamp/v1/pamp.proto"
+
+_protos_lock = threading.Lock()
+_protos: Optional[Tuple] = None
+
+
+def load_protos():
+    """Message and service modules compiled from the proto definitions, compiled once"""
+    global _protos
+    with _protos_lock:
+        if _protos is None:
+            if grpc is None:
+                raise RuntimeError("grpcio and grpcio-tools are required for the gRPC server")
+            # Proto files are found, and their modules named, relative to the import path
+            if str(PROTO_ROOT) not in sys.path:
+                sys.path.append(str(PROTO_ROOT))
+            _protos = grpc.protos_and_services(PROTO_PATH)
+    return _protos
+
+
+def call_metadata(context) -> Mapping[str, str]:
+    return {key.lower(): value for key, value in context.invocation_metadata() or ()}
+
+
+def client_info(context) -> Tuple[Optional[str], Optional[str]]:
+    """Client IP and user agent of a call, as recorded for HTTP requests"""
+    metadata = call_metadata(context)
+    forwarded_for = metadata.get("x-forwarded-for")
+    if forwarded_for:
+        return forwarded_for.split(",")[0].strip(), metadata.get("user-agent")
+
+    # Peers are "ipv4:10.0.0.1:51234" or "ipv6:[::1]:51234"
+    kind, _, address = (context.peer() or "").partition(":")
+    ip_address = address.rsplit(":", 1)[0].strip("[]") if kind in ("ipv4", "ipv6") else None
+    return ip_address, metadata.get("user-agent")
+
+
+def abort(context, error: Exception) -> None:
+    """End a call with the gRPC status of an error, raises"""
+    code, detail = grpc_status(error)
+    if code == "INTERNAL":
+        logger.error(f"gRPC call failed: {detail}", exc_info=error)
+    context.abort(getattr(grpc.StatusCode, code), detail)
+
+
+def unary(method):
+    """
+    Run a unary call in the span of its request ID and the tenancy of its metadata, answering its failures with
+    their gRPC status
+    """
+
+    @functools.wraps(method)
+    def handle(self, request, context):
+        metadata = call_metadata(context)
+        with span(request_id=new_request_id(metadata.get("x-request-id"))), tenancy_scope(tenancy_of(metadata)):
+            try:
+                return method(self, request, context)
+            except Exception as e:
+                abort(context, e)
+
+    return handle
+
+
+def streaming(method):
+    """
+    Run a server-streaming call in the span of its request ID and the tenancy of its metadata, ending it with the
+    gRPC status of its failure
+    """
+
+    @functools.wraps(method)
+    def handle(self, request, context):
+        metadata = call_metadata(context)
+        with span(request_id=new_request_id(metadata.get("x-request-id"))), tenancy_scope(tenancy_of(metadata)):
+            try:
+                yield from method(self, request, context)
+            except Exception as e:
+                abort(context, e)
+
+    return handle
+
+
+class PampServicer:
+    """Servicer of the SubmissionService and DetectionService of the proto, with one database session per call"""
+
+    def __init__(self, protos, session_factory=None, poll_interval_seconds: float = 1.0):
+        self.protos = protos
+        self.poll_interval_seconds = poll_interval_seconds
+        self._session_factory = session_factory
+
+    def _new_session(self):
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from sqlmodel import Session
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    @staticmethod
+    def _submission_service(session):
+        from app.domains.submissions.submissions_service import SubmissionService
+
+        return SubmissionService(session)
+
+    @staticmethod
+    def _run_service(session):
+        from app.domains.runs.runs_service import DetectionRunService
+        from app.shared.services import get_detection_scheduler
+
+        return DetectionRunService(session, get_detection_scheduler())
+
+    @staticmethod
+    def _priority(request, context) -> JobPriority:
+        """Priority of a request, urgent needs the admin scope as over HTTP"""
+        priority = parse_priority(request.priority)
+        if priority == JobPriority.URGENT:
+            require_admin_scope(call_metadata(context).get("authorization"))
+        return priority
+
+    @staticmethod
+    def _audit(session, context, action, resource_type: str, resource_id) -> None:
+        """Record a call in the audit log, as the HTTP endpoints it mirrors"""
+        from app.domains.audit.audit_hook import actor_of
+        from app.domains.audit.audit_service import AuditService
+
+        ip_address, user_agent = client_info(context)
+        AuditService(session).record(
+            action,
+            actor_of(call_metadata(context)),
+            resource_type,
+            resource_id=str(resource_id),
+            details={"method": "grpc"},
+            ip_address=ip_address,
+            user_agent=user_agent,
+            request_id=current_request_id(),
+        )
+
+    @unary
+    def CreateSubmission(self, request, context):
+        submission_data = create_submission_dto(request)
+        priority = self._priority(request, context)
+        ip_address, user_agent = client_info(context)
+        with self._new_session() as session:
+            response = self._submission_service(session).create_submission(
+                submission_data=submission_data,
+                ip_address=ip_address,
+                user_agent=user_agent,
+                allow_duplicates=request.allow_duplicates,
+                profile=request.profile,
+                priority=priority,
+            )
+        return message(
+            self.protos.CreateSubmissionResponse,
+            submission_id=text(response.submission_id),
+            run_id=text(response.run_id),
+            is_late=response.data.is_late,
+            minutes_late=response.data.minutes_late,
+        )
+
+    @unary
+    def StartDetection(self, request, context):
+        submission_id = parse_uuid(request.submission_id, "submission_id")
+        priority = self._priority(request, context)
+        with self._new_session() as session:
+            run_id = self._submission_service(session).start_detection(submission_id, request.profile, priority)
+        if run_id is None:
+            raise HTTPException(status_code=409, detail=f"Submission {submission_id} has no submission to compare with")
+        return self.protos.StartDetectionResponse(run_id=str(run_id))
+
+    @unary
+    def GetRun(self, request, context):
+        run_id = parse_uuid(request.run_id, "run_id")
+        with self._new_session() as session:
+            self._audit(session, context, AuditAction.REPORT_VIEW, "run", run_id)
+            return run_message(self.protos, self._run_service(session).get_run_report(run_id, top=0))
+
+    @streaming
+    def StreamResults(self, request, context):
+        run_id = parse_uuid(request.run_id, "run_id")
+        with self._new_session() as session:
+            self._audit(session, context, AuditAction.REPORT_VIEW, "run", run_id)
+            pairs = self._run_service(session).follow_pairs(
+                run_id,
+                request.min_score,
+                self.poll_interval_seconds,
+                should_stop=lambda: not context.is_active(),
+            )
+            for pair in pairs:
+                yield pair_message(self.protos, pair)
+
+    @unary
+    def GetPairFragments(self, request, context):
+        pair_id = parse_uuid(request.pair_id, "pair_id")
+        with self._new_session() as session:
+            self._audit(session, context, AuditAction.FRAGMENT_VIEW, "pair", pair_id)
+            detail = self._run_service(session).get_pair_detail(pair_id)
+        return self.protos.GetPairFragmentsResponse(
+            pair=pair_message(self.protos, detail.pair),
+            file_fragments=[fragment_message(self.protos, f) for f in detail.file_fragments],
+            block_fragments=[fragment_message(self.protos, f) for f in detail.block_fragments],
+        )
+
+
+class GrpcServer:
+    """gRPC server of the submission and detection services, on its own port next to the HTTP API"""
+
+    def __init__(
+        self,
+        port: int = 50051,
+        max_workers: int = 8,
+        poll_interval_seconds: float = 1.0,
+        session_factory=None,
+        host: str = "[::]",
+    ):
+        self.host = host
+        self.port = port
+        self.max_workers = max(max_workers, 1)
+        self.poll_interval_seconds = poll_interval_seconds
+        self._session_factory = session_factory
+        self._server = None
+
+    def start(self) -> int:
+        """Start serving, returns the bound port, the one picked by the system when the port is 0"""
+        protos, services = load_protos()
+        servicer = PampServicer(protos, self._session_factory, self.poll_interval_seconds)
+        self._server = grpc.server(futures.ThreadPoolExecutor(max_workers=self.max_workers, thread_name_prefix="grpc"))
+        services.add_SubmissionServiceServicer_to_server(servicer, self._server)
+        services.add_DetectionServiceServicer_to_server(servicer, self._server)
+        self.port = self._server.add_insecure_port(f"{self.host}:{self.port}")
+        self._server.start()
+        logger.info(f"gRPC server listening on port {self.port} with {self.max_workers} workers")
+        return self.port
+
+    def stop(self, grace_seconds: float = 5.0) -> None:
+        """Stop accepting calls and cancel the ones still running after the grace period"""
+        if self._server is not None:
+            self._server.stop(grace_seconds).wait()
+            self._server = None
+
+
+def create_grpc_server(settings: Settings) -> Optional[GrpcServer]:
+    """Build the gRPC server from the settings, None when it is disabled"""
+    if not settings.grpc_enabled:
+        return None
+    return GrpcServer(settings.grpc_port, settings.grpc_max_workers, settings.grpc_stream_poll_interval_seconds)
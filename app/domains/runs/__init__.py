@@ -0,0 +1 @@
+# Runs domain package
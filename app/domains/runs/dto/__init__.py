@@ -0,0 +1,39 @@
+from .run_response_dto import (
+    DetectionFragmentDto,
+    DetectionPairDetailDto,
+    DetectionPairDto,
+    DetectionPairListResponseDto,
+    DetectionRunDto,
+    DetectionRunParticipantDto,
+    DetectionRunQueueDto,
+    DetectionRunReportDto,
+    HeatmapCellDto,
+    HeatmapFileDto,
+    HeatmapForm,
+    HighlightedCodeDto,
+    HighlightedLineDto,
+    PairHeatmapDto,
+    PairMatchStatsDto,
+    PairSortKey,
+    SubmitterHistoryDto,
+)
+
+__all__ = [
+    "DetectionRunDto",
+    "DetectionRunParticipantDto",
+    "PairMatchStatsDto",
+    "DetectionPairDto",
+    "HighlightedLineDto",
+    "HighlightedCodeDto",
+    "DetectionFragmentDto",
+    "DetectionRunQueueDto",
+    "DetectionRunReportDto",
+    "DetectionPairListResponseDto",
+    "DetectionPairDetailDto",
+    "SubmitterHistoryDto",
+    "PairSortKey",
+    "HeatmapForm",
+    "HeatmapFileDto",
+    "HeatmapCellDto",
+    "PairHeatmapDto",
+]
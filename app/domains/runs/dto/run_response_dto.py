@@ -0,0 +1,319 @@
+from enum import Enum
+from typing import Any, Dict, List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict
+
+from app.domains.runs.runs_models import DetectionRunStatus, DetectionRunTrigger, FragmentType
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.concurrency import JobPriority
+from app.shared.timestamps import UtcTimestamp
+
+
+class DetectionRunDto(BaseModel):
+    """DTO for detection run metadata"""
+
+    model_config = ConfigDict(
+        from_attributes=True,
+        use_enum_values=True,
+        json_schema_extra={
+            "example": {
+                "id": "550e8400-e29b-41d4-a716-446655440010",
+                "project_uuid": "550e8400-e29b-41d4-a716-446655440000",
+                "project_step_uuid": "550e8400-e29b-41d4-a716-446655440003",
+                "trigger": "submission",
+                "trigger_submission_id": "550e8400-e29b-41d4-a716-446655440020",
+                "priority": "normal",
+                "status": "completed",
+                "total_pairs": 12,
+                "completed_pairs": 11,
+                "failed_pairs": 1,
+                "cache_stats": {"hits": 40, "misses": 4, "writes": 4, "errors": 0, "tokenizations": 4},
+                "started_at": "2024-01-15T10:30:00Z",
+                "finished_at": "2024-01-15T10:32:10Z",
+            }
+        },
+    )
+
+    id: UUID
+    project_uuid: UUID
+    project_step_uuid: UUID
+    trigger: DetectionRunTrigger
+    trigger_submission_id: Optional[UUID] = None
+    schedule_id: Optional[UUID] = None
+    priority: JobPriority = JobPriority.NORMAL
+    detection_algorithm: str
+    detection_version: str
+    parameters: Optional[Dict[str, Any]] = None
+    declared_collaborations: Optional[List[Dict[str, Any]]] = None
+    cache_stats: Optional[Dict[str, Any]] = None
+    profile: Optional[Dict[str, Any]] = None
+    status: DetectionRunStatus
+    total_pairs: int
+    completed_pairs: int
+    failed_pairs: int
+    started_at: UtcTimestamp
+    finished_at: Optional[UtcTimestamp] = None
+    error_message: Optional[str] = None
+    legal_hold: bool = False
+    legal_hold_reason: Optional[str] = None
+
+
+class DetectionRunParticipantDto(BaseModel):
+    """DTO for a submission taking part in a run"""
+
+    model_config = ConfigDict(from_attributes=True)
+
+    submission_id: UUID
+    group_uuid: UUID
+    submitted_by_uuid: Optional[UUID] = None
+    team_id: Optional[UUID] = None
+    team_name: Optional[str] = None
+    team_members: Optional[List[UUID]] = None
+
+
+class RunReclassificationDto(BaseModel):
+    """DTO for the classification of the pairs of a run against the declared collaborations of its step"""
+
+    run_id: UUID
+    declared_collaborations: List[Dict[str, Any]]  # declarations of the step the run now classifies its pairs with
+    declared_collaboration_pairs: int  # completed pairs the declarations allow
+    changed_pairs: int  # completed pairs classified differently than before
+
+
+class NotComparableSubmissionDto(BaseModel):
+    """DTO for a participant that was not compared, with the reason"""
+
+    submission_id: UUID
+    reason: str  # "no_supported_files", "empty" or "comments_only"
+    description: str
+
+
+class FileErrorDto(BaseModel):
+    """DTO for a file left out of a run because it failed, the scores of its submission may be understated"""
+
+    submission_id: UUID
+    path: str
+    stage: str  # "decoding", "tokenization", "fingerprinting" or "fragment_extraction"
+    message: str
+    timed_out: bool = False  # skipped by the file timeout rather than failing
+
+
+class PairMatchStatsDto(BaseModel):
+    """DTO for the match statistics of a pair, computed from its block fragments"""
+
+    fragments_count: int  # block fragments
+    longest_fragment_lines: int  # longer side of the longest block
+    longest_fragment_tokens: Optional[int] = None  # unknown for blocks recorded before token counts were kept
+    mean_fragment_lines: float
+    top_fragments_coverage: float  # share of the matched lines in the 3 longest blocks, 0.0 to 1.0
+
+
+class DetectionPairDto(BaseModel):
+    """DTO for the comparison result of two submissions within a run"""
+
+    model_config = ConfigDict(
+        from_attributes=True,
+        use_enum_values=True,
+        json_schema_extra={
+            "example": {
+                "id": "550e8400-e29b-41d4-a716-446655440030",
+                "run_id": "550e8400-e29b-41d4-a716-446655440010",
+                "submission_id": "550e8400-e29b-41d4-a716-446655440020",
+                "compared_submission_id": "550e8400-e29b-41d4-a716-446655440021",
+                "overall_similarity": 0.85,
+                "fragments_count": 4,
+                "status": "completed",
+            }
+        },
+    )
+
+    id: UUID
+    run_id: UUID
+    project_uuid: UUID
+    project_step_uuid: UUID
+    submission_id: UUID
+    compared_submission_id: UUID
+    submitted_by_uuid: Optional[UUID] = None
+    compared_submitted_by_uuid: Optional[UUID] = None
+    similarity_id: Optional[UUID] = None
+    overall_similarity: float
+    estimated_similarity: Optional[float] = None
+    jaccard_similarity: float
+    type_similarity: float
+    structural_similarity: float
+    type_sequence_similarity: float
+    flow_similarity: float
+    operation_similarity: float
+    fragments_count: int
+    matched_tokens: Optional[int] = None
+    # A side has fewer comparable tokens than the configured minimum, the score rests on little code
+    low_confidence: bool = False
+    # Files of the submissions failed and were left out, the scores may be understated
+    partial: bool = False
+    status: SimilarityStatus
+    error_message: Optional[str] = None
+    processing_time_seconds: Optional[float] = None
+    created_at: UtcTimestamp
+    # Only set in run reports, pair pages and pair views, where the fragments of the pair are loaded
+    match_stats: Optional[PairMatchStatsDto] = None
+
+
+class HighlightedLineDto(BaseModel):
+    """DTO for a highlighted source line of a fragment"""
+
+    number: int  # 1-based, as in the file
+    html: str  # escaped source with token spans, wrapped in <mark class="match"> when matched
+    matched: bool
+
+
+class HighlightedCodeDto(BaseModel):
+    """DTO for the highlighted region of one side of a fragment, with its context lines"""
+
+    path: str
+    language: Optional[str] = None  # None when the file is shown as escaped plain text
+    available: bool = True  # False when the file is no longer stored
+    lines: List[HighlightedLineDto] = []
+
+
+class DetectionFragmentDto(BaseModel):
+    """DTO for a shared fragment of a pair"""
+
+    model_config = ConfigDict(from_attributes=True, use_enum_values=True)
+
+    id: UUID
+    fragment_type: FragmentType
+    file1_path: str
+    file2_path: str
+    file1_start_line: Optional[int] = None
+    file1_end_line: Optional[int] = None
+    file2_start_line: Optional[int] = None
+    file2_end_line: Optional[int] = None
+    similarity: float
+    details: Optional[Dict[str, Any]] = None
+    # Highlighted code of each side, only for blocks of a pair view asked with highlight
+    file1_code: Optional[HighlightedCodeDto] = None
+    file2_code: Optional[HighlightedCodeDto] = None
+
+
+class DetectionRunQueueDto(BaseModel):
+    """DTO for the place of a run waiting for a detection slot"""
+
+    model_config = ConfigDict(use_enum_values=True)
+
+    effective_priority: JobPriority
+    position: int
+    waiting_seconds: float
+
+
+class DetectionRunReportDto(BaseModel):
+    """DTO for a run report assembled from the run tables"""
+
+    run: DetectionRunDto
+    # Only set while the run waits for a detection slot
+    queue: Optional[DetectionRunQueueDto] = None
+    participants: List[DetectionRunParticipantDto]
+    # Participants excluded from the pairwise comparison, their pairs are recorded as not_comparable
+    not_comparable: List[NotComparableSubmissionDto] = []
+    # Files skipped in the comparisons because they failed, the run went on with the other files
+    file_errors: List[FileErrorDto] = []
+    # Pairs stopped by the pair or the run timeout, recorded without scores
+    timed_out_pairs: List[DetectionPairDto] = []
+    persisted_pairs: int
+    top_pairs: List[DetectionPairDto]
+
+
+class DetectionPairListResponseDto(BaseModel):
+    """DTO for a page of pairs of a run"""
+
+    run_id: UUID
+    min_similarity: float
+    total: int
+    skip: int
+    limit: int
+    pairs: List[DetectionPairDto]
+
+
+class DetectionPairDetailDto(BaseModel):
+    """DTO for a pair with its fragments"""
+
+    pair: DetectionPairDto
+    file_fragments: List[DetectionFragmentDto]
+    block_fragments: List[DetectionFragmentDto]
+
+
+class SubmitterHistoryDto(BaseModel):
+    """DTO for the cross-run similarity history of a student"""
+
+    submitted_by_uuid: UUID
+    min_similarity: float
+    total_pairs: int
+    runs_count: int
+    max_similarity: float
+    pairs: List[DetectionPairDto]
+
+
+class PairSortKey(str, Enum):
+    """Columns the pairs of a CSV export can be sorted by, highest first"""
+
+    OVERALL_SIMILARITY = "overall_similarity"
+    FRAGMENTS_COUNT = "fragments_count"
+    LONGEST_FRAGMENT_LINES = "longest_fragment_lines"
+    LONGEST_FRAGMENT_TOKENS = "longest_fragment_tokens"
+    MEAN_FRAGMENT_LINES = "mean_fragment_lines"
+    TOP_FRAGMENTS_COVERAGE = "top_fragments_coverage"
+
+
+class HeatmapForm(str, Enum):
+    """Forms of the score matrix of a pair heatmap"""
+
+    DENSE = "dense"
+    SPARSE = "sparse"
+
+
+class HeatmapFileDto(BaseModel):
+    """DTO for a compared file, a row or column of a pair heatmap"""
+
+    path: str
+    label: Optional[str] = None  # shortest trailing part of the path telling it apart from the other files shown
+    tokens: Optional[int] = None  # unknown for pairs recorded before token counts were kept
+
+
+class HeatmapCellDto(BaseModel):
+    """DTO for a scored file pair of a sparse heatmap, by row and column index"""
+
+    row: int
+    column: int
+    score: float
+
+
+class PairHeatmapDto(BaseModel):
+    """DTO for the per-file-pair scores of a pair: rows are the files of A, columns the files of B"""
+
+    model_config = ConfigDict(
+        use_enum_values=True,
+        json_schema_extra={
+            "example": {
+                "run_id": "550e8400-e29b-41d4-a716-446655440010",
+                "pair_id": "550e8400-e29b-41d4-a716-446655440030",
+                "submission_id": "550e8400-e29b-41d4-a716-446655440020",
+                "compared_submission_id": "550e8400-e29b-41d4-a716-446655440021",
+                "form": "sparse",
+                "min_score": 0.5,
+                "rows": [{"path": "main.py", "tokens": 412}, {"path": "utils.py", "tokens": 96}],
+                "columns": [{"path": "app.py", "tokens": 388}],
+                "cells": [{"row": 0, "column": 0, "score": 0.87}],
+            }
+        },
+    )
+
+    run_id: UUID
+    pair_id: UUID
+    submission_id: UUID
+    compared_submission_id: UUID
+    form: HeatmapForm
+    min_score: float
+    rows: List[HeatmapFileDto]
+    columns: List[HeatmapFileDto]
+    matrix: Optional[List[List[float]]] = None  # dense form, rows x columns, 0.0 for file pairs without similarity
+    cells: Optional[List[HeatmapCellDto]] = None  # sparse form, file pairs scored at or above min_score
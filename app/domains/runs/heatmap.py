@@ -0,0 +1,84 @@
+"""
+File-pair heatmap of a detection pair
+
+Rows are the files of one submission, columns the files of the other, and cells the similarity of the file pairs
+recorded as file fragments. Only files that took part in the comparison are listed, with their token counts:
+excluded, binary or undecodable files never appear, rather than as rows of zeros. Pairs recorded before the compared
+files were kept list the files of their file fragments only, without token counts. Files are identified by their
+path, their label adding the parent directories telling apart files of the same name.
+"""
+
+from typing import Dict, List, Optional, Tuple
+
+from app.domains.reports.display_paths import short_paths
+from app.domains.runs.dto.run_response_dto import HeatmapCellDto, HeatmapFileDto, HeatmapForm, PairHeatmapDto
+from app.domains.runs.runs_models import FragmentType
+
+
+def _compared_files(compared_files: Optional[dict], side: str) -> Dict[str, Optional[int]]:
+    return dict((compared_files or {}).get(side) or {})
+
+
+def build_heatmap(
+    pair,
+    fragments: List,
+    submission_id=None,
+    form: HeatmapForm = HeatmapForm.DENSE,
+    min_score: float = 0.0,
+) -> PairHeatmapDto:
+    """
+    Heatmap of a pair, files sorted by path
+
+    Args:
+        pair: DetectionPair of the heatmap
+        fragments: Fragments of the pair, only its file fragments are used
+        submission_id: Submission whose files are the rows, the first compared submission by default
+        form: Dense matrix of every cell or sparse list of the cells scored at or above min_score
+        min_score: Lowest score of the cells of the sparse form
+    """
+    rows = _compared_files(pair.compared_files, "submission1")
+    columns = _compared_files(pair.compared_files, "submission2")
+    scores: Dict[Tuple[str, str], float] = {}
+    for fragment in fragments:
+        if fragment.fragment_type != FragmentType.FILE:
+            continue
+        rows.setdefault(fragment.file1_path, None)
+        columns.setdefault(fragment.file2_path, None)
+        key = (fragment.file1_path, fragment.file2_path)
+        # Pairs recorded with file names only share the cell of files of the same name, keeping the most similar
+        scores[key] = max(scores.get(key, 0.0), fragment.similarity)
+
+    first, second = pair.submission_id, pair.compared_submission_id
+    if submission_id is not None and str(submission_id) == str(pair.compared_submission_id):
+        rows, columns = columns, rows
+        first, second = second, first
+        scores = {(column, row): score for (row, column), score in scores.items()}
+
+    row_paths, column_paths = sorted(rows), sorted(columns)
+    row_labels, column_labels = short_paths(row_paths), short_paths(column_paths)
+    heatmap = PairHeatmapDto(
+        run_id=pair.run_id,
+        pair_id=pair.id,
+        submission_id=first,
+        compared_submission_id=second,
+        form=form,
+        min_score=min_score,
+        rows=[HeatmapFileDto(path=path, label=row_labels[path], tokens=rows[path]) for path in row_paths],
+        columns=[
+            HeatmapFileDto(path=path, label=column_labels[path], tokens=columns[path]) for path in column_paths
+        ],
+    )
+    if form == HeatmapForm.SPARSE:
+        row_index = {path: i for i, path in enumerate(row_paths)}
+        column_index = {path: j for j, path in enumerate(column_paths)}
+        heatmap.cells = sorted(
+            (
+                HeatmapCellDto(row=row_index[row], column=column_index[column], score=score)
+                for (row, column), score in scores.items()
+                if score >= min_score
+            ),
+            key=lambda cell: (cell.row, cell.column),
+        )
+    else:
+        heatmap.matrix = [[scores.get((row, column), 0.0) for column in column_paths] for row in row_paths]
+    return heatmap
@@ -0,0 +1,149 @@
+"""
+Match statistics of detection pairs and their CSV export
+
+Two pairs of the same similarity can be forty scattered one-line matches or a single long block. The statistics
+tell them apart from the block fragments of a pair: number of blocks, longest block in lines and tokens, mean
+block length and the share of the matched lines held by the TOP_FRAGMENTS longest blocks. They are always
+recomputed from the fragment rows, so they cannot drift from the fragment list of the pair view.
+"""
+
+import csv
+import io
+from typing import Dict, Iterable, Iterator, List, Optional, Tuple
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.summary import line_span
+from app.domains.runs.dto.run_response_dto import PairMatchStatsDto, PairSortKey
+from app.domains.runs.runs_models import FragmentType
+from app.shared.i18n import DEFAULT_LOCALE, Locale, translate
+
+TOP_FRAGMENTS = 3
+
+CSV_COLUMNS = [
+    "pair_id",
+    "submission_id",
+    "compared_submission_id",
+    "submitted_by_uuid",
+    "compared_submitted_by_uuid",
+    "overall_similarity",
+    "fragments_count",
+    "longest_fragment_lines",
+    "longest_fragment_tokens",
+    "mean_fragment_lines",
+    "top_fragments_coverage",
+]
+
+
+def block_lines(fragment) -> int:
+    """Lines of a block, its longer side"""
+    return max(
+        line_span(fragment.file1_start_line, fragment.file1_end_line),
+        line_span(fragment.file2_start_line, fragment.file2_end_line),
+    )
+
+
+def block_tokens(fragment) -> Optional[int]:
+    """Tokens of a block, its shorter side, None for blocks recorded without token counts"""
+    return (fragment.details or {}).get("matched_tokens")
+
+
+def side_coverage(pair, fragments: Iterable) -> Tuple[float, float]:
+    """
+    Share of the tokens of the compared files of each side of a pair matched by its blocks, capped at 1.0
+
+    A block matches the tokens of its shorter side on both sides, so the larger submission gets the lower share.
+    Pairs recorded without token counts get their overall similarity on both sides.
+    """
+    tokens = [block_tokens(f) for f in fragments if f.fragment_type == FragmentType.BLOCK]
+    matched = sum(tokens) if tokens and None not in tokens else None
+
+    def share(side: str) -> float:
+        total = sum(count or 0 for count in ((pair.compared_files or {}).get(side) or {}).values())
+        if matched is None or not total:
+            return pair.overall_similarity
+        return min(1.0, matched / total)
+
+    return share("submission1"), share("submission2")
+
+
+def match_stats(fragments: Iterable) -> PairMatchStatsDto:
+    """Match statistics of a pair from its fragments, file fragments are ignored"""
+    blocks = [f for f in fragments if f.fragment_type == FragmentType.BLOCK]
+    if not blocks:
+        return PairMatchStatsDto(
+            fragments_count=0, longest_fragment_lines=0, mean_fragment_lines=0.0, top_fragments_coverage=0.0
+        )
+
+    lines = sorted((block_lines(f) for f in blocks), reverse=True)
+    total = sum(lines)
+    longest = max(blocks, key=lambda f: (block_lines(f), block_tokens(f) or 0, f.similarity))
+    return PairMatchStatsDto(
+        fragments_count=len(blocks),
+        longest_fragment_lines=lines[0],
+        longest_fragment_tokens=block_tokens(longest),
+        mean_fragment_lines=round(total / len(blocks), 4),
+        top_fragments_coverage=round(sum(lines[:TOP_FRAGMENTS]) / total, 4) if total else 0.0,
+    )
+
+
+def with_match_stats(pair_dtos: Iterable, fragments_by_pair: Dict[str, List]) -> None:
+    """Set the match statistics of pair DTOs from the fragments of their pairs, by pair ID"""
+    for dto in pair_dtos:
+        dto.match_stats = match_stats(fragments_by_pair.get(str(dto.id), []))
+
+
+def _sort_key(row: Tuple, sort: PairSortKey) -> tuple:
+    pair, stats = row
+    value = pair.overall_similarity if sort == PairSortKey.OVERALL_SIMILARITY else getattr(stats, sort.value)
+    return (
+        value is None,
+        -(value or 0),
+        -pair.overall_similarity,
+        str(pair.submission_id),
+        str(pair.compared_submission_id),
+        str(pair.id),
+    )
+
+
+def pairs_csv(
+    rows: Iterable[Tuple],
+    sort: PairSortKey = PairSortKey.OVERALL_SIMILARITY,
+    pseudonyms: Optional[RunPseudonyms] = None,
+    locale: Locale = DEFAULT_LOCALE,
+) -> Iterator[str]:
+    """
+    CSV lines of pairs with their match statistics, header first
+
+    Args:
+        rows: (pair, PairMatchStatsDto) tuples
+        sort: Column the pairs are sorted by, highest first, unknown token counts last
+        pseudonyms: Pseudonyms of the run replacing submission and submitter identifiers, None to keep them
+        locale: Language of the header, the column names themselves in English, the rows being the same anyway
+    """
+    identity = pseudonyms or (lambda value: value)
+    buffer = io.StringIO()
+    writer = csv.writer(buffer, lineterminator="\n")
+
+    def line(values: list) -> str:
+        buffer.seek(0)
+        buffer.truncate()
+        writer.writerow(values)
+        return buffer.getvalue()
+
+    yield line([translate(f"csv.{column}", locale, default=column) for column in CSV_COLUMNS])
+    for pair, stats in sorted(rows, key=lambda row: _sort_key(row, sort)):
+        yield line(
+            [
+                pair.id,
+                identity(pair.submission_id),
+                identity(pair.compared_submission_id),
+                identity(pair.submitted_by_uuid) or "",
+                identity(pair.compared_submitted_by_uuid) or "",
+                pair.overall_similarity,
+                stats.fragments_count,
+                stats.longest_fragment_lines,
+                "" if stats.longest_fragment_tokens is None else stats.longest_fragment_tokens,
+                stats.mean_fragment_lines,
+                stats.top_fragments_coverage,
+            ]
+        )
@@ -0,0 +1,91 @@
+"""
+Highlighted code of the shared blocks of a pair view
+
+Each side of a block is highlighted with the lexers of the HTML reports, with a few context lines around the
+matched region. Lines keep their number in the file, so highlighting never shifts them relative to the line ranges
+of the fragments, and files of languages without a lexer come out as escaped plain text. Sides located by byte
+offsets, in files with long lines, show their matched bytes only, cut to the excerpt budget.
+"""
+
+from typing import Dict, Iterable, Optional, Tuple
+
+from app.domains.reports.highlighting import MATCH_END, MATCH_START, highlight_lines, highlight_region, language_name
+from app.domains.reports.html_report import SourceReader
+from app.domains.runs.dto.run_response_dto import DetectionFragmentDto, HighlightedCodeDto, HighlightedLineDto
+from app.domains.runs.runs_models import FragmentType
+from app.domains.tokenization.line_index import DEFAULT_EXCERPT_MAX_BYTES, byte_excerpt
+
+# Lines shown before and after a matched region
+CONTEXT_LINES = 3
+# Matched lines shown per side of a block, longer blocks are cut
+MAX_HIGHLIGHTED_LINES = 200
+
+
+def highlighted_code(
+    content: Optional[str], path: str, start: Optional[int], end: Optional[int], context: int = CONTEXT_LINES
+) -> HighlightedCodeDto:
+    """Highlighted region of a file, start and end 0-based and inclusive like the fragment rows"""
+    if content is None or start is None:
+        return HighlightedCodeDto(path=path, language=language_name(path), available=False)
+    end = min(max(start, end if end is not None else start), start + MAX_HIGHLIGHTED_LINES - 1)
+    return HighlightedCodeDto(
+        path=path,
+        language=language_name(path),
+        lines=[
+            HighlightedLineDto(number=number, html=html, matched=matched)
+            for number, html, matched in highlight_region(content, path, start, end, context)
+        ],
+    )
+
+
+def highlighted_excerpt(
+    content: Optional[str],
+    path: str,
+    start: Optional[int],
+    start_byte: int,
+    end_byte: int,
+    max_bytes: int = DEFAULT_EXCERPT_MAX_BYTES,
+) -> HighlightedCodeDto:
+    """Highlighted bytes of a side located by byte offsets, every line matched and numbered from start"""
+    if content is None:
+        return HighlightedCodeDto(path=path, language=language_name(path), available=False)
+    excerpt = byte_excerpt(content, start_byte, end_byte, max_bytes)
+    return HighlightedCodeDto(
+        path=path,
+        language=language_name(path),
+        lines=[
+            HighlightedLineDto(number=(start or 0) + index + 1, html=f"{MATCH_START}{html}{MATCH_END}", matched=True)
+            for index, html in enumerate(highlight_lines(excerpt, path))
+        ],
+    )
+
+
+def highlight_blocks(
+    pair,
+    fragments: Iterable[DetectionFragmentDto],
+    read_source: SourceReader,
+    excerpt_max_bytes: int = DEFAULT_EXCERPT_MAX_BYTES,
+) -> None:
+    """Set the highlighted code of both sides of the block fragments of a pair, each file read once"""
+    contents: Dict[Tuple[str, str], Optional[str]] = {}
+
+    def read(submission_id, path: str) -> Optional[str]:
+        key = (str(submission_id), path)
+        if key not in contents:
+            contents[key] = read_source(*key)
+        return contents[key]
+
+    def code(submission_id, fragment: DetectionFragmentDto, side: str) -> HighlightedCodeDto:
+        path, start = getattr(fragment, f"{side}_path"), getattr(fragment, f"{side}_start_line")
+        content = read(submission_id, path)
+        details = fragment.details or {}
+        if f"{side}_start_byte" in details:
+            byte_range = details[f"{side}_start_byte"], details[f"{side}_end_byte"]
+            return highlighted_excerpt(content, path, start, *byte_range, excerpt_max_bytes)
+        return highlighted_code(content, path, start, getattr(fragment, f"{side}_end_line"))
+
+    for fragment in fragments:
+        if fragment.fragment_type != FragmentType.BLOCK.value:
+            continue
+        fragment.file1_code = code(pair.submission_id, fragment, "file1")
+        fragment.file2_code = code(pair.compared_submission_id, fragment, "file2")
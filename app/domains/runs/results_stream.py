@@ -0,0 +1,159 @@
+"""
+NDJSON stream of the full results of a detection run
+
+The stream is one JSON document per line, each with a "type":
+
+    metadata    the run, its participants, the files left out because they failed and the options of the stream,
+                always first
+    pair        a persisted pair whatever its status, with its fragments inlined or the URL of its pair view
+    cluster     a cluster of the completed pairs at or above the cluster threshold, largest first
+    end         the number of pairs and clusters written, always last
+
+Pairs are read and written in batches so that only one batch of pairs and fragments is held at a time; clusters
+are built as the pairs go by and only keep the submissions seen. A stream cut short by a failure has no end
+record, which is how clients tell it from a complete one.
+"""
+
+import json
+import zlib
+from typing import Callable, Dict, Iterable, Iterator, List, Optional
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.clusters import ClusterFinder
+from app.domains.runs.dto.run_response_dto import (
+    DetectionFragmentDto,
+    DetectionPairDto,
+    DetectionRunDto,
+    DetectionRunParticipantDto,
+)
+from app.domains.submissions.submissions_models import SimilarityStatus
+
+RESULTS_FORMAT = "pamp-run-results"
+RESULTS_FORMAT_VERSION = 1
+RESULTS_CONTENT_TYPE = "application/x-ndjson"
+# Window bits of zlib giving a gzip header and trailer
+_GZIP_WBITS = 31
+
+FragmentsReader = Callable[[List], Dict[str, List]]
+
+
+def pair_view_url(pair_id) -> str:
+    """URL of the pair view listing the fragments of a pair"""
+    return f"/runs/pairs/{pair_id}"
+
+
+def results_ndjson(
+    run,
+    participants: List,
+    pairs: Iterable,
+    read_fragments: FragmentsReader,
+    min_score: float,
+    cluster_threshold: float,
+    include_fragments: bool = False,
+    pseudonyms: Optional[RunPseudonyms] = None,
+    batch_size: int = 500,
+) -> Iterator[str]:
+    """
+    Lines of the NDJSON results of a run, one chunk of lines per batch of pairs
+
+    Args:
+        run: DetectionRun of the stream
+        participants: Participants of the run
+        pairs: Pairs of the run at or above min_score, each once, read lazily
+        read_fragments: Reader of the fragments of a batch of pair IDs by pair ID, only called with include_fragments
+        min_score: Similarity at or above which pairs were read
+        cluster_threshold: Similarity at or above which completed pairs link their submissions in clusters
+        include_fragments: Inline the fragments of each pair instead of the URL of its pair view
+        pseudonyms: Pseudonyms of the run replacing identities and home directories everywhere, None to keep them
+    """
+
+    def line(record: dict) -> str:
+        if pseudonyms is not None:
+            record = pseudonyms.anonymize(record)
+        return json.dumps(record, separators=(",", ":")) + "\n"
+
+    yield line(
+        {
+            "type": "metadata",
+            "format": RESULTS_FORMAT,
+            "version": RESULTS_FORMAT_VERSION,
+            "run": DetectionRunDto.model_validate(run).model_dump(mode="json"),
+            "participants": [
+                DetectionRunParticipantDto.model_validate(p).model_dump(mode="json") for p in participants
+            ],
+            "file_errors": getattr(run, "file_errors", None) or [],
+            "min_score": min_score,
+            "cluster_threshold": cluster_threshold,
+            "include_fragments": include_fragments,
+        }
+    )
+
+    finder = ClusterFinder(cluster_threshold)
+    written = 0
+    batch: List = []
+
+    def flush() -> str:
+        fragments = read_fragments([p.id for p in batch]) if include_fragments and batch else {}
+        lines = []
+        for pair in batch:
+            record = {"type": "pair", **DetectionPairDto.model_validate(pair).model_dump(mode="json")}
+            record.pop("match_stats", None)
+            if include_fragments:
+                record["fragments"] = [
+                    DetectionFragmentDto.model_validate(f).model_dump(mode="json", exclude={"file1_code", "file2_code"})
+                    for f in fragments.get(str(pair.id), [])
+                ]
+            else:
+                record["fragments_url"] = pair_view_url(pair.id)
+            lines.append(line(record))
+        batch.clear()
+        return "".join(lines)
+
+    for pair in pairs:
+        if pair.status == SimilarityStatus.COMPLETED:
+            finder.add(pair)
+        batch.append(pair)
+        written += 1
+        if len(batch) == batch_size:
+            yield flush()
+    if batch:
+        yield flush()
+
+    clusters = finder.clusters()
+    for cluster in clusters:
+        yield line(
+            {
+                "type": "cluster",
+                "members": cluster.members,
+                "pair_count": cluster.pair_count,
+                "max_similarity": cluster.max_similarity,
+            }
+        )
+    yield line({"type": "end", "pairs": written, "clusters": len(clusters)})
+
+
+def gzip_chunks(chunks: Iterable[str]) -> Iterator[bytes]:
+    """Gzip a stream of text chunks as it goes, holding no more than the compressor window"""
+    compressor = zlib.compressobj(wbits=_GZIP_WBITS)
+    for chunk in chunks:
+        compressed = compressor.compress(chunk.encode("utf-8"))
+        if compressed:
+            yield compressed
+    yield compressor.flush()
+
+
+def accepts_gzip(accept_encoding: Optional[str]) -> bool:
+    """Whether an Accept-Encoding header allows a gzip response"""
+    for coding in (accept_encoding or "").split(","):
+        name, *parameters = (part.strip() for part in coding.split(";"))
+        if name.lower() not in ("gzip", "x-gzip"):
+            continue
+        for parameter in parameters:
+            key, _, value = parameter.partition("=")
+            if key.strip().lower() == "q":
+                try:
+                    return float(value) > 0
+                except ValueError:
+                    return False
+        return True
+    return False
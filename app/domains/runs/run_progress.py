@@ -0,0 +1,373 @@
+"""
+Live progress of detection runs, streamed as Server-Sent Events
+
+The workers of a run report its stage, the files they tokenized, the pairs they compared and their warnings to the
+RunProgress of the run. Each report becomes an event numbered after the previous one of the run, kept in a bounded
+history and broadcast to the streams following the run:
+
+    snapshot    the state of the run, first event of a stream
+    stage       the run entered a stage: queued, candidate_generation, comparison
+    progress    files tokenized and pairs compared so far, at most one per PROGRESS_INTERVAL_SECONDS
+    warning     a file failed, a pair timed out or could not be recorded, the run goes on
+    completed   the run finished, completed, incomplete or partial, last event of a stream
+    failed      the run failed, last event of a stream
+    interrupted the run was stopped by a shutdown and will be resumed, last event of the stream of its instance
+
+Every event carries the counters of the run, which only grow. A stream resumes after the Last-Event-ID of a
+reconnecting client from the history, or starts over with a snapshot when the history no longer reaches it; the
+terminal event is kept until the run is forgotten, so a client reconnecting after the end still gets it.
+
+Progress lives in the memory of the instance running the run, and is forgotten RETENTION_SECONDS after the run
+finished. Streams of the runs an instance does not know, forgotten or running on another instance, are built from
+the run in the database instead, without file counters.
+"""
+
+import asyncio
+import json
+import logging
+import threading
+import time
+from collections import deque
+from dataclasses import dataclass
+from typing import Any, AsyncIterator, Awaitable, Callable, Dict, List, Optional, Tuple
+from uuid import UUID
+
+logger = logging.getLogger(__name__)
+
+SNAPSHOT = "snapshot"
+STAGE = "stage"
+PROGRESS = "progress"
+WARNING = "warning"
+COMPLETED = "completed"
+FAILED = "failed"
+INTERRUPTED = "interrupted"
+TERMINAL_EVENTS = (COMPLETED, FAILED, INTERRUPTED)
+
+# Statuses of the runs whose pairs may still change, read from the database
+UNFINISHED_STATUSES = ("running", "interrupted")
+
+
+def terminal_type(status: str) -> str:
+    """Type of the last event of a run ending with a status"""
+    return {"failed": FAILED, "interrupted": INTERRUPTED}.get(status, COMPLETED)
+
+
+HISTORY_SIZE = 256
+PROGRESS_INTERVAL_SECONDS = 0.25
+RETENTION_SECONDS = 600.0
+KEEPALIVE_SECONDS = 15.0
+DATABASE_POLL_SECONDS = 2.0
+
+
+@dataclass(frozen=True)
+class ProgressEvent:
+    """Event of a run, numbered within the run, None for the events built from the database"""
+
+    id: Optional[int]
+    type: str
+    data: Dict[str, Any]
+
+    @property
+    def terminal(self) -> bool:
+        return self.type in TERMINAL_EVENTS
+
+    def to_sse(self) -> str:
+        lines = [f"id: {self.id}"] if self.id is not None else []
+        lines += [f"event: {self.type}", f"data: {json.dumps(self.data, separators=(',', ':'), default=str)}"]
+        return "\n".join(lines) + "\n\n"
+
+
+class RunProgress:
+    """Thread-safe progress of one run, turning the reports of its workers into events"""
+
+    def __init__(
+        self,
+        run_id: Optional[UUID],
+        publish: Callable[[ProgressEvent], None] = lambda event: None,
+        total_pairs: int = 0,
+        history_size: int = HISTORY_SIZE,
+        progress_interval_seconds: float = PROGRESS_INTERVAL_SECONDS,
+        clock: Callable[[], float] = time.monotonic,
+    ):
+        self.run_id = run_id
+        self.total_pairs = total_pairs
+        self.stage = "queued"
+        self.status = "running"
+        self.files_tokenized = 0
+        self.pairs_compared = 0
+        self.warnings = 0
+        self.finished_at: Optional[float] = None
+        self.terminal: Optional[ProgressEvent] = None
+        self.progress_interval_seconds = progress_interval_seconds
+        self._publish = publish
+        self._clock = clock
+        self._history: deque = deque(maxlen=max(history_size, 1))
+        self._last_id = 0
+        self._last_progress_at: Optional[float] = None
+        self._lock = threading.Lock()
+
+    def _state(self) -> Dict[str, Any]:
+        return {
+            "run_id": str(self.run_id) if self.run_id is not None else None,
+            "stage": self.stage,
+            "status": self.status,
+            "files_tokenized": self.files_tokenized,
+            "pairs_compared": self.pairs_compared,
+            "total_pairs": self.total_pairs,
+            "warnings": self.warnings,
+        }
+
+    def _emit(self, event_type: str, **data: Any) -> ProgressEvent:
+        # Called with the lock held, so events are numbered and broadcast in the same order
+        self._last_id += 1
+        event = ProgressEvent(self._last_id, event_type, {**self._state(), **data})
+        self._history.append(event)
+        try:
+            self._publish(event)
+        except Exception as e:
+            logger.warning(f"Failed to broadcast {event_type} event of run {self.run_id}: {str(e)}")
+        return event
+
+    def _counted(self) -> None:
+        now = self._clock()
+        if self._last_progress_at is None or now - self._last_progress_at >= self.progress_interval_seconds:
+            self._last_progress_at = now
+            self._emit(PROGRESS)
+
+    def set_stage(self, stage: str) -> None:
+        with self._lock:
+            if self.terminal is None and stage != self.stage:
+                self.stage = stage
+                self._emit(STAGE)
+
+    def file_tokenized(self, count: int = 1) -> None:
+        with self._lock:
+            if self.terminal is None:
+                self.files_tokenized += count
+                self._counted()
+
+    def pair_compared(self, count: int = 1) -> None:
+        with self._lock:
+            if self.terminal is None:
+                self.pairs_compared += count
+                self._counted()
+
+    def warn(self, message: str, **details: Any) -> None:
+        with self._lock:
+            if self.terminal is None:
+                self.warnings += 1
+                self._emit(WARNING, message=message, **details)
+
+    def finish(self, status: str, error_message: Optional[str] = None) -> None:
+        """End the run with its final status, later reports are ignored"""
+        with self._lock:
+            if self.terminal is None:
+                self.status = getattr(status, "value", status)
+                self.stage = "finished"
+                self.finished_at = self._clock()
+                self.terminal = self._emit(terminal_type(self.status), error_message=error_message)
+
+    def snapshot(self) -> ProgressEvent:
+        """The state of the run, numbered after the last event so a reconnection resumes after it"""
+        with self._lock:
+            return ProgressEvent(self._last_id, SNAPSHOT, self._state())
+
+    def events_after(self, last_id: int) -> Optional[List[ProgressEvent]]:
+        """Events after an event of the run, None when the history no longer reaches it"""
+        with self._lock:
+            if last_id >= self._last_id:
+                return []
+            if not self._history or self._history[0].id > last_id + 1:
+                return None
+            return [event for event in self._history if event.id > last_id]
+
+
+class NullRunProgress(RunProgress):
+    """Progress of runs that are not followed, records nothing"""
+
+    def __init__(self):
+        super().__init__(None)
+
+    def set_stage(self, stage: str) -> None:
+        pass
+
+    def file_tokenized(self, count: int = 1) -> None:
+        pass
+
+    def pair_compared(self, count: int = 1) -> None:
+        pass
+
+    def warn(self, message: str, **details: Any) -> None:
+        pass
+
+    def finish(self, status: str, error_message: Optional[str] = None) -> None:
+        pass
+
+
+NULL_RUN_PROGRESS = NullRunProgress()
+
+
+class RunProgressRegistry:
+    """
+    Progress of the runs of this instance and the streams following them
+
+    Events are broadcast to each stream through an asyncio queue of its event loop, filled from the worker threads.
+    """
+
+    def __init__(self, retention_seconds: float = RETENTION_SECONDS, clock: Callable[[], float] = time.monotonic):
+        self.retention_seconds = retention_seconds
+        self._clock = clock
+        self._runs: Dict[UUID, RunProgress] = {}
+        self._subscribers: Dict[UUID, List[Tuple[asyncio.AbstractEventLoop, asyncio.Queue]]] = {}
+        self._lock = threading.Lock()
+
+    def start(self, run_id: UUID, total_pairs: int = 0) -> RunProgress:
+        """Progress of a new run, queued"""
+        progress = RunProgress(run_id, lambda event: self._broadcast(run_id, event), total_pairs, clock=self._clock)
+        with self._lock:
+            self._forget_finished()
+            self._runs[run_id] = progress
+        return progress
+
+    def get(self, run_id: UUID) -> Optional[RunProgress]:
+        with self._lock:
+            return self._runs.get(run_id)
+
+    def _forget_finished(self) -> None:
+        now = self._clock()
+        for run_id, progress in list(self._runs.items()):
+            if progress.finished_at is not None and now - progress.finished_at >= self.retention_seconds:
+                del self._runs[run_id]
+
+    def subscribe(self, run_id: UUID) -> asyncio.Queue:
+        """Queue receiving the next events of a run, from within the event loop of the stream"""
+        queue: asyncio.Queue = asyncio.Queue()
+        with self._lock:
+            self._subscribers.setdefault(run_id, []).append((asyncio.get_running_loop(), queue))
+        return queue
+
+    def unsubscribe(self, run_id: UUID, queue: asyncio.Queue) -> None:
+        with self._lock:
+            subscribers = [s for s in self._subscribers.get(run_id, []) if s[1] is not queue]
+            if subscribers:
+                self._subscribers[run_id] = subscribers
+            else:
+                self._subscribers.pop(run_id, None)
+
+    def subscriber_count(self, run_id: UUID) -> int:
+        with self._lock:
+            return len(self._subscribers.get(run_id, []))
+
+    def _broadcast(self, run_id: UUID, event: ProgressEvent) -> None:
+        with self._lock:
+            subscribers = list(self._subscribers.get(run_id, []))
+        for loop, queue in subscribers:
+            try:
+                loop.call_soon_threadsafe(queue.put_nowait, event)
+            except RuntimeError:
+                # The loop of the stream is closed, the stream is gone
+                self.unsubscribe(run_id, queue)
+
+
+RUN_PROGRESS = RunProgressRegistry()
+
+
+def parse_last_event_id(value: Optional[str]) -> Optional[int]:
+    try:
+        return int(value) if value else None
+    except ValueError:
+        return None
+
+
+def run_state(run) -> Dict[str, Any]:
+    """Event data of a run read from the database, pairs counted once persisted"""
+    status = getattr(run.status, "value", run.status)
+    return {
+        "run_id": str(run.id),
+        "stage": "comparison" if status in UNFINISHED_STATUSES else "finished",
+        "status": status,
+        "files_tokenized": None,
+        "pairs_compared": run.completed_pairs + run.failed_pairs,
+        "total_pairs": run.total_pairs,
+        "warnings": len(run.file_errors or []),
+    }
+
+
+def terminal_event(run) -> ProgressEvent:
+    state = run_state(run)
+    return ProgressEvent(None, terminal_type(state["status"]), {**state, "error_message": run.error_message})
+
+
+async def run_events(
+    run_id: UUID,
+    read_run: Callable[[], Any],
+    last_event_id: Optional[str] = None,
+    registry: RunProgressRegistry = RUN_PROGRESS,
+    is_disconnected: Optional[Callable[[], Awaitable[bool]]] = None,
+    keepalive_seconds: float = KEEPALIVE_SECONDS,
+    poll_seconds: float = DATABASE_POLL_SECONDS,
+) -> AsyncIterator[str]:
+    """
+    Server-Sent Events of a run, until its terminal event or the client went away
+
+    Args:
+        run_id: ID of the run
+        read_run: Reader of the run from the database, called in a thread, for runs this instance does not know
+        last_event_id: Last-Event-ID header of a reconnecting client
+        is_disconnected: Whether the client went away, checked at each keep-alive
+    """
+    queue = registry.subscribe(run_id)
+    try:
+        progress = registry.get(run_id)
+        if progress is None:
+            async for chunk in _database_events(read_run, is_disconnected, poll_seconds):
+                yield chunk
+            return
+
+        last_id = parse_last_event_id(last_event_id)
+        replay = progress.events_after(last_id) if last_id is not None else None
+        if replay is None:
+            snapshot = progress.snapshot()
+            replay = [snapshot] + ([progress.terminal] if progress.terminal is not None else [])
+        sent = last_id or 0
+        for event in replay:
+            yield event.to_sse()
+            sent = max(sent, event.id)
+            if event.terminal:
+                return
+
+        while True:
+            try:
+                event = await asyncio.wait_for(queue.get(), keepalive_seconds)
+            except asyncio.TimeoutError:
+                if is_disconnected is not None and await is_disconnected():
+                    return
+                yield ": keep-alive\n\n"
+                continue
+            if event.id <= sent:
+                continue
+            yield event.to_sse()
+            sent = event.id
+            if event.terminal:
+                return
+    finally:
+        registry.unsubscribe(run_id, queue)
+
+
+async def _database_events(
+    read_run: Callable[[], Any], is_disconnected: Optional[Callable[[], Awaitable[bool]]], poll_seconds: float
+) -> AsyncIterator[str]:
+    run = await asyncio.to_thread(read_run)
+    state = run_state(run)
+    yield ProgressEvent(None, SNAPSHOT, state).to_sse()
+    # Interrupted runs are followed until an instance resumes and finishes them
+    while state["status"] in UNFINISHED_STATUSES:
+        await asyncio.sleep(poll_seconds)
+        if is_disconnected is not None and await is_disconnected():
+            return
+        run = await asyncio.to_thread(read_run)
+        current = run_state(run)
+        if current["status"] in UNFINISHED_STATUSES and current != state:
+            yield ProgressEvent(None, PROGRESS, current).to_sse()
+        state = current
+    yield terminal_event(run).to_sse()
@@ -0,0 +1,243 @@
+import logging
+from typing import Dict, List, Optional
+from uuid import UUID
+
+from app.domains.detection.file_errors import FileError
+from app.domains.runs.runs_models import DetectionFragment, DetectionPair, DetectionRunStatus, FragmentType
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.domains.tokenization.line_index import BYTE_OFFSET_KEYS
+from app.shared.metrics import COMPARED_PAIRS
+
+logger = logging.getLogger(__name__)
+
+PAIR_METRICS = (
+    "overall_similarity",
+    "jaccard_similarity",
+    "type_similarity",
+    "structural_similarity",
+    "type_sequence_similarity",
+    "flow_similarity",
+    "operation_similarity",
+)
+
+
+class DetectionRunRecorder:
+    """
+    Buffers the pairs and fragments produced by a run and writes them in batches,
+    so a crash loses at most the batch currently being filled.
+
+    A pair and its fragments always land in the same batch, hence the same transaction: a lost batch never leaves
+    orphan fragments. A run that lost batches, because a write failed or it was aborted, is finished as incomplete.
+    A run stopped by a shutdown flushes its last batch and is interrupted, to be resumed from the persisted pairs.
+    """
+
+    def __init__(self, repository: DetectionRunRepository, run_id: UUID, batch_size: int = 500):
+        self.repository = repository
+        self.run_id = run_id
+        self.batch_size = max(1, batch_size)
+
+        self._pairs: List[DetectionPair] = []
+        self._fragments: List[DetectionFragment] = []
+        self.persisted_pairs = 0
+        self.lost_pairs = 0
+        # Reason of each participant recorded as not comparable, written when the run finishes
+        self.not_comparable: Dict[UUID, str] = {}
+        # Files that failed in any comparison, once per submission, path and stage, written when the run finishes
+        self.file_errors: Dict[tuple, FileError] = {}
+
+    def restore_file_errors(self, file_errors: Optional[list]) -> None:
+        """Keep the file errors recorded before the run was interrupted, for a resumed run"""
+        for error in file_errors or []:
+            file_error = FileError.from_dict(error)
+            self.file_errors.setdefault((file_error.submission_id, file_error.path, file_error.stage), file_error)
+
+    def record_pair(self, pair_data: dict, fragments: Optional[List[dict]] = None) -> DetectionPair:
+        """Buffer one pair with its fragments, flushing when the batch is full"""
+        fragments = fragments or []
+        pair = DetectionPair(run_id=self.run_id, fragments_count=len(fragments), **pair_data)
+        self._pairs.append(pair)
+        self._fragments.extend(DetectionFragment(pair_id=pair.id, run_id=self.run_id, **f) for f in fragments)
+        COMPARED_PAIRS.labels(getattr(pair.status, "value", pair.status)).inc()
+
+        if len(self._pairs) >= self.batch_size:
+            self.flush()
+        return pair
+
+    def record_comparison(self, similarity, submission1, submission2, results: Optional[dict] = None) -> DetectionPair:
+        """
+        Buffer the result of a submission comparison
+
+        Args:
+            similarity: SubmissionSimilarity record of the comparison (completed or failed)
+            submission1: First compared submission
+            submission2: Second compared submission
+            results: Raw results of the comparison, used to extract fragments
+        """
+        pair_data = {
+            "project_uuid": submission1.project_uuid,
+            "project_step_uuid": submission1.project_step_uuid,
+            "submission_id": submission1.id,
+            "compared_submission_id": submission2.id,
+            "submitted_by_uuid": submission1.submitted_by_uuid,
+            "compared_submitted_by_uuid": submission2.submitted_by_uuid,
+            "similarity_id": similarity.id if similarity else None,
+            "status": similarity.status if similarity else SimilarityStatus.FAILED,
+            "error_message": similarity.error_message if similarity else "Comparison did not produce a result",
+            "processing_time_seconds": similarity.processing_time_seconds if similarity else None,
+        }
+        for metric in PAIR_METRICS:
+            pair_data[metric] = getattr(similarity, metric, 0.0) if similarity else 0.0
+
+        similarity_details = (results or {}).get("similarity_details") or getattr(
+            similarity, "similarity_details", None
+        )
+        pair_data["matched_tokens"] = (similarity_details or {}).get("common_elements")
+        pair_data["compared_files"] = (similarity_details or {}).get("files_tokens")
+        pair_data["low_confidence"] = bool((similarity_details or {}).get("low_confidence"))
+        # Timed out pairs are flagged like those with failed files, their scores are missing
+        pair_data["partial"] = bool((similarity_details or {}).get("partial")) or (
+            pair_data["status"] == SimilarityStatus.TIMED_OUT
+        )
+        errors = (similarity_details or {}).get("file_errors") or {}
+        reasons = (similarity_details or {}).get("not_comparable") or {}
+        for side, submission in (("submission1", submission1), ("submission2", submission2)):
+            if reasons.get(side):
+                self.not_comparable[submission.id] = reasons[side]
+            for error in errors.get(side) or []:
+                file_error = FileError.from_dict({**error, "submission_id": str(submission.id)})
+                self.file_errors.setdefault((file_error.submission_id, file_error.path, file_error.stage), file_error)
+
+        visualization_data = (results or {}).get("visualization_data")
+        if visualization_data is None and similarity is not None:
+            visualization_data = similarity.visualization_data
+        return self.record_pair(pair_data, extract_fragments(visualization_data))
+
+    def record_pruned(self, submission1, submission2, estimated_similarity: float) -> DetectionPair:
+        """Buffer a pair that was pruned before its detailed comparison, its metrics are left at zero"""
+        return self.record_pair(
+            {
+                "project_uuid": submission1.project_uuid,
+                "project_step_uuid": submission1.project_step_uuid,
+                "submission_id": submission1.id,
+                "compared_submission_id": submission2.id,
+                "submitted_by_uuid": submission1.submitted_by_uuid,
+                "compared_submitted_by_uuid": submission2.submitted_by_uuid,
+                "status": SimilarityStatus.PRUNED,
+                "estimated_similarity": estimated_similarity,
+            }
+        )
+
+    def flush(self) -> int:
+        """Write the buffered pairs and fragments, returns the number of written pairs"""
+        if not self._pairs and not self._fragments:
+            return 0
+
+        pairs, fragments = self._pairs, self._fragments
+        self._pairs, self._fragments = [], []
+
+        try:
+            inserted = self.repository.insert_batch(self.run_id, pairs, fragments)
+        except Exception:
+            self.lost_pairs += len(pairs)
+            raise
+        self.persisted_pairs += inserted
+        logger.debug(f"Persisted batch of {inserted} pairs and {len(fragments)} fragments for run {self.run_id}")
+        return inserted
+
+    def finish(
+        self,
+        status: DetectionRunStatus = DetectionRunStatus.COMPLETED,
+        error_message: Optional[str] = None,
+        cache_stats: Optional[dict] = None,
+        profile: Optional[dict] = None,
+    ):
+        """Flush the last batch and close the run, as incomplete if batches were lost"""
+        try:
+            self.flush()
+        except Exception as e:
+            logger.error(f"Failed to flush last batch of run {self.run_id}: {str(e)}")
+            error_message = error_message or str(e)
+        try:
+            self.repository.mark_not_comparable(self.run_id, self.not_comparable)
+        except Exception as e:
+            logger.error(f"Failed to record not comparable participants of run {self.run_id}: {str(e)}")
+
+        if self.lost_pairs:
+            status = DetectionRunStatus.INCOMPLETE
+            lost = f"{self.lost_pairs} pairs could not be persisted"
+            error_message = f"{lost}: {error_message}" if error_message else lost
+        return self.repository.finish_run(self.run_id, status, error_message, cache_stats, profile, self._file_errors())
+
+    def interrupt(self, error_message: str, cache_stats: Optional[dict] = None, profile: Optional[dict] = None):
+        """
+        Flush the last batch as a checkpoint and mark the run interrupted, its resumption compares the pairs not
+        persisted, including those of a batch that could not be flushed
+        """
+        try:
+            self.flush()
+        except Exception as e:
+            logger.error(f"Failed to flush last batch of interrupted run {self.run_id}: {str(e)}")
+        try:
+            self.repository.mark_not_comparable(self.run_id, self.not_comparable)
+        except Exception as e:
+            logger.error(f"Failed to record not comparable participants of run {self.run_id}: {str(e)}")
+        return self.repository.interrupt_run(self.run_id, error_message, cache_stats, profile, self._file_errors())
+
+    def abort(self, error_message: str, cache_stats: Optional[dict] = None, profile: Optional[dict] = None):
+        """Drop the batch being filled and close the run as incomplete, the batches already written are kept"""
+        self.lost_pairs += len(self._pairs)
+        self._pairs, self._fragments = [], []
+        return self.repository.finish_run(
+            self.run_id, DetectionRunStatus.INCOMPLETE, error_message, cache_stats, profile, self._file_errors()
+        )
+
+    def _file_errors(self) -> List[dict]:
+        """File errors of the run, by submission, path and stage"""
+        return [self.file_errors[key].to_dict() for key in sorted(self.file_errors)]
+
+
+def extract_fragments(visualization_data: Optional[List[Dict]]) -> List[dict]:
+    """
+    Convert the per-file-pair visualization data of a comparison to fragment rows:
+    one 'file' fragment per similar file pair plus one 'block' fragment per shared code block
+    """
+    fragments = []
+    for entry in visualization_data or []:
+        file_pair = entry.get("file_pair", {})
+        react_flow = entry.get("react_flow", {})
+        file1_path = file_pair.get("file_from_submission1", "")
+        file2_path = file_pair.get("file_from_submission2", "")
+        metadata = react_flow.get("analysis_metadata", {})
+
+        fragments.append(
+            {
+                "fragment_type": FragmentType.FILE,
+                "file1_path": file1_path,
+                "file2_path": file2_path,
+                "similarity": metadata.get("average_similarity", 0.0),
+                "details": {"total_similarities": metadata.get("total_similarities", 0)},
+            }
+        )
+
+        for block in react_flow.get("shared_blocks", []):
+            fragments.append(
+                {
+                    "fragment_type": FragmentType.BLOCK,
+                    "file1_path": file1_path,
+                    "file2_path": file2_path,
+                    "file1_start_line": block.get("file1_start_line"),
+                    "file1_end_line": block.get("file1_end_line"),
+                    "file2_start_line": block.get("file2_start_line"),
+                    "file2_end_line": block.get("file2_end_line"),
+                    "similarity": block.get("similarity_score", 0.0),
+                    "details": {
+                        "file1_function": block.get("file1_function"),
+                        "file2_function": block.get("file2_function"),
+                        "matched_tokens": block.get("matched_tokens"),
+                        # Sides in files with long lines are located by byte offsets, their lines say next to nothing
+                        **{key: block[key] for key in BYTE_OFFSET_KEYS if key in block},
+                    },
+                }
+            )
+    return fragments
@@ -0,0 +1,157 @@
+"""
+Recovery of the detection runs stopped before they finished
+
+Each instance heartbeats the runs it holds. A run is abandoned when it was interrupted by a shutdown, when its
+instance stopped heartbeating for longer than the stale timeout, e.g. killed by the OOM killer, or when it was
+held by a previous process of the same instance. Abandoned runs are claimed with a conditional update, so a run
+only goes to one instance, and resumed from their persisted pairs on the detection scheduler.
+"""
+
+import logging
+import threading
+from datetime import timedelta
+from typing import Callable, List, Optional
+from uuid import UUID
+
+from app.config.config import Settings
+from app.domains.runs.runs_models import DetectionRun
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+def interrupt_queued_runs(run_ids: List[UUID], session_factory=None) -> int:
+    """Mark the runs dropped from the queue by a shutdown as interrupted, errors are logged"""
+    if not run_ids:
+        return 0
+    try:
+        with _new_session(session_factory) as session:
+            interrupted = DetectionRunRepository(session).interrupt_runs(
+                run_ids, "Interrupted by a shutdown before it started"
+            )
+        logger.info(f"{interrupted} queued detection runs interrupted by the shutdown")
+        return interrupted
+    except Exception as e:
+        logger.error(f"Failed to interrupt queued detection runs: {str(e)}")
+        return 0
+
+
+def _new_session(session_factory=None):
+    if session_factory is not None:
+        return session_factory()
+
+    from sqlmodel import Session
+
+    from app.shared.database import engine
+
+    return Session(engine)
+
+
+class RunRecovery:
+    """Background thread heartbeating the runs of this instance and resuming abandoned runs"""
+
+    def __init__(
+        self,
+        instance_id: str,
+        heartbeat_interval_seconds: float = 30.0,
+        stale_after_seconds: float = 120.0,
+        batch_size: int = 100,
+        session_factory=None,
+        resume: Optional[Callable[[DetectionRun], bool]] = None,
+    ):
+        self.instance_id = instance_id
+        self.heartbeat_interval_seconds = heartbeat_interval_seconds
+        self.stale_after_seconds = stale_after_seconds
+        self.batch_size = max(batch_size, 1)
+        self._session_factory = session_factory
+        self._resume = resume
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def resume(self, run: DetectionRun) -> bool:
+        """Queue the pairs left of a claimed run, returns whether it was queued"""
+        if self._resume is not None:
+            return self._resume(run)
+
+        from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+
+        with _new_session(self._session_factory) as session:
+            return DetectionIntegrationService(session).resume_detection_run(run)
+
+    def heartbeat_once(self) -> int:
+        """Record the runs of this instance as alive, returns how many it holds"""
+        with _new_session(self._session_factory) as session:
+            return DetectionRunRepository(session).heartbeat(self.instance_id)
+
+    def sweep_once(self, startup: bool = False) -> int:
+        """
+        Claim and resume the abandoned runs, returns how many were resumed
+
+        Args:
+            startup: Whether this process just started, the runs held by its instance are then abandoned too
+        """
+        stale_before = utc_now() - timedelta(seconds=self.stale_after_seconds)
+        restarted = self.instance_id if startup else None
+        with _new_session(self._session_factory) as session:
+            repository = DetectionRunRepository(session)
+            runs = repository.get_abandoned_runs(stale_before, restarted, self.batch_size)
+            claimed = [run for run in runs if repository.claim_run(run.id, self.instance_id, stale_before, restarted)]
+            for run in claimed:
+                session.refresh(run)
+
+        resumed = 0
+        for run in claimed:
+            try:
+                resumed += self.resume(run)
+            except Exception as e:
+                # Left claimed, its heartbeat keeps it here until this instance stops
+                logger.error(f"Failed to resume detection run {run.id}: {str(e)}")
+        if claimed:
+            logger.info(f"Claimed {len(claimed)} abandoned detection runs, {resumed} resumed")
+        return resumed
+
+    def run_once(self, startup: bool = False) -> int:
+        """Heartbeat and sweep, errors are logged and never stop the recovery"""
+        resumed = 0
+        try:
+            if not startup:
+                self.heartbeat_once()
+            resumed = self.sweep_once(startup)
+        except Exception as e:
+            logger.error(f"Detection run recovery failed: {str(e)}")
+        return resumed
+
+    def _run(self) -> None:
+        while not self._stop_event.wait(self.heartbeat_interval_seconds):
+            self.run_once()
+
+    def start(self) -> None:
+        """Take over the runs of the previous process of this instance before it creates any, then heartbeat"""
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self.run_once(startup=True)
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="run-recovery", daemon=True)
+        self._thread.start()
+        logger.info(f"Runs of instance {self.instance_id} heartbeated every {self.heartbeat_interval_seconds:g}s")
+
+    def stop(self, timeout: float = 5.0) -> None:
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+
+
+def create_run_recovery(settings: Settings) -> Optional[RunRecovery]:
+    """Build the run recovery from the settings, None when it is disabled"""
+    if not settings.run_recovery_enabled:
+        return None
+
+    from app.shared.shutdown import instance_id
+
+    return RunRecovery(
+        instance_id(),
+        settings.run_heartbeat_interval_seconds,
+        settings.run_heartbeat_stale_seconds,
+    )
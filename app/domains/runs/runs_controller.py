@@ -0,0 +1,267 @@
+from typing import List, Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, Header, HTTPException, Query, Request
+from fastapi.responses import JSONResponse, StreamingResponse
+from sqlmodel import Session
+
+from app.domains.audit.audit_hook import audited
+from app.domains.audit.audit_models import AuditAction
+from app.domains.runs.dto.run_response_dto import (
+    DetectionPairDetailDto,
+    DetectionPairListResponseDto,
+    DetectionRunDto,
+    DetectionRunReportDto,
+    HeatmapForm,
+    PairHeatmapDto,
+    PairSortKey,
+    RunReclassificationDto,
+    SubmitterHistoryDto,
+)
+from app.domains.runs.results_stream import RESULTS_CONTENT_TYPE, accepts_gzip, gzip_chunks
+from app.domains.runs.run_progress import run_events
+from app.domains.runs.runs_service import DetectionRunService
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException
+from app.shared.i18n import Locale, get_locale
+from app.shared.services import get_detection_scheduler
+
+router = APIRouter(prefix="/runs", tags=["runs"])
+
+
+def get_run_service(session: Session = Depends(get_session)) -> DetectionRunService:
+    """Dependency to get detection run service"""
+    return DetectionRunService(session, get_detection_scheduler())
+
+
+@router.get("/project/{project_uuid}/step/{project_step_uuid}", response_model=List[DetectionRunDto])
+async def list_project_step_runs(
+    project_uuid: UUID, project_step_uuid: UUID, service: DetectionRunService = Depends(get_run_service)
+):
+    """List the detection runs of a project step"""
+    try:
+        return service.list_runs(project_uuid, project_step_uuid)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get(
+    "/pairs/{pair_id}",
+    response_model=DetectionPairDetailDto,
+    dependencies=[Depends(audited(AuditAction.FRAGMENT_VIEW, "pair", "pair_id"))],
+)
+async def get_pair_detail(
+    pair_id: UUID,
+    highlight: bool = Query(False, description="Add the highlighted code of both sides of each shared block"),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """
+    Get a pair with its per-file breakdown and shared fragments
+
+    With **highlight**, each side of a block comes with its lines and a few context lines as escaped HTML with token
+    spans (classes k, s, c and n), matched lines wrapped in `<mark class="match">`. Line numbers are those of the
+    file, and files of languages without a lexer are escaped plain text.
+    """
+    try:
+        return service.get_pair_detail(pair_id, highlight)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/submitters/{submitted_by_uuid}/history", response_model=SubmitterHistoryDto)
+async def get_submitter_history(
+    submitted_by_uuid: UUID,
+    min_similarity: float = Query(0.0, ge=0.0, le=1.0, description="Only pairs at or above this similarity"),
+    project_uuid: Optional[UUID] = Query(None, description="Restrict to one project"),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """Get the similarity history of a student across all runs"""
+    try:
+        return service.get_submitter_history(submitted_by_uuid, min_similarity, project_uuid)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get(
+    "/{run_id}",
+    response_model=DetectionRunReportDto,
+    dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))],
+)
+async def get_run_report(
+    run_id: UUID,
+    top: int = Query(10, ge=0, le=100, description="Number of most similar pairs to include"),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """Get a detection run report, with its effective priority and queue position while it waits for a slot"""
+    try:
+        report = service.get_run_report(run_id, top)
+        if anonymize:
+            return JSONResponse(service.get_pseudonyms(run_id).anonymize(report.model_dump(mode="json")))
+        return report
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/{run_id}/reclassify", response_model=RunReclassificationDto)
+async def reclassify_run(run_id: UUID, service: DetectionRunService = Depends(get_run_service)):
+    """
+    Classify the pairs of a run with the current declared collaborations of its step
+
+    Scores are kept as they are: only which pairs are reported apart as declared collaborations, and left out of
+    the statistics and summaries, changes.
+    """
+    try:
+        return service.reclassify_run(run_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get(
+    "/{run_id}/pairs",
+    response_model=DetectionPairListResponseDto,
+    dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))],
+)
+async def get_run_pairs(
+    run_id: UUID,
+    min_similarity: float = Query(0.0, ge=0.0, le=1.0, description="Only pairs at or above this similarity"),
+    skip: int = Query(0, ge=0, description="Number of pairs to skip"),
+    limit: int = Query(100, ge=1, le=1000, description="Number of pairs to return"),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """Get the pairs of a detection run"""
+    try:
+        pairs = service.get_run_pairs(run_id, min_similarity, skip, limit)
+        if anonymize:
+            return JSONResponse(service.get_pseudonyms(run_id).anonymize(pairs.model_dump(mode="json")))
+        return pairs
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/{run_id}/pairs.csv", dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))])
+async def export_run_pairs_csv(
+    run_id: UUID,
+    min_similarity: float = Query(0.0, ge=0.0, le=1.0, description="Only pairs at or above this similarity"),
+    sort: PairSortKey = Query(PairSortKey.OVERALL_SIMILARITY, description="Column the pairs are sorted by"),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    locale: Locale = Depends(get_locale),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """
+    Export the completed pairs of a run as CSV with their match statistics: number of shared blocks, longest block
+    in lines and tokens, mean block length and share of the matched lines in the 3 longest blocks, so pairs matching
+    one long block can be told from pairs matching many scattered lines
+
+    The header row holds the column names in English, labels in French with the fr **locale** or Accept-Language;
+    columns are in the same order in every locale.
+    """
+    try:
+        content = service.export_pairs_csv(run_id, min_similarity, sort, anonymize, locale=locale)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    return StreamingResponse(
+        content,
+        media_type="text/csv",
+        headers={
+            "Content-Disposition": f'attachment; filename="run-{run_id}-pairs.csv"',
+            "Content-Language": locale.value,
+            "Vary": "Accept-Language",
+        },
+    )
+
+
+@router.get("/{run_id}/results.ndjson", dependencies=[Depends(audited(AuditAction.REPORT_VIEW, "run", "run_id"))])
+async def stream_run_results(
+    run_id: UUID,
+    request: Request,
+    min_score: float = Query(0.0, ge=0.0, le=1.0, description="Only pairs at or above this similarity"),
+    include_fragments: bool = Query(False, description="Inline the fragments of each pair instead of a link"),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """
+    Stream the full results of a run as newline-delimited JSON: a metadata record, one record per persisted pair
+    with its fragments inlined or the URL of its pair view, one record per cluster and an end record with the
+    counts. Pairs are read from the database as the response is written, gzipped when the client accepts it
+    """
+    try:
+        content = service.stream_results(run_id, min_score, include_fragments, anonymize)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    headers = {"Content-Disposition": f'attachment; filename="run-{run_id}-results.ndjson"', "Vary": "Accept-Encoding"}
+    if accepts_gzip(request.headers.get("accept-encoding")):
+        headers["Content-Encoding"] = "gzip"
+        content = gzip_chunks(content)
+    return StreamingResponse(content, media_type=RESULTS_CONTENT_TYPE, headers=headers)
+
+
+@router.get("/{run_id}/events")
+async def stream_run_events(
+    run_id: UUID,
+    request: Request,
+    last_event_id: Optional[str] = Header(None, description="ID of the last event received before reconnecting"),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """
+    Stream the live progress of a run as Server-Sent Events: a snapshot of its state, then its stage transitions,
+    files tokenized and pairs compared, warnings and a last completed or failed event, after which the stream ends.
+    Clients reconnecting with Last-Event-ID get the events they missed, or a new snapshot when they are too old
+    """
+    try:
+        service.read_run(run_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    events = run_events(
+        run_id, lambda: service.read_run(run_id), last_event_id, is_disconnected=request.is_disconnected
+    )
+    return StreamingResponse(
+        events, media_type="text/event-stream", headers={"Cache-Control": "no-cache", "X-Accel-Buffering": "no"}
+    )
+
+
+@router.get(
+    "/{run_id}/pairs/{submission_id}/{compared_submission_id}/heatmap",
+    response_model=PairHeatmapDto,
+    dependencies=[Depends(audited(AuditAction.FRAGMENT_VIEW, "run", "run_id"))],
+)
+async def get_pair_heatmap(
+    run_id: UUID,
+    submission_id: UUID,
+    compared_submission_id: UUID,
+    form: HeatmapForm = Query(HeatmapForm.DENSE, description="Dense matrix or sparse list of scored cells"),
+    min_score: float = Query(0.0, ge=0.0, le=1.0, description="Omit the cells scored below this in the sparse form"),
+    anonymize: bool = Query(False, description="Replace identities with the pseudonyms of the run"),
+    service: DetectionRunService = Depends(get_run_service),
+):
+    """
+    Get the file-pair scores of two submissions compared in a run for a heatmap: rows are the files of
+    submission_id, columns the files of compared_submission_id, each with its token count
+    """
+    try:
+        heatmap = service.get_pair_heatmap(run_id, submission_id, compared_submission_id, form, min_score)
+        if anonymize:
+            return JSONResponse(service.get_pseudonyms(run_id).anonymize(heatmap.model_dump(mode="json")))
+        return heatmap
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
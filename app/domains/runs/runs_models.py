@@ -0,0 +1,210 @@
+from datetime import datetime
+from enum import Enum
+from typing import Optional
+from uuid import UUID, uuid4
+
+from sqlmodel import JSON, Column, Field, SQLModel
+
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.concurrency import JobPriority
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class DetectionRunStatus(str, Enum):
+    """Enumeration for detection run status"""
+
+    RUNNING = "running"
+    COMPLETED = "completed"
+    FAILED = "failed"
+    INCOMPLETE = "incomplete"  # finished, but batches of pairs could not be persisted
+    INTERRUPTED = "interrupted"  # stopped by a shutdown with its batches persisted, resumed by a running instance
+    PARTIAL = "partial"  # stopped by the run timeout, with the pairs compared until then
+
+
+# Runs whose pairs may still change
+UNFINISHED_RUN_STATUSES = (DetectionRunStatus.RUNNING, DetectionRunStatus.INTERRUPTED)
+
+
+class DetectionRunTrigger(str, Enum):
+    """Enumeration for what started a detection run"""
+
+    SUBMISSION = "submission"
+    MANUAL = "manual"
+    SCHEDULED = "scheduled"  # a tick of a detection schedule of its step
+
+
+class FragmentType(str, Enum):
+    """Enumeration for fragment granularity"""
+
+    FILE = "file"
+    BLOCK = "block"
+
+
+class DetectionRun(SQLModel, table=True):
+    """Database model for one similarity detection run over a project step"""
+
+    __tablename__ = "detection_run"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+
+    # Project context
+    project_uuid: UUID = Field(index=True, description="UUID of the associated project")
+    project_step_uuid: UUID = Field(index=True, description="UUID of the project step")
+
+    # Origin of the run
+    trigger: DetectionRunTrigger = Field(default=DetectionRunTrigger.SUBMISSION, description="What started the run")
+    trigger_submission_id: Optional[UUID] = Field(default=None, description="Submission whose creation started the run")
+    schedule_id: Optional[UUID] = Field(default=None, index=True, description="Schedule whose tick started the run")
+    priority: JobPriority = Field(default=JobPriority.NORMAL, description="Priority the run was queued with")
+
+    # Detection metadata
+    detection_algorithm: str = Field(default="ast_similarity_v2", description="Algorithm used for detection")
+    detection_version: str = Field(default="2.1.0", description="Version of the detection system")
+    parameters: Optional[dict] = Field(default=None, sa_column=Column(JSON), description="Parameters of the run")
+    declared_collaborations: Optional[list] = Field(
+        default=None,
+        sa_column=Column(JSON),
+        description="Declared collaborations of its step classifying its pairs, as of its start or reclassification",
+    )
+    cache_stats: Optional[dict] = Field(
+        default=None, sa_column=Column(JSON), description="Fingerprint cache hits and misses of the run"
+    )
+    profile: Optional[dict] = Field(
+        default=None, sa_column=Column(JSON), description="Per-stage timings, only recorded for profiled runs"
+    )
+    file_errors: Optional[list] = Field(
+        default=None,
+        sa_column=Column(JSON),
+        description="Files skipped because they failed, with their submission, path, stage and error message",
+    )
+
+    # Progress
+    status: DetectionRunStatus = Field(default=DetectionRunStatus.RUNNING, description="Status of the run")
+    total_pairs: int = Field(default=0, description="Number of pairs scheduled for comparison")
+    completed_pairs: int = Field(default=0, description="Number of pairs persisted as completed")
+    failed_pairs: int = Field(default=0, description="Number of pairs persisted as failed or timed out")
+
+    # Ownership, runs of an instance that stopped heartbeating are resumed by another one
+    instance_id: Optional[str] = Field(
+        default=None, max_length=255, index=True, description="Instance processing the run"
+    )
+    heartbeat_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When the instance processing the run was last alive"
+    )
+
+    # Timing
+    started_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the run started",
+    )
+    finished_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When the run finished"
+    )
+
+    # Error handling
+    error_message: Optional[str] = Field(default=None, description="Error message if the run failed")
+
+    # Retention
+    legal_hold: bool = Field(default=False, description="Exempts the run from retention purges")
+    legal_hold_reason: Optional[str] = Field(default=None, max_length=500, description="Reason of the legal hold")
+
+
+class DetectionRunParticipant(SQLModel, table=True):
+    """Database model for a submission taking part in a detection run"""
+
+    __tablename__ = "detection_run_participant"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    run_id: UUID = Field(foreign_key="detection_run.id", index=True, description="ID of the detection run")
+
+    submission_id: UUID = Field(index=True, description="ID of the participating submission")
+    group_uuid: UUID = Field(description="UUID of the submission group")
+    submitted_by_uuid: Optional[UUID] = Field(default=None, index=True, description="UUID of the submitter")
+    not_comparable_reason: Optional[str] = Field(
+        default=None, description="Why the submission was not compared, None when it was"
+    )
+    team_id: Optional[UUID] = Field(default=None, description="ID of the team of the submission")
+    team_name: Optional[str] = Field(default=None, description="Name of the team of the submission")
+    team_members: Optional[list] = Field(
+        default=None, sa_column=Column(JSON), description="UUIDs of the members of its team when it was submitted"
+    )
+
+
+class DetectionPair(SQLModel, table=True):
+    """Database model for the comparison result of two submissions within a run"""
+
+    __tablename__ = "detection_pair"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    run_id: UUID = Field(foreign_key="detection_run.id", index=True, description="ID of the detection run")
+
+    # Project context, denormalized for cross-run queries
+    project_uuid: UUID = Field(index=True, description="UUID of the associated project")
+    project_step_uuid: UUID = Field(description="UUID of the project step")
+
+    # Compared submissions
+    submission_id: UUID = Field(index=True, description="ID of the first submission")
+    compared_submission_id: UUID = Field(index=True, description="ID of the second submission")
+    submitted_by_uuid: Optional[UUID] = Field(default=None, index=True, description="Submitter of the first submission")
+    compared_submitted_by_uuid: Optional[UUID] = Field(
+        default=None, index=True, description="Submitter of the second submission"
+    )
+    similarity_id: Optional[UUID] = Field(default=None, description="Matching submission_similarity record")
+
+    # Similarity metrics
+    overall_similarity: float = Field(default=0.0, index=True, description="Overall similarity score (0.0 to 1.0)")
+    jaccard_similarity: float = Field(default=0.0, description="Jaccard similarity score (0.0 to 1.0)")
+    type_similarity: float = Field(default=0.0, description="Type similarity score (0.0 to 1.0)")
+    structural_similarity: float = Field(default=0.0, description="Structural similarity score (0.0 to 1.0)")
+    type_sequence_similarity: float = Field(default=0.0, description="Type sequence similarity score (0.0 to 1.0)")
+    flow_similarity: float = Field(default=0.0, description="Flow similarity score (0.0 to 1.0)")
+    operation_similarity: float = Field(default=0.0, description="Operation similarity score (0.0 to 1.0)")
+    fragments_count: int = Field(default=0, description="Number of shared fragments")
+    matched_tokens: Optional[int] = Field(
+        default=None, description="Number of token signature elements common to both submissions"
+    )
+    compared_files: Optional[dict] = Field(
+        default=None,
+        sa_column=Column(JSON),
+        description="Token count of each compared file by file name, under submission1 and submission2",
+    )
+    estimated_similarity: Optional[float] = Field(
+        default=None, description="Upper bound of the overall similarity, set for pruned pairs"
+    )
+    low_confidence: bool = Field(
+        default=False, description="Whether a side has fewer comparable tokens than the configured minimum"
+    )
+    partial: bool = Field(
+        default=False, description="Whether files of the submissions failed and were left out of the comparison"
+    )
+
+    # Status and timing
+    status: SimilarityStatus = Field(default=SimilarityStatus.COMPLETED, description="Status of the comparison")
+    error_message: Optional[str] = Field(default=None, description="Error message if the comparison failed")
+    processing_time_seconds: Optional[float] = Field(default=None, description="Time taken by the comparison")
+    created_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the pair was persisted",
+    )
+
+
+class DetectionFragment(SQLModel, table=True):
+    """Database model for a shared fragment (file pair or code block) of a detection pair"""
+
+    __tablename__ = "detection_fragment"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    pair_id: UUID = Field(foreign_key="detection_pair.id", index=True, description="ID of the detection pair")
+    run_id: UUID = Field(index=True, description="ID of the detection run")
+
+    fragment_type: FragmentType = Field(default=FragmentType.BLOCK, description="Granularity of the fragment")
+    file1_path: str = Field(description="Path of the fragment in the first submission")
+    file2_path: str = Field(description="Path of the fragment in the second submission")
+    file1_start_line: Optional[int] = Field(default=None, description="First line in the first submission")
+    file1_end_line: Optional[int] = Field(default=None, description="Last line in the first submission")
+    file2_start_line: Optional[int] = Field(default=None, description="First line in the second submission")
+    file2_end_line: Optional[int] = Field(default=None, description="Last line in the second submission")
+    similarity: float = Field(default=0.0, description="Similarity score of the fragment (0.0 to 1.0)")
+    details: Optional[dict] = Field(default=None, sa_column=Column(JSON), description="Additional fragment metadata")
@@ -0,0 +1,613 @@
+import logging
+from datetime import datetime
+from typing import Dict, Iterable, Iterator, List, Optional, Set, Tuple
+from uuid import UUID
+
+from sqlalchemy import and_, delete, func, insert, or_, update
+from sqlmodel import Session, select
+
+from app.domains.callbacks.callbacks import record_callback
+from app.domains.callbacks.callbacks_models import CallbackDelivery
+from app.domains.events.events import detection_finished, record_event
+from app.domains.runs.runs_models import (
+    DetectionFragment,
+    DetectionPair,
+    DetectionRun,
+    DetectionRunParticipant,
+    DetectionRunStatus,
+)
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.exceptions import DatabaseException, NotFoundException
+from app.shared.tenancy import require_project_access
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+def pair_order() -> tuple:
+    """Canonical order of pairs, most similar first, ties by submissions rather than by random IDs"""
+    return (
+        DetectionPair.overall_similarity.desc(),
+        DetectionPair.submission_id,
+        DetectionPair.compared_submission_id,
+        DetectionPair.id,
+    )
+
+
+def fragment_order() -> tuple:
+    """Canonical order of fragments, most similar first, ties by paths, lines and type rather than by random IDs"""
+    return (
+        DetectionFragment.similarity.desc(),
+        DetectionFragment.file1_path,
+        DetectionFragment.file2_path,
+        DetectionFragment.file1_start_line,
+        DetectionFragment.file2_start_line,
+        DetectionFragment.fragment_type,
+        DetectionFragment.id,
+    )
+
+
+def table_rows(models: Iterable) -> List[dict]:
+    """Column values of table models, for multi-row inserts that bypass the ORM unit of work"""
+    return [{column.name: getattr(model, column.name) for column in model.__table__.columns} for model in models]
+
+
+class DetectionRunRepository:
+    """Repository for detection runs, participants, pairs and fragments"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def create_run(self, run_data: dict, participants: List[dict]) -> DetectionRun:
+        """Create a run together with its participants in a single transaction"""
+        require_project_access(run_data["project_uuid"])
+        try:
+            run = DetectionRun(**run_data)
+            self.session.add(run)
+            self.session.flush()
+
+            self.session.add_all([DetectionRunParticipant(run_id=run.id, **p) for p in participants])
+            self.session.commit()
+            self.session.refresh(run)
+            return run
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to create detection run: {str(e)}")
+
+    def get_run(self, run_id: UUID) -> Optional[DetectionRun]:
+        """Get a run by ID"""
+        try:
+            return self.session.exec(select(DetectionRun).where(DetectionRun.id == run_id)).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection run: {str(e)}")
+
+    def get_runs_by_project_step(self, project_uuid: UUID, project_step_uuid: UUID) -> List[DetectionRun]:
+        """Get all runs of a project step, newest first"""
+        try:
+            statement = (
+                select(DetectionRun)
+                .where(DetectionRun.project_uuid == project_uuid, DetectionRun.project_step_uuid == project_step_uuid)
+                .order_by(DetectionRun.started_at.desc())
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection runs for project step: {str(e)}")
+
+    def get_recent_completed_runs(self, limit: int = 20) -> List[DetectionRun]:
+        """Get the last completed runs that compared pairs, most recently finished first"""
+        try:
+            statement = (
+                select(DetectionRun)
+                .where(
+                    DetectionRun.status == DetectionRunStatus.COMPLETED,
+                    DetectionRun.finished_at.is_not(None),
+                    DetectionRun.completed_pairs > 0,
+                )
+                .order_by(DetectionRun.finished_at.desc())
+                .limit(limit)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get recent detection runs: {str(e)}")
+
+    def get_participants(self, run_id: UUID) -> List[DetectionRunParticipant]:
+        """Get the participants of a run"""
+        try:
+            statement = (
+                select(DetectionRunParticipant)
+                .where(DetectionRunParticipant.run_id == run_id)
+                .order_by(DetectionRunParticipant.submission_id)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection run participants: {str(e)}")
+
+    def mark_not_comparable(self, run_id: UUID, reasons: Dict[UUID, str]) -> int:
+        """Record why participants of a run were not compared, returns the number of updated participants"""
+        if not reasons:
+            return 0
+        try:
+            updated = 0
+            for submission_id, reason in reasons.items():
+                updated += self.session.execute(
+                    update(DetectionRunParticipant)
+                    .where(
+                        DetectionRunParticipant.run_id == run_id,
+                        DetectionRunParticipant.submission_id == submission_id,
+                    )
+                    .values(not_comparable_reason=reason)
+                ).rowcount
+            self.session.commit()
+            return updated
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to record not comparable participants: {str(e)}")
+
+    def update_parameters(self, run_id: UUID, parameters: dict) -> int:
+        """Replace the parameters of a run once they are settled, returns the number of updated runs"""
+        try:
+            updated = self.session.execute(
+                update(DetectionRun).where(DetectionRun.id == run_id).values(parameters=parameters)
+            ).rowcount
+            self.session.commit()
+            return updated
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to update detection run parameters: {str(e)}")
+
+    def update_declared_collaborations(self, run_id: UUID, declarations: list) -> int:
+        """Replace the declared collaborations classifying the pairs of a run, returns the number of updated runs"""
+        try:
+            updated = self.session.execute(
+                update(DetectionRun).where(DetectionRun.id == run_id).values(declared_collaborations=declarations)
+            ).rowcount
+            self.session.commit()
+            return updated
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to update detection run declared collaborations: {str(e)}")
+
+    def insert_batch(self, run_id: UUID, pairs: List[DetectionPair], fragments: List[DetectionFragment]) -> int:
+        """
+        Persist a batch of pairs and their fragments and advance the run counters, all in one transaction
+
+        Pairs and fragments are written with one multi-row insert each, so a failing batch leaves neither.
+
+        Returns:
+            Number of inserted pairs
+        """
+        if not pairs and not fragments:
+            return 0
+
+        completed = len([p for p in pairs if p.status == SimilarityStatus.COMPLETED])
+        failed = len([p for p in pairs if p.status in (SimilarityStatus.FAILED, SimilarityStatus.TIMED_OUT)])
+        try:
+            updated = self.session.execute(
+                update(DetectionRun)
+                .where(DetectionRun.id == run_id)
+                .values(
+                    completed_pairs=DetectionRun.completed_pairs + completed,
+                    failed_pairs=DetectionRun.failed_pairs + failed,
+                )
+            )
+            if updated.rowcount == 0:
+                raise NotFoundException(f"Detection run with ID {run_id} not found")
+
+            # Pairs have to exist before the fragments that reference them
+            if pairs:
+                self.session.execute(insert(DetectionPair.__table__), table_rows(pairs))
+            if fragments:
+                self.session.execute(insert(DetectionFragment.__table__), table_rows(fragments))
+
+            self.session.commit()
+            return len(pairs)
+        except NotFoundException:
+            self.session.rollback()
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to insert detection batch: {str(e)}")
+
+    def finish_run(
+        self,
+        run_id: UUID,
+        status: DetectionRunStatus,
+        error_message: Optional[str] = None,
+        cache_stats: Optional[dict] = None,
+        profile: Optional[dict] = None,
+        file_errors: Optional[list] = None,
+    ) -> DetectionRun:
+        """Mark a run as finished, with the fingerprint cache statistics, stage timings and file errors it gathered"""
+        try:
+            run = self.get_run(run_id)
+            if not run:
+                raise NotFoundException(f"Detection run with ID {run_id} not found")
+
+            run.status = status
+            run.finished_at = utc_now()
+            if error_message:
+                run.error_message = error_message
+            if cache_stats is not None:
+                run.cache_stats = cache_stats
+            if profile is not None:
+                run.profile = profile
+            if file_errors:
+                run.file_errors = file_errors
+
+            self.session.add(run)
+            record_event(self.session, detection_finished, run)
+            record_callback(self.session, run)
+            self.session.commit()
+            self.session.refresh(run)
+            return run
+        except NotFoundException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to finish detection run: {str(e)}")
+
+    def interrupt_run(
+        self,
+        run_id: UUID,
+        error_message: str,
+        cache_stats: Optional[dict] = None,
+        profile: Optional[dict] = None,
+        file_errors: Optional[list] = None,
+    ) -> DetectionRun:
+        """Mark a run stopped by a shutdown as interrupted, it is not finished so no event nor callback is recorded"""
+        try:
+            run = self.get_run(run_id)
+            if not run:
+                raise NotFoundException(f"Detection run with ID {run_id} not found")
+
+            run.status = DetectionRunStatus.INTERRUPTED
+            run.error_message = error_message
+            if cache_stats is not None:
+                run.cache_stats = cache_stats
+            if profile is not None:
+                run.profile = profile
+            if file_errors:
+                run.file_errors = file_errors
+
+            self.session.add(run)
+            self.session.commit()
+            self.session.refresh(run)
+            return run
+        except NotFoundException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to interrupt detection run: {str(e)}")
+
+    def interrupt_runs(self, run_ids: List[UUID], error_message: str) -> int:
+        """Mark runs whose job never started as interrupted, returns the number of updated runs"""
+        if not run_ids:
+            return 0
+        try:
+            updated = self.session.execute(
+                update(DetectionRun)
+                .where(DetectionRun.id.in_(run_ids), DetectionRun.status == DetectionRunStatus.RUNNING)
+                .values(status=DetectionRunStatus.INTERRUPTED, error_message=error_message)
+            ).rowcount
+            self.session.commit()
+            return updated
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to interrupt detection runs: {str(e)}")
+
+    def heartbeat(self, instance_id: str) -> int:
+        """Record that an instance is still processing its runs, returns the number of runs it holds"""
+        try:
+            updated = self.session.execute(
+                update(DetectionRun)
+                .where(DetectionRun.instance_id == instance_id, DetectionRun.status == DetectionRunStatus.RUNNING)
+                .values(heartbeat_at=utc_now())
+            ).rowcount
+            self.session.commit()
+            return updated
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to record detection run heartbeat: {str(e)}")
+
+    @staticmethod
+    def _abandoned(stale_before: datetime, restarted_instance_id: Optional[str] = None):
+        """
+        Condition of the runs no instance is processing: interrupted, running without a heartbeat since
+        stale_before, or running on a previous process of an instance that restarted
+        """
+        stale = func.coalesce(DetectionRun.heartbeat_at, DetectionRun.started_at) < stale_before
+        if restarted_instance_id is not None:
+            stale = or_(stale, DetectionRun.instance_id == restarted_instance_id)
+        return or_(
+            DetectionRun.status == DetectionRunStatus.INTERRUPTED,
+            and_(DetectionRun.status == DetectionRunStatus.RUNNING, stale),
+        )
+
+    def get_abandoned_runs(
+        self, stale_before: datetime, restarted_instance_id: Optional[str] = None, limit: int = 100
+    ) -> List[DetectionRun]:
+        """Get the runs no instance is processing, oldest first"""
+        try:
+            statement = (
+                select(DetectionRun)
+                .where(self._abandoned(stale_before, restarted_instance_id))
+                .order_by(DetectionRun.started_at)
+                .limit(limit)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get abandoned detection runs: {str(e)}")
+
+    def claim_run(
+        self, run_id: UUID, instance_id: str, stale_before: datetime, restarted_instance_id: Optional[str] = None
+    ) -> bool:
+        """
+        Take over an abandoned run as running on an instance, False when it is no longer abandoned, e.g. because
+        another instance claimed it first
+        """
+        try:
+            claimed = self.session.execute(
+                update(DetectionRun)
+                .where(DetectionRun.id == run_id, self._abandoned(stale_before, restarted_instance_id))
+                .values(status=DetectionRunStatus.RUNNING, instance_id=instance_id, heartbeat_at=utc_now())
+            ).rowcount
+            self.session.commit()
+            return claimed == 1
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to claim detection run: {str(e)}")
+
+    def get_compared_submission_ids(self, run_id: UUID) -> Set[UUID]:
+        """Get the submissions appearing in the persisted pairs of a run, on either side"""
+        try:
+            statement = select(DetectionPair.submission_id, DetectionPair.compared_submission_id).where(
+                DetectionPair.run_id == run_id
+            )
+            return {submission_id for pair in self.session.exec(statement).all() for submission_id in pair}
+        except Exception as e:
+            raise DatabaseException(f"Failed to get compared submissions of detection run: {str(e)}")
+
+    def get_pairs(
+        self, run_id: UUID, min_similarity: float = 0.0, skip: int = 0, limit: int = 100, completed_only: bool = False
+    ) -> Tuple[List[DetectionPair], int]:
+        """Get the pairs of a run above a similarity threshold, most similar first, with the total count"""
+        try:
+            conditions = [DetectionPair.run_id == run_id, DetectionPair.overall_similarity >= min_similarity]
+            if completed_only:
+                conditions.append(DetectionPair.status == SimilarityStatus.COMPLETED)
+            statement = (
+                select(DetectionPair)
+                .where(*conditions)
+                .order_by(*pair_order())
+                .offset(skip)
+                .limit(limit)
+            )
+            total = self.session.exec(select(func.count()).select_from(DetectionPair).where(*conditions)).one()
+            return list(self.session.exec(statement).all()), total
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection pairs: {str(e)}")
+
+    def get_timed_out_pairs(self, run_id: UUID) -> List[DetectionPair]:
+        """Get the pairs of a run stopped by the pair or the run timeout, in pair order"""
+        try:
+            statement = (
+                select(DetectionPair)
+                .where(DetectionPair.run_id == run_id, DetectionPair.status == SimilarityStatus.TIMED_OUT)
+                .order_by(*pair_order())
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get timed out detection pairs: {str(e)}")
+
+    def get_pair(self, pair_id: UUID) -> Optional[DetectionPair]:
+        """Get a pair by ID"""
+        try:
+            return self.session.exec(select(DetectionPair).where(DetectionPair.id == pair_id)).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection pair: {str(e)}")
+
+    def get_pair_by_submissions(
+        self, run_id: UUID, submission_id: UUID, compared_submission_id: UUID
+    ) -> Optional[DetectionPair]:
+        """Get the pair of two submissions within a run, whichever order they were compared in"""
+        try:
+            statement = select(DetectionPair).where(
+                DetectionPair.run_id == run_id,
+                or_(
+                    and_(
+                        DetectionPair.submission_id == submission_id,
+                        DetectionPair.compared_submission_id == compared_submission_id,
+                    ),
+                    and_(
+                        DetectionPair.submission_id == compared_submission_id,
+                        DetectionPair.compared_submission_id == submission_id,
+                    ),
+                ),
+            )
+            return self.session.exec(statement).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection pair: {str(e)}")
+
+    def get_fragments(self, pair_id: UUID) -> List[DetectionFragment]:
+        """Get the fragments of a pair, most similar first"""
+        try:
+            statement = (
+                select(DetectionFragment)
+                .where(DetectionFragment.pair_id == pair_id)
+                .order_by(*fragment_order())
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection fragments: {str(e)}")
+
+    def get_fragments_by_pairs(self, pair_ids: List[UUID]) -> Dict[str, List[DetectionFragment]]:
+        """Get the fragments of several pairs by pair ID, most similar first"""
+        if not pair_ids:
+            return {}
+        try:
+            statement = (
+                select(DetectionFragment)
+                .where(DetectionFragment.pair_id.in_(pair_ids))
+                .order_by(*fragment_order())
+            )
+            fragments: Dict[str, List[DetectionFragment]] = {}
+            for fragment in self.session.exec(statement).all():
+                fragments.setdefault(str(fragment.pair_id), []).append(fragment)
+            return fragments
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection fragments: {str(e)}")
+
+    def iter_completed_pairs(self, run_id: UUID, batch_size: int = 1000) -> Iterator[DetectionPair]:
+        """Iterate over the completed pairs of a run, most similar first, loaded in pages"""
+        statement = (
+            select(DetectionPair)
+            .where(DetectionPair.run_id == run_id, DetectionPair.status == SimilarityStatus.COMPLETED)
+            .order_by(*pair_order())
+        )
+        skip = 0
+        while True:
+            try:
+                pairs = self.session.exec(statement.offset(skip).limit(batch_size)).all()
+            except Exception as e:
+                raise DatabaseException(f"Failed to get detection pairs: {str(e)}")
+            yield from pairs
+            if len(pairs) < batch_size:
+                return
+            skip += batch_size
+
+    def iter_pair_edges(self, run_id: UUID, min_similarity: float = 0.0, batch_size: int = 1000) -> Iterator:
+        """
+        Iterate over the completed pairs of a run above a similarity threshold, most similar first, loaded in pages
+
+        Yields rows with submission_id, compared_submission_id, overall_similarity and matched_tokens only.
+        """
+        statement = (
+            select(
+                DetectionPair.submission_id,
+                DetectionPair.compared_submission_id,
+                DetectionPair.overall_similarity,
+                DetectionPair.matched_tokens,
+            )
+            .where(
+                DetectionPair.run_id == run_id,
+                DetectionPair.overall_similarity >= min_similarity,
+                DetectionPair.status == SimilarityStatus.COMPLETED,
+            )
+            .order_by(*pair_order())
+        )
+        skip = 0
+        while True:
+            try:
+                rows = self.session.exec(statement.offset(skip).limit(batch_size)).all()
+            except Exception as e:
+                raise DatabaseException(f"Failed to get detection pairs: {str(e)}")
+            yield from rows
+            if len(rows) < batch_size:
+                return
+            skip += batch_size
+
+    def iter_pairs_by_id(
+        self, run_id: UUID, min_similarity: float = 0.0, batch_size: int = 1000
+    ) -> Iterator[DetectionPair]:
+        """
+        Iterate over the pairs of a run at or above a similarity threshold, whatever their status, by ID
+
+        Pages continue after the last ID read rather than at an offset, so every pair is yielded exactly once and
+        later pages cost as much as the first one.
+        """
+        statement = (
+            select(DetectionPair)
+            .where(DetectionPair.run_id == run_id, DetectionPair.overall_similarity >= min_similarity)
+            .order_by(DetectionPair.id)
+            .limit(batch_size)
+        )
+        last_id = None
+        while True:
+            page = statement if last_id is None else statement.where(DetectionPair.id > last_id)
+            try:
+                pairs = self.session.exec(page).all()
+            except Exception as e:
+                raise DatabaseException(f"Failed to get detection pairs: {str(e)}")
+            yield from pairs
+            if len(pairs) < batch_size:
+                return
+            last_id = pairs[-1].id
+
+    def get_pairs_by_submission(
+        self, run_id: UUID, submission_id: UUID, min_similarity: float = 0.0
+    ) -> List[DetectionPair]:
+        """Get the completed pairs of a run involving a submission on either side, most similar first"""
+        try:
+            statement = (
+                select(DetectionPair)
+                .where(
+                    DetectionPair.run_id == run_id,
+                    or_(
+                        DetectionPair.submission_id == submission_id,
+                        DetectionPair.compared_submission_id == submission_id,
+                    ),
+                    DetectionPair.status == SimilarityStatus.COMPLETED,
+                    DetectionPair.overall_similarity >= min_similarity,
+                )
+                .order_by(*pair_order())
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection pairs for submission: {str(e)}")
+
+    def get_pairs_by_submitter(
+        self, submitted_by_uuid: UUID, min_similarity: float = 0.0, project_uuid: Optional[UUID] = None
+    ) -> List[DetectionPair]:
+        """Get the pairs involving a student across all runs, most similar first"""
+        try:
+            conditions = [
+                or_(
+                    DetectionPair.submitted_by_uuid == submitted_by_uuid,
+                    DetectionPair.compared_submitted_by_uuid == submitted_by_uuid,
+                ),
+                DetectionPair.overall_similarity >= min_similarity,
+            ]
+            if project_uuid:
+                conditions.append(DetectionPair.project_uuid == project_uuid)
+
+            statement = (
+                select(DetectionPair)
+                .where(*conditions)
+                .order_by(DetectionPair.overall_similarity.desc(), DetectionPair.created_at.desc(), *pair_order()[1:])
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection pairs for submitter: {str(e)}")
+
+    def count_pairs(self, run_id: UUID, min_similarity: float = 0.0, completed_only: bool = False) -> int:
+        """Count the pairs persisted for a run, optionally only the completed ones above a similarity threshold"""
+        try:
+            conditions = [DetectionPair.run_id == run_id]
+            if min_similarity > 0.0:
+                conditions.append(DetectionPair.overall_similarity >= min_similarity)
+            if completed_only:
+                conditions.append(DetectionPair.status == SimilarityStatus.COMPLETED)
+            statement = select(func.count()).select_from(DetectionPair).where(*conditions)
+            return self.session.exec(statement).one()
+        except Exception as e:
+            raise DatabaseException(f"Failed to count detection pairs: {str(e)}")
+
+    def delete_run(self, run_id: UUID) -> int:
+        """Delete a run with its participants, pairs and fragments, returns the number of deleted pairs"""
+        try:
+            run = self.get_run(run_id)
+            if not run:
+                raise NotFoundException(f"Detection run with ID {run_id} not found")
+
+            self.session.execute(delete(DetectionFragment).where(DetectionFragment.run_id == run_id))
+            deleted_pairs = self.session.execute(delete(DetectionPair).where(DetectionPair.run_id == run_id)).rowcount
+            self.session.execute(delete(DetectionRunParticipant).where(DetectionRunParticipant.run_id == run_id))
+            self.session.execute(delete(CallbackDelivery).where(CallbackDelivery.run_id == run_id))
+            self.session.delete(run)
+            self.session.commit()
+            return deleted_pairs
+        except NotFoundException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to delete detection run: {str(e)}")
@@ -0,0 +1,356 @@
+import logging
+import time
+from typing import Callable, Iterator, List, Optional
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.detection.comparability import describe_reason
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.collaborations import DeclaredCollaborations
+from app.domains.reports.report_service import stored_source_reader
+from app.domains.runs.dto.run_response_dto import (
+    DetectionFragmentDto,
+    DetectionPairDetailDto,
+    DetectionPairDto,
+    DetectionPairListResponseDto,
+    DetectionRunDto,
+    DetectionRunParticipantDto,
+    DetectionRunQueueDto,
+    DetectionRunReportDto,
+    FileErrorDto,
+    HeatmapForm,
+    NotComparableSubmissionDto,
+    PairHeatmapDto,
+    PairSortKey,
+    RunReclassificationDto,
+    SubmitterHistoryDto,
+)
+from app.domains.runs.heatmap import build_heatmap
+from app.domains.runs.match_stats import match_stats, pairs_csv, with_match_stats
+from app.domains.runs.pair_view import highlight_blocks
+from app.domains.runs.results_stream import results_ndjson
+from app.domains.runs.runs_models import UNFINISHED_RUN_STATUSES, FragmentType
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.shared.concurrency import JobScheduler
+from app.shared.exceptions import NotFoundException
+from app.shared.i18n import DEFAULT_LOCALE, Locale
+
+logger = logging.getLogger(__name__)
+
+
+def not_comparable_submissions(participants: List) -> List[NotComparableSubmissionDto]:
+    """Participants of a run that were not compared, with their reason"""
+    return [
+        NotComparableSubmissionDto(
+            submission_id=p.submission_id,
+            reason=p.not_comparable_reason,
+            description=describe_reason(p.not_comparable_reason),
+        )
+        for p in participants
+        if getattr(p, "not_comparable_reason", None)
+    ]
+
+
+class DetectionRunService:
+    """Service assembling run reports from the persisted run tables"""
+
+    def __init__(
+        self,
+        session: Session,
+        job_scheduler: Optional[JobScheduler] = None,
+        storage_service: Optional[SubmissionStorageService] = None,
+    ):
+        self.repository = DetectionRunRepository(session)
+        self.submission_repository = SubmissionRepository(session)
+        # Scheduler of the runs, reports the queue position of the runs waiting for a slot
+        self.job_scheduler = job_scheduler
+        self._storage_service = storage_service
+
+    @property
+    def storage_service(self) -> SubmissionStorageService:
+        """Store of the files highlighted in pair views, created on first use"""
+        if self._storage_service is None:
+            self._storage_service = SubmissionStorageService()
+        return self._storage_service
+
+    def _get_run_or_raise(self, run_id: UUID):
+        run = self.repository.get_run(run_id)
+        if not run:
+            raise NotFoundException(f"Detection run with ID {run_id} not found")
+        return run
+
+    def get_pseudonyms(self, run_id: UUID) -> RunPseudonyms:
+        """Get the pseudonyms replacing the identities of a run in anonymized reports"""
+        run = self._get_run_or_raise(run_id)
+        return RunPseudonyms(run.id, self.repository.get_participants(run.id))
+
+    def get_run_report(self, run_id: UUID, top: int = 10) -> DetectionRunReportDto:
+        """Get a run with its participants and most similar pairs"""
+        run = self._get_run_or_raise(run_id)
+        participants = self.repository.get_participants(run_id)
+        top_pairs, total = self.repository.get_pairs(run_id, limit=top)
+
+        return DetectionRunReportDto(
+            run=DetectionRunDto.model_validate(run),
+            queue=self._queue_status(run_id),
+            participants=[DetectionRunParticipantDto.model_validate(p) for p in participants],
+            not_comparable=not_comparable_submissions(participants),
+            file_errors=[FileErrorDto(**error) for error in run.file_errors or []],
+            timed_out_pairs=[DetectionPairDto.model_validate(p) for p in self.repository.get_timed_out_pairs(run_id)],
+            persisted_pairs=total,
+            top_pairs=self._pair_dtos(top_pairs),
+        )
+
+    def reclassify_run(self, run_id: UUID) -> RunReclassificationDto:
+        """
+        Classify the pairs of a run with the declared collaborations its step has now, nothing is compared again
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        run = self._get_run_or_raise(run_id)
+        config = self.submission_repository.get_step_config(run.project_step_uuid)
+        declarations = (config.declared_collaborations if config is not None else None) or []
+        participants = self.repository.get_participants(run_id)
+        before = DeclaredCollaborations(run.declared_collaborations, participants)
+        after = DeclaredCollaborations(declarations, participants)
+
+        declared = changed = 0
+        for pair in self.repository.iter_completed_pairs(run_id):
+            allowed = after(pair)
+            declared += allowed
+            changed += allowed != before(pair)
+        self.repository.update_declared_collaborations(run_id, declarations)
+        logger.info(f"Reclassified run {run_id}: {declared} declared collaboration pairs, {changed} changed")
+        return RunReclassificationDto(
+            run_id=run_id,
+            declared_collaborations=declarations,
+            declared_collaboration_pairs=declared,
+            changed_pairs=changed,
+        )
+
+    def _pair_dtos(self, pairs: List) -> List[DetectionPairDto]:
+        """Pair DTOs with the match statistics of their fragments"""
+        dtos = [DetectionPairDto.model_validate(p) for p in pairs]
+        with_match_stats(dtos, self.repository.get_fragments_by_pairs([p.id for p in pairs]))
+        return dtos
+
+    def _queue_status(self, run_id: UUID) -> Optional[DetectionRunQueueDto]:
+        """Effective priority and queue position of a run waiting for a detection slot"""
+        queued = self.job_scheduler.status(run_id) if self.job_scheduler is not None else None
+        if queued is None:
+            return None
+        return DetectionRunQueueDto(
+            effective_priority=queued.effective_priority,
+            position=queued.position,
+            waiting_seconds=round(queued.waiting_seconds, 3),
+        )
+
+    def list_runs(self, project_uuid: UUID, project_step_uuid: UUID) -> list:
+        """List the runs of a project step, newest first"""
+        runs = self.repository.get_runs_by_project_step(project_uuid, project_step_uuid)
+        return [DetectionRunDto.model_validate(run) for run in runs]
+
+    def get_run_pairs(
+        self, run_id: UUID, min_similarity: float = 0.0, skip: int = 0, limit: int = 100
+    ) -> DetectionPairListResponseDto:
+        """Get a page of the pairs of a run above a threshold"""
+        self._get_run_or_raise(run_id)
+        if limit > 1000:  # Prevent excessive data retrieval
+            limit = 1000
+
+        pairs, total = self.repository.get_pairs(run_id, min_similarity, skip, limit)
+        return DetectionPairListResponseDto(
+            run_id=run_id,
+            min_similarity=min_similarity,
+            total=total,
+            skip=skip,
+            limit=limit,
+            pairs=self._pair_dtos(pairs),
+        )
+
+    def export_pairs_csv(
+        self,
+        run_id: UUID,
+        min_similarity: float = 0.0,
+        sort: PairSortKey = PairSortKey.OVERALL_SIMILARITY,
+        anonymize: bool = False,
+        batch_size: int = 1000,
+        locale: Locale = DEFAULT_LOCALE,
+    ) -> Iterator[str]:
+        """
+        Export the completed pairs of a run at or above a threshold as CSV lines, with their match statistics
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        run = self._get_run_or_raise(run_id)
+        pseudonyms = self.get_pseudonyms(run_id) if anonymize else None
+        rows, batch = [], []
+
+        def add_batch():
+            fragments = self.repository.get_fragments_by_pairs([p.id for p in batch])
+            rows.extend((pair, match_stats(fragments.get(str(pair.id), []))) for pair in batch)
+            batch.clear()
+
+        for pair in self.repository.iter_completed_pairs(run.id, batch_size):
+            # Pairs come most similar first, none of the next ones is above the threshold
+            if pair.overall_similarity < min_similarity:
+                break
+            batch.append(pair)
+            if len(batch) == batch_size:
+                add_batch()
+        add_batch()
+        return pairs_csv(rows, sort, pseudonyms, locale)
+
+    def stream_results(
+        self,
+        run_id: UUID,
+        min_score: float = 0.0,
+        include_fragments: bool = False,
+        anonymize: bool = False,
+        batch_size: int = 500,
+    ) -> Iterator[str]:
+        """
+        Stream the full results of a run as NDJSON: its metadata, every persisted pair at or above min_score and
+        the clusters of the completed ones, read from the database one batch at a time
+
+        Clusters link the pairs at or above min_score or REPORT_MIN_SIMILARITY, whichever is higher, so that a low
+        min_score does not merge the whole run into one cluster.
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        from app.config.config import get_settings
+
+        run = self._get_run_or_raise(run_id)
+        participants = self.repository.get_participants(run.id)
+        return results_ndjson(
+            run,
+            participants,
+            self.repository.iter_pairs_by_id(run.id, min_score, batch_size),
+            self.repository.get_fragments_by_pairs,
+            min_score,
+            max(min_score, get_settings().report_min_similarity),
+            include_fragments,
+            RunPseudonyms(run.id, participants) if anonymize else None,
+            batch_size,
+        )
+
+    def follow_pairs(
+        self,
+        run_id: UUID,
+        min_score: float = 0.0,
+        poll_interval_seconds: float = 1.0,
+        should_stop: Callable[[], bool] = lambda: False,
+        batch_size: int = 500,
+    ) -> Iterator[DetectionPairDto]:
+        """
+        Pairs of a run at or above min_score as they are persisted, until the run finished and its last pairs were read
+
+        The pairs already persisted come first, then the run is read again every poll_interval_seconds while it is
+        running. should_stop is checked between two polls, to stop following for a client that went away.
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        self._get_run_or_raise(run_id)
+        session = self.repository.session
+
+        def follow() -> Iterator[DetectionPairDto]:
+            seen = set()
+            while True:
+                # End the read transaction between polls, which also expires the loaded run so its status is read again
+                session.rollback()
+                run = self._get_run_or_raise(run_id)
+                # Interrupted runs are resumed, their pairs keep coming
+                running = run.status in UNFINISHED_RUN_STATUSES
+                for pair in self.repository.iter_pairs_by_id(run_id, min_score, batch_size):
+                    if pair.id not in seen:
+                        seen.add(pair.id)
+                        yield DetectionPairDto.model_validate(pair)
+                if not running or should_stop():
+                    return
+                time.sleep(poll_interval_seconds)
+
+        return follow()
+
+    def read_run(self, run_id: UUID):
+        """
+        Read a run again from the database, for streams following its progress
+
+        Raises:
+            NotFoundException: If the run does not exist
+        """
+        # End the read transaction of the previous read, so the status and counters are current
+        self.repository.session.rollback()
+        return self._get_run_or_raise(run_id)
+
+    def get_pair_detail(self, pair_id: UUID, highlight: bool = False) -> DetectionPairDetailDto:
+        """
+        Get a pair with its per-file breakdown and shared blocks
+
+        With highlight, both sides of each block come with their highlighted code, read from the latest stored
+        version of the submissions.
+        """
+        from app.config.config import get_settings
+
+        pair = self.repository.get_pair(pair_id)
+        if not pair:
+            raise NotFoundException(f"Detection pair with ID {pair_id} not found")
+
+        fragments = [DetectionFragmentDto.model_validate(f) for f in self.repository.get_fragments(pair_id)]
+        if highlight:
+            submissions = {}
+            for submission_id in (pair.submission_id, pair.compared_submission_id):
+                submission = self.submission_repository.get_by_id(submission_id)
+                if submission is not None:
+                    submissions[str(submission_id)] = submission
+            highlight_blocks(
+                pair,
+                fragments,
+                stored_source_reader(self.storage_service, submissions),
+                get_settings().fragment_excerpt_max_bytes,
+            )
+        pair_dto = DetectionPairDto.model_validate(pair)
+        pair_dto.match_stats = match_stats(fragments)
+        return DetectionPairDetailDto(
+            pair=pair_dto,
+            file_fragments=[f for f in fragments if f.fragment_type == FragmentType.FILE.value],
+            block_fragments=[f for f in fragments if f.fragment_type == FragmentType.BLOCK.value],
+        )
+
+    def get_pair_heatmap(
+        self,
+        run_id: UUID,
+        submission_id: UUID,
+        compared_submission_id: UUID,
+        form: HeatmapForm = HeatmapForm.DENSE,
+        min_score: float = 0.0,
+    ) -> PairHeatmapDto:
+        """Get the file-pair scores of two submissions compared in a run, files of submission_id as rows"""
+        self._get_run_or_raise(run_id)
+        pair = self.repository.get_pair_by_submissions(run_id, submission_id, compared_submission_id)
+        if not pair:
+            raise NotFoundException(
+                f"Submissions {submission_id} and {compared_submission_id} were not compared in run {run_id}"
+            )
+        return build_heatmap(pair, self.repository.get_fragments(pair.id), submission_id, form, min_score)
+
+    def get_submitter_history(
+        self, submitted_by_uuid: UUID, min_similarity: float = 0.0, project_uuid: Optional[UUID] = None
+    ) -> SubmitterHistoryDto:
+        """Get every pair involving a student across all runs"""
+        pairs = self.repository.get_pairs_by_submitter(submitted_by_uuid, min_similarity, project_uuid)
+
+        return SubmitterHistoryDto(
+            submitted_by_uuid=submitted_by_uuid,
+            min_similarity=min_similarity,
+            total_pairs=len(pairs),
+            runs_count=len({p.run_id for p in pairs}),
+            max_similarity=max((p.overall_similarity for p in pairs), default=0.0),
+            pairs=[DetectionPairDto.model_validate(p) for p in pairs],
+        )
@@ -0,0 +1 @@
+# Schedules domain package
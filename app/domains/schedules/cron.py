@@ -0,0 +1,120 @@
+"""
+Cron expressions of detection schedules
+
+Five fields, minute hour day-of-month month day-of-week, each `*`, a value, a range `a-b` or a list of them, with an
+optional step `/n`; months and days of the week may be named (`jan`, `mon`) and Sunday is 0 or 7. When both the day
+of the month and the day of the week are restricted a day matching either fires, like cron. `@hourly`, `@daily`,
+`@weekly`, `@monthly` and `@yearly` are shorthands.
+"""
+
+from datetime import datetime, timedelta, timezone
+from typing import FrozenSet, List, Tuple
+from zoneinfo import ZoneInfo
+
+MACROS = {
+    "@yearly": "0 0 1 1 *",
+    "@annually": "0 0 1 1 *",
+    "@monthly": "0 0 1 * *",
+    "@weekly": "0 0 * * 0",
+    "@daily": "0 0 * * *",
+    "@midnight": "0 0 * * *",
+    "@hourly": "0 * * * *",
+}
+
+MONTHS = ("jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec")
+DAYS = ("sun", "mon", "tue", "wed", "thu", "fri", "sat")
+
+# Name, lowest and highest value, names of the values from the lowest
+FIELDS: Tuple[Tuple[str, int, int, Tuple[str, ...]], ...] = (
+    ("minute", 0, 59, ()),
+    ("hour", 0, 23, ()),
+    ("day of month", 1, 31, ()),
+    ("month", 1, 12, MONTHS),
+    ("day of week", 0, 7, DAYS),
+)
+
+# Ticks are searched over this many days before a schedule is given up as never firing, e.g. on February 30
+SEARCH_DAYS = 366 * 5
+
+
+def _value(text: str, field: str, low: int, high: int, names: Tuple[str, ...]) -> int:
+    if text.lower() in names:
+        return names.index(text.lower()) + low
+    if not text.isdigit() or not low <= int(text) <= high:
+        raise ValueError(f"{field} {text!r} must be between {low} and {high}")
+    return int(text)
+
+
+def _parse_field(text: str, field: str, low: int, high: int, names: Tuple[str, ...]) -> FrozenSet[int]:
+    values = set()
+    for item in text.split(","):
+        item, _, step_text = item.partition("/")
+        step = _value(step_text, f"{field} step", 1, high - low + 1, ()) if step_text else 1
+        if item == "*":
+            start, end = low, high
+        elif "-" in item:
+            start_text, _, end_text = item.partition("-")
+            start, end = _value(start_text, field, low, high, names), _value(end_text, field, low, high, names)
+            if start > end:
+                raise ValueError(f"{field} range {item!r} must go upwards")
+        else:
+            start = _value(item, field, low, high, names)
+            end = high if step_text else start
+        values.update(range(start, end + 1, step))
+    return frozenset(values)
+
+
+class CronExpression:
+    """
+    Parsed cron expression, evaluated in the time zone of its schedule
+
+    Raises:
+        ValueError: If the expression is not five valid fields or a shorthand
+    """
+
+    def __init__(self, expression: str):
+        self.expression = expression.strip()
+        fields = MACROS.get(self.expression.lower(), self.expression).split()
+        if len(fields) != len(FIELDS):
+            raise ValueError(f"cron expression {expression!r} must have 5 fields: minute hour day month weekday")
+
+        parsed: List[FrozenSet[int]] = [_parse_field(text, *definition) for text, definition in zip(fields, FIELDS)]
+        self.minutes, self.hours, self.days, self.months, weekdays = parsed
+        self.weekdays = frozenset(day % 7 for day in weekdays)
+        self._any_day = fields[2] == "*"
+        self._any_weekday = fields[4] == "*"
+
+    def _day_matches(self, day: datetime) -> bool:
+        in_month = day.day in self.days
+        # isoweekday is 1 for Monday to 7 for Sunday, cron counts from Sunday
+        in_week = day.isoweekday() % 7 in self.weekdays
+        if self._any_day or self._any_weekday:
+            return in_month and in_week
+        return in_month or in_week
+
+    def next_after(self, moment: datetime, zone: str = "UTC") -> datetime:
+        """
+        First tick strictly after a moment, in UTC; local times skipped by a DST change fire an hour later, local
+        times repeated by one fire once
+
+        Raises:
+            ValueError: If the expression never fires, e.g. 0 0 30 2 *
+        """
+        tz = ZoneInfo(zone)
+        local = moment.astimezone(tz).replace(tzinfo=None, second=0, microsecond=0) + timedelta(minutes=1)
+        limit = local + timedelta(days=SEARCH_DAYS)
+        while local < limit:
+            if local.month not in self.months:
+                local = (local.replace(day=1) + timedelta(days=32)).replace(day=1, hour=0, minute=0)
+            elif not self._day_matches(local):
+                local = (local + timedelta(days=1)).replace(hour=0, minute=0)
+            elif local.hour not in self.hours:
+                local = (local + timedelta(hours=1)).replace(minute=0)
+            elif local.minute not in self.minutes:
+                local += timedelta(minutes=1)
+            else:
+                tick = local.replace(tzinfo=tz).astimezone(timezone.utc)
+                if tick > moment:
+                    return tick
+                local += timedelta(minutes=1)
+        raise ValueError(f"cron expression {self.expression!r} never fires")
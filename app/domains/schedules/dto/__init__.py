@@ -0,0 +1,3 @@
+from .schedule_dto import DetectionScheduleDto, DetectionScheduleParametersDto, DetectionScheduleResponseDto
+
+__all__ = ["DetectionScheduleDto", "DetectionScheduleParametersDto", "DetectionScheduleResponseDto"]
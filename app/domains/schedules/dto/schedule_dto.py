@@ -0,0 +1,90 @@
+from typing import List, Optional
+from uuid import UUID
+from zoneinfo import ZoneInfo, ZoneInfoNotFoundError
+
+from pydantic import BaseModel, ConfigDict, Field, field_validator, model_validator
+
+from app.domains.schedules.cron import CronExpression
+from app.domains.schedules.schedules_models import DetectionScheduleScope, ScheduleOutcome
+from app.shared.concurrency import JobPriority
+from app.shared.timestamps import UtcTimestamp, utc_now
+
+
+class DetectionScheduleParametersDto(BaseModel):
+    """DTO for the parameters of the runs a schedule starts, like those of POST /submissions/{id}/detection"""
+
+    profile: bool = Field(default=False, description="Record per-stage timings of the runs")
+    priority: JobPriority = Field(
+        default=JobPriority.LOW, description="Priority of the runs, low so that rescans wait for the runs of uploads"
+    )
+    fingerprint_k: Optional[int] = Field(default=None, ge=1, description="Tokens per k-gram, FINGERPRINT_K if omitted")
+    fingerprint_window: Optional[int] = Field(
+        default=None, ge=1, description="Winnowing window, FINGERPRINT_WINDOW if omitted"
+    )
+    auto_tune: Optional[bool] = Field(
+        default=None, description="Tune k and window left unset from the corpus, DETECTION_AUTO_TUNE if omitted"
+    )
+
+
+class DetectionScheduleDto(BaseModel):
+    """DTO for creating a detection schedule of a project step"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "project_step_uuid": "550e8400-e29b-41d4-a716-446655440002",
+                "cron": "0 2 * * *",
+                "timezone": "Europe/Paris",
+                "scope": "new",
+                "parameters": {"priority": "low"},
+            }
+        }
+    )
+
+    project_step_uuid: UUID = Field(description="Project step whose submissions are rescanned")
+    cron: str = Field(max_length=255, description="Cron expression of the ticks, e.g. 0 2 * * * for 2 AM every night")
+    timezone: str = Field(default="UTC", max_length=64, description="IANA time zone of the cron expression")
+    scope: DetectionScheduleScope = Field(
+        default=DetectionScheduleScope.NEW,
+        description="new: the submissions created since the previous tick, full: every submission of the step",
+    )
+    parameters: DetectionScheduleParametersDto = Field(default_factory=DetectionScheduleParametersDto)
+    paused: bool = Field(default=False, description="Create it paused")
+
+    @field_validator("timezone")
+    def check_timezone(cls, zone: str) -> str:
+        """Validate that the time zone is known"""
+        try:
+            ZoneInfo(zone)
+        except (ZoneInfoNotFoundError, ValueError):
+            raise ValueError(f"unknown time zone {zone!r}")
+        return zone
+
+    @model_validator(mode="after")
+    def check_cron(self) -> "DetectionScheduleDto":
+        """Validate that the cron expression parses and fires"""
+        CronExpression(self.cron).next_after(utc_now(), self.timezone)
+        return self
+
+
+class DetectionScheduleResponseDto(BaseModel):
+    """DTO for reading a detection schedule and its last tick"""
+
+    model_config = ConfigDict(from_attributes=True, use_enum_values=True)
+
+    id: UUID
+    project_uuid: UUID
+    project_step_uuid: UUID
+    cron: str
+    timezone: str
+    scope: DetectionScheduleScope
+    parameters: DetectionScheduleParametersDto
+    paused: bool
+    next_run_at: UtcTimestamp = Field(description="When it ticks next, unless paused")
+    scanned_until: UtcTimestamp = Field(description="Submissions created before it were rescanned, by the new scope")
+    last_run_at: Optional[UtcTimestamp] = None
+    last_outcome: Optional[ScheduleOutcome] = None
+    last_error: Optional[str] = None
+    last_run_ids: Optional[List[UUID]] = None
+    created_at: UtcTimestamp
+    updated_at: Optional[UtcTimestamp] = None
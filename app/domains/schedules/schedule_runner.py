@@ -0,0 +1,70 @@
+import logging
+import threading
+from typing import Callable, List, Optional
+
+from app.config.config import Settings
+from app.domains.schedules.schedules_models import ScheduleOutcome
+from app.shared.shutdown import SHUTDOWN
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class ScheduleRunner:
+    """Background thread ticking the detection schedules that are due, polled at a fixed interval"""
+
+    def __init__(self, interval_seconds: float, session_factory=None, clock: Callable = utc_now):
+        self.interval_seconds = interval_seconds
+        self.clock = clock
+        self._session_factory = session_factory
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def _new_session(self):
+        if self._session_factory is not None:
+            return self._session_factory()
+
+        from sqlmodel import Session
+
+        from app.shared.database import engine
+
+        return Session(engine)
+
+    def run_once(self) -> List[ScheduleOutcome]:
+        """Tick the due schedules in a dedicated session, errors are logged and never stop the runner"""
+        from app.domains.schedules.schedules_service import DetectionScheduleService
+
+        if SHUTDOWN.draining:
+            return []
+        try:
+            with self._new_session() as session:
+                return DetectionScheduleService(session).run_due_schedules(self.clock())
+        except Exception as e:
+            logger.error(f"Detection schedules tick failed: {str(e)}")
+            return []
+
+    def _run(self) -> None:
+        while not self._stop_event.wait(self.interval_seconds):
+            self.run_once()
+
+    def start(self) -> None:
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="detection-schedules", daemon=True)
+        self._thread.start()
+        logger.info(f"Detection schedules polled every {self.interval_seconds:g} seconds")
+
+    def stop(self, timeout: float = 5.0) -> None:
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+
+
+def create_schedule_runner(settings: Settings) -> Optional[ScheduleRunner]:
+    """Build the schedule runner from the settings, None when detection schedules are disabled"""
+    if not settings.detection_schedules_enabled:
+        logger.info("Detection schedules disabled")
+        return None
+    return ScheduleRunner(settings.detection_schedules_poll_seconds)
@@ -0,0 +1,126 @@
+from typing import List, Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, Header, HTTPException
+from sqlmodel import Session
+
+from app.domains.audit.audit_hook import audited
+from app.domains.audit.audit_models import AuditAction
+from app.domains.schedules.dto import DetectionScheduleDto, DetectionScheduleResponseDto
+from app.domains.schedules.schedules_service import DetectionScheduleService
+from app.shared.concurrency import JobPriority
+from app.shared.database import get_session
+from app.shared.exceptions import DatabaseException, NotFoundException
+from app.shared.security import require_admin_scope
+
+router = APIRouter(prefix="/projects", tags=["schedules"])
+
+
+def get_schedule_service(session: Session = Depends(get_session)) -> DetectionScheduleService:
+    """Dependency to get detection schedule service"""
+    return DetectionScheduleService(session)
+
+
+@router.post(
+    "/{project_uuid}/schedules",
+    response_model=DetectionScheduleResponseDto,
+    status_code=201,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "schedule"))],
+)
+async def create_schedule(
+    project_uuid: UUID,
+    schedule_data: DetectionScheduleDto,
+    authorization: Optional[str] = Header(None),
+    service: DetectionScheduleService = Depends(get_schedule_service),
+):
+    """
+    Schedule periodic rescans of a project step against its corpus
+
+    - **project_step_uuid**: Project step whose submissions are rescanned (required)
+    - **cron**: Cron expression of the ticks, evaluated in **timezone** (UTC by default)
+    - **scope**: `new` rescans the submissions created since the previous tick, `full` every submission of the step
+    - **parameters**: Parameters of the runs, like POST /submissions/{id}/detection, `low` priority by default
+
+    A tick is skipped while the runs of the previous one are still in progress, and the ticks missed while the
+    service was down fire once. Runs queued `urgent` need the admin scope.
+    """
+    if schedule_data.parameters.priority == JobPriority.URGENT:
+        require_admin_scope(authorization)
+
+    try:
+        return service.create_schedule(project_uuid, schedule_data)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/{project_uuid}/schedules", response_model=List[DetectionScheduleResponseDto])
+async def list_schedules(project_uuid: UUID, service: DetectionScheduleService = Depends(get_schedule_service)):
+    """List the detection schedules of a project, with their last tick"""
+    try:
+        return service.list_schedules(project_uuid)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/{project_uuid}/schedules/{schedule_id}", response_model=DetectionScheduleResponseDto)
+async def get_schedule(
+    project_uuid: UUID, schedule_id: UUID, service: DetectionScheduleService = Depends(get_schedule_service)
+):
+    """Get a detection schedule of a project"""
+    try:
+        return service.get_schedule(project_uuid, schedule_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post(
+    "/{project_uuid}/schedules/{schedule_id}/pause",
+    response_model=DetectionScheduleResponseDto,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "schedule", "schedule_id"))],
+)
+async def pause_schedule(
+    project_uuid: UUID, schedule_id: UUID, service: DetectionScheduleService = Depends(get_schedule_service)
+):
+    """Pause a detection schedule, the runs it already started go on"""
+    try:
+        return service.pause_schedule(project_uuid, schedule_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post(
+    "/{project_uuid}/schedules/{schedule_id}/resume",
+    response_model=DetectionScheduleResponseDto,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "schedule", "schedule_id"))],
+)
+async def resume_schedule(
+    project_uuid: UUID, schedule_id: UUID, service: DetectionScheduleService = Depends(get_schedule_service)
+):
+    """Resume a paused detection schedule from its next tick, the ticks missed while paused do not fire"""
+    try:
+        return service.resume_schedule(project_uuid, schedule_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.delete(
+    "/{project_uuid}/schedules/{schedule_id}",
+    status_code=204,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "schedule", "schedule_id"))],
+)
+async def delete_schedule(
+    project_uuid: UUID, schedule_id: UUID, service: DetectionScheduleService = Depends(get_schedule_service)
+):
+    """Delete a detection schedule, the runs it started are kept"""
+    try:
+        service.delete_schedule(project_uuid, schedule_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
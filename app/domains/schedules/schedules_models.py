@@ -0,0 +1,70 @@
+from datetime import datetime
+from enum import Enum
+from typing import Optional
+from uuid import UUID, uuid4
+
+from sqlmodel import JSON, Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class DetectionScheduleScope(str, Enum):
+    """Enumeration for the submissions a scheduled rescan compares with the corpus of their step"""
+
+    NEW = "new"  # those created since the previous tick that started runs
+    FULL = "full"  # every submission of the step
+
+
+class ScheduleOutcome(str, Enum):
+    """Enumeration for what a tick of a schedule did"""
+
+    STARTED = "started"
+    NOTHING_NEW = "nothing_new"
+    SKIPPED_RUNNING = "skipped_running"  # the runs of the previous tick are still in progress
+    QUOTA_EXCEEDED = "quota_exceeded"  # the quotas of the project stopped it, the rest is left for the next tick
+    FAILED = "failed"
+
+
+class DetectionSchedule(SQLModel, table=True):
+    """Database model for the periodic rescans of a project step against its corpus"""
+
+    __tablename__ = "detection_schedule"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    project_uuid: UUID = Field(index=True, description="UUID of the project")
+    project_step_uuid: UUID = Field(index=True, description="UUID of the project step rescanned")
+
+    # When and what it runs
+    cron: str = Field(max_length=255, description="Cron expression of its ticks")
+    timezone: str = Field(default="UTC", max_length=64, description="Time zone the cron expression is evaluated in")
+    scope: DetectionScheduleScope = Field(default=DetectionScheduleScope.NEW, description="Submissions rescanned")
+    parameters: dict = Field(
+        default_factory=dict,
+        sa_column=Column(JSON, nullable=False),
+        description="Parameters of the runs it starts, like POST /submissions/{id}/detection",
+    )
+    paused: bool = Field(default=False, description="Paused schedules do not tick")
+
+    # Ticks
+    next_run_at: datetime = Field(
+        sa_column=Column(UtcDateTime(), nullable=False, index=True), description="When it ticks next"
+    )
+    scanned_until: datetime = Field(
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="Submissions created before it were rescanned, by the new scope",
+    )
+    last_run_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When it last ticked"
+    )
+    last_outcome: Optional[ScheduleOutcome] = Field(default=None, description="What its last tick did")
+    last_error: Optional[str] = Field(default=None, description="Why its last tick stopped, failed or was refused")
+    last_run_ids: Optional[list] = Field(
+        default=None, sa_column=Column(JSON), description="Detection runs its last tick started"
+    )
+
+    created_at: datetime = Field(
+        default_factory=utc_now, sa_column=Column(UtcDateTime(), nullable=False), description="When it was created"
+    )
+    updated_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When it was last updated"
+    )
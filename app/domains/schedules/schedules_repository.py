@@ -0,0 +1,114 @@
+from datetime import datetime
+from typing import List, Optional
+from uuid import UUID
+
+from sqlalchemy import func, update
+from sqlmodel import Session, select
+
+from app.domains.runs.runs_models import UNFINISHED_RUN_STATUSES, DetectionRun
+from app.domains.schedules.schedules_models import DetectionSchedule
+from app.domains.submissions.submissions_models import Submission
+from app.shared.exceptions import DatabaseException
+from app.shared.tenancy import require_project_access
+
+
+class DetectionScheduleRepository:
+    """Repository for the detection schedules of project steps and the submissions their ticks rescan"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def get(self, schedule_id: UUID) -> Optional[DetectionSchedule]:
+        """Get a schedule by ID"""
+        try:
+            return self.session.get(DetectionSchedule, schedule_id)
+        except Exception as e:
+            raise DatabaseException(f"Failed to get detection schedule: {str(e)}")
+
+    def list_by_project(self, project_uuid: UUID) -> List[DetectionSchedule]:
+        """Schedules of a project, oldest first"""
+        try:
+            statement = (
+                select(DetectionSchedule)
+                .where(DetectionSchedule.project_uuid == project_uuid)
+                .order_by(DetectionSchedule.created_at)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to list detection schedules: {str(e)}")
+
+    def list_due(self, now: datetime) -> List[DetectionSchedule]:
+        """Schedules not paused whose next tick is now or past, the most overdue first"""
+        try:
+            statement = (
+                select(DetectionSchedule)
+                .where(DetectionSchedule.paused.is_(False), DetectionSchedule.next_run_at <= now)
+                .order_by(DetectionSchedule.next_run_at)
+            )
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to list due detection schedules: {str(e)}")
+
+    def save(self, schedule: DetectionSchedule) -> DetectionSchedule:
+        """Insert or update a schedule"""
+        require_project_access(schedule.project_uuid)
+        try:
+            self.session.add(schedule)
+            self.session.commit()
+            self.session.refresh(schedule)
+            return schedule
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save detection schedule: {str(e)}")
+
+    def delete(self, schedule: DetectionSchedule) -> None:
+        """Delete a schedule, the runs it started are kept"""
+        try:
+            self.session.delete(schedule)
+            self.session.commit()
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to delete detection schedule: {str(e)}")
+
+    def claim_tick(self, schedule_id: UUID, next_run_at: datetime, following_run_at: datetime) -> bool:
+        """
+        Move the next tick of a schedule from the one due to the following one, False when another instance did
+        first, so each tick fires on one instance only
+        """
+        try:
+            claimed = self.session.execute(
+                update(DetectionSchedule)
+                .where(DetectionSchedule.id == schedule_id, DetectionSchedule.next_run_at == next_run_at)
+                .values(next_run_at=following_run_at)
+            ).rowcount
+            self.session.commit()
+            return claimed == 1
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to claim detection schedule tick: {str(e)}")
+
+    def count_unfinished_runs(self, schedule_id: UUID) -> int:
+        """Runs started by a schedule that are still running or interrupted"""
+        try:
+            statement = select(func.count(DetectionRun.id)).where(
+                DetectionRun.schedule_id == schedule_id, DetectionRun.status.in_(UNFINISHED_RUN_STATUSES)
+            )
+            return self.session.exec(statement).one()
+        except Exception as e:
+            raise DatabaseException(f"Failed to count unfinished scheduled runs: {str(e)}")
+
+    def get_step_submissions(
+        self, project_uuid: UUID, project_step_uuid: UUID, until: datetime, since: Optional[datetime] = None
+    ) -> List[Submission]:
+        """Submissions of a step created before a time, and since another, oldest first"""
+        try:
+            statement = select(Submission).where(
+                Submission.project_uuid == project_uuid,
+                Submission.project_step_uuid == project_step_uuid,
+                Submission.created_at < until,
+            )
+            if since is not None:
+                statement = statement.where(Submission.created_at >= since)
+            return list(self.session.exec(statement.order_by(Submission.created_at)).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get submissions of the step: {str(e)}")
@@ -0,0 +1,172 @@
+import logging
+from datetime import datetime
+from typing import List
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.runs.runs_models import DetectionRunTrigger
+from app.domains.schedules.cron import CronExpression
+from app.domains.schedules.dto.schedule_dto import (
+    DetectionScheduleDto,
+    DetectionScheduleParametersDto,
+    DetectionScheduleResponseDto,
+)
+from app.domains.schedules.schedules_models import DetectionSchedule, DetectionScheduleScope, ScheduleOutcome
+from app.domains.schedules.schedules_repository import DetectionScheduleRepository
+from app.shared.exceptions import NotFoundException, QuotaExceededException
+from app.shared.metrics import SCHEDULE_TICKS
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+
+class DetectionScheduleService:
+    """Service for the detection schedules of project steps, and for their ticks starting detection runs"""
+
+    def __init__(self, session: Session, detection_service=None):
+        self.session = session
+        self.repository = DetectionScheduleRepository(session)
+        self._detection_service = detection_service
+
+    @property
+    def detection_service(self):
+        if self._detection_service is None:
+            from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+
+            self._detection_service = DetectionIntegrationService(self.session)
+        return self._detection_service
+
+    def create_schedule(self, project_uuid: UUID, schedule_data: DetectionScheduleDto) -> DetectionScheduleResponseDto:
+        """Create a schedule of a project step, its new scope starting with the submissions created from now"""
+        now = utc_now()
+        schedule = DetectionSchedule(
+            project_uuid=project_uuid,
+            project_step_uuid=schedule_data.project_step_uuid,
+            cron=schedule_data.cron,
+            timezone=schedule_data.timezone,
+            scope=schedule_data.scope,
+            parameters=schedule_data.parameters.model_dump(mode="json"),
+            paused=schedule_data.paused,
+            next_run_at=CronExpression(schedule_data.cron).next_after(now, schedule_data.timezone),
+            scanned_until=now,
+        )
+        return DetectionScheduleResponseDto.model_validate(self.repository.save(schedule))
+
+    def list_schedules(self, project_uuid: UUID) -> List[DetectionScheduleResponseDto]:
+        """Schedules of a project, paused ones included"""
+        return [DetectionScheduleResponseDto.model_validate(s) for s in self.repository.list_by_project(project_uuid)]
+
+    def get_schedule(self, project_uuid: UUID, schedule_id: UUID) -> DetectionScheduleResponseDto:
+        """
+        Get a schedule of a project
+
+        Raises:
+            NotFoundException: If the project has no such schedule
+        """
+        return DetectionScheduleResponseDto.model_validate(self._get_or_raise(project_uuid, schedule_id))
+
+    def pause_schedule(self, project_uuid: UUID, schedule_id: UUID) -> DetectionScheduleResponseDto:
+        """Stop the ticks of a schedule, the runs it started go on"""
+        schedule = self._get_or_raise(project_uuid, schedule_id)
+        schedule.paused = True
+        schedule.updated_at = utc_now()
+        return DetectionScheduleResponseDto.model_validate(self.repository.save(schedule))
+
+    def resume_schedule(self, project_uuid: UUID, schedule_id: UUID) -> DetectionScheduleResponseDto:
+        """Tick a paused schedule again from its next tick after now, the ticks missed while paused never fire"""
+        schedule = self._get_or_raise(project_uuid, schedule_id)
+        if schedule.paused:
+            schedule.paused = False
+            schedule.updated_at = utc_now()
+            schedule.next_run_at = CronExpression(schedule.cron).next_after(schedule.updated_at, schedule.timezone)
+        return DetectionScheduleResponseDto.model_validate(self.repository.save(schedule))
+
+    def delete_schedule(self, project_uuid: UUID, schedule_id: UUID) -> None:
+        """Delete a schedule, the runs it started are kept"""
+        self.repository.delete(self._get_or_raise(project_uuid, schedule_id))
+
+    def _get_or_raise(self, project_uuid: UUID, schedule_id: UUID) -> DetectionSchedule:
+        schedule = self.repository.get(schedule_id)
+        if schedule is None or schedule.project_uuid != project_uuid:
+            raise NotFoundException("Detection schedule", str(schedule_id))
+        return schedule
+
+    def run_due_schedules(self, now: datetime) -> List[ScheduleOutcome]:
+        """
+        Tick the schedules due at a time, once each however many of their ticks were missed, e.g. while the service
+        was down. Each tick is claimed first, so that it fires on a single instance.
+        """
+        outcomes = []
+        for schedule in self.repository.list_due(now):
+            due_at = schedule.next_run_at
+            try:
+                following = CronExpression(schedule.cron).next_after(now, schedule.timezone)
+            except ValueError as e:
+                logger.error(f"Detection schedule {schedule.id} cannot tick again, pausing it: {str(e)}")
+                schedule.paused = True
+                self.repository.save(schedule)
+                continue
+            if not self.repository.claim_tick(schedule.id, due_at, following):
+                continue
+            outcomes.append(self.tick(schedule, now))
+        return outcomes
+
+    def tick(self, schedule: DetectionSchedule, now: datetime) -> ScheduleOutcome:
+        """
+        Start the runs of a tick of a schedule: one per submission rescanned, against the corpus of its step. A tick
+        is skipped while runs of the previous one are still in progress, the submissions it would have rescanned are
+        left for the next one.
+        """
+        run_ids = []
+        error = None
+        try:
+            if self.repository.count_unfinished_runs(schedule.id):
+                outcome = ScheduleOutcome.SKIPPED_RUNNING
+                run_ids = schedule.last_run_ids or []
+            else:
+                outcome, scanned_until, run_ids, error = self._start_runs(schedule, now)
+                schedule.scanned_until = scanned_until
+        except Exception as e:
+            logger.error(f"Tick of detection schedule {schedule.id} failed: {str(e)}")
+            outcome, error = ScheduleOutcome.FAILED, str(e)
+
+        schedule.last_run_at = now
+        schedule.last_outcome = outcome
+        schedule.last_error = error
+        schedule.last_run_ids = [str(run_id) for run_id in run_ids] or None
+        self.repository.save(schedule)
+        SCHEDULE_TICKS.labels(outcome.value).inc()
+        logger.info(
+            f"Detection schedule {schedule.id} of step {schedule.project_step_uuid} ticked: {outcome.value}, "
+            f"{len(run_ids)} runs"
+        )
+        return outcome
+
+    def _start_runs(self, schedule: DetectionSchedule, now: datetime) -> tuple:
+        """(outcome, scanned until, run IDs, error) of the runs of a tick"""
+        since = schedule.scanned_until if schedule.scope == DetectionScheduleScope.NEW else None
+        submissions = self.repository.get_step_submissions(
+            schedule.project_uuid, schedule.project_step_uuid, now, since
+        )
+        parameters = DetectionScheduleParametersDto.model_validate(schedule.parameters)
+
+        run_ids = []
+        for submission in submissions:
+            try:
+                run_id = self.detection_service.process_submission_similarities_async(
+                    submission,
+                    profile=parameters.profile,
+                    priority=parameters.priority,
+                    trigger=DetectionRunTrigger.SCHEDULED,
+                    fingerprint_k=parameters.fingerprint_k,
+                    fingerprint_window=parameters.fingerprint_window,
+                    auto_tune=parameters.auto_tune,
+                    schedule_id=schedule.id,
+                )
+            except QuotaExceededException as e:
+                # The submission refused and those after it are rescanned by the next tick
+                return ScheduleOutcome.QUOTA_EXCEEDED, submission.created_at, run_ids, e.detail["message"]
+            if run_id is not None:
+                run_ids.append(run_id)
+        return ScheduleOutcome.STARTED if run_ids else ScheduleOutcome.NOTHING_NEW, now, run_ids, None
@@ -0,0 +1,31 @@
+from .content_addressed_store import ContentAddressedStore
+from .exceptions import (
+    InvalidStorageKeyException,
+    StorageConfigurationException,
+    StorageException,
+    StoredObjectNotFoundException,
+)
+from .submission_storage_service import SubmissionStorageService
+from .submission_store import (
+    StoredObject,
+    SubmissionStore,
+    build_object_key,
+    manifest_key,
+    normalize_key,
+    submission_prefix,
+)
+
+__all__ = [
+    "ContentAddressedStore",
+    "InvalidStorageKeyException",
+    "StorageConfigurationException",
+    "StorageException",
+    "StoredObject",
+    "StoredObjectNotFoundException",
+    "SubmissionStorageService",
+    "SubmissionStore",
+    "build_object_key",
+    "manifest_key",
+    "normalize_key",
+    "submission_prefix",
+]
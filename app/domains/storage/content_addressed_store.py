@@ -0,0 +1,187 @@
+"""
+Content-addressable blob layer on top of a SubmissionStore.
+
+File contents are stored once under their digest as ``blobs/{algorithm}/{aa}/{bb}/{digest}``.
+Every submission version referencing a blob owns an empty marker object
+``refs/{algorithm}/{digest}/projects/{project_uuid}/submissions/{submission_id}/v{version}``.
+The same content hashed with two algorithms is two distinct blobs, which lets existing blobs be rehashed
+while they are being read.
+
+Releasing a reference never deletes the blob: the locks only serialize the threads of one process, and
+replicas sharing a bucket would otherwise delete a blob another replica just referenced. Unreferenced blobs
+are deleted by the garbage collection once older than its grace period. Concurrent ingestion of the same new
+content is safe: backends replace objects atomically, so two writers of one digest store identical bytes.
+"""
+
+import logging
+import threading
+from dataclasses import dataclass
+from datetime import timedelta
+from pathlib import Path
+from typing import ContextManager, Iterator, List, Optional, Set
+
+from app.domains.storage.submission_store import DEFAULT_CHUNK_SIZE, SubmissionStore, normalize_key
+from app.shared.content_hash import DEFAULT_HASH_ALGORITHM, LEGACY_HASH_ALGORITHM, HashAlgorithm, hash_chunks
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+BLOBS_PREFIX = "blobs/"
+REFS_PREFIX = "refs/"
+
+# Number of locks digests are spread over, bounds memory while keeping contention low
+LOCK_STRIPES = 64
+
+
+def blob_key(digest: str, algorithm: HashAlgorithm = LEGACY_HASH_ALGORITHM) -> str:
+    """Build the object key of a blob, fanned out over two directory levels"""
+    return f"{BLOBS_PREFIX}{HashAlgorithm(algorithm).value}/{digest[:2]}/{digest[2:4]}/{digest}"
+
+
+def blob_ref_prefix(digest: str, algorithm: HashAlgorithm = LEGACY_HASH_ALGORITHM) -> str:
+    """Build the key prefix holding every reference to a blob"""
+    return f"{REFS_PREFIX}{HashAlgorithm(algorithm).value}/{digest}/"
+
+
+def blob_ref_key(digest: str, owner: str, algorithm: HashAlgorithm = LEGACY_HASH_ALGORITHM) -> str:
+    """Build the reference marker key of one owner (a submission version prefix) of a blob"""
+    return blob_ref_prefix(digest, algorithm) + normalize_key(owner)
+
+
+def hash_file(
+    file_path: Path, algorithm: HashAlgorithm = DEFAULT_HASH_ALGORITHM, chunk_size: int = DEFAULT_CHUNK_SIZE
+) -> tuple:
+    """Return the hex digest and size of a file, read in chunks"""
+    with open(file_path, "rb") as source:
+        return hash_chunks(iter(lambda: source.read(chunk_size), b""), algorithm)
+
+
+@dataclass
+class BlobWrite:
+    """Result of adding a file to the blob store"""
+
+    digest: str
+    size: int
+    created: bool
+    algorithm: HashAlgorithm = DEFAULT_HASH_ALGORITHM
+
+
+class ContentAddressedStore:
+    """
+    Deduplicated blob storage with per-owner reference counting
+
+    Digests are of the algorithm of the store unless another one is given, which is how blobs written
+    before a change of algorithm are reached.
+    """
+
+    def __init__(
+        self,
+        store: SubmissionStore,
+        algorithm: HashAlgorithm = DEFAULT_HASH_ALGORITHM,
+        lock_stripes: int = LOCK_STRIPES,
+    ):
+        self.store = store
+        self.algorithm = HashAlgorithm(algorithm)
+        self._locks = [threading.Lock() for _ in range(lock_stripes)]
+
+    def _lock(self, digest: str) -> threading.Lock:
+        return self._locks[int(digest[:8], 16) % len(self._locks)]
+
+    def add_file(
+        self,
+        file_path: Path,
+        owner: str,
+        content_type: Optional[str] = None,
+        algorithm: Optional[HashAlgorithm] = None,
+    ) -> BlobWrite:
+        """
+        Reference the content of a file from an owner, uploading it only if no identical blob exists
+
+        The reference is written first so that a concurrent garbage collection never sees the blob as unused.
+        """
+        algorithm = HashAlgorithm(algorithm or self.algorithm)
+        digest, size = hash_file(file_path, algorithm)
+        with self._lock(digest):
+            self.store.put(blob_ref_key(digest, owner, algorithm), b"")
+            if self.store.exists(blob_key(digest, algorithm)):
+                return BlobWrite(digest=digest, size=size, created=False, algorithm=algorithm)
+            with open(file_path, "rb") as source:
+                self.store.put(blob_key(digest, algorithm), source, content_type)
+        return BlobWrite(digest=digest, size=size, created=True, algorithm=algorithm)
+
+    def add_reference(self, digest: str, owner: str, algorithm: Optional[HashAlgorithm] = None) -> bool:
+        """Reference an existing blob from an owner, returns False (referencing nothing) if the blob is missing"""
+        algorithm = algorithm or self.algorithm
+        with self._lock(digest):
+            if not self.store.exists(blob_key(digest, algorithm)):
+                return False
+            self.store.put(blob_ref_key(digest, owner, algorithm), b"")
+        return True
+
+    def release(self, digest: str, owner: str, algorithm: Optional[HashAlgorithm] = None) -> bool:
+        """Drop the reference of an owner, returns False if it had none; the blob is left to the garbage collection"""
+        return self.store.delete(blob_ref_key(digest, owner, algorithm or self.algorithm))
+
+    def get(self, digest: str, algorithm: Optional[HashAlgorithm] = None) -> bytes:
+        return self.store.get(blob_key(digest, algorithm or self.algorithm))
+
+    def stream(
+        self, digest: str, algorithm: Optional[HashAlgorithm] = None, chunk_size: int = DEFAULT_CHUNK_SIZE
+    ) -> Iterator[bytes]:
+        return self.store.stream(blob_key(digest, algorithm or self.algorithm), chunk_size)
+
+    def view(self, digest: str, algorithm: Optional[HashAlgorithm] = None) -> ContextManager[memoryview]:
+        return self.store.view(blob_key(digest, algorithm or self.algorithm))
+
+    def exists(self, digest: str, algorithm: Optional[HashAlgorithm] = None) -> bool:
+        return self.store.exists(blob_key(digest, algorithm or self.algorithm))
+
+    def refcount(self, digest: str, algorithm: Optional[HashAlgorithm] = None) -> int:
+        """Number of owners referencing a blob"""
+        return len(self.store.list(blob_ref_prefix(digest, algorithm or self.algorithm)))
+
+    def list_digests(self, algorithm: Optional[HashAlgorithm] = None) -> List[str]:
+        """Digests of every stored blob, of one algorithm or of all of them"""
+        prefix = f"{BLOBS_PREFIX}{HashAlgorithm(algorithm).value}/" if algorithm else BLOBS_PREFIX
+        return [obj.key.rsplit("/", 1)[-1] for obj in self.store.list(prefix)]
+
+    def collect_garbage(self, live_owners: Set[str], grace_seconds: float = 3600) -> dict:
+        """
+        Mark-and-sweep pass removing released blobs and what reference counting missed (e.g. after a crash)
+
+        References whose owner is not live and blobs without references are removed once they are older
+        than the grace period, which protects ingestions still in progress. The references of a blob are
+        listed again right before it is deleted.
+
+        Args:
+            live_owners: Owners (submission version prefixes without trailing slash) that have a manifest
+            grace_seconds: Minimum age of an object before it can be collected
+        """
+        cutoff = utc_now() - timedelta(seconds=grace_seconds)
+
+        def expired(obj) -> bool:
+            return obj.last_modified is None or obj.last_modified <= cutoff
+
+        removed_refs = 0
+        referenced: Set[tuple] = set()
+        for obj in self.store.list(REFS_PREFIX):
+            algorithm, _, rest = obj.key[len(REFS_PREFIX) :].partition("/")
+            digest, _, owner = rest.partition("/")
+            if owner in live_owners or not expired(obj):
+                referenced.add((algorithm, digest))
+                continue
+            if self.store.delete(obj.key):
+                removed_refs += 1
+
+        removed_blobs = 0
+        for obj in self.store.list(BLOBS_PREFIX):
+            algorithm = obj.key[len(BLOBS_PREFIX) :].partition("/")[0]
+            digest = obj.key.rsplit("/", 1)[-1]
+            if (algorithm, digest) in referenced or not expired(obj):
+                continue
+            with self._lock(digest):
+                if not self.store.list(blob_ref_prefix(digest, algorithm)) and self.store.delete(obj.key):
+                    removed_blobs += 1
+
+        logger.info(f"Blob garbage collection removed {removed_refs} orphaned references and {removed_blobs} blobs")
+        return {"removed_references": removed_refs, "removed_blobs": removed_blobs}
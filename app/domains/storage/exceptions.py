@@ -0,0 +1,42 @@
+"""Storage-specific exceptions"""
+
+from fastapi import HTTPException, status
+
+from app.shared.exceptions import NotFoundException, ValidationException
+from app.shared.metrics import STORAGE_ERRORS
+
+
+class StorageException(HTTPException):
+    """Raised when a storage backend operation fails"""
+
+    def __init__(self, detail: str = "Storage operation failed", backend: str = None):
+        super().__init__(status_code=status.HTTP_500_INTERNAL_SERVER_ERROR, detail=detail)
+        self.backend = backend
+        STORAGE_ERRORS.labels(backend or "unknown").inc()
+
+
+class StorageConfigurationException(StorageException):
+    """Raised when the configured storage backend cannot be initialized"""
+
+    def __init__(self, detail: str, backend: str = None):
+        super().__init__(f"Storage configuration error: {detail}", backend)
+
+
+class StoredObjectNotFoundException(NotFoundException):
+    """Raised when a key does not exist in the store"""
+
+    def __init__(self, key: str):
+        super().__init__("Stored object", key)
+        self.key = key
+
+
+class InvalidStorageKeyException(ValidationException):
+    """Raised when a storage key is malformed or tries to escape its namespace"""
+
+    def __init__(self, key: str, reason: str):
+        super().__init__(
+            f"Invalid storage key '{key}': {reason}",
+            details={"error_type": "invalid_storage_key", "key": key, "reason": reason},
+        )
+        self.key = key
+        self.reason = reason
@@ -0,0 +1,266 @@
+import logging
+import mimetypes
+import os
+import shutil
+import time
+import uuid
+from contextlib import ExitStack, contextmanager
+from datetime import datetime
+from pathlib import Path
+from typing import BinaryIO, ContextManager, Iterator, List, Optional, Union
+
+from app.domains.storage.exceptions import (
+    InvalidStorageKeyException,
+    StorageConfigurationException,
+    StorageException,
+    StoredObjectNotFoundException,
+)
+from app.domains.storage.mapped_file import map_file
+from app.domains.storage.submission_store import DEFAULT_CHUNK_SIZE, StoredObject, SubmissionStore, normalize_key
+from app.shared.timestamps import UTC
+
+logger = logging.getLogger(__name__)
+
+# In-flight writes live next to their target under this prefix until they are renamed
+TEMP_FILE_PREFIX = ".pamp-tmp-"
+
+# Most filesystems (ext4, xfs, apfs) limit a single name to 255 bytes
+MAX_FILENAME_BYTES = 255
+
+
+class LocalFileSystemSubmissionStore(SubmissionStore):
+    """
+    Store backed by a directory on the local disk, for development and on-prem deployments.
+    Writes are atomic: data goes to a temp file in the target directory which is fsynced and renamed,
+    so a killed process leaves at most an orphaned temp file that the startup sweep removes.
+    """
+
+    backend_name = "local"
+
+    def __init__(
+        self,
+        root_dir: Union[str, Path],
+        fsync: bool = True,
+        temp_file_max_age_seconds: Optional[int] = 3600,
+        memory_map: bool = True,
+    ):
+        """
+        Initialize the local store.
+
+        Args:
+            root_dir: Directory holding every object
+            fsync: Flush file and directory to disk before a write returns
+            temp_file_max_age_seconds: Age above which temp files are swept at startup, None disables the sweep
+            memory_map: Serve views of objects from memory maps, disable when other hosts write in place to the root
+        """
+        self.root_dir = Path(root_dir).resolve()
+        self.fsync = fsync
+        self.memory_map = memory_map
+        try:
+            self.root_dir.mkdir(parents=True, exist_ok=True)
+        except OSError as e:
+            raise StorageConfigurationException(f"Cannot create storage root {self.root_dir}: {str(e)}", "local")
+
+        if temp_file_max_age_seconds is not None:
+            self.sweep_temp_files(temp_file_max_age_seconds)
+
+        logger.info(f"LocalFileSystemSubmissionStore initialized at {self.root_dir}")
+
+    def _resolve(self, key: str) -> Path:
+        """
+        Map a key to a path inside the root directory
+
+        Raises:
+            InvalidStorageKeyException: If the key escapes the root (including through symlinks),
+                collides with the temp file namespace or has a segment too long for the filesystem
+        """
+        key = normalize_key(key)
+        for segment in key.split("/"):
+            if len(segment.encode("utf-8")) > MAX_FILENAME_BYTES:
+                raise InvalidStorageKeyException(key, f"path segment exceeds {MAX_FILENAME_BYTES} bytes")
+            if segment.startswith(TEMP_FILE_PREFIX):
+                raise InvalidStorageKeyException(key, f"path segments cannot start with '{TEMP_FILE_PREFIX}'")
+
+        path = (self.root_dir / key).resolve()
+        if path != self.root_dir and self.root_dir not in path.parents:
+            raise InvalidStorageKeyException(key, "key resolves outside of the storage root")
+        return path
+
+    @staticmethod
+    def _is_temp_file(path: Path) -> bool:
+        return path.name.startswith(TEMP_FILE_PREFIX)
+
+    def _fsync_directory(self, directory: Path) -> None:
+        """Persist a rename by flushing the directory entry, a no-op where directories can't be opened"""
+        if not self.fsync:
+            return
+        try:
+            fd = os.open(directory, os.O_RDONLY)
+        except OSError:
+            return
+        try:
+            os.fsync(fd)
+        except OSError:
+            pass
+        finally:
+            os.close(fd)
+
+    @staticmethod
+    def _remove_temp_file(temp_path: Path) -> None:
+        try:
+            temp_path.unlink()
+        except FileNotFoundError:
+            pass
+        except OSError as e:
+            logger.warning(f"Failed to remove temp file {temp_path}: {str(e)}")
+
+    def sweep_temp_files(self, max_age_seconds: int) -> int:
+        """
+        Remove orphaned temp files left by interrupted writes
+
+        Args:
+            max_age_seconds: Only temp files not modified for at least this long are removed,
+                so writes still in flight in another process are left alone
+
+        Returns:
+            Number of removed temp files
+        """
+        cutoff = time.time() - max_age_seconds
+        removed = 0
+        for path in self.root_dir.rglob(f"{TEMP_FILE_PREFIX}*"):
+            try:
+                if path.is_file() and path.stat().st_mtime <= cutoff:
+                    path.unlink()
+                    removed += 1
+            except FileNotFoundError:
+                continue
+            except OSError as e:
+                logger.warning(f"Failed to sweep temp file {path}: {str(e)}")
+
+        if removed:
+            logger.info(f"Swept {removed} orphaned temp files from {self.root_dir}")
+        return removed
+
+    def _to_stored_object(self, path: Path) -> StoredObject:
+        stat = path.stat()
+        return StoredObject(
+            key=path.relative_to(self.root_dir).as_posix(),
+            size=stat.st_size,
+            last_modified=datetime.fromtimestamp(stat.st_mtime, UTC),
+            content_type=mimetypes.guess_type(path.name)[0],
+        )
+
+    def put(self, key: str, data: Union[bytes, BinaryIO], content_type: Optional[str] = None) -> StoredObject:
+        path = self._resolve(key)
+        temp_path = path.parent / f"{TEMP_FILE_PREFIX}{uuid.uuid4().hex}"
+        try:
+            path.parent.mkdir(parents=True, exist_ok=True)
+            with open(temp_path, "wb") as target:
+                if isinstance(data, (bytes, bytearray)):
+                    target.write(data)
+                else:
+                    shutil.copyfileobj(data, target, DEFAULT_CHUNK_SIZE)
+                target.flush()
+                if self.fsync:
+                    os.fsync(target.fileno())
+
+            os.replace(temp_path, path)
+        except OSError as e:
+            self._remove_temp_file(temp_path)
+            raise StorageException(f"Failed to write object {key}: {str(e)}", self.backend_name)
+        except Exception:
+            # Errors raised by the source stream must not leave the temp file behind either
+            self._remove_temp_file(temp_path)
+            raise
+
+        self._fsync_directory(path.parent)
+
+        stored = self._to_stored_object(path)
+        stored.content_type = content_type or stored.content_type
+        return stored
+
+    def get(self, key: str) -> bytes:
+        path = self._resolve(key)
+        if not path.is_file():
+            raise StoredObjectNotFoundException(key)
+        try:
+            return path.read_bytes()
+        except OSError as e:
+            raise StorageException(f"Failed to read object {key}: {str(e)}", self.backend_name)
+
+    def stream(self, key: str, chunk_size: int = DEFAULT_CHUNK_SIZE) -> Iterator[bytes]:
+        path = self._resolve(key)
+        if not path.is_file():
+            raise StoredObjectNotFoundException(key)
+        return self._iter_file(path, chunk_size)
+
+    def view(self, key: str) -> ContextManager[memoryview]:
+        if not self.memory_map:
+            return self._buffered_view(key)
+        path = self._resolve(key)
+        if not path.is_file():
+            raise StoredObjectNotFoundException(key)
+        return self._mapped_view(key, path)
+
+    @contextmanager
+    def _mapped_view(self, key: str, path: Path) -> Iterator[memoryview]:
+        with ExitStack() as stack:
+            try:
+                view = stack.enter_context(map_file(path))
+            except FileNotFoundError:
+                # Deleted since it was resolved
+                raise StoredObjectNotFoundException(key)
+            except OSError as e:
+                raise StorageException(f"Failed to map object {key}: {str(e)}", self.backend_name)
+            yield view
+
+    @staticmethod
+    def _iter_file(path: Path, chunk_size: int) -> Iterator[bytes]:
+        with open(path, "rb") as source:
+            while True:
+                chunk = source.read(chunk_size)
+                if not chunk:
+                    break
+                yield chunk
+
+    def delete(self, key: str) -> bool:
+        path = self._resolve(key)
+        if not path.is_file():
+            return False
+        try:
+            path.unlink()
+        except FileNotFoundError:
+            return False
+        except OSError as e:
+            raise StorageException(f"Failed to delete object {key}: {str(e)}", self.backend_name)
+
+        self._prune_empty_parents(path.parent)
+        return True
+
+    def _prune_empty_parents(self, directory: Path) -> None:
+        """Remove directories left empty by a delete, up to the root"""
+        while directory != self.root_dir and self.root_dir in directory.parents:
+            try:
+                directory.rmdir()
+            except OSError:
+                break
+            directory = directory.parent
+
+    def list(self, prefix: str = "", start_after: Optional[str] = None) -> List[StoredObject]:
+        # Walk from the deepest directory fully covered by the prefix
+        base = self.root_dir
+        if "/" in prefix:
+            base = self._resolve(prefix.rsplit("/", 1)[0])
+        if not base.is_dir():
+            return []
+
+        objects = []
+        for path in base.rglob("*"):
+            if not path.is_file() or self._is_temp_file(path):
+                continue
+            key = path.relative_to(self.root_dir).as_posix()
+            if key.startswith(prefix) and (start_after is None or key > start_after):
+                objects.append(self._to_stored_object(path))
+
+        objects.sort(key=lambda obj: obj.key)
+        return objects
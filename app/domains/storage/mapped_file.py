@@ -0,0 +1,79 @@
+"""
+Read-only memory maps of stored files
+
+The kernel raises SIGBUS when a mapped page past the end of a file that was truncated after mapping is read, and
+Python cannot recover from it. Maps are therefore only taken of files nothing truncates in place: the local store
+replaces objects by renaming a new file over them, so a mapped file keeps its inode and its size until the map is
+closed. A file whose size or modification time changed while it was mapped (written in place by another process)
+raises MappedFileChangedException when the map is closed, so the caller discards what it read as a failed read.
+Files that are not regular files, empty or smaller than a page are read instead of mapped.
+"""
+
+import logging
+import mmap
+import os
+import stat
+from contextlib import contextmanager
+from pathlib import Path
+from typing import Iterator, Union
+
+from app.domains.storage.exceptions import StorageException
+
+logger = logging.getLogger(__name__)
+
+# Mapping costs a few system calls and page faults, below a page a read is cheaper
+MIN_MAPPED_BYTES = mmap.PAGESIZE
+
+
+class MappedFileChangedException(StorageException):
+    """Raised when a mapped file was modified in place while it was mapped"""
+
+    def __init__(self, path: Path):
+        super().__init__(f"File {path} changed while it was read", "local")
+        self.path = path
+
+
+def _signature(status: os.stat_result):
+    return status.st_ino, status.st_size, status.st_mtime_ns
+
+
+@contextmanager
+def map_file(path: Union[str, Path]) -> Iterator[memoryview]:
+    """
+    Read-only view of the content of a file, mapped when the file allows it
+
+    The view is released on exit; slices copied out of it with bytes() stay valid, views of it do not.
+
+    Raises:
+        OSError: If the file cannot be opened or mapped
+        MappedFileChangedException: If the file changed while it was mapped
+    """
+    path = Path(path)
+    with open(path, "rb") as source:
+        before = os.fstat(source.fileno())
+        if not stat.S_ISREG(before.st_mode) or before.st_size < MIN_MAPPED_BYTES:
+            view = memoryview(source.read())
+            try:
+                yield view
+            finally:
+                view.release()
+            return
+
+        try:
+            mapping = mmap.mmap(source.fileno(), before.st_size, access=mmap.ACCESS_READ)
+        except ValueError:
+            # Shorter than its size a moment ago
+            raise MappedFileChangedException(path)
+        view = memoryview(mapping)
+        try:
+            yield view
+        finally:
+            view.release()
+            try:
+                mapping.close()
+            except BufferError:
+                # A view of the map is still referenced, it is unmapped once that view is collected
+                logger.debug(f"Map of {path} still exported, left to the garbage collector")
+
+        if _signature(os.fstat(source.fileno())) != _signature(before):
+            raise MappedFileChangedException(path)
@@ -0,0 +1,60 @@
+import hashlib
+import threading
+from typing import BinaryIO, Dict, Iterator, List, Optional, Tuple, Union
+
+from app.domains.storage.exceptions import StoredObjectNotFoundException
+from app.domains.storage.submission_store import DEFAULT_CHUNK_SIZE, StoredObject, SubmissionStore, normalize_key
+from app.shared.timestamps import utc_now
+
+
+class InMemorySubmissionStore(SubmissionStore):
+    """
+    Dictionary-backed store.
+    Used by unit tests and for throwaway local runs, nothing survives a restart.
+    """
+
+    backend_name = "memory"
+
+    def __init__(self):
+        self._objects: Dict[str, Tuple[bytes, StoredObject]] = {}
+        self._lock = threading.Lock()
+
+    def put(self, key: str, data: Union[bytes, BinaryIO], content_type: Optional[str] = None) -> StoredObject:
+        key = normalize_key(key)
+        content = data if isinstance(data, (bytes, bytearray)) else data.read()
+        content = bytes(content)
+
+        stored = StoredObject(
+            key=key,
+            size=len(content),
+            last_modified=utc_now(),
+            etag=hashlib.md5(content).hexdigest(),
+            content_type=content_type,
+        )
+        with self._lock:
+            self._objects[key] = (content, stored)
+        return stored
+
+    def get(self, key: str) -> bytes:
+        key = normalize_key(key)
+        with self._lock:
+            if key not in self._objects:
+                raise StoredObjectNotFoundException(key)
+            return self._objects[key][0]
+
+    def stream(self, key: str, chunk_size: int = DEFAULT_CHUNK_SIZE) -> Iterator[bytes]:
+        content = self.get(key)
+        return (content[offset : offset + chunk_size] for offset in range(0, len(content), chunk_size))
+
+    def delete(self, key: str) -> bool:
+        key = normalize_key(key)
+        with self._lock:
+            return self._objects.pop(key, None) is not None
+
+    def list(self, prefix: str = "", start_after: Optional[str] = None) -> List[StoredObject]:
+        with self._lock:
+            return [
+                stored
+                for key, (_, stored) in sorted(self._objects.items())
+                if key.startswith(prefix) and (start_after is None or key > start_after)
+            ]
@@ -0,0 +1,93 @@
+import logging
+import threading
+from typing import Optional
+
+from app.config.config import Settings
+from app.shared.migrations import MigrationLock, MigrationLockTimeoutException
+
+logger = logging.getLogger(__name__)
+
+# Arbitrary application-wide key of the PostgreSQL advisory lock taken by each batch
+CONTENT_REHASH_LOCK_KEY = 7_310_452_020
+
+
+class ContentRehashJob:
+    """
+    Background thread moving stored blobs to the configured content hash algorithm
+
+    Runs batches of manifests until none is outdated, then stops. The service stays available throughout,
+    as reads accept both algorithms. Every replica runs the job, each batch holds an advisory lock so that only
+    one of them rehashes at a time, the others waiting for the next interval.
+    """
+
+    def __init__(self, batch_size: int = 100, interval_seconds: float = 5, storage_service=None, engine=None):
+        self.batch_size = batch_size
+        self.interval_seconds = interval_seconds
+        self._storage_service = storage_service
+        self._engine = engine
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+        self.totals = {"manifests": 0, "files": 0}
+
+    def _get_storage_service(self):
+        if self._storage_service is None:
+            from app.domains.storage.submission_storage_service import SubmissionStorageService
+
+            self._storage_service = SubmissionStorageService()
+        return self._storage_service
+
+    def _get_engine(self):
+        if self._engine is None:
+            from app.shared.database import engine
+
+            self._engine = engine
+        return self._engine
+
+    def run_once(self) -> Optional[dict]:
+        """Rehash one batch, None when another instance holds the lock or on errors, which are logged"""
+        try:
+            with MigrationLock(self._get_engine(), key=CONTENT_REHASH_LOCK_KEY, timeout_seconds=0):
+                result = self._get_storage_service().rehash(max_manifests=self.batch_size)
+        except MigrationLockTimeoutException:
+            logger.debug("Content rehash batch skipped, another instance is rehashing")
+            return None
+        except Exception as e:
+            logger.error(f"Content rehash batch failed: {str(e)}")
+            return None
+        self.totals["manifests"] += result["manifests"]
+        self.totals["files"] += result["files"]
+        return result
+
+    def _run(self) -> None:
+        while not self._stop_event.is_set():
+            result = self.run_once()
+            if result is not None and not result["remaining"]:
+                logger.info(
+                    f"Content rehash finished: {self.totals['files']} files of {self.totals['manifests']} manifests"
+                )
+                return
+            self._stop_event.wait(self.interval_seconds)
+
+    def start(self) -> None:
+        if self._thread is not None and self._thread.is_alive():
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(target=self._run, name="content-rehash", daemon=True)
+        self._thread.start()
+        logger.info(f"Content rehash started ({self.batch_size} manifests per batch)")
+
+    def stop(self, timeout: float = 5.0) -> None:
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+
+
+def create_rehash_job(settings: Settings) -> Optional[ContentRehashJob]:
+    """Build the rehash job from the settings, None when background rehashing is disabled"""
+    if not settings.content_rehash_enabled:
+        logger.info("Background content rehash disabled")
+        return None
+    return ContentRehashJob(
+        batch_size=settings.content_rehash_batch_size, interval_seconds=settings.content_rehash_interval_seconds
+    )
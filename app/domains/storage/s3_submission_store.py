@@ -0,0 +1,250 @@
+import io
+import logging
+from typing import BinaryIO, Callable, Iterator, List, Optional, TypeVar, Union
+from urllib.parse import urlparse
+
+try:
+    import boto3
+    from boto3.s3.transfer import TransferConfig
+    from botocore.config import Config as BotoConfig
+    from botocore.exceptions import BotoCoreError, ClientError
+except ImportError:
+    boto3 = None
+
+from app.domains.storage.exceptions import (
+    StorageConfigurationException,
+    StorageException,
+    StoredObjectNotFoundException,
+)
+from app.domains.storage.submission_store import DEFAULT_CHUNK_SIZE, StoredObject, SubmissionStore, normalize_key
+from app.shared.retries import CircuitOpenError, retrier
+
+logger = logging.getLogger(__name__)
+
+T = TypeVar("T")
+
+MB = 1024 * 1024
+
+
+class S3SubmissionStore(SubmissionStore):
+    """
+    Store backed by an S3-compatible bucket (AWS S3, MinIO).
+    Large payloads are sent with multipart upload, objects can be encrypted server side.
+    Requests failing transiently are sent again, under the circuit breaker of the endpoint.
+    """
+
+    backend_name = "s3"
+
+    def __init__(
+        self,
+        bucket: str,
+        prefix: str = "",
+        endpoint_url: Optional[str] = None,
+        region_name: Optional[str] = None,
+        access_key_id: Optional[str] = None,
+        secret_access_key: Optional[str] = None,
+        force_path_style: bool = False,
+        server_side_encryption: Optional[str] = None,
+        kms_key_id: Optional[str] = None,
+        multipart_threshold_mb: int = 8,
+        multipart_chunk_mb: int = 8,
+        client=None,
+    ):
+        """
+        Initialize the S3 store.
+
+        Args:
+            bucket: Target bucket name
+            prefix: Optional key prefix shared by every object of this service
+            endpoint_url: Custom endpoint, required for MinIO
+            region_name: AWS region
+            access_key_id: Access key, falls back to the default boto3 credential chain
+            secret_access_key: Secret key, falls back to the default boto3 credential chain
+            force_path_style: Use path-style addressing (MinIO)
+            server_side_encryption: "AES256" or "aws:kms"
+            kms_key_id: KMS key used when server_side_encryption is "aws:kms"
+            multipart_threshold_mb: Payload size above which multipart upload is used
+            multipart_chunk_mb: Size of each multipart part
+            client: Pre-built boto3 client, mostly for tests
+        """
+        if not bucket:
+            raise StorageConfigurationException("storage_s3_bucket is required for the s3 backend", "s3")
+
+        self.bucket = bucket
+        self.prefix = normalize_key(prefix) + "/" if prefix else ""
+        self.server_side_encryption = server_side_encryption
+        self.kms_key_id = kms_key_id
+        self.multipart_threshold_bytes = multipart_threshold_mb * MB
+        self.host = (urlparse(endpoint_url).hostname if endpoint_url else None) or (
+            f"s3.{region_name or 'us-east-1'}.amazonaws.com"
+        )
+
+        if boto3 is None:
+            raise StorageConfigurationException(
+                "boto3 is required for the s3 storage backend. Install with: pip install boto3", "s3"
+            )
+
+        if client is None:
+            client = boto3.client(
+                "s3",
+                endpoint_url=endpoint_url,
+                region_name=region_name,
+                aws_access_key_id=access_key_id,
+                aws_secret_access_key=secret_access_key,
+                config=BotoConfig(s3={"addressing_style": "path" if force_path_style else "auto"}),
+            )
+        self.client = client
+
+        self.transfer_config = TransferConfig(
+            multipart_threshold=self.multipart_threshold_bytes, multipart_chunksize=multipart_chunk_mb * MB
+        )
+
+        logger.info(f"S3SubmissionStore initialized for bucket '{bucket}' (endpoint: {endpoint_url or 'aws'})")
+
+    def _full_key(self, key: str) -> str:
+        return self.prefix + normalize_key(key)
+
+    def _strip_prefix(self, full_key: str) -> str:
+        return full_key[len(self.prefix) :] if self.prefix and full_key.startswith(self.prefix) else full_key
+
+    def _extra_args(self, content_type: Optional[str]) -> dict:
+        extra_args = {}
+        if content_type:
+            extra_args["ContentType"] = content_type
+        if self.server_side_encryption:
+            extra_args["ServerSideEncryption"] = self.server_side_encryption
+            if self.server_side_encryption == "aws:kms" and self.kms_key_id:
+                extra_args["SSEKMSKeyId"] = self.kms_key_id
+        return extra_args
+
+    def _call(self, operation: Callable[[], T], retryable: bool = True) -> T:
+        """Send a request, again while it fails transiently, failing at once while the endpoint circuit is open"""
+        try:
+            return retrier("storage").call(self.host, operation, retryable)
+        except CircuitOpenError as e:
+            raise StorageException(str(e), "s3")
+
+    @staticmethod
+    def _is_not_found(error: "ClientError") -> bool:
+        return error.response.get("Error", {}).get("Code") in ("404", "NoSuchKey", "NotFound")
+
+    def put(self, key: str, data: Union[bytes, BinaryIO], content_type: Optional[str] = None) -> StoredObject:
+        full_key = self._full_key(key)
+        fileobj = io.BytesIO(data) if isinstance(data, (bytes, bytearray)) else data
+        # Streams that cannot be rewound are sent once
+        start = fileobj.tell() if getattr(fileobj, "seekable", lambda: False)() else None
+
+        def upload() -> None:
+            if start is not None:
+                fileobj.seek(start)
+            # upload_fileobj switches to multipart above the configured threshold
+            self.client.upload_fileobj(
+                fileobj,
+                self.bucket,
+                full_key,
+                ExtraArgs=self._extra_args(content_type),
+                Config=self.transfer_config,
+            )
+
+        try:
+            self._call(upload, retryable=start is not None)
+            head = self._call(lambda: self.client.head_object(Bucket=self.bucket, Key=full_key))
+        except (ClientError, BotoCoreError) as e:
+            raise StorageException(f"Failed to upload object {full_key} to bucket {self.bucket}: {str(e)}", "s3")
+
+        return StoredObject(
+            key=self._strip_prefix(full_key),
+            size=head.get("ContentLength", 0),
+            last_modified=head.get("LastModified"),
+            etag=(head.get("ETag") or "").strip('"') or None,
+            content_type=head.get("ContentType", content_type),
+        )
+
+    def _get_body(self, key: str):
+        full_key = self._full_key(key)
+        try:
+            return self._call(lambda: self.client.get_object(Bucket=self.bucket, Key=full_key))["Body"]
+        except ClientError as e:
+            if self._is_not_found(e):
+                raise StoredObjectNotFoundException(key)
+            raise StorageException(f"Failed to download object {full_key} from bucket {self.bucket}: {str(e)}", "s3")
+        except BotoCoreError as e:
+            raise StorageException(f"Failed to download object {full_key} from bucket {self.bucket}: {str(e)}", "s3")
+
+    def get(self, key: str) -> bytes:
+        body = self._get_body(key)
+        try:
+            return body.read()
+        finally:
+            body.close()
+
+    def stream(self, key: str, chunk_size: int = DEFAULT_CHUNK_SIZE) -> Iterator[bytes]:
+        body = self._get_body(key)
+        return self._iter_body(body, chunk_size)
+
+    @staticmethod
+    def _iter_body(body, chunk_size: int) -> Iterator[bytes]:
+        try:
+            while True:
+                chunk = body.read(chunk_size)
+                if not chunk:
+                    break
+                yield chunk
+        finally:
+            body.close()
+
+    def delete(self, key: str) -> bool:
+        full_key = self._full_key(key)
+        try:
+            self._call(lambda: self.client.head_object(Bucket=self.bucket, Key=full_key))
+        except ClientError as e:
+            if self._is_not_found(e):
+                return False
+            raise StorageException(f"Failed to delete object {full_key} from bucket {self.bucket}: {str(e)}", "s3")
+
+        try:
+            self._call(lambda: self.client.delete_object(Bucket=self.bucket, Key=full_key))
+        except (ClientError, BotoCoreError) as e:
+            raise StorageException(f"Failed to delete object {full_key} from bucket {self.bucket}: {str(e)}", "s3")
+        return True
+
+    def list(self, prefix: str = "", start_after: Optional[str] = None) -> List[StoredObject]:
+        def list_pages() -> List[StoredObject]:
+            # A listing failing halfway is started over
+            paginator = self.client.get_paginator("list_objects_v2")
+            options = {"StartAfter": self.prefix + start_after} if start_after is not None else {}
+            return [
+                StoredObject(
+                    key=self._strip_prefix(item["Key"]),
+                    size=item.get("Size", 0),
+                    last_modified=item.get("LastModified"),
+                    etag=(item.get("ETag") or "").strip('"') or None,
+                )
+                for page in paginator.paginate(Bucket=self.bucket, Prefix=self.prefix + prefix, **options)
+                for item in page.get("Contents", [])
+            ]
+
+        try:
+            objects = self._call(list_pages)
+        except (ClientError, BotoCoreError) as e:
+            raise StorageException(f"Failed to list objects under {prefix} in bucket {self.bucket}: {str(e)}", "s3")
+
+        objects.sort(key=lambda obj: obj.key)
+        return objects
+
+    def delete_prefix(self, prefix: str) -> int:
+        keys = [self.prefix + obj.key for obj in self.list(prefix)]
+        deleted = 0
+        try:
+            # DeleteObjects accepts at most 1000 keys per call
+            for start in range(0, len(keys), 1000):
+                batch = keys[start : start + 1000]
+                self._call(
+                    lambda: self.client.delete_objects(
+                        Bucket=self.bucket, Delete={"Objects": [{"Key": key} for key in batch], "Quiet": True}
+                    )
+                )
+                deleted += len(batch)
+        except (ClientError, BotoCoreError) as e:
+            raise StorageException(f"Failed to delete objects under {prefix} in bucket {self.bucket}: {str(e)}", "s3")
+        return deleted
@@ -0,0 +1,56 @@
+import logging
+
+from app.config.config import Settings
+from app.domains.storage.exceptions import StorageConfigurationException
+from app.domains.storage.submission_store import SubmissionStore
+
+logger = logging.getLogger(__name__)
+
+SUPPORTED_BACKENDS = ("local", "s3", "memory")
+
+
+def create_submission_store(settings: Settings) -> SubmissionStore:
+    """
+    Build the submission store selected by the storage_backend setting
+
+    Raises:
+        StorageConfigurationException: If the backend is unknown or misconfigured
+    """
+    backend = (settings.storage_backend or "local").lower()
+    logger.info(f"Creating submission store with backend '{backend}'")
+
+    if backend == "local":
+        from app.domains.storage.local_submission_store import LocalFileSystemSubmissionStore
+
+        return LocalFileSystemSubmissionStore(
+            settings.storage_local_root,
+            fsync=settings.storage_local_fsync,
+            temp_file_max_age_seconds=settings.storage_local_temp_max_age_seconds,
+            memory_map=settings.storage_local_mmap,
+        )
+
+    if backend == "s3":
+        from app.domains.storage.s3_submission_store import S3SubmissionStore
+
+        return S3SubmissionStore(
+            bucket=settings.storage_s3_bucket,
+            prefix=settings.storage_s3_prefix,
+            endpoint_url=settings.storage_s3_endpoint_url,
+            region_name=settings.aws_default_region,
+            access_key_id=settings.aws_access_key_id,
+            secret_access_key=settings.aws_secret_access_key,
+            force_path_style=settings.storage_s3_force_path_style,
+            server_side_encryption=settings.storage_s3_server_side_encryption,
+            kms_key_id=settings.storage_s3_kms_key_id,
+            multipart_threshold_mb=settings.storage_s3_multipart_threshold_mb,
+            multipart_chunk_mb=settings.storage_s3_multipart_chunk_mb,
+        )
+
+    if backend == "memory":
+        from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+
+        return InMemorySubmissionStore()
+
+    raise StorageConfigurationException(
+        f"Unknown storage backend '{settings.storage_backend}'. Supported backends: {', '.join(SUPPORTED_BACKENDS)}"
+    )
@@ -0,0 +1,517 @@
+import json
+import logging
+import mimetypes
+import re
+import tempfile
+from datetime import datetime
+from pathlib import Path
+from types import SimpleNamespace
+from typing import ContextManager, Dict, Iterator, List, Optional, Tuple
+
+from app.domains.repositories.archive_extraction import ARCHIVE_NAMES_FILE, read_archive_names
+from app.domains.storage.content_addressed_store import BLOBS_PREFIX, ContentAddressedStore
+from app.domains.storage.exceptions import InvalidStorageKeyException, StoredObjectNotFoundException
+from app.domains.storage.submission_store import (
+    StoredObject,
+    SubmissionStore,
+    build_object_key,
+    manifest_key,
+    normalize_key,
+    submission_prefix,
+)
+from app.domains.tokenization.streaming_source import read_bom
+from app.shared.content_hash import DIGEST_PATTERN, HashAlgorithm, parse_hash_algorithm
+from app.shared.exceptions import ValidationException
+from app.shared.metrics import INGESTED_BYTES, INGESTED_FILES
+from app.shared.timestamps import as_utc, utc_now
+
+logger = logging.getLogger(__name__)
+
+# Directories that are never worth keeping in storage
+IGNORED_DIRECTORIES = {".git", ".hg", ".svn"}
+
+MANIFEST_FORMAT_VERSION = 1
+
+# Last manifest examined by the rehash of each algorithm, kept between batches
+REHASH_CURSOR_PREFIX = "rehash/"
+
+_MANIFEST_PATTERN = re.compile(r"^manifests/v(\d+)\.json$")
+_LEGACY_VERSION_PATTERN = re.compile(r"^v(\d+)/")
+
+
+class SubmissionStorageService:
+    """
+    Service mapping submissions to objects of the configured SubmissionStore
+
+    Each version is a manifest of relative paths pointing to deduplicated content-addressed blobs, each entry
+    recording the hash algorithm of its digest and, for files starting with one, the encoding of their byte order
+    mark. Blobs keep the original bytes. Versions written before deduplication, as plain objects under
+    v{version}/, are still read.
+    """
+
+    def __init__(self, store: Optional[SubmissionStore] = None, hash_algorithm: Optional[str] = None):
+        # Use injected store or get singleton
+        if store is None:
+            from app.shared.services import get_submission_store
+
+            self.store = get_submission_store()
+        else:
+            self.store = store
+        if hash_algorithm is None:
+            from app.config.config import get_settings
+
+            hash_algorithm = get_settings().content_hash_algorithm
+        self.blobs = ContentAddressedStore(self.store, parse_hash_algorithm(hash_algorithm))
+
+    @staticmethod
+    def entry_blob(entry: dict) -> Tuple[str, HashAlgorithm]:
+        """Digest and hash algorithm of a manifest entry, entries without algorithm predate BLAKE3 support"""
+        return entry["blob"], parse_hash_algorithm(entry.get("algorithm"))
+
+    @staticmethod
+    def _owner(submission, version: int) -> str:
+        """Reference owner of a submission version in the blob store"""
+        return submission_prefix(submission.project_uuid, submission.id, version).rstrip("/")
+
+    def get_versions(self, submission) -> List[int]:
+        """Get all stored versions of a submission, oldest first"""
+        prefix = submission_prefix(submission.project_uuid, submission.id)
+        versions = set()
+        for obj in self.store.list(prefix):
+            relative_key = obj.key[len(prefix) :]
+            match = _MANIFEST_PATTERN.match(relative_key) or _LEGACY_VERSION_PATTERN.match(relative_key)
+            if match:
+                versions.add(int(match.group(1)))
+        return sorted(versions)
+
+    def get_latest_version(self, submission) -> Optional[int]:
+        """Get the most recent stored version of a submission, None if nothing was stored"""
+        versions = self.get_versions(submission)
+        return versions[-1] if versions else None
+
+    def _load_manifest(self, submission, version: int) -> Optional[dict]:
+        """Load the manifest of a version, None for versions stored before deduplication"""
+        try:
+            return json.loads(self.store.get(manifest_key(submission.project_uuid, submission.id, version)))
+        except StoredObjectNotFoundException:
+            return None
+
+    def _resolve(self, submission, version: Optional[int]) -> Tuple[Optional[int], Optional[dict]]:
+        if version is None:
+            version = self.get_latest_version(submission)
+            if version is None:
+                return None, None
+        return version, self._load_manifest(submission, version)
+
+    def ingest_directory(self, submission, directory: Path, extracted: bool = False) -> dict:
+        """
+        Store every file of a fetched submission under a new version
+
+        File contents already held by another submission are not uploaded again.
+
+        Args:
+            submission: Submission the files belong to
+            directory: Root of the fetched submission
+            extracted: Whether the directory is an uploaded archive extracted by the fetcher, whose names file is
+                then recorded instead of stored
+
+        Returns:
+            Summary with the stored version, file count, total size, the number of newly uploaded blobs
+            and the files that could not be stored
+        """
+        latest_version = self.get_latest_version(submission)
+        version = (latest_version or 0) + 1
+        owner = self._owner(submission, version)
+
+        files = {}
+        total_bytes = 0
+        new_blobs = 0
+        # Entries of an extracted archive renamed or skipped during extraction
+        archive_names = read_archive_names(directory) if extracted else {"renamed": {}, "skipped": []}
+        skipped_files = [
+            {"path": entry["name"], "reason": entry["reason"], "archive_name": entry["raw"]}
+            for entry in archive_names["skipped"]
+        ]
+        for file_path in sorted(directory.rglob("*")):
+            if not file_path.is_file() or file_path.is_symlink():
+                continue
+            relative_path = file_path.relative_to(directory)
+            if any(part in IGNORED_DIRECTORIES for part in relative_path.parts):
+                continue
+            if extracted and relative_path.as_posix() == ARCHIVE_NAMES_FILE:
+                continue
+
+            try:
+                path = normalize_key(relative_path.as_posix())
+            except InvalidStorageKeyException as e:
+                logger.warning(f"Skipping file {relative_path} of submission {submission.id}: {e.reason}")
+                skipped_files.append({"path": relative_path.as_posix(), "reason": e.reason})
+                continue
+
+            content_type = mimetypes.guess_type(file_path.name)[0]
+            written = self.blobs.add_file(file_path, owner, content_type)
+            files[path] = {
+                "blob": written.digest,
+                "algorithm": written.algorithm.value,
+                "size": written.size,
+                "content_type": content_type,
+            }
+            bom = read_bom(file_path)
+            if bom:
+                files[path]["bom"] = bom
+            archive_name = archive_names["renamed"].get(relative_path.as_posix())
+            if archive_name:
+                files[path]["archive_name"] = archive_name
+            total_bytes += written.size
+            if written.created:
+                new_blobs += 1
+
+        # The manifest is written last, references without one are collected by the garbage collection
+        self._write_manifest(submission, version, files)
+
+        INGESTED_FILES.inc(len(files))
+        INGESTED_BYTES.inc(total_bytes)
+        logger.info(
+            f"Stored {len(files)} files ({total_bytes} bytes, {new_blobs} new blobs) for submission {submission.id} "
+            f"as version {version} in {self.store.backend_name} store"
+        )
+        return {
+            "version": version,
+            "file_count": len(files),
+            "total_bytes": total_bytes,
+            "new_blobs": new_blobs,
+            "skipped_files": skipped_files,
+        }
+
+    def ingest_manifest(self, submission, files: Dict[str, dict], blob_directory: Path) -> dict:
+        """
+        Store a new version from a manifest whose blobs were extracted as {algorithm}/{digest} files
+
+        Used by corpus imports, the content of every blob is checked against its digest.
+
+        Raises:
+            ValidationException: If a blob is missing or does not match its digest
+        """
+        version = (self.get_latest_version(submission) or 0) + 1
+        owner = self._owner(submission, version)
+
+        stored_files = {}
+        new_blobs = 0
+        for path, entry in sorted(files.items()):
+            path = normalize_key(path)
+            digest = str(entry.get("blob", ""))
+            algorithm = parse_hash_algorithm(entry.get("algorithm"))
+            content_type = entry.get("content_type")
+            blob_file = blob_directory / algorithm.value / digest
+            if not DIGEST_PATTERN.match(digest) or not blob_file.is_file():
+                raise ValidationException(f"Blob '{digest}' of file {path} is missing")
+            written = self.blobs.add_file(blob_file, owner, content_type, algorithm=algorithm)
+            if written.digest != digest:
+                self.blobs.release(written.digest, owner, algorithm)
+                raise ValidationException(f"Blob of file {path} does not match its digest {digest}")
+            stored_files[path] = {
+                "blob": digest,
+                "algorithm": algorithm.value,
+                "size": written.size,
+                "content_type": content_type,
+            }
+            bom = read_bom(blob_file)
+            if bom:
+                stored_files[path]["bom"] = bom
+            if entry.get("archive_name"):
+                stored_files[path]["archive_name"] = entry["archive_name"]
+            if written.created:
+                new_blobs += 1
+
+        self._write_manifest(submission, version, stored_files)
+        return {
+            "version": version,
+            "file_count": len(stored_files),
+            "total_bytes": sum(entry["size"] for entry in stored_files.values()),
+            "new_blobs": new_blobs,
+        }
+
+    def _write_manifest(
+        self, submission, version: int, files: Dict[str, dict], created_at: Optional[str] = None
+    ) -> None:
+        manifest = {
+            "format": MANIFEST_FORMAT_VERSION,
+            "project_uuid": str(submission.project_uuid),
+            "submission_id": str(submission.id),
+            "version": version,
+            "created_at": created_at or utc_now().isoformat(),
+            "files": files,
+        }
+        self.store.put(
+            manifest_key(submission.project_uuid, submission.id, version),
+            json.dumps(manifest, sort_keys=True).encode("utf-8"),
+            "application/json",
+        )
+
+    def get_manifest(self, submission, version: Optional[int] = None) -> Optional[dict]:
+        """Get the manifest of a version (latest by default), None if missing or stored before deduplication"""
+        return self._resolve(submission, version)[1]
+
+    def list_files(self, submission, version: Optional[int] = None) -> List[StoredObject]:
+        """
+        List the files of a submission version (latest by default), keys are relative to the version root
+        """
+        version, manifest = self._resolve(submission, version)
+        if version is None:
+            return []
+
+        if manifest is not None:
+            created_at = as_utc(datetime.fromisoformat(manifest["created_at"]))
+            return [
+                StoredObject(
+                    key=path,
+                    size=entry["size"],
+                    last_modified=created_at,
+                    etag=entry["blob"],
+                    content_type=entry.get("content_type") or mimetypes.guess_type(path)[0],
+                    bom=entry.get("bom"),
+                    archive_name=entry.get("archive_name"),
+                )
+                for path, entry in sorted(manifest["files"].items())
+            ]
+
+        prefix = submission_prefix(submission.project_uuid, submission.id, version)
+        files = []
+        for obj in self.store.list(prefix):
+            files.append(
+                StoredObject(
+                    key=obj.key[len(prefix) :],
+                    size=obj.size,
+                    last_modified=obj.last_modified,
+                    etag=obj.etag,
+                    content_type=obj.content_type or mimetypes.guess_type(obj.key)[0],
+                )
+            )
+        return files
+
+    def _locate(self, submission, relative_path: str, version: Optional[int]) -> Tuple[Optional[dict], str]:
+        """Return the manifest entry of a file, or None and the plain object key for versions without manifest"""
+        path = normalize_key(relative_path)
+        version, manifest = self._resolve(submission, version)
+        if version is None:
+            raise StoredObjectNotFoundException(path)
+
+        if manifest is None:
+            return None, build_object_key(submission.project_uuid, submission.id, version, path)
+        entry = manifest["files"].get(path)
+        if entry is None:
+            raise StoredObjectNotFoundException(path)
+        return entry, path
+
+    def read_file(self, submission, relative_path: str, version: Optional[int] = None) -> bytes:
+        """Read one file of a submission"""
+        entry, key = self._locate(submission, relative_path, version)
+        return self.blobs.get(*self.entry_blob(entry)) if entry else self.store.get(key)
+
+    def view_file(self, submission, relative_path: str, version: Optional[int] = None) -> ContextManager[memoryview]:
+        """Read-only view of one file of a submission, memory mapped by the local store"""
+        entry, key = self._locate(submission, relative_path, version)
+        return self.blobs.view(*self.entry_blob(entry)) if entry else self.store.view(key)
+
+    def stream_file(self, submission, relative_path: str, version: Optional[int] = None) -> Iterator[bytes]:
+        """Stream one file of a submission"""
+        entry, key = self._locate(submission, relative_path, version)
+        return self.blobs.stream(*self.entry_blob(entry)) if entry else self.store.stream(key)
+
+    def materialize(self, submission, version: Optional[int] = None) -> Optional[Path]:
+        """
+        Download a stored submission into a fresh temporary directory
+
+        Returns:
+            Path to the temporary directory, or None if the submission was never stored.
+            The caller is responsible for cleaning it up.
+        """
+        version, manifest = self._resolve(submission, version)
+        if version is None:
+            return None
+
+        if manifest is not None:
+            sources = [(path, entry) for path, entry in sorted(manifest["files"].items())]
+        else:
+            sources = [(stored.key, None) for stored in self.list_files(submission, version)]
+        if not sources:
+            return None
+
+        target_dir = Path(tempfile.mkdtemp(prefix="submission_store_"))
+        for path, entry in sources:
+            target = target_dir / path
+            target.parent.mkdir(parents=True, exist_ok=True)
+            if entry:
+                chunks = self.blobs.stream(*self.entry_blob(entry))
+            else:
+                chunks = self.store.stream(build_object_key(submission.project_uuid, submission.id, version, path))
+            with open(target, "wb") as output:
+                for chunk in chunks:
+                    output.write(chunk)
+
+        logger.debug(f"Materialized {len(sources)} stored files of submission {submission.id} into {target_dir}")
+        return target_dir
+
+    def delete_submission_files(self, submission) -> int:
+        """
+        Delete every stored version of a submission, returns the number of deleted files
+
+        Only the references are dropped, blobs nothing references anymore are removed by the garbage collection.
+        """
+        released_files = 0
+        manifests = 0
+        for version in self.get_versions(submission):
+            manifest = self._load_manifest(submission, version)
+            if manifest is None:
+                continue
+            manifests += 1
+            released_files += len(manifest["files"])
+            owner = self._owner(submission, version)
+            for digest, algorithm in {self.entry_blob(entry) for entry in manifest["files"].values()}:
+                self.blobs.release(digest, owner, algorithm)
+
+        # Manifests go last so that an interrupted deletion can be resumed
+        removed_objects = self.store.delete_prefix(submission_prefix(submission.project_uuid, submission.id))
+        deleted = released_files + removed_objects - manifests
+        logger.info(f"Deleted {deleted} stored files for submission {submission.id}")
+        return deleted
+
+    def rehash(self, max_manifests: Optional[int] = None) -> dict:
+        """
+        Move manifests whose entries use another hash algorithm to the blobs of the current one
+
+        For each outdated entry the content is referenced (copied the first time) under its new digest, the
+        manifest is rewritten in place, then the previous references are released. Readers follow whatever
+        digest the manifest they loaded names, and both blobs exist in between, so nothing is unreadable
+        during the migration.
+
+        Batches resume after the last manifest examined by the previous one, whichever instance ran it, from a
+        cursor kept in the store until the last manifest is done.
+
+        Args:
+            max_manifests: Maximum number of manifests rewritten by this call
+
+        Returns:
+            Number of rewritten manifests and files, and whether outdated manifests remain
+        """
+        algorithm = self.blobs.algorithm
+        cursor_key = f"{REHASH_CURSOR_PREFIX}{algorithm.value}.json"
+        try:
+            after = json.loads(self.store.get(cursor_key))["after"]
+        except StoredObjectNotFoundException:
+            after = None
+        migrated = {}
+        manifests = 0
+        files = 0
+        for obj in self.store.list("projects/", start_after=after):
+            parts = obj.key.split("/", 4)
+            if len(parts) < 5 or parts[2] != "submissions" or not _MANIFEST_PATTERN.match(parts[4]):
+                continue
+            try:
+                manifest = json.loads(self.store.get(obj.key))
+            except StoredObjectNotFoundException:
+                continue
+            outdated = {
+                path: entry
+                for path, entry in manifest["files"].items()
+                if self.entry_blob(entry)[1] != algorithm
+            }
+            if outdated and max_manifests is not None and manifests >= max_manifests:
+                if after is not None:
+                    self.store.put(cursor_key, json.dumps({"after": after}).encode("utf-8"), "application/json")
+                return {"manifests": manifests, "files": files, "remaining": True}
+            after = obj.key
+            if not outdated:
+                continue
+
+            owner = f"{parts[0]}/{parts[1]}/{parts[2]}/{parts[3]}/v{manifest['version']}"
+            rehashed = dict(manifest["files"])
+            for path, entry in outdated.items():
+                previous = self.entry_blob(entry)
+                digest = migrated.get(previous)
+                if digest is None or not self.blobs.add_reference(digest, owner, algorithm):
+                    digest = self._copy_blob(previous, owner, entry.get("content_type"))
+                    migrated[previous] = digest
+                rehashed[path] = {**entry, "blob": digest, "algorithm": algorithm.value}
+
+            submission = SimpleNamespace(project_uuid=parts[1], id=parts[3])
+            if not self.store.exists(obj.key):
+                # Deleted meanwhile, the new references are collected by the garbage collection
+                continue
+            self._write_manifest(submission, manifest["version"], rehashed, manifest.get("created_at"))
+            for digest, previous_algorithm in {self.entry_blob(entry) for entry in outdated.values()}:
+                self.blobs.release(digest, owner, previous_algorithm)
+            manifests += 1
+            files += len(outdated)
+
+        self.store.delete(cursor_key)
+        if manifests:
+            logger.info(f"Rehashed {files} files of {manifests} manifests with {algorithm.value}")
+        return {"manifests": manifests, "files": files, "remaining": False}
+
+    def _copy_blob(self, previous: Tuple[str, HashAlgorithm], owner: str, content_type: Optional[str]) -> str:
+        """Store the content of a blob under the current algorithm, returns its new digest"""
+        with tempfile.NamedTemporaryFile(prefix="submission_rehash_") as copy:
+            for chunk in self.blobs.stream(*previous):
+                copy.write(chunk)
+            copy.flush()
+            return self.blobs.add_file(Path(copy.name), owner, content_type).digest
+
+    def get_usage(self) -> dict:
+        """
+        Aggregate the stored files of every project by reading all manifests
+
+        This lists the whole store, it backs the periodic statistics and is not meant for request paths.
+
+        Returns:
+            Totals and per-project file count, logical bytes (sum of all versions) and physical bytes
+            (distinct blobs referenced by the project plus files stored before deduplication)
+        """
+        projects = {}
+        project_blobs = {}
+        legacy_bytes = 0
+        for obj in self.store.list("projects/"):
+            parts = obj.key.split("/", 4)
+            if len(parts) < 5 or parts[2] != "submissions":
+                continue
+            usage = projects.setdefault(parts[1], {"files": 0, "logical_bytes": 0, "physical_bytes": 0})
+            blobs = project_blobs.setdefault(parts[1], {})
+
+            if _MANIFEST_PATTERN.match(parts[4]):
+                try:
+                    manifest = json.loads(self.store.get(obj.key))
+                except StoredObjectNotFoundException:
+                    # Deleted since it was listed
+                    continue
+                for entry in manifest["files"].values():
+                    usage["files"] += 1
+                    usage["logical_bytes"] += entry["size"]
+                    blobs[self.entry_blob(entry)] = entry["size"]
+            elif _LEGACY_VERSION_PATTERN.match(parts[4]):
+                usage["files"] += 1
+                usage["logical_bytes"] += obj.size
+                usage["physical_bytes"] += obj.size
+                legacy_bytes += obj.size
+
+        for project, blobs in project_blobs.items():
+            projects[project]["physical_bytes"] += sum(blobs.values())
+
+        # Blobs shared across projects count once in the total, unreferenced ones until they are collected
+        stored_blobs = self.store.list(BLOBS_PREFIX)
+        return {
+            "files": sum(u["files"] for u in projects.values()),
+            "logical_bytes": sum(u["logical_bytes"] for u in projects.values()),
+            "physical_bytes": sum(obj.size for obj in stored_blobs) + legacy_bytes,
+            "blobs": len(stored_blobs),
+            "projects": projects,
+        }
+
+    def collect_garbage(self, grace_seconds: float = 3600) -> dict:
+        """Remove blob references of versions without manifest and blobs nothing references anymore"""
+        live_owners = set()
+        for obj in self.store.list("projects/"):
+            prefix, _, name = obj.key.rpartition("/manifests/")
+            match = _MANIFEST_PATTERN.match(f"manifests/{name}") if prefix else None
+            if match:
+                live_owners.add(f"{prefix}/v{match.group(1)}")
+        return self.blobs.collect_garbage(live_owners, grace_seconds)
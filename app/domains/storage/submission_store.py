@@ -0,0 +1,175 @@
+"""
+Submission storage abstraction.
+
+Every byte of a submission that the service keeps goes through a SubmissionStore.
+Submission objects are namespaced under ``projects/{project_uuid}/submissions/{submission_id}/``
+so that a whole submission can be listed or removed by prefix. File contents live in the shared
+content-addressed blob area, each version only keeps a manifest at ``manifests/v{version}.json``.
+Versions stored before deduplication keep their files under ``v{version}/{relative_path}``.
+The keys of a project are only built for work that may reach it, other tenants get not found.
+"""
+
+from abc import ABC, abstractmethod
+from contextlib import contextmanager
+from dataclasses import dataclass
+from datetime import datetime
+from typing import BinaryIO, ContextManager, Iterator, List, Optional, Union
+from uuid import UUID
+
+from app.domains.storage.exceptions import InvalidStorageKeyException, StoredObjectNotFoundException
+from app.shared.tenancy import project_accessible
+
+DEFAULT_CHUNK_SIZE = 64 * 1024
+
+
+@dataclass
+class StoredObject:
+    """Metadata of an object held by a SubmissionStore"""
+
+    key: str
+    size: int
+    last_modified: Optional[datetime] = None
+    etag: Optional[str] = None
+    content_type: Optional[str] = None
+    # Encoding of the byte order mark the file starts with, only recorded in the manifests of submission versions
+    bom: Optional[str] = None
+    # Original name of a file extracted from an archive under a sanitized one: decoded name, percent-encoded raw
+    # bytes and encoding, only recorded in the manifests of submission versions
+    archive_name: Optional[dict] = None
+
+
+def project_prefix(project_uuid: Union[UUID, str]) -> str:
+    """
+    Build the key prefix of a project
+
+    Raises:
+        StoredObjectNotFoundException: If the project belongs to another tenant than the current one
+    """
+    prefix = f"projects/{project_uuid}/"
+    if not project_accessible(project_uuid):
+        raise StoredObjectNotFoundException(prefix)
+    return prefix
+
+
+def submission_prefix(project_uuid: Union[UUID, str], submission_id: Union[UUID, str], version: int = None) -> str:
+    """Build the key prefix of a submission, optionally restricted to one version"""
+    prefix = f"{project_prefix(project_uuid)}submissions/{submission_id}/"
+    if version is not None:
+        prefix += f"v{version}/"
+    return prefix
+
+
+def build_object_key(
+    project_uuid: Union[UUID, str], submission_id: Union[UUID, str], version: int, relative_path: str
+) -> str:
+    """Build the object key of a single submission file"""
+    return submission_prefix(project_uuid, submission_id, version) + normalize_key(relative_path)
+
+
+def manifest_key(project_uuid: Union[UUID, str], submission_id: Union[UUID, str], version: int) -> str:
+    """Build the key of the manifest mapping the paths of a submission version to blob digests"""
+    return f"{submission_prefix(project_uuid, submission_id)}manifests/v{version}.json"
+
+
+def normalize_key(key: str) -> str:
+    """
+    Normalize a storage key to forward slashes and reject anything that could escape its namespace
+
+    Raises:
+        InvalidStorageKeyException: If the key is empty, absolute or contains '.'/'..' segments
+    """
+    if key is None:
+        raise InvalidStorageKeyException(str(key), "key is required")
+
+    normalized = key.replace("\\", "/")
+    if normalized.startswith("/"):
+        raise InvalidStorageKeyException(key, "absolute keys are not allowed")
+
+    segments = [segment for segment in normalized.split("/") if segment != ""]
+    if not segments:
+        raise InvalidStorageKeyException(key, "key is empty")
+
+    for segment in segments:
+        if segment in (".", ".."):
+            raise InvalidStorageKeyException(key, "relative path segments are not allowed")
+        if "\x00" in segment:
+            raise InvalidStorageKeyException(key, "null bytes are not allowed")
+
+    return "/".join(segments)
+
+
+class SubmissionStore(ABC):
+    """
+    Abstract base class for submission file storage backends.
+    Implementations must be safe to share between threads.
+    """
+
+    backend_name: str
+
+    @abstractmethod
+    def put(self, key: str, data: Union[bytes, BinaryIO], content_type: Optional[str] = None) -> StoredObject:
+        """Store data (bytes or a readable binary stream) under the given key, replacing any existing object"""
+        pass
+
+    @abstractmethod
+    def get(self, key: str) -> bytes:
+        """
+        Return the full content of an object
+
+        Raises:
+            StoredObjectNotFoundException: If the key does not exist
+        """
+        pass
+
+    @abstractmethod
+    def stream(self, key: str, chunk_size: int = DEFAULT_CHUNK_SIZE) -> Iterator[bytes]:
+        """
+        Iterate over the content of an object in chunks
+
+        Raises:
+            StoredObjectNotFoundException: If the key does not exist
+        """
+        pass
+
+    def view(self, key: str) -> ContextManager[memoryview]:
+        """
+        Read-only view of the content of an object, valid until the context exits
+
+        The default views the buffer returned by get, which borrows the stored bytes of the in-memory store and
+        reads remote objects into memory. The local store maps its files instead of copying them.
+
+        Raises:
+            StoredObjectNotFoundException: If the key does not exist
+        """
+        return self._buffered_view(key)
+
+    @contextmanager
+    def _buffered_view(self, key: str) -> Iterator[memoryview]:
+        view = memoryview(self.get(key))
+        try:
+            yield view
+        finally:
+            view.release()
+
+    @abstractmethod
+    def delete(self, key: str) -> bool:
+        """Delete an object, returns False if it did not exist"""
+        pass
+
+    @abstractmethod
+    def list(self, prefix: str = "", start_after: Optional[str] = None) -> List[StoredObject]:
+        """List all objects whose key starts with the prefix, sorted by key, only those after start_after if given"""
+        pass
+
+    def exists(self, key: str) -> bool:
+        """Check whether an object exists"""
+        key = normalize_key(key)
+        return any(obj.key == key for obj in self.list(key))
+
+    def delete_prefix(self, prefix: str) -> int:
+        """Delete every object under a prefix, returns the number of deleted objects"""
+        deleted = 0
+        for obj in self.list(prefix):
+            if self.delete(obj.key):
+                deleted += 1
+        return deleted
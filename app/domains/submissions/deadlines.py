@@ -0,0 +1,22 @@
+"""
+Lateness of submissions against the deadline of their project step
+
+Both instants are compared in UTC, so that a deadline set on either side of a DST change is neither an hour early
+nor an hour late.
+"""
+
+import math
+from datetime import datetime, timedelta
+from typing import Optional
+
+from app.shared.timestamps import as_utc
+
+
+def minutes_late(submitted_at: Optional[datetime], deadline: Optional[datetime]) -> Optional[int]:
+    """Minutes a submission was late by, any started minute counting, None when on time or without deadline"""
+    if submitted_at is None or deadline is None:
+        return None
+    delay = as_utc(submitted_at) - as_utc(deadline)
+    if delay <= timedelta(0):
+        return None
+    return math.ceil(delay / timedelta(minutes=1))
@@ -1,40 +1,97 @@
+import copy
 import logging
+import math
 import threading
 import time
-from concurrent.futures import ThreadPoolExecutor
 from pathlib import Path
-from typing import Any, Dict, List, Optional
+from typing import Any, Dict, List, Optional, Tuple
 from uuid import UUID
 
 from fastapi import HTTPException
 from sqlmodel import Session
 
+from app.config.config import in_settings_snapshot
+from app.domains.detection.comparability import PairComparability
+from app.domains.detection.directory_comparison import (
+    DirectoryComparator,
+    assess_comparability,
+    not_comparable_results,
+    read_source,
+    relative_path,
+    visualize_file_pairs,
+)
+from app.domains.detection.file_errors import FileErrorLog, is_systemic
+from app.domains.detection.pairwise_comparison import PairwiseProgress, ParallelPairwiseComparator
+from app.domains.detection.pruning import PairPruner, PrunedPair
 from app.domains.detection.similarity_detection_service import SimilarityDetectionService
 from app.domains.detection.visualization import VisualizationService
+from app.domains.fingerprints.comparison_index import ComparisonIndex
+from app.domains.fingerprints.auto_tuning import TuningFile, TuningSubmission, tune_fingerprinting
+from app.domains.fingerprints.comparison_index_service import ComparisonIndexService
+from app.domains.fingerprints.fingerprint_models import FingerprintCacheStats
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprinting import fingerprint_similarity
+from app.domains.quotas.quota_service import QuotaService
 from app.domains.repositories.submission_fetcher import SubmissionFetcher, cleanup_temp_directory
+from app.domains.runs.run_progress import NULL_RUN_PROGRESS, RUN_PROGRESS, RunProgress
+from app.domains.runs.run_recorder import DetectionRunRecorder
+from app.domains.runs.runs_models import DetectionRun, DetectionRunStatus, DetectionRunTrigger
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.detection_plan import (
+    THROUGHPUT_HISTORY_RUNS,
+    SubmissionExclusionReason,
+    build_plan,
+    plan_files,
+    read_head,
+)
 from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.domains.submissions.dto.start_detection_dto import DetectionPlanDto, PlannedSubmissionExclusionDto
 from app.domains.submissions.submissions_models import SimilarityStatus, Submission
 from app.domains.submissions.submissions_repository import SubmissionRepository
 from app.domains.submissions.submissions_similarity_repository import SubmissionSimilarityRepository
+from app.domains.tokenization.code_metrics import METRICS_VERSION, CodeMetricsCollector
+from app.domains.tokenization.streaming_source import decode_source
 from app.domains.tokenization.tokenization_service import TokenizationService
-from app.shared.exceptions import DatabaseException, ValidationException
+from app.shared.concurrency import JobPriority, JobScheduler, resolve_workers
+from app.shared.exceptions import DatabaseException, QuotaExceededException, ValidationException
+from app.shared.metrics import DETECTION_TIMEOUTS
+from app.shared.profiling import NULL_PROFILER, StageProfiler
+from app.shared.shutdown import instance_id
+from app.shared.tenancy import current_tenancy, in_tenancy, tenancy_of_project
+from app.shared.timeouts import PAIR, RUN, TimedOut, Timeout
+from app.shared.timestamps import utc_now
+from app.shared.tracing import in_span
 
 logger = logging.getLogger(__name__)
 
+# Submissions whose code metrics are being computed, by the comparison of one of their pairs whatever the run
+_measuring: set = set()
+_measuring_lock = threading.Lock()
+
 
 class DetectionIntegrationService:
     """Service for integrating similarity detection with submissions"""
 
+    # The comparison index of a step is fingerprinted with the parameters of the fingerprint service
+    uses_comparison_index = True
+
     def __init__(
         self,
         session: Session,
         tokenization_service: Optional[TokenizationService] = None,
         similarity_service: Optional[SimilarityDetectionService] = None,
         submission_fetcher: Optional[SubmissionFetcher] = None,
+        storage_service: Optional[SubmissionStorageService] = None,
+        fingerprint_service: Optional[FingerprintService] = None,
+        comparison_index_service: Optional[ComparisonIndexService] = None,
+        job_scheduler: Optional[JobScheduler] = None,
     ):
         self.session = session
         self.submission_repository = SubmissionRepository(session)
         self.similarity_repository = SubmissionSimilarityRepository(session)
+        self.run_repository = DetectionRunRepository(session)
+        self.quota_service = QuotaService(session)
 
         # Use injected services or get singletons
         if tokenization_service is None:
@@ -58,12 +115,36 @@ class DetectionIntegrationService:
         else:
             self.submission_fetcher = submission_fetcher
 
+        self.storage_service = storage_service or SubmissionStorageService()
+
+        if fingerprint_service is None:
+            from app.shared.services import get_fingerprint_service
+
+            self.fingerprint_service = get_fingerprint_service()
+        else:
+            self.fingerprint_service = fingerprint_service
+
+        if comparison_index_service is None:
+            from app.config.config import get_settings
+
+            comparison_index_service = ComparisonIndexService(
+                self.storage_service,
+                self.fingerprint_service,
+                get_settings().comparison_index_bloom_false_positive_rate,
+                self.similarity_service,
+            )
+        self.comparison_index_service = comparison_index_service
+
         from app.shared.services import get_visualization_service
 
         self.visualization_service = get_visualization_service(self.tokenization_service)
 
-        # Create thread pool with limited workers to prevent server overload
-        self.similarity_executor = ThreadPoolExecutor(max_workers=1, thread_name_prefix="similarity")
+        # Runs of every request share the process-wide cap on concurrent detection jobs
+        if job_scheduler is None:
+            from app.shared.services import get_detection_scheduler
+
+            job_scheduler = get_detection_scheduler()
+        self.job_scheduler = job_scheduler
 
         # Thread-local storage for database sessions
         self._local = threading.local()
@@ -76,47 +157,622 @@ class DetectionIntegrationService:
             self._local.session = next(get_session())
         return self._local.session
 
-    def process_submission_similarities_async(self, submission: Submission) -> None:
+    def _fetch_submission_files(self, submission: Submission, submission_data: CreateSubmissionDto) -> Path:
         """
-        Process similarity detection asynchronously - doesn't block submission creation
+        Get a local copy of a submission, from the submission store when it was ingested,
+        otherwise from its original link
         """
         try:
-            # Get all other submissions in the same project step
-            other_submissions = self.submission_repository.get_by_project_step(
-                submission.project_uuid, submission.project_step_uuid
-            )
+            stored_path = self.storage_service.materialize(submission)
+            if stored_path is not None:
+                return stored_path
+        except Exception as e:
+            logger.warning(f"Failed to load submission {submission.id} from storage, fetching from link: {str(e)}")
 
-            # Filter out the current submission and get only completed ones
-            other_submissions = [
-                s for s in other_submissions if s.id != submission.id and s.group_uuid != submission.group_uuid
-            ]
+        return self.submission_fetcher.fetch_submission(submission_data)
+
+    def process_submission_similarities_async(
+        self,
+        submission: Submission,
+        profile: bool = False,
+        priority: JobPriority = JobPriority.NORMAL,
+        trigger: DetectionRunTrigger = DetectionRunTrigger.SUBMISSION,
+        fingerprint_k: Optional[int] = None,
+        fingerprint_window: Optional[int] = None,
+        auto_tune: Optional[bool] = None,
+        schedule_id: Optional[UUID] = None,
+    ) -> Optional[UUID]:
+        """
+        Process similarity detection asynchronously - doesn't block submission creation
+
+        Args:
+            submission: The new submission
+            profile: Record per-stage timings of the run, also enabled for every run by detection_profiling_enabled
+            priority: Priority of the run on the detection scheduler
+            trigger: What started the run
+            fingerprint_k: k-gram size of the run, the configured one by default, never changed by auto-tuning
+            fingerprint_window: Winnowing window of the run, the configured one by default, never changed by
+                auto-tuning
+            auto_tune: Tune the parameters the request leaves unset from the corpus of the run, detection_auto_tune
+                by default
+            schedule_id: Schedule whose tick started the run, with the scheduled trigger
+
+        Returns:
+            ID of the queued run, None when there was nothing to compare or the run could not be started
+
+        Raises:
+            QuotaExceededException: If the run would exceed the runs per day or run submissions quota of the project
+        """
+        try:
+            other_submissions = self._comparison_candidates(submission)
 
             if not other_submissions:
                 logger.info(f"No other submissions found for comparison with submission {submission.id}")
-                return
-
-            # Submit all comparisons to thread pool (fire and forget)
-            for other_submission in other_submissions:
-                self.similarity_executor.submit(
-                    self._process_single_comparison_threaded,
-                    submission.id,
-                    other_submission.id,
-                    submission.project_uuid,
-                    submission.project_step_uuid,
-                )
+                return None
+
+            self.quota_service.check_run(submission.project_uuid, len(other_submissions) + 1)
+
+            # Persist the run before scheduling so its comparisons can be queried while in flight
+            run_id = self._create_detection_run(
+                submission,
+                other_submissions,
+                priority,
+                trigger,
+                self._run_parameters(fingerprint_k, fingerprint_window, auto_tune),
+                schedule_id,
+            )
+            if run_id is not None:
+                RUN_PROGRESS.start(run_id, len(other_submissions))
+
+            # Queue the whole run on the detection scheduler (fire and forget)
+            self._queue_detection_run(
+                run_id,
+                submission.id,
+                [other_submission.id for other_submission in other_submissions],
+                submission.project_uuid,
+                submission.project_step_uuid,
+                profile,
+                priority,
+            )
 
             logger.info(
                 f"Started async similarity processing for submission {submission.id} "
-                f"against {len(other_submissions)} other submissions"
+                f"against {len(other_submissions)} other submissions (run {run_id})"
             )
+            return run_id
 
+        except QuotaExceededException:
+            raise
         except Exception as e:
             logger.error(f"Failed to start async similarity processing: {str(e)}")
+            return None
 
-    def _process_single_comparison_threaded(
-        self, submission1_id: UUID, submission2_id: UUID, project_uuid: UUID, project_step_uuid: UUID
+    def _comparison_candidates(self, submission: Submission) -> List[Submission]:
+        """Submissions of the step a run of a submission compares it with, those of the other groups"""
+        return [
+            other
+            for other in self.submission_repository.get_by_project_step(
+                submission.project_uuid, submission.project_step_uuid
+            )
+            if other.id != submission.id and other.group_uuid != submission.group_uuid
+        ]
+
+    def plan_detection(
+        self,
+        submission: Submission,
+        fingerprint_k: Optional[int] = None,
+        fingerprint_window: Optional[int] = None,
+        auto_tune: Optional[bool] = None,
+    ) -> DetectionPlanDto:
+        """
+        What a detection run of a submission would do, without queuing it: its submissions and their files, those
+        left out and why, their languages, its pairs and their estimated duration, and the parameters it would be
+        recorded with, resolved from k, window and auto_tune like the run does. Nothing is tokenized or written.
+        """
+        parameters = self._run_parameters(fingerprint_k, fingerprint_window, auto_tune)
+        candidates = self._comparison_candidates(submission)
+        candidate_ids = {other.id for other in candidates}
+        excluded = [
+            PlannedSubmissionExclusionDto(
+                submission_id=other.id, group_uuid=other.group_uuid, reason=SubmissionExclusionReason.SAME_GROUP.value
+            )
+            for other in self.submission_repository.get_by_project_step(
+                submission.project_uuid, submission.project_step_uuid
+            )
+            if other.id != submission.id and other.id not in candidate_ids
+        ]
+
+        planned = []
+        for participant in [submission] + candidates:
+            version = self.storage_service.get_latest_version(participant)
+            paths = [f.key for f in self.storage_service.list_files(participant, version)] if version else []
+            planned.append(
+                plan_files(
+                    self.tokenization_service,
+                    participant,
+                    paths,
+                    lambda path, participant=participant, version=version: read_head(
+                        self.storage_service.stream_file(participant, path, version)
+                    ),
+                    stored=version is not None,
+                )
+            )
+        return build_plan(
+            submission.id,
+            planned,
+            excluded,
+            self.run_repository.get_recent_completed_runs(THROUGHPUT_HISTORY_RUNS),
+            parameters,
+        )
+
+    def resume_detection_run(self, run: DetectionRun) -> bool:
+        """
+        Queue the pairs of an interrupted or abandoned run that were not persisted, the run being claimed already.
+        The pairs persisted before it stopped are kept.
+
+        Returns:
+            Whether the run was queued, a run with no pair left is finished instead
+        """
+        if run.trigger_submission_id is None:
+            self.run_repository.finish_run(
+                run.id, DetectionRunStatus.FAILED, "Run stopped before it finished and has no submission to resume"
+            )
+            return False
+
+        compared = self.run_repository.get_compared_submission_ids(run.id)
+        remaining = [
+            participant.submission_id
+            for participant in self.run_repository.get_participants(run.id)
+            if participant.submission_id != run.trigger_submission_id and participant.submission_id not in compared
+        ]
+        if not remaining:
+            DetectionRunRecorder(self.run_repository, run.id).finish(DetectionRunStatus.COMPLETED)
+            return False
+
+        RUN_PROGRESS.start(run.id, len(remaining))
+        self._queue_detection_run(
+            run.id,
+            run.trigger_submission_id,
+            remaining,
+            run.project_uuid,
+            run.project_step_uuid,
+            run.profile is not None,
+            run.priority,
+            run.file_errors,
+        )
+        logger.info(f"Resumed detection run {run.id}, {len(remaining)} of {run.total_pairs} pairs left")
+        return True
+
+    def _queue_detection_run(
+        self,
+        run_id: Optional[UUID],
+        submission_id: UUID,
+        other_submission_ids: List[UUID],
+        project_uuid: UUID,
+        project_step_uuid: UUID,
+        profile: bool = False,
+        priority: JobPriority = JobPriority.NORMAL,
+        file_errors: Optional[list] = None,
     ) -> None:
-        """Process a single comparison in a thread with its own database session"""
+        # Runs queued outside of a request, by schedules or recovery, are restricted to the tenant of their project
+        tenancy = current_tenancy() if current_tenancy().restricted else tenancy_of_project(project_uuid)
+        self.job_scheduler.submit(
+            # The run keeps the settings it starts with, whatever is reloaded while it is in progress
+            in_settings_snapshot(in_span(in_tenancy(tenancy, self._start_detection_run_threaded), run_id=run_id)),
+            run_id,
+            submission_id,
+            other_submission_ids,
+            project_uuid,
+            project_step_uuid,
+            profile,
+            file_errors,
+            priority=priority,
+            job_id=run_id,
+            group=project_uuid,
+        )
+
+    def _create_detection_run(
+        self,
+        submission: Submission,
+        other_submissions: List[Submission],
+        priority: JobPriority = JobPriority.NORMAL,
+        trigger: DetectionRunTrigger = DetectionRunTrigger.SUBMISSION,
+        parameters: Optional[dict] = None,
+        schedule_id: Optional[UUID] = None,
+    ) -> Optional[UUID]:
+        """
+        Persist a detection run and its participants, returns None if the run could not be recorded
+
+        The run classifies its pairs with the declared collaborations its step has now.
+        """
+        try:
+            step_config = self.submission_repository.get_step_config(submission.project_step_uuid)
+            run = self.run_repository.create_run(
+                {
+                    "project_uuid": submission.project_uuid,
+                    "project_step_uuid": submission.project_step_uuid,
+                    "trigger": trigger,
+                    "trigger_submission_id": submission.id,
+                    "schedule_id": schedule_id,
+                    "priority": priority,
+                    "total_pairs": len(other_submissions),
+                    "instance_id": instance_id(),
+                    "heartbeat_at": utc_now(),
+                    "parameters": parameters or {"fingerprint": self.fingerprint_service.parameters},
+                    "declared_collaborations": getattr(step_config, "declared_collaborations", None),
+                },
+                [
+                    {
+                        "submission_id": participant.id,
+                        "group_uuid": participant.group_uuid,
+                        "submitted_by_uuid": participant.submitted_by_uuid,
+                        "team_id": getattr(participant, "team_id", None),
+                        "team_name": getattr(participant, "team_name", None),
+                        "team_members": getattr(participant, "team_members", None),
+                    }
+                    for participant in [submission] + other_submissions
+                ],
+            )
+            return run.id
+        except Exception as e:
+            logger.error(f"Failed to record detection run for submission {submission.id}: {str(e)}")
+            return None
+
+    def _run_parameters(
+        self, fingerprint_k: Optional[int], fingerprint_window: Optional[int], auto_tune: Optional[bool]
+    ) -> dict:
+        """Parameters recorded with a new run, auto-tuning being left pending for the run to do before comparing"""
+        from app.config.config import get_settings
+
+        settings = get_settings()
+        overrides = {
+            name: value for name, value in (("k", fingerprint_k), ("window", fingerprint_window)) if value is not None
+        }
+        fingerprint_service = self.fingerprint_service
+        if overrides:
+            fingerprint_service = fingerprint_service.with_parameters(fingerprint_k, fingerprint_window)
+        parameters = {"fingerprint": fingerprint_service.parameters}
+
+        if settings.detection_auto_tune if auto_tune is None else auto_tune:
+            parameters["auto_tuning"] = {
+                "status": "skipped" if len(overrides) == 2 else "pending",
+                "overrides": overrides,
+                "target": settings.detection_auto_tune_target,
+            }
+            if len(overrides) == 2:
+                parameters["auto_tuning"]["rationale"] = "k and window are set by the request"
+        return parameters
+
+    def _start_detection_run_threaded(
+        self,
+        run_id: Optional[UUID],
+        submission_id: UUID,
+        other_submission_ids: List[UUID],
+        project_uuid: UUID,
+        project_step_uuid: UUID,
+        profile: bool = False,
+        file_errors: Optional[list] = None,
+    ) -> None:
+        """Settle the fingerprinting parameters of a run, tuning them when asked, then process it with them"""
+        service = self
+        if run_id is not None:
+            service = self._with_run_parameters(run_id, [submission_id] + list(other_submission_ids))
+        service._process_detection_run_threaded(
+            run_id, submission_id, other_submission_ids, project_uuid, project_step_uuid, profile, file_errors
+        )
+
+    def _with_run_parameters(self, run_id: UUID, submission_ids: List[UUID]) -> "DetectionIntegrationService":
+        """
+        Service comparing with the k and window of a run
+
+        A pending auto-tuning is done first and recorded with the parameters it chose, explicit parameters of the
+        request winning over the tuned ones. A resumed run keeps the parameters recorded when it started.
+        """
+        from app.config.config import get_settings
+
+        try:
+            repository = DetectionRunRepository(self._get_thread_session())
+            run = repository.get_run(run_id)
+            parameters = dict((run.parameters if run else None) or {})
+        except Exception as e:
+            logger.warning(f"Parameters of detection run {run_id} unavailable, using the configured ones: {str(e)}")
+            return self
+
+        tuning = parameters.get("auto_tuning") or {}
+        if tuning.get("status") == "pending":
+            settings = get_settings()
+            overrides = tuning.get("overrides") or {}
+            (RUN_PROGRESS.get(run_id) or NULL_RUN_PROGRESS).set_stage("auto_tuning")
+            try:
+                result = tune_fingerprinting(
+                    self._tuning_corpus(submission_ids[: settings.detection_auto_tune_sample_submissions]),
+                    tuning.get("target", settings.detection_auto_tune_target),
+                    scheme=self.fingerprint_service.hash_scheme,
+                    tokenizer_config=self.fingerprint_service.tokenizer_config,
+                )
+                fingerprint_service = self.fingerprint_service.with_parameters(
+                    overrides.get("k", result.k), overrides.get("window", result.window)
+                )
+                tuning = {**result.to_dict(), "status": "applied", "overrides": overrides}
+                logger.info(
+                    f"Detection run {run_id} tuned to k={fingerprint_service.k} and "
+                    f"window={fingerprint_service.window}: {result.rationale}"
+                )
+            except Exception as e:
+                logger.warning(f"Auto-tuning of detection run {run_id} failed, using the configured parameters: {e}")
+                fingerprint_service = self.fingerprint_service.with_parameters(
+                    overrides.get("k"), overrides.get("window")
+                )
+                tuning = {**tuning, "status": "failed", "error": str(e)}
+            parameters.update(fingerprint=fingerprint_service.parameters, auto_tuning=tuning)
+            try:
+                repository.update_parameters(run_id, parameters)
+            except Exception as e:
+                logger.error(f"Failed to record the tuned parameters of detection run {run_id}: {str(e)}")
+
+        fingerprint = parameters.get("fingerprint") or {}
+        if not {"k", "window"} <= set(fingerprint):
+            return self
+        fingerprint_service = self.fingerprint_service.with_parameters(fingerprint["k"], fingerprint["window"])
+        if fingerprint_service is self.fingerprint_service:
+            return self
+        service = copy.copy(self)
+        service.fingerprint_service = fingerprint_service
+        service.uses_comparison_index = False
+        return service
+
+    def _tuning_corpus(self, submission_ids: List[UUID]) -> List[TuningSubmission]:
+        """Tokens of the stored files of submissions a run would compare, for auto-tuning"""
+        repository = SubmissionRepository(self._get_thread_session())
+        corpus = []
+        for submission_id in submission_ids:
+            submission = repository.get_by_id(submission_id)
+            version = self.storage_service.get_latest_version(submission) if submission else None
+            if version is None:
+                continue
+            tuning_submission = TuningSubmission(submission.id, submission.group_uuid)
+            for stored in self.storage_service.list_files(submission, version):
+                file_path = Path(stored.key)
+                if not self.tokenization_service.is_supported_file(file_path):
+                    continue
+                content = decode_source(self.storage_service.read_file(submission, stored.key, version))
+                if self.fingerprint_service.is_generated(content, file_path):
+                    continue
+                key = self.fingerprint_service.build_key(content, file_path)
+                tokens = self.fingerprint_service.get_fingerprints(content, file_path).tokens
+                tuning_submission.files.append(TuningFile(key.language, tokens, key.normalization))
+            corpus.append(tuning_submission)
+        return corpus
+
+    def _process_detection_run_threaded(
+        self,
+        run_id: Optional[UUID],
+        submission_id: UUID,
+        other_submission_ids: List[UUID],
+        project_uuid: UUID,
+        project_step_uuid: UUID,
+        profile: bool = False,
+        file_errors: Optional[list] = None,
+    ) -> None:
+        """
+        Process every comparison of a run in a thread, persisting pairs and fragments in batches
+
+        Once the scheduler is interrupted by a shutdown, no new pair is started and the run is checkpointed as
+        interrupted; file_errors are those recorded before a resumed run was interrupted. Once the run timeout
+        expired, the pairs being compared stop at their next checkpoint and are recorded as timed out, no new pair
+        is started and the run is finished as partial with the pairs compared until then.
+        """
+        from app.config.config import get_settings
+
+        settings = get_settings()
+        run_timeout = Timeout(RUN, settings.detection_run_timeout_seconds)
+        profiler = StageProfiler() if profile or settings.detection_profiling_enabled else NULL_PROFILER
+        recorder = None
+        cache_stats = self.fingerprint_service.new_stats()
+        run_progress = (RUN_PROGRESS.get(run_id) if run_id is not None else None) or NULL_RUN_PROGRESS
+        if run_id is not None:
+            recorder = DetectionRunRecorder(
+                DetectionRunRepository(self._get_thread_session()), run_id, settings.detection_run_batch_size
+            )
+            recorder.restore_file_errors(file_errors)
+        interrupted = self.job_scheduler.interrupted
+
+        comparator = ParallelPairwiseComparator(
+            resolve_workers(settings.detection_comparison_workers, lambda cpus: cpus),
+            settings.detection_comparison_chunk_size,
+        )
+        progress = PairwiseProgress()
+        index_stats = pruner = None
+
+        def run_stats() -> dict:
+            return {**cache_stats.to_dict(), "index": index_stats, "pruning": pruner.to_dict() if pruner else None}
+
+        try:
+            # Runs tuned to other parameters than those of the index compare every pair
+            if settings.comparison_index_enabled and self.uses_comparison_index:
+                run_progress.set_stage("candidate_generation")
+                with profiler.stage("candidate_generation"):
+                    index, index_stats = self._sync_comparison_index(project_uuid, project_step_uuid)
+                if index is not None and settings.detection_pruning_enabled:
+                    pruner = PairPruner(
+                        index,
+                        settings.report_min_similarity,
+                        settings.detection_pruning_margin,
+                        self.similarity_service,
+                    )
+
+            # Failures of the database or the storage would fail every remaining pair, they stop the run
+            systemic_errors: List[Exception] = []
+            run_progress.set_stage("comparison")
+
+            def compare(pair: Tuple[UUID, UUID]) -> Optional[tuple]:
+                try:
+                    return self._compare_or_prune_threaded(
+                        pair, pruner, project_uuid, project_step_uuid, cache_stats, profiler, run_progress
+                    )
+                except Exception as e:
+                    if is_systemic(e):
+                        systemic_errors.append(e)
+                    raise
+
+            # Results come back sorted by pair, so the recorded run does not depend on the number of workers
+            with run_timeout:
+                for _, comparison in comparator.compare(
+                    [(submission_id, other_submission_id) for other_submission_id in other_submission_ids],
+                    compare,
+                    progress,
+                    should_cancel=lambda: bool(systemic_errors) or interrupted.is_set() or run_timeout.expired,
+                ):
+                    run_progress.pair_compared()
+                    if recorder and comparison:
+                        try:
+                            with profiler.stage("report_persistence"):
+                                if isinstance(comparison[0], PrunedPair):
+                                    pruned, submission1, submission2 = comparison
+                                    recorder.record_pruned(submission1, submission2, pruned.estimated_similarity)
+                                else:
+                                    recorder.record_comparison(*comparison)
+                        except Exception as e:
+                            logger.error(f"Failed to record comparison in run {run_id}: {str(e)}")
+                            run_progress.warn(f"Failed to record comparison: {str(e)}")
+            timed_out = run_timeout.expired
+            if systemic_errors:
+                raise systemic_errors[0]
+            if progress.cancelled and interrupted.is_set():
+                message = f"Interrupted by a shutdown after {progress.processed}/{progress.total} pairs"
+                logger.warning(f"Detection run {run_id} interrupted by a shutdown, {progress.processed} pairs compared")
+                if recorder:
+                    with profiler.stage("report_persistence"):
+                        recorder.interrupt(
+                            message, cache_stats=run_stats(), profile=profiler.to_dict() if profiler.enabled else None
+                        )
+                run_progress.finish(DetectionRunStatus.INTERRUPTED, message)
+                return
+
+            logger.info(
+                f"Detection run {run_id} compared {progress.processed}/{progress.total} pairs"
+                f"{f' ({pruner.pruned} pruned)' if pruner else ''}, fingerprint cache: "
+                f"{cache_stats.hits} hits, {cache_stats.misses} misses"
+            )
+            status, message = DetectionRunStatus.COMPLETED, None
+            if timed_out:
+                status = DetectionRunStatus.PARTIAL
+                message = f"{TimedOut(run_timeout)}, {progress.processed}/{progress.total} pairs compared"
+                DETECTION_TIMEOUTS.labels(RUN).inc()
+                logger.warning(f"Detection run {run_id} timed out, {progress.processed} pairs compared")
+            finished = None
+            if recorder:
+                with profiler.stage("report_persistence"):
+                    finished = recorder.finish(
+                        status,
+                        message,
+                        cache_stats=run_stats(),
+                        profile=profiler.to_dict() if profiler.enabled else None,
+                    )
+            # Streams end with the terminal event, sent once the run is final in the database, maybe incomplete
+            if finished is not None:
+                run_progress.finish(finished.status, finished.error_message)
+            else:
+                run_progress.finish(status, message)
+        except Exception as e:
+            logger.error(f"Detection run {run_id} failed: {str(e)}")
+            if recorder:
+                recorder.finish(
+                    DetectionRunStatus.FAILED,
+                    str(e),
+                    cache_stats=run_stats(),
+                    profile=profiler.to_dict() if profiler.enabled else None,
+                )
+            run_progress.finish(DetectionRunStatus.FAILED, str(e))
+        finally:
+            # No-op once finished, ends the streams of a run whose failure could not be recorded either
+            run_progress.finish(DetectionRunStatus.FAILED, "Detection run stopped before it was recorded as finished")
+            if profiler.enabled:
+                logger.info(f"Detection run {run_id} profile: {profiler.summary()}")
+
+    def _sync_comparison_index(
+        self, project_uuid: UUID, project_step_uuid: UUID
+    ) -> Tuple[Optional[ComparisonIndex], Optional[dict]]:
+        """Load the comparison index of the step and index its new submissions, (None, None) if it is unavailable"""
+        try:
+            submissions = SubmissionRepository(self._get_thread_session()).get_by_project_step(
+                project_uuid, project_step_uuid
+            )
+            index, stats = self.comparison_index_service.sync(project_uuid, project_step_uuid, submissions)
+            logger.info(
+                f"Comparison index of step {project_step_uuid}: {stats['documents']} submissions, "
+                f"{stats['added']} indexed, {stats['removed']} removed, rebuilt: {stats['rebuilt']}"
+            )
+            return index, stats
+        except Exception as e:
+            logger.warning(f"Comparison index of step {project_step_uuid} unavailable: {str(e)}")
+            return None, None
+
+    def _compare_or_prune_threaded(
+        self,
+        pair: Tuple[UUID, UUID],
+        pruner: Optional[PairPruner],
+        project_uuid: UUID,
+        project_step_uuid: UUID,
+        cache_stats: Optional[FingerprintCacheStats] = None,
+        profiler: StageProfiler = NULL_PROFILER,
+        run_progress: RunProgress = NULL_RUN_PROGRESS,
+    ) -> Optional[tuple]:
+        """
+        Compare a pair in detail unless the pruner rules it out
+
+        Returns:
+            (pruned_pair, submission1, submission2) for a pruned pair, the result of
+            _process_single_comparison_threaded otherwise
+        """
+        pruned = pruner.prune(*pair) if pruner else None
+        if pruned is None:
+            return self._process_single_comparison_threaded(
+                pair[0], pair[1], project_uuid, project_step_uuid, cache_stats, profiler, run_progress
+            )
+
+        submissions = self._pair_submissions(SubmissionRepository(self._get_thread_session()), *pair, project_uuid)
+        if submissions is None:
+            return None
+        return (pruned, *submissions)
+
+    @staticmethod
+    def _pair_submissions(
+        repository: SubmissionRepository, submission1_id: UUID, submission2_id: UUID, project_uuid: UUID
+    ) -> Optional[Tuple[Submission, Submission]]:
+        """Submissions of a pair, None unless both are found in the project of the run"""
+        submission1 = repository.get_by_id(submission1_id)
+        submission2 = repository.get_by_id(submission2_id)
+        if not submission1 or not submission2:
+            logger.error(f"Submissions not found: {submission1_id}, {submission2_id}")
+            return None
+        # Never compared across projects, so across tenants, whatever IDs the run was given
+        if submission1.project_uuid != project_uuid or submission2.project_uuid != project_uuid:
+            logger.error(f"Submissions {submission1_id}, {submission2_id} are not both of project {project_uuid}")
+            return None
+        return submission1, submission2
+
+    def _process_single_comparison_threaded(
+        self,
+        submission1_id: UUID,
+        submission2_id: UUID,
+        project_uuid: UUID,
+        project_step_uuid: UUID,
+        cache_stats: Optional[FingerprintCacheStats] = None,
+        profiler: StageProfiler = NULL_PROFILER,
+        run_progress: RunProgress = NULL_RUN_PROGRESS,
+    ) -> Optional[tuple]:
+        """
+        Process a single comparison in a thread with its own database session
+
+        Returns:
+            (similarity_record, submission1, submission2, results) once the comparison ran (even if it failed or
+            timed out), None if it was skipped
+        """
+        from app.config.config import get_settings
+
+        similarity_record = None
+        submission1 = submission2 = None
         try:
             # Get thread-local session and repositories
             thread_session = self._get_thread_session()
@@ -124,17 +780,15 @@ class DetectionIntegrationService:
             thread_similarity_repo = SubmissionSimilarityRepository(thread_session)
 
             # Get submissions
-            submission1 = thread_submission_repo.get_by_id(submission1_id)
-            submission2 = thread_submission_repo.get_by_id(submission2_id)
-
-            if not submission1 or not submission2:
-                logger.error(f"Submissions not found: {submission1_id}, {submission2_id}")
-                return
+            submissions = self._pair_submissions(thread_submission_repo, submission1_id, submission2_id, project_uuid)
+            if submissions is None:
+                return None
+            submission1, submission2 = submissions
 
             # Check if comparison already exists
             if thread_similarity_repo.check_existing_comparison(submission1_id, submission2_id):
                 logger.info(f"Comparison already exists between {submission1_id} and {submission2_id}")
-                return
+                return None
 
             # Create similarity record
             similarity_record = thread_similarity_repo.create(
@@ -148,14 +802,37 @@ class DetectionIntegrationService:
             )
 
             # Process the comparison using existing logic
-            self._process_single_comparison_with_repos(
-                similarity_record, submission1, submission2, thread_submission_repo, thread_similarity_repo
-            )
+            with Timeout(PAIR, get_settings().detection_pair_timeout_seconds):
+                results = self._process_single_comparison_with_repos(
+                    similarity_record,
+                    submission1,
+                    submission2,
+                    thread_submission_repo,
+                    thread_similarity_repo,
+                    cache_stats,
+                    profiler,
+                    run_progress,
+                )
 
             logger.info(f"Completed async comparison between {submission1_id} and {submission2_id}")
-
+            return similarity_record, submission1, submission2, results
+
+        except TimedOut as e:
+            # Recorded as a timed out pair, by the timeout of the pair or that of the run
+            logger.warning(f"Comparison between {submission1_id} and {submission2_id} stopped: {e}")
+            if e.timeout.scope == PAIR:
+                DETECTION_TIMEOUTS.labels(PAIR).inc()
+            run_progress.warn(str(e), pair=[str(submission1_id), str(submission2_id)])
+            if similarity_record is not None:
+                return similarity_record, submission1, submission2, None
+            return None
         except Exception as e:
             logger.error(f"Failed async comparison between {submission1_id} and {submission2_id}: {str(e)}")
+            if is_systemic(e):
+                raise
+            if similarity_record is not None:
+                return similarity_record, submission1, submission2, None
+            return None
 
     def _process_single_comparison_with_repos(
         self,
@@ -164,8 +841,11 @@ class DetectionIntegrationService:
         submission2: Submission,
         submission_repo: SubmissionRepository,
         similarity_repo: SubmissionSimilarityRepository,
-    ) -> None:
-        """Process comparison with provided repositories (for thread safety)"""
+        cache_stats: Optional[FingerprintCacheStats] = None,
+        profiler: StageProfiler = NULL_PROFILER,
+        run_progress: RunProgress = NULL_RUN_PROGRESS,
+    ) -> dict:
+        """Process comparison with provided repositories (for thread safety), returns the stored results"""
         start_time = time.time()
 
         try:
@@ -192,119 +872,139 @@ class DetectionIntegrationService:
             # Fetch repositories
             repo1_path = None
             repo2_path = None
+            # Submissions whose metrics were not computed when they were stored are measured by their comparison
+            measured = self._claim_metrics({"submission1": submission1, "submission2": submission2}, submission_repo)
 
             try:
-                repo1_path = self.submission_fetcher.fetch_submission(submission1_data)
-                repo2_path = self.submission_fetcher.fetch_submission(submission2_data)
-
-                if not repo1_path.exists() or not repo2_path.exists():
-                    raise HTTPException(status_code=404, detail="Test projects not found")
-
-                # Tokenize all files
-                tokens1 = []
-                tokens2 = []
-
-                repo1_compatible_files = self.tokenization_service.extract_supported_files_from_directory(repo1_path)
-                repo2_compatible_files = self.tokenization_service.extract_supported_files_from_directory(repo2_path)
-
-                for file_path in repo1_compatible_files:
-                    if not file_path.is_file():
-                        continue
-                    content = self._read_file_with_encoding_detection(file_path)
-                    if content is not None:
-                        tokens = self.tokenization_service.tokenize(content, file_path)
-                        tokens1.extend(tokens)
-
-                for file_path in repo2_compatible_files:
-                    if not file_path.is_file():
-                        continue
-                    content = self._read_file_with_encoding_detection(file_path)
-                    if content is not None:
-                        tokens = self.tokenization_service.tokenize(content, file_path)
-                        tokens2.extend(tokens)
-
-                # Perform similarity analysis
-                similarity_result = self.similarity_service.compare_similarity(tokens1, tokens2)
-
-                files_with_similarities_visualization = []
-
-                for file_path in repo1_compatible_files:
-                    content1 = self._read_file_with_encoding_detection(file_path)
-
-                    for file_path2 in repo2_compatible_files:
-                        content2 = self._read_file_with_encoding_detection(file_path2)
-
-                        if content1 is None or content2 is None:
-                            continue
-
-                        react_flow_data = self.visualization_service.generate_react_flow_ast(
-                            content1, content2, file_path.name, file_path2.name, "elk"
-                        )
-
-                        if react_flow_data.get("has_similarity", False):
-                            files_with_similarities_visualization.append(
-                                {
-                                    "file_pair": {
-                                        "file_from_submission1": f"{file_path.name}",
-                                        "file_from_submission2": f"{file_path2.name}",
-                                    },
-                                    "react_flow": react_flow_data,
-                                }
-                            )
-
-                # Sort by similarity
-                files_with_similarities_visualization.sort(
-                    key=lambda x: x["react_flow"].get("analysis_metadata", {}).get("average_similarity", 0.0),
-                    reverse=True,
+                with profiler.stage("file_collection"):
+                    repo1_path = self._fetch_submission_files(submission1, submission1_data)
+                    repo2_path = self._fetch_submission_files(submission2, submission2_data)
+
+                    if not repo1_path.exists() or not repo2_path.exists():
+                        raise HTTPException(status_code=404, detail="Test projects not found")
+
+                # Compare the fetched files, the same way as the CLI compares directories
+                results = self._directory_comparator().compare(
+                    repo1_path,
+                    repo2_path,
+                    cache_stats,
+                    profiler,
+                    run_progress,
+                    start_time,
+                    metrics={side: collector for side, (_, collector) in measured.items()},
                 )
 
-                # Prepare results
-                processing_time = time.time() - start_time
-
-                results = {
-                    "jaccard_similarity": similarity_result["jaccard_similarity"],
-                    "type_similarity": similarity_result["type_similarity"],
-                    "overall_similarity": similarity_result["overall_similarity"],
-                    "structural_similarity": similarity_result["structural_similarity"],
-                    "type_sequence_similarity": similarity_result["type_sequence_similarity"],
-                    "flow_similarity": similarity_result["flow_similarity"],
-                    "operation_similarity": similarity_result["operation_similarity"],
-                    "processing_time_seconds": processing_time,
-                    "similarity_details": {
-                        "algorithm": "ast_similarity",
-                        "common_elements": similarity_result["common_elements"],
-                        "total_unique_elements": similarity_result["total_unique_elements"],
-                        "length_ratio": similarity_result["length_ratio"],
-                        "length_penalty": similarity_result["length_penalty"],
-                        "tokens_count": {"submission1": len(tokens1), "submission2": len(tokens2)},
-                        "processed_tokens_count": {
-                            "submission1": similarity_result["tokens1_length"],
-                            "submission2": similarity_result["tokens2_length"],
-                        },
-                        "files_count": {
-                            "submission1": len(repo1_compatible_files),
-                            "submission2": len(repo2_compatible_files),
-                        },
-                    },
-                    "visualization_data": files_with_similarities_visualization,
-                }
-
                 # Update the similarity record with results
-                similarity_repo.update_results(similarity_record.id, results)
+                with profiler.stage("report_persistence"):
+                    similarity_repo.update_results(similarity_record.id, results)
+                    self._save_metrics(measured, submission_repo)
+                return results
 
             finally:
+                self._release_metrics(measured)
                 # Clean up temporary directories
                 if repo1_path and repo1_path.exists():
                     cleanup_temp_directory(repo1_path)
                 if repo2_path and repo2_path.exists():
                     cleanup_temp_directory(repo2_path)
 
+        except TimedOut as e:
+            similarity_repo.update_status(similarity_record.id, SimilarityStatus.TIMED_OUT, str(e))
+            raise
         except Exception as e:
             # Update status to failed
             similarity_repo.update_status(similarity_record.id, SimilarityStatus.FAILED, str(e))
             logger.error(f"Failed to process comparison: {str(e)}")
             raise
 
+    def _claim_metrics(
+        self, submissions: Dict[str, Submission], submission_repo: SubmissionRepository
+    ) -> Dict[str, Tuple[Submission, CodeMetricsCollector]]:
+        """Collectors of the sides of a pair whose code metrics are not stored in the current version yet"""
+        try:
+            stored = submission_repo.get_metrics_by_submissions([submission.id for submission in submissions.values()])
+        except Exception as e:
+            logger.warning(f"Code metrics not computed, failed to get the stored ones: {e}")
+            return {}
+
+        claimed = {}
+        with _measuring_lock:
+            for side, submission in submissions.items():
+                record = stored.get(submission.id)
+                if record is not None and record.metrics_version == METRICS_VERSION or submission.id in _measuring:
+                    continue
+                _measuring.add(submission.id)
+                claimed[side] = (submission, CodeMetricsCollector(self.tokenization_service.tokenizer_config))
+        return claimed
+
+    def _save_metrics(
+        self, measured: Dict[str, Tuple[Submission, CodeMetricsCollector]], submission_repo: SubmissionRepository
+    ) -> None:
+        """Store the code metrics of the measured submissions, failures never failing the comparison"""
+        for submission, collector in measured.values():
+            try:
+                # Comparisons read the latest stored version, the link when nothing was stored
+                content_version = self.storage_service.get_latest_version(submission)
+                submission_repo.save_metrics(submission, METRICS_VERSION, collector.to_dict(), content_version)
+            except Exception as e:
+                logger.warning(f"Failed to save the code metrics of submission {submission.id}: {e}")
+
+    def measure_submission(
+        self,
+        submission: Submission,
+        directory: Path,
+        content_version: Optional[int],
+        submission_repo: SubmissionRepository,
+    ) -> None:
+        """Compute and store the code metrics of the fetched files of a submission, failures are logged only"""
+        try:
+            metrics = self._directory_comparator().measure(directory)
+            submission_repo.save_metrics(submission, METRICS_VERSION, metrics, content_version)
+        except Exception as e:
+            logger.warning(f"Failed to compute the code metrics of submission {submission.id}: {e}")
+
+    @staticmethod
+    def _release_metrics(measured: Dict[str, Tuple[Submission, CodeMetricsCollector]]) -> None:
+        with _measuring_lock:
+            for submission, _ in measured.values():
+                _measuring.discard(submission.id)
+
+    def _directory_comparator(self) -> DirectoryComparator:
+        from app.config.config import get_settings
+
+        return DirectoryComparator(
+            self.tokenization_service,
+            self.fingerprint_service,
+            self.similarity_service,
+            self.visualization_service,
+            file_timeout_seconds=get_settings().detection_file_timeout_seconds,
+        )
+
+    def _assess_comparability(
+        self, repo1_files: List[Path], tokens1: List[dict], repo2_files: List[Path], tokens2: List[dict]
+    ) -> PairComparability:
+        """Comparability of the tokens of both submissions, flagged low confidence below the configured minimum"""
+        return assess_comparability(repo1_files, tokens1, repo2_files, tokens2)
+
+    def _not_comparable_results(
+        self, comparability: PairComparability, processing_time: float, details: Dict[str, Any]
+    ) -> dict:
+        """Results of a pair that is not scored, every metric at zero and the reasons in its details"""
+        return not_comparable_results(comparability, processing_time, details)
+
+    def _visualize_file_pairs(
+        self,
+        repo1_files: List[Path],
+        repo2_files: List[Path],
+        repo1_path: Path,
+        repo2_path: Path,
+        file_errors: Optional[FileErrorLog] = None,
+    ) -> List[dict]:
+        """Visualization of every pair of files sharing code, most similar first, see visualize_file_pairs"""
+        return visualize_file_pairs(
+            self.visualization_service, repo1_files, repo2_files, repo1_path, repo2_path, file_errors, self._read_source
+        )
+
     def process_submission_similarities(self, submission: Submission) -> List[str]:
         """
         Process similarity detection for a new submission against all other submissions
@@ -396,15 +1096,17 @@ class DetectionIntegrationService:
             repo2_path = None
 
             try:
-                repo1_path = self.submission_fetcher.fetch_submission(submission1_data)
-                repo2_path = self.submission_fetcher.fetch_submission(submission2_data)
+                repo1_path = self._fetch_submission_files(submission1, submission1_data)
+                repo2_path = self._fetch_submission_files(submission2, submission2_data)
 
                 if not repo1_path.exists() or not repo2_path.exists():
                     raise HTTPException(status_code=404, detail="Test projects not found")
 
-                # Tokenize all files
+                # Tokenize all files, reusing cached fingerprints of unchanged files
                 tokens1 = []
                 tokens2 = []
+                fingerprints1 = set()
+                fingerprints2 = set()
                 source1 = ""
                 source2 = ""
 
@@ -418,9 +1120,10 @@ class DetectionIntegrationService:
                     # Read and tokenize the file with encoding detection
                     content = self._read_file_with_encoding_detection(file_path)
                     if content is not None:
-                        tokens = self.tokenization_service.tokenize(content, file_path)
-                        tokens1.extend(tokens)
-                        source1 += f"\n# === {file_path.name} ===\n" + content + "\n"
+                        fingerprint_set = self.fingerprint_service.get_fingerprints(content, file_path)
+                        tokens1.extend(fingerprint_set.tokens)
+                        fingerprints1 |= fingerprint_set.hashes
+                        source1 += f"\n# === {relative_path(file_path, repo1_path)} ===\n" + content + "\n"
 
                 for file_path in repo2_compatible_files:
                     if not file_path.is_file():
@@ -429,43 +1132,37 @@ class DetectionIntegrationService:
                     # Read and tokenize the file with encoding detection
                     content = self._read_file_with_encoding_detection(file_path)
                     if content is not None:
-                        tokens = self.tokenization_service.tokenize(content, file_path)
-                        tokens2.extend(tokens)
-                        source2 += f"\n# === {file_path.name} ===\n" + content + "\n"
+                        fingerprint_set = self.fingerprint_service.get_fingerprints(content, file_path)
+                        tokens2.extend(fingerprint_set.tokens)
+                        fingerprints2 |= fingerprint_set.hashes
+                        source2 += f"\n# === {relative_path(file_path, repo2_path)} ===\n" + content + "\n"
+
+                # Submissions without comparable tokens are never scored
+                comparability = self._assess_comparability(
+                    repo1_compatible_files, tokens1, repo2_compatible_files, tokens2
+                )
+                if not comparability.comparable:
+                    self.similarity_repository.update_results(
+                        similarity_record.id,
+                        self._not_comparable_results(
+                            comparability,
+                            time.time() - start_time,
+                            {
+                                "tokens_count": {"submission1": len(tokens1), "submission2": len(tokens2)},
+                                "files_count": {
+                                    "submission1": len(repo1_compatible_files),
+                                    "submission2": len(repo2_compatible_files),
+                                },
+                            },
+                        ),
+                    )
+                    return
 
                 # Perform similarity analysis
                 similarity_result = self.similarity_service.compare_similarity(tokens1, tokens2)
 
-                files_with_similarities_visualization = []
-
-                for file_path in repo1_compatible_files:
-                    content1 = self._read_file_with_encoding_detection(file_path)
-
-                    for file_path2 in repo2_compatible_files:
-                        content2 = self._read_file_with_encoding_detection(file_path2)
-
-                        if content1 is None or content2 is None:
-                            continue
-
-                        react_flow_data = self.visualization_service.generate_react_flow_ast(
-                            content1, content2, file_path.name, file_path2.name, "elk"  # Always use ELK
-                        )
-
-                        if react_flow_data.get("has_similarity", False):
-                            files_with_similarities_visualization.append(
-                                {
-                                    "file_pair": {
-                                        "file_from_submission1": f"{file_path.name}",
-                                        "file_from_submission2": f"{file_path2.name}",
-                                    },
-                                    "react_flow": react_flow_data,
-                                }
-                            )
-
-                # order the files pairs by the "analysis_metadata" -> "average_similarity" from the react_flow_data, most similar first
-                files_with_similarities_visualization.sort(
-                    key=lambda x: x["react_flow"].get("analysis_metadata", {}).get("average_similarity", 0.0),
-                    reverse=True,
+                files_with_similarities_visualization = self._visualize_file_pairs(
+                    repo1_compatible_files, repo2_compatible_files, repo1_path, repo2_path
                 )
 
                 # Calculate overall similarity score
@@ -494,6 +1191,8 @@ class DetectionIntegrationService:
                         "length_ratio": similarity_result["length_ratio"],
                         "length_penalty": similarity_result["length_penalty"],
                         "tokens_count": {"submission1": len(tokens1), "submission2": len(tokens2)},
+                        **comparability.to_details(),
+                        "fingerprint_similarity": fingerprint_similarity(fingerprints1, fingerprints2),
                         "processed_tokens_count": {
                             "submission1": similarity_result["tokens1_length"],
                             "submission2": similarity_result["tokens2_length"],
@@ -590,7 +1289,7 @@ class DetectionIntegrationService:
                 continue
 
         # Calculate aggregate statistics
-        total_similarity = sum(block.get("similarity_score", 0.0) for block in all_shared_blocks)
+        total_similarity = math.fsum(block.get("similarity_score", 0.0) for block in all_shared_blocks)
         average_similarity = total_similarity / len(all_shared_blocks) if all_shared_blocks else 0.0
 
         result = {
@@ -633,7 +1332,7 @@ class DetectionIntegrationService:
             file_data = {
                 "path": file_path,
                 "name": file_path.name,
-                "relative_path": str(file_path.relative_to(repo_path)),
+                "relative_path": relative_path(file_path, repo_path),
                 "content": content,
                 "language": language,
                 "functions": functions,
@@ -686,8 +1385,8 @@ class DetectionIntegrationService:
         comparison_result = self.similarity_service.detect_shared_code_blocks(
             source1=file1_data["content"],
             source2=file2_data["content"],
-            file1_name=file1_data["name"],
-            file2_name=file2_data["name"],
+            file1_name=file1_data["relative_path"],
+            file2_name=file2_data["relative_path"],
             file1_path=file1_data["path"],
             file2_path=file2_data["path"],
             tokenization_service=self.tokenization_service,
@@ -715,7 +1414,10 @@ class DetectionIntegrationService:
 
     def _read_file_with_encoding_detection(self, file_path) -> Optional[str]:
         """
-        Read a file with multiple encoding attempts to handle various file encodings.
+        Read and decode a source file whatever its encoding.
+
+        The encoding of a byte order mark wins and the mark is stripped, otherwise UTF-8 then latin-1, which
+        decodes any byte sequence. Line endings are normalized, like every decoded source.
 
         Args:
             file_path: Path to the file to read
@@ -723,37 +1425,16 @@ class DetectionIntegrationService:
         Returns:
             File content as string, or None if reading fails
         """
-        encodings_to_try = [
-            "utf-8",
-            "utf-8-sig",  # UTF-8 with BOM
-            "latin-1",
-            "cp1252",  # Windows-1252
-            "iso-8859-1",
-            "ascii",
-        ]
-
-        for encoding in encodings_to_try:
-            try:
-                with open(file_path, "r", encoding=encoding) as f:
-                    content = f.read()
-                logger.debug(f"Successfully read {file_path} with encoding: {encoding}")
-                return content
-            except UnicodeDecodeError:
-                continue
-            except Exception as e:
-                logger.warning(f"Failed to read {file_path} with encoding {encoding}: {e}")
-                continue
-
-        # If all encodings fail, try with error handling
         try:
-            with open(file_path, "r", encoding="utf-8", errors="replace") as f:
-                content = f.read()
-            logger.warning(f"Read {file_path} with UTF-8 and replaced invalid characters")
-            return content
+            return self._read_source(file_path)
         except Exception as e:
-            logger.error(f"Failed to read {file_path} with all encoding attempts: {e}")
+            logger.error(f"Failed to read {file_path}: {e}")
             return None
 
+    def _read_source(self, file_path) -> str:
+        """Same as _read_file_with_encoding_detection, raising the errors of reading the file"""
+        return read_source(file_path)
+
     def get_submission_similarities(self, submission_id: UUID) -> List[dict]:
         """Get all similarity results for a submission (bidirectional)"""
         try:
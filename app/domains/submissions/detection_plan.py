@@ -0,0 +1,165 @@
+"""
+Detection plans
+What a detection run of a submission would do, computed without tokenizing or comparing anything: the submissions
+it would compare, the files of each it would tokenize and those it would leave out with why, their languages, its
+pairs and a rough duration from the throughput of the recent runs.
+
+Files are those of the latest stored version of each submission, classified by the tokenization service like it
+classifies the files of a fetched submission in extract_supported_files_from_directory. Only the first bytes of the
+files of languages with generated file markers are read.
+"""
+
+from enum import Enum
+from pathlib import Path
+from typing import Any, Callable, Dict, Iterable, List, Optional
+
+from app.domains.submissions.dto.start_detection_dto import (
+    DetectionPlanDto,
+    PlannedFileExclusionDto,
+    PlannedSubmissionDto,
+    PlannedSubmissionExclusionDto,
+)
+from app.domains.tokenization.streaming_source import decode_source
+from app.domains.tokenization.tokenizer_config import GENERATED_MARKER_HEAD_BYTES
+from app.shared.timestamps import as_utc
+
+# Completed runs the duration of a pair is averaged over
+THROUGHPUT_HISTORY_RUNS = 20
+
+
+class FileExclusionReason(str, Enum):
+    """Why a detection run leaves a file of a submission out"""
+
+    UNSUPPORTED_LANGUAGE = "unsupported_language"
+    GENERATED = "generated"
+
+
+class SubmissionExclusionReason(str, Enum):
+    """Why a detection run does not compare a submission of the step"""
+
+    SAME_GROUP = "same_group"
+
+
+def read_head(chunks: Iterable[bytes], size: int = GENERATED_MARKER_HEAD_BYTES) -> bytes:
+    """First bytes of a streamed file, the rest is never read"""
+    head = b""
+    for chunk in chunks:
+        head += chunk
+        if len(head) >= size:
+            break
+    return head[:size]
+
+
+def _read_or_empty(read: Callable[[str], bytes], path: str) -> bytes:
+    """First bytes of a file, none when it cannot be read, as runs do not find unreadable files generated"""
+    try:
+        return read(path)
+    except Exception:
+        return b""
+
+
+def plan_files(
+    tokenization_service, submission, paths: Iterable[str], read: Callable[[str], bytes], stored: bool = True
+) -> PlannedSubmissionDto:
+    """
+    Files of a submission a run would tokenize and leave out
+
+    Args:
+        tokenization_service: Service deciding which files are supported and generated
+        submission: The submission
+        paths: Paths of its files, relative to its root
+        read: First bytes of a file from its path, only called for files that may be generated
+        stored: Whether the files are those of a stored version
+    """
+    languages: Dict[str, int] = {}
+    excluded: List[PlannedFileExclusionDto] = []
+    for path in sorted(paths):
+        file_path = Path(path)
+        if not tokenization_service.is_supported_file(file_path):
+            excluded.append(PlannedFileExclusionDto(path=path, reason=FileExclusionReason.UNSUPPORTED_LANGUAGE.value))
+            continue
+        if tokenization_service.may_be_generated(file_path) and tokenization_service.is_generated_head(
+            file_path, decode_source(_read_or_empty(read, path))
+        ):
+            excluded.append(PlannedFileExclusionDto(path=path, reason=FileExclusionReason.GENERATED.value))
+            continue
+        language = tokenization_service._detect_language(file_path)
+        languages[language] = languages.get(language, 0) + 1
+
+    return PlannedSubmissionDto(
+        submission_id=submission.id,
+        group_uuid=submission.group_uuid,
+        stored=stored,
+        files=sum(languages.values()),
+        languages=dict(sorted(languages.items())),
+        excluded_files=excluded,
+    )
+
+
+def seconds_per_pair(runs: Iterable) -> Optional[float]:
+    """Average duration of a compared pair over finished runs, None without any compared pair"""
+    runs = [run for run in runs if run.finished_at is not None and run.completed_pairs]
+    pairs = sum(run.completed_pairs for run in runs)
+    if not pairs:
+        return None
+    seconds = sum((as_utc(run.finished_at) - as_utc(run.started_at)).total_seconds() for run in runs)
+    return round(max(seconds, 0.0) / pairs, 3)
+
+
+def plan_warnings(submissions: List[PlannedSubmissionDto], pairs: int) -> List[str]:
+    """Misconfigurations a run would hit, the submissions in plan order"""
+    warnings = []
+    if not pairs:
+        warnings.append("No submission of another group to compare with, no run would be started")
+
+    for planned in submissions:
+        if not planned.stored:
+            warnings.append(
+                f"Submission {planned.submission_id} is not stored, its files are only known once fetched from its link"
+            )
+        elif not planned.files:
+            reasons = {exclusion.reason for exclusion in planned.excluded_files}
+            if reasons == {FileExclusionReason.UNSUPPORTED_LANGUAGE.value}:
+                problem = "only has files of unsupported languages"
+            elif reasons:
+                problem = "has no file left after exclusions"
+            else:
+                problem = "has no file"
+            warnings.append(f"Submission {planned.submission_id} {problem}, its pairs would not be comparable")
+
+    if submissions and all(planned.stored and not planned.files for planned in submissions):
+        warnings.append("No file would be compared, the corpus is empty after exclusions")
+    return warnings
+
+
+def build_plan(
+    submission_id,
+    submissions: List[PlannedSubmissionDto],
+    excluded_submissions: List[PlannedSubmissionExclusionDto],
+    recent_runs: Iterable = (),
+    parameters: Optional[Dict[str, Any]] = None,
+) -> DetectionPlanDto:
+    """Plan of a run comparing the first submission with each other one"""
+    pairs = max(len(submissions) - 1, 0)
+    languages: Dict[str, int] = {}
+    excluded_files: Dict[str, int] = {}
+    for planned in submissions:
+        for language, count in planned.languages.items():
+            languages[language] = languages.get(language, 0) + count
+        for exclusion in planned.excluded_files:
+            excluded_files[exclusion.reason] = excluded_files.get(exclusion.reason, 0) + 1
+
+    pair_seconds = seconds_per_pair(recent_runs)
+    return DetectionPlanDto(
+        submission_id=submission_id,
+        submissions=submissions,
+        excluded_submissions=excluded_submissions,
+        files=sum(planned.files for planned in submissions),
+        excluded_files=dict(sorted(excluded_files.items())),
+        languages=dict(sorted(languages.items(), key=lambda item: (-item[1], item[0]))),
+        pairs=pairs,
+        seconds_per_pair=pair_seconds,
+        estimated_seconds=round(pairs * pair_seconds, 1) if pair_seconds is not None else None,
+        parameters=parameters or {},
+        warnings=plan_warnings(submissions, pairs),
+    )
@@ -11,6 +11,13 @@ from .similarity_response_dto import (
     SimilarityStatisticsDto,
     SubmissionSummaryDto,
 )
+from .start_detection_dto import (
+    DetectionPlanDto,
+    PlannedFileExclusionDto,
+    PlannedSubmissionDto,
+    PlannedSubmissionExclusionDto,
+    StartDetectionResponseDto,
+)
 from .submission_response_dto import SubmissionResponseDto
 from .submission_update_dto import SubmissionUpdateDto
 
@@ -19,6 +26,11 @@ __all__ = [
     "RuleDto",
     "SubmissionResponseDto",
     "SubmissionUpdateDto",
+    "StartDetectionResponseDto",
+    "DetectionPlanDto",
+    "PlannedSubmissionDto",
+    "PlannedSubmissionExclusionDto",
+    "PlannedFileExclusionDto",
     "CreateSubmissionResponseDto",
     "SimilarityMetricsDto",
     "SubmissionSummaryDto",
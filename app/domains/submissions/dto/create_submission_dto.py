@@ -1,4 +1,3 @@
-from datetime import datetime
 from typing import List, Optional
 from uuid import UUID
 
@@ -6,6 +5,7 @@ from pydantic import BaseModel, ConfigDict, Field, field_validator
 
 from app.domains.submissions.dto.rule_dto import RuleDto
 from app.domains.submissions.submissions_models import LinkType
+from app.shared.timestamps import OffsetTimestamp
 
 
 class CreateSubmissionDto(BaseModel):
@@ -25,7 +25,7 @@ class CreateSubmissionDto(BaseModel):
                 "submitted_by_uuid": "550e8400-e29b-41d4-a716-446655440005",
                 "file_size_bytes": 1024000,
                 "file_count": 25,
-                "upload_date_time": "2024-01-15T10:30:00Z",
+                "upload_date_time": "2024-01-15T11:30:00+01:00",
                 "rules": [
                     {"name": "max_archive_size", "params": {"max_size_mb": 100}},
                     {
@@ -51,9 +51,14 @@ class CreateSubmissionDto(BaseModel):
     link_type: Optional[LinkType] = None
     description: Optional[str] = None
     submitted_by_uuid: Optional[UUID] = None
+    team_id: Optional[UUID] = Field(
+        default=None, description="Team submitting, its name and members are recorded with the submission"
+    )
     file_size_bytes: Optional[int] = None
     file_count: Optional[int] = None
-    upload_date_time: Optional[datetime] = None
+    upload_date_time: Optional[OffsetTimestamp] = Field(
+        default=None, description="When the submission was made, with an explicit offset, now if omitted"
+    )
 
     # Rules as properly typed DTOs for OpenAPI schema
     rules: Optional[List[RuleDto]] = None
@@ -37,3 +37,8 @@ class CreateSubmissionResponseDto(BaseModel):
     submission_id: Optional[UUID] = None
     data: Optional[SubmissionResponseDto] = None
     rule_results: Optional[List[Dict[str, Any]]] = None
+    storage: Optional[Dict[str, Any]] = None
+    # Detection run started by the submission, None when there was nothing to compare it with
+    run_id: Optional[UUID] = None
+    # Quota of the project that kept the detection run from starting, with its usage, None when it started
+    run_quota_exceeded: Optional[Dict[str, Any]] = None
@@ -0,0 +1,122 @@
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field, field_validator, model_validator
+
+from app.domains.submissions.submissions_models import LanguageEnforcement
+from app.shared.timestamps import OffsetTimestamp, UtcTimestamp
+
+
+# Kinds of the parties of declared collaborations
+COLLABORATION_PARTY_KINDS = ("student", "team", "group")
+
+
+class LanguagePolicyDto(BaseModel):
+    """DTO for the languages the submissions of a project step are allowed in"""
+
+    allowed_languages: List[str] = Field(min_length=1, description="Languages of the assignment, e.g. [\"c\"]")
+    auxiliary_languages: List[str] = Field(
+        default=[], description="Languages left out of the shares, e.g. build scripts and documentation"
+    )
+    warning_threshold: float = Field(
+        default=0.1, ge=0.0, le=1.0, description="Share of content in disallowed languages from which it is a warning"
+    )
+    violation_threshold: float = Field(
+        default=0.5, ge=0.0, le=1.0, description="Share of content in disallowed languages from which it is a violation"
+    )
+    enforce: LanguageEnforcement = Field(
+        default=LanguageEnforcement.FLAG, description="flag violating submissions, or reject them at upload"
+    )
+
+    @field_validator("allowed_languages", "auxiliary_languages")
+    def normalize_languages(cls, languages: List[str]) -> List[str]:
+        """Language names in lowercase, sorted and without duplicates"""
+        return sorted({language.strip().lower() for language in languages if language.strip()})
+
+    @model_validator(mode="after")
+    def check_thresholds(self) -> "LanguagePolicyDto":
+        """Validate that warnings come before violations and that allowed languages are not auxiliary"""
+        if self.warning_threshold > self.violation_threshold:
+            raise ValueError("warning_threshold cannot be above violation_threshold")
+        if set(self.allowed_languages) & set(self.auxiliary_languages):
+            raise ValueError("a language cannot be both allowed and auxiliary")
+        return self
+
+
+class DeclaredCollaborationDto(BaseModel):
+    """DTO for two parties of a project step allowed to share code, with each other or among themselves"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "parties": ["student:550e8400-e29b-41d4-a716-446655440005", "team:7d1f6a52-3c1e-4f0b-9a51-2f6e1c9b8a10"]
+            }
+        }
+    )
+
+    parties: List[str] = Field(
+        min_length=2,
+        max_length=2,
+        description="student:<uuid>, team:<uuid> or group:<uuid>, every student of the group, * for any of a kind",
+    )
+
+    @field_validator("parties")
+    def normalize_parties(cls, parties: List[str]) -> List[str]:
+        """Validate the kind and identifier of each party, as kind:uuid in lowercase"""
+        normalized = []
+        for party in parties:
+            kind, _, identifier = party.strip().partition(":")
+            kind, identifier = kind.lower(), identifier.strip()
+            if party.strip() == "*":
+                normalized.append("*")
+                continue
+            if kind not in COLLABORATION_PARTY_KINDS:
+                raise ValueError(f"party {party!r} must be student:<uuid>, team:<uuid>, group:<uuid> or *")
+            if identifier != "*":
+                try:
+                    identifier = str(UUID(identifier))
+                except ValueError:
+                    raise ValueError(f"party {party!r} must name a UUID or *")
+            normalized.append(f"{kind}:{identifier}")
+        return normalized
+
+
+class ProjectStepConfigDto(BaseModel):
+    """DTO for creating or replacing the configuration of a project step"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "deadline": "2024-03-31T23:59:00+02:00",
+                "language_policy": {
+                    "allowed_languages": ["c"],
+                    "auxiliary_languages": ["make", "markdown", "python"],
+                    "warning_threshold": 0.1,
+                    "violation_threshold": 0.5,
+                    "enforce": "reject",
+                },
+            }
+        }
+    )
+
+    deadline: Optional[OffsetTimestamp] = Field(
+        default=None, description="Deadline of the submissions with an explicit offset, none if omitted"
+    )
+    language_policy: Optional[LanguagePolicyDto] = Field(
+        default=None, description="Languages the submissions are allowed in, any if omitted"
+    )
+
+
+class ProjectStepConfigResponseDto(BaseModel):
+    """DTO for reading the configuration of a project step"""
+
+    model_config = ConfigDict(from_attributes=True)
+
+    project_uuid: UUID
+    project_step_uuid: UUID
+    deadline: Optional[UtcTimestamp] = None
+    language_policy: Optional[LanguagePolicyDto] = None
+    declared_collaborations: Optional[List[DeclaredCollaborationDto]] = None
+    late_submissions: int = Field(default=0, description="Submissions of the step uploaded after the deadline")
+    created_at: UtcTimestamp
+    updated_at: Optional[UtcTimestamp] = None
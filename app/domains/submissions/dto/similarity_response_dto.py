@@ -1,10 +1,10 @@
-from datetime import datetime
 from typing import Any, Dict, List, Optional
 from uuid import UUID
 
 from pydantic import BaseModel, ConfigDict
 
 from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.timestamps import UtcTimestamp
 
 
 class SimilarityMetricsDto(BaseModel):
@@ -50,7 +50,7 @@ class SubmissionSummaryDto(BaseModel):
     link: str
     description: Optional[str]
     submitted_by_uuid: Optional[UUID]
-    upload_date_time: datetime
+    upload_date_time: UtcTimestamp
 
 
 class SimilarityResponseDto(BaseModel):
@@ -87,7 +87,7 @@ class SimilarityResponseDto(BaseModel):
     flow_similarity: Optional[float] = None
     operation_similarity: Optional[float] = None
     status: SimilarityStatus
-    created_at: datetime
+    created_at: UtcTimestamp
     processing_time_seconds: Optional[float]
     error_message: Optional[str]
 
@@ -174,7 +174,7 @@ class SimilarityAlertDto(BaseModel):
     shared_blocks_count: int
     submission1: SubmissionSummaryDto
     submission2: SubmissionSummaryDto
-    detected_at: datetime
+    detected_at: UtcTimestamp
 
 
 class SimilarityStatisticsDto(BaseModel):
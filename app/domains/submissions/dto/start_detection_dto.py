@@ -0,0 +1,85 @@
+from typing import Any, Dict, List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict
+
+
+class StartDetectionResponseDto(BaseModel):
+    """DTO for a detection run queued for an existing submission"""
+
+    submission_id: UUID
+    run_id: UUID
+    message: str
+
+
+class PlannedFileExclusionDto(BaseModel):
+    """DTO for a file of a submission a detection run would leave out"""
+
+    path: str
+    reason: str  # "unsupported_language" or "generated"
+
+
+class PlannedSubmissionDto(BaseModel):
+    """DTO for a submission a detection run would compare"""
+
+    submission_id: UUID
+    group_uuid: UUID
+    stored: bool  # files of submissions never stored are only known once fetched from their link
+    files: int  # files that would be tokenized
+    languages: Dict[str, int]  # files that would be tokenized by language
+    excluded_files: List[PlannedFileExclusionDto]
+
+
+class PlannedSubmissionExclusionDto(BaseModel):
+    """DTO for a submission of the step a detection run would not compare"""
+
+    submission_id: UUID
+    group_uuid: UUID
+    reason: str  # "same_group"
+
+
+class DetectionPlanDto(BaseModel):
+    """DTO for what a detection run of a submission would do, computed without running it"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "submission_id": "550e8400-e29b-41d4-a716-446655440000",
+                "dry_run": True,
+                "submissions": [
+                    {
+                        "submission_id": "550e8400-e29b-41d4-a716-446655440000",
+                        "group_uuid": "550e8400-e29b-41d4-a716-446655440001",
+                        "stored": True,
+                        "files": 12,
+                        "languages": {"python": 11, "yaml": 1},
+                        "excluded_files": [{"path": "migrations/0001_initial.py", "reason": "generated"}],
+                    }
+                ],
+                "excluded_submissions": [],
+                "files": 12,
+                "excluded_files": {"generated": 1},
+                "languages": {"python": 11, "yaml": 1},
+                "pairs": 0,
+                "seconds_per_pair": 0.8,
+                "estimated_seconds": 0.0,
+                "parameters": {"fingerprint": {"k": 5, "window": 4}},
+                "warnings": ["No submission of another group to compare with, no run would be started"],
+            }
+        }
+    )
+
+    submission_id: UUID
+    dry_run: bool = True
+    submissions: List[PlannedSubmissionDto]  # the submission first, then those it would be compared with
+    excluded_submissions: List[PlannedSubmissionExclusionDto]
+    files: int
+    excluded_files: Dict[str, int]  # by reason
+    languages: Dict[str, int]
+    pairs: int
+    # Average duration of a pair over the recent completed runs, None without any
+    seconds_per_pair: Optional[float] = None
+    estimated_seconds: Optional[float] = None
+    # Parameters the run would be recorded with, a pending auto-tuning being done by the run itself
+    parameters: Dict[str, Any] = {}
+    warnings: List[str]
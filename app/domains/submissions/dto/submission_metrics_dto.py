@@ -0,0 +1,30 @@
+from typing import Dict, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, Field
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class CodeMetricsDto(BaseModel):
+    """DTO for the code metrics of files, null when they could not be computed for one of them"""
+
+    files: int = Field(description="Number of files")
+    code_lines: Optional[int] = Field(description="Lines holding code, without the comment and blank lines")
+    comment_lines: Optional[int] = Field(description="Lines holding comments only")
+    blank_lines: Optional[int] = Field(description="Lines holding whitespace only")
+    comment_ratio: Optional[float] = Field(description="Comment lines out of the code and comment lines")
+    functions: Optional[int] = Field(description="Functions, named and anonymous")
+    cyclomatic_complexity: Optional[int] = Field(description="Functions plus decision points")
+
+
+class SubmissionMetricsDto(CodeMetricsDto):
+    """DTO for reading the code metrics of a submission, in total and per language"""
+
+    submission_id: UUID
+    metrics_version: str = Field(description="Version of the metric definitions they were computed with")
+    content_version: Optional[int] = Field(
+        default=None, description="Stored version of the files they were computed from, None for the fetched link"
+    )
+    computed_at: UtcTimestamp
+    languages: Dict[str, CodeMetricsDto] = Field(description="Metrics of the files of each language")
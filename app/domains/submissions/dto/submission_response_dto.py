@@ -1,10 +1,25 @@
-from datetime import datetime
-from typing import Optional
+from typing import Dict, List, Optional
 from uuid import UUID
 
 from pydantic import BaseModel, ConfigDict
 
-from app.domains.submissions.submissions_models import LinkType, SubmissionStatus
+from app.domains.submissions.submissions_models import LanguagePolicyStatus, LinkType, SubmissionStatus
+from app.shared.timestamps import UtcTimestamp
+
+
+class LanguagePolicyResultDto(BaseModel):
+    """DTO for the result of a submission against the language policy of its step"""
+
+    model_config = ConfigDict(use_enum_values=True)
+
+    status: LanguagePolicyStatus
+    # Bytes of the files a detection run would compare, by language
+    content_bytes: Dict[str, int]
+    # Share of the content in each language, auxiliary languages left out
+    shares: Dict[str, float]
+    disallowed_share: float
+    disallowed_languages: List[str]
+    ignored_languages: List[str]  # auxiliary languages found in the submission
 
 
 class SubmissionResponseDto(BaseModel):
@@ -22,6 +37,9 @@ class SubmissionResponseDto(BaseModel):
                 "link_type": "github",
                 "description": "Final submission for project step 1",
                 "submitted_by_uuid": "550e8400-e29b-41d4-a716-446655440005",
+                "team_id": "550e8400-e29b-41d4-a716-446655440007",
+                "team_name": "Team Rocket",
+                "team_members": ["550e8400-e29b-41d4-a716-446655440005", "550e8400-e29b-41d4-a716-446655440006"],
                 "file_size_bytes": 1024000,
                 "file_count": 25,
                 "upload_date_time": "2024-01-15T10:30:00Z",
@@ -30,6 +48,17 @@ class SubmissionResponseDto(BaseModel):
                 "updated_at": "2024-01-15T11:00:00Z",
                 "ip_address": "192.168.1.100",
                 "user_agent": "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36",
+                "legal_hold": False,
+                "is_late": True,
+                "minutes_late": 75,
+                "language_policy_result": {
+                    "status": "warning",
+                    "content_bytes": {"c": 18230, "cpp": 2950, "python": 840},
+                    "shares": {"c": 0.8607, "cpp": 0.1393},
+                    "disallowed_share": 0.1393,
+                    "disallowed_languages": ["cpp"],
+                    "ignored_languages": ["python"],
+                },
             }
         },
     )
@@ -42,11 +71,20 @@ class SubmissionResponseDto(BaseModel):
     link_type: Optional[LinkType]
     description: Optional[str]
     submitted_by_uuid: Optional[UUID]
+    team_id: Optional[UUID] = None
+    team_name: Optional[str] = None
+    team_members: Optional[List[UUID]] = None  # members of the team when the submission was uploaded
     file_size_bytes: Optional[int]
     file_count: Optional[int]
-    upload_date_time: datetime
+    upload_date_time: UtcTimestamp
     status: SubmissionStatus
-    created_at: datetime
-    updated_at: Optional[datetime]
+    created_at: UtcTimestamp
+    updated_at: Optional[UtcTimestamp]
     ip_address: Optional[str]
     user_agent: Optional[str]
+    legal_hold: bool = False
+    legal_hold_reason: Optional[str] = None
+    is_late: bool = False
+    minutes_late: Optional[int] = None
+    language_policy_result: Optional[LanguagePolicyResultDto] = None
+    storage_error: Optional[str] = None  # the files of the submission could not be stored
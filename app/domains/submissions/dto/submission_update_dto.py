@@ -1,19 +1,10 @@
-from datetime import datetime
 from typing import Optional
 from uuid import UUID
 
-import pytz
 from pydantic import BaseModel, ConfigDict, Field, field_validator
 
 from app.domains.submissions.submissions_models import SubmissionStatus
-
-# Paris timezone
-PARIS_TZ = pytz.timezone("Europe/Paris")
-
-
-def get_paris_time() -> datetime:
-    """Get current time in Paris timezone"""
-    return datetime.now(PARIS_TZ)
+from app.shared.timestamps import OffsetTimestamp, utc_now
 
 
 class SubmissionUpdateDto(BaseModel):
@@ -38,7 +29,7 @@ class SubmissionUpdateDto(BaseModel):
     submitted_by_uuid: Optional[UUID] = None
     file_size_bytes: Optional[int] = None
     file_count: Optional[int] = None
-    updated_at: datetime = Field(default_factory=get_paris_time)
+    updated_at: OffsetTimestamp = Field(default_factory=utc_now)
 
     @field_validator("description")
     def validate_description(cls, v):
@@ -0,0 +1,47 @@
+from typing import List, Optional
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict, Field, field_validator
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class TeamDto(BaseModel):
+    """DTO for creating or replacing a team of a project"""
+
+    model_config = ConfigDict(
+        json_schema_extra={
+            "example": {
+                "name": "Team Rocket",
+                "members": ["550e8400-e29b-41d4-a716-446655440005", "550e8400-e29b-41d4-a716-446655440006"],
+            }
+        }
+    )
+
+    name: str = Field(min_length=1, max_length=200, description="Name of the team")
+    members: List[UUID] = Field(min_length=1, description="UUIDs of the students of the team")
+
+    @field_validator("name")
+    def strip_name(cls, name: str) -> str:
+        """Validate that the name is not blank"""
+        if not name.strip():
+            raise ValueError("Team name cannot be blank")
+        return name.strip()
+
+    @field_validator("members")
+    def unique_members(cls, members: List[UUID]) -> List[UUID]:
+        """Members without duplicates, in the order given"""
+        return list(dict.fromkeys(members))
+
+
+class TeamResponseDto(BaseModel):
+    """DTO for reading a team"""
+
+    model_config = ConfigDict(from_attributes=True)
+
+    id: UUID
+    project_uuid: UUID
+    name: str
+    members: List[UUID]
+    created_at: UtcTimestamp
+    updated_at: Optional[UtcTimestamp] = None
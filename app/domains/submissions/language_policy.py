@@ -0,0 +1,51 @@
+"""
+Language policies of project steps
+
+A step may only allow some languages, "implement this in C, not C++". At upload, the files a detection run would
+compare, the supported ones that are not generated, are sized by language. Auxiliary languages, such as a Python
+build script of a C project, are left out, and the share of the remaining content in languages that are not
+allowed gives the result: a violation from violation_threshold, a warning from warning_threshold, compliant below.
+A submission without any content left is compliant, there is nothing to judge.
+"""
+
+from pathlib import Path
+from typing import Dict
+
+from app.domains.submissions.dto.project_step_config_dto import LanguagePolicyDto
+from app.domains.submissions.dto.submission_response_dto import LanguagePolicyResultDto
+from app.domains.submissions.submissions_models import LanguagePolicyStatus
+
+
+def content_by_language(tokenization_service, directory: Path) -> Dict[str, int]:
+    """Bytes of the files of a fetched submission a detection run would compare, by language"""
+    content: Dict[str, int] = {}
+    for file_path in tokenization_service.extract_supported_files_from_directory(directory):
+        language = tokenization_service._detect_language(file_path)
+        content[language] = content.get(language, 0) + file_path.stat().st_size
+    return content
+
+
+def evaluate_language_policy(policy: LanguagePolicyDto, content: Dict[str, int]) -> LanguagePolicyResultDto:
+    """Result of a submission against a language policy from the bytes of its content by language"""
+    judged = {language: size for language, size in content.items() if language not in policy.auxiliary_languages}
+    total = sum(judged.values())
+    disallowed = sorted(
+        language for language, size in judged.items() if size and language not in policy.allowed_languages
+    )
+    disallowed_share = sum(judged[language] for language in disallowed) / total if disallowed else 0.0
+
+    if disallowed and disallowed_share >= policy.violation_threshold:
+        status = LanguagePolicyStatus.VIOLATION
+    elif disallowed and disallowed_share >= policy.warning_threshold:
+        status = LanguagePolicyStatus.WARNING
+    else:
+        status = LanguagePolicyStatus.COMPLIANT
+
+    return LanguagePolicyResultDto(
+        status=status,
+        content_bytes=dict(sorted(content.items())),
+        shares={language: round(size / total, 4) for language, size in sorted(judged.items())} if total else {},
+        disallowed_share=round(disallowed_share, 4),
+        disallowed_languages=disallowed,
+        ignored_languages=sorted(language for language in content if language in policy.auxiliary_languages),
+    )
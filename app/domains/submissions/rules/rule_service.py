@@ -1,6 +1,6 @@
 import logging
 from pathlib import Path
-from typing import List, Optional
+from typing import Callable, List, Optional
 
 from app.domains.repositories.exceptions import (
     RepositoryFetchException,
@@ -50,12 +50,15 @@ class RuleService:
         else:
             self.submission_fetcher = submission_fetcher
 
-    def validate_submission(self, submission_data: CreateSubmissionDto) -> List[RuleExecutionResult]:
+    def validate_submission(
+        self, submission_data: CreateSubmissionDto, fetch: Optional[Callable[[], Path]] = None
+    ) -> List[RuleExecutionResult]:
         """
         Validate a submission by executing all specified rules
 
         Args:
             submission_data: The submission data containing rules to execute
+            fetch: Fetches the submission instead of the submission fetcher, its files are left to the caller
 
         Returns:
             List of rule execution results
@@ -89,7 +92,7 @@ class RuleService:
         try:
             # Fetch submission with simplified error handling
             try:
-                repo_path = self.submission_fetcher.fetch_submission(submission_data)
+                repo_path = fetch() if fetch else self.submission_fetcher.fetch_submission(submission_data)
                 logger.info(f"Successfully fetched submission for validation: {repo_path}")
             except (UnsupportedRepositoryException, RepositoryFetchException):
                 # Re-raise repository-specific exceptions
@@ -129,7 +132,7 @@ class RuleService:
             return results
 
         finally:
-            if "repo_path" in locals() and repo_path and repo_path.exists():
+            if not fetch and "repo_path" in locals() and repo_path and repo_path.exists():
                 cleanup_temp_directory(repo_path)
 
     def _execute_rule(self, rule_dto: RuleDto, repo_path: Path) -> RuleExecutionResult:
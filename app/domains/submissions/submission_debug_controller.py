@@ -0,0 +1,52 @@
+from typing import Optional
+from uuid import UUID
+
+from fastapi import APIRouter, Depends, HTTPException, Query
+
+from app.domains.audit.audit_hook import audited
+from app.domains.audit.audit_models import AuditAction
+from app.domains.fingerprints.dto.fingerprint_debug_dto import FileFingerprintDebugDto
+from app.domains.fingerprints.fingerprint_models import NormalizationLevel
+from app.domains.storage.exceptions import StorageException
+from app.domains.submissions.submissions_controller import get_submission_service
+from app.domains.submissions.submissions_service import SubmissionService
+from app.shared.exceptions import NotFoundException, ValidationException
+from app.shared.security import require_admin_scope
+
+# Included before the submissions router, whose file route would otherwise take the paths ending in /debug
+router = APIRouter(prefix="/submissions", tags=["admin"], dependencies=[Depends(require_admin_scope)])
+
+
+@router.get(
+    "/{submission_id}/files/{file_path:path}/debug",
+    response_model=FileFingerprintDebugDto,
+    dependencies=[Depends(audited(AuditAction.FILE_DOWNLOAD, "submission", "submission_id"))],
+)
+async def debug_submission_file(
+    submission_id: UUID,
+    file_path: str,
+    version: Optional[int] = Query(None, ge=1, description="Stored version, latest by default"),
+    normalization: Optional[NormalizationLevel] = Query(
+        None, description="Normalization level, that of the language or the configured one by default"
+    ),
+    k: Optional[int] = Query(None, ge=1, le=100, description="K-gram size, the configured one by default"),
+    window: Optional[int] = Query(None, ge=1, le=100, description="Winnowing window, the configured one by default"),
+    skip: int = Query(0, ge=0, description="Number of tokens to skip"),
+    limit: int = Query(1000, ge=1, le=10000, description="Number of tokens to return"),
+    service: SubmissionService = Depends(get_submission_service),
+):
+    """
+    Debug the tokenization of a stored file: its language, its tokens, their k-grams and the selected fingerprints
+
+    The file is decoded, tokenized and fingerprinted like a detection run does, without reading or writing the
+    fingerprint cache; without overrides the fingerprints are those runs cache for it. Tokens are paged with skip
+    and limit, each page holds the k-grams and fingerprints starting at one of its tokens.
+    """
+    try:
+        return service.debug_submission_file(submission_id, file_path, version, normalization, k, window, skip, limit)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except ValidationException as e:
+        raise HTTPException(status_code=422, detail=str(e))
+    except StorageException as e:
+        raise HTTPException(status_code=500, detail=str(e))
@@ -1,23 +1,37 @@
 from typing import List, Optional
 from uuid import UUID
 
-from fastapi import APIRouter, Depends, HTTPException, Query, Request
+from fastapi import APIRouter, Depends, Header, HTTPException, Query, Request
+from fastapi.responses import JSONResponse, StreamingResponse
 from sqlmodel import Session
 
+from app.domains.audit.audit_hook import audited
+from app.domains.audit.audit_models import AuditAction
+from app.domains.storage.exceptions import InvalidStorageKeyException, StorageException
 from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
 from app.domains.submissions.dto.create_submission_response_dto import CreateSubmissionResponseDto
+from app.domains.submissions.dto.project_step_config_dto import (
+    DeclaredCollaborationDto,
+    ProjectStepConfigDto,
+    ProjectStepConfigResponseDto,
+)
 from app.domains.submissions.dto.similarity_response_dto import (
     DetailedComparisonDto,
     SimilarityAlertsResponseDto,
     SimilarityListResponseDto,
     SimilarityStatisticsDto,
 )
+from app.domains.submissions.dto.start_detection_dto import DetectionPlanDto, StartDetectionResponseDto
+from app.domains.submissions.dto.submission_metrics_dto import SubmissionMetricsDto
 from app.domains.submissions.dto.submission_response_dto import SubmissionResponseDto
 from app.domains.submissions.dto.submission_update_dto import SubmissionUpdateDto
+from app.domains.submissions.dto.team_dto import TeamDto, TeamResponseDto
 from app.domains.submissions.submissions_service import SubmissionService
 from app.domains.submissions.submissions_similarity_repository import SubmissionSimilarityRepository
+from app.shared.concurrency import JobPriority
 from app.shared.database import get_session
-from app.shared.exceptions import DatabaseException, NotFoundException, ValidationException
+from app.shared.exceptions import DatabaseException, NotFoundException, QuotaExceededException, ValidationException
+from app.shared.security import get_client_info, require_admin_scope
 
 router = APIRouter(prefix="/submissions", tags=["submissions"])
 
@@ -27,24 +41,22 @@ def get_submission_service(session: Session = Depends(get_session)) -> Submissio
     return SubmissionService(session)
 
 
-def get_client_info(request: Request) -> tuple[Optional[str], Optional[str]]:
-    """Extract client IP and user agent from request"""
-    # Get real IP address considering reverse proxy headers
-    forwarded_for = request.headers.get("X-Forwarded-For")
-    if forwarded_for:
-        ip_address = forwarded_for.split(",")[0].strip()
-    else:
-        ip_address = request.client.host if request.client else None
-
-    user_agent = request.headers.get("User-Agent")
-    return ip_address, user_agent
-
-
-@router.post("", response_model=CreateSubmissionResponseDto, status_code=201)
+@router.post(
+    "",
+    response_model=CreateSubmissionResponseDto,
+    status_code=201,
+    responses={
+        403: {"description": "The project keeps its quota of submissions or stored bytes already"},
+        404: {"description": "The project belongs to another tenant than the one of the request"},
+    },
+)
 async def create_submission(
     submission_data: CreateSubmissionDto,
     request: Request,
     allow_duplicates: bool = Query(False, description="Allow duplicate submissions"),
+    profile: bool = Query(False, description="Record per-stage timings of the detection run"),
+    priority: JobPriority = Query(JobPriority.NORMAL, description="Priority of the detection run, urgent needs admin"),
+    authorization: Optional[str] = Header(None),
     service: SubmissionService = Depends(get_submission_service),
 ):
     """
@@ -57,11 +69,18 @@ async def create_submission(
     - **upload_date_time**: Upload timestamp (optional, defaults to current time)
     - **description**: Optional description of the submission
     - **submitted_by_uuid**: UUID of the submitter (optional)
+    - **team_id**: ID of the team making the submission, the submitter must be a member (optional)
     - **file_size_bytes**: Size of the submission in bytes
     - **file_count**: Number of files in the submission
     - **rules**: List of validation rules to execute (optional)
     - **force_rules**: If True, submission is created even if validation rules fail (optional, defaults to False)
+
+    With **profile**, the detection run started by the submission records its per-stage timings in its profile.
+    The detection run is queued with **priority** (`low`, `normal`, `high`, or `urgent` with the admin scope).
     """
+    if priority == JobPriority.URGENT:
+        require_admin_scope(authorization)
+
     try:
         ip_address, user_agent = get_client_info(request)
 
@@ -70,6 +89,8 @@ async def create_submission(
             ip_address=ip_address,
             user_agent=user_agent,
             allow_duplicates=allow_duplicates,
+            profile=profile,
+            priority=priority,
         )
     except ValidationException as e:
         # Return structured error details if available
@@ -77,6 +98,10 @@ async def create_submission(
             raise HTTPException(status_code=422, detail=e.detail)
         else:
             raise HTTPException(status_code=422, detail=str(e.detail))
+    except QuotaExceededException as e:
+        raise HTTPException(status_code=e.status_code, detail=e.detail)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
     except DatabaseException as e:
         raise HTTPException(status_code=500, detail=str(e))
     except Exception as e:
@@ -94,6 +119,17 @@ async def get_submission(submission_id: UUID, service: SubmissionService = Depen
         raise HTTPException(status_code=500, detail=str(e))
 
 
+@router.get("/{submission_id}/metrics", response_model=SubmissionMetricsDto)
+async def get_submission_metrics(submission_id: UUID, service: SubmissionService = Depends(get_submission_service)):
+    """Get the code metrics of a submission, computed when its files are stored"""
+    try:
+        return service.get_submission_metrics(submission_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
 @router.get(
     "/project/{project_uuid}/group/{group_uuid}/step/{project_step_uuid}", response_model=CreateSubmissionResponseDto
 )
@@ -116,11 +152,12 @@ async def get_submission_by_project_group_step(
 async def list_submissions(
     skip: int = Query(0, ge=0, description="Number of submissions to skip"),
     limit: int = Query(100, ge=1, le=1000, description="Maximum number of submissions to return"),
+    member: Optional[UUID] = Query(None, description="Only the submissions of this student, alone or in a team"),
     service: SubmissionService = Depends(get_submission_service),
 ):
     """List all submissions with pagination"""
     try:
-        return service.list_submissions(skip=skip, limit=limit)
+        return service.list_submissions(skip=skip, limit=limit, member=member)
     except DatabaseException as e:
         raise HTTPException(status_code=500, detail=str(e))
 
@@ -147,6 +184,122 @@ async def get_submissions_by_project_step(
         raise HTTPException(status_code=500, detail=str(e))
 
 
+@router.get("/project/{project_uuid}/step/{project_step_uuid}/config", response_model=ProjectStepConfigResponseDto)
+async def get_project_step_config(
+    project_uuid: UUID, project_step_uuid: UUID, service: SubmissionService = Depends(get_submission_service)
+):
+    """Get the configuration of a project step, with its deadline"""
+    try:
+        return service.get_step_config(project_uuid, project_step_uuid)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put(
+    "/project/{project_uuid}/step/{project_step_uuid}/config",
+    response_model=ProjectStepConfigResponseDto,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "project_step", "project_step_uuid"))],
+)
+async def set_project_step_config(
+    project_uuid: UUID,
+    project_step_uuid: UUID,
+    config_data: ProjectStepConfigDto,
+    service: SubmissionService = Depends(get_submission_service),
+):
+    """
+    Create or replace the configuration of a project step
+
+    - **deadline**: Deadline of the submissions, with an explicit offset (none if omitted). Submissions of the step
+      uploaded after it are marked late, existing ones included
+    - **language_policy**: Languages the submissions are allowed in (any if omitted). Uploads are sized by language
+      and get a compliant, warning or violation result, violations are refused with enforce=reject
+    """
+    try:
+        return service.set_step_config(project_uuid, project_step_uuid, config_data)
+    except ValidationException as e:
+        raise HTTPException(status_code=422, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put(
+    "/project/{project_uuid}/step/{project_step_uuid}/collaborations",
+    response_model=ProjectStepConfigResponseDto,
+    dependencies=[Depends(audited(AuditAction.CONFIG_CHANGE, "project_step", "project_step_uuid"))],
+)
+async def set_declared_collaborations(
+    project_uuid: UUID,
+    project_step_uuid: UUID,
+    declarations: List[DeclaredCollaborationDto],
+    service: SubmissionService = Depends(get_submission_service),
+):
+    """
+    Replace the declared collaborations of a project step, the pairs of parties allowed to share code
+
+    Each party is student:<uuid>, team:<uuid> or group:<uuid> (every student of the group), with * for any of a
+    kind or anyone. Pairs of runs started from now on matching a declaration are reported apart and left out of the
+    statistics and summaries, existing runs are classified again with POST /runs/{run_id}/reclassify.
+    """
+    try:
+        return service.set_declared_collaborations(project_uuid, project_step_uuid, declarations)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/project/{project_uuid}/teams", response_model=TeamResponseDto, status_code=201)
+async def create_team(
+    project_uuid: UUID, team_data: TeamDto, service: SubmissionService = Depends(get_submission_service)
+):
+    """
+    Create a team of a project, for the submissions its members make together
+
+    Submissions naming the team with team_id record its name and members as they are at upload, and detection
+    treats pairs of submissions whose teams share a member as self-matches.
+    """
+    try:
+        return service.create_team(project_uuid, team_data)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/project/{project_uuid}/teams", response_model=List[TeamResponseDto])
+async def list_teams(project_uuid: UUID, service: SubmissionService = Depends(get_submission_service)):
+    """Get the teams of a project"""
+    try:
+        return service.list_teams(project_uuid)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/project/{project_uuid}/teams/{team_id}", response_model=TeamResponseDto)
+async def get_team(project_uuid: UUID, team_id: UUID, service: SubmissionService = Depends(get_submission_service)):
+    """Get a team of a project"""
+    try:
+        return service.get_team(project_uuid, team_id)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put("/project/{project_uuid}/teams/{team_id}", response_model=TeamResponseDto)
+async def update_team(
+    project_uuid: UUID,
+    team_id: UUID,
+    team_data: TeamDto,
+    service: SubmissionService = Depends(get_submission_service),
+):
+    """Replace the name and members of a team, the submissions it already made keep the members they had"""
+    try:
+        return service.update_team(project_uuid, team_id, team_data)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
 @router.get("/project/{project_uuid}/group/{group_uuid}/statistics")
 async def get_submission_statistics(
     project_uuid: UUID, group_uuid: UUID, service: SubmissionService = Depends(get_submission_service)
@@ -175,7 +328,11 @@ async def update_submission(
         raise HTTPException(status_code=500, detail=str(e))
 
 
-@router.delete("/{submission_id}", response_model=CreateSubmissionResponseDto)
+@router.delete(
+    "/{submission_id}",
+    response_model=CreateSubmissionResponseDto,
+    dependencies=[Depends(audited(AuditAction.SUBMISSION_DELETE, "submission", "submission_id"))],
+)
 async def delete_submission(
     submission_id: UUID,
     service: SubmissionService = Depends(get_submission_service),
@@ -197,6 +354,103 @@ async def delete_submission(
         raise HTTPException(status_code=500, detail=str(e))
 
 
+@router.post(
+    "/{submission_id}/detection",
+    response_model=StartDetectionResponseDto,
+    status_code=202,
+    responses={
+        200: {"model": DetectionPlanDto, "description": "Plan of the run, with dry_run"},
+        403: {"description": "The run would compare more submissions than the quota of the project allows"},
+        429: {"description": "The project started its quota of detection runs over the last 24 hours"},
+    },
+)
+async def start_submission_detection(
+    submission_id: UUID,
+    profile: bool = Query(False, description="Record per-stage timings of the detection run"),
+    priority: JobPriority = Query(JobPriority.NORMAL, description="Priority of the detection run, urgent needs admin"),
+    dry_run: bool = Query(False, description="Return the plan of the run instead of queuing it"),
+    k: Optional[int] = Query(None, ge=1, description="Tokens per k-gram of the run, FINGERPRINT_K by default"),
+    window: Optional[int] = Query(None, ge=1, description="Winnowing window of the run, FINGERPRINT_WINDOW by default"),
+    auto_tune: Optional[bool] = Query(
+        None, description="Tune k and window left unset from the corpus of the run, DETECTION_AUTO_TUNE by default"
+    ),
+    authorization: Optional[str] = Header(None),
+    service: SubmissionService = Depends(get_submission_service),
+):
+    """
+    Queue a new detection run of a submission against the submissions of the other groups of its step
+
+    Returns 409 when the step has no other submission to compare it with, 403 or 429 with the quota and its usage
+    when the run would exceed a quota of the project. With dry_run, nothing is queued, tokenized or written: the
+    plan of the run is returned with 200, its submissions, the files each would tokenize by language and those
+    left out with why, its pairs, an estimated duration from the recent runs and warnings for what would make it
+    useless, such as a submission without supported file. k and window set the fingerprinting parameters of the
+    run, auto-tuning only chooses those left unset; a dry run resolves them the same way and returns them in the
+    parameters of the plan.
+    """
+    if priority == JobPriority.URGENT:
+        require_admin_scope(authorization)
+
+    if dry_run:
+        try:
+            plan = service.plan_detection(submission_id, k, window, auto_tune)
+        except NotFoundException as e:
+            raise HTTPException(status_code=404, detail=str(e))
+        except DatabaseException as e:
+            raise HTTPException(status_code=500, detail=str(e))
+        return JSONResponse(status_code=200, content=plan.model_dump(mode="json"))
+
+    try:
+        run_id = service.start_detection(submission_id, profile, priority, k, window, auto_tune)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except QuotaExceededException as e:
+        raise HTTPException(status_code=e.status_code, detail=e.detail)
+    except DatabaseException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+    if run_id is None:
+        raise HTTPException(status_code=409, detail=f"Submission {submission_id} has no submission to compare with")
+    return StartDetectionResponseDto(submission_id=submission_id, run_id=run_id, message="Detection run queued")
+
+
+@router.get("/{submission_id}/files")
+async def list_submission_files(
+    submission_id: UUID,
+    version: Optional[int] = Query(None, ge=1, description="Stored version, latest by default"),
+    service: SubmissionService = Depends(get_submission_service),
+):
+    """List the files stored for a submission"""
+    try:
+        return service.list_submission_files(submission_id, version)
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except StorageException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get(
+    "/{submission_id}/files/{file_path:path}",
+    dependencies=[Depends(audited(AuditAction.FILE_DOWNLOAD, "submission", "submission_id"))],
+)
+async def get_submission_file_content(
+    submission_id: UUID,
+    file_path: str,
+    version: Optional[int] = Query(None, ge=1, description="Stored version, latest by default"),
+    service: SubmissionService = Depends(get_submission_service),
+):
+    """Stream the content of a stored submission file"""
+    try:
+        content = service.stream_submission_file(submission_id, file_path, version)
+        return StreamingResponse(content, media_type="application/octet-stream")
+    except NotFoundException as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except InvalidStorageKeyException as e:
+        raise HTTPException(status_code=422, detail=str(e))
+    except StorageException as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
 @router.get("/rules/documentation")
 async def get_rules_documentation():
     """Get detailed documentation for all available validation rules"""
@@ -402,7 +656,11 @@ async def get_submission_similarities(
         raise HTTPException(status_code=500, detail=str(e))
 
 
-@router.get("/similarities/{similarity_id}/detailed", response_model=DetailedComparisonDto)
+@router.get(
+    "/similarities/{similarity_id}/detailed",
+    response_model=DetailedComparisonDto,
+    dependencies=[Depends(audited(AuditAction.FRAGMENT_VIEW, "similarity", "similarity_id"))],
+)
 async def get_detailed_comparison(similarity_id: UUID, service: SubmissionService = Depends(get_submission_service)):
     """Get detailed comparison results including visualization data"""
     try:
@@ -3,17 +3,11 @@ from enum import Enum
 from typing import Optional
 from uuid import UUID, uuid4
 
-import pytz
 from pydantic import field_validator
+from sqlalchemy import BigInteger
 from sqlmodel import JSON, Column, Field, SQLModel
 
-# Paris timezone
-PARIS_TZ = pytz.timezone("Europe/Paris")
-
-
-def get_paris_time() -> datetime:
-    """Get current time in Paris timezone"""
-    return datetime.now(PARIS_TZ)
+from app.shared.timestamps import UtcDateTime, utc_now
 
 
 class SubmissionStatus(str, Enum):
@@ -34,6 +28,21 @@ class LinkType(str, Enum):
     GITLAB = "gitlab"
 
 
+class LanguageEnforcement(str, Enum):
+    """What a language policy does with violating submissions"""
+
+    FLAG = "flag"  # created with their policy result
+    REJECT = "reject"  # refused at upload
+
+
+class LanguagePolicyStatus(str, Enum):
+    """Result of a submission against the language policy of its step"""
+
+    COMPLIANT = "compliant"
+    WARNING = "warning"
+    VIOLATION = "violation"
+
+
 class SimilarityStatus(str, Enum):
     """Enumeration for similarity detection status"""
 
@@ -41,6 +50,9 @@ class SimilarityStatus(str, Enum):
     PROCESSING = "processing"
     COMPLETED = "completed"
     FAILED = "failed"
+    PRUNED = "pruned"  # not compared in detail, its similarity bound was below the reporting threshold
+    NOT_COMPARABLE = "not_comparable"  # not scored, a submission has no comparable token
+    TIMED_OUT = "timed_out"  # not scored, stopped by the pair or the run timeout
 
 
 class SubmissionBase(SQLModel):
@@ -57,6 +69,7 @@ class SubmissionBase(SQLModel):
         default=None, max_length=1000, description="Optional description of the submission"
     )
     submitted_by_uuid: Optional[UUID] = Field(default=None, description="UUID of the submitter")
+    team_id: Optional[UUID] = Field(default=None, index=True, description="ID of the team submitting, None for none")
     file_size_bytes: Optional[int] = Field(default=None, ge=0, description="Size of the submission in bytes")
     file_count: Optional[int] = Field(default=None, ge=0, description="Number of files in the submission")
 
@@ -84,10 +97,20 @@ class Submission(SubmissionBase, table=True):
     __tablename__ = "submission"
 
     id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
-    upload_date_time: datetime = Field(default_factory=get_paris_time, description="When the submission was uploaded")
+    upload_date_time: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the submission was uploaded",
+    )
     status: SubmissionStatus = Field(default=SubmissionStatus.PENDING, description="Current status of the submission")
-    created_at: datetime = Field(default_factory=get_paris_time, description="When the record was created")
-    updated_at: Optional[datetime] = Field(default=None, description="When the record was last updated")
+    created_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the record was created",
+    )
+    updated_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When the record was last updated"
+    )
 
     # Metadata fields
     ip_address: Optional[str] = Field(default=None, max_length=45, description="IP address of the submitter")
@@ -96,6 +119,96 @@ class Submission(SubmissionBase, table=True):
     # Rule validation results
     rule_results: Optional[str] = Field(default=None, description="JSON string containing rule validation results")
 
+    # Retention
+    legal_hold: bool = Field(default=False, description="Exempts the submission from retention purges")
+    legal_hold_reason: Optional[str] = Field(default=None, max_length=500, description="Reason of the legal hold")
+
+    # Lateness against the deadline of the project step when the submission was created or the deadline last set
+    is_late: bool = Field(default=False, description="Whether the submission was uploaded after the deadline")
+    minutes_late: Optional[int] = Field(default=None, description="Minutes after the deadline, None when on time")
+
+    # Share of its content in each language against the language policy of its step when it was uploaded
+    language_policy_result: Optional[dict] = Field(
+        default=None, sa_column=Column(JSON), description="Language policy result, None without policy"
+    )
+
+    # Team as it was when the submission was uploaded, later membership changes do not apply to it
+    team_name: Optional[str] = Field(default=None, max_length=200, description="Name of the team, None without team")
+    team_members: Optional[list] = Field(
+        default=None, sa_column=Column(JSON), description="UUIDs of the members of the team, None without team"
+    )
+
+    # Counted against the stored bytes quota of the project, released when the submission is deleted
+    stored_bytes: int = Field(
+        default=0,
+        sa_column=Column(BigInteger(), nullable=False, default=0),
+        description="Bytes of the files stored for the submission",
+    )
+    storage_error: Optional[str] = Field(default=None, description="Why its files could not be stored, None if stored")
+
+
+class Team(SQLModel, table=True):
+    """Database model for a team of students submitting together for a project"""
+
+    __tablename__ = "team"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    project_uuid: UUID = Field(index=True, description="UUID of the project")
+    name: str = Field(max_length=200, description="Name of the team")
+    members: list = Field(default_factory=list, sa_column=Column(JSON, nullable=False), description="Member UUIDs")
+
+    created_at: datetime = Field(
+        default_factory=utc_now, sa_column=Column(UtcDateTime(), nullable=False), description="When it was created"
+    )
+    updated_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When it was last updated"
+    )
+
+
+class ProjectStepConfig(SQLModel, table=True):
+    """Database model for the configuration of a project step"""
+
+    __tablename__ = "project_step_config"
+
+    id: Optional[UUID] = Field(default_factory=uuid4, primary_key=True)
+    project_uuid: UUID = Field(index=True, description="UUID of the project")
+    project_step_uuid: UUID = Field(index=True, unique=True, description="UUID of the project step")
+    deadline: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="Deadline of the submissions, None for none"
+    )
+    language_policy: Optional[dict] = Field(
+        default=None, sa_column=Column(JSON), description="Languages the submissions are allowed in, None for any"
+    )
+    declared_collaborations: Optional[list] = Field(
+        default=None, sa_column=Column(JSON), description="Pairs of students, teams or groups allowed to share code"
+    )
+
+    created_at: datetime = Field(
+        default_factory=utc_now, sa_column=Column(UtcDateTime(), nullable=False), description="When it was created"
+    )
+    updated_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When it was last updated"
+    )
+
+
+class SubmissionMetrics(SQLModel, table=True):
+    """Database model for the code metrics of a submission, computed when its files are stored"""
+
+    __tablename__ = "submission_metrics"
+
+    submission_id: UUID = Field(foreign_key="submission.id", primary_key=True, description="ID of the submission")
+    project_uuid: UUID = Field(index=True, description="UUID of the project")
+    metrics_version: str = Field(max_length=20, description="Version of the metric definitions they were computed with")
+    content_version: Optional[int] = Field(
+        default=None, description="Stored version of the files they were computed from, None for the fetched link"
+    )
+    metrics: dict = Field(
+        default_factory=dict, sa_column=Column(JSON, nullable=False), description="Totals and metrics per language"
+    )
+    computed_at: datetime = Field(
+        default_factory=utc_now, sa_column=Column(UtcDateTime(), nullable=False), description="When they were computed"
+    )
+
 
 class SubmissionSimilarity(SQLModel, table=True):
     """Database model for storing similarity detection results between submissions"""
@@ -144,8 +257,14 @@ class SubmissionSimilarity(SQLModel, table=True):
 
     # Status and timing
     status: SimilarityStatus = Field(default=SimilarityStatus.PENDING, description="Status of the similarity detection")
-    created_at: datetime = Field(default_factory=get_paris_time, description="When the similarity analysis was created")
-    updated_at: Optional[datetime] = Field(default=None, description="When the similarity analysis was last updated")
+    created_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the similarity analysis was created",
+    )
+    updated_at: Optional[datetime] = Field(
+        default=None, sa_column=Column(UtcDateTime()), description="When the similarity analysis was last updated"
+    )
     processing_time_seconds: Optional[float] = Field(
         default=None, description="Time taken to process the similarity analysis"
     )
@@ -1,22 +1,19 @@
-from datetime import datetime, timezone
-from typing import List, Optional
+from datetime import datetime
+from typing import Dict, List, Optional
 from uuid import UUID
 
-import pytz
+from sqlalchemy import String, cast, delete, or_
 from sqlmodel import Session, select
 
+from app.domains.events.events import record_event, submission_created, submission_deleted
+from app.domains.quotas.quota_repository import record_usage
+from app.domains.submissions.deadlines import minutes_late
 from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
 from app.domains.submissions.dto.submission_update_dto import SubmissionUpdateDto
-from app.domains.submissions.submissions_models import LinkType, Submission
+from app.domains.submissions.submissions_models import LinkType, ProjectStepConfig, Submission, SubmissionMetrics, Team
 from app.shared.exceptions import DatabaseException, NotFoundException
-
-# Paris timezone
-PARIS_TZ = pytz.timezone("Europe/Paris")
-
-
-def get_paris_time() -> datetime:
-    """Get current time in Paris timezone"""
-    return datetime.now(PARIS_TZ)
+from app.shared.tenancy import require_project_access
+from app.shared.timestamps import utc_now
 
 
 class SubmissionRepository:
@@ -31,11 +28,19 @@ class SubmissionRepository:
         ip_address: Optional[str] = None,
         user_agent: Optional[str] = None,
         rule_results_json: Optional[str] = None,
+        deadline: Optional[datetime] = None,
+        language_policy_result: Optional[dict] = None,
+        team: Optional[Team] = None,
     ) -> Submission:
-        """Create a new submission"""
+        """
+        Create a new submission, marked as late if uploaded after the deadline of its step, with the name and
+        members of its team as they are now
+        """
+        require_project_access(submission_data.project_uuid)
         try:
-            # Set upload_date_time to current time in Paris timezone if not provided
-            upload_time = submission_data.upload_date_time or get_paris_time()
+            # Set upload_date_time to the current time if not provided
+            upload_time = submission_data.upload_date_time or utc_now()
+            late = minutes_late(upload_time, deadline)
 
             # Use provided link_type from DTO, or determine it automatically if not provided
             link_type = submission_data.link_type
@@ -58,6 +63,11 @@ class SubmissionRepository:
                     "link_type": link_type,
                     "ip_address": ip_address,
                     "user_agent": user_agent,
+                    "is_late": late is not None,
+                    "minutes_late": late,
+                    "language_policy_result": language_policy_result,
+                    "team_name": team.name if team else None,
+                    "team_members": [str(member) for member in team.members] if team else None,
                 }
             )
 
@@ -67,6 +77,8 @@ class SubmissionRepository:
             submission = Submission(**submission_dict)
 
             self.session.add(submission)
+            record_event(self.session, submission_created, submission)
+            record_usage(self.session, submission.project_uuid, submissions=1)
             self.session.commit()
             self.session.refresh(submission)
             return submission
@@ -131,10 +143,11 @@ class SubmissionRepository:
             for field, value in update_dict.items():
                 setattr(submission, field, value)
 
-            # Always update the updated_at timestamp with Paris timezone
-            submission.updated_at = get_paris_time()
+            # Always update the updated_at timestamp
+            submission.updated_at = utc_now()
 
             self.session.add(submission)
+            record_event(self.session, submission_created, submission)
             self.session.commit()
             self.session.refresh(submission)
             return submission
@@ -152,7 +165,12 @@ class SubmissionRepository:
             if not submission:
                 raise NotFoundException(f"Submission with ID {submission_id} not found")
 
+            self.session.execute(delete(SubmissionMetrics).where(SubmissionMetrics.submission_id == submission_id))
             self.session.delete(submission)
+            record_event(self.session, submission_deleted, submission)
+            record_usage(
+                self.session, submission.project_uuid, submissions=-1, stored_bytes=-(submission.stored_bytes or 0)
+            )
             self.session.commit()
             return True
 
@@ -162,10 +180,50 @@ class SubmissionRepository:
             self.session.rollback()
             raise DatabaseException(f"Failed to delete submission: {str(e)}")
 
-    def list_all(self, skip: int = 0, limit: int = 100) -> List[Submission]:
-        """List all submissions with pagination"""
+    def add_stored_bytes(self, submission_id: UUID, stored_bytes: int) -> Optional[Submission]:
+        """Count bytes stored for a submission, in its total and the usage of its project in one transaction"""
+        try:
+            submission = self.get_by_id(submission_id)
+            if not submission:
+                return None
+            submission.stored_bytes = (submission.stored_bytes or 0) + stored_bytes
+            self.session.add(submission)
+            record_usage(self.session, submission.project_uuid, stored_bytes=stored_bytes)
+            self.session.commit()
+            self.session.refresh(submission)
+            return submission
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to count stored bytes of submission: {str(e)}")
+
+    def set_storage_error(self, submission_id: UUID, error: str) -> Optional[Submission]:
+        """Flag a submission whose files could not be stored with the reason"""
+        try:
+            submission = self.get_by_id(submission_id)
+            if not submission:
+                return None
+            submission.storage_error = error
+            self.session.add(submission)
+            self.session.commit()
+            self.session.refresh(submission)
+            return submission
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to flag the storage error of submission: {str(e)}")
+
+    def list_all(self, skip: int = 0, limit: int = 100, member: Optional[UUID] = None) -> List[Submission]:
+        """List all submissions with pagination, only those submitted by a student or their team with member"""
         try:
-            statement = select(Submission).order_by(Submission.upload_date_time.desc()).offset(skip).limit(limit)
+            statement = select(Submission)
+            if member is not None:
+                # UUIDs are stored in their canonical form, with nothing a LIKE pattern would interpret
+                statement = statement.where(
+                    or_(
+                        Submission.submitted_by_uuid == member,
+                        cast(Submission.team_members, String).like(f"%{member}%"),
+                    )
+                )
+            statement = statement.order_by(Submission.upload_date_time.desc()).offset(skip).limit(limit)
             return list(self.session.exec(statement).all())
         except Exception as e:
             raise DatabaseException(f"Failed to list submissions: {str(e)}")
@@ -192,3 +250,142 @@ class SubmissionRepository:
             return existing is not None
         except Exception as e:
             raise DatabaseException(f"Failed to check for duplicate submission: {str(e)}")
+
+    def get_step_config(self, project_step_uuid: UUID) -> Optional[ProjectStepConfig]:
+        """Get the configuration of a project step"""
+        try:
+            statement = select(ProjectStepConfig).where(ProjectStepConfig.project_step_uuid == project_step_uuid)
+            return self.session.exec(statement).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get project step configuration: {str(e)}")
+
+    def upsert_step_config(self, project_uuid: UUID, project_step_uuid: UUID, config_data: dict) -> ProjectStepConfig:
+        """Create or replace the configuration of a project step"""
+        require_project_access(project_uuid)
+        try:
+            config = self.get_step_config(project_step_uuid)
+            if config is None:
+                config = ProjectStepConfig(
+                    project_uuid=project_uuid, project_step_uuid=project_step_uuid, **config_data
+                )
+            else:
+                for field, value in config_data.items():
+                    setattr(config, field, value)
+                config.updated_at = utc_now()
+
+            self.session.add(config)
+            self.session.commit()
+            self.session.refresh(config)
+            return config
+        except DatabaseException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save project step configuration: {str(e)}")
+
+    def get_metrics(self, submission_id: UUID) -> Optional[SubmissionMetrics]:
+        """Get the code metrics of a submission, None until they were computed"""
+        try:
+            return self.session.get(SubmissionMetrics, submission_id)
+        except Exception as e:
+            raise DatabaseException(f"Failed to get submission metrics: {str(e)}")
+
+    def get_metrics_by_submissions(self, submission_ids: List[UUID]) -> Dict[UUID, SubmissionMetrics]:
+        """Get the code metrics of submissions by submission ID, those not computed yet left out"""
+        if not submission_ids:
+            return {}
+        try:
+            statement = select(SubmissionMetrics).where(SubmissionMetrics.submission_id.in_(submission_ids))
+            return {metrics.submission_id: metrics for metrics in self.session.exec(statement).all()}
+        except Exception as e:
+            raise DatabaseException(f"Failed to get submission metrics: {str(e)}")
+
+    def save_metrics(
+        self, submission: Submission, metrics_version: str, metrics: dict, content_version: Optional[int] = None
+    ) -> SubmissionMetrics:
+        """Create or replace the code metrics of a submission, computed from a stored version or the fetched link"""
+        require_project_access(submission.project_uuid)
+        try:
+            record = self.get_metrics(submission.id)
+            if record is None:
+                record = SubmissionMetrics(submission_id=submission.id, project_uuid=submission.project_uuid)
+            record.metrics_version = metrics_version
+            record.content_version = content_version
+            record.metrics = metrics
+            record.computed_at = utc_now()
+
+            self.session.add(record)
+            self.session.commit()
+            self.session.refresh(record)
+            return record
+        except DatabaseException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to save submission metrics: {str(e)}")
+
+    def create_team(self, project_uuid: UUID, name: str, members: List[UUID]) -> Team:
+        """Create a team of a project"""
+        require_project_access(project_uuid)
+        try:
+            team = Team(project_uuid=project_uuid, name=name, members=[str(member) for member in members])
+            self.session.add(team)
+            self.session.commit()
+            self.session.refresh(team)
+            return team
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to create team: {str(e)}")
+
+    def get_team(self, team_id: UUID) -> Optional[Team]:
+        """Get a team by ID"""
+        try:
+            return self.session.exec(select(Team).where(Team.id == team_id)).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get team: {str(e)}")
+
+    def get_teams_by_project(self, project_uuid: UUID) -> List[Team]:
+        """Get the teams of a project by name"""
+        try:
+            statement = select(Team).where(Team.project_uuid == project_uuid).order_by(Team.name)
+            return list(self.session.exec(statement).all())
+        except Exception as e:
+            raise DatabaseException(f"Failed to get teams: {str(e)}")
+
+    def update_team(self, team_id: UUID, name: str, members: List[UUID]) -> Team:
+        """Replace the name and members of a team, the submissions it made keep those they were uploaded with"""
+        try:
+            team = self.get_team(team_id)
+            if not team:
+                raise NotFoundException(f"Team with ID {team_id} not found")
+            team.name = name
+            team.members = [str(member) for member in members]
+            team.updated_at = utc_now()
+            self.session.add(team)
+            self.session.commit()
+            self.session.refresh(team)
+            return team
+        except (NotFoundException, DatabaseException):
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to update team: {str(e)}")
+
+    def mark_lateness(self, project_uuid: UUID, project_step_uuid: UUID, deadline: Optional[datetime]) -> int:
+        """Mark the submissions of a step as late or on time against a deadline, returns how many are late"""
+        try:
+            late_count = 0
+            for submission in self.get_by_project_step(project_uuid, project_step_uuid):
+                late = minutes_late(submission.upload_date_time, deadline)
+                late_count += late is not None
+                if (submission.is_late, submission.minutes_late) != (late is not None, late):
+                    submission.is_late = late is not None
+                    submission.minutes_late = late
+                    self.session.add(submission)
+            self.session.commit()
+            return late_count
+        except DatabaseException:
+            raise
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to mark late submissions: {str(e)}")
@@ -1,30 +1,72 @@
 import json
 import logging
+from pathlib import Path
 from typing import List, Optional
 from uuid import UUID
 
 from sqlmodel import Session
 
+from app.config.config import get_settings
+from app.domains.fingerprints.dto.fingerprint_debug_dto import FileFingerprintDebugDto
+from app.domains.fingerprints.fingerprint_debug import trace_page
+from app.domains.fingerprints.fingerprint_models import NormalizationLevel
+from app.domains.quotas.quota_service import QuotaService
+from app.domains.repositories.exceptions import MalwareDetectedException, MalwareScannerUnavailableException
+from app.domains.repositories.submission_fetcher import FetchedSubmission
+from app.domains.runs.runs_models import DetectionRunTrigger
+from app.domains.storage.submission_storage_service import SubmissionStorageService
 from app.domains.submissions.detection_integration_service import DetectionIntegrationService
 from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
 from app.domains.submissions.dto.create_submission_response_dto import CreateSubmissionResponseDto
+from app.domains.submissions.dto.project_step_config_dto import (
+    DeclaredCollaborationDto,
+    LanguagePolicyDto,
+    ProjectStepConfigDto,
+    ProjectStepConfigResponseDto,
+)
+from app.domains.submissions.dto.start_detection_dto import DetectionPlanDto
+from app.domains.submissions.dto.submission_metrics_dto import SubmissionMetricsDto
 from app.domains.submissions.dto.submission_response_dto import SubmissionResponseDto
 from app.domains.submissions.dto.submission_update_dto import SubmissionUpdateDto
+from app.domains.submissions.dto.team_dto import TeamDto, TeamResponseDto
+from app.domains.submissions.language_policy import content_by_language, evaluate_language_policy
 from app.domains.submissions.rules.rule_service import RuleService
-from app.domains.submissions.submissions_models import LinkType, SubmissionStatus
+from app.domains.submissions.submissions_models import (
+    LanguageEnforcement,
+    LanguagePolicyStatus,
+    LinkType,
+    ProjectStepConfig,
+    Submission,
+    SubmissionStatus,
+    Team,
+)
 from app.domains.submissions.submissions_repository import SubmissionRepository
-from app.shared.exceptions import NotFoundException, ValidationException
+from app.domains.tokenization.streaming_source import decode_source
+from app.shared.concurrency import JobPriority
+from app.shared.exceptions import NotFoundException, QuotaExceededException, ValidationException
+from app.shared.metrics import SUBMISSION_REJECTIONS, SUBMISSIONS_CREATED
+from app.shared.services import get_ingestion_scheduler
+from app.shared.tenancy import require_project_access
+from app.shared.timestamps import to_rfc3339
 
 logger = logging.getLogger(__name__)
 
 
+def rejected(reason: str, message: str, details: Optional[dict] = None) -> ValidationException:
+    """Exception refusing a submission, counted by reason"""
+    SUBMISSION_REJECTIONS.labels(reason).inc()
+    return ValidationException(message, details=details)
+
+
 class SubmissionService:
     """Service for submission business logic"""
 
     def __init__(self, session: Session):
         self.repository = SubmissionRepository(session)
         self.rule_service = RuleService()
-        self.detection_service = DetectionIntegrationService(session)
+        self.storage_service = SubmissionStorageService()
+        self.detection_service = DetectionIntegrationService(session, storage_service=self.storage_service)
+        self.quota_service = QuotaService(session)
 
     def create_submission(
         self,
@@ -32,8 +74,27 @@ class SubmissionService:
         ip_address: Optional[str] = None,
         user_agent: Optional[str] = None,
         allow_duplicates: bool = False,
+        profile: bool = False,
+        priority: JobPriority = JobPriority.NORMAL,
+    ) -> CreateSubmissionResponseDto:
+        """Create a new submission with business logic validation, fetching its files at most once"""
+        with FetchedSubmission(self.rule_service.submission_fetcher, submission_data) as files:
+            return self._create_submission(
+                files, submission_data, ip_address, user_agent, allow_duplicates, profile, priority
+            )
+
+    def _create_submission(
+        self,
+        files: FetchedSubmission,
+        submission_data: CreateSubmissionDto,
+        ip_address: Optional[str],
+        user_agent: Optional[str],
+        allow_duplicates: bool,
+        profile: bool,
+        priority: JobPriority,
     ) -> CreateSubmissionResponseDto:
-        """Create a new submission with business logic validation"""
+        # A project of another tenant is not found, before anything is fetched for it
+        require_project_access(submission_data.project_uuid)
 
         # Check for duplicate submissions if not allowed
         if not allow_duplicates:
@@ -44,10 +105,11 @@ class SubmissionService:
             )
 
             if is_duplicate:
-                raise ValidationException("A submission with the same project, group and step already exists")
+                raise rejected("duplicate", "A submission with the same project, group and step already exists")
 
         # Validate business rules
         self._validate_submission_data(submission_data)
+        team = self._get_submission_team(submission_data)
 
         if not submission_data.link_type:
             # Determine link type based on the link
@@ -59,12 +121,15 @@ class SubmissionService:
             elif "gitlab.com" in link_lower:
                 submission_data.link_type = LinkType.GITLAB
 
+        # Uploads are scanned for malware when fetched, refused before any rule reads them
+        self._scan_upload(files)
+
         # Execute validation rules if specified and requested
         rule_results = []
         if submission_data.rules:
             try:
                 logger.info(f"Executing {len(submission_data.rules)} validation rules")
-                rule_results = self.rule_service.validate_submission(submission_data)
+                rule_results = self.rule_service.validate_submission(submission_data, fetch=files.path)
 
                 # Check if any rules failed
                 failed_rules = [r for r in rule_results if not r.passed]
@@ -92,6 +157,7 @@ class SubmissionService:
 
                     # Create detailed error response
                     error_response = {
+                        "code": "rulesFailed",
                         "validation_failed": True,
                         "failed_rule_count": len(failed_rules),
                         "total_rule_count": len(rule_results),
@@ -109,7 +175,8 @@ class SubmissionService:
                         rule_results_json = json.dumps([r.to_dict() for r in rule_results])
                     else:
                         # Raise structured validation exception
-                        raise ValidationException(
+                        raise rejected(
+                            "rules_failed",
                             f"Submission validation failed: {len(failed_rules)} of {len(rule_results)} rules failed",
                             details=error_response,
                         )
@@ -122,28 +189,49 @@ class SubmissionService:
             except Exception as e:
                 # Log unexpected errors but don't fail the submission
                 logger.error(f"Unexpected error during rule execution: {str(e)}")
-                raise ValidationException(
+                raise rejected(
+                    "rule_error",
                     f"Rule execution failed: {str(e)}",
                     details={"error_type": type(e).__name__, "error_message": str(e)},
                 )
 
-        # Create the submission
+        # Refused when the project keeps its quota of submissions or stored bytes already
+        self.quota_service.check_submission(submission_data.project_uuid, submission_data.file_size_bytes)
+
+        # Create the submission, late if uploaded after the deadline of its step, with its language policy result
+        step_config = self.repository.get_step_config(submission_data.project_step_uuid)
+        language_policy_result = self._check_language_policy(files, step_config)
         submission = self.repository.create(
             submission_data=submission_data,
             ip_address=ip_address,
             user_agent=user_agent,
             rule_results_json=rule_results_json if "rule_results_json" in locals() else None,
+            deadline=step_config.deadline if step_config else None,
+            language_policy_result=language_policy_result,
+            team=team,
         )
 
+        SUBMISSIONS_CREATED.labels(getattr(submission_data.link_type, "value", None) or "unknown").inc()
+
         # Update submission status to completed for similarity detection
         update_data = SubmissionUpdateDto(status=SubmissionStatus.COMPLETED)
         submission = self.repository.update(submission.id, update_data)
 
+        # Keep a copy of the submitted files in the submission store
+        storage_summary = self._store_submission_files(submission, files)
+
         # Start similarity detection asynchronously (non-blocking)
+        run_id = None
+        run_quota = None
         try:
             logger.info(f"Starting async similarity detection for submission {submission.id}")
-            self.detection_service.process_submission_similarities_async(submission)
+            run_id = self.detection_service.process_submission_similarities_async(
+                submission, profile=profile, priority=priority
+            )
             logger.info(f"Async similarity detection initiated for submission {submission.id}")
+        except QuotaExceededException as e:
+            # The submission is kept, its run can be started once the quota allows it
+            run_quota = e.detail
         except Exception as e:
             logger.error(f"Failed to start async similarity detection for submission {submission.id}: {str(e)}")
             # Log the error but don't fail the submission creation, it should be logged in the similarity entity either way
@@ -154,6 +242,8 @@ class SubmissionService:
             "message": "Submission created successfully",
             "submission_id": submission.id,
             "data": SubmissionResponseDto.model_validate(submission.model_dump()),
+            "run_id": run_id,
+            "run_quota_exceeded": run_quota,
         }
 
         # Add similarity detection metadata
@@ -162,6 +252,9 @@ class SubmissionService:
             "message": "Similarity detection has been started in the background. Results will be available shortly.",
         }
 
+        if storage_summary:
+            response_data["storage"] = storage_summary
+
         # Include rule results from execution or from stored submission
         if rule_results:
             response_data["rule_results"] = [
@@ -204,6 +297,22 @@ class SubmissionService:
 
         return CreateSubmissionResponseDto(**response_data)
 
+    def get_submission_metrics(self, submission_id: UUID) -> SubmissionMetricsDto:
+        """Get the code metrics of a submission, computed when its files were stored or by its next comparison"""
+        if not self.repository.get_by_id(submission_id):
+            raise NotFoundException(f"Submission with ID {submission_id} not found")
+        record = self.repository.get_metrics(submission_id)
+        if record is None:
+            raise NotFoundException(f"Metrics of submission {submission_id} not computed yet, run a detection first")
+
+        return SubmissionMetricsDto(
+            submission_id=record.submission_id,
+            metrics_version=record.metrics_version,
+            content_version=record.content_version,
+            computed_at=record.computed_at,
+            **record.metrics,
+        )
+
     def get_submission_by_project_group_step(self, project_uuid, group_uuid, project_step_uuid):
         """Get a submission by project, group and step"""
         submission = self.repository.get_by_project_group_step(
@@ -247,6 +356,68 @@ class SubmissionService:
         submissions = self.repository.get_by_project_step(project_uuid, project_step_uuid)
         return [SubmissionResponseDto.model_validate(sub.model_dump()) for sub in submissions]
 
+    def get_step_config(self, project_uuid: UUID, project_step_uuid: UUID) -> ProjectStepConfigResponseDto:
+        """Get the configuration of a project step"""
+        config = self.repository.get_step_config(project_step_uuid)
+        if config is None or config.project_uuid != project_uuid:
+            raise NotFoundException(f"No configuration for step {project_step_uuid} of project {project_uuid}")
+        late = sum(s.is_late for s in self.repository.get_by_project_step(project_uuid, project_step_uuid))
+        return ProjectStepConfigResponseDto(**config.model_dump(), late_submissions=late)
+
+    def set_step_config(
+        self, project_uuid: UUID, project_step_uuid: UUID, config_data: ProjectStepConfigDto
+    ) -> ProjectStepConfigResponseDto:
+        """
+        Create or replace the configuration of a project step, marking its submissions late against the deadline
+
+        Raises:
+            ValidationException: If the language policy names a language that is not supported
+        """
+        values = config_data.model_dump()
+        if config_data.language_policy is not None:
+            self._validate_language_policy(config_data.language_policy)
+            values["language_policy"] = config_data.language_policy.model_dump(mode="json")
+        config = self.repository.upsert_step_config(project_uuid, project_step_uuid, values)
+        late = self.repository.mark_lateness(project_uuid, project_step_uuid, config.deadline)
+        return ProjectStepConfigResponseDto(**config.model_dump(), late_submissions=late)
+
+    def set_declared_collaborations(
+        self, project_uuid: UUID, project_step_uuid: UUID, declarations: List[DeclaredCollaborationDto]
+    ) -> ProjectStepConfigResponseDto:
+        """
+        Replace the declared collaborations of a project step, for its next runs: existing runs keep classifying
+        their pairs with the previous ones until they are reclassified
+        """
+        config = self.repository.upsert_step_config(
+            project_uuid,
+            project_step_uuid,
+            {"declared_collaborations": [declaration.model_dump(mode="json") for declaration in declarations]},
+        )
+        late = sum(s.is_late for s in self.repository.get_by_project_step(project_uuid, project_step_uuid))
+        return ProjectStepConfigResponseDto(**config.model_dump(), late_submissions=late)
+
+    def create_team(self, project_uuid: UUID, team_data: TeamDto) -> TeamResponseDto:
+        """Create a team of a project"""
+        team = self.repository.create_team(project_uuid, team_data.name, team_data.members)
+        return TeamResponseDto.model_validate(team)
+
+    def get_team(self, project_uuid: UUID, team_id: UUID) -> TeamResponseDto:
+        """Get a team of a project"""
+        return TeamResponseDto.model_validate(self._get_team_or_raise(project_uuid, team_id))
+
+    def list_teams(self, project_uuid: UUID) -> List[TeamResponseDto]:
+        """Get the teams of a project"""
+        return [TeamResponseDto.model_validate(team) for team in self.repository.get_teams_by_project(project_uuid)]
+
+    def update_team(self, project_uuid: UUID, team_id: UUID, team_data: TeamDto) -> TeamResponseDto:
+        """
+        Replace the name and members of a team, for its next submissions: those already uploaded keep the team
+        they were made by
+        """
+        self._get_team_or_raise(project_uuid, team_id)
+        team = self.repository.update_team(team_id, team_data.name, team_data.members)
+        return TeamResponseDto.model_validate(team)
+
     def update_submission(self, submission_id: UUID, update_data: SubmissionUpdateDto) -> CreateSubmissionResponseDto:
         """Update a submission"""
         submission = self.repository.update(submission_id, update_data)
@@ -259,19 +430,75 @@ class SubmissionService:
         )
 
     def delete_submission(self, submission_id: UUID) -> CreateSubmissionResponseDto:
-        """Delete a submission"""
+        """Delete a submission and its stored files"""
+        submission = self.repository.get_by_id(submission_id)
+        if submission:
+            self.storage_service.delete_submission_files(submission)
+
         success = self.repository.delete(submission_id)
 
         return CreateSubmissionResponseDto(
             success=success, message="Submission deleted successfully", submission_id=submission_id
         )
 
-    def list_submissions(self, skip: int = 0, limit: int = 100) -> List[SubmissionResponseDto]:
-        """List all submissions with pagination"""
+    def start_detection(
+        self,
+        submission_id: UUID,
+        profile: bool = False,
+        priority: JobPriority = JobPriority.NORMAL,
+        fingerprint_k: Optional[int] = None,
+        fingerprint_window: Optional[int] = None,
+        auto_tune: Optional[bool] = None,
+    ) -> Optional[UUID]:
+        """
+        Queue a new detection run of a submission against the other groups of its step
+
+        k and window set by the request win over those auto-tuning would choose.
+
+        Returns:
+            ID of the run, None when there is no other submission to compare it with
+
+        Raises:
+            NotFoundException: If the submission does not exist
+            QuotaExceededException: If the run would exceed the runs per day or run submissions quota of the project
+        """
+        submission = self._get_submission_or_raise(submission_id)
+        return self.detection_service.process_submission_similarities_async(
+            submission,
+            profile=profile,
+            priority=priority,
+            trigger=DetectionRunTrigger.MANUAL,
+            fingerprint_k=fingerprint_k,
+            fingerprint_window=fingerprint_window,
+            auto_tune=auto_tune,
+        )
+
+    def plan_detection(
+        self,
+        submission_id: UUID,
+        fingerprint_k: Optional[int] = None,
+        fingerprint_window: Optional[int] = None,
+        auto_tune: Optional[bool] = None,
+    ) -> DetectionPlanDto:
+        """
+        What a detection run of a submission would do, nothing being queued, tokenized or written
+
+        The plan holds the parameters that start_detection would record with the same k, window and auto_tune.
+
+        Raises:
+            NotFoundException: If the submission does not exist
+        """
+        submission = self._get_submission_or_raise(submission_id)
+        return self.detection_service.plan_detection(submission, fingerprint_k, fingerprint_window, auto_tune)
+
+    def list_submissions(
+        self, skip: int = 0, limit: int = 100, member: Optional[UUID] = None
+    ) -> List[SubmissionResponseDto]:
+        """List all submissions with pagination, only those of a student, alone or in a team, with member"""
         if limit > 1000:  # Prevent excessive data retrieval
             limit = 1000
 
-        submissions = self.repository.list_all(skip=skip, limit=limit)
+        submissions = self.repository.list_all(skip=skip, limit=limit, member=member)
         return [SubmissionResponseDto.model_validate(sub.model_dump()) for sub in submissions]
 
     def get_submission_statistics(self, project_uuid: UUID, group_uuid: UUID) -> dict:
@@ -301,9 +528,201 @@ class SubmissionService:
             "status_breakdown": status_counts,
             "step_breakdown": step_counts,
             "link_type_breakdown": link_type_counts,
-            "latest_submission": submissions[0].upload_date_time.isoformat() if submissions else None,
+            "latest_submission": to_rfc3339(submissions[0].upload_date_time) if submissions else None,
         }
 
+    def list_submission_files(self, submission_id: UUID, version: Optional[int] = None) -> dict:
+        """List the stored files of a submission"""
+        submission = self._get_submission_or_raise(submission_id)
+
+        version = version or self.storage_service.get_latest_version(submission)
+        files = self.storage_service.list_files(submission, version) if version else []
+
+        return {
+            "submission_id": submission.id,
+            "version": version,
+            "available_versions": self.storage_service.get_versions(submission),
+            "file_count": len(files),
+            "files": [
+                {
+                    "path": f.key,
+                    "size": f.size,
+                    "content_type": f.content_type,
+                    "bom": f.bom,
+                    "archive_name": f.archive_name,
+                }
+                for f in files
+            ],
+        }
+
+    def stream_submission_file(self, submission_id: UUID, file_path: str, version: Optional[int] = None):
+        """Stream the content of a stored submission file"""
+        submission = self._get_submission_or_raise(submission_id)
+        return self.storage_service.stream_file(submission, file_path, version)
+
+    def debug_submission_file(
+        self,
+        submission_id: UUID,
+        file_path: str,
+        version: Optional[int] = None,
+        normalization: Optional[NormalizationLevel] = None,
+        k: Optional[int] = None,
+        window: Optional[int] = None,
+        skip: int = 0,
+        limit: int = 1000,
+    ) -> FileFingerprintDebugDto:
+        """
+        Tokens, k-grams and fingerprints of a stored file, decoded, tokenized and fingerprinted like a detection run
+
+        Raises:
+            NotFoundException: If the submission or the file does not exist
+            ValidationException: If the file is not of a supported language
+        """
+        submission = self._get_submission_or_raise(submission_id)
+        version = version or self.storage_service.get_latest_version(submission)
+        path = Path(file_path)
+        tokenization_service = self.detection_service.tokenization_service
+        if not tokenization_service.is_supported_file(path):
+            raise ValidationException(f"{file_path} is not of a supported language, detection runs leave it out")
+
+        fingerprint_service = self.detection_service.fingerprint_service
+        content = decode_source(self.storage_service.read_file(submission, file_path, version))
+        trace = fingerprint_service.trace_fingerprints(content, path, k, window, normalization)
+        return trace_page(
+            trace,
+            fingerprint_service.tokenizer_config,
+            submission.id,
+            version,
+            file_path,
+            fingerprint_service.is_generated(content, path),
+            skip,
+            limit,
+        )
+
+    def _get_submission_or_raise(self, submission_id: UUID) -> Submission:
+        submission = self.repository.get_by_id(submission_id)
+        if not submission:
+            raise NotFoundException(f"Submission with ID {submission_id} not found")
+        return submission
+
+    def _get_team_or_raise(self, project_uuid: UUID, team_id: UUID) -> Team:
+        team = self.repository.get_team(team_id)
+        if not team or team.project_uuid != project_uuid:
+            raise NotFoundException(f"Team with ID {team_id} not found in project {project_uuid}")
+        return team
+
+    def _get_submission_team(self, submission_data: CreateSubmissionDto) -> Optional[Team]:
+        """Team a submission is made by, None without team, its submitter must be one of its members"""
+        if submission_data.team_id is None:
+            return None
+        team = self.repository.get_team(submission_data.team_id)
+        if not team or team.project_uuid != submission_data.project_uuid:
+            raise rejected(
+                "unknown_team", f"Team {submission_data.team_id} not found in project {submission_data.project_uuid}"
+            )
+        submitter = submission_data.submitted_by_uuid
+        if submitter is not None and str(submitter) not in team.members:
+            raise rejected("not_team_member", f"Submitter {submitter} is not a member of team {team.name}")
+        return team
+
+    def _store_submission_files(self, submission: Submission, files: FetchedSubmission) -> Optional[dict]:
+        """
+        Push the files of the submission to the submission store
+
+        Returns:
+            The storage summary, None when storage is disabled, the error when the files could not be stored, which
+            is also flagged on the submission
+        """
+        if not get_settings().storage_ingest_enabled:
+            return None
+
+        # Waits for a free slot when ingestion_max_concurrent_jobs submissions are already being stored
+        return get_ingestion_scheduler().run(self._ingest_submission_files, submission, files)
+
+    def _ingest_submission_files(self, submission: Submission, files: FetchedSubmission) -> dict:
+        try:
+            repo_path = files.path()
+            extracted = files.submission.link_type == LinkType.S3
+            summary = self.storage_service.ingest_directory(submission, repo_path, extracted=extracted)
+            self.repository.add_stored_bytes(submission.id, summary["total_bytes"])
+        except Exception as e:
+            logger.error(f"Failed to store files of submission {submission.id}: {str(e)}")
+            self.repository.set_storage_error(submission.id, str(e))
+            return {"error": str(e)}
+        self.detection_service.measure_submission(submission, repo_path, summary["version"], self.repository)
+        return summary
+
+    def _scan_upload(self, files: FetchedSubmission) -> None:
+        """Fetch an uploaded archive so that it is scanned before the submission is created, refused if infected"""
+        submission_data = files.submission
+        if not get_settings().malware_scan_enabled or submission_data.link_type != LinkType.S3:
+            return
+
+        try:
+            get_ingestion_scheduler().run(files.path)
+        except MalwareDetectedException:
+            SUBMISSION_REJECTIONS.labels("malware").inc()
+            raise
+        except MalwareScannerUnavailableException:
+            SUBMISSION_REJECTIONS.labels("malware_scan_unavailable").inc()
+            raise
+        except Exception as e:
+            # Other fetch failures do not refuse a submission, storing its files fails later and is flagged on it
+            logger.warning(f"Failed to fetch submission {submission_data.link} for its malware scan: {str(e)}")
+
+    def _validate_language_policy(self, policy: LanguagePolicyDto) -> None:
+        """Refuse language policies naming a language detection does not know, which no file would ever be in"""
+        known = self.detection_service.tokenization_service.language_registry.names()
+        unknown = [name for name in policy.allowed_languages + policy.auxiliary_languages if name not in known]
+        if unknown:
+            raise ValidationException(f"Unknown languages in the language policy: {', '.join(unknown)}")
+
+    def _check_language_policy(
+        self, files: FetchedSubmission, step_config: Optional[ProjectStepConfig]
+    ) -> Optional[dict]:
+        """
+        Fetch a submission to size its content by language against the language policy of its step
+
+        Returns:
+            The policy result, None without policy or when the submission could not be fetched
+        Raises:
+            ValidationException: If the submission violates a policy enforced by rejection
+        """
+        if step_config is None or not step_config.language_policy:
+            return None
+
+        policy = LanguagePolicyDto.model_validate(step_config.language_policy)
+        submission_data = files.submission
+        try:
+            repo_path = get_ingestion_scheduler().run(files.path)
+            content = content_by_language(self.detection_service.tokenization_service, repo_path)
+        except (MalwareDetectedException, MalwareScannerUnavailableException):
+            raise
+        except Exception as e:
+            # Like storing its files, a submission that cannot be fetched is not refused
+            logger.warning(f"Failed to fetch submission {submission_data.link} for its language policy: {str(e)}")
+            return None
+
+        result = evaluate_language_policy(policy, content)
+        if result.status != LanguagePolicyStatus.COMPLIANT:
+            summary = (
+                f"{result.disallowed_share:.0%} of the submission is in {', '.join(result.disallowed_languages)}, "
+                f"the step allows {', '.join(policy.allowed_languages)}"
+            )
+            if result.status == LanguagePolicyStatus.VIOLATION and policy.enforce == LanguageEnforcement.REJECT:
+                raise rejected(
+                    "language_not_allowed",
+                    summary,
+                    details={
+                        "code": "languageNotAllowed",
+                        "message": summary,
+                        "allowed_languages": policy.allowed_languages,
+                        "language_policy_result": result.model_dump(mode="json"),
+                    },
+                )
+            logger.info(f"Language policy {result.status} for submission {submission_data.link}: {summary}")
+        return result.model_dump(mode="json")
+
     def _validate_submission_data(self, submission_data: CreateSubmissionDto) -> None:
         """Validate submission data according to business rules"""
 
@@ -313,8 +732,9 @@ class SubmissionService:
         if "github.com" in link:
             # GitHub repository validation
             if "/tree/" in link or "/blob/" in link:
-                raise ValidationException(
-                    "GitHub link should point to the repository root, not specific files or branches"
+                raise rejected(
+                    "invalid_link",
+                    "GitHub link should point to the repository root, not specific files or branches",
                 )
             if not link.endswith(".git") and "/archive/" not in link:
                 # Allow both .git URLs and archive URLs
@@ -323,24 +743,25 @@ class SubmissionService:
         elif "gitlab.com" in link:
             # GitLab repository validation
             if "/tree/" in link or "/blob/" in link:
-                raise ValidationException(
-                    "GitLab link should point to the repository root, not specific files or branches"
+                raise rejected(
+                    "invalid_link",
+                    "GitLab link should point to the repository root, not specific files or branches",
                 )
 
         # Validate file size if provided
         if submission_data.file_size_bytes is not None:
             max_size = 1024 * 1024 * 1024 * 5  # 5GB limit
             if submission_data.file_size_bytes > max_size:
-                raise ValidationException("File size cannot exceed 5GB")
+                raise rejected("file_size", "File size cannot exceed 5GB")
 
         # Validate file count if provided
         if submission_data.file_count is not None:
             if submission_data.file_count > 10000:
-                raise ValidationException("File count cannot exceed 10,000 files")
+                raise rejected("file_count", "File count cannot exceed 10,000 files")
 
         # Validate description length
         if submission_data.description and len(submission_data.description) > 1000:
-            raise ValidationException("Description cannot exceed 1000 characters")
+            raise rejected("description", "Description cannot exceed 1000 characters")
 
     def get_submission_similarities(self, submission_id: UUID) -> List[dict]:
         """Get all similarity results for a submission"""
@@ -8,6 +8,8 @@ from sqlmodel import Session, select
 
 from app.domains.submissions.submissions_models import SimilarityStatus, SubmissionSimilarity
 from app.shared.exceptions import DatabaseException, NotFoundException
+from app.shared.tenancy import require_project_access
+from app.shared.timestamps import utc_now
 
 
 class SubmissionSimilarityRepository:
@@ -18,6 +20,7 @@ class SubmissionSimilarityRepository:
 
     def create(self, similarity_data: dict) -> SubmissionSimilarity:
         """Create a new similarity record"""
+        require_project_access(similarity_data["project_uuid"])
         try:
             similarity = SubmissionSimilarity(**similarity_data)
             self.session.add(similarity)
@@ -47,7 +50,11 @@ class SubmissionSimilarityRepository:
                         SubmissionSimilarity.compared_submission_id == submission_id,
                     )
                 )
-                .order_by(SubmissionSimilarity.overall_similarity.desc())
+                .order_by(
+                    SubmissionSimilarity.overall_similarity.desc(),
+                    SubmissionSimilarity.submission_id,
+                    SubmissionSimilarity.compared_submission_id,
+                )
             )
             return list(self.session.exec(statement).all())
         except Exception as e:
@@ -62,7 +69,11 @@ class SubmissionSimilarityRepository:
                     SubmissionSimilarity.project_uuid == project_uuid,
                     SubmissionSimilarity.project_step_uuid == project_step_uuid,
                 )
-                .order_by(SubmissionSimilarity.overall_similarity.desc())
+                .order_by(
+                    SubmissionSimilarity.overall_similarity.desc(),
+                    SubmissionSimilarity.submission_id,
+                    SubmissionSimilarity.compared_submission_id,
+                )
             )
             return list(self.session.exec(statement).all())
         except Exception as e:
@@ -80,7 +91,11 @@ class SubmissionSimilarityRepository:
                     SubmissionSimilarity.project_step_uuid == project_step_uuid,
                     SubmissionSimilarity.overall_similarity >= similarity_threshold,
                 )
-                .order_by(SubmissionSimilarity.overall_similarity.desc())
+                .order_by(
+                    SubmissionSimilarity.overall_similarity.desc(),
+                    SubmissionSimilarity.submission_id,
+                    SubmissionSimilarity.compared_submission_id,
+                )
             )
             return list(self.session.exec(statement).all())
         except Exception as e:
@@ -107,7 +122,7 @@ class SubmissionSimilarityRepository:
                 raise NotFoundException(f"Similarity record with ID {similarity_id} not found")
 
             similarity.status = status
-            similarity.updated_at = datetime.utcnow()
+            similarity.updated_at = utc_now()
 
             if error_message:
                 similarity.error_message = error_message
@@ -147,8 +162,11 @@ class SubmissionSimilarityRepository:
 
             # Update timing and status
             similarity.processing_time_seconds = results.get("processing_time_seconds")
-            similarity.status = SimilarityStatus.COMPLETED
-            similarity.updated_at = datetime.utcnow()
+            # Pairs that are not scored carry their own status and reason
+            similarity.status = results.get("status", SimilarityStatus.COMPLETED)
+            if results.get("error_message"):
+                similarity.error_message = results["error_message"]
+            similarity.updated_at = utc_now()
 
             self.session.add(similarity)
             self.session.commit()
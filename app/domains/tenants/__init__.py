@@ -0,0 +1 @@
+# Tenants domain package
@@ -0,0 +1,3 @@
+from .tenant_dto import TenantProjectDto
+
+__all__ = ["TenantProjectDto"]
@@ -0,0 +1,15 @@
+from uuid import UUID
+
+from pydantic import BaseModel, ConfigDict
+
+from app.shared.timestamps import UtcTimestamp
+
+
+class TenantProjectDto(BaseModel):
+    """DTO for reading the assignment of a project to its tenant"""
+
+    model_config = ConfigDict(from_attributes=True)
+
+    project_uuid: UUID
+    tenant_id: str
+    assigned_at: UtcTimestamp
@@ -0,0 +1,120 @@
+"""
+Tenant isolation of the ORM sessions
+
+While the current tenancy is restricted, every query of a session, relationship loads, bulk updates and deletes
+included, only reaches the rows of the projects assigned to its tenant: a row of another tenant is not found, the
+existing not found paths answer for it. Flushes writing a row to a project of another tenant or of none are refused
+the same way, so a check missed above the repositories still cannot reach another tenant.
+
+Statements run with the SKIP_TENANCY execution option are not filtered, for the assignments themselves.
+"""
+
+from typing import List, Optional
+from uuid import UUID
+
+from sqlalchemy import event, select
+from sqlalchemy.orm import with_loader_criteria
+from sqlmodel import Session
+
+from app.domains.callbacks.callbacks_models import CallbackDelivery
+from app.domains.quotas.quota_models import ProjectUsage
+from app.domains.retention.retention_models import ProjectRetentionPolicy
+from app.domains.runs.runs_models import DetectionFragment, DetectionPair, DetectionRun, DetectionRunParticipant
+from app.domains.schedules.schedules_models import DetectionSchedule
+from app.domains.submissions.submissions_models import (
+    ProjectStepConfig,
+    Submission,
+    SubmissionMetrics,
+    SubmissionSimilarity,
+    Team,
+)
+from app.domains.tenants.tenants_models import ProjectTenant
+from app.shared.exceptions import NotFoundException
+from app.shared.tenancy import Tenancy, current_tenancy
+
+SKIP_TENANCY = "skip_tenancy"
+
+# Models with the project they belong to
+PROJECT_OWNED_MODELS = (
+    Submission,
+    Team,
+    ProjectStepConfig,
+    SubmissionSimilarity,
+    SubmissionMetrics,
+    DetectionRun,
+    DetectionPair,
+    DetectionSchedule,
+    ProjectRetentionPolicy,
+    ProjectUsage,
+)
+# Models with the run they belong to
+RUN_OWNED_MODELS = (DetectionRunParticipant, DetectionFragment, CallbackDelivery)
+
+
+def tenant_criteria(tenant_id: Optional[str]) -> List:
+    """Loader criteria restricting the tenant-owned models to the projects of a tenant"""
+    # tenant_id is not nullable, a tenancy without tenant matches no project
+    projects = select(ProjectTenant.project_uuid).where(ProjectTenant.tenant_id == tenant_id)
+    # Core columns, which the criteria of DetectionRun do not apply to again
+    runs_table = DetectionRun.__table__
+    runs = select(runs_table.c.id).where(runs_table.c.project_uuid.in_(projects))
+    criteria = [
+        with_loader_criteria(model, model.project_uuid.in_(projects), include_aliases=True)
+        for model in PROJECT_OWNED_MODELS
+    ]
+    criteria += [
+        with_loader_criteria(model, model.run_id.in_(runs), include_aliases=True) for model in RUN_OWNED_MODELS
+    ]
+    return criteria
+
+
+def _filter_by_tenant(execute_state) -> None:
+    tenancy = current_tenancy()
+    if not tenancy.restricted or execute_state.is_column_load:
+        return
+    if execute_state.execution_options.get(SKIP_TENANCY):
+        return
+    if execute_state.is_select or execute_state.is_update or execute_state.is_delete:
+        execute_state.statement = execute_state.statement.options(*tenant_criteria(tenancy.tenant_id))
+
+
+def _owns(session: Session, tenancy: Tenancy, project_uuid: Optional[UUID]) -> bool:
+    if project_uuid is None or tenancy.tenant_id is None:
+        return False
+    if project_uuid not in tenancy.projects:
+        statement = select(ProjectTenant.tenant_id).where(ProjectTenant.project_uuid == project_uuid)
+        if session.execute(statement.execution_options(**{SKIP_TENANCY: True})).scalar() != tenancy.tenant_id:
+            return False
+        tenancy.projects.add(project_uuid)
+    return True
+
+
+def _run_project(session: Session, run_id: UUID) -> Optional[UUID]:
+    for instance in session.new:
+        if isinstance(instance, DetectionRun) and instance.id == run_id:
+            return instance.project_uuid
+    runs_table = DetectionRun.__table__
+    return session.execute(select(runs_table.c.project_uuid).where(runs_table.c.id == run_id)).scalar()
+
+
+def _check_tenant_writes(session: Session, flush_context, instances) -> None:
+    tenancy = current_tenancy()
+    if not tenancy.restricted:
+        return
+    with session.no_autoflush:
+        for instance in list(session.new) + list(session.dirty):
+            if isinstance(instance, PROJECT_OWNED_MODELS):
+                project_uuid = instance.project_uuid
+            elif isinstance(instance, RUN_OWNED_MODELS):
+                project_uuid = _run_project(session, instance.run_id)
+            else:
+                continue
+            if not _owns(session, tenancy, project_uuid):
+                raise NotFoundException("Project", str(project_uuid))
+
+
+def install_tenant_filters() -> None:
+    """Filter the queries and check the flushes of every session by the current tenancy"""
+    if not event.contains(Session, "do_orm_execute", _filter_by_tenant):
+        event.listen(Session, "do_orm_execute", _filter_by_tenant)
+        event.listen(Session, "before_flush", _check_tenant_writes)
@@ -0,0 +1,20 @@
+from datetime import datetime
+from uuid import UUID
+
+from sqlmodel import Column, Field, SQLModel
+
+from app.shared.timestamps import UtcDateTime, utc_now
+
+
+class ProjectTenant(SQLModel, table=True):
+    """Database model for the tenant a project belongs to, its resources are only reached by that tenant"""
+
+    __tablename__ = "project_tenant"
+
+    project_uuid: UUID = Field(primary_key=True, description="UUID of the project")
+    tenant_id: str = Field(index=True, max_length=64, description="Tenant, the institution owning the project")
+    assigned_at: datetime = Field(
+        default_factory=utc_now,
+        sa_column=Column(UtcDateTime(), nullable=False),
+        description="When the project was assigned to its tenant",
+    )
@@ -0,0 +1,51 @@
+from typing import List, Optional
+from uuid import UUID
+
+from sqlmodel import Session, select
+
+from app.domains.tenants.tenant_filters import SKIP_TENANCY
+from app.domains.tenants.tenants_models import ProjectTenant
+from app.shared.exceptions import DatabaseException
+from app.shared.timestamps import utc_now
+
+
+class TenantRepository:
+    """Repository for the assignments of projects to tenants"""
+
+    def __init__(self, session: Session):
+        self.session = session
+
+    def get_tenant(self, project_uuid: UUID) -> Optional[str]:
+        """Tenant a project is assigned to, None while it is assigned to none"""
+        try:
+            statement = select(ProjectTenant.tenant_id).where(ProjectTenant.project_uuid == project_uuid)
+            return self.session.exec(statement.execution_options(**{SKIP_TENANCY: True})).first()
+        except Exception as e:
+            raise DatabaseException(f"Failed to get the tenant of project {project_uuid}: {str(e)}")
+
+    def list_projects(self, tenant_id: str) -> List[ProjectTenant]:
+        """Projects assigned to a tenant, oldest assignment first"""
+        try:
+            statement = (
+                select(ProjectTenant).where(ProjectTenant.tenant_id == tenant_id).order_by(ProjectTenant.assigned_at)
+            )
+            return list(self.session.exec(statement))
+        except Exception as e:
+            raise DatabaseException(f"Failed to list the projects of tenant {tenant_id}: {str(e)}")
+
+    def assign(self, project_uuid: UUID, tenant_id: str) -> ProjectTenant:
+        """Assign a project to a tenant, moving it from its previous tenant if any"""
+        try:
+            assignment = self.session.get(ProjectTenant, project_uuid)
+            if assignment is None:
+                assignment = ProjectTenant(project_uuid=project_uuid, tenant_id=tenant_id)
+            elif assignment.tenant_id != tenant_id:
+                assignment.tenant_id = tenant_id
+                assignment.assigned_at = utc_now()
+            self.session.add(assignment)
+            self.session.commit()
+            self.session.refresh(assignment)
+            return assignment
+        except Exception as e:
+            self.session.rollback()
+            raise DatabaseException(f"Failed to assign project {project_uuid} to tenant {tenant_id}: {str(e)}")
@@ -0,0 +1,45 @@
+import logging
+from typing import List
+from uuid import UUID
+
+from sqlmodel import Session
+
+from app.domains.tenants.dto.tenant_dto import TenantProjectDto
+from app.domains.tenants.tenants_repository import TenantRepository
+from app.shared.exceptions import ValidationException
+from app.shared.tenancy import TENANT_ID_PATTERN
+
+logger = logging.getLogger(__name__)
+
+
+def validated_tenant_id(tenant_id: str) -> str:
+    """
+    Raises:
+        ValidationException: If the tenant ID is not 1 to 64 letters, digits, dots, dashes or underscores
+    """
+    if not TENANT_ID_PATTERN.match(tenant_id or ""):
+        raise ValidationException(f"Invalid tenant ID '{tenant_id}': 1 to 64 letters, digits, '.', '-' or '_'")
+    return tenant_id
+
+
+class TenantService:
+    """Service assigning projects to the tenants whose requests reach them"""
+
+    def __init__(self, session: Session):
+        self.repository = TenantRepository(session)
+
+    def assign_project(self, tenant_id: str, project_uuid: UUID) -> TenantProjectDto:
+        """Assign a project to a tenant, the requests of its previous tenant no longer find it"""
+        tenant_id = validated_tenant_id(tenant_id)
+        previous = self.repository.get_tenant(project_uuid)
+        assignment = self.repository.assign(project_uuid, tenant_id)
+        if previous is not None and previous != tenant_id:
+            logger.warning(f"Project {project_uuid} moved from tenant {previous} to tenant {tenant_id}")
+        return TenantProjectDto.model_validate(assignment)
+
+    def list_projects(self, tenant_id: str) -> List[TenantProjectDto]:
+        """Projects assigned to a tenant"""
+        return [
+            TenantProjectDto.model_validate(assignment)
+            for assignment in self.repository.list_projects(validated_tenant_id(tenant_id))
+        ]
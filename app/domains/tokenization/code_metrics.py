@@ -0,0 +1,270 @@
+"""
+Code metrics of submissions
+
+Quantitative context graders read next to the similarity scores, computed from the text and the tokens of every
+supported file of a submission when it is stored:
+
+- code lines: lines holding something else than comments and whitespace, a line of code ending with a comment
+  included
+- comment lines: lines holding comments only, comments being the tokens of the comment class of the tokenizer
+  configuration; docstrings are strings, so code
+- blank lines: lines holding whitespace only
+- comment ratio: comment lines out of the code and comment lines
+- functions: nodes of one of the function types of the language, named and anonymous functions alike, see
+  FUNCTION_TYPES; declarations without a body are not functions
+- cyclomatic complexity: one per function plus one per decision point, the nodes of one of the decision types of
+  the language: branches, loops, every arm of a switch or match, default arms included, exception handlers and
+  conditional expressions, see DECISION_TYPES. Boolean operators are not counted, as most grammars do not tell them
+  apart from the other binary operators.
+
+Languages without functions, markup, data and build files, have no function and a complexity of 0. A metric that
+cannot be computed for a file is null rather than guessed, for the file, its language and the totals: functions and
+complexity of languages defining neither, comments left out by the stop tokens of the language, any metric of a file
+without tokens or whose computation failed. Metrics never fail a comparison.
+"""
+
+import logging
+from dataclasses import dataclass, field, fields
+from typing import Any, Dict, FrozenSet, List, Optional
+
+from app.domains.tokenization.streaming_source import normalize_source
+from app.domains.tokenization.tokenizer_config import TokenizerConfig
+
+logger = logging.getLogger(__name__)
+
+# Bump whenever a definition changes, metrics computed with another version are computed again
+METRICS_VERSION = "1"
+
+COMMENT_TOKEN_CLASS = "comment"
+
+# Languages without functions, with no function and no decision point
+NO_FUNCTION_LANGUAGES = frozenset(
+    {"xml", "html", "css", "json", "yaml", "toml", "markdown", "sql", "cmake", "make", "dockerfile", "gomod"}
+)
+
+# Node types of the functions of each language, by tree-sitter grammar
+FUNCTION_TYPES: Dict[str, FrozenSet[str]] = {
+    "python": frozenset({"function_definition", "lambda"}),
+    "rust": frozenset({"function_item", "closure_expression"}),
+    "javascript": frozenset(
+        {
+            "function_declaration",
+            "function_expression",
+            "function",
+            "generator_function_declaration",
+            "generator_function",
+            "arrow_function",
+            "method_definition",
+        }
+    ),
+    "java": frozenset({"method_declaration", "constructor_declaration", "lambda_expression"}),
+    "c": frozenset({"function_definition"}),
+    "cpp": frozenset({"function_definition", "lambda_expression"}),
+    "go": frozenset({"function_declaration", "method_declaration", "func_literal"}),
+    "bash": frozenset({"function_definition"}),
+}
+FUNCTION_TYPES["typescript"] = FUNCTION_TYPES["javascript"]
+
+# Node types of the decision points of each language
+DECISION_TYPES: Dict[str, FrozenSet[str]] = {
+    "python": frozenset(
+        {
+            "if_statement",
+            "elif_clause",
+            "for_statement",
+            "while_statement",
+            "except_clause",
+            "except_group_clause",
+            "case_clause",
+            "conditional_expression",
+            # Loops and filters of comprehensions and generator expressions
+            "for_in_clause",
+            "if_clause",
+        }
+    ),
+    "rust": frozenset(
+        {
+            "if_expression",
+            "if_let_expression",
+            "while_expression",
+            "while_let_expression",
+            "for_expression",
+            "match_arm",
+        }
+    ),
+    "javascript": frozenset(
+        {
+            "if_statement",
+            "for_statement",
+            "for_in_statement",
+            "while_statement",
+            "do_statement",
+            "switch_case",
+            "switch_default",
+            "catch_clause",
+            "ternary_expression",
+        }
+    ),
+    "java": frozenset(
+        {
+            "if_statement",
+            "for_statement",
+            "enhanced_for_statement",
+            "while_statement",
+            "do_statement",
+            "switch_label",
+            "catch_clause",
+            "ternary_expression",
+        }
+    ),
+    "c": frozenset(
+        {"if_statement", "for_statement", "while_statement", "do_statement", "case_statement", "conditional_expression"}
+    ),
+    "go": frozenset(
+        {"if_statement", "for_statement", "expression_case", "type_case", "communication_case", "default_case"}
+    ),
+    "bash": frozenset(
+        {"if_statement", "elif_clause", "for_statement", "c_style_for_statement", "while_statement", "case_item"}
+    ),
+}
+DECISION_TYPES["typescript"] = DECISION_TYPES["javascript"]
+DECISION_TYPES["cpp"] = DECISION_TYPES["c"] | {"for_range_loop", "catch_clause"}
+
+
+def _total(left: Optional[int], right: Optional[int]) -> Optional[int]:
+    return None if left is None or right is None else left + right
+
+
+@dataclass
+class MetricCounts:
+    """Metrics of files, of one file, one language or a whole submission; None when they cannot be computed"""
+
+    files: int = 0
+    code_lines: Optional[int] = 0
+    comment_lines: Optional[int] = 0
+    blank_lines: Optional[int] = 0
+    functions: Optional[int] = 0
+    cyclomatic_complexity: Optional[int] = 0
+
+    def add(self, other: "MetricCounts") -> None:
+        for metric in fields(self):
+            setattr(self, metric.name, _total(getattr(self, metric.name), getattr(other, metric.name)))
+
+    @property
+    def comment_ratio(self) -> Optional[float]:
+        if self.code_lines is None or self.comment_lines is None or not self.code_lines + self.comment_lines:
+            return None
+        return round(self.comment_lines / (self.code_lines + self.comment_lines), 4)
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "files": self.files,
+            "code_lines": self.code_lines,
+            "comment_lines": self.comment_lines,
+            "blank_lines": self.blank_lines,
+            "comment_ratio": self.comment_ratio,
+            "functions": self.functions,
+            "cyclomatic_complexity": self.cyclomatic_complexity,
+        }
+
+
+def _uncommented_lines(lines: List[str], comments: List[Dict[str, Any]]) -> List[str]:
+    """Lines with the text of every comment token removed"""
+    residue = list(lines)
+    enclosing_end, enclosing_text = -1, ""
+    for token in comments:
+        # Some grammars end line comments with their newline
+        parts = token["text"].rstrip("\n").split("\n")
+        start = token["start"]
+        end = start + len(parts) - 1
+        # Parts of comments, like the doc comments of Rust, were removed with the comment holding them
+        if end <= enclosing_end and token["text"] in enclosing_text:
+            continue
+        enclosing_end, enclosing_text = end, token["text"]
+        if start >= len(residue):
+            continue
+        if start == end:
+            residue[start] = residue[start].replace(parts[0], "", 1)
+            continue
+        # A comment running over several lines ends the first of them and starts the last one
+        if residue[start].endswith(parts[0]):
+            residue[start] = residue[start][: len(residue[start]) - len(parts[0])]
+        for row in range(start + 1, min(end, len(residue))):
+            residue[row] = ""
+        if end < len(residue) and residue[end].startswith(parts[-1]):
+            residue[end] = residue[end][len(parts[-1]) :]
+    return residue
+
+
+def file_metrics(
+    text: str, tokens: List[Dict[str, Any]], language: Optional[str], tokenizer_config: TokenizerConfig
+) -> MetricCounts:
+    """Metrics of a file from its decoded text and its tokens, see the definitions above"""
+    text = normalize_source(text)
+    lines = text.split("\n")
+    if lines and not lines[-1]:
+        lines.pop()
+    blank = [not line.strip() for line in lines]
+    metrics = MetricCounts(files=1, blank_lines=sum(blank))
+    # A file with a line of code has tokens, without any it was not tokenized
+    tokenized = bool(tokens) or all(blank)
+    if not tokenized:
+        metrics.code_lines = metrics.comment_lines = metrics.functions = metrics.cyclomatic_complexity = None
+        return metrics
+
+    config = tokenizer_config.language(language)
+    try:
+        if any(tokenizer_config.token_class(stop) == COMMENT_TOKEN_CLASS for stop in config.stop_tokens):
+            raise ValueError("comments are left out of the tokens")
+        comments = [token for token in tokens if tokenizer_config.token_class(token["type"]) == COMMENT_TOKEN_CLASS]
+        residue = _uncommented_lines(lines, comments)
+        metrics.comment_lines = sum(1 for is_blank, rest in zip(blank, residue) if not is_blank and not rest.strip())
+        metrics.code_lines = len(lines) - metrics.blank_lines - metrics.comment_lines
+    except Exception as e:
+        logger.debug(f"Line metrics of a {language} file not computed: {e}")
+        metrics.code_lines = metrics.comment_lines = None
+
+    if language in NO_FUNCTION_LANGUAGES:
+        metrics.functions = metrics.cyclomatic_complexity = 0
+    elif language in FUNCTION_TYPES and not (FUNCTION_TYPES[language] | DECISION_TYPES[language]) & config.stop_tokens:
+        functions = sum(1 for token in tokens if token["type"] in FUNCTION_TYPES[language])
+        decisions = sum(1 for token in tokens if token["type"] in DECISION_TYPES[language])
+        metrics.functions, metrics.cyclomatic_complexity = functions, functions + decisions
+    else:
+        metrics.functions = metrics.cyclomatic_complexity = None
+    return metrics
+
+
+@dataclass
+class CodeMetricsCollector:
+    """Metrics of the files of a submission, added one by one as they are tokenized, by language and in total"""
+
+    tokenizer_config: TokenizerConfig
+    languages: Dict[str, MetricCounts] = field(default_factory=dict)
+
+    def add(self, text: str, tokens: List[Dict[str, Any]], language: Optional[str]) -> None:
+        """Add the metrics of a file"""
+        try:
+            metrics = file_metrics(text, tokens, language, self.tokenizer_config)
+        except Exception as e:
+            logger.warning(f"Code metrics of a {language} file not computed: {e}")
+            self.add_failed(language)
+            return
+        self.languages.setdefault(language or "unknown", MetricCounts()).add(metrics)
+
+    def add_failed(self, language: Optional[str]) -> None:
+        """Add a file whose metrics cannot be computed, every metric of its language null but its file count"""
+        failed = MetricCounts(
+            files=1, code_lines=None, comment_lines=None, blank_lines=None, functions=None, cyclomatic_complexity=None
+        )
+        self.languages.setdefault(language or "unknown", MetricCounts()).add(failed)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Totals of the submission, with the metrics of each of its languages under languages"""
+        total = MetricCounts()
+        for metrics in self.languages.values():
+            total.add(metrics)
+        return {
+            **total.to_dict(),
+            "languages": {language: metrics.to_dict() for language, metrics in sorted(self.languages.items())},
+        }
@@ -0,0 +1,42 @@
+import threading
+from typing import Dict
+
+DEFAULT_MAX_INTERNED_BYTES = 64 * 1024 * 1024
+
+
+class TokenInterner:
+    """
+    Shares one string per distinct token text
+
+    Token texts repeat heavily, within a file (identifiers, keywords) and across the submissions of a run (starter
+    code), so tokens keep a reference to the interned string instead of a copy of their own. Tokens stay plain
+    dicts holding str texts, the comparison and fragment code is unaffected. The interned texts are dropped once
+    they exceed max_bytes, tokens already produced keep their strings.
+    """
+
+    def __init__(self, max_bytes: int = DEFAULT_MAX_INTERNED_BYTES):
+        self.max_bytes = max_bytes
+        self._texts: Dict[str, str] = {}
+        self._size = 0
+        self._lock = threading.Lock()
+
+    def __len__(self) -> int:
+        return len(self._texts)
+
+    def intern(self, text: str) -> str:
+        interned = self._texts.get(text)
+        if interned is not None:
+            return interned
+        if self.max_bytes <= 0:
+            return text
+        with self._lock:
+            interned = self._texts.setdefault(text, text)
+            if interned is text:
+                self._size += len(text)
+                if self._size > self.max_bytes:
+                    self._texts, self._size = {}, 0
+        return interned
+
+    def clear(self) -> None:
+        with self._lock:
+            self._texts, self._size = {}, 0
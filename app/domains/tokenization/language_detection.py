@@ -0,0 +1,47 @@
+"""
+Language detection
+Maps file names to the language of their tree-sitter grammar, independently of their case.
+
+Well-known file names (Makefile, Dockerfile, CMakeLists.txt, ...) are looked up first, whole. Otherwise the
+longest suffix of the name in the extension table wins, so `types.d.ts` is a declaration before it is a `.ts` file
+and `backup.tar.gz` an archive whatever `.gz` maps to. Both tables are keyed in lowercase: `Main.JAVA` is Java.
+Extensions mapped to None are known not to be source code. A leading dot starts no extension, `.bashrc` has none.
+The tables come from the tokenizer configuration, see tokenizer_config.toml.
+"""
+
+from typing import Dict, Iterator, Optional
+
+from app.domains.tokenization.tokenizer_config import builtin_tokenizer_config
+
+# Language of each lowercase extension of the built-in tokenizer configuration, dots included, compound extensions
+# listed whole, None for ignored extensions
+EXTENSION_LANGUAGES: Dict[str, Optional[str]] = builtin_tokenizer_config().extension_languages()
+
+# Language of the well-known file names of the built-in tokenizer configuration, lowercase, checked before their
+# extension
+FILENAME_LANGUAGES: Dict[str, str] = builtin_tokenizer_config().filename_languages()
+
+
+def extension_candidates(file_name: str) -> Iterator[str]:
+    """Extensions of a file name from the longest to the shortest, lowercase, a leading dot excluded"""
+    name = file_name.lower()
+    start = 1 if name.startswith(".") else 0
+    index = name.find(".", start)
+    while index >= 0:
+        yield name[index:]
+        index = name.find(".", index + 1)
+
+
+def detect_language(
+    file_name: str,
+    extensions: Dict[str, Optional[str]] = EXTENSION_LANGUAGES,
+    filenames: Dict[str, str] = FILENAME_LANGUAGES,
+) -> Optional[str]:
+    """Language of a file from its name, a path being reduced to its last segment, None when unknown or not code"""
+    name = file_name.replace("\\", "/").rsplit("/", 1)[-1]
+    if name.lower() in filenames:
+        return filenames[name.lower()]
+    for extension in extension_candidates(name):
+        if extension in extensions:
+            return extensions[extension]
+    return None
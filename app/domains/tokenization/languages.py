@@ -0,0 +1,248 @@
+"""
+Language registry
+Every language the service compares is a Language registered in a LanguageRegistry, the one list read by language
+detection, tokenization, the validation of tokenizer configuration files and the labels of reports.
+
+The built-in languages are the tree-sitter grammars of tree-sitter-language-pack, their extensions, stop tokens and
+generated file markers kept in tokenizer_config.toml. With LANGUAGE_PLUGINS_ENABLED, each module of LANGUAGE_PLUGINS
+is imported at startup and its register_languages(registry) function adds its own languages, which bring their
+tokenizer and configuration table:
+
+    class Zig(Language):
+        name = "zig"
+        label = "Zig"
+        extensions = (".zig",)
+
+        def create_tokenizer(self):
+            return tokenize_zig  # text -> [{"type", "text", "start", "end"}, ...]
+
+    def register_languages(registry):
+        registry.register(Zig())
+"""
+
+import importlib
+import logging
+import re
+from abc import ABC
+from functools import lru_cache
+from typing import Any, Callable, Dict, Iterator, List, Optional, Set, Tuple
+
+logger = logging.getLogger(__name__)
+
+# Tokens of a source text, with their type, text and first and last rows
+Tokenizer = Callable[[str], List[Dict[str, Any]]]
+
+# Languages of the tree-sitter grammars of tree-sitter-language-pack, with the label reports give them
+# Based on https://pypi.org/project/tree-sitter-language-pack/
+BUILTIN_LANGUAGES = {
+    "ada": "Ada",
+    "asm": "Assembly",
+    "bash": "Bash",
+    "c": "C",
+    "csharp": "C#",
+    "cpp": "C++",
+    "cmake": "CMake",
+    "css": "CSS",
+    "dart": "Dart",
+    "dockerfile": "Dockerfile",
+    "fortran": "Fortran",
+    "go": "Go",
+    "gomod": "Go module",
+    "graphql": "GraphQL",
+    "groovy": "Groovy",
+    "haskell": "Haskell",
+    "html": "HTML",
+    "java": "Java",
+    "javascript": "JavaScript",
+    "json": "JSON",
+    "julia": "Julia",
+    "kotlin": "Kotlin",
+    "lua": "Lua",
+    "make": "Makefile",
+    "markdown": "Markdown",
+    "matlab": "MATLAB",
+    "ocaml": "OCaml",
+    "pascal": "Pascal",
+    "perl": "Perl",
+    "php": "PHP",
+    "python": "Python",
+    "r": "R",
+    "ruby": "Ruby",
+    "rust": "Rust",
+    "scala": "Scala",
+    "solidity": "Solidity",
+    "sql": "SQL",
+    "svelte": "Svelte",
+    "swift": "Swift",
+    "toml": "TOML",
+    "typescript": "TypeScript",
+    "vue": "Vue",
+    "xml": "XML",
+    "yaml": "YAML",
+}
+
+LANGUAGE_NAME_PATTERN = re.compile(r"^[a-z][a-z0-9_]*$")
+
+
+class LanguageRegistrationError(ValueError):
+    """Raised when a language cannot be registered, or a language plugin cannot be loaded"""
+
+
+class Language(ABC):
+    """
+    A language the service compares
+
+    Tree-sitter languages return their grammar, the others a tokenizer. The other attributes are the defaults of
+    the [languages.<name>] table of the tokenizer configuration, which deployment files still override.
+    """
+
+    # Lowercase name, the key of the language in configuration files, fingerprints and statistics
+    name: str = ""
+    # Name shown in reports, the name itself when empty
+    label: str = ""
+    # Lowercase extensions with their dot, and well-known file names
+    extensions: Tuple[str, ...] = ()
+    filenames: Tuple[str, ...] = ()
+    # None follows the FINGERPRINT_NORMALIZATION setting
+    normalization: Optional[str] = None
+    stop_tokens: Tuple[str, ...] = ()
+    generated_markers: Tuple[str, ...] = ()
+
+    def grammar(self) -> Optional[Any]:
+        """Tree-sitter grammar of the language, None for the languages that bring their own tokenizer"""
+        return None
+
+    def create_tokenizer(self) -> Optional[Tokenizer]:
+        """Tokenizer of the language, created once per tokenization service, None to parse with the grammar"""
+        return None
+
+    def is_generated(self, head: str) -> bool:
+        """Whether the start of a decoded file shows it was generated, beyond the configured markers"""
+        return False
+
+    def config_table(self) -> Dict[str, Any]:
+        """Default [languages.<name>] table of the language in the tokenizer configuration"""
+        table: Dict[str, Any] = {"extensions": list(self.extensions), "filenames": list(self.filenames)}
+        if self.normalization is not None:
+            table["normalization"] = self.normalization
+        if self.stop_tokens:
+            table["stop_tokens"] = list(self.stop_tokens)
+        if self.generated_markers:
+            table["generated_markers"] = list(self.generated_markers)
+        return table
+
+
+class TreeSitterLanguage(Language):
+    """Built-in language parsed by its grammar of tree-sitter-language-pack, configured by tokenizer_config.toml"""
+
+    def __init__(self, name: str, label: str):
+        self.name = name
+        self.label = label
+
+    def grammar(self) -> Any:
+        from tree_sitter_language_pack import get_language
+
+        return get_language(self.name)
+
+    def config_table(self) -> Dict[str, Any]:
+        return {}
+
+
+class LanguageRegistry:
+    """Registered languages by name, in their registration order"""
+
+    def __init__(self):
+        self._languages: Dict[str, Language] = {}
+        # Built-in languages, configured by tokenizer_config.toml rather than by their table
+        self._builtin: Set[str] = set()
+
+    def register(self, language: Language, builtin: bool = False) -> None:
+        """
+        Register a language
+
+        Raises:
+            LanguageRegistrationError: If its name is invalid or taken, or a plugin language matches no file
+        """
+        name = language.name
+        if not isinstance(name, str) or not LANGUAGE_NAME_PATTERN.match(name):
+            raise LanguageRegistrationError(f"Invalid language name {name!r}, expected a lowercase identifier")
+        if name in self._languages:
+            raise LanguageRegistrationError(f"Language {name} is already registered")
+        if not builtin and not (language.extensions or language.filenames):
+            raise LanguageRegistrationError(f"Language {name} has neither extensions nor file names")
+
+        self._languages[name] = language
+        if builtin:
+            self._builtin.add(name)
+
+    def get(self, name: Optional[str]) -> Optional[Language]:
+        return self._languages.get(name)
+
+    def names(self) -> Tuple[str, ...]:
+        return tuple(self._languages)
+
+    def is_builtin(self, name: str) -> bool:
+        return name in self._builtin
+
+    def label(self, name: str) -> str:
+        """Name of a language in reports, the name itself for unknown languages"""
+        language = self._languages.get(name)
+        return (language.label or language.name) if language else name
+
+    def config_tables(self) -> Dict[str, Dict[str, Any]]:
+        """Tables the registered languages add to the built-in tokenizer configuration"""
+        return {
+            name: language.config_table() for name, language in self._languages.items() if name not in self._builtin
+        }
+
+    def __contains__(self, name: object) -> bool:
+        return name in self._languages
+
+    def __iter__(self) -> Iterator[Language]:
+        return iter(self._languages.values())
+
+    def __len__(self) -> int:
+        return len(self._languages)
+
+
+def create_builtin_registry() -> LanguageRegistry:
+    """Registry of the built-in languages only"""
+    registry = LanguageRegistry()
+    for name, label in BUILTIN_LANGUAGES.items():
+        registry.register(TreeSitterLanguage(name, label), builtin=True)
+    return registry
+
+
+def load_plugins(registry: LanguageRegistry, modules: str) -> None:
+    """
+    Import each module of a comma-separated list and let its register_languages(registry) add its languages
+
+    Raises:
+        LanguageRegistrationError: If a module cannot be imported, has no register_languages or registers an invalid
+            language, so a deployment never runs without the languages it was configured with
+    """
+    for module_name in (name.strip() for name in modules.split(",")):
+        if not module_name:
+            continue
+        try:
+            module = importlib.import_module(module_name)
+        except ImportError as e:
+            raise LanguageRegistrationError(f"Language plugin {module_name} cannot be imported: {e}")
+        register = getattr(module, "register_languages", None)
+        if not callable(register):
+            raise LanguageRegistrationError(f"Language plugin {module_name} has no register_languages(registry)")
+        before = len(registry)
+        register(registry)
+        logger.info(f"Language plugin {module_name} registered {len(registry) - before} languages")
+
+
+@lru_cache
+def get_language_registry() -> LanguageRegistry:
+    """Registry of this deployment, the built-in languages and those of the plugins when they are enabled"""
+    from app.config.config import get_settings
+
+    settings = get_settings()
+    registry = create_builtin_registry()
+    if settings.language_plugins_enabled:
+        load_plugins(registry, settings.language_plugins)
+    return registry
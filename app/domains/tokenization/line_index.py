@@ -0,0 +1,102 @@
+"""
+Line positions and bounded excerpts of decoded sources
+
+Line starts are found lazily, only up to the last line asked for, and kept as a compact array of at most
+max_lines offsets: a file of one huge line costs a couple of integers, and one of millions of short lines never
+has its lines split into strings. Lines past the cap are found by scanning from the last indexed one.
+
+Files whose average line is longer than a threshold, typically minified bundles, have their line numbers
+reporting next to nothing: their functions and fragments are located by byte offsets into the UTF-8 normalized
+source instead. Excerpts of either kind are cut to a byte budget with an explicit truncation marker.
+"""
+
+from array import array
+from typing import Optional
+
+# Offsets of line starts kept per index, lines past it are located without being indexed
+MAX_INDEXED_LINES = 1 << 20
+# Average line length in characters above which positions are reported by byte offsets, 0 never
+DEFAULT_LONG_LINE_THRESHOLD = 1000
+# Bytes of a code excerpt, marker included, 0 never cuts
+DEFAULT_EXCERPT_MAX_BYTES = 16 * 1024
+# Appended to excerpts cut to their budget, omitted counts characters
+TRUNCATION_MARKER = "\n[... {omitted} more characters truncated]"
+# Bytes counted as one line when sizing a span located by byte offsets
+EQUIVALENT_LINE_BYTES = 80
+# Keys of the byte offsets of the sides of a shared block located by them, end exclusive
+BYTE_OFFSET_KEYS = ("file1_start_byte", "file1_end_byte", "file2_start_byte", "file2_end_byte")
+
+
+class LineIndex:
+    """Lazy index of the line starts of a text, lines split at LF like the normalized sources"""
+
+    def __init__(self, text: str, max_lines: int = MAX_INDEXED_LINES):
+        self.text = text
+        self.max_lines = max(max_lines, 1)
+        self._starts = array("q", [0])
+        # Whether the last line start has been indexed
+        self._complete = False
+
+    def line_start(self, line: int) -> Optional[int]:
+        """Offset of the first character of a 0-based line, None past the last line"""
+        starts = self._starts
+        while len(starts) <= line and not self._complete and len(starts) < self.max_lines:
+            newline = self.text.find("\n", starts[-1])
+            if newline < 0:
+                self._complete = True
+                break
+            starts.append(newline + 1)
+        if line < len(starts):
+            return starts[line]
+        if self._complete:
+            return None
+        offset = starts[-1]
+        for _ in range(line - len(starts) + 1):
+            newline = self.text.find("\n", offset)
+            if newline < 0:
+                return None
+            offset = newline + 1
+        return offset
+
+    def lines(self, start: int, end: int) -> str:
+        """Text of lines start to end exclusive joined by LF, like "\\n".join(text.split("\\n")[start:end])"""
+        start = max(start, 0)
+        begin = self.line_start(start) if end > start else None
+        if begin is None:
+            return ""
+        stop = self.line_start(end)
+        return self.text[begin : len(self.text) if stop is None else stop - 1]
+
+
+def average_line_length(text: str) -> float:
+    """Characters per line, line endings included"""
+    return len(text) / (text.count("\n") + 1)
+
+
+def has_long_lines(text: str, threshold: int = DEFAULT_LONG_LINE_THRESHOLD) -> bool:
+    """Whether positions in a text are reported by byte offsets rather than line numbers"""
+    return threshold > 0 and average_line_length(text) > threshold
+
+
+def clamp_excerpt(text: str, max_bytes: int = DEFAULT_EXCERPT_MAX_BYTES) -> str:
+    """
+    Text cut to max_bytes of UTF-8, marker included, never splitting a character
+
+    Texts within the budget are returned unchanged, as is every text with a budget of 0. A budget smaller than the
+    marker keeps the marker only.
+    """
+    if max_bytes <= 0 or len(text) * 4 <= max_bytes:
+        return text
+    # A character is at least one byte, so the first max_bytes characters cover the budget
+    head = text[:max_bytes].encode("utf-8")
+    if len(text) <= max_bytes and len(head) <= max_bytes:
+        return text
+    reserved = len(TRUNCATION_MARKER.format(omitted=len(text)).encode("utf-8"))
+    kept = head[: max(max_bytes - reserved, 0)].decode("utf-8", errors="ignore")
+    return kept + TRUNCATION_MARKER.format(omitted=len(text) - len(kept))
+
+
+def byte_excerpt(text: str, start_byte: int, end_byte: int, max_bytes: int = DEFAULT_EXCERPT_MAX_BYTES) -> str:
+    """Clamped excerpt of a text between byte offsets of its UTF-8 encoding"""
+    data = text.encode("utf-8")
+    return clamp_excerpt(data[start_byte:end_byte].decode("utf-8", errors="ignore"), max_bytes)
@@ -0,0 +1,173 @@
+import codecs
+import io
+import logging
+import tempfile
+from pathlib import Path
+from typing import Iterator, List, Optional, Tuple
+
+from app.shared.content_hash import HashAlgorithm, hash_chunks
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_CHUNK_SIZE = 64 * 1024
+
+# Tried in order, latin-1 decodes any byte sequence
+ENCODINGS = ("utf-8", "latin-1")
+# Byte order marks stripped when decoding, with the encoding they announce
+BYTE_ORDER_MARKS = ((codecs.BOM_UTF8, "utf-8"), (codecs.BOM_UTF16_LE, "utf-16-le"), (codecs.BOM_UTF16_BE, "utf-16-be"))
+# Longest byte order mark, the bytes read to detect one
+BOM_MAX_BYTES = 3
+# The byte order mark as decoded, left at the start of texts read in text mode with utf-8
+BOM_CHARACTER = "\ufeff"
+
+
+def detect_bom(head) -> Optional[Tuple[str, int]]:
+    """Encoding and length of the byte order mark a file starts with, given its first bytes, None without one"""
+    head = bytes(head[:BOM_MAX_BYTES])
+    for mark, encoding in BYTE_ORDER_MARKS:
+        if head.startswith(mark):
+            return encoding, len(mark)
+    return None
+
+
+def read_bom(path: Path) -> Optional[str]:
+    """Encoding announced by the byte order mark of a file, None without one"""
+    with open(path, "rb") as source:
+        bom = detect_bom(source.read(BOM_MAX_BYTES))
+    return bom[0] if bom else None
+
+
+def _candidates(head) -> List[Tuple[str, int]]:
+    """Encodings to try in order with the bytes to skip first, the one of the byte order mark if any"""
+    bom = detect_bom(head)
+    return ([bom] if bom else []) + [(encoding, 0) for encoding in ENCODINGS]
+
+
+def normalize_newlines(text: str) -> str:
+    """
+    CRLF and lone CR line endings translated to LF, like a text mode read
+
+    Line N of the result is line N of the original split at any of the three endings. Stored files keep their
+    original bytes, which the file content endpoint serves as they were submitted.
+    """
+    if "\r" not in text:
+        return text
+    return text.replace("\r\n", "\n").replace("\r", "\n")
+
+
+def normalize_source(text: str) -> str:
+    """
+    Text without its leading byte order mark and with LF line endings
+
+    Every text that is hashed, tokenized or split into lines goes through this first, so a file has the same
+    content hash, fingerprints and positions whether or not it starts with a BOM and whatever its line endings.
+    A BOM is not a line, removing it shifts no line number.
+    """
+    if text.startswith(BOM_CHARACTER):
+        text = text[len(BOM_CHARACTER) :]
+    return normalize_newlines(text)
+
+
+def decode_source(data) -> str:
+    """
+    Decode file bytes, or any buffer such as a memory map, like a text mode read of the file: the encoding of its
+    byte order mark, stripped, or the first encoding that fits, universal newlines
+    """
+    candidates = _candidates(data)
+    for encoding, skip in candidates[:-1]:
+        try:
+            return normalize_source(str(data[skip:], encoding))
+        except UnicodeDecodeError:
+            continue
+    encoding, skip = candidates[-1]
+    return normalize_source(str(data[skip:], encoding))
+
+
+class StreamingSource:
+    """
+    Source file exposed to tree-sitter as UTF-8 bytes without ever holding it whole in memory
+
+    The file is decoded chunk by chunk into a temporary spool file, with the same result as decode_source: the
+    encoding of its byte order mark, which is left out, else UTF-8 when valid and latin-1 otherwise, and CRLF or CR
+    line endings translated to LF.
+    Tree-sitter then reads the spool through a callback and token texts are sliced from it on demand.
+
+    Use as a context manager, the spool is deleted on exit.
+    """
+
+    def __init__(self, path: Path, chunk_size: int = DEFAULT_CHUNK_SIZE):
+        self.path = Path(path)
+        self.chunk_size = max(chunk_size, 1)
+        self.encoding: Optional[str] = None
+        # Encoding announced by the byte order mark of the file, stripped from the content, None without one
+        self.bom: Optional[str] = None
+        self.size = 0
+        self._spool = None
+
+    def __enter__(self) -> "StreamingSource":
+        self._spool = tempfile.TemporaryFile(prefix="pamp-tokenize-")
+        with open(self.path, "rb") as raw:
+            head = raw.read(BOM_MAX_BYTES)
+        for encoding, skip in _candidates(head):
+            try:
+                self._decode(encoding, skip)
+                self.encoding = encoding
+                self.bom = encoding if skip else None
+                break
+            except UnicodeDecodeError:
+                logger.debug(f"{self.path} is not valid {encoding}, retrying")
+        return self
+
+    def __exit__(self, *exc_info) -> None:
+        if self._spool is not None:
+            self._spool.close()
+            self._spool = None
+
+    def _decode(self, encoding: str, skip: int = 0) -> None:
+        self._spool.seek(0)
+        self._spool.truncate()
+        size = 0
+        # Translates line endings like open(path, "r") does, holding back a trailing CR until the next chunk
+        decoder = io.IncrementalNewlineDecoder(codecs.getincrementaldecoder(encoding)(errors="strict"), translate=True)
+        with open(self.path, "rb") as raw:
+            raw.seek(skip)
+            while True:
+                chunk = raw.read(self.chunk_size)
+                # Bytes of a character split by the chunk boundary stay in the decoder until the next chunk
+                data = decoder.decode(chunk, final=not chunk).encode("utf-8")
+                self._spool.write(data)
+                size += len(data)
+                if not chunk:
+                    break
+        self._spool.flush()
+        self.size = size
+
+    def chunks(self) -> Iterator[bytes]:
+        """Normalized UTF-8 content, chunk by chunk"""
+        offset = 0
+        while offset < self.size:
+            chunk = self.read(offset)
+            offset += len(chunk)
+            yield chunk
+
+    def hash(self, algorithm: HashAlgorithm) -> str:
+        """Hash of the normalized content, equal to the hash of the text read in memory"""
+        return hash_chunks(self.chunks(), algorithm)[0]
+
+    def read(self, byte_offset: int, point=None) -> bytes:
+        """Tree-sitter read callback, returns the chunk starting at byte_offset, empty at the end"""
+        if byte_offset >= self.size:
+            return b""
+        self._spool.seek(byte_offset)
+        return self._spool.read(self.chunk_size)
+
+    def __getitem__(self, item: slice) -> bytes:
+        """Bytes of a node, sliced like the in-memory source"""
+        start, stop, _ = item.indices(self.size)
+        if stop <= start:
+            return b""
+        self._spool.seek(start)
+        return self._spool.read(stop - start)
+
+    def __len__(self) -> int:
+        return self.size
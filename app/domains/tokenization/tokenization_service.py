@@ -1,12 +1,12 @@
 import logging
 import shutil
 import tempfile
+import threading
 from pathlib import Path
-from typing import Any, Dict, List, Optional
+from typing import Any, Dict, FrozenSet, List, Optional
 from uuid import UUID, uuid4
 
 from tree_sitter import Language, Parser, Query
-from tree_sitter_language_pack import get_language, get_parser
 
 from app.domains.detection.similarity_detection_service import SimilarityDetectionService
 from app.domains.repositories.exceptions import (
@@ -18,17 +18,59 @@ from app.domains.repositories.exceptions import (
 from app.domains.repositories.submission_fetcher import SubmissionFetcher
 from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
 from app.domains.tokenization.custom_cache import CustomCache
+from app.domains.tokenization.interning import TokenInterner
+from app.domains.tokenization.language_detection import detect_language
+from app.domains.tokenization.languages import LanguageRegistry, Tokenizer, get_language_registry
+from app.domains.tokenization.line_index import DEFAULT_LONG_LINE_THRESHOLD, LineIndex, has_long_lines
+from app.domains.tokenization.streaming_source import (
+    DEFAULT_CHUNK_SIZE,
+    StreamingSource,
+    decode_source,
+    normalize_source,
+)
+from app.domains.tokenization.tokenizer_config import (
+    GENERATED_MARKER_HEAD_BYTES,
+    TokenizerConfig,
+    get_tokenizer_config,
+)
 from app.shared.exceptions import ValidationException
+from app.shared.timeouts import checkpoint
 
 logger = logging.getLogger(__name__)
 
+# Bump whenever a change could alter the tokens produced for the same file (parser upgrade, extraction logic,
+# node limits): fingerprints cached under a previous version are then never reused
+TOKENIZER_VERSION = "1"
+
 
 class TokenizationService:
-    def __init__(self):
-        """Initialize the tokenization service with tree-sitter parsers"""
+    def __init__(
+        self,
+        long_line_threshold: int = DEFAULT_LONG_LINE_THRESHOLD,
+        tokenizer_config: Optional[TokenizerConfig] = None,
+        language_registry: Optional[LanguageRegistry] = None,
+    ):
+        """
+        Initialize the tokenization service with the tree-sitter parsers and tokenizers of the registered languages
+
+        Args:
+            long_line_threshold: Average line length above which functions are also located by byte offsets
+            tokenizer_config: Per-language configuration, the one of the deployment by default
+            language_registry: Languages to tokenize, the registry of the deployment by default
+        """
+        self.long_line_threshold = long_line_threshold
+        self.tokenizer_config = tokenizer_config or get_tokenizer_config()
+        self.language_registry = language_registry or get_language_registry()
         self.parsers = {}
         self.languages = {}
+        # Tokenizers of the languages without a tree-sitter grammar
+        self.tokenizers: Dict[str, Tokenizer] = {}
         self.language_mapping = {}
+        self.filename_mapping = {}
+        # Files are tokenized concurrently and tree-sitter parsers are not thread-safe
+        self._thread_parsers = threading.local()
+        # Shared by every file tokenized by the service, so the files of a run share their repeated token texts
+        self.interner = TokenInterner()
         self.similarity_service = SimilarityDetectionService()
         self.submission_fetcher = SubmissionFetcher()
         self.cache = CustomCache(
@@ -38,260 +80,84 @@ class TokenizationService:
         self._setup_parsers()
 
     def _setup_language_mapping(self):
-        """Set up file extension to language mapping"""
-        self.language_mapping = {
-            # Ada
-            ".ada": "ada",
-            ".ads": "ada",
-            ".adb": "ada",
-            # Assembly
-            ".asm": "asm",
-            ".s": "asm",
-            ".S": "asm",
-            # Bash/Shell
-            ".sh": "bash",
-            ".bash": "bash",
-            ".zsh": "bash",
-            ".fish": "bash",
-            # C
-            ".c": "c",
-            ".h": "c",
-            # C#
-            ".cs": "csharp",
-            ".csx": "csharp",
-            # C++
-            ".cpp": "cpp",
-            ".cxx": "cpp",
-            ".cc": "cpp",
-            ".c++": "cpp",
-            ".hpp": "cpp",
-            ".hxx": "cpp",
-            ".hh": "cpp",
-            ".h++": "cpp",
-            # CMake
-            ".cmake": "cmake",
-            "CMakeLists.txt": "cmake",
-            # CSS
-            ".css": "css",
-            ".scss": "css",
-            ".sass": "css",
-            ".less": "css",
-            # Dart
-            ".dart": "dart",
-            # Dockerfile
-            "Dockerfile": "dockerfile",
-            ".dockerfile": "dockerfile",
-            # Fortran
-            ".f": "fortran",
-            ".f90": "fortran",
-            ".f95": "fortran",
-            ".f03": "fortran",
-            ".f08": "fortran",
-            ".for": "fortran",
-            ".ftn": "fortran",
-            ".fpp": "fortran",
-            # Go
-            ".go": "go",
-            ".mod": "gomod",
-            ".sum": "gomod",
-            # GraphQL
-            ".graphql": "graphql",
-            ".gql": "graphql",
-            # Groovy
-            ".groovy": "groovy",
-            ".gradle": "groovy",
-            # Haskell
-            ".hs": "haskell",
-            ".lhs": "haskell",
-            # HTML
-            ".html": "html",
-            ".htm": "html",
-            ".xhtml": "html",
-            # Java
-            ".java": "java",
-            ".jsp": "java",
-            # JavaScript
-            ".js": "javascript",
-            ".mjs": "javascript",
-            ".jsx": "javascript",
-            # JSON
-            ".json": "json",
-            ".jsonc": "json",
-            ".json5": "json",
-            # Julia
-            ".jl": "julia",
-            # Kotlin
-            ".kt": "kotlin",
-            ".kts": "kotlin",
-            # Lua
-            ".lua": "lua",
-            # Make
-            "Makefile": "make",
-            "makefile": "make",
-            ".mk": "make",
-            ".make": "make",
-            # Markdown
-            ".md": "markdown",
-            ".markdown": "markdown",
-            ".mdown": "markdown",
-            ".mkd": "markdown",
-            ".mdx": "markdown",
-            # MATLAB
-            ".m": "matlab",
-            ".mlx": "matlab",
-            # OCaml
-            ".ml": "ocaml",
-            ".mli": "ocaml",
-            # Pascal/Delphi/Object Pascal
-            ".pas": "pascal",
-            ".pp": "pascal",
-            ".inc": "pascal",
-            ".dpr": "pascal",
-            ".dpk": "pascal",
-            ".dfm": "pascal",
-            ".fmx": "pascal",
-            # Perl
-            ".pl": "perl",
-            ".pm": "perl",
-            ".perl": "perl",
-            # PHP
-            ".php": "php",
-            ".php3": "php",
-            ".php4": "php",
-            ".php5": "php",
-            ".phtml": "php",
-            # Python
-            ".py": "python",
-            ".pyi": "python",
-            ".pyw": "python",
-            ".pyx": "python",
-            ".pxd": "python",
-            ".pxi": "python",
-            # R
-            ".r": "r",
-            ".R": "r",
-            ".rmd": "r",
-            ".Rmd": "r",
-            # Ruby
-            ".rb": "ruby",
-            ".rbw": "ruby",
-            ".rake": "ruby",
-            ".gemspec": "ruby",
-            "Rakefile": "ruby",
-            "Gemfile": "ruby",
-            # Rust
-            ".rs": "rust",
-            # Scala
-            ".scala": "scala",
-            ".sc": "scala",
-            # Solidity
-            ".sol": "solidity",
-            # SQL
-            ".sql": "sql",
-            ".mysql": "sql",
-            ".pgsql": "sql",
-            ".plsql": "sql",
-            # Svelte
-            ".svelte": "svelte",
-            # Swift
-            ".swift": "swift",
-            # TOML
-            ".toml": "toml",
-            # TypeScript
-            ".ts": "typescript",
-            ".tsx": "typescript",
-            ".mts": "typescript",
-            ".cts": "typescript",
-            # Vue.js
-            ".vue": "vue",
-            # XML
-            ".xml": "xml",
-            ".xsl": "xml",
-            ".xslt": "xml",
-            ".xsd": "xml",
-            ".wsdl": "xml",
-            ".svg": "xml",
-            # YAML
-            ".yaml": "yaml",
-            ".yml": "yaml",
-        }
+        """Set up file extension and file name to language mappings of the tokenizer configuration, both lowercase"""
+        self.language_mapping = self.tokenizer_config.extension_languages()
+        self.filename_mapping = self.tokenizer_config.filename_languages()
+
+    def is_supported_file(self, file_path: Path) -> bool:
+        """Whether detection compares the file, as decided by its name or longest known extension"""
+        return detect_language(Path(file_path).name, self.language_mapping, self.filename_mapping) is not None
 
     def extract_supported_files_from_directory(self, directory: Path) -> List[Path]:
         """
         Extracts all files from the given directory that are supported by the tokenization service.
-        Returns a list of file paths.
+        Returns a list of file paths, ordered by their path relative to the directory whatever the
+        listing order of the filesystem, so that tokens are concatenated in the same order on every run.
+        Files generated by a tool, with a generated file marker of their language, are left out.
         """
         if not directory.is_dir():
             raise ValidationException(f"Invalid directory path: {directory}")
 
         supported_files = []
+        generated = 0
         for file_path in directory.rglob("*"):
-            if file_path.is_file() and file_path.suffix.lower() in self.language_mapping:
+            if file_path.is_file() and self.is_supported_file(file_path):
+                if self.is_generated_file(file_path):
+                    generated += 1
+                    continue
                 supported_files.append(file_path)
+        supported_files.sort(key=lambda file_path: file_path.relative_to(directory).as_posix())
 
-        logger.info(f"Extracted {len(supported_files)} supported files from {directory}")
+        skipped = f", {generated} generated files skipped" if generated else ""
+        logger.info(f"Extracted {len(supported_files)} supported files from {directory}{skipped}")
         return supported_files
 
-    def _setup_parsers(self):
-        """Set up tree-sitter parsers for different languages"""
-        # List of languages supported by tree-sitter-language-pack
-        # Based on https://pypi.org/project/tree-sitter-language-pack/
-        supported_languages = [
-            "ada",
-            "asm",
-            "bash",
-            "c",
-            "csharp",
-            "cpp",
-            "cmake",
-            "css",
-            "dart",
-            "dockerfile",
-            "fortran",
-            "go",
-            "gomod",
-            "graphql",
-            "groovy",
-            "haskell",
-            "html",
-            "java",
-            "javascript",
-            "json",
-            "julia",
-            "kotlin",
-            "lua",
-            "make",
-            "markdown",
-            "matlab",
-            "ocaml",
-            "pascal",
-            "perl",
-            "php",
-            "python",
-            "r",
-            "ruby",
-            "rust",
-            "scala",
-            "solidity",
-            "sql",
-            "svelte",
-            "swift",
-            "toml",
-            "typescript",
-            "vue",
-            "xml",
-            "yaml",
-        ]
+    def is_generated_file(self, file_path: Path) -> bool:
+        """
+        Whether the first lines of a file hold a generated file marker of its language, or the heuristics of a plugin
+        language find it generated. Unreadable files are not.
+        """
+        if not self.may_be_generated(file_path):
+            return False
+        try:
+            with open(file_path, "rb") as f:
+                head = decode_source(f.read(GENERATED_MARKER_HEAD_BYTES))
+        except OSError:
+            return False
+        return self.is_generated_head(file_path, head)
+
+    def may_be_generated(self, file_path: Path) -> bool:
+        """Whether the language of a file has generated file markers or heuristics, so its head must be read"""
+        language = self._detect_language(file_path)
+        if self.tokenizer_config.language(language).generated_markers:
+            return True
+        return language in self.language_registry and not self.language_registry.is_builtin(language)
+
+    def is_generated_head(self, file_path: Path, head: str) -> bool:
+        """Whether the decoded start of a file, its first GENERATED_MARKER_HEAD_BYTES bytes, shows it was generated"""
+        language = self._detect_language(file_path)
+        plugin = None if self.language_registry.is_builtin(language) else self.language_registry.get(language)
+        return self.tokenizer_config.language(language).is_generated(head) or (
+            plugin is not None and plugin.is_generated(head)
+        )
 
+    def _setup_parsers(self):
+        """Set up the tree-sitter parsers, or the tokenizers, of the registered languages"""
         initialized_count = 0
         failed_languages = []
 
-        for language in supported_languages:
+        for registered in self.language_registry:
+            language = registered.name
             try:
-                parser = get_parser(language)
-                lang = get_language(language)
-                self.parsers[language] = parser
-                self.languages[language] = lang
+                tokenizer = registered.create_tokenizer()
+                if tokenizer is not None:
+                    self.tokenizers[language] = tokenizer
+                else:
+                    lang = registered.grammar()
+                    if lang is None:
+                        raise ValueError("the language has neither a grammar nor a tokenizer")
+                    self.parsers[language] = Parser(lang)
+                    self.languages[language] = lang
                 initialized_count += 1
                 logger.debug(f"Initialized parser for {language}")
             except Exception as e:
@@ -305,7 +171,7 @@ class TokenizationService:
                 self.languages[ext] = self.languages[lang]
 
         logger.info(
-            f"Tree-sitter parsers initialized: {initialized_count}/{len(supported_languages)} languages successful"
+            f"Parsers initialized: {initialized_count}/{len(self.language_registry)} languages successful"
         )
         if failed_languages:
             logger.warning(f"Failed to initialize parsers for: {', '.join(failed_languages)}")
@@ -630,7 +496,9 @@ class TokenizationService:
             file_path: Optional file path to detect language
 
         Returns:
-            Dictionary mapping function identifiers to function data
+            Dictionary mapping function identifiers to function data. In files whose average line is longer than
+            long_line_threshold, each function also has its start_byte and end_byte in the UTF-8 normalized text
+            and its code block is the function alone rather than its whole lines.
         """
         try:
             # Detect language
@@ -645,15 +513,17 @@ class TokenizationService:
                 return {}
 
             # Get parser and language
-            parser = self.parsers.get(lang_key)
+            parser = self._get_thread_parser(lang_key)
             language = self.languages.get(lang_key)
 
             if not parser or not language:
                 logger.warning(f"No parser/language available for {lang_key}")
                 return {}
 
-            # Parse the text
-            tree = parser.parse(bytes(text, "utf8"))
+            # Parse the text, line numbers are counted on the normalized text like everywhere else
+            text = normalize_source(text)
+            source_bytes = text.encode("utf8")
+            tree = parser.parse(source_bytes)
             root_node = tree.root_node
 
             try:
@@ -664,7 +534,8 @@ class TokenizationService:
                 return self._extract_functions_fallback(tree, text, lang_key)
 
             functions = {}
-            source_lines = text.split("\n")
+            source_lines = LineIndex(text)
+            by_bytes = has_long_lines(text, self.long_line_threshold)
 
             # Tree-sitter Python API: query.matches() returns a list of tuples
             # Each tuple is (pattern_index, captures_dict) where captures_dict maps capture names to nodes
@@ -691,7 +562,7 @@ class TokenizationService:
                                     end_line = node.end_point[0]
 
                                     # Extract function name from the node
-                                    func_name = self._extract_function_name_from_node(node, text.encode("utf8"))
+                                    func_name = self._extract_function_name_from_node(node, source_bytes)
 
                                     if func_name is None:
                                         # Skip if function name was filtered out (e.g., constructor)
@@ -699,20 +570,21 @@ class TokenizationService:
                                     elif not func_name:
                                         func_name = f"function_{len(functions)}"
 
-                                    # Extract code block
-                                    code_block = self._extract_code_block_from_lines(
-                                        source_lines, start_line, end_line + 1
-                                    )
-
                                     function_id = f"{func_name}_{start_line}"
                                     functions[function_id] = {
                                         "function_name": func_name,
                                         "start_line": start_line,
                                         "end_line": end_line,
-                                        "code_block": code_block,
+                                        "code_block": self._function_code_block(
+                                            node, source_bytes, source_lines, by_bytes
+                                        ),
                                         "node_type": node.type,
                                         "language": lang_key,
                                     }
+                                    if by_bytes:
+                                        functions[function_id].update(
+                                            start_byte=node.start_byte, end_byte=node.end_byte
+                                        )
 
                 except Exception as e:
                     logger.debug(f"Error processing match: {e}")
@@ -755,7 +627,9 @@ class TokenizationService:
             return {}
 
         functions = {}
-        source_lines = text.split("\n")
+        source_bytes = text.encode("utf8")
+        source_lines = LineIndex(text)
+        by_bytes = has_long_lines(text, self.long_line_threshold)
 
         # Common function-related node types across languages
         function_types = {
@@ -787,7 +661,7 @@ class TokenizationService:
                     end_line = node.end_point[0]
 
                     # Try to extract function name
-                    func_name = self._extract_function_name_from_node(node, text.encode("utf8"))
+                    func_name = self._extract_function_name_from_node(node, source_bytes)
                     if func_name is None:
                         # Skip if function name was filtered out (e.g., constructor)
                         continue
@@ -795,18 +669,17 @@ class TokenizationService:
                         # Assign generic name for unnamed functions
                         func_name = f"function_{len(functions)}"
 
-                    # Extract code block
-                    code_block = self._extract_code_block_from_lines(source_lines, start_line, end_line + 1)
-
                     function_id = f"{func_name}_{start_line}"
                     functions[function_id] = {
                         "function_name": func_name,
                         "start_line": start_line,
                         "end_line": end_line,
-                        "code_block": code_block,
+                        "code_block": self._function_code_block(node, source_bytes, source_lines, by_bytes),
                         "node_type": node.type,
                         "language": language,
                     }
+                    if by_bytes:
+                        functions[function_id].update(start_byte=node.start_byte, end_byte=node.end_byte)
 
                     logger.debug(f"Extracted function '{func_name}' at line {start_line} via fallback method")
                 except Exception as e:
@@ -888,6 +761,12 @@ class TokenizationService:
 
         return False
 
+    def _function_code_block(self, node, source_bytes: bytes, source_lines: LineIndex, by_bytes: bool) -> str:
+        """Code of a function node: its whole lines, or only its own bytes in files with long lines"""
+        if by_bytes:
+            return source_bytes[node.start_byte : node.end_byte].decode("utf8", errors="replace")
+        return source_lines.lines(node.start_point[0], node.end_point[0] + 1)
+
     def _extract_code_block_from_lines(self, source_lines: List[str], start_line: int, end_line: int) -> str:
         """Extract code block from source lines between start and end line numbers."""
         if not source_lines:
@@ -910,35 +789,12 @@ class TokenizationService:
     def _detect_language(self, file_path: Optional[Path] = None, content: Optional[str] = None) -> str:
         """Detect the programming language based on file extension or content"""
         if file_path:
-            # Check for exact filename matches first (e.g., Dockerfile, Makefile)
-            filename = file_path.name
-            if filename in self.language_mapping:
-                detected_lang = self.language_mapping[filename]
-                logger.debug(f"Detected language by filename '{filename}': {detected_lang}")
-                return detected_lang
-
-            # Check file extension
-            suffix = file_path.suffix.lower()
-            if suffix in self.language_mapping:
-                detected_lang = self.language_mapping[suffix]
-                logger.debug(f"Detected language by extension '{suffix}': {detected_lang}")
+            # Well-known file names first (e.g., Dockerfile, Makefile), then the longest known extension
+            detected_lang = detect_language(file_path.name, self.language_mapping, self.filename_mapping)
+            if detected_lang:
+                logger.debug(f"Detected language by file name '{file_path.name}': {detected_lang}")
                 return detected_lang
 
-            # Special cases for files without extensions
-            if not suffix:
-                filename_lower = filename.lower()
-                special_files = {
-                    "dockerfile": "dockerfile",
-                    "makefile": "make",
-                    "rakefile": "ruby",
-                    "gemfile": "ruby",
-                    "cmakelists.txt": "cmake",
-                }
-                if filename_lower in special_files:
-                    detected_lang = special_files[filename_lower]
-                    logger.debug(f"Detected language by special filename '{filename}': {detected_lang}")
-                    return detected_lang
-
         # Content-based detection as fallback (simplified heuristics)
         if content:
             content_lower = content.lower().strip()
@@ -973,11 +829,12 @@ class TokenizationService:
 
     def get_supported_languages(self) -> List[str]:
         """Get list of all supported programming languages"""
-        return list(set(self.language_mapping.values()))
+        languages = set(self.language_mapping.values()) | set(self.filename_mapping.values())
+        return list(languages - {None})
 
     def get_supported_extensions(self) -> List[str]:
         """Get list of all supported file extensions"""
-        return list(self.language_mapping.keys())
+        return [extension for extension, language in self.language_mapping.items() if language]
 
     def _extract_relative_path(self, file_path: Path, temp_base_path: Optional[Path] = None) -> str:
         """
@@ -1100,6 +957,7 @@ class TokenizationService:
         file_path: Optional[Path] = None,
         submission_id: Optional[UUID] = None,
         project_root_path: Optional[Path] = None,
+        raise_errors: bool = False,
     ) -> List[Dict[str, Any]]:
         """
         Tokenizes the input text into a list of tokens using tree-sitter.
@@ -1109,6 +967,7 @@ class TokenizationService:
             file_path: Full path to the file being tokenized
             submission_id: UUID of the submission for cache key
             project_root_path: Root path of the extracted project (optional, used for relative path calculation)
+            raise_errors: Raise tokenization failures instead of returning no token
         """
         try:
             # CACHE DISABLED FOR PERFORMANCE REASON -> it was ruining everything sadly
@@ -1135,26 +994,29 @@ class TokenizationService:
 
             # Detect language
             lang_key = self._detect_language(file_path)
+            if lang_key in self.tokenizers:
+                return self._tokenize_with_plugin(lang_key, normalize_source(text))
 
             # Try to get parser by language name first, then by extension
-            parser = self.parsers.get(lang_key)
+            parser = self._get_thread_parser(lang_key)
             if not parser and file_path:
                 # Try the detected language mapping
                 detected_lang = self.language_mapping.get(lang_key)
                 if detected_lang:
-                    parser = self.parsers.get(detected_lang)
+                    parser = self._get_thread_parser(detected_lang)
 
             if not parser:
                 logger.warning(f"No parser available for {lang_key}, skipping tokenization")
                 return []
 
-            # Parse the text
+            # Parse the text, token rows are counted on the normalized text like everywhere else
+            text = normalize_source(text)
             tree = parser.parse(bytes(text, "utf8"))
             root_node = tree.root_node
 
             # Extract tokens
             tokens = []
-            self._extract_tokens(root_node, text.encode("utf8"), tokens)
+            self._extract_tokens(root_node, text.encode("utf8"), tokens, self._stop_tokens(lang_key))
 
             logger.debug(f"Tokenized {len(tokens)} tokens for language: {lang_key}")
 
@@ -1166,11 +1028,93 @@ class TokenizationService:
             return tokens
 
         except Exception as e:
+            if raise_errors:
+                raise
             logger.error(f"Tokenization failed for {lang_key}: {e}")
             return []
 
-    def _extract_tokens(self, node, source_code: bytes, tokens: List[Dict[str, Any]]):
-        """Iteratively extract tokens from the syntax tree to avoid recursion limits"""
+    def tokenize_file(
+        self, file_path: Path, chunk_size: int = DEFAULT_CHUNK_SIZE, raise_errors: bool = False
+    ) -> List[Dict[str, Any]]:
+        """
+        Tokenize a file streamed from disk, producing the same tokens as tokenize() on its text
+
+        Peak memory is bounded by the read buffer plus the produced tokens, whatever the size of the file.
+        """
+        with StreamingSource(file_path, chunk_size) as source:
+            return self.tokenize_source(source, file_path, raise_errors)
+
+    def tokenize_source(
+        self, source: StreamingSource, file_path: Optional[Path] = None, raise_errors: bool = False
+    ) -> List[Dict[str, Any]]:
+        """Tokenize an opened streaming source, raising its failures with raise_errors instead of returning []"""
+        lang_key = self._detect_language(file_path)
+        try:
+            if lang_key in self.tokenizers:
+                return self._tokenize_with_plugin(lang_key, b"".join(source.chunks()).decode("utf8"))
+
+            parser = self._get_thread_parser(lang_key)
+            if not parser and file_path:
+                detected_lang = self.language_mapping.get(lang_key)
+                if detected_lang:
+                    parser = self._get_thread_parser(detected_lang)
+
+            if not parser:
+                logger.warning(f"No parser available for {lang_key}, skipping tokenization")
+                return []
+
+            tree = parser.parse(source.read)
+            tokens = []
+            self._extract_tokens(tree.root_node, source, tokens, self._stop_tokens(lang_key))
+
+            logger.debug(f"Tokenized {len(tokens)} tokens from {source.size} streamed bytes for language: {lang_key}")
+            return tokens
+
+        except Exception as e:
+            if raise_errors:
+                raise
+            logger.error(f"Streaming tokenization failed for {lang_key}: {e}")
+            return []
+
+    def _get_thread_parser(self, language: str) -> Optional[Parser]:
+        """Get the parser of a language owned by the calling thread, created on first use"""
+        if language not in self.parsers:
+            return None
+        parsers = getattr(self._thread_parsers, "parsers", None)
+        if parsers is None:
+            parsers = self._thread_parsers.parsers = {}
+        if language not in parsers:
+            parsers[language] = Parser(self.languages[language])
+        return parsers[language]
+
+    def _tokenize_with_plugin(self, language: str, text: str) -> List[Dict[str, Any]]:
+        """Tokens of the tokenizer of a plugin language, stop token types left out and texts interned like parsed"""
+        intern = self.interner.intern
+        stop_tokens = self._stop_tokens(language)
+        tokens = [
+            {**token, "text": intern(token["text"])}
+            for token in self.tokenizers[language](text)
+            if token["type"] not in stop_tokens
+        ]
+        logger.debug(f"Tokenized {len(tokens)} tokens for language: {language}")
+        return tokens
+
+    def _stop_tokens(self, language: str) -> FrozenSet[str]:
+        """Token types of a language left out of its tokens"""
+        return self.tokenizer_config.language(language).stop_tokens
+
+    def _extract_tokens(
+        self, node, source_code, tokens: List[Dict[str, Any]], stop_tokens: FrozenSet[str] = frozenset()
+    ):
+        """
+        Iteratively extract tokens from the syntax tree to avoid recursion limits
+
+        source_code is the UTF-8 source, as bytes or a StreamingSource sliced by node offsets. Token texts are
+        interned and node types looked up once per node kind, so repeated tokens share their strings. Nodes of
+        a stop token type are left out with their children.
+        """
+        intern = self.interner.intern
+        node_types: Dict[int, str] = {}
         # Use iterative approach with a stack to avoid recursion depth issues
         nodes_to_process = [node]
         processed_count = 0
@@ -1179,13 +1123,20 @@ class TokenizationService:
         while nodes_to_process and processed_count < max_nodes:
             current_node = nodes_to_process.pop()
             processed_count += 1
+            if not processed_count % 1024:
+                checkpoint()
 
             # Add current node as token if it has meaningful content and is named
             if current_node.start_byte < current_node.end_byte and current_node.is_named:
-                token_text = source_code[current_node.start_byte : current_node.end_byte].decode("utf8")
+                node_type = node_types.get(current_node.kind_id)
+                if node_type is None:
+                    node_type = node_types[current_node.kind_id] = current_node.type
+                if node_type in stop_tokens:
+                    continue
+                token_text = intern(source_code[current_node.start_byte : current_node.end_byte].decode("utf8"))
 
                 token = {
-                    "type": current_node.type,
+                    "type": node_type,
                     "text": token_text,
                     "start": current_node.start_point[0],  # Just row number
                     "end": current_node.end_point[0],  # Just row number
@@ -0,0 +1,460 @@
+"""
+Per-language tokenizer configuration
+
+The built-in configuration, tokenizer_config.toml next to this module, is merged with the file at
+TOKENIZER_CONFIG_PATH when one is set: tables key by key, lists and values replaced. Each file is validated before
+the merge and mistakes are reported with the file and the dotted key at fault, syntax errors with their line and
+column. Unknown keys, languages and token classes are errors, never silently ignored.
+The languages are those of the language registry, see languages.py: the tables of plugin languages are merged
+over the built-in configuration before the deployment file, which configures them like the built-in ones.
+"""
+
+import difflib
+import hashlib
+import json
+import logging
+import threading
+import tomllib
+from dataclasses import dataclass, field
+from functools import cached_property, lru_cache
+from pathlib import Path
+from typing import Any, Dict, FrozenSet, Iterable, List, Optional, Tuple
+
+from app.domains.fingerprints.fingerprint_models import NormalizationLevel
+from app.domains.tokenization.languages import (
+    BUILTIN_LANGUAGES,
+    LanguageRegistry,
+    create_builtin_registry,
+    get_language_registry,
+)
+
+logger = logging.getLogger(__name__)
+
+BUILTIN_CONFIG_PATH = Path(__file__).with_name("tokenizer_config.toml")
+
+# Names of the built-in languages
+SUPPORTED_LANGUAGES = tuple(BUILTIN_LANGUAGES)
+
+# Token classes with type markers, in the order token types are classified
+TOKEN_CLASSES = ("identifier", "literal", "comment")
+# Class of the token types matching no marker
+OTHER_TOKEN_CLASS = "other"
+WEIGHTED_TOKEN_CLASSES = TOKEN_CLASSES + (OTHER_TOKEN_CLASS,)
+
+TOP_LEVEL_KEYS = ("ignored_extensions", "token_classes", "defaults", "languages")
+DEFAULT_KEYS = ("stop_tokens", "normalization", "generated_markers", "generated_marker_lines", "token_weights")
+LANGUAGE_KEYS = ("extensions", "filenames") + DEFAULT_KEYS
+
+DEFAULT_GENERATED_MARKER_LINES = 5
+# Bytes read from the start of a file to look for generated file markers
+GENERATED_MARKER_HEAD_BYTES = 8192
+
+
+class TokenizerConfigError(ValueError):
+    """Raised when a tokenizer configuration file cannot be read or is invalid, with every mistake found"""
+
+    def __init__(self, source: str, errors: List[str]):
+        self.source = source
+        self.errors = errors
+        super().__init__(f"Invalid tokenizer configuration {source}: " + "; ".join(errors))
+
+
+@dataclass(frozen=True)
+class LanguageConfig:
+    """Effective configuration of one language, its table merged over [defaults]"""
+
+    extensions: Tuple[str, ...] = ()
+    filenames: Tuple[str, ...] = ()
+    stop_tokens: FrozenSet[str] = frozenset()
+    # None follows the FINGERPRINT_NORMALIZATION setting
+    normalization: Optional[str] = None
+    generated_markers: Tuple[str, ...] = ()
+    generated_marker_lines: int = DEFAULT_GENERATED_MARKER_LINES
+    token_weights: Dict[str, int] = field(default_factory=lambda: dict.fromkeys(WEIGHTED_TOKEN_CLASSES, 1))
+
+    @property
+    def weighted(self) -> bool:
+        """Whether any token class counts other than once in the fingerprints"""
+        return any(weight != 1 for weight in self.token_weights.values())
+
+    def is_generated(self, content: str) -> bool:
+        """Whether the first lines of a decoded file, within its first bytes, hold a generated file marker"""
+        if not self.generated_markers:
+            return False
+        head = content[:GENERATED_MARKER_HEAD_BYTES]
+        lines = head.split("\n", self.generated_marker_lines)[: self.generated_marker_lines]
+        return any(marker in line for line in lines for marker in self.generated_markers)
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "extensions": list(self.extensions),
+            "filenames": list(self.filenames),
+            "stop_tokens": sorted(self.stop_tokens),
+            "normalization": self.normalization,
+            "generated_markers": list(self.generated_markers),
+            "generated_marker_lines": self.generated_marker_lines,
+            "token_weights": dict(self.token_weights),
+        }
+
+
+@dataclass
+class TokenizerConfig:
+    """Tokenizer configuration of every supported language"""
+
+    token_classes: Dict[str, Tuple[str, ...]]
+    ignored_extensions: Tuple[str, ...]
+    defaults: LanguageConfig
+    languages: Dict[str, LanguageConfig]
+    # Files merged into this configuration, the built-in one first
+    sources: Tuple[str, ...] = ()
+    _classes: Dict[str, str] = field(default_factory=dict, repr=False, compare=False)
+    _versions: Dict[Tuple[str, str], str] = field(default_factory=dict, repr=False, compare=False)
+
+    def language(self, language: Optional[str]) -> LanguageConfig:
+        """Configuration of a language, the defaults for an unknown one"""
+        return self.languages.get(language, self.defaults)
+
+    def extension_languages(self) -> Dict[str, Optional[str]]:
+        """Language of each extension, None for the ignored ones"""
+        mapping: Dict[str, Optional[str]] = {
+            extension: language for language, config in self.languages.items() for extension in config.extensions
+        }
+        mapping.update(dict.fromkeys(self.ignored_extensions))
+        return mapping
+
+    def filename_languages(self) -> Dict[str, str]:
+        """Language of each well-known file name"""
+        return {name: language for language, config in self.languages.items() for name in config.filenames}
+
+    def token_class(self, token_type: str) -> str:
+        """Class of a token type, remembered per type as tokens repeat a handful of types"""
+        token_class = self._classes.get(token_type)
+        if token_class is None:
+            lowered_type = token_type.lower()
+            token_class = next(
+                (name for name in TOKEN_CLASSES if any(m in lowered_type for m in self.token_classes.get(name, ()))),
+                OTHER_TOKEN_CLASS,
+            )
+            self._classes[token_type] = token_class
+        return token_class
+
+    def digest(self, language: Optional[str]) -> str:
+        """Hash of the configuration the tokens and fingerprints of a language depend on"""
+        config = self.language(language)
+        document = {
+            "token_classes": {name: list(markers) for name, markers in self.token_classes.items()},
+            "stop_tokens": sorted(config.stop_tokens),
+            "token_weights": config.token_weights,
+        }
+        return hashlib.sha256(json.dumps(document, sort_keys=True).encode("utf-8")).hexdigest()
+
+    def tokenizer_version(self, language: Optional[str], base_version: str) -> str:
+        """
+        Tokenizer version of the fingerprints of a language, the base version while its configuration is the
+        built-in one, suffixed with the hash of its configuration otherwise so cached fingerprints are not reused
+        """
+        version = self._versions.get((language, base_version))
+        if version is None:
+            digest = self.digest(language)
+            builtin = digest == builtin_tokenizer_config().digest(language)
+            version = base_version if builtin else f"{base_version}+{digest[:8]}"
+            self._versions[(language, base_version)] = version
+        return version
+
+    @cached_property
+    def revision(self) -> Optional[str]:
+        """Short hash of what the fingerprints of every language depend on, None for the built-in configuration"""
+        document = {
+            language: [self.digest(language), config.normalization] for language, config in self.languages.items()
+        }
+        builtin = builtin_tokenizer_config()
+        if self is builtin or all(
+            digest == builtin.digest(language) and normalization == builtin.language(language).normalization
+            for language, (digest, normalization) in document.items()
+        ):
+            return None
+        return hashlib.sha256(json.dumps(document, sort_keys=True).encode("utf-8")).hexdigest()[:8]
+
+    def to_dict(self, base_version: str) -> Dict[str, Any]:
+        """Effective configuration, with the tokenizer version of each language"""
+        return {
+            "sources": list(self.sources),
+            "token_classes": {name: list(markers) for name, markers in self.token_classes.items()},
+            "ignored_extensions": list(self.ignored_extensions),
+            "languages": {
+                language: {**config.to_dict(), "tokenizer_version": self.tokenizer_version(language, base_version)}
+                for language, config in sorted(self.languages.items())
+            },
+        }
+
+
+def _suggestion(name: str, expected: Iterable[str]) -> str:
+    expected = list(expected)
+    matches = difflib.get_close_matches(name, expected, n=1)
+    if matches:
+        return f", did you mean '{matches[0]}'?"
+    return f", expected one of {', '.join(expected)}"
+
+
+class _Validator:
+    """Collects the mistakes of one configuration document, located by dotted keys"""
+
+    def __init__(self, languages: Tuple[str, ...] = SUPPORTED_LANGUAGES):
+        self.errors: List[str] = []
+        self.languages = languages
+
+    def error(self, location: str, problem: str) -> None:
+        self.errors.append(f"{location}: {problem}")
+
+    def table(self, document: dict, key: str, location: str) -> dict:
+        value = document.get(key, {})
+        if not isinstance(value, dict):
+            self.error(location, f"must be a table, got {type(value).__name__}")
+            return {}
+        return value
+
+    def keys(self, table: dict, expected: Iterable[str], location: str, kind: str = "key") -> None:
+        expected = list(expected)
+        for key in table:
+            if key not in expected:
+                self.error(f"{location}.{key}" if location else key, f"unknown {kind}{_suggestion(key, expected)}")
+
+    def strings(self, value: Any, location: str) -> List[str]:
+        if not isinstance(value, list) or not all(isinstance(item, str) and item for item in value):
+            self.error(location, "must be a list of non-empty strings")
+            return []
+        return value
+
+    def integer(self, value: Any, location: str, minimum: int) -> None:
+        if isinstance(value, bool) or not isinstance(value, int) or value < minimum:
+            self.error(location, f"must be an integer of at least {minimum}, got {value!r}")
+
+    def extensions(self, value: Any, location: str) -> None:
+        for extension in self.strings(value, location):
+            if not extension.startswith(".") or len(extension) < 2 or extension != extension.lower():
+                self.error(location, f"'{extension}' must be a lowercase extension starting with its dot")
+
+    def filenames(self, value: Any, location: str) -> None:
+        for name in self.strings(value, location):
+            if name.startswith(".") or name != name.lower():
+                self.error(location, f"'{name}' must be a lowercase file name, extensions go in extensions")
+
+    def language_table(self, table: dict, location: str, expected: Tuple[str, ...]) -> None:
+        for key in table:
+            if key in LANGUAGE_KEYS and key not in expected:
+                self.error(f"{location}.{key}", "is only set per language, in a [languages.<name>] table")
+            elif key not in expected:
+                self.error(f"{location}.{key}", f"unknown key{_suggestion(key, expected)}")
+
+        if "extensions" in table and "extensions" in expected:
+            self.extensions(table["extensions"], f"{location}.extensions")
+        if "filenames" in table and "filenames" in expected:
+            self.filenames(table["filenames"], f"{location}.filenames")
+        if "stop_tokens" in table:
+            self.strings(table["stop_tokens"], f"{location}.stop_tokens")
+        if "generated_markers" in table:
+            self.strings(table["generated_markers"], f"{location}.generated_markers")
+        if "generated_marker_lines" in table:
+            self.integer(table["generated_marker_lines"], f"{location}.generated_marker_lines", 1)
+        if "normalization" in table:
+            levels = [level.value for level in NormalizationLevel]
+            normalization = table["normalization"]
+            if normalization not in levels:
+                self.error(
+                    f"{location}.normalization",
+                    f"unknown normalization {normalization!r}{_suggestion(str(normalization), levels)}",
+                )
+        if "token_weights" in table:
+            weights = self.table(table, "token_weights", f"{location}.token_weights")
+            self.keys(weights, WEIGHTED_TOKEN_CLASSES, f"{location}.token_weights", "token class")
+            for name, weight in weights.items():
+                if name in WEIGHTED_TOKEN_CLASSES:
+                    self.integer(weight, f"{location}.token_weights.{name}", 0)
+
+    def document(self, document: dict) -> None:
+        self.keys(document, TOP_LEVEL_KEYS, "")
+        if "ignored_extensions" in document:
+            self.extensions(document["ignored_extensions"], "ignored_extensions")
+
+        token_classes = self.table(document, "token_classes", "token_classes")
+        for name, markers in token_classes.items():
+            location = f"token_classes.{name}"
+            if name == OTHER_TOKEN_CLASS:
+                self.error(location, "is the class of the token types matching no marker, it takes no markers")
+            elif name not in TOKEN_CLASSES:
+                self.error(location, f"unknown token class{_suggestion(name, TOKEN_CLASSES)}")
+            elif any(marker != marker.lower() for marker in self.strings(markers, location)):
+                self.error(location, "markers are found in lowercase token types, they must be lowercase")
+
+        self.language_table(self.table(document, "defaults", "defaults"), "defaults", DEFAULT_KEYS)
+        for language, table in self.table(document, "languages", "languages").items():
+            location = f"languages.{language}"
+            if language not in self.languages:
+                self.error(location, f"unknown language{_suggestion(language, self.languages)}")
+            elif not isinstance(table, dict):
+                self.error(location, f"must be a table, got {type(table).__name__}")
+            else:
+                self.language_table(table, location, LANGUAGE_KEYS)
+
+
+def read_config_file(path: Path, languages: Tuple[str, ...] = SUPPORTED_LANGUAGES) -> dict:
+    """
+    Read and validate one configuration file, configuring the given languages
+
+    Raises:
+        TokenizerConfigError: If the file cannot be read, is not valid TOML or has mistakes
+    """
+    try:
+        with open(path, "rb") as f:
+            document = tomllib.load(f)
+    except FileNotFoundError:
+        raise TokenizerConfigError(str(path), ["file not found"])
+    except OSError as e:
+        raise TokenizerConfigError(str(path), [f"cannot be read: {e.strerror}"])
+    except tomllib.TOMLDecodeError as e:
+        raise TokenizerConfigError(str(path), [str(e)])
+
+    validator = _Validator(languages)
+    validator.document(document)
+    if validator.errors:
+        raise TokenizerConfigError(str(path), validator.errors)
+    return document
+
+
+def plugin_document(registry: LanguageRegistry) -> dict:
+    """
+    Tables of the languages of plugins, validated like the [languages.<name>] tables of a file
+
+    Raises:
+        TokenizerConfigError: If the table of a language is invalid
+    """
+    tables = registry.config_tables()
+    for name, table in tables.items():
+        validator = _Validator(registry.names())
+        validator.language_table(table, f"languages.{name}", LANGUAGE_KEYS)
+        if validator.errors:
+            raise TokenizerConfigError(f"of language plugin {name}", validator.errors)
+    return {"languages": tables} if tables else {}
+
+
+def merge(base: dict, override: dict) -> dict:
+    """Merge a document over another, tables key by key, any other value replaced"""
+    merged = dict(base)
+    for key, value in override.items():
+        if isinstance(value, dict) and isinstance(merged.get(key), dict):
+            merged[key] = merge(merged[key], value)
+        else:
+            merged[key] = value
+    return merged
+
+
+def _language_config(defaults: dict, table: dict) -> LanguageConfig:
+    values = merge(defaults, table)
+    weights = values.get("token_weights", {})
+    return LanguageConfig(
+        extensions=tuple(values.get("extensions", ())),
+        filenames=tuple(values.get("filenames", ())),
+        stop_tokens=frozenset(values.get("stop_tokens", ())),
+        normalization=values.get("normalization"),
+        generated_markers=tuple(values.get("generated_markers", ())),
+        generated_marker_lines=values.get("generated_marker_lines", DEFAULT_GENERATED_MARKER_LINES),
+        token_weights={name: weights.get(name, 1) for name in WEIGHTED_TOKEN_CLASSES},
+    )
+
+
+def build_config(
+    document: dict, sources: Tuple[str, ...], languages: Tuple[str, ...] = SUPPORTED_LANGUAGES
+) -> TokenizerConfig:
+    """
+    Build the configuration of a merged document, checking that no extension or file name has two languages
+
+    Raises:
+        TokenizerConfigError: If an extension or file name is given to two languages, or an extension is also ignored
+    """
+    defaults = document.get("defaults", {})
+    languages = {
+        language: _language_config(defaults, document.get("languages", {}).get(language, {}))
+        for language in languages
+    }
+    ignored_extensions = tuple(document.get("ignored_extensions", ()))
+
+    errors = []
+    for key, mapped in (("extensions", {extension: None for extension in ignored_extensions}), ("filenames", {})):
+        for language, config in languages.items():
+            for name in getattr(config, key):
+                if name in mapped:
+                    other = f"mapped to {mapped[name]}" if mapped[name] else "in ignored_extensions"
+                    errors.append(f"languages.{language}.{key}: '{name}' is also {other}")
+                else:
+                    mapped[name] = language
+    if errors:
+        raise TokenizerConfigError(" merged with ".join(sources), errors)
+
+    return TokenizerConfig(
+        token_classes={name: tuple(markers) for name, markers in document.get("token_classes", {}).items()},
+        ignored_extensions=ignored_extensions,
+        defaults=_language_config(defaults, {}),
+        languages=languages,
+        sources=sources,
+    )
+
+
+def load_tokenizer_config(path: Optional[str] = None, registry: Optional[LanguageRegistry] = None) -> TokenizerConfig:
+    """
+    Load the built-in configuration with the tables of the plugin languages of a registry, merged with the file at
+    path when given. Without registry only the built-in languages are configured.
+
+    Raises:
+        TokenizerConfigError: If either file, a plugin table, or their merge, is invalid
+    """
+    registry = registry or create_builtin_registry()
+    languages = registry.names()
+    document = merge(read_config_file(BUILTIN_CONFIG_PATH), plugin_document(registry))
+    sources = (str(BUILTIN_CONFIG_PATH),)
+    if path:
+        document = merge(document, read_config_file(Path(path), languages))
+        sources += (str(path),)
+    return build_config(document, sources, languages)
+
+
+@lru_cache
+def builtin_tokenizer_config() -> TokenizerConfig:
+    """Built-in configuration, what tokenizer versions are relative to"""
+    return load_tokenizer_config()
+
+
+def load_deployment_tokenizer_config(settings) -> TokenizerConfig:
+    """Configuration of the settings, the built-in one with the plugin languages merged with TOKENIZER_CONFIG_PATH"""
+    path = settings.tokenizer_config_path
+    registry = get_language_registry()
+    if not path and registry.names() == SUPPORTED_LANGUAGES:
+        return builtin_tokenizer_config()
+    config = load_tokenizer_config(path, registry)
+    if path:
+        logger.info(f"Tokenizer configuration loaded from {path}")
+    return config
+
+
+_deployment_config: Optional[TokenizerConfig] = None
+_deployment_lock = threading.Lock()
+
+
+def get_tokenizer_config() -> TokenizerConfig:
+    """Configuration of this deployment, loaded once then replaced as a whole by configuration reloads"""
+    global _deployment_config
+
+    if _deployment_config is None:
+        from app.config.config import get_settings
+
+        with _deployment_lock:
+            if _deployment_config is None:
+                _deployment_config = load_deployment_tokenizer_config(get_settings())
+    return _deployment_config
+
+
+def replace_tokenizer_config(config: TokenizerConfig) -> None:
+    """Swap the configuration of this deployment, services built before keep the one they were built with"""
+    global _deployment_config
+
+    with _deployment_lock:
+        _deployment_config = config
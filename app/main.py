@@ -6,41 +6,46 @@ from fastapi import FastAPI
 from fastapi.middleware.cors import CORSMiddleware
 
 from app.config.config import get_settings
+from app.domains.admin.admin_controller import router as admin_router
+from app.domains.audit.audit_controller import router as audit_router
+from app.domains.callbacks.callbacks_controller import router as callbacks_router
+from app.domains.corpus.corpus_controller import router as corpus_router
 from app.domains.detection.router import router as detection_router
+from app.domains.fingerprints.fingerprint_controller import router as fingerprint_router
 
 # Import domain routers
+from app.domains.health.metrics_router import router as metrics_router
 from app.domains.health.router import router as health_router
+from app.domains.notifications.notifications_controller import router as notifications_router
+from app.domains.reports.reports_controller import router as reports_router
+from app.domains.retention.retention_controller import router as retention_router
+from app.domains.runs.runs_controller import router as runs_router
+from app.domains.schedules.schedules_controller import router as schedules_router
+from app.domains.submissions.submission_debug_controller import router as submission_debug_router
 from app.domains.submissions.submissions_controller import router as submissions_router
-from app.shared.database import create_db_and_tables
+from app.shared.config_reload import CONFIG_RELOADER
+from app.shared.database import migrate_database
+from app.shared.metrics import MetricsMiddleware
+from app.shared.shutdown import SHUTDOWN, DrainingMiddleware
+from app.shared.tenancy import TenancyMiddleware
+from app.shared.tracing import RequestIdMiddleware, configure_logging, install_error_handlers
 
 settings = get_settings()
 
 
 # Configure logging
 def setup_logging():
-    """Configure logging for the application"""
-    log_level = logging.DEBUG if settings.debug else logging.INFO
-
-    # Configure the root logger
-    logging.basicConfig(
-        level=log_level,
-        format="%(asctime)s - %(name)s - %(levelname)s - %(message)s",
-        handlers=[
+    """Configure logging for the application, JSON lines correlated by request and run unless LOG_FORMAT=text"""
+    configure_logging(
+        settings,
+        [
             logging.StreamHandler(sys.stdout),
             logging.FileHandler("app.log") if not settings.debug else logging.NullHandler(),
         ],
     )
 
-    # Set specific loggers to appropriate levels
-    logging.getLogger("uvicorn").setLevel(logging.INFO)
-    logging.getLogger("uvicorn.access").setLevel(logging.INFO)
-    logging.getLogger("fastapi").setLevel(logging.INFO)
-
-    # Create and configure the main application logger
-    logger = logging.getLogger("app")
-    logger.setLevel(log_level)
-
-    return logger
+    # Create the main application logger
+    return logging.getLogger("app")
 
 
 # Setup logging
@@ -52,22 +57,128 @@ async def lifespan(app: FastAPI):
     """
     Application lifespan events
     """
-    # Startup: Create database tables
-    create_db_and_tables()
-    logger.info("🚀 Database tables created successfully")
+    # Startup: Refuse to start with an invalid tokenizer configuration, the error names each key at fault
+    from app.domains.tokenization.tokenizer_config import get_tokenizer_config
+
+    get_tokenizer_config()
+
+    # Apply pending schema migrations, refusing to start against a newer schema
+    if settings.database_migrate_on_startup:
+        migrate_database()
+        logger.info("🚀 Database schema migrated successfully")
+
+    # Drain on SIGTERM before the server stops, see the shutdown below
+    SHUTDOWN.reset()
+    SHUTDOWN.install_signal_handlers()
+
+    # Reload the settings that can change at runtime on SIGHUP and when the configuration files change
+    CONFIG_RELOADER.start(settings)
 
     # Initialize singleton services
-    from app.shared.services import cleanup_services, init_services
+    from app.shared.services import cleanup_services, drain_detection_scheduler, init_services
 
     init_services()
     logger.info("🔧 Singleton services initialized")
 
+    # Heartbeat the runs of this instance and resume the runs left by stopped instances
+    from app.domains.runs.run_recovery import create_run_recovery, interrupt_queued_runs
+
+    run_recovery = create_run_recovery(settings)
+    if run_recovery:
+        run_recovery.start()
+
+    # Start scheduled retention purges
+    from app.domains.retention.retention_scheduler import create_retention_scheduler
+
+    retention_scheduler = create_retention_scheduler(settings)
+    if retention_scheduler:
+        retention_scheduler.start()
+
+    # Tick the detection schedules of project steps
+    from app.domains.schedules.schedule_runner import create_schedule_runner
+
+    schedule_runner = create_schedule_runner(settings)
+    if schedule_runner:
+        schedule_runner.start()
+
+    # Move stored blobs hashed with a previous algorithm to the configured one
+    from app.domains.storage.rehash_job import create_rehash_job
+
+    rehash_job = create_rehash_job(settings)
+    if rehash_job:
+        rehash_job.start()
+
+    # Publish the lifecycle events written to the outbox
+    from app.domains.events.outbox_relay import create_outbox_relay
+
+    outbox_relay = create_outbox_relay(settings)
+    if outbox_relay:
+        outbox_relay.start()
+
+    # Post the summaries of finished runs to the main PAMP service
+    from app.domains.callbacks.callback_dispatcher import create_callback_dispatcher
+
+    callback_dispatcher = create_callback_dispatcher(settings)
+    if callback_dispatcher:
+        callback_dispatcher.start()
+
+    # Consume detection requests enqueued on RabbitMQ
+    from app.domains.messaging.detection_request_consumer import create_detection_request_consumer
+
+    detection_request_consumer = create_detection_request_consumer(settings)
+    if detection_request_consumer:
+        detection_request_consumer.start()
+
+    # Ingest the archives notified by a bucket through SQS
+    from app.domains.notifications.sqs_poller import create_sqs_notification_poller
+
+    sqs_notification_poller = create_sqs_notification_poller(settings)
+    if sqs_notification_poller:
+        sqs_notification_poller.start()
+
+    # Serve the gRPC interface on its own port
+    from app.domains.rpc.grpc_server import create_grpc_server
+
+    grpc_server = create_grpc_server(settings)
+    if grpc_server:
+        grpc_server.start()
+
+    # Stop taking work from queues as soon as the instance drains, while the requests in flight finish
+    def stop_intake():
+        if grpc_server:
+            grpc_server.stop()
+        if detection_request_consumer:
+            detection_request_consumer.stop()
+        if sqs_notification_poller:
+            sqs_notification_poller.stop()
+
+    SHUTDOWN.on_drain(stop_intake)
+
     logger.info(f"📊 Starting {settings.app_name} v{settings.app_version}")
     logger.info(f"🔧 Debug mode: {settings.debug}")
     yield
 
-    # Shutdown: Cleanup services
+    # Shutdown: Stop consuming requests, then let the runs finish within the grace period or checkpoint them as
+    # interrupted, then stop background tasks, the outbox relay publishing the events left
+    SHUTDOWN.begin("application shutdown")
+    CONFIG_RELOADER.stop()
+    current = get_settings()  # the grace periods may have been reloaded
+    dropped_runs = drain_detection_scheduler(current.shutdown_grace_seconds, current.shutdown_interrupt_timeout_seconds)
+    interrupt_queued_runs(dropped_runs)
+    if run_recovery:
+        run_recovery.stop()
+    if retention_scheduler:
+        retention_scheduler.stop()
+    if schedule_runner:
+        schedule_runner.stop()
+    if rehash_job:
+        rehash_job.stop()
+    if outbox_relay:
+        outbox_relay.stop()
+    if callback_dispatcher:
+        callback_dispatcher.stop()
     cleanup_services()
+    SHUTDOWN.reset()
     logger.info("🛑 Application shutting down")
 
 
@@ -82,6 +193,9 @@ app = FastAPI(
     openapi_url="/openapi.json",
 )
 
+# Restrict the data each request reaches to the projects of its tenant
+app.add_middleware(TenancyMiddleware)
+
 # Add CORS middleware
 app.add_middleware(
     CORSMiddleware,
@@ -91,10 +205,34 @@ app.add_middleware(
     allow_headers=["*"],
 )
 
+# Time every request by route, outermost so rejected CORS requests are counted too
+if settings.metrics_enabled:
+    app.add_middleware(MetricsMiddleware)
+
+# Refuse new requests with 503 once the instance drains for a shutdown, probes excepted
+app.add_middleware(DrainingMiddleware)
+
+# Run every request in a span with its request ID, returned in error responses
+app.add_middleware(RequestIdMiddleware)
+install_error_handlers(app)
+
 # Include domain routers
 app.include_router(health_router)
+app.include_router(submission_debug_router)
 app.include_router(submissions_router)
 app.include_router(detection_router)
+app.include_router(runs_router)
+app.include_router(schedules_router)
+app.include_router(reports_router)
+app.include_router(callbacks_router)
+app.include_router(notifications_router)
+app.include_router(fingerprint_router)
+app.include_router(retention_router)
+app.include_router(corpus_router)
+app.include_router(admin_router)
+app.include_router(audit_router)
+if settings.metrics_enabled:
+    app.include_router(metrics_router)
 
 
 @app.get("/")
@@ -113,11 +251,51 @@ async def root():
         "architecture": "Clean Architecture with Domain-Driven Design",
         "swagger": "/swagger-ui",
         "health": "/health",
-        "domains": {"health": "/health", "submissions": "/submissions", "detection": "/detection"},
+        "domains": {
+            "health": "/health",
+            "submissions": "/submissions",
+            "detection": "/detection",
+            "runs": "/runs",
+            "retention": "/retention",
+            "corpus": "/projects",
+            "admin": "/admin",
+            "metrics": "/metrics",
+        },
     }
 
 
 if __name__ == "__main__":
+    import argparse
+
+    parser = argparse.ArgumentParser(description=settings.app_name)
+    parser.add_argument(
+        "--migrate-only",
+        action="store_true",
+        help="Apply pending database migrations and exit without starting the HTTP server",
+    )
+    parser.add_argument(
+        "--print-config",
+        action="store_true",
+        help="Print the effective configuration with the source of each setting, secrets redacted, and exit",
+    )
+    args = parser.parse_args()
+
+    if args.print_config:
+        import json
+
+        from app.config.sources import redacted_config
+
+        print(json.dumps(redacted_config(), indent=2, default=str))
+        sys.exit(0)
+
+    if args.migrate_only:
+        try:
+            migrate_database()
+        except Exception as e:
+            logger.critical(f"Database migration failed: {str(e)}")
+            sys.exit(1)
+        sys.exit(0)
+
     import uvicorn
 
     uvicorn.run(
@@ -0,0 +1,360 @@
+"""
+Worker pool sizing, concurrency caps and priorities of background jobs
+
+Pool sizes left at 0 in the configuration are derived from the CPUs the process may actually use: its CPU
+affinity, capped by the cgroup CPU quota of the container (Kubernetes CPU limits) when there is one.
+"""
+
+import functools
+import itertools
+import logging
+import math
+import os
+import threading
+import time
+from collections import deque
+from concurrent.futures import Future
+from dataclasses import dataclass, field
+from enum import Enum
+from pathlib import Path
+from typing import Any, Callable, Deque, Dict, Hashable, List, Optional, Tuple
+
+from app.shared.tracing import in_current_context
+
+logger = logging.getLogger(__name__)
+
+CGROUP_ROOT = Path("/sys/fs/cgroup")
+
+
+def cgroup_cpu_limit(cgroup_root: Optional[Path] = None) -> Optional[float]:
+    """CPUs allowed by the cgroup quota (v2 cpu.max or v1 cfs quota), None when unlimited or unknown"""
+    cgroup_root = cgroup_root or CGROUP_ROOT
+    try:
+        cpu_max = cgroup_root / "cpu.max"
+        if cpu_max.exists():
+            quota, period = cpu_max.read_text().split()[:2]
+            return None if quota == "max" else int(quota) / int(period)
+
+        quota_file, period_file = cgroup_root / "cpu" / "cpu.cfs_quota_us", cgroup_root / "cpu" / "cpu.cfs_period_us"
+        if quota_file.exists() and period_file.exists():
+            quota = int(quota_file.read_text())
+            return None if quota <= 0 else quota / int(period_file.read_text())
+    except (OSError, ValueError) as e:
+        logger.warning(f"Unreadable cgroup CPU limit: {str(e)}")
+    return None
+
+
+def available_cpus(cgroup_root: Optional[Path] = None) -> int:
+    """CPUs the process can use, the cgroup quota rounded up"""
+    try:
+        cpus = len(os.sched_getaffinity(0))
+    except AttributeError:
+        cpus = os.cpu_count() or 1
+
+    limit = cgroup_cpu_limit(cgroup_root)
+    if limit is not None:
+        cpus = min(cpus, math.ceil(limit))
+    return max(cpus, 1)
+
+
+def resolve_workers(configured: int, default: Callable[[int], int]) -> int:
+    """The configured size, or the default derived from the available CPUs when it is 0 or less"""
+    if configured > 0:
+        return configured
+    return max(default(available_cpus()), 1)
+
+
+class JobPriority(str, Enum):
+    """Priority of a background job, from lowest to highest"""
+
+    LOW = "low"
+    NORMAL = "normal"
+    HIGH = "high"
+    URGENT = "urgent"
+
+    @property
+    def rank(self) -> int:
+        return PRIORITY_ORDER.index(self)
+
+
+PRIORITY_ORDER = list(JobPriority)
+# Aging never promotes a job beyond this level, urgent is only given by the caller
+MAX_AGED_PRIORITY = JobPriority.HIGH
+
+
+@dataclass
+class _QueuedJob:
+    job_id: Optional[Hashable]
+    priority: JobPriority
+    sequence: int
+    enqueued_at: float
+    call: Callable[[], Any]
+    group: Optional[Hashable] = None
+    future: Future = field(default_factory=Future)
+
+
+@dataclass(frozen=True)
+class QueuedJobStatus:
+    """Position of a job waiting for a slot"""
+
+    priority: JobPriority
+    effective_priority: JobPriority
+    position: int  # 1 for the next job to start
+    waiting_seconds: float
+
+
+class JobScheduler:
+    """
+    Runs background jobs with at most max_concurrent of them at once
+
+    Jobs beyond the cap are queued and started as running jobs finish, never rejected: highest effective priority
+    first, in submission order within a priority. A queued job gains one priority level for every aging_seconds it
+    has waited, up to high, so a steady flow of higher priority jobs cannot starve the lower ones; 0 disables aging.
+
+    Jobs can belong to a group, e.g. the project of a detection run. At most max_per_group jobs of a group run at
+    once, 0 for no cap, and within a priority the groups take turns: the next job is the oldest of the group that
+    started a job the longest ago, so a group queuing many jobs cannot hold back the single job of another.
+    Ungrouped jobs are one more group, never capped.
+
+    Jobs long enough to be worth resuming check interrupted, set when a drain gave up waiting for them, to
+    checkpoint their progress and return.
+    """
+
+    def __init__(
+        self,
+        name: str,
+        max_concurrent: int,
+        aging_seconds: float = 0,
+        clock: Callable[[], float] = time.monotonic,
+        max_per_group: int = 0,
+    ):
+        self.name = name
+        self.max_concurrent = max(max_concurrent, 1)
+        self.aging_seconds = aging_seconds
+        self.max_per_group = max(max_per_group, 0)
+        self._clock = clock
+        self._condition = threading.Condition()
+        # Queued jobs by priority and group, in submission order, empty queues are removed
+        self._queues: Dict[Tuple[JobPriority, Optional[Hashable]], Deque[_QueuedJob]] = {}
+        self._queued_by_id: Dict[Hashable, _QueuedJob] = {}
+        self._running_by_group: Dict[Hashable, int] = {}
+        # Dispatch number of the last job each group started, for the turns of the groups
+        self._last_started: Dict[Optional[Hashable], int] = {}
+        self._dispatches = itertools.count()
+        self._workers: List[threading.Thread] = []
+        self._sequence = itertools.count()
+        self._worker_sequence = itertools.count()
+        self._shutdown = False
+        self.interrupted = threading.Event()
+        self.queued = 0
+        self.running = 0
+
+    def _effective_priority(self, queued: _QueuedJob, now: float) -> JobPriority:
+        if self.aging_seconds <= 0 or queued.priority.rank >= MAX_AGED_PRIORITY.rank:
+            return queued.priority
+        levels = int((now - queued.enqueued_at) // self.aging_seconds)
+        return PRIORITY_ORDER[min(queued.priority.rank + levels, MAX_AGED_PRIORITY.rank)]
+
+    def _order(self, queued: _QueuedJob, now: float, last_started: Dict) -> Tuple[int, int, int]:
+        """Sort key of a queued job: effective priority, then the turn of its group, then submission order"""
+        return -self._effective_priority(queued, now).rank, last_started.get(queued.group, -1), queued.sequence
+
+    def _capped(self, group: Optional[Hashable]) -> bool:
+        """Whether a group runs as many jobs as it may, the queue being locked"""
+        return group is not None and 0 < self.max_per_group <= self._running_by_group.get(group, 0)
+
+    def submit(
+        self,
+        job: Callable[..., Any],
+        *args,
+        priority: JobPriority = JobPriority.NORMAL,
+        job_id: Optional[Hashable] = None,
+        group: Optional[Hashable] = None,
+        **kwargs,
+    ) -> Future:
+        """
+        Queue a job, returns its future
+
+        Args:
+            priority: Priority of the job among the queued ones
+            job_id: Identifier the queue position of the job can be looked up by, see status
+            group: Group of the job, capped at max_per_group running jobs and taking turns with the other groups
+        """
+        # Jobs log in the span of their submitter, e.g. the request that created them
+        call = in_current_context(functools.partial(job, *args, **kwargs))
+        queued = _QueuedJob(job_id, JobPriority(priority), next(self._sequence), self._clock(), call, group)
+        with self._condition:
+            if self._shutdown:
+                raise RuntimeError(f"Scheduler {self.name} is shut down")
+            self._queues.setdefault((queued.priority, group), deque()).append(queued)
+            if job_id is not None:
+                self._queued_by_id[job_id] = queued
+            self.queued += 1
+            if len(self._workers) < self.max_concurrent:
+                self._start_worker()
+            self._condition.notify()
+        return queued.future
+
+    def _start_worker(self) -> None:
+        """Start one more worker, the queue being locked"""
+        worker = threading.Thread(target=self._work, name=f"{self.name}_{next(self._worker_sequence)}", daemon=True)
+        self._workers.append(worker)
+        worker.start()
+
+    def resize(
+        self, max_concurrent: int, aging_seconds: Optional[float] = None, max_per_group: Optional[int] = None
+    ) -> None:
+        """
+        Change the cap on concurrent jobs, and the aging of queued jobs and the cap of each group when given
+
+        Queued jobs start right away when a cap is raised. Running jobs are never stopped when one is lowered, the
+        workers beyond the new cap stop once their job is done.
+        """
+        with self._condition:
+            self.max_concurrent = max(max_concurrent, 1)
+            if aging_seconds is not None:
+                self.aging_seconds = aging_seconds
+            if max_per_group is not None:
+                self.max_per_group = max(max_per_group, 0)
+            if not self._shutdown:
+                for _ in range(min(self.max_concurrent - len(self._workers), self.queued)):
+                    self._start_worker()
+            self._condition.notify_all()
+
+    def _next(self) -> Optional[_QueuedJob]:
+        """Job to start, None when every queued job belongs to a capped group, the queue being locked"""
+        now = self._clock()
+        # Within a priority and group the oldest job has waited and aged the most, only the heads compete
+        heads = [queue[0] for (_, group), queue in self._queues.items() if not self._capped(group)]
+        if not heads:
+            return None
+        return min(heads, key=lambda head: self._order(head, now, self._last_started))
+
+    def _pop(self, queued: _QueuedJob) -> None:
+        """Remove the next job from the queue and count it running, the queue being locked"""
+        key = (queued.priority, queued.group)
+        self._queues[key].popleft()
+        if not self._queues[key]:
+            del self._queues[key]
+        if queued.job_id is not None:
+            self._queued_by_id.pop(queued.job_id, None)
+        self._last_started[queued.group] = next(self._dispatches)
+        if queued.group is not None:
+            self._running_by_group[queued.group] = self._running_by_group.get(queued.group, 0) + 1
+        self.queued -= 1
+        self.running += 1
+
+    def _finish(self, queued: _QueuedJob) -> None:
+        """Count a job done, the queue being locked"""
+        self.running -= 1
+        if queued.group is not None:
+            self._running_by_group[queued.group] -= 1
+            if not self._running_by_group[queued.group]:
+                del self._running_by_group[queued.group]
+                # Groups with nothing left start their next job first, like a group never seen
+                if not any(group == queued.group for _, group in self._queues):
+                    self._last_started.pop(queued.group, None)
+
+    def _surplus(self) -> bool:
+        """Whether there are more workers than the cap, after it was lowered, the queue being locked"""
+        return len(self._workers) > self.max_concurrent and not self._shutdown
+
+    def _work(self) -> None:
+        while True:
+            with self._condition:
+                # Jobs of capped groups wait for a job of their group to finish, even with free slots
+                queued = self._next()
+                while queued is None and (self.queued or not self._shutdown) and not self._surplus():
+                    self._condition.wait()
+                    queued = self._next()
+                if self._surplus():
+                    self._workers.remove(threading.current_thread())
+                    return
+                if queued is None:
+                    return
+                self._pop(queued)
+            try:
+                if queued.future.set_running_or_notify_cancel():
+                    try:
+                        queued.future.set_result(queued.call())
+                    except BaseException as e:
+                        queued.future.set_exception(e)
+            finally:
+                with self._condition:
+                    self._finish(queued)
+                    self._condition.notify_all()
+
+    def status(self, job_id: Hashable) -> Optional[QueuedJobStatus]:
+        """
+        Effective priority and position of a queued job, None once it started or if it is unknown
+
+        The position is the place of the job in the order the queued jobs would start in with the groups taking
+        turns, as if no priority changed meanwhile; the caps of the groups only delay jobs and are left out.
+        """
+        with self._condition:
+            queued = self._queued_by_id.get(job_id)
+            if queued is None:
+                return None
+            now = self._clock()
+            heads = {key: deque(queue) for key, queue in self._queues.items()}
+            last_started = dict(self._last_started)
+            turns = itertools.count(max(last_started.values(), default=-1) + 1)
+            for position in itertools.count(1):
+                key, head = min(
+                    ((key, queue[0]) for key, queue in heads.items() if queue),
+                    key=lambda item: self._order(item[1], now, last_started),
+                )
+                if head is queued:
+                    break
+                heads[key].popleft()
+                last_started[head.group] = next(turns)
+            return QueuedJobStatus(
+                priority=queued.priority,
+                effective_priority=self._effective_priority(queued, now),
+                position=position,
+                waiting_seconds=now - queued.enqueued_at,
+            )
+
+    def run(self, job: Callable[..., Any], *args, **kwargs) -> Any:
+        """Run a job once a slot is free and wait for its result"""
+        return self.submit(job, *args, **kwargs).result()
+
+    def shutdown(self, wait: bool = False) -> None:
+        """Stop accepting jobs, the queued ones still run"""
+        with self._condition:
+            self._shutdown = True
+            self._condition.notify_all()
+            workers = list(self._workers)
+        if wait:
+            for worker in workers:
+                worker.join()
+
+    def drain(self, grace_seconds: float, interrupt_timeout_seconds: float = 5.0) -> List[Hashable]:
+        """
+        Stop accepting jobs and drop the queued ones, then wait for the running ones to finish, interrupting them
+        once the grace period is over
+
+        Returns:
+            IDs of the queued jobs that were dropped, their futures are cancelled
+        """
+        with self._condition:
+            self._shutdown = True
+            dropped = sorted((queued for queue in self._queues.values() for queued in queue), key=lambda q: q.sequence)
+            self._queues.clear()
+            self._queued_by_id.clear()
+            self.queued = 0
+            self._condition.notify_all()
+        for queued in dropped:
+            queued.future.cancel()
+
+        if not self._wait_idle(grace_seconds):
+            logger.warning(f"Interrupting {self.running} {self.name} jobs still running after {grace_seconds:g}s")
+            self.interrupted.set()
+            if not self._wait_idle(interrupt_timeout_seconds):
+                logger.error(f"{self.running} {self.name} jobs did not stop within {interrupt_timeout_seconds:g}s")
+        return [queued.job_id for queued in dropped if queued.job_id is not None]
+
+    def _wait_idle(self, timeout: float) -> bool:
+        """Wait for the running jobs to finish, returns whether they all did in time"""
+        with self._condition:
+            return self._condition.wait_for(lambda: self.running == 0, max(timeout, 0))
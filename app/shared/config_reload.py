@@ -0,0 +1,267 @@
+"""
+Configuration reloads without a restart
+
+A reload reads the layered sources of the settings, see app/config/sources.py, and TOKENIZER_CONFIG_PATH again
+and validates them before anything changes. Only the settings of RELOADABLE_SETTINGS can change at runtime: when
+any other setting differs, e.g. the database URL or the storage backend, the whole reload is rejected naming them
+and nothing is applied. Otherwise the settings, the tokenizer configuration and the services built from them are
+swapped as a whole, the job schedulers resized and the log filter of the configuration applied again.
+
+Detection runs in flight keep the settings and services they started with, see in_settings_snapshot, new runs use
+the reloaded ones. Reloads are triggered by POST /admin/config/reload, SIGHUP and, when polled, changes of the files.
+"""
+
+import logging
+import signal
+import threading
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Callable, Dict, List, Optional, Tuple
+
+from app.config.config import Settings, get_settings, replace_settings
+from app.config.sources import ENV_FILE, load_settings, profile_path
+
+logger = logging.getLogger(__name__)
+
+# Settings read when a run, request or delivery starts, or applied in place by a reload
+RELOADABLE_SETTINGS = (
+    "detection_run_batch_size",
+    "detection_comparison_workers",
+    "detection_comparison_chunk_size",
+    "detection_pruning_enabled",
+    "detection_pruning_margin",
+    "detection_max_concurrent_jobs",
+    "detection_priority_aging_seconds",
+    "detection_max_concurrent_jobs_per_project",
+    "ingestion_max_concurrent_jobs",
+    "detection_profiling_enabled",
+    "detection_min_comparable_tokens",
+    "detection_auto_tune",
+    "detection_auto_tune_target",
+    "detection_auto_tune_sample_submissions",
+    "detection_file_timeout_seconds",
+    "detection_pair_timeout_seconds",
+    "detection_run_timeout_seconds",
+    "fetch_retry_max_attempts",
+    "fetch_retry_base_delay_seconds",
+    "fetch_retry_max_delay_seconds",
+    "fetch_retry_jitter",
+    "fetch_circuit_failure_threshold",
+    "fetch_circuit_reset_seconds",
+    "shutdown_grace_seconds",
+    "shutdown_interrupt_timeout_seconds",
+    "log_filter",
+    "pamp_callback_thresholds",
+    "pamp_callback_public_url",
+    "tokenization_workers",
+    "tokenization_max_files_in_memory",
+    "tokenization_streaming_threshold_mb",
+    "fragment_long_line_threshold",
+    "fragment_excerpt_max_bytes",
+    "tokenizer_config_path",
+    "report_min_similarity",
+    "report_max_pairs",
+    "report_async_min_pairs",
+    "report_max_concurrent_jobs",
+    "admin_stats_max_age_seconds",
+    "quota_max_stored_bytes",
+    "quota_max_submissions",
+    "quota_max_runs_per_day",
+    "quota_max_run_submissions",
+    "project_quotas",
+)
+# Settings the tokenization, similarity and fingerprint services are built with, replaced when they change
+SERVICE_SETTINGS = (
+    "tokenization_workers",
+    "tokenization_max_files_in_memory",
+    "tokenization_streaming_threshold_mb",
+    "fragment_long_line_threshold",
+    "fragment_excerpt_max_bytes",
+)
+
+
+class ConfigReloadError(ValueError):
+    """The configuration read again is invalid, nothing was reloaded"""
+
+
+class ConfigChangeRejected(ConfigReloadError):
+    """Settings that cannot change at runtime differ from the running ones, nothing was reloaded"""
+
+    def __init__(self, fields: List[str]):
+        self.fields = fields
+        super().__init__(
+            f"{', '.join(fields)} cannot be changed without a restart, nothing was reloaded: "
+            f"revert {'them' if len(fields) > 1 else 'it'} to reload the other settings, or restart the service"
+        )
+
+
+@dataclass(frozen=True)
+class ConfigReload:
+    """What a reload changed"""
+
+    changed: Tuple[str, ...]  # names of the changed settings
+    tokenizer_config_reloaded: bool
+    tokenizer_config_revision: Optional[str]
+
+
+def changed_settings(current: Settings, new: Settings) -> List[str]:
+    """Names of the settings that differ, in declaration order"""
+    return [name for name in Settings.model_fields if getattr(current, name) != getattr(new, name)]
+
+
+def watched_files(settings: Settings) -> List[Path]:
+    """Files the configuration is read from: .env, the profile and the tokenizer configuration when set"""
+    paths = [Path(ENV_FILE)]
+    if settings.app_env:
+        paths.append(profile_path(settings.app_env, settings.app_profiles_dir))
+    if settings.tokenizer_config_path:
+        paths.append(Path(settings.tokenizer_config_path))
+    return paths
+
+
+def modification_times(paths: List[Path]) -> Dict[Path, Optional[int]]:
+    """Modification time of each file, None for the missing ones"""
+    times = {}
+    for path in paths:
+        try:
+            times[path] = path.stat().st_mtime_ns
+        except OSError:
+            times[path] = None
+    return times
+
+
+class ConfigReloader:
+    """Reloads of the runtime configuration, one at a time, and what triggers them besides the admin endpoint"""
+
+    def __init__(self, load_settings: Callable[[], Settings] = load_settings):
+        self._load_settings = load_settings
+        self._lock = threading.Lock()
+        self._previous_handler: object = None
+        self._stop_event = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def reload(self, reason: str) -> ConfigReload:
+        """
+        Read, validate and apply the configuration again
+
+        Raises:
+            ConfigChangeRejected: If a setting that cannot change at runtime differs, nothing is applied
+            ConfigReloadError: If the settings, the tokenizer configuration or the log filter are invalid
+        """
+        from app.domains.tokenization.tokenization_service import TOKENIZER_VERSION
+        from app.domains.tokenization.tokenizer_config import (
+            get_tokenizer_config,
+            load_deployment_tokenizer_config,
+            replace_tokenizer_config,
+        )
+        from app.shared import services
+        from app.shared.tracing import LOG_FILTER, configured_log_filter, parse_log_filter
+
+        with self._lock:
+            current = get_settings()
+            try:
+                new = self._load_settings()
+            except ValueError as e:
+                raise ConfigReloadError(f"Invalid settings: {str(e)}")
+            changed = changed_settings(current, new)
+            rejected = [name for name in changed if name not in RELOADABLE_SETTINGS]
+            if rejected:
+                raise ConfigChangeRejected(rejected)
+
+            # Everything is validated before the first swap, an invalid file leaves the running configuration
+            try:
+                tokenizer_config = load_deployment_tokenizer_config(new)
+            except ValueError as e:
+                raise ConfigReloadError(str(e))
+            log_filter = configured_log_filter(new)
+            try:
+                parse_log_filter(log_filter)
+            except ValueError as e:
+                raise ConfigReloadError(f"Invalid LOG_FILTER: {str(e)}")
+
+            effective = tokenizer_config.to_dict(TOKENIZER_VERSION)
+            tokenizer_config_reloaded = effective != get_tokenizer_config().to_dict(TOKENIZER_VERSION)
+            if tokenizer_config_reloaded:
+                replace_tokenizer_config(tokenizer_config)
+            if tokenizer_config_reloaded or set(changed) & set(SERVICE_SETTINGS):
+                services.replace_detection_services(new, get_tokenizer_config())
+            replace_settings(new)
+            services.resize_job_schedulers(new)
+            LOG_FILTER.apply(log_filter)
+
+        if changed or tokenizer_config_reloaded:
+            logger.info(
+                f"Configuration reloaded ({reason}): {', '.join(changed) or 'no setting'} changed"
+                f"{', tokenizer configuration reloaded' if tokenizer_config_reloaded else ''}"
+            )
+        else:
+            logger.info(f"Configuration reloaded ({reason}), nothing changed")
+        return ConfigReload(tuple(changed), tokenizer_config_reloaded, get_tokenizer_config().revision)
+
+    def try_reload(self, reason: str) -> Optional[ConfigReload]:
+        """Reload, logging why the configuration was not reloaded instead of raising"""
+        try:
+            return self.reload(reason)
+        except ConfigReloadError as e:
+            logger.error(f"Configuration not reloaded ({reason}): {str(e)}")
+        except Exception as e:
+            logger.error(f"Configuration reload failed ({reason}): {str(e)}")
+        return None
+
+    def handle_signal(self, signum: int, frame) -> None:
+        # Services are built outside of the signal handler which blocks the event loop
+        reason = f"received {signal.Signals(signum).name}"
+        threading.Thread(target=self.try_reload, args=(reason,), name="config-reload", daemon=True).start()
+
+        if callable(self._previous_handler):
+            self._previous_handler(signum, frame)
+
+    def install_signal_handler(self) -> None:
+        """Reload on SIGHUP, then hand the signal to the previous handler if it is a function"""
+        try:
+            self._previous_handler = signal.getsignal(signal.SIGHUP)
+            signal.signal(signal.SIGHUP, self.handle_signal)
+        except (ValueError, AttributeError):
+            # Only the main thread handles signals, and there is no SIGHUP on Windows
+            self._previous_handler = None
+            logger.info("Not in the main thread, configuration reloads are left to POST /admin/config/reload")
+
+    def _run(self, interval_seconds: float) -> None:
+        times = modification_times(watched_files(get_settings()))
+        while not self._stop_event.wait(interval_seconds):
+            latest = modification_times(watched_files(get_settings()))
+            if latest != times:
+                changed = ", ".join(str(path) for path in latest if latest[path] != times.get(path))
+                self.try_reload(f"{changed} changed")
+                # Files are compared again once reloaded, a newly set TOKENIZER_CONFIG_PATH is then watched too
+                latest = modification_times(watched_files(get_settings()))
+            times = latest
+
+    def start(self, settings: Settings) -> None:
+        """Install the SIGHUP handler and poll the configuration files, as configured"""
+        if settings.config_reload_on_sighup:
+            self.install_signal_handler()
+        if settings.config_watch_interval_seconds <= 0 or (self._thread is not None and self._thread.is_alive()):
+            return
+        self._stop_event.clear()
+        self._thread = threading.Thread(
+            target=self._run, args=(settings.config_watch_interval_seconds,), name="config-watch", daemon=True
+        )
+        self._thread.start()
+        logger.info(f"Configuration files polled every {settings.config_watch_interval_seconds:g}s")
+
+    def stop(self, timeout: float = 5.0) -> None:
+        """Stop polling and restore the previous SIGHUP handler"""
+        self._stop_event.set()
+        if self._thread is not None:
+            self._thread.join(timeout)
+            self._thread = None
+        previous, self._previous_handler = self._previous_handler, None
+        if previous is not None:
+            try:
+                signal.signal(signal.SIGHUP, previous)
+            except (ValueError, AttributeError):
+                pass
+
+
+CONFIG_RELOADER = ConfigReloader()
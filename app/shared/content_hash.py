@@ -0,0 +1,77 @@
+"""
+Content hash algorithms used as identities of stored content
+
+Every stored content hash (blob digests in manifests, fingerprint cache keys) is recorded together with the
+algorithm that produced it. Hashes stored before the algorithm was recorded are SHA-256.
+The rolling k-gram hashes of the fingerprinting hot path are not identities and keep their fast hash.
+"""
+
+import hashlib
+import re
+from enum import Enum
+from typing import Iterable, Optional, Tuple
+
+from app.shared.exceptions import ValidationException
+
+
+class HashAlgorithm(str, Enum):
+    """Cryptographic hash algorithms content can be identified by"""
+
+    SHA256 = "sha256"
+    BLAKE3 = "blake3"
+
+
+# Algorithm of hashes stored without one
+LEGACY_HASH_ALGORITHM = HashAlgorithm.SHA256
+DEFAULT_HASH_ALGORITHM = HashAlgorithm.BLAKE3
+
+# Both algorithms produce 256-bit digests
+DIGEST_PATTERN = re.compile(r"^[0-9a-f]{64}$")
+
+
+def parse_hash_algorithm(value: Optional[str]) -> HashAlgorithm:
+    """
+    Resolve a recorded algorithm name, None meaning a hash stored before algorithms were recorded
+
+    Raises:
+        ValidationException: If the algorithm is unknown
+    """
+    if value is None:
+        return LEGACY_HASH_ALGORITHM
+    if isinstance(value, HashAlgorithm):
+        return value
+    try:
+        return HashAlgorithm(str(value).lower())
+    except ValueError:
+        raise ValidationException(
+            f"Unknown content hash algorithm '{value}'. "
+            f"Supported algorithms: {', '.join(algorithm.value for algorithm in HashAlgorithm)}"
+        )
+
+
+def new_hasher(algorithm: HashAlgorithm):
+    """Create an incremental hasher exposing update() and hexdigest()"""
+    if algorithm == HashAlgorithm.BLAKE3:
+        try:
+            import blake3
+        except ImportError:
+            raise ValidationException("The blake3 package is required for the blake3 content hash algorithm")
+        return blake3.blake3()
+    return hashlib.sha256()
+
+
+def hash_bytes(data: bytes, algorithm: HashAlgorithm) -> str:
+    """Hex digest of a byte string"""
+    hasher = new_hasher(algorithm)
+    hasher.update(data)
+    return hasher.hexdigest()
+
+
+def hash_chunks(chunks: Iterable[bytes], algorithm: HashAlgorithm) -> Tuple[str, int]:
+    """Hex digest and total size of a stream of chunks"""
+    hasher = new_hasher(algorithm)
+    size = 0
+    for chunk in chunks:
+        hasher.update(chunk)
+        size += len(chunk)
+    return hasher.hexdigest(), size
@@ -1,12 +1,26 @@
-from sqlmodel import Session, SQLModel, create_engine, text
+from sqlmodel import Session, create_engine
 
 from app.config.config import get_settings
 
 # Import all models to ensure they are registered with SQLModel
+from app.domains.admin.admin_stats_models import AdminStatsSnapshot
+from app.domains.audit.audit_models import AuditEntry
+from app.domains.callbacks.callbacks_models import CallbackDelivery
+from app.domains.events.events_models import OutboxEvent
+from app.domains.notifications.notifications_models import ObjectIngestion
+from app.domains.quotas.quota_models import ProjectUsage
+from app.domains.retention.retention_models import ProjectRetentionPolicy
+from app.domains.runs.runs_models import DetectionRun
+from app.domains.schedules.schedules_models import DetectionSchedule
 from app.domains.submissions.submissions_models import Submission
+from app.domains.tenants.tenant_filters import install_tenant_filters
+from app.domains.tenants.tenants_models import ProjectTenant
 
 settings = get_settings()
 
+# Queries and flushes of every session are restricted to the projects of the current tenant, see app.shared.tenancy
+install_tenant_filters()
+
 # Create database engine
 engine = create_engine(
     settings.database_url,
@@ -16,18 +30,11 @@ engine = create_engine(
 )
 
 
-def create_db_and_tables():
-    """Drop and recreate all database tables to ensure full sync with entities"""
-    # Uncomment the following line to drop all tables and recreate them
-    # with Session(engine) as session:
-    #     session.exec(text("DROP SCHEMA public CASCADE"))
-    #     session.exec(text("CREATE SCHEMA public"))
-    #     session.exec(text("GRANT ALL ON SCHEMA public TO postgres"))
-    #     session.exec(text("GRANT ALL ON SCHEMA public TO public"))
-    #     session.commit()
+def migrate_database():
+    """Apply the embedded schema migrations the database is missing, see app.shared.migrations"""
+    from app.shared.migrations import run_migrations
 
-    # Create all tables from scratch
-    SQLModel.metadata.create_all(engine)
+    return run_migrations(engine, lock_timeout_seconds=settings.database_migration_lock_timeout_seconds)
 
 
 def get_session():
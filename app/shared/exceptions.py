@@ -29,3 +29,36 @@ class DatabaseException(HTTPException):
 
     def __init__(self, detail: str = "Database operation failed"):
         super().__init__(status_code=status.HTTP_500_INTERNAL_SERVER_ERROR, detail=detail)
+
+
+class QuotaExceededException(HTTPException):
+    """
+    Raised when a request would exceed a quota of its project, 403 for the capacity quotas and 429 for the rate of
+    detection runs, with the quota, its limit and the current usage
+    """
+
+    def __init__(self, quota: str, project_uuid, limit: int, usage: int, requested: int = 1, rate: bool = False):
+        self.quota = quota
+        super().__init__(
+            status_code=status.HTTP_429_TOO_MANY_REQUESTS if rate else status.HTTP_403_FORBIDDEN,
+            detail={
+                "code": f"quota_{quota}_exceeded",
+                "message": f"Quota {quota} of project {project_uuid} exceeded: {usage} used of {limit}, "
+                f"{requested} requested",
+                "project_uuid": str(project_uuid),
+                "quota": quota,
+                "limit": limit,
+                "usage": usage,
+                "requested": requested,
+            },
+        )
+
+
+class AuditWriteException(HTTPException):
+    """Raised when an operation that must be audited could not be, the operation is then not performed"""
+
+    def __init__(self, action: str, reason: str):
+        super().__init__(
+            status_code=status.HTTP_503_SERVICE_UNAVAILABLE,
+            detail=f"The {action} operation was not performed, its audit entry could not be written: {reason}",
+        )
@@ -0,0 +1,200 @@
+"""
+Localization of reports and error messages
+
+Human-readable text is looked up by message ID in the catalog of a locale, English or French, and falls back to
+the English catalog, or to a default given by the caller, when a translation is missing. Machine-readable content
+never goes through the catalogs: error codes, validation error types, identifiers and scores are the same in every
+locale.
+
+Values are interpolated with str.format fields, {count} or {share:.0%}, restricted to plain names: attribute and
+index lookups are left as written, so a value or a translation cannot reach into the objects being formatted, and
+a field without a value is left as written rather than failing. Lists are joined with commas. Interpolated values
+are not escaped, the HTML templates escape the translated text as a whole.
+
+The locale of a request is its locale query parameter, else the preferred supported language of its
+Accept-Language header, else English.
+"""
+
+import string
+from enum import Enum
+from typing import Any, Callable, Mapping, Optional
+
+from fastapi import Query, Request
+
+from app.shared.messages import CATALOGS
+
+
+class Locale(str, Enum):
+    """Enumeration for the supported locales"""
+
+    EN = "en"
+    FR = "fr"
+
+
+DEFAULT_LOCALE = Locale.EN
+
+# Human-readable fields of error details, translated when the details carry a code of the catalog
+MESSAGE_FIELDS = ("message", "summary")
+
+Translator = Callable[..., str]
+
+
+class _SafeFormatter(string.Formatter):
+    """Formatter interpolating named values only, fields it cannot fill are left as written"""
+
+    def get_field(self, field_name, args, kwargs):
+        if not field_name.isidentifier() or field_name not in kwargs:
+            return _Verbatim(field_name), field_name
+        return kwargs[field_name], field_name
+
+    def convert_field(self, value, conversion):
+        if isinstance(value, _Verbatim) or conversion not in ("r", "s", "a"):
+            return value
+        return super().convert_field(value, conversion)
+
+    def format_field(self, value, format_spec):
+        if isinstance(value, _Verbatim):
+            return value.render(format_spec)
+        if isinstance(value, (list, tuple, set, frozenset)):
+            value = ", ".join(str(item) for item in value)
+        try:
+            return super().format_field(value, format_spec)
+        except (TypeError, ValueError):
+            return str(value)
+
+
+class _Verbatim:
+    """Field left as written in the translation"""
+
+    def __init__(self, field_name: str):
+        self.field_name = field_name
+
+    def render(self, format_spec: str) -> str:
+        return "{" + self.field_name + (":" + format_spec if format_spec else "") + "}"
+
+
+_formatter = _SafeFormatter()
+
+
+def parse_locale(value: Optional[str]) -> Optional[Locale]:
+    """Supported locale of a language tag, fr-CA being French, None when unsupported"""
+    if not value:
+        return None
+    language = value.strip().replace("_", "-").split("-")[0].lower()
+    try:
+        return Locale(language)
+    except ValueError:
+        return None
+
+
+def negotiate_locale(accept_language: Optional[str] = None, requested: Optional[str] = None) -> Locale:
+    """Locale of a request from its locale parameter, else its Accept-Language header, else the default"""
+    locale = parse_locale(requested)
+    if locale is not None:
+        return locale
+
+    preferences = []
+    for position, entry in enumerate((accept_language or "").split(",")):
+        tag, _, parameters = entry.strip().partition(";")
+        quality = 1.0
+        for parameter in parameters.split(";"):
+            name, _, value = parameter.strip().partition("=")
+            if name == "q":
+                try:
+                    quality = float(value)
+                except ValueError:
+                    quality = 0.0
+        if tag and quality > 0:
+            preferences.append((-quality, position, tag.strip()))
+
+    for _, _, tag in sorted(preferences):
+        if tag == "*":
+            return DEFAULT_LOCALE
+        locale = parse_locale(tag)
+        if locale is not None:
+            return locale
+    return DEFAULT_LOCALE
+
+
+def request_locale(request) -> Locale:
+    """Locale of a request, also read outside of endpoints like in the error handlers, which must not fail"""
+    headers = getattr(request, "headers", None) or {}
+    query_params = getattr(request, "query_params", None) or {}
+    return negotiate_locale(headers.get("accept-language"), query_params.get("locale"))
+
+
+def get_locale(
+    request: Request,
+    locale: Optional[str] = Query(
+        None, description="Language of the human-readable text, en or fr, the Accept-Language header by default"
+    ),
+) -> Locale:
+    """Locale of the request of an endpoint"""
+    return negotiate_locale(request.headers.get("accept-language"), locale)
+
+
+def translate(message_id: str, locale: Locale = DEFAULT_LOCALE, default: Optional[str] = None, **values: Any) -> str:
+    """
+    Message of a locale with its values interpolated
+
+    Falls back to the English message, then to the default, then to the message ID itself.
+    """
+    template = CATALOGS[locale.value].get(message_id)
+    if template is None:
+        template = CATALOGS[DEFAULT_LOCALE.value].get(message_id, default)
+    if template is None:
+        return message_id
+    try:
+        return _formatter.vformat(template, (), values)
+    except ValueError:
+        # Unbalanced braces in a translation, shown as written
+        return template
+
+
+def translator(locale: Locale = DEFAULT_LOCALE) -> Translator:
+    """Translation function of a locale, for templates"""
+
+    def translate_in_locale(message_id: str, default: Optional[str] = None, **values: Any) -> str:
+        return translate(message_id, locale, default, **values)
+
+    return translate_in_locale
+
+
+def _message_values(detail: Mapping[str, Any]) -> dict:
+    """Values of error details for their message, those of nested details included unless they clash"""
+    values = {}
+    for value in detail.values():
+        if isinstance(value, Mapping):
+            values.update(value)
+    values.update(detail)
+    return values
+
+
+def localize_detail(detail: Any, locale: Locale) -> Any:
+    """
+    Error details with the messages of their codes in a locale, everything else kept as is
+
+    Details carrying a code of the catalog, "error.<code>", get their message and summary translated. Validation
+    errors get their msg translated from their type, "validation.<type>", with the values of their context. English
+    details are returned as they are, their messages already being English and often more specific.
+    """
+    if locale == DEFAULT_LOCALE:
+        return detail
+    if isinstance(detail, list):
+        return [localize_detail(item, locale) for item in detail]
+    if not isinstance(detail, Mapping):
+        return detail
+
+    localized = {key: localize_detail(value, locale) for key, value in detail.items()}
+    code = detail.get("code")
+    if isinstance(code, str) and f"error.{code}" in CATALOGS[locale.value]:
+        values = _message_values(detail)
+        for field in MESSAGE_FIELDS:
+            if isinstance(detail.get(field), str):
+                localized[field] = translate(f"error.{code}", locale, **values)
+    error_type = detail.get("type")
+    if isinstance(error_type, str) and isinstance(detail.get("msg"), str):
+        if f"validation.{error_type}" in CATALOGS[locale.value]:
+            context = detail.get("ctx") if isinstance(detail.get("ctx"), Mapping) else {}
+            localized["msg"] = translate(f"validation.{error_type}", locale, **context)
+    return localized
@@ -0,0 +1,277 @@
+"""
+Message catalogs by locale
+
+Messages are keyed by ID: report.* for the HTML report of a run, csv.<column> for the header of the pair CSV
+export, reason.<reason> for the reasons submissions are not compared, status.<status> for run statuses,
+error.<code> for error details carrying a code and validation.<type> for request validation errors.
+
+English is the reference, and texts whose English lives next to their code, like the column names of the CSV
+export or the reasons of comparability, are only in the French catalog: without a translation the caller's English
+default is used. A translation may use any value of its English message and of the details it translates.
+"""
+
+from typing import Dict
+
+Catalog = Dict[str, str]
+
+ENGLISH: Catalog = {
+    "report.title": "Detection run {run_id}",
+    "report.heading": "Similarity report of detection run {run_id}",
+    "report.project": "Project",
+    "report.project_step": "Project step",
+    "report.status": "Status",
+    "report.started": "Started",
+    "report.finished": "Finished",
+    "report.not_finished": "not finished",
+    "report.compared_pairs": "Compared pairs",
+    "report.compared_pairs_value": "{completed} completed, {failed} failed out of {total}",
+    "report.flagged_at": "Flagged at",
+    "report.flagged_at_value": "{threshold:.2f} overall similarity or more",
+    "report.generated": "Generated",
+    "report.flagged_pairs": "Flagged pairs ({count})",
+    "report.omitted_pairs": "{count} less similar flagged pairs are not included in this report.",
+    "report.submission_a": "Submission A",
+    "report.submission_b": "Submission B",
+    "report.overall": "Overall",
+    "report.jaccard": "Jaccard",
+    "report.structural": "Structural",
+    "report.fragments": "Fragments",
+    "report.cluster": "Cluster",
+    "report.low_confidence": "low confidence",
+    "report.low_confidence_hint": "A submission has too few comparable tokens for the score to be reliable",
+    "report.partial": "partial",
+    "report.partial_hint": "Files of these submissions failed and were left out, the score may be understated",
+    "report.no_flagged_pair": "No pair reaches the threshold.",
+    "report.clusters": "Clusters ({count})",
+    "report.submissions": "Submissions",
+    "report.cluster_pairs": "Flagged pairs",
+    "report.highest_similarity": "Highest similarity",
+    "report.submission_hint": "submission {submission_id}",
+    "report.submitted_by_hint": "submission {submission_id}, submitted by {submitter}",
+    "report.no_cluster": "No cluster.",
+    "report.self_matches": "Self-similarity ({count})",
+    "report.self_matches_note": (
+        "These pairs compare submissions of the same group or of teams sharing a student, they are not flagged."
+    ),
+    "report.declared_collaborations": "Declared collaboration ({count})",
+    "report.declared_collaborations_note": (
+        "The project step allows these submissions to share code, their pairs are not flagged."
+    ),
+    "report.team": "team {name} ({members})",
+    "report.team_members": "team of {members}",
+    "report.not_comparable": "Not comparable ({count})",
+    "report.not_comparable_note": "These submissions were not compared with any other, none of their pairs is scored.",
+    "report.submission": "Submission",
+    "report.reason": "Reason",
+    "report.late_submissions": "Late submissions ({count})",
+    "report.late_note": "These submissions were uploaded after the deadline of their project step.",
+    "report.minutes_late": "Minutes late",
+    "report.code_metrics": "Code metrics",
+    "report.code_metrics_note": (
+        "Computed when each submission was first compared, from its supported files. Complexity counts one per "
+        "function and one per decision point, n/a when a metric could not be computed for one of the files."
+    ),
+    "report.code_lines": "Lines of code",
+    "report.comment_ratio": "Comments",
+    "report.functions": "Functions",
+    "report.cyclomatic_complexity": "Cyclomatic complexity",
+    "report.files_per_language": "Files per language",
+    "report.not_available": "n/a",
+    "report.file_errors": "File errors ({count})",
+    "report.file_errors_note": (
+        "These files failed and were left out of the comparisons, the scores of their submissions may be understated."
+    ),
+    "report.timed_out": "Timed out pairs ({count})",
+    "report.timed_out_note": (
+        "These pairs took longer than the detection timeout and were stopped, they have no score. Files that timed "
+        "out are listed with the file errors."
+    ),
+    "report.file": "File",
+    "report.stage": "Stage",
+    "report.error": "Error",
+    "report.shared_fragments": "Shared fragments",
+    "report.expand_all": "Expand all",
+    "report.collapse_all": "Collapse all",
+    "report.pair_summary": "#{rank} {left} and {right}, {similarity:.3f}",
+    "report.side_a": "Submission A {submission_id}",
+    "report.side_b": "submission B {submission_id}",
+    "report.by": " by {submitter}",
+    "report.late": ", {minutes} minutes late",
+    "report.file_of_a": "File of A",
+    "report.file_of_b": "File of B",
+    "report.similarity": "Similarity",
+    "report.in_function": "{name} ({function})",
+    "report.block_summary": "{left} and {right}, {similarity:.3f}",
+    "report.lines": "{path}, lines {first} to {last}",
+    "report.cut": "Fragment cut after {count} lines.",
+    "report.source_not_available": "Source not available.",
+    "report.no_fragment": "No fragment recorded for this pair.",
+    "error.internal": "Internal server error",
+}
+
+FRENCH: Catalog = {
+    "report.title": "Analyse {run_id}",
+    "report.heading": "Rapport de similarité de l'analyse {run_id}",
+    "report.project": "Projet",
+    "report.project_step": "Étape du projet",
+    "report.status": "Statut",
+    "report.started": "Début",
+    "report.finished": "Fin",
+    "report.not_finished": "non terminée",
+    "report.compared_pairs": "Paires comparées",
+    "report.compared_pairs_value": "{completed} terminées, {failed} en échec sur {total}",
+    "report.flagged_at": "Signalées à partir de",
+    "report.flagged_at_value": "{threshold:.2f} de similarité globale",
+    "report.generated": "Généré le",
+    "report.flagged_pairs": "Paires signalées ({count})",
+    "report.omitted_pairs": "{count} paires signalées moins similaires ne figurent pas dans ce rapport.",
+    "report.submission_a": "Rendu A",
+    "report.submission_b": "Rendu B",
+    "report.overall": "Globale",
+    "report.jaccard": "Jaccard",
+    "report.structural": "Structurelle",
+    "report.fragments": "Fragments",
+    "report.cluster": "Groupe",
+    "report.low_confidence": "confiance faible",
+    "report.low_confidence_hint": "Un rendu a trop peu de tokens comparables pour que le score soit fiable",
+    "report.partial": "partiel",
+    "report.partial_hint": (
+        "Des fichiers de ces rendus ont échoué et ont été écartés, le score peut être sous-estimé"
+    ),
+    "report.no_flagged_pair": "Aucune paire n'atteint le seuil.",
+    "report.clusters": "Groupes ({count})",
+    "report.submissions": "Rendus",
+    "report.cluster_pairs": "Paires signalées",
+    "report.highest_similarity": "Similarité maximale",
+    "report.submission_hint": "rendu {submission_id}",
+    "report.submitted_by_hint": "rendu {submission_id}, déposé par {submitter}",
+    "report.no_cluster": "Aucun groupe.",
+    "report.self_matches": "Auto-similarité ({count})",
+    "report.self_matches_note": (
+        "Ces paires comparent des rendus du même groupe ou d'équipes ayant un étudiant en commun, elles ne sont "
+        "pas signalées."
+    ),
+    "report.declared_collaborations": "Collaboration déclarée ({count})",
+    "report.declared_collaborations_note": (
+        "L'étape du projet autorise ces rendus à partager du code, leurs paires ne sont pas signalées."
+    ),
+    "report.team": "équipe {name} ({members})",
+    "report.team_members": "équipe de {members}",
+    "report.not_comparable": "Non comparables ({count})",
+    "report.not_comparable_note": (
+        "Ces rendus n'ont été comparés à aucun autre, aucune de leurs paires n'est notée."
+    ),
+    "report.submission": "Rendu",
+    "report.reason": "Raison",
+    "report.late_submissions": "Rendus en retard ({count})",
+    "report.late_note": "Ces rendus ont été déposés après la date limite de leur étape.",
+    "report.minutes_late": "Minutes de retard",
+    "report.code_metrics": "Métriques du code",
+    "report.code_metrics_note": (
+        "Calculées à la première comparaison de chaque rendu, à partir de ses fichiers pris en charge. La complexité "
+        "compte un par fonction et un par point de décision, n.d. quand une métrique n'a pu être calculée pour l'un "
+        "des fichiers."
+    ),
+    "report.code_lines": "Lignes de code",
+    "report.comment_ratio": "Commentaires",
+    "report.functions": "Fonctions",
+    "report.cyclomatic_complexity": "Complexité cyclomatique",
+    "report.files_per_language": "Fichiers par langage",
+    "report.not_available": "n.d.",
+    "report.file_errors": "Fichiers en erreur ({count})",
+    "report.file_errors_note": (
+        "Ces fichiers ont échoué et ont été écartés des comparaisons, les scores de leurs rendus peuvent être "
+        "sous-estimés."
+    ),
+    "report.timed_out": "Paires interrompues ({count})",
+    "report.timed_out_note": (
+        "Ces paires ont dépassé le délai de la détection et ont été interrompues, elles n'ont pas de score. Les "
+        "fichiers ayant dépassé le délai figurent parmi les fichiers en erreur."
+    ),
+    "report.file": "Fichier",
+    "report.stage": "Étape",
+    "report.error": "Erreur",
+    "report.shared_fragments": "Fragments communs",
+    "report.expand_all": "Tout déplier",
+    "report.collapse_all": "Tout replier",
+    "report.pair_summary": "n°{rank} {left} et {right}, {similarity:.3f}",
+    "report.side_a": "Rendu A {submission_id}",
+    "report.side_b": "rendu B {submission_id}",
+    "report.by": " par {submitter}",
+    "report.late": ", {minutes} minutes de retard",
+    "report.file_of_a": "Fichier de A",
+    "report.file_of_b": "Fichier de B",
+    "report.similarity": "Similarité",
+    "report.in_function": "{name} ({function})",
+    "report.block_summary": "{left} et {right}, {similarity:.3f}",
+    "report.lines": "{path}, lignes {first} à {last}",
+    "report.cut": "Fragment coupé après {count} lignes.",
+    "report.source_not_available": "Source indisponible.",
+    "report.no_fragment": "Aucun fragment enregistré pour cette paire.",
+    "status.running": "en cours",
+    "status.completed": "terminée",
+    "status.failed": "en échec",
+    "status.incomplete": "incomplète",
+    "status.interrupted": "interrompue",
+    "status.partial": "partielle",
+    "reason.no_supported_files": "aucun fichier dans un langage pris en charge",
+    "reason.empty": "aucun code dans ses fichiers",
+    "reason.comments_only": "uniquement des commentaires dans ses fichiers",
+    "csv.pair_id": "identifiant de la paire",
+    "csv.submission_id": "rendu",
+    "csv.compared_submission_id": "rendu comparé",
+    "csv.submitted_by_uuid": "déposé par",
+    "csv.compared_submitted_by_uuid": "rendu comparé déposé par",
+    "csv.overall_similarity": "similarité globale",
+    "csv.fragments_count": "nombre de fragments",
+    "csv.longest_fragment_lines": "lignes du plus long fragment",
+    "csv.longest_fragment_tokens": "tokens du plus long fragment",
+    "csv.mean_fragment_lines": "lignes moyennes par fragment",
+    "csv.top_fragments_coverage": "part des plus longs fragments",
+    "error.internal": "Erreur interne du serveur",
+    "error.rulesFailed": (
+        "Échec de la validation du rendu : {failed_rule_count} règles sur {total_rule_count} non respectées"
+    ),
+    "error.ruleValidationFailed": "La règle {rule_name} n'est pas respectée",
+    "error.ruleExecutionError": "Erreur d'exécution de la règle : {error_message}",
+    "error.unexpectedRuleError": "Erreur inattendue à l'exécution de la règle : {error_message}",
+    "error.parameterValidationError": "Paramètre invalide : {error_message}",
+    "error.missingRequiredParameters": "Paramètres obligatoires manquants : {required_parameters}",
+    "error.invalidParameterType": "Le paramètre '{parameter}' doit être de type {expected_type}, reçu {actual_type}",
+    "error.invalidParameterValue": "Valeur invalide du paramètre '{parameter}' : {value}",
+    "error.fileValidationFailed": "Échec de la validation des fichiers",
+    "error.missingRequiredFiles": "Fichiers obligatoires manquants : {missing_files}",
+    "error.forbiddenFilesFound": "Fichiers interdits présents : {forbidden_files}",
+    "error.fileContentValidationFailed": "Échec de la validation du contenu des fichiers",
+    "error.noMatchingFiles": "Aucun fichier ne correspond au motif '{file_pattern}' de la vérification {check_index}",
+    "error.contentCheckFailed": (
+        "Échec de la vérification du contenu des fichiers correspondant au motif '{file_pattern}'"
+    ),
+    "error.invalidRegexPattern": "Expression régulière invalide à l'index {check_index} : {regex}",
+    "error.repositorySizeExceeded": (
+        "La taille du dépôt, {actual_size_mb} Mo, dépasse la taille maximale autorisée de {max_size_mb} Mo"
+    ),
+    "error.languageNotAllowed": (
+        "{disallowed_share:.0%} du rendu est en {disallowed_languages}, l'étape autorise {allowed_languages}"
+    ),
+    "validation.missing": "Champ obligatoire",
+    "validation.extra_forbidden": "Champ non autorisé",
+    "validation.string_type": "Une chaîne de caractères est attendue",
+    "validation.int_parsing": "Un entier valide est attendu",
+    "validation.float_parsing": "Un nombre valide est attendu",
+    "validation.bool_parsing": "Un booléen valide est attendu",
+    "validation.uuid_parsing": "Un UUID valide est attendu",
+    "validation.datetime_parsing": "Une date et heure valide est attendue",
+    "validation.enum": "Une des valeurs suivantes est attendue : {expected}",
+    "validation.greater_than": "La valeur doit être supérieure à {gt}",
+    "validation.greater_than_equal": "La valeur doit être supérieure ou égale à {ge}",
+    "validation.less_than": "La valeur doit être inférieure à {lt}",
+    "validation.less_than_equal": "La valeur doit être inférieure ou égale à {le}",
+    "validation.string_too_short": "La chaîne doit contenir au moins {min_length} caractères",
+    "validation.string_too_long": "La chaîne doit contenir au plus {max_length} caractères",
+    "validation.too_short": "Au moins {min_length} éléments sont attendus",
+    "validation.too_long": "Au plus {max_length} éléments sont attendus",
+    "validation.json_invalid": "JSON invalide",
+}
+
+CATALOGS: Dict[str, Catalog] = {"en": ENGLISH, "fr": FRENCH}
@@ -0,0 +1,374 @@
+"""
+Prometheus metrics of the service
+
+Metrics are registered once at import. A labelled child is created the first time its label values are seen and
+kept, with its label string rendered once, so recording on a hot path is a dictionary lookup and a locked addition.
+Children of fixed label sets are created upfront and recorded through directly. The text exposition format is only
+rendered when /metrics is scraped; gauges describing the current state, like queue depths, are read at that time.
+"""
+
+import bisect
+import math
+import threading
+import time
+from typing import Callable, Dict, Hashable, Iterable, List, Optional, Sequence, Tuple
+
+CONTENT_TYPE = "text/plain; version=0.0.4; charset=utf-8"
+
+# Request durations from 5 ms to 30 s, uploads and synchronous reports take seconds
+HTTP_BUCKETS = (0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0)
+
+
+def _escape(value: str) -> str:
+    return value.replace("\\", "\\\\").replace("\n", "\\n").replace('"', '\\"')
+
+
+def _format_value(value: float) -> str:
+    if math.isinf(value):
+        return "+Inf" if value > 0 else "-Inf"
+    if math.isnan(value):
+        return "NaN"
+    return repr(float(value))
+
+
+def _label_string(names: Sequence[str], values: Sequence[str]) -> str:
+    return ",".join(f'{name}="{_escape(value)}"' for name, value in zip(names, values))
+
+
+class _Value:
+    """Value of a counter or gauge child"""
+
+    __slots__ = ("labels", "_value", "_lock")
+
+    def __init__(self, labels: str):
+        self.labels = labels
+        self._value = 0.0
+        self._lock = threading.Lock()
+
+    def inc(self, amount: float = 1) -> None:
+        with self._lock:
+            self._value += amount
+
+    def dec(self, amount: float = 1) -> None:
+        with self._lock:
+            self._value -= amount
+
+    def set(self, value: float) -> None:
+        with self._lock:
+            self._value = value
+
+    def get(self) -> float:
+        with self._lock:
+            return self._value
+
+
+class _Buckets:
+    """Observations of a histogram child"""
+
+    __slots__ = ("labels", "_bounds", "_counts", "_sum", "_lock")
+
+    def __init__(self, labels: str, bounds: Tuple[float, ...]):
+        self.labels = labels
+        self._bounds = bounds
+        # One count per bound plus the +Inf bucket, not cumulative until rendered
+        self._counts = [0] * (len(bounds) + 1)
+        self._sum = 0.0
+        self._lock = threading.Lock()
+
+    def observe(self, value: float) -> None:
+        index = bisect.bisect_left(self._bounds, value)
+        with self._lock:
+            self._counts[index] += 1
+            self._sum += value
+
+    def get(self) -> Tuple[List[int], float]:
+        with self._lock:
+            return list(self._counts), self._sum
+
+
+class Metric:
+    """Metric family with its help text and label names, children by label values"""
+
+    kind = "untyped"
+
+    def __init__(self, name: str, documentation: str, labelnames: Sequence[str] = (), registry=None):
+        self.name = name
+        self.documentation = documentation
+        self.labelnames = tuple(labelnames)
+        self._children: Dict[Tuple[Hashable, ...], object] = {}
+        self._lock = threading.Lock()
+        (registry if registry is not None else REGISTRY).register(self)
+
+    def labels(self, *values: Hashable):
+        """Child of the label values, created on first use; values are rendered with str"""
+        child = self._children.get(values)
+        if child is None:
+            if len(values) != len(self.labelnames):
+                raise ValueError(f"{self.name} expects labels {self.labelnames}, got {values}")
+            with self._lock:
+                child = self._children.get(values)
+                if child is None:
+                    child = self._new_child(_label_string(self.labelnames, [str(value) for value in values]))
+                    self._children[values] = child
+        return child
+
+    def _new_child(self, labels: str):
+        return _Value(labels)
+
+    def _default(self):
+        return self.labels()
+
+    def collect(self) -> Iterable[object]:
+        with self._lock:
+            return list(self._children.values())
+
+    def render(self) -> List[str]:
+        lines = [f"# HELP {self.name} {_escape(self.documentation)}", f"# TYPE {self.name} {self.kind}"]
+        for child in self.collect():
+            labels = f"{{{child.labels}}}" if child.labels else ""
+            lines.append(f"{self.name}{labels} {_format_value(child.get())}")
+        return lines
+
+
+class Counter(Metric):
+    """Monotonic count, named with the _total suffix"""
+
+    kind = "counter"
+
+    def inc(self, amount: float = 1) -> None:
+        self._default().inc(amount)
+
+
+class Gauge(Metric):
+    """
+    Value going up and down
+
+    With a callback the gauge describes the current state: on each scrape the callback returns the value of each
+    label tuple and only those are rendered.
+    """
+
+    kind = "gauge"
+
+    def __init__(
+        self,
+        name: str,
+        documentation: str,
+        labelnames: Sequence[str] = (),
+        registry=None,
+        callback: Optional[Callable[[], Dict[Tuple[Hashable, ...], float]]] = None,
+    ):
+        super().__init__(name, documentation, labelnames, registry)
+        self.callback = callback
+
+    def set(self, value: float) -> None:
+        self._default().set(value)
+
+    def inc(self, amount: float = 1) -> None:
+        self._default().inc(amount)
+
+    def dec(self, amount: float = 1) -> None:
+        self._default().dec(amount)
+
+    def collect(self) -> Iterable[object]:
+        if self.callback is None:
+            return super().collect()
+        children = []
+        for values, value in self.callback().items():
+            child = self.labels(*values)
+            child.set(value)
+            children.append(child)
+        return children
+
+
+class Histogram(Metric):
+    """Distribution of observations in cumulative buckets, with their sum and count"""
+
+    kind = "histogram"
+
+    def __init__(
+        self,
+        name: str,
+        documentation: str,
+        labelnames: Sequence[str] = (),
+        registry=None,
+        buckets: Sequence[float] = HTTP_BUCKETS,
+    ):
+        self.buckets = tuple(sorted(buckets))
+        super().__init__(name, documentation, labelnames, registry)
+
+    def _new_child(self, labels: str):
+        return _Buckets(labels, self.buckets)
+
+    def observe(self, value: float) -> None:
+        self._default().observe(value)
+
+    def render(self) -> List[str]:
+        lines = [f"# HELP {self.name} {_escape(self.documentation)}", f"# TYPE {self.name} {self.kind}"]
+        bounds = [_format_value(bound) for bound in self.buckets] + ["+Inf"]
+        for child in self.collect():
+            counts, total = child.get()
+            prefix = f"{child.labels}," if child.labels else ""
+            cumulative = 0
+            for bound, count in zip(bounds, counts):
+                cumulative += count
+                lines.append(f'{self.name}_bucket{{{prefix}le="{bound}"}} {cumulative}')
+            labels = f"{{{child.labels}}}" if child.labels else ""
+            lines.append(f"{self.name}_sum{labels} {_format_value(total)}")
+            lines.append(f"{self.name}_count{labels} {cumulative}")
+        return lines
+
+
+class MetricsRegistry:
+    """Metric families rendered together, in registration order"""
+
+    def __init__(self):
+        self._metrics: Dict[str, Metric] = {}
+        self._lock = threading.Lock()
+
+    def register(self, metric: Metric) -> None:
+        with self._lock:
+            if metric.name in self._metrics:
+                raise ValueError(f"Metric {metric.name} is already registered")
+            self._metrics[metric.name] = metric
+
+    def render(self) -> str:
+        with self._lock:
+            metrics = list(self._metrics.values())
+        return "\n".join(line for metric in metrics for line in metric.render()) + "\n"
+
+
+REGISTRY = MetricsRegistry()
+
+
+def _scheduler_jobs() -> Dict[Tuple[str], float]:
+    from app.shared.services import get_job_schedulers
+
+    scheduler = get_job_schedulers().get("detection")
+    return {
+        ("queued",): scheduler.queued if scheduler else 0,
+        ("running",): scheduler.running if scheduler else 0,
+    }
+
+
+def _queue_depths() -> Dict[Tuple[str], float]:
+    from app.shared.services import get_job_schedulers
+
+    return {(name,): scheduler.queued for name, scheduler in get_job_schedulers().items()}
+
+
+def _fingerprint_cache_hit_ratio() -> Dict[tuple, float]:
+    hits, misses = FINGERPRINT_CACHE_HITS.get(), FINGERPRINT_CACHE_MISSES.get()
+    return {(): hits / (hits + misses) if hits + misses else 0.0}
+
+
+# HTTP
+HTTP_REQUEST_DURATION = Histogram(
+    "pamp_http_request_duration_seconds",
+    "Duration of HTTP requests by route template, method and response status",
+    ["route", "method", "status"],
+)
+
+# Ingestion
+SUBMISSIONS_CREATED = Counter("pamp_submissions_created_total", "Submissions created by link type", ["link_type"])
+SUBMISSION_REJECTIONS = Counter(
+    "pamp_submission_rejections_total", "Submissions refused at creation by reason", ["reason"]
+)
+QUOTA_REJECTIONS = Counter(
+    "pamp_quota_rejections_total", "Submissions and detection runs refused by the quotas of their project", ["quota"]
+)
+INGESTED_FILES = Counter("pamp_ingested_files_total", "Files of submissions stored in the submission store")
+INGESTED_BYTES = Counter("pamp_ingested_bytes_total", "Bytes of submission files stored in the submission store")
+NOTIFIED_OBJECTS = Counter(
+    "pamp_notified_objects_total", "Objects of S3 event notifications by outcome, duplicates included", ["outcome"]
+)
+MALWARE_SCANS = Counter(
+    "pamp_malware_scans_total", "Uploads and extracted files scanned by result: clean, infected or error", ["result"]
+)
+FETCH_RETRIES = Counter(
+    "pamp_fetch_retries_total", "Git clones and storage requests attempted again after a transient failure", ["client"]
+)
+FETCH_SHORT_CIRCUITS = Counter(
+    "pamp_fetch_short_circuits_total", "Fetches failed at once, the circuit of their host being open", ["client"]
+)
+
+# Tokenization
+TOKENIZED_FILES = Counter(
+    "pamp_tokenized_files_total", "Files tokenized, fingerprint cache hits excluded", ["language"]
+)
+TOKENS = Counter("pamp_tokens_total", "Tokens produced by the tokenizer", ["language"])
+TOKENIZATION_SECONDS = Counter(
+    "pamp_tokenization_seconds_total", "Time spent tokenizing, summed across workers", ["language"]
+)
+
+# Fingerprint cache
+FINGERPRINT_CACHE_LOOKUPS = Counter(
+    "pamp_fingerprint_cache_lookups_total", "Fingerprint cache lookups by result: hit, miss or error", ["result"]
+)
+FINGERPRINT_CACHE_HITS = FINGERPRINT_CACHE_LOOKUPS.labels("hit")
+FINGERPRINT_CACHE_MISSES = FINGERPRINT_CACHE_LOOKUPS.labels("miss")
+FINGERPRINT_CACHE_ERRORS = FINGERPRINT_CACHE_LOOKUPS.labels("error")
+FINGERPRINT_CACHE_HIT_RATIO = Gauge(
+    "pamp_fingerprint_cache_hit_ratio",
+    "Share of fingerprint cache lookups that were hits since the process started",
+    callback=_fingerprint_cache_hit_ratio,
+)
+
+# Detection
+DETECTION_JOBS = Gauge("pamp_detection_jobs", "Detection runs queued or running", ["state"], callback=_scheduler_jobs)
+QUEUE_DEPTH = Gauge("pamp_queue_depth", "Jobs waiting for a slot by queue", ["queue"], callback=_queue_depths)
+COMPARED_PAIRS = Counter(
+    "pamp_compared_pairs_total", "Pairs recorded by detection runs by status, rate() gives pairs per second", ["status"]
+)
+DETECTION_TIMEOUTS = Counter(
+    "pamp_detection_timeouts_total", "Files, pairs and runs stopped by their detection timeout", ["scope"]
+)
+SCHEDULE_TICKS = Counter(
+    "pamp_schedule_ticks_total", "Ticks of detection schedules by outcome, skipped ones included", ["outcome"]
+)
+
+# Storage
+STORAGE_ERRORS = Counter("pamp_storage_errors_total", "Failed operations of the submission store", ["backend"])
+
+# Audit log
+AUDIT_WRITE_FAILURES = Counter(
+    "pamp_audit_write_failures_total", "Audit entries that could not be written, by action", ["action"]
+)
+
+
+class MetricsMiddleware:
+    """
+    ASGI middleware timing every HTTP request
+
+    Requests are labelled by the template of the route they matched, /runs/{run_id} rather than each run, so the
+    label sets stay bounded; requests matching no route are labelled unmatched.
+    """
+
+    UNMATCHED = "unmatched"
+
+    def __init__(self, app):
+        self.app = app
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http":
+            await self.app(scope, receive, send)
+            return
+
+        started = time.perf_counter()
+        status = 500
+
+        async def send_with_status(message):
+            nonlocal status
+            if message["type"] == "http.response.start":
+                status = message["status"]
+            await send(message)
+
+        try:
+            await self.app(scope, receive, send_with_status)
+        finally:
+            route = getattr(scope.get("route"), "path", self.UNMATCHED)
+            HTTP_REQUEST_DURATION.labels(route, scope["method"], status).observe(time.perf_counter() - started)
+
+
+def render_metrics() -> str:
+    """Every registered metric in the Prometheus text exposition format"""
+    return REGISTRY.render()
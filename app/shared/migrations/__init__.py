@@ -0,0 +1,21 @@
+from .migration_runner import (
+    DatabaseAheadException,
+    Migration,
+    MigrationException,
+    MigrationLock,
+    MigrationLockTimeoutException,
+    MigrationRunner,
+    load_migrations,
+    run_migrations,
+)
+
+__all__ = [
+    "DatabaseAheadException",
+    "Migration",
+    "MigrationException",
+    "MigrationLock",
+    "MigrationLockTimeoutException",
+    "MigrationRunner",
+    "load_migrations",
+    "run_migrations",
+]
@@ -0,0 +1,249 @@
+"""
+Embedded schema migrations.
+
+Migrations are modules of ``app.shared.migrations.versions`` named ``v{NNNN}_{description}.py``
+exposing an ``upgrade(connection)`` function. They ship with the application and are applied in order at
+startup, each in its own transaction together with its row of the ``schema_migrations`` table.
+"""
+
+import importlib
+import logging
+import pkgutil
+import re
+import threading
+import time
+from dataclasses import dataclass
+from typing import Callable, Dict, List, Optional, Tuple
+
+from sqlalchemy import Column, DateTime, Integer, MetaData, String, Table, select, text
+from sqlalchemy.engine import Connection, Engine
+
+from app.shared.exceptions import DatabaseException
+from app.shared.timestamps import utc_now
+
+logger = logging.getLogger(__name__)
+
+VERSIONS_PACKAGE = "app.shared.migrations.versions"
+
+# Arbitrary application-wide key of the PostgreSQL advisory lock taken while migrating
+MIGRATION_LOCK_KEY = 7_310_452_019
+
+_MODULE_PATTERN = re.compile(r"^v(\d{4})_(\w+)$")
+
+_migration_metadata = MetaData()
+schema_migrations = Table(
+    "schema_migrations",
+    _migration_metadata,
+    Column("version", Integer, primary_key=True),
+    Column("name", String(255), nullable=False),
+    Column("applied_at", DateTime(timezone=True), nullable=False),
+)
+
+# Databases without advisory locks (SQLite) are only migrated concurrently from threads of one process
+_local_locks: Dict[Tuple[str, int], threading.Lock] = {}
+_local_locks_guard = threading.Lock()
+
+
+class MigrationException(DatabaseException):
+    """Raised when the schema cannot be migrated"""
+
+    def __init__(self, detail: str):
+        super().__init__(f"Migration failed: {detail}")
+
+
+class DatabaseAheadException(MigrationException):
+    """Raised when the database was migrated by a newer build than the running one"""
+
+    def __init__(self, database_version: int, latest_known_version: int):
+        super().__init__(
+            f"database schema is at version {database_version} but this build only knows migrations up to "
+            f"{latest_known_version}; refusing to start against a newer schema, deploy a matching build"
+        )
+        self.database_version = database_version
+        self.latest_known_version = latest_known_version
+
+
+class MigrationLockTimeoutException(MigrationException):
+    """Raised when another instance holds the migration lock for too long"""
+
+    def __init__(self, timeout_seconds: float):
+        super().__init__(f"could not acquire the migration lock within {timeout_seconds:g} seconds")
+
+
+@dataclass
+class Migration:
+    """One schema change"""
+
+    version: int
+    name: str
+    upgrade: Callable[[Connection], None]
+
+
+def load_migrations(package: str = VERSIONS_PACKAGE) -> List[Migration]:
+    """
+    Discover the migrations embedded in a package, sorted by version
+
+    Raises:
+        MigrationException: If two modules share a version or a module has no upgrade function
+    """
+    module_package = importlib.import_module(package)
+    migrations: Dict[int, Migration] = {}
+    for module_info in pkgutil.iter_modules(module_package.__path__):
+        match = _MODULE_PATTERN.match(module_info.name)
+        if not match:
+            continue
+        version = int(match.group(1))
+        if version in migrations:
+            raise MigrationException(f"duplicate migration version {version}")
+
+        module = importlib.import_module(f"{package}.{module_info.name}")
+        upgrade = getattr(module, "upgrade", None)
+        if not callable(upgrade):
+            raise MigrationException(f"migration {module_info.name} has no upgrade function")
+        migrations[version] = Migration(version=version, name=match.group(2), upgrade=upgrade)
+    return [migrations[version] for version in sorted(migrations)]
+
+
+class MigrationLock:
+    """
+    Exclusive lock held while migrating
+
+    PostgreSQL uses a session-level advisory lock so that replicas starting together migrate one at a time,
+    other databases fall back to a lock shared by the threads of this process.
+    """
+
+    def __init__(
+        self,
+        engine: Engine,
+        key: int = MIGRATION_LOCK_KEY,
+        timeout_seconds: float = 300,
+        poll_interval_seconds: float = 0.5,
+    ):
+        self.engine = engine
+        self.key = key
+        self.timeout_seconds = timeout_seconds
+        self.poll_interval_seconds = poll_interval_seconds
+        self._connection: Optional[Connection] = None
+        self._local_lock: Optional[threading.Lock] = None
+
+    @property
+    def uses_advisory_lock(self) -> bool:
+        return self.engine.dialect.name == "postgresql"
+
+    def acquire(self) -> None:
+        if not self.uses_advisory_lock:
+            with _local_locks_guard:
+                self._local_lock = _local_locks.setdefault((str(self.engine.url), self.key), threading.Lock())
+            if not self._local_lock.acquire(timeout=self.timeout_seconds):
+                raise MigrationLockTimeoutException(self.timeout_seconds)
+            return
+
+        self._connection = self.engine.connect()
+        deadline = time.monotonic() + self.timeout_seconds
+        while True:
+            acquired = self._connection.execute(text("SELECT pg_try_advisory_lock(:key)"), {"key": self.key}).scalar()
+            self._connection.commit()
+            if acquired:
+                return
+            if time.monotonic() >= deadline:
+                self._connection.close()
+                self._connection = None
+                raise MigrationLockTimeoutException(self.timeout_seconds)
+            logger.info("Waiting for another instance to finish migrating the database")
+            time.sleep(self.poll_interval_seconds)
+
+    def release(self) -> None:
+        if self._local_lock is not None:
+            self._local_lock.release()
+            self._local_lock = None
+        if self._connection is not None:
+            try:
+                self._connection.execute(text("SELECT pg_advisory_unlock(:key)"), {"key": self.key})
+                self._connection.commit()
+            finally:
+                self._connection.close()
+                self._connection = None
+
+    def __enter__(self) -> "MigrationLock":
+        self.acquire()
+        return self
+
+    def __exit__(self, exc_type, exc, traceback) -> None:
+        self.release()
+
+
+class MigrationRunner:
+    """Applies the embedded migrations a database is missing"""
+
+    def __init__(
+        self,
+        engine: Engine,
+        migrations: Optional[List[Migration]] = None,
+        lock_timeout_seconds: float = 300,
+    ):
+        self.engine = engine
+        self.migrations = migrations if migrations is not None else load_migrations()
+        self.lock_timeout_seconds = lock_timeout_seconds
+
+    @property
+    def latest_version(self) -> int:
+        return self.migrations[-1].version if self.migrations else 0
+
+    def applied_versions(self) -> List[int]:
+        """Versions recorded in the schema_migrations table, empty for a database never migrated"""
+        with self.engine.connect() as connection:
+            if not self.engine.dialect.has_table(connection, schema_migrations.name):
+                return []
+            return sorted(connection.execute(select(schema_migrations.c.version)).scalars())
+
+    def check_not_ahead(self, applied_versions: List[int]) -> None:
+        """
+        Raises:
+            DatabaseAheadException: If the database holds migrations this build does not know
+        """
+        if applied_versions and applied_versions[-1] > self.latest_version:
+            raise DatabaseAheadException(applied_versions[-1], self.latest_version)
+
+    def upgrade(self) -> List[int]:
+        """
+        Apply every pending migration under the migration lock
+
+        Returns:
+            Versions applied by this call, empty when the schema was already up to date
+        """
+        with MigrationLock(self.engine, timeout_seconds=self.lock_timeout_seconds):
+            _migration_metadata.create_all(self.engine)
+            applied = self.applied_versions()
+            self.check_not_ahead(applied)
+
+            newly_applied = []
+            for migration in self.migrations:
+                if migration.version in applied:
+                    continue
+                logger.info(f"Applying migration {migration.version:04d} {migration.name}")
+                try:
+                    with self.engine.begin() as connection:
+                        migration.upgrade(connection)
+                        connection.execute(
+                            schema_migrations.insert().values(
+                                version=migration.version, name=migration.name, applied_at=utc_now()
+                            )
+                        )
+                except Exception as e:
+                    raise MigrationException(f"migration {migration.version:04d} {migration.name}: {str(e)}")
+                newly_applied.append(migration.version)
+
+        if newly_applied:
+            logger.info(f"Database migrated to version {self.latest_version:04d}")
+        else:
+            logger.info(f"Database schema up to date at version {self.latest_version:04d}")
+        return newly_applied
+
+
+def run_migrations(engine: Engine, lock_timeout_seconds: float = 300) -> List[int]:
+    """Migrate the database to the latest embedded version, logging why startup must stop on failure"""
+    try:
+        return MigrationRunner(engine, lock_timeout_seconds=lock_timeout_seconds).upgrade()
+    except DatabaseAheadException as e:
+        logger.critical(f"Refusing to start: {e.detail}")
+        raise
@@ -0,0 +1,57 @@
+"""
+Idempotent schema operations for migrations.
+
+Migrations create their tables from definitions frozen as they were when the migration was written, never from the
+models, so that a fresh database gets its later columns from the migrations adding them like any other. Databases
+created by create_all before migrations existed may already have some of them, migrations therefore check the live
+schema before changing it.
+"""
+
+from typing import Iterable, Optional
+
+from sqlalchemy import Table, inspect, text
+from sqlalchemy.engine import Connection
+from sqlalchemy.types import TypeEngine
+
+
+def has_table(connection: Connection, table_name: str) -> bool:
+    return inspect(connection).has_table(table_name)
+
+
+def has_column(connection: Connection, table_name: str, column_name: str) -> bool:
+    return any(column["name"] == column_name for column in inspect(connection).get_columns(table_name))
+
+
+def has_index(connection: Connection, table_name: str, index_name: str) -> bool:
+    return any(index["name"] == index_name for index in inspect(connection).get_indexes(table_name))
+
+
+def create_tables_if_missing(connection: Connection, tables: Iterable[Table]) -> None:
+    """Create the given tables and their indexes, leaving existing ones untouched"""
+    for table in tables:
+        table.create(connection, checkfirst=True)
+
+
+def add_column_if_missing(
+    connection: Connection,
+    table_name: str,
+    column_name: str,
+    column_type: TypeEngine,
+    nullable: bool = True,
+    server_default: Optional[str] = None,
+) -> bool:
+    """
+    Add a column unless it already exists, returns True if it was added
+
+    Non-nullable columns need a server_default so that existing rows get a value.
+    """
+    if has_column(connection, table_name, column_name):
+        return False
+
+    ddl = f"ALTER TABLE {table_name} ADD COLUMN {column_name} {column_type.compile(dialect=connection.dialect)}"
+    if server_default is not None:
+        ddl += f" DEFAULT {server_default}"
+    if not nullable:
+        ddl += " NOT NULL"
+    connection.execute(text(ddl))
+    return True
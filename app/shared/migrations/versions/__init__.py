@@ -0,0 +1 @@
+# Embedded migrations, one v{NNNN}_{description}.py module per schema change
@@ -0,0 +1,170 @@
+"""
+Baseline schema, as created by create_all at startup before migrations existed
+
+The tables are defined as they were then rather than from the models, the columns added since come from the later
+migrations. Tables that already exist are left untouched so that databases deployed before migrations are adopted.
+"""
+
+from sqlalchemy import JSON, Column, DateTime, Enum, Float, ForeignKey, Integer, MetaData, String, Table, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+
+metadata = MetaData()
+
+# Shared by the similarities and the pairs, PostgreSQL creates the native type once
+similarity_status = Enum("PENDING", "PROCESSING", "COMPLETED", "FAILED", name="similaritystatus")
+
+submission = Table(
+    "submission",
+    metadata,
+    Column("link", String(), nullable=False),
+    Column("project_uuid", Uuid(), nullable=False),
+    Column("group_uuid", Uuid(), nullable=False),
+    Column("project_step_uuid", Uuid(), nullable=False),
+    Column("link_type", Enum("S3", "GITHUB", "GITLAB", name="linktype")),
+    Column("description", String(1000)),
+    Column("submitted_by_uuid", Uuid()),
+    Column("file_size_bytes", Integer()),
+    Column("file_count", Integer()),
+    Column("id", Uuid(), primary_key=True),
+    Column("upload_date_time", DateTime(), nullable=False),
+    Column(
+        "status",
+        Enum("PENDING", "PROCESSING", "COMPLETED", "FAILED", "REJECTED", name="submissionstatus"),
+        nullable=False,
+    ),
+    Column("created_at", DateTime(), nullable=False),
+    Column("updated_at", DateTime()),
+    Column("ip_address", String(45)),
+    Column("user_agent", String(500)),
+    Column("rule_results", String()),
+)
+
+submission_similarity = Table(
+    "submission_similarity",
+    metadata,
+    Column("id", Uuid(), primary_key=True),
+    Column("submission_id", Uuid(), ForeignKey("submission.id"), nullable=False),
+    Column("compared_submission_id", Uuid(), ForeignKey("submission.id"), nullable=False),
+    Column("project_uuid", Uuid(), nullable=False),
+    Column("project_step_uuid", Uuid(), nullable=False),
+    Column("jaccard_similarity", Float(), nullable=False),
+    Column("type_similarity", Float(), nullable=False),
+    Column("overall_similarity", Float(), nullable=False),
+    Column("shared_blocks_count", Integer(), nullable=False),
+    Column("average_shared_similarity", Float(), nullable=False),
+    Column("structural_similarity", Float(), nullable=False),
+    Column("type_sequence_similarity", Float(), nullable=False),
+    Column("flow_similarity", Float(), nullable=False),
+    Column("operation_similarity", Float(), nullable=False),
+    Column("detection_algorithm", String(), nullable=False),
+    Column("detection_version", String(), nullable=False),
+    Column("similarity_details", JSON()),
+    Column("shared_blocks", JSON()),
+    Column("visualization_data", JSON()),
+    Column("status", similarity_status, nullable=False),
+    Column("created_at", DateTime(), nullable=False),
+    Column("updated_at", DateTime()),
+    Column("processing_time_seconds", Float()),
+    Column("error_message", String()),
+)
+
+detection_run = Table(
+    "detection_run",
+    metadata,
+    Column("id", Uuid(), primary_key=True),
+    Column("project_uuid", Uuid(), nullable=False, index=True),
+    Column("project_step_uuid", Uuid(), nullable=False, index=True),
+    Column("trigger", Enum("SUBMISSION", "MANUAL", name="detectionruntrigger"), nullable=False),
+    Column("trigger_submission_id", Uuid()),
+    Column("detection_algorithm", String(), nullable=False),
+    Column("detection_version", String(), nullable=False),
+    Column("parameters", JSON()),
+    Column("status", Enum("RUNNING", "COMPLETED", "FAILED", name="detectionrunstatus"), nullable=False),
+    Column("total_pairs", Integer(), nullable=False),
+    Column("completed_pairs", Integer(), nullable=False),
+    Column("failed_pairs", Integer(), nullable=False),
+    Column("started_at", DateTime(), nullable=False),
+    Column("finished_at", DateTime()),
+    Column("error_message", String()),
+)
+
+detection_run_participant = Table(
+    "detection_run_participant",
+    metadata,
+    Column("id", Uuid(), primary_key=True),
+    Column("run_id", Uuid(), ForeignKey("detection_run.id"), nullable=False, index=True),
+    Column("submission_id", Uuid(), nullable=False, index=True),
+    Column("group_uuid", Uuid(), nullable=False),
+    Column("submitted_by_uuid", Uuid(), index=True),
+)
+
+detection_pair = Table(
+    "detection_pair",
+    metadata,
+    Column("id", Uuid(), primary_key=True),
+    Column("run_id", Uuid(), ForeignKey("detection_run.id"), nullable=False, index=True),
+    Column("project_uuid", Uuid(), nullable=False, index=True),
+    Column("project_step_uuid", Uuid(), nullable=False),
+    Column("submission_id", Uuid(), nullable=False, index=True),
+    Column("compared_submission_id", Uuid(), nullable=False, index=True),
+    Column("submitted_by_uuid", Uuid(), index=True),
+    Column("compared_submitted_by_uuid", Uuid(), index=True),
+    Column("similarity_id", Uuid()),
+    Column("overall_similarity", Float(), nullable=False, index=True),
+    Column("jaccard_similarity", Float(), nullable=False),
+    Column("type_similarity", Float(), nullable=False),
+    Column("structural_similarity", Float(), nullable=False),
+    Column("type_sequence_similarity", Float(), nullable=False),
+    Column("flow_similarity", Float(), nullable=False),
+    Column("operation_similarity", Float(), nullable=False),
+    Column("fragments_count", Integer(), nullable=False),
+    Column("status", similarity_status, nullable=False),
+    Column("error_message", String()),
+    Column("processing_time_seconds", Float()),
+    Column("created_at", DateTime(), nullable=False),
+)
+
+detection_fragment = Table(
+    "detection_fragment",
+    metadata,
+    Column("id", Uuid(), primary_key=True),
+    Column("pair_id", Uuid(), ForeignKey("detection_pair.id"), nullable=False, index=True),
+    Column("run_id", Uuid(), nullable=False, index=True),
+    Column("fragment_type", Enum("FILE", "BLOCK", name="fragmenttype"), nullable=False),
+    Column("file1_path", String(), nullable=False),
+    Column("file2_path", String(), nullable=False),
+    Column("file1_start_line", Integer()),
+    Column("file1_end_line", Integer()),
+    Column("file2_start_line", Integer()),
+    Column("file2_end_line", Integer()),
+    Column("similarity", Float(), nullable=False),
+    Column("details", JSON()),
+)
+
+project_retention_policy = Table(
+    "project_retention_policy",
+    metadata,
+    Column("id", Uuid(), primary_key=True),
+    Column("project_uuid", Uuid(), nullable=False, index=True, unique=True),
+    Column("submission_retention_days", Integer()),
+    Column("report_retention_days", Integer()),
+    Column("created_at", DateTime(), nullable=False),
+    Column("updated_at", DateTime()),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(
+        connection,
+        [
+            submission,
+            submission_similarity,
+            detection_run,
+            detection_run_participant,
+            detection_pair,
+            detection_fragment,
+            project_retention_policy,
+        ],
+    )
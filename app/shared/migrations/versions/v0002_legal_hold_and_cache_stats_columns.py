@@ -0,0 +1,17 @@
+"""
+Legal hold and fingerprint cache statistics columns
+
+Databases whose tables were created before these columns existed only get them from here.
+"""
+
+from sqlalchemy import JSON, Boolean, String
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    for table_name in ("submission", "detection_run"):
+        add_column_if_missing(connection, table_name, "legal_hold", Boolean(), nullable=False, server_default="false")
+        add_column_if_missing(connection, table_name, "legal_hold_reason", String(500))
+    add_column_if_missing(connection, "detection_run", "cache_stats", JSON())
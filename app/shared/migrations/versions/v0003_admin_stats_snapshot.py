@@ -0,0 +1,21 @@
+"""
+Snapshot table of the admin usage statistics
+"""
+
+from sqlalchemy import JSON, Column, DateTime, Float, MetaData, String, Table
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+
+admin_stats_snapshot = Table(
+    "admin_stats_snapshot",
+    MetaData(),
+    Column("name", String(50), primary_key=True),
+    Column("computed_at", DateTime(), nullable=False),
+    Column("duration_seconds", Float(), nullable=False),
+    Column("data", JSON()),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [admin_stats_snapshot])
@@ -0,0 +1,12 @@
+"""
+Per-stage timings of profiled detection runs
+"""
+
+from sqlalchemy import JSON
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "detection_run", "profile", JSON())
@@ -0,0 +1,13 @@
+"""
+Incomplete status of detection runs that lost batches of pairs
+
+PostgreSQL stores the run status in a native enum type, other databases in a plain string column.
+"""
+
+from sqlalchemy import text
+from sqlalchemy.engine import Connection
+
+
+def upgrade(connection: Connection) -> None:
+    if connection.dialect.name == "postgresql":
+        connection.execute(text("ALTER TYPE detectionrunstatus ADD VALUE IF NOT EXISTS 'INCOMPLETE'"))
@@ -0,0 +1,14 @@
+"""
+Pruned status and estimated similarity of detection pairs
+"""
+
+from sqlalchemy import Float, text
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    if connection.dialect.name == "postgresql":
+        connection.execute(text("ALTER TYPE similaritystatus ADD VALUE IF NOT EXISTS 'PRUNED'"))
+    add_column_if_missing(connection, "detection_pair", "estimated_similarity", Float())
@@ -0,0 +1,19 @@
+"""
+Priority detection runs were queued with
+
+PostgreSQL stores the priority in a native enum type created here, other databases in a plain string column.
+"""
+
+from sqlalchemy import Enum
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    priority_type = Enum("LOW", "NORMAL", "HIGH", "URGENT", name="jobpriority")
+    if connection.dialect.name == "postgresql":
+        priority_type.create(connection, checkfirst=True)
+    add_column_if_missing(
+        connection, "detection_run", "priority", priority_type, nullable=False, server_default="'NORMAL'"
+    )
@@ -0,0 +1,12 @@
+"""
+Matched token count of detection pairs
+"""
+
+from sqlalchemy import Integer
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "detection_pair", "matched_tokens", Integer())
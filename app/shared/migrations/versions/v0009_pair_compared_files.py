@@ -0,0 +1,12 @@
+"""
+Compared files of detection pairs with their token counts
+"""
+
+from sqlalchemy import JSON
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "detection_pair", "compared_files", JSON())
@@ -0,0 +1,17 @@
+"""
+Not comparable status of detection pairs, reason of not comparable participants and low confidence pairs
+"""
+
+from sqlalchemy import Boolean, String, text
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    if connection.dialect.name == "postgresql":
+        connection.execute(text("ALTER TYPE similaritystatus ADD VALUE IF NOT EXISTS 'NOT_COMPARABLE'"))
+    add_column_if_missing(connection, "detection_run_participant", "not_comparable_reason", String())
+    add_column_if_missing(
+        connection, "detection_pair", "low_confidence", Boolean(), nullable=False, server_default="false"
+    )
@@ -0,0 +1,92 @@
+"""
+Timestamps stored in UTC, project step configurations and lateness of submissions
+
+Timestamps were written as Paris local times without offset. PostgreSQL columns become timezone-aware, their values
+read as Paris times; SQLite, without timezone support, keeps naive columns whose values are rewritten in UTC.
+"""
+
+from datetime import datetime
+from zoneinfo import ZoneInfo
+
+from sqlalchemy import Boolean, Column, DateTime, Integer, MetaData, Table, Uuid, inspect, text
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing, create_tables_if_missing, has_table
+from app.shared.timestamps import UTC, UtcDateTime
+
+# Timezone the timestamps were written in
+LEGACY_TIMEZONE = "Europe/Paris"
+
+TIMESTAMP_COLUMNS = {
+    "submission": ["upload_date_time", "created_at", "updated_at"],
+    "submission_similarity": ["created_at", "updated_at"],
+    "detection_run": ["started_at", "finished_at"],
+    "detection_pair": ["created_at"],
+    "project_retention_policy": ["created_at", "updated_at"],
+    "admin_stats_snapshot": ["computed_at"],
+}
+
+# Format SQLAlchemy stores SQLite timestamps in
+SQLITE_FORMAT = "%Y-%m-%d %H:%M:%S.%f"
+
+project_step_config = Table(
+    "project_step_config",
+    MetaData(),
+    Column("id", Uuid(), primary_key=True),
+    Column("project_uuid", Uuid(), nullable=False, index=True),
+    Column("project_step_uuid", Uuid(), nullable=False, index=True, unique=True),
+    Column("deadline", UtcDateTime()),
+    Column("created_at", UtcDateTime(), nullable=False),
+    Column("updated_at", UtcDateTime()),
+)
+
+
+def _naive_utc(value: str) -> str:
+    moment = datetime.fromisoformat(value)
+    # Times of the hour repeated when DST ends are ambiguous, they are taken as its first occurrence
+    if moment.tzinfo is None:
+        moment = moment.replace(tzinfo=ZoneInfo(LEGACY_TIMEZONE))
+    return moment.astimezone(UTC).replace(tzinfo=None).strftime(SQLITE_FORMAT)
+
+
+def _convert_postgresql(connection: Connection, table_name: str, columns: dict) -> None:
+    for column_name, column_type in columns.items():
+        if isinstance(column_type, DateTime) and not column_type.timezone:
+            connection.execute(
+                text(
+                    f"ALTER TABLE {table_name} ALTER COLUMN {column_name} TYPE TIMESTAMP WITH TIME ZONE "
+                    f"USING {column_name} AT TIME ZONE '{LEGACY_TIMEZONE}'"
+                )
+            )
+
+
+def _rewrite_values(connection: Connection, table_name: str, columns: dict) -> None:
+    for column_name in columns:
+        rows = connection.execute(
+            text(f"SELECT rowid, {column_name} FROM {table_name} WHERE {column_name} IS NOT NULL")
+        ).all()
+        for rowid, value in rows:
+            connection.execute(
+                text(f"UPDATE {table_name} SET {column_name} = :value WHERE rowid = :rowid"),
+                {"value": _naive_utc(str(value)), "rowid": rowid},
+            )
+
+
+def upgrade(connection: Connection) -> None:
+    for table_name, column_names in TIMESTAMP_COLUMNS.items():
+        if not has_table(connection, table_name):
+            continue
+        # Legacy tables may lack some of the timestamp columns
+        columns = {
+            column["name"]: column["type"]
+            for column in inspect(connection).get_columns(table_name)
+            if column["name"] in column_names
+        }
+        if connection.dialect.name == "postgresql":
+            _convert_postgresql(connection, table_name, columns)
+        elif connection.dialect.name == "sqlite":
+            _rewrite_values(connection, table_name, columns)
+
+    add_column_if_missing(connection, "submission", "is_late", Boolean(), nullable=False, server_default="false")
+    add_column_if_missing(connection, "submission", "minutes_late", Integer())
+    create_tables_if_missing(connection, [project_step_config])
@@ -0,0 +1,13 @@
+"""
+Files left out of detection runs because they failed, and partial pairs
+"""
+
+from sqlalchemy import JSON, Boolean
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "detection_run", "file_errors", JSON())
+    add_column_if_missing(connection, "detection_pair", "partial", Boolean(), nullable=False, server_default="false")
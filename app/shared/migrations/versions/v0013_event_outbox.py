@@ -0,0 +1,26 @@
+"""
+Outbox of the lifecycle events of submissions and detection runs
+"""
+
+from sqlalchemy import JSON, Column, Integer, MetaData, String, Table, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+from app.shared.timestamps import UtcDateTime
+
+event_outbox = Table(
+    "event_outbox",
+    MetaData(),
+    Column("sequence", Integer(), primary_key=True),
+    Column("event_id", Uuid(), nullable=False, unique=True),
+    Column("event_type", String(100), nullable=False, index=True),
+    Column("payload", JSON()),
+    Column("occurred_at", UtcDateTime(), nullable=False),
+    Column("published_at", UtcDateTime(), index=True),
+    Column("attempts", Integer(), nullable=False),
+    Column("last_error", String()),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [event_outbox])
@@ -0,0 +1,35 @@
+"""
+Deliveries of the summaries of finished runs to the main PAMP service
+"""
+
+from sqlalchemy import JSON, Column, Enum, ForeignKey, Integer, MetaData, String, Table, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+from app.shared.timestamps import UtcDateTime
+
+metadata = MetaData()
+
+# Referenced by the deliveries, created by the baseline
+Table("detection_run", metadata, Column("id", Uuid(), primary_key=True))
+
+callback_delivery = Table(
+    "callback_delivery",
+    metadata,
+    Column("id", Uuid(), primary_key=True),
+    Column("run_id", Uuid(), ForeignKey("detection_run.id"), nullable=False, index=True),
+    Column("url", String(2048), nullable=False),
+    Column("payload", JSON()),
+    Column("status", Enum("PENDING", "DELIVERED", "FAILED", name="callbackstatus"), nullable=False, index=True),
+    Column("attempts", Integer(), nullable=False),
+    Column("next_attempt_at", UtcDateTime(), index=True),
+    Column("last_attempt_at", UtcDateTime()),
+    Column("last_status_code", Integer()),
+    Column("last_error", String()),
+    Column("delivered_at", UtcDateTime()),
+    Column("created_at", UtcDateTime(), nullable=False),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [callback_delivery])
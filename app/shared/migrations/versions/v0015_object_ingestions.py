@@ -0,0 +1,34 @@
+"""
+Objects notified by buckets, one row per object version for the idempotency of their ingestion
+"""
+
+from sqlalchemy import Column, Enum, Integer, MetaData, String, Table, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+from app.shared.timestamps import UtcDateTime
+
+object_ingestion = Table(
+    "object_ingestion",
+    MetaData(),
+    Column("id", Uuid(), primary_key=True),
+    Column("object_digest", String(64), nullable=False, unique=True),
+    Column("bucket", String(255), nullable=False),
+    Column("object_key", String(1024), nullable=False),
+    Column("version", String(1024), nullable=False),
+    Column(
+        "status",
+        Enum("PENDING", "INGESTED", "QUARANTINED", "REFUSED", name="objectingestionstatus"),
+        nullable=False,
+        index=True,
+    ),
+    Column("submission_id", Uuid(), index=True),
+    Column("reason", String()),
+    Column("notifications", Integer(), nullable=False),
+    Column("created_at", UtcDateTime(), nullable=False),
+    Column("updated_at", UtcDateTime(), nullable=False),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [object_ingestion])
@@ -0,0 +1,20 @@
+"""
+Interrupted status of detection runs, and the instance processing each run with its last heartbeat
+
+PostgreSQL stores the run status in a native enum type, other databases in a plain string column.
+"""
+
+from sqlalchemy import String, text
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing, has_index
+from app.shared.timestamps import UtcDateTime
+
+
+def upgrade(connection: Connection) -> None:
+    if connection.dialect.name == "postgresql":
+        connection.execute(text("ALTER TYPE detectionrunstatus ADD VALUE IF NOT EXISTS 'INTERRUPTED'"))
+    add_column_if_missing(connection, "detection_run", "instance_id", String(255))
+    add_column_if_missing(connection, "detection_run", "heartbeat_at", UtcDateTime())
+    if not has_index(connection, "detection_run", "ix_detection_run_instance_id"):
+        connection.execute(text("CREATE INDEX ix_detection_run_instance_id ON detection_run (instance_id)"))
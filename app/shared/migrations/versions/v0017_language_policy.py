@@ -0,0 +1,13 @@
+"""
+Language policy of project steps and the result of each submission against it
+"""
+
+from sqlalchemy import JSON
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "project_step_config", "language_policy", JSON())
+    add_column_if_missing(connection, "submission", "language_policy_result", JSON())
@@ -0,0 +1,31 @@
+"""
+Teams of students submitting together, and the team of each submission and run participant when it was submitted
+"""
+
+from sqlalchemy import JSON, Column, MetaData, String, Table, Uuid, text
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing, create_tables_if_missing, has_index
+from app.shared.timestamps import UtcDateTime
+
+team = Table(
+    "team",
+    MetaData(),
+    Column("id", Uuid(), primary_key=True),
+    Column("project_uuid", Uuid(), nullable=False, index=True),
+    Column("name", String(200), nullable=False),
+    Column("members", JSON(), nullable=False),
+    Column("created_at", UtcDateTime(), nullable=False),
+    Column("updated_at", UtcDateTime()),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [team])
+    add_column_if_missing(connection, "submission", "team_id", Uuid())
+    add_column_if_missing(connection, "submission", "team_name", String(200))
+    add_column_if_missing(connection, "submission", "team_members", JSON())
+    add_column_if_missing(connection, "detection_run_participant", "team_name", String())
+    add_column_if_missing(connection, "detection_run_participant", "team_members", JSON())
+    if not has_index(connection, "submission", "ix_submission_team_id"):
+        connection.execute(text("CREATE INDEX ix_submission_team_id ON submission (team_id)"))
@@ -0,0 +1,14 @@
+"""
+Declared collaborations of project steps and of the runs classifying their pairs, and the team of run participants
+"""
+
+from sqlalchemy import JSON, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "project_step_config", "declared_collaborations", JSON())
+    add_column_if_missing(connection, "detection_run", "declared_collaborations", JSON())
+    add_column_if_missing(connection, "detection_run_participant", "team_id", Uuid())
@@ -0,0 +1,40 @@
+"""
+Usage of projects counted against their quotas and the stored bytes of each submission, the submissions of existing
+projects counted from the submission table; bytes stored before the upgrade are not counted
+"""
+
+from sqlalchemy import BigInteger, Column, Integer, MetaData, Table, Uuid, column, func, select, table
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing, create_tables_if_missing
+from app.shared.timestamps import UtcDateTime, utc_now
+
+project_usage = Table(
+    "project_usage",
+    MetaData(),
+    Column("project_uuid", Uuid(), primary_key=True),
+    Column("submissions", Integer(), nullable=False),
+    Column("stored_bytes", BigInteger(), nullable=False),
+    Column("updated_at", UtcDateTime(), nullable=False),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "submission", "stored_bytes", BigInteger(), nullable=False, server_default="0")
+    create_tables_if_missing(connection, [project_usage])
+
+    if connection.execute(select(func.count()).select_from(project_usage)).scalar():
+        return
+    submission = table("submission", column("project_uuid", Uuid()))
+    counts = connection.execute(
+        select(submission.c.project_uuid, func.count()).group_by(submission.c.project_uuid)
+    ).all()
+    if counts:
+        now = utc_now()
+        connection.execute(
+            project_usage.insert(),
+            [
+                {"project_uuid": project_uuid, "submissions": count, "stored_bytes": 0, "updated_at": now}
+                for project_uuid, count in counts
+            ],
+        )
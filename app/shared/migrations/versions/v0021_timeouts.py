@@ -0,0 +1,14 @@
+"""
+Partial status of detection runs stopped by their timeout, and timed out status of their pairs
+
+PostgreSQL stores both statuses in native enum types, other databases in plain string columns.
+"""
+
+from sqlalchemy import text
+from sqlalchemy.engine import Connection
+
+
+def upgrade(connection: Connection) -> None:
+    if connection.dialect.name == "postgresql":
+        connection.execute(text("ALTER TYPE detectionrunstatus ADD VALUE IF NOT EXISTS 'PARTIAL'"))
+        connection.execute(text("ALTER TYPE similaritystatus ADD VALUE IF NOT EXISTS 'TIMED_OUT'"))
@@ -0,0 +1,12 @@
+"""
+Fetches retried while ingesting a notified object, listed on its ingestion
+"""
+
+from sqlalchemy import JSON
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "object_ingestion", "retries", JSON())
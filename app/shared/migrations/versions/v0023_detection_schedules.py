@@ -0,0 +1,44 @@
+"""
+Detection schedules of project steps and the schedule that started each run
+
+PostgreSQL stores the run trigger in a native enum type, other databases in a plain string column.
+"""
+
+from sqlalchemy import JSON, Boolean, Column, Enum, MetaData, String, Table, Uuid, text
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing, create_tables_if_missing, has_index
+from app.shared.timestamps import UtcDateTime
+
+detection_schedule = Table(
+    "detection_schedule",
+    MetaData(),
+    Column("id", Uuid(), primary_key=True),
+    Column("project_uuid", Uuid(), nullable=False, index=True),
+    Column("project_step_uuid", Uuid(), nullable=False, index=True),
+    Column("cron", String(255), nullable=False),
+    Column("timezone", String(64), nullable=False),
+    Column("scope", Enum("NEW", "FULL", name="detectionschedulescope"), nullable=False),
+    Column("parameters", JSON(), nullable=False),
+    Column("paused", Boolean(), nullable=False),
+    Column("next_run_at", UtcDateTime(), nullable=False, index=True),
+    Column("scanned_until", UtcDateTime(), nullable=False),
+    Column("last_run_at", UtcDateTime()),
+    Column(
+        "last_outcome",
+        Enum("STARTED", "NOTHING_NEW", "SKIPPED_RUNNING", "QUOTA_EXCEEDED", "FAILED", name="scheduleoutcome"),
+    ),
+    Column("last_error", String()),
+    Column("last_run_ids", JSON()),
+    Column("created_at", UtcDateTime(), nullable=False),
+    Column("updated_at", UtcDateTime()),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [detection_schedule])
+    add_column_if_missing(connection, "detection_run", "schedule_id", Uuid())
+    if not has_index(connection, "detection_run", "ix_detection_run_schedule_id"):
+        connection.execute(text("CREATE INDEX ix_detection_run_schedule_id ON detection_run (schedule_id)"))
+    if connection.dialect.name == "postgresql":
+        connection.execute(text("ALTER TYPE detectionruntrigger ADD VALUE IF NOT EXISTS 'SCHEDULED'"))
@@ -0,0 +1,42 @@
+"""
+Append-only audit log of sensitive operations
+"""
+
+from sqlalchemy import JSON, Column, Enum, Integer, MetaData, String, Table, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+from app.shared.timestamps import UtcDateTime
+
+audit_action = Enum(
+    "REPORT_VIEW",
+    "FRAGMENT_VIEW",
+    "FILE_DOWNLOAD",
+    "SUBMISSION_DELETE",
+    "CONFIG_CHANGE",
+    "PSEUDONYM_ACCESS",
+    name="auditaction",
+)
+
+audit_entry = Table(
+    "audit_entry",
+    MetaData(),
+    Column("id", Uuid(), primary_key=True),
+    Column("sequence", Integer(), nullable=False, index=True, unique=True),
+    Column("occurred_at", UtcDateTime(), nullable=False, index=True),
+    Column("action", audit_action, nullable=False, index=True),
+    Column("actor", String(255), nullable=False, index=True),
+    Column("project_uuid", Uuid(), index=True),
+    Column("resource_type", String(64), nullable=False),
+    Column("resource_id", String(255)),
+    Column("details", JSON()),
+    Column("ip_address", String(45)),
+    Column("user_agent", String(500)),
+    Column("request_id", String(128)),
+    Column("previous_hash", String(64), nullable=False),
+    Column("entry_hash", String(64), nullable=False),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [audit_entry])
@@ -0,0 +1,21 @@
+"""
+Tenant each project belongs to, for tenant isolation
+"""
+
+from sqlalchemy import Column, MetaData, String, Table, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+from app.shared.timestamps import UtcDateTime
+
+project_tenant = Table(
+    "project_tenant",
+    MetaData(),
+    Column("project_uuid", Uuid(), primary_key=True),
+    Column("tenant_id", String(64), nullable=False, index=True),
+    Column("assigned_at", UtcDateTime(), nullable=False),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [project_tenant])
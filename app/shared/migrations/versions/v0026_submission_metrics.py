@@ -0,0 +1,28 @@
+"""
+Code metrics of each submission, computed while it is first compared
+"""
+
+from sqlalchemy import JSON, Column, ForeignKey, MetaData, String, Table, Uuid
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import create_tables_if_missing
+from app.shared.timestamps import UtcDateTime
+
+metadata = MetaData()
+
+# Referenced by the metrics, created by the baseline
+Table("submission", metadata, Column("id", Uuid(), primary_key=True))
+
+submission_metrics = Table(
+    "submission_metrics",
+    metadata,
+    Column("submission_id", Uuid(), ForeignKey("submission.id"), primary_key=True),
+    Column("project_uuid", Uuid(), nullable=False, index=True),
+    Column("metrics_version", String(20), nullable=False),
+    Column("metrics", JSON(), nullable=False),
+    Column("computed_at", UtcDateTime(), nullable=False),
+)
+
+
+def upgrade(connection: Connection) -> None:
+    create_tables_if_missing(connection, [submission_metrics])
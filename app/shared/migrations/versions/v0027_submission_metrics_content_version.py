@@ -0,0 +1,12 @@
+"""
+Stored version of the files the code metrics of a submission were computed from
+"""
+
+from sqlalchemy import Integer
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "submission_metrics", "content_version", Integer())
@@ -0,0 +1,12 @@
+"""
+Reason the files of a submission could not be stored
+"""
+
+from sqlalchemy import String
+from sqlalchemy.engine import Connection
+
+from app.shared.migrations.operations import add_column_if_missing
+
+
+def upgrade(connection: Connection) -> None:
+    add_column_if_missing(connection, "submission", "storage_error", String())
@@ -0,0 +1,78 @@
+"""
+Per-stage timings of detection runs
+
+Stage durations are measured with the monotonic perf_counter clock and summed across workers, so with parallel
+workers the stages of a run can add up to more than its wall time. Shares are relative to the summed stages.
+"""
+
+import threading
+import time
+from contextlib import contextmanager
+from typing import Any, Dict, Iterator
+
+
+class StageProfiler:
+    """Thread-safe aggregate of the time spent and items processed in each stage"""
+
+    enabled = True
+
+    def __init__(self):
+        self._started = time.perf_counter()
+        self._seconds: Dict[str, float] = {}
+        self._counts: Dict[str, int] = {}
+        self._lock = threading.Lock()
+
+    def record(self, stage: str, seconds: float, count: int = 1) -> None:
+        with self._lock:
+            self._seconds[stage] = self._seconds.get(stage, 0.0) + seconds
+            self._counts[stage] = self._counts.get(stage, 0) + count
+
+    @contextmanager
+    def stage(self, stage: str, count: int = 1) -> Iterator[None]:
+        """Time the enclosed block as one occurrence of a stage processing count items"""
+        started = time.perf_counter()
+        try:
+            yield
+        finally:
+            self.record(stage, time.perf_counter() - started, count)
+
+    def to_dict(self) -> Dict[str, Any]:
+        with self._lock:
+            seconds, counts = dict(self._seconds), dict(self._counts)
+        total = sum(seconds.values())
+        return {
+            "wall_seconds": round(time.perf_counter() - self._started, 6),
+            "stages": {
+                stage: {
+                    "seconds": round(seconds[stage], 6),
+                    "count": counts[stage],
+                    "share": round(seconds[stage] / total * 100, 2) if total > 0 else 0.0,
+                }
+                for stage in sorted(seconds, key=seconds.get, reverse=True)
+            },
+        }
+
+    def summary(self) -> str:
+        """One line breakdown, slowest stage first"""
+        profile = self.to_dict()
+        stages = ", ".join(
+            f"{stage} {values['seconds']:.3f}s ({values['share']:.1f}%, {values['count']})"
+            for stage, values in profile["stages"].items()
+        )
+        return f"{profile['wall_seconds']:.3f}s wall: {stages or 'no stage recorded'}"
+
+
+class NullProfiler(StageProfiler):
+    """Profiler used when profiling is off, records nothing"""
+
+    enabled = False
+
+    def record(self, stage: str, seconds: float, count: int = 1) -> None:
+        pass
+
+    @contextmanager
+    def stage(self, stage: str, count: int = 1) -> Iterator[None]:
+        yield
+
+
+NULL_PROFILER = NullProfiler()
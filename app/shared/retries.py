@@ -0,0 +1,276 @@
+"""
+Retries of remote fetches, with exponential backoff and a circuit breaker per remote host
+
+Git clones and storage requests failing for a reason that may pass, a timeout, a 5xx answer, a reset connection,
+are attempted again after a wait doubling with each attempt, drawn partly at random so that the fetches of a bulk
+ingestion failing together do not retry together. Failures that would fail again, an authentication failure or a
+404, are raised at once.
+
+Each remote host has a circuit breaker: after consecutive transient failures it opens and fetches from the host
+fail at once with CircuitOpenError, instead of each waiting through its retry budget. Once the circuit has been
+open for its reset time, a single trial fetch goes through (half-open): its success closes the circuit, its
+failure opens it again.
+
+The attempts that failed and were retried are collected by recorded_retries, for the status of the item fetched.
+"""
+
+import contextlib
+import contextvars
+import logging
+import random
+import threading
+import time
+from dataclasses import asdict, dataclass
+from typing import Callable, Dict, Iterator, List, Optional, TypeVar
+
+from app.shared.metrics import FETCH_RETRIES, FETCH_SHORT_CIRCUITS
+
+logger = logging.getLogger(__name__)
+
+T = TypeVar("T")
+
+CLOSED = "closed"
+OPEN = "open"
+HALF_OPEN = "half_open"
+
+# Answers of a server that may be different a moment later
+TRANSIENT_STATUS_CODES = frozenset({408, 429, *range(500, 600)})
+
+# Connection errors of botocore, matched by name so that retries do not depend on boto3
+TRANSIENT_ERROR_NAMES = frozenset(
+    {
+        "EndpointConnectionError",
+        "ConnectionClosedError",
+        "ConnectTimeoutError",
+        "ReadTimeoutError",
+        "ResponseStreamingError",
+        "IncompleteReadError",
+    }
+)
+
+_retries: contextvars.ContextVar[Optional[List[dict]]] = contextvars.ContextVar("retries", default=None)
+
+
+class CircuitOpenError(Exception):
+    """Raised instead of fetching from a host whose circuit is open"""
+
+    def __init__(self, host: str, retry_after_seconds: float):
+        super().__init__(f"{host} is failing, fetches from it are suspended for {retry_after_seconds:.0f}s")
+        self.host = host
+        self.retry_after_seconds = retry_after_seconds
+
+
+@dataclass(frozen=True)
+class RetryPolicy:
+    """Attempts of a fetch and the waits between them, 1 attempt for no retry"""
+
+    max_attempts: int = 4
+    base_delay_seconds: float = 1.0
+    max_delay_seconds: float = 30.0
+    jitter: float = 0.5  # share of each wait drawn at random
+
+    def delay(self, attempt: int, draw: Callable[[], float] = random.random) -> float:
+        """Wait after a failed attempt, numbered from 1"""
+        delay = min(self.base_delay_seconds * 2 ** (attempt - 1), self.max_delay_seconds)
+        return delay * (1 - min(max(self.jitter, 0.0), 1.0) * draw())
+
+
+class CircuitBreaker:
+    """Circuit of a remote host, opened by consecutive transient failures, 0 failures never opens it"""
+
+    def __init__(
+        self, failure_threshold: int = 5, reset_seconds: float = 60.0, clock: Callable[[], float] = time.monotonic
+    ):
+        self.failure_threshold = failure_threshold
+        self.reset_seconds = reset_seconds
+        self._clock = clock
+        self._lock = threading.Lock()
+        self.state = CLOSED
+        self.failures = 0
+        self._opened_at = 0.0
+
+    def allow(self) -> bool:
+        """Whether a fetch may go, an open circuit letting a single trial through once its reset time passed"""
+        with self._lock:
+            if self.state == OPEN and self._clock() >= self._opened_at + self.reset_seconds:
+                self.state = HALF_OPEN
+                return True
+            return self.state == CLOSED
+
+    def retry_after(self) -> float:
+        """Seconds before the circuit lets a trial through, 0 when it is closed"""
+        with self._lock:
+            if self.state == CLOSED:
+                return 0.0
+            return max(self._opened_at + self.reset_seconds - self._clock(), 0.0)
+
+    def record_success(self) -> None:
+        """The host answered, even with a failure that is not transient"""
+        with self._lock:
+            self.state = CLOSED
+            self.failures = 0
+
+    def record_failure(self) -> bool:
+        """A transient failure, returns True if it opened the circuit"""
+        with self._lock:
+            self.failures += 1
+            if self.state == OPEN or (self.state == CLOSED and not 0 < self.failure_threshold <= self.failures):
+                return False
+            self.state = OPEN
+            self._opened_at = self._clock()
+            return True
+
+
+class CircuitBreakers:
+    """Circuit breakers by remote host, created closed the first time a host is fetched from"""
+
+    def __init__(self, clock: Callable[[], float] = time.monotonic):
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._breakers: Dict[str, CircuitBreaker] = {}
+
+    def get(self, host: str, failure_threshold: int, reset_seconds: float) -> CircuitBreaker:
+        """Breaker of a host, with the thresholds currently configured"""
+        with self._lock:
+            breaker = self._breakers.get(host)
+            if breaker is None:
+                breaker = self._breakers[host] = CircuitBreaker(failure_threshold, reset_seconds, self._clock)
+            breaker.failure_threshold, breaker.reset_seconds = failure_threshold, reset_seconds
+            return breaker
+
+
+# Breakers of the process, shared by every fetch
+CIRCUIT_BREAKERS = CircuitBreakers()
+
+
+def http_status(error: BaseException) -> Optional[int]:
+    """HTTP status of the answer an error was raised for, like botocore ClientError, None without"""
+    response = getattr(error, "response", None)
+    if isinstance(response, dict):
+        status = response.get("ResponseMetadata", {}).get("HTTPStatusCode")
+        return int(status) if status else None
+    status = getattr(response, "status_code", None) or getattr(error, "status_code", None)
+    return status if isinstance(status, int) else None
+
+
+def is_transient(error: BaseException) -> bool:
+    """Whether a failure may pass: errors flagged transient, timeouts, connection errors and 408, 429 or 5xx answers"""
+    transient = getattr(error, "transient", None)
+    if transient is not None:
+        return bool(transient)
+    if isinstance(error, (ConnectionError, TimeoutError)):
+        return True
+    status = http_status(error)
+    if status is not None:
+        return status in TRANSIENT_STATUS_CODES
+    return type(error).__name__ in TRANSIENT_ERROR_NAMES
+
+
+@dataclass(frozen=True)
+class RetriedAttempt:
+    """Attempt of a fetch that failed and was retried"""
+
+    client: str
+    host: str
+    attempt: int
+    error: str
+    delay_seconds: float
+
+    def to_dict(self) -> dict:
+        return {**asdict(self), "delay_seconds": round(self.delay_seconds, 3)}
+
+
+@contextlib.contextmanager
+def recorded_retries() -> Iterator[List[dict]]:
+    """Collect the retried attempts of the fetches made in the block, threads started with in_current_context too"""
+    attempts: List[dict] = []
+    token = _retries.set(attempts)
+    try:
+        yield attempts
+    finally:
+        _retries.reset(token)
+
+
+class Retrier:
+    """Calls fetch operations of a client with a retry policy and the circuit breaker of their host"""
+
+    def __init__(
+        self,
+        client: str,
+        policy: RetryPolicy = RetryPolicy(),
+        breakers: CircuitBreakers = CIRCUIT_BREAKERS,
+        failure_threshold: int = 5,
+        reset_seconds: float = 60.0,
+        sleep: Callable[[float], None] = time.sleep,
+        draw: Callable[[], float] = random.random,
+    ):
+        self.client = client
+        self.policy = policy
+        self.breakers = breakers
+        self.failure_threshold = failure_threshold
+        self.reset_seconds = reset_seconds
+        self._sleep = sleep
+        self._draw = draw
+
+    def call(self, host: str, operation: Callable[[], T], retryable: bool = True) -> T:
+        """
+        Run an operation fetching from a host, again while it fails transiently and attempts are left
+
+        Args:
+            host: Remote host of the operation, whose circuit breaker guards it
+            operation: Fetch to attempt, attempting it again must be harmless
+            retryable: False for operations that cannot be attempted again, still guarded by the circuit breaker
+
+        Raises:
+            CircuitOpenError: If the circuit of the host is open
+        """
+        breaker = self.breakers.get(host, self.failure_threshold, self.reset_seconds)
+        attempts = max(self.policy.max_attempts, 1) if retryable else 1
+        attempt = 0
+        while True:
+            attempt += 1
+            if not breaker.allow():
+                FETCH_SHORT_CIRCUITS.labels(self.client).inc()
+                raise CircuitOpenError(host, breaker.retry_after())
+            try:
+                result = operation()
+            except Exception as e:
+                if not is_transient(e):
+                    breaker.record_success()
+                    raise
+                if breaker.record_failure():
+                    logger.warning(f"Circuit of {host} opened after {breaker.failures} consecutive failures")
+                if attempt >= attempts:
+                    raise
+                delay = self.policy.delay(attempt, self._draw)
+                retried = RetriedAttempt(self.client, host, attempt, f"{type(e).__name__}: {str(e)}"[:500], delay)
+                logger.warning(f"{self.client} fetch from {host} failed, attempt {attempt}/{attempts}: {retried.error}")
+                FETCH_RETRIES.labels(self.client).inc()
+                recorded = _retries.get()
+                if recorded is not None:
+                    recorded.append(retried.to_dict())
+                self._sleep(delay)
+            else:
+                breaker.record_success()
+                return result
+
+
+def retrier(client: str, settings=None) -> Retrier:
+    """Retrier of a client with the configured policy and the breakers of the process"""
+    if settings is None:
+        from app.config.config import get_settings
+
+        settings = get_settings()
+
+    return Retrier(
+        client,
+        RetryPolicy(
+            settings.fetch_retry_max_attempts,
+            settings.fetch_retry_base_delay_seconds,
+            settings.fetch_retry_max_delay_seconds,
+            settings.fetch_retry_jitter,
+        ),
+        breakers=CIRCUIT_BREAKERS,
+        failure_threshold=settings.fetch_circuit_failure_threshold,
+        reset_seconds=settings.fetch_circuit_reset_seconds,
+    )
@@ -0,0 +1,92 @@
+"""
+Access control of the operator endpoints
+
+The admin scope is granted by presenting the configured ADMIN_API_TOKEN as a bearer token, the audit scope, which
+reads the audit log, by presenting AUDIT_API_TOKEN. Without a configured token the endpoints requiring its scope
+are closed.
+"""
+
+import hmac
+from typing import Optional
+
+from fastapi import Header, HTTPException, Request
+
+from app.config.config import get_settings
+
+ADMIN_SCOPE = "admin"
+AUDIT_SCOPE = "audit"
+# Granted to the S3 notification webhook only, never by granted_scope
+WEBHOOK_SCOPE = "webhook"
+
+
+def _bearer_credentials(authorization: Optional[str]) -> Optional[str]:
+    scheme, _, credentials = (authorization or "").partition(" ")
+    if scheme.lower() != "bearer" or not credentials.strip():
+        return None
+    return credentials.strip()
+
+
+# Setting holding the token of each scope
+SCOPE_TOKEN_SETTINGS = {ADMIN_SCOPE: "admin_api_token", AUDIT_SCOPE: "audit_api_token"}
+
+
+def _scope_token(scope: str):
+    return getattr(get_settings(), SCOPE_TOKEN_SETTINGS[scope])
+
+
+def require_bearer_token(token, scope: str, disabled_detail: str, authorization: Optional[str]) -> str:
+    """Reject requests that do not carry token as their bearer token, 403 with disabled_detail without a token"""
+    if token is None or not token.get_secret_value():
+        raise HTTPException(status_code=403, detail=disabled_detail)
+
+    credentials = _bearer_credentials(authorization)
+    if credentials is None:
+        raise HTTPException(
+            status_code=401,
+            detail=f"{scope.capitalize()} scope required",
+            headers={"WWW-Authenticate": f'Bearer scope="{scope}"'},
+        )
+    if not hmac.compare_digest(credentials.encode(), token.get_secret_value().encode()):
+        raise HTTPException(status_code=403, detail=f"{scope.capitalize()} scope required")
+    return scope
+
+
+def _require_scope(scope: str, setting: str, authorization: Optional[str]) -> str:
+    disabled_detail = f"{scope.capitalize()} endpoints are disabled, {setting} is not configured"
+    return require_bearer_token(_scope_token(scope), scope, disabled_detail, authorization)
+
+
+def require_admin_scope(authorization: Optional[str] = Header(None)) -> str:
+    """Dependency rejecting requests that do not carry the admin token, returns the granted scope"""
+    return _require_scope(ADMIN_SCOPE, "ADMIN_API_TOKEN", authorization)
+
+
+def require_audit_scope(authorization: Optional[str] = Header(None)) -> str:
+    """Dependency rejecting requests that do not carry the audit token, returns the granted scope"""
+    return _require_scope(AUDIT_SCOPE, "AUDIT_API_TOKEN", authorization)
+
+
+def granted_scope(authorization: Optional[str]) -> Optional[str]:
+    """Scope whose token a request carries, None without a valid token"""
+    credentials = _bearer_credentials(authorization)
+    if credentials is None:
+        return None
+    for scope in SCOPE_TOKEN_SETTINGS:
+        token = _scope_token(scope)
+        if token is not None and token.get_secret_value():
+            if hmac.compare_digest(credentials.encode(), token.get_secret_value().encode()):
+                return scope
+    return None
+
+
+def get_client_info(request: Request) -> tuple[Optional[str], Optional[str]]:
+    """Extract client IP and user agent from request"""
+    # Get real IP address considering reverse proxy headers
+    forwarded_for = request.headers.get("X-Forwarded-For")
+    if forwarded_for:
+        ip_address = forwarded_for.split(",")[0].strip()
+    else:
+        ip_address = request.client.host if request.client else None
+
+    user_agent = request.headers.get("User-Agent")
+    return ip_address, user_agent
@@ -8,7 +8,7 @@ once and reused across requests.
 
 import logging
 import threading
-from typing import Optional
+from typing import Dict, Hashable, List, Optional
 
 logger = logging.getLogger(__name__)
 
@@ -19,6 +19,11 @@ _services_lock = threading.Lock()
 _tokenization_service: Optional["TokenizationService"] = None
 _similarity_service: Optional["SimilarityDetectionService"] = None
 _submission_fetcher: Optional["SubmissionFetcher"] = None
+_submission_store: Optional["SubmissionStore"] = None
+_fingerprint_service: Optional["FingerprintService"] = None
+_detection_scheduler: Optional["JobScheduler"] = None
+_ingestion_scheduler: Optional["JobScheduler"] = None
+_report_scheduler: Optional["JobScheduler"] = None
 
 
 def get_tokenization_service() -> "TokenizationService":
@@ -33,9 +38,10 @@ def get_tokenization_service() -> "TokenizationService":
             # Double-check locking pattern
             if _tokenization_service is None:
                 logger.info("Initializing singleton TokenizationService...")
+                from app.config.config import get_settings
                 from app.domains.tokenization.tokenization_service import TokenizationService
 
-                _tokenization_service = TokenizationService()
+                _tokenization_service = TokenizationService(get_settings().fragment_long_line_threshold)
                 logger.info("TokenizationService singleton initialized successfully")
 
     return _tokenization_service
@@ -53,9 +59,10 @@ def get_similarity_service() -> "SimilarityDetectionService":
             # Double-check locking pattern
             if _similarity_service is None:
                 logger.info("Initializing singleton SimilarityDetectionService...")
+                from app.config.config import get_settings
                 from app.domains.detection.similarity_detection_service import SimilarityDetectionService
 
-                _similarity_service = SimilarityDetectionService()
+                _similarity_service = SimilarityDetectionService(get_settings().fragment_excerpt_max_bytes)
                 logger.info("SimilarityDetectionService singleton initialized successfully")
 
     return _similarity_service
@@ -81,6 +88,172 @@ def get_submission_fetcher() -> "SubmissionFetcher":
     return _submission_fetcher
 
 
+def get_submission_store() -> "SubmissionStore":
+    """
+    Get singleton instance of the configured SubmissionStore.
+    Thread-safe lazy initialization, the backend is selected by the storage_backend setting.
+    """
+    global _submission_store
+
+    if _submission_store is None:
+        with _services_lock:
+            # Double-check locking pattern
+            if _submission_store is None:
+                logger.info("Initializing singleton SubmissionStore...")
+                from app.config.config import get_settings
+                from app.domains.storage.storage_factory import create_submission_store
+
+                _submission_store = create_submission_store(get_settings())
+                logger.info(f"SubmissionStore singleton initialized successfully ({_submission_store.backend_name})")
+
+    return _submission_store
+
+
+def get_fingerprint_service() -> "FingerprintService":
+    """
+    Get singleton instance of FingerprintService.
+    Thread-safe lazy initialization, shares the singleton TokenizationService.
+    """
+    global _fingerprint_service
+
+    if _fingerprint_service is None:
+        tokenization_service = get_tokenization_service()
+        with _services_lock:
+            # Double-check locking pattern
+            if _fingerprint_service is None:
+                logger.info("Initializing singleton FingerprintService...")
+                from app.config.config import get_settings
+                from app.domains.fingerprints.fingerprint_factory import create_fingerprint_service
+
+                _fingerprint_service = create_fingerprint_service(get_settings(), tokenization_service)
+                logger.info("FingerprintService singleton initialized successfully")
+
+    return _fingerprint_service
+
+
+def get_detection_scheduler() -> "JobScheduler":
+    """
+    Get singleton JobScheduler of detection runs, capped at detection_max_concurrent_jobs and queued by priority,
+    the projects taking turns and capped at detection_max_concurrent_jobs_per_project. Thread-safe lazy
+    initialization.
+    """
+    global _detection_scheduler
+
+    if _detection_scheduler is None:
+        with _services_lock:
+            if _detection_scheduler is None:
+                from app.config.config import get_settings
+                from app.shared.concurrency import JobScheduler, resolve_workers
+
+                settings = get_settings()
+                max_jobs = resolve_workers(settings.detection_max_concurrent_jobs, lambda cpus: cpus // 4)
+                _detection_scheduler = JobScheduler(
+                    "detection",
+                    max_jobs,
+                    settings.detection_priority_aging_seconds,
+                    max_per_group=settings.detection_max_concurrent_jobs_per_project,
+                )
+                logger.info(f"Detection scheduler initialized, {max_jobs} concurrent runs")
+
+    return _detection_scheduler
+
+
+def get_ingestion_scheduler() -> "JobScheduler":
+    """
+    Get singleton JobScheduler of submission ingestions, capped at ingestion_max_concurrent_jobs.
+    Thread-safe lazy initialization.
+    """
+    global _ingestion_scheduler
+
+    if _ingestion_scheduler is None:
+        with _services_lock:
+            if _ingestion_scheduler is None:
+                from app.config.config import get_settings
+                from app.shared.concurrency import JobScheduler, resolve_workers
+
+                max_jobs = resolve_workers(get_settings().ingestion_max_concurrent_jobs, lambda cpus: cpus)
+                _ingestion_scheduler = JobScheduler("ingestion", max_jobs)
+                logger.info(f"Ingestion scheduler initialized, {max_jobs} concurrent ingestions")
+
+    return _ingestion_scheduler
+
+
+def get_report_scheduler() -> "JobScheduler":
+    """
+    Get singleton JobScheduler of the HTML reports rendered in the background, capped at report_max_concurrent_jobs.
+    Thread-safe lazy initialization.
+    """
+    global _report_scheduler
+
+    if _report_scheduler is None:
+        with _services_lock:
+            if _report_scheduler is None:
+                from app.config.config import get_settings
+                from app.shared.concurrency import JobScheduler, resolve_workers
+
+                max_jobs = resolve_workers(get_settings().report_max_concurrent_jobs, lambda cpus: 1)
+                _report_scheduler = JobScheduler("report", max_jobs)
+                logger.info(f"Report scheduler initialized, {max_jobs} concurrent reports")
+
+    return _report_scheduler
+
+
+def get_job_schedulers() -> Dict[str, "JobScheduler"]:
+    """Job schedulers initialized so far by name, without initializing the others"""
+    schedulers = (_detection_scheduler, _ingestion_scheduler, _report_scheduler)
+    return {scheduler.name: scheduler for scheduler in schedulers if scheduler is not None}
+
+
+def resize_job_schedulers(settings) -> None:
+    """Apply the concurrency caps of reloaded settings to the job schedulers initialized so far"""
+    from app.shared.concurrency import resolve_workers
+
+    if _detection_scheduler is not None:
+        _detection_scheduler.resize(
+            resolve_workers(settings.detection_max_concurrent_jobs, lambda cpus: cpus // 4),
+            settings.detection_priority_aging_seconds,
+            settings.detection_max_concurrent_jobs_per_project,
+        )
+    if _ingestion_scheduler is not None:
+        _ingestion_scheduler.resize(resolve_workers(settings.ingestion_max_concurrent_jobs, lambda cpus: cpus))
+    if _report_scheduler is not None:
+        _report_scheduler.resize(resolve_workers(settings.report_max_concurrent_jobs, lambda cpus: 1))
+
+
+def replace_detection_services(settings, tokenizer_config: "TokenizerConfig") -> None:
+    """
+    Replace the tokenization, similarity and fingerprint services with ones built from reloaded settings
+
+    Services handed out before, to the detection runs in flight, are left as they are. The fingerprint store is
+    shared by the new fingerprint service, its backend cannot change at runtime.
+    """
+    global _tokenization_service, _similarity_service, _fingerprint_service
+
+    from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+    from app.domains.fingerprints.fingerprint_factory import create_fingerprint_service
+    from app.domains.tokenization.tokenization_service import TokenizationService
+
+    tokenization_service = TokenizationService(settings.fragment_long_line_threshold, tokenizer_config)
+    similarity_service = SimilarityDetectionService(settings.fragment_excerpt_max_bytes)
+    with _services_lock:
+        store = _fingerprint_service.store if _fingerprint_service is not None else None
+        fingerprint_service = create_fingerprint_service(settings, tokenization_service, store=store)
+        _tokenization_service = tokenization_service
+        _similarity_service = similarity_service
+        _fingerprint_service = fingerprint_service
+    logger.info("Tokenization, similarity and fingerprint services replaced with the reloaded configuration")
+
+
+def drain_detection_scheduler(grace_seconds: float, interrupt_timeout_seconds: float) -> List[Hashable]:
+    """
+    Drain the detection scheduler if it was initialized: running runs get the grace period to finish, then are
+    interrupted. Returns the IDs of the queued runs that were dropped.
+    """
+    if _detection_scheduler is None:
+        return []
+    return _detection_scheduler.drain(grace_seconds, interrupt_timeout_seconds)
+
+
 def get_visualization_service(tokenization_service: Optional["TokenizationService"] = None) -> "VisualizationService":
     """
     Get instance of VisualizationService.
@@ -104,6 +277,10 @@ def init_services():
     get_tokenization_service()
     get_similarity_service()
     get_submission_fetcher()
+    get_submission_store()
+    get_fingerprint_service()
+    get_detection_scheduler()
+    get_ingestion_scheduler()
     logger.info("All singleton services warmed up successfully")
 
 
@@ -111,13 +288,26 @@ def cleanup_services():
     """
     Cleanup services during application shutdown.
     """
-    global _tokenization_service, _similarity_service, _submission_fetcher
+    global _tokenization_service, _similarity_service, _submission_fetcher, _submission_store, _fingerprint_service
+    global _detection_scheduler, _ingestion_scheduler, _report_scheduler
 
     logger.info("Cleaning up singleton services...")
 
+    for scheduler in (_detection_scheduler, _ingestion_scheduler, _report_scheduler):
+        if scheduler is not None:
+            scheduler.shutdown()
+
+    if _fingerprint_service is not None and _fingerprint_service.store is not None:
+        _fingerprint_service.store.close()
+
     # Reset singleton references
     _tokenization_service = None
     _similarity_service = None
     _submission_fetcher = None
+    _submission_store = None
+    _fingerprint_service = None
+    _detection_scheduler = None
+    _ingestion_scheduler = None
+    _report_scheduler = None
 
     logger.info("Singleton services cleaned up")
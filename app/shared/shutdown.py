@@ -0,0 +1,132 @@
+"""
+Coordinated shutdown of the instance
+
+On SIGTERM, as sent by Kubernetes before it kills a pod, the instance starts draining: readiness fails so it is
+taken out of its service, new requests are refused with 503 and the drain callbacks stop taking work from queues.
+The server then finishes the requests in flight and the lifespan drains the background jobs, see main.py.
+"""
+
+import logging
+import os
+import signal
+import socket
+import threading
+from typing import Callable, Dict, List, Optional, Tuple
+
+logger = logging.getLogger(__name__)
+
+# Requests still answered while draining: probes and metrics
+DRAIN_EXEMPT_PATHS = ("/health", "/metrics")
+
+
+def instance_id() -> str:
+    """Identity of this process in the runs it holds, INSTANCE_ID or its host name and PID"""
+    from app.config.config import get_settings
+
+    return get_settings().instance_id or f"{socket.gethostname()}:{os.getpid()}"
+
+
+class ShutdownCoordinator:
+    """Draining state of the instance and the callbacks run when it starts draining"""
+
+    SIGNALS = (signal.SIGTERM, signal.SIGINT)
+
+    def __init__(self):
+        self._draining = threading.Event()
+        self._drained = threading.Event()
+        self._lock = threading.Lock()
+        self._callbacks: List[Callable[[], None]] = []
+        self._previous_handlers: Dict[int, object] = {}
+
+    @property
+    def draining(self) -> bool:
+        return self._draining.is_set()
+
+    def on_drain(self, callback: Callable[[], None]) -> None:
+        """Run a callback once the instance starts draining, e.g. to stop consuming a queue"""
+        with self._lock:
+            self._callbacks.append(callback)
+
+    def begin(self, reason: str) -> None:
+        """Start draining and run the drain callbacks once, later calls wait for them to return"""
+        with self._lock:
+            first = not self._draining.is_set()
+            self._draining.set()
+            callbacks = list(self._callbacks)
+        if not first:
+            self._drained.wait()
+            return
+
+        logger.warning(f"Draining before shutdown: {reason}")
+        for callback in callbacks:
+            try:
+                callback()
+            except Exception as e:
+                logger.error(f"Drain callback failed: {str(e)}")
+        self._drained.set()
+
+    def handle_signal(self, signum: int, frame) -> None:
+        # Callbacks join threads, they run outside of the signal handler which blocks the event loop
+        reason = f"received {signal.Signals(signum).name}"
+        threading.Thread(target=self.begin, args=(reason,), name="shutdown-drain", daemon=True).start()
+
+        previous = self._previous_handlers.get(signum)
+        if callable(previous):
+            previous(signum, frame)
+        elif previous == signal.SIG_DFL:
+            signal.signal(signum, signal.SIG_DFL)
+            signal.raise_signal(signum)
+
+    def install_signal_handlers(self) -> None:
+        """Drain on SIGTERM and SIGINT, then hand the signal to the previous handler, the server's"""
+        try:
+            for signum in self.SIGNALS:
+                self._previous_handlers[signum] = signal.getsignal(signum)
+                signal.signal(signum, self.handle_signal)
+        except ValueError:
+            # Only the main thread handles signals, e.g. not under a test client
+            self._restore_signal_handlers()
+            logger.info("Not in the main thread, shutdown signals are left to the server")
+
+    def _restore_signal_handlers(self) -> None:
+        handlers, self._previous_handlers = self._previous_handlers, {}
+        for signum, handler in handlers.items():
+            if handler is not None:
+                try:
+                    signal.signal(signum, handler)
+                except ValueError:
+                    pass
+
+    def reset(self) -> None:
+        """Forget the draining state, the callbacks and the signal handlers, for a new lifespan"""
+        self._restore_signal_handlers()
+        with self._lock:
+            self._draining.clear()
+            self._drained.clear()
+            self._callbacks = []
+
+
+SHUTDOWN = ShutdownCoordinator()
+
+
+class DrainingMiddleware:
+    """ASGI middleware refusing new requests with 503 while the instance drains, except probes and metrics"""
+
+    BODY = b'{"detail":"Service is shutting down"}'
+    HEADERS: List[Tuple[bytes, bytes]] = [
+        (b"content-type", b"application/json"),
+        (b"content-length", str(len(BODY)).encode()),
+        (b"retry-after", b"1"),
+    ]
+
+    def __init__(self, app, coordinator: Optional[ShutdownCoordinator] = None):
+        self.app = app
+        self.coordinator = coordinator or SHUTDOWN
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http" or not self.coordinator.draining or scope["path"].startswith(DRAIN_EXEMPT_PATHS):
+            await self.app(scope, receive, send)
+            return
+
+        await send({"type": "http.response.start", "status": 503, "headers": self.HEADERS})
+        await send({"type": "http.response.body", "body": self.BODY})
@@ -0,0 +1,145 @@
+"""
+Tenancy context of requests and of the work they hand over
+
+With TENANCY_ENABLED every project belongs to one tenant, an institution, and a request only reaches the projects
+of the tenant the PAMP gateway authenticated it for, from the TENANT_HEADER header. Requests carrying an operator
+token are not restricted. The context is set by TenancyMiddleware and, being a context variable, follows work
+wrapped with in_current_context into job schedulers and executors.
+
+It is enforced below the handlers: the queries of the session are filtered to the projects of the tenant, see
+app.domains.tenants.tenant_filters, and the storage and cache keys of a project are only reached through it. A
+resource of another tenant is not found, exactly like one that does not exist.
+"""
+
+import contextvars
+import re
+from contextlib import contextmanager
+from dataclasses import dataclass, field
+from typing import Callable, Iterator, Mapping, Optional, Set, Union
+from uuid import UUID
+
+from app.config.config import get_settings
+from app.shared.exceptions import NotFoundException
+from app.shared.security import granted_scope
+
+# Kept in cache keys and in the tenant_id column, so without the separators of either
+TENANT_ID_PATTERN = re.compile(r"^[A-Za-z0-9._-]{1,64}$")
+
+
+@dataclass(frozen=True)
+class Tenancy:
+    """Tenant the current work is restricted to, a restricted tenancy without tenant reaches no project"""
+
+    tenant_id: Optional[str] = None
+    restricted: bool = True
+    # Projects already found to belong to the tenant, so that each is looked up once per request
+    projects: Set[UUID] = field(default_factory=set, compare=False, repr=False)
+
+
+UNRESTRICTED = Tenancy(restricted=False)
+
+_tenancy: contextvars.ContextVar[Tenancy] = contextvars.ContextVar("tenancy", default=UNRESTRICTED)
+
+
+def current_tenancy() -> Tenancy:
+    return _tenancy.get()
+
+
+@contextmanager
+def tenancy_scope(tenancy: Tenancy) -> Iterator[Tenancy]:
+    """Run the block restricted to a tenancy, restoring the previous one after it"""
+    token = _tenancy.set(tenancy)
+    try:
+        yield tenancy
+    finally:
+        _tenancy.reset(token)
+
+
+def in_tenancy(tenancy: Tenancy, function: Callable) -> Callable:
+    """Function running restricted to a tenancy, wherever it is called from"""
+
+    def restricted(*args, **kwargs):
+        with tenancy_scope(tenancy):
+            return function(*args, **kwargs)
+
+    return restricted
+
+
+def tenancy_of(headers: Mapping[str, str]) -> Tenancy:
+    """Tenancy of a request from its headers or of a call from its metadata, with lowercase names"""
+    settings = get_settings()
+    if not settings.tenancy_enabled or granted_scope(headers.get("authorization")) is not None:
+        return UNRESTRICTED
+    tenant_id = (headers.get(settings.tenant_header.lower()) or "").strip()
+    return Tenancy(tenant_id if TENANT_ID_PATTERN.match(tenant_id) else None)
+
+
+def tenant_namespace() -> Optional[str]:
+    """Namespace of the cache keys written and read by the current work, None when unrestricted"""
+    tenancy = current_tenancy()
+    return tenancy.tenant_id if tenancy.restricted else None
+
+
+def project_tenant(project_uuid: UUID) -> Optional[str]:
+    """Tenant a project is assigned to, None while it is assigned to none"""
+    from sqlmodel import Session
+
+    from app.domains.tenants.tenants_repository import TenantRepository
+    from app.shared.database import engine
+
+    with Session(engine) as session:
+        return TenantRepository(session).get_tenant(project_uuid)
+
+
+def project_accessible(project_uuid: Union[UUID, str]) -> bool:
+    """Whether the current work may reach a project"""
+    tenancy = current_tenancy()
+    if not tenancy.restricted:
+        return True
+    try:
+        project_uuid = UUID(str(project_uuid))
+    except ValueError:
+        return False
+    if project_uuid in tenancy.projects:
+        return True
+    if tenancy.tenant_id is None or project_tenant(project_uuid) != tenancy.tenant_id:
+        return False
+    tenancy.projects.add(project_uuid)
+    return True
+
+
+def require_project_access(project_uuid: Union[UUID, str]) -> None:
+    """
+    Refuse writes to a project of another tenant or of none, before anything is fetched or stored for them
+
+    Raises:
+        NotFoundException: If the current work may not reach the project
+    """
+    if not project_accessible(project_uuid):
+        raise NotFoundException("Project", str(project_uuid))
+
+
+def tenancy_of_project(project_uuid: UUID) -> Tenancy:
+    """Tenancy of work done for a project outside of a request, unrestricted while the project has no tenant"""
+    if not get_settings().tenancy_enabled:
+        return UNRESTRICTED
+    tenant_id = project_tenant(project_uuid)
+    return Tenancy(tenant_id, projects={project_uuid}) if tenant_id is not None else UNRESTRICTED
+
+
+class TenancyMiddleware:
+    """ASGI middleware running each HTTP request in the tenancy of its headers"""
+
+    def __init__(self, app):
+        self.app = app
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http":
+            await self.app(scope, receive, send)
+            return
+
+        headers = {
+            name.decode("latin-1").lower(): value.decode("latin-1") for name, value in scope.get("headers") or []
+        }
+        with tenancy_scope(tenancy_of(headers)):
+            await self.app(scope, receive, send)
@@ -0,0 +1,61 @@
+"""
+Cooperative timeouts of detection runs
+
+A Timeout is entered for the time one file is tokenized, one pair compared or one run processed. The algorithms call
+checkpoint() in their loops, which raises TimedOut once a timeout entered in the current context has expired:
+nothing is killed, the work stops at its next checkpoint and the code that entered the timeout records the item as
+timed out. Threads started through in_current_context inherit the timeouts of their submitter.
+
+TimedOut is not an Exception, so the handlers of failing files and pairs never swallow it on its way up, like
+asyncio.CancelledError.
+"""
+
+import contextvars
+import time
+from typing import Callable, Optional, Tuple
+
+FILE = "file"
+PAIR = "pair"
+RUN = "run"
+
+_timeouts: contextvars.ContextVar[Tuple["Timeout", ...]] = contextvars.ContextVar("timeouts", default=())
+
+
+class TimedOut(BaseException):
+    """Raised at a checkpoint once a timeout expired, by the outermost expired one"""
+
+    def __init__(self, timeout: "Timeout"):
+        super().__init__(f"{timeout.scope.capitalize()} timed out after {timeout.seconds:g}s")
+        self.timeout = timeout
+
+
+class Timeout:
+    """Timeout of a scope, seconds from its creation, 0 for none; checkpoints enforce it while entered"""
+
+    def __init__(self, scope: str, seconds: float, clock: Callable[[], float] = time.monotonic):
+        self.scope = scope
+        self.seconds = seconds
+        self._clock = clock
+        self._expires_at = clock() + seconds if seconds > 0 else None
+        self._token: Optional[contextvars.Token] = None
+
+    @property
+    def expired(self) -> bool:
+        return self._expires_at is not None and self._clock() >= self._expires_at
+
+    def __enter__(self) -> "Timeout":
+        if self._expires_at is not None:
+            self._token = _timeouts.set(_timeouts.get() + (self,))
+        return self
+
+    def __exit__(self, *exc_info) -> None:
+        if self._token is not None:
+            _timeouts.reset(self._token)
+            self._token = None
+
+
+def checkpoint() -> None:
+    """Raise TimedOut if a timeout entered in the current context expired, to call regularly in long loops"""
+    for timeout in _timeouts.get():
+        if timeout.expired:
+            raise TimedOut(timeout)
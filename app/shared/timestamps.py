@@ -0,0 +1,109 @@
+"""
+Timezone-aware timestamps
+
+Timestamps are stored and computed in UTC and serialized in RFC 3339 with their offset. Timestamps supplied by
+clients, like submission dates taken from git commits, must carry an explicit offset: a naive time would be read in
+whatever timezone the server runs in, and deadline comparisons across a DST change would be off by an hour.
+"""
+
+import re
+from datetime import datetime, timezone
+from email.utils import parsedate_to_datetime
+from typing import Annotated, Any, Optional
+
+from pydantic import AfterValidator, BeforeValidator
+from sqlalchemy import DateTime, TypeDecorator
+
+UTC = timezone.utc
+
+# Formats of git dates besides ISO 8601 and RFC 2822: the default one and the raw one
+GIT_DEFAULT_FORMAT = "%a %b %d %H:%M:%S %Y %z"
+_GIT_RAW_PATTERN = re.compile(r"^@?(\d+) ([+-]\d{4})$")
+
+
+def utc_now() -> datetime:
+    """Current time in UTC"""
+    return datetime.now(UTC)
+
+
+def as_utc(value: datetime) -> datetime:
+    """The same instant in UTC, naive values being UTC already as read back from databases without timezones"""
+    if value.tzinfo is None or value.utcoffset() is None:
+        return value.replace(tzinfo=UTC)
+    return value.astimezone(UTC)
+
+
+def to_rfc3339(value: Optional[datetime]) -> Optional[str]:
+    """RFC 3339 representation of an instant in UTC, None for None"""
+    if value is None:
+        return None
+    return as_utc(value).isoformat().replace("+00:00", "Z")
+
+
+def parse_timestamp(value: str) -> datetime:
+    """
+    Instant of a timestamp carrying an explicit offset, in UTC
+
+    Accepts ISO 8601 and RFC 3339 (`2024-03-31T03:15:00+02:00`, `Z` for UTC), RFC 2822
+    (`Sun, 31 Mar 2024 03:15:00 +0200`) and the default and raw git date formats (`Sun Mar 31 03:15:00 2024 +0200`,
+    `1711847700 +0200`). Raises ValueError for timestamps without offset or in another format.
+    """
+    text = value.strip()
+    raw = _GIT_RAW_PATTERN.match(text)
+    if raw:
+        return datetime.fromtimestamp(int(raw.group(1)), UTC)
+
+    parsed = None
+    try:
+        parsed = datetime.fromisoformat(text)
+    except ValueError:
+        for parse in (lambda: datetime.strptime(text, GIT_DEFAULT_FORMAT), lambda: parsedate_to_datetime(text)):
+            try:
+                parsed = parse()
+                break
+            except (TypeError, ValueError):
+                continue
+    if parsed is None:
+        raise ValueError(f"unrecognized timestamp {value!r}")
+    # RFC 2822 dates in "-0000" are parsed as naive: their offset is explicitly unknown
+    if parsed.tzinfo is None or parsed.utcoffset() is None:
+        raise ValueError(f"timestamp {value!r} has no offset, add one such as Z or +02:00")
+    return parsed.astimezone(UTC)
+
+
+def _client_timestamp(value: Any) -> Any:
+    if isinstance(value, str):
+        return parse_timestamp(value)
+    if isinstance(value, datetime) and (value.tzinfo is None or value.utcoffset() is None):
+        raise ValueError("timestamp has no offset, add one such as Z or +02:00")
+    # Numbers are Unix timestamps, unambiguous and validated as UTC
+    return value
+
+
+# Timestamps of responses: converted to UTC, serialized in RFC 3339 with the Z offset
+UtcTimestamp = Annotated[datetime, AfterValidator(as_utc)]
+
+# Timestamps supplied by clients: an explicit offset is required, converted to UTC
+OffsetTimestamp = Annotated[datetime, BeforeValidator(_client_timestamp), AfterValidator(as_utc)]
+
+
+class UtcDateTime(TypeDecorator):
+    """
+    Timestamp column stored in UTC
+
+    Values are converted to UTC before being written and read back as aware UTC datetimes, including from databases
+    storing timestamps without timezone (SQLite).
+    """
+
+    impl = DateTime(timezone=True)
+    cache_ok = True
+
+    def process_bind_param(self, value: Optional[datetime], dialect) -> Optional[datetime]:
+        if value is None:
+            return None
+        value = as_utc(value)
+        return value if dialect.name == "postgresql" else value.replace(tzinfo=None)
+
+    def process_result_value(self, value: Optional[datetime], dialect) -> Optional[datetime]:
+        return None if value is None else as_utc(value)
+
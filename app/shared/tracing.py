@@ -0,0 +1,265 @@
+"""
+Structured logs correlated by request and detection run
+
+Spans hold correlation fields, request_id for HTTP requests and run_id for detection runs, in a context variable.
+Every log record is stamped with the fields of the span it was emitted in and written as one JSON object per line,
+so the whole lifecycle of a request or a run can be filtered by one ID. Threads do not inherit context variables:
+work handed to job schedulers, executors or threads is wrapped with in_current_context to carry the span along.
+
+Log levels come from a filter like "info,app.domains.detection=debug,uvicorn.access=warning": a default level
+followed by per logger levels, which may be changed at runtime.
+"""
+
+import contextvars
+import json
+import logging
+import re
+import threading
+import uuid
+from contextlib import contextmanager
+from datetime import datetime, timezone
+from typing import Any, Callable, Dict, Iterator, Mapping, Optional, Tuple
+
+from fastapi.encoders import jsonable_encoder
+from fastapi.exceptions import RequestValidationError
+from fastapi.responses import JSONResponse
+from starlette.exceptions import HTTPException as StarletteHTTPException
+
+from app.shared.i18n import localize_detail, request_locale, translate
+from app.shared.timestamps import to_rfc3339
+
+logger = logging.getLogger(__name__)
+
+REQUEST_ID_HEADER = "X-Request-Id"
+# Incoming request IDs are kept when they are reasonably short and printable, replaced otherwise
+REQUEST_ID_PATTERN = re.compile(r"^[A-Za-z0-9._:-]{1,128}$")
+
+_span_fields: contextvars.ContextVar[Mapping[str, str]] = contextvars.ContextVar("span_fields", default={})
+
+
+def current_fields() -> Mapping[str, str]:
+    """Correlation fields of the current span"""
+    return _span_fields.get()
+
+
+def current_request_id() -> Optional[str]:
+    return _span_fields.get().get("request_id")
+
+
+@contextmanager
+def span(**fields: Any) -> Iterator[Mapping[str, str]]:
+    """Add correlation fields to the logs of the enclosed block, nested spans keep the fields of outer ones"""
+    token = _span_fields.set({**_span_fields.get(), **{name: str(value) for name, value in fields.items()}})
+    try:
+        yield _span_fields.get()
+    finally:
+        _span_fields.reset(token)
+
+
+def in_span(function: Callable, **fields: Any) -> Callable:
+    """Function running in a span with the given fields, for jobs started later or elsewhere"""
+
+    def run(*args, **kwargs):
+        with span(**fields):
+            return function(*args, **kwargs)
+
+    return run
+
+
+def in_current_context(function: Callable) -> Callable:
+    """Function running in a copy of the current context, so a worker thread logs in the span of the caller"""
+    context = contextvars.copy_context()
+    return lambda *args, **kwargs: context.run(function, *args, **kwargs)
+
+
+def new_request_id(incoming: Optional[str] = None) -> str:
+    """The incoming request ID when it is acceptable, a new random one otherwise"""
+    if incoming and REQUEST_ID_PATTERN.match(incoming):
+        return incoming
+    return uuid.uuid4().hex
+
+
+class CorrelationFilter(logging.Filter):
+    """Handler filter stamping records with the fields of the span they were emitted in"""
+
+    def filter(self, record: logging.LogRecord) -> bool:
+        if not hasattr(record, "span"):
+            record.span = current_fields()
+        return True
+
+
+class JsonFormatter(logging.Formatter):
+    """One JSON object per record with its level, logger, thread, message and correlation fields"""
+
+    def format(self, record: logging.LogRecord) -> str:
+        entry = {
+            "timestamp": to_rfc3339(datetime.fromtimestamp(record.created, timezone.utc)),
+            "level": record.levelname,
+            "logger": record.name,
+            "thread": record.threadName,
+            "message": record.getMessage(),
+            **getattr(record, "span", {}),
+        }
+        if record.exc_info:
+            entry["exception"] = self.formatException(record.exc_info)
+        return json.dumps(entry, default=str)
+
+
+class TextFormatter(logging.Formatter):
+    """Plain text records followed by their correlation fields, for reading logs in a terminal"""
+
+    def __init__(self):
+        super().__init__("%(asctime)s - %(name)s - %(levelname)s - %(message)s")
+
+    def format(self, record: logging.LogRecord) -> str:
+        text = super().format(record)
+        fields = getattr(record, "span", {})
+        if fields:
+            text += " [" + " ".join(f"{name}={value}" for name, value in fields.items()) + "]"
+        return text
+
+
+def parse_log_filter(spec: str) -> Tuple[Optional[int], Dict[str, int]]:
+    """
+    Default level and per logger levels of a log filter
+
+    Raises:
+        ValueError: If a level is unknown or a logger has no level
+    """
+    default, levels = None, {}
+    for directive in (part.strip() for part in spec.split(",")):
+        if not directive:
+            continue
+        name, _, level_name = directive.rpartition("=")
+        level = logging.getLevelName(level_name.strip().upper())
+        if not isinstance(level, int):
+            raise ValueError(f"Unknown log level '{level_name.strip()}' in '{directive}'")
+        if name:
+            levels[name.strip()] = level
+        elif "=" in directive:
+            raise ValueError(f"Missing logger name in '{directive}'")
+        else:
+            default = level
+    return default, levels
+
+
+class LogFilter:
+    """Log levels applied to the loggers, replaced as a whole when the filter changes"""
+
+    def __init__(self):
+        self.spec = ""
+        self._configured: Dict[str, int] = {}
+        self._lock = threading.Lock()
+
+    def apply(self, spec: str) -> str:
+        """Apply a filter, loggers it no longer names go back to the default level; returns the applied filter"""
+        default, levels = parse_log_filter(spec)
+        with self._lock:
+            for name in set(self._configured) - set(levels):
+                logging.getLogger(name).setLevel(logging.NOTSET)
+            logging.getLogger().setLevel(default if default is not None else logging.INFO)
+            for name, level in levels.items():
+                logging.getLogger(name).setLevel(level)
+            self._configured = levels
+            self.spec = spec
+        return spec
+
+    def levels(self) -> Tuple[int, Dict[str, int]]:
+        """Level of the root logger and of each logger named by the filter"""
+        with self._lock:
+            return logging.getLogger().level, dict(self._configured)
+
+
+LOG_FILTER = LogFilter()
+
+
+class RequestIdMiddleware:
+    """
+    ASGI middleware running each HTTP request in a span with its request ID
+
+    The ID comes from the X-Request-Id header or is generated, is returned in the same response header and is kept
+    in the scope for error handlers running outside the span.
+    """
+
+    def __init__(self, app):
+        self.app = app
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http":
+            await self.app(scope, receive, send)
+            return
+
+        incoming = None
+        for name, value in scope.get("headers") or []:
+            if name.lower() == b"x-request-id":
+                incoming = value.decode("latin-1")
+                break
+        request_id = new_request_id(incoming)
+        scope["request_id"] = request_id
+        header = (REQUEST_ID_HEADER.lower().encode(), request_id.encode())
+
+        async def send_with_request_id(message):
+            if message["type"] == "http.response.start":
+                headers = [item for item in message.get("headers") or [] if item[0].lower() != header[0]]
+                message = {**message, "headers": headers + [header]}
+            await send(message)
+
+        with span(request_id=request_id):
+            await self.app(scope, receive, send_with_request_id)
+
+
+def request_id_of(request) -> Optional[str]:
+    """Request ID of a request, from its scope when the span already ended"""
+    return request.scope.get("request_id") or current_request_id()
+
+
+def error_response(request, status_code: int, detail: Any, headers: Optional[Mapping[str, str]] = None):
+    """JSON error response carrying the request ID in its body and headers, for users to quote in bug reports"""
+    request_id = request_id_of(request)
+    headers = dict(headers or {})
+    if request_id:
+        headers[REQUEST_ID_HEADER] = request_id
+    return JSONResponse({"detail": detail, "request_id": request_id}, status_code=status_code, headers=headers)
+
+
+def install_error_handlers(app) -> None:
+    """
+    Error responses of the application with the request ID, unexpected errors logged with their traceback
+
+    Messages of error details are translated to the locale of the request, their codes and types stay as they are.
+    """
+
+    async def http_exception_handler(request, exc: StarletteHTTPException):
+        detail = localize_detail(exc.detail, request_locale(request))
+        return error_response(request, exc.status_code, detail, getattr(exc, "headers", None))
+
+    async def validation_exception_handler(request, exc: RequestValidationError):
+        return error_response(request, 422, localize_detail(jsonable_encoder(exc.errors()), request_locale(request)))
+
+    async def unexpected_exception_handler(request, exc: Exception):
+        with span(request_id=request_id_of(request)):
+            logger.error(f"Unhandled error on {request.method} {request.url.path}", exc_info=exc)
+        return error_response(request, 500, translate("error.internal", request_locale(request)))
+
+    app.add_exception_handler(StarletteHTTPException, http_exception_handler)
+    app.add_exception_handler(RequestValidationError, validation_exception_handler)
+    app.add_exception_handler(Exception, unexpected_exception_handler)
+
+
+def configured_log_filter(settings) -> str:
+    """Log filter of the settings, by default the debug or info level with the server loggers at info"""
+    if settings.log_filter:
+        return settings.log_filter
+    return f"{'debug' if settings.debug else 'info'},uvicorn=info,uvicorn.access=info,fastapi=info"
+
+
+def configure_logging(settings, handlers) -> None:
+    """Install the formatter and correlation filter on the handlers of the root logger and apply the levels"""
+    formatter = JsonFormatter() if settings.log_format == "json" else TextFormatter()
+    correlation = CorrelationFilter()
+    root = logging.getLogger()
+    for handler in handlers:
+        handler.setFormatter(formatter)
+        handler.addFilter(correlation)
+        root.addHandler(handler)
+    LOG_FILTER.apply(configured_log_filter(settings))
@@ -0,0 +1,353 @@
+#!/usr/bin/env python3
+"""
+pamp-detect, plagiarism detection of a directory of submissions without the service
+With run, each subdirectory of --dir is a submission and every pair is compared by the code of detection runs,
+offline and without database, then written as a JSON or HTML report. Exits with 1 when a pair reaches the
+threshold so it can gate CI, 2 when the arguments are invalid or a comparison failed.
+With tokenize, prints the tokens of a file instead, one JSON object per line.
+"""
+
+import argparse
+import json
+import logging
+import sys
+from dataclasses import dataclass, field
+from pathlib import Path
+from types import SimpleNamespace
+from typing import Dict, List, Optional
+from uuid import NAMESPACE_URL, UUID, uuid5
+
+from app.config.config import get_settings
+from app.domains.detection.directory_comparison import DirectoryComparator, read_source
+from app.domains.detection.pairwise_comparison import ParallelPairwiseComparator
+from app.domains.reports.html_report import render_run_report
+from app.domains.runs.run_recorder import PAIR_METRICS, DetectionRunRecorder
+from app.domains.runs.runs_models import (
+    DetectionFragment,
+    DetectionPair,
+    DetectionRun,
+    DetectionRunParticipant,
+    DetectionRunStatus,
+    DetectionRunTrigger,
+)
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.domains.tokenization.languages import get_language_registry
+from app.shared.concurrency import resolve_workers
+from app.shared.timestamps import utc_now
+
+EXIT_CLEAN = 0
+EXIT_FLAGGED = 1
+EXIT_ERROR = 2
+
+
+def submission_id(name: str) -> UUID:
+    """Stable ID of the submission of a directory, so reports of the same directory name the same submissions"""
+    return uuid5(NAMESPACE_URL, f"pamp-detect:{name}")
+
+
+class LocalRunRepository:
+    """Detection run repository keeping the pairs and fragments of a run in memory, for the recorder"""
+
+    def __init__(self, run: DetectionRun):
+        self.run = run
+        self.pairs: List[DetectionPair] = []
+        self.fragments: List[DetectionFragment] = []
+        self.not_comparable: Dict[UUID, str] = {}
+
+    def insert_batch(self, run_id: UUID, pairs: List[DetectionPair], fragments: List[DetectionFragment]) -> int:
+        self.pairs.extend(pairs)
+        self.fragments.extend(fragments)
+        self.run.completed_pairs += len([p for p in pairs if p.status == SimilarityStatus.COMPLETED])
+        self.run.failed_pairs += len([p for p in pairs if p.status == SimilarityStatus.FAILED])
+        return len(pairs)
+
+    def mark_not_comparable(self, run_id: UUID, reasons: Dict[UUID, str]) -> int:
+        self.not_comparable.update(reasons)
+        return len(reasons)
+
+    def finish_run(self, run_id, status, error_message=None, cache_stats=None, profile=None, file_errors=None):
+        self.run.status = status
+        self.run.finished_at = utc_now()
+        self.run.error_message = error_message
+        self.run.cache_stats = cache_stats
+        self.run.file_errors = file_errors or None
+        return self.run
+
+
+@dataclass
+class LocalRun:
+    """Pairs and fragments of the comparison of the submissions of a directory, most similar pairs first"""
+
+    run: DetectionRun
+    submissions: Dict[str, Path]
+    pairs: List[DetectionPair]
+    fragments: Dict[str, List[DetectionFragment]]
+    threshold: float
+    not_comparable: Dict[UUID, str] = field(default_factory=dict)
+
+    @property
+    def names(self) -> Dict[str, str]:
+        return {str(submission_id(name)): name for name in self.submissions}
+
+    @property
+    def flagged(self) -> List[DetectionPair]:
+        return [
+            p for p in self.pairs if p.status == SimilarityStatus.COMPLETED and p.overall_similarity >= self.threshold
+        ]
+
+    @property
+    def failed(self) -> List[DetectionPair]:
+        return [p for p in self.pairs if p.status == SimilarityStatus.FAILED]
+
+    @property
+    def exit_code(self) -> int:
+        if self.flagged:
+            return EXIT_FLAGGED
+        return EXIT_ERROR if self.failed else EXIT_CLEAN
+
+
+def collect_submissions(directory: Path) -> Dict[str, Path]:
+    """Submissions of a directory by name, each of its subdirectories but the hidden ones"""
+    return {
+        path.name: path for path in sorted(directory.iterdir()) if path.is_dir() and not path.name.startswith(".")
+    }
+
+
+def create_comparator(language: Optional[str] = None, cache: bool = False) -> DirectoryComparator:
+    """Comparator built from the settings like the services of the server, the fingerprint cache only on demand"""
+    from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+    from app.domains.detection.visualization import VisualizationService
+    from app.domains.fingerprints.fingerprint_factory import create_fingerprint_service
+    from app.domains.tokenization.tokenization_service import TokenizationService
+
+    settings = get_settings()
+    if not cache:
+        settings = settings.model_copy(update={"fingerprint_cache_enabled": False})
+    tokenization_service = TokenizationService(settings.fragment_long_line_threshold)
+    return DirectoryComparator(
+        tokenization_service,
+        create_fingerprint_service(settings, tokenization_service),
+        SimilarityDetectionService(settings.fragment_excerpt_max_bytes),
+        VisualizationService(tokenization_service),
+        language,
+    )
+
+
+def run_detection(comparator: DirectoryComparator, submissions: Dict[str, Path], threshold: float) -> LocalRun:
+    """Compare every pair of submissions and record them like a detection run"""
+    settings = get_settings()
+    project_uuid = uuid5(NAMESPACE_URL, "pamp-detect")
+    run = DetectionRun(
+        project_uuid=project_uuid,
+        project_step_uuid=project_uuid,
+        trigger=DetectionRunTrigger.MANUAL,
+        parameters={**comparator.fingerprint_service.parameters, "language": comparator.language or "auto"},
+    )
+    repository = LocalRunRepository(run)
+    recorder = DetectionRunRecorder(repository, run.id)
+    cache_stats = comparator.fingerprint_service.new_stats()
+
+    def submission(name: str) -> SimpleNamespace:
+        return SimpleNamespace(
+            id=submission_id(name), project_uuid=project_uuid, project_step_uuid=project_uuid, submitted_by_uuid=None
+        )
+
+    def compare(pair) -> tuple:
+        results = comparator.compare(submissions[pair[0]], submissions[pair[1]], cache_stats)
+        similarity = SimpleNamespace(
+            id=None,
+            status=results.get("status", SimilarityStatus.COMPLETED),
+            error_message=results.get("error_message"),
+            processing_time_seconds=results["processing_time_seconds"],
+            **{metric: results[metric] for metric in PAIR_METRICS},
+        )
+        return similarity, submission(pair[0]), submission(pair[1]), results
+
+    pairs = [(first, second) for first in submissions for second in submissions if first < second]
+    run.total_pairs = len(pairs)
+    pairwise = ParallelPairwiseComparator(
+        resolve_workers(settings.detection_comparison_workers, lambda cpus: cpus),
+        settings.detection_comparison_chunk_size,
+    )
+    for pair, comparison in pairwise.compare(pairs, compare):
+        if comparison:
+            recorder.record_comparison(*comparison)
+        else:
+            # Failed comparisons are recorded without result, like the pairs of a run
+            recorder.record_comparison(None, submission(pair[0]), submission(pair[1]))
+    recorder.finish(DetectionRunStatus.COMPLETED, cache_stats=cache_stats.to_dict())
+
+    fragments: Dict[str, List[DetectionFragment]] = {}
+    for fragment in repository.fragments:
+        fragments.setdefault(str(fragment.pair_id), []).append(fragment)
+    names = {submission_id(name): name for name in submissions}
+    ordered = sorted(
+        repository.pairs,
+        key=lambda p: (-p.overall_similarity, names[p.submission_id], names[p.compared_submission_id]),
+    )
+    return LocalRun(run, submissions, ordered, fragments, threshold, repository.not_comparable)
+
+
+def json_report(local_run: LocalRun) -> str:
+    """Report of every pair of a local run, the flagged ones marked, submissions named by their directory"""
+    names = local_run.names
+    flagged = {p.id for p in local_run.flagged}
+    pairs = []
+    for pair in local_run.pairs:
+        pairs.append(
+            {
+                "submission": names[str(pair.submission_id)],
+                "compared_submission": names[str(pair.compared_submission_id)],
+                "status": getattr(pair.status, "value", pair.status),
+                "flagged": pair.id in flagged,
+                **{metric: getattr(pair, metric) for metric in PAIR_METRICS},
+                "low_confidence": pair.low_confidence,
+                "partial": pair.partial,
+                "error_message": pair.error_message,
+                "fragments": [
+                    {
+                        "type": getattr(f.fragment_type, "value", f.fragment_type),
+                        "file1_path": f.file1_path,
+                        "file2_path": f.file2_path,
+                        "file1_lines": [f.file1_start_line, f.file1_end_line],
+                        "file2_lines": [f.file2_start_line, f.file2_end_line],
+                        "similarity": f.similarity,
+                    }
+                    for f in local_run.fragments.get(str(pair.id), [])
+                ],
+            }
+        )
+    return json.dumps(
+        {
+            "threshold": local_run.threshold,
+            "parameters": local_run.run.parameters,
+            "submissions": [
+                {"name": name, "not_comparable_reason": local_run.not_comparable.get(submission_id(name))}
+                for name in local_run.submissions
+            ],
+            "flagged_pairs": len(flagged),
+            "failed_pairs": len(local_run.failed),
+            "pairs": pairs,
+            "file_errors": [
+                {**error, "submission": names.get(error["submission_id"])} for error in local_run.run.file_errors or []
+            ],
+        },
+        indent=2,
+    )
+
+
+def html_report(local_run: LocalRun) -> str:
+    """HTML report of the flagged pairs of a local run, rendered like the report of a detection run"""
+    names = local_run.names
+
+    def read(submission_id, path: str) -> Optional[str]:
+        try:
+            return read_source(local_run.submissions[names[str(submission_id)]] / path)
+        except (KeyError, OSError):
+            return None
+
+    participants = [
+        DetectionRunParticipant(
+            run_id=local_run.run.id,
+            submission_id=submission_id(name),
+            group_uuid=submission_id(name),
+            not_comparable_reason=local_run.not_comparable.get(submission_id(name)),
+        )
+        for name in local_run.submissions
+    ]
+    return render_run_report(
+        local_run.run, participants, local_run.flagged, local_run.fragments, read, local_run.threshold, labels=names
+    )
+
+
+def run_command(args: argparse.Namespace) -> int:
+    if not args.dir.is_dir():
+        print(f"pamp-detect: {args.dir} is not a directory", file=sys.stderr)
+        return EXIT_ERROR
+    if args.out is not None and args.out.suffix not in (".json", ".html"):
+        print(f"pamp-detect: {args.out} must end with .json or .html", file=sys.stderr)
+        return EXIT_ERROR
+    submissions = collect_submissions(args.dir)
+    if len(submissions) < 2:
+        print(f"pamp-detect: {args.dir} needs at least two submission directories", file=sys.stderr)
+        return EXIT_ERROR
+
+    language = None if args.language == "auto" else args.language
+    local_run = run_detection(create_comparator(language, args.cache), submissions, args.threshold)
+
+    if args.out is not None:
+        report = json_report(local_run) if args.out.suffix == ".json" else html_report(local_run)
+        args.out.write_text(report, encoding="utf-8")
+    else:
+        names = local_run.names
+        for pair in local_run.flagged:
+            print(
+                f"{pair.overall_similarity:.3f}  {names[str(pair.submission_id)]}  "
+                f"{names[str(pair.compared_submission_id)]}"
+            )
+    print(
+        f"{len(submissions)} submissions, {len(local_run.pairs)} pairs compared, {len(local_run.failed)} failed, "
+        f"{len(local_run.flagged)} at {args.threshold:.2f} or more",
+        file=sys.stderr,
+    )
+    return local_run.exit_code
+
+
+def tokenize_command(args: argparse.Namespace) -> int:
+    from app.domains.tokenization.tokenization_service import TokenizationService
+
+    try:
+        content = read_source(args.file)
+    except OSError as e:
+        print(f"pamp-detect: {e}", file=sys.stderr)
+        return EXIT_ERROR
+    tokenization_service = TokenizationService(get_settings().fragment_long_line_threshold)
+    for token in tokenization_service.tokenize(content, args.file):
+        print(json.dumps(token))
+    return EXIT_CLEAN
+
+
+def threshold(value: str) -> float:
+    similarity = float(value)
+    if not 0 <= similarity <= 1:
+        raise argparse.ArgumentTypeError(f"{value} is not between 0 and 1")
+    return similarity
+
+
+def parser() -> argparse.ArgumentParser:
+    parser = argparse.ArgumentParser(prog="pamp-detect", description=__doc__.strip().splitlines()[0])
+    commands = parser.add_subparsers(dest="command", required=True)
+
+    run = commands.add_parser("run", help="Compare every pair of submissions of a directory")
+    run.add_argument("--dir", type=Path, required=True, help="Directory with one subdirectory per submission")
+    run.add_argument(
+        "--language",
+        default="auto",
+        choices=("auto", *get_language_registry().names()),
+        help="Only compare files of a language",
+    )
+    run.add_argument(
+        "--threshold",
+        type=threshold,
+        default=get_settings().report_min_similarity,
+        help="Overall similarity at or above which pairs are flagged",
+    )
+    run.add_argument("--out", type=Path, help="Report written as JSON or HTML by its extension, flagged pairs printed")
+    run.add_argument("--cache", action="store_true", help="Reuse fingerprints through the configured cache")
+    run.set_defaults(handler=run_command)
+
+    tokenize = commands.add_parser("tokenize", help="Print the tokens of a file, one JSON object per line")
+    tokenize.add_argument("file", type=Path, help="Source file")
+    tokenize.set_defaults(handler=tokenize_command)
+    return parser
+
+
+def main(argv: Optional[List[str]] = None) -> int:
+    """Run a command, returns its exit code"""
+    logging.basicConfig(level=logging.WARNING, stream=sys.stderr, format="%(levelname)s %(name)s: %(message)s")
+    args = parser().parse_args(argv)
+    return args.handler(args)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
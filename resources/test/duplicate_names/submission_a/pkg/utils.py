@@ -0,0 +1,11 @@
+def moving_average(values, window):
+    averages = []
+    for index in range(len(values) - window + 1):
+        chunk = values[index : index + window]
+        averages.append(sum(chunk) / window)
+    return averages
+
+
+def normalize(values):
+    low, high = min(values), max(values)
+    return [(value - low) / (high - low) for value in values]
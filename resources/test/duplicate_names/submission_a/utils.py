@@ -0,0 +1,6 @@
+def greet(name):
+    return f"Hello, {name}!"
+
+
+def shout(text):
+    return text.upper() + "!"
@@ -0,0 +1,3 @@
+from utils import moving_average
+
+print(moving_average([1, 2, 3, 4, 5], 2))
@@ -0,0 +1,317 @@
+#!/usr/bin/env python3
+"""
+Benchmark of the detection stages over the language samples
+Fingerprints every sample from scratch and compares it with itself, then prints the per-stage timings.
+With --allocations, counts the heap blocks held by the tokens of the samples with and without interning instead.
+With --candidates, times the incremental comparison of one new submission against a synthetic step instead.
+With --mapped, compares reading a corpus of large stored files through buffered reads and memory maps.
+With --hashing, times the k-gram hashing and fingerprinting of the tokens of the samples with every hash scheme.
+With --scaling, times the parallel pairwise comparison of a synthetic corpus with 1, 2 and 4 workers.
+"""
+
+import argparse
+import json
+import random
+import shutil
+import sys
+import tempfile
+import time
+import tracemalloc
+from pathlib import Path
+
+from app.domains.detection.pairwise_comparison import ParallelPairwiseComparator
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.fingerprints.comparison_index import ComparisonIndex
+from app.domains.fingerprints.fingerprint_models import KgramHashScheme, NormalizationLevel
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprinting import (
+    compute_fingerprints,
+    fingerprint_similarity,
+    kgram_hashes,
+    normalize_token,
+)
+from app.domains.storage.local_submission_store import LocalFileSystemSubmissionStore
+from app.domains.tokenization.interning import TokenInterner
+from app.domains.tokenization.streaming_source import decode_source
+from app.domains.tokenization.tokenization_service import TokenizationService
+from app.shared.profiling import StageProfiler
+
+SAMPLES_DIRECTORY = Path(__file__).parent / "resources" / "test" / "language_samples"
+
+
+def run_benchmark(samples_directory: Path, iterations: int) -> StageProfiler:
+    """Run every stage on every sample, a fingerprint service without store so each iteration tokenizes"""
+    tokenization_service = TokenizationService()
+    fingerprint_service = FingerprintService(tokenization_service)
+    similarity_service = SimilarityDetectionService()
+    profiler = StageProfiler()
+
+    for _ in range(iterations):
+        with profiler.stage("file_collection"):
+            files = tokenization_service.extract_supported_files_from_directory(samples_directory)
+        for _, fingerprint_set in fingerprint_service.fingerprint_files(
+            files, lambda path: path.read_text(encoding="utf-8", errors="replace"), profiler=profiler
+        ):
+            with profiler.stage("pairwise_comparison"):
+                similarity_service.compare_similarity(fingerprint_set.tokens, fingerprint_set.tokens)
+    return profiler
+
+
+def measure_allocations(samples_directory: Path, iterations: int) -> dict:
+    """Heap blocks and bytes held by the tokens of the samples tokenized iterations times, and the time it took"""
+    results = {}
+    for mode, interner in (("plain", TokenInterner(max_bytes=0)), ("interned", TokenInterner())):
+        tokenization_service = TokenizationService()
+        tokenization_service.interner = interner
+        files = tokenization_service.extract_supported_files_from_directory(samples_directory)
+        contents = [(file_path, file_path.read_text(encoding="utf-8", errors="replace")) for file_path in files]
+
+        tracemalloc.start()
+        started = time.perf_counter()
+        tokens = [
+            tokenization_service.tokenize(content, file_path)
+            for _ in range(iterations)
+            for file_path, content in contents
+        ]
+        seconds = time.perf_counter() - started
+        statistics = tracemalloc.take_snapshot().statistics("filename")
+        tracemalloc.stop()
+
+        results[mode] = {
+            "tokens": sum(len(file_tokens) for file_tokens in tokens),
+            "blocks": sum(statistic.count for statistic in statistics),
+            "bytes": sum(statistic.size for statistic in statistics),
+            "seconds": round(seconds, 6),
+        }
+    return results
+
+
+def measure_candidate_selection(iterations: int, submissions: int = 1000, real_candidates: int = 5) -> dict:
+    """
+    Latency of finding the submissions sharing fingerprints with a new one in a persisted step index, where only
+    real_candidates of the submissions share any: probing the inverted index for every fingerprint against
+    probing the Bloom filters first, both from loading the index ("cold") and on an index already in memory
+    """
+    rng = random.Random(0)
+    parameters = {"tokenizer_version": "benchmark", "k": 5, "window": 4}
+    index = ComparisonIndex(parameters)
+    for submission in range(submissions):
+        index.add(f"submission-{submission}", 1, {rng.getrandbits(64) for _ in range(rng.randint(100, 600))})
+    new_hashes = {rng.getrandbits(64) for _ in range(250)}
+    for submission in range(real_candidates):
+        new_hashes |= set(rng.sample(sorted(index.documents[f"submission-{submission}"].hashes), 30))
+    index.add("new", 1, new_hashes)
+    data = index.serialize()
+
+    results = {}
+    modes = (("index_probe", ComparisonIndex.probe_shared_counts), ("bloom", ComparisonIndex.shared_counts))
+    for mode, shared_counts in modes:
+        cold = warm = float("inf")
+        for _ in range(iterations):
+            started = time.perf_counter()
+            loaded = ComparisonIndex.deserialize(data, parameters)
+            shared = shared_counts(loaded, "new")
+            cold = min(cold, time.perf_counter() - started)
+
+            started = time.perf_counter()
+            shared_counts(loaded, "new")
+            warm = min(warm, time.perf_counter() - started)
+        results[mode] = {
+            "cold_seconds": round(cold, 6),
+            "warm_seconds": round(warm, 6),
+            "sharing": len(shared),
+            # Submissions whose exact fingerprints were read, the sharing ones and the false positives
+            "checked_exactly": len(loaded.candidates("new")) if mode == "bloom" else len(loaded),
+        }
+    return results
+
+
+def measure_mapped_reads(samples_directory: Path, iterations: int, files: int = 16, file_mb: int = 4) -> dict:
+    """
+    Time and peak Python heap of decoding every file of a corpus of large stored files, the files being the
+    samples concatenated up to file_mb MiB each, read with get (a buffer per file) and through views (memory maps)
+    """
+    corpus = b"\n".join(sample.read_bytes() for sample in sorted(samples_directory.iterdir()) if sample.is_file())
+    content = (corpus * (file_mb * 1024 * 1024 // max(len(corpus), 1) + 1))[: file_mb * 1024 * 1024]
+    root = Path(tempfile.mkdtemp(prefix="pamp-benchmark-"))
+    try:
+        store = LocalFileSystemSubmissionStore(root, fsync=False)
+        keys = [f"corpus/file{index}.txt" for index in range(files)]
+        for key in keys:
+            store.put(key, content)
+
+        def buffered(key: str) -> str:
+            return decode_source(store.get(key))
+
+        def mapped(key: str) -> str:
+            with store.view(key) as view:
+                return decode_source(view)
+
+        results = {}
+        for mode, read in (("buffered", buffered), ("mapped", mapped)):
+            seconds = peak = float("inf")
+            for _ in range(iterations):
+                tracemalloc.start()
+                started = time.perf_counter()
+                for key in keys:
+                    read(key)
+                seconds = min(seconds, time.perf_counter() - started)
+                peak = min(peak, tracemalloc.get_traced_memory()[1])
+                tracemalloc.stop()
+            results[mode] = {"bytes": files * len(content), "seconds": round(seconds, 6), "peak_heap_bytes": peak}
+        return results
+    finally:
+        shutil.rmtree(root, ignore_errors=True)
+
+
+def measure_hashing(samples_directory: Path, iterations: int, k: int = 5, window: int = 4) -> dict:
+    """Best time of hashing the k-grams, and of the whole fingerprinting, of the tokens of the samples per scheme"""
+    tokenization_service = TokenizationService()
+    files = tokenization_service.extract_supported_files_from_directory(samples_directory)
+    token_lists = [
+        tokenization_service.tokenize(file_path.read_text(encoding="utf-8", errors="replace"), file_path)
+        for file_path in files
+    ]
+    normalization = NormalizationLevel.IDENTIFIERS
+    parts = [[normalize_token(token, normalization) for token in tokens] for tokens in token_lists]
+
+    results = {}
+    for scheme in KgramHashScheme:
+        hashing = fingerprinting = float("inf")
+        for _ in range(iterations):
+            started = time.perf_counter()
+            for file_parts in parts:
+                kgram_hashes(file_parts, k, scheme)
+            hashing = min(hashing, time.perf_counter() - started)
+
+            started = time.perf_counter()
+            for tokens in token_lists:
+                compute_fingerprints(tokens, k, window, normalization, scheme)
+            fingerprinting = min(fingerprinting, time.perf_counter() - started)
+        results[scheme.value] = {
+            "kgrams": sum(max(len(file_parts) - k + 1, 1) for file_parts in parts if file_parts),
+            "hashing_seconds": round(hashing, 6),
+            "fingerprinting_seconds": round(fingerprinting, 6),
+        }
+    return results
+
+
+def measure_pairwise_scaling(iterations: int, submissions: int = 200, workers=(1, 2, 4)) -> dict:
+    """
+    Best time of comparing every pair of a synthetic corpus per number of workers, and its speedup over one worker;
+    each comparison sleeps briefly to release the GIL like the native similarity engines
+    """
+    rng = random.Random(7)
+    template = {rng.getrandbits(32) for _ in range(40)}
+    corpus = [
+        frozenset(template | {rng.getrandbits(32) for _ in range(rng.randint(20, 120))}) for _ in range(submissions)
+    ]
+    pairs = [(first, second) for first in range(submissions) for second in range(first + 1, submissions)]
+
+    def compare_pair(pair):
+        time.sleep(0.00005)
+        return fingerprint_similarity(corpus[pair[0]], corpus[pair[1]])
+
+    results = {}
+    for count in workers:
+        comparator = ParallelPairwiseComparator(workers=count, chunk_size=64)
+        seconds = float("inf")
+        for _ in range(iterations):
+            started = time.perf_counter()
+            for _ in comparator.compare(pairs, compare_pair):
+                pass
+            seconds = min(seconds, time.perf_counter() - started)
+        results[count] = {"pairs": len(pairs), "seconds": round(seconds, 6)}
+    for values in results.values():
+        values["speedup"] = round(results[workers[0]]["seconds"] / values["seconds"], 2)
+    return results
+
+
+def main():
+    """Run the benchmark and print its profile"""
+    parser = argparse.ArgumentParser(description=__doc__.strip().splitlines()[0])
+    parser.add_argument("--iterations", type=int, default=3, help="Runs over the samples")
+    parser.add_argument("--samples", type=Path, default=SAMPLES_DIRECTORY, help="Directory of the samples")
+    parser.add_argument("--json", action="store_true", help="Print the profile as JSON")
+    parser.add_argument("--allocations", action="store_true", help="Measure the heap blocks held by the tokens")
+    parser.add_argument("--candidates", action="store_true", help="Time the candidate selection of a new submission")
+    parser.add_argument("--mapped", action="store_true", help="Compare buffered and mapped reads of stored files")
+    parser.add_argument("--hashing", action="store_true", help="Time the k-gram hashing of every hash scheme")
+    parser.add_argument("--scaling", action="store_true", help="Time the pairwise comparison per number of workers")
+    args = parser.parse_args()
+
+    if args.scaling:
+        scaling = measure_pairwise_scaling(max(args.iterations, 1))
+        if args.json:
+            print(json.dumps(scaling, indent=2))
+            return 0
+        print(f"{'workers':>8} {'pairs':>8} {'seconds':>10} {'speedup':>8}")
+        for workers, values in scaling.items():
+            print(f"{workers:>8} {values['pairs']:>8} {values['seconds']:>10.4f} {values['speedup']:>8.2f}")
+        return 0
+
+    if args.mapped:
+        reads = measure_mapped_reads(args.samples, max(args.iterations, 1))
+        if args.json:
+            print(json.dumps(reads, indent=2))
+            return 0
+        print(f"{'mode':<10} {'bytes':>12} {'seconds':>10} {'peak heap (bytes)':>18}")
+        for mode, values in reads.items():
+            print(f"{mode:<10} {values['bytes']:>12} {values['seconds']:>10.4f} {values['peak_heap_bytes']:>18}")
+        return 0
+
+    if args.hashing:
+        hashing = measure_hashing(args.samples, max(args.iterations, 1))
+        if args.json:
+            print(json.dumps(hashing, indent=2))
+            return 0
+        print(f"{'scheme':<14} {'k-grams':>10} {'hashing (s)':>12} {'fingerprinting (s)':>19}")
+        for scheme, values in hashing.items():
+            print(
+                f"{scheme:<14} {values['kgrams']:>10} {values['hashing_seconds']:>12.4f} "
+                f"{values['fingerprinting_seconds']:>19.4f}"
+            )
+        return 0
+
+    if args.candidates:
+        candidates = measure_candidate_selection(max(args.iterations, 1))
+        if args.json:
+            print(json.dumps(candidates, indent=2))
+            return 0
+        print(f"{'mode':<12} {'cold (s)':>10} {'warm (s)':>10} {'sharing':>8} {'checked':>8}")
+        for mode, values in candidates.items():
+            print(
+                f"{mode:<12} {values['cold_seconds']:>10.4f} {values['warm_seconds']:>10.4f} "
+                f"{values['sharing']:>8} {values['checked_exactly']:>8}"
+            )
+        return 0
+
+    if args.allocations:
+        allocations = measure_allocations(args.samples, max(args.iterations, 1))
+        if args.json:
+            print(json.dumps(allocations, indent=2))
+            return 0
+        print(f"{'mode':<12} {'tokens':>10} {'blocks':>10} {'bytes':>12} {'seconds':>10}")
+        for mode, values in allocations.items():
+            print(
+                f"{mode:<12} {values['tokens']:>10} {values['blocks']:>10} {values['bytes']:>12} "
+                f"{values['seconds']:>10.4f}"
+            )
+        return 0
+
+    profiler = run_benchmark(args.samples, max(args.iterations, 1))
+
+    if args.json:
+        print(json.dumps(profiler.to_dict(), indent=2))
+        return 0
+
+    profile = profiler.to_dict()
+    print(f"⏱️  {args.iterations} iteration(s) over {args.samples} in {profile['wall_seconds']:.3f}s")
+    print(f"{'stage':<28} {'seconds':>10} {'count':>8} {'share':>8}")
+    for stage, values in profile["stages"].items():
+        print(f"{stage:<28} {values['seconds']:>10.4f} {values['count']:>8} {values['share']:>7.2f}%")
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
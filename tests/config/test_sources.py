@@ -0,0 +1,168 @@
+"""
+Tests for the layered configuration sources: precedence of the layers, secret files and errors naming their source
+"""
+
+import tempfile
+import textwrap
+import unittest
+from pathlib import Path
+
+from app.config.sources import (
+    PROFILES_DIRECTORY,
+    REDACTED,
+    ConfigurationError,
+    load_settings_with_sources,
+    redacted_config,
+)
+
+
+class SourcesTestCase(unittest.TestCase):
+    """Base class with a directory for profiles, the .env file and secret files"""
+
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        self.directory = Path(directory.name)
+        self.env_file = self.directory / ".env"
+
+    def write(self, name: str, content: str) -> Path:
+        path = self.directory / name
+        path.write_text(textwrap.dedent(content), encoding="utf-8")
+        return path
+
+    def environ(self, **variables) -> dict:
+        return {"APP_PROFILES_DIR": str(self.directory), **variables}
+
+    def load(self, **variables):
+        return load_settings_with_sources(self.environ(**variables), str(self.env_file))
+
+
+class TestLayering(SourcesTestCase):
+    """Tests for the precedence of the layers"""
+
+    def test_each_layer_overrides_the_ones_below(self):
+        """PAMP_ variables override variables, which override .env, which overrides the profile and the defaults."""
+        profile = self.write(
+            "staging.toml",
+            """
+            port = 4000
+            debug = false
+            log_format = "text"
+            report_max_pairs = 10
+            """,
+        )
+        self.write(".env", "PORT=5000\nLOG_FORMAT=json\n")
+
+        settings, values = self.load(APP_ENV="staging", PORT="6000", PAMP_PORT="7000", debug="true")
+
+        self.assertEqual(
+            (settings.port, settings.debug, settings.log_format, settings.report_max_pairs),
+            (7000, True, "json", 10),
+        )
+        self.assertEqual(values["port"].source, "environment PAMP_PORT")
+        self.assertEqual(values["debug"].source, "environment debug")
+        self.assertEqual(values["log_format"].source, f"{self.env_file} LOG_FORMAT")
+        self.assertEqual(values["report_max_pairs"].source, f"profile {profile}")
+        self.assertEqual((settings.report_min_similarity, values["report_min_similarity"].source), (0.5, "default"))
+
+    def test_without_profile(self):
+        """Without APP_ENV only the variables and the defaults are read."""
+        self.write("staging.toml", "port = 4000\n")
+
+        settings, values = self.load()
+
+        self.assertEqual((settings.app_env, settings.port, values["port"].source), (None, 3002, "default"))
+
+    def test_shipped_profiles_are_valid(self):
+        """The dev, staging and prod profiles load."""
+        for name in ("dev", "staging", "prod"):
+            _, values = load_settings_with_sources({"APP_ENV": name}, None)
+            self.assertEqual(values["debug"].source, f"profile {PROFILES_DIRECTORY / f'{name}.toml'}")
+
+
+class TestSecretFiles(SourcesTestCase):
+    """Tests for values read from the files named by _FILE variables"""
+
+    def test_values_are_read_from_files(self):
+        """A _FILE variable names the file holding the value, its trailing newline left out, then redacted."""
+        token = self.write("admin_token", "s3cret\n")
+        password = self.write("database_url", "postgresql://postgres:hunter2@db:5432/submissions_db\n")
+        environ = self.environ(PAMP_ADMIN_API_TOKEN_FILE=str(token), DATABASE_URL_FILE=str(password))
+
+        settings, values = load_settings_with_sources(environ, None)
+
+        self.assertEqual(settings.admin_api_token.get_secret_value(), "s3cret")
+        self.assertEqual(settings.database_url, "postgresql://postgres:hunter2@db:5432/submissions_db")
+        self.assertEqual(values["admin_api_token"].source, f"file {token} (environment PAMP_ADMIN_API_TOKEN_FILE)")
+
+        config = redacted_config(environ, None)
+        self.assertEqual(config["admin_api_token"], {"value": REDACTED, "source": values["admin_api_token"].source})
+        self.assertEqual(config["database_url"]["value"], f"postgresql://postgres:{REDACTED}@db:5432/submissions_db")
+        self.assertNotIn("hunter2", str(config))
+
+    def test_file_variables_take_the_place_of_the_value(self):
+        """A _FILE variable overrides the layers below, but cannot be set with the variable it replaces."""
+        token = self.write("admin_token", "s3cret")
+        self.write(".env", "ADMIN_API_TOKEN=from-dotenv\n")
+
+        settings, _ = self.load(ADMIN_API_TOKEN_FILE=str(token))
+        self.assertEqual(settings.admin_api_token.get_secret_value(), "s3cret")
+
+        with self.assertRaises(ConfigurationError) as context:
+            self.load(PAMP_ADMIN_API_TOKEN="token", PAMP_ADMIN_API_TOKEN_FILE=str(token))
+        self.assertEqual(
+            context.exception.errors,
+            [
+                "admin_api_token (environment): both PAMP_ADMIN_API_TOKEN and PAMP_ADMIN_API_TOKEN_FILE are set, "
+                "set only one of them"
+            ],
+        )
+
+        missing = self.directory / "missing"
+        with self.assertRaisesRegex(ConfigurationError, "postgres_password \\(environment POSTGRES_PASSWORD_FILE\\)"):
+            self.load(POSTGRES_PASSWORD_FILE=str(missing))
+
+
+class TestErrors(SourcesTestCase):
+    """Tests for the validation of the layered values"""
+
+    def test_every_error_names_the_key_and_its_source(self):
+        """Unknown keys and variables and invalid values are all reported, each with where it was set."""
+        profile = self.write(
+            "prod.toml",
+            """
+            prot = 3002
+            report_max_pairs = "many"
+            """,
+        )
+
+        with self.assertRaises(ConfigurationError) as context:
+            self.load(APP_ENV="prod", PAMP_DEBUG="maybe", PAMP_DATABSE_URL="postgresql://db")
+
+        self.assertEqual(
+            context.exception.errors,
+            [
+                "PAMP_DATABSE_URL (environment): unknown setting, did you mean PAMP_DATABASE_URL?",
+                f"prot (profile {profile}): unknown setting, did you mean port?",
+                "debug (environment PAMP_DEBUG): Input should be a valid boolean, unable to interpret input",
+                f"report_max_pairs (profile {profile}): "
+                "Input should be a valid integer, unable to parse string as an integer",
+            ],
+        )
+        self.assertTrue(str(context.exception).startswith("Invalid configuration:\n  - PAMP_DATABSE_URL"))
+
+    def test_unknown_profile(self):
+        """An APP_ENV without a profile file is reported with the closest profile."""
+        self.write("staging.toml", "")
+
+        with self.assertRaises(ConfigurationError) as context:
+            self.load(APP_ENV="stagign")
+
+        path = self.directory / "stagign.toml"
+        self.assertEqual(
+            context.exception.errors, [f"app_env (environment APP_ENV): no profile file {path}, did you mean staging?"]
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1 @@
+# Admin tests module
@@ -0,0 +1,185 @@
+"""
+Tests for the admin usage statistics and the admin scope
+"""
+
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from fastapi import HTTPException
+from pydantic import SecretStr
+from sqlmodel import Session, SQLModel
+
+from app.domains.admin.admin_stats_service import AdminStatsService
+from app.domains.fingerprints.fingerprint_models import FingerprintKey, FingerprintSet
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.runs.runs_models import DetectionRunStatus
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.submissions_models import Submission
+from app.shared.security import require_admin_scope
+from tests.helpers import create_test_engine
+
+STARTER_FILE = b"def main():\n    return run()\n"
+
+
+class TestAdminStatsService(unittest.TestCase):
+    """Tests for the statistics snapshot through ingestion and deletion"""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+        self.storage = SubmissionStorageService(InMemorySubmissionStore())
+        self.fingerprint_store = InMemoryFingerprintStore()
+        self.service = AdminStatsService(
+            self.session,
+            storage_service=self.storage,
+            fingerprint_service=FingerprintService(tokenization_service=None, store=self.fingerprint_store),
+            max_age_seconds=3600,
+        )
+        self.project_uuid = uuid4()
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def create_submission(self, files: dict) -> Submission:
+        submission = Submission(
+            link="https://github.com/user/repository.git",
+            project_uuid=self.project_uuid,
+            group_uuid=uuid4(),
+            project_step_uuid=uuid4(),
+        )
+        self.session.add(submission)
+        self.session.commit()
+        self.session.refresh(submission)
+
+        directory = Path(tempfile.mkdtemp(prefix="test_admin_stats_"))
+        try:
+            for path, content in files.items():
+                (directory / path).write_bytes(content)
+            self.storage.ingest_directory(submission, directory)
+        finally:
+            shutil.rmtree(directory, ignore_errors=True)
+        return submission
+
+    def delete_submission(self, submission: Submission):
+        self.storage.delete_submission_files(submission)
+        self.session.delete(submission)
+        self.session.commit()
+        # Unreferenced blobs are only removed by the garbage collection
+        self.storage.collect_garbage(grace_seconds=0)
+
+    def test_counters_follow_an_ingest_delete_cycle(self):
+        """Logical bytes count every copy and physical bytes each distinct content once, until deleted."""
+        first = self.create_submission({"main.py": STARTER_FILE, "a.py": b"x = 1\n"})
+        second = self.create_submission({"main.py": STARTER_FILE, "b.py": b"y = 22\n"})
+
+        stats = self.service.get_stats(refresh=True)
+
+        self.assertEqual(stats.submissions, 2)
+        self.assertEqual(stats.storage.files, 4)
+        self.assertEqual(stats.storage.logical_bytes, 2 * len(STARTER_FILE) + 6 + 7)
+        self.assertEqual(stats.storage.physical_bytes, len(STARTER_FILE) + 6 + 7)
+        self.assertEqual(stats.blobs, 3)
+        self.assertEqual(len(stats.projects), 1)
+        self.assertEqual(stats.projects[0].storage, stats.storage)
+
+        self.delete_submission(first)
+        stats = self.service.get_stats(refresh=True)
+
+        self.assertEqual(stats.submissions, 1)
+        self.assertEqual(stats.storage.files, 2)
+        self.assertEqual(stats.storage.logical_bytes, len(STARTER_FILE) + 7)
+        self.assertEqual(stats.storage.physical_bytes, len(STARTER_FILE) + 7)
+
+        self.delete_submission(second)
+        stats = self.service.get_stats(refresh=True)
+
+        self.assertEqual((stats.submissions, stats.storage.files, stats.storage.physical_bytes), (0, 0, 0))
+        self.assertEqual(stats.projects, [])
+        self.assertIsNone(stats.oldest_resource_at)
+
+    def test_snapshot_is_served_until_refreshed(self):
+        """Requests read the last snapshot, refresh=true recomputes it."""
+        self.create_submission({"main.py": STARTER_FILE})
+        computed = self.service.get_stats()
+
+        self.create_submission({"other.py": b"z = 3\n"})
+        cached = self.service.get_stats()
+        refreshed = self.service.get_stats(refresh=True)
+
+        self.assertEqual(cached.submissions, 1)
+        self.assertEqual(cached.computed_at, computed.computed_at)
+        self.assertEqual(refreshed.submissions, 2)
+        self.assertGreaterEqual(refreshed.computed_at, computed.computed_at)
+
+    def test_stale_snapshot_is_recomputed(self):
+        """A snapshot older than the max age is recomputed on the next request."""
+        self.service.get_stats()
+        self.create_submission({"main.py": STARTER_FILE})
+        self.service.max_age_seconds = 0
+
+        self.assertEqual(self.service.get_stats().submissions, 1)
+
+    def test_runs_and_fingerprint_cache_are_counted(self):
+        """Runs are counted by status per project and the fingerprint cache reports its entries."""
+        submission = self.create_submission({"main.py": STARTER_FILE})
+        runs = DetectionRunRepository(self.session)
+        for status in (DetectionRunStatus.COMPLETED, DetectionRunStatus.COMPLETED, DetectionRunStatus.FAILED):
+            run = runs.create_run(
+                {"project_uuid": self.project_uuid, "project_step_uuid": submission.project_step_uuid}, []
+            )
+            runs.finish_run(run.id, status)
+        key = FingerprintKey("0" * 64, "python", "1", "identifiers", 5, 4)
+        self.fingerprint_store.put(key, FingerprintSet(tokens=[], fingerprints=[(1, 0)]))
+
+        stats = self.service.get_stats(refresh=True)
+
+        self.assertEqual(stats.runs_by_status, {"completed": 2, "failed": 1})
+        self.assertEqual(stats.projects[0].runs_by_status, stats.runs_by_status)
+        self.assertEqual(stats.fingerprint_cache.entries, 1)
+        self.assertGreater(stats.fingerprint_cache.size_bytes, 0)
+
+
+class TestAdminScope(unittest.TestCase):
+    """Tests for the admin scope dependency"""
+
+    def settings(self, token):
+        return patch(
+            "app.shared.security.get_settings",
+            return_value=SimpleNamespace(admin_api_token=SecretStr(token) if token else None),
+        )
+
+    def test_matching_bearer_token_grants_the_scope(self):
+        """The configured token presented as a bearer token is accepted."""
+        with self.settings("s3cret"):
+            self.assertEqual(require_admin_scope("Bearer s3cret"), "admin")
+
+    def test_missing_or_wrong_token_is_rejected(self):
+        """Requests without the token or with another one are rejected."""
+        with self.settings("s3cret"):
+            for authorization, status_code in ((None, 401), ("Basic s3cret", 401), ("Bearer other", 403)):
+                with self.assertRaises(HTTPException) as context:
+                    require_admin_scope(authorization)
+                self.assertEqual(context.exception.status_code, status_code)
+
+    def test_admin_endpoints_are_closed_without_configured_token(self):
+        """Nothing grants the scope while no token is configured."""
+        with self.settings(None):
+            with self.assertRaises(HTTPException) as context:
+                require_admin_scope("Bearer anything")
+        self.assertEqual(context.exception.status_code, 403)
+
+
+if __name__ == "__main__":
+    unittest.main()
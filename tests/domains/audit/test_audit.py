@@ -0,0 +1,146 @@
+"""
+Tests for the audit log, its hash chain and the identity it records, against a repository double
+"""
+
+import unittest
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from pydantic import SecretStr
+
+from app.config.config import Settings
+from app.domains.audit.audit_chain import GENESIS_HASH, entry_digest, verify_chain
+from app.domains.audit.audit_hook import actor_of
+from app.domains.audit.audit_models import AuditAction
+from app.domains.audit.audit_service import AuditService
+from app.shared.exceptions import AuditWriteException, DatabaseException
+from app.shared.metrics import AUDIT_WRITE_FAILURES
+
+
+class AuditRepository:
+    """Audit repository double keeping the entries in memory, as rows that tests may tamper with"""
+
+    def __init__(self, projects=None):
+        self.entries = []
+        self.projects = projects or {}
+        self.failing = False
+
+    def last_entry(self):
+        return self.entries[-1] if self.entries else None
+
+    def append(self, entry):
+        if self.failing:
+            raise DatabaseException("Failed to append audit entry: database is down")
+        self.entries.append(entry)
+        return entry
+
+    def project_of(self, resource_type, resource_id):
+        return self.projects.get(resource_id)
+
+    def iterate_chain(self, batch_size=1000):
+        return iter(sorted(self.entries, key=lambda entry: entry.sequence))
+
+
+class AuditTestCase(unittest.TestCase):
+    def setUp(self):
+        self.run_id = uuid4()
+        self.project_uuid = uuid4()
+        self.repository = AuditRepository({self.run_id: self.project_uuid})
+        self.service = AuditService.__new__(AuditService)
+        self.service.session = None
+        self.service.repository = self.repository
+
+    def record_operations(self):
+        self.service.record(AuditAction.REPORT_VIEW, "user:alice", "run", str(self.run_id), ip_address="10.0.0.1")
+        self.service.record(AuditAction.FRAGMENT_VIEW, "user:bob", "pair", str(uuid4()), details={"method": "GET"})
+        self.service.record(AuditAction.SUBMISSION_DELETE, "admin", "submission", str(uuid4()))
+        self.service.record(AuditAction.CONFIG_CHANGE, "admin", "retention_policy", project_uuid=self.project_uuid)
+
+
+class TestHashChain(AuditTestCase):
+    def test_recorded_entries_chain_and_verify(self):
+        self.record_operations()
+
+        entries = self.repository.entries
+        self.assertEqual([entry.sequence for entry in entries], [1, 2, 3, 4])
+        self.assertEqual(entries[0].previous_hash, GENESIS_HASH)
+        self.assertEqual(entries[1].previous_hash, entries[0].entry_hash)
+        self.assertEqual(entries[0].project_uuid, self.project_uuid)
+
+        verification = self.service.verify()
+        self.assertTrue(verification.valid)
+        self.assertEqual(verification.checked, 4)
+        self.assertEqual(verification.last_hash, entries[-1].entry_hash)
+
+    def test_a_modified_row_breaks_the_chain(self):
+        self.record_operations()
+        self.repository.entries[1].actor = "user:mallory"
+
+        verification = verify_chain(self.repository.iterate_chain())
+        self.assertFalse(verification.valid)
+        self.assertEqual(verification.broken_at, 2)
+        self.assertEqual(verification.checked, 1)
+        self.assertIn("entry hash", verification.reason)
+
+    def test_a_rehashed_row_breaks_the_next_link(self):
+        self.record_operations()
+        tampered = self.repository.entries[1]
+        tampered.details = {"method": "HEAD"}
+        tampered.entry_hash = "f" * 64
+
+        verification = verify_chain(self.repository.iterate_chain())
+        self.assertEqual(verification.broken_at, 2)
+
+        # Recomputing its hash still leaves the next entry pointing at the original one
+        tampered.entry_hash = entry_digest(tampered, tampered.previous_hash)
+        verification = verify_chain(self.repository.iterate_chain())
+        self.assertEqual(verification.broken_at, 3)
+        self.assertIn("previous hash", verification.reason)
+
+    def test_a_removed_row_breaks_the_chain(self):
+        self.record_operations()
+        del self.repository.entries[2]
+
+        verification = verify_chain(self.repository.iterate_chain())
+        self.assertFalse(verification.valid)
+        self.assertEqual(verification.broken_at, 4)
+        self.assertEqual(verification.last_sequence, 2)
+
+
+class TestAuditWrites(AuditTestCase):
+    def test_deletions_and_config_changes_fail_without_their_entry(self):
+        self.repository.failing = True
+        for action in (AuditAction.SUBMISSION_DELETE, AuditAction.CONFIG_CHANGE):
+            with self.subTest(action=action), self.assertRaises(AuditWriteException) as context:
+                self.service.record(action, "admin", "submission", str(uuid4()))
+            self.assertEqual(context.exception.status_code, 503)
+
+    def test_views_are_served_when_their_entry_is_lost(self):
+        self.repository.failing = True
+        failures = AUDIT_WRITE_FAILURES.labels(AuditAction.REPORT_VIEW.value)
+        before = failures.get()
+
+        self.assertIsNone(self.service.record(AuditAction.REPORT_VIEW, "user:alice", "run", str(self.run_id)))
+        self.assertEqual(failures.get(), before + 1)
+
+
+class TestActor(unittest.TestCase):
+    def setUp(self):
+        settings = Settings(admin_api_token=SecretStr("admin-token"), audit_api_token=SecretStr("audit-token"))
+        for target in ("app.shared.security.get_settings", "app.domains.audit.audit_hook.get_settings"):
+            patcher = patch(target, return_value=settings)
+            patcher.start()
+            self.addCleanup(patcher.stop)
+
+    def test_identity_of_requests(self):
+        user = str(uuid4())
+        self.assertEqual(actor_of({"authorization": "Bearer admin-token", "x-actor-id": user}), "admin")
+        self.assertEqual(actor_of({"authorization": "Bearer audit-token"}), "audit")
+        self.assertEqual(actor_of({"authorization": "Bearer forged", "x-actor-id": user}), f"user:{user}")
+        self.assertEqual(actor_of({}), "anonymous")
+        self.assertEqual(actor_of(SimpleNamespace(get={"x-actor-id": " 42 "}.get)), "user:42")
+
+
+if __name__ == "__main__":
+    unittest.main()
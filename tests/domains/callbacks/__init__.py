@@ -0,0 +1 @@
+# Callbacks tests module
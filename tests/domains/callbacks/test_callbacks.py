@@ -0,0 +1,279 @@
+"""
+Tests for the signed summaries of finished runs posted to the main PAMP service.
+
+Delivery tests run against TEST_DATABASE_URL when set, in-memory SQLite otherwise.
+"""
+
+import json
+import unittest
+from datetime import timedelta
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from pydantic import SecretStr
+from sqlmodel import Session, SQLModel, select
+
+from app.domains.callbacks.callback_dispatcher import CallbackDispatcher
+from app.domains.callbacks.callbacks_models import CallbackDelivery, CallbackStatus
+from app.domains.callbacks.callbacks_service import CallbackService
+from app.domains.callbacks.signing import (
+    NONCE_HEADER,
+    SIGNATURE_HEADER,
+    TIMESTAMP_HEADER,
+    ReplayGuard,
+    compute_signature,
+    signed_headers,
+)
+from app.domains.runs.runs_models import DetectionPair, DetectionRunStatus
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.timestamps import utc_now
+from tests.helpers import create_test_engine
+
+SECRET = "pamp-test-secret"
+BODY = b'{"run_id":"550e8400-e29b-41d4-a716-446655440010","status":"completed"}'
+
+
+def callback_settings(**fields):
+    return SimpleNamespace(
+        **{
+            "events_enabled": False,
+            "pamp_callback_enabled": True,
+            "pamp_callback_url": "https://pamp.example/api/plagiarism/callbacks",
+            "pamp_callback_secret": SecretStr(SECRET),
+            "pamp_callback_threshold_values": [0.5, 0.9],
+            "pamp_callback_public_url": "https://submissions.pamp.example/",
+            **fields,
+        }
+    )
+
+
+class TestSignatures(unittest.TestCase):
+    """Tests for the signature of callbacks and its verification by the receiver"""
+
+    def test_signature_of_a_fixed_vector(self):
+        """The signature is the HMAC-SHA256 of timestamp, nonce and body, as computed by openssl."""
+        signature = compute_signature(SECRET, 1705314672, "9f86d081884c7d659a2feaa0c55ad015", BODY)
+
+        self.assertEqual(signature, "sha256=5b456830b90a90c502b7bb06ede1c52e5111d3ed83a558018c5240be7e661830")
+
+    def test_every_attempt_has_a_new_nonce(self):
+        """Nonces are random, never repeated across attempts."""
+        nonces = {signed_headers(SECRET, BODY, timestamp=1705314672)[NONCE_HEADER] for _ in range(1000)}
+
+        self.assertEqual(len(nonces), 1000)
+        self.assertTrue(all(len(nonce) == 32 for nonce in nonces))
+
+    def test_replays_are_refused(self):
+        """A request is accepted once, and refused when replayed, tampered with, stale or signed with another key."""
+        now = 1705314672
+        guard = ReplayGuard(SECRET, tolerance_seconds=300, clock=lambda: now)
+        headers = signed_headers(SECRET, BODY, timestamp=now)
+
+        self.assertTrue(guard.verify(headers, BODY))
+        self.assertFalse(guard.verify(headers, BODY))
+        self.assertFalse(guard.verify(signed_headers(SECRET, BODY, timestamp=now), BODY + b" "))
+        self.assertFalse(guard.verify(signed_headers(SECRET, BODY, timestamp=now - 301), BODY))
+        self.assertFalse(guard.verify(signed_headers("other-secret", BODY, timestamp=now), BODY))
+        self.assertTrue(guard.verify(signed_headers(SECRET, BODY, timestamp=now - 299), BODY))
+
+    def test_backoff_doubles_up_to_its_maximum(self):
+        """The wait after each failed attempt doubles, capped by the maximum."""
+        dispatcher = CallbackDispatcher(SECRET, backoff_seconds=30, backoff_max_seconds=300)
+
+        self.assertEqual([dispatcher.backoff(attempts) for attempts in range(1, 6)], [30, 60, 120, 240, 300])
+
+
+class PampReceiver:
+    """Main PAMP service answering with the next status, verifying every request it receives"""
+
+    def __init__(self, *statuses):
+        self.statuses = list(statuses)
+        self.guard = ReplayGuard(SECRET)
+        self.requests = []
+
+    def __call__(self, url, body, headers, timeout):
+        self.requests.append((url, body, dict(headers)))
+        if not self.guard.verify(headers, body):
+            return 401
+        status = self.statuses.pop(0) if self.statuses else 200
+        if status is None:
+            raise ConnectionRefusedError("Connection refused")
+        return status
+
+
+class TestCallbackDeliveries(unittest.TestCase):
+    """Tests for the summaries written with finished runs, their retries and their redelivery"""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+
+        self.settings = callback_settings()
+        settings = patch("app.config.config.get_settings", side_effect=lambda: self.settings)
+        settings.start()
+        self.addCleanup(settings.stop)
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def dispatcher(self, receiver, **options):
+        return CallbackDispatcher(
+            SECRET, receiver, backoff_seconds=60, session_factory=lambda: Session(self.engine), **options
+        )
+
+    def finish_run(self, scores=(0.95, 0.6, 0.2), status=DetectionRunStatus.COMPLETED):
+        runs = DetectionRunRepository(self.session)
+        run = runs.create_run({"project_uuid": uuid4(), "project_step_uuid": uuid4(), "total_pairs": len(scores)}, [])
+        for score in scores:
+            self.session.add(
+                DetectionPair(
+                    run_id=run.id,
+                    project_uuid=run.project_uuid,
+                    project_step_uuid=run.project_step_uuid,
+                    submission_id=uuid4(),
+                    compared_submission_id=uuid4(),
+                    overall_similarity=score,
+                )
+            )
+        self.session.add(
+            DetectionPair(
+                run_id=run.id,
+                project_uuid=run.project_uuid,
+                project_step_uuid=run.project_step_uuid,
+                submission_id=uuid4(),
+                compared_submission_id=uuid4(),
+                overall_similarity=0.99,
+                status=SimilarityStatus.FAILED,
+            )
+        )
+        self.session.commit()
+        return runs.finish_run(run.id, status)
+
+    def deliveries(self):
+        with Session(self.engine) as session:
+            return list(session.exec(select(CallbackDelivery).order_by(CallbackDelivery.created_at)).all())
+
+    def make_due(self):
+        """Move the next attempts of the pending deliveries to now, as if the backoff elapsed"""
+        with Session(self.engine) as session:
+            for delivery in session.exec(select(CallbackDelivery)).all():
+                if delivery.next_attempt_at is not None:
+                    delivery.next_attempt_at = utc_now() - timedelta(seconds=1)
+                    session.add(delivery)
+            session.commit()
+
+    def test_summary_is_written_with_the_finished_run(self):
+        """Finishing a run writes its summary: counts of completed pairs above each threshold and the report link."""
+        run = self.finish_run()
+
+        (delivery,) = self.deliveries()
+        self.assertEqual(delivery.status, CallbackStatus.PENDING)
+        self.assertEqual(delivery.url, self.settings.pamp_callback_url)
+        self.assertEqual(delivery.payload["run_id"], str(run.id))
+        self.assertEqual(delivery.payload["status"], "completed")
+        self.assertEqual(
+            delivery.payload["pairs_above_thresholds"], [{"threshold": 0.5, "pairs": 2}, {"threshold": 0.9, "pairs": 1}]
+        )
+        self.assertEqual(delivery.payload["report_url"], f"https://submissions.pamp.example/runs/{run.id}/report.html")
+        self.assertTrue(delivery.payload["finished_at"].endswith("Z"))
+
+    def test_nothing_is_written_without_configuration(self):
+        """No summary is written while callbacks are disabled or have no secret."""
+        for settings in [callback_settings(pamp_callback_enabled=False), callback_settings(pamp_callback_secret=None)]:
+            self.settings = settings
+            self.finish_run()
+
+        self.assertEqual(self.deliveries(), [])
+
+    def test_failed_attempts_are_retried_with_backoff(self):
+        """A delivery refused or unanswered is attempted again once its backoff elapsed, signed anew each time."""
+        self.finish_run()
+        receiver = PampReceiver(503, None)
+        dispatcher = self.dispatcher(receiver)
+
+        self.assertEqual(dispatcher.deliver_due(), 0)
+        (delivery,) = self.deliveries()
+        self.assertEqual((delivery.status, delivery.attempts), (CallbackStatus.PENDING, 1))
+        self.assertEqual((delivery.last_status_code, delivery.last_error), (503, "HTTP 503"))
+        self.assertAlmostEqual((delivery.next_attempt_at - delivery.last_attempt_at).total_seconds(), 60, places=3)
+
+        # Not due before its backoff elapsed
+        self.assertEqual(dispatcher.deliver_due(), 0)
+        self.assertEqual(len(receiver.requests), 1)
+
+        self.make_due()
+        self.assertEqual(dispatcher.deliver_due(), 0)
+        (delivery,) = self.deliveries()
+        self.assertEqual(delivery.attempts, 2)
+        self.assertIsNone(delivery.last_status_code)
+        self.assertEqual(delivery.last_error, "Connection refused")
+        self.assertAlmostEqual((delivery.next_attempt_at - delivery.last_attempt_at).total_seconds(), 120, places=3)
+
+        self.make_due()
+        self.assertEqual(dispatcher.deliver_due(), 1)
+        (delivery,) = self.deliveries()
+        self.assertEqual((delivery.status, delivery.attempts), (CallbackStatus.DELIVERED, 3))
+        self.assertEqual((delivery.last_status_code, delivery.last_error), (200, None))
+        self.assertIsNotNone(delivery.delivered_at)
+        self.assertIsNone(delivery.next_attempt_at)
+
+        bodies = {body for _, body, _ in receiver.requests}
+        nonces = {headers[NONCE_HEADER] for _, _, headers in receiver.requests}
+        self.assertEqual(len(bodies), 1)
+        self.assertEqual(len(nonces), 3)
+        self.assertEqual(json.loads(bodies.pop())["run_id"], str(delivery.run_id))
+        self.assertTrue(all(TIMESTAMP_HEADER in h and SIGNATURE_HEADER in h for _, _, h in receiver.requests))
+
+    def test_redelivery_after_the_retry_budget(self):
+        """A delivery failed after its last attempt is sent again, with a new budget, once redelivered."""
+        run = self.finish_run(status=DetectionRunStatus.INCOMPLETE)
+        receiver = PampReceiver(503, 503)
+        dispatcher = self.dispatcher(receiver, max_attempts=2)
+
+        dispatcher.deliver_due()
+        self.make_due()
+        dispatcher.deliver_due()
+        (delivery,) = self.deliveries()
+        self.assertEqual((delivery.status, delivery.attempts), (CallbackStatus.FAILED, 2))
+        self.make_due()
+        self.assertEqual(dispatcher.deliver_due(), 0)
+        self.assertEqual(len(receiver.requests), 2)
+
+        redelivered = CallbackService(self.session).redeliver(run.id)
+        self.assertEqual((redelivered.status, redelivered.attempts), ("pending", 0))
+        self.assertEqual(dispatcher.deliver_due(), 1)
+
+        (delivery,) = self.deliveries()
+        self.assertEqual((delivery.status, delivery.attempts), (CallbackStatus.DELIVERED, 1))
+        self.assertEqual(delivery.payload["status"], "incomplete")
+        self.assertEqual(len({headers[NONCE_HEADER] for _, _, headers in receiver.requests}), 3)
+        self.assertEqual(len(CallbackService(self.session).list_run_callbacks(run.id)), 1)
+
+    def test_redelivery_of_a_run_finished_without_callbacks(self):
+        """Runs finished while callbacks were disabled get a delivery; nothing is queued while they are disabled."""
+        self.settings = callback_settings(pamp_callback_enabled=False)
+        run = self.finish_run()
+        self.assertIsNone(CallbackService(self.session).redeliver(run.id))
+
+        self.settings = callback_settings()
+        delivery = CallbackService(self.session).redeliver(run.id)
+
+        self.assertEqual(delivery.run_id, run.id)
+        self.assertEqual(self.dispatcher(PampReceiver()).deliver_due(), 1)
+
+    def test_deliveries_are_deleted_with_their_run(self):
+        run = self.finish_run()
+
+        DetectionRunRepository(self.session).delete_run(run.id)
+
+        self.assertEqual(self.deliveries(), [])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1 @@
+# Corpus tests module
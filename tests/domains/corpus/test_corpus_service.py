@@ -0,0 +1,334 @@
+"""
+Tests for exporting a project as a corpus archive and importing it into a clean deployment
+"""
+
+import io
+import json
+import shutil
+import tarfile
+import tempfile
+import unittest
+from itertools import combinations
+from pathlib import Path
+from uuid import uuid4
+
+from sqlmodel import Session, SQLModel, select
+
+from app.domains.corpus.corpus_archive import ARCHIVE_SCHEMA_VERSION, IncompatibleArchiveException
+from app.domains.corpus.corpus_service import CorpusService
+from app.domains.corpus.dto.corpus_dto import CorpusExportDto, CorpusImportDto
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprinting import fingerprint_similarity
+from app.domains.quotas.quota_models import ProjectUsage
+from app.domains.runs.runs_models import DetectionFragment, DetectionPair, DetectionRunStatus
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.submissions_models import Submission, SubmissionSimilarity
+from app.shared.exceptions import ValidationException
+from tests.helpers import WordTokenizer, create_test_engine
+
+STARTER_FILE = "def main run setup loop while True step update draw end return 0"
+
+SUBMISSION_FILES = [
+    {"main.py": STARTER_FILE, "game.py": "class Game def play self score = score + 1 return score"},
+    {"main.py": STARTER_FILE, "game.py": "class Game def play self points = points + 1 return points"},
+    {"main.py": STARTER_FILE, "src/board.py": "class Board def cells self return rows * columns"},
+]
+
+
+def make_engine(isolated: bool = False):
+    engine = create_test_engine(isolated)
+    SQLModel.metadata.drop_all(engine)
+    SQLModel.metadata.create_all(engine)
+    return engine
+
+
+def rerun_similarities(storage_service: SubmissionStorageService, submissions) -> dict:
+    """Fingerprint similarity of every pair of submissions computed from the stored files"""
+    fingerprints = FingerprintService(WordTokenizer(), k=3, window=2)
+    hashes = {}
+    for submission in submissions:
+        directory = storage_service.materialize(submission)
+        try:
+            hashes[submission.id] = set()
+            for path in sorted(directory.rglob("*.py")):
+                hashes[submission.id] |= fingerprints.get_fingerprints(path.read_text(), path).hashes
+        finally:
+            shutil.rmtree(directory, ignore_errors=True)
+    return {
+        (first.id, second.id): fingerprint_similarity(hashes[first.id], hashes[second.id])
+        for first, second in combinations(submissions, 2)
+    }
+
+
+class TestCorpusRoundTrip(unittest.TestCase):
+    """Tests exporting a project and importing it into a clean database and storage backend"""
+
+    def setUp(self):
+        self.source_engine = make_engine()
+        self.source_session = Session(self.source_engine)
+        self.source_storage = SubmissionStorageService(InMemorySubmissionStore())
+        self.project_uuid = uuid4()
+        self.step_uuid = uuid4()
+
+        self.submissions = []
+        for index, files in enumerate(SUBMISSION_FILES):
+            submission = Submission(
+                link=f"https://github.com/student{index}/game.git",
+                project_uuid=self.project_uuid,
+                group_uuid=uuid4(),
+                project_step_uuid=self.step_uuid,
+                submitted_by_uuid=uuid4(),
+                description=f"Submission of student {index}",
+                ip_address="192.168.1.10",
+                user_agent="pytest",
+            )
+            self.source_session.add(submission)
+            self.source_session.commit()
+            self.source_session.refresh(submission)
+            self.ingest(submission, files)
+            self.submissions.append(submission)
+
+        first, second, _ = self.submissions
+        similarity = SubmissionSimilarity(
+            submission_id=first.id,
+            compared_submission_id=second.id,
+            project_uuid=self.project_uuid,
+            project_step_uuid=self.step_uuid,
+            overall_similarity=0.8,
+            shared_blocks={"blocks": [{"code": STARTER_FILE}]},
+        )
+        self.source_session.add(similarity)
+        self.source_session.commit()
+        self.source_session.refresh(similarity)
+        self.similarity = similarity
+
+        runs = DetectionRunRepository(self.source_session)
+        run = runs.create_run(
+            {"project_uuid": self.project_uuid, "project_step_uuid": self.step_uuid, "total_pairs": 1},
+            [
+                {"submission_id": s.id, "group_uuid": s.group_uuid, "submitted_by_uuid": s.submitted_by_uuid}
+                for s in self.submissions
+            ],
+        )
+        pair = DetectionPair(
+            run_id=run.id,
+            project_uuid=self.project_uuid,
+            project_step_uuid=self.step_uuid,
+            submission_id=first.id,
+            compared_submission_id=second.id,
+            similarity_id=similarity.id,
+            overall_similarity=0.8,
+            fragments_count=1,
+        )
+        fragment = DetectionFragment(
+            pair_id=pair.id, run_id=run.id, file1_path="main.py", file2_path="main.py", similarity=1.0
+        )
+        runs.insert_batch(run.id, [pair], [fragment])
+        runs.finish_run(run.id, DetectionRunStatus.COMPLETED)
+
+        # The clean deployment, a database of its own
+        self.target_engine = make_engine(isolated=True)
+        self.target_session = Session(self.target_engine)
+        self.target_storage = SubmissionStorageService(InMemorySubmissionStore())
+        self.target_project = uuid4()
+
+    def tearDown(self):
+        self.source_session.close()
+        self.target_session.close()
+        SQLModel.metadata.drop_all(self.source_engine)
+        SQLModel.metadata.drop_all(self.target_engine)
+        self.source_engine.dispose()
+        self.target_engine.dispose()
+
+    def ingest(self, submission, files: dict):
+        directory = Path(tempfile.mkdtemp(prefix="test_corpus_"))
+        try:
+            for path, content in files.items():
+                (directory / path).parent.mkdir(parents=True, exist_ok=True)
+                (directory / path).write_text(content)
+            self.source_storage.ingest_directory(submission, directory)
+        finally:
+            shutil.rmtree(directory, ignore_errors=True)
+
+    def export(self, **options):
+        service = CorpusService(
+            self.source_session, self.source_storage, FingerprintService(WordTokenizer(), k=3, window=2)
+        )
+        return service.export_project(self.project_uuid, CorpusExportDto(**options))
+
+    def transfer(self, archive_key: str) -> CorpusService:
+        """Copy an archive to the clean deployment, the way an operator would move it between buckets"""
+        archive = self.source_storage.store.get(archive_key)
+        self.target_storage.store.put(archive_key, archive, "application/gzip")
+        return CorpusService(self.target_session, self.target_storage)
+
+    def read_archive(self, archive_key: str) -> dict:
+        archive = self.source_storage.store.get(archive_key)
+        with tarfile.open(fileobj=io.BytesIO(archive), mode="r:gz") as tar:
+            return {member.name: tar.extractfile(member).read() for member in tar if member.isfile()}
+
+    def test_export_counts_project_resources(self):
+        """The export reports every submission, distinct blob, similarity, run and pair of the project."""
+        result = self.export()
+
+        self.assertEqual(result.schema_version, ARCHIVE_SCHEMA_VERSION)
+        self.assertEqual(result.submissions, 3)
+        # The starter file is shared by every submission and stored once
+        self.assertEqual(result.blobs, 4)
+        self.assertEqual((result.similarities, result.runs, result.pairs), (1, 1, 1))
+        self.assertTrue(result.archive_key.startswith(f"exports/projects/{self.project_uuid}/"))
+        self.assertEqual(result.size_bytes, len(self.source_storage.store.get(result.archive_key)))
+
+    def test_round_trip_recreates_the_corpus_with_new_ids(self):
+        """An imported corpus has new IDs, the same cross-references and identical file contents."""
+        export = self.export()
+
+        result = self.transfer(export.archive_key).import_archive(
+            self.target_project, CorpusImportDto(archive_key=export.archive_key)
+        )
+
+        self.assertEqual((result.submissions, result.similarities, result.runs, result.pairs), (3, 1, 1, 1))
+        self.assertEqual(result.files, 6)
+        self.assertEqual(result.source_project_uuid, self.project_uuid)
+
+        imported = {s.id: s for s in self.target_session.exec(select(Submission)).all()}
+        self.assertEqual(set(imported), set(result.submission_ids.values()))
+        self.assertFalse(set(imported) & {s.id for s in self.submissions})
+        for original in self.submissions:
+            copy = imported[result.submission_ids[str(original.id)]]
+            self.assertEqual(copy.project_uuid, self.target_project)
+            self.assertEqual(copy.link, original.link)
+            self.assertEqual(copy.group_uuid, original.group_uuid)
+            for stored in self.source_storage.list_files(original):
+                self.assertEqual(
+                    self.target_storage.read_file(copy, stored.key), self.source_storage.read_file(original, stored.key)
+                )
+
+        similarity = self.target_session.exec(select(SubmissionSimilarity)).one()
+        self.assertNotEqual(similarity.id, self.similarity.id)
+        self.assertEqual(similarity.submission_id, result.submission_ids[str(self.similarity.submission_id)])
+
+        pair = self.target_session.exec(select(DetectionPair)).one()
+        self.assertEqual(pair.similarity_id, similarity.id)
+        self.assertEqual(pair.project_uuid, self.target_project)
+        self.assertEqual(pair.submission_id, similarity.submission_id)
+        self.assertEqual(pair.compared_submission_id, similarity.compared_submission_id)
+        fragment = self.target_session.exec(select(DetectionFragment)).one()
+        self.assertEqual((fragment.pair_id, fragment.run_id), (pair.id, pair.run_id))
+
+        participants = DetectionRunRepository(self.target_session).get_participants(pair.run_id)
+        self.assertEqual({p.submission_id for p in participants}, set(imported))
+
+    def test_reruns_on_imported_corpus_match_the_source(self):
+        """Comparing the imported submissions gives the same scores as comparing the originals."""
+        export = self.export()
+        result = self.transfer(export.archive_key).import_archive(
+            self.target_project, CorpusImportDto(archive_key=export.archive_key)
+        )
+        copies = [self.target_session.get(Submission, result.submission_ids[str(s.id)]) for s in self.submissions]
+
+        source_scores = rerun_similarities(self.source_storage, self.submissions)
+        target_scores = rerun_similarities(self.target_storage, copies)
+
+        mapped = {
+            (result.submission_ids[str(first)], result.submission_ids[str(second)]): score
+            for (first, second), score in source_scores.items()
+        }
+        self.assertEqual(mapped, target_scores)
+        self.assertTrue(any(score > 0 for score in target_scores.values()))
+
+    def test_anonymized_export_strips_identifying_data(self):
+        """Anonymized archives carry pseudonyms instead of identifiers and no links or client metadata."""
+        export = self.export(anonymize=True)
+
+        members = self.read_archive(export.archive_key)
+        archive_text = b"".join(members.values()).decode("utf-8", errors="replace")
+        for submission in self.submissions:
+            self.assertNotIn(str(submission.id), archive_text)
+            self.assertNotIn(str(submission.submitted_by_uuid), archive_text)
+            self.assertNotIn(submission.link, archive_text)
+        self.assertNotIn(str(self.project_uuid), archive_text)
+        self.assertNotIn("192.168.1.10", archive_text)
+
+        result = self.transfer(export.archive_key).import_archive(
+            self.target_project, CorpusImportDto(archive_key=export.archive_key)
+        )
+        self.assertTrue(result.anonymized)
+        self.assertIsNone(result.source_project_uuid)
+        pair = self.target_session.exec(select(DetectionPair)).one()
+        self.assertIn(pair.submission_id, set(result.submission_ids.values()))
+
+    def test_fingerprint_export_holds_no_source_code(self):
+        """Fingerprint-only archives leave out blobs and code excerpts of similarities."""
+        export = self.export(content="fingerprints")
+
+        members = self.read_archive(export.archive_key)
+        self.assertEqual(export.blobs, 0)
+        self.assertFalse([name for name in members if name.startswith("blobs/")])
+        self.assertNotIn(STARTER_FILE, b"".join(members.values()).decode("utf-8"))
+        header = json.loads(members["archive.json"])
+        self.assertEqual(header["fingerprint_parameters"]["k"], 3)
+
+        result = self.transfer(export.archive_key).import_archive(
+            self.target_project, CorpusImportDto(archive_key=export.archive_key)
+        )
+        self.assertEqual((result.submissions, result.files), (3, 0))
+        self.assertIsNone(self.target_session.exec(select(SubmissionSimilarity)).one().shared_blocks)
+
+    def test_unsupported_schema_version_is_rejected(self):
+        """Archives written with a schema version this build does not know are refused."""
+        key = f"exports/projects/{self.project_uuid}/future.tar.gz"
+        header = json.dumps({"format": "pamp-corpus", "schema_version": 99, "content": "blobs"}).encode()
+        buffer = io.BytesIO()
+        with tarfile.open(fileobj=buffer, mode="w:gz") as tar:
+            info = tarfile.TarInfo("archive.json")
+            info.size = len(header)
+            tar.addfile(info, io.BytesIO(header))
+        self.target_storage.store.put(key, buffer.getvalue(), "application/gzip")
+
+        with self.assertRaises(IncompatibleArchiveException) as context:
+            CorpusService(self.target_session, self.target_storage).import_archive(
+                self.target_project, CorpusImportDto(archive_key=key)
+            )
+
+        self.assertIn("schema version 99", context.exception.reason)
+        self.assertEqual(self.target_session.exec(select(Submission)).all(), [])
+
+    def test_failed_import_creates_nothing(self):
+        """An archive whose last submission has a corrupted blob leaves no record, usage or stored file behind."""
+        export = self.export()
+        corrupted = next(f.etag for f in self.source_storage.list_files(self.submissions[2]) if f.key == "src/board.py")
+        buffer = io.BytesIO()
+        with tarfile.open(fileobj=io.BytesIO(self.source_storage.store.get(export.archive_key)), mode="r:gz") as source:
+            with tarfile.open(fileobj=buffer, mode="w:gz") as tar:
+                for member in source:
+                    content = source.extractfile(member).read()
+                    if member.name.endswith(f"/{corrupted}"):
+                        content = b"tampered"
+                    member.size = len(content)
+                    tar.addfile(member, io.BytesIO(content))
+        self.target_storage.store.put(export.archive_key, buffer.getvalue(), "application/gzip")
+
+        with self.assertRaises(ValidationException):
+            CorpusService(self.target_session, self.target_storage).import_archive(
+                self.target_project, CorpusImportDto(archive_key=export.archive_key)
+            )
+
+        self.assertEqual(self.target_session.exec(select(Submission)).all(), [])
+        self.assertEqual(self.target_session.exec(select(ProjectUsage)).all(), [])
+        self.assertEqual(self.target_storage.store.list("projects/"), [])
+        self.target_storage.collect_garbage(grace_seconds=0)
+        self.assertEqual(self.target_storage.store.list("blobs/"), [])
+
+    def test_import_outside_exports_is_rejected(self):
+        """Only objects of the exports area can be imported."""
+        with self.assertRaises(ValidationException):
+            CorpusService(self.target_session, self.target_storage).import_archive(
+                self.target_project, CorpusImportDto(archive_key="projects/other/submissions/x/manifests/v1.json")
+            )
+
+
+if __name__ == "__main__":
+    unittest.main()
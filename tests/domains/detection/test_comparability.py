@@ -0,0 +1,192 @@
+"""
+Tests for the comparability of empty, comments-only and trivially small submissions
+"""
+
+import math
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.detection.comparability import (
+    NotComparableReason,
+    assess_pair,
+    comparable_token_count,
+    not_comparable_reason,
+)
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.runs.run_recorder import DetectionRunRecorder
+from app.domains.runs.runs_service import not_comparable_submissions
+from app.domains.submissions.submissions_models import SimilarityStatus
+from tests.helpers import TREE_SITTER_AVAILABLE
+
+# Tokens of the files as tree-sitter gives them, the root node spanning the whole file first
+EMPTY = []
+COMMENTS_ONLY = [
+    {"type": "module", "text": "# TODO\n# nothing yet", "start": 0, "end": 1},
+    {"type": "comment", "text": "# TODO", "start": 0, "end": 0},
+    {"type": "comment", "text": "# nothing yet", "start": 1, "end": 1},
+]
+ONE_TOKEN = [
+    {"type": "module", "text": "x", "start": 0, "end": 0},
+    {"type": "expression_statement", "text": "x", "start": 0, "end": 0},
+    {"type": "identifier", "text": "x", "start": 0, "end": 0},
+]
+PROGRAM = [
+    {"type": "module", "text": "...", "start": 0, "end": 9},
+    *[{"type": "function_definition", "text": f"def f{n}(a):", "start": n, "end": n} for n in range(10)],
+    *[{"type": "return_statement", "text": "return a + 1", "start": n, "end": n} for n in range(10)],
+    *[{"type": "binary_operator", "text": "a + 1", "start": n, "end": n} for n in range(10)],
+]
+
+
+class RecordingRepository:
+    """Run repository double keeping what the recorder writes"""
+
+    def __init__(self):
+        self.pairs = []
+        self.not_comparable = {}
+
+    def insert_batch(self, run_id, pairs, fragments):
+        self.pairs.extend(pairs)
+        return len(pairs)
+
+    def mark_not_comparable(self, run_id, reasons):
+        self.not_comparable.update(reasons)
+        return len(reasons)
+
+    def finish_run(self, run_id, status, error_message=None, cache_stats=None, profile=None, file_errors=None):
+        return SimpleNamespace(id=run_id, status=status, error_message=error_message)
+
+
+def submission():
+    return SimpleNamespace(id=uuid4(), project_uuid=uuid4(), project_step_uuid=uuid4(), submitted_by_uuid=None)
+
+
+class TestComparability(unittest.TestCase):
+    """Tests for the reasons and low confidence flag of submissions with little or no code"""
+
+    def test_empty_submission_is_not_comparable(self):
+        """Supported files without tokens, or no supported file at all, are not compared."""
+        self.assertEqual(not_comparable_reason(1, EMPTY), NotComparableReason.EMPTY)
+        self.assertEqual(not_comparable_reason(0, PROGRAM), NotComparableReason.NO_SUPPORTED_FILES)
+
+        comparability = assess_pair([(1, EMPTY), (3, PROGRAM)])
+        self.assertFalse(comparability.comparable)
+        self.assertFalse(comparability.low_confidence)
+        self.assertEqual(comparability.to_details()["not_comparable"], {"submission1": "empty"})
+        self.assertEqual(comparability.error_message, "Not comparable: submission1 has no code in its files")
+
+    def test_comments_only_submission_is_not_comparable(self):
+        """Comments and the root node around them are no comparable token."""
+        self.assertEqual(comparable_token_count(COMMENTS_ONLY), 0)
+        self.assertEqual(not_comparable_reason(1, COMMENTS_ONLY), NotComparableReason.COMMENTS_ONLY)
+        with_errors = COMMENTS_ONLY + [{"type": "ERROR", "text": "?", "start": 2, "end": 2}]
+        self.assertEqual(not_comparable_reason(1, with_errors), NotComparableReason.COMMENTS_ONLY)
+
+        comparability = assess_pair([(2, PROGRAM), (1, COMMENTS_ONLY)])
+        self.assertEqual(comparability.to_details()["not_comparable"], {"submission2": "comments_only"})
+
+    def test_one_token_file_is_compared_with_low_confidence(self):
+        """A single statement is compared, but its pairs are flagged below the minimum only."""
+        comparability = assess_pair([(1, ONE_TOKEN), (1, PROGRAM)], minimum=20)
+
+        self.assertTrue(comparability.comparable)
+        self.assertIsNone(comparability.error_message)
+        self.assertEqual(comparability.comparable_tokens, {"submission1": 2, "submission2": 30})
+        self.assertTrue(comparability.low_confidence)
+        self.assertFalse(assess_pair([(1, ONE_TOKEN), (1, PROGRAM)], minimum=2).low_confidence)
+        self.assertFalse(assess_pair([(1, ONE_TOKEN), (1, ONE_TOKEN)], minimum=0).low_confidence)
+
+    def test_scores_are_never_nan(self):
+        """Every score of empty, comments-only and one-token inputs is a finite number between 0 and 1."""
+        service = SimilarityDetectionService()
+        inputs = [EMPTY, COMMENTS_ONLY, ONE_TOKEN, PROGRAM]
+        for tokens1 in inputs:
+            for tokens2 in inputs:
+                result = service.compare_similarity(tokens1, tokens2)
+                for name, value in result.items():
+                    if isinstance(value, float):
+                        self.assertTrue(math.isfinite(value) and 0.0 <= value <= 1.0, (name, value))
+        self.assertEqual(service.compare_similarity(EMPTY, EMPTY)["overall_similarity"], 0.0)
+
+
+class TestNotComparableRecording(unittest.TestCase):
+    """Tests that not comparable and low confidence pairs reach the run tables and the run report"""
+
+    def setUp(self):
+        self.repository = RecordingRepository()
+        self.recorder = DetectionRunRecorder(self.repository, uuid4())
+
+    def record(self, submission1, submission2, status, details):
+        similarity = SimpleNamespace(
+            id=uuid4(),
+            status=status,
+            error_message=None,
+            processing_time_seconds=0.1,
+            similarity_details=details,
+            visualization_data=[],
+        )
+        return self.recorder.record_comparison(similarity, submission1, submission2)
+
+    def test_reasons_are_written_to_the_participants(self):
+        """The pair of a comments-only submission is not comparable, its reason reaches the report."""
+        present, commented = submission(), submission()
+        comparability = assess_pair([(1, PROGRAM), (1, COMMENTS_ONLY)])
+        pair = self.record(present, commented, SimilarityStatus.NOT_COMPARABLE, comparability.to_details())
+        self.recorder.finish()
+
+        self.assertEqual(pair.status, SimilarityStatus.NOT_COMPARABLE)
+        self.assertEqual(pair.overall_similarity, 0.0)
+        self.assertEqual(self.repository.not_comparable, {commented.id: "comments_only"})
+
+        participants = [
+            SimpleNamespace(submission_id=present.id, not_comparable_reason=None),
+            SimpleNamespace(submission_id=commented.id, not_comparable_reason="comments_only"),
+        ]
+        listed = not_comparable_submissions(participants)
+        self.assertEqual([(s.submission_id, s.reason) for s in listed], [(commented.id, "comments_only")])
+        self.assertEqual(listed[0].description, "only comments in its files")
+
+    def test_low_confidence_is_recorded_on_the_pair(self):
+        """Pairs with a one-token side are recorded as completed and low confidence."""
+        details = assess_pair([(1, ONE_TOKEN), (1, PROGRAM)]).to_details()
+        pair = self.record(submission(), submission(), SimilarityStatus.COMPLETED, details)
+        self.recorder.finish()
+
+        self.assertTrue(pair.low_confidence)
+        self.assertEqual(self.repository.not_comparable, {})
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestComparabilityTokenization(unittest.TestCase):
+    """Tests the comparability of files tokenized by tree-sitter"""
+
+    @classmethod
+    def setUpClass(cls):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        cls.service = TokenizationService()
+
+    def test_comments_only_file(self):
+        """A Python file of comments only has no comparable token."""
+        tokens = self.service.tokenize("# TODO\n# nothing yet\n", Path("main.py"))
+
+        self.assertEqual(not_comparable_reason(1, tokens), NotComparableReason.COMMENTS_ONLY)
+
+    def test_empty_file(self):
+        """An empty file gives no token."""
+        tokens = self.service.tokenize("", Path("main.py"))
+
+        self.assertEqual(not_comparable_reason(1, tokens), NotComparableReason.EMPTY)
+
+    def test_one_token_file(self):
+        """A file of a single name is compared, with too few tokens for a confident score."""
+        tokens = self.service.tokenize("x\n", Path("main.py"))
+
+        self.assertIsNone(not_comparable_reason(1, tokens))
+        self.assertTrue(assess_pair([(1, tokens), (1, PROGRAM)]).low_confidence)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,118 @@
+"""
+Tests for the parallel pairwise comparison of detection runs
+"""
+
+import json
+import random
+import threading
+import time
+import unittest
+from uuid import UUID
+
+from app.domains.detection.pairwise_comparison import (
+    PairwiseProgress,
+    ParallelPairwiseComparator,
+    canonical_pairs,
+    fingerprint_similarities,
+)
+
+CORPUS_SIZE = 200
+PAIR_COUNT = CORPUS_SIZE * (CORPUS_SIZE - 1) // 2
+
+
+def synthetic_corpus(size: int = CORPUS_SIZE, seed: int = 7) -> dict:
+    """Fingerprint hashes of submissions sharing a starter template and some copied code"""
+    generator = random.Random(seed)
+    template = {generator.getrandbits(32) for _ in range(40)}
+    shared = [{generator.getrandbits(32) for _ in range(30)} for _ in range(10)]
+    corpus = {}
+    for _ in range(size):
+        hashes = set(template) | {generator.getrandbits(32) for _ in range(generator.randint(20, 120))}
+        if generator.random() < 0.3:
+            hashes |= generator.choice(shared)
+        corpus[UUID(int=generator.getrandbits(128))] = frozenset(hashes)
+    return corpus
+
+
+def report(results) -> str:
+    return json.dumps([[str(first), str(second), score] for (first, second), score in results])
+
+
+class TestParallelPairwiseComparator(unittest.TestCase):
+    """Tests for ordering, progress and cancellation of parallel comparisons"""
+
+    def setUp(self):
+        self.corpus = synthetic_corpus()
+
+    def test_reports_are_identical_at_any_thread_count(self):
+        """A 200 submission corpus produces byte-identical reports with 1 and 8 threads."""
+        sequential = fingerprint_similarities(self.corpus, ParallelPairwiseComparator(workers=1))
+        parallel = fingerprint_similarities(self.corpus, ParallelPairwiseComparator(workers=8, chunk_size=7))
+
+        self.assertEqual(len(sequential), PAIR_COUNT)
+        self.assertEqual(report(parallel), report(sequential))
+        self.assertEqual([pair for pair, _ in parallel], canonical_pairs(pair for pair, _ in parallel))
+
+    def test_index_is_shared_not_copied(self):
+        """Workers read the fingerprint sets of the index itself."""
+        seen = set()
+        lock = threading.Lock()
+
+        def compare_pair(pair):
+            with lock:
+                seen.add(id(self.corpus[pair[0]]))
+            return 1.0
+
+        pairs = [(key, key) for key in self.corpus]
+        list(ParallelPairwiseComparator(workers=4).compare(pairs, compare_pair))
+
+        self.assertEqual(seen, {id(hashes) for hashes in self.corpus.values()})
+
+    def test_progress_counts_every_pair(self):
+        """Completed and failed pairs are counted from every worker."""
+        progress = PairwiseProgress()
+
+        def compare_pair(pair):
+            if pair[0] % 10 == 0:
+                raise RuntimeError("unreadable submission")
+            return None if pair[0] % 10 == 1 else pair[0] * pair[1]
+
+        results = list(
+            ParallelPairwiseComparator(workers=8, chunk_size=3).compare(
+                [(index, index + 1) for index in range(500)], compare_pair, progress
+            )
+        )
+
+        self.assertEqual(len(results), 500)
+        self.assertEqual(progress.to_dict(), {"total": 500, "completed": 400, "failed": 100, "cancelled": False})
+
+    def test_cancellation_stops_inside_the_parallel_loop(self):
+        """Once cancelled, no new pair starts and only compared pairs are yielded."""
+        compared = []
+        lock = threading.Lock()
+        cancelled = threading.Event()
+
+        def compare_pair(pair):
+            with lock:
+                compared.append(pair)
+                if len(compared) == 50:
+                    cancelled.set()
+            time.sleep(0.001)
+            return 0.5
+
+        progress = PairwiseProgress()
+        pairs = [(index, index) for index in range(2000)]
+        results = list(
+            ParallelPairwiseComparator(workers=4, chunk_size=10).compare(
+                pairs, compare_pair, progress, should_cancel=cancelled.is_set
+            )
+        )
+
+        self.assertTrue(progress.cancelled)
+        self.assertLess(len(compared), 100)
+        self.assertLessEqual(len(results), len(compared))
+        self.assertEqual(progress.processed, len(compared))
+
+
+if __name__ == "__main__":
+    unittest.main()
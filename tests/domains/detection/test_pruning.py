@@ -0,0 +1,240 @@
+"""
+Tests for the pruning of pair comparisons from the comparison index
+"""
+
+import itertools
+import random
+import shutil
+import tempfile
+import unittest
+import uuid
+from pathlib import Path
+from types import SimpleNamespace
+
+from app.domains.detection.pruning import PairPruner, PrunedPair
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.fingerprints.comparison_index import ComparisonIndex
+from app.domains.fingerprints.comparison_index_service import ComparisonIndexService
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from tests.helpers import SharedLinesVisualization, detection_service, write_submissions
+
+# Token types of the tree-sitter grammars read by the similarity metrics
+TYPES = [
+    "function_definition",
+    "if_statement",
+    "else_clause",
+    "for_statement",
+    "while_statement",
+    "return_statement",
+    "try_statement",
+    "except_clause",
+    "call",
+    "attribute",
+    "assignment",
+    "binary_operator",
+    "comparison_operator",
+    "identifier",
+    "integer",
+    "string",
+    "list",
+]
+STARTER_LINES = [
+    "function_definition identifier identifier return_statement identifier",
+    "for_statement identifier identifier call identifier binary_operator integer",
+    "if_statement identifier comparison_operator integer return_statement string",
+    "while_statement identifier call attribute identifier assignment",
+    "try_statement call identifier except_clause identifier return_statement",
+]
+
+
+class TypedWordTokenizer:
+    """Tokenization service double, one token per whitespace separated word, typed by the word"""
+
+    def _detect_language(self, file_path=None, content=None):
+        return "python"
+
+    def is_supported_file(self, file_path):
+        return file_path.suffix == ".py"
+
+    def tokenize(self, text, file_path=None, raise_errors=False):
+        return [
+            {"type": word, "text": word, "start": line, "end": line}
+            for line, words in enumerate(text.split("\n"))
+            for word in words.split()
+        ]
+
+
+def random_tokens(rng: random.Random, length: int):
+    return [{"type": token_type, "text": token_type} for token_type in rng.choices(TYPES, k=length)]
+
+
+class RecordingSimilarityRepository:
+    """Similarity repository double keeping the results stored for each record"""
+
+    def __init__(self):
+        self.results = {}
+
+    def update_status(self, record_id, status, error_message=None):
+        pass
+
+    def update_results(self, record_id, results):
+        self.results[record_id] = results
+
+
+class TestPairPruner(unittest.TestCase):
+    """Tests for the pruning decisions of single pairs"""
+
+    PARAMETERS = {"tokenizer_version": "1", "k": 3, "window": 2}
+
+    def setUp(self):
+        rng = random.Random(0)
+        self.similarity = SimilarityDetectionService()
+        self.tokens = {"a": random_tokens(rng, 200), "b": random_tokens(rng, 180), "c": random_tokens(rng, 20)}
+        self.index = ComparisonIndex(self.PARAMETERS)
+        for document_id, tokens in self.tokens.items():
+            self.index.add(document_id, 1, {hash(document_id)}, self.similarity.similarity_profile(tokens))
+        self.index.add("unprofiled", 1, {1})
+
+    def test_pairs_below_the_cutoff_are_pruned(self):
+        """Only pairs whose bound is below threshold minus margin are pruned, the bound recorded as estimate."""
+        bound = self.similarity.overall_similarity_bound(self.index.profile_of("a"), self.index.profile_of("c"))
+        pruner = PairPruner(self.index, threshold=0.5, margin=0.05)
+
+        self.assertIsNone(pruner.prune("a", "b"))
+        pruned = pruner.prune("a", "c")
+
+        self.assertLess(bound, 0.45)
+        self.assertEqual((pruned.compared_submission_id, pruned.estimated_similarity), ("c", bound))
+
+    def test_unprofiled_submissions_are_never_pruned(self):
+        """A pair with a submission missing from the index, or indexed without profile, is always compared."""
+        pruner = PairPruner(self.index, threshold=1.0, margin=0.0)
+
+        self.assertIsNone(pruner.prune("a", "unknown"))
+        self.assertIsNone(pruner.prune("unprofiled", "c"))
+        self.assertEqual(pruner.pruned, 0)
+
+    def test_stats_report_the_pruning_ratio(self):
+        """The stats count evaluated and pruned pairs."""
+        pruner = PairPruner(self.index, threshold=0.5, margin=0.0)
+        pruner.prune("a", "b")
+        pruner.prune("a", "c")
+
+        self.assertEqual(pruner.to_dict(), {"threshold": 0.5, "margin": 0.0, "evaluated": 2, "pruned": 1, "ratio": 0.5})
+
+
+class TestSimilarityBound(unittest.TestCase):
+    """The bound from the profiles is never below the overall similarity of the tokens"""
+
+    def test_bound_holds_over_random_token_lists(self):
+        """Short, empty and long sequences, whose skipped metrics move their weight to the others."""
+        similarity = SimilarityDetectionService()
+        rng = random.Random(1)
+        for _ in range(200):
+            tokens1 = random_tokens(rng, rng.choice([0, 1, 5, 40, 300]))
+            tokens2 = random_tokens(rng, rng.choice([1, 5, 40, 300, 1200]))
+            # Long flows past the skipping length without any flow element shared
+            if rng.random() < 0.1:
+                tokens1 += [{"type": "if_statement", "text": "if"}] * 1100
+                tokens2 += [{"type": "for_statement", "text": "for"}] * 50
+
+            overall = similarity.compare_similarity(tokens1, tokens2)["overall_similarity"]
+            bound = similarity.overall_similarity_bound(
+                similarity.similarity_profile(tokens1), similarity.similarity_profile(tokens2)
+            )
+
+            self.assertGreaterEqual(bound, overall)
+
+
+class TestPruningSoundness(unittest.TestCase):
+    """Pruning never drops a pair the unpruned run reports"""
+
+    CORPORA = 4
+    SUBMISSIONS = 10
+    THRESHOLD = 0.5
+
+    def setUp(self):
+        self.directory = Path(tempfile.mkdtemp(prefix="test_pruning_"))
+
+    def tearDown(self):
+        shutil.rmtree(self.directory, ignore_errors=True)
+
+    def submission_files(self, rng: random.Random) -> dict:
+        """Random files sharing starter lines, of very different sizes, with unsupported files"""
+        files = {}
+        for file_index in range(rng.randint(1, 3)):
+            lines = [line for line in STARTER_LINES if rng.random() < 0.7] * rng.choice([1, 1, 2, 6])
+            lines += [" ".join(rng.choices(TYPES, k=rng.randint(3, 8))) for _ in range(rng.randint(1, 20))]
+            rng.shuffle(lines)
+            suffix = ".py" if file_index == 0 or rng.random() < 0.7 else ".txt"
+            files[f"src/file{file_index}{suffix}"] = "\n".join(lines)
+        return files
+
+    def run_detection(self, corpus: Path, submissions, pruner=None) -> dict:
+        """Results of every pair of the corpus compared like a run, pruned pairs as their PrunedPair"""
+        service = detection_service(
+            corpus,
+            FingerprintService(TypedWordTokenizer(), InMemoryFingerprintStore(), k=3, window=2),
+            SharedLinesVisualization(),
+        )
+        repository = RecordingSimilarityRepository()
+        by_id = {submission.id: submission for submission in submissions}
+        service._get_thread_session = lambda: None
+        service._pair_submissions = lambda _, first, second, project_uuid: (by_id[first], by_id[second])
+
+        def compare(first, second, *args):
+            record = SimpleNamespace(id=(first, second))
+            return service._process_single_comparison_with_repos(record, by_id[first], by_id[second], None, repository)
+
+        service._process_single_comparison_threaded = compare
+
+        results = {}
+        for first, second in itertools.combinations(sorted(by_id), 2):
+            result = service._compare_or_prune_threaded((first, second), pruner, None, None)
+            results[(first, second)] = result[0] if isinstance(result, tuple) else result
+        return results
+
+    def test_pairs_above_the_threshold_are_never_pruned(self):
+        """Over random corpora, every pair the unpruned run reports is compared, with the same score, when pruned."""
+        reported = pruned = 0
+        for seed in range(self.CORPORA):
+            rng = random.Random(seed)
+            corpus = self.directory / str(seed)
+            storage = SubmissionStorageService(InMemorySubmissionStore(), hash_algorithm="sha256")
+            fingerprints = FingerprintService(TypedWordTokenizer(), InMemoryFingerprintStore(), k=3, window=2)
+            project_uuid, step_uuid = uuid.uuid4(), uuid.uuid4()
+            submissions = [
+                SimpleNamespace(
+                    id=uuid.uuid4(),
+                    link="s3://bucket/submission.zip",
+                    project_uuid=project_uuid,
+                    group_uuid=uuid.uuid4(),
+                    project_step_uuid=step_uuid,
+                    link_type=None,
+                )
+                for _ in range(self.SUBMISSIONS)
+            ]
+            write_submissions(corpus, {submission.id: self.submission_files(rng) for submission in submissions})
+            for submission in submissions:
+                storage.ingest_directory(submission, corpus / str(submission.id))
+            index, _ = ComparisonIndexService(storage, fingerprints).sync(project_uuid, step_uuid, submissions)
+
+            unpruned = self.run_detection(corpus, submissions)
+            with_pruning = self.run_detection(corpus, submissions, PairPruner(index, self.THRESHOLD, margin=0.0))
+
+            for pair, results in unpruned.items():
+                if results["overall_similarity"] >= self.THRESHOLD:
+                    reported += 1
+                    self.assertNotIsInstance(with_pruning[pair], PrunedPair, f"seed {seed}: reported pair pruned")
+                    self.assertEqual(with_pruning[pair]["overall_similarity"], results["overall_similarity"])
+            pruned += sum(isinstance(result, PrunedPair) for result in with_pruning.values())
+
+        self.assertGreater(reported, 0)
+        self.assertGreater(pruned, 0)
+
+
+if __name__ == "__main__":
+    unittest.main()
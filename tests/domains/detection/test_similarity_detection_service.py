@@ -145,10 +145,11 @@ class TestSimilarityDetectionService(unittest.TestCase):
         """Test similarity comparison with empty inputs."""
         result = self.service.compare_similarity([], [])
 
-        # Empty inputs result in empty signatures which are considered identical
-        self.assertEqual(result['jaccard_similarity'], 1.0)
+        # Two empty inputs have nothing in common, they are not identical
+        self.assertEqual(result['jaccard_similarity'], 0.0)
         self.assertEqual(result['type_similarity'], 0)
-        self.assertEqual(result['common_elements'], 1)  # Both have empty signature
+        self.assertEqual(result['overall_similarity'], 0.0)
+        self.assertEqual(result['common_elements'], 0)
 
     def test_compare_similarity_one_empty(self):
         """Test similarity comparison with one empty input."""
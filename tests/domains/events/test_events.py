@@ -0,0 +1,246 @@
+"""
+Tests for lifecycle events, their schemas and their replay from the outbox.
+
+Outbox tests run against TEST_DATABASE_URL when set (e.g. a throwaway PostgreSQL database), in-memory SQLite otherwise.
+"""
+
+import unittest
+from datetime import timedelta
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+import jsonschema
+from sqlmodel import Session, SQLModel, select
+
+from app.domains.events.dto.event_dto import EventDto
+from app.domains.events.event_publisher import InMemoryEventPublisher, event_key
+from app.domains.events.events import (
+    DETECTION_COMPLETED,
+    DETECTION_FAILED,
+    EVENT_SCHEMAS,
+    SUBMISSION_CREATED,
+    SUBMISSION_DELETED,
+    build_event,
+    detection_finished,
+    submission_created,
+    submission_deleted,
+)
+from app.domains.events.events_models import OutboxEvent
+from app.domains.events.outbox_relay import OutboxRelay
+from app.domains.events.outbox_repository import EventOutboxRepository
+from app.domains.runs.runs_models import DetectionRunStatus
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.shared.timestamps import utc_now
+from tests.helpers import create_test_engine
+
+
+def submission(**fields):
+    return SimpleNamespace(
+        **{
+            "id": uuid4(),
+            "project_uuid": uuid4(),
+            "group_uuid": uuid4(),
+            "project_step_uuid": uuid4(),
+            "submitted_by_uuid": uuid4(),
+            "link": "https://github.com/user/secret-repository.git",
+            "link_type": "github",
+            "description": "My solution",
+            "upload_date_time": utc_now(),
+            "is_late": False,
+            "minutes_late": 0,
+            "file_count": 3,
+            "file_size_bytes": 2048,
+            **fields,
+        }
+    )
+
+
+def run(status: str, **fields):
+    return SimpleNamespace(
+        **{
+            "id": uuid4(),
+            "project_uuid": uuid4(),
+            "project_step_uuid": uuid4(),
+            "trigger": "manual",
+            "trigger_submission_id": None,
+            "status": status,
+            "total_pairs": 3,
+            "completed_pairs": 3 if status != "failed" else 0,
+            "failed_pairs": 0,
+            "file_errors": [{"path": "a.py", "stage": "tokenization", "message": "boom"}],
+            "started_at": utc_now() - timedelta(seconds=5),
+            "finished_at": utc_now(),
+            "error_message": "Worker crashed" if status == "failed" else None,
+            **fields,
+        }
+    )
+
+
+class TestEventSchemas(unittest.TestCase):
+    """Tests for the payloads of each event type"""
+
+    def assert_valid(self, payload: dict, event_type: str):
+        schema, version = EVENT_SCHEMAS[event_type]
+        jsonschema.validate(payload, EventDto.model_json_schema())
+        jsonschema.validate(payload["data"], schema.model_json_schema())
+        self.assertEqual(payload["type"], event_type)
+        self.assertEqual(payload["version"], version)
+
+    def test_submission_events_match_their_schemas(self):
+        """Submission events validate against their schemas and never carry the link or the description."""
+        for build, event_type in [(submission_created, SUBMISSION_CREATED), (submission_deleted, SUBMISSION_DELETED)]:
+            resource = submission()
+            event = build(resource)
+
+            self.assert_valid(event.payload, event_type)
+            self.assertEqual(event.payload["data"]["submission_id"], str(resource.id))
+            self.assertEqual(event.event_id, EventDto(**event.payload).id)
+            for field in ["link", "description", "files", "source"]:
+                self.assertNotIn(field, event.payload["data"])
+
+    def test_detection_events_follow_the_run_status(self):
+        """Failed runs give detection.failed, completed and incomplete runs detection.completed."""
+        for status, event_type in [
+            ("completed", DETECTION_COMPLETED),
+            ("incomplete", DETECTION_COMPLETED),
+            ("failed", DETECTION_FAILED),
+        ]:
+            event = detection_finished(run(status))
+
+            self.assert_valid(event.payload, event_type)
+            self.assertEqual(event.payload["data"]["status"], status)
+            self.assertEqual(event.payload["data"]["file_error_count"], 1)
+
+    def test_timestamps_are_utc(self):
+        """Timestamps are serialized in UTC."""
+        payload = submission_created(submission()).payload
+        self.assertTrue(payload["occurred_at"].endswith("Z"))
+        self.assertTrue(payload["data"]["upload_date_time"].endswith("Z"))
+
+    def test_data_must_match_the_event_type(self):
+        """An event cannot be built with the data of another type."""
+        data = EVENT_SCHEMAS[SUBMISSION_DELETED][0](
+            submission_id=uuid4(), project_uuid=uuid4(), group_uuid=uuid4(), project_step_uuid=uuid4()
+        )
+        with self.assertRaises(TypeError):
+            build_event(DETECTION_COMPLETED, data)
+
+    def test_events_are_keyed_by_resource(self):
+        """The partition key is the ID of the submission or the run."""
+        resource = run("completed")
+        self.assertEqual(event_key(detection_finished(resource).payload), str(resource.id))
+
+
+class TestOutboxRelay(unittest.TestCase):
+    """Tests for events written with their change and replayed after broker outages"""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+        self.publisher = InMemoryEventPublisher()
+        self.relay = OutboxRelay(self.publisher, batch_size=2, session_factory=lambda: Session(self.engine))
+
+        settings = patch(
+            "app.config.config.get_settings",
+            return_value=SimpleNamespace(events_enabled=True, pamp_callback_enabled=False),
+        )
+        settings.start()
+        self.addCleanup(settings.stop)
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def create_submission(self):
+        return SubmissionRepository(self.session).create(
+            CreateSubmissionDto(
+                link="https://github.com/user/repository.git",
+                project_uuid=uuid4(),
+                group_uuid=uuid4(),
+                project_step_uuid=uuid4(),
+            )
+        )
+
+    def record_lifecycle(self) -> list:
+        """Create and delete a submission and finish a run, returns the expected event types in order"""
+        created = self.create_submission()
+        SubmissionRepository(self.session).delete(created.id)
+        runs = DetectionRunRepository(self.session)
+        detection = runs.create_run({"project_uuid": uuid4(), "project_step_uuid": uuid4()}, [])
+        runs.finish_run(detection.id, DetectionRunStatus.COMPLETED)
+        return [SUBMISSION_CREATED, SUBMISSION_DELETED, DETECTION_COMPLETED]
+
+    def outbox(self) -> list:
+        with Session(self.engine) as session:
+            return list(session.exec(select(OutboxEvent).order_by(OutboxEvent.sequence)).all())
+
+    def test_events_are_written_with_their_change(self):
+        """Each committed change leaves one pending event in the outbox."""
+        expected = self.record_lifecycle()
+
+        self.assertEqual([event.event_type for event in self.outbox()], expected)
+        self.assertEqual(EventOutboxRepository(self.session).count_pending(), 3)
+
+    def test_events_are_not_written_when_disabled(self):
+        """Nothing is written to the outbox while events are disabled."""
+        with patch("app.config.config.get_settings", return_value=SimpleNamespace(events_enabled=False)):
+            self.create_submission()
+
+        self.assertEqual(self.outbox(), [])
+
+    def test_events_are_replayed_in_order_after_an_outage(self):
+        """Events refused during an outage stay pending and are published in order once the broker is back."""
+        expected = self.record_lifecycle()
+
+        self.publisher.available = False
+        self.assertEqual(self.relay.drain_once(), 0)
+        self.assertEqual(self.relay.drain_once(), 0)
+        first, *others = self.outbox()
+        self.assertEqual(first.attempts, 2)
+        self.assertEqual(first.last_error, "Broker unavailable")
+        self.assertEqual([event.attempts for event in others], [0, 0])
+
+        self.publisher.available = True
+        self.assertEqual(self.relay.drain_once(), 3)
+
+        self.assertEqual([event["type"] for event in self.publisher.events], expected)
+        self.assertTrue(all(event.published_at is not None and event.last_error is None for event in self.outbox()))
+        self.assertEqual(self.relay.drain_once(), 0)
+        self.assertEqual(len(self.publisher.events), 3)
+
+    def test_outage_midway_keeps_the_remaining_events(self):
+        """Events published before a failure stay published, the ones after it are published on the next drain."""
+        self.record_lifecycle()
+        publish = self.publisher.publish
+        calls = []
+
+        def fail_second(event):
+            calls.append(event)
+            if len(calls) == 2:
+                raise RuntimeError("Connection reset")
+            publish(event)
+
+        with patch.object(self.publisher, "publish", side_effect=fail_second):
+            self.assertEqual(self.relay.drain_once(), 1)
+        self.assertEqual(self.relay.drain_once(), 2)
+
+        event_ids = [event["id"] for event in self.publisher.events]
+        self.assertEqual(event_ids, [str(event.event_id) for event in self.outbox()])
+
+    def test_published_events_are_purged_after_the_retention(self):
+        """Only events published longer than the retention ago are deleted."""
+        self.record_lifecycle()
+        self.relay.drain_once()
+        self.create_submission()
+
+        self.relay.retention_seconds = 3600
+        self.assertEqual(self.relay.purge_published(), 0)
+        self.relay.retention_seconds = -1
+        self.assertEqual(self.relay.purge_published(), 3)
+        self.assertEqual([event.event_type for event in self.outbox()], [SUBMISSION_CREATED])
@@ -0,0 +1 @@
+# Fingerprints tests module
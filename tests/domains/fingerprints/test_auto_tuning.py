@@ -0,0 +1,226 @@
+"""
+Tests for the automatic tuning of the fingerprinting parameters of detection runs
+"""
+
+import random
+import unittest
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from app.config.config import Settings
+from app.domains.fingerprints.auto_tuning import TuningFile, TuningSubmission, tune_fingerprinting, tuning_rows
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+from tests.helpers import RegexTokenizer
+
+NAMES = ["total", "count", "items", "value", "result", "index", "data", "name", "size", "line", "row", "key"]
+OPERATORS = ["+", "-", "*", "/", "%", "<", ">", "==", "and", "or"]
+
+
+def expression(rng: random.Random, depth: int = 0) -> str:
+    """Random expression, so that independent files share short token sequences only"""
+    choice = rng.randrange(6 if depth < 2 else 2)
+    if choice == 0:
+        return rng.choice(NAMES)
+    if choice == 1:
+        return str(rng.randint(0, 99))
+    if choice == 2:
+        return f"{rng.choice(NAMES)}({expression(rng, depth + 1)})"
+    if choice == 3:
+        return f"{rng.choice(NAMES)}[{expression(rng, depth + 1)}]"
+    if choice == 4:
+        return f"({expression(rng, depth + 1)})"
+    return f"{expression(rng, depth + 1)} {rng.choice(OPERATORS)} {expression(rng, depth + 1)}"
+
+
+def python_statement(rng: random.Random) -> str:
+    return rng.choice(
+        [
+            lambda: f"{rng.choice(NAMES)} = {expression(rng)}",
+            lambda: f"if {expression(rng)}: {rng.choice(NAMES)} = {expression(rng)}",
+            lambda: f"for {rng.choice(NAMES)} in {expression(rng)}: print({expression(rng)})",
+            lambda: f"return {expression(rng)}",
+        ]
+    )()
+
+
+def java_statement(rng: random.Random) -> str:
+    return rng.choice(
+        [
+            lambda: f"int {rng.choice(NAMES)} = {expression(rng)};",
+            lambda: f"if ({expression(rng)}) {{ {rng.choice(NAMES)} = {expression(rng)}; }}",
+            lambda: f"while ({expression(rng)}) {{ {rng.choice(NAMES)}.add({expression(rng)}); }}",
+            lambda: f"return {expression(rng)};",
+        ]
+    )()
+
+
+def python_script(rng: random.Random) -> str:
+    """Short script, a function of a few statements"""
+    lines = [f"def {rng.choice(NAMES)}({rng.choice(NAMES)}):"]
+    lines += ["    " + python_statement(rng) for _ in range(rng.randint(4, 8))]
+    return "\n".join(lines)
+
+
+def java_class(rng: random.Random) -> str:
+    """Large class with the usual imports, fields and accessors around its methods"""
+    lines = ["package com.example.project;", "import java.util.List;", "import java.util.ArrayList;"]
+    lines += ["import java.util.Map;", "import java.io.IOException;", f"public class {rng.choice(NAMES).title()} {{"]
+    fields = rng.sample(NAMES, 10)
+    for field in fields:
+        lines.append(f"    private int {field};")
+    for field in fields:
+        lines.append(f"    public int get{field.title()}() {{ return this.{field}; }}")
+        lines.append(f"    public void set{field.title()}(int {field}) {{ this.{field} = {field}; }}")
+    for _ in range(6):
+        lines.append(f"    public int {rng.choice(NAMES)}(List<Integer> {rng.choice(NAMES)}) {{")
+        lines += ["        " + java_statement(rng) for _ in range(rng.randint(20, 30))]
+        lines.append("    }")
+    lines.append("}")
+    return "\n".join(lines)
+
+
+def corpus(language: str, write, files: int, groups: int = 8, seed: int = 7):
+    """Submissions of independent groups, each with files written by write, tokenized like the real service"""
+    tokenizer, rng = RegexTokenizer(), random.Random(seed)
+    return [
+        TuningSubmission(
+            group,
+            f"group-{group}",
+            [TuningFile(language, tokenizer.tokenize(write(rng))) for _ in range(files)],
+        )
+        for group in range(groups)
+    ]
+
+
+class TestAutoTuning(unittest.TestCase):
+    """Tests for the parameters chosen for a corpus"""
+
+    def test_short_scripts_and_large_projects_get_their_own_parameters(self):
+        """Short Python scripts get a smaller k and window than large Java projects, with why."""
+        scripts = tune_fingerprinting(corpus("python", python_script, files=3))
+        projects = tune_fingerprinting(corpus("java", java_class, files=4))
+
+        self.assertEqual((scripts.language, projects.language), ("python", "java"))
+        self.assertLess(scripts.median_tokens, 200)
+        self.assertGreater(projects.median_tokens, 1500)
+        self.assertLess(scripts.k, projects.k)
+        self.assertLess(scripts.window, projects.window)
+        for result, rows in ((scripts, tuning_rows("python")), (projects, tuning_rows("java"))):
+            self.assertIn((result.k, result.window), [(k, window) for _, k, window in rows])
+            self.assertTrue(result.target_reached)
+            self.assertLess(result.background_similarity, result.target)
+            self.assertEqual(result.sampled_pairs, 28)
+            self.assertIn(result.language, result.rationale)
+
+    def test_larger_rows_until_the_background_is_under_the_target(self):
+        """Candidates grow from the row of the median until one is under the target, else the largest is kept."""
+        projects = corpus("java", java_class, files=4)
+
+        strict = tune_fingerprinting(projects, target=0.0)
+        tuned = tune_fingerprinting(projects, target=0.5)
+
+        self.assertFalse(strict.target_reached)
+        self.assertEqual((strict.k, strict.window), tuning_rows("java")[-1][1:])
+        self.assertEqual([candidate["k"] for candidate in strict.candidates], [9, 12])
+        self.assertIn("no row of the table", strict.rationale)
+        backgrounds = [candidate["background_similarity"] for candidate in strict.candidates]
+        self.assertEqual(backgrounds, sorted(backgrounds, reverse=True))
+        self.assertEqual(tuned.candidates, strict.candidates[:1])
+
+    def test_submissions_of_a_group_are_not_paired(self):
+        """Resubmissions of a group are not independent work, without other groups there is nothing to measure."""
+        scripts = corpus("python", python_script, files=3, groups=3)
+        for submission in scripts:
+            submission.group = "same-group"
+
+        result = tune_fingerprinting(scripts)
+
+        self.assertEqual(result.sampled_pairs, 0)
+        self.assertIsNone(result.background_similarity)
+        self.assertEqual((result.k, result.window), tuning_rows("python")[0][1:])
+        self.assertEqual(result.to_dict()["languages"], {"python": 1.0})
+
+
+class RunRepository:
+    """Run repository double keeping the parameters of one run"""
+
+    def __init__(self, parameters):
+        self.run = SimpleNamespace(parameters=parameters)
+        self.updates = []
+
+    def get_run(self, run_id):
+        return self.run
+
+    def update_parameters(self, run_id, parameters):
+        self.updates.append(parameters)
+        self.run.parameters = parameters
+        return 1
+
+
+class TestAutoTunedRuns(unittest.TestCase):
+    """Tests for the parameters of runs, tuned before they compare"""
+
+    def setUp(self):
+        self.service = DetectionIntegrationService.__new__(DetectionIntegrationService)
+        self.service.fingerprint_service = FingerprintService(RegexTokenizer())
+        self.service._get_thread_session = lambda: None
+        self.service._tuning_corpus = lambda submission_ids: corpus("java", java_class, files=4)
+        self.settings = Settings(detection_auto_tune_target=0.1)
+        patcher = patch("app.config.config.get_settings", return_value=self.settings)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def settle(self, **request):
+        """Parameters recorded for a run, then the service comparing its pairs"""
+        repository = RunRepository(self.service._run_parameters(**request))
+        with patch(
+            "app.domains.submissions.detection_integration_service.DetectionRunRepository", return_value=repository
+        ):
+            return repository, self.service._with_run_parameters(uuid4(), [uuid4()])
+
+    def test_tuned_parameters_are_recorded_and_used(self):
+        """The chosen parameters replace the configured ones of the run, which compares every pair with them."""
+        repository, service = self.settle(fingerprint_k=None, fingerprint_window=None, auto_tune=True)
+
+        tuning = repository.run.parameters["auto_tuning"]
+        self.assertEqual(tuning["status"], "applied")
+        self.assertEqual(repository.run.parameters["fingerprint"]["k"], tuning["k"])
+        self.assertEqual((service.fingerprint_service.k, service.fingerprint_service.window), (12, 8))
+        self.assertIsNotNone(tuning["rationale"])
+        self.assertFalse(service.uses_comparison_index)
+        self.assertTrue(self.service.uses_comparison_index)
+        self.assertEqual(self.service.fingerprint_service.k, 5)
+
+    def test_request_parameters_win_over_tuned_ones(self):
+        """An explicit k is kept with the tuned window, both explicit skip the tuning."""
+        repository, service = self.settle(fingerprint_k=6, fingerprint_window=None, auto_tune=True)
+
+        self.assertEqual(repository.run.parameters["auto_tuning"]["overrides"], {"k": 6})
+        self.assertEqual((service.fingerprint_service.k, service.fingerprint_service.window), (6, 8))
+
+        repository, service = self.settle(fingerprint_k=6, fingerprint_window=3, auto_tune=True)
+        self.assertEqual(repository.run.parameters["auto_tuning"]["status"], "skipped")
+        self.assertEqual(repository.updates, [])
+        self.assertEqual((service.fingerprint_service.k, service.fingerprint_service.window), (6, 3))
+
+    def test_runs_without_auto_tuning_keep_the_configured_parameters(self):
+        """Without auto-tuning nothing is analyzed, and resumed runs keep the parameters they recorded."""
+        self.service._tuning_corpus = None
+        repository, service = self.settle(fingerprint_k=None, fingerprint_window=None, auto_tune=None)
+
+        self.assertNotIn("auto_tuning", repository.run.parameters)
+        self.assertIs(service, self.service)
+
+        recorded = RunRepository({"fingerprint": {"k": 9, "window": 6}, "auto_tuning": {"status": "applied"}})
+        with patch(
+            "app.domains.submissions.detection_integration_service.DetectionRunRepository", return_value=recorded
+        ):
+            resumed = self.service._with_run_parameters(uuid4(), [uuid4()])
+        self.assertEqual((resumed.fingerprint_service.k, resumed.fingerprint_service.window), (9, 6))
+        self.assertEqual(recorded.updates, [])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,257 @@
+"""
+Tests for the persisted comparison index of project steps
+"""
+
+import random
+import shutil
+import tempfile
+import unittest
+import uuid
+from pathlib import Path
+from types import SimpleNamespace
+
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.fingerprints.comparison_index import (
+    INDEX_FORMAT_VERSION,
+    ComparisonIndex,
+    ComparisonIndexCorruptedException,
+)
+from app.domains.fingerprints.comparison_index_service import ComparisonIndexService, index_key
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.fingerprints.fingerprinting import fingerprint_similarity
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from tests.helpers import CountingTokenizer
+
+CORPUS_SIZE = 100
+
+
+class TestComparisonIndex(unittest.TestCase):
+    """Tests for the inverted index and its serialization"""
+
+    PARAMETERS = {"tokenizer_version": "1", "k": 3, "window": 2}
+
+    def setUp(self):
+        self.index = ComparisonIndex(self.PARAMETERS)
+        self.index.add("a", 1, {1, 2, 3, 4}, {"tokens": 2, "parts": 1, "types": {"identifier": 2}})
+        self.index.add("b", 1, {3, 4, 5})
+        self.index.add("c", 1, {9})
+
+    def test_similarities_match_fingerprint_similarity(self):
+        """Scores from the postings equal the Jaccard similarity of the fingerprint sets."""
+        self.assertEqual(self.index.shared_counts("a"), {"b": 2})
+        self.assertEqual(self.index.similarities("a"), {"b": fingerprint_similarity({1, 2, 3, 4}, {3, 4, 5})})
+        self.assertEqual(self.index.similarities("c"), {})
+
+    def test_resubmission_replaces_previous_postings(self):
+        """Indexing a new version drops the postings of the previous one."""
+        self.index.add("b", 2, {9})
+
+        self.assertEqual(self.index.version_of("b"), 2)
+        self.assertEqual(self.index.shared_counts("a"), {})
+        self.assertEqual(self.index.shared_counts("c"), {"b": 1})
+
+    def test_round_trip(self):
+        """A deserialized index has the same documents, profiles and postings."""
+        loaded = ComparisonIndex.deserialize(self.index.serialize(), self.PARAMETERS)
+
+        self.assertEqual(loaded.documents, self.index.documents)
+        self.assertEqual(loaded.profile_of("a"), self.index.profile_of("a"))
+        self.assertIsNone(loaded.profile_of("b"))
+        self.assertEqual(dict(loaded.postings), dict(self.index.postings))
+        self.assertEqual(loaded.postings_processed, 0)
+
+    def test_corruption_and_mismatches_are_detected(self):
+        """Flipped bytes, truncation, other formats or parameters are rejected, never loaded."""
+        data = self.index.serialize()
+        flipped = bytearray(data)
+        flipped[-5] ^= 0xFF
+        other_format = data.replace(f'"format": {INDEX_FORMAT_VERSION}'.encode(), b'"format": 99')
+
+        for corrupted in (bytes(flipped), data[:-3], b"not an index", other_format):
+            with self.assertRaises(ComparisonIndexCorruptedException):
+                ComparisonIndex.deserialize(corrupted, self.PARAMETERS)
+        with self.assertRaises(ComparisonIndexCorruptedException):
+            ComparisonIndex.deserialize(data, {**self.PARAMETERS, "k": 4})
+
+
+class TestBloomCandidates(unittest.TestCase):
+    """Tests for the Bloom filter pre-filter of candidate submissions"""
+
+    PARAMETERS = {"tokenizer_version": "1", "k": 3, "window": 2}
+
+    def build(self, rng: random.Random, false_positive_rate: float, documents: int = 200) -> ComparisonIndex:
+        """Random corpus where some submissions copy fingerprints of the first one"""
+        index = ComparisonIndex(self.PARAMETERS, false_positive_rate)
+        target = {rng.getrandbits(64) for _ in range(300)}
+        index.add("target", 1, target)
+        for document in range(documents):
+            hashes = {rng.getrandbits(64) for _ in range(rng.randint(1, 400))}
+            if document % 25 == 0:
+                hashes |= set(rng.sample(sorted(target), rng.randint(1, 20)))
+            index.add(f"submission-{document}", 1, hashes)
+        return index
+
+    def test_false_positives_never_change_shared_counts(self):
+        """Even with filters passing half of the unrelated submissions, counts equal the exact probe of the index."""
+        for seed in range(3):
+            index = self.build(random.Random(seed), false_positive_rate=0.5)
+            sharing = set(index.probe_shared_counts("target"))
+
+            candidates = index.candidates("target")
+
+            self.assertTrue(sharing <= candidates)
+            self.assertGreater(len(candidates - sharing), 0)
+            self.assertEqual(index.shared_counts("target"), index.probe_shared_counts("target"))
+            self.assertEqual(
+                index.similarities("target"),
+                {
+                    other: fingerprint_similarity(index.documents["target"].hashes, index.documents[other].hashes)
+                    for other in sharing
+                },
+            )
+
+    def test_false_positive_rate_is_respected(self):
+        """About the configured share of unrelated submissions pass their filter."""
+        index = self.build(random.Random(7), false_positive_rate=0.01, documents=1000)
+
+        false_positives = index.candidates("target") - set(index.probe_shared_counts("target"))
+
+        self.assertLess(len(false_positives), 20)
+
+    def test_filters_follow_updates_and_round_trips(self):
+        """Replaced, removed and reloaded submissions keep candidates a superset of the sharing submissions."""
+        index = self.build(random.Random(3), false_positive_rate=0.05)
+        target = sorted(index.documents["target"].hashes)
+        index.add("submission-0", 2, {1, 2, 3})
+        index.remove("submission-25")
+        index.add("late", 1, target[:5])
+
+        loaded = ComparisonIndex.deserialize(index.serialize(), self.PARAMETERS)
+        loaded.add("later", 1, target[5:10])
+
+        for current in (index, loaded):
+            self.assertNotIn("submission-0", current.shared_counts("target"))
+            self.assertNotIn("submission-25", current.candidates("target"))
+        self.assertEqual(loaded.shared_counts("target"), loaded.probe_shared_counts("target"))
+        self.assertEqual(loaded.shared_counts("target")["later"], 5)
+
+    def test_loading_decodes_no_hashes(self):
+        """Finding the submissions sharing fingerprints only decodes the hashes of the candidates."""
+        index = self.build(random.Random(5), false_positive_rate=0.01)
+        loaded = ComparisonIndex.deserialize(index.serialize(), self.PARAMETERS)
+
+        loaded.shared_counts("target")
+
+        decoded = [document_id for document_id, document in loaded.documents.items() if document._hashes is not None]
+        self.assertEqual(set(decoded), loaded.candidates("target") | {"target"})
+
+
+class TestComparisonIndexService(unittest.TestCase):
+    """Tests for the incremental update of the index across runs"""
+
+    def setUp(self):
+        self.store = InMemorySubmissionStore()
+        self.storage = SubmissionStorageService(self.store, hash_algorithm="sha256")
+        self.tokenizer = CountingTokenizer()
+        self.fingerprints = FingerprintService(self.tokenizer, InMemoryFingerprintStore(), k=3, window=2)
+        self.service = ComparisonIndexService(self.storage, self.fingerprints)
+        self.project_uuid = uuid.uuid4()
+        self.step_uuid = uuid.uuid4()
+        self.directory = Path(tempfile.mkdtemp(prefix="test_comparison_index_"))
+        self.submissions = [self.ingest(index) for index in range(CORPUS_SIZE)]
+
+    def tearDown(self):
+        shutil.rmtree(self.directory, ignore_errors=True)
+
+    def ingest(self, index: int, submission=None):
+        submission = submission or SimpleNamespace(id=uuid.uuid4(), project_uuid=self.project_uuid)
+        (self.directory / "main.py").write_text(f"def solve value{index} return value{index} * {index} + offset")
+        self.storage.ingest_directory(submission, self.directory)
+        return submission
+
+    def sync(self):
+        return self.service.sync(self.project_uuid, self.step_uuid, self.submissions)
+
+    def test_first_run_builds_the_index(self):
+        """Without a persisted index every submission is indexed and the index is saved."""
+        index, stats = self.sync()
+
+        self.assertTrue(stats["rebuilt"])
+        self.assertEqual(stats["rebuild_reason"], "missing")
+        self.assertEqual((stats["documents"], stats["added"]), (CORPUS_SIZE, CORPUS_SIZE))
+        self.assertTrue(self.store.exists(index_key(self.project_uuid, self.step_uuid, self.fingerprints.parameters)))
+
+    def test_profiles_are_those_of_the_compared_tokens(self):
+        """Each submission is indexed with the similarity profile of the tokens a detailed comparison reads."""
+        index, _ = self.sync()
+
+        tokens = self.tokenizer.tokenize("def solve value3 return value3 * 3 + offset")
+        self.assertEqual(
+            index.profile_of(self.submissions[3].id), SimilarityDetectionService().similarity_profile(tokens)
+        )
+
+    def test_new_submission_only_processes_its_postings(self):
+        """Adding one submission to an indexed corpus of 100 indexes that submission alone."""
+        self.sync()
+        calls_before = self.tokenizer.calls
+        self.submissions.append(self.ingest(CORPUS_SIZE))
+
+        index, stats = self.sync()
+        new_hashes = index.documents[str(self.submissions[-1].id)].hashes
+
+        self.assertFalse(stats["rebuilt"])
+        self.assertEqual((stats["added"], stats["removed"]), (1, 0))
+        self.assertEqual(stats["postings_processed"], len(new_hashes))
+        self.assertEqual(self.tokenizer.calls - calls_before, 1)
+        self.assertEqual(stats["documents"], CORPUS_SIZE + 1)
+
+    def test_unchanged_corpus_processes_nothing(self):
+        """A rerun on the same corpus loads the index without touching a posting."""
+        self.sync()
+
+        _, stats = self.sync()
+
+        self.assertEqual((stats["added"], stats["removed"], stats["postings_processed"]), (0, 0, 0))
+
+    def test_resubmitted_and_removed_submissions_are_updated(self):
+        """A new version is reindexed and a submission gone from the step is removed."""
+        self.sync()
+        self.ingest(500, self.submissions[0])
+        removed = self.submissions.pop()
+
+        index, stats = self.sync()
+
+        self.assertEqual((stats["added"], stats["removed"]), (1, 1))
+        self.assertEqual(index.version_of(self.submissions[0].id), 2)
+        self.assertNotIn(removed.id, index)
+
+    def test_corrupted_index_is_rebuilt(self):
+        """A persisted index failing its checksum is rebuilt rather than used."""
+        self.sync()
+        key = index_key(self.project_uuid, self.step_uuid, self.fingerprints.parameters)
+        data = bytearray(self.store.get(key))
+        data[-10] ^= 0xFF
+        self.store.put(key, bytes(data))
+
+        index, stats = self.sync()
+
+        self.assertTrue(stats["rebuilt"])
+        self.assertEqual(stats["rebuild_reason"], "Index checksum mismatch")
+        self.assertEqual(len(index), CORPUS_SIZE)
+        self.assertEqual(self.sync()[1]["rebuilt"], False)
+
+    def test_parameter_change_rebuilds_the_index(self):
+        """Other fingerprinting parameters never reuse the index built with the previous ones."""
+        self.sync()
+        self.service.fingerprint_service = FingerprintService(self.tokenizer, None, k=4, window=2)
+
+        _, stats = self.sync()
+
+        self.assertTrue(stats["rebuilt"])
+        self.assertEqual(stats["added"], CORPUS_SIZE)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,162 @@
+"""
+Tests for the debug traces of stored files, compared with what detection runs fingerprint and cache
+"""
+
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.detection.directory_comparison import read_source
+from app.domains.fingerprints.fingerprint_debug import hex_hash
+from app.domains.fingerprints.fingerprint_models import NormalizationLevel
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.submissions.submissions_service import SubmissionService
+from app.domains.tokenization.tokenizer_config import load_tokenizer_config
+from app.shared.exceptions import ValidationException
+from tests.helpers import WordTokenizer
+
+KEYWORDS = ("def", "return")
+SOURCE = "\ufeffdef add a b :\r\n    return a + b\r\n\r\ndef sub a b :\r\n    return a - b\r\n"
+
+
+class Submissions:
+    def __init__(self, submission):
+        self.submission = submission
+
+    def get_by_id(self, submission_id):
+        return self.submission if submission_id == self.submission.id else None
+
+
+class Storage:
+    """Submission storage double holding the stored bytes of one version"""
+
+    def __init__(self, files):
+        self.files = files
+
+    def get_latest_version(self, submission):
+        return 1
+
+    def read_file(self, submission, relative_path, version=None):
+        return self.files[relative_path]
+
+
+class FingerprintDebugTestCase(unittest.TestCase):
+    """Base class with a stored submission of one file, fingerprinted through an in-memory store"""
+
+    def setUp(self):
+        self.submission = SimpleNamespace(id=uuid4())
+        self.files = {"src/calculator.py": SOURCE.encode("utf-8"), "logo.png": b"not an image"}
+        self.store = InMemoryFingerprintStore()
+        self.fingerprint_service = self.new_fingerprint_service()
+
+        self.service = SubmissionService.__new__(SubmissionService)
+        self.service.repository = Submissions(self.submission)
+        self.service.storage_service = Storage(self.files)
+        self.service.detection_service = SimpleNamespace(
+            tokenization_service=WordTokenizer(KEYWORDS), fingerprint_service=self.fingerprint_service
+        )
+
+    def new_fingerprint_service(self, tokenizer_config=None) -> FingerprintService:
+        return FingerprintService(
+            WordTokenizer(KEYWORDS), store=self.store, k=3, window=2, tokenizer_config=tokenizer_config
+        )
+
+    def run_fingerprints(self, path: str):
+        """Fingerprints a detection run caches for a stored file, read from its materialized copy"""
+        root = Path(tempfile.mkdtemp())
+        self.addCleanup(shutil.rmtree, root)
+        file_path = root / path
+        file_path.parent.mkdir(parents=True)
+        file_path.write_bytes(self.files[path])
+
+        [(_, fingerprint_set)] = self.fingerprint_service.fingerprint_files([file_path], read_source)
+        key = self.fingerprint_service.build_key(read_source(file_path), file_path)
+        self.assertIsNotNone(self.store.get(key))
+        return key, self.store.get(key)
+
+    def debug(self, path: str = "src/calculator.py", **options):
+        return self.service.debug_submission_file(self.submission.id, path, **options)
+
+
+class TestFingerprintDebug(FingerprintDebugTestCase):
+    """Tests for the content of the debug trace of a file"""
+
+    def test_fingerprints_are_those_detection_cached(self):
+        """Without overrides the key, tokens and fingerprints are those the detection run cached for the file."""
+        key, cached = self.run_fingerprints("src/calculator.py")
+        entries = self.store.count()
+
+        debug = self.debug()
+
+        self.assertEqual(
+            (debug.language, debug.content_hash, debug.normalization, debug.k, debug.window),
+            (key.language, key.content_hash, key.normalization, key.k, key.window),
+        )
+        self.assertEqual(
+            [(fingerprint.hash, fingerprint.token) for fingerprint in debug.fingerprints],
+            [(hex_hash(fingerprint_hash), token) for fingerprint_hash, token in cached.fingerprints],
+        )
+        self.assertEqual([token.text for token in debug.tokens], [token["text"] for token in cached.tokens])
+        self.assertEqual((debug.token_count, debug.kgram_count), (18, 16))
+        self.assertEqual(debug.kgrams[0].parts, ["keyword:def", "identifier", "identifier"])
+        self.assertEqual(
+            [(token.line, token.token_class, token.normalized) for token in debug.tokens[5:7]],
+            [(2, "other", "keyword:return"), (2, "identifier", "identifier")],
+        )
+        # The debug trace leaves the cache as it was
+        self.assertEqual(self.store.count(), entries)
+
+    def test_overridden_parameters_match_a_service_configured_with_them(self):
+        """Overrides give the fingerprints a service configured with them caches, weighted languages included."""
+        path = Path(tempfile.mkdtemp()) / "weights.toml"
+        self.addCleanup(shutil.rmtree, path.parent)
+        path.write_text("[languages.python.token_weights]\nidentifier = 2\n", encoding="utf-8")
+        self.fingerprint_service = self.new_fingerprint_service(load_tokenizer_config(str(path)))
+        self.service.detection_service.fingerprint_service = self.fingerprint_service
+        default_debug = self.debug()
+
+        self.fingerprint_service.k, self.fingerprint_service.window = 4, 3
+        self.fingerprint_service.normalization = NormalizationLevel.TYPES
+        _, cached = self.run_fingerprints("src/calculator.py")
+        self.fingerprint_service.k, self.fingerprint_service.window = 3, 2
+        self.fingerprint_service.normalization = NormalizationLevel.IDENTIFIERS
+
+        debug = self.debug(normalization=NormalizationLevel.TYPES, k=4, window=3)
+
+        self.assertEqual((debug.normalization, debug.k, debug.window), ("types", 4, 3))
+        self.assertEqual(
+            [(fingerprint.hash, fingerprint.token) for fingerprint in debug.fingerprints],
+            [(hex_hash(fingerprint_hash), token) for fingerprint_hash, token in cached.fingerprints],
+        )
+        self.assertNotEqual(debug.fingerprints, default_debug.fingerprints)
+        self.assertEqual([token.weight for token in debug.tokens[:2]], [1, 2])
+        self.assertEqual(debug.kgram_count, 25)
+        # Tokens repeated by their weight start several k-grams, all on the page of the token
+        pages = [
+            self.debug(normalization=NormalizationLevel.TYPES, k=4, window=3, skip=skip, limit=4) for skip in (0, 4)
+        ]
+        self.assertEqual([kgram.token for kgram in pages[0].kgrams], [0, 1, 1, 2, 2, 3, 3])
+        self.assertEqual(pages[1].kgrams[0].token, 4)
+
+    def test_pages_cover_every_kgram_and_fingerprint_once(self):
+        """Consecutive pages of tokens hold each k-gram and fingerprint once, with the totals of the file."""
+        everything = self.debug()
+        pages = [self.debug(skip=skip, limit=5) for skip in range(0, 20, 5)]
+
+        self.assertEqual([page.token_count for page in pages], [18] * 4)
+        self.assertEqual([len(page.tokens) for page in pages], [5, 5, 5, 3])
+        self.assertEqual([kgram for page in pages for kgram in page.kgrams], everything.kgrams)
+        self.assertEqual([fingerprint for page in pages for fingerprint in page.fingerprints], everything.fingerprints)
+
+    def test_unsupported_files_are_rejected(self):
+        """Files a run leaves out for their language cannot be debugged."""
+        with self.assertRaises(ValidationException):
+            self.debug("logo.png")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,124 @@
+"""
+Tests for the compact encoding of stored fingerprint sets
+"""
+
+import importlib.util
+import pickle
+import unittest
+
+from app.domains.fingerprints import fingerprint_encoding
+from app.domains.fingerprints.fingerprint_encoding import (
+    Codec,
+    FingerprintEncodingException,
+    LazyTokens,
+    decode_fingerprint_set,
+    encode_fingerprint_set,
+)
+from app.domains.fingerprints.fingerprint_models import FingerprintSet, NormalizationLevel
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.fingerprints.fingerprinting import compute_fingerprints
+from tests.helpers import SAMPLES_DIRECTORY, RegexTokenizer
+
+ZSTD_AVAILABLE = importlib.util.find_spec("zstandard") is not None
+
+
+def sample_fingerprint_sets():
+    tokenizer = RegexTokenizer()
+    for sample in sorted(SAMPLES_DIRECTORY.iterdir()):
+        tokens = tokenizer.tokenize(sample.read_text(encoding="utf-8", errors="replace"), sample)
+        yield sample, FingerprintSet(tokens, compute_fingerprints(tokens, 5, 4, NormalizationLevel.IDENTIFIERS))
+
+
+class TestFingerprintEncoding(unittest.TestCase):
+    """Tests for encoding and decoding single entries"""
+
+    def setUp(self):
+        self.tokens = [
+            {"type": "identifier", "text": f"name{i % 7}", "start": i // 3, "end": i // 3} for i in range(3000)
+        ]
+        self.fingerprint_set = FingerprintSet(
+            self.tokens, compute_fingerprints(self.tokens, 3, 4, NormalizationLevel.NONE)
+        )
+
+    def test_round_trip_is_lossless(self):
+        """Tokens and fingerprints decode to exactly what was encoded, for every available codec."""
+        codecs = [Codec.ZLIB, Codec.ZSTD] if ZSTD_AVAILABLE else [Codec.ZLIB]
+        for codec in codecs:
+            decoded = decode_fingerprint_set(encode_fingerprint_set(self.fingerprint_set, codec))
+
+            self.assertEqual(decoded.fingerprints, self.fingerprint_set.fingerprints)
+            self.assertEqual(list(decoded.tokens), self.tokens)
+            self.assertEqual(decoded.tokens[2999], self.tokens[2999])
+
+    def test_fingerprints_are_read_without_decompressing_tokens(self):
+        """Fingerprints and the token count come from the header, tokens are decompressed on iteration only."""
+        data = encode_fingerprint_set(self.fingerprint_set, Codec.ZLIB)
+        calls = []
+        original = fingerprint_encoding.iter_token_stream
+        fingerprint_encoding.iter_token_stream = lambda *args: calls.append(args) or original(*args)
+        try:
+            decoded = decode_fingerprint_set(data)
+            self.assertEqual(decoded.hashes, self.fingerprint_set.hashes)
+            self.assertEqual(len(decoded.tokens), len(self.tokens))
+            self.assertEqual(calls, [])
+
+            self.assertEqual(sum(1 for _ in decoded.tokens), len(self.tokens))
+            self.assertEqual(len(calls), 1)
+        finally:
+            fingerprint_encoding.iter_token_stream = original
+
+    def test_legacy_pickled_entries_stay_readable(self):
+        """Entries stored as plain pickles before the encoding are decoded unchanged."""
+        legacy = pickle.dumps(
+            {"tokens": self.tokens, "fingerprints": self.fingerprint_set.fingerprints}, protocol=pickle.HIGHEST_PROTOCOL
+        )
+
+        decoded = decode_fingerprint_set(legacy)
+
+        self.assertEqual((decoded.tokens, decoded.fingerprints), (self.tokens, self.fingerprint_set.fingerprints))
+
+    def test_unknown_versions_and_truncation_are_rejected(self):
+        """Entries of a future version or cut in their header raise instead of decoding garbage."""
+        data = bytearray(encode_fingerprint_set(self.fingerprint_set, Codec.ZLIB))
+        future = bytes(data[:3]) + bytes((99,)) + bytes(data[4:])
+
+        for corrupted in (future, bytes(data[:8])):
+            with self.assertRaises(FingerprintEncodingException):
+                decode_fingerprint_set(corrupted)
+
+    def test_empty_set(self):
+        """An entry without tokens nor fingerprints round trips."""
+        decoded = decode_fingerprint_set(encode_fingerprint_set(FingerprintSet([], [])))
+
+        self.assertEqual((list(decoded.tokens), decoded.fingerprints), ([], []))
+        self.assertIsInstance(decoded.tokens, LazyTokens)
+
+
+class TestCompressedCacheSize(unittest.TestCase):
+    """The encoded cache of a representative corpus is at most half the size of the pickled one"""
+
+    def test_corpus_cache_size_drops_by_half(self):
+        """Caching the language samples takes at most 50% of the space plain pickles took, losslessly."""
+        store = InMemoryFingerprintStore()
+        service = FingerprintService(RegexTokenizer(), store, k=5, window=4)
+        pickled_bytes = 0
+        for sample, fingerprint_set in sample_fingerprint_sets():
+            key = service.build_key(sample.read_text(encoding="utf-8", errors="replace"), sample)
+            store.put(key, fingerprint_set)
+            pickled_bytes += len(
+                pickle.dumps(
+                    {"tokens": fingerprint_set.tokens, "fingerprints": fingerprint_set.fingerprints},
+                    protocol=pickle.HIGHEST_PROTOCOL,
+                )
+            )
+
+            cached = store.get(key)
+            self.assertEqual(cached.fingerprints, fingerprint_set.fingerprints)
+            self.assertEqual(list(cached.tokens), fingerprint_set.tokens)
+
+        self.assertLessEqual(store.size_bytes(), pickled_bytes * 0.5)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,518 @@
+"""
+Tests for FingerprintService, fingerprinting and the fingerprint stores
+"""
+
+import importlib.util
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+from unittest.mock import MagicMock
+
+from app.domains.detection.file_errors import FileStage
+from app.domains.fingerprints.fingerprint_models import FingerprintKey, KgramHashScheme, NormalizationLevel
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.fingerprints.fingerprinting import compute_fingerprints, fingerprint_similarity, kgram_hashes
+from app.shared.exceptions import DatabaseException
+from app.shared.profiling import StageProfiler
+from tests.helpers import CountingTokenizer
+
+LMDB_AVAILABLE = importlib.util.find_spec("lmdb") is not None
+
+
+class SlowTokenizer(CountingTokenizer):
+    """Tokenizer double spending a fixed time per file, releasing the GIL like tree-sitter does"""
+
+    def __init__(self, delay_seconds):
+        super().__init__()
+        self.delay_seconds = delay_seconds
+        self._lock = threading.Lock()
+
+    def tokenize(self, text, file_path=None):
+        time.sleep(self.delay_seconds)
+        with self._lock:
+            return super().tokenize(text, file_path)
+
+
+class FailingTokenizer(CountingTokenizer):
+    """Tokenizer double failing on files whose name contains "corrupt", like the tokenization service"""
+
+    def __init__(self, error=None):
+        super().__init__()
+        self.error = error or RecursionError("maximum recursion depth exceeded")
+
+    def tokenize(self, text, file_path=None, raise_errors=False):
+        if file_path is not None and "corrupt" in file_path.name:
+            if raise_errors:
+                raise self.error
+            return []
+        return super().tokenize(text, file_path)
+
+
+def make_tokens(words):
+    return [{"type": "keyword", "text": word, "start": 0, "end": 0} for word in words]
+
+
+SUBMISSION_FILES = {
+    Path("calculator.py"): "def add a b return a + b def sub a b return a - b",
+    Path("game.py"): "class Game def play self score = score + 1 return score",
+    Path("ui.js"): "function render root root . append child return root",
+}
+
+
+class TestFingerprinting(unittest.TestCase):
+    """Tests for k-gram winnowing"""
+
+    def test_fingerprints_are_deterministic(self):
+        """The same tokens always give the same fingerprints."""
+        tokens = make_tokens("a b c d e f g h i j k l".split())
+
+        first = compute_fingerprints(tokens, 3, 4, NormalizationLevel.NONE)
+        second = compute_fingerprints(tokens, 3, 4, NormalizationLevel.NONE)
+
+        self.assertEqual(first, second)
+        self.assertTrue(first)
+
+    def test_shared_run_shares_a_fingerprint(self):
+        """A shared run of at least window + k - 1 tokens yields a shared fingerprint."""
+        k, window = 3, 4
+        shared = "x1 x2 x3 x4 x5 x6".split()
+        tokens1 = make_tokens("a b c".split() + shared + "d e".split())
+        tokens2 = make_tokens("p q".split() + shared + "r s t u".split())
+
+        hashes1 = {h for h, _ in compute_fingerprints(tokens1, k, window, NormalizationLevel.NONE)}
+        hashes2 = {h for h, _ in compute_fingerprints(tokens2, k, window, NormalizationLevel.NONE)}
+
+        self.assertTrue(hashes1 & hashes2)
+
+    def test_identifier_normalization_ignores_renaming(self):
+        """Renamed identifiers only change fingerprints without normalization."""
+        tokens1 = [{"type": "identifier", "text": name, "start": 0, "end": 0} for name in "a b c d e f".split()]
+        tokens2 = [{"type": "identifier", "text": name, "start": 0, "end": 0} for name in "u v w x y z".split()]
+
+        normalized1 = compute_fingerprints(tokens1, 2, 2, NormalizationLevel.IDENTIFIERS)
+        normalized2 = compute_fingerprints(tokens2, 2, 2, NormalizationLevel.IDENTIFIERS)
+        raw1 = compute_fingerprints(tokens1, 2, 2, NormalizationLevel.NONE)
+        raw2 = compute_fingerprints(tokens2, 2, 2, NormalizationLevel.NONE)
+
+        self.assertEqual(normalized1, normalized2)
+        self.assertNotEqual(raw1, raw2)
+
+    def test_short_and_empty_inputs(self):
+        """Inputs shorter than k give a single fingerprint, empty inputs none."""
+        self.assertEqual(compute_fingerprints([], 5, 4, NormalizationLevel.NONE), [])
+        self.assertEqual(len(compute_fingerprints(make_tokens(["a", "b"]), 5, 4, NormalizationLevel.NONE)), 1)
+
+    def test_rolling_hashes_match_hashing_each_kgram(self):
+        """Sliding the rolling hash gives every k-gram the hash it has on its own."""
+        parts = [f"identifier:{name}" for name in "a b c a b d e a b c f".split()]
+
+        hashes = kgram_hashes(parts, 3, KgramHashScheme.ROLLING_XXH3)
+
+        self.assertEqual(hashes, [kgram_hashes(parts[i : i + 3], 3)[0] for i in range(len(parts) - 2)])
+        self.assertEqual(hashes[0], hashes[7])
+
+    def test_every_scheme_shares_fingerprints_of_shared_runs(self):
+        """The winnowing guarantee holds whatever the k-gram hash scheme, and schemes hash differently."""
+        shared = "x1 x2 x3 x4 x5 x6".split()
+        tokens1 = make_tokens("a b c".split() + shared + "d e".split())
+        tokens2 = make_tokens("p q".split() + shared + "r s t u".split())
+
+        per_scheme = {}
+        for scheme in KgramHashScheme:
+            hashes1 = {h for h, _ in compute_fingerprints(tokens1, 3, 4, NormalizationLevel.NONE, scheme)}
+            hashes2 = {h for h, _ in compute_fingerprints(tokens2, 3, 4, NormalizationLevel.NONE, scheme)}
+            self.assertTrue(hashes1 & hashes2, scheme)
+            per_scheme[scheme] = hashes1
+
+        self.assertFalse(per_scheme[KgramHashScheme.BLAKE2B] & per_scheme[KgramHashScheme.ROLLING_XXH3])
+
+    def test_fingerprint_similarity(self):
+        """Fingerprint similarity is the Jaccard index of the hash sets."""
+        self.assertEqual(fingerprint_similarity({1, 2, 3}, {2, 3, 4}), 0.5)
+        self.assertEqual(fingerprint_similarity(set(), set()), 0.0)
+
+
+class TestFingerprintKey(unittest.TestCase):
+    """Tests for fingerprint cache keys"""
+
+    def test_cache_key_round_trip(self):
+        """Keys survive serialization."""
+        key = FingerprintKey("abc123", "python", "1", "identifiers", 5, 4)
+
+        self.assertEqual(FingerprintKey.from_cache_key(key.to_cache_key()), key)
+
+    def test_matches_filters(self):
+        """Invalidation filters select by language and version."""
+        key = FingerprintKey("abc123", "python", "1", "identifiers", 5, 4)
+
+        self.assertTrue(key.matches(language="python"))
+        self.assertTrue(key.matches(tokenizer_version="1"))
+        self.assertFalse(key.matches(language="python", tokenizer_version="2"))
+        self.assertFalse(key.matches(language="java"))
+
+    def test_hash_algorithm_is_part_of_the_key(self):
+        """SHA-256 keys keep their original layout, other algorithms are named in the key."""
+        legacy = FingerprintKey("abc123", "python", "1", "identifiers", 5, 4)
+        blake3 = FingerprintKey("abc123", "python", "1", "identifiers", 5, 4, hash_algorithm="blake3")
+
+        self.assertEqual(legacy.to_cache_key(), "1|python|identifiers|5|4|abc123")
+        self.assertNotEqual(blake3.to_cache_key(), legacy.to_cache_key())
+        self.assertEqual(FingerprintKey.from_cache_key(blake3.to_cache_key()), blake3)
+        self.assertTrue(blake3.matches(hash_algorithm="blake3"))
+        self.assertFalse(legacy.matches(hash_algorithm="blake3"))
+
+    def test_hash_scheme_is_part_of_the_key(self):
+        """Keys of the previous k-gram hash keep their layout, keys of other schemes name the scheme."""
+        legacy = FingerprintKey("abc123", "python", "1", "identifiers", 5, 4, hash_algorithm="blake3")
+        rolling = [
+            FingerprintKey("abc123", "python", "1", "identifiers", 5, 4, hash_algorithm=algorithm, hash_scheme="x")
+            for algorithm in ("sha256", "blake3")
+        ]
+
+        self.assertEqual(legacy.to_cache_key(), "1|python|identifiers|5|4|blake3|abc123")
+        for key in rolling:
+            self.assertNotEqual(key.to_cache_key(), legacy.to_cache_key())
+            self.assertEqual(FingerprintKey.from_cache_key(key.to_cache_key()), key)
+
+
+class FingerprintServiceContract:
+    """Cache behaviour every store backend must provide, mixed into unittest.TestCase subclasses"""
+
+    def create_store(self):
+        raise NotImplementedError
+
+    def setUp(self):
+        self.tokenizer = CountingTokenizer()
+        self.store = self.create_store()
+        self.service = FingerprintService(self.tokenizer, self.store, k=3, window=2)
+
+    def run_detection(self, service=None, files=SUBMISSION_FILES):
+        service = service or self.service
+        stats = service.new_stats()
+        results = {path: service.get_fingerprints(content, path, stats) for path, content in files.items()}
+        return stats, results
+
+    def test_rerun_performs_zero_tokenizations(self):
+        """A rerun with identical parameters loads every file from the cache."""
+        first_stats, first_results = self.run_detection()
+        calls_after_first_run = self.tokenizer.calls
+
+        second_stats, second_results = self.run_detection()
+
+        self.assertEqual(first_stats.misses, len(SUBMISSION_FILES))
+        self.assertEqual(second_stats.tokenizations, 0)
+        self.assertEqual(second_stats.hits, len(SUBMISSION_FILES))
+        self.assertEqual(self.tokenizer.calls, calls_after_first_run)
+        for path in SUBMISSION_FILES:
+            self.assertEqual(second_results[path].fingerprints, first_results[path].fingerprints)
+            self.assertEqual(second_results[path].tokens, first_results[path].tokens)
+
+    def test_changed_content_is_tokenized(self):
+        """Only the modified file is tokenized again."""
+        self.run_detection()
+        modified = dict(SUBMISSION_FILES)
+        modified[Path("game.py")] += " + bonus"
+
+        stats, _ = self.run_detection(files=modified)
+
+        self.assertEqual(stats.misses, 1)
+        self.assertEqual(stats.hits, len(SUBMISSION_FILES) - 1)
+
+    def test_tokenizer_version_bump_never_reuses_entries(self):
+        """Entries of a previous tokenizer version are not reused."""
+        self.run_detection()
+        bumped = FingerprintService(self.tokenizer, self.store, k=3, window=2, tokenizer_version="2")
+
+        stats, _ = self.run_detection(service=bumped)
+
+        self.assertEqual(stats.hits, 0)
+        self.assertEqual(stats.misses, len(SUBMISSION_FILES))
+
+    def test_fingerprint_parameters_are_part_of_the_key(self):
+        """Changing k, window, normalization or the k-gram hash scheme misses the cache."""
+        self.run_detection()
+        for service in [
+            FingerprintService(self.tokenizer, self.store, k=4, window=2),
+            FingerprintService(self.tokenizer, self.store, k=3, window=3),
+            FingerprintService(self.tokenizer, self.store, k=3, window=2, normalization="types"),
+            FingerprintService(self.tokenizer, self.store, k=3, window=2, hash_scheme="blake2b"),
+        ]:
+            stats, _ = self.run_detection(service=service)
+            self.assertEqual(stats.hits, 0)
+
+    def test_invalidate_by_language(self):
+        """Invalidating a language only drops the entries of that language."""
+        self.run_detection()
+
+        invalidated = self.service.invalidate(language="python")
+        stats, _ = self.run_detection()
+
+        self.assertEqual(invalidated, 2)
+        self.assertEqual(stats.misses, 2)
+        self.assertEqual(stats.hits, 1)
+
+    def test_invalidate_by_version(self):
+        """Invalidating a tokenizer version drops its entries only."""
+        self.run_detection()
+        FingerprintService(self.tokenizer, self.store, k=3, window=2, tokenizer_version="2").get_fingerprints(
+            "x = 1", Path("other.py")
+        )
+
+        invalidated = self.service.invalidate(tokenizer_version=self.service.tokenizer_version)
+
+        self.assertEqual(invalidated, len(SUBMISSION_FILES))
+        self.assertEqual(self.store.count(), 1)
+
+    def test_streamed_files_share_entries_with_in_memory_content(self):
+        """A file streamed from disk hits the entry of the same content read in memory, whatever its line endings."""
+        with tempfile.TemporaryDirectory() as directory:
+            path = Path(directory) / "calculator.py"
+            path.write_bytes(SUBMISSION_FILES[Path("calculator.py")].replace(" ", "\r\n").encode("utf-8"))
+            self.service.get_fingerprints(path.read_text(encoding="utf-8"), path)
+            stats = self.service.new_stats()
+
+            streamed = self.service.get_file_fingerprints(path, stats)
+
+        self.assertEqual(stats.hits, 1)
+        self.assertEqual(len(streamed.tokens), len(SUBMISSION_FILES[Path("calculator.py")].split()))
+
+    def test_empty_token_lists_are_not_cached(self):
+        """Files without tokens are retried on the next run."""
+        self.service.get_fingerprints("", Path("empty.py"))
+
+        self.assertEqual(self.store.count(), 0)
+
+    def test_entries_of_accepted_algorithm_are_migrated_on_read(self):
+        """Entries keyed by an accepted previous algorithm are hits and copied under the current one."""
+        legacy = FingerprintService(self.tokenizer, self.store, k=3, window=2, hash_algorithm="sha256")
+        self.run_detection(service=legacy)
+        migrating = FingerprintService(
+            self.tokenizer, self.store, k=3, window=2, hash_algorithm="blake3", accepted_hash_algorithms=["sha256"]
+        )
+
+        stats, _ = self.run_detection(service=migrating)
+        invalidated = migrating.invalidate(hash_algorithm="sha256")
+        after_stats, _ = self.run_detection(service=migrating)
+
+        self.assertEqual(stats.hits, len(SUBMISSION_FILES))
+        self.assertEqual(stats.tokenizations, 0)
+        self.assertEqual(invalidated, len(SUBMISSION_FILES))
+        self.assertEqual(after_stats.hits, len(SUBMISSION_FILES))
+
+    def test_entries_of_other_algorithms_are_not_reused(self):
+        """Without accepted algorithms, entries keyed by another algorithm miss."""
+        legacy = FingerprintService(self.tokenizer, self.store, k=3, window=2, hash_algorithm="sha256")
+        self.run_detection(service=legacy)
+
+        stats, _ = self.run_detection(
+            service=FingerprintService(self.tokenizer, self.store, k=3, window=2, hash_algorithm="blake3")
+        )
+
+        self.assertEqual(stats.hits, 0)
+
+
+class TestInMemoryFingerprintCache(FingerprintServiceContract, unittest.TestCase):
+    """Fingerprint cache behaviour with the in-memory store"""
+
+    def create_store(self):
+        return InMemoryFingerprintStore()
+
+
+@unittest.skipUnless(LMDB_AVAILABLE, "lmdb is not installed")
+class TestLmdbFingerprintCache(FingerprintServiceContract, unittest.TestCase):
+    """Fingerprint cache behaviour with the LMDB store"""
+
+    def create_store(self):
+        from app.domains.fingerprints.lmdb_fingerprint_store import LmdbFingerprintStore
+
+        self.temp_dir = tempfile.TemporaryDirectory()
+        self.addCleanup(self.temp_dir.cleanup)
+        return LmdbFingerprintStore(self.temp_dir.name, max_size_gb=1)
+
+    def tearDown(self):
+        self.store.close()
+
+    def test_entries_survive_restart(self):
+        """A new store on the same directory serves the entries of the previous one."""
+        from app.domains.fingerprints.lmdb_fingerprint_store import LmdbFingerprintStore
+
+        self.run_detection()
+        self.store.close()
+
+        self.store = LmdbFingerprintStore(self.temp_dir.name, max_size_gb=1)
+        restarted = FingerprintService(CountingTokenizer(), self.store, k=3, window=2)
+        stats, _ = self.run_detection(service=restarted)
+
+        self.assertEqual(stats.tokenizations, 0)
+        self.assertEqual(restarted.tokenization_service.calls, 0)
+
+
+class TestFingerprintServiceFailures(unittest.TestCase):
+    """Tests for cache failures and the disabled cache"""
+
+    def test_store_errors_fall_back_to_tokenization(self):
+        """A failing store never fails fingerprinting."""
+        store = MagicMock()
+        store.get.side_effect = RuntimeError("disk full")
+        store.put.side_effect = RuntimeError("disk full")
+        service = FingerprintService(CountingTokenizer(), store, k=3, window=2)
+        stats = service.new_stats()
+
+        fingerprint_set = service.get_fingerprints("a b c d", Path("a.py"), stats)
+
+        self.assertEqual(len(fingerprint_set.tokens), 4)
+        self.assertEqual(stats.errors, 2)
+        self.assertEqual(stats.misses, 1)
+
+    def test_disabled_cache_always_tokenizes(self):
+        """Without a store every lookup is a tokenization."""
+        tokenizer = CountingTokenizer()
+        service = FingerprintService(tokenizer, None, k=3, window=2)
+
+        for _ in range(2):
+            service.get_fingerprints("a b c d", Path("a.py"))
+
+        self.assertEqual(tokenizer.calls, 2)
+        self.assertEqual(service.invalidate(language="python"), 0)
+        self.assertFalse(service.get_cache_info()["enabled"])
+
+
+class TestParallelFingerprinting(unittest.TestCase):
+    """Tests for fingerprinting the files of a submission on the tokenization pool"""
+
+    FILES = [Path(f"module_{index:02d}.py") for index in range(24)]
+
+    def read_file(self, file_path):
+        return f"def {file_path.stem} value return value + {file_path.stem} * {len(file_path.stem)}"
+
+    def fingerprint(self, workers, max_files_in_memory=64):
+        service = FingerprintService(
+            SlowTokenizer(0.02), None, k=3, window=2, workers=workers, max_files_in_memory=max_files_in_memory
+        )
+        stats = service.new_stats()
+        started = time.perf_counter()
+        results = list(service.fingerprint_files(self.FILES, self.read_file, stats))
+        return results, stats, time.perf_counter() - started
+
+    def test_parallel_output_matches_sequential(self):
+        """Results come back in input order, identical to the sequential path, and several times faster."""
+        sequential, sequential_stats, sequential_seconds = self.fingerprint(workers=1)
+        parallel, parallel_stats, parallel_seconds = self.fingerprint(workers=8)
+
+        self.assertEqual([path for path, _ in parallel], self.FILES)
+        self.assertEqual(
+            [(path, result.tokens, result.fingerprints) for path, result in parallel],
+            [(path, result.tokens, result.fingerprints) for path, result in sequential],
+        )
+        self.assertEqual(parallel_stats.misses, sequential_stats.misses)
+        self.assertEqual(parallel_stats.misses, len(self.FILES))
+        self.assertLess(parallel_seconds, sequential_seconds / 3)
+
+    def test_files_in_memory_are_bounded(self):
+        """Never more than max_files_in_memory files are read ahead of the consumer."""
+        service = FingerprintService(SlowTokenizer(0.005), None, k=3, window=2, workers=8, max_files_in_memory=3)
+        lock = threading.Lock()
+        read = []
+
+        def read_file(file_path):
+            with lock:
+                read.append(file_path)
+            return self.read_file(file_path)
+
+        ahead = []
+        for consumed, _ in enumerate(service.fingerprint_files(self.FILES, read_file), start=1):
+            time.sleep(0.01)
+            with lock:
+                ahead.append(len(read) - consumed)
+
+        self.assertEqual(len(read), len(self.FILES))
+        self.assertLessEqual(max(ahead), 3)
+
+    def test_files_above_threshold_are_streamed(self):
+        """Files larger than the streaming threshold never go through read_file."""
+        with tempfile.TemporaryDirectory() as directory:
+            small, large = Path(directory) / "small.py", Path(directory) / "large.py"
+            small.write_text("a b c")
+            large.write_text("word " * 100)
+            read = []
+            service = FingerprintService(CountingTokenizer(), None, k=3, window=2, streaming_threshold_bytes=100)
+
+            results = dict(service.fingerprint_files([small, large], lambda path: read.append(path) or "a b c"))
+
+        self.assertEqual(read, [small])
+        self.assertEqual(len(results[large].tokens), 100)
+
+    def test_skipped_files_are_not_yielded(self):
+        """Files the reader cannot decode are left out."""
+        service = FingerprintService(CountingTokenizer(), None, k=3, window=2, workers=4)
+
+        def read_file(file_path):
+            return None if file_path.stem.endswith("1") else "a b c"
+
+        results = list(service.fingerprint_files(self.FILES[:4], read_file))
+
+        self.assertEqual([path for path, _ in results], [self.FILES[0], self.FILES[2], self.FILES[3]])
+
+    def test_failing_files_are_reported_and_skipped(self):
+        """Files failing at any stage go to on_error in input order, the other files are still fingerprinted."""
+        files = [Path("a.py"), Path("corrupt.py"), Path("unreadable.py"), Path("b.py"), Path("corrupt_too.py")]
+
+        def read_file(file_path):
+            if file_path.stem == "unreadable":
+                raise PermissionError(f"permission denied: {file_path}")
+            return "a b c d"
+
+        for workers in (1, 4):
+            errors = []
+            service = FingerprintService(FailingTokenizer(), None, k=3, window=2, workers=workers)
+            results = list(
+                service.fingerprint_files(
+                    files, read_file, on_error=lambda path, stage, error: errors.append((path, stage, type(error)))
+                )
+            )
+
+            self.assertEqual([path for path, _ in results], [Path("a.py"), Path("b.py")])
+            self.assertEqual(
+                errors,
+                [
+                    (Path("corrupt.py"), FileStage.TOKENIZATION, RecursionError),
+                    (Path("unreadable.py"), FileStage.DECODING, PermissionError),
+                    (Path("corrupt_too.py"), FileStage.TOKENIZATION, RecursionError),
+                ],
+            )
+
+    def test_failures_are_raised_without_handler_and_when_systemic(self):
+        """Without on_error failures are raised as before, database failures are raised in any case."""
+        files = [Path("a.py"), Path("corrupt.py")]
+        service = FingerprintService(FailingTokenizer(), None, k=3, window=2)
+
+        # Without on_error the tokenization failure gives no token, like with the tokenization service
+        results = service.fingerprint_files(files, lambda path: "a b")
+        self.assertEqual([len(result.tokens) for _, result in results], [2, 0])
+        with self.assertRaises(PermissionError):
+            list(service.fingerprint_files(files, lambda path: (_ for _ in ()).throw(PermissionError("denied"))))
+
+        systemic = FingerprintService(FailingTokenizer(DatabaseException("database is down")), None, k=3, window=2)
+        with self.assertRaises(DatabaseException):
+            list(systemic.fingerprint_files(files, lambda path: "a b", on_error=lambda *args: None))
+
+    def test_profiler_records_stages_per_file(self):
+        """Decoding, tokenization per language and fingerprinting are timed, cache hits skip the last two."""
+        service = FingerprintService(CountingTokenizer(), InMemoryFingerprintStore(), k=3, window=2)
+        profiler = StageProfiler()
+        files = [Path("a.py"), Path("b.js"), Path("c.py")]
+
+        list(service.fingerprint_files(files, lambda path: "x = f ( y )", profiler=profiler))
+
+        stages = profiler.to_dict()["stages"]
+        self.assertEqual(stages["decoding"]["count"], 3)
+        self.assertEqual(stages["tokenization.python"]["count"], 1)
+        self.assertEqual(stages["tokenization.javascript"]["count"], 1)
+        self.assertEqual(stages["fingerprinting"]["count"], 2)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,58 @@
+"""
+Tests for the distribution of k-gram hashes over the language samples
+"""
+
+import unittest
+
+from app.domains.fingerprints.fingerprint_models import KgramHashScheme, NormalizationLevel
+from app.domains.fingerprints.fingerprinting import kgram_hashes, normalize_token
+from tests.helpers import SAMPLES_DIRECTORY, RegexTokenizer
+
+BUCKETS = 64
+# Chi-squared value a uniform hash stays below with probability 0.999 for BUCKETS - 1 degrees of freedom
+CHI_SQUARED_CRITICAL = 103.44
+
+
+def sample_kgrams(k: int, normalization: NormalizationLevel):
+    """Distinct k-grams of every language sample, identical k-grams would weigh the same hash several times"""
+    tokenizer = RegexTokenizer()
+    kgrams = set()
+    for sample in sorted(SAMPLES_DIRECTORY.iterdir()):
+        tokens = tokenizer.tokenize(sample.read_text(encoding="utf-8", errors="replace"), sample)
+        parts = [normalize_token(token, normalization) for token in tokens]
+        kgrams.update(tuple(parts[i : i + k]) for i in range(len(parts) - k + 1))
+    return kgrams
+
+
+def chi_squared(counts) -> float:
+    expected = sum(counts) / len(counts)
+    return sum((count - expected) ** 2 / expected for count in counts)
+
+
+class TestKgramHashDistribution(unittest.TestCase):
+    """Hashes of the distinct k-grams of the samples are uniform over buckets of their high and low bits"""
+
+    def assert_uniform(self, scheme: KgramHashScheme, normalization: NormalizationLevel):
+        kgrams = sample_kgrams(5, normalization)
+        hashes = [kgram_hashes(list(kgram), 5, scheme)[0] for kgram in kgrams]
+        self.assertGreater(len(hashes), BUCKETS * 20)
+
+        # Winnowing compares whole hashes, the cache and index bucket on the low bits
+        for bucket_of in (lambda value: value >> 58, lambda value: value % BUCKETS):
+            counts = [0] * BUCKETS
+            for value in hashes:
+                counts[bucket_of(value)] += 1
+            self.assertLess(chi_squared(counts), CHI_SQUARED_CRITICAL, f"{scheme.value}, {normalization.value}")
+
+    def test_rolling_hashes_are_uniform(self):
+        """The rolling scheme passes a chi-squared test with and without normalization."""
+        for normalization in (NormalizationLevel.NONE, NormalizationLevel.IDENTIFIERS, NormalizationLevel.TYPES):
+            self.assert_uniform(KgramHashScheme.ROLLING_XXH3, normalization)
+
+    def test_previous_scheme_is_uniform(self):
+        """The previous scheme stays available with the same distribution."""
+        self.assert_uniform(KgramHashScheme.BLAKE2B, NormalizationLevel.IDENTIFIERS)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1 @@
+# Messaging tests module
@@ -0,0 +1,342 @@
+"""
+Tests for detection requests consumed from RabbitMQ: outcome of each delivery, shutdown order and, against a
+broker container, acknowledgement, requeueing and dead-lettering
+"""
+
+import json
+import os
+import threading
+import time
+import unittest
+import uuid
+from collections import deque
+from types import SimpleNamespace
+from unittest.mock import patch
+
+import pytest
+
+from app.domains.messaging import detection_request_consumer
+from app.domains.messaging.detection_request_consumer import (
+    DEAD_LETTER_REASON_HEADER,
+    DeliveryOutcome,
+    DetectionRequestConsumer,
+    DetectionRequestHandler,
+    Disposition,
+    delivery_attempt,
+)
+from app.shared.concurrency import JobPriority
+from app.shared.exceptions import DatabaseException, ValidationException
+from tests.helpers import RecordingSubmissions
+
+SUBMISSION = {
+    "link": "https://github.com/user/repository.git",
+    "project_uuid": "550e8400-e29b-41d4-a716-446655440000",
+    "group_uuid": "550e8400-e29b-41d4-a716-446655440001",
+    "project_step_uuid": "550e8400-e29b-41d4-a716-446655440002",
+}
+
+
+def message(**fields) -> bytes:
+    return json.dumps({"submission": SUBMISSION, **fields}).encode()
+
+
+class RecordingHandler(RecordingSubmissions, DetectionRequestHandler):
+    """Request handler creating no submission, recording the requests and raising the queued errors in turn"""
+
+    def __init__(self, errors=(), max_attempts: int = 3):
+        super().__init__(max_attempts, errors=errors)
+
+
+class TestDetectionRequestHandler(unittest.TestCase):
+    """Tests for the outcome of the deliveries of detection requests"""
+
+    def test_valid_requests_are_acknowledged(self):
+        """A valid request creates its submission with the options of the HTTP query parameters."""
+        handler = RecordingHandler()
+
+        disposition = handler.handle(message(priority="high", allow_duplicates=True))
+
+        self.assertEqual(disposition, Disposition(DeliveryOutcome.ACK))
+        request = handler.requests[0]
+        self.assertEqual(request.priority, JobPriority.HIGH)
+        self.assertTrue(request.allow_duplicates)
+        self.assertFalse(request.profile)
+        self.assertEqual(str(request.submission.project_uuid), SUBMISSION["project_uuid"])
+
+    def test_malformed_requests_are_dead_lettered(self):
+        """Bodies that are not JSON or fail the submission DTO validation are dead-lettered without attempt."""
+        handler = RecordingHandler()
+        bodies = [
+            b"not json",
+            json.dumps({"submission": {**SUBMISSION, "link": "ftp://example.com/repo"}}).encode(),
+            message(unknown_option=True),
+            json.dumps({"link": SUBMISSION["link"]}).encode(),
+        ]
+
+        for body in bodies:
+            disposition = handler.handle(body)
+            self.assertEqual(disposition.outcome, DeliveryOutcome.DEAD_LETTER)
+            self.assertTrue(disposition.reason.startswith("Malformed message"))
+        self.assertEqual(handler.requests, [])
+
+    def test_refused_requests_are_dead_lettered(self):
+        """Requests the submission pipeline refuses, like duplicates, are not retried."""
+        handler = RecordingHandler([ValidationException("A submission with the same project already exists")])
+
+        disposition = handler.handle(message())
+
+        self.assertEqual(disposition.outcome, DeliveryOutcome.DEAD_LETTER)
+        self.assertIn("status 422", disposition.reason)
+        self.assertIn("already exists", disposition.reason)
+
+    def test_failures_are_requeued_until_the_last_attempt(self):
+        """Failures that may pass are requeued, the last allowed attempt dead-letters the request."""
+        handler = RecordingHandler([DatabaseException("database is down"), RuntimeError("boom")], max_attempts=2)
+
+        self.assertEqual(handler.handle(message(), attempt=1), Disposition(DeliveryOutcome.REQUEUE))
+        last = handler.handle(message(), attempt=2)
+
+        self.assertEqual(last.outcome, DeliveryOutcome.DEAD_LETTER)
+        self.assertEqual(last.reason, "Failed after 2 attempts: RuntimeError: boom")
+
+    def test_delivery_attempts_are_counted_by_the_broker(self):
+        """The attempt is one more than the deliveries counted by the quorum queue."""
+        self.assertEqual(delivery_attempt(None), 1)
+        self.assertEqual(delivery_attempt({"x-delivery-count": 2}), 3)
+
+
+class FakeChannel:
+    """Channel double recording the settlement of deliveries and the cancellation of the consumer"""
+
+    def __init__(self, events):
+        self.events = events
+        self.on_message = None
+        self.published = []
+
+    def queue_declare(self, queue, durable=False, arguments=None):
+        self.events.append(("declare", queue, arguments))
+
+    def confirm_delivery(self):
+        pass
+
+    def basic_qos(self, prefetch_count):
+        self.events.append(("qos", prefetch_count))
+
+    def basic_consume(self, queue, on_message_callback):
+        self.on_message = on_message_callback
+        return "consumer-1"
+
+    def basic_cancel(self, consumer_tag):
+        self.events.append(("cancel", consumer_tag))
+
+    def basic_ack(self, delivery_tag):
+        self.events.append(("ack", delivery_tag))
+
+    def basic_nack(self, delivery_tag, requeue=True):
+        self.events.append(("nack", delivery_tag, requeue))
+
+    def basic_publish(self, exchange, routing_key, body, properties=None, mandatory=False):
+        self.published.append((routing_key, body, properties))
+        self.events.append(("publish", routing_key))
+
+
+class FakeConnection:
+    """Connection double delivering queued messages and running the callbacks of worker threads on its thread"""
+
+    def __init__(self, bodies):
+        self.events = []
+        self.channel_ = FakeChannel(self.events)
+        self.pending = deque(bodies)
+        self.delivered = 0
+        self.callbacks = deque()
+        self.lock = threading.Lock()
+
+    def channel(self):
+        return self.channel_
+
+    def add_callback_threadsafe(self, callback):
+        with self.lock:
+            self.callbacks.append(callback)
+
+    def process_data_events(self, time_limit=0):
+        if self.pending:
+            self.delivered += 1
+            tag, body = self.delivered, self.pending.popleft()
+            properties = SimpleNamespace(headers=None, correlation_id=f"message-{tag}")
+            self.channel_.on_message(self.channel_, SimpleNamespace(delivery_tag=tag), properties, body)
+        with self.lock:
+            callbacks, self.callbacks = list(self.callbacks), deque()
+        for callback in callbacks:
+            callback()
+        time.sleep(0.01)
+
+    def close(self):
+        self.events.append(("close",))
+
+
+class ProfileFailingHandler(RecordingHandler):
+    """Handler failing on the requests asking for a profile"""
+
+    def create_submission(self, request):
+        if request.profile:
+            raise DatabaseException("database is down")
+        return super().create_submission(request)
+
+
+class SlowHandler(RecordingHandler):
+    """Handler blocking on its first request until released"""
+
+    def __init__(self):
+        super().__init__()
+        self.started = threading.Event()
+        self.release = threading.Event()
+
+    def create_submission(self, request):
+        self.started.set()
+        self.release.wait(5)
+        return super().create_submission(request)
+
+
+class TestDetectionRequestConsumer(unittest.TestCase):
+    """Tests for the settlement of deliveries and the shutdown of the consumer"""
+
+    def consumer(self, connection, handler):
+        return DetectionRequestConsumer(
+            "amqp://localhost", "requests", "requests.dead", handler, prefetch_count=2, connect=lambda: connection
+        )
+
+    @patch.object(detection_request_consumer, "pika", SimpleNamespace(BasicProperties=SimpleNamespace))
+    def test_deliveries_are_settled_by_outcome(self):
+        """Valid requests are acked, malformed ones published to the dead-letter queue with their reason."""
+        connection = FakeConnection([message(), b"not json", message(profile=True)])
+        consumer = self.consumer(connection, ProfileFailingHandler())
+        consumer.start()
+        try:
+            for _ in range(100):
+                if sum(event[0] in ("ack", "nack") for event in connection.events) == 3:
+                    break
+                time.sleep(0.01)
+        finally:
+            consumer.stop(timeout=5)
+
+        settled = {event[1]: event for event in connection.events if event[0] in ("ack", "nack")}
+        self.assertEqual(settled, {1: ("ack", 1), 2: ("ack", 2), 3: ("nack", 3, True)})
+        self.assertLess(connection.events.index(("publish", "requests.dead")), connection.events.index(("ack", 2)))
+        [(routing_key, body, properties)] = connection.channel_.published
+        self.assertEqual((routing_key, body), ("requests.dead", b"not json"))
+        self.assertTrue(properties.headers[DEAD_LETTER_REASON_HEADER].startswith("Malformed message"))
+        self.assertEqual(properties.correlation_id, "message-2")
+        self.assertEqual(
+            connection.events[:3],
+            [("declare", "requests.dead", None), ("declare", "requests", {"x-queue-type": "quorum"}), ("qos", 2)],
+        )
+
+    def test_stop_cancels_the_consumer_before_draining(self):
+        """The consumer is cancelled while a request is handled, which is still acked before the connection closes."""
+        connection = FakeConnection([message()])
+        handler = SlowHandler()
+        consumer = self.consumer(connection, handler)
+        consumer.start()
+        self.assertTrue(handler.started.wait(5))
+
+        stopping = threading.Thread(target=consumer.stop)
+        stopping.start()
+        for _ in range(100):
+            if ("cancel", "consumer-1") in connection.events:
+                break
+            time.sleep(0.01)
+        self.assertNotIn(("ack", 1), connection.events)
+        handler.release.set()
+        stopping.join(5)
+
+        self.assertEqual(connection.events[-3:], [("cancel", "consumer-1"), ("ack", 1), ("close",)])
+        self.assertFalse(consumer.consuming.is_set())
+
+
+@pytest.mark.integration
+@unittest.skipUnless(os.environ.get("AMQP_URL"), "AMQP_URL not set")
+class TestDetectionRequestConsumerRabbitMQ(unittest.TestCase):
+    """Tests against a RabbitMQ container (docker compose up rabbitmq)"""
+
+    def setUp(self):
+        import pika
+
+        suffix = uuid.uuid4().hex[:8]
+        self.queue = f"pamp.test.requests.{suffix}"
+        self.dead_letter_queue = f"{self.queue}.dead"
+        self.connection = pika.BlockingConnection(pika.URLParameters(os.environ["AMQP_URL"]))
+        self.channel = self.connection.channel()
+        self.channel.queue_declare(self.dead_letter_queue, durable=True)
+        self.channel.queue_declare(self.queue, durable=True, arguments={"x-queue-type": "quorum"})
+
+    def tearDown(self):
+        self.channel.queue_delete(self.queue)
+        self.channel.queue_delete(self.dead_letter_queue)
+        self.connection.close()
+
+    def publish(self, body: bytes):
+        import pika
+
+        self.channel.basic_publish(
+            "", self.queue, body, properties=pika.BasicProperties(delivery_mode=2, correlation_id="request-1")
+        )
+
+    def consume(self, handler, until):
+        consumer = DetectionRequestConsumer(
+            os.environ["AMQP_URL"], self.queue, self.dead_letter_queue, handler, reconnect_delay_seconds=0.5
+        )
+        consumer.start()
+        try:
+            for _ in range(200):
+                if until():
+                    break
+                time.sleep(0.05)
+        finally:
+            consumer.stop(timeout=10)
+
+    def message_count(self, queue: str) -> int:
+        return self.channel.queue_declare(queue, passive=True).method.message_count
+
+    def test_valid_requests_are_acknowledged(self):
+        """An acknowledged request leaves both queues empty."""
+        handler = RecordingHandler()
+        self.publish(message())
+
+        self.consume(handler, lambda: handler.requests)
+
+        self.assertEqual(len(handler.requests), 1)
+        self.assertEqual(self.message_count(self.queue), 0)
+        self.assertEqual(self.message_count(self.dead_letter_queue), 0)
+
+    def test_failing_requests_are_requeued_then_succeed(self):
+        """A request failing once is redelivered, counted by the broker, and acknowledged on its second attempt."""
+        handler = RecordingHandler([DatabaseException("database is down")])
+        self.publish(message())
+
+        self.consume(handler, lambda: len(handler.requests) == 2)
+
+        self.assertEqual(len(handler.requests), 2)
+        self.assertEqual(self.message_count(self.queue), 0)
+        self.assertEqual(self.message_count(self.dead_letter_queue), 0)
+
+    def test_malformed_and_repeatedly_failing_requests_are_dead_lettered(self):
+        """Malformed requests and requests failing every attempt end in the dead-letter queue with their reason."""
+        handler = RecordingHandler([DatabaseException("database is down")] * 3, max_attempts=3)
+        self.publish(b"not json")
+        self.publish(message())
+
+        self.consume(handler, lambda: self.message_count(self.dead_letter_queue) == 2)
+
+        reasons = []
+        for _ in range(2):
+            method, properties, body = self.channel.basic_get(self.dead_letter_queue, auto_ack=True)
+            reasons.append(properties.headers[DEAD_LETTER_REASON_HEADER])
+            self.assertEqual(properties.correlation_id, "request-1")
+        self.assertEqual(len(handler.requests), 3)
+        self.assertTrue(reasons[0].startswith("Malformed message"))
+        self.assertEqual(reasons[1], "Failed after 3 attempts: database is down")
+        self.assertEqual(self.message_count(self.queue), 0)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1 @@
+# Notifications tests module
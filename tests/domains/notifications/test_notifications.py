@@ -0,0 +1,336 @@
+"""
+Tests for submissions ingested from S3 event notifications: key templates, notification formats, suppression of
+duplicate notifications and the SQS poller.
+
+Ingestion tests run against TEST_DATABASE_URL when set, in-memory SQLite otherwise.
+"""
+
+import json
+import unittest
+from datetime import timedelta
+from types import SimpleNamespace
+from uuid import UUID, uuid4
+
+from sqlmodel import Session, SQLModel, select
+
+from app.domains.notifications.key_template import KeyMismatch, KeyTemplate
+from app.domains.notifications.notification_handler import IngestionOutcome, ObjectNotificationHandler
+from app.domains.notifications.notifications_models import ObjectIngestion, ObjectIngestionStatus
+from app.domains.notifications.notifications_service import NotificationService
+from app.domains.notifications.s3_notifications import ObjectCreated, parse_notification
+from app.domains.notifications.sqs_poller import SqsNotificationPoller
+from app.shared.exceptions import DatabaseException, ValidationException
+from app.shared.retries import CircuitBreakers, Retrier, RetryPolicy
+from app.shared.timestamps import utc_now
+from tests.helpers import RecordingSubmissions, create_test_engine
+
+PROJECT = "550e8400-e29b-41d4-a716-446655440000"
+STEP = "550e8400-e29b-41d4-a716-446655440002"
+GROUP = "550e8400-e29b-41d4-a716-446655440001"
+TEMPLATE = "{project_uuid}/{project_step_uuid}/{group_uuid}/{filename}"
+
+
+def s3_record(key: str, version_id: str = "3HL4kqtJlcpXroDTDmJ", event_name: str = "ObjectCreated:Put") -> dict:
+    return {
+        "eventVersion": "2.1",
+        "eventSource": "aws:s3",
+        "eventTime": "2024-01-15T10:30:00.000Z",
+        "eventName": event_name,
+        "s3": {
+            "bucket": {"name": "lms-uploads"},
+            "object": {"key": key, "size": 1024, "eTag": "d41d8cd98f00b204e9800998ecf8427e", "versionId": version_id},
+        },
+    }
+
+
+def notification(*records) -> str:
+    return json.dumps({"Records": list(records)})
+
+
+class TestKeyTemplate(unittest.TestCase):
+    """Tests for the mapping of object keys to submissions"""
+
+    def test_key_is_mapped_to_its_submission(self):
+        fields = KeyTemplate(TEMPLATE).parse(f"{PROJECT}/{STEP}/{GROUP}/archive.zip")
+
+        self.assertEqual(
+            fields, {"project_uuid": UUID(PROJECT), "project_step_uuid": UUID(STEP), "group_uuid": UUID(GROUP)}
+        )
+
+    def test_literal_text_and_other_placeholders(self):
+        template = KeyTemplate("lms/{course}/p-{project_uuid}/{project_step_uuid}/{group_uuid}_{submitted_by_uuid}.zip")
+        author = str(uuid4())
+
+        fields = template.parse(f"lms/algo-101/p-{PROJECT}/{STEP}/{GROUP}_{author}.zip")
+
+        self.assertEqual(fields["project_uuid"], UUID(PROJECT))
+        self.assertEqual(fields["submitted_by_uuid"], UUID(author))
+        self.assertNotIn("course", fields)
+
+    def test_placeholders_match_one_segment(self):
+        with self.assertRaises(KeyMismatch):
+            KeyTemplate(TEMPLATE).parse(f"{PROJECT}/{STEP}/{GROUP}/nested/archive.zip")
+
+    def test_keys_not_matching_are_refused(self):
+        template = KeyTemplate("lms/" + TEMPLATE)
+
+        for key in (f"other/{PROJECT}/{STEP}/{GROUP}/a.zip", f"lms/{PROJECT}/{STEP}/a.zip", "lms/", ""):
+            with self.subTest(key=key), self.assertRaises(KeyMismatch):
+                template.parse(key)
+
+    def test_placeholders_must_be_uuids(self):
+        with self.assertRaises(KeyMismatch) as raised:
+            KeyTemplate(TEMPLATE).parse(f"{PROJECT}/step-1/{GROUP}/archive.zip")
+
+        self.assertIn("{project_step_uuid}", str(raised.exception))
+
+    def test_regex_characters_are_literal(self):
+        template = KeyTemplate("a.b+/{project_uuid}/{project_step_uuid}/{group_uuid}.zip")
+
+        self.assertEqual(template.parse(f"a.b+/{PROJECT}/{STEP}/{GROUP}.zip")["group_uuid"], UUID(GROUP))
+        with self.assertRaises(KeyMismatch):
+            template.parse(f"axbb/{PROJECT}/{STEP}/{GROUP}.zip")
+
+    def test_invalid_templates(self):
+        missing_step = "{project_uuid}/{group_uuid}/{filename}"
+        repeated = "{project_uuid}/{project_step_uuid}/{group_uuid}/{x}/{x}"
+        for template in (missing_step, repeated):
+            with self.subTest(template=template), self.assertRaises(ValueError):
+                KeyTemplate(template)
+
+
+class TestParseNotification(unittest.TestCase):
+    """Tests for the objects read from the notification formats"""
+
+    def test_sqs_records_with_encoded_keys(self):
+        objects = parse_notification(notification(s3_record("projects/Final+report%C3%A9.zip")))
+
+        self.assertEqual(
+            objects,
+            [
+                ObjectCreated(
+                    bucket="lms-uploads",
+                    key="projects/Final reporté.zip",
+                    version_id="3HL4kqtJlcpXroDTDmJ",
+                    etag="d41d8cd98f00b204e9800998ecf8427e",
+                    size=1024,
+                    event_time="2024-01-15T10:30:00.000Z",
+                )
+            ],
+        )
+        self.assertEqual(objects[0].link, "s3://lms-uploads/projects/Final reporté.zip")
+
+    def test_sns_wrapped_notification(self):
+        body = json.dumps({"Type": "Notification", "Message": notification(s3_record("a.zip"))})
+
+        self.assertEqual([created.key for created in parse_notification(body)], ["a.zip"])
+
+    def test_minio_webhook(self):
+        body = {
+            "EventName": "s3:ObjectCreated:Put",
+            "Key": "lms-uploads/a.zip",
+            "Records": [s3_record("a.zip", version_id="", event_name="s3:ObjectCreated:Put")],
+        }
+
+        (created,) = parse_notification(body)
+
+        self.assertIsNone(created.version_id)
+        self.assertEqual(created.version, "etag:d41d8cd98f00b204e9800998ecf8427e")
+
+    def test_test_events_and_other_events_give_no_object(self):
+        self.assertEqual(parse_notification(json.dumps({"Event": "s3:TestEvent", "Bucket": "lms-uploads"})), [])
+        self.assertEqual(parse_notification(notification(s3_record("a.zip", event_name="ObjectRemoved:Delete"))), [])
+
+    def test_invalid_notifications(self):
+        no_object = notification({"eventName": "ObjectCreated:Put"})
+        for body in ("not json", "[]", json.dumps({"Message": "hello"}), no_object):
+            with self.subTest(body=body), self.assertRaises(ValueError):
+                parse_notification(body)
+
+    def test_digest_is_per_version(self):
+        first = ObjectCreated("lms-uploads", "a.zip", "v1")
+
+        self.assertEqual(first.digest, ObjectCreated("lms-uploads", "a.zip", "v1", size=10).digest)
+        self.assertNotEqual(first.digest, ObjectCreated("lms-uploads", "a.zip", "v2").digest)
+        self.assertNotEqual(first.digest, ObjectCreated("lms-uploads", "b.zip", "v1").digest)
+
+
+class RecordingHandler(RecordingSubmissions, ObjectNotificationHandler):
+    """Notification handler creating no submission, recording the submissions and raising the queued errors in turn"""
+
+    def __init__(self, session_factory):
+        super().__init__(KeyTemplate(TEMPLATE), session_factory=session_factory)
+
+
+class TestDuplicateNotifications(unittest.TestCase):
+    """Tests for the ingestion of notified objects once per version"""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.handler = RecordingHandler(lambda: Session(self.engine))
+        self.key = f"{PROJECT}/{STEP}/{GROUP}/archive.zip"
+
+    def tearDown(self):
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def ingestions(self):
+        with Session(self.engine) as session:
+            return list(session.exec(select(ObjectIngestion).order_by(ObjectIngestion.created_at)).all())
+
+    def test_duplicate_notifications_create_one_submission(self):
+        body = notification(s3_record(self.key))
+
+        outcomes = [outcome for _ in range(3) for _, outcome in self.handler.handle(body)]
+
+        self.assertEqual(outcomes, [IngestionOutcome.INGESTED, IngestionOutcome.DUPLICATE, IngestionOutcome.DUPLICATE])
+        (submission,) = self.handler.submissions
+        self.assertEqual(submission.link, f"s3://lms-uploads/{self.key}")
+        self.assertEqual(submission.group_uuid, UUID(GROUP))
+        self.assertEqual(submission.file_size_bytes, 1024)
+        (ingestion,) = self.ingestions()
+        self.assertEqual(ingestion.status, ObjectIngestionStatus.INGESTED)
+        self.assertEqual(ingestion.notifications, 3)
+        self.assertIsNotNone(ingestion.submission_id)
+
+    def test_new_versions_are_new_submissions(self):
+        self.handler.handle(notification(s3_record(self.key, version_id="v1")))
+        self.handler.handle(notification(s3_record(self.key, version_id="v2"), s3_record(self.key, version_id="v1")))
+
+        self.assertEqual(len(self.handler.submissions), 2)
+        self.assertEqual([ingestion.version for ingestion in self.ingestions()], ["v1", "v2"])
+
+    def test_unparseable_keys_are_quarantined(self):
+        body = notification(s3_record("uploads/archive.zip"))
+
+        self.assertEqual([outcome for _, outcome in self.handler.handle(body)], [IngestionOutcome.QUARANTINED])
+        self.assertEqual([outcome for _, outcome in self.handler.handle(body)], [IngestionOutcome.DUPLICATE])
+
+        self.assertEqual(self.handler.submissions, [])
+        with Session(self.engine) as session:
+            (quarantined,) = NotificationService(session).list_quarantined()
+            self.assertEqual(quarantined.object_key, "uploads/archive.zip")
+            self.assertIn("does not match", quarantined.reason)
+
+            NotificationService(session).dismiss_quarantined(quarantined.id)
+            self.assertEqual(NotificationService(session).list_quarantined(), [])
+
+    def test_failed_ingestions_are_retried_by_redeliveries(self):
+        self.handler.errors = [DatabaseException("Database is down")]
+        body = notification(s3_record(self.key))
+
+        self.assertEqual([outcome for _, outcome in self.handler.handle(body)], [IngestionOutcome.FAILED])
+        self.assertEqual(self.ingestions(), [])
+        self.assertEqual([outcome for _, outcome in self.handler.handle(body)], [IngestionOutcome.INGESTED])
+        self.assertEqual(len(self.handler.submissions), 1)
+
+    def test_refused_objects_are_not_retried(self):
+        self.handler.errors = [ValidationException("File count cannot exceed 10,000 files")]
+        body = notification(s3_record(self.key))
+
+        self.assertEqual([outcome for _, outcome in self.handler.handle(body)], [IngestionOutcome.REFUSED])
+        self.assertEqual([outcome for _, outcome in self.handler.handle(body)], [IngestionOutcome.DUPLICATE])
+        (ingestion,) = self.ingestions()
+        self.assertEqual(ingestion.status, ObjectIngestionStatus.REFUSED)
+        self.assertEqual(ingestion.reason, "File count cannot exceed 10,000 files")
+
+    def test_retried_fetches_are_listed_on_the_ingestion(self):
+        failures = [ConnectionResetError("Connection reset by peer")]
+
+        def download():
+            if failures:
+                raise failures.pop(0)
+
+        def create_submission(submission):
+            retrier.call("s3.eu-west-3.amazonaws.com", download)
+            return SimpleNamespace(submission_id=uuid4())
+
+        retrier = Retrier("s3", RetryPolicy(base_delay_seconds=0), breakers=CircuitBreakers())
+        self.handler.create_submission = create_submission
+
+        self.assertEqual(
+            [outcome for _, outcome in self.handler.handle(notification(s3_record(self.key)))],
+            [IngestionOutcome.INGESTED],
+        )
+        with Session(self.engine) as session:
+            (ingestion,) = NotificationService(session).list_ingestions(ObjectIngestionStatus.INGESTED)
+        (retry,) = ingestion.retries
+        self.assertEqual((retry["client"], retry["host"], retry["attempt"]), ("s3", "s3.eu-west-3.amazonaws.com", 1))
+        self.assertEqual(retry["error"], "ConnectionResetError: Connection reset by peer")
+
+    def test_abandoned_ingestions_are_taken_over(self):
+        created = parse_notification(notification(s3_record(self.key)))[0]
+        with Session(self.engine) as session:
+            session.add(
+                ObjectIngestion(
+                    object_digest=created.digest,
+                    bucket=created.bucket,
+                    object_key=created.key,
+                    version=created.version,
+                    updated_at=utc_now() - timedelta(hours=1),
+                )
+            )
+            session.commit()
+
+        self.assertEqual(self.handler.ingest(created), IngestionOutcome.INGESTED)
+        self.assertEqual(len(self.handler.submissions), 1)
+
+
+class StubHandler:
+    """Handler answering the queued outcomes of each message body"""
+
+    def __init__(self, outcomes):
+        self.outcomes = outcomes
+
+    def handle(self, body):
+        outcome = self.outcomes[body]
+        if isinstance(outcome, Exception):
+            raise outcome
+        return [(ObjectCreated("lms-uploads", body), outcome)]
+
+
+class StubSqsClient:
+    def __init__(self, bodies):
+        self.messages = [
+            {"MessageId": str(index), "ReceiptHandle": f"receipt-{index}", "Body": body}
+            for index, body in enumerate(bodies)
+        ]
+        self.receives = []
+        self.deleted = []
+
+    def receive_message(self, **arguments):
+        self.receives.append(arguments)
+        return {"Messages": self.messages}
+
+    def delete_message(self, QueueUrl, ReceiptHandle):
+        self.deleted.append(ReceiptHandle)
+
+
+class TestSqsNotificationPoller(unittest.TestCase):
+    """Tests for the deletion of the SQS messages handled"""
+
+    def test_only_failed_messages_are_left_for_redelivery(self):
+        outcomes = {
+            "ingested": IngestionOutcome.INGESTED,
+            "duplicate": IngestionOutcome.DUPLICATE,
+            "failed": IngestionOutcome.FAILED,
+            "invalid": ValueError("Not an S3 event notification"),
+            "error": RuntimeError("Unexpected"),
+        }
+        client = StubSqsClient(list(outcomes))
+        poller = SqsNotificationPoller("https://sqs.example/queue", StubHandler(outcomes), client=client)
+
+        with self.assertLogs("app.domains.notifications.sqs_poller", "ERROR"):
+            self.assertEqual(poller.poll_once(), 5)
+
+        self.assertEqual(client.deleted, ["receipt-0", "receipt-1", "receipt-3"])
+        self.assertEqual(
+            client.receives,
+            [{"QueueUrl": "https://sqs.example/queue", "MaxNumberOfMessages": 10, "WaitTimeSeconds": 20}],
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1 @@
+# Quotas tests module
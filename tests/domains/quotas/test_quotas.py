@@ -0,0 +1,203 @@
+"""
+Tests for the per-project quotas and the usage counted against them
+
+Counter tests run against TEST_DATABASE_URL when set (e.g. a throwaway PostgreSQL database), in-memory SQLite
+otherwise.
+"""
+
+import json
+import unittest
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from sqlmodel import Session, SQLModel
+
+from app.config.config import Settings
+from app.domains.quotas.quota_models import ProjectUsage
+from app.domains.quotas.quota_repository import QuotaRepository
+from app.domains.quotas.quota_service import QuotaService
+from app.domains.quotas.quotas import parse_project_quotas, project_quotas
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.shared.exceptions import QuotaExceededException
+from tests.helpers import create_test_engine
+
+
+class UsageRepository:
+    """Quota repository double holding the usage of a project and the runs it started over the last day"""
+
+    def __init__(self, project_uuid, submissions=0, stored_bytes=0, runs=0):
+        self.usage = ProjectUsage(project_uuid=project_uuid, submissions=submissions, stored_bytes=stored_bytes)
+        self.runs = runs
+
+    def get_usage(self, project_uuid):
+        return self.usage if project_uuid == self.usage.project_uuid else None
+
+    def list_usage(self):
+        return [self.usage]
+
+    def count_runs_since(self, project_uuid, since):
+        return self.runs
+
+    def count_runs_by_project_since(self, since):
+        return {str(self.usage.project_uuid): self.runs}
+
+
+class QuotaTestCase(unittest.TestCase):
+    def setUp(self):
+        self.project_uuid = uuid4()
+        self.settings = Settings(
+            quota_max_stored_bytes=10_000,
+            quota_max_submissions=3,
+            quota_max_runs_per_day=2,
+            quota_max_run_submissions=4,
+        )
+        patcher = patch("app.config.config.get_settings", return_value=self.settings)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def service(self, **usage) -> QuotaService:
+        service = QuotaService.__new__(QuotaService)
+        service.repository = UsageRepository(self.project_uuid, **usage)
+        return service
+
+    def assertRefused(self, quota: str, status_code: int, check, *args) -> dict:
+        with self.assertRaises(QuotaExceededException) as raised:
+            check(*args)
+        self.assertEqual(raised.exception.status_code, status_code)
+        detail = raised.exception.detail
+        self.assertEqual((detail["code"], detail["quota"]), (f"quota_{quota}_exceeded", quota))
+        self.assertEqual(detail["project_uuid"], str(self.project_uuid))
+        return detail
+
+
+class TestQuotaEnforcement(QuotaTestCase):
+    """Tests hitting each quota"""
+
+    def test_submissions_quota(self):
+        """A project keeping its quota of submissions is refused one more with 403 and its usage."""
+        self.service(submissions=2).check_submission(self.project_uuid, 100)
+
+        full = self.service(submissions=3)
+        detail = self.assertRefused("submissions", 403, full.check_submission, self.project_uuid)
+        self.assertEqual((detail["limit"], detail["usage"], detail["requested"]), (3, 3, 1))
+
+    def test_stored_bytes_quota(self):
+        """A declared size going over the stored bytes is refused, at the limit anything is refused."""
+        self.service(stored_bytes=9_000).check_submission(self.project_uuid, 1_000)
+
+        detail = self.assertRefused(
+            "stored_bytes", 403, self.service(stored_bytes=9_000).check_submission, self.project_uuid, 1_001
+        )
+        self.assertEqual((detail["limit"], detail["usage"], detail["requested"]), (10_000, 9_000, 1_001))
+        self.assertRefused("stored_bytes", 403, self.service(stored_bytes=10_000).check_submission, self.project_uuid)
+
+    def test_runs_per_day_quota(self):
+        """The run after the quota of the last 24 hours is refused with 429."""
+        self.service(runs=1).check_run(self.project_uuid, 2)
+
+        detail = self.assertRefused("runs_per_day", 429, self.service(runs=2).check_run, self.project_uuid, 2)
+        self.assertEqual((detail["limit"], detail["usage"]), (2, 2))
+
+    def test_run_submissions_quota(self):
+        """A run comparing more submissions than allowed is refused before anything is created."""
+        self.service().check_run(self.project_uuid, 4)
+        self.assertRefused("run_submissions", 403, self.service().check_run, self.project_uuid, 5)
+
+        detection = DetectionIntegrationService.__new__(DetectionIntegrationService)
+        submissions = [
+            SimpleNamespace(id=uuid4(), group_uuid=uuid4(), project_uuid=self.project_uuid) for _ in range(5)
+        ]
+        detection.submission_repository = SimpleNamespace(get_by_project_step=lambda *step: submissions)
+        detection.quota_service = self.service()
+        detection._create_detection_run = lambda *args: self.fail("no run is created over the quota")
+        submission = SimpleNamespace(**vars(submissions[0]), project_step_uuid=uuid4())
+        self.assertRefused("run_submissions", 403, detection.process_submission_similarities_async, submission)
+
+    def test_overrides_and_unlimited_quotas(self):
+        """PROJECT_QUOTAS overrides the quotas of its projects only, 0 lifting a limit."""
+        other = uuid4()
+        overrides = {"submissions": 0, "runs_per_day": 5}
+        self.settings.project_quotas = json.dumps({str(self.project_uuid).upper(): overrides})
+
+        self.assertEqual(
+            project_quotas(self.project_uuid).to_dict(),
+            {"stored_bytes": 10_000, "submissions": 0, "runs_per_day": 5, "run_submissions": 4},
+        )
+        self.assertEqual(project_quotas(other).submissions, 3)
+        self.service(submissions=1_000, runs=4).check_submission(self.project_uuid)
+        self.service(runs=4).check_run(self.project_uuid, 2)
+        for invalid in (["quotas"], {"not-a-uuid": {}}, {str(other): {"files": 1}}, {str(other): {"submissions": -1}}):
+            with self.assertRaises(ValueError):
+                parse_project_quotas(json.dumps(invalid))
+
+    def test_usage_report(self):
+        """The report of a project gives each quota with its usage, what remains and whether it is exceeded."""
+        report = self.service(submissions=3, stored_bytes=2_500, runs=1).get_project_quota(self.project_uuid)
+
+        quotas = {quota.quota: quota for quota in report.quotas}
+        self.assertEqual((quotas["submissions"].remaining, quotas["submissions"].exceeded), (0, False))
+        self.assertEqual((quotas["stored_bytes"].usage, quotas["stored_bytes"].remaining), (2_500, 7_500))
+        self.assertEqual(quotas["runs_per_day"].usage, 1)
+        self.assertIsNone(quotas["run_submissions"].usage)
+        self.assertFalse(report.overridden)
+
+
+class TestUsageCounters(QuotaTestCase):
+    """Tests for the usage counted with the ingestions and deletions of submissions"""
+
+    def setUp(self):
+        super().setUp()
+        self.engine = create_test_engine()
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def create_submission(self):
+        return SubmissionRepository(self.session).create(
+            CreateSubmissionDto(
+                link="https://github.com/user/repository.git",
+                project_uuid=self.project_uuid,
+                group_uuid=uuid4(),
+                project_step_uuid=uuid4(),
+            )
+        )
+
+    def usage(self):
+        self.session.expire_all()
+        usage = QuotaRepository(self.session).get_usage(self.project_uuid)
+        return usage.submissions, usage.stored_bytes
+
+    def test_counters_follow_ingestions_and_deletions(self):
+        """Submissions and their stored bytes are counted when created and stored, released when deleted."""
+        repository = SubmissionRepository(self.session)
+        first, second = self.create_submission(), self.create_submission()
+        repository.add_stored_bytes(first.id, 4_000)
+        repository.add_stored_bytes(second.id, 2_500)
+        repository.add_stored_bytes(second.id, 500)
+        self.assertEqual(self.usage(), (2, 7_000))
+
+        repository.delete(second.id)
+        self.assertEqual(self.usage(), (1, 4_000))
+        self.assertEqual(QuotaRepository(self.session).list_usage()[0].project_uuid, self.project_uuid)
+
+    def test_a_deletion_frees_the_quota(self):
+        """A project refused at its quota of submissions takes one more once one of its submissions is deleted."""
+        service = QuotaService(self.session)
+        submissions = [self.create_submission() for _ in range(3)]
+        self.assertRefused("submissions", 403, service.check_submission, self.project_uuid)
+
+        SubmissionRepository(self.session).delete(submissions[0].id)
+        service.check_submission(self.project_uuid)
+        self.create_submission()
+        self.assertEqual(self.usage(), (3, 0))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,171 @@
+"""
+Tests for the anonymized reports and exports of a run
+"""
+
+import json
+import unittest
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.anonymization import RunPseudonyms, scrub_home_directories
+from app.domains.reports.dto.report_dto import GraphFormat, PseudonymKind, RunSummaryDto, SummaryMetric
+from app.domains.reports.graph_export import export_graph
+from app.domains.reports.html_report import render_run_report
+from app.domains.reports.summary import summarize_pairs
+from app.domains.runs.runs_models import DetectionRunStatus, FragmentType
+
+SOURCE = (
+    '#include "/home/jdupont/projects/tp1/utils.h"\n'
+    "int main() {\n"
+    '    return load("C:\\\\Users\\\\jdupont\\\\data.txt");\n'
+    "}\n"
+)
+
+
+class TestAnonymizedReports(unittest.TestCase):
+    """Tests for the pseudonyms of a fixture run of three submissions, two of the same group"""
+
+    def setUp(self):
+        self.run = SimpleNamespace(
+            id=uuid4(),
+            project_uuid=uuid4(),
+            project_step_uuid=uuid4(),
+            status=DetectionRunStatus.COMPLETED,
+            started_at="2024-01-15T10:30:00+01:00",
+            finished_at="2024-01-15T10:32:00+01:00",
+            total_pairs=2,
+            completed_pairs=2,
+            failed_pairs=0,
+        )
+        group = uuid4()
+        self.participants = [
+            SimpleNamespace(submission_id=uuid4(), group_uuid=group, submitted_by_uuid=uuid4()),
+            SimpleNamespace(submission_id=uuid4(), group_uuid=group, submitted_by_uuid=uuid4()),
+            SimpleNamespace(submission_id=uuid4(), group_uuid=uuid4(), submitted_by_uuid=None),
+        ]
+        self.pairs = [self.pair(0, 1, 0.92), self.pair(1, 2, 0.71)]
+        self.fragments = {
+            str(pair.id): [
+                self.fragment(FragmentType.FILE, "/home/jdupont/tp1/main.c", None),
+                self.fragment(FragmentType.BLOCK, "/home/jdupont/tp1/main.c", (0, 3)),
+            ]
+            for pair in self.pairs
+        }
+        self.pseudonyms = RunPseudonyms(self.run.id, self.participants)
+
+    def pair(self, first, second, similarity):
+        return SimpleNamespace(
+            id=uuid4(),
+            submission_id=self.participants[first].submission_id,
+            compared_submission_id=self.participants[second].submission_id,
+            submitted_by_uuid=self.participants[first].submitted_by_uuid,
+            compared_submitted_by_uuid=self.participants[second].submitted_by_uuid,
+            overall_similarity=similarity,
+            jaccard_similarity=similarity,
+            structural_similarity=similarity,
+            fragments_count=1,
+            matched_tokens=40,
+        )
+
+    def fragment(self, fragment_type, path, lines):
+        return SimpleNamespace(
+            id=uuid4(),
+            fragment_type=fragment_type,
+            file1_path=path,
+            file2_path=path,
+            file1_start_line=lines[0] if lines else None,
+            file1_end_line=lines[1] if lines else None,
+            file2_start_line=lines[0] if lines else None,
+            file2_end_line=lines[1] if lines else None,
+            similarity=0.9,
+            details=None,
+        )
+
+    def identifiers(self):
+        """Every identity of the fixture, in full and as the short labels of reports"""
+        values = set()
+        for participant in self.participants:
+            for value in (participant.submission_id, participant.group_uuid, participant.submitted_by_uuid):
+                if value is not None:
+                    values.update((str(value), str(value)[:8]))
+        return values | {"jdupont"}
+
+    def assert_anonymous(self, document: str):
+        leaks = sorted(identifier for identifier in self.identifiers() if identifier in document)
+        self.assertEqual(leaks, [])
+
+    def read(self, submission_id, path):
+        return SOURCE
+
+    def test_pseudonyms_are_stable_within_a_run(self):
+        """Pseudonyms are numbered per kind, the same for every instance of a run and not tied to another run."""
+        again = RunPseudonyms(self.run.id, list(reversed(self.participants)))
+        participant = self.participants[0]
+
+        self.assertEqual(again.mapping(), self.pseudonyms.mapping())
+        self.assertRegex(self.pseudonyms(participant.submitted_by_uuid), r"^Student 00[12]$")
+        self.assertRegex(self.pseudonyms(participant.submission_id), r"^Submission 00[123]$")
+        self.assertEqual(self.pseudonyms(participant.group_uuid), self.pseudonyms(self.participants[1].group_uuid))
+        self.assertEqual(
+            [dto.kind for dto in self.pseudonyms.mapping()],
+            [PseudonymKind.STUDENT] * 2 + [PseudonymKind.SUBMISSION] * 3 + [PseudonymKind.GROUP] * 2,
+        )
+
+        many = [SimpleNamespace(submission_id=uuid4(), submitted_by_uuid=None) for _ in range(30)]
+        numbering = [RunPseudonyms(run_id, many)(many[0].submission_id) for run_id in (uuid4() for _ in range(10))]
+        self.assertGreater(len(set(numbering)), 1)
+
+    def test_home_directories_are_scrubbed(self):
+        """User names of Unix, macOS and Windows home directories are replaced, other paths are kept."""
+        self.assertEqual(scrub_home_directories("/home/jdupont/tp1/main.c"), "/home/user/tp1/main.c")
+        self.assertEqual(scrub_home_directories("/Users/jdupont/x.py"), "/Users/user/x.py")
+        self.assertEqual(scrub_home_directories("C:\\Users\\jdupont\\x.c"), "C:\\Users\\user\\x.c")
+        self.assertEqual(scrub_home_directories('"C:\\\\Users\\\\jdupont\\\\x"'), '"C:\\\\Users\\\\user\\\\x"')
+        self.assertEqual(scrub_home_directories("src/home/main.c"), "src/home/main.c")
+
+    def test_anonymized_html_report_has_no_identity(self):
+        """The anonymized HTML report shows pseudonyms, scrubbed paths and scrubbed code only."""
+        report = render_run_report(
+            self.run, self.participants, self.pairs, self.fragments, self.read, 0.5, pseudonyms=self.pseudonyms
+        )
+
+        self.assert_anonymous(report)
+        self.assertIn(self.pseudonyms(self.participants[0].submission_id), report)
+        self.assertIn("/home/user/tp1/main.c", report)
+        plain = render_run_report(self.run, self.participants, self.pairs, self.fragments, self.read, 0.5)
+        self.assertIn(str(self.participants[0].submission_id), plain)
+        self.assertIn("jdupont", plain)
+
+    def test_anonymized_json_has_no_identity(self):
+        """Anonymized JSON documents replace identities in every string and blank the PII fields."""
+        summary = RunSummaryDto(
+            run_id=self.run.id,
+            metric=SummaryMetric.OVERALL_SIMILARITY,
+            min_similarity=0.5,
+            top=10,
+            flagged_pairs=2,
+            pairs=summarize_pairs(self.pairs, self.fragments, self.read, SummaryMetric.OVERALL_SIMILARITY),
+        )
+        document = json.dumps(self.pseudonyms.anonymize(summary.model_dump(mode="json")))
+
+        self.assert_anonymous(document)
+        self.assertIn(str(self.run.id), document)
+        self.assertIn("/home/user/projects/tp1/utils.h", document)
+        self.assertEqual(
+            self.pseudonyms.anonymize({"link": "https://github.com/jdupont/tp1", "files": {"/home/jdupont/a": 1}}),
+            {"link": None, "files": {"/home/user/a": 1}},
+        )
+
+    def test_anonymized_graph_has_no_identity(self):
+        """DOT and GraphML exports use the pseudonyms of the run as node identifiers and submitters."""
+        for graph_format in GraphFormat:
+            document = "".join(
+                export_graph(graph_format, "run", self.participants, self.pairs, 0.5, pseudonyms=self.pseudonyms)
+            )
+
+            self.assert_anonymous(document)
+            self.assertRegex(document, r"Student \d{3}")
+
+
+if __name__ == "__main__":
+    unittest.main()
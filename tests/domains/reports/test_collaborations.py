@@ -0,0 +1,161 @@
+"""
+Tests for the declared collaborations of a project step and the reclassification of runs
+"""
+
+import unittest
+from types import SimpleNamespace
+from uuid import uuid4
+
+from pydantic import ValidationError
+
+from app.domains.reports.collaborations import DeclaredCollaborations
+from app.domains.reports.run_stats import compute_run_stats
+from app.domains.runs.runs_service import DetectionRunService
+from app.domains.submissions.dto.project_step_config_dto import DeclaredCollaborationDto
+
+
+class RunRepository:
+    """Run repository double holding one run, its participants and completed pairs"""
+
+    def __init__(self, run, participants, pairs):
+        self.run, self.participants, self.pairs = run, participants, pairs
+
+    def get_run(self, run_id):
+        return self.run if run_id == self.run.id else None
+
+    def get_participants(self, run_id):
+        return self.participants
+
+    def iter_completed_pairs(self, run_id):
+        return iter(self.pairs)
+
+    def update_declared_collaborations(self, run_id, declarations):
+        self.run.declared_collaborations = declarations
+        return 1
+
+
+class TestDeclaredCollaborations(unittest.TestCase):
+    """Tests for the pairs of a run declarations allow, in a run of two pair programmers, a team and a group"""
+
+    def setUp(self):
+        self.alice, self.bob, self.carol = uuid4(), uuid4(), uuid4()
+        self.team, self.group_a, self.group_b = uuid4(), uuid4(), uuid4()
+        self.participants = [
+            self.participant(self.alice, self.group_a),
+            self.participant(self.bob, self.group_b),
+            self.participant(self.carol, self.group_a, team_id=self.team, team_members=[str(uuid4())]),
+            self.participant(uuid4(), uuid4()),
+        ]
+        self.pairs = [
+            self.pair(0, 1, 0.95),  # alice and bob, pair programmers
+            self.pair(1, 2, 0.9),  # bob and the team of carol
+            self.pair(0, 3, 0.6),
+            self.pair(2, 3, 0.3),
+        ]
+        self.run = SimpleNamespace(id=uuid4(), project_step_uuid=uuid4(), declared_collaborations=None)
+
+    def participant(self, submitted_by, group_uuid, team_id=None, team_members=None):
+        return SimpleNamespace(
+            submission_id=uuid4(),
+            group_uuid=group_uuid,
+            submitted_by_uuid=submitted_by,
+            team_id=team_id,
+            team_members=team_members,
+        )
+
+    def pair(self, first, second, similarity):
+        return SimpleNamespace(
+            submission_id=self.participants[first].submission_id,
+            compared_submission_id=self.participants[second].submission_id,
+            submitted_by_uuid=self.participants[first].submitted_by_uuid,
+            compared_submitted_by_uuid=self.participants[second].submitted_by_uuid,
+            overall_similarity=similarity,
+            compared_files=None,
+        )
+
+    def declarations(self, *parties):
+        return [DeclaredCollaborationDto(parties=list(pair)).model_dump(mode="json") for pair in parties]
+
+    def allowed(self, *parties):
+        declared = DeclaredCollaborations(self.declarations(*parties), self.participants)
+        return [declared(pair) for pair in self.pairs]
+
+    def test_students_teams_and_groups(self):
+        """A declaration matches the pairs with a party on each side, in either order."""
+        self.assertEqual(
+            self.allowed((f"student:{self.bob}", f"student:{self.alice}")), [True, False, False, False]
+        )
+        self.assertEqual(self.allowed((f"team:{self.team}", f"student:{self.bob}")), [False, True, False, False])
+        self.assertEqual(self.allowed((f"group:{self.group_a}", f"group:{self.group_b}")), [True, True, False, False])
+        self.assertEqual(self.allowed(), [False] * 4)
+        self.assertFalse(DeclaredCollaborations(None, self.participants))
+
+    def test_wildcards(self):
+        """group:A with group:A covers everyone in group A, * any of a kind, * alone anyone."""
+        self.assertEqual(
+            self.allowed((f"group:{self.group_a}", f"group:{self.group_a}")), [False, False, False, False]
+        )
+        self.pairs.append(self.pair(0, 2, 0.8))
+        self.assertEqual(
+            self.allowed((f"group:{self.group_a}", f"group:{self.group_a}")), [False, False, False, False, True]
+        )
+        self.assertEqual(self.allowed(("team:*", "*")), [False, True, False, True, True])
+        self.assertEqual(self.allowed((f"student:{self.alice}", "student:*")), [True, False, True, False, True])
+
+    def test_parties_are_validated(self):
+        """Parties are normalized, unknown kinds, identifiers that are not UUIDs and lone parties are refused."""
+        dto = DeclaredCollaborationDto(parties=[f" Student:{str(self.alice).upper()}", "*"])
+        self.assertEqual(dto.parties, [f"student:{self.alice}", "*"])
+        for parties in (["course:*", "*"], ["student:alice", "*"], ["*"]):
+            with self.assertRaises(ValidationError):
+                DeclaredCollaborationDto(parties=parties)
+
+    def test_declared_pairs_are_left_out_of_the_statistics(self):
+        """Declared pairs are counted apart, neither in the histogram nor in the maximum of their submissions."""
+        declared = DeclaredCollaborations(
+            self.declarations((f"student:{self.alice}", f"student:{self.bob}")), self.participants
+        )
+
+        stats = compute_run_stats(self.participants, self.pairs, lambda name: "python", declared=declared)
+        plain = compute_run_stats(self.participants, self.pairs, lambda name: "python")
+
+        self.assertEqual((stats["pair_count"], stats["declared_collaboration_pairs"]), (3, 1))
+        self.assertEqual((plain["pair_count"], plain["declared_collaboration_pairs"]), (4, 0))
+        self.assertEqual(sum(bucket.count for bucket in stats["histogram"]), 3)
+        by_submission = {s.submission_id: s for s in stats["submissions"]}
+        self.assertEqual(by_submission[self.participants[0].submission_id].max_similarity, 0.6)
+        self.assertEqual(by_submission[self.participants[1].submission_id].max_similarity, 0.9)
+
+    def test_reclassification_applies_the_current_declarations(self):
+        """A run takes the declarations its step has now, without comparing, and reports what changed."""
+        config = SimpleNamespace(declared_collaborations=None)
+        service = DetectionRunService.__new__(DetectionRunService)
+        service.repository = RunRepository(self.run, self.participants, self.pairs)
+        service.submission_repository = SimpleNamespace(get_step_config=lambda project_step_uuid: config)
+        scores = [pair.overall_similarity for pair in self.pairs]
+
+        config.declared_collaborations = self.declarations((f"student:{self.alice}", f"student:{self.bob}"))
+        first = service.reclassify_run(self.run.id)
+        self.assertEqual((first.declared_collaboration_pairs, first.changed_pairs), (1, 1))
+        self.assertEqual(self.run.declared_collaborations, config.declared_collaborations)
+
+        config.declared_collaborations = self.declarations((f"group:{self.group_a}", f"group:{self.group_b}"))
+        second = service.reclassify_run(self.run.id)
+        self.assertEqual((second.declared_collaboration_pairs, second.changed_pairs), (2, 1))
+        stats = compute_run_stats(
+            self.participants,
+            self.pairs,
+            lambda name: "python",
+            declared=DeclaredCollaborations(self.run.declared_collaborations, self.participants),
+        )
+        self.assertEqual((stats["pair_count"], stats["declared_collaboration_pairs"]), (2, 2))
+
+        config.declared_collaborations = None
+        cleared = service.reclassify_run(self.run.id)
+        self.assertEqual((cleared.declared_collaboration_pairs, cleared.changed_pairs), (0, 2))
+        self.assertEqual(self.run.declared_collaborations, [])
+        self.assertEqual([pair.overall_similarity for pair in self.pairs], scores)
+
+
+if __name__ == "__main__":
+    unittest.main()
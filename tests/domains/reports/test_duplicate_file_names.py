@@ -0,0 +1,184 @@
+"""
+Tests that files sharing a name in different directories are paired, reported and read back by their full path
+"""
+
+import shutil
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.display_paths import short_paths
+from app.domains.reports.html_report import render_run_report
+from app.domains.reports.report_service import stored_source_reader
+from app.domains.runs.heatmap import build_heatmap
+from app.domains.runs.run_recorder import extract_fragments
+from app.domains.runs.runs_models import DetectionRunStatus, FragmentType
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+
+FIXTURE = Path(__file__).parents[3] / "resources" / "test" / "duplicate_names"
+
+
+class IdenticalFilesVisualization:
+    """Visualization service double finding one shared block in files of identical content"""
+
+    def __init__(self):
+        self.names = []
+
+    def generate_react_flow_ast(self, content1, content2, file1_name, file2_name, layout):
+        self.names.append((file1_name, file2_name))
+        if content1 != content2:
+            return {"has_similarity": False}
+        lines = content1.count("\n") - 1
+        return {
+            "has_similarity": True,
+            "analysis_metadata": {"average_similarity": 1.0, "total_similarities": 1},
+            "shared_blocks": [
+                {
+                    "file1_start_line": 0,
+                    "file1_end_line": lines,
+                    "file2_start_line": 0,
+                    "file2_end_line": lines,
+                    "similarity_score": 1.0,
+                }
+            ],
+        }
+
+
+def fragment(row: dict) -> SimpleNamespace:
+    fields = {"file1_start_line": None, "file1_end_line": None, "file2_start_line": None, "file2_end_line": None}
+    return SimpleNamespace(**{**fields, **row})
+
+
+class TestShortPaths(unittest.TestCase):
+    """Tests for the display names of paths shown together"""
+
+    def test_file_names_are_kept_when_unique(self):
+        """Paths whose file name is unique show their file name only."""
+        names = short_paths(["src/main.py", "src/app/utils.py"])
+
+        self.assertEqual(names, {"src/main.py": "main.py", "src/app/utils.py": "utils.py"})
+
+    def test_duplicate_names_get_their_parent_directories(self):
+        """Files of the same name get as many parent directories as needed to tell them apart."""
+        names = short_paths(["pkg/utils.py", "utils.py", "a/x/utils.py", "b/x/utils.py", "x/utils.py", "main.py"])
+
+        self.assertEqual(
+            names,
+            {
+                "pkg/utils.py": "pkg/utils.py",
+                "utils.py": "utils.py",
+                "a/x/utils.py": "a/x/utils.py",
+                "b/x/utils.py": "b/x/utils.py",
+                "x/utils.py": "x/utils.py",
+                "main.py": "main.py",
+            },
+        )
+        self.assertEqual(len(set(names.values())), len(names))
+        self.assertEqual(short_paths(["a/b/c/d.py", "z/b/c/d.py", "e.py"])["a/b/c/d.py"], "a/b/c/d.py")
+        packages = short_paths(["lib/one/__init__.py", "lib/two/__init__.py"])
+        self.assertEqual(packages["lib/two/__init__.py"], "two/__init__.py")
+
+
+class TestDuplicateFileNames(unittest.TestCase):
+    """Tests the pairing, storage and report of a submission with two utils.py, only the nested one copied"""
+
+    def setUp(self):
+        self.submission_a = SimpleNamespace(id=uuid4(), project_uuid=uuid4())
+        self.submission_b = SimpleNamespace(id=uuid4(), project_uuid=self.submission_a.project_uuid)
+        self.storage = SubmissionStorageService(InMemorySubmissionStore())
+        self.storage.ingest_directory(self.submission_a, FIXTURE / "submission_a")
+        self.storage.ingest_directory(self.submission_b, FIXTURE / "submission_b")
+
+        # Compared like detection does, from the materialized copies of the stored submissions
+        self.root_a = self.storage.materialize(self.submission_a)
+        self.root_b = self.storage.materialize(self.submission_b)
+        service = DetectionIntegrationService.__new__(DetectionIntegrationService)
+        service.visualization_service = IdenticalFilesVisualization()
+        self.visualization = service._visualize_file_pairs(
+            sorted(self.root_a.rglob("*.py")), sorted(self.root_b.rglob("*.py")), self.root_a, self.root_b
+        )
+        self.compared_names = service.visualization_service.names
+        self.fragments = [fragment(row) for row in extract_fragments(self.visualization)]
+
+    def tearDown(self):
+        shutil.rmtree(self.root_a, ignore_errors=True)
+        shutil.rmtree(self.root_b, ignore_errors=True)
+
+    def test_pairs_are_keyed_by_path(self):
+        """File pairs and fragments name the nested utils.py, never its namesake at the root."""
+        self.assertIn(("pkg/utils.py", "utils.py"), self.compared_names)
+        self.assertIn(("utils.py", "utils.py"), self.compared_names)
+        self.assertEqual(
+            [(f.fragment_type, f.file1_path, f.file2_path) for f in self.fragments],
+            [(FragmentType.FILE, "pkg/utils.py", "utils.py"), (FragmentType.BLOCK, "pkg/utils.py", "utils.py")],
+        )
+
+    def test_file_content_is_read_by_path(self):
+        """Each path reads its own file, names recorded without directory resolve only when unambiguous."""
+        read = stored_source_reader(
+            self.storage, {str(self.submission_a.id): self.submission_a, str(self.submission_b.id): self.submission_b}
+        )
+        submission_a = str(self.submission_a.id)
+
+        self.assertIn("moving_average", read(submission_a, "pkg/utils.py"))
+        self.assertIn("greet", read(submission_a, "utils.py"))
+        self.assertIn("moving_average", read(str(self.submission_b.id), "main.py"))
+        self.assertIsNone(read(submission_a, "other/utils.py"))
+
+    def test_report_points_at_the_nested_file(self):
+        """The report shows the code of pkg/utils.py under its disambiguated name."""
+        pair = SimpleNamespace(
+            id=uuid4(),
+            run_id=uuid4(),
+            submission_id=self.submission_a.id,
+            compared_submission_id=self.submission_b.id,
+            submitted_by_uuid=None,
+            compared_submitted_by_uuid=None,
+            overall_similarity=0.9,
+            jaccard_similarity=0.9,
+            structural_similarity=0.9,
+            fragments_count=2,
+            compared_files={
+                "submission1": {"pkg/utils.py": 60, "utils.py": 20},
+                "submission2": {"main.py": 15, "utils.py": 60},
+            },
+        )
+        run = SimpleNamespace(
+            id=uuid4(),
+            project_uuid=self.submission_a.project_uuid,
+            project_step_uuid=uuid4(),
+            status=DetectionRunStatus.COMPLETED,
+            started_at="2024-01-15T10:30:00+01:00",
+            finished_at="2024-01-15T10:32:00+01:00",
+            total_pairs=1,
+            completed_pairs=1,
+            failed_pairs=0,
+        )
+        participants = [
+            SimpleNamespace(submission_id=submission.id, submitted_by_uuid=None)
+            for submission in (self.submission_a, self.submission_b)
+        ]
+        read = stored_source_reader(
+            self.storage, {str(self.submission_a.id): self.submission_a, str(self.submission_b.id): self.submission_b}
+        )
+        document = render_run_report(run, participants, [pair], {str(pair.id): self.fragments}, read, threshold=0.5)
+
+        self.assertIn('<td title="pkg/utils.py">pkg/utils.py</td>', document)
+        self.assertIn('<td title="utils.py">utils.py</td>', document)
+        self.assertIn("<summary>pkg/utils.py and utils.py, 1.000</summary>", document)
+        self.assertIn("<h4>pkg/utils.py, lines 1 to 11</h4>", document)
+        self.assertIn("moving_average", document)
+        self.assertNotIn("greet", document)
+
+        heatmap = build_heatmap(pair, self.fragments)
+        rows = [(row.path, row.label) for row in heatmap.rows]
+        self.assertEqual(rows, [("pkg/utils.py", "pkg/utils.py"), ("utils.py", "utils.py")])
+        self.assertEqual([column.path for column in heatmap.columns], ["main.py", "utils.py"])
+        self.assertEqual(heatmap.matrix, [[0.0, 1.0], [0.0, 0.0]])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,222 @@
+"""
+Tests for the DOT and GraphML exports of the similarity graph of a run
+"""
+
+import json
+import re
+import unittest
+import xml.etree.ElementTree as ElementTree
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.dto.report_dto import GraphFormat
+from app.domains.reports.graph_export import CHUNK_SIZE, dot_quote, export_graph
+from app.domains.reports.html_report import render_run_report
+from app.domains.runs.runs_models import DetectionRunStatus
+
+DOT_TOKEN = re.compile(r'\s*(?:"((?:[^"\\]|\\.)*)"|(--|[{}\[\];,=])|([A-Za-z0-9_.]+))', re.S)
+GRAPHML_NAMESPACES = {"g": "http://graphml.graphdrawing.org/xmlns"}
+
+
+def parse_dot(document: str):
+    """Minimal parser of the DOT subset of the export, returns the graph name, nodes and edges with their attributes"""
+    tokens = []
+    position = 0
+    while document[position:].strip():
+        match = DOT_TOKEN.match(document, position)
+        if not match:
+            raise ValueError(f"Unexpected DOT at {document[position:position + 20]!r}")
+        quoted, punctuation, word = match.groups()
+        if quoted is not None:
+            tokens.append(("id", re.sub(r"\\(.)", lambda m: "\n" if m.group(1) == "n" else m.group(1), quoted)))
+        else:
+            tokens.append(("op", punctuation) if punctuation else ("id", word))
+        position = match.end()
+
+    def attributes(index):
+        values = {}
+        if index < len(tokens) and tokens[index] == ("op", "["):
+            index += 1
+            while tokens[index] != ("op", "]"):
+                values[tokens[index][1]] = tokens[index + 2][1]
+                index += 3
+                if tokens[index] == ("op", ","):
+                    index += 1
+            index += 1
+        return values, index
+
+    assert tokens[0] == ("id", "graph") and tokens[2] == ("op", "{") and tokens[-1] == ("op", "}")
+    nodes, edges = {}, []
+    index = 3
+    while index < len(tokens) - 1:
+        kind, value = tokens[index]
+        if kind == "id" and value in ("node", "edge", "graph"):
+            _, index = attributes(index + 1)
+        elif index + 1 < len(tokens) and tokens[index + 1] == ("op", "--"):
+            target = tokens[index + 2][1]
+            values, index = attributes(index + 3)
+            edges.append((value, target, values))
+        else:
+            values, index = attributes(index + 1)
+            nodes[value] = values
+        assert tokens[index] == ("op", ";"), tokens[index]
+        index += 1
+    return tokens[1][1], nodes, edges
+
+
+class TestGraphExport(unittest.TestCase):
+    """Tests for the similarity graph exports, parsed back and compared with the HTML report"""
+
+    def setUp(self):
+        self.run = SimpleNamespace(
+            id=uuid4(),
+            project_uuid=uuid4(),
+            project_step_uuid=uuid4(),
+            status=DetectionRunStatus.COMPLETED,
+            started_at="2024-01-15T10:30:00+01:00",
+            finished_at="2024-01-15T10:32:00+01:00",
+            total_pairs=4,
+            completed_pairs=4,
+            failed_pairs=0,
+        )
+        self.submissions = [uuid4() for _ in range(5)]
+        self.participants = [SimpleNamespace(submission_id=s, submitted_by_uuid=uuid4()) for s in self.submissions]
+        self.pairs = [
+            self.pair(0, 1, 0.92, 120),
+            self.pair(1, 2, 0.71, None),
+            self.pair(3, 4, 0.64, 35),
+            self.pair(2, 3, 0.2, 4),
+        ]
+
+    def pair(self, first, second, similarity, matched_tokens):
+        return SimpleNamespace(
+            id=uuid4(),
+            submission_id=self.submissions[first],
+            compared_submission_id=self.submissions[second],
+            submitted_by_uuid=None,
+            compared_submitted_by_uuid=None,
+            overall_similarity=similarity,
+            jaccard_similarity=similarity,
+            structural_similarity=similarity,
+            fragments_count=0,
+            matched_tokens=matched_tokens,
+        )
+
+    def export(self, graph_format, anonymize=False, threshold=0.5):
+        pseudonyms = RunPseudonyms(self.run.id, self.participants) if anonymize else None
+        return "".join(
+            export_graph(graph_format, f"run {self.run.id}", self.participants, self.pairs, threshold, pseudonyms)
+        )
+
+    def report_data(self, threshold=0.5):
+        report = render_run_report(
+            self.run,
+            self.participants,
+            [p for p in self.pairs if p.overall_similarity >= threshold],
+            {},
+            lambda submission_id, path: None,
+            threshold,
+        )
+        return json.loads(re.search(r'id="report-data">(.*?)</script>', report, re.S).group(1))
+
+    def test_dot_counts_match_the_report(self):
+        """The DOT export has a node per participant and an edge per pair flagged by the report."""
+        name, nodes, edges = parse_dot(self.export(GraphFormat.DOT))
+        data = self.report_data()
+
+        self.assertEqual(name, f"run {self.run.id}")
+        self.assertEqual(len(nodes), len(self.participants))
+        self.assertEqual(len(edges), len(data["pairs"]))
+        self.assertEqual(
+            {(source, target) for source, target, _ in edges},
+            {(p["submission_id"], p["compared_submission_id"]) for p in data["pairs"]},
+        )
+        self.assertEqual(edges[0][2], {"similarity": "0.9200", "label": "0.920", "matched_tokens": "120"})
+        self.assertNotIn("matched_tokens", edges[1][2])
+
+    def test_clusters_are_node_attributes(self):
+        """Nodes carry the cluster of the report, unflagged submissions have none."""
+        _, nodes, _ = parse_dot(self.export(GraphFormat.DOT))
+        clusters = {p["submission_id"]: p["cluster"] for p in self.report_data()["pairs"]}
+
+        self.assertEqual(nodes[str(self.submissions[0])]["cluster"], str(clusters[str(self.submissions[0])]))
+        self.assertEqual([nodes[str(s)].get("cluster") for s in self.submissions], ["1", "1", "1", "2", "2"])
+        self.participants.append(SimpleNamespace(submission_id=uuid4(), submitted_by_uuid=None))
+        _, nodes, _ = parse_dot(self.export(GraphFormat.DOT))
+        self.assertEqual(set(nodes[str(self.participants[-1].submission_id)]), {"label"})
+
+    def test_threshold_filters_edges(self):
+        """Raising the threshold drops edges but keeps every node."""
+        _, nodes, edges = parse_dot(self.export(GraphFormat.DOT, threshold=0.9))
+
+        self.assertEqual(len(nodes), len(self.participants))
+        self.assertEqual(len(edges), len(self.report_data(0.9)["pairs"]))
+
+    def test_graphml_counts_match_the_report(self):
+        """The GraphML export is valid XML with the same nodes and edges, and typed keys for their attributes."""
+        root = ElementTree.fromstring(self.export(GraphFormat.GRAPHML))
+        graph = root.find("g:graph", GRAPHML_NAMESPACES)
+        nodes = graph.findall("g:node", GRAPHML_NAMESPACES)
+        edges = graph.findall("g:edge", GRAPHML_NAMESPACES)
+
+        self.assertEqual(graph.get("edgedefault"), "undirected")
+        self.assertEqual(len(nodes), len(self.participants))
+        self.assertEqual(len(edges), len(self.report_data()["pairs"]))
+        keys = {key.get("id"): key.get("attr.type") for key in root.findall("g:key", GRAPHML_NAMESPACES)}
+        self.assertEqual(keys["similarity"], "double")
+        data = {d.get("key"): d.text for d in edges[0].findall("g:data", GRAPHML_NAMESPACES)}
+        self.assertEqual(data, {"similarity": "0.9200", "matched_tokens": "120"})
+
+    def test_anonymized_exports_use_stable_pseudonyms(self):
+        """Pseudonyms replace submission and submitter identifiers, the same ones in every export of the run."""
+        document = self.export(GraphFormat.DOT, anonymize=True)
+        _, nodes, edges = parse_dot(document)
+
+        for participant in self.participants:
+            self.assertNotIn(str(participant.submission_id), document)
+            self.assertNotIn(str(participant.submitted_by_uuid), document)
+        self.assertEqual(len(nodes), len(self.participants))
+        self.assertTrue(all(source in nodes and target in nodes for source, target, _ in edges))
+        self.assertEqual(len({values["submitter"] for values in nodes.values()}), len(self.participants))
+        self.assertEqual(set(parse_dot(self.export(GraphFormat.DOT, anonymize=True))[1]), set(nodes))
+        self.assertTrue(all(re.fullmatch(r"Submission \d{3}", node) for node in nodes))
+
+    def test_hostile_labels_are_escaped(self):
+        """Quotes, backslashes, line breaks and markup in identifiers survive both formats unchanged."""
+        hostile = 'a "quoted" \\ name\n</node><node id="x">'
+        self.participants.append(SimpleNamespace(submission_id=hostile, submitted_by_uuid=None))
+        self.pairs.append(
+            SimpleNamespace(
+                submission_id=hostile,
+                compared_submission_id=self.submissions[0],
+                overall_similarity=0.8,
+                matched_tokens=1,
+            )
+        )
+
+        _, nodes, edges = parse_dot(self.export(GraphFormat.DOT))
+        self.assertIn(hostile, nodes)
+        self.assertIn((hostile, str(self.submissions[0])), {(s, t) for s, t, _ in edges})
+        self.assertEqual(dot_quote('\\"'), '"\\\\\\""')
+
+        graph = ElementTree.fromstring(self.export(GraphFormat.GRAPHML)).find("g:graph", GRAPHML_NAMESPACES)
+        self.assertEqual(len(graph.findall("g:node", GRAPHML_NAMESPACES)), len(self.participants))
+        self.assertIn(hostile, {node.get("id") for node in graph.findall("g:node", GRAPHML_NAMESPACES)})
+
+    def test_large_graphs_are_streamed_in_chunks(self):
+        """Thousands of edges are produced as several chunks that join into the full document."""
+        self.submissions = [uuid4() for _ in range(100)]
+        self.participants = [SimpleNamespace(submission_id=s, submitted_by_uuid=uuid4()) for s in self.submissions]
+        self.pairs = [self.pair(i, j, 0.75, 10) for i in range(100) for j in range(i + 1, 100)]
+
+        chunks = list(export_graph(GraphFormat.DOT, "large", self.participants, self.pairs, 0.5))
+
+        self.assertGreater(len(chunks), 1)
+        self.assertTrue(all(len(chunk) < 2 * CHUNK_SIZE for chunk in chunks))
+        _, nodes, edges = parse_dot("".join(chunks))
+        self.assertEqual((len(nodes), len(edges)), (100, 4950))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,375 @@
+"""
+Tests for the self-contained HTML report of a run, its highlighting and its clusters
+"""
+
+import json
+import unittest
+from html.parser import HTMLParser
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.clusters import find_clusters
+from app.domains.reports.highlighting import highlight_lines
+from app.domains.reports.html_report import MAX_FRAGMENT_LINES, render_run_report
+from app.domains.runs.runs_models import DetectionRunStatus, FragmentType
+from app.shared.i18n import Locale
+
+# Elements without end tag
+VOID_ELEMENTS = {"area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"}
+
+HOSTILE_SOURCE = (
+    "def render():\n"
+    '    html = "</script><script>alert(1)</script>"\n'
+    "    return html  # <!-- & -->\n"
+)
+
+
+class ReportParser(HTMLParser):
+    """Parser checking that every element is closed in order, collecting scripts and the text of code cells"""
+
+    def __init__(self):
+        super().__init__(convert_charrefs=True)
+        self.stack = []
+        self.errors = []
+        self.scripts = []
+        self.attributes = []
+        self.code_text = []
+        self._script = None
+        self._code_depth = 0
+
+    def handle_starttag(self, tag, attrs):
+        self.attributes.extend((tag, name, value) for name, value in attrs)
+        if tag in VOID_ELEMENTS:
+            return
+        self.stack.append(tag)
+        if tag == "script":
+            self._script = {"attrs": dict(attrs), "text": ""}
+        if tag == "table" and ("class", "code") in attrs:
+            self._code_depth = len(self.stack)
+
+    def handle_startendtag(self, tag, attrs):
+        if tag not in VOID_ELEMENTS:
+            self.errors.append(f"self-closed <{tag}/>")
+
+    def handle_endtag(self, tag):
+        if tag in VOID_ELEMENTS:
+            self.errors.append(f"end tag of void element {tag}")
+            return
+        if not self.stack or self.stack[-1] != tag:
+            self.errors.append(f"</{tag}> closes <{self.stack[-1] if self.stack else None}>")
+            return
+        if self._code_depth == len(self.stack):
+            self._code_depth = 0
+        self.stack.pop()
+        if tag == "script":
+            self.scripts.append(self._script)
+            self._script = None
+
+    def handle_data(self, data):
+        if self._script is not None:
+            self._script["text"] += data
+        elif self._code_depth:
+            self.code_text.append(data)
+
+
+def parse(document: str) -> ReportParser:
+    parser = ReportParser()
+    parser.feed(document)
+    parser.close()
+    return parser
+
+
+class TestHighlighting(unittest.TestCase):
+    """Tests for the lexical highlighting of source files"""
+
+    def test_lines_stay_aligned_with_the_source(self):
+        """Tokens spanning lines are split per line, the output has one entry per source line."""
+        source = '/* first\n   second */\nint main() {\n  return "a\\\n b";\n}\n'
+
+        lines = highlight_lines(source, "main.c")
+
+        self.assertEqual(len(lines), len(source.split("\n")))
+        self.assertEqual(lines[0], '<span class="c">/* first</span>')
+        self.assertEqual(lines[1], '<span class="c">   second */</span>')
+        self.assertIn('<span class="k">int</span>', lines[2])
+
+    def test_unknown_languages_are_escaped_plain_text(self):
+        """Files without a lexer are escaped without spans, CRLF line endings are normalized."""
+        self.assertEqual(highlight_lines("a <b> & c\r\nd", "notes.unknown"), ["a &lt;b&gt; &amp; c", "d"])
+
+    def test_string_literals_are_escaped(self):
+        """Markup inside string literals and comments is escaped."""
+        lines = highlight_lines(HOSTILE_SOURCE, "render.py")
+
+        self.assertNotIn("<script>", "".join(lines))
+        self.assertIn("&lt;/script&gt;", lines[1])
+        self.assertIn('<span class="c"># &lt;!-- &amp; --&gt;</span>', lines[2])
+
+
+class TestClusters(unittest.TestCase):
+    """Tests for the clusters of flagged pairs"""
+
+    def pair(self, first, second, similarity):
+        return SimpleNamespace(submission_id=first, compared_submission_id=second, overall_similarity=similarity)
+
+    def test_chains_of_flagged_pairs_form_one_cluster(self):
+        """Submissions linked through other submissions share a cluster, pairs below the threshold do not link."""
+        pairs = [
+            self.pair("a", "b", 0.9),
+            self.pair("b", "c", 0.6),
+            self.pair("d", "e", 0.95),
+            self.pair("c", "d", 0.2),
+        ]
+
+        clusters = find_clusters(pairs, 0.5)
+
+        self.assertEqual([c.members for c in clusters], [["a", "b", "c"], ["d", "e"]])
+        self.assertEqual([(c.pair_count, c.max_similarity) for c in clusters], [(2, 0.9), (1, 0.95)])
+
+
+class TestRunReport(unittest.TestCase):
+    """Tests for the rendering of the HTML report of a run"""
+
+    def setUp(self):
+        self.run = SimpleNamespace(
+            id=uuid4(),
+            project_uuid=uuid4(),
+            project_step_uuid=uuid4(),
+            status=DetectionRunStatus.COMPLETED,
+            started_at="2024-01-15T10:30:00+01:00",
+            finished_at="2024-01-15T10:32:00+01:00",
+            total_pairs=3,
+            completed_pairs=3,
+            failed_pairs=0,
+        )
+        self.submissions = [uuid4() for _ in range(3)]
+        self.participants = [SimpleNamespace(submission_id=s, submitted_by_uuid=uuid4()) for s in self.submissions]
+        self.pairs = [
+            self.pair(self.submissions[0], self.submissions[1], 0.92),
+            self.pair(self.submissions[1], self.submissions[2], 0.71),
+        ]
+        self.sources = {
+            (str(self.submissions[0]), "render.py"): HOSTILE_SOURCE,
+            (str(self.submissions[1]), "view.py"): HOSTILE_SOURCE.replace("render", "view"),
+        }
+        self.fragments = {
+            str(self.pairs[0].id): [
+                self.fragment(FragmentType.FILE, "render.py", "view.py", None, None, similarity=0.9),
+                self.fragment(FragmentType.BLOCK, "render.py", "view.py", (0, 2), (0, 2), similarity=0.95),
+            ],
+            str(self.pairs[1].id): [
+                self.fragment(FragmentType.BLOCK, "view.py", "<img src=x onerror=alert(1)>.py", (1, 1), (0, 0)),
+            ],
+        }
+
+    def pair(self, first, second, similarity):
+        return SimpleNamespace(
+            id=uuid4(),
+            submission_id=first,
+            compared_submission_id=second,
+            submitted_by_uuid=None,
+            compared_submitted_by_uuid=None,
+            overall_similarity=similarity,
+            jaccard_similarity=similarity,
+            structural_similarity=similarity,
+            fragments_count=1,
+        )
+
+    def fragment(self, fragment_type, file1, file2, lines1, lines2, similarity=0.8):
+        return SimpleNamespace(
+            fragment_type=fragment_type,
+            file1_path=file1,
+            file2_path=file2,
+            file1_start_line=lines1[0] if lines1 else None,
+            file1_end_line=lines1[1] if lines1 else None,
+            file2_start_line=lines2[0] if lines2 else None,
+            file2_end_line=lines2[1] if lines2 else None,
+            similarity=similarity,
+            details={"file1_function": "render", "file2_function": "</summary>"},
+        )
+
+    def render(self, **kwargs):
+        return render_run_report(
+            self.run,
+            self.participants,
+            self.pairs,
+            self.fragments,
+            lambda submission_id, path: self.sources.get((submission_id, path)),
+            threshold=0.5,
+            **kwargs,
+        )
+
+    def test_report_is_well_formed(self):
+        """Every element is closed in order and the embedded data parses and lists the flagged pairs."""
+        parser = parse(self.render(omitted_pairs=4))
+
+        self.assertEqual(parser.errors, [])
+        self.assertEqual(parser.stack, [])
+        data = json.loads(next(s["text"] for s in parser.scripts if s["attrs"].get("id") == "report-data"))
+        self.assertEqual([p["id"] for p in data["pairs"]], [str(p.id) for p in self.pairs])
+        self.assertEqual([p["cluster"] for p in data["pairs"]], [1, 1])
+
+    def test_report_is_self_contained(self):
+        """The report references no external resource, links only point inside the document."""
+        parser = parse(self.render())
+
+        self.assertEqual([a for a in parser.attributes if a[1] in ("src", "srcset", "action", "data")], [])
+        self.assertTrue(all(value.startswith("#") for _, name, value in parser.attributes if name == "href"))
+        self.assertNotIn("link", {tag for tag, _, _ in parser.attributes})
+        self.assertNotIn("url(", self.render())
+
+    def test_script_end_tags_in_submissions_cannot_break_out(self):
+        """A </script> in a string literal or a file name stays text: only the two report scripts exist."""
+        document = self.render()
+        parser = parse(document)
+
+        self.assertEqual(parser.errors, [])
+        self.assertEqual(len(parser.scripts), 2)
+        self.assertNotIn("alert(1)", "".join(s["text"] for s in parser.scripts if "id" not in s["attrs"]))
+        self.assertEqual(document.count("<script"), 2)
+        self.assertNotIn("<img", document)
+        # The code reads as written once the entities are decoded
+        self.assertIn('"</script><script>alert(1)</script>"', "".join(parser.code_text))
+
+        data = json.loads(next(s["text"] for s in parser.scripts if s["attrs"].get("id") == "report-data"))
+        self.assertNotIn("</", next(s["text"] for s in parser.scripts if s["attrs"].get("id") == "report-data"))
+        self.assertEqual(data["pairs"][0]["files"], [["render.py", "view.py", 0.9]])
+
+    def test_unreadable_sources_and_long_fragments(self):
+        """Sides whose file cannot be read are marked, fragments longer than the limit are cut."""
+        self.sources[(str(self.submissions[0]), "render.py")] = "\n".join(f"x = {i}" for i in range(500))
+        self.fragments[str(self.pairs[0].id)][1].file1_end_line = 450
+
+        document = self.render()
+
+        self.assertIn("Source not available.", document)
+        self.assertIn(f"Fragment cut after {MAX_FRAGMENT_LINES} lines.", document)
+        self.assertIn(f"lines 1 to {MAX_FRAGMENT_LINES}", document)
+        self.assertEqual(parse(document).errors, [])
+
+    def test_not_comparable_submissions_and_low_confidence_pairs(self):
+        """Submissions that were not compared are listed with their reason, low confidence pairs are marked."""
+        self.participants.append(
+            SimpleNamespace(submission_id=uuid4(), submitted_by_uuid=None, not_comparable_reason="comments_only")
+        )
+        self.pairs[1].low_confidence = True
+
+        document = self.render()
+
+        self.assertIn("Not comparable (1)", document)
+        self.assertIn("only comments in its files", document)
+        self.assertEqual(document.count("low confidence</span>"), 1)
+        self.assertEqual(parse(document).errors, [])
+
+    def test_late_submissions_are_listed(self):
+        """Submissions uploaded after their deadline are listed and marked in their pairs."""
+        document = self.render(late={str(self.submissions[1]): 75})
+
+        self.assertIn("Late submissions (1)", document)
+        self.assertIn(f"{self.submissions[1]}, 75 minutes late", document)
+        self.assertNotIn(f"{self.submissions[0]}, 75 minutes late", document)
+        self.assertNotIn("Late submissions", self.render())
+        self.assertEqual(parse(document).errors, [])
+
+    def test_file_errors_are_listed(self):
+        """Files that failed in the run are listed with their stage, partial pairs are marked."""
+        self.assertNotIn("File errors", self.render())
+        self.run.file_errors = [
+            {
+                "submission_id": str(self.submissions[2]),
+                "path": "src/<deep>.py",
+                "stage": "tokenization",
+                "message": "RecursionError: maximum recursion depth exceeded",
+            }
+        ]
+        self.pairs[1].partial = True
+
+        document = self.render()
+
+        self.assertIn("File errors (1)", document)
+        self.assertIn("<td>src/&lt;deep&gt;.py</td><td>tokenization</td>", document)
+        self.assertEqual(document.count("partial</span>"), 1)
+        self.assertEqual(parse(document).errors, [])
+
+    def test_timed_out_pairs_are_listed(self):
+        """Pairs stopped by a timeout are listed apart with their message, not among the scored pairs."""
+        self.assertNotIn("Timed out pairs", self.render())
+        stopped = self.pair(self.submissions[0], self.submissions[2], 0.0)
+        stopped.error_message = "Pair timed out after 900s"
+
+        document = self.render(timed_out_pairs=[stopped])
+
+        self.assertIn("Timed out pairs (1)", document)
+        self.assertIn("<td>Pair timed out after 900s</td>", document)
+        self.assertEqual(parse(document).errors, [])
+
+    def test_pairs_of_teams_sharing_a_student_are_self_matches(self):
+        """Pairs of two teams of a student are listed under self-similarity, not flagged, and teams are named."""
+        dave = uuid4()
+        self.participants[1].team_name, self.participants[1].team_members = "Team <Rocket>", [str(dave), str(uuid4())]
+        self.participants[2].team_name, self.participants[2].team_members = "Team Magma", [str(uuid4()), str(dave)]
+
+        document = self.render()
+        parser = parse(document)
+
+        self.assertEqual(parser.errors, [])
+        data = json.loads(next(s["text"] for s in parser.scripts if s["attrs"].get("id") == "report-data"))
+        self.assertEqual([p["id"] for p in data["pairs"]], [str(self.pairs[0].id)])
+        self.assertIn("Flagged pairs (1)", document)
+        self.assertIn("Self-similarity (1)", document)
+        self.assertIn(f"by team Team &lt;Rocket&gt; ({dave}, ", document)
+        self.assertEqual([p["cluster"] for p in data["pairs"]], [1])
+
+        anonymized = self.render(pseudonyms=RunPseudonyms(self.run.id, self.participants))
+        self.assertNotIn("Rocket", anonymized)
+        self.assertNotIn(str(dave), anonymized)
+        self.assertIn("by team of Student", anonymized)
+
+    def test_declared_collaborations_are_listed_apart(self):
+        """Pairs of declared collaborations leave the flagged pairs and clusters for their own section."""
+
+        def declared(pair):
+            return pair.submission_id == self.submissions[0]
+
+        document = self.render(declared=declared)
+        parser = parse(document)
+
+        self.assertEqual(parser.errors, [])
+        data = json.loads(next(s["text"] for s in parser.scripts if s["attrs"].get("id") == "report-data"))
+        self.assertEqual([p["id"] for p in data["pairs"]], [str(self.pairs[1].id)])
+        self.assertIn("Declared collaboration (1)", document)
+        self.assertNotIn("Declared collaboration", self.render())
+
+    def test_locales_share_the_machine_readable_content(self):
+        """English and French reports differ in their text only: embedded data, anchors, values and code are equal."""
+        self.participants.append(
+            SimpleNamespace(submission_id=uuid4(), submitted_by_uuid=None, not_comparable_reason="empty")
+        )
+        machine_readable = ("id", "href", "class", "data-type", "data-value", "aria-sort")
+        parsers = {}
+        for locale in Locale:
+            document = self.render(late={str(self.submissions[1]): 75}, locale=locale)
+            parsers[locale] = parse(document)
+            self.assertEqual(parsers[locale].errors, [])
+            self.assertIn(f'<html lang="{locale.value}">', document)
+
+        english, french = parsers[Locale.EN], parsers[Locale.FR]
+        self.assertEqual(
+            [a for a in english.attributes if a[1] in machine_readable],
+            [a for a in french.attributes if a[1] in machine_readable],
+        )
+        self.assertEqual(english.scripts, french.scripts)
+        self.assertEqual(english.code_text, french.code_text)
+
+        document = self.render(late={str(self.submissions[1]): 75}, locale=Locale.FR)
+        self.assertIn("Paires signalées (2)", document)
+        self.assertIn("aucun code dans ses fichiers", document)
+        self.assertIn(f"{self.submissions[1]}, 75 minutes de retard", document)
+        # Interpolated names are escaped like any other text
+        self.assertIn("render.py (render) et view.py (&lt;/summary&gt;), 0.950", document)
+        self.assertNotIn("Flagged pairs", document)
+
+
+if __name__ == "__main__":
+    unittest.main()
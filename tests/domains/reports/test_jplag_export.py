@@ -0,0 +1,182 @@
+"""
+Tests for the JPlag-compatible result archive of a run
+"""
+
+import io
+import json
+import unittest
+import zipfile
+from datetime import datetime, timedelta
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.jplag_export import DEFAULT_MATCH_SENSITIVITY, distribution, export_jplag
+from app.domains.runs.runs_models import FragmentType
+
+OVERVIEW_KEYS = {
+    "jplag_version",
+    "submission_folder_path",
+    "base_code_folder_path",
+    "language",
+    "file_extensions",
+    "submission_id_to_display_name",
+    "submission_ids_to_comparison_file_name",
+    "failed_submission_names",
+    "excluded_files",
+    "match_sensitivity",
+    "date_of_execution",
+    "execution_time",
+    "metrics",
+    "clusters",
+    "total_comparisons",
+}
+COMPARISON_KEYS = {"id1", "id2", "similarities", "matches", "first_similarity", "second_similarity"}
+MATCH_KEYS = {"file1", "file2", "start1", "end1", "start2", "end2", "tokens"}
+
+
+class TestJPlagExport(unittest.TestCase):
+    """Tests for the archive of a fixture run of three submissions, two flagged pairs and one below the threshold"""
+
+    def setUp(self):
+        started = datetime(2024, 1, 15, 10, 30)
+        self.run = SimpleNamespace(id=uuid4(), started_at=started, finished_at=started + timedelta(seconds=130))
+        self.participants = [SimpleNamespace(submission_id=uuid4(), submitted_by_uuid=uuid4()) for _ in range(3)]
+        ids = [p.submission_id for p in self.participants]
+        self.pairs = [
+            self.pair(ids[0], ids[1], 0.82, {"submission1": {"main.py": 200}, "submission2": {"app.py": 400}}),
+            self.pair(ids[1], ids[2], 0.64, None),
+        ]
+        self.fragments = {
+            str(self.pairs[0].id): [
+                self.fragment(FragmentType.FILE, "main.py", None, "app.py", None),
+                self.fragment(FragmentType.BLOCK, "main.py", (0, 9), "app.py", (20, 31), tokens=80),
+            ],
+            str(self.pairs[1].id): [self.fragment(FragmentType.BLOCK, "app.py", (2, 4), "lib/util.py", (5, 7))],
+        }
+        self.sources = {
+            (str(ids[0]), "main.py"): "print('/home/jdupont/data')\n",
+            (str(ids[1]), "app.py"): "print('hello')\n",
+        }
+
+    def pair(self, first, second, similarity, compared_files):
+        return SimpleNamespace(
+            id=uuid4(),
+            submission_id=first,
+            compared_submission_id=second,
+            overall_similarity=similarity,
+            compared_files=compared_files,
+        )
+
+    def fragment(self, fragment_type, path1, lines1, path2, lines2, tokens=None):
+        return SimpleNamespace(
+            id=uuid4(),
+            fragment_type=fragment_type,
+            file1_path=path1,
+            file2_path=path2,
+            file1_start_line=lines1[0] if lines1 else None,
+            file1_end_line=lines1[1] if lines1 else None,
+            file2_start_line=lines2[0] if lines2 else None,
+            file2_end_line=lines2[1] if lines2 else None,
+            similarity=0.9,
+            details={"matched_tokens": tokens} if tokens is not None else None,
+        )
+
+    def export(self, pseudonyms=None):
+        archive = export_jplag(
+            self.run,
+            self.participants,
+            self.pairs,
+            self.fragments,
+            [0.82, 0.64, 0.2],
+            lambda submission_id, path: self.sources.get((submission_id, path)),
+            0.5,
+            pseudonyms,
+        )
+        with zipfile.ZipFile(io.BytesIO(archive)) as zip_file:
+            return archive, {name: zip_file.read(name).decode("utf-8") for name in zip_file.namelist()}
+
+    def test_overview_has_the_keys_of_the_jplag_format(self):
+        """The overview carries every key the viewer reads, with scores, clusters and documented defaults."""
+        _, entries = self.export()
+        overview = json.loads(entries["overview.json"])
+        first, second, third = (str(p.submission_id) for p in self.participants)
+
+        self.assertEqual(set(overview), OVERVIEW_KEYS)
+        self.assertEqual(overview["match_sensitivity"], DEFAULT_MATCH_SENSITIVITY)
+        self.assertEqual(overview["execution_time"], 130000)
+        self.assertEqual(overview["total_comparisons"], 3)
+        self.assertEqual(overview["file_extensions"], [".py"])
+        self.assertEqual(overview["submission_id_to_display_name"][first], first[:8])
+        self.assertEqual(overview["submission_ids_to_comparison_file_name"][second][first], f"{first}-{second}.json")
+        self.assertEqual([metric["name"] for metric in overview["metrics"]], ["AVG", "MAX"])
+        for metric in overview["metrics"]:
+            self.assertEqual(set(metric), {"name", "distribution", "topComparisons", "description"})
+            self.assertEqual(len(metric["distribution"]), 100)
+        self.assertEqual(sum(overview["metrics"][0]["distribution"]), 3)
+        self.assertEqual(overview["metrics"][0]["topComparisons"][0]["similarity"], 0.82)
+        self.assertEqual(overview["clusters"][0]["members"], sorted([first, second, third]))
+        self.assertEqual(set(overview["clusters"][0]), {"average_similarity", "strength", "members"})
+
+    def test_comparisons_list_matches_with_their_token_positions(self):
+        """Comparison files give the directional similarities, 1-based line ranges and tokens of each block."""
+        _, entries = self.export()
+        first, second = (str(p.submission_id) for p in self.participants[:2])
+        comparison = json.loads(entries[f"{first}-{second}.json"])
+
+        self.assertEqual(set(comparison), COMPARISON_KEYS)
+        self.assertEqual(comparison["similarities"], {"AVG": 0.82, "MAX": 0.4})
+        self.assertEqual((comparison["first_similarity"], comparison["second_similarity"]), (0.4, 0.2))
+        [match] = comparison["matches"]
+        self.assertEqual(set(match), MATCH_KEYS)
+        self.assertEqual(
+            match,
+            {
+                "file1": f"{first}/main.py",
+                "file2": f"{second}/app.py",
+                "start1": 1,
+                "end1": 10,
+                "start2": 21,
+                "end2": 32,
+                "tokens": 80,
+            },
+        )
+
+    def test_sources_and_file_index_cover_the_matched_files(self):
+        """Every file a match points to is in the archive, empty when it is no longer stored."""
+        _, entries = self.export()
+        index = json.loads(entries["submissionFileIndex.json"])["submission_file_indexes"]
+
+        for name in entries:
+            if name.endswith(".json") and name not in ("overview.json", "submissionFileIndex.json"):
+                for match in json.loads(entries[name])["matches"]:
+                    for file_name in (match["file1"], match["file2"]):
+                        self.assertIn(f"submissions/{file_name}", entries)
+                        self.assertIn(file_name, index[file_name.split("/")[0]])
+        third = str(self.participants[2].submission_id)
+        self.assertEqual(entries[f"submissions/{third}/lib/util.py"], "")
+        self.assertEqual(index[third][f"{third}/lib/util.py"], {"token_count": 0})
+
+    def test_anonymized_archive_uses_the_pseudonyms(self):
+        """Anonymized archives name submissions by pseudonym and scrub the sources, with the same bytes each time."""
+        pseudonyms = RunPseudonyms(self.run.id, self.participants)
+        archive, entries = self.export(pseudonyms)
+        again, _ = self.export(pseudonyms)
+        document = "".join(entries) + "".join(entries.values())
+
+        self.assertEqual(archive, again)
+        for participant in self.participants:
+            self.assertNotIn(str(participant.submission_id)[:8], document)
+        self.assertNotIn("jdupont", document)
+        first = pseudonyms(self.participants[0].submission_id)
+        self.assertIn(f"submissions/{first}/main.py", entries)
+
+    def test_distribution_is_highest_first(self):
+        """Scores fall in buckets of 1% from the highest, a perfect score in the first one."""
+        buckets = distribution([1.0, 0.995, 0.5, 0.0])
+
+        self.assertEqual((buckets[0], buckets[49], buckets[99]), (2, 1, 1))
+
+
+if __name__ == "__main__":
+    unittest.main()
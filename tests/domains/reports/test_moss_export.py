@@ -0,0 +1,121 @@
+"""
+Tests for the Moss-compatible export of a run
+"""
+
+import io
+import re
+import unittest
+import zipfile
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.moss_export import MAPPING_NOTES, export_moss
+from app.domains.runs.runs_models import FragmentType
+
+INDEX_ROW = re.compile(
+    r'<TR><TD><A HREF="match(\d+)\.html">(\S+)/ \((\d+)%\)</A>\s*'
+    r'<TD><A HREF="match\1\.html">(\S+)/ \((\d+)%\)</A>\s*<TD ALIGN=right>(\d+)'
+)
+MATCH_ROW = re.compile(r"<TR><TD>(\S+) (\d+)-(\d+)\s*<TD>(\S+) (\d+)-(\d+)\s*<TD ALIGN=right>([\d.]+)")
+
+
+class TestMossExport(unittest.TestCase):
+    """Tests for the Moss pages of a corpus of three submissions and two flagged pairs"""
+
+    def setUp(self):
+        self.run = SimpleNamespace(id=uuid4())
+        self.participants = [SimpleNamespace(submission_id=uuid4(), submitted_by_uuid=uuid4()) for _ in range(3)]
+        ids = [p.submission_id for p in self.participants]
+        self.pairs = [
+            self.pair(ids[0], ids[1], 0.82, {"submission1": {"a.py": 300, "b.py": 100}, "submission2": {"a.py": 200}}),
+            self.pair(ids[1], ids[2], 0.61, None),
+        ]
+        self.fragments = {
+            str(self.pairs[0].id): [
+                self.fragment(FragmentType.FILE, "a.py", None, "a.py", None),
+                self.fragment(FragmentType.BLOCK, "b.py", (30, 39), "a.py", (0, 9), tokens=60),
+                self.fragment(FragmentType.BLOCK, "a.py", (0, 19), "a.py", (4, 23), tokens=140),
+                # Overlaps the first lines of the previous block on the left side
+                self.fragment(FragmentType.BLOCK, "a.py", (10, 24), "a.py", (40, 54), tokens=40),
+            ],
+            str(self.pairs[1].id): [self.fragment(FragmentType.BLOCK, "c.py", (5, 5), "main.py", (7, 7))],
+        }
+
+    def pair(self, first, second, similarity, compared_files):
+        return SimpleNamespace(
+            id=uuid4(),
+            submission_id=first,
+            compared_submission_id=second,
+            overall_similarity=similarity,
+            compared_files=compared_files,
+        )
+
+    def fragment(self, fragment_type, path1, lines1, path2, lines2, tokens=None):
+        return SimpleNamespace(
+            id=uuid4(),
+            fragment_type=fragment_type,
+            file1_path=path1,
+            file2_path=path2,
+            file1_start_line=lines1[0] if lines1 else None,
+            file1_end_line=lines1[1] if lines1 else None,
+            file2_start_line=lines2[0] if lines2 else None,
+            file2_end_line=lines2[1] if lines2 else None,
+            similarity=0.9,
+            details={"matched_tokens": tokens} if tokens is not None else None,
+        )
+
+    def export(self, **kwargs):
+        archive = export_moss(self.run, self.participants, self.pairs, self.fragments, 0.5, **kwargs)
+        with zipfile.ZipFile(io.BytesIO(archive)) as zip_file:
+            return archive, {name: zip_file.read(name).decode("utf-8") for name in zip_file.namelist()}
+
+    def test_index_lists_directional_percentages_and_lines(self):
+        """The index has one row per pair, most similar first, with token-based percentages per side."""
+        _, pages = self.export()
+
+        self.assertEqual(sorted(pages), ["index.html", "match0.html", "match1.html"])
+        labels = [(str(p.submission_id)[:8], str(p.compared_submission_id)[:8]) for p in self.pairs]
+        # 240 matched tokens of 400 and 200, 35 distinct lines on the left and 39 on the right
+        self.assertEqual(
+            INDEX_ROW.findall(pages["index.html"]),
+            [
+                ("0", labels[0][0], "60", labels[0][1], "100", "39"),
+                ("1", labels[1][0], "61", labels[1][1], "61", "1"),
+            ],
+        )
+        for note in MAPPING_NOTES:
+            self.assertIn(note, pages["index.html"])
+
+    def test_match_ranges_agree_with_fragment_spans(self):
+        """Each located block is listed once with its 1-based inclusive ranges, file fragments are not listed."""
+        _, pages = self.export()
+
+        for number, pair in enumerate(self.pairs):
+            listed = sorted(
+                (left, int(first1) - 1, int(last1) - 1, right, int(first2) - 1, int(last2) - 1)
+                for left, first1, last1, right, first2, last2, _ in MATCH_ROW.findall(pages[f"match{number}.html"])
+            )
+            blocks = sorted(
+                (f.file1_path, f.file1_start_line, f.file1_end_line, f.file2_path, f.file2_start_line, f.file2_end_line)
+                for f in self.fragments[str(pair.id)]
+                if f.fragment_type == FragmentType.BLOCK
+            )
+            self.assertEqual(listed, blocks)
+
+    def test_export_is_deterministic_and_can_be_anonymized(self):
+        """The same run gives the same bytes, and anonymized pages show pseudonyms only."""
+        archive, _ = self.export()
+        again, _ = self.export()
+        self.assertEqual(archive, again)
+
+        pseudonyms = RunPseudonyms(self.run.id, self.participants)
+        _, pages = self.export(pseudonyms=pseudonyms)
+        document = "".join(pages.values())
+        for participant in self.participants:
+            self.assertNotIn(str(participant.submission_id)[:8], document)
+        self.assertIn(f"{pseudonyms(self.pairs[0].submission_id)}/ (60%)", pages["index.html"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,244 @@
+"""
+Tests for ReportService, rendering reports on request or in the background into the submission store
+"""
+
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+from uuid import uuid4
+
+from sqlmodel import Session, SQLModel
+
+from app.domains.reports.dto.report_dto import GraphFormat, ReportPendingDto
+from app.domains.reports.report_service import ReportGenerationException, ReportService, run_reports_prefix
+from app.domains.runs.run_recorder import DetectionRunRecorder
+from app.domains.runs.runs_models import DetectionRunStatus, FragmentType
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.submissions_models import SimilarityStatus, Submission
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.shared.concurrency import JobScheduler
+from app.shared.exceptions import ValidationException
+from tests.helpers import create_test_engine
+
+SOURCE = 'def greet(name):\n    return "</script>" + name\n'
+
+
+class TestReportService(unittest.TestCase):
+    """Tests for synchronous and background report rendering with an in-memory store"""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+        self.storage = SubmissionStorageService(InMemorySubmissionStore(), hash_algorithm="sha256")
+        self.directory = Path(tempfile.mkdtemp(prefix="test_reports_"))
+
+        self.project_uuid = uuid4()
+        self.project_step_uuid = uuid4()
+        self.submissions = [self.create_submission() for _ in range(2)]
+        repository = DetectionRunRepository(self.session)
+        self.run = repository.create_run(
+            {
+                "project_uuid": self.project_uuid,
+                "project_step_uuid": self.project_step_uuid,
+                "status": DetectionRunStatus.COMPLETED,
+                "total_pairs": 1,
+            },
+            [{"submission_id": s.id, "group_uuid": s.group_uuid} for s in self.submissions],
+        )
+        recorder = DetectionRunRecorder(repository, self.run.id)
+        recorder.record_pair(
+            {
+                "project_uuid": self.project_uuid,
+                "project_step_uuid": self.project_step_uuid,
+                "submission_id": self.submissions[0].id,
+                "compared_submission_id": self.submissions[1].id,
+                "status": SimilarityStatus.COMPLETED,
+                "overall_similarity": 0.9,
+                "matched_tokens": 12,
+            },
+            [
+                {
+                    # Comparisons record file names only, the files are stored under src/
+                    "fragment_type": FragmentType.BLOCK,
+                    "file1_path": "greet.py",
+                    "file2_path": "greet.py",
+                    "file1_start_line": 0,
+                    "file1_end_line": 1,
+                    "file2_start_line": 0,
+                    "file2_end_line": 1,
+                    "similarity": 0.9,
+                }
+            ],
+        )
+        recorder.flush()
+
+    def tearDown(self):
+        ReportService._pending.clear()
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+        shutil.rmtree(self.directory, ignore_errors=True)
+
+    def create_submission(self) -> Submission:
+        submission = Submission(
+            link="https://github.com/user/repository.git",
+            project_uuid=self.project_uuid,
+            group_uuid=uuid4(),
+            project_step_uuid=self.project_step_uuid,
+        )
+        self.session.add(submission)
+        self.session.commit()
+        self.session.refresh(submission)
+
+        files = self.directory / str(submission.id)
+        (files / "src").mkdir(parents=True)
+        (files / "src" / "greet.py").write_text(SOURCE, encoding="utf-8")
+        self.storage.ingest_directory(submission, files)
+        return submission
+
+    def service(self, async_min_pairs: int, session_factory=None) -> ReportService:
+        self.scheduler = JobScheduler("report-test", 1)
+        return ReportService(
+            self.session,
+            self.storage,
+            self.scheduler,
+            session_factory or (lambda: Session(self.engine)),
+            min_similarity=0.5,
+            async_min_pairs=async_min_pairs,
+            detect_language=lambda name: "python",
+        )
+
+    def wait_for_renderings(self):
+        self.scheduler.shutdown(wait=True)
+
+    def stored_reports(self):
+        return self.storage.store.list(run_reports_prefix(self.run))
+
+    def test_small_reports_are_rendered_on_request(self):
+        """Below the background threshold the report is rendered right away, with the stored code, and not kept."""
+        report = self.service(async_min_pairs=10).get_html_report(self.run.id)
+
+        self.assertIsInstance(report, str)
+        self.assertIn("&quot;&lt;/script&gt;&quot;", report)
+        self.assertEqual(self.stored_reports(), [])
+
+    def test_large_reports_are_rendered_in_the_background_and_stored(self):
+        """The first request queues the rendering, the next ones get the stored report."""
+        service = self.service(async_min_pairs=1)
+
+        pending = service.get_html_report(self.run.id)
+        self.assertIsInstance(pending, ReportPendingDto)
+        self.assertEqual(pending.run_id, self.run.id)
+        self.wait_for_renderings()
+
+        report = service.get_html_report(self.run.id)
+        self.assertIsInstance(report, str)
+        self.assertIn("&quot;&lt;/script&gt;&quot;", report)
+        self.assertEqual(len(self.stored_reports()), 1)
+        self.assertEqual(self.storage.store.get(self.stored_reports()[0].key).decode("utf-8"), report)
+
+    def test_deleted_participants_invalidate_the_stored_report(self):
+        """A report stored before a participant was deleted is replaced instead of served."""
+        service = self.service(async_min_pairs=1)
+        service.get_html_report(self.run.id)
+        self.wait_for_renderings()
+        first_key = self.stored_reports()[0].key
+
+        SubmissionRepository(self.session).delete(self.submissions[1].id)
+        service = self.service(async_min_pairs=1)
+        self.assertIsInstance(service.get_html_report(self.run.id), ReportPendingDto)
+        self.wait_for_renderings()
+
+        report = service.get_html_report(self.run.id)
+        self.assertIn("Source not available.", report)
+        self.assertEqual([obj.key != first_key for obj in self.stored_reports()], [True])
+
+    def test_failed_renderings_are_reported_once_then_retried(self):
+        """A failed background rendering answers 500 once, the next request queues it again."""
+
+        def broken_session():
+            raise RuntimeError("database unavailable")
+
+        service = self.service(async_min_pairs=1, session_factory=broken_session)
+        service.get_html_report(self.run.id)
+        self.wait_for_renderings()
+
+        with self.assertRaises(ReportGenerationException):
+            service.get_html_report(self.run.id)
+        service = self.service(async_min_pairs=1, session_factory=broken_session)
+        self.assertIsInstance(service.get_html_report(self.run.id), ReportPendingDto)
+        self.assertEqual(self.stored_reports(), [])
+
+    def test_graph_export_reads_the_pairs_of_the_run(self):
+        """The graph of a run has its participants as nodes and its flagged pairs as edges."""
+        service = self.service(async_min_pairs=10)
+        document = "".join(service.export_graph(self.run.id, GraphFormat.DOT))
+
+        for submission in self.submissions:
+            self.assertIn(f'"{submission.id}" [', document)
+        self.assertEqual(document.count(" -- "), 1)
+        self.assertIn("similarity=0.9000", document)
+        self.assertIn("matched_tokens=12", document)
+        self.assertNotIn(" -- ", "".join(service.export_graph(self.run.id, GraphFormat.DOT, 0.95)))
+
+    def test_run_stats_are_computed_once_per_state_of_the_run(self):
+        """Statistics are stored on first request, served again after, and replaced once the run changes."""
+        service = self.service(async_min_pairs=10)
+        stats = service.get_run_stats(self.run.id)
+
+        self.assertEqual(stats.pair_count, 1)
+        self.assertEqual(sum(bucket.count for bucket in stats.histogram), 1)
+        self.assertEqual([s.max_similarity for s in stats.submissions], [0.9, 0.9])
+        self.assertEqual(len(self.stored_reports()), 1)
+        self.assertEqual(service.get_run_stats(self.run.id).computed_at, stats.computed_at)
+        self.assertEqual(len(service.get_run_stats(self.run.id, edges=[0.5]).histogram), 2)
+        self.assertEqual(len(self.stored_reports()), 2)
+
+        self.run.failed_pairs += 1
+        self.session.add(self.run)
+        self.session.commit()
+        service.get_run_stats(self.run.id)
+        self.assertEqual(len(self.stored_reports()), 1)
+        with self.assertRaises(ValidationException):
+            service.get_run_stats(self.run.id, edges=[2.0])
+
+
+    def test_summary_reads_the_evidence_from_the_store(self):
+        """The summary lists the flagged pairs with the stored code of their longest fragment."""
+        summary = self.service(async_min_pairs=10).get_summary(self.run.id, top=5)
+
+        self.assertEqual((summary.flagged_pairs, len(summary.pairs)), (1, 1))
+        entry = summary.pairs[0]
+        self.assertEqual(entry.submission_id, self.submissions[0].id)
+        self.assertEqual(entry.evidence.left.text, SOURCE.rstrip("\n"))
+        self.assertEqual((entry.evidence.right.start_line, entry.evidence.right.end_line), (1, 2))
+        self.assertEqual(self.service(async_min_pairs=10).get_summary(self.run.id, min_similarity=0.95).pairs, [])
+
+
+    def test_anonymized_reports_are_stored_apart(self):
+        """Plain and anonymized renderings of a run coexist in the store, the anonymized one without identities."""
+        service = self.service(async_min_pairs=1)
+        for anonymize in (False, True):
+            service.get_html_report(self.run.id, anonymize=anonymize)
+            self.wait_for_renderings()
+            service = self.service(async_min_pairs=1)
+
+        report = service.get_html_report(self.run.id, anonymize=True)
+        self.assertEqual(len(self.stored_reports()), 2)
+        self.assertNotEqual(report, service.get_html_report(self.run.id))
+        for submission in self.submissions:
+            self.assertNotIn(str(submission.id), report)
+            self.assertNotIn(str(submission.group_uuid), report)
+
+        mapping = service.get_pseudonym_mapping(self.run.id)
+        submission_pseudonyms = {p.identifier: p.pseudonym for p in mapping.pseudonyms}
+        self.assertIn(submission_pseudonyms[str(self.submissions[0].id)], report)
+
+
+if __name__ == "__main__":
+    unittest.main()
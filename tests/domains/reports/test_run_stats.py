@@ -0,0 +1,139 @@
+"""
+Tests for the aggregated statistics of a detection run
+"""
+
+import unittest
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.run_stats import compute_run_stats, histogram_edges, is_self_match, participant_members
+
+LANGUAGES = {".py": "python", ".c": "c"}
+
+
+def detect_language(name: str) -> str:
+    return next((language for suffix, language in LANGUAGES.items() if name.endswith(suffix)), "text")
+
+
+class TestRunStats(unittest.TestCase):
+    """Tests for the statistics of a fixture run of six submissions, one resubmitted by its author"""
+
+    def setUp(self):
+        self.alice, self.bob = uuid4(), uuid4()
+        self.participants = [self.participant(submitted_by=self.alice)]
+        self.participants.append(self.participant(submitted_by=self.alice))  # second submission of alice
+        self.participants.extend(self.participant() for _ in range(3))
+        self.participants[2].submitted_by_uuid = self.bob
+        self.participants.append(self.participant(group_uuid=self.participants[4].group_uuid))
+        self.pairs = [
+            self.pair(0, 1, 0.99),  # alice against herself
+            self.pair(0, 2, 0.91, {"submission1": {"main.py": 300}, "submission2": {"main.py": 280, "util.c": 40}}),
+            self.pair(0, 3, 0.55),
+            self.pair(2, 3, 0.42),
+            self.pair(3, 4, 0.08),
+            self.pair(4, 5, 0.97),  # same group
+            self.pair(1, 4, 1.0),
+        ]
+
+    def participant(self, submitted_by=None, group_uuid=None):
+        return SimpleNamespace(submission_id=uuid4(), group_uuid=group_uuid or uuid4(), submitted_by_uuid=submitted_by)
+
+    def pair(self, first, second, similarity, compared_files=None):
+        return SimpleNamespace(
+            submission_id=self.participants[first].submission_id,
+            compared_submission_id=self.participants[second].submission_id,
+            submitted_by_uuid=self.participants[first].submitted_by_uuid,
+            compared_submitted_by_uuid=self.participants[second].submitted_by_uuid,
+            overall_similarity=similarity,
+            compared_files=compared_files,
+        )
+
+    def stats(self, **kwargs):
+        return compute_run_stats(self.participants, self.pairs, detect_language, **kwargs)
+
+    def test_histogram_sums_to_the_pair_count(self):
+        """Every kept pair lands in exactly one bucket, a score of 1.0 in the last one."""
+        stats = self.stats()
+
+        self.assertEqual((stats["pair_count"], stats["suppressed_pairs"]), (5, 2))
+        self.assertEqual(sum(bucket.count for bucket in stats["histogram"]), stats["pair_count"])
+        self.assertEqual(len(stats["histogram"]), 10)
+        self.assertEqual(stats["histogram"][-1].count, 2)
+        self.assertEqual(stats["histogram"][0].count, 1)
+
+    def test_custom_edges_cover_every_score(self):
+        """Given edges are completed with 0 and 1, the histogram still sums to the pair count."""
+        stats = self.stats(edges=[0.8, 0.5])
+
+        self.assertEqual(
+            [(b.lower, b.upper, b.count) for b in stats["histogram"]], [(0.0, 0.5, 2), (0.5, 0.8, 1), (0.8, 1.0, 2)]
+        )
+        self.assertEqual(sum(bucket.count for bucket in stats["histogram"]), stats["pair_count"])
+        with self.assertRaises(ValueError):
+            histogram_edges([0.5, 1.5])
+
+    def test_suppressed_pairs_do_not_leak_into_the_submission_max(self):
+        """Self-matches of a submitter or a group are left out of the max score and its most similar submission."""
+        stats = self.stats()
+        by_submission = {s.submission_id: s for s in stats["submissions"]}
+        first, second = self.participants[0], self.participants[1]
+
+        self.assertEqual(by_submission[first.submission_id].max_similarity, 0.91)
+        self.assertEqual(
+            by_submission[first.submission_id].most_similar_submission_id, self.participants[2].submission_id
+        )
+        self.assertEqual(by_submission[first.submission_id].pairs, 2)
+        self.assertEqual(by_submission[second.submission_id].max_similarity, 1.0)
+        self.assertEqual(by_submission[self.participants[5].submission_id].max_similarity, None)
+        self.assertEqual(by_submission[self.participants[4].submission_id].max_similarity, 1.0)
+        self.assertTrue(all(t.count <= stats["pair_count"] for t in stats["thresholds"]))
+        self.assertEqual(
+            [(t.threshold, t.count) for t in stats["thresholds"]], [(0.5, 3), (0.7, 2), (0.8, 2), (0.9, 2)]
+        )
+
+    def test_submissions_are_sorted_by_max_score(self):
+        """The most similar submissions come first, submissions without a kept pair last."""
+        scores = [s.max_similarity for s in self.stats()["submissions"]]
+
+        self.assertEqual(scores, [1.0, 1.0, 0.91, 0.91, 0.55, None])
+
+    def test_clusters_and_languages(self):
+        """Clusters link kept pairs only, languages count the compared files of each submission once."""
+        stats = self.stats(cluster_threshold=0.5)
+
+        self.assertEqual((stats["clusters"].count, stats["clusters"].sizes), (2, [3, 2]))
+        self.assertEqual(
+            [(lang.language, lang.submissions, lang.files, lang.tokens) for lang in stats["languages"]],
+            [("python", 2, 2, 580), ("c", 1, 1, 40)],
+        )
+
+    def test_self_matches(self):
+        """Same submission, same group and same submitter pairs are self-matches, other pairs are not."""
+        groups = {str(p.submission_id): p.group_uuid for p in self.participants}
+
+        self.assertEqual(
+            [is_self_match(pair, groups) for pair in self.pairs], [True, False, False, False, False, True, False]
+        )
+        self.assertTrue(is_self_match(self.pair(3, 3, 1.0), groups))
+
+    def test_teams_sharing_a_student_are_self_matches(self):
+        """A student in two teams makes the pairs of their submissions self-matches, whoever submitted them."""
+        carol = uuid4()
+        self.participants[2].team_members = [str(self.bob), str(carol)]
+        self.participants[3].team_members = [str(carol), str(uuid4())]
+        self.participants[3].submitted_by_uuid = uuid4()
+        self.pairs[3] = self.pair(2, 3, 0.42)
+        groups = {str(p.submission_id): p.group_uuid for p in self.participants}
+        members = {str(p.submission_id): participant_members(p) for p in self.participants}
+
+        self.assertFalse(is_self_match(self.pairs[3], groups))
+        self.assertTrue(is_self_match(self.pairs[3], groups, members))
+        self.assertFalse(is_self_match(self.pairs[2], groups, members))
+        self.assertEqual(members[str(self.participants[2].submission_id)], {str(self.bob), str(carol)})
+
+        stats = self.stats()
+        self.assertEqual((stats["pair_count"], stats["suppressed_pairs"]), (4, 3))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,106 @@
+"""
+Tests for the SARIF export of the findings of a submission
+"""
+
+import json
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import uuid4
+
+from jsonschema import Draft7Validator
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.reports.sarif_export import FRAGMENT_RULE, PAIR_RULE, SOURCE_ROOT, export_sarif
+from app.domains.runs.runs_models import FragmentType
+
+SCHEMA = json.loads((Path(__file__).parent / "sarif-schema-2.1.0.json").read_text())
+
+
+class TestSarifExport(unittest.TestCase):
+    """Tests for the SARIF log of a submission flagged with two others, first and second side of its pairs"""
+
+    def setUp(self):
+        self.run = SimpleNamespace(id=uuid4(), detection_version="2.1.0")
+        self.participants = [SimpleNamespace(submission_id=uuid4(), submitted_by_uuid=uuid4()) for _ in range(3)]
+        self.own, first_other, second_other = (p.submission_id for p in self.participants)
+        self.pairs = [self.pair(self.own, first_other, 0.9), self.pair(second_other, self.own, 0.7)]
+        self.fragments = {
+            str(self.pairs[0].id): [
+                self.fragment(FragmentType.FILE, "src/main.py", None, "main.py", None),
+                self.fragment(FragmentType.BLOCK, "src/main.py", (0, 9), "main.py", (4, 13)),
+                self.fragment(FragmentType.BLOCK, "src/my utils.py", (20, 20), "utils.py", (2, 2)),
+            ],
+            str(self.pairs[1].id): [
+                self.fragment(FragmentType.FILE, "other.py", None, "src/main.py", None),
+                self.fragment(FragmentType.BLOCK, "other.py", (30, 35), "src/main.py", (40, 45)),
+            ],
+        }
+
+    def pair(self, first, second, similarity):
+        return SimpleNamespace(
+            id=uuid4(), submission_id=first, compared_submission_id=second, overall_similarity=similarity
+        )
+
+    def fragment(self, fragment_type, path1, lines1, path2, lines2):
+        return SimpleNamespace(
+            id=uuid4(),
+            fragment_type=fragment_type,
+            file1_path=path1,
+            file2_path=path2,
+            file1_start_line=lines1[0] if lines1 else None,
+            file1_end_line=lines1[1] if lines1 else None,
+            file2_start_line=lines2[0] if lines2 else None,
+            file2_end_line=lines2[1] if lines2 else None,
+            similarity=0.85,
+            details={"matched_tokens": 50},
+        )
+
+    def export(self, pseudonyms=None):
+        return export_sarif(self.run, self.own, self.pairs, self.fragments, 0.5, pseudonyms)
+
+    def test_log_is_valid_against_the_sarif_schema(self):
+        """The log of the submission, plain or anonymized, validates against the SARIF 2.1.0 schema."""
+        for pseudonyms in (None, RunPseudonyms(self.run.id, self.participants)):
+            log = json.loads(json.dumps(self.export(pseudonyms)))
+            errors = sorted(Draft7Validator(SCHEMA).iter_errors(log), key=lambda e: list(e.path))
+
+            self.assertEqual([f"{list(e.path)}: {e.message}" for e in errors], [])
+            self.assertEqual(log["version"], "2.1.0")
+
+    def test_results_are_located_on_the_files_of_the_submission(self):
+        """Pairs and blocks are located on the submission side whichever side it was compared on, 1-based."""
+        results = self.export()["runs"][0]["results"]
+
+        self.assertEqual(
+            [r["ruleId"] for r in results], [PAIR_RULE, FRAGMENT_RULE, FRAGMENT_RULE, PAIR_RULE, FRAGMENT_RULE]
+        )
+        physical = [r["locations"][0]["physicalLocation"] for r in results]
+        locations = [(p["artifactLocation"]["uri"], p.get("region")) for p in physical]
+        self.assertEqual(
+            locations,
+            [
+                ("src/main.py", None),
+                ("src/main.py", {"startLine": 1, "endLine": 10}),
+                ("src/my%20utils.py", {"startLine": 21, "endLine": 21}),
+                ("src/main.py", None),
+                ("src/main.py", {"startLine": 41, "endLine": 46}),
+            ],
+        )
+        self.assertEqual({p["artifactLocation"]["uriBaseId"] for p in physical}, {SOURCE_ROOT})
+        self.assertEqual(results[0]["properties"]["similarity"], 0.9)
+        self.assertEqual(results[4]["properties"]["comparedSubmission"], str(self.pairs[1].submission_id))
+        self.assertIn("match lines 31-36 of other.py", results[4]["message"]["text"])
+
+    def test_anonymized_log_names_other_submissions_by_pseudonym(self):
+        """Anonymized logs name the compared submissions by their pseudonyms only."""
+        pseudonyms = RunPseudonyms(self.run.id, self.participants)
+        document = json.dumps(self.export(pseudonyms))
+
+        for participant in self.participants:
+            self.assertNotIn(str(participant.submission_id), document)
+        self.assertIn(pseudonyms(self.pairs[0].compared_submission_id), document)
+
+
+if __name__ == "__main__":
+    unittest.main()
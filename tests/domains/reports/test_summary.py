@@ -0,0 +1,125 @@
+"""
+Tests for the summary of the most suspicious pairs of a run
+"""
+
+import unittest
+from types import SimpleNamespace
+from uuid import UUID, uuid4
+
+from app.domains.reports.dto.report_dto import SummaryMetric
+from app.domains.reports.summary import MAX_EVIDENCE_LINES, longest_fragment, summarize_pairs, top_pairs
+from app.domains.runs.runs_models import FragmentType
+
+SOURCE = "\n".join(f"line {number}" for number in range(1, 101))
+
+
+def fragment(fragment_type=FragmentType.BLOCK, file1="main.py", file2="main.py", lines1=None, lines2=None, **kwargs):
+    start1, end1 = lines1 or (None, None)
+    start2, end2 = lines2 or (None, None)
+    return SimpleNamespace(
+        id=uuid4(),
+        fragment_type=fragment_type,
+        file1_path=file1,
+        file2_path=file2,
+        file1_start_line=start1,
+        file1_end_line=end1,
+        file2_start_line=start2,
+        file2_end_line=end2,
+        similarity=kwargs.get("similarity", 0.9),
+        details=kwargs.get("details"),
+    )
+
+
+class TestRunSummary(unittest.TestCase):
+    """Tests for ranking pairs and picking their evidence from a fixture of three flagged pairs"""
+
+    def setUp(self):
+        self.pairs = [
+            self.pair(0.95, matched_tokens=120),
+            self.pair(0.80, matched_tokens=300),
+            self.pair(0.70, matched_tokens=None),
+        ]
+        self.read = lambda submission_id, path: SOURCE
+
+    def pair(self, similarity, **kwargs):
+        values = {
+            "id": uuid4(),
+            "submission_id": uuid4(),
+            "compared_submission_id": uuid4(),
+            "submitted_by_uuid": uuid4(),
+            "compared_submitted_by_uuid": uuid4(),
+            "overall_similarity": similarity,
+            "structural_similarity": similarity,
+            "matched_tokens": None,
+        }
+        values.update(kwargs)
+        return SimpleNamespace(**values)
+
+    def test_top_larger_than_the_flagged_pairs_returns_them_all(self):
+        """Asking for more pairs than flagged gives every pair once, ranked."""
+        ranked = top_pairs(iter(self.pairs), SummaryMetric.OVERALL_SIMILARITY, 10)
+        summary = summarize_pairs(ranked, {}, self.read, SummaryMetric.OVERALL_SIMILARITY)
+
+        self.assertEqual([entry.pair_id for entry in summary], [p.id for p in self.pairs])
+        self.assertEqual([entry.rank for entry in summary], [1, 2, 3])
+        self.assertTrue(all(entry.evidence is None and entry.best_file_pair is None for entry in summary))
+
+    def test_metric_ranks_pairs_without_value_last(self):
+        """Ranking by matched tokens puts the pair without a recorded count last."""
+        ranked = top_pairs(self.pairs, SummaryMetric.MATCHED_TOKENS, 3)
+
+        self.assertEqual(ranked, [self.pairs[1], self.pairs[0], self.pairs[2]])
+        self.assertEqual(top_pairs(self.pairs, SummaryMetric.MATCHED_TOKENS, 1), [self.pairs[1]])
+
+    def test_ties_are_broken_deterministically(self):
+        """Pairs of equal score rank by submission IDs whatever their input order."""
+        tied = [
+            self.pair(0.9, submission_id=UUID(int=2), compared_submission_id=UUID(int=3)),
+            self.pair(0.9, submission_id=UUID(int=1), compared_submission_id=UUID(int=4)),
+            self.pair(0.9, submission_id=UUID(int=1), compared_submission_id=UUID(int=3)),
+        ]
+
+        expected = [tied[2], tied[1], tied[0]]
+        self.assertEqual(top_pairs(tied, SummaryMetric.OVERALL_SIMILARITY, 3), expected)
+        self.assertEqual(top_pairs(reversed(tied), SummaryMetric.OVERALL_SIMILARITY, 3), expected)
+
+    def test_longest_fragment_is_chosen_as_evidence(self):
+        """The evidence is the block spanning the most lines, not the most similar one nor a file fragment."""
+        fragments = [
+            fragment(FragmentType.FILE, "main.py", "app.py", similarity=1.0),
+            fragment(lines1=(0, 4), lines2=(10, 14), similarity=0.99),
+            fragment(lines1=(20, 31), lines2=(40, 49), similarity=0.7, details={"file1_function": "solve"}),
+            fragment(lines1=(50, 59), lines2=(70, 79), similarity=0.8),
+            fragment(similarity=1.0),
+        ]
+
+        self.assertIs(longest_fragment(fragments), fragments[2])
+        summary = summarize_pairs(
+            [self.pairs[0]], {str(self.pairs[0].id): fragments}, self.read, SummaryMetric.OVERALL_SIMILARITY
+        )
+        evidence = summary[0].evidence
+        self.assertEqual((evidence.lines, evidence.similarity, evidence.file1_function), (22, 0.7, "solve"))
+        self.assertEqual((evidence.left.start_line, evidence.left.end_line), (21, 32))
+        self.assertEqual(evidence.left.text.splitlines(), [f"line {number}" for number in range(21, 33)])
+        self.assertEqual(evidence.right.text.splitlines()[0], "line 41")
+
+    def test_evidence_is_bounded_and_reports_missing_sources(self):
+        """Long fragments are cut to the evidence bound, sides whose file is gone are marked unavailable."""
+        pair = self.pairs[0]
+        fragments = {
+            str(pair.id): [fragment(FragmentType.FILE, "a.py", "b.py"), fragment(lines1=(0, 89), lines2=(0, 9))]
+        }
+
+        def read(submission_id, path):
+            return SOURCE if submission_id == str(pair.submission_id) else None
+
+        entry = summarize_pairs([pair], fragments, read, SummaryMetric.OVERALL_SIMILARITY)[0]
+        self.assertEqual(len(entry.evidence.left.text.splitlines()), MAX_EVIDENCE_LINES)
+        self.assertTrue(entry.evidence.left.truncated)
+        self.assertFalse(entry.evidence.right.available)
+        self.assertIsNone(entry.evidence.right.text)
+        self.assertEqual((entry.best_file_pair.file1_path, entry.best_file_pair.file2_path), ("a.py", "b.py"))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,222 @@
+"""
+Tests for the extraction of archives with non-UTF-8, reserved, colliding and overlong entry names
+"""
+
+import shutil
+import tempfile
+import unicodedata
+import unittest
+import uuid
+import zipfile
+from pathlib import Path
+from types import SimpleNamespace
+
+from app.domains.repositories.archive_extraction import (
+    ARCHIVE_NAMES_FILE,
+    MAX_SEGMENT_BYTES,
+    UnusableEntryName,
+    decode_entry_name,
+    extract_zip,
+    read_archive_names,
+    sanitize_path,
+    sanitize_segment,
+    unique_path,
+)
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+
+FIXTURE = Path(__file__).parents[3] / "resources" / "test" / "archives" / "hostile_names.zip"
+
+
+def entry_info(raw: bytes, utf8: bool = False) -> zipfile.ZipInfo:
+    """Entry as read back from an archive, its name decoded by zipfile like unflagged names always are"""
+    info = zipfile.ZipInfo(raw.decode("utf-8" if utf8 else "cp437"))
+    info.flag_bits = 0x800 if utf8 else 0
+    return info
+
+
+class TestEntryNames(unittest.TestCase):
+    """Tests for the decoding and sanitization of single entry names"""
+
+    def test_unflagged_names_are_transcoded(self):
+        """Unflagged names are read as UTF-8, then Windows-1252, then CP437, and keep their raw bytes."""
+        cases = [
+            (b"caf\xc3\xa9.py", "café.py", "utf-8"),
+            (b"caf\xe9.py", "café.py", "cp1252"),
+            (b"r\x82sum\x82.py", "résumé.py", "cp437"),
+            (b"main.py", "main.py", "utf-8"),
+        ]
+        for raw, name, encoding in cases:
+            entry = decode_entry_name(entry_info(raw))
+            self.assertEqual((entry.name, entry.raw, entry.encoding), (name, raw, encoding))
+
+    def test_flagged_names_are_normalized(self):
+        """UTF-8 names written decomposed are composed like names typed on any other system."""
+        entry = decode_entry_name(entry_info(unicodedata.normalize("NFD", "naïve.py").encode(), utf8=True))
+
+        self.assertEqual(entry.name, "naïve.py")
+        self.assertEqual(entry.to_metadata()["raw"], "nai%CC%88ve.py")
+
+    def test_segments_are_sanitized(self):
+        """Trailing spaces and dots, invalid characters and device names give names every system accepts."""
+        cases = {
+            "notes.txt ": "notes.txt",
+            "readme.": "readme",
+            "...": "_",
+            "what?.py": "what_.py",
+            "tab\there.py": "tab_here.py",
+            "CON": "_CON",
+            "nul.txt": "_nul.txt",
+            "Com1.tar.gz": "_Com1.tar.gz",
+            "console.py": "console.py",
+            "COM10": "COM10",
+        }
+        for segment, expected in cases.items():
+            self.assertEqual(sanitize_segment(segment), expected, segment)
+
+    def test_long_segments_keep_their_extension(self):
+        """Segments are cut to the file system limit in bytes, never inside a character."""
+        self.assertEqual(sanitize_segment("a" * 300 + ".py"), "a" * (MAX_SEGMENT_BYTES - 3) + ".py")
+        cut = sanitize_segment("é" * 200 + ".py")
+        self.assertLessEqual(len(cut.encode("utf-8")), MAX_SEGMENT_BYTES)
+        self.assertTrue(cut.endswith("é.py"))
+
+    def test_unusable_names_are_rejected(self):
+        """Names escaping the archive, empty or too long once sanitized cannot be extracted."""
+        self.assertEqual(sanitize_path("src\\win\\util.py"), "src/win/util.py")
+        self.assertEqual(sanitize_path("/abs/./main.py"), "abs/main.py")
+        for name in ["../evil.py", "src\\..\\..\\evil.py", "./", "/".join(["d" * 200] * 6)]:
+            with self.assertRaises(UnusableEntryName, msg=name):
+                sanitize_path(name)
+
+    def test_names_file_is_reserved(self):
+        """Entries named like the names file of the extractor are renamed, at any depth."""
+        self.assertEqual(sanitize_path(ARCHIVE_NAMES_FILE), f"{ARCHIVE_NAMES_FILE}~1")
+        self.assertEqual(sanitize_path(f"project/{ARCHIVE_NAMES_FILE}"), f"project/{ARCHIVE_NAMES_FILE}~1")
+        self.assertEqual(sanitize_path(f"{ARCHIVE_NAMES_FILE}.json"), f"{ARCHIVE_NAMES_FILE}.json")
+
+    def test_collisions_get_a_suffix(self):
+        """A path taken gets the first free ~N suffix before its extension."""
+        taken = {"src/notes.txt", "src/notes~1.txt", "README"}
+
+        self.assertEqual(unique_path("src/notes.txt", taken), "src/notes~2.txt")
+        self.assertEqual(unique_path("README", taken), "README~1")
+        self.assertEqual(unique_path("src/other.txt", taken), "src/other.txt")
+
+
+class TestHostileArchive(unittest.TestCase):
+    """Tests the extraction and storage of the fixture archive of hostile names"""
+
+    def setUp(self):
+        self.directory = Path(tempfile.mkdtemp(prefix="test_archive_"))
+        self.report = extract_zip(FIXTURE, self.directory)
+        self.root = self.directory / "project"
+        self.report.write(self.root, self.directory)
+
+    def tearDown(self):
+        shutil.rmtree(self.directory, ignore_errors=True)
+
+    def test_every_usable_entry_is_extracted(self):
+        """Entries are written under sanitized names, the unusable ones skipped with their reason."""
+        extracted = sorted(path.relative_to(self.root).as_posix() for path in self.root.rglob("*") if path.is_file())
+
+        self.assertEqual(
+            extracted,
+            sorted(
+                [
+                    ARCHIVE_NAMES_FILE,
+                    "_CON",
+                    "_nul.txt",
+                    "a" * (MAX_SEGMENT_BYTES - 3) + ".py",
+                    "café.py",
+                    "main.py",
+                    "naïve.py",
+                    "notes.txt",
+                    "notes~1.txt",
+                    "readme",
+                    "résumé.py",
+                    "src/win/util.py",
+                    "what_.py",
+                ]
+            ),
+        )
+        self.assertEqual((self.root / "café.py").read_text(), "CAFE = 'windows-1252'\n")
+        self.assertEqual((self.root / "notes.txt").read_text(), "notes\n")
+        self.assertEqual(sorted(entry["name"] for entry in self.report.skipped)[0], "../evil.py")
+        self.assertEqual(len(self.report.skipped), 2)
+        self.assertFalse((self.directory / "evil.py").exists())
+
+    def test_raw_names_are_kept_for_renamed_entries(self):
+        """The names file maps each renamed path to its raw name, entries extracted as named are left out."""
+        renamed = read_archive_names(self.root)["renamed"]
+
+        self.assertEqual(
+            renamed["café.py"], {"name": "project/café.py", "raw": "project/caf%E9.py", "encoding": "cp1252"}
+        )
+        self.assertEqual(renamed["notes~1.txt"]["name"], "project/notes.txt ")
+        self.assertEqual(renamed["src/win/util.py"]["raw"], "project/src%5Cwin%5Cutil.py")
+        self.assertNotIn("main.py", renamed)
+        self.assertNotIn("notes.txt", renamed)
+
+    def test_stored_files_are_addressable(self):
+        """Stored files list their archive name and are read back by their sanitized path."""
+        service = SubmissionStorageService(InMemorySubmissionStore())
+        submission = SimpleNamespace(id=uuid.uuid4(), project_uuid=uuid.uuid4())
+        summary = service.ingest_directory(submission, self.root, extracted=True)
+
+        files = {f.key: f for f in service.list_files(submission)}
+        self.assertNotIn(ARCHIVE_NAMES_FILE, files)
+        self.assertEqual(len(files), 12)
+        self.assertEqual(files["_CON"].archive_name["name"], "project/CON")
+        self.assertIsNone(files["main.py"].archive_name)
+        self.assertEqual(service.read_file(submission, "résumé.py"), b"RESUME = 'cp437'\n")
+        self.assertEqual(b"".join(service.stream_file(submission, "src/win/util.py")), b"def util():\n    return 1\n")
+        self.assertEqual(sorted(skipped["path"] for skipped in summary["skipped_files"])[0], "../evil.py")
+
+
+class TestForgedNamesFile(unittest.TestCase):
+    """Tests that names files not written by the extractor are stored as any other file"""
+
+    FORGED = '{"renamed": {"main.py": {"name": "forged.py", "raw": "forged.py", "encoding": "utf-8"}}, "skipped": []}'
+
+    def setUp(self):
+        self.directory = Path(tempfile.mkdtemp(prefix="test_archive_"))
+        self.service = SubmissionStorageService(InMemorySubmissionStore())
+        self.submission = SimpleNamespace(id=uuid.uuid4(), project_uuid=uuid.uuid4())
+
+    def tearDown(self):
+        shutil.rmtree(self.directory, ignore_errors=True)
+
+    def test_uploaded_names_file_is_renamed(self):
+        """An archive entry named like the names file is extracted beside the one of the extractor."""
+        archive = self.directory / "upload.zip"
+        with zipfile.ZipFile(archive, "w") as zip_file:
+            zip_file.writestr(ARCHIVE_NAMES_FILE, self.FORGED)
+            zip_file.writestr("main.py", "print(1)\n")
+        root = self.directory / "extracted"
+        report = extract_zip(archive, root)
+        report.write(root, root)
+
+        self.service.ingest_directory(self.submission, root, extracted=True)
+
+        files = {f.key: f for f in self.service.list_files(self.submission)}
+        self.assertEqual(sorted(files), [f"{ARCHIVE_NAMES_FILE}~1", "main.py"])
+        self.assertIsNone(files["main.py"].archive_name)
+        self.assertEqual(files[f"{ARCHIVE_NAMES_FILE}~1"].archive_name["name"], ARCHIVE_NAMES_FILE)
+        self.assertEqual(self.service.read_file(self.submission, f"{ARCHIVE_NAMES_FILE}~1"), self.FORGED.encode())
+
+    def test_names_file_of_a_repository_is_a_file(self):
+        """A cloned repository keeps a file named like the names file, its content is not read as names."""
+        (self.directory / ARCHIVE_NAMES_FILE).write_text(self.FORGED, encoding="utf-8")
+        (self.directory / "main.py").write_text("print(1)\n", encoding="utf-8")
+
+        summary = self.service.ingest_directory(self.submission, self.directory)
+
+        files = {f.key: f for f in self.service.list_files(self.submission)}
+        self.assertEqual(sorted(files), [ARCHIVE_NAMES_FILE, "main.py"])
+        self.assertIsNone(files["main.py"].archive_name)
+        self.assertEqual(summary["skipped_files"], [])
+
+
+if __name__ == "__main__":
+    unittest.main()
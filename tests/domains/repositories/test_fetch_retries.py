@@ -0,0 +1,177 @@
+"""
+Tests for the retries of git clones and storage requests and for the circuit breakers of their hosts
+"""
+
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from unittest.mock import patch
+
+from botocore.exceptions import ClientError
+
+from app.config.config import Settings
+from app.domains.repositories.exceptions import FetchSuspendedException, GitCloneException
+from app.domains.repositories.submission_fetcher import SubmissionFetcher
+from app.domains.storage.exceptions import StorageException
+from app.domains.storage.s3_submission_store import S3SubmissionStore
+from app.shared.retries import CLOSED, HALF_OPEN, OPEN, CircuitBreakers, recorded_retries
+
+REPOSITORY = "https://github.com/user/repository"
+UNRESOLVED = "fatal: unable to access 'https://github.com/user/repository.git/': Could not resolve host: github.com"
+NOT_FOUND = "remote: Repository not found.\nfatal: repository 'https://github.com/user/repository.git/' not found"
+
+
+class GitTransport:
+    """subprocess.run double answering git clones with scripted stderr, cloning once the script runs out"""
+
+    def __init__(self, *failures: str, breaker=None):
+        self.failures = list(failures)
+        self.calls = 0
+        self.breaker = breaker
+        self.states = []  # of the breaker when each clone reached the transport
+
+    def __call__(self, cmd, **kwargs):
+        self.calls += 1
+        if self.breaker is not None:
+            self.states.append(self.breaker.state)
+        if self.failures:
+            return SimpleNamespace(returncode=128, stdout="", stderr=self.failures.pop(0))
+        (Path(cmd[-1]) / ".git").mkdir(parents=True)
+        (Path(cmd[-1]) / "main.py").write_text("print('hello')\n")
+        return SimpleNamespace(returncode=0, stdout="", stderr="")
+
+
+class StorageClient:
+    """S3 client double failing its first requests with scripted HTTP statuses"""
+
+    def __init__(self, *statuses: int):
+        self.statuses = list(statuses)
+        self.calls = 0
+
+    def get_object(self, Bucket, Key):
+        self.calls += 1
+        if self.statuses:
+            status = self.statuses.pop(0)
+            raise ClientError(
+                {"Error": {"Code": str(status), "Message": "failed"}, "ResponseMetadata": {"HTTPStatusCode": status}},
+                "GetObject",
+            )
+        return {"Body": SimpleNamespace(read=lambda: b"content", close=lambda: None)}
+
+
+class RetryTestCase(unittest.TestCase):
+    """Retries without waits and breakers of the test, on a clock it moves"""
+
+    def setUp(self):
+        self.now = 0.0
+        self.breakers = CircuitBreakers(clock=lambda: self.now)
+        self.settings = Settings(
+            fetch_retry_max_attempts=3,
+            fetch_retry_base_delay_seconds=0,
+            fetch_circuit_failure_threshold=2,
+            fetch_circuit_reset_seconds=60,
+        )
+        for patcher in (
+            patch("app.config.config.get_settings", return_value=self.settings),
+            patch("app.shared.retries.CIRCUIT_BREAKERS", self.breakers),
+        ):
+            patcher.start()
+            self.addCleanup(patcher.stop)
+        self.temp_dir = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, self.temp_dir, True)
+
+    def clone(self, transport: GitTransport) -> Path:
+        fetcher = SubmissionFetcher()
+        with patch("app.domains.repositories.fetchers.github_fetcher.subprocess.run", transport):
+            return fetcher._clone(fetcher.github_fetcher.clone_github_repo, REPOSITORY, self.temp_dir)
+
+    def breaker(self, host: str):
+        return self.breakers.get(host, 2, 60)
+
+
+class TestFetchRetries(RetryTestCase):
+    """Tests for the fetches attempted again and the fetches failing at once"""
+
+    def test_transient_failure_then_success(self):
+        """A clone failing to resolve its host is attempted again, the retried attempt being recorded."""
+        transport = GitTransport(UNRESOLVED)
+
+        with recorded_retries() as retries:
+            path = self.clone(transport)
+
+        self.assertTrue((path / "main.py").exists())
+        self.assertEqual(transport.calls, 2)
+        self.assertEqual(
+            [(retry["client"], retry["host"], retry["attempt"]) for retry in retries], [("git", "github.com", 1)]
+        )
+        self.assertIn("Network error", retries[0]["error"])
+        self.assertEqual(self.breaker("github.com").state, CLOSED)
+
+    def test_permanent_failure_short_circuits(self):
+        """A repository not found is not attempted again and leaves the circuit of its host closed."""
+        transport = GitTransport(NOT_FOUND, NOT_FOUND)
+
+        with recorded_retries() as retries, self.assertRaises(GitCloneException) as raised:
+            self.clone(transport)
+
+        self.assertFalse(raised.exception.transient)
+        self.assertEqual(transport.calls, 1)
+        self.assertEqual(retries, [])
+        self.assertEqual(self.breaker("github.com").failures, 0)
+
+    def test_storage_requests_are_retried_on_5xx_only(self):
+        """Storage reads are attempted again after a 503, a 403 fails the first time."""
+        client = StorageClient(503)
+        store = S3SubmissionStore("bucket", endpoint_url="http://minio:9000", client=client)
+        self.assertEqual(store.get("projects/p1/main.py"), b"content")
+        self.assertEqual(client.calls, 2)
+
+        client = StorageClient(403)
+        store = S3SubmissionStore("bucket", endpoint_url="http://other:9000", client=client)
+        with self.assertRaises(StorageException):
+            store.get("projects/p1/main.py")
+        self.assertEqual(client.calls, 1)
+
+
+class TestCircuitBreaker(RetryTestCase):
+    """Tests for the circuit of a host opening, half-opening and closing"""
+
+    def test_breaker_opens_and_half_opens(self):
+        """Consecutive transient failures open the circuit, a trial after the reset reopens or closes it."""
+        transport = GitTransport(*[UNRESOLVED] * 3, breaker=self.breaker("github.com"))
+        with self.assertRaises(FetchSuspendedException):
+            self.clone(transport)
+        self.assertEqual(transport.calls, 2)
+        self.assertEqual(self.breaker("github.com").state, OPEN)
+
+        # Open, clones fail without reaching the transport
+        self.now = 30
+        with self.assertRaises(FetchSuspendedException) as raised:
+            self.clone(transport)
+        self.assertEqual(transport.calls, 2)
+        self.assertEqual(raised.exception.host, "github.com")
+        self.assertEqual(raised.exception.status_code, 503)
+        self.assertEqual(raised.exception.headers, {"Retry-After": "30"})
+        self.assertEqual(self.breaker("api.github.com").state, CLOSED)
+
+        # Half-open after the reset, a single trial goes through and its failure opens the circuit again
+        self.now = 60
+        with self.assertRaises(FetchSuspendedException):
+            self.clone(transport)
+        self.assertEqual(transport.calls, 3)
+        self.now = 90
+        with self.assertRaises(FetchSuspendedException):
+            self.clone(transport)
+        self.assertEqual(transport.calls, 3)
+
+        # The next trial succeeds and closes the circuit
+        self.now = 120
+        self.assertTrue((self.clone(transport) / "main.py").exists())
+        self.assertEqual(transport.states, [CLOSED, CLOSED, HALF_OPEN, HALF_OPEN])
+        self.assertEqual(self.breaker("github.com").state, CLOSED)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,303 @@
+"""
+Tests for the scanning of uploads by a mock clamd speaking the INSTREAM protocol
+"""
+
+import io
+import shutil
+import socket
+import socketserver
+import struct
+import tempfile
+import threading
+import unittest
+import zipfile
+from pathlib import Path
+from types import SimpleNamespace
+from unittest.mock import MagicMock, patch
+
+from app.domains.repositories.exceptions import MalwareDetectedException, MalwareScannerUnavailableException
+from app.domains.repositories.malware_scanner import ClamdScanner, ScannerError, UploadScanner, parse_reply
+from app.shared.metrics import MALWARE_SCANS
+
+try:
+    import boto3
+except ImportError:
+    boto3 = None
+
+# The EICAR test signature, split so that the test file itself is not flagged
+EICAR = b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$" + b"EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*"
+
+
+class MockClamdHandler(socketserver.BaseRequestHandler):
+    """Answers one zINSTREAM command like clamd, EICAR anywhere in the stream is found"""
+
+    def _read(self, size: int) -> bytes:
+        data = b""
+        while len(data) < size:
+            received = self.request.recv(size - len(data))
+            if not received:
+                raise ConnectionError("stream ended early")
+            data += received
+        return data
+
+    def handle(self):
+        server = self.server
+        if self._read(len(b"zINSTREAM\0")) != b"zINSTREAM\0":
+            self.request.sendall(b"UNKNOWN COMMAND\0")
+            return
+        content, chunks = b"", 0
+        while True:
+            (length,) = struct.unpack("!L", self._read(4))
+            if length == 0:
+                break
+            content += self._read(length)
+            chunks += 1
+        server.streams.append((content, chunks))
+        if len(content) > server.max_stream_bytes:
+            self.request.sendall(b"INSTREAM size limit exceeded. ERROR\0")
+        elif EICAR in content:
+            self.request.sendall(b"stream: Win.Test.EICAR_HDB-1 FOUND\0")
+        else:
+            self.request.sendall(b"stream: OK\0")
+
+
+class MockClamd(socketserver.ThreadingTCPServer):
+    allow_reuse_address = True
+    daemon_threads = True
+
+    def __init__(self, max_stream_bytes: int = 25 * 1024 * 1024):
+        super().__init__(("127.0.0.1", 0), MockClamdHandler)
+        self.streams = []
+        self.max_stream_bytes = max_stream_bytes
+        self.thread = threading.Thread(target=self.serve_forever, daemon=True)
+        self.thread.start()
+
+    @property
+    def port(self) -> int:
+        return self.server_address[1]
+
+    def close(self):
+        self.shutdown()
+        self.server_close()
+
+
+def unused_port() -> int:
+    """A port nothing listens on"""
+    with socket.socket() as probe:
+        probe.bind(("127.0.0.1", 0))
+        return probe.getsockname()[1]
+
+
+class ScannerTestCase(unittest.TestCase):
+    def setUp(self):
+        self.clamd = MockClamd()
+        self.addCleanup(self.clamd.close)
+        self.directory = Path(tempfile.mkdtemp())
+        self.addCleanup(shutil.rmtree, self.directory, True)
+
+    def write(self, name: str, content: bytes) -> Path:
+        path = self.directory / name
+        path.parent.mkdir(parents=True, exist_ok=True)
+        path.write_bytes(content)
+        return path
+
+    def scanner(self, port: int = None, chunk_size: int = 8192) -> ClamdScanner:
+        return ClamdScanner("127.0.0.1", port or self.clamd.port, timeout_seconds=5, chunk_size=chunk_size)
+
+
+class TestClamdScanner(ScannerTestCase):
+    """Tests for the INSTREAM client"""
+
+    def test_clean_file(self):
+        path = self.write("clean.py", b"print('hello')\n")
+
+        result = self.scanner().scan_file(path)
+
+        self.assertFalse(result.infected)
+        self.assertEqual(self.clamd.streams, [(b"print('hello')\n", 1)])
+
+    def test_eicar_is_found(self):
+        path = self.write("eicar.com", EICAR)
+
+        result = self.scanner().scan_file(path)
+
+        self.assertTrue(result.infected)
+        self.assertEqual(result.signature, "Win.Test.EICAR_HDB-1")
+
+    def test_content_is_streamed_in_chunks(self):
+        content = bytes(range(256)) * 40
+        path = self.write("big.bin", content)
+
+        self.scanner(chunk_size=1000).scan_file(path)
+
+        self.assertEqual(self.clamd.streams, [(content, 11)])
+
+    def test_unreachable_daemon(self):
+        path = self.write("clean.py", b"pass\n")
+
+        with self.assertRaises(ScannerError):
+            self.scanner(port=unused_port()).scan_file(path)
+
+    def test_error_reply(self):
+        self.clamd.max_stream_bytes = 4
+        path = self.write("clean.py", b"print('hello')\n")
+
+        with self.assertRaises(ScannerError) as raised:
+            self.scanner().scan_file(path)
+        self.assertIn("size limit exceeded", str(raised.exception))
+
+    def test_parse_reply(self):
+        self.assertFalse(parse_reply(b"stream: OK\0").infected)
+        self.assertEqual(parse_reply(b"stream: Eicar-Signature FOUND\0").signature, "Eicar-Signature")
+        with self.assertRaises(ScannerError):
+            parse_reply(b"")
+
+
+class TestUploadScanner(ScannerTestCase):
+    """Tests for the policy applied to the verdicts"""
+
+    def test_clean_upload_is_accepted(self):
+        path = self.write("upload.zip", b"clean content")
+        clean = MALWARE_SCANS.labels("clean").get()
+
+        result = UploadScanner(self.scanner()).scan_file(path, "s3://bucket/upload.zip")
+
+        self.assertFalse(result.infected)
+        self.assertEqual(MALWARE_SCANS.labels("clean").get(), clean + 1)
+
+    def test_infected_upload_is_refused(self):
+        path = self.write("upload.zip", b"prefix " + EICAR)
+        infected = MALWARE_SCANS.labels("infected").get()
+
+        with self.assertLogs("app.domains.repositories.malware_scanner", "WARNING"):
+            with self.assertRaises(MalwareDetectedException) as raised:
+                UploadScanner(self.scanner(), fail_open=True).scan_file(path, "s3://bucket/upload.zip")
+
+        self.assertEqual(raised.exception.status_code, 422)
+        self.assertEqual(raised.exception.detail["error_type"], "malware_detected")
+        self.assertEqual(raised.exception.detail["signature"], "Win.Test.EICAR_HDB-1")
+        self.assertEqual(raised.exception.detail["file"], "upload.zip")
+        self.assertEqual(MALWARE_SCANS.labels("infected").get(), infected + 1)
+
+    def test_unreachable_daemon_fails_closed(self):
+        path = self.write("upload.zip", b"clean content")
+        errors = MALWARE_SCANS.labels("error").get()
+
+        with self.assertRaises(MalwareScannerUnavailableException) as raised:
+            UploadScanner(self.scanner(port=unused_port())).scan_file(path, "s3://bucket/upload.zip")
+
+        self.assertEqual(raised.exception.detail["error_type"], "malware_scanner_unavailable")
+        self.assertEqual(MALWARE_SCANS.labels("error").get(), errors + 1)
+
+    def test_unreachable_daemon_fails_open(self):
+        path = self.write("upload.zip", b"clean content")
+
+        with self.assertLogs("app.domains.repositories.malware_scanner", "WARNING"):
+            result = UploadScanner(self.scanner(port=unused_port()), fail_open=True).scan_file(path, "s3://b/u.zip")
+
+        self.assertIsNone(result)
+
+    def test_extracted_file_is_named(self):
+        self.write("project/src/main.py", b"print('hello')\n")
+        self.write("project/src/payload.bin", EICAR)
+
+        with self.assertRaises(MalwareDetectedException) as raised:
+            UploadScanner(self.scanner()).scan_directory(self.directory / "project", "s3://bucket/upload.zip")
+
+        self.assertEqual(raised.exception.detail["file"], "src/payload.bin")
+
+    def test_directory_scan_goes_on_when_failing_open(self):
+        """Files the daemon cannot scan are skipped with a warning each, the files after them still scanned."""
+        self.clamd.max_stream_bytes = 100
+        self.write("project/a.py", b"a = 1\n")
+        self.write("project/b.bin", b"0" * 200)
+        self.write("project/c.bin", b"1" * 200)
+        self.write("project/d.py", b"d = 4\n")
+        scanner = UploadScanner(self.scanner(), fail_open=True)
+
+        with self.assertLogs("app.domains.repositories.malware_scanner", "WARNING") as logs:
+            scanned = scanner.scan_directory(self.directory / "project", "s3://bucket/upload.zip")
+
+        self.assertEqual(scanned, 2)
+        self.assertEqual(len(logs.records), 2)
+        self.assertEqual(len(self.clamd.streams), 4)
+
+    def test_infected_file_after_an_unscannable_one_is_found(self):
+        self.clamd.max_stream_bytes = 100
+        self.write("project/a.bin", b"0" * 200)
+        self.write("project/b.com", EICAR)
+        scanner = UploadScanner(self.scanner(), fail_open=True)
+
+        with self.assertRaises(MalwareDetectedException) as raised:
+            scanner.scan_directory(self.directory / "project", "s3://bucket/upload.zip")
+
+        self.assertEqual(raised.exception.detail["file"], "b.com")
+
+
+@unittest.skipIf(boto3 is None, "boto3 is not installed")
+class TestS3FetcherScanning(ScannerTestCase):
+    """Tests for the scan of archives downloaded by the S3 fetcher"""
+
+    def fetch(self, archive: bytes, fail_open: bool = False, extracted_files: bool = False, port: int = None):
+        from app.domains.repositories.fetchers.s3_fetcher import S3Fetcher
+
+        settings = SimpleNamespace(
+            aws_access_key_id=None,
+            aws_secret_access_key=None,
+            aws_default_region="us-east-1",
+            malware_scan_enabled=True,
+            malware_scan_clamd_host="127.0.0.1",
+            malware_scan_clamd_port=port or self.clamd.port,
+            malware_scan_timeout_seconds=5,
+            malware_scan_fail_open=fail_open,
+            malware_scan_extracted_files=extracted_files,
+        )
+        with patch("app.domains.repositories.fetchers.s3_fetcher.get_settings", return_value=settings):
+            fetcher = S3Fetcher()
+        client = MagicMock()
+        client.download_file.side_effect = lambda bucket, key, path: Path(path).write_bytes(archive)
+        with patch.object(fetcher, "_get_s3_client", return_value=client):
+            return fetcher.fetch_s3_content("s3://bucket/upload.zip", str(self.directory))
+
+    @staticmethod
+    def archive(files: dict, compression: int = zipfile.ZIP_STORED) -> bytes:
+        buffer = io.BytesIO()
+        with zipfile.ZipFile(buffer, "w", compression) as archive:
+            for name, content in files.items():
+                archive.writestr(name, content)
+        return buffer.getvalue()
+
+    def test_clean_archive_is_extracted(self):
+        path = self.fetch(self.archive({"main.py": "print('hello')\n"}), extracted_files=True)
+
+        self.assertTrue((path / "main.py").exists())
+        self.assertEqual(len(self.clamd.streams), 2)
+
+    def test_infected_archive_is_not_extracted(self):
+        with self.assertRaises(MalwareDetectedException):
+            self.fetch(EICAR)
+
+        self.assertFalse((self.directory / "upload").exists())
+
+    def test_infected_extracted_file_is_removed(self):
+        # The mock only finds EICAR in plain bytes, deflated it is only found once extracted
+        archive = self.archive({"main.py": "print('hello')\n", "payload.bin": EICAR}, zipfile.ZIP_DEFLATED)
+
+        with self.assertRaises(MalwareDetectedException) as raised:
+            self.fetch(archive, extracted_files=True)
+
+        self.assertEqual(raised.exception.detail["file"], "payload.bin")
+        self.assertFalse((self.directory / "upload").exists())
+
+    def test_unreachable_daemon_policies(self):
+        archive = self.archive({"main.py": "print('hello')\n"})
+
+        with self.assertRaises(MalwareScannerUnavailableException):
+            self.fetch(archive, port=unused_port())
+        with self.assertLogs("app.domains.repositories.malware_scanner", "WARNING"):
+            path = self.fetch(archive, fail_open=True, port=unused_port())
+        self.assertTrue((path / "main.py").exists())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1 @@
+# Retention tests module
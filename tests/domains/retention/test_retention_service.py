@@ -0,0 +1,241 @@
+"""
+Tests for RetentionService purges and legal holds
+"""
+
+import unittest
+from datetime import datetime, timedelta
+from uuid import uuid4
+
+import pytz
+from sqlmodel import Session, SQLModel
+
+from app.domains.reports.report_service import run_reports_prefix
+from app.domains.retention.dto.retention_dto import LegalHoldDto, RetentionPolicyDto
+from app.domains.retention.retention_service import RetentionService
+from app.domains.runs.runs_models import DetectionRunStatus
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.submissions_models import SimilarityStatus, Submission, SubmissionSimilarity
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from tests.helpers import create_test_engine
+
+PARIS_TZ = pytz.timezone("Europe/Paris")
+
+
+class FakeClock:
+    """Injectable clock that only moves when told to"""
+
+    def __init__(self, now: datetime):
+        self.now = now
+
+    def __call__(self) -> datetime:
+        return self.now
+
+    def advance(self, days: int) -> None:
+        self.now += timedelta(days=days)
+
+
+class RecordingSubmissionService:
+    """Submission service double deleting the row and recording which stored files would be removed"""
+
+    def __init__(self, session: Session):
+        self.repository = SubmissionRepository(session)
+        self.deleted_files = []
+
+    def delete_submission(self, submission_id):
+        self.deleted_files.append(submission_id)
+        return self.repository.delete(submission_id)
+
+
+class TestRetentionService(unittest.TestCase):
+    """Tests for retention purges with a fast-forwarded clock"""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+
+        self.start = PARIS_TZ.localize(datetime(2024, 1, 15, 10, 0))
+        self.clock = FakeClock(self.start)
+        self.submission_service = RecordingSubmissionService(self.session)
+        self.storage_service = SubmissionStorageService(InMemorySubmissionStore(), hash_algorithm="sha256")
+        self.service = RetentionService(
+            self.session,
+            clock=self.clock,
+            submission_service=self.submission_service,
+            storage_service=self.storage_service,
+        )
+        self.run_repository = DetectionRunRepository(self.session)
+
+        self.project_uuid = uuid4()
+        self.project_step_uuid = uuid4()
+        self.service.set_policy(
+            self.project_uuid, RetentionPolicyDto(submission_retention_days=548, report_retention_days=1826)
+        )
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def create_submission(self, project_uuid=None) -> Submission:
+        submission = Submission(
+            link="https://github.com/user/repository.git",
+            project_uuid=project_uuid or self.project_uuid,
+            group_uuid=uuid4(),
+            project_step_uuid=self.project_step_uuid,
+            created_at=self.clock(),
+            upload_date_time=self.clock(),
+        )
+        self.session.add(submission)
+        self.session.commit()
+        self.session.refresh(submission)
+        return submission
+
+    def create_run(self, *participants):
+        return self.run_repository.create_run(
+            {
+                "project_uuid": self.project_uuid,
+                "project_step_uuid": self.project_step_uuid,
+                "started_at": self.clock(),
+                "status": DetectionRunStatus.COMPLETED,
+            },
+            [{"submission_id": s.id, "group_uuid": s.group_uuid} for s in participants],
+        )
+
+    def test_nothing_is_purged_inside_the_window(self):
+        """Resources younger than the retention window are kept."""
+        self.create_submission()
+        self.clock.advance(547)
+
+        report = self.service.purge()
+
+        self.assertEqual(report.total_deleted_submissions, 0)
+        self.assertEqual(report.total_deleted_runs, 0)
+
+    def test_expired_submissions_are_purged_through_the_cascade(self):
+        """Expired submissions are deleted with their similarities and stored files."""
+        expired = self.create_submission()
+        other = self.create_submission()
+        self.session.add(
+            SubmissionSimilarity(
+                submission_id=expired.id,
+                compared_submission_id=other.id,
+                project_uuid=self.project_uuid,
+                project_step_uuid=self.project_step_uuid,
+                status=SimilarityStatus.COMPLETED,
+            )
+        )
+        self.session.commit()
+        self.clock.advance(300)
+        recent = self.create_submission()
+        self.clock.advance(300)
+
+        report = self.service.purge()
+
+        self.assertEqual(set(report.projects[0].deleted_submissions), {expired.id, other.id})
+        self.assertEqual(set(self.submission_service.deleted_files), {expired.id, other.id})
+        self.assertIsNone(self.session.get(Submission, expired.id))
+        self.assertIsNotNone(self.session.get(Submission, recent.id))
+        self.assertEqual(self.service.similarity_repository.get_by_submission_id(expired.id), [])
+
+    def test_dry_run_reports_without_deleting(self):
+        """A dry run lists expired resources and deletes nothing."""
+        submission = self.create_submission()
+        self.clock.advance(2000)
+        run = self.create_run(submission)
+        self.clock.advance(2000)
+
+        report = self.service.purge(dry_run=True)
+
+        self.assertTrue(report.dry_run)
+        self.assertEqual(report.projects[0].deleted_submissions, [submission.id])
+        self.assertEqual(report.projects[0].deleted_runs, [run.id])
+        self.assertIsNotNone(self.session.get(Submission, submission.id))
+        self.assertIsNotNone(self.run_repository.get_run(run.id))
+        self.assertEqual(self.submission_service.deleted_files, [])
+
+    def test_expired_runs_are_purged_with_their_pairs(self):
+        """Runs past the report window are deleted with their pairs, participants and stored reports."""
+        submission = self.create_submission()
+        run = self.create_run(submission)
+        self.clock.advance(1827)
+        kept = self.create_run(submission)
+        for stored_run in (run, kept):
+            self.storage_service.store.put(f"{run_reports_prefix(stored_run)}report-0.html", b"<html></html>")
+
+        report = self.service.purge()
+
+        self.assertEqual(report.projects[0].deleted_runs, [run.id])
+        self.assertIsNone(self.run_repository.get_run(run.id))
+        self.assertEqual(self.run_repository.get_participants(run.id), [])
+        self.assertEqual(self.storage_service.store.list(run_reports_prefix(run)), [])
+        self.assertEqual(len(self.storage_service.store.list(run_reports_prefix(kept))), 1)
+
+    def test_submission_legal_hold_exempts_submission_and_its_runs(self):
+        """A held submission and the runs it took part in survive the purge."""
+        held = self.create_submission()
+        free = self.create_submission()
+        run = self.create_run(held, free)
+        self.service.set_submission_legal_hold(held.id, LegalHoldDto(legal_hold=True, reason="Disciplinary case"))
+        self.clock.advance(4000)
+
+        report = self.service.purge()
+
+        self.assertEqual(report.projects[0].deleted_submissions, [free.id])
+        self.assertEqual(report.projects[0].held_submissions, [held.id])
+        self.assertEqual(report.projects[0].held_runs, [run.id])
+        self.assertIsNotNone(self.session.get(Submission, held.id))
+        self.assertIsNotNone(self.run_repository.get_run(run.id))
+
+    def test_run_legal_hold_exempts_run_and_participants(self):
+        """A held run keeps its participating submissions."""
+        participant = self.create_submission()
+        outsider = self.create_submission()
+        run = self.create_run(participant)
+        self.service.set_run_legal_hold(run.id, LegalHoldDto(legal_hold=True, reason="Appeal"))
+        self.clock.advance(4000)
+
+        report = self.service.purge()
+
+        self.assertEqual(report.projects[0].deleted_submissions, [outsider.id])
+        self.assertEqual(report.projects[0].held_runs, [run.id])
+        self.assertIsNotNone(self.session.get(Submission, participant.id))
+
+    def test_lifted_hold_allows_purge(self):
+        """Once the hold is lifted the next purge deletes the submission."""
+        submission = self.create_submission()
+        self.service.set_submission_legal_hold(submission.id, LegalHoldDto(legal_hold=True))
+        self.clock.advance(600)
+        self.assertEqual(self.service.purge().total_deleted_submissions, 0)
+
+        self.service.set_submission_legal_hold(submission.id, LegalHoldDto(legal_hold=False))
+        report = self.service.purge()
+
+        self.assertEqual(report.projects[0].deleted_submissions, [submission.id])
+
+    def test_projects_without_policy_are_never_purged(self):
+        """Retention only applies to projects with a policy."""
+        submission = self.create_submission(project_uuid=uuid4())
+        self.clock.advance(10000)
+
+        self.service.purge()
+
+        self.assertIsNotNone(self.session.get(Submission, submission.id))
+
+    def test_policy_without_report_window_keeps_runs(self):
+        """A missing retention window keeps that kind of resource forever."""
+        self.service.set_policy(self.project_uuid, RetentionPolicyDto(submission_retention_days=30))
+        run = self.create_run()
+        self.clock.advance(10000)
+
+        report = self.service.purge()
+
+        self.assertIsNone(report.projects[0].report_cutoff)
+        self.assertIsNotNone(self.run_repository.get_run(run.id))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1 @@
+# RPC tests module
@@ -0,0 +1,225 @@
+"""
+Tests for the gRPC interface.
+
+Server tests call an in-process server through generated stubs, against TEST_DATABASE_URL when set and a temporary
+SQLite database otherwise, and are skipped unless grpcio and grpcio-tools are installed.
+"""
+
+import os
+import tempfile
+import unittest
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from fastapi import HTTPException
+from pydantic import SecretStr
+from sqlmodel import Session, SQLModel, create_engine
+
+from app.domains.rpc.grpc_messages import grpc_status, parse_priority, parse_uuid
+from app.domains.rpc.grpc_server import GrpcServer, grpc, load_protos
+from app.domains.runs.run_recorder import DetectionRunRecorder
+from app.domains.runs.runs_models import FragmentType
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.shared.concurrency import JobPriority
+from app.shared.exceptions import DatabaseException, NotFoundException, ValidationException
+
+
+class TestGrpcStatus(unittest.TestCase):
+    """Tests for the gRPC status of the failures of the service layer"""
+
+    def test_http_statuses_map_to_grpc_codes(self):
+        """Each failure gets the gRPC code of the HTTP status it gets over HTTP."""
+        for error, code in [
+            (NotFoundException("Detection run"), "NOT_FOUND"),
+            (ValidationException("Invalid submission"), "INVALID_ARGUMENT"),
+            (DatabaseException(), "INTERNAL"),
+            (HTTPException(status_code=401, detail="Admin scope required"), "UNAUTHENTICATED"),
+            (HTTPException(status_code=403, detail="Admin scope required"), "PERMISSION_DENIED"),
+            (HTTPException(status_code=409, detail="Nothing to compare"), "FAILED_PRECONDITION"),
+            (HTTPException(status_code=418, detail="Teapot"), "INVALID_ARGUMENT"),
+            (RuntimeError("boom"), "INTERNAL"),
+        ]:
+            self.assertEqual(grpc_status(error)[0], code, error)
+
+    def test_structured_details_are_kept(self):
+        """Structured details of refused submissions are returned as JSON."""
+        _, detail = grpc_status(ValidationException("Rules failed", details={"failed_rule_count": 1}))
+        self.assertEqual(detail, '{"failed_rule_count": 1}')
+
+    def test_request_fields_are_validated(self):
+        """Missing or malformed IDs and unknown priorities are invalid arguments."""
+        with self.assertRaises(ValidationException):
+            parse_uuid("", "run_id")
+        with self.assertRaises(ValidationException):
+            parse_uuid("not-a-uuid", "run_id")
+        self.assertIsNone(parse_uuid("", "submitted_by_uuid", required=False))
+        self.assertEqual(parse_priority(0), JobPriority.NORMAL)
+        with self.assertRaises(ValidationException):
+            parse_priority(9)
+
+
+@unittest.skipUnless(grpc is not None, "grpcio is not installed")
+class TestGrpcServer(unittest.TestCase):
+    """Tests calling the services through a client of an in-process server"""
+
+    def setUp(self):
+        self.directory = tempfile.TemporaryDirectory()
+        database_url = os.environ.get("TEST_DATABASE_URL")
+        if database_url:
+            self.engine = create_engine(database_url)
+        else:
+            self.engine = create_engine(
+                f"sqlite:///{self.directory.name}/grpc.db", connect_args={"check_same_thread": False}
+            )
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+        self.repository = DetectionRunRepository(self.session)
+
+        self.server = GrpcServer(
+            port=0, poll_interval_seconds=0.05, session_factory=lambda: Session(self.engine), host="localhost"
+        )
+        port = self.server.start()
+        self.channel = grpc.insecure_channel(f"localhost:{port}")
+        self.protos, services = load_protos()
+        self.submissions = services.SubmissionServiceStub(self.channel)
+        self.detection = services.DetectionServiceStub(self.channel)
+
+    def tearDown(self):
+        self.channel.close()
+        self.server.stop(0)
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+        self.directory.cleanup()
+
+    def create_run(self):
+        return self.repository.create_run({"project_uuid": uuid4(), "project_step_uuid": uuid4()}, [])
+
+    def pair_data(self, run, similarity: float) -> dict:
+        return {
+            "project_uuid": run.project_uuid,
+            "project_step_uuid": run.project_step_uuid,
+            "submission_id": uuid4(),
+            "compared_submission_id": uuid4(),
+            "overall_similarity": similarity,
+        }
+
+    def assert_status(self, call, code):
+        with self.assertRaises(grpc.RpcError) as raised:
+            call()
+        self.assertEqual(raised.exception.code(), code, raised.exception.details())
+        return raised.exception
+
+    def test_results_are_streamed_as_they_are_persisted(self):
+        """Persisted pairs come first, later ones as they are persisted, and the stream ends with the run."""
+        run = self.create_run()
+        recorder = DetectionRunRecorder(self.repository, run.id)
+        first = recorder.record_pair(self.pair_data(run, 0.9))
+        recorder.record_pair(self.pair_data(run, 0.4))
+        recorder.flush()
+
+        responses = self.detection.StreamResults(self.protos.StreamResultsRequest(run_id=str(run.id), min_score=0.5))
+        streamed = next(responses)
+        self.assertEqual(streamed.id, str(first.id))
+        self.assertAlmostEqual(streamed.overall_similarity, 0.9)
+
+        later = recorder.record_pair(self.pair_data(run, 0.7))
+        recorder.finish()
+
+        self.assertEqual([pair.id for pair in responses], [str(later.id)])
+
+    def test_stream_of_unknown_run_is_not_found(self):
+        """Streaming a run that does not exist ends with NOT_FOUND."""
+        request = self.protos.StreamResultsRequest(run_id=str(uuid4()))
+        self.assert_status(lambda: list(self.detection.StreamResults(request)), grpc.StatusCode.NOT_FOUND)
+
+    def test_get_run(self):
+        """A run is returned with its status and persisted pairs, unknown and malformed IDs are refused."""
+        run = self.create_run()
+        recorder = DetectionRunRecorder(self.repository, run.id)
+        recorder.record_pair(self.pair_data(run, 0.9))
+        recorder.finish()
+
+        response = self.detection.GetRun(self.protos.GetRunRequest(run_id=str(run.id)))
+        self.assertEqual(response.status, "completed")
+        self.assertEqual(response.persisted_pairs, 1)
+        self.assertTrue(response.HasField("finished_at"))
+
+        self.assert_status(
+            lambda: self.detection.GetRun(self.protos.GetRunRequest(run_id=str(uuid4()))), grpc.StatusCode.NOT_FOUND
+        )
+        self.assert_status(
+            lambda: self.detection.GetRun(self.protos.GetRunRequest(run_id="run-1")), grpc.StatusCode.INVALID_ARGUMENT
+        )
+
+    def test_get_pair_fragments(self):
+        """The fragments of a pair are split by type, with their details."""
+        run = self.create_run()
+        recorder = DetectionRunRecorder(self.repository, run.id)
+        fragment = {"file1_path": "a.py", "file2_path": "b.py", "similarity": 0.9}
+        pair = recorder.record_pair(
+            self.pair_data(run, 0.9),
+            [
+                {**fragment, "fragment_type": FragmentType.FILE, "details": {"tokens": 120}},
+                {**fragment, "fragment_type": FragmentType.BLOCK, "file1_start_line": 1, "file1_end_line": 10},
+            ],
+        )
+        recorder.finish()
+
+        response = self.detection.GetPairFragments(self.protos.GetPairFragmentsRequest(pair_id=str(pair.id)))
+        self.assertEqual(response.pair.id, str(pair.id))
+        self.assertEqual(len(response.file_fragments), 1)
+        self.assertEqual(response.file_fragments[0].details["tokens"], 120)
+        self.assertEqual(response.block_fragments[0].file1_end_line, 10)
+
+        request = self.protos.GetPairFragmentsRequest(pair_id=str(uuid4()))
+        self.assert_status(lambda: self.detection.GetPairFragments(request), grpc.StatusCode.NOT_FOUND)
+
+    def test_start_detection_needs_another_submission(self):
+        """A submission without any other to compare with cannot start a run, as over HTTP."""
+        submission = SubmissionRepository(self.session).create(
+            CreateSubmissionDto(
+                link="https://github.com/user/repository.git",
+                project_uuid=uuid4(),
+                group_uuid=uuid4(),
+                project_step_uuid=uuid4(),
+            )
+        )
+        request = self.protos.StartDetectionRequest(submission_id=str(submission.id))
+        self.assert_status(lambda: self.detection.StartDetection(request), grpc.StatusCode.FAILED_PRECONDITION)
+
+        request = self.protos.StartDetectionRequest(submission_id=str(uuid4()))
+        self.assert_status(lambda: self.detection.StartDetection(request), grpc.StatusCode.NOT_FOUND)
+
+    def test_urgent_priority_needs_the_admin_token(self):
+        """Urgent runs need the admin token in the authorization metadata."""
+        settings = SimpleNamespace(admin_api_token=SecretStr("secret"))
+        request = self.protos.StartDetectionRequest(
+            submission_id=str(uuid4()), priority=self.protos.PRIORITY_URGENT
+        )
+        with patch("app.shared.security.get_settings", return_value=settings):
+            self.assert_status(lambda: self.detection.StartDetection(request), grpc.StatusCode.UNAUTHENTICATED)
+            self.assert_status(
+                lambda: self.detection.StartDetection(request, metadata=[("authorization", "Bearer wrong")]),
+                grpc.StatusCode.PERMISSION_DENIED,
+            )
+            # Past the admin check, the submission does not exist
+            self.assert_status(
+                lambda: self.detection.StartDetection(request, metadata=[("authorization", "Bearer secret")]),
+                grpc.StatusCode.NOT_FOUND,
+            )
+
+    def test_invalid_submissions_are_refused(self):
+        """Submissions without a source or with an invalid link are invalid arguments."""
+        uuids = {"project_uuid": str(uuid4()), "group_uuid": str(uuid4()), "project_step_uuid": str(uuid4())}
+        for request in [
+            self.protos.CreateSubmissionRequest(**uuids),
+            self.protos.CreateSubmissionRequest(git_url="http://github.com/user/repository.git", **uuids),
+            self.protos.CreateSubmissionRequest(storage_reference="submissions/archive.zip", **uuids),
+            self.protos.CreateSubmissionRequest(git_url="https://github.com/user/repository.git"),
+        ]:
+            self.assert_status(lambda: self.submissions.CreateSubmission(request), grpc.StatusCode.INVALID_ARGUMENT)
@@ -0,0 +1,101 @@
+"""
+Tests for the file-pair heatmap of a detection pair
+"""
+
+import unittest
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.runs.dto.run_response_dto import HeatmapForm
+from app.domains.runs.heatmap import build_heatmap
+from app.domains.runs.runs_models import FragmentType
+
+
+class TestPairHeatmap(unittest.TestCase):
+    """Tests for the heatmap of a fixture pair: three tokenized files in A, two in B"""
+
+    def setUp(self):
+        self.pair = SimpleNamespace(
+            id=uuid4(),
+            run_id=uuid4(),
+            submission_id=uuid4(),
+            compared_submission_id=uuid4(),
+            # A binary logo.png and an excluded vendor file were not tokenized, hence not listed
+            compared_files={
+                "submission1": {"main.py": 400, "utils.py": 120, "models.py": 80},
+                "submission2": {"app.py": 380, "helpers.py": 150},
+            },
+        )
+        self.fragments = [
+            self.fragment(FragmentType.FILE, "main.py", "app.py", 0.9),
+            self.fragment(FragmentType.BLOCK, "main.py", "app.py", 0.95),
+            self.fragment(FragmentType.FILE, "utils.py", "helpers.py", 0.6),
+            self.fragment(FragmentType.FILE, "utils.py", "app.py", 0.3),
+        ]
+
+    def fragment(self, fragment_type, file1, file2, similarity):
+        return SimpleNamespace(fragment_type=fragment_type, file1_path=file1, file2_path=file2, similarity=similarity)
+
+    def test_dense_matrix_dimensions(self):
+        """The matrix has a row per file of A and a column per file of B, sorted by path, with token counts."""
+        heatmap = build_heatmap(self.pair, self.fragments)
+
+        self.assertEqual(
+            [(f.path, f.tokens) for f in heatmap.rows], [("main.py", 400), ("models.py", 80), ("utils.py", 120)]
+        )
+        self.assertEqual([(f.path, f.tokens) for f in heatmap.columns], [("app.py", 380), ("helpers.py", 150)])
+        self.assertEqual(heatmap.matrix, [[0.9, 0.0], [0.0, 0.0], [0.3, 0.6]])
+        self.assertIsNone(heatmap.cells)
+        self.assertEqual(heatmap.submission_id, self.pair.submission_id)
+
+    def test_swapped_submissions_transpose_the_matrix(self):
+        """Asking with B first gives the transposed matrix and the swapped file lists."""
+        heatmap = build_heatmap(self.pair, self.fragments)
+        transposed = build_heatmap(self.pair, self.fragments, self.pair.compared_submission_id)
+
+        self.assertEqual(transposed.rows, heatmap.columns)
+        self.assertEqual(transposed.columns, heatmap.rows)
+        self.assertEqual(transposed.matrix, [list(column) for column in zip(*heatmap.matrix)])
+        self.assertEqual(
+            (transposed.submission_id, transposed.compared_submission_id),
+            (self.pair.compared_submission_id, self.pair.submission_id),
+        )
+
+    def test_sparse_form_omits_cells_below_min_score(self):
+        """The sparse form lists the scored cells at or above min_score by index, and matches the dense matrix."""
+        dense = build_heatmap(self.pair, self.fragments)
+        sparse = build_heatmap(self.pair, self.fragments, form=HeatmapForm.SPARSE, min_score=0.5)
+
+        self.assertIsNone(sparse.matrix)
+        self.assertEqual([(c.row, c.column, c.score) for c in sparse.cells], [(0, 0, 0.9), (2, 1, 0.6)])
+        self.assertTrue(all(dense.matrix[c.row][c.column] == c.score for c in sparse.cells))
+        self.assertEqual(len(build_heatmap(self.pair, self.fragments, form=HeatmapForm.SPARSE).cells), 3)
+
+        transposed = build_heatmap(self.pair, self.fragments, self.pair.compared_submission_id, HeatmapForm.SPARSE, 0.5)
+        self.assertEqual(
+            sorted((c.column, c.row, c.score) for c in transposed.cells),
+            [(c.row, c.column, c.score) for c in sparse.cells],
+        )
+
+    def test_uncompared_files_are_absent(self):
+        """Files that were not tokenized are neither rows nor columns, not zero-scored lines."""
+        heatmap = build_heatmap(self.pair, self.fragments)
+        paths = {f.path for f in heatmap.rows + heatmap.columns}
+
+        self.assertNotIn("logo.png", paths)
+        self.assertEqual(len(heatmap.matrix), 3)
+        self.assertTrue(all(len(row) == 2 for row in heatmap.matrix))
+
+    def test_pairs_without_compared_files_use_their_file_fragments(self):
+        """Pairs recorded before the compared files were kept list the files of their file fragments."""
+        self.pair.compared_files = None
+
+        heatmap = build_heatmap(self.pair, self.fragments)
+
+        self.assertEqual([(f.path, f.tokens) for f in heatmap.rows], [("main.py", None), ("utils.py", None)])
+        self.assertEqual([f.path for f in heatmap.columns], ["app.py", "helpers.py"])
+        self.assertEqual(heatmap.matrix, [[0.9, 0.0], [0.3, 0.6]])
+
+
+if __name__ == "__main__":
+    unittest.main()
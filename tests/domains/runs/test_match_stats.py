@@ -0,0 +1,121 @@
+"""
+Tests for the match statistics of pairs and their CSV export
+"""
+
+import csv
+import unittest
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.runs.dto.run_response_dto import PairSortKey
+from app.domains.runs.match_stats import CSV_COLUMNS, block_lines, match_stats, pairs_csv
+from app.domains.runs.runs_models import FragmentType
+from app.shared.i18n import Locale
+
+
+def fragment(fragment_type, start=None, end=None, tokens=None, similarity=0.9):
+    return SimpleNamespace(
+        id=uuid4(),
+        fragment_type=fragment_type,
+        file1_path="main.py",
+        file2_path="main.py",
+        file1_start_line=start,
+        file1_end_line=end,
+        file2_start_line=start + 3 if start is not None else None,
+        file2_end_line=end + 3 if end is not None else None,
+        similarity=similarity,
+        details={"matched_tokens": tokens} if tokens is not None else None,
+    )
+
+
+def pair(similarity):
+    return SimpleNamespace(
+        id=uuid4(),
+        submission_id=uuid4(),
+        compared_submission_id=uuid4(),
+        submitted_by_uuid=uuid4(),
+        compared_submitted_by_uuid=None,
+        overall_similarity=similarity,
+    )
+
+
+class TestMatchStats(unittest.TestCase):
+    """Tests for two pairs of 40% similarity, one of forty scattered 1-line matches and one of a 200-line block"""
+
+    def setUp(self):
+        self.scattered = [fragment(FragmentType.FILE)] + [
+            fragment(FragmentType.BLOCK, line * 5, line * 5, tokens=4) for line in range(40)
+        ]
+        self.block = [fragment(FragmentType.FILE), fragment(FragmentType.BLOCK, 10, 209, tokens=1800)]
+
+    def test_statistics_separate_scattered_matches_from_a_block(self):
+        """The long block wins on longest match and top coverage, the scattered pair only on fragment count."""
+        scattered = match_stats(self.scattered)
+        block = match_stats(self.block)
+
+        self.assertEqual((scattered.fragments_count, block.fragments_count), (40, 1))
+        self.assertEqual((scattered.longest_fragment_lines, block.longest_fragment_lines), (1, 200))
+        self.assertEqual((scattered.longest_fragment_tokens, block.longest_fragment_tokens), (4, 1800))
+        self.assertEqual((scattered.mean_fragment_lines, block.mean_fragment_lines), (1.0, 200.0))
+        self.assertEqual((scattered.top_fragments_coverage, block.top_fragments_coverage), (0.075, 1.0))
+
+    def test_statistics_follow_the_fragment_list(self):
+        """Statistics are recomputed from the blocks listed, with unknown tokens for blocks recorded without them."""
+        blocks = [
+            fragment(FragmentType.BLOCK, 0, 9),
+            fragment(FragmentType.BLOCK, 20, 24, tokens=30),
+            fragment(FragmentType.BLOCK, 40, 41, tokens=12),
+            fragment(FragmentType.BLOCK, 50, 50, tokens=5),
+        ]
+        stats = match_stats(blocks + [fragment(FragmentType.FILE)])
+
+        lines = [block_lines(f) for f in blocks]
+        self.assertEqual(stats.fragments_count, len(blocks))
+        self.assertEqual(stats.longest_fragment_lines, max(lines))
+        self.assertIsNone(stats.longest_fragment_tokens)
+        self.assertEqual(stats.mean_fragment_lines, sum(lines) / len(lines))
+        self.assertEqual(stats.top_fragments_coverage, round(17 / 18, 4))
+        self.assertEqual(match_stats([fragment(FragmentType.FILE)]).fragments_count, 0)
+
+    def test_csv_export_sorts_by_longest_match(self):
+        """Sorting by longest match lists the block pair first though the scattered pair scores higher."""
+        scattered_pair, block_pair = pair(0.41), pair(0.40)
+        rows = [(scattered_pair, match_stats(self.scattered)), (block_pair, match_stats(self.block))]
+
+        by_score = list(csv.DictReader(pairs_csv(rows)))
+        by_longest = list(csv.DictReader(pairs_csv(rows, PairSortKey.LONGEST_FRAGMENT_LINES)))
+
+        self.assertEqual(list(by_score[0]), CSV_COLUMNS)
+        self.assertEqual([r["pair_id"] for r in by_score], [str(scattered_pair.id), str(block_pair.id)])
+        self.assertEqual([r["pair_id"] for r in by_longest], [str(block_pair.id), str(scattered_pair.id)])
+        self.assertEqual(by_longest[0]["longest_fragment_tokens"], "1800")
+        self.assertEqual(by_longest[0]["compared_submitted_by_uuid"], "")
+
+    def test_csv_export_lists_unknown_tokens_last(self):
+        """Pairs whose longest block has no token count come last when sorting by tokens."""
+        unknown, known = pair(0.9), pair(0.5)
+        rows = [
+            (unknown, match_stats([fragment(FragmentType.BLOCK, 0, 99)])),
+            (known, match_stats([fragment(FragmentType.BLOCK, 0, 4, tokens=20)])),
+        ]
+
+        exported = list(csv.DictReader(pairs_csv(rows, PairSortKey.LONGEST_FRAGMENT_TOKENS)))
+
+        self.assertEqual([r["pair_id"] for r in exported], [str(known.id), str(unknown.id)])
+        self.assertEqual(exported[1]["longest_fragment_tokens"], "")
+
+    def test_csv_header_is_localized(self):
+        """The French export labels the same columns in the same order, its rows are those of the English one."""
+        rows = [(pair(0.41), match_stats(self.scattered)), (pair(0.40), match_stats(self.block))]
+
+        english = list(csv.reader(pairs_csv(rows)))
+        french = list(csv.reader(pairs_csv(rows, locale=Locale.FR)))
+
+        self.assertEqual(english[0], CSV_COLUMNS)
+        self.assertEqual(len(french[0]), len(CSV_COLUMNS))
+        self.assertEqual(french[0][5], "similarité globale")
+        self.assertEqual(french[1:], english[1:])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,144 @@
+"""
+Tests for the highlighted code of the shared blocks of a pair view
+"""
+
+import html
+import re
+import unittest
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.reports.highlighting import MATCH_END, MATCH_START
+from app.domains.reports.html_report import plain_lines
+from app.domains.runs.dto.run_response_dto import DetectionFragmentDto
+from app.domains.runs.pair_view import CONTEXT_LINES, highlight_blocks, highlighted_code
+from app.domains.runs.runs_models import FragmentType
+
+PYTHON_SAMPLE = (
+    "import math\n"
+    "\n"
+    "def area(radius):\n"
+    '    """Area of a circle\n'
+    '    of a given <radius>"""\n'
+    "    return math.pi * radius ** 2  # & done\n"
+    "\n"
+    "print(area(2))\n"
+)
+RUST_SAMPLE = (
+    "use std::f64::consts::PI;\n"
+    "\n"
+    "/* Area of a circle\n"
+    "   of a given radius */\n"
+    "fn area(radius: f64) -> f64 {\n"
+    '    let label = "<area>";\n'
+    "    PI * radius * radius\n"
+    "}\n"
+    "\n"
+    "fn main() { println!(\"{}\", area(2.0)); }\n"
+)
+TAG = re.compile(r"</?(\w+)[^>]*>")
+
+
+def text_of(line_html: str) -> str:
+    return html.unescape(TAG.sub("", line_html))
+
+
+def balanced(line_html: str) -> bool:
+    stack = []
+    for match in TAG.finditer(line_html):
+        if match.group(0).startswith("</"):
+            if not stack or stack.pop() != match.group(1):
+                return False
+        else:
+            stack.append(match.group(1))
+    return not stack
+
+
+class TestPairView(unittest.TestCase):
+    """Tests for highlighting the matched regions of Python and Rust fragments"""
+
+    def assert_region(self, source, path, start, end):
+        code = highlighted_code(source, path, start, end)
+        unhighlighted = plain_lines(source, path)
+
+        for line in code.lines:
+            self.assertEqual(text_of(line.html), unhighlighted[line.number - 1])
+            self.assertTrue(balanced(line.html), line.html)
+            self.assertEqual(line.matched, start + 1 <= line.number <= end + 1)
+            if line.matched:
+                self.assertTrue(line.html.startswith(MATCH_START) and line.html.endswith(MATCH_END))
+            else:
+                self.assertNotIn(MATCH_START, line.html)
+        self.assertEqual(code.lines[0].number, max(1, start + 1 - CONTEXT_LINES))
+        return code
+
+    def test_python_matched_region_markers_survive_highlighting(self):
+        """A matched region starting inside a docstring keeps whole, balanced marks around its lines."""
+        code = self.assert_region(PYTHON_SAMPLE, "geometry.py", 4, 5)
+
+        self.assertEqual(code.language, "python")
+        lines = {line.number: line.html for line in code.lines}
+        self.assertEqual([line.number for line in code.lines if line.matched], [5, 6])
+        self.assertIn('<span class="s">    of a given &lt;radius&gt;&quot;&quot;&quot;</span>', lines[5])
+        self.assertIn('<span class="c"># &amp; done</span>', lines[6])
+
+    def test_rust_matched_region_markers_survive_highlighting(self):
+        """A matched region covering a block comment and a string keeps its marks and its line numbers."""
+        code = self.assert_region(RUST_SAMPLE, "src/main.rs", 3, 6)
+
+        self.assertEqual(code.language, "rust")
+        self.assertEqual([line.number for line in code.lines], list(range(1, 11)))
+        self.assertIn('<span class="k">fn</span>', code.lines[4].html)
+        self.assertEqual(code.lines[3].html, f'{MATCH_START}<span class="c">   of a given radius */</span>{MATCH_END}')
+
+    def test_unsupported_languages_are_escaped_plain_text(self):
+        """Files without a lexer keep their marks around plain escaped text."""
+        code = self.assert_region("a <b>\r\nc & d\r\n", "notes.unknown", 1, 1)
+
+        self.assertIsNone(code.language)
+        self.assertEqual(code.lines[1].html, f"{MATCH_START}c &amp; d{MATCH_END}")
+
+    def test_blocks_of_a_pair_are_highlighted_from_their_sources(self):
+        """Both sides of blocks get their code, file fragments none, missing files are marked unavailable."""
+        pair = SimpleNamespace(submission_id=uuid4(), compared_submission_id=uuid4())
+        sources = {(str(pair.submission_id), "geometry.py"): PYTHON_SAMPLE}
+        reads = []
+
+        def read(submission_id, path):
+            reads.append((submission_id, path))
+            return sources.get((submission_id, path))
+
+        fragments = [
+            DetectionFragmentDto(
+                id=uuid4(),
+                fragment_type=FragmentType.FILE,
+                file1_path="geometry.py",
+                file2_path="main.rs",
+                similarity=1.0,
+            ),
+            DetectionFragmentDto(
+                id=uuid4(),
+                fragment_type=FragmentType.BLOCK,
+                file1_path="geometry.py",
+                file2_path="main.rs",
+                file1_start_line=2,
+                file1_end_line=5,
+                file2_start_line=4,
+                file2_end_line=6,
+                similarity=0.9,
+            ),
+        ]
+        # A second block of the same files, which are not read again
+        fragments.append(fragments[1].model_copy(update={"id": uuid4()}))
+
+        highlight_blocks(pair, fragments, read)
+
+        self.assertIsNone(fragments[0].file1_code)
+        self.assertEqual([line.number for line in fragments[1].file1_code.lines if line.matched], [3, 4, 5, 6])
+        self.assertFalse(fragments[1].file2_code.available)
+        self.assertEqual(fragments[1].file2_code.lines, [])
+        self.assertEqual(len(reads), 2)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,194 @@
+"""
+Tests for the NDJSON stream of the full results of a run
+"""
+
+import gzip
+import json
+import tracemalloc
+import unittest
+from datetime import datetime
+from types import SimpleNamespace
+from uuid import UUID, uuid4
+
+from app.domains.reports.anonymization import RunPseudonyms
+from app.domains.runs.results_stream import accepts_gzip, gzip_chunks, pair_view_url, results_ndjson
+from app.domains.runs.runs_models import DetectionRunStatus, DetectionRunTrigger, FragmentType
+from app.domains.submissions.submissions_models import SimilarityStatus
+
+CREATED_AT = datetime(2024, 1, 15, 10, 30)
+
+
+class SyntheticRun:
+    """Run of many pairs between a fixed set of submissions, whose pairs and fragments are built when read"""
+
+    def __init__(self, pair_count: int, submission_count: int = 50):
+        self.pair_count = pair_count
+        self.run = SimpleNamespace(
+            id=uuid4(),
+            project_uuid=uuid4(),
+            project_step_uuid=uuid4(),
+            trigger=DetectionRunTrigger.SUBMISSION,
+            detection_algorithm="hybrid",
+            detection_version="2.1.0",
+            status=DetectionRunStatus.COMPLETED,
+            total_pairs=pair_count,
+            completed_pairs=pair_count,
+            failed_pairs=0,
+            started_at=CREATED_AT,
+        )
+        self.participants = [
+            SimpleNamespace(submission_id=uuid4(), group_uuid=uuid4(), submitted_by_uuid=uuid4())
+            for _ in range(submission_count)
+        ]
+
+    def pair(self, number: int):
+        count = len(self.participants)
+        first, second = self.participants[number % count], self.participants[(number * 7 + 1) % count]
+        similarity = (number % 100) / 100
+        return SimpleNamespace(
+            id=UUID(int=number + 1),
+            run_id=self.run.id,
+            project_uuid=self.run.project_uuid,
+            project_step_uuid=self.run.project_step_uuid,
+            submission_id=first.submission_id,
+            compared_submission_id=second.submission_id,
+            submitted_by_uuid=first.submitted_by_uuid,
+            overall_similarity=similarity,
+            jaccard_similarity=similarity,
+            type_similarity=similarity,
+            structural_similarity=similarity,
+            type_sequence_similarity=similarity,
+            flow_similarity=similarity,
+            operation_similarity=similarity,
+            fragments_count=2,
+            status=SimilarityStatus.FAILED if number % 50 == 0 else SimilarityStatus.COMPLETED,
+            created_at=CREATED_AT,
+        )
+
+    def pairs(self, min_score: float = 0.0):
+        for number in range(self.pair_count):
+            pair = self.pair(number)
+            if pair.overall_similarity >= min_score:
+                yield pair
+
+    def fragments(self, pair_ids):
+        return {
+            str(pair_id): [
+                SimpleNamespace(
+                    id=UUID(int=(pair_id.int << 8) + block),
+                    fragment_type=FragmentType.BLOCK,
+                    file1_path="/home/jdupont/main.py",
+                    file2_path="app.py",
+                    file1_start_line=block * 10,
+                    file1_end_line=block * 10 + 9,
+                    file2_start_line=block * 10 + 4,
+                    file2_end_line=block * 10 + 13,
+                    similarity=0.9,
+                    details={"matched_tokens": 40},
+                )
+                for block in range(2)
+            ]
+            for pair_id in pair_ids
+        }
+
+    def stream(self, min_score=0.0, include_fragments=True, pseudonyms=None, batch_size=200):
+        return results_ndjson(
+            self.run,
+            self.participants,
+            self.pairs(min_score),
+            self.fragments,
+            min_score,
+            0.9,
+            include_fragments,
+            pseudonyms,
+            batch_size,
+        )
+
+
+def records(chunks):
+    return [json.loads(line) for chunk in chunks for line in chunk.splitlines()]
+
+
+class TestResultsStream(unittest.TestCase):
+    """Tests for the records of the stream of a synthetic run"""
+
+    def test_stream_is_metadata_pairs_clusters_then_end(self):
+        """The metadata comes first and the end record last, with every pair read and the clusters between."""
+        synthetic = SyntheticRun(120)
+        lines = records(synthetic.stream(min_score=0.5))
+        types = [record["type"] for record in lines]
+        pairs = [record for record in lines if record["type"] == "pair"]
+
+        self.assertEqual(types[0], "metadata")
+        self.assertEqual(lines[0]["run"]["id"], str(synthetic.run.id))
+        self.assertEqual(len(lines[0]["participants"]), 50)
+        self.assertEqual(types[-1], "end")
+        self.assertEqual(types[1:-1], ["pair"] * len(pairs) + ["cluster"] * lines[-1]["clusters"])
+        self.assertEqual(len(pairs), len(list(synthetic.pairs(0.5))))
+        self.assertEqual(lines[-1]["pairs"], len(pairs))
+        self.assertIn("failed", {record["status"] for record in pairs})
+        self.assertEqual(pairs[0]["fragments"][1]["file1_start_line"], 10)
+
+    def test_fragments_are_referenced_unless_inlined(self):
+        """Without include_fragments each pair links its pair view instead of carrying its fragments."""
+        synthetic = SyntheticRun(10)
+        pairs = [record for record in records(synthetic.stream(include_fragments=False)) if record["type"] == "pair"]
+
+        self.assertNotIn("fragments", pairs[0])
+        self.assertEqual(pairs[0]["fragments_url"], pair_view_url(pairs[0]["id"]))
+
+    def test_anonymized_stream_names_submissions_by_pseudonym(self):
+        """Anonymized streams carry no submission ID or home directory user name."""
+        synthetic = SyntheticRun(20)
+        pseudonyms = RunPseudonyms(synthetic.run.id, synthetic.participants)
+        document = "".join(synthetic.stream(pseudonyms=pseudonyms))
+
+        for participant in synthetic.participants:
+            self.assertNotIn(str(participant.submission_id), document)
+        self.assertNotIn("jdupont", document)
+
+    def test_large_run_streams_in_bounded_memory(self):
+        """Streaming a large run holds about a batch in memory, and writes every pair exactly once."""
+        synthetic = SyntheticRun(10000)
+        seen = bytearray(synthetic.pair_count)
+        size = duplicates = 0
+
+        tracemalloc.start()
+        try:
+            for chunk in synthetic.stream():
+                size += len(chunk)
+                for line in chunk.splitlines():
+                    record = json.loads(line)
+                    if record["type"] == "pair":
+                        number = UUID(record["id"]).int - 1
+                        duplicates += seen[number]
+                        seen[number] = 1
+            _, peak = tracemalloc.get_traced_memory()
+        finally:
+            tracemalloc.stop()
+
+        self.assertEqual(duplicates, 0)
+        self.assertEqual(sum(seen), synthetic.pair_count)
+        self.assertGreater(size, 10 * 1024 * 1024)
+        self.assertLess(peak, 3 * 1024 * 1024)
+
+    def test_gzip_stream_decompresses_to_the_plain_stream(self):
+        """The gzipped stream is a valid gzip member of the same lines."""
+        synthetic = SyntheticRun(300)
+        plain = "".join(synthetic.stream()).encode("utf-8")
+        compressed = b"".join(gzip_chunks(synthetic.stream()))
+
+        self.assertEqual(gzip.decompress(compressed), plain)
+        self.assertLess(len(compressed), len(plain))
+
+    def test_accepts_gzip_reads_quality_values(self):
+        """Gzip is used when listed without a zero quality value."""
+        self.assertTrue(accepts_gzip("gzip, deflate, br"))
+        self.assertTrue(accepts_gzip("br;q=1.0, gzip;q=0.8"))
+        self.assertFalse(accepts_gzip("gzip;q=0"))
+        self.assertFalse(accepts_gzip("identity"))
+        self.assertFalse(accepts_gzip(None))
+
+
+if __name__ == "__main__":
+    unittest.main()
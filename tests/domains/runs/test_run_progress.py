@@ -0,0 +1,214 @@
+"""
+Tests for the live progress of detection runs streamed as Server-Sent Events
+"""
+
+import asyncio
+import json
+import threading
+import time
+import unittest
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.runs.run_progress import RunProgress, RunProgressRegistry, run_events
+from app.domains.runs.runs_models import DetectionRunStatus
+
+
+def parse_events(chunks):
+    """Events of a stream as (id, type, data), keep-alive comments dropped"""
+    events = []
+    for chunk in chunks:
+        if chunk.startswith(":"):
+            continue
+        fields = dict(line.split(": ", 1) for line in chunk.strip().split("\n"))
+        event_id = int(fields["id"]) if "id" in fields else None
+        events.append((event_id, fields["event"], json.loads(fields["data"])))
+    return events
+
+
+def collect(stream, on_first=None):
+    """Read a whole stream, calling on_first once its first chunk was received"""
+
+    async def read():
+        chunks = []
+        async for chunk in stream:
+            chunks.append(chunk)
+            if on_first is not None and len(chunks) == 1:
+                on_first()
+        return chunks
+
+    return parse_events(asyncio.run(asyncio.wait_for(read(), 10)))
+
+
+def database_run(status, completed_pairs=0, total_pairs=3):
+    return SimpleNamespace(
+        id=uuid4(),
+        status=status,
+        completed_pairs=completed_pairs,
+        failed_pairs=0,
+        total_pairs=total_pairs,
+        file_errors=[],
+        error_message=None,
+    )
+
+
+class TestRunProgress(unittest.TestCase):
+    """Tests for the events of the progress of a run"""
+
+    def setUp(self):
+        self.now = 0.0
+        self.events = []
+        self.progress = RunProgress(uuid4(), self.events.append, total_pairs=3, clock=lambda: self.now)
+
+    def test_progress_events_are_throttled(self):
+        """Counters reported within the progress interval ride on the next event."""
+        self.progress.pair_compared()
+        self.progress.pair_compared()
+        self.now = 1.0
+        self.progress.pair_compared()
+
+        self.assertEqual([e.type for e in self.events], ["progress", "progress"])
+        self.assertEqual(self.events[-1].data["pairs_compared"], 3)
+
+    def test_reports_after_the_end_are_ignored(self):
+        """The terminal event is the last one of a run."""
+        self.progress.finish(DetectionRunStatus.FAILED, "Storage unavailable")
+        self.progress.pair_compared()
+        self.progress.warn("late")
+
+        self.assertEqual([e.type for e in self.events], ["failed"])
+        self.assertEqual(self.events[0].data["error_message"], "Storage unavailable")
+        self.assertEqual(self.events[0].data["status"], "failed")
+
+    def test_history_gap(self):
+        """Events too old for the history cannot be replayed."""
+        progress = RunProgress(uuid4(), history_size=2)
+        for stage in ("candidate_generation", "comparison", "other"):
+            progress.set_stage(stage)
+
+        self.assertEqual([e.id for e in progress.events_after(1)], [2, 3])
+        self.assertIsNone(progress.events_after(0))
+        self.assertEqual(progress.events_after(3), [])
+
+
+class TestRunEvents(unittest.TestCase):
+    """Tests for the Server-Sent Events of a run"""
+
+    def setUp(self):
+        self.registry = RunProgressRegistry()
+        self.run_id = uuid4()
+        self.progress = self.registry.start(self.run_id, total_pairs=20)
+        self.progress.progress_interval_seconds = 0
+
+    def stream(self, last_event_id=None, read_run=None):
+        return run_events(self.run_id, read_run, last_event_id, self.registry, keepalive_seconds=0.05)
+
+    def run_worker(self):
+        """Report the comparison of the 20 pairs of the run from a thread, as the detection workers do"""
+
+        def work():
+            self.progress.set_stage("comparison")
+            for _ in range(20):
+                self.progress.file_tokenized(2)
+                self.progress.pair_compared()
+                time.sleep(0.002)
+            self.progress.warn("File skipped at tokenization", submission="submission2", path="broken.py")
+            self.progress.finish(DetectionRunStatus.COMPLETED)
+
+        thread = threading.Thread(target=work)
+        thread.start()
+        return thread
+
+    def test_client_connecting_during_a_run(self):
+        """A client connecting mid-run gets a snapshot, growing counters and ends at the completed event."""
+        self.progress.pair_compared()
+        threads = []
+        events = collect(self.stream(), on_first=lambda: threads.append(self.run_worker()))
+        threads[0].join()
+
+        self.assertEqual(events[0][1], "snapshot")
+        self.assertEqual(events[0][2]["pairs_compared"], 1)
+        self.assertEqual(events[-1][1], "completed")
+        self.assertEqual(events[-1][2]["pairs_compared"], 21)
+        self.assertEqual(events[-1][2]["files_tokenized"], 40)
+        self.assertEqual(events[-1][2]["warnings"], 1)
+        self.assertIn("warning", [event[1] for event in events])
+
+        ids = [event[0] for event in events]
+        self.assertEqual(ids, sorted(set(ids)))
+        for counter in ("pairs_compared", "files_tokenized", "warnings"):
+            values = [event[2][counter] for event in events]
+            self.assertEqual(values, sorted(values), counter)
+        self.assertEqual(self.registry.subscriber_count(self.run_id), 0)
+
+    def test_reconnection_replays_missed_events(self):
+        """A client reconnecting after the end gets the events it missed, terminal event included."""
+        self.progress.set_stage("comparison")
+        self.progress.pair_compared()
+        self.progress.pair_compared()
+        self.progress.finish(DetectionRunStatus.COMPLETED)
+
+        events = collect(self.stream(last_event_id="1"))
+
+        self.assertEqual([(e[0], e[1]) for e in events], [(2, "progress"), (3, "progress"), (4, "completed")])
+
+    def test_reconnection_beyond_the_history(self):
+        """A client whose last event left the history starts over with a snapshot, then the terminal event."""
+        progress = RunProgress(self.run_id, history_size=2)
+        self.registry._runs[self.run_id] = progress
+        for stage in ("candidate_generation", "comparison"):
+            progress.set_stage(stage)
+        progress.finish(DetectionRunStatus.INCOMPLETE, "2 pairs could not be persisted")
+
+        events = collect(self.stream(last_event_id="0"))
+
+        self.assertEqual([e[1] for e in events], ["snapshot", "completed"])
+        self.assertEqual(events[1][2]["status"], "incomplete")
+
+    def test_disconnected_client_is_unsubscribed(self):
+        """The stream ends once the client went away, its queue removed."""
+
+        async def disconnected():
+            return True
+
+        async def read():
+            stream = run_events(
+                self.run_id, None, None, self.registry, is_disconnected=disconnected, keepalive_seconds=0.01
+            )
+            return [chunk async for chunk in stream]
+
+        events = parse_events(asyncio.run(read()))
+
+        self.assertEqual([e[1] for e in events], ["snapshot"])
+        self.assertEqual(self.registry.subscriber_count(self.run_id), 0)
+
+    def test_run_unknown_to_the_instance(self):
+        """Runs of other instances are followed from the database until they finish."""
+        self.run_id = uuid4()
+        reads = [
+            database_run(DetectionRunStatus.RUNNING, 0),
+            database_run(DetectionRunStatus.RUNNING, 2),
+            database_run(DetectionRunStatus.COMPLETED, 3),
+        ]
+
+        stream = run_events(self.run_id, lambda: reads.pop(0), None, self.registry, poll_seconds=0.01)
+        events = collect(stream)
+
+        self.assertEqual([e[1] for e in events], ["snapshot", "progress", "completed"])
+        self.assertEqual([e[2]["pairs_compared"] for e in events], [0, 2, 3])
+        self.assertTrue(all(e[0] is None for e in events))
+
+    def test_finished_runs_are_forgotten(self):
+        """Runs finished longer than the retention ago are followed from the database."""
+        now = [0.0]
+        registry = RunProgressRegistry(retention_seconds=60, clock=lambda: now[0])
+        run_id = uuid4()
+        registry.start(run_id).finish(DetectionRunStatus.COMPLETED)
+        now[0] = 61.0
+        registry.start(uuid4())
+
+        self.assertIsNone(registry.get(run_id))
+
+
+if __name__ == "__main__":
+    unittest.main()
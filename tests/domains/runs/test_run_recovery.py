@@ -0,0 +1,220 @@
+"""
+Tests for runs interrupted by a shutdown and their recovery by a restarted instance.
+
+Runs against TEST_DATABASE_URL when set (e.g. a throwaway PostgreSQL database), in-memory SQLite otherwise.
+"""
+
+import threading
+import unittest
+from datetime import timedelta
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from sqlmodel import Session, SQLModel
+
+from app.config.config import Settings
+from app.domains.detection.pruning import PrunedPair
+from app.domains.fingerprints.fingerprint_models import FingerprintCacheStats
+from app.domains.runs.run_recovery import RunRecovery, interrupt_queued_runs
+from app.domains.runs.runs_models import DetectionRunStatus
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+from app.shared.concurrency import JobScheduler
+from app.shared.timestamps import utc_now
+from tests.helpers import create_test_engine
+
+SETTINGS = Settings(detection_comparison_workers=1, detection_run_batch_size=2, comparison_index_enabled=False)
+
+
+class RunRecoveryTestCase(unittest.TestCase):
+    """Base class creating a fresh schema for each test."""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+        self.repository = DetectionRunRepository(self.session)
+        self.project_uuid = uuid4()
+        self.project_step_uuid = uuid4()
+        settings = patch("app.config.config.get_settings", return_value=SETTINGS)
+        settings.start()
+        self.addCleanup(settings.stop)
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def new_session(self):
+        return Session(self.engine)
+
+    def create_run(self, others: int, instance_id: str = "pod-a", heartbeat_at=None):
+        trigger = uuid4()
+        participants = [trigger] + [uuid4() for _ in range(others)]
+        return self.repository.create_run(
+            {
+                "project_uuid": self.project_uuid,
+                "project_step_uuid": self.project_step_uuid,
+                "trigger_submission_id": trigger,
+                "total_pairs": others,
+                "instance_id": instance_id,
+                "heartbeat_at": heartbeat_at or utc_now(),
+            },
+            [{"submission_id": submission_id, "group_uuid": uuid4()} for submission_id in participants],
+        )
+
+    def new_service(self, scheduler: JobScheduler, compare) -> DetectionIntegrationService:
+        """Detection service of one instance, pairs pruned by compare instead of compared in detail"""
+        service = DetectionIntegrationService.__new__(DetectionIntegrationService)
+        service.session = self.session
+        service.run_repository = self.repository
+        service.job_scheduler = scheduler
+        service.fingerprint_service = SimpleNamespace(new_stats=FingerprintCacheStats)
+        service._local = threading.local()
+        service._get_thread_session = self.new_session
+
+        def compare_or_prune(pair, *args):
+            compare(pair)
+            first, second = (
+                SimpleNamespace(
+                    id=submission_id,
+                    project_uuid=self.project_uuid,
+                    project_step_uuid=self.project_step_uuid,
+                    submitted_by_uuid=None,
+                )
+                for submission_id in pair
+            )
+            return PrunedPair(pair[0], pair[1], 0.1), first, second
+
+        service._compare_or_prune_threaded = compare_or_prune
+        return service
+
+    def queue(self, service: DetectionIntegrationService, run) -> None:
+        others = [p.submission_id for p in self.repository.get_participants(run.id)]
+        others.remove(run.trigger_submission_id)
+        service._queue_detection_run(
+            run.id, run.trigger_submission_id, others, self.project_uuid, self.project_step_uuid
+        )
+
+    def reload(self, run):
+        self.session.expire_all()
+        return self.repository.get_run(run.id)
+
+    def compared(self, run) -> list:
+        """The other participant of each persisted pair of a run"""
+        pairs = self.repository.get_pairs(run.id, limit=1000)
+        return sorted(
+            str(pair.compared_submission_id if pair.submission_id == run.trigger_submission_id else pair.submission_id)
+            for pair in pairs
+        )
+
+
+class TestShutdownMidRun(RunRecoveryTestCase):
+    """Tests for a SIGTERM received while a run compares its pairs"""
+
+    def test_interrupted_run_is_resumed_without_duplicate_pairs(self):
+        """The running run checkpoints its pairs and is interrupted, a restart compares the pairs left only."""
+        running, waiting = self.create_run(6), self.create_run(3)
+        scheduler = JobScheduler("detection", 1)
+        compared, mid_run = [], threading.Event()
+
+        def slow_compare(pair):
+            compared.append(pair)
+            if len(compared) == 4:
+                # The fourth comparison is still going when SIGTERM arrives, it takes longer than the grace period
+                mid_run.set()
+                scheduler.interrupted.wait(5)
+
+        service = self.new_service(scheduler, slow_compare)
+        self.queue(service, running)
+        self.queue(service, waiting)
+        self.assertTrue(mid_run.wait(5))
+
+        # What the lifespan does on SIGTERM
+        dropped = scheduler.drain(grace_seconds=0.05, interrupt_timeout_seconds=5)
+        interrupt_queued_runs(dropped, session_factory=self.new_session)
+
+        self.assertEqual(dropped, [waiting.id])
+        interrupted = self.reload(running)
+        self.assertEqual(interrupted.status, DetectionRunStatus.INTERRUPTED)
+        self.assertIsNone(interrupted.finished_at)
+        self.assertIn("Interrupted by a shutdown after 4/6 pairs", interrupted.error_message)
+        self.assertEqual(self.repository.count_pairs(running.id), 4)
+        self.assertEqual(self.reload(waiting).status, DetectionRunStatus.INTERRUPTED)
+        self.assertEqual(self.repository.count_pairs(waiting.id), 0)
+
+        # Another instance starts and resumes both runs, their jobs start once the sweep is over
+        restarted, sweeping = JobScheduler("detection", 1), threading.Event()
+        restarted.submit(sweeping.wait, 5)
+        resumed_pairs = []
+        recovery = RunRecovery(
+            "pod-b",
+            session_factory=self.new_session,
+            resume=self.new_service(restarted, resumed_pairs.append).resume_detection_run,
+        )
+        self.assertEqual(recovery.sweep_once(startup=True), 2)
+        sweeping.set()
+        restarted.shutdown(wait=True)
+
+        self.assertEqual(len(resumed_pairs), 2 + 3)
+        for run, total in ((running, 6), (waiting, 3)):
+            finished = self.reload(run)
+            self.assertEqual(finished.status, DetectionRunStatus.COMPLETED)
+            self.assertEqual(finished.instance_id, "pod-b")
+            others = [str(p.submission_id) for p in self.repository.get_participants(run.id)]
+            others.remove(str(run.trigger_submission_id))
+            self.assertEqual(self.compared(run), sorted(others))
+            self.assertEqual(self.repository.count_pairs(run.id), total)
+
+
+class TestRunRecovery(RunRecoveryTestCase):
+    """Tests for the heartbeat and the claim of abandoned runs"""
+
+    def recovery(self, instance_id: str, resumed: list) -> RunRecovery:
+        return RunRecovery(
+            instance_id,
+            stale_after_seconds=60,
+            session_factory=self.new_session,
+            resume=lambda run: resumed.append(run) or True,
+        )
+
+    def test_runs_of_a_dead_instance_are_claimed_once(self):
+        """A run whose instance stopped heartbeating goes to the first instance claiming it, live runs stay."""
+        stale = self.create_run(2, heartbeat_at=utc_now() - timedelta(minutes=5))
+        alive = self.create_run(2, instance_id="pod-c")
+        resumed_by_b, resumed_by_c = [], []
+
+        self.assertEqual(self.recovery("pod-b", resumed_by_b).sweep_once(), 1)
+        self.assertEqual(self.recovery("pod-c", resumed_by_c).sweep_once(), 0)
+
+        self.assertEqual([run.id for run in resumed_by_b], [stale.id])
+        self.assertEqual(resumed_by_c, [])
+        self.assertEqual(self.reload(stale).instance_id, "pod-b")
+        self.assertEqual(self.reload(alive).instance_id, "pod-c")
+
+    def test_restarted_instance_takes_its_runs_back_at_startup(self):
+        """Runs held by the previous process of an instance are abandoned once it restarts, not before."""
+        run = self.create_run(2)
+        resumed = []
+        recovery = self.recovery("pod-a", resumed)
+
+        recovery.sweep_once()
+        self.assertEqual(resumed, [])
+        recovery.sweep_once(startup=True)
+        self.assertEqual([run.id for run in resumed], [run.id])
+
+    def test_heartbeat_keeps_the_runs_of_the_instance_alive(self):
+        """Only the running runs of the instance are heartbeated."""
+        old = utc_now() - timedelta(minutes=5)
+        own, other = self.create_run(2, heartbeat_at=old), self.create_run(2, instance_id="pod-b", heartbeat_at=old)
+
+        self.assertEqual(self.recovery("pod-a", []).heartbeat_once(), 1)
+
+        self.assertGreater(self.reload(own).heartbeat_at, old)
+        self.assertEqual(self.reload(other).heartbeat_at, old)
+
+
+if __name__ == "__main__":
+    unittest.main()
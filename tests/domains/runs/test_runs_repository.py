@@ -0,0 +1,450 @@
+"""
+Tests for DetectionRunRepository and DetectionRunRecorder.
+
+Runs against TEST_DATABASE_URL when set (e.g. a throwaway PostgreSQL database), in-memory SQLite otherwise. CI must
+set it, so that the batch insert throughput and abort integrity are checked on PostgreSQL.
+"""
+
+import time
+import unittest
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+import pytest
+from sqlalchemy import inspect
+from sqlmodel import Session, SQLModel, select
+
+from app.domains.runs.run_recorder import DetectionRunRecorder, extract_fragments
+from app.domains.runs.runs_models import DetectionFragment, DetectionRunStatus, FragmentType
+from app.domains.runs.runs_repository import DetectionRunRepository, table_rows
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.exceptions import DatabaseException
+from tests.helpers import create_test_engine
+
+
+class RunRepositoryTestCase(unittest.TestCase):
+    """Base class creating a fresh schema and a run for each test."""
+
+    def setUp(self):
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+        self.repository = DetectionRunRepository(self.session)
+
+        self.project_uuid = uuid4()
+        self.project_step_uuid = uuid4()
+        self.student_a = uuid4()
+        self.student_b = uuid4()
+        self.run = self.create_run()
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def create_run(self, total_pairs: int = 0):
+        return self.repository.create_run(
+            {
+                "project_uuid": self.project_uuid,
+                "project_step_uuid": self.project_step_uuid,
+                "total_pairs": total_pairs,
+            },
+            [
+                {"submission_id": uuid4(), "group_uuid": uuid4(), "submitted_by_uuid": self.student_a},
+                {"submission_id": uuid4(), "group_uuid": uuid4(), "submitted_by_uuid": self.student_b},
+            ],
+        )
+
+    def pair_data(self, similarity: float, submitted_by=None, compared_submitted_by=None, **overrides) -> dict:
+        data = {
+            "project_uuid": self.project_uuid,
+            "project_step_uuid": self.project_step_uuid,
+            "submission_id": uuid4(),
+            "compared_submission_id": uuid4(),
+            "submitted_by_uuid": submitted_by,
+            "compared_submitted_by_uuid": compared_submitted_by,
+            "overall_similarity": similarity,
+        }
+        data.update(overrides)
+        return data
+
+
+class TestDetectionRunSchema(RunRepositoryTestCase):
+    """Tests that the schema matches what the queries rely on."""
+
+    def test_tables_exist(self):
+        """The four run tables are created."""
+        tables = set(inspect(self.engine).get_table_names())
+        for table in ["detection_run", "detection_run_participant", "detection_pair", "detection_fragment"]:
+            self.assertIn(table, tables)
+
+    def test_query_columns_are_indexed(self):
+        """Columns used by report and cross-run queries are indexed."""
+        indexed = {
+            column for index in inspect(self.engine).get_indexes("detection_pair") for column in index["column_names"]
+        }
+        for column in ["run_id", "overall_similarity", "submitted_by_uuid", "compared_submitted_by_uuid"]:
+            self.assertIn(column, indexed)
+
+
+class TestDetectionRunRepository(RunRepositoryTestCase):
+    """Tests for run persistence and report queries."""
+
+    def test_create_run_with_participants(self):
+        """Participants are stored with the run."""
+        participants = self.repository.get_participants(self.run.id)
+        self.assertEqual(len(participants), 2)
+        self.assertEqual(self.run.status, DetectionRunStatus.RUNNING)
+
+    def test_batch_updates_counters(self):
+        """Completed and failed pairs are counted per batch."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id, batch_size=10)
+        recorder.record_pair(self.pair_data(0.9))
+        recorder.record_pair(self.pair_data(0.0, status=SimilarityStatus.FAILED, error_message="boom"))
+        recorder.flush()
+
+        run = self.repository.get_run(self.run.id)
+        self.assertEqual(run.completed_pairs, 1)
+        self.assertEqual(run.failed_pairs, 1)
+
+    def test_get_pairs_filters_and_orders(self):
+        """Pairs are filtered by threshold and sorted by similarity."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id)
+        for similarity in [0.2, 0.95, 0.81, 0.5]:
+            recorder.record_pair(self.pair_data(similarity))
+        recorder.finish()
+
+        pairs, total = self.repository.get_pairs(self.run.id, min_similarity=0.8)
+        self.assertEqual(total, 2)
+        self.assertEqual([p.overall_similarity for p in pairs], [0.95, 0.81])
+
+    def test_fragments_are_linked_to_pair(self):
+        """Fragments are stored and returned for their pair."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id)
+        pair = recorder.record_pair(
+            self.pair_data(0.9),
+            [
+                {"fragment_type": FragmentType.FILE, "file1_path": "a.py", "file2_path": "b.py", "similarity": 0.9},
+                {
+                    "fragment_type": FragmentType.BLOCK,
+                    "file1_path": "a.py",
+                    "file2_path": "b.py",
+                    "file1_start_line": 1,
+                    "file1_end_line": 10,
+                    "file2_start_line": 3,
+                    "file2_end_line": 12,
+                    "similarity": 0.85,
+                },
+            ],
+        )
+        recorder.finish()
+
+        fragments = self.repository.get_fragments(pair.id)
+        self.assertEqual(len(fragments), 2)
+        self.assertEqual(self.repository.get_pair(pair.id).fragments_count, 2)
+
+    def test_pair_by_submissions_in_either_order(self):
+        """The pair of two submissions is found whichever order they were compared in, within its run only."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id)
+        pair = recorder.record_pair(self.pair_data(0.9))
+        recorder.finish()
+
+        for first, second in [
+            (pair.submission_id, pair.compared_submission_id),
+            (pair.compared_submission_id, pair.submission_id),
+        ]:
+            self.assertEqual(self.repository.get_pair_by_submissions(self.run.id, first, second).id, pair.id)
+        other_run = self.create_run()
+        self.assertIsNone(
+            self.repository.get_pair_by_submissions(other_run.id, pair.submission_id, pair.compared_submission_id)
+        )
+
+    def test_pairs_of_a_submission_in_either_position(self):
+        """The completed pairs of a submission above the threshold are returned, within its run only."""
+        submission_id = uuid4()
+        recorder = DetectionRunRecorder(self.repository, self.run.id)
+        recorder.record_pair(self.pair_data(0.9, submission_id=submission_id))
+        recorder.record_pair(self.pair_data(0.7, compared_submission_id=submission_id))
+        recorder.record_pair(self.pair_data(0.3, submission_id=submission_id))
+        recorder.record_pair(self.pair_data(0.95, compared_submission_id=submission_id, status=SimilarityStatus.FAILED))
+        recorder.record_pair(self.pair_data(0.99))
+        recorder.finish()
+
+        pairs = self.repository.get_pairs_by_submission(self.run.id, submission_id, min_similarity=0.5)
+
+        self.assertEqual([p.overall_similarity for p in pairs], [0.9, 0.7])
+        self.assertEqual(self.repository.get_pairs_by_submission(self.create_run().id, submission_id), [])
+
+    def test_pairs_by_id_are_read_once_in_pages(self):
+        """Every pair above the threshold is read exactly once across pages, whatever its status."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id)
+        for number in range(25):
+            recorder.record_pair(self.pair_data(number / 25))
+        recorder.record_pair(self.pair_data(0.9, status=SimilarityStatus.FAILED, error_message="boom"))
+        recorder.finish()
+
+        pairs = list(self.repository.iter_pairs_by_id(self.run.id, min_similarity=0.4, batch_size=4))
+
+        self.assertEqual(len(pairs), 16)
+        self.assertEqual(len({p.id for p in pairs}), 16)
+        self.assertEqual([p.id for p in pairs], sorted(p.id for p in pairs))
+
+    def test_cross_run_history_for_student(self):
+        """Pairs in any position and any run are returned for a student."""
+        other_run = self.create_run()
+        first = DetectionRunRecorder(self.repository, self.run.id)
+        first.record_pair(self.pair_data(0.9, submitted_by=self.student_a))
+        first.record_pair(self.pair_data(0.3, submitted_by=self.student_a))
+        first.finish()
+        second = DetectionRunRecorder(self.repository, other_run.id)
+        second.record_pair(self.pair_data(0.85, compared_submitted_by=self.student_a))
+        second.record_pair(self.pair_data(0.99, submitted_by=self.student_b))
+        second.finish()
+
+        pairs = self.repository.get_pairs_by_submitter(self.student_a, min_similarity=0.8)
+
+        self.assertEqual([p.overall_similarity for p in pairs], [0.9, 0.85])
+        self.assertEqual({p.run_id for p in pairs}, {self.run.id, other_run.id})
+
+    def test_crash_loses_at_most_current_batch(self):
+        """Pairs of full batches are persisted before the run finishes."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id, batch_size=5)
+        for i in range(12):
+            recorder.record_pair(self.pair_data(i / 12))
+
+        # Simulate a crash: the recorder is dropped without finish()
+        self.assertEqual(self.repository.count_pairs(self.run.id), 10)
+        self.assertEqual(self.repository.get_run(self.run.id).status, DetectionRunStatus.RUNNING)
+
+    def test_finish_marks_run(self):
+        """Finishing flushes the last batch and closes the run."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id, batch_size=5)
+        for i in range(7):
+            recorder.record_pair(self.pair_data(0.5))
+        run = recorder.finish()
+
+        self.assertEqual(run.status, DetectionRunStatus.COMPLETED)
+        self.assertIsNotNone(run.finished_at)
+        self.assertEqual(self.repository.count_pairs(self.run.id), 7)
+
+    def test_file_errors_are_recorded_on_the_run(self):
+        """Pairs with failed files are partial and the run records each failed file once, the run still completes."""
+        submissions = [
+            SimpleNamespace(
+                id=uuid4(),
+                project_uuid=self.project_uuid,
+                project_step_uuid=self.project_step_uuid,
+                submitted_by_uuid=student,
+            )
+            for student in (self.student_a, self.student_b, uuid4())
+        ]
+        corrupt = {"path": "src/corrupt.py", "stage": "tokenization", "message": "RecursionError"}
+        recorder = DetectionRunRecorder(self.repository, self.run.id)
+        for first, second in [(0, 1), (0, 2), (1, 2)]:
+            errors = {"submission1": [corrupt] if first == 0 else [], "submission2": []}
+            similarity = SimpleNamespace(
+                id=None,
+                status=SimilarityStatus.COMPLETED,
+                error_message=None,
+                processing_time_seconds=0.1,
+                similarity_details={"partial": first == 0, "file_errors": errors},
+                visualization_data=[],
+            )
+            recorder.record_comparison(similarity, submissions[first], submissions[second])
+        run = recorder.finish()
+
+        self.assertEqual(run.status, DetectionRunStatus.COMPLETED)
+        self.assertEqual(run.file_errors, [{**corrupt, "submission_id": str(submissions[0].id)}])
+        pairs, _ = self.repository.get_pairs(self.run.id)
+        self.assertEqual(sorted(pair.partial for pair in pairs), [False, True, True])
+
+    @pytest.mark.slow
+    def test_insert_throughput_10k_pairs(self):
+        """A 10k-pair run with fragments is persisted in batches within a reasonable time."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id, batch_size=500)
+        fragment = {"fragment_type": FragmentType.FILE, "file1_path": "a.py", "file2_path": "b.py", "similarity": 0.5}
+
+        start = time.time()
+        for i in range(10_000):
+            recorder.record_pair(self.pair_data((i % 100) / 100), [fragment])
+        recorder.finish()
+        elapsed = time.time() - start
+
+        self.assertEqual(self.repository.count_pairs(self.run.id), 10_000)
+        self.assertEqual(self.repository.get_run(self.run.id).completed_pairs, 10_000)
+        self.assertLess(elapsed, 120, f"Persisting 10k pairs took {elapsed:.1f}s")
+
+    @pytest.mark.slow
+    def test_batched_inserts_outpace_row_at_a_time(self):
+        """50k results persist in batches several times faster per pair than one transaction per pair."""
+        fragment = {"fragment_type": FragmentType.FILE, "file1_path": "a.py", "file2_path": "b.py", "similarity": 0.5}
+
+        def persist(run, count, batch_size):
+            recorder = DetectionRunRecorder(self.repository, run.id, batch_size=batch_size)
+            start = time.perf_counter()
+            for i in range(count):
+                recorder.record_pair(self.pair_data((i % 100) / 100), [fragment])
+            recorder.finish()
+            return (time.perf_counter() - start) / count
+
+        row_at_a_time = persist(self.create_run(), 2_000, batch_size=1)
+        batched = persist(self.run, 50_000, batch_size=500)
+
+        self.assertEqual(self.repository.count_pairs(self.run.id), 50_000)
+        self.assertLess(batched * 5, row_at_a_time)
+
+    def test_abort_keeps_written_batches_consistent(self):
+        """An aborted run keeps whole batches, every fragment has its pair and the counters match the pairs."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id, batch_size=4)
+        fragment = {"fragment_type": FragmentType.FILE, "file1_path": "a.py", "file2_path": "b.py", "similarity": 0.5}
+        for i in range(10):
+            status = SimilarityStatus.FAILED if i % 3 == 0 else SimilarityStatus.COMPLETED
+            recorder.record_pair(self.pair_data(0.5, status=status), [fragment, fragment])
+
+        run = recorder.abort("worker killed")
+
+        pairs, total = self.repository.get_pairs(self.run.id, limit=100)
+        pair_ids = {pair.id for pair in pairs}
+        fragments = self.session.exec(select(DetectionFragment).where(DetectionFragment.run_id == self.run.id)).all()
+        self.assertEqual(total, 8)
+        self.assertEqual(len(fragments), 16)
+        self.assertTrue(all(fragment.pair_id in pair_ids for fragment in fragments))
+        self.assertEqual(run.completed_pairs + run.failed_pairs, total)
+        self.assertEqual(run.status, DetectionRunStatus.INCOMPLETE)
+        self.assertEqual(recorder.lost_pairs, 2)
+
+    def test_failed_batch_leaves_no_rows_and_marks_run_incomplete(self):
+        """A batch failing between its pairs and its fragments is rolled back whole, the run reports the loss."""
+        recorder = DetectionRunRecorder(self.repository, self.run.id, batch_size=3)
+        fragment = {"fragment_type": FragmentType.FILE, "file1_path": "a.py", "file2_path": "b.py", "similarity": 0.5}
+        calls = []
+
+        def failing_fragments_of_second_batch(models):
+            calls.append(models)
+            # Each batch converts its pairs then its fragments
+            if len(calls) == 4:
+                raise RuntimeError("connection lost")
+            return table_rows(models)
+
+        with patch("app.domains.runs.runs_repository.table_rows", side_effect=failing_fragments_of_second_batch):
+            for i in range(9):
+                try:
+                    recorder.record_pair(self.pair_data(0.5), [fragment])
+                except DatabaseException:
+                    pass
+            run = recorder.finish()
+
+        fragments = self.session.exec(select(DetectionFragment).where(DetectionFragment.run_id == self.run.id)).all()
+        self.assertEqual(self.repository.count_pairs(self.run.id), 6)
+        self.assertEqual(len(fragments), 6)
+        self.assertEqual(run.completed_pairs, 6)
+        self.assertEqual(run.status, DetectionRunStatus.INCOMPLETE)
+        self.assertEqual(run.error_message, "3 pairs could not be persisted")
+
+
+class TestExtractFragments(unittest.TestCase):
+    """Tests for converting visualization data to fragments."""
+
+    def test_file_and_block_fragments(self):
+        """Each file pair yields a file fragment plus one fragment per shared block."""
+        visualization_data = [
+            {
+                "file_pair": {"file_from_submission1": "calc.py", "file_from_submission2": "game.py"},
+                "react_flow": {
+                    "analysis_metadata": {"average_similarity": 0.8, "total_similarities": 2},
+                    "shared_blocks": [
+                        {
+                            "file1_function": "add",
+                            "file2_function": "score",
+                            "file1_start_line": 1,
+                            "file1_end_line": 5,
+                            "file2_start_line": 10,
+                            "file2_end_line": 14,
+                            "similarity_score": 0.9,
+                            "matched_tokens": 42,
+                        }
+                    ],
+                },
+            }
+        ]
+
+        fragments = extract_fragments(visualization_data)
+
+        self.assertEqual(len(fragments), 2)
+        self.assertEqual(fragments[0]["fragment_type"], FragmentType.FILE)
+        self.assertEqual(fragments[1]["fragment_type"], FragmentType.BLOCK)
+        self.assertEqual(fragments[1]["file2_start_line"], 10)
+        self.assertEqual(fragments[1]["details"]["matched_tokens"], 42)
+
+    def test_empty_visualization(self):
+        """Missing visualization data yields no fragments."""
+        self.assertEqual(extract_fragments(None), [])
+
+    def test_record_comparison_from_similarity(self):
+        """A failed comparison without results is still recorded."""
+        repository = SimpleNamespace(insert_batch=lambda run_id, pairs, fragments: len(pairs))
+        recorder = DetectionRunRecorder(repository, uuid4(), batch_size=10)
+        submission = SimpleNamespace(
+            id=uuid4(), project_uuid=uuid4(), project_step_uuid=uuid4(), submitted_by_uuid=uuid4()
+        )
+        similarity = SimpleNamespace(
+            id=uuid4(),
+            status=SimilarityStatus.FAILED,
+            error_message="clone failed",
+            processing_time_seconds=None,
+            visualization_data=None,
+            overall_similarity=0.0,
+        )
+
+        pair = recorder.record_comparison(similarity, submission, submission)
+
+        self.assertEqual(pair.status, SimilarityStatus.FAILED)
+        self.assertEqual(pair.fragments_count, 0)
+        self.assertIsNone(pair.matched_tokens)
+
+    def test_record_comparison_keeps_matched_tokens(self):
+        """The number of common token elements and the token counts of the compared files are kept on the pair."""
+        repository = SimpleNamespace(insert_batch=lambda run_id, pairs, fragments: len(pairs))
+        recorder = DetectionRunRecorder(repository, uuid4(), batch_size=10)
+        submission = SimpleNamespace(
+            id=uuid4(), project_uuid=uuid4(), project_step_uuid=uuid4(), submitted_by_uuid=uuid4()
+        )
+        similarity = SimpleNamespace(
+            id=uuid4(),
+            status=SimilarityStatus.COMPLETED,
+            error_message=None,
+            processing_time_seconds=1.5,
+            visualization_data=None,
+            overall_similarity=0.8,
+        )
+
+        files_tokens = {"submission1": {"main.py": 30}, "submission2": {"app.py": 28, "util.py": 5}}
+
+        pair = recorder.record_comparison(
+            similarity,
+            submission,
+            submission,
+            {"similarity_details": {"common_elements": 42, "files_tokens": files_tokens}},
+        )
+
+        self.assertEqual(pair.matched_tokens, 42)
+        self.assertEqual(pair.compared_files, files_tokens)
+
+    def test_record_pruned_pair(self):
+        """A pruned pair keeps its estimate and has no metrics nor fragments."""
+        repository = SimpleNamespace(insert_batch=lambda run_id, pairs, fragments: len(pairs))
+        recorder = DetectionRunRecorder(repository, uuid4(), batch_size=10)
+        submission = SimpleNamespace(
+            id=uuid4(), project_uuid=uuid4(), project_step_uuid=uuid4(), submitted_by_uuid=uuid4()
+        )
+
+        pair = recorder.record_pruned(submission, submission, 0.12)
+
+        self.assertEqual((pair.status, pair.estimated_similarity), (SimilarityStatus.PRUNED, 0.12))
+        self.assertEqual((pair.overall_similarity, pair.fragments_count), (0.0, 0))
+
+
+if __name__ == "__main__":
+    unittest.main()
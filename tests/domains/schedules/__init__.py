@@ -0,0 +1 @@
+# Schedules tests module
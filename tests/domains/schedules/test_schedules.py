@@ -0,0 +1,240 @@
+"""
+Tests for the detection schedules of project steps, ticked by a fake clock against repository doubles
+"""
+
+import unittest
+from datetime import datetime, timedelta, timezone
+from types import SimpleNamespace
+from uuid import uuid4
+
+from pydantic import ValidationError
+
+from app.domains.runs.runs_models import DetectionRunTrigger
+from app.domains.schedules.cron import CronExpression
+from app.domains.schedules.dto import DetectionScheduleDto
+from app.domains.schedules.schedules_models import DetectionSchedule, DetectionScheduleScope, ScheduleOutcome
+from app.domains.schedules.schedules_service import DetectionScheduleService
+from app.shared.concurrency import JobPriority
+
+START = datetime(2026, 3, 2, 1, 30, tzinfo=timezone.utc)  # a Monday
+
+
+class Clock:
+    """Clock moved by hand"""
+
+    def __init__(self, now: datetime):
+        self.now = now
+
+    def __call__(self) -> datetime:
+        return self.now
+
+    def advance(self, **delta) -> datetime:
+        self.now += timedelta(**delta)
+        return self.now
+
+
+class ScheduleRepository:
+    """Schedule repository double holding schedules, the submissions of their steps and their unfinished runs"""
+
+    def __init__(self):
+        self.schedules = {}
+        self.submissions = []
+        self.unfinished_runs = {}
+
+    def get(self, schedule_id):
+        return self.schedules.get(schedule_id)
+
+    def list_by_project(self, project_uuid):
+        return [s for s in self.schedules.values() if s.project_uuid == project_uuid]
+
+    def list_due(self, now):
+        due = [s for s in self.schedules.values() if not s.paused and s.next_run_at <= now]
+        return sorted(due, key=lambda s: s.next_run_at)
+
+    def save(self, schedule):
+        self.schedules[schedule.id] = schedule
+        return schedule
+
+    def delete(self, schedule):
+        del self.schedules[schedule.id]
+
+    def claim_tick(self, schedule_id, next_run_at, following_run_at):
+        schedule = self.schedules[schedule_id]
+        if schedule.next_run_at != next_run_at:
+            return False
+        schedule.next_run_at = following_run_at
+        return True
+
+    def count_unfinished_runs(self, schedule_id):
+        return self.unfinished_runs.get(schedule_id, 0)
+
+    def get_step_submissions(self, project_uuid, project_step_uuid, until, since=None):
+        return [
+            s
+            for s in self.submissions
+            if s.project_uuid == project_uuid
+            and s.project_step_uuid == project_step_uuid
+            and s.created_at < until
+            and (since is None or s.created_at >= since)
+        ]
+
+
+class DetectionService:
+    """Detection service double recording the runs started"""
+
+    def __init__(self):
+        self.calls = []
+
+    def process_submission_similarities_async(self, submission, **parameters):
+        self.calls.append((submission, parameters))
+        return uuid4()
+
+
+class ScheduleTestCase(unittest.TestCase):
+    def setUp(self):
+        self.project_uuid = uuid4()
+        self.project_step_uuid = uuid4()
+        self.clock = Clock(START)
+        self.repository = ScheduleRepository()
+        self.detection = DetectionService()
+        self.service = DetectionScheduleService.__new__(DetectionScheduleService)
+        self.service.session = None
+        self.service.repository = self.repository
+        self.service._detection_service = self.detection
+
+    def add_schedule(self, cron="0 2 * * *", scope=DetectionScheduleScope.NEW, **parameters) -> DetectionSchedule:
+        schedule = DetectionSchedule(
+            project_uuid=self.project_uuid,
+            project_step_uuid=self.project_step_uuid,
+            cron=cron,
+            scope=scope,
+            parameters=parameters,
+            next_run_at=CronExpression(cron).next_after(self.clock()),
+            scanned_until=self.clock(),
+        )
+        return self.repository.save(schedule)
+
+    def add_submission(self, minutes_ago=0):
+        submission = SimpleNamespace(
+            id=uuid4(),
+            project_uuid=self.project_uuid,
+            project_step_uuid=self.project_step_uuid,
+            created_at=self.clock() - timedelta(minutes=minutes_ago),
+        )
+        self.repository.submissions.append(submission)
+        return submission
+
+    def rescanned(self):
+        return [submission for submission, _ in self.detection.calls]
+
+
+class TestCronExpression(unittest.TestCase):
+    def test_next_tick_after_a_moment(self):
+        self.assertEqual(CronExpression("0 2 * * *").next_after(START), START.replace(hour=2, minute=0))
+        self.assertEqual(CronExpression("*/15 * * * *").next_after(START), START.replace(minute=45))
+        self.assertEqual(CronExpression("@weekly").next_after(START), datetime(2026, 3, 8, tzinfo=timezone.utc))
+        self.assertEqual(
+            CronExpression("30 9 * * mon-fri").next_after(START.replace(hour=10)), START.replace(day=3, hour=9)
+        )
+
+    def test_time_zone_of_the_schedule(self):
+        # 2 AM in Paris is 1 AM UTC in winter
+        tick = CronExpression("0 2 * * *").next_after(START, "Europe/Paris")
+        self.assertEqual(tick, datetime(2026, 3, 3, 1, 0, tzinfo=timezone.utc))
+
+    def test_invalid_expressions(self):
+        for expression in ("0 2 * *", "60 * * * *", "0 2 5-1 * *", "0 0 30 2 *"):
+            with self.subTest(expression=expression), self.assertRaises(ValueError):
+                CronExpression(expression).next_after(START)
+        with self.assertRaises(ValidationError):
+            DetectionScheduleDto(project_step_uuid=uuid4(), cron="0 2 * * *", timezone="Mars/Olympus")
+
+
+class TestScheduleTicks(ScheduleTestCase):
+    def test_fires_at_its_cron_time_with_its_parameters(self):
+        schedule = self.add_schedule(priority="high", fingerprint_k=7)
+        self.clock.advance(minutes=20)
+        self.add_submission()
+
+        self.assertEqual(self.service.run_due_schedules(self.clock()), [])
+        self.assertEqual(self.detection.calls, [])
+
+        self.clock.advance(minutes=10)
+        self.assertEqual(self.service.run_due_schedules(self.clock()), [ScheduleOutcome.STARTED])
+
+        _, parameters = self.detection.calls[0]
+        self.assertEqual(parameters["trigger"], DetectionRunTrigger.SCHEDULED)
+        self.assertEqual(parameters["schedule_id"], schedule.id)
+        self.assertEqual(parameters["priority"], JobPriority.HIGH)
+        self.assertEqual(parameters["fingerprint_k"], 7)
+        self.assertEqual(schedule.last_outcome, ScheduleOutcome.STARTED)
+        self.assertEqual(len(schedule.last_run_ids), 1)
+        self.assertEqual(schedule.next_run_at, START.replace(day=3, hour=2, minute=0))
+
+    def test_ticks_missed_while_down_fire_once(self):
+        schedule = self.add_schedule(cron="0 * * * *")
+        self.add_submission()
+        self.clock.advance(hours=5)
+
+        self.assertEqual(self.service.run_due_schedules(self.clock()), [ScheduleOutcome.STARTED])
+        self.assertEqual(self.service.run_due_schedules(self.clock()), [])
+        self.assertEqual(schedule.next_run_at, START.replace(hour=7, minute=0))
+
+    def test_new_scope_rescans_only_the_submissions_created_since_the_previous_tick(self):
+        self.add_schedule()
+        before = self.add_submission(minutes_ago=10)
+        self.clock.advance(minutes=10)
+        first = self.add_submission()
+        self.clock.advance(minutes=30)
+        self.service.run_due_schedules(self.clock())
+        self.assertEqual(self.rescanned(), [first])
+
+        self.clock.advance(hours=12)
+        second = self.add_submission()
+        self.clock.advance(hours=12)
+        self.service.run_due_schedules(self.clock())
+        self.assertEqual(self.rescanned(), [first, second])
+        self.assertNotIn(before, self.rescanned())
+
+    def test_full_scope_rescans_every_submission_of_the_step(self):
+        self.add_schedule(scope=DetectionScheduleScope.FULL)
+        before = self.add_submission(minutes_ago=10)
+        self.clock.advance(minutes=40)
+        self.service.run_due_schedules(self.clock())
+        self.assertEqual(self.rescanned(), [before])
+
+    def test_skipped_while_the_previous_runs_are_in_progress(self):
+        schedule = self.add_schedule()
+        first = self.add_submission()
+        self.clock.advance(minutes=30)
+        self.service.run_due_schedules(self.clock())
+        previous_runs = schedule.last_run_ids
+
+        self.repository.unfinished_runs[schedule.id] = 1
+        self.clock.advance(hours=12)
+        second = self.add_submission()
+        self.clock.advance(hours=12)
+        self.assertEqual(self.service.run_due_schedules(self.clock()), [ScheduleOutcome.SKIPPED_RUNNING])
+        self.assertEqual(self.rescanned(), [first])
+        self.assertEqual(schedule.last_run_ids, previous_runs)
+
+        # The next tick rescans what the skipped one left
+        self.repository.unfinished_runs[schedule.id] = 0
+        self.clock.advance(days=1)
+        self.assertEqual(self.service.run_due_schedules(self.clock()), [ScheduleOutcome.STARTED])
+        self.assertEqual(self.rescanned(), [first, second])
+
+    def test_paused_schedules_do_not_tick_until_resumed(self):
+        schedule = self.add_schedule()
+        self.add_submission()
+        self.service.pause_schedule(self.project_uuid, schedule.id)
+        self.clock.advance(days=2)
+        self.assertEqual(self.service.run_due_schedules(self.clock()), [])
+
+        self.service.resume_schedule(self.project_uuid, schedule.id)
+        self.assertFalse(schedule.paused)
+        self.assertGreater(schedule.next_run_at, START + timedelta(days=2))
+
+
+if __name__ == "__main__":
+    unittest.main()
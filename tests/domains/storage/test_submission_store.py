@@ -0,0 +1,705 @@
+"""
+Tests for the SubmissionStore backends and SubmissionStorageService
+"""
+
+import io
+import json
+import mmap
+import os
+import shutil
+import tempfile
+import time
+import unittest
+import uuid
+from pathlib import Path
+from types import SimpleNamespace
+from unittest.mock import patch
+
+import pytest
+
+from app.domains.storage.content_addressed_store import BLOBS_PREFIX, REFS_PREFIX, blob_key, blob_ref_prefix
+from app.domains.storage.exceptions import InvalidStorageKeyException, StoredObjectNotFoundException
+from app.domains.storage.local_submission_store import TEMP_FILE_PREFIX, LocalFileSystemSubmissionStore
+from app.domains.storage.mapped_file import MappedFileChangedException
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.rehash_job import CONTENT_REHASH_LOCK_KEY, ContentRehashJob
+from app.domains.storage.submission_storage_service import REHASH_CURSOR_PREFIX, SubmissionStorageService
+from app.domains.storage.submission_store import build_object_key, manifest_key, normalize_key, submission_prefix
+from app.domains.tokenization.streaming_source import decode_source
+from app.shared.migrations import MigrationLock
+from tests.helpers import create_test_engine
+
+
+class TestSubmissionKeys(unittest.TestCase):
+    """Tests for object key helpers."""
+
+    def test_build_object_key_is_namespaced(self):
+        """Keys are namespaced by project, submission and version."""
+        key = build_object_key("p1", "s1", 2, "src/main.py")
+        self.assertEqual(key, "projects/p1/submissions/s1/v2/src/main.py")
+
+    def test_manifest_key_is_namespaced(self):
+        """Manifests live next to the submission versions they describe."""
+        self.assertEqual(manifest_key("p1", "s1", 3), "projects/p1/submissions/s1/manifests/v3.json")
+
+    def test_blob_key_fans_out_by_digest(self):
+        """Blob keys are spread over two directory levels taken from the digest."""
+        digest = "ab" * 32
+        self.assertEqual(blob_key(digest), f"blobs/sha256/ab/ab/{digest}")
+        self.assertEqual(blob_ref_prefix(digest), f"refs/sha256/{digest}/")
+
+    def test_submission_prefix_without_version(self):
+        """Prefix without version covers every version."""
+        self.assertEqual(submission_prefix("p1", "s1"), "projects/p1/submissions/s1/")
+
+    def test_normalize_key_converts_backslashes(self):
+        """Windows separators are converted."""
+        self.assertEqual(normalize_key("src\\app\\main.py"), "src/app/main.py")
+
+    def test_normalize_key_rejects_escapes(self):
+        """Parent segments, absolute keys and empty keys are rejected."""
+        for key in ["../etc/passwd", "a/../../b", "/absolute", "", "a/./b"]:
+            with self.assertRaises(InvalidStorageKeyException):
+                normalize_key(key)
+
+
+class SubmissionStoreContract:
+    """Behaviour every SubmissionStore backend must provide."""
+
+    def create_store(self):
+        raise NotImplementedError
+
+    def setUp(self):
+        self.store = self.create_store()
+
+    def test_put_and_get_bytes(self):
+        """Stored bytes are returned unchanged."""
+        stored = self.store.put("a/b.txt", b"hello")
+        self.assertEqual(stored.key, "a/b.txt")
+        self.assertEqual(stored.size, 5)
+        self.assertEqual(self.store.get("a/b.txt"), b"hello")
+
+    def test_put_stream(self):
+        """A readable stream can be stored."""
+        self.store.put("stream.bin", io.BytesIO(b"x" * 200_000))
+        self.assertEqual(len(self.store.get("stream.bin")), 200_000)
+
+    def test_put_overwrites(self):
+        """Putting an existing key replaces the content."""
+        self.store.put("file.txt", b"first")
+        self.store.put("file.txt", b"second")
+        self.assertEqual(self.store.get("file.txt"), b"second")
+
+    def test_stream_in_chunks(self):
+        """Stream yields the full content in chunks."""
+        self.store.put("big.bin", b"0123456789")
+        chunks = list(self.store.stream("big.bin", chunk_size=3))
+        self.assertEqual(b"".join(chunks), b"0123456789")
+        self.assertEqual(len(chunks), 4)
+
+    def test_get_missing_raises(self):
+        """Missing keys raise StoredObjectNotFoundException."""
+        with self.assertRaises(StoredObjectNotFoundException):
+            self.store.get("missing.txt")
+        with self.assertRaises(StoredObjectNotFoundException):
+            self.store.stream("missing.txt")
+
+    def test_delete(self):
+        """Delete reports whether the object existed."""
+        self.store.put("gone.txt", b"data")
+        self.assertTrue(self.store.delete("gone.txt"))
+        self.assertFalse(self.store.delete("gone.txt"))
+        self.assertFalse(self.store.exists("gone.txt"))
+
+    def test_list_by_prefix_sorted(self):
+        """List only returns keys under the prefix, sorted."""
+        self.store.put("p/2.txt", b"2")
+        self.store.put("p/1.txt", b"1")
+        self.store.put("q/3.txt", b"3")
+        self.assertEqual([o.key for o in self.store.list("p/")], ["p/1.txt", "p/2.txt"])
+        self.assertEqual(len(self.store.list()), 3)
+
+    def test_delete_prefix(self):
+        """Delete prefix removes only the matching objects."""
+        self.store.put("p/1.txt", b"1")
+        self.store.put("p/sub/2.txt", b"2")
+        self.store.put("other/3.txt", b"3")
+        self.assertEqual(self.store.delete_prefix("p/"), 2)
+        self.assertEqual([o.key for o in self.store.list()], ["other/3.txt"])
+
+    def test_rejects_escaping_key(self):
+        """Keys escaping the namespace are rejected."""
+        with self.assertRaises(InvalidStorageKeyException):
+            self.store.put("../outside.txt", b"data")
+
+    def test_view_matches_get(self):
+        """Views expose the stored bytes of small and multi-page objects, missing keys raise."""
+        for size in (0, 10, 3 * mmap.PAGESIZE + 7):
+            content = bytes(range(256)) * (size // 256) + b"x" * (size % 256)
+            self.store.put(f"view/{size}.bin", content)
+            with self.store.view(f"view/{size}.bin") as view:
+                self.assertEqual(bytes(view), content)
+                self.assertEqual(decode_source(view), decode_source(content))
+        with self.assertRaises(StoredObjectNotFoundException):
+            with self.store.view("missing.txt"):
+                pass
+
+
+class TestInMemorySubmissionStore(SubmissionStoreContract, unittest.TestCase):
+    """Contract tests for the in-memory fake."""
+
+    def create_store(self):
+        return InMemorySubmissionStore()
+
+
+class TestLocalFileSystemSubmissionStore(SubmissionStoreContract, unittest.TestCase):
+    """Contract tests for the local filesystem backend."""
+
+    def create_store(self):
+        self.root_dir = tempfile.mkdtemp(prefix="test_store_")
+        return LocalFileSystemSubmissionStore(self.root_dir)
+
+    def tearDown(self):
+        shutil.rmtree(self.root_dir, ignore_errors=True)
+
+    def test_view_keeps_the_content_it_mapped(self):
+        """Replacing an object while it is viewed leaves the view unchanged, puts rename over the mapped file."""
+        first, second = b"a" * 2 * mmap.PAGESIZE, b"b" * mmap.PAGESIZE
+        self.store.put("mapped.bin", first)
+
+        with self.store.view("mapped.bin") as view:
+            self.store.put("mapped.bin", second)
+            self.assertEqual(bytes(view), first)
+
+        self.assertEqual(self.store.get("mapped.bin"), second)
+
+    def test_in_place_writes_fail_the_read(self):
+        """A file written in place by another process while it is mapped raises instead of returning its view."""
+        self.store.put("mapped.bin", b"a" * 2 * mmap.PAGESIZE)
+
+        with self.assertRaises(MappedFileChangedException):
+            with self.store.view("mapped.bin") as view:
+                with open(Path(self.root_dir) / "mapped.bin", "ab") as target:
+                    target.write(b"appended")
+                bytes(view)
+
+    def test_views_are_buffered_without_memory_maps(self):
+        """With memory maps disabled views read the file like get."""
+        store = LocalFileSystemSubmissionStore(self.root_dir, memory_map=False)
+        store.put("plain.bin", b"p" * 2 * mmap.PAGESIZE)
+
+        with patch("app.domains.storage.local_submission_store.map_file") as map_file:
+            with store.view("plain.bin") as view:
+                self.assertEqual(bytes(view), b"p" * 2 * mmap.PAGESIZE)
+        map_file.assert_not_called()
+
+    def test_delete_prunes_empty_directories(self):
+        """Deleting the last object of a directory removes the directory."""
+        self.store.put("a/b/c.txt", b"data")
+        self.store.delete("a/b/c.txt")
+        self.assertFalse((Path(self.root_dir) / "a").exists())
+        self.assertTrue(Path(self.root_dir).exists())
+
+
+class TestLocalFileSystemCrashSafety(unittest.TestCase):
+    """Tests for atomic writes, the temp file sweep and path handling of the local backend."""
+
+    def setUp(self):
+        self.root_dir = Path(tempfile.mkdtemp(prefix="test_store_crash_"))
+        self.store = LocalFileSystemSubmissionStore(self.root_dir)
+
+    def tearDown(self):
+        shutil.rmtree(self.root_dir, ignore_errors=True)
+
+    def _temp_files(self):
+        return list(self.root_dir.rglob(f"{TEMP_FILE_PREFIX}*"))
+
+    def _simulate_crash_before_rename(self, key: str, data: bytes):
+        """Fail between write and rename without the in-process cleanup, as a killed pod would."""
+        failing_replace = patch("app.domains.storage.local_submission_store.os.replace", side_effect=OSError("killed"))
+        skipped_cleanup = patch.object(LocalFileSystemSubmissionStore, "_remove_temp_file")
+        with failing_replace, skipped_cleanup:
+            with self.assertRaises(Exception):
+                self.store.put(key, data)
+
+    def test_write_leaves_no_temp_file(self):
+        """A successful write renames its temp file."""
+        self.store.put("a/file.txt", b"data")
+        self.assertEqual(self._temp_files(), [])
+        self.assertEqual((self.root_dir / "a" / "file.txt").read_bytes(), b"data")
+
+    def test_failed_rename_keeps_previous_content(self):
+        """An interrupted overwrite leaves the previous version intact."""
+        self.store.put("a/file.txt", b"original")
+        self._simulate_crash_before_rename("a/file.txt", b"half-written")
+
+        self.assertEqual(self.store.get("a/file.txt"), b"original")
+        self.assertEqual(len(self._temp_files()), 1)
+
+    def test_failed_rename_cleans_up_in_process(self):
+        """Errors caught in-process remove the temp file immediately."""
+        with patch("app.domains.storage.local_submission_store.os.replace", side_effect=OSError("disk full")):
+            with self.assertRaises(Exception):
+                self.store.put("a/file.txt", b"data")
+        self.assertEqual(self._temp_files(), [])
+        self.assertFalse(self.store.exists("a/file.txt"))
+
+    def test_orphaned_temp_files_are_invisible(self):
+        """Temp files never show up in listings."""
+        self.store.put("a/valid.txt", b"valid")
+        self._simulate_crash_before_rename("a/orphan.txt", b"partial")
+
+        self.assertEqual([o.key for o in self.store.list("a/")], ["a/valid.txt"])
+
+    def test_sweep_removes_old_orphans_only(self):
+        """The sweep removes orphaned temp files older than the threshold and keeps valid data."""
+        self.store.put("a/valid.txt", b"valid")
+        self._simulate_crash_before_rename("a/old.txt", b"partial")
+        old_temp = self._temp_files()[0]
+        old_time = time.time() - 7200
+        os.utime(old_temp, (old_time, old_time))
+        self._simulate_crash_before_rename("b/recent.txt", b"partial")
+
+        removed = self.store.sweep_temp_files(max_age_seconds=3600)
+
+        self.assertEqual(removed, 1)
+        self.assertFalse(old_temp.exists())
+        self.assertEqual(len(self._temp_files()), 1)
+        self.assertEqual(self.store.get("a/valid.txt"), b"valid")
+
+    def test_startup_sweep(self):
+        """Opening a store sweeps orphans left by a previous process."""
+        self.store.put("a/valid.txt", b"valid")
+        self._simulate_crash_before_rename("a/orphan.txt", b"partial")
+
+        reopened = LocalFileSystemSubmissionStore(self.root_dir, temp_file_max_age_seconds=0)
+
+        self.assertEqual(self._temp_files(), [])
+        self.assertEqual(reopened.get("a/valid.txt"), b"valid")
+
+    def test_rejects_symlink_escape(self):
+        """Keys going through a symlink out of the root are rejected."""
+        outside = Path(tempfile.mkdtemp(prefix="test_store_outside_"))
+        try:
+            (self.root_dir / "link").symlink_to(outside, target_is_directory=True)
+            with self.assertRaises(InvalidStorageKeyException):
+                self.store.put("link/escaped.txt", b"data")
+            self.assertEqual(list(outside.iterdir()), [])
+        finally:
+            shutil.rmtree(outside, ignore_errors=True)
+
+    def test_rejects_overly_long_filename(self):
+        """Segments longer than the filesystem limit are rejected explicitly."""
+        with self.assertRaises(InvalidStorageKeyException) as context:
+            self.store.put("a/" + "x" * 300 + ".py", b"data")
+        self.assertIn("255 bytes", context.exception.reason)
+
+    def test_rejects_temp_prefix_keys(self):
+        """Keys cannot collide with the temp file namespace."""
+        with self.assertRaises(InvalidStorageKeyException):
+            self.store.put(f"a/{TEMP_FILE_PREFIX}file", b"data")
+
+    def test_ingestion_stores_files_with_long_names(self):
+        """File names only live in the manifest, so names above the store segment limit are kept."""
+        source_dir = Path(tempfile.mkdtemp(prefix="test_ingest_long_"))
+        try:
+            (source_dir / "ok.py").write_text("x = 1\n")
+            long_name = "y" * 250 + ".py"
+            try:
+                (source_dir / long_name).write_text("y = 2\n")
+            except OSError:
+                self.skipTest("Filesystem does not allow the long test filename")
+
+            submission = SimpleNamespace(id=uuid.uuid4(), project_uuid=uuid.uuid4())
+            service = SubmissionStorageService(self.store)
+            with patch("app.domains.storage.local_submission_store.MAX_FILENAME_BYTES", 100):
+                summary = service.ingest_directory(submission, source_dir)
+
+            self.assertEqual(summary["file_count"], 2)
+            self.assertEqual(summary["skipped_files"], [])
+            self.assertEqual(service.read_file(submission, long_name), b"y = 2\n")
+        finally:
+            shutil.rmtree(source_dir, ignore_errors=True)
+
+
+@pytest.mark.integration
+@unittest.skipUnless(os.environ.get("MINIO_ENDPOINT"), "MINIO_ENDPOINT not set")
+class TestS3SubmissionStoreMinio(SubmissionStoreContract, unittest.TestCase):
+    """Contract tests against a MinIO container (docker compose up minio)."""
+
+    def create_store(self):
+        import boto3
+
+        from app.domains.storage.s3_submission_store import S3SubmissionStore
+
+        bucket = os.environ.get("MINIO_BUCKET", "pamp-submissions-test")
+        client = boto3.client(
+            "s3",
+            endpoint_url=os.environ["MINIO_ENDPOINT"],
+            aws_access_key_id=os.environ.get("MINIO_ACCESS_KEY", "minioadmin"),
+            aws_secret_access_key=os.environ.get("MINIO_SECRET_KEY", "minioadmin"),
+            region_name="us-east-1",
+        )
+        try:
+            client.create_bucket(Bucket=bucket)
+        except client.exceptions.BucketAlreadyOwnedByYou:
+            pass
+
+        # Each test works in its own prefix so runs don't interfere
+        return S3SubmissionStore(bucket=bucket, prefix=f"tests/{uuid.uuid4()}", client=client, multipart_threshold_mb=5)
+
+    def tearDown(self):
+        self.store.delete_prefix("")
+
+    def test_multipart_upload(self):
+        """Payloads above the multipart threshold round-trip."""
+        payload = os.urandom(12 * 1024 * 1024)
+        self.store.put("large.zip", io.BytesIO(payload))
+        self.assertEqual(self.store.get("large.zip"), payload)
+
+
+class TestSubmissionStorageService(unittest.TestCase):
+    """Tests for SubmissionStorageService using the in-memory store."""
+
+    def setUp(self):
+        self.store = InMemorySubmissionStore()
+        self.service = SubmissionStorageService(self.store)
+        self.submission = SimpleNamespace(id=uuid.uuid4(), project_uuid=uuid.uuid4())
+
+        self.source_dir = Path(tempfile.mkdtemp(prefix="test_ingest_"))
+        (self.source_dir / "src").mkdir()
+        (self.source_dir / "src" / "main.py").write_text("print('hello')\n")
+        (self.source_dir / "README.md").write_text("# Project\n")
+        (self.source_dir / ".git").mkdir()
+        (self.source_dir / ".git" / "HEAD").write_text("ref: refs/heads/main\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.source_dir, ignore_errors=True)
+
+    def test_ingest_directory_skips_vcs_metadata(self):
+        """Ingestion stores project files but not .git content."""
+        summary = self.service.ingest_directory(self.submission, self.source_dir)
+
+        self.assertEqual(summary["version"], 1)
+        self.assertEqual(summary["file_count"], 2)
+        self.assertEqual([f.key for f in self.service.list_files(self.submission)], ["README.md", "src/main.py"])
+
+    def test_ingest_records_byte_order_marks(self):
+        """Files starting with a BOM list its encoding and keep their original bytes."""
+        (self.source_dir / "src" / "main.py").write_bytes(b"\xef\xbb\xbfprint('hello')\n")
+        self.service.ingest_directory(self.submission, self.source_dir)
+
+        files = {f.key: f for f in self.service.list_files(self.submission)}
+        self.assertEqual(files["src/main.py"].bom, "utf-8")
+        self.assertIsNone(files["README.md"].bom)
+        self.assertEqual(self.service.read_file(self.submission, "src/main.py"), b"\xef\xbb\xbfprint('hello')\n")
+
+    def test_ingest_twice_creates_new_version(self):
+        """Each ingestion creates a new version and reads default to the latest one."""
+        self.service.ingest_directory(self.submission, self.source_dir)
+        (self.source_dir / "src" / "main.py").write_text("print('v2')\n")
+        summary = self.service.ingest_directory(self.submission, self.source_dir)
+
+        self.assertEqual(summary["version"], 2)
+        self.assertEqual(self.service.get_versions(self.submission), [1, 2])
+        self.assertEqual(self.service.read_file(self.submission, "src/main.py"), b"print('v2')\n")
+        self.assertEqual(self.service.read_file(self.submission, "src/main.py", version=1), b"print('hello')\n")
+
+    def test_materialize_round_trip(self):
+        """Materialized directory contains the stored files."""
+        self.service.ingest_directory(self.submission, self.source_dir)
+        target = self.service.materialize(self.submission)
+        try:
+            self.assertEqual((target / "src" / "main.py").read_text(), "print('hello')\n")
+            self.assertFalse((target / ".git").exists())
+        finally:
+            shutil.rmtree(target, ignore_errors=True)
+
+    def test_materialize_unknown_submission(self):
+        """Submissions that were never stored materialize to None."""
+        self.assertIsNone(self.service.materialize(self.submission))
+
+    def test_read_file_of_unknown_submission_raises(self):
+        """Reading from a submission without stored files raises not found."""
+        with self.assertRaises(StoredObjectNotFoundException):
+            self.service.read_file(self.submission, "src/main.py")
+
+    def test_delete_submission_files_only_affects_submission(self):
+        """Deletion cascade removes every version of one submission only."""
+        other = SimpleNamespace(id=uuid.uuid4(), project_uuid=self.submission.project_uuid)
+        self.service.ingest_directory(self.submission, self.source_dir)
+        self.service.ingest_directory(self.submission, self.source_dir)
+        self.service.ingest_directory(other, self.source_dir)
+
+        self.assertEqual(self.service.delete_submission_files(self.submission), 4)
+        self.assertEqual(self.service.get_versions(self.submission), [])
+        self.assertEqual(len(self.service.list_files(other)), 2)
+
+
+class TestContentAddressedStorage(unittest.TestCase):
+    """Tests for blob deduplication across submissions and refcount-correct deletion."""
+
+    def setUp(self):
+        self.store = InMemorySubmissionStore()
+        self.service = SubmissionStorageService(self.store)
+        self.project_uuid = uuid.uuid4()
+        self.first = SimpleNamespace(id=uuid.uuid4(), project_uuid=self.project_uuid)
+        self.second = SimpleNamespace(id=uuid.uuid4(), project_uuid=self.project_uuid)
+
+        self.first_dir = Path(tempfile.mkdtemp(prefix="test_cas_first_"))
+        self.second_dir = Path(tempfile.mkdtemp(prefix="test_cas_second_"))
+        for source_dir, solution in [(self.first_dir, "answer = 1\n"), (self.second_dir, "answer = 2\n")]:
+            (source_dir / "starter").mkdir()
+            (source_dir / "starter" / "utils.py").write_text("def helper():\n    return 42\n")
+            (source_dir / "starter" / "README.md").write_text("# Assignment\n")
+            (source_dir / "solution.py").write_text(solution)
+
+    def tearDown(self):
+        shutil.rmtree(self.first_dir, ignore_errors=True)
+        shutil.rmtree(self.second_dir, ignore_errors=True)
+
+    def blob_count(self) -> int:
+        return len(self.store.list(BLOBS_PREFIX))
+
+    def digest_of(self, submission, path: str) -> str:
+        return next(f.etag for f in self.service.list_files(submission) if f.key == path)
+
+    def test_shared_files_are_stored_once(self):
+        """Two submissions sharing starter files store each distinct content once."""
+        first = self.service.ingest_directory(self.first, self.first_dir)
+        second = self.service.ingest_directory(self.second, self.second_dir)
+
+        self.assertEqual(first["new_blobs"], 3)
+        self.assertEqual(second["new_blobs"], 1)
+        self.assertEqual(self.blob_count(), 4)
+        self.assertEqual(self.service.blobs.refcount(self.digest_of(self.first, "starter/utils.py")), 2)
+        self.assertEqual(self.service.blobs.refcount(self.digest_of(self.first, "solution.py")), 1)
+
+    def test_identical_files_within_a_submission_share_a_blob(self):
+        """Duplicate contents inside one version are one blob with one reference."""
+        (self.first_dir / "copy.py").write_text("def helper():\n    return 42\n")
+        self.service.ingest_directory(self.first, self.first_dir)
+
+        self.assertEqual(self.blob_count(), 3)
+        self.assertEqual(self.digest_of(self.first, "copy.py"), self.digest_of(self.first, "starter/utils.py"))
+        self.assertEqual(self.service.blobs.refcount(self.digest_of(self.first, "copy.py")), 1)
+
+    def test_deletion_keeps_blobs_until_collected(self):
+        """Deleting a submission drops its references, its unused blobs stay until the garbage collection."""
+        self.service.ingest_directory(self.first, self.first_dir)
+        unique = self.digest_of(self.first, "solution.py")
+
+        self.service.delete_submission_files(self.first)
+
+        self.assertTrue(self.service.blobs.exists(unique))
+        self.assertEqual(self.service.blobs.refcount(unique), 0)
+        self.assertEqual(self.service.collect_garbage(grace_seconds=3600)["removed_blobs"], 0)
+        self.assertEqual(self.service.collect_garbage(grace_seconds=0)["removed_blobs"], 3)
+        self.assertFalse(self.service.blobs.exists(unique))
+
+    def test_deletion_keeps_blobs_still_referenced(self):
+        """Deleting a submission then collecting removes its unique blobs and keeps shared ones readable."""
+        self.service.ingest_directory(self.first, self.first_dir)
+        self.service.ingest_directory(self.second, self.second_dir)
+        shared = self.digest_of(self.first, "starter/utils.py")
+        unique = self.digest_of(self.first, "solution.py")
+
+        self.assertEqual(self.service.delete_submission_files(self.first), 3)
+        self.service.collect_garbage(grace_seconds=0)
+
+        self.assertEqual(self.blob_count(), 3)
+        self.assertFalse(self.service.blobs.exists(unique))
+        self.assertEqual(self.service.blobs.refcount(shared), 1)
+        self.assertEqual(self.service.read_file(self.second, "starter/utils.py"), b"def helper():\n    return 42\n")
+        self.assertEqual(self.service.get_versions(self.first), [])
+
+    def test_deleting_every_submission_leaves_no_blob(self):
+        """Once no manifest references a blob it is collected, with its references."""
+        self.service.ingest_directory(self.first, self.first_dir)
+        self.service.ingest_directory(self.first, self.first_dir)
+        self.service.ingest_directory(self.second, self.second_dir)
+
+        self.service.delete_submission_files(self.first)
+        self.service.delete_submission_files(self.second)
+        self.service.collect_garbage(grace_seconds=0)
+
+        self.assertEqual(self.blob_count(), 0)
+        self.assertEqual(self.store.list(REFS_PREFIX), [])
+        self.assertEqual(self.store.list(), [])
+
+    def test_versions_of_one_submission_reference_blobs_separately(self):
+        """An unchanged file across versions is one blob referenced by each version."""
+        self.service.ingest_directory(self.first, self.first_dir)
+        self.service.ingest_directory(self.first, self.first_dir)
+
+        self.assertEqual(self.blob_count(), 3)
+        self.assertEqual(self.service.blobs.refcount(self.digest_of(self.first, "solution.py")), 2)
+
+    def test_concurrent_ingestion_of_the_same_content(self):
+        """Submissions ingested in parallel with identical new files end up with one intact blob each."""
+        from concurrent.futures import ThreadPoolExecutor
+
+        submissions = [SimpleNamespace(id=uuid.uuid4(), project_uuid=self.project_uuid) for _ in range(8)]
+        with ThreadPoolExecutor(max_workers=8) as executor:
+            list(executor.map(lambda s: self.service.ingest_directory(s, self.first_dir), submissions))
+
+        self.assertEqual(self.blob_count(), 3)
+        for submission in submissions:
+            self.assertEqual(self.service.read_file(submission, "solution.py"), b"answer = 1\n")
+        self.assertEqual(self.service.blobs.refcount(self.digest_of(submissions[0], "solution.py")), 8)
+
+    def test_garbage_collection_removes_orphans(self):
+        """References of versions without manifest and unreferenced blobs are swept after the grace period."""
+        self.service.ingest_directory(self.first, self.first_dir)
+        self.service.ingest_directory(self.second, self.second_dir)
+        # Simulate an ingestion that crashed before writing its manifest
+        self.store.delete(manifest_key(self.project_uuid, self.second.id, 1))
+
+        self.assertEqual(self.service.collect_garbage(grace_seconds=3600)["removed_blobs"], 0)
+        result = self.service.collect_garbage(grace_seconds=0)
+
+        self.assertEqual(result, {"removed_references": 3, "removed_blobs": 1})
+        self.assertEqual(self.blob_count(), 3)
+        self.assertEqual(self.service.read_file(self.first, "solution.py"), b"answer = 1\n")
+
+    def test_legacy_versions_are_still_readable(self):
+        """Versions stored as plain objects before deduplication are listed, read and deleted."""
+        self.store.put(build_object_key(self.project_uuid, self.first.id, 1, "main.py"), b"legacy = True\n")
+        self.service.ingest_directory(self.first, self.first_dir)
+
+        self.assertEqual(self.service.get_versions(self.first), [1, 2])
+        self.assertEqual([f.key for f in self.service.list_files(self.first, version=1)], ["main.py"])
+        self.assertEqual(self.service.read_file(self.first, "main.py", version=1), b"legacy = True\n")
+        self.assertEqual(self.service.delete_submission_files(self.first), 4)
+        self.service.collect_garbage(grace_seconds=0)
+        self.assertEqual(self.store.list(), [])
+
+
+class TestContentHashMigration(unittest.TestCase):
+    """Tests for reading SHA-256 blobs and rehashing them to BLAKE3 without downtime."""
+
+    def setUp(self):
+        self.store = InMemorySubmissionStore()
+        self.legacy_service = SubmissionStorageService(self.store, hash_algorithm="sha256")
+        self.service = SubmissionStorageService(self.store, hash_algorithm="blake3")
+        self.project_uuid = uuid.uuid4()
+        self.submissions = [SimpleNamespace(id=uuid.uuid4(), project_uuid=self.project_uuid) for _ in range(3)]
+        self.engine = create_test_engine()
+
+        self.source_dir = Path(tempfile.mkdtemp(prefix="test_rehash_"))
+        (self.source_dir / "starter.py").write_text("def helper():\n    return 42\n")
+        for index, submission in enumerate(self.submissions):
+            (self.source_dir / "solution.py").write_text(f"answer = {index}\n")
+            self.legacy_service.ingest_directory(submission, self.source_dir)
+
+    def tearDown(self):
+        shutil.rmtree(self.source_dir, ignore_errors=True)
+        self.engine.dispose()
+
+    def algorithms_of(self, submission) -> set:
+        manifest = self.service.get_manifest(submission)
+        return {entry["algorithm"] for entry in manifest["files"].values()}
+
+    def assert_contents_intact(self):
+        for index, submission in enumerate(self.submissions):
+            self.assertEqual(self.service.read_file(submission, "solution.py"), f"answer = {index}\n".encode())
+            self.assertEqual(self.service.read_file(submission, "starter.py"), b"def helper():\n    return 42\n")
+
+    def test_blobs_of_previous_algorithm_are_read(self):
+        """A service configured for BLAKE3 reads the SHA-256 blobs of existing manifests."""
+        self.assertEqual(self.algorithms_of(self.submissions[0]), {"sha256"})
+        self.assert_contents_intact()
+
+    def test_manifests_without_recorded_algorithm_are_sha256(self):
+        """Entries written before the algorithm was recorded resolve to SHA-256 blobs."""
+        submission = self.submissions[0]
+        manifest = self.service.get_manifest(submission)
+        for entry in manifest["files"].values():
+            del entry["algorithm"]
+        self.store.put(manifest_key(self.project_uuid, submission.id, 1), json.dumps(manifest).encode())
+
+        self.assertEqual(self.service.read_file(submission, "solution.py"), b"answer = 0\n")
+
+    def test_new_versions_use_configured_algorithm(self):
+        """Ingestion after the switch writes BLAKE3 blobs next to the old ones."""
+        self.service.ingest_directory(self.submissions[0], self.source_dir)
+
+        self.assertEqual(self.algorithms_of(self.submissions[0]), {"blake3"})
+        self.assertTrue(self.store.list(f"{BLOBS_PREFIX}blake3/"))
+        self.assertEqual(self.service.read_file(self.submissions[0], "solution.py", version=1), b"answer = 0\n")
+
+    def test_partial_rehash_keeps_everything_readable(self):
+        """Between two batches migrated and pending manifests are both served."""
+        result = self.service.rehash(max_manifests=1)
+
+        self.assertEqual((result["manifests"], result["remaining"]), (1, True))
+        self.assertEqual(sorted(len(self.algorithms_of(s)) for s in self.submissions), [1, 1, 1])
+        self.assertEqual(
+            sorted(self.algorithms_of(s).pop() for s in self.submissions), ["blake3", "sha256", "sha256"]
+        )
+        self.assert_contents_intact()
+
+    def test_rehash_moves_every_blob_and_releases_old_ones(self):
+        """After the migration only BLAKE3 blobs remain, each distinct content copied once."""
+        result = self.service.rehash()
+        self.service.collect_garbage(grace_seconds=0)
+
+        self.assertEqual(result, {"manifests": 3, "files": 6, "remaining": False})
+        for submission in self.submissions:
+            self.assertEqual(self.algorithms_of(submission), {"blake3"})
+        self.assertEqual(self.store.list(f"{BLOBS_PREFIX}sha256/"), [])
+        self.assertEqual(self.store.list(f"{REFS_PREFIX}sha256/"), [])
+        self.assertEqual(len(self.service.blobs.list_digests("blake3")), 4)
+        shared = next(f.etag for f in self.service.list_files(self.submissions[0]) if f.key == "starter.py")
+        self.assertEqual(self.service.blobs.refcount(shared), 3)
+        self.assert_contents_intact()
+        self.assertEqual(self.service.rehash(), {"manifests": 0, "files": 0, "remaining": False})
+
+    def test_rehashed_submissions_are_deleted_cleanly(self):
+        """Deleting migrated submissions then collecting removes their BLAKE3 blobs."""
+        self.service.rehash()
+        for submission in self.submissions:
+            self.service.delete_submission_files(submission)
+        self.service.collect_garbage(grace_seconds=0)
+
+        self.assertEqual(self.store.list(), [])
+
+    def test_batches_resume_after_the_previous_one(self):
+        """Each batch reads the manifests after the cursor left by the previous one, on any instance."""
+        self.service.rehash(max_manifests=1)
+        done = next(s for s in self.submissions if self.algorithms_of(s) == {"blake3"})
+        read = []
+        get = self.store.get
+        with patch.object(self.store, "get", side_effect=lambda key: read.append(key) or get(key)):
+            replica = SubmissionStorageService(self.store, hash_algorithm="blake3")
+            self.assertEqual(replica.rehash(max_manifests=1)["manifests"], 1)
+            self.assertEqual(replica.rehash(), {"manifests": 1, "files": 2, "remaining": False})
+
+        self.assertNotIn(manifest_key(self.project_uuid, done.id, 1), read)
+        for submission in self.submissions:
+            self.assertEqual(self.algorithms_of(submission), {"blake3"})
+        self.assertEqual(self.store.list(REHASH_CURSOR_PREFIX), [])
+
+    def test_rehash_job_runs_until_done(self):
+        """The background job processes batches until no outdated manifest remains."""
+        job = ContentRehashJob(batch_size=1, interval_seconds=0, storage_service=self.service, engine=self.engine)
+
+        job.start()
+        job._thread.join(5)
+
+        self.assertEqual(job.totals, {"manifests": 3, "files": 6})
+        self.assert_contents_intact()
+
+    def test_rehash_job_waits_while_another_instance_rehashes(self):
+        """A batch is skipped while the lock is held elsewhere, manifests are left as they are."""
+        job = ContentRehashJob(batch_size=1, interval_seconds=0, storage_service=self.service, engine=self.engine)
+
+        with MigrationLock(self.engine, key=CONTENT_REHASH_LOCK_KEY):
+            self.assertIsNone(job.run_once())
+
+        self.assertEqual({self.algorithms_of(s).pop() for s in self.submissions}, {"sha256"})
+        self.assertEqual(job.run_once()["manifests"], 1)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,60 @@
+"""
+Tests for the lateness of submissions against deadlines, across DST changes
+"""
+
+import unittest
+from datetime import datetime, timedelta, timezone
+
+from app.domains.submissions.deadlines import minutes_late
+from app.shared.timestamps import parse_timestamp
+
+UTC = timezone.utc
+
+
+class TestMinutesLate(unittest.TestCase):
+    """Tests for the minutes a submission was late by"""
+
+    def test_on_time_submissions(self):
+        """Submissions before or exactly at the deadline, or without deadline, are not late."""
+        deadline = datetime(2024, 1, 15, 23, 59, tzinfo=UTC)
+
+        self.assertIsNone(minutes_late(deadline - timedelta(minutes=5), deadline))
+        self.assertIsNone(minutes_late(deadline, deadline))
+        self.assertIsNone(minutes_late(deadline + timedelta(hours=1), None))
+
+    def test_started_minutes_count(self):
+        """A second after the deadline is a minute late, minutes are otherwise whole."""
+        deadline = datetime(2024, 1, 15, 23, 59, tzinfo=UTC)
+
+        self.assertEqual(minutes_late(deadline + timedelta(seconds=1), deadline), 1)
+        self.assertEqual(minutes_late(deadline + timedelta(minutes=75), deadline), 75)
+        self.assertEqual(minutes_late(deadline + timedelta(minutes=75, seconds=30), deadline), 76)
+
+    def test_deadline_before_summer_time(self):
+        """A winter-time deadline and a summer-time upload of the night of the change are an instant apart."""
+        deadline = parse_timestamp("2024-03-31T01:59:00+01:00")
+        submitted = parse_timestamp("2024-03-31T03:15:00+02:00")
+
+        # Clocks jumped from 02:00 to 03:00, the wall clock difference of 76 minutes is an hour too many
+        self.assertEqual(minutes_late(submitted, deadline), 16)
+        self.assertIsNone(minutes_late(parse_timestamp("2024-03-31T01:58:30+01:00"), deadline))
+
+    def test_repeated_hour_at_the_end_of_summer_time(self):
+        """Uploads of the hour repeated when summer time ends are told apart by their offset."""
+        deadline = parse_timestamp("2024-10-27T02:45:00+02:00")
+
+        self.assertIsNone(minutes_late(parse_timestamp("2024-10-27T02:30:00+02:00"), deadline))
+        # Second 02:15 of the night, earlier on the wall clock than the deadline but half an hour after it
+        self.assertEqual(minutes_late(parse_timestamp("2024-10-27T02:15:00+01:00"), deadline), 30)
+
+    def test_offsets_of_the_deadline_and_the_upload_may_differ(self):
+        """A deadline set in Paris applies to uploads dated in any timezone."""
+        deadline = parse_timestamp("2024-06-30T23:59:00+02:00")
+
+        self.assertIsNone(minutes_late(parse_timestamp("2024-06-30T17:30:00-04:00"), deadline))
+        self.assertEqual(minutes_late(parse_timestamp("2024-07-01T08:00:00+09:00"), deadline), 61)
+        self.assertEqual(minutes_late(datetime(2024, 6, 30, 22, 0), deadline), 1)
+
+
+if __name__ == "__main__":
+    unittest.main()
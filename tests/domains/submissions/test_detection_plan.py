@@ -0,0 +1,238 @@
+"""
+Tests for dry runs: the plan of a detection run computed from the stored files, without queuing anything
+"""
+
+import shutil
+import tempfile
+import threading
+import unittest
+from datetime import timedelta
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+from app.shared.timestamps import utc_now
+from tests.helpers import tokenization_service
+
+MIGRATION = "# Generated by Django 4.2 on 2024-01-15 10:30\n\nfrom django.db import migrations\n"
+
+
+class StepSubmissions:
+    """Submission repository double holding the submissions of one step"""
+
+    def __init__(self, submissions):
+        self.submissions = submissions
+
+    def get_by_project_step(self, project_uuid, project_step_uuid):
+        return list(self.submissions)
+
+    def get_step_config(self, project_step_uuid):
+        return None
+
+
+class MemoryStorage:
+    """Submission storage double keeping the files of the stored submissions, and what was read of them"""
+
+    def __init__(self, files):
+        self.files = files
+        self.streamed = []
+
+    def get_latest_version(self, submission):
+        return 1 if submission.id in self.files else None
+
+    def list_files(self, submission, version=None):
+        return [SimpleNamespace(key=path) for path in self.files.get(submission.id, {})]
+
+    def stream_file(self, submission, relative_path, version=None):
+        self.streamed.append(relative_path)
+        return iter([self.files[submission.id][relative_path].encode("utf-8")])
+
+    def materialize(self, submission, version=None):
+        root = Path(tempfile.mkdtemp())
+        for path, content in self.files[submission.id].items():
+            (root / path).parent.mkdir(parents=True, exist_ok=True)
+            (root / path).write_text(content, encoding="utf-8")
+        return root
+
+
+class RecordingRuns:
+    """Detection run repository double recording the created runs"""
+
+    def __init__(self, recent_runs=()):
+        self.recent_runs = list(recent_runs)
+        self.created = []
+
+    def get_recent_completed_runs(self, limit=20):
+        return self.recent_runs[:limit]
+
+    def create_run(self, run_data, participants):
+        self.created.append((run_data, participants))
+        return SimpleNamespace(id=uuid4())
+
+
+class RecordingScheduler:
+    def __init__(self):
+        self.submitted = []
+
+    def submit(self, *args, **kwargs):
+        self.submitted.append(args)
+
+
+def completed_run(pairs: int, seconds: float):
+    finished_at = utc_now()
+    return SimpleNamespace(
+        completed_pairs=pairs, started_at=finished_at - timedelta(seconds=seconds), finished_at=finished_at
+    )
+
+
+class DetectionPlanTestCase(unittest.TestCase):
+    """Base class with a step of three submissions, two of them of the same group"""
+
+    def setUp(self):
+        step = SimpleNamespace(project_uuid=uuid4(), project_step_uuid=uuid4())
+        group, other_group = uuid4(), uuid4()
+        self.alice, self.bob, self.carol = (
+            SimpleNamespace(id=uuid4(), group_uuid=group_uuid, submitted_by_uuid=None, **vars(step))
+            for group_uuid in (group, other_group, group)
+        )
+        self.files = {
+            self.alice.id: {
+                "main.py": "total = 1\n",
+                "migrations/0001_initial.py": MIGRATION,
+                "logo.png": "not an image",
+                "README.md": "# Alice\n",
+            },
+            self.bob.id: {"src/main.py": "total = 2\n", "archive.zip": "not an archive"},
+            self.carol.id: {"main.py": "total = 3\n"},
+        }
+        self.storage = MemoryStorage(self.files)
+        self.runs = RecordingRuns([completed_run(10, 20), completed_run(10, 40)])
+        self.scheduler = RecordingScheduler()
+        self.service = self.new_service([self.alice, self.bob, self.carol])
+
+    def new_service(self, submissions) -> DetectionIntegrationService:
+        service = DetectionIntegrationService.__new__(DetectionIntegrationService)
+        service.submission_repository = StepSubmissions(submissions)
+        service.storage_service = self.storage
+        service.tokenization_service = tokenization_service()
+        service.run_repository = self.runs
+        service.job_scheduler = self.scheduler
+        service.fingerprint_service = SimpleNamespace(parameters={})
+        service._local = threading.local()
+        service.quota_service = SimpleNamespace(check_run=lambda project_uuid, run_submissions: None)
+        return service
+
+
+class TestDetectionPlan(DetectionPlanTestCase):
+    """Tests for the content of a plan"""
+
+    def test_plan_lists_submissions_files_and_exclusions(self):
+        """Submissions of the other groups are planned with their files by language, exclusions with their reason."""
+        plan = self.service.plan_detection(self.alice)
+
+        self.assertTrue(plan.dry_run)
+        self.assertEqual([planned.submission_id for planned in plan.submissions], [self.alice.id, self.bob.id])
+        self.assertEqual(
+            [(excluded.submission_id, excluded.reason) for excluded in plan.excluded_submissions],
+            [(self.carol.id, "same_group")],
+        )
+        alice = plan.submissions[0]
+        self.assertEqual((alice.files, alice.languages), (2, {"markdown": 1, "python": 1}))
+        self.assertEqual(
+            [(exclusion.path, exclusion.reason) for exclusion in alice.excluded_files],
+            [("logo.png", "unsupported_language"), ("migrations/0001_initial.py", "generated")],
+        )
+        self.assertEqual((plan.files, plan.languages), (3, {"python": 2, "markdown": 1}))
+        self.assertEqual(plan.excluded_files, {"generated": 1, "unsupported_language": 2})
+        self.assertEqual(plan.pairs, 1)
+        self.assertEqual((plan.seconds_per_pair, plan.estimated_seconds), (3.0, 3.0))
+        self.assertEqual(plan.warnings, [])
+        # Files of unsupported languages are never read
+        self.assertNotIn("logo.png", self.storage.streamed)
+
+    def test_misconfigurations_are_warned(self):
+        """Submissions without supported file, never stored, or without anything to compare with are warned of."""
+        self.files[self.bob.id] = {"logo.png": "not an image"}
+        plan = self.service.plan_detection(self.alice)
+        self.assertEqual(
+            plan.warnings,
+            [f"Submission {self.bob.id} only has files of unsupported languages, its pairs would not be comparable"],
+        )
+
+        del self.files[self.bob.id]
+        plan = self.service.plan_detection(self.alice)
+        self.assertFalse(plan.submissions[1].stored)
+        self.assertIn("is not stored", plan.warnings[0])
+
+        self.files[self.alice.id] = {"migrations/0001_initial.py": MIGRATION}
+        self.runs.recent_runs = []
+        plan = self.new_service([self.alice]).plan_detection(self.alice)
+        self.assertEqual(plan.pairs, 0)
+        self.assertIsNone(plan.estimated_seconds)
+        self.assertEqual(
+            plan.warnings,
+            [
+                "No submission of another group to compare with, no run would be started",
+                f"Submission {self.alice.id} has no file left after exclusions, its pairs would not be comparable",
+                "No file would be compared, the corpus is empty after exclusions",
+            ],
+        )
+
+
+class TestDryRunSideEffects(DetectionPlanTestCase):
+    """Tests comparing a dry run with the run it plans"""
+
+    def test_dry_run_queues_and_writes_nothing(self):
+        """Planning creates no run and submits no job, unlike the run itself."""
+        self.service.plan_detection(self.alice)
+
+        self.assertEqual(self.runs.created, [])
+        self.assertEqual(self.scheduler.submitted, [])
+
+        self.service.process_submission_similarities_async(self.alice)
+        self.assertEqual(len(self.runs.created), 1)
+        self.assertEqual(len(self.scheduler.submitted), 1)
+
+    def test_plan_includes_what_the_run_would_use(self):
+        """The planned submissions are the participants of the run, their files those it would tokenize."""
+        plan = self.service.plan_detection(self.alice)
+        self.service.process_submission_similarities_async(self.alice)
+
+        _, participants = self.runs.created[0]
+        self.assertEqual(
+            [participant["submission_id"] for participant in participants],
+            [planned.submission_id for planned in plan.submissions],
+        )
+        for planned in plan.submissions:
+            submission = next(s for s in (self.alice, self.bob) if s.id == planned.submission_id)
+            root = self.storage.materialize(submission)
+            self.addCleanup(shutil.rmtree, root)
+            files = self.service.tokenization_service.extract_supported_files_from_directory(root)
+            self.assertEqual(len(files), planned.files)
+            excluded = {exclusion.path for exclusion in planned.excluded_files}
+            self.assertEqual(
+                {file_path.relative_to(root).as_posix() for file_path in files},
+                set(self.files[submission.id]) - excluded,
+            )
+
+    def test_plan_resolves_the_parameters_of_the_run(self):
+        """k, window and auto_tune of the request are resolved in the plan as in the run they would start."""
+        self.service.fingerprint_service = FingerprintService(self.service.tokenization_service, k=5, window=4)
+        cases = ((None, None, False, None), (7, None, True, "pending"), (7, 3, True, "skipped"), (None, 6, False, None))
+        for k, window, auto_tune, tuning in cases:
+            plan = self.service.plan_detection(self.alice, k, window, auto_tune)
+            self.service.process_submission_similarities_async(
+                self.alice, fingerprint_k=k, fingerprint_window=window, auto_tune=auto_tune
+            )
+
+            run_data, _ = self.runs.created[-1]
+            self.assertEqual(plan.parameters, run_data["parameters"])
+            fingerprint = plan.parameters["fingerprint"]
+            self.assertEqual((fingerprint["k"], fingerprint["window"]), (k or 5, window or 4))
+            self.assertEqual((plan.parameters.get("auto_tuning") or {}).get("status"), tuning)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,113 @@
+"""
+Tests that files failing during a comparison are recorded and skipped, and that systemic failures still fail it
+"""
+
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import UUID
+
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.exceptions import DatabaseException
+from tests.helpers import (
+    RECURSION,
+    SOURCE,
+    FailingTokenizer,
+    FailingVisualization,
+    detection_service,
+    write_submissions,
+)
+
+SUBMISSION_FILES = {
+    UUID(int=1): {"main.py": SOURCE, "src/corrupt.py": SOURCE, "broken.py": SOURCE},
+    UUID(int=2): {"main.py": SOURCE},
+}
+
+
+class RecordingSimilarityRepository:
+    """Similarity repository double keeping the statuses and results stored"""
+
+    def __init__(self):
+        self.statuses = []
+        self.results = None
+
+    def update_status(self, record_id, status, error_message=None):
+        self.statuses.append((status, error_message))
+
+    def update_results(self, record_id, results):
+        self.results = results
+
+
+class TestFileErrors(unittest.TestCase):
+    """Tests for comparisons of submissions with files failing at some stage"""
+
+    def setUp(self):
+        self.root = Path(tempfile.mkdtemp())
+        write_submissions(self.root, SUBMISSION_FILES)
+
+    def tearDown(self):
+        shutil.rmtree(self.root, ignore_errors=True)
+
+    def compare(self, tokenizer, repository):
+        service = detection_service(self.root, FingerprintService(tokenizer, k=3, window=2), FailingVisualization())
+        first, second = (
+            SimpleNamespace(
+                id=submission_id,
+                link=f"https://github.com/user/{submission_id}.git",
+                project_uuid=UUID(int=10),
+                group_uuid=UUID(int=11),
+                project_step_uuid=UUID(int=12),
+                link_type=None,
+            )
+            for submission_id in SUBMISSION_FILES
+        )
+        return service._process_single_comparison_with_repos(SimpleNamespace(id=1), first, second, None, repository)
+
+    def test_failing_files_are_recorded_and_skipped(self):
+        """A file failing tokenization or fragment extraction is recorded, the comparison completes as partial."""
+        repository = RecordingSimilarityRepository()
+        results = self.compare(FailingTokenizer(), repository)
+
+        details = results["similarity_details"]
+        self.assertTrue(details["partial"])
+        self.assertEqual(
+            details["file_errors"],
+            {
+                "submission1": [
+                    {"path": "src/corrupt.py", "stage": "tokenization", "message": f"RecursionError: {RECURSION}"},
+                    {"path": "broken.py", "stage": "fragment_extraction", "message": "ValueError: unbalanced block"},
+                ],
+                "submission2": [],
+            },
+        )
+        self.assertEqual(sorted(details["files_tokens"]["submission1"]), ["broken.py", "main.py"])
+        self.assertGreater(results["overall_similarity"], 0.0)
+        visualized = [entry["file_pair"]["file_from_submission1"] for entry in results["visualization_data"]]
+        self.assertEqual(sorted(visualized), ["main.py", "src/corrupt.py"])
+        self.assertIs(repository.results, results)
+
+    def test_comparisons_without_failures_are_not_partial(self):
+        """Comparisons where no file failed have no file error."""
+        shutil.rmtree(self.root / str(UUID(int=1)) / "src")
+        (self.root / str(UUID(int=1)) / "broken.py").unlink()
+
+        details = self.compare(FailingTokenizer(), RecordingSimilarityRepository())["similarity_details"]
+
+        self.assertFalse(details["partial"])
+        self.assertEqual(details["file_errors"], {"submission1": [], "submission2": []})
+
+    def test_systemic_failures_fail_the_comparison(self):
+        """A database failure while processing a file is not recorded as a file error, the comparison fails."""
+        repository = RecordingSimilarityRepository()
+
+        with self.assertRaises(DatabaseException):
+            self.compare(FailingTokenizer(DatabaseException("database is down")), repository)
+        self.assertEqual(repository.statuses[-1][0], SimilarityStatus.FAILED)
+        self.assertIsNone(repository.results)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,246 @@
+"""
+Tests for the language policies of project steps, applied to submissions at upload
+"""
+
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from unittest.mock import MagicMock, patch
+from uuid import uuid4
+
+from app.config.config import Settings
+from app.domains.repositories.exceptions import MalwareDetectedException
+from app.domains.repositories.submission_fetcher import FetchedSubmission
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.domains.submissions.dto.project_step_config_dto import LanguagePolicyDto
+from app.domains.submissions.dto.rule_dto import RuleDto
+from app.domains.submissions.language_policy import evaluate_language_policy
+from app.domains.submissions.rules.rule_service import RuleService
+from app.domains.submissions.submissions_models import Submission
+from app.domains.submissions.submissions_service import SubmissionService
+from app.shared.exceptions import ValidationException
+from app.shared.metrics import SUBMISSION_REJECTIONS
+from tests.helpers import tokenization_service
+
+C_PROJECT = {
+    "src/main.c": "int main(void) { return run(); }\n" * 30,
+    "src/run.h": "int run(void);\n" * 10,
+    "build.py": "import subprocess\nsubprocess.run(['make'])\n" * 40,
+    "README.md": "# Project\n",
+}
+
+
+class DirectoryFetcher:
+    """Submission fetcher double writing the files of the submission to a fresh directory"""
+
+    def __init__(self, files):
+        self.files = files
+        self.fetched = []  # directory of every successful fetch
+        self.attempts = 0
+        self.error = None
+
+    def fetch_submission(self, submission_data):
+        self.attempts += 1
+        if self.error:
+            raise self.error
+        root = Path(tempfile.mkdtemp())
+        for path, content in self.files.items():
+            (root / path).parent.mkdir(parents=True, exist_ok=True)
+            (root / path).write_text(content, encoding="utf-8")
+        self.fetched.append(root)
+        return root
+
+    def is_supported_submission(self, submission_data):
+        return True
+
+
+class LanguagePolicyTestCase(unittest.TestCase):
+    """Base class with a C project whose build script is in Python"""
+
+    def setUp(self):
+        self.files = dict(C_PROJECT)
+        self.service = SubmissionService.__new__(SubmissionService)
+        self.service.rule_service = SimpleNamespace(submission_fetcher=DirectoryFetcher(self.files))
+        self.service.detection_service = SimpleNamespace(tokenization_service=tokenization_service())
+        self.submission_data = CreateSubmissionDto(
+            link="https://github.com/user/repository.git",
+            project_uuid=uuid4(),
+            group_uuid=uuid4(),
+            project_step_uuid=uuid4(),
+        )
+
+        scheduler = SimpleNamespace(run=lambda function, *args: function(*args))
+        patcher = patch("app.domains.submissions.submissions_service.get_ingestion_scheduler", return_value=scheduler)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def check(self, **policy):
+        step_config = SimpleNamespace(language_policy=LanguagePolicyDto(**policy).model_dump(mode="json"))
+        with FetchedSubmission(self.service.rule_service.submission_fetcher, self.submission_data) as files:
+            return self.service._check_language_policy(files, step_config)
+
+
+class TestLanguagePolicy(LanguagePolicyTestCase):
+    """Tests for the result of uploads against the language policy of their step"""
+
+    def test_auxiliary_languages_are_exempt(self):
+        """A build script in an auxiliary language leaves a C project compliant, counted otherwise."""
+        result = self.check(allowed_languages=["C"], auxiliary_languages=["python", "markdown"])
+
+        self.assertEqual(result["status"], "compliant")
+        self.assertEqual(result["shares"], {"c": 1.0})
+        self.assertEqual(result["ignored_languages"], ["markdown", "python"])
+        self.assertEqual(set(result["content_bytes"]), {"c", "markdown", "python"})
+
+        result = self.check(allowed_languages=["c"], auxiliary_languages=["markdown"])
+        self.assertEqual((result["status"], result["disallowed_languages"]), ("violation", ["python"]))
+        self.assertGreater(result["disallowed_share"], 0.5)
+
+    def test_warning_below_the_violation_threshold(self):
+        """Some C++ in a C project is a warning, the submission is kept with its result even with enforce=reject."""
+        self.files["src/util.cpp"] = "int twice(int x) { return 2 * x; }\n" * 8
+        result = self.check(allowed_languages=["c"], auxiliary_languages=["python", "markdown"], enforce="reject")
+
+        self.assertEqual((result["status"], result["disallowed_languages"]), ("warning", ["cpp"]))
+        self.assertTrue(0.1 <= result["disallowed_share"] < 0.5)
+
+    def test_violations_are_rejected_when_enforced(self):
+        """A project mostly in C++ is refused with its result under enforce=reject, only flagged otherwise."""
+        self.files["src/engine.cpp"] = "class Engine { public: int run(); };\n" * 200
+        policy = {"allowed_languages": ["c"], "auxiliary_languages": ["python", "markdown"]}
+
+        self.assertEqual(self.check(**policy)["status"], "violation")
+        with self.assertRaises(ValidationException) as context:
+            self.check(**policy, enforce="reject")
+
+        self.assertEqual(context.exception.status_code, 422)
+        self.assertEqual(context.exception.detail["code"], "languageNotAllowed")
+        self.assertEqual(context.exception.detail["language_policy_result"]["disallowed_languages"], ["cpp"])
+
+    def test_steps_without_policy_are_not_fetched(self):
+        """Without policy nothing is fetched nor recorded."""
+        files = FetchedSubmission(None, self.submission_data)
+
+        self.assertIsNone(self.service._check_language_policy(files, None))
+        self.assertIsNone(self.service._check_language_policy(files, SimpleNamespace(language_policy=None)))
+
+
+class TestUploadFetches(LanguagePolicyTestCase):
+    """Tests for the files of an upload, fetched once for its rules, malware scan, language policy and storage"""
+
+    def setUp(self):
+        super().setUp()
+        self.fetcher = self.service.rule_service.submission_fetcher
+        self.service.rule_service = RuleService(self.fetcher)
+        self.service.quota_service = MagicMock()
+        self.service.detection_service = MagicMock(tokenization_service=tokenization_service())
+        self.service.detection_service.process_submission_similarities_async.return_value = None
+        self.service.storage_service = MagicMock()
+        self.service.storage_service.ingest_directory.side_effect = self.ingest
+        self.stored = []
+
+        policy = LanguagePolicyDto(allowed_languages=["c"], auxiliary_languages=["python", "markdown"])
+        step_config = SimpleNamespace(deadline=None, language_policy=policy.model_dump(mode="json"))
+        self.service.repository = MagicMock()
+        self.service.repository.check_duplicate_submission.return_value = False
+        self.service.repository.get_step_config.return_value = step_config
+        self.service.repository.create.side_effect = self.create
+        self.service.repository.update.side_effect = lambda submission_id, update_data: self.submission
+
+        settings = Settings(malware_scan_enabled=True)
+        patcher = patch("app.domains.submissions.submissions_service.get_settings", return_value=settings)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def create(self, submission_data, **kwargs):
+        self.submission = Submission(
+            link=submission_data.link,
+            project_uuid=submission_data.project_uuid,
+            group_uuid=submission_data.group_uuid,
+            project_step_uuid=submission_data.project_step_uuid,
+            language_policy_result=kwargs["language_policy_result"],
+        )
+        return self.submission
+
+    def ingest(self, submission, directory, extracted=False):
+        files = sorted(path.relative_to(directory).as_posix() for path in directory.rglob("*") if path.is_file())
+        self.stored.append(files)
+        return {"version": 1, "files": len(files), "total_bytes": 100}
+
+    def upload(self, link: str, **fields) -> CreateSubmissionDto:
+        return CreateSubmissionDto(
+            link=link, project_uuid=uuid4(), group_uuid=uuid4(), project_step_uuid=uuid4(), **fields
+        )
+
+    def test_rules_policy_and_storage_share_one_fetch(self):
+        """An upload with rules is fetched once for them, its language policy and its storage, then removed."""
+        rules = [RuleDto(name="file_presence", params={"must_exist": ["src/*.c"]})]
+
+        response = self.service.create_submission(self.upload("https://github.com/user/repository.git", rules=rules))
+
+        self.assertEqual(self.fetcher.attempts, 1)
+        self.assertEqual([result["passed"] for result in response.rule_results], [True])
+        self.assertEqual(response.data.language_policy_result.status, "compliant")
+        self.assertEqual(self.stored, [["README.md", "build.py", "src/main.c", "src/run.h"]])
+        self.assertEqual(response.storage["files"], 4)
+        self.assertFalse(self.fetcher.fetched[0].exists())
+
+    def test_scanned_archive_is_fetched_once(self):
+        """An archive without rules is fetched once for its malware scan, its language policy and its storage."""
+        response = self.service.create_submission(self.upload("s3://bucket/submission.zip"))
+
+        self.assertEqual(self.fetcher.attempts, 1)
+        self.assertEqual(len(self.stored), 1)
+        self.assertIsNone(response.data.storage_error)
+        self.assertFalse(self.fetcher.fetched[0].exists())
+
+    def test_infected_archive_with_rules_is_counted(self):
+        """An infected archive is refused before its rules are run, counted as a malware rejection."""
+        self.fetcher.error = MalwareDetectedException("s3://bucket/submission.zip", "Win.Test.EICAR_HDB-1")
+        rejections = SUBMISSION_REJECTIONS.labels("malware").get()
+        rules = [RuleDto(name="file_presence", params={"must_exist": ["src/*.c"]})]
+
+        with self.assertRaises(MalwareDetectedException):
+            self.service.create_submission(self.upload("s3://bucket/submission.zip", rules=rules))
+
+        self.assertEqual(self.fetcher.attempts, 1)
+        self.assertEqual(SUBMISSION_REJECTIONS.labels("malware").get(), rejections + 1)
+        self.service.repository.create.assert_not_called()
+
+    def test_failed_fetch_is_flagged_on_the_submission(self):
+        """A failed fetch is not attempted again, the submission is created with the error of its storage."""
+        self.fetcher.error = OSError("bucket unreachable")
+
+        response = self.service.create_submission(self.upload("s3://bucket/submission.zip"))
+
+        self.assertEqual(self.fetcher.attempts, 1)
+        self.assertEqual(response.storage, {"error": "bucket unreachable"})
+        self.assertIsNone(response.data.language_policy_result)
+        self.assertEqual(self.stored, [])
+        self.service.repository.set_storage_error.assert_called_once_with(self.submission.id, "bucket unreachable")
+
+
+class TestLanguagePolicyValidation(LanguagePolicyTestCase):
+    """Tests for the validation of language policies"""
+
+    def test_inconsistent_policies_are_refused(self):
+        """Thresholds out of order, languages both allowed and auxiliary and unknown languages are refused."""
+        with self.assertRaises(ValueError):
+            LanguagePolicyDto(allowed_languages=["c"], warning_threshold=0.6, violation_threshold=0.5)
+        with self.assertRaises(ValueError):
+            LanguagePolicyDto(allowed_languages=["c", "python"], auxiliary_languages=["python"])
+        with self.assertRaisesRegex(ValidationException, "klingon"):
+            self.service._validate_language_policy(LanguagePolicyDto(allowed_languages=["c", "klingon"]))
+
+    def test_empty_submissions_are_compliant(self):
+        """Submissions with nothing but auxiliary content have nothing to judge."""
+        policy = LanguagePolicyDto(allowed_languages=["c"], auxiliary_languages=["markdown"])
+
+        result = evaluate_language_policy(policy, {"markdown": 120})
+
+        self.assertEqual((result.status, result.shares, result.disallowed_share), ("compliant", {}, 0.0))
+
+
+if __name__ == "__main__":
+    unittest.main()
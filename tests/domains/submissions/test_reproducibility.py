@@ -0,0 +1,137 @@
+"""
+Tests that identical runs over the same corpus produce byte-identical reports, whatever the number of threads
+"""
+
+import json
+import random
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import UUID
+
+from app.domains.detection.pairwise_comparison import ParallelPairwiseComparator
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.runs.run_recorder import extract_fragments
+from tests.helpers import SharedLinesVisualization, WordTokenizer, detection_service, write_submissions
+
+SUBMISSION_FILES = {
+    UUID(int=1): {
+        "main.py": "def main ( ) :\n    total = 0\n    for value in values :\n        total = total + value\n",
+        "src/utils.py": "def average ( values ) :\n    return sum ( values ) / len ( values )\n",
+        "utils.py": "def greet ( name ) :\n    return name\n",
+    },
+    UUID(int=2): {
+        "main.py": "def main ( ) :\n    total = 0\n    for item in items :\n        total = total + item\n",
+        "lib/utils.py": "def average ( values ) :\n    return sum ( values ) / len ( values )\n",
+    },
+    UUID(int=3): {
+        "app.py": "class App :\n    def run ( self ) :\n        return self . total / 3\n",
+        "utils.py": "def greet ( name ) :\n    return name\n",
+        "z/main.py": "def main ( ) :\n    total = 0\n",
+    },
+}
+
+
+class RecordingSimilarityRepository:
+    """Similarity repository double keeping the results stored for each record"""
+
+    def __init__(self):
+        self.results = {}
+
+    def update_status(self, record_id, status, error_message=None):
+        pass
+
+    def update_results(self, record_id, results):
+        self.results[record_id] = results
+
+
+def shuffled_listing(seed: int):
+    """Path.rglob listing files in a shuffled order, as filesystems list directories in different orders"""
+    generator = random.Random(seed)
+    rglob = Path.rglob
+
+    def listing(self, pattern):
+        paths = list(rglob(self, pattern))
+        generator.shuffle(paths)
+        return iter(paths)
+
+    return patch.object(Path, "rglob", listing)
+
+
+def run_report(workers: int, seed: int) -> str:
+    """JSON report of a run comparing every pair of the corpus, with comparisons and fingerprinting on workers"""
+    root = Path(tempfile.mkdtemp())
+    try:
+        write_submissions(root, SUBMISSION_FILES)
+        service = detection_service(
+            root, FingerprintService(WordTokenizer(), k=3, window=2, workers=workers), SharedLinesVisualization()
+        )
+        repository = RecordingSimilarityRepository()
+
+        def compare(pair):
+            first, second = (
+                SimpleNamespace(
+                    id=submission_id,
+                    link=f"https://github.com/user/{submission_id}.git",
+                    project_uuid=UUID(int=10),
+                    group_uuid=UUID(int=11),
+                    project_step_uuid=UUID(int=12),
+                    link_type=None,
+                )
+                for submission_id in pair
+            )
+            record = SimpleNamespace(id=pair)
+            return service._process_single_comparison_with_repos(record, first, second, None, repository)
+
+        pairs = [(first, second) for first in SUBMISSION_FILES for second in SUBMISSION_FILES if first < second]
+        with shuffled_listing(seed):
+            results = list(ParallelPairwiseComparator(workers=workers, chunk_size=1).compare(pairs, compare))
+    finally:
+        shutil.rmtree(root, ignore_errors=True)
+
+    report = []
+    for (first, second), result in results:
+        # Only the time spent differs between identical runs
+        result = {key: value for key, value in result.items() if key != "processing_time_seconds"}
+        report.append(
+            {
+                "pair": [str(first), str(second)],
+                "results": result,
+                "fragments": extract_fragments(result["visualization_data"]),
+            }
+        )
+    return json.dumps(report, sort_keys=True, default=str)
+
+
+class TestReproducibility(unittest.TestCase):
+    """Tests for the reports of identical runs with different thread counts and file listing orders"""
+
+    def test_reports_are_byte_identical(self):
+        """One thread and four threads over the same corpus give the same JSON report, byte for byte."""
+        sequential = run_report(workers=1, seed=1)
+        parallel = run_report(workers=4, seed=2)
+
+        self.assertEqual(parallel, sequential)
+        self.assertEqual(run_report(workers=4, seed=3), sequential)
+
+    def test_report_lists_pairs_and_fragments_canonically(self):
+        """Pairs follow their submission IDs, file pairs of equal similarity their paths."""
+        report = json.loads(run_report(workers=4, seed=4))
+
+        ids = [str(submission_id) for submission_id in SUBMISSION_FILES]
+        self.assertEqual([entry["pair"] for entry in report], [ids[:2], [ids[0], ids[2]], ids[1:]])
+        files = [
+            (fragment["similarity"], fragment["file1_path"], fragment["file2_path"])
+            for fragment in report[0]["fragments"]
+            if fragment["fragment_type"] == "file"
+        ]
+        self.assertEqual(files, sorted(files, key=lambda file: (-file[0], file[1], file[2])))
+        files_tokens = report[0]["results"]["similarity_details"]["files_tokens"]["submission1"]
+        self.assertEqual(list(files_tokens), ["main.py", "src/utils.py", "utils.py"])
+
+
+if __name__ == "__main__":
+    unittest.main()
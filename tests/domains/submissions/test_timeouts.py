@@ -0,0 +1,258 @@
+"""
+Tests for the timeouts of files, pairs and runs, and for the results salvaged when they expire
+"""
+
+import shutil
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import UUID, uuid4
+
+from app.config.config import Settings
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.runs.run_recorder import PAIR_METRICS
+from app.domains.runs.runs_models import DetectionRunStatus
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.shared.timeouts import RUN, TimedOut, Timeout, checkpoint
+from tests.helpers import SOURCE, FailingTokenizer, FailingVisualization, detection_service, write_submissions
+
+MODULE = "app.domains.submissions.detection_integration_service"
+
+# Word of the sources the slow doubles never finish with, short of a timeout
+PATHOLOGICAL = "pathological"
+
+
+def wait_for_timeout() -> None:
+    """Loop like a pathological comparison, stopped only by a checkpoint"""
+    while True:
+        checkpoint()
+        time.sleep(0.005)
+
+
+class SlowTokenizer(FailingTokenizer):
+    """Tokenization service double never done with files named slow"""
+
+    def tokenize(self, text, file_path=None, raise_errors=False):
+        if file_path is not None and file_path.stem == "slow":
+            wait_for_timeout()
+        return super().tokenize(text, file_path, raise_errors)
+
+
+class SlowComparator(SimilarityDetectionService):
+    """Similarity service never done comparing submissions with a pathological source"""
+
+    def compare_similarity(self, tokens1, tokens2):
+        if any(token["text"] == PATHOLOGICAL for token in tokens1 + tokens2):
+            wait_for_timeout()
+        return super().compare_similarity(tokens1, tokens2)
+
+
+class SubmissionRepository:
+    """Submission repository double, over the submissions of its subclass"""
+
+    submissions: dict = {}
+
+    def __init__(self, session=None):
+        pass
+
+    def get_by_id(self, submission_id):
+        return self.submissions.get(submission_id)
+
+
+class SimilarityRepository:
+    """Similarity repository double updating the records of its subclass in place, like the database session does"""
+
+    records: dict = {}
+
+    def __init__(self, session=None):
+        pass
+
+    def check_existing_comparison(self, submission1_id, submission2_id):
+        return False
+
+    def create(self, data):
+        record = SimpleNamespace(
+            id=uuid4(),
+            error_message=None,
+            similarity_details=None,
+            visualization_data=None,
+            processing_time_seconds=None,
+            **dict.fromkeys(PAIR_METRICS, 0.0),
+            **data,
+        )
+        self.records[record.id] = record
+        return record
+
+    def update_status(self, record_id, status, error_message=None):
+        record = self.records[record_id]
+        record.status, record.error_message = status, error_message
+
+    def update_results(self, record_id, results):
+        record = self.records[record_id]
+        for metric in PAIR_METRICS + ("similarity_details", "visualization_data", "processing_time_seconds"):
+            setattr(record, metric, results.get(metric))
+        record.status = results.get("status", SimilarityStatus.COMPLETED)
+
+
+class RunRepository:
+    """Run repository double keeping the pairs written and how the run finished"""
+
+    def __init__(self):
+        self.pairs = []
+        self.finished = None
+
+    def insert_batch(self, run_id, pairs, fragments):
+        self.pairs.extend(pairs)
+        return len(pairs)
+
+    def mark_not_comparable(self, run_id, reasons):
+        return 0
+
+    def finish_run(self, run_id, status, error_message=None, cache_stats=None, profile=None, file_errors=None):
+        self.finished = SimpleNamespace(id=run_id, status=status, error_message=error_message, file_errors=file_errors)
+        return self.finished
+
+
+class TimeoutTestCase(unittest.TestCase):
+    """Detection service comparing submissions written under a temporary directory, one per UUID"""
+
+    def setUp(self):
+        self.root = Path(tempfile.mkdtemp())
+        self.addCleanup(shutil.rmtree, self.root, True)
+        self.submissions = {}
+        self.run_repository = RunRepository()
+
+    def submission(self, number: int, files: dict):
+        submission_id = UUID(int=number)
+        write_submissions(self.root, {submission_id: files})
+        self.submissions[submission_id] = SimpleNamespace(
+            id=submission_id,
+            link=f"https://github.com/user/{submission_id}.git",
+            project_uuid=UUID(int=100),
+            group_uuid=uuid4(),
+            project_step_uuid=UUID(int=101),
+            submitted_by_uuid=None,
+            link_type=None,
+        )
+        return submission_id
+
+    def settings(self, **timeouts) -> None:
+        settings = Settings(detection_comparison_workers=1, comparison_index_enabled=False, **timeouts)
+        patcher = patch("app.config.config.get_settings", return_value=settings)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def service(self, tokenizer=None) -> DetectionIntegrationService:
+        service = detection_service(
+            self.root,
+            FingerprintService(tokenizer or FailingTokenizer(), k=3, window=2),
+            FailingVisualization(),
+            SlowComparator(),
+        )
+        service.job_scheduler = SimpleNamespace(interrupted=threading.Event())
+        service._get_thread_session = lambda: None
+
+        class Submissions(SubmissionRepository):
+            submissions = self.submissions
+
+        class Similarities(SimilarityRepository):
+            records = {}
+
+        for patcher in (
+            patch(f"{MODULE}.SubmissionRepository", Submissions),
+            patch(f"{MODULE}.SubmissionSimilarityRepository", Similarities),
+            patch(f"{MODULE}.DetectionRunRepository", lambda session: self.run_repository),
+        ):
+            patcher.start()
+            self.addCleanup(patcher.stop)
+        return service
+
+    def detect(self, service, trigger, others) -> list:
+        """Process a run of the trigger against the others, its pairs in canonical order"""
+        service._process_detection_run_threaded(uuid4(), trigger, others, UUID(int=100), UUID(int=101))
+        return self.run_repository.pairs
+
+
+class TestTimeouts(TimeoutTestCase):
+    """Tests for each timeout expiring, the run going on or ending with what it compared"""
+
+    def test_timeout_of_a_file(self):
+        """A file tokenized too long is skipped as timed out, its pair completes as partial with the other files."""
+        self.settings(detection_file_timeout_seconds=0.1)
+        trigger = self.submission(1, {"main.py": SOURCE, "src/slow.py": SOURCE})
+        other = self.submission(2, {"main.py": SOURCE})
+
+        [pair] = self.detect(self.service(SlowTokenizer()), trigger, [other])
+
+        self.assertEqual(pair.status, SimilarityStatus.COMPLETED)
+        self.assertTrue(pair.partial)
+        self.assertGreater(pair.overall_similarity, 0.0)
+        self.assertEqual(self.run_repository.finished.status, DetectionRunStatus.COMPLETED)
+        self.assertEqual(
+            self.run_repository.finished.file_errors,
+            [
+                {
+                    "path": "src/slow.py",
+                    "stage": "tokenization",
+                    "message": "File timed out after 0.1s",
+                    "submission_id": str(trigger),
+                    "timed_out": True,
+                }
+            ],
+        )
+
+    def test_timeout_of_a_pair(self):
+        """A pair compared too long is recorded as timed out and partial, the run goes on with the next pairs."""
+        self.settings(detection_pair_timeout_seconds=0.1)
+        trigger = self.submission(1, {"main.py": SOURCE})
+        others = [self.submission(2, {"main.py": SOURCE})]
+        others.append(self.submission(3, {"main.py": f"{SOURCE}{PATHOLOGICAL} = 1\n"}))
+        others.append(self.submission(4, {"main.py": SOURCE}))
+
+        pairs = self.detect(self.service(), trigger, others)
+
+        self.assertEqual(
+            [pair.status for pair in pairs],
+            [SimilarityStatus.COMPLETED, SimilarityStatus.TIMED_OUT, SimilarityStatus.COMPLETED],
+        )
+        self.assertEqual([pair.partial for pair in pairs], [False, True, False])
+        self.assertEqual(pairs[1].error_message, "Pair timed out after 0.1s")
+        self.assertEqual(self.run_repository.finished.status, DetectionRunStatus.COMPLETED)
+
+    def test_timeout_of_the_run(self):
+        """A run processed too long stops its pair in progress and starts no other, it is partial with the rest."""
+        self.settings(detection_run_timeout_seconds=0.3)
+        trigger = self.submission(1, {"main.py": SOURCE})
+        others = [self.submission(2, {"main.py": SOURCE})]
+        others.append(self.submission(3, {"main.py": f"{SOURCE}{PATHOLOGICAL} = 1\n"}))
+        others += [self.submission(number, {"main.py": SOURCE}) for number in (4, 5)]
+
+        pairs = self.detect(self.service(), trigger, others)
+
+        self.assertEqual([pair.status for pair in pairs], [SimilarityStatus.COMPLETED, SimilarityStatus.TIMED_OUT])
+        self.assertEqual(pairs[1].error_message, "Run timed out after 0.3s")
+        finished = self.run_repository.finished
+        self.assertEqual(finished.status, DetectionRunStatus.PARTIAL)
+        self.assertEqual(finished.error_message, "Run timed out after 0.3s, 2/4 pairs compared")
+
+    def test_checkpoints_raise_for_the_outermost_expired_timeout(self):
+        """Checkpoints only enforce the timeouts entered, the run before the pair, and none once left."""
+        now = [0.0]
+        run = Timeout(RUN, 10, clock=lambda: now[0])
+        with run, Timeout("pair", 0):
+            checkpoint()
+            now[0] = 10
+            with self.assertRaises(TimedOut) as raised:
+                checkpoint()
+        self.assertIs(raised.exception.timeout, run)
+        checkpoint()
+
+
+if __name__ == "__main__":
+    unittest.main()
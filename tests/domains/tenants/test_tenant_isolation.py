@@ -0,0 +1,278 @@
+"""
+Tests for the isolation of tenants in the data access layer
+"""
+
+import unittest
+from datetime import timedelta
+from unittest.mock import patch
+from uuid import uuid4
+
+from fastapi.testclient import TestClient
+from sqlalchemy import update
+from sqlmodel import Session, SQLModel
+
+from app.config.config import Settings
+from app.domains.retention.retention_repository import RetentionRepository
+from app.domains.runs.runs_models import DetectionPair, DetectionRunStatus
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.schedules.schedules_models import DetectionSchedule
+from app.domains.schedules.schedules_repository import DetectionScheduleRepository
+from app.domains.submissions.submissions_models import SimilarityStatus, Submission, SubmissionSimilarity
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.domains.submissions.submissions_similarity_repository import SubmissionSimilarityRepository
+from app.domains.tenants.tenant_filters import install_tenant_filters
+from app.domains.tenants.tenants_repository import TenantRepository
+from app.main import app
+from app.shared.database import get_session
+from app.shared.exceptions import NotFoundException
+from app.shared.tenancy import Tenancy, tenancy_scope
+from app.shared.timestamps import utc_now
+from tests.helpers import create_test_engine
+
+
+# Endpoints reaching one resource of a project, formatted with the IDs of the resources of a project
+RESOURCE_ENDPOINTS = [
+    ("GET", "/submissions/{submission_id}"),
+    ("PUT", "/submissions/{submission_id}"),
+    ("DELETE", "/submissions/{submission_id}"),
+    ("GET", "/submissions/{submission_id}/metrics"),
+    ("GET", "/submissions/{submission_id}/files"),
+    ("GET", "/submissions/{submission_id}/files/main.py"),
+    ("GET", "/submissions/{submission_id}/similarities"),
+    ("GET", "/submissions/similarities/{similarity_id}/detailed"),
+    ("GET", "/submissions/project/{project_uuid}/teams/{team_id}"),
+    ("GET", "/runs/{run_id}"),
+    ("GET", "/runs/{run_id}/pairs"),
+    ("GET", "/runs/pairs/{pair_id}"),
+    ("POST", "/runs/{run_id}/reclassify"),
+    ("GET", "/runs/{run_id}/callbacks"),
+    ("GET", "/runs/{run_id}/report.html"),
+    ("GET", "/runs/{run_id}/stats"),
+    ("GET", "/runs/{run_id}/summary"),
+    ("GET", "/runs/{run_id}/graph"),
+    ("GET", "/runs/{run_id}/pairs.csv"),
+    ("GET", "/runs/{run_id}/results.ndjson"),
+    ("GET", "/runs/{run_id}/export/jplag"),
+    ("GET", "/runs/{run_id}/export/sarif"),
+    ("GET", "/runs/{run_id}/moss.zip"),
+    ("GET", "/projects/{project_uuid}/schedules/{schedule_id}"),
+    ("POST", "/projects/{project_uuid}/schedules/{schedule_id}/pause"),
+    ("DELETE", "/projects/{project_uuid}/schedules/{schedule_id}"),
+    ("POST", "/projects/{project_uuid}/export"),
+    ("GET", "/projects/{project_uuid}/exports/{submission_id}"),
+    ("POST", "/projects/{project_uuid}/import"),
+]
+
+
+class TenantFixture(unittest.TestCase):
+    """Two tenants, each with a project holding one resource of each kind"""
+
+    def setUp(self):
+        install_tenant_filters()
+        self.engine = create_test_engine()
+        SQLModel.metadata.drop_all(self.engine)
+        SQLModel.metadata.create_all(self.engine)
+        self.session = Session(self.engine)
+
+        patcher = patch(
+            "app.shared.tenancy.project_tenant",
+            side_effect=lambda project_uuid: TenantRepository(Session(self.engine)).get_tenant(project_uuid),
+        )
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+        self.own = self.create_project("tenant-a")
+        self.other = self.create_project("tenant-b")
+
+    def tearDown(self):
+        self.session.close()
+        SQLModel.metadata.drop_all(self.engine)
+        self.engine.dispose()
+
+    def create_project(self, tenant_id: str) -> dict:
+        """Project of a tenant with one resource of each kind"""
+        project_uuid, project_step_uuid = uuid4(), uuid4()
+        TenantRepository(self.session).assign(project_uuid, tenant_id)
+        submissions = [
+            Submission(
+                link="https://github.com/user/repository.git",
+                project_uuid=project_uuid,
+                group_uuid=uuid4(),
+                project_step_uuid=project_step_uuid,
+            )
+            for _ in range(2)
+        ]
+        self.session.add_all(submissions)
+        self.session.commit()
+
+        repository = SubmissionRepository(self.session)
+        team = repository.create_team(project_uuid, "Team", [uuid4()])
+        repository.upsert_step_config(project_uuid, project_step_uuid, {"declared_collaborations": []})
+        similarity = SubmissionSimilarityRepository(self.session).create(
+            {
+                "submission_id": submissions[0].id,
+                "compared_submission_id": submissions[1].id,
+                "project_uuid": project_uuid,
+                "project_step_uuid": project_step_uuid,
+                "status": SimilarityStatus.COMPLETED,
+            }
+        )
+        run_repository = DetectionRunRepository(self.session)
+        run = run_repository.create_run(
+            {
+                "project_uuid": project_uuid,
+                "project_step_uuid": project_step_uuid,
+                "started_at": utc_now(),
+                "status": DetectionRunStatus.COMPLETED,
+            },
+            [{"submission_id": s.id, "group_uuid": s.group_uuid} for s in submissions],
+        )
+        pair = DetectionPair(
+            run_id=run.id,
+            project_uuid=project_uuid,
+            project_step_uuid=project_step_uuid,
+            submission_id=submissions[0].id,
+            compared_submission_id=submissions[1].id,
+            status=SimilarityStatus.COMPLETED,
+        )
+        run_repository.insert_batch(run.id, [pair], [])
+        schedule = DetectionScheduleRepository(self.session).save(
+            DetectionSchedule(
+                project_uuid=project_uuid,
+                project_step_uuid=project_step_uuid,
+                cron="0 2 * * *",
+                next_run_at=utc_now() + timedelta(days=1),
+                scanned_until=utc_now(),
+            )
+        )
+        RetentionRepository(self.session).upsert_policy(project_uuid, {"submission_retention_days": 365})
+        return {
+            "project_uuid": project_uuid,
+            "project_step_uuid": project_step_uuid,
+            "submission_id": submissions[0].id,
+            "team_id": team.id,
+            "similarity_id": similarity.id,
+            "run_id": run.id,
+            "pair_id": pair.id,
+            "schedule_id": schedule.id,
+        }
+
+    def lookups(self, session: Session, resources: dict) -> dict:
+        """Every lookup of the resources of a project through the repositories"""
+        submissions = SubmissionRepository(session)
+        runs = DetectionRunRepository(session)
+        return {
+            "submission": submissions.get_by_id(resources["submission_id"]),
+            "step_submissions": submissions.get_by_project_step(
+                resources["project_uuid"], resources["project_step_uuid"]
+            ),
+            "team": submissions.get_team(resources["team_id"]),
+            "step_config": submissions.get_step_config(resources["project_step_uuid"]),
+            "similarity": SubmissionSimilarityRepository(session).get_by_id(resources["similarity_id"]),
+            "run": runs.get_run(resources["run_id"]),
+            "participants": runs.get_participants(resources["run_id"]),
+            "pairs": runs.get_pairs(resources["run_id"])[0],
+            "pair": runs.get_pair(resources["pair_id"]),
+            "schedule": DetectionScheduleRepository(session).get(resources["schedule_id"]),
+            "policy": RetentionRepository(session).get_policy(resources["project_uuid"]),
+        }
+
+
+class TestTenantIsolation(TenantFixture):
+    """Tests for the resources of two tenants, each reaching only its own"""
+
+    def test_resources_of_other_tenants_are_not_found(self):
+        """Resources of another tenant are answered exactly like resources that do not exist."""
+        missing = {name: uuid4() for name in self.other}
+        with tenancy_scope(Tenancy("tenant-a")), Session(self.engine) as session:
+            own = self.lookups(session, self.own)
+            other = self.lookups(session, self.other)
+            absent = self.lookups(session, missing)
+
+        self.assertTrue(all(own.values()), own)
+        self.assertEqual(other, absent)
+        with Session(self.engine) as session:
+            self.assertTrue(all(self.lookups(session, self.other).values()))
+
+    def test_a_tenancy_without_tenant_reaches_nothing(self):
+        with tenancy_scope(Tenancy(None)), Session(self.engine) as session:
+            self.assertFalse(any(self.lookups(session, self.own).values()))
+
+    def test_writes_to_projects_of_other_tenants_are_refused(self):
+        with tenancy_scope(Tenancy("tenant-a")), Session(self.engine) as session:
+            repository = SubmissionRepository(session)
+            with self.assertRaises(NotFoundException):
+                repository.create_team(self.other["project_uuid"], "Intruders", [])
+            with self.assertRaises(NotFoundException):
+                RetentionRepository(session).upsert_policy(self.other["project_uuid"], {})
+            with self.assertRaises(NotFoundException):
+                RetentionRepository(session).set_submission_legal_hold(self.other["submission_id"], True, "hold")
+
+            # Below the repositories, the flush refuses them too
+            session.add(
+                SubmissionSimilarity(
+                    submission_id=self.own["submission_id"],
+                    compared_submission_id=self.other["submission_id"],
+                    project_uuid=self.other["project_uuid"],
+                    project_step_uuid=self.other["project_step_uuid"],
+                )
+            )
+            with self.assertRaises(NotFoundException):
+                session.commit()
+            session.rollback()
+
+            self.assertIsNotNone(repository.create_team(self.own["project_uuid"], "Team", []))
+
+    def test_bulk_updates_only_reach_the_rows_of_the_tenant(self):
+        with tenancy_scope(Tenancy("tenant-a")), Session(self.engine) as session:
+            session.execute(update(Submission).values(description="Updated"))
+            session.commit()
+
+        with Session(self.engine) as session:
+            repository = SubmissionRepository(session)
+            self.assertEqual(repository.get_by_id(self.own["submission_id"]).description, "Updated")
+            self.assertIsNone(repository.get_by_id(self.other["submission_id"]).description)
+
+
+class TestTenantIsolationOverHttp(TenantFixture):
+    """Tests for the endpoints, answering a tenant about the resources of another like about missing ones"""
+
+    def setUp(self):
+        super().setUp()
+        patcher = patch("app.shared.tenancy.get_settings", return_value=Settings(tenancy_enabled=True))
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+        def session_override():
+            with Session(self.engine) as session:
+                yield session
+
+        app.dependency_overrides[get_session] = session_override
+        self.addCleanup(app.dependency_overrides.clear)
+        self.client = TestClient(app)
+
+    def request(self, method: str, path: str, resources: dict):
+        url = path.format(**resources)
+        body = {"archive_key": f"exports/projects/{resources['project_uuid']}/archive.tar.gz"}
+        return self.client.request(method, url, headers={"X-Tenant-Id": "tenant-a"}, json=body)
+
+    def test_resources_of_other_tenants_are_not_found(self):
+        """Every resource endpoint answers 404 for the IDs of another tenant, as for IDs that do not exist."""
+        missing = {name: uuid4() for name in self.other}
+        for method, path in RESOURCE_ENDPOINTS:
+            with self.subTest(f"{method} {path}"):
+                other = self.request(method, path, self.other)
+                absent = self.request(method, path, missing)
+
+                self.assertEqual(other.status_code, 404, other.text)
+                self.assertEqual(absent.status_code, 404, absent.text)
+
+        self.assertEqual(self.request("GET", "/submissions/{submission_id}", self.own).status_code, 200)
+        self.assertEqual(self.request("GET", "/runs/{run_id}", self.own).status_code, 200)
+        # Nothing of the other tenant was changed by the refused writes
+        with Session(self.engine) as session:
+            self.assertTrue(all(self.lookups(session, self.other).values()))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,117 @@
+"""
+Tests for the stripping of byte order marks before hashing, tokenization and line numbering
+"""
+
+import codecs
+import tempfile
+import unittest
+from pathlib import Path
+
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.fingerprints.fingerprinting import content_hash
+from app.domains.reports.highlighting import highlight_lines
+from app.domains.tokenization.streaming_source import (
+    BOM_CHARACTER,
+    StreamingSource,
+    decode_source,
+    normalize_source,
+    read_bom,
+)
+from app.shared.content_hash import HashAlgorithm
+from tests.helpers import SAMPLES_DIRECTORY, TREE_SITTER_AVAILABLE, WordTokenizer
+
+SAMPLE = SAMPLES_DIRECTORY / "sample.py"
+SOURCE = SAMPLE.read_text(encoding="utf-8")
+# The sample as saved by editors writing a byte order mark, by encoding of the mark
+VARIANTS = {
+    "utf-8": codecs.BOM_UTF8 + SOURCE.encode("utf-8"),
+    "utf-16-le": codecs.BOM_UTF16_LE + SOURCE.encode("utf-16-le"),
+    "utf-16-be": codecs.BOM_UTF16_BE + SOURCE.encode("utf-16-be"),
+}
+
+
+class TestByteOrderMarks(unittest.TestCase):
+    """Tests that sources starting with a BOM decode, hash and fingerprint like the same source without one"""
+
+    def setUp(self):
+        self.temp_dir = tempfile.TemporaryDirectory()
+        self.paths = {}
+        for encoding, data in VARIANTS.items():
+            self.paths[encoding] = Path(self.temp_dir.name) / f"{encoding}.py"
+            self.paths[encoding].write_bytes(data)
+
+    def tearDown(self):
+        self.temp_dir.cleanup()
+
+    def test_variants_decode_to_the_original_text(self):
+        """Decoded in memory or streamed in small chunks, every variant is the text without BOM."""
+        for encoding, data in VARIANTS.items():
+            self.assertEqual(decode_source(data), SOURCE, encoding)
+            self.assertEqual(read_bom(self.paths[encoding]), encoding)
+            for chunk_size in (1, 3, 64):
+                with StreamingSource(self.paths[encoding], chunk_size=chunk_size) as streamed:
+                    self.assertEqual(b"".join(streamed.chunks()), SOURCE.encode("utf-8"), encoding)
+                    self.assertEqual(streamed.bom, encoding)
+        self.assertIsNone(read_bom(SAMPLE))
+
+    def test_variants_have_the_content_hash_of_the_original(self):
+        """The BOM is not part of the content hash, in memory or streamed."""
+        expected = content_hash(SOURCE, HashAlgorithm.SHA256)
+
+        # A utf-8 text mode read keeps the BOM as a character
+        self.assertEqual(content_hash(BOM_CHARACTER + SOURCE, HashAlgorithm.SHA256), expected)
+        for encoding, path in self.paths.items():
+            with StreamingSource(path) as streamed:
+                self.assertEqual(streamed.hash(HashAlgorithm.SHA256), expected, encoding)
+
+    def test_variants_share_the_tokens_and_fingerprints_of_the_original(self):
+        """A BOM left by a text mode read is no token, so tokens, rows and fingerprints are identical."""
+        service = FingerprintService(WordTokenizer(), InMemoryFingerprintStore(), k=3, window=2)
+        expected = service.get_fingerprints(SOURCE, Path("sample.py"))
+
+        stats = service.new_stats()
+        fingerprint_set = service.get_fingerprints(BOM_CHARACTER + SOURCE, Path("sample.py"), stats)
+        self.assertEqual(stats.hits, 1)
+        self.assertEqual(fingerprint_set.fingerprints, expected.fingerprints)
+
+        uncached = FingerprintService(WordTokenizer(), None, k=3, window=2)
+        tokens = uncached.get_fingerprints(BOM_CHARACTER + SOURCE, Path("sample.py")).tokens
+        self.assertEqual(tokens, expected.tokens)
+        self.assertEqual(normalize_source(BOM_CHARACTER + SOURCE), SOURCE)
+
+    def test_highlighted_lines_are_those_of_the_original(self):
+        """The pair view shows no BOM and numbers lines like the original."""
+        self.assertEqual(highlight_lines(BOM_CHARACTER + SOURCE, "sample.py"), highlight_lines(SOURCE, "sample.py"))
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestByteOrderMarkTokenization(unittest.TestCase):
+    """Tests that tree-sitter gives identical tokens and function positions with or without a BOM"""
+
+    @classmethod
+    def setUpClass(cls):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        cls.service = TokenizationService()
+
+    def test_token_streams_are_identical(self):
+        """Tokens of every variant, in memory or streamed from disk, are those of the original."""
+        expected = self.service.tokenize(SOURCE, SAMPLE)
+        with tempfile.TemporaryDirectory() as directory:
+            path = Path(directory) / "sample.py"
+            self.assertEqual(self.service.tokenize(BOM_CHARACTER + SOURCE, path), expected)
+            for encoding, data in VARIANTS.items():
+                path.write_bytes(data)
+                self.assertEqual(self.service.tokenize_file(path, chunk_size=5), expected, encoding)
+
+    def test_function_positions_are_identical(self):
+        """Fragment line spans come from function positions, which the BOM does not shift."""
+        expected = self.service.extract_functions_with_positions(SOURCE, SAMPLE)
+
+        self.assertTrue(expected)
+        self.assertEqual(self.service.extract_functions_with_positions(BOM_CHARACTER + SOURCE, SAMPLE), expected)
+
+
+if __name__ == "__main__":
+    unittest.main()
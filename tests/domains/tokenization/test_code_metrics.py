@@ -0,0 +1,240 @@
+"""
+Tests for the code metrics of submissions
+"""
+
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+
+from app.domains.detection.directory_comparison import DirectoryComparator
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.tokenization.code_metrics import CodeMetricsCollector, file_metrics
+from app.domains.tokenization.tokenizer_config import builtin_tokenizer_config, load_tokenizer_config
+from tests.helpers import SAMPLES_DIRECTORY, TREE_SITTER_AVAILABLE
+
+
+def token(token_type: str, text: str, start: int, end: int = None) -> dict:
+    return {"type": token_type, "text": text, "start": start, "end": start if end is None else end}
+
+
+PYTHON_SOURCE = (
+    '#!/usr/bin/env python\n"""Docstring."""\n\ndef add(a, b):  # sum\n    return a if a else (lambda: b)()\n\n\n'
+)
+PYTHON_TOKENS = [
+    token("module", PYTHON_SOURCE.strip(), 0, 4),
+    token("comment", "#!/usr/bin/env python", 0),
+    token("expression_statement", '"""Docstring."""', 1),
+    token("string", '"""Docstring."""', 1),
+    token("function_definition", "def add(a, b):  # sum\n    return a if a else (lambda: b)()", 3, 4),
+    token("comment", "# sum", 3),
+    token("conditional_expression", "a if a else (lambda: b)()", 4),
+    token("lambda", "lambda: b", 4),
+]
+
+
+class TestFileMetrics(unittest.TestCase):
+    """Tests for the metrics of one file from its text and tokens"""
+
+    def setUp(self):
+        self.config = builtin_tokenizer_config()
+
+    def test_lines_functions_and_complexity(self):
+        """Docstrings are code, lines of code ending with a comment too, lambdas are functions."""
+        metrics = file_metrics(PYTHON_SOURCE, PYTHON_TOKENS, "python", self.config)
+
+        self.assertEqual(
+            metrics.to_dict(),
+            {
+                "files": 1,
+                "code_lines": 3,
+                "comment_lines": 1,
+                "blank_lines": 3,
+                "comment_ratio": 0.25,
+                "functions": 2,
+                "cyclomatic_complexity": 3,
+            },
+        )
+
+    def test_comments_over_several_lines(self):
+        """Lines inside a block comment are comments, those it starts or ends after code are code."""
+        source = "int x; /* starts\n   middle\n ends */ int y;\n/* only\n comment */\n"
+        tokens = [
+            token("translation_unit", source.strip(), 0, 4),
+            token("comment", "/* starts\n   middle\n ends */", 0, 2),
+            token("comment", "/* only\n comment */", 3, 4),
+        ]
+        metrics = file_metrics(source, tokens, "c", self.config)
+
+        self.assertEqual((metrics.code_lines, metrics.comment_lines, metrics.blank_lines), (2, 3, 0))
+        self.assertEqual((metrics.functions, metrics.cyclomatic_complexity), (0, 0))
+
+    def test_comments_with_nested_parts_and_their_newline(self):
+        """Parts of a doc comment are not removed twice, and the newline of a line comment ends it."""
+        source = "/// Docs\nfn main() {}"
+        tokens = [
+            token("source_file", source, 0, 1),
+            token("line_comment", "/// Docs\n", 0, 1),
+            token("outer_doc_comment_marker", "/", 0),
+            token("doc_comment", " Docs\n", 0, 1),
+            token("function_item", "fn main() {}", 1),
+        ]
+        metrics = file_metrics(source, tokens, "rust", self.config)
+
+        self.assertEqual((metrics.code_lines, metrics.comment_lines), (1, 1))
+        self.assertEqual((metrics.functions, metrics.cyclomatic_complexity), (1, 1))
+
+    def test_metrics_that_cannot_be_computed_are_null(self):
+        """Files without tokens, languages without definitions and stopped comments give null, never guesses."""
+        untokenized = file_metrics("x = 1\n\n", [], "python", self.config)
+        self.assertEqual(untokenized.blank_lines, 1)
+        self.assertIsNone(untokenized.code_lines)
+        self.assertIsNone(untokenized.functions)
+
+        undefined = file_metrics("main = print 1\n", [token("haskell", "main = print 1", 0)], "haskell", self.config)
+        self.assertEqual(undefined.code_lines, 1)
+        self.assertIsNone(undefined.cyclomatic_complexity)
+
+        data = file_metrics('{"a": 1}\n', [token("document", '{"a": 1}', 0)], "json", self.config)
+        self.assertEqual((data.code_lines, data.functions, data.cyclomatic_complexity), (1, 0, 0))
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = Path(directory) / "tokenizer.toml"
+            path.write_text('[languages.python]\nstop_tokens = ["comment"]\n', encoding="utf-8")
+            stopped = load_tokenizer_config(str(path))
+        tokens = [t for t in PYTHON_TOKENS if t["type"] != "comment"]
+        metrics = file_metrics(PYTHON_SOURCE, tokens, "python", stopped)
+        self.assertIsNone(metrics.comment_lines)
+        self.assertIsNone(metrics.comment_ratio)
+        self.assertEqual((metrics.blank_lines, metrics.functions), (3, 2))
+
+
+class TestCodeMetricsCollector(unittest.TestCase):
+    """Tests for the metrics of a submission, by language and in total"""
+
+    def test_totals_and_languages(self):
+        collector = CodeMetricsCollector(builtin_tokenizer_config())
+        collector.add(PYTHON_SOURCE, PYTHON_TOKENS, "python")
+        collector.add(PYTHON_SOURCE, PYTHON_TOKENS, "python")
+        collector.add('{"a": 1}\n', [token("document", '{"a": 1}', 0)], "json")
+
+        metrics = collector.to_dict()
+        self.assertEqual((metrics["files"], metrics["code_lines"], metrics["functions"]), (3, 7, 4))
+        self.assertEqual(metrics["comment_ratio"], 0.2222)
+        self.assertEqual(
+            {language: counts["files"] for language, counts in metrics["languages"].items()}, {"json": 1, "python": 2}
+        )
+        self.assertEqual(metrics["languages"]["python"]["cyclomatic_complexity"], 6)
+
+    def test_one_failed_file_nulls_its_language_and_the_totals(self):
+        """The language and the totals of a failed file are null, the other languages keep theirs."""
+        collector = CodeMetricsCollector(builtin_tokenizer_config())
+        collector.add(PYTHON_SOURCE, PYTHON_TOKENS, "python")
+        collector.add_failed("rust")
+
+        metrics = collector.to_dict()
+        self.assertEqual(metrics["files"], 2)
+        self.assertIsNone(metrics["code_lines"])
+        self.assertIsNone(metrics["comment_ratio"])
+        self.assertEqual(metrics["languages"]["rust"]["files"], 1)
+        self.assertIsNone(metrics["languages"]["rust"]["functions"])
+        self.assertEqual(metrics["languages"]["python"]["code_lines"], 3)
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestLanguageSampleMetrics(unittest.TestCase):
+    """Tests for the exact metrics of the language samples"""
+
+    EXPECTED = {
+        "sample.rs": {
+            "files": 1,
+            "code_lines": 171,
+            "comment_lines": 22,
+            "blank_lines": 38,
+            "comment_ratio": 0.114,
+            "functions": 16,
+            "cyclomatic_complexity": 45,
+        },
+        "sample.py": {
+            "files": 1,
+            "code_lines": 123,
+            "comment_lines": 14,
+            "blank_lines": 35,
+            "comment_ratio": 0.1022,
+            "functions": 13,
+            "cyclomatic_complexity": 34,
+        },
+        "sample.c": {
+            "files": 1,
+            "code_lines": 37,
+            "comment_lines": 5,
+            "blank_lines": 11,
+            "comment_ratio": 0.119,
+            "functions": 3,
+            "cyclomatic_complexity": 6,
+        },
+        "sample.sh": {
+            "files": 1,
+            "code_lines": 21,
+            "comment_lines": 8,
+            "blank_lines": 7,
+            "comment_ratio": 0.2759,
+            "functions": 1,
+            "cyclomatic_complexity": 4,
+        },
+        "sample.json": {
+            "files": 1,
+            "code_lines": 227,
+            "comment_lines": 0,
+            "blank_lines": 0,
+            "comment_ratio": 0.0,
+            "functions": 0,
+            "cyclomatic_complexity": 0,
+        },
+    }
+
+    @classmethod
+    def setUpClass(cls):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        cls.service = TokenizationService()
+
+    def measure(self, *names: str) -> dict:
+        collector = CodeMetricsCollector(self.service.tokenizer_config)
+        for name in names:
+            file_path = SAMPLES_DIRECTORY / name
+            content = file_path.read_text(encoding="utf-8")
+            collector.add(content, self.service.tokenize(content, file_path), self.service._detect_language(file_path))
+        return collector.to_dict()
+
+    def test_samples(self):
+        for name, expected in self.EXPECTED.items():
+            with self.subTest(name):
+                metrics = self.measure(name)
+                self.assertEqual({key: value for key, value in metrics.items() if key != "languages"}, expected)
+
+    def test_submission_of_several_samples(self):
+        metrics = self.measure(*self.EXPECTED)
+
+        self.assertEqual(metrics["files"], 5)
+        self.assertEqual(metrics["code_lines"], 171 + 123 + 37 + 21 + 227)
+        self.assertEqual(metrics["functions"], 16 + 13 + 3 + 1)
+        self.assertEqual(
+            {language: counts["files"] for language, counts in metrics["languages"].items()},
+            {"bash": 1, "c": 1, "json": 1, "python": 1, "rust": 1},
+        )
+        self.assertEqual(metrics["languages"]["rust"]["cyclomatic_complexity"], 45)
+
+    def test_directory_of_a_submission(self):
+        """The fetched files of a submission are measured as they are stored, unsupported files left out."""
+        with tempfile.TemporaryDirectory() as directory:
+            for name in self.EXPECTED:
+                shutil.copy(SAMPLES_DIRECTORY / name, Path(directory) / name)
+            (Path(directory) / "notes.bin").write_bytes(b"\x00\x01")
+            comparator = DirectoryComparator(self.service, FingerprintService(self.service), None, None)
+
+            self.assertEqual(comparator.measure(Path(directory)), self.measure(*self.EXPECTED))
+
+
+if __name__ == "__main__":
+    unittest.main()
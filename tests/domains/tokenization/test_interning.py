@@ -0,0 +1,90 @@
+"""
+Tests for interning of token texts
+"""
+
+import threading
+import unittest
+
+from app.domains.tokenization.interning import TokenInterner
+from tests.helpers import SAMPLES_DIRECTORY, TREE_SITTER_AVAILABLE
+
+
+class TestTokenInterner(unittest.TestCase):
+    """Tests for the shared token texts"""
+
+    def test_equal_texts_share_one_string(self):
+        """Texts decoded separately come back as the first string interned."""
+        interner = TokenInterner()
+        first = interner.intern(b"identifier".decode("utf-8"))
+
+        self.assertIs(interner.intern(b"identifier".decode("utf-8")), first)
+        self.assertEqual(len(interner), 1)
+
+    def test_interned_texts_are_bounded(self):
+        """Once the interned texts exceed max_bytes they are dropped, new texts are interned again."""
+        interner = TokenInterner(max_bytes=10)
+        kept = interner.intern("abcdef")
+        interner.intern("ghijkl")
+
+        self.assertEqual(len(interner), 0)
+        self.assertEqual(kept, "abcdef")
+        self.assertIsNot(interner.intern("".join(["abc", "def"])), kept)
+
+    def test_disabled_interner_returns_texts_unchanged(self):
+        """With a zero budget nothing is kept."""
+        interner = TokenInterner(max_bytes=0)
+        text = "".join(["to", "ken"])
+
+        self.assertIs(interner.intern(text), text)
+        self.assertEqual(len(interner), 0)
+
+    def test_concurrent_interning_converges(self):
+        """Workers interning the same texts all end up with the same strings."""
+        interner = TokenInterner()
+        results = [[] for _ in range(8)]
+
+        def work(result):
+            for index in range(500):
+                result.append(interner.intern(f"name_{index % 50}"))
+
+        threads = [threading.Thread(target=work, args=(result,)) for result in results]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+        self.assertEqual(len({id(text) for result in results for text in result}), 50)
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestInternedTokenization(unittest.TestCase):
+    """Tests for the token texts of the language samples"""
+
+    SUBMISSIONS = 10
+
+    def tokenize_corpus(self, interner: TokenInterner):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        service = TokenizationService()
+        service.interner = interner
+        files = service.extract_supported_files_from_directory(SAMPLES_DIRECTORY)
+        contents = [(file_path, file_path.read_text(encoding="utf-8", errors="replace")) for file_path in files]
+
+        # The same corpus submitted several times, as submissions sharing starter code
+        return [
+            service.tokenize(content, file_path) for _ in range(self.SUBMISSIONS) for file_path, content in contents
+        ]
+
+    def test_corpus_allocates_an_order_of_magnitude_fewer_texts(self):
+        """Tokens are unchanged while an order of magnitude fewer token texts stay allocated."""
+        plain_tokens = self.tokenize_corpus(TokenInterner(max_bytes=0))
+        interned_tokens = self.tokenize_corpus(TokenInterner())
+
+        self.assertEqual(interned_tokens, plain_tokens)
+        plain_texts = {id(token["text"]) for file_tokens in plain_tokens for token in file_tokens}
+        interned_texts = {id(token["text"]) for file_tokens in interned_tokens for token in file_tokens}
+        self.assertLessEqual(len(interned_texts) * self.SUBMISSIONS, len(plain_texts))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,137 @@
+"""
+Tests for the case-insensitive, longest-suffix and file name language detection
+"""
+
+import unittest
+from pathlib import Path
+
+from app.domains.tokenization.language_detection import (
+    EXTENSION_LANGUAGES,
+    FILENAME_LANGUAGES,
+    detect_language,
+    extension_candidates,
+)
+from tests.helpers import TREE_SITTER_AVAILABLE
+
+FIXTURE = Path(__file__).parents[3] / "resources" / "test" / "mixed_case_names"
+
+# Language of every file of the fixture, None for the files detection does not compare
+FIXTURE_LANGUAGES = {
+    "CMakeLists.txt": "cmake",
+    "Dockerfile": "dockerfile",
+    "Makefile": "make",
+    "README.MD": "markdown",
+    "backup.TAR.GZ": None,
+    "build.gradle.kts": "kotlin",
+    "notes.txt": None,
+    "script.PY": "python",
+    "src/Main.JAVA": "java",
+    "src/Util.C": "c",
+    "src/types/user.d.ts": "typescript",
+    "web/App.Tsx": "typescript",
+    "web/add.TS": "typescript",
+    "web/add.spec.ts": "typescript",
+}
+
+
+def case_variants(name: str):
+    return [name, name.lower(), name.upper(), name.title(), name.swapcase()]
+
+
+class TestLanguageTables(unittest.TestCase):
+    """Tests for every entry of the extension and file name tables"""
+
+    def test_tables_are_keyed_in_lowercase(self):
+        """Keys are lowercase, extensions start with their dot and file names never do."""
+        for extension in EXTENSION_LANGUAGES:
+            self.assertEqual(extension, extension.lower())
+            self.assertTrue(extension.startswith(".") and len(extension) > 1, extension)
+        for name in FILENAME_LANGUAGES:
+            self.assertEqual(name, name.lower())
+            self.assertFalse(name.startswith("."), name)
+
+    def test_every_extension_is_detected_in_any_case(self):
+        """Each extension gives its language whatever the case of the name."""
+        for extension, language in EXTENSION_LANGUAGES.items():
+            for name in case_variants(f"file{extension}"):
+                self.assertEqual(detect_language(name), language, name)
+
+    def test_every_file_name_is_detected_in_any_case(self):
+        """Each well-known file name gives its language whatever its case or directory."""
+        for file_name, language in FILENAME_LANGUAGES.items():
+            for name in case_variants(file_name):
+                self.assertEqual(detect_language(name), language, name)
+                self.assertEqual(detect_language(f"src/{name}"), language, name)
+
+    def test_compound_extensions_take_precedence(self):
+        """A compound extension wins over the extension it ends with."""
+        compound = [extension for extension in EXTENSION_LANGUAGES if extension.count(".") > 1]
+        self.assertIn(".d.ts", compound)
+        self.assertIn(".tar.gz", compound)
+        for extension in compound:
+            self.assertEqual(detect_language(f"name{extension}"), EXTENSION_LANGUAGES[extension], extension)
+
+
+class TestLanguageDetection(unittest.TestCase):
+    """Tests for the detection of unusual and unknown names"""
+
+    def test_longest_suffix_wins(self):
+        """Extensions are tried from the longest, unknown inner parts fall through to the last extension."""
+        self.assertEqual(list(extension_candidates("types.D.ts")), [".d.ts", ".ts"])
+        self.assertEqual(detect_language("jquery.min.js"), "javascript")
+        self.assertEqual(detect_language("my.module.py"), "python")
+        self.assertEqual(detect_language("archive.TAR.GZ"), None)
+
+    def test_declarations_can_be_excluded(self):
+        """Mapping .d.ts to None keeps declarations out while other TypeScript files stay detected."""
+        extensions = {**EXTENSION_LANGUAGES, ".d.ts": None}
+
+        self.assertIsNone(detect_language("index.d.ts", extensions))
+        self.assertEqual(detect_language("index.ts", extensions), "typescript")
+        self.assertEqual(detect_language("index.d.ts"), "typescript")
+
+    def test_names_without_a_known_language(self):
+        """Hidden files, names without extension and unknown extensions have no language."""
+        for name in [".bashrc", "README", "LICENSE", "notes.txt", "data.xyz", "", "trailing."]:
+            self.assertIsNone(detect_language(name), name)
+        self.assertEqual(detect_language(".eslintrc.json"), "json")
+        self.assertEqual(detect_language("src\\Main.Java"), "java")
+
+    def test_fixture_files_are_detected(self):
+        """Every file of the mixed-case fixture gets its language."""
+        files = sorted(path.relative_to(FIXTURE).as_posix() for path in FIXTURE.rglob("*") if path.is_file())
+
+        self.assertEqual(files, sorted(FIXTURE_LANGUAGES))
+        for name, language in FIXTURE_LANGUAGES.items():
+            self.assertEqual(detect_language(name), language, name)
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestTokenizationServiceDetection(unittest.TestCase):
+    """Tests the detection of the fixture through the tokenization service"""
+
+    @classmethod
+    def setUpClass(cls):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        cls.service = TokenizationService()
+
+    def test_supported_files_of_the_fixture(self):
+        """Files with a language are extracted and detected, the others are left out."""
+        files = self.service.extract_supported_files_from_directory(FIXTURE)
+
+        expected = {name for name, language in FIXTURE_LANGUAGES.items() if language}
+        self.assertEqual({path.relative_to(FIXTURE).as_posix() for path in files}, expected)
+        for path in files:
+            name = path.relative_to(FIXTURE).as_posix()
+            self.assertEqual(self.service._detect_language(path), FIXTURE_LANGUAGES[name], name)
+
+    def test_mixed_case_file_is_tokenized(self):
+        """A Java file with an uppercase extension is parsed by the Java grammar."""
+        tokens = self.service.tokenize_file(FIXTURE / "src" / "Main.JAVA")
+
+        self.assertIn("class_declaration", {token["type"] for token in tokens})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,169 @@
+"""
+Tests for the language registry: built-in languages, plugin registration and a plugin language through detection
+"""
+
+import sys
+import tempfile
+import textwrap
+import types
+import unittest
+from pathlib import Path
+
+from app.domains.detection.directory_comparison import DirectoryComparator
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.tokenization.languages import (
+    Language,
+    LanguageRegistrationError,
+    TreeSitterLanguage,
+    create_builtin_registry,
+    load_plugins,
+)
+from app.domains.tokenization.tokenization_service import TokenizationService
+from app.domains.tokenization.tokenizer_config import SUPPORTED_LANGUAGES, TokenizerConfigError, load_tokenizer_config
+from tests.helpers import SharedLinesVisualization
+
+
+def tokenize_toy(text: str) -> list:
+    """Words of each line, comment lines starting with a semicolon"""
+    tokens = []
+    for row, line in enumerate(text.split("\n")):
+        token_type = "comment" if line.startswith(";") else "identifier"
+        tokens.extend({"type": token_type, "text": word, "start": row, "end": row} for word in line.split())
+    return tokens
+
+
+class Toy(Language):
+    name = "toy"
+    label = "Toy"
+    extensions = (".toy",)
+    filenames = ("toyfile",)
+    stop_tokens = ("comment",)
+    generated_markers = ("; generated",)
+
+    def create_tokenizer(self):
+        return tokenize_toy
+
+    def is_generated(self, head: str) -> bool:
+        return head.startswith("; built by toyc")
+
+
+def toy_registry():
+    registry = create_builtin_registry()
+    registry.register(Toy())
+    return registry
+
+
+class TestLanguageRegistry(unittest.TestCase):
+    """Tests for the registration of languages"""
+
+    def test_builtin_languages(self):
+        """Every tree-sitter language is registered in the built-in registry, with its report label."""
+        registry = create_builtin_registry()
+
+        self.assertEqual(registry.names(), SUPPORTED_LANGUAGES)
+        self.assertTrue(all(isinstance(language, TreeSitterLanguage) for language in registry))
+        self.assertTrue(registry.is_builtin("python"))
+        self.assertEqual(registry.label("csharp"), "C#")
+        self.assertEqual(registry.label("zig"), "zig")
+
+    def test_invalid_languages_are_rejected(self):
+        """Taken or invalid names and plugin languages matching no file cannot be registered."""
+        registry = toy_registry()
+        nameless = type("Nameless", (Language,), {"name": "Toy Language", "extensions": (".tl",)})
+        fileless = type("Fileless", (Language,), {"name": "fileless"})
+
+        for language in (Toy(), nameless(), fileless(), TreeSitterLanguage("python", "Python")):
+            with self.assertRaises(LanguageRegistrationError):
+                registry.register(language)
+        self.assertEqual(len(registry), len(SUPPORTED_LANGUAGES) + 1)
+
+    def test_plugins_register_their_languages(self):
+        """Modules of the plugin list register through register_languages, others fail the startup."""
+        plugin = types.ModuleType("toy_language_plugin")
+        plugin.register_languages = lambda registry: registry.register(Toy())
+        self.addCleanup(sys.modules.pop, "toy_language_plugin", None)
+        sys.modules["toy_language_plugin"] = plugin
+        registry = create_builtin_registry()
+
+        load_plugins(registry, " toy_language_plugin, ")
+
+        self.assertEqual(registry.get("toy").label, "Toy")
+        with self.assertRaisesRegex(LanguageRegistrationError, "cannot be imported"):
+            load_plugins(registry, "missing_language_plugin")
+        with self.assertRaisesRegex(LanguageRegistrationError, "register_languages"):
+            load_plugins(registry, "textwrap")
+
+
+class TestPluginLanguage(unittest.TestCase):
+    """Tests for a plugin language through configuration, tokenization and detection"""
+
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        self.directory = Path(directory.name)
+        self.registry = toy_registry()
+        self.config = load_tokenizer_config(registry=self.registry)
+
+    def write(self, path: str, content: str) -> Path:
+        target = self.directory / path
+        target.parent.mkdir(parents=True, exist_ok=True)
+        target.write_text(textwrap.dedent(content), encoding="utf-8")
+        return target
+
+    def test_configuration(self):
+        """The table of a plugin language is configured like a built-in one and overridden by deployment files."""
+        toy = self.config.language("toy")
+        self.assertEqual((toy.extensions, toy.filenames), ((".toy",), ("toyfile",)))
+        self.assertEqual(toy.stop_tokens, frozenset({"comment"}))
+        self.assertEqual(self.config.extension_languages()[".toy"], "toy")
+
+        path = self.write("tokenizer.toml", '[languages.toy]\nstop_tokens = []\n')
+        self.assertEqual(load_tokenizer_config(str(path), self.registry).language("toy").stop_tokens, frozenset())
+        with self.assertRaises(TokenizerConfigError) as context:
+            load_tokenizer_config(str(path))
+        self.assertIn("languages.toy: unknown language", str(context.exception))
+
+        invalid = type("Invalid", (Toy,), {"name": "invalid", "extensions": ("inv",), "filenames": ()})
+        self.registry.register(invalid())
+        with self.assertRaisesRegex(TokenizerConfigError, "languages.invalid.extensions"):
+            load_tokenizer_config(registry=self.registry)
+
+    def test_end_to_end_detection(self):
+        """Files of the plugin language are detected, tokenized by its tokenizer and compared like any other."""
+        service = TokenizationService(tokenizer_config=self.config, language_registry=self.registry)
+        program = """
+            ; sum of the values
+            let total = 0
+            for value in values
+                total = total + value
+            print total
+        """
+        self.write("alice/main.toy", program)
+        self.write("alice/build.toy", "; generated by the build\nlet x = 1\n")
+        self.write("bob/src/main.toy", program.replace("sum of the values", "add them up"))
+        self.write("bob/out.toy", "; built by toyc 1.0\nlet total = 0\n")
+
+        tokens = service.tokenize(program, Path("main.toy"))
+        self.assertTrue(tokens)
+        self.assertNotIn("comment", {token["type"] for token in tokens})
+        self.assertEqual(service._detect_language(Path("Toyfile")), "toy")
+
+        comparator = DirectoryComparator(
+            service,
+            FingerprintService(service, k=3, window=2, tokenizer_config=self.config),
+            SimilarityDetectionService(),
+            SharedLinesVisualization(),
+            language="toy",
+        )
+        results = comparator.compare(self.directory / "alice", self.directory / "bob")
+
+        details = results["similarity_details"]
+        self.assertEqual(details["files_tokens"], {"submission1": {"main.toy": 15}, "submission2": {"src/main.toy": 15}})
+        self.assertEqual(details["fingerprint_similarity"], 1.0)
+        self.assertGreater(results["overall_similarity"], 0.9)
+        self.assertEqual(results["visualization_data"][0]["file_pair"]["file_from_submission2"], "src/main.toy")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,140 @@
+"""
+Tests for the normalization of line endings before hashing, tokenization and line numbering
+"""
+
+import re
+import tempfile
+import unittest
+from pathlib import Path
+
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.fingerprints.fingerprinting import content_hash
+from app.domains.reports.highlighting import highlight_lines
+from app.domains.tokenization.streaming_source import StreamingSource, decode_source, normalize_newlines
+from app.shared.content_hash import HashAlgorithm
+from tests.helpers import TREE_SITTER_AVAILABLE
+
+# Two functions on lines 0-1 and 4-5, the last line without a newline
+LF_SOURCE = "def add(a, b):\n    return a + b\n\n\ndef sub(a, b):\n    return a - b\n\nprint(add(1, sub(3, 2)))"
+CRLF_SOURCE = LF_SOURCE.replace("\n", "\r\n")
+# CRLF, LF and lone CR in turn, as left by editors on different systems, no CR directly followed by LF
+MIXED_SOURCE = "".join(
+    line + ("\r\n", "\n", "\r")[number % 3] for number, line in enumerate(LF_SOURCE.split("\n")[:-1])
+) + LF_SOURCE.split("\n")[-1]
+VARIANTS = {"crlf": CRLF_SOURCE, "mixed": MIXED_SOURCE}
+WORD = re.compile(r"\w+|[^\w\s]")
+
+
+class LineTokenizer:
+    """Tokenization service double numbering token rows by LF only, like tree-sitter does"""
+
+    def _detect_language(self, file_path=None, content=None):
+        return "python"
+
+    def tokenize(self, text, file_path=None):
+        tokens = []
+        for match in WORD.finditer(text):
+            row = text.count("\n", 0, match.start())
+            tokens.append({"type": "word", "text": match.group(), "start": row, "end": row})
+        return tokens
+
+    def tokenize_source(self, source, file_path=None):
+        return self.tokenize(source[0 : len(source)].decode("utf-8"), file_path)
+
+
+class TestLineEndingNormalization(unittest.TestCase):
+    """Tests that CRLF and mixed line endings hash, fingerprint and number lines like LF"""
+
+    def setUp(self):
+        self.temp_dir = tempfile.TemporaryDirectory()
+        self.service = FingerprintService(LineTokenizer(), InMemoryFingerprintStore(), k=3, window=2)
+
+    def tearDown(self):
+        self.temp_dir.cleanup()
+
+    def test_variants_decode_to_the_lf_text(self):
+        """Decoded in memory or streamed in small chunks, every variant is the LF text."""
+        self.assertRegex(MIXED_SOURCE, "\r[^\n]")
+        for name, source in VARIANTS.items():
+            path = Path(self.temp_dir.name) / f"{name}.py"
+            path.write_bytes(source.encode("utf-8"))
+
+            self.assertEqual(decode_source(source.encode("utf-8")), LF_SOURCE, name)
+            self.assertEqual(normalize_newlines(source), LF_SOURCE, name)
+            with StreamingSource(path, chunk_size=3) as streamed:
+                self.assertEqual(b"".join(streamed.chunks()), LF_SOURCE.encode("utf-8"), name)
+
+    def test_variants_have_the_content_hash_of_the_lf_text(self):
+        """The content hash, in memory or streamed, is computed after normalization."""
+        expected = content_hash(LF_SOURCE, HashAlgorithm.SHA256)
+        for name, source in VARIANTS.items():
+            path = Path(self.temp_dir.name) / f"{name}.py"
+            path.write_bytes(source.encode("utf-8"))
+
+            self.assertEqual(content_hash(source, HashAlgorithm.SHA256), expected, name)
+            with StreamingSource(path) as streamed:
+                self.assertEqual(streamed.hash(HashAlgorithm.SHA256), expected, name)
+
+    def test_variants_share_the_fingerprints_and_token_rows_of_the_lf_text(self):
+        """Every variant hits the cache entry of the LF text, with the same fingerprints and token rows."""
+        expected = self.service.get_fingerprints(LF_SOURCE, Path("main.py"))
+        for name, source in VARIANTS.items():
+            stats = self.service.new_stats()
+            fingerprint_set = self.service.get_fingerprints(source, Path("main.py"), stats)
+
+            self.assertEqual(stats.hits, 1, name)
+            self.assertEqual(fingerprint_set.fingerprints, expected.fingerprints, name)
+
+        uncached = FingerprintService(LineTokenizer(), None, k=3, window=2)
+        rows = [(t["text"], t["start"]) for t in expected.tokens]
+        for name, source in VARIANTS.items():
+            tokens = uncached.get_fingerprints(source, Path("main.py")).tokens
+            self.assertEqual([(t["text"], t["start"]) for t in tokens], rows, name)
+        self.assertEqual({t["start"] for t in expected.tokens if t["text"] == "def"}, {0, 4})
+        self.assertEqual(expected.tokens[-1]["start"], 7)
+
+    def test_highlighted_lines_follow_the_lf_lines(self):
+        """The pair view highlights as many lines, on the same numbers, as the LF text has."""
+        expected = highlight_lines(LF_SOURCE, "main.py")
+        for name, source in VARIANTS.items():
+            self.assertEqual(highlight_lines(source, "main.py"), expected, name)
+        self.assertEqual(len(expected), 8)
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestLineEndingTokenization(unittest.TestCase):
+    """Tests that tree-sitter numbers the lines of every variant like those of the LF text"""
+
+    @classmethod
+    def setUpClass(cls):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        cls.service = TokenizationService()
+
+    def spans(self, source):
+        functions = self.service.extract_functions_with_positions(source, Path("main.py"))
+        return sorted((f["function_name"], f["start_line"], f["end_line"]) for f in functions.values())
+
+    def test_function_spans_match_the_lf_text(self):
+        """Fragment line spans come from function positions, which are the same for every variant."""
+        expected = self.spans(LF_SOURCE)
+
+        self.assertEqual(expected, [("add", 0, 1), ("sub", 4, 5)])
+        for name, source in VARIANTS.items():
+            self.assertEqual(self.spans(source), expected, name)
+
+    def test_token_rows_match_the_lf_text(self):
+        """Tokens of every variant, in memory or streamed from disk, are on the rows of the LF tokens."""
+        expected = self.service.tokenize(LF_SOURCE, Path("main.py"))
+        with tempfile.TemporaryDirectory() as directory:
+            for name, source in VARIANTS.items():
+                path = Path(directory) / "main.py"
+                path.write_bytes(source.encode("utf-8"))
+
+                self.assertEqual(self.service.tokenize(source, path), expected, name)
+                self.assertEqual(self.service.tokenize_file(path, chunk_size=5), expected, name)
+
+
+if __name__ == "__main__":
+    unittest.main()
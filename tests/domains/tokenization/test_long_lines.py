@@ -0,0 +1,230 @@
+"""
+Tests for the bounded line indexing, excerpts and byte-offset fragments of files with extremely long lines
+"""
+
+import re
+import tempfile
+import tracemalloc
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import uuid4
+
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.runs.dto.run_response_dto import DetectionFragmentDto
+from app.domains.runs.pair_view import highlight_blocks
+from app.domains.runs.run_recorder import extract_fragments
+from app.domains.runs.runs_models import FragmentType
+from app.domains.tokenization.line_index import (
+    DEFAULT_LONG_LINE_THRESHOLD,
+    TRUNCATION_MARKER,
+    LineIndex,
+    clamp_excerpt,
+    has_long_lines,
+)
+from tests.helpers import TREE_SITTER_AVAILABLE
+
+MB = 1024 * 1024
+BUDGET = 4096
+# Excerpt budget of shared blocks, below the size of a bundle function
+BLOCK_BUDGET = 256
+FUNCTION = re.compile(rb"function (\w+)\([^)]*\)\{[^}]*\}")
+WORD = re.compile(r"\w+|[^\w\s]")
+
+
+def minified_bundle(size: int) -> str:
+    """A generated single-line JavaScript bundle of about size bytes, flat functions of about 600 bytes each"""
+    body = ";".join(f"c=c*{n}+a-b;d=d+c%{n + 7}" for n in range(20))
+    functions = []
+    total = number = 0
+    while total < size:
+        function = f"function f{number}(a,b){{var c=a+b,d=0;{body};return c+d}}"
+        functions.append(function)
+        total += len(function) + 1
+        number += 1
+    return "!function(){" + ";".join(functions) + "}();"
+
+
+BUNDLE = minified_bundle(5 * MB)
+
+
+class BundleTokenizer:
+    """Tokenization service double locating the first functions of a bundle by byte offsets, as on long lines"""
+
+    def extract_functions_with_positions(self, text, file_path=None):
+        source = text.encode("utf-8")
+        functions = {}
+        for match in list(FUNCTION.finditer(source, 0, 10000))[:3]:
+            name = match.group(1).decode()
+            functions[f"{name}_0"] = {
+                "function_name": name,
+                "start_line": 0,
+                "end_line": 0,
+                "code_block": match.group().decode(),
+                "node_type": "function_declaration",
+                "language": "javascript",
+                "start_byte": match.start(),
+                "end_byte": match.end(),
+            }
+        return functions
+
+    def tokenize(self, text, file_path=None):
+        return [{"type": "identifier", "text": word, "start": 0, "end": 0} for word in WORD.findall(text)]
+
+
+class TestLineIndex(unittest.TestCase):
+    """Tests for the lazy and capped index of line starts"""
+
+    def test_lines_match_split_lines(self):
+        """Lines are those of a split at LF, past the cap of indexed lines too."""
+        for text in ["a\nb\nc", "a\n", "", "x\n\ny\n", "one line"]:
+            lines = text.split("\n")
+            for max_lines in (1, 2, 100):
+                index = LineIndex(text, max_lines)
+                for start in range(-1, 5):
+                    for end in range(0, 6):
+                        self.assertEqual(index.lines(start, end), "\n".join(lines[max(start, 0) : end]), text)
+
+    def test_index_of_a_single_line_file_stays_small(self):
+        """Indexing a 5 MB single-line file holds a couple of offsets, whatever line is asked for."""
+        index = LineIndex(BUNDLE)
+        tracemalloc.start()
+        try:
+            self.assertEqual(index.line_start(0), 0)
+            self.assertIsNone(index.line_start(1))
+            self.assertIsNone(index.line_start(10**6))
+            _, peak = tracemalloc.get_traced_memory()
+        finally:
+            tracemalloc.stop()
+
+        self.assertLess(peak, 64 * 1024)
+        self.assertEqual(len(index._starts), 1)
+
+    def test_cap_bounds_indexed_lines(self):
+        """Lines past the cap are found without growing the index."""
+        text = "\n".join(str(number) for number in range(1000))
+        index = LineIndex(text, max_lines=10)
+
+        self.assertEqual(index.lines(500, 502), "500\n501")
+        self.assertEqual(len(index._starts), 10)
+
+
+class TestExcerpts(unittest.TestCase):
+    """Tests for excerpts cut to a byte budget"""
+
+    def test_long_excerpt_is_cut_with_a_marker(self):
+        """An excerpt of the bundle fits the budget, marker included, and says how much was cut."""
+        excerpt = clamp_excerpt(BUNDLE, BUDGET)
+
+        self.assertLessEqual(len(excerpt.encode("utf-8")), BUDGET)
+        self.assertTrue(BUNDLE.startswith(excerpt[: excerpt.index("\n[...")]))
+        omitted = len(BUNDLE) - excerpt.index("\n[...")
+        self.assertTrue(excerpt.endswith(TRUNCATION_MARKER.format(omitted=omitted)))
+
+    def test_multi_byte_characters_are_never_split(self):
+        """Cutting inside a multi-byte character keeps the characters before it only."""
+        excerpt = clamp_excerpt("é" * 10000, 100)
+
+        self.assertLessEqual(len(excerpt.encode("utf-8")), 100)
+        self.assertRegex(excerpt, r"^é+\n\[\.\.\. \d+ more characters truncated\]$")
+
+    def test_excerpts_within_the_budget_are_unchanged(self):
+        """Short excerpts, and any excerpt with a budget of 0, are returned whole."""
+        self.assertEqual(clamp_excerpt("x = 1\n", BUDGET), "x = 1\n")
+        self.assertEqual(clamp_excerpt("é" * BUDGET, BUDGET * 2), "é" * BUDGET)
+        self.assertEqual(clamp_excerpt(BUNDLE, 0), BUNDLE)
+
+    def test_bundle_has_long_lines(self):
+        """Files whose average line exceeds the threshold are reported by byte offsets."""
+        self.assertTrue(has_long_lines(BUNDLE))
+        self.assertFalse(has_long_lines("x = 1\n" * 1000))
+        self.assertFalse(has_long_lines(BUNDLE, 0))
+        self.assertFalse(has_long_lines("y" * DEFAULT_LONG_LINE_THRESHOLD))
+
+
+class TestByteOffsetFragments(unittest.TestCase):
+    """Tests that blocks of a single-line bundle are located by byte offsets with bounded excerpts"""
+
+    def setUp(self):
+        self.service = SimilarityDetectionService(excerpt_max_bytes=BLOCK_BUDGET)
+        result = self.service.detect_shared_code_blocks(
+            BUNDLE, BUNDLE, "a.min.js", "b.min.js", Path("a.min.js"), Path("b.min.js"), BundleTokenizer()
+        )
+        self.blocks = result["shared_blocks"]
+
+    def test_shared_blocks_carry_byte_offsets_and_clamped_code(self):
+        """Single-line functions long enough in bytes are compared, each side cut to the excerpt budget."""
+        self.assertEqual(len(self.blocks), 9)
+        for block in self.blocks:
+            self.assertEqual((block["file1_start_line"], block["file1_end_line"]), (0, 0))
+            self.assertLess(block["file1_start_byte"], block["file1_end_byte"])
+            self.assertLessEqual(len(block["file1_code_block"].encode("utf-8")), BLOCK_BUDGET)
+            self.assertIn("more characters truncated]", block["file2_code_block"])
+
+    def test_fragments_and_pair_view_stay_bounded(self):
+        """Recorded fragments keep the byte offsets and the pair view highlights the excerpt of each side only."""
+        fragments = extract_fragments(
+            [
+                {
+                    "file_pair": {"file_from_submission1": "a.min.js", "file_from_submission2": "b.min.js"},
+                    "react_flow": {"shared_blocks": self.blocks},
+                }
+            ]
+        )
+        blocks = [f for f in fragments if f["fragment_type"] == FragmentType.BLOCK]
+        self.assertEqual(blocks[0]["details"]["file1_start_byte"], self.blocks[0]["file1_start_byte"])
+
+        dtos = [DetectionFragmentDto(id=uuid4(), fragment_type=f["fragment_type"].value, **_fields(f)) for f in blocks]
+        pair = SimpleNamespace(submission_id=uuid4(), compared_submission_id=uuid4())
+        tracemalloc.start()
+        try:
+            highlight_blocks(pair, dtos, lambda submission_id, path: BUNDLE, BUDGET)
+            _, peak = tracemalloc.get_traced_memory()
+        finally:
+            tracemalloc.stop()
+
+        self.assertLess(peak, 3 * len(BUNDLE))
+        for dto in dtos:
+            for code in (dto.file1_code, dto.file2_code):
+                self.assertEqual([line.number for line in code.lines], [1])
+                self.assertTrue(code.lines[0].matched)
+                self.assertLess(len(code.lines[0].html), 4 * BUDGET)
+
+
+def _fields(fragment: dict) -> dict:
+    return {key: value for key, value in fragment.items() if key != "fragment_type"}
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestLongLineTokenization(unittest.TestCase):
+    """Tests that tree-sitter locates the functions of a single-line bundle by byte offsets"""
+
+    @classmethod
+    def setUpClass(cls):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        cls.service = TokenizationService()
+
+    def test_functions_are_located_by_byte_offsets(self):
+        """Each function code block is its own bytes, not the whole 5 MB line."""
+        functions = self.service.extract_functions_with_positions(BUNDLE, Path("bundle.min.js"))
+        source = BUNDLE.encode("utf-8")
+
+        self.assertGreater(len(functions), 1000)
+        for function in functions.values():
+            self.assertEqual(function["start_line"], 0)
+            self.assertEqual(function["code_block"], source[function["start_byte"] : function["end_byte"]].decode())
+        inner = [f for f in functions.values() if f["function_name"].startswith("f")]
+        self.assertLess(max(len(f["code_block"]) for f in inner), 1024)
+
+    def test_tokenization_of_the_bundle_streams(self):
+        """The bundle streamed from disk tokenizes like its text in memory."""
+        with tempfile.TemporaryDirectory() as directory:
+            path = Path(directory) / "bundle.min.js"
+            path.write_text(BUNDLE, encoding="utf-8")
+
+            self.assertEqual(self.service.tokenize_file(path, chunk_size=4096), self.service.tokenize(BUNDLE, path))
+
+
+if __name__ == "__main__":
+    unittest.main()
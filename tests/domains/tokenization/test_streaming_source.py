@@ -0,0 +1,164 @@
+"""
+Tests for streaming tokenization of large files
+"""
+
+import hashlib
+import sys
+import tempfile
+import tracemalloc
+import unittest
+from pathlib import Path
+
+from app.domains.tokenization.streaming_source import StreamingSource
+from app.shared.content_hash import HashAlgorithm
+from tests.helpers import TREE_SITTER_AVAILABLE
+
+MB = 1024 * 1024
+
+
+def read_in_memory(path: Path) -> str:
+    """The in-memory path: text mode read, UTF-8 first then latin-1"""
+    try:
+        return path.read_text(encoding="utf-8")
+    except UnicodeDecodeError:
+        return path.read_text(encoding="latin-1")
+
+
+def generate_sql(path: Path, size: int) -> None:
+    """Write a generated SQL dump of about size bytes, with CRLF line endings and multi-byte literals"""
+    statement = "INSERT INTO étudiants (id, nom, note) VALUES ({0}, 'Zoë “{0}” 😀', {1});\r\n"
+    with open(path, "wb") as file:
+        written = index = 0
+        while written < size:
+            line = "".join(statement.format(index + offset, (index + offset) % 20) for offset in range(1000))
+            data = line.encode("utf-8")
+            file.write(data)
+            written += len(data)
+            index += 1000
+
+
+class TestStreamingSource(unittest.TestCase):
+    """Tests for the chunked decoding of source files"""
+
+    def setUp(self):
+        self.temp_dir = tempfile.TemporaryDirectory()
+        self.path = Path(self.temp_dir.name) / "source.sql"
+
+    def tearDown(self):
+        self.temp_dir.cleanup()
+
+    def assert_same_as_in_memory(self, data: bytes, chunk_sizes=range(1, 12)):
+        self.path.write_bytes(data)
+        expected = read_in_memory(self.path).encode("utf-8")
+        for chunk_size in chunk_sizes:
+            with StreamingSource(self.path, chunk_size) as source:
+                self.assertEqual(b"".join(source.chunks()), expected, f"chunk size {chunk_size}")
+                self.assertEqual(source[0 : len(source)], expected)
+
+    def test_multi_byte_sequences_split_across_chunks(self):
+        """Characters of 2, 3 and 4 bytes decode identically wherever the chunk boundary falls."""
+        self.assert_same_as_in_memory("é€😀 x = 'ñ' -- ☃\n".encode("utf-8") * 5)
+
+    def test_crlf_split_across_chunks(self):
+        """CRLF and lone CR become LF even when CR ends a chunk and LF starts the next one."""
+        self.assert_same_as_in_memory(b"a\r\nbb\r\n\r\nccc\rdddd\r\r\n" * 5)
+        self.assert_same_as_in_memory(b"trailing\r")
+
+    def test_invalid_utf8_falls_back_to_latin1(self):
+        """A file that is not valid UTF-8 is decoded as latin-1, like the in-memory path."""
+        self.path.write_bytes(b"SELECT 1;\r\n" * 100 + b"-- caf\xe9\r\n")
+
+        with StreamingSource(self.path, chunk_size=64) as source:
+            self.assertEqual(source.encoding, "latin-1")
+
+        self.assert_same_as_in_memory(self.path.read_bytes(), chunk_sizes=[1, 7, 64])
+
+    def test_byte_order_mark_is_stripped(self):
+        """The BOM is left out of the content and recorded, whatever the chunk size."""
+        self.path.write_bytes(b"\xef\xbb\xbfselect 1;\n")
+
+        for chunk_size in [1, 2, 3]:
+            with StreamingSource(self.path, chunk_size=chunk_size) as source:
+                self.assertEqual(b"".join(source.chunks()), b"select 1;\n", chunk_size)
+                self.assertEqual(source.bom, "utf-8")
+
+    def test_slices_match_in_memory_slices(self):
+        """Node texts sliced by byte offsets are the bytes of the in-memory source."""
+        data = "select 'é' from t;\r\n".encode("utf-8") * 200
+        self.path.write_bytes(data)
+        expected = read_in_memory(self.path).encode("utf-8")
+
+        with StreamingSource(self.path, chunk_size=13) as source:
+            for start, stop in [(0, 5), (7, 9), (100, 1000), (len(expected) - 3, len(expected) + 10), (50, 10)]:
+                self.assertEqual(source[start:stop], expected[start:stop])
+
+    def test_hash_matches_in_memory_content_hash(self):
+        """The streamed content hashes like its in-memory text, so fingerprint cache keys are shared."""
+        self.path.write_bytes("é\r\n".encode("utf-8") * 1000)
+
+        with StreamingSource(self.path, chunk_size=100) as source:
+            digest = source.hash(HashAlgorithm.SHA256)
+
+        self.assertEqual(digest, hashlib.sha256(read_in_memory(self.path).encode("utf-8")).hexdigest())
+
+    def test_large_file_is_decoded_under_memory_ceiling(self):
+        """Decoding and hashing a 50 MB file never allocates more than a few chunks."""
+        generate_sql(self.path, 50 * MB)
+
+        tracemalloc.start()
+        try:
+            with StreamingSource(self.path) as source:
+                source.hash(HashAlgorithm.SHA256)
+                size = len(source)
+            _, peak = tracemalloc.get_traced_memory()
+        finally:
+            tracemalloc.stop()
+
+        self.assertGreater(size, 48 * MB)
+        self.assertLess(peak, 2 * MB)
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestStreamingTokenization(unittest.TestCase):
+    """Tests for tokenizing a large file from disk"""
+
+    @classmethod
+    def setUpClass(cls):
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        cls.service = TokenizationService()
+        cls.temp_dir = tempfile.TemporaryDirectory()
+        cls.path = Path(cls.temp_dir.name) / "dump.sql"
+        generate_sql(cls.path, 50 * MB)
+
+    @classmethod
+    def tearDownClass(cls):
+        cls.temp_dir.cleanup()
+
+    def measure(self, tokenize):
+        tracemalloc.start()
+        try:
+            tokens = tokenize()
+            _, peak = tracemalloc.get_traced_memory()
+        finally:
+            tracemalloc.stop()
+        return tokens, peak
+
+    def test_streamed_tokens_equal_in_memory_tokens(self):
+        """A 50 MB file yields the in-memory token stream while staying under the memory ceiling."""
+        size = self.path.stat().st_size
+        streamed, streamed_peak = self.measure(lambda: self.service.tokenize_file(self.path))
+        in_memory, in_memory_peak = self.measure(lambda: self.service.tokenize(read_in_memory(self.path), self.path))
+
+        self.assertTrue(streamed)
+        self.assertEqual(streamed, in_memory)
+        # Token texts include the root node, the whole file once: the ceiling is that output, the bytes of the
+        # largest node while its text is decoded, and buffers
+        token_output = sum(sys.getsizeof(token) + sys.getsizeof(token["text"]) for token in streamed)
+        largest_node = max(len(token["text"].encode("utf-8")) for token in streamed)
+        self.assertLess(streamed_peak, token_output + largest_node + 32 * MB)
+        self.assertLess(streamed_peak, in_memory_peak - size)
+
+
+if __name__ == "__main__":
+    unittest.main()
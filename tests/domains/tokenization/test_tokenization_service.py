@@ -432,7 +432,7 @@ def outer():
         mock_run.return_value = MagicMock(returncode=0)
         # Test would require actual implementation details
 
-    @patch('app.domains.tokenization.tokenization_service.get_parser')
+    @patch('app.domains.tokenization.languages.TreeSitterLanguage.grammar')
     def test_setup_parsers_failure(self, mock_get_parser):
         """Test parser setup handles failures gracefully."""
         mock_get_parser.side_effect = Exception("Parser initialization failed")
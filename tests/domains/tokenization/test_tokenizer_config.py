@@ -0,0 +1,280 @@
+"""
+Tests for the per-language tokenizer configuration: deployment files merged over the built-in one and validation
+"""
+
+import tempfile
+import textwrap
+import unittest
+from pathlib import Path
+
+from app.domains.fingerprints.fingerprint_models import NormalizationLevel
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprinting import compute_fingerprints
+from app.domains.tokenization.tokenizer_config import (
+    BUILTIN_CONFIG_PATH,
+    SUPPORTED_LANGUAGES,
+    TokenizerConfigError,
+    builtin_tokenizer_config,
+    load_tokenizer_config,
+)
+from tests.helpers import TREE_SITTER_AVAILABLE
+
+
+class TokenizerConfigTestCase(unittest.TestCase):
+    """Base class writing deployment files to a temporary directory"""
+
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        self.directory = Path(directory.name)
+
+    def write(self, content: str, name: str = "tokenizer.toml") -> str:
+        path = self.directory / name
+        path.write_text(textwrap.dedent(content), encoding="utf-8")
+        return str(path)
+
+    def load(self, content: str):
+        return load_tokenizer_config(self.write(content))
+
+    def errors(self, content: str) -> list:
+        path = self.write(content)
+        with self.assertRaises(TokenizerConfigError) as context:
+            load_tokenizer_config(path)
+        self.assertIn(path, str(context.exception))
+        return context.exception.errors
+
+
+class TestBuiltinConfig(TokenizerConfigTestCase):
+    """Tests for the configuration shipped with the service"""
+
+    def test_every_supported_language_is_configured(self):
+        """Each grammar has a configuration, the languages keep the tokenizer version their fingerprints have."""
+        config = builtin_tokenizer_config()
+
+        self.assertEqual(set(config.languages), set(SUPPORTED_LANGUAGES))
+        self.assertEqual(config.sources, (str(BUILTIN_CONFIG_PATH),))
+        self.assertIsNone(config.revision)
+        for language in SUPPORTED_LANGUAGES:
+            self.assertEqual(config.tokenizer_version(language, "1"), "1", language)
+        self.assertEqual(config.extension_languages()[".d.ts"], "typescript")
+        self.assertIsNone(config.extension_languages()[".tar.gz"])
+
+    def test_token_classes(self):
+        """Types are classified by the first class with a marker in the lowercase type, other without any."""
+        config = builtin_tokenizer_config()
+
+        self.assertEqual(config.token_class("identifier"), "identifier")
+        self.assertEqual(config.token_class("String_Literal"), "literal")
+        self.assertEqual(config.token_class("line_comment"), "comment")
+        self.assertEqual(config.token_class("if_statement"), "other")
+
+    def test_generated_markers(self):
+        """Files are generated when one of the first lines of the file holds a marker of its language."""
+        python = builtin_tokenizer_config().language("python")
+        migration = "# Generated by Django 4.2 on 2024-01-15 10:30\n\nfrom django.db import migrations\n"
+
+        self.assertTrue(python.is_generated(migration))
+        self.assertFalse(builtin_tokenizer_config().language("java").is_generated(migration))
+        self.assertFalse(python.is_generated("import os\n" * 5 + "# @generated\n"))
+
+
+class TestOverrideMerging(TokenizerConfigTestCase):
+    """Tests for a deployment file merged over the built-in configuration"""
+
+    def test_deployment_file_overrides_the_builtin_defaults(self):
+        """Tables are merged key by key, lists and values of the deployment file replace the built-in ones."""
+        config = self.load(
+            """
+            ignored_extensions = [".zip", ".min.js"]
+
+            [defaults]
+            stop_tokens = ["comment"]
+
+            [languages.python]
+            extensions = [".py", ".py3"]
+            normalization = "types"
+
+            [languages.python.token_weights]
+            literal = 0
+            """
+        )
+
+        python = config.language("python")
+        self.assertEqual(python.extensions, (".py", ".py3"))
+        self.assertEqual(python.stop_tokens, frozenset({"comment"}))
+        self.assertEqual(python.normalization, "types")
+        self.assertEqual(python.token_weights, {"identifier": 1, "literal": 0, "comment": 1, "other": 1})
+        # Built-in values the file does not set are kept, per language and in the defaults
+        self.assertIn("Generated by Django", python.generated_markers)
+        self.assertEqual(python.generated_marker_lines, 5)
+        self.assertEqual(config.language("java").stop_tokens, frozenset({"comment"}))
+        self.assertEqual(config.language("java").extensions, (".java", ".jsp"))
+        self.assertEqual(config.token_classes["identifier"], ("identifier", "name"))
+
+        extensions = config.extension_languages()
+        self.assertEqual(extensions[".py3"], "python")
+        self.assertNotIn(".pyi", extensions)
+        self.assertIsNone(extensions[".min.js"])
+        self.assertNotIn(".tar.gz", extensions)
+        self.assertEqual(config.sources[-1], str(self.directory / "tokenizer.toml"))
+
+    def test_only_the_languages_configured_otherwise_change_version(self):
+        """Fingerprints of a language are keyed by a new version once its tokens or weights differ."""
+        config = self.load(
+            """
+            [languages.python]
+            stop_tokens = ["comment"]
+            generated_markers = []
+            """
+        )
+
+        self.assertRegex(config.tokenizer_version("python", "1"), r"^1\+[0-9a-f]{8}$")
+        self.assertEqual(config.tokenizer_version("java", "1"), "1")
+        self.assertIsNotNone(config.revision)
+        # Generated markers leave files out but change no token
+        markers_only = self.load("[languages.python]\ngenerated_markers = []\n")
+        self.assertEqual(markers_only.tokenizer_version("python", "1"), "1")
+        self.assertIsNone(markers_only.revision)
+
+    def test_weights_and_normalization_apply_to_fingerprints(self):
+        """A weight of 0 leaves a class out of the k-grams, the key carries the version and normalization."""
+        config = self.load(
+            """
+            [languages.python]
+            normalization = "none"
+
+            [languages.python.token_weights]
+            literal = 0
+            """
+        )
+        code = [{"type": "identifier", "text": "total"}, {"type": "operator", "text": "+"}]
+        first = code + [{"type": "string", "text": "'a'"}] + code
+        second = code + [{"type": "number", "text": "42"}] + code
+
+        weighted = [
+            compute_fingerprints(tokens, 2, 2, NormalizationLevel.NONE, tokenizer_config=config, language="python")
+            for tokens in (first, second)
+        ]
+        unweighted = [compute_fingerprints(tokens, 2, 2, NormalizationLevel.NONE) for tokens in (first, second)]
+
+        self.assertEqual([h for h, _ in weighted[0]], [h for h, _ in weighted[1]])
+        self.assertNotEqual([h for h, _ in unweighted[0]], [h for h, _ in unweighted[1]])
+        self.assertTrue(all(first[position]["type"] != "string" for _, position in weighted[0]))
+
+        tokenizer = type("Tokenizer", (), {"_detect_language": lambda self, file_path=None: "python"})()
+        key = FingerprintService(tokenizer, tokenizer_config=config).build_key("x = 1", Path("a.py"))
+        self.assertEqual(key.tokenizer_version, config.tokenizer_version("python", "1"))
+        self.assertEqual(key.normalization, "none")
+
+
+class TestValidation(TokenizerConfigTestCase):
+    """Tests for the errors of invalid deployment files, located by their dotted key"""
+
+    def test_unknown_language(self):
+        """A misspelt language fails with the closest supported one."""
+        self.assertEqual(
+            self.errors("[languages.pyhton]\nstop_tokens = []\n"),
+            ["languages.pyhton: unknown language, did you mean 'python'?"],
+        )
+
+    def test_unknown_token_classes(self):
+        """Token classes of markers and weights must be known, other takes no markers."""
+        errors = self.errors(
+            """
+            [token_classes]
+            keywords = ["keyword"]
+            other = ["operator"]
+
+            [languages.java.token_weights]
+            identifiers = 2
+            """
+        )
+
+        self.assertEqual(
+            errors,
+            [
+                "token_classes.keywords: unknown token class, expected one of identifier, literal, comment",
+                "token_classes.other: is the class of the token types matching no marker, it takes no markers",
+                "languages.java.token_weights.identifiers: unknown token class, did you mean 'identifier'?",
+            ],
+        )
+
+    def test_unknown_keys_and_invalid_values(self):
+        """Every mistake of a file is reported at once, each with its key."""
+        errors = self.errors(
+            """
+            ignored_extension = [".zip"]
+
+            [defaults]
+            extensions = [".txt"]
+            stop_tokens = "comment"
+            generated_marker_lines = 0
+
+            [languages.go]
+            normalization = "identifier"
+            filenames = ["Makefile.go"]
+            extensions = ["go"]
+
+            [languages.go.token_weights]
+            literal = -1
+            """
+        )
+
+        self.assertEqual(
+            errors,
+            [
+                "ignored_extension: unknown key, did you mean 'ignored_extensions'?",
+                "defaults.extensions: is only set per language, in a [languages.<name>] table",
+                "defaults.stop_tokens: must be a list of non-empty strings",
+                "defaults.generated_marker_lines: must be an integer of at least 1, got 0",
+                "languages.go.extensions: 'go' must be a lowercase extension starting with its dot",
+                "languages.go.filenames: 'Makefile.go' must be a lowercase file name, extensions go in extensions",
+                "languages.go.normalization: unknown normalization 'identifier', did you mean 'identifiers'?",
+                "languages.go.token_weights.literal: must be an integer of at least 0, got -1",
+            ],
+        )
+
+    def test_syntax_errors_give_their_line(self):
+        """A file that is not valid TOML fails with the line and column of the mistake."""
+        (error,) = self.errors("[defaults]\nstop_tokens = [comment]\n")
+
+        self.assertIn("line 2", error)
+
+    def test_extension_given_to_two_languages(self):
+        """An extension added to a language without being removed from another fails after the merge."""
+        errors = self.errors('[languages.cpp]\nextensions = [".cpp", ".h"]\n')
+
+        self.assertEqual(errors, ["languages.cpp.extensions: '.h' is also mapped to c"])
+
+    def test_missing_file(self):
+        """A deployment path that does not exist fails instead of falling back to the built-in configuration."""
+        path = str(self.directory / "missing.toml")
+        with self.assertRaises(TokenizerConfigError) as context:
+            load_tokenizer_config(path)
+
+        self.assertEqual(context.exception.errors, ["file not found"])
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestConfiguredTokenization(TokenizerConfigTestCase):
+    """Tests for stop tokens and generated files through the tokenization service"""
+
+    def test_stop_tokens_and_generated_files(self):
+        """Stop token types are left out of the tokens, files with a generated marker out of the extracted files."""
+        from app.domains.tokenization.tokenization_service import TokenizationService
+
+        config = self.load('[languages.python]\nstop_tokens = ["comment"]\ngenerated_markers = ["# autogen"]\n')
+        service = TokenizationService(tokenizer_config=config)
+        source = "# total of the values\ntotal = 1 + 2\n"
+        (self.directory / "main.py").write_text(source, encoding="utf-8")
+        (self.directory / "schema.py").write_text("# autogen: do not edit\n" + source, encoding="utf-8")
+
+        types = {token["type"] for token in service.tokenize(source, Path("main.py"))}
+        self.assertNotIn("comment", types)
+        self.assertIn("comment", {token["type"] for token in TokenizationService().tokenize(source, Path("a.py"))})
+        files = service.extract_supported_files_from_directory(self.directory)
+        self.assertEqual([path.name for path in files], ["main.py"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,251 @@
+"""
+Doubles and fixtures shared by the test modules
+"""
+
+import importlib.util
+import os
+import re
+import shutil
+import tempfile
+from pathlib import Path
+from types import SimpleNamespace
+from typing import Dict, Optional
+from uuid import uuid4
+
+from sqlmodel import create_engine
+from sqlmodel.pool import StaticPool
+
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+from app.domains.tokenization.interning import TokenInterner
+from app.domains.tokenization.languages import create_builtin_registry
+from app.domains.tokenization.tokenization_service import TokenizationService
+from app.domains.tokenization.tokenizer_config import builtin_tokenizer_config
+
+TREE_SITTER_AVAILABLE = importlib.util.find_spec("tree_sitter_language_pack") is not None
+
+SAMPLES_DIRECTORY = Path(__file__).parents[1] / "resources" / "test" / "language_samples"
+
+# Python source spaced so that each of its words is a token of WordTokenizer
+SOURCE = "def main ( ) :\n    total = 0\n    for value in values :\n        total = total + value\n"
+
+RECURSION = "maximum recursion depth exceeded"
+
+TOKEN_PATTERN = re.compile(r"[A-Za-z_]\w*|\d+(?:\.\d+)?|\"[^\"\n]*\"|'[^'\n]*'|[^\w\s]")
+REGEX_KEYWORDS = {"def", "return", "class", "if", "else", "for", "while", "function", "int", "void", "import", "public"}
+
+
+def get_test_database_url() -> Optional[str]:
+    """TEST_DATABASE_URL, None to fall back to SQLite; required in CI, whose PostgreSQL service must be tested"""
+    database_url = os.environ.get("TEST_DATABASE_URL")
+    if not database_url and os.environ.get("CI"):
+        raise RuntimeError("TEST_DATABASE_URL is not set, CI runs the database tests against PostgreSQL")
+    return database_url
+
+
+def create_test_engine(isolated: bool = False):
+    """
+    Engine of TEST_DATABASE_URL when set (e.g. a throwaway PostgreSQL database), in-memory SQLite otherwise;
+    isolated for a second database beside it, always in-memory SQLite
+    """
+    database_url = None if isolated else get_test_database_url()
+    if database_url:
+        return create_engine(database_url)
+    return create_engine("sqlite://", connect_args={"check_same_thread": False}, poolclass=StaticPool)
+
+
+class WordTokenizer:
+    """Tokenization service double, one token per whitespace separated word with its line"""
+
+    def __init__(self, keywords=()):
+        self.keywords = set(keywords)
+
+    def _detect_language(self, file_path=None, content=None):
+        return "python"
+
+    def is_supported_file(self, file_path: Path) -> bool:
+        return file_path.suffix == ".py"
+
+    def tokenize(self, text, file_path=None, raise_errors=False):
+        tokens = []
+        for line, words in enumerate(text.splitlines()):
+            for word in words.split():
+                token_type = "keyword" if word in self.keywords else "identifier" if word.isidentifier() else "operator"
+                tokens.append({"type": token_type, "text": word, "start": line, "end": line})
+        return tokens
+
+
+class CountingTokenizer(WordTokenizer):
+    """Tokenization service double counting tokenize calls, one token per word, languages by file extension"""
+
+    LANGUAGES = {".py": "python", ".js": "javascript"}
+
+    def __init__(self):
+        super().__init__()
+        self.calls = 0
+
+    def _detect_language(self, file_path=None, content=None):
+        return self.LANGUAGES.get(file_path.suffix if file_path else "", "python")
+
+    def tokenize(self, text, file_path=None, raise_errors=False):
+        self.calls += 1
+        return super().tokenize(text, file_path, raise_errors)
+
+    def tokenize_source(self, source, file_path=None):
+        return self.tokenize(source[0 : len(source)].decode("utf-8"), file_path)
+
+
+class FailingTokenizer(WordTokenizer):
+    """Tokenization service double, one token per word, failing on files named corrupt like deeply nested sources"""
+
+    def __init__(self, error=None):
+        super().__init__()
+        self.error = error or RecursionError(RECURSION)
+
+    def tokenize(self, text, file_path=None, raise_errors=False):
+        if file_path is not None and file_path.stem == "corrupt":
+            if raise_errors:
+                raise self.error
+            return []
+        return super().tokenize(text, file_path, raise_errors)
+
+
+class RegexTokenizer:
+    """Tokenization service double producing tree-sitter like tokens, texts interned like the real service"""
+
+    def __init__(self):
+        self.interner = TokenInterner()
+
+    def _detect_language(self, file_path=None, content=None):
+        return file_path.suffix.lstrip(".") if file_path else "text"
+
+    @staticmethod
+    def _type(text: str) -> str:
+        if text in REGEX_KEYWORDS:
+            return text
+        if text[0].isalpha() or text[0] == "_":
+            return "identifier"
+        if text[0].isdigit():
+            return "number"
+        if text[0] in "\"'":
+            return "string"
+        return text
+
+    def tokenize(self, text, file_path=None):
+        tokens = []
+        for line_number, line in enumerate(text.split("\n")):
+            for match in TOKEN_PATTERN.finditer(line):
+                token_text = self.interner.intern(match.group())
+                tokens.append(
+                    {"type": self._type(token_text), "text": token_text, "start": line_number, "end": line_number}
+                )
+        return tokens
+
+
+class PythonFiles:
+    """Tokenization service double supporting Python files, listed like the tokenization service lists them"""
+
+    extract_supported_files_from_directory = TokenizationService.extract_supported_files_from_directory
+
+    def is_supported_file(self, file_path: Path) -> bool:
+        return file_path.suffix == ".py"
+
+    def is_generated_file(self, file_path: Path) -> bool:
+        return False
+
+
+def tokenization_service() -> TokenizationService:
+    """Tokenization service classifying files by the built-in configuration, without any parser"""
+    service = TokenizationService.__new__(TokenizationService)
+    service.tokenizer_config = builtin_tokenizer_config()
+    service.language_registry = create_builtin_registry()
+    service._setup_language_mapping()
+    return service
+
+
+class RecordingSubmissions:
+    """
+    Mixin of the ingestion handler doubles creating no submission: records every request, raises the queued errors
+    in turn and records the submissions created otherwise
+    """
+
+    def __init__(self, *args, errors=(), **kwargs):
+        super().__init__(*args, **kwargs)
+        self.errors = list(errors)
+        self.requests = []
+        self.submissions = []
+
+    def create_submission(self, request):
+        self.requests.append(request)
+        if self.errors:
+            raise self.errors.pop(0)
+        self.submissions.append(request)
+        return SimpleNamespace(submission_id=uuid4())
+
+
+class SharedLinesVisualization:
+    """Visualization service double sharing one block per identical line, scored by its share of the file"""
+
+    def generate_react_flow_ast(self, content1, content2, file1_name, file2_name, layout):
+        lines2 = content2.splitlines()
+        blocks = [
+            {
+                "file1_start_line": number,
+                "file1_end_line": number,
+                "file2_start_line": lines2.index(line),
+                "file2_end_line": lines2.index(line),
+                "similarity_score": len(line) / (len(content1) + len(content2)) * 3,
+            }
+            for number, line in enumerate(content1.splitlines())
+            if line.strip() and line in lines2
+        ]
+        if not blocks:
+            return {"has_similarity": False}
+        scores = [block["similarity_score"] for block in blocks]
+        return {
+            "has_similarity": True,
+            "analysis_metadata": {"average_similarity": sum(scores) / len(scores), "total_similarities": len(blocks)},
+            "shared_blocks": blocks,
+        }
+
+
+class FailingVisualization:
+    """Visualization service double sharing the whole file, failing on files named broken"""
+
+    def generate_react_flow_ast(self, content1, content2, file1_name, file2_name, layout):
+        if Path(file1_name).stem == "broken":
+            raise ValueError("unbalanced block")
+        return {
+            "has_similarity": True,
+            "analysis_metadata": {"average_similarity": 1.0, "total_similarities": 1},
+            "shared_blocks": [{"file1_start_line": 0, "file1_end_line": 3, "file2_start_line": 0, "file2_end_line": 3}],
+        }
+
+
+def write_submissions(root: Path, submissions: Dict[object, Dict[str, str]]) -> None:
+    """Write the files of each submission under root, in a directory named after its ID"""
+    for submission_id, files in submissions.items():
+        for path, content in files.items():
+            target = root / str(submission_id) / path
+            target.parent.mkdir(parents=True, exist_ok=True)
+            target.write_text(content)
+
+
+def detection_service(
+    root: Path, fingerprint_service, visualization_service, similarity_service=None
+) -> DetectionIntegrationService:
+    """Detection service without database, fetching the submissions written under root by write_submissions"""
+    service = DetectionIntegrationService.__new__(DetectionIntegrationService)
+    service.tokenization_service = PythonFiles()
+    service.fingerprint_service = fingerprint_service
+    service.similarity_service = similarity_service or SimilarityDetectionService()
+    service.visualization_service = visualization_service
+
+    def fetch(submission, submission_data):
+        # Comparisons clean up the fetched files, each gets its own copy
+        copy = Path(tempfile.mkdtemp()) / "repository"
+        shutil.copytree(root / str(submission.id), copy)
+        return copy
+
+    service._fetch_submission_files = fetch
+    return service
@@ -0,0 +1,419 @@
+"""
+Tests for worker pool sizing and the concurrency caps of background jobs
+"""
+
+import os
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+from unittest.mock import patch
+
+from app.config.config import Settings
+from app.domains.fingerprints.fingerprint_factory import create_fingerprint_service
+from app.shared import services
+from app.shared.concurrency import JobPriority, JobScheduler, available_cpus, cgroup_cpu_limit
+
+POOL_VARIABLES = (
+    "TOKENIZATION_WORKERS",
+    "DETECTION_COMPARISON_WORKERS",
+    "DETECTION_MAX_CONCURRENT_JOBS",
+    "INGESTION_MAX_CONCURRENT_JOBS",
+)
+
+
+class TestAvailableCpus(unittest.TestCase):
+    """Tests for the cgroup-aware CPU count"""
+
+    def setUp(self):
+        self.temp_dir = tempfile.TemporaryDirectory()
+        self.cgroup_root = Path(self.temp_dir.name)
+
+    def tearDown(self):
+        self.temp_dir.cleanup()
+
+    def test_cgroup_v2_quota(self):
+        """cpu.max quotas are read, an unlimited quota is no limit."""
+        (self.cgroup_root / "cpu.max").write_text("150000 100000\n")
+        self.assertEqual(cgroup_cpu_limit(self.cgroup_root), 1.5)
+
+        (self.cgroup_root / "cpu.max").write_text("max 100000\n")
+        self.assertIsNone(cgroup_cpu_limit(self.cgroup_root))
+
+    def test_cgroup_v1_quota(self):
+        """CFS quotas are read, -1 is no limit."""
+        (self.cgroup_root / "cpu").mkdir()
+        (self.cgroup_root / "cpu" / "cpu.cfs_period_us").write_text("100000\n")
+        (self.cgroup_root / "cpu" / "cpu.cfs_quota_us").write_text("400000\n")
+        self.assertEqual(cgroup_cpu_limit(self.cgroup_root), 4)
+
+        (self.cgroup_root / "cpu" / "cpu.cfs_quota_us").write_text("-1\n")
+        self.assertIsNone(cgroup_cpu_limit(self.cgroup_root))
+
+    def test_quota_caps_the_cpus_of_the_host(self):
+        """A 2 CPU limit on a 64 core host gives 2 CPUs, rounded up from fractional quotas."""
+        (self.cgroup_root / "cpu.max").write_text("150000 100000\n")
+
+        with patch("os.sched_getaffinity", return_value=set(range(64)), create=True):
+            self.assertEqual(available_cpus(self.cgroup_root), 2)
+            self.assertEqual(available_cpus(self.cgroup_root / "missing"), 64)
+
+    def test_cgroup_default_is_used_without_configuration(self):
+        """Without the variables every pool is sized from the cgroup limit."""
+        (self.cgroup_root / "cpu.max").write_text("200000 100000\n")
+        environment = {name: value for name, value in os.environ.items() if name not in POOL_VARIABLES}
+
+        with (
+            patch.dict(os.environ, environment, clear=True),
+            patch("app.shared.concurrency.CGROUP_ROOT", self.cgroup_root),
+            patch("os.sched_getaffinity", return_value=set(range(64)), create=True),
+        ):
+            settings = Settings(fingerprint_cache_enabled=False)
+            fingerprint_service = create_fingerprint_service(settings, tokenization_service=None)
+            with patch("app.config.config.get_settings", return_value=settings):
+                services.cleanup_services()
+                detection_jobs = services.get_detection_scheduler().max_concurrent
+                ingestion_jobs = services.get_ingestion_scheduler().max_concurrent
+                services.cleanup_services()
+
+        self.assertEqual(settings.tokenization_workers, 0)
+        self.assertEqual(fingerprint_service.workers, 2)
+        self.assertEqual(detection_jobs, 1)
+        self.assertEqual(ingestion_jobs, 2)
+
+
+class TestJobScheduler(unittest.TestCase):
+    """Tests for the cap on concurrent jobs"""
+
+    def run_jobs(self, scheduler: JobScheduler, count: int = 6):
+        lock = threading.Lock()
+        running, started, peak = [0], [], [0]
+
+        def job(index):
+            with lock:
+                running[0] += 1
+                peak[0] = max(peak[0], running[0])
+                started.append(index)
+            time.sleep(0.02)
+            with lock:
+                running[0] -= 1
+            return index
+
+        futures = [scheduler.submit(job, index) for index in range(count)]
+        return [future.result(timeout=5) for future in futures], started, peak[0]
+
+    def test_cap_of_one_serializes_jobs(self):
+        """With a cap of 1 jobs run one at a time, in submission order, none rejected."""
+        scheduler = JobScheduler("test", 1)
+        try:
+            results, started, peak = self.run_jobs(scheduler)
+        finally:
+            scheduler.shutdown(wait=True)
+
+        self.assertEqual(results, list(range(6)))
+        self.assertEqual(started, list(range(6)))
+        self.assertEqual(peak, 1)
+        self.assertEqual((scheduler.queued, scheduler.running), (0, 0))
+
+    def test_higher_cap_runs_jobs_concurrently(self):
+        """Jobs beyond the cap wait, up to the cap run together."""
+        scheduler = JobScheduler("test", 3)
+        try:
+            _, _, peak = self.run_jobs(scheduler)
+        finally:
+            scheduler.shutdown(wait=True)
+
+        self.assertEqual(peak, 3)
+
+    def test_blocking_runs_queue_behind_the_cap(self):
+        """Callers of run() wait for a free slot instead of failing."""
+        scheduler = JobScheduler("test", 1)
+        lock = threading.Lock()
+        active, peak = [0], [0]
+
+        def ingest():
+            with lock:
+                active[0] += 1
+                peak[0] = max(peak[0], active[0])
+            time.sleep(0.01)
+            with lock:
+                active[0] -= 1
+            return "stored"
+
+        results = []
+        callers = [threading.Thread(target=lambda: results.append(scheduler.run(ingest))) for _ in range(4)]
+        for caller in callers:
+            caller.start()
+        for caller in callers:
+            caller.join()
+        scheduler.shutdown(wait=True)
+
+        self.assertEqual(results, ["stored"] * 4)
+        self.assertEqual(peak[0], 1)
+
+    def test_resize_changes_the_cap(self):
+        """Queued jobs start once the cap is raised, a lowered cap lets the running jobs finish."""
+        scheduler = JobScheduler("test", 1)
+        self.addCleanup(scheduler.shutdown, wait=True)
+        release = threading.Event()
+        futures = [scheduler.submit(release.wait, 5) for _ in range(3)]
+        time.sleep(0.02)
+        self.assertEqual((scheduler.running, scheduler.queued), (1, 2))
+
+        scheduler.resize(3)
+        deadline = time.monotonic() + 5
+        while scheduler.running < 3 and time.monotonic() < deadline:
+            time.sleep(0.001)
+        self.assertEqual((scheduler.running, scheduler.queued), (3, 0))
+
+        scheduler.resize(1)
+        release.set()
+        self.assertEqual([future.result(timeout=5) for future in futures], [True] * 3)
+        _, _, peak = self.run_jobs(scheduler)
+        self.assertEqual(peak, 1)
+
+
+class TestJobSchedulerDrain(unittest.TestCase):
+    """Tests for draining the jobs of a scheduler on shutdown"""
+
+    def test_queued_jobs_are_dropped_and_running_ones_finish(self):
+        """Queued jobs never start and are reported by ID, the running job finishes within the grace period."""
+        scheduler = JobScheduler("test", 1)
+        started, release = threading.Event(), threading.Event()
+
+        def job(name):
+            started.set()
+            release.wait(5)
+            return name
+
+        running = scheduler.submit(job, "running", job_id="running")
+        started.wait(5)
+        queued = [scheduler.submit(job, name, job_id=name) for name in ("first", "second")]
+
+        threading.Timer(0.05, release.set).start()
+        dropped = scheduler.drain(grace_seconds=5)
+
+        self.assertEqual(dropped, ["first", "second"])
+        self.assertEqual(running.result(timeout=1), "running")
+        self.assertTrue(all(future.cancelled() for future in queued))
+        self.assertFalse(scheduler.interrupted.is_set())
+        self.assertEqual((scheduler.queued, scheduler.running), (0, 0))
+        with self.assertRaises(RuntimeError):
+            scheduler.submit(job, "late")
+
+    def test_jobs_still_running_after_the_grace_period_are_interrupted(self):
+        """Once the grace period is over jobs are interrupted, and get the interrupt timeout to checkpoint."""
+        scheduler = JobScheduler("test", 2)
+        checkpoints = []
+
+        def job(name):
+            scheduler.interrupted.wait(5)
+            checkpoints.append(name)
+            return name
+
+        futures = [scheduler.submit(job, name) for name in ("first", "second")]
+        while scheduler.running < 2:
+            time.sleep(0.01)
+        started = time.monotonic()
+        scheduler.drain(grace_seconds=0.05, interrupt_timeout_seconds=5)
+
+        self.assertLess(time.monotonic() - started, 4)
+        self.assertTrue(scheduler.interrupted.is_set())
+        self.assertEqual(sorted(checkpoints), ["first", "second"])
+        self.assertEqual([future.result(timeout=1) for future in futures], ["first", "second"])
+
+
+class FakeClock:
+    """Clock of the scheduler advanced by hand"""
+
+    def __init__(self):
+        self.now = 0.0
+
+    def __call__(self) -> float:
+        return self.now
+
+
+class TestJobPriorities(unittest.TestCase):
+    """Tests for the priority queue of the scheduler and the aging of waiting jobs"""
+
+    def setUp(self):
+        self.clock = FakeClock()
+        self.scheduler = JobScheduler("test", 1, aging_seconds=10, clock=self.clock)
+        self.release = threading.Event()
+        self.started = []
+        blocking = threading.Event()
+
+        def block():
+            blocking.set()
+            self.release.wait(5)
+
+        # Holds the only slot so the following jobs queue until released
+        self.scheduler.submit(block)
+        blocking.wait(5)
+
+    def tearDown(self):
+        self.release.set()
+        self.scheduler.shutdown(wait=True)
+
+    def enqueue(self, name: str, priority: JobPriority):
+        return self.scheduler.submit(self.started.append, name, priority=priority, job_id=name)
+
+    def run_queue(self, futures) -> list:
+        self.release.set()
+        for future in futures:
+            future.result(timeout=5)
+        return self.started
+
+    def test_mixed_priorities_run_highest_first(self):
+        """Queued jobs start by priority, in submission order within a priority."""
+        jobs = [
+            ("low-1", JobPriority.LOW),
+            ("normal-1", JobPriority.NORMAL),
+            ("high-1", JobPriority.HIGH),
+            ("normal-2", JobPriority.NORMAL),
+            ("urgent-1", JobPriority.URGENT),
+            ("low-2", JobPriority.LOW),
+            ("high-2", JobPriority.HIGH),
+        ]
+        futures = [self.enqueue(name, priority) for name, priority in jobs]
+
+        self.assertEqual(
+            self.run_queue(futures), ["urgent-1", "high-1", "high-2", "normal-1", "normal-2", "low-1", "low-2"]
+        )
+
+    def test_waiting_jobs_are_promoted_by_aging(self):
+        """A low job gains a level per aging period and overtakes later jobs of the level it reached."""
+        futures = [self.enqueue("low", JobPriority.LOW)]
+        self.clock.now = 15
+        futures.append(self.enqueue("normal", JobPriority.NORMAL))
+
+        status = self.scheduler.status("low")
+        self.assertEqual((status.priority, status.effective_priority), (JobPriority.LOW, JobPriority.NORMAL))
+        self.assertEqual((status.position, status.waiting_seconds), (1, 15))
+        self.assertEqual(self.scheduler.status("normal").position, 2)
+
+        self.clock.now = 22
+        futures.append(self.enqueue("high", JobPriority.HIGH))
+        self.assertEqual(self.scheduler.status("low").effective_priority, JobPriority.HIGH)
+
+        self.assertEqual(self.run_queue(futures), ["low", "high", "normal"])
+
+    def test_aging_never_reaches_urgent(self):
+        """However long a job waits, an urgent job still starts first."""
+        futures = [self.enqueue("low", JobPriority.LOW)]
+        self.clock.now = 1000
+        futures.append(self.enqueue("urgent", JobPriority.URGENT))
+
+        self.assertEqual(self.scheduler.status("low").effective_priority, JobPriority.HIGH)
+        self.assertEqual(self.scheduler.status("low").position, 2)
+        self.assertEqual(self.run_queue(futures), ["urgent", "low"])
+
+    def test_started_jobs_have_no_queue_status(self):
+        """Only waiting jobs report a queue position."""
+        future = self.enqueue("normal", JobPriority.NORMAL)
+        self.assertIsNotNone(self.scheduler.status("normal"))
+
+        self.run_queue([future])
+
+        self.assertIsNone(self.scheduler.status("normal"))
+        self.assertIsNone(self.scheduler.status("unknown"))
+        self.assertEqual((self.scheduler.queued, self.scheduler.running), (0, 0))
+
+
+class TestFairScheduling(unittest.TestCase):
+    """Tests for the cap on the running jobs of a group and the turns the groups take"""
+
+    def setUp(self):
+        self.clock = FakeClock()
+        self.scheduler = JobScheduler("test", 1, aging_seconds=10, clock=self.clock, max_per_group=1)
+        self.release = threading.Event()
+        self.started = []
+        blocking = threading.Event()
+
+        def block():
+            blocking.set()
+            self.release.wait(5)
+
+        # An ungrouped job holds the only slot so the following jobs queue until released
+        self.scheduler.submit(block)
+        blocking.wait(5)
+
+    def tearDown(self):
+        self.release.set()
+        self.scheduler.shutdown(wait=True)
+
+    def enqueue(self, name: str, priority: JobPriority = JobPriority.NORMAL):
+        return self.scheduler.submit(self.started.append, name, priority=priority, job_id=name, group=name[0])
+
+    def run_queue(self, futures) -> list:
+        self.release.set()
+        for future in futures:
+            future.result(timeout=5)
+        return self.started
+
+    def test_projects_take_turns(self):
+        """A project queuing many jobs alternates with one queuing a few, which then leaves it the queue."""
+        futures = [self.enqueue(f"A{index}") for index in range(1, 7)]
+        futures += [self.enqueue(f"B{index}") for index in range(1, 3)]
+
+        order = ["A1", "B1", "A2", "B2", "A3", "A4", "A5", "A6"]
+        self.assertEqual([self.scheduler.status(name).position for name in order], list(range(1, 9)))
+        self.assertEqual(self.run_queue(futures), order)
+
+    def test_turns_are_kept_within_a_priority(self):
+        """A higher priority still starts first, and aging lifts a waiting job into the turns of its new level."""
+        futures = [self.enqueue("B1", JobPriority.LOW)]
+        self.clock.now = 5
+        futures += [self.enqueue(f"A{index}") for index in range(1, 4)]
+        self.assertEqual(self.scheduler.status("B1").position, 4)
+
+        self.clock.now = 10
+        self.assertEqual(self.scheduler.status("B1").effective_priority, JobPriority.NORMAL)
+        futures += [self.enqueue("B2"), self.enqueue("C1", JobPriority.HIGH)]
+
+        order = ["C1", "B1", "A1", "B2", "A2", "A3"]
+        self.assertEqual([self.scheduler.status(name).position for name in order], list(range(1, 7)))
+        self.assertEqual(self.run_queue(futures), order)
+
+    def test_a_project_at_its_cap_leaves_the_free_slots_to_others(self):
+        """Past its cap the jobs of a project wait for one of them to finish, even with free slots."""
+        self.release.set()
+        scheduler = JobScheduler("capped", 3, max_per_group=1)
+        self.addCleanup(scheduler.shutdown, wait=True)
+        finish_a, finish_b = threading.Event(), threading.Event()
+        b_started = threading.Event()
+        lock = threading.Lock()
+        running = {"A": 0, "B": 0}
+        peak = {"A": 0, "B": 0}
+        started = []
+
+        def job(name, until):
+            with lock:
+                running[name[0]] += 1
+                peak[name[0]] = max(peak[name[0]], running[name[0]])
+                started.append(name)
+            if name == "B1":
+                b_started.set()
+            until.wait(5)
+            with lock:
+                running[name[0]] -= 1
+
+        futures = [scheduler.submit(job, "A1", finish_a, group="A")]
+        futures.append(scheduler.submit(job, "A2", finish_a, job_id="A2", group="A"))
+        futures.append(scheduler.submit(job, "B1", finish_b, group="B"))
+
+        self.assertTrue(b_started.wait(5))
+        self.assertEqual(started, ["A1", "B1"])
+        self.assertEqual(scheduler.status("A2").position, 1)
+
+        finish_a.set()
+        finish_b.set()
+        for future in futures:
+            future.result(timeout=5)
+        self.assertEqual(peak["A"], 1)
+        self.assertEqual(started, ["A1", "B1", "A2"])
+
+
+if __name__ == "__main__":
+    unittest.main()
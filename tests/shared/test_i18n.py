@@ -0,0 +1,143 @@
+"""
+Tests for the localization of messages and error details
+"""
+
+import asyncio
+import json
+import string
+import unittest
+from types import SimpleNamespace
+from unittest.mock import patch
+
+from fastapi.exceptions import RequestValidationError
+from starlette.exceptions import HTTPException as StarletteHTTPException
+
+from app.shared.i18n import Locale, localize_detail, negotiate_locale, translate
+from app.shared.messages import ENGLISH, FRENCH
+from app.shared.tracing import install_error_handlers
+
+RULE_FAILURE = {
+    "code": "rulesFailed",
+    "validation_failed": True,
+    "failed_rule_count": 1,
+    "total_rule_count": 2,
+    "errors": [
+        {
+            "code": "missingRequiredFiles",
+            "missing_files": ["README.md", "Makefile"],
+            "message": "Missing required files: README.md, Makefile",
+            "rule_name": "file_presence",
+        },
+        {"code": "someFutureCode", "message": "Not translated yet"},
+    ],
+    "summary": "Submission validation failed: 1 of 2 rules failed",
+}
+
+
+HUMAN_READABLE = ("message", "summary", "msg")
+
+
+def without_messages(detail):
+    """Error details without their human-readable fields"""
+    if isinstance(detail, list):
+        return [without_messages(item) for item in detail]
+    if isinstance(detail, dict):
+        return {key: without_messages(value) for key, value in detail.items() if key not in HUMAN_READABLE}
+    return detail
+
+
+class TestTranslate(unittest.TestCase):
+    """Tests for the lookup and interpolation of messages"""
+
+    def test_missing_translations_fall_back_to_english(self):
+        """A message missing in French is the English one, then the default, then the message ID."""
+        with patch.dict(FRENCH):
+            del FRENCH["report.omitted_pairs"]
+            self.assertEqual(
+                translate("report.omitted_pairs", Locale.FR, count=2),
+                "2 less similar flagged pairs are not included in this report.",
+            )
+        self.assertEqual(translate("csv.pair_id", Locale.EN, default="pair_id"), "pair_id")
+        self.assertEqual(translate("report.unknown", Locale.FR), "report.unknown")
+
+    def test_interpolation_only_fills_plain_names(self):
+        """Values are formatted by name, lookups and missing values are left as written instead of failing."""
+        self.assertEqual(translate("report.flagged_pairs", Locale.FR, count=3), "Paires signalées (3)")
+        self.assertEqual(translate("report.flagged_at_value", threshold="high"), "high overall similarity or more")
+        self.assertEqual(translate("report.lines", path="{first}.py", first=1), "{first}.py, lines 1 to {last}")
+        self.assertEqual(
+            translate("missing.lookup", default="{value.__class__} {values[0]}", value=1, values=[2]),
+            "{value.__class__} {values[0]}",
+        )
+
+    def test_translations_use_values_of_their_message(self):
+        """French messages are never empty and only use the fields of their English message when there is one."""
+        for message_id, template in FRENCH.items():
+            self.assertNotEqual(template.strip(), "", message_id)
+            if message_id in ENGLISH:
+                fields = {name for _, name, _, _ in string.Formatter().parse(template) if name}
+                english = {name for _, name, _, _ in string.Formatter().parse(ENGLISH[message_id]) if name}
+                self.assertLessEqual(fields, english, message_id)
+
+    def test_locale_negotiation(self):
+        """The locale parameter wins, then the preferred supported language of Accept-Language, then English."""
+        self.assertEqual(negotiate_locale("fr-CA,fr;q=0.9,en;q=0.8"), Locale.FR)
+        self.assertEqual(negotiate_locale("de-DE, en;q=0.5, fr;q=0.7"), Locale.FR)
+        self.assertEqual(negotiate_locale("fr;q=0, de"), Locale.EN)
+        self.assertEqual(negotiate_locale("fr", requested="en"), Locale.EN)
+        self.assertEqual(negotiate_locale(None, requested="FR_be"), Locale.FR)
+        self.assertEqual(negotiate_locale("*;q=0.9, fr;q=0.1", requested="klingon"), Locale.EN)
+        self.assertEqual(negotiate_locale("fr;q=abc"), Locale.EN)
+
+
+class TestLocalizedErrors(unittest.TestCase):
+    """Tests for the error details translated to the locale of the request"""
+
+    def respond(self, exception_class, error, **request):
+        app = SimpleNamespace(handlers={})
+        app.add_exception_handler = lambda exception, handler: app.handlers.__setitem__(exception, handler)
+        install_error_handlers(app)
+        request = SimpleNamespace(
+            scope={"request_id": "client-42"},
+            method="POST",
+            url=SimpleNamespace(path="/submissions"),
+            headers=request.get("headers", {}),
+            query_params=request.get("query_params", {}),
+        )
+        return json.loads(asyncio.run(app.handlers[exception_class](request, error)).body)["detail"]
+
+    def test_codes_stay_and_messages_are_translated(self):
+        """French details keep every field and code, only the messages of translated codes change."""
+        error = StarletteHTTPException(422, RULE_FAILURE)
+
+        english = self.respond(StarletteHTTPException, error, headers={"accept-language": "en-GB"})
+        french = self.respond(StarletteHTTPException, error, headers={"accept-language": "fr-FR,fr;q=0.9"})
+
+        self.assertEqual(english, RULE_FAILURE)
+        self.assertEqual(set(french), set(english))
+        self.assertEqual(french["summary"], "Échec de la validation du rendu : 1 règles sur 2 non respectées")
+        self.assertEqual(french["errors"][0]["message"], "Fichiers obligatoires manquants : README.md, Makefile")
+        self.assertEqual(french["errors"][1]["message"], "Not translated yet")
+        self.assertEqual(without_messages(french), without_messages(english))
+
+    def test_validation_errors_and_unexpected_errors(self):
+        """Validation errors keep their type and context with a French msg, unexpected errors are translated."""
+        errors = [
+            {"type": "greater_than_equal", "loc": ["query", "k"], "msg": "Input should be >= 1", "ctx": {"ge": 1}},
+            {"type": "some_new_type", "loc": ["body"], "msg": "Something"},
+        ]
+
+        detail = self.respond(RequestValidationError, RequestValidationError(errors), query_params={"locale": "fr"})
+
+        self.assertEqual(detail[0]["msg"], "La valeur doit être supérieure ou égale à 1")
+        self.assertEqual(without_messages(detail), without_messages(errors))
+        self.assertEqual(detail[1]["msg"], "Something")
+        self.assertEqual(
+            self.respond(Exception, RuntimeError("boom"), headers={"accept-language": "fr"}),
+            "Erreur interne du serveur",
+        )
+        self.assertEqual(localize_detail("Submission not found", Locale.FR), "Submission not found")
+
+
+if __name__ == "__main__":
+    unittest.main()
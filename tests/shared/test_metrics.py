@@ -0,0 +1,266 @@
+"""
+Tests for the Prometheus metrics of the service, their exposition and the instrumentation of a small run
+"""
+
+import asyncio
+import re
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from uuid import UUID, uuid4
+
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from app.domains.fingerprints.fingerprint_store import InMemoryFingerprintStore
+from app.domains.health.metrics_router import metrics
+from app.domains.runs.run_recorder import DetectionRunRecorder
+from app.domains.storage.exceptions import StorageException
+from app.domains.storage.memory_submission_store import InMemorySubmissionStore
+from app.domains.storage.submission_storage_service import SubmissionStorageService
+from app.domains.submissions.submissions_models import SimilarityStatus
+from app.domains.submissions.submissions_service import SubmissionService
+from app.shared.exceptions import ValidationException
+from app.shared.metrics import CONTENT_TYPE, Counter, Gauge, Histogram, MetricsMiddleware, MetricsRegistry
+from app.shared.services import get_detection_scheduler
+from tests.helpers import SOURCE, WordTokenizer, detection_service, write_submissions
+
+SAMPLE = re.compile(r'^([a-zA-Z_:][a-zA-Z0-9_:]*)(?:\{(.*)\})? (\S+)$')
+LABEL = re.compile(r'([a-zA-Z_][a-zA-Z0-9_]*)="((?:[^"\\]|\\.)*)"')
+
+
+def parse(text: str):
+    """Types of the metric families and values of the samples by name and labels of an exposition"""
+    types, samples = {}, {}
+    for line in text.splitlines():
+        if line.startswith("# TYPE "):
+            _, _, name, kind = line.split(" ")
+            types[name] = kind
+        elif line and not line.startswith("#"):
+            name, labels, value = SAMPLE.match(line).groups()
+            samples[(name, frozenset(LABEL.findall(labels or "")))] = float(value)
+    return types, samples
+
+
+def label_sets(samples, name: str):
+    return {labels for sample, labels in samples if sample == name}
+
+
+class NoVisualization:
+    """Visualization service double finding no shared block"""
+
+    def generate_react_flow_ast(self, content1, content2, file1_name, file2_name, layout):
+        return {"has_similarity": False}
+
+
+class SimilarityRepository:
+    """Similarity repository double"""
+
+    def update_status(self, record_id, status, error_message=None):
+        pass
+
+    def update_results(self, record_id, results):
+        pass
+
+
+class RunRepository:
+    """Detection run repository double accepting every batch"""
+
+    def insert_batch(self, run_id, pairs, fragments):
+        return len(pairs)
+
+    def mark_not_comparable(self, run_id, not_comparable):
+        pass
+
+    def finish_run(self, *args):
+        return None
+
+
+class TestMetricsRegistry(unittest.TestCase):
+    """Tests for the text exposition of counters, gauges and histograms"""
+
+    def test_counters_and_gauges(self):
+        """Children are rendered once per label values, label values escaped, gauge callbacks read on scrape."""
+        registry = MetricsRegistry()
+        counter = Counter("test_events_total", "Events", ["kind"], registry=registry)
+        counter.labels('quoted "x"\\').inc()
+        counter.labels("plain").inc(2)
+        counter.labels("plain").inc()
+        depth = {"a": 3}
+        Gauge("test_depth", "Depth", ["queue"], registry=registry, callback=lambda: {(k,): v for k, v in depth.items()})
+
+        text = registry.render()
+
+        self.assertIn("# TYPE test_events_total counter", text)
+        self.assertIn('test_events_total{kind="quoted \\"x\\"\\\\"} 1.0', text)
+        self.assertIn('test_events_total{kind="plain"} 3.0', text)
+        self.assertIn('test_depth{queue="a"} 3.0', text)
+        depth = {"b": 1}
+        self.assertNotIn('queue="a"', registry.render().split("# TYPE test_depth")[1])
+        with self.assertRaises(ValueError):
+            counter.labels("one", "two")
+        with self.assertRaises(ValueError):
+            Counter("test_events_total", "Events", registry=registry)
+
+    def test_histogram_buckets_are_cumulative(self):
+        """Bucket counts include the smaller buckets, +Inf equals the count."""
+        registry = MetricsRegistry()
+        histogram = Histogram("test_seconds", "Durations", ["route"], registry=registry, buckets=[0.1, 1.0])
+        child = histogram.labels("/a")
+        for value in [0.05, 0.1, 0.5, 3.0]:
+            child.observe(value)
+
+        _, samples = parse(registry.render())
+
+        route = ("route", "/a")
+        self.assertEqual(samples[("test_seconds_bucket", frozenset({route, ("le", "0.1")}))], 2)
+        self.assertEqual(samples[("test_seconds_bucket", frozenset({route, ("le", "1.0")}))], 3)
+        self.assertEqual(samples[("test_seconds_bucket", frozenset({route, ("le", "+Inf")}))], 4)
+        self.assertEqual(samples[("test_seconds_count", frozenset({route}))], 4)
+        self.assertAlmostEqual(samples[("test_seconds_sum", frozenset({route}))], 3.65)
+
+
+class TestMetricsMiddleware(unittest.TestCase):
+    """Tests for the timing of HTTP requests"""
+
+    def request(self, route, status):
+        async def app(scope, receive, send):
+            if route is not None:
+                scope["route"] = SimpleNamespace(path=route)
+            await send({"type": "http.response.start", "status": status})
+            await send({"type": "http.response.body", "body": b""})
+
+        sent = []
+
+        async def send(message):
+            sent.append(message)
+
+        scope = {"type": "http", "method": "GET", "path": "/anything"}
+        asyncio.run(MetricsMiddleware(app)(scope, None, send))
+        return sent
+
+    def test_requests_are_labelled_by_route_template_and_status(self):
+        """Requests are counted under the template of their route, unmatched ones under one label."""
+        self.request("/test/{item_id}", 200)
+        self.request("/test/{item_id}", 200)
+        self.assertEqual(len(self.request("/test/{item_id}", 404)), 2)
+        self.request(None, 404)
+
+        _, samples = parse(asyncio.run(metrics()).body.decode())
+
+        count = "pamp_http_request_duration_seconds_count"
+        request = {("route", "/test/{item_id}"), ("method", "GET")}
+        self.assertEqual(samples[(count, frozenset(request | {("status", "200")}))], 2)
+        self.assertEqual(samples[(count, frozenset(request | {("status", "404")}))], 1)
+        unmatched = frozenset({("route", "unmatched"), ("method", "GET"), ("status", "404")})
+        self.assertIn(unmatched, label_sets(samples, count))
+
+
+class TestMetricsEndpoint(unittest.TestCase):
+    """Tests for the metrics scraped after a small run"""
+
+    def setUp(self):
+        self.root = Path(tempfile.mkdtemp())
+        write_submissions(self.root, {UUID(int=1): {"main.py": SOURCE}, UUID(int=2): {"main.py": SOURCE}})
+
+    def tearDown(self):
+        shutil.rmtree(self.root, ignore_errors=True)
+
+    def run_comparisons(self):
+        """Compare two submissions twice through the fingerprint cache and record the pairs"""
+        service = detection_service(
+            self.root, FingerprintService(WordTokenizer(), InMemoryFingerprintStore(), k=3, window=2), NoVisualization()
+        )
+        first, second = (
+            SimpleNamespace(
+                id=UUID(int=number),
+                link=f"https://github.com/user/{name}.git",
+                project_uuid=UUID(int=10),
+                group_uuid=UUID(int=11),
+                project_step_uuid=UUID(int=12),
+                submitted_by_uuid=None,
+                link_type=None,
+            )
+            for number, name in [(1, "first"), (2, "second")]
+        )
+        recorder = DetectionRunRecorder(RunRepository(), uuid4())
+        for _ in range(2):
+            results = service._process_single_comparison_with_repos(
+                SimpleNamespace(id=1), first, second, None, SimilarityRepository()
+            )
+            similarity = SimpleNamespace(
+                id=None,
+                status=SimilarityStatus.COMPLETED,
+                error_message=None,
+                processing_time_seconds=results["processing_time_seconds"],
+                overall_similarity=results["overall_similarity"],
+            )
+            recorder.record_comparison(similarity, first, second, results)
+        recorder.finish()
+
+    def test_families_and_label_sets_after_a_run(self):
+        """Every family is exposed with its type, the instrumented paths add their label sets."""
+        get_detection_scheduler()
+        self.run_comparisons()
+        storage = SubmissionStorageService(InMemorySubmissionStore())
+        storage.ingest_directory(SimpleNamespace(id=uuid4(), project_uuid=uuid4()), self.root / str(UUID(int=1)))
+        with self.assertRaises(ValidationException):
+            SubmissionService.__new__(SubmissionService)._validate_submission_data(
+                SimpleNamespace(link="https://example.com/repo.git", file_size_bytes=10**13, description=None)
+            )
+        StorageException("Failed to read object", "test")
+
+        response = asyncio.run(metrics())
+        self.assertEqual(response.media_type, CONTENT_TYPE)
+        types, samples = parse(response.body.decode())
+
+        self.assertEqual(
+            {name: kind for name, kind in types.items() if name.startswith("pamp_")},
+            {
+                "pamp_http_request_duration_seconds": "histogram",
+                "pamp_submissions_created_total": "counter",
+                "pamp_submission_rejections_total": "counter",
+                "pamp_quota_rejections_total": "counter",
+                "pamp_ingested_files_total": "counter",
+                "pamp_ingested_bytes_total": "counter",
+                "pamp_notified_objects_total": "counter",
+                "pamp_malware_scans_total": "counter",
+                "pamp_fetch_retries_total": "counter",
+                "pamp_fetch_short_circuits_total": "counter",
+                "pamp_tokenized_files_total": "counter",
+                "pamp_tokens_total": "counter",
+                "pamp_tokenization_seconds_total": "counter",
+                "pamp_fingerprint_cache_lookups_total": "counter",
+                "pamp_fingerprint_cache_hit_ratio": "gauge",
+                "pamp_detection_jobs": "gauge",
+                "pamp_queue_depth": "gauge",
+                "pamp_compared_pairs_total": "counter",
+                "pamp_detection_timeouts_total": "counter",
+                "pamp_schedule_ticks_total": "counter",
+                "pamp_storage_errors_total": "counter",
+                "pamp_audit_write_failures_total": "counter",
+            },
+        )
+        python = frozenset({("language", "python")})
+        self.assertIn(python, label_sets(samples, "pamp_tokenized_files_total"))
+        self.assertGreater(samples[("pamp_tokens_total", python)], 0)
+        self.assertGreater(samples[("pamp_tokenization_seconds_total", python)], 0)
+        self.assertTrue(
+            {frozenset({("result", "hit")}), frozenset({("result", "miss")})}
+            <= label_sets(samples, "pamp_fingerprint_cache_lookups_total")
+        )
+        self.assertGreater(samples[("pamp_fingerprint_cache_hit_ratio", frozenset())], 0)
+        self.assertIn(frozenset({("status", "completed")}), label_sets(samples, "pamp_compared_pairs_total"))
+        self.assertEqual(
+            label_sets(samples, "pamp_detection_jobs"),
+            {frozenset({("state", "queued")}), frozenset({("state", "running")})},
+        )
+        self.assertIn(frozenset({("queue", "detection")}), label_sets(samples, "pamp_queue_depth"))
+        self.assertIn(frozenset({("reason", "file_size")}), label_sets(samples, "pamp_submission_rejections_total"))
+        self.assertIn(frozenset({("backend", "test")}), label_sets(samples, "pamp_storage_errors_total"))
+        self.assertGreaterEqual(samples[("pamp_ingested_files_total", frozenset())], 1)
+        self.assertGreaterEqual(samples[("pamp_ingested_bytes_total", frozenset())], len(SOURCE))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,285 @@
+"""
+Tests for the embedded schema migrations.
+
+Runs against TEST_DATABASE_URL when set (e.g. a throwaway PostgreSQL database), a temporary SQLite file otherwise.
+CI must set it, the tests fail there without it, so that the locking tests take the PostgreSQL advisory lock across
+connections rather than the local lock used on SQLite.
+"""
+
+import shutil
+import tempfile
+import threading
+import time
+import unittest
+from datetime import datetime, timezone
+from uuid import uuid4
+
+from sqlalchemy import Column, DateTime, MetaData, String, Table, func, inspect, select, text
+from sqlmodel import Session, SQLModel, create_engine
+
+from app.domains.retention.retention_repository import RetentionRepository
+from app.domains.runs.runs_repository import DetectionRunRepository
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.domains.submissions.submissions_models import Submission
+from app.domains.submissions.submissions_repository import SubmissionRepository
+from app.shared.migrations import (
+    DatabaseAheadException,
+    Migration,
+    MigrationException,
+    MigrationLock,
+    MigrationLockTimeoutException,
+    MigrationRunner,
+    load_migrations,
+)
+from app.shared.migrations.migration_runner import schema_migrations
+from tests.helpers import get_test_database_url
+
+
+class MigrationTestCase(unittest.TestCase):
+    """Base class giving each test an empty database."""
+
+    def setUp(self):
+        database_url = get_test_database_url()
+        self.temp_dir = None
+        if database_url:
+            self.engine = create_engine(database_url)
+        else:
+            self.temp_dir = tempfile.mkdtemp(prefix="test_migrations_")
+            self.engine = create_engine(f"sqlite:///{self.temp_dir}/test.db", connect_args={"check_same_thread": False})
+        self.drop_everything()
+
+    def tearDown(self):
+        self.drop_everything()
+        self.engine.dispose()
+        if self.temp_dir:
+            shutil.rmtree(self.temp_dir, ignore_errors=True)
+
+    def drop_everything(self):
+        SQLModel.metadata.drop_all(self.engine)
+        with self.engine.begin() as connection:
+            connection.execute(text("DROP TABLE IF EXISTS schema_migrations"))
+            connection.execute(text("DROP TABLE IF EXISTS migration_test_counter"))
+
+
+class TestEmbeddedMigrations(MigrationTestCase):
+    """Tests applying the shipped migrations."""
+
+    def test_migrations_are_discovered_in_order(self):
+        """Embedded migrations are numbered without gaps starting at 1."""
+        versions = [migration.version for migration in load_migrations()]
+        self.assertEqual(versions, list(range(1, len(versions) + 1)))
+
+    def test_fresh_database_gets_every_table(self):
+        """Applying all migrations to an empty database creates every model table."""
+        runner = MigrationRunner(self.engine)
+
+        applied = runner.upgrade()
+
+        self.assertEqual(applied, [migration.version for migration in runner.migrations])
+        self.assertEqual(runner.applied_versions(), applied)
+        tables = set(inspect(self.engine).get_table_names())
+        self.assertTrue(set(SQLModel.metadata.tables).issubset(tables))
+
+    def test_fresh_database_matches_the_models(self):
+        """Migrations alone give every table the columns, nullability and indexes of its model."""
+        MigrationRunner(self.engine).upgrade()
+
+        inspector = inspect(self.engine)
+        for name, table in SQLModel.metadata.tables.items():
+            with self.subTest(name):
+                migrated = {column["name"]: column["nullable"] for column in inspector.get_columns(name)}
+                self.assertEqual(migrated, {column.name: column.nullable for column in table.columns})
+                # PostgreSQL also lists the indexes backing unique constraints
+                indexes = inspector.get_indexes(name)
+                self.assertEqual(
+                    {index["name"] for index in indexes if "duplicates_constraint" not in index},
+                    {index.name for index in table.indexes},
+                )
+
+    def test_upgrade_is_idempotent(self):
+        """A second startup has nothing left to apply."""
+        MigrationRunner(self.engine).upgrade()
+
+        self.assertEqual(MigrationRunner(self.engine).upgrade(), [])
+
+    def test_repository_layer_works_on_migrated_schema(self):
+        """Repositories read and write through the migrated schema."""
+        MigrationRunner(self.engine).upgrade()
+
+        with Session(self.engine) as session:
+            submission = SubmissionRepository(session).create(
+                CreateSubmissionDto(
+                    link="https://github.com/user/repository.git",
+                    project_uuid=uuid4(),
+                    group_uuid=uuid4(),
+                    project_step_uuid=uuid4(),
+                )
+            )
+            self.assertIsNotNone(SubmissionRepository(session).get_by_id(submission.id))
+            self.assertFalse(submission.legal_hold)
+
+            run_repository = DetectionRunRepository(session)
+            run = run_repository.create_run(
+                {"project_uuid": submission.project_uuid, "project_step_uuid": submission.project_step_uuid},
+                [{"submission_id": submission.id, "group_uuid": submission.group_uuid}],
+            )
+            self.assertEqual(len(run_repository.get_participants(run.id)), 1)
+
+            policy = RetentionRepository(session).upsert_policy(
+                submission.project_uuid, {"submission_retention_days": 30, "report_retention_days": None}
+            )
+            self.assertEqual(policy.submission_retention_days, 30)
+
+    def test_database_created_before_migrations_is_adopted(self):
+        """Tables left by the former create_all startup are kept and the history is recorded."""
+        SQLModel.metadata.create_all(self.engine)
+
+        applied = MigrationRunner(self.engine).upgrade()
+
+        self.assertEqual(applied, [migration.version for migration in load_migrations()])
+
+    def test_missing_columns_are_added_to_existing_tables(self):
+        """Columns introduced after a table was first created are added with their defaults."""
+        legacy = Table(
+            "submission",
+            MetaData(),
+            Column("id", Submission.__table__.c.id.type, primary_key=True),
+            Column("link", String(255)),
+        )
+        legacy.create(self.engine)
+        with self.engine.begin() as connection:
+            connection.execute(legacy.insert().values(id=uuid4(), link="https://github.com/user/repo.git"))
+
+        MigrationRunner(self.engine).upgrade()
+
+        columns = {column["name"] for column in inspect(self.engine).get_columns("submission")}
+        self.assertTrue({"legal_hold", "legal_hold_reason"}.issubset(columns))
+        with self.engine.connect() as connection:
+            self.assertFalse(connection.execute(text("SELECT legal_hold FROM submission")).scalar())
+
+    def test_paris_timestamps_are_converted_to_utc(self):
+        """Naive timestamps written as Paris times are read back as the same instants in UTC."""
+        legacy = Table(
+            "submission",
+            MetaData(),
+            Column("id", Submission.__table__.c.id.type, primary_key=True),
+            Column("link", String(255)),
+            Column("upload_date_time", DateTime()),
+        )
+        legacy.create(self.engine)
+        with self.engine.begin() as connection:
+            for paris_time in (datetime(2024, 1, 15, 10, 30), datetime(2024, 7, 15, 10, 30)):
+                connection.execute(legacy.insert().values(id=uuid4(), link="s3://b/a", upload_date_time=paris_time))
+
+        MigrationRunner(self.engine).upgrade()
+
+        with self.engine.connect() as connection:
+            uploaded = connection.execute(
+                select(Submission.__table__.c.upload_date_time).order_by(Submission.__table__.c.upload_date_time)
+            ).scalars()
+            self.assertEqual(
+                list(uploaded),
+                [datetime(2024, 1, 15, 9, 30, tzinfo=timezone.utc), datetime(2024, 7, 15, 8, 30, tzinfo=timezone.utc)],
+            )
+
+
+class TestMigrationRunner(MigrationTestCase):
+    """Tests for ordering, downgrade protection and locking with stand-in migrations."""
+
+    def setUp(self):
+        super().setUp()
+        self.calls = []
+
+    def recording_migration(self, version: int, delay: float = 0.0) -> Migration:
+        def upgrade(connection):
+            self.calls.append(version)
+            if delay:
+                time.sleep(delay)
+            connection.execute(text("CREATE TABLE IF NOT EXISTS migration_test_counter (version INTEGER PRIMARY KEY)"))
+            connection.execute(text("INSERT INTO migration_test_counter (version) VALUES (:v)"), {"v": version})
+
+        return Migration(version=version, name=f"step_{version}", upgrade=upgrade)
+
+    def test_only_pending_migrations_are_applied(self):
+        """Migrations already recorded are skipped on the next upgrade."""
+        MigrationRunner(self.engine, [self.recording_migration(1)]).upgrade()
+
+        applied = MigrationRunner(self.engine, [self.recording_migration(1), self.recording_migration(2)]).upgrade()
+
+        self.assertEqual(applied, [2])
+        self.assertEqual(self.calls, [1, 2])
+
+    def test_failed_migration_is_rolled_back(self):
+        """A failing migration is not recorded and the previous ones stay applied."""
+
+        def failing(connection):
+            connection.execute(text("INSERT INTO migration_test_counter (version) VALUES (2)"))
+            raise RuntimeError("boom")
+
+        runner = MigrationRunner(self.engine, [self.recording_migration(1), Migration(2, "failing", failing)])
+
+        with self.assertRaises(MigrationException):
+            runner.upgrade()
+
+        self.assertEqual(runner.applied_versions(), [1])
+        with self.engine.connect() as connection:
+            versions = connection.execute(text("SELECT version FROM migration_test_counter")).scalars().all()
+        self.assertEqual(versions, [1])
+
+    def test_database_ahead_of_build_refuses_to_start(self):
+        """A database migrated by a newer build is rejected before anything is applied."""
+        MigrationRunner(self.engine, [self.recording_migration(1), self.recording_migration(2)]).upgrade()
+        self.calls.clear()
+
+        with self.assertRaises(DatabaseAheadException) as context:
+            MigrationRunner(self.engine, [self.recording_migration(1)]).upgrade()
+
+        self.assertEqual(context.exception.database_version, 2)
+        self.assertEqual(context.exception.latest_known_version, 1)
+        self.assertIn("refusing to start", context.exception.detail)
+        self.assertEqual(self.calls, [])
+
+    def test_concurrent_startups_apply_each_migration_once(self):
+        """Instances starting together wait on the lock and never apply a migration twice."""
+        migrations = [self.recording_migration(1, delay=0.2), self.recording_migration(2)]
+        results, errors = [], []
+
+        def start_instance():
+            try:
+                engine = create_engine(self.engine.url, connect_args=self.connect_args())
+                try:
+                    results.append(MigrationRunner(engine, migrations).upgrade())
+                finally:
+                    engine.dispose()
+            except Exception as e:
+                errors.append(e)
+
+        threads = [threading.Thread(target=start_instance) for _ in range(4)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join(30)
+
+        self.assertEqual(errors, [])
+        self.assertEqual(sorted(self.calls), [1, 2])
+        self.assertEqual(sorted(results, key=len), [[], [], [], [1, 2]])
+        with self.engine.connect() as connection:
+            self.assertEqual(connection.execute(text("SELECT COUNT(*) FROM migration_test_counter")).scalar(), 2)
+            self.assertEqual(connection.execute(select(func.count()).select_from(schema_migrations)).scalar(), 2)
+
+    def test_lock_times_out_while_held(self):
+        """A second instance gives up once the lock timeout elapses."""
+        with MigrationLock(self.engine):
+            other_engine = create_engine(self.engine.url, connect_args=self.connect_args())
+            try:
+                with self.assertRaises(MigrationLockTimeoutException):
+                    MigrationLock(other_engine, timeout_seconds=0.3, poll_interval_seconds=0.1).acquire()
+            finally:
+                other_engine.dispose()
+
+    def connect_args(self) -> dict:
+        return {"check_same_thread": False} if self.engine.dialect.name == "sqlite" else {}
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,62 @@
+"""
+Tests for the per-stage profiler of detection runs
+"""
+
+import threading
+import time
+import unittest
+
+from app.shared.profiling import NULL_PROFILER, StageProfiler
+
+
+class TestStageProfiler(unittest.TestCase):
+    """Tests for the aggregation and reporting of stage timings"""
+
+    def test_stages_are_aggregated_across_threads(self):
+        """Occurrences recorded concurrently by workers all end up in the stage totals."""
+        profiler = StageProfiler()
+
+        def work():
+            for _ in range(100):
+                profiler.record("tokenization.python", 0.001)
+                profiler.record("fingerprinting", 0.0005, count=2)
+
+        threads = [threading.Thread(target=work) for _ in range(8)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+        stages = profiler.to_dict()["stages"]
+        self.assertEqual(stages["tokenization.python"]["count"], 800)
+        self.assertEqual(stages["fingerprinting"]["count"], 1600)
+        self.assertAlmostEqual(stages["tokenization.python"]["seconds"], 0.8, places=3)
+
+    def test_stage_times_the_enclosed_block(self):
+        """A timed block is recorded even when it raises, slowest stage first with shares summing to 100."""
+        profiler = StageProfiler()
+        with profiler.stage("pairwise_comparison"):
+            time.sleep(0.02)
+        with self.assertRaises(ValueError):
+            with profiler.stage("decoding"):
+                raise ValueError("undecodable")
+
+        profile = profiler.to_dict()
+
+        self.assertEqual(list(profile["stages"]), ["pairwise_comparison", "decoding"])
+        self.assertGreaterEqual(profile["stages"]["pairwise_comparison"]["seconds"], 0.02)
+        self.assertAlmostEqual(sum(stage["share"] for stage in profile["stages"].values()), 100, delta=0.1)
+        self.assertIn("pairwise_comparison", profiler.summary())
+
+    def test_null_profiler_records_nothing(self):
+        """The profiler of unprofiled runs keeps no timings."""
+        with NULL_PROFILER.stage("decoding"):
+            pass
+        NULL_PROFILER.record("decoding", 1.0)
+
+        self.assertFalse(NULL_PROFILER.enabled)
+        self.assertEqual(NULL_PROFILER.to_dict()["stages"], {})
+
+
+if __name__ == "__main__":
+    unittest.main()
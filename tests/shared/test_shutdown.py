@@ -0,0 +1,120 @@
+"""
+Tests for the coordinated shutdown: draining state, signal handling and the refusal of new requests
+"""
+
+import asyncio
+import os
+import signal
+import threading
+import time
+import unittest
+
+from app.shared.shutdown import DrainingMiddleware, ShutdownCoordinator
+
+
+class TestShutdownCoordinator(unittest.TestCase):
+    """Tests for starting to drain"""
+
+    def setUp(self):
+        self.coordinator = ShutdownCoordinator()
+
+    def tearDown(self):
+        self.coordinator.reset()
+
+    def test_callbacks_run_once(self):
+        """Drain callbacks run on the first begin only, a failing one does not stop the others."""
+        calls = []
+        self.coordinator.on_drain(lambda: calls.append("consumer"))
+        self.coordinator.on_drain(lambda: 1 / 0)
+        self.coordinator.on_drain(lambda: calls.append("poller"))
+
+        self.coordinator.begin("test")
+        self.coordinator.begin("again")
+
+        self.assertTrue(self.coordinator.draining)
+        self.assertEqual(calls, ["consumer", "poller"])
+
+    def test_later_begin_waits_for_the_callbacks(self):
+        """The lifespan shutdown waits for the callbacks a signal started before draining the jobs."""
+        release, stopped = threading.Event(), []
+
+        def stop_consumer():
+            release.wait(5)
+            stopped.append(True)
+
+        self.coordinator.on_drain(stop_consumer)
+        first = threading.Thread(target=self.coordinator.begin, args=("signal",))
+        first.start()
+        while not self.coordinator.draining:
+            time.sleep(0.001)
+        threading.Timer(0.05, release.set).start()
+        self.coordinator.begin("application shutdown")
+
+        self.assertEqual(stopped, [True])
+        first.join(5)
+
+    def test_sigterm_drains_then_reaches_the_server_handler(self):
+        """SIGTERM starts draining and is still handed to the handler installed before, the server's."""
+        received, stopped = [], threading.Event()
+        previous = signal.signal(signal.SIGTERM, lambda signum, frame: received.append(signum))
+        try:
+            self.coordinator.on_drain(stopped.set)
+            self.coordinator.install_signal_handlers()
+
+            os.kill(os.getpid(), signal.SIGTERM)
+
+            self.assertTrue(stopped.wait(5))
+            self.assertTrue(self.coordinator.draining)
+            self.assertEqual(received, [signal.SIGTERM])
+        finally:
+            self.coordinator.reset()
+            signal.signal(signal.SIGTERM, previous)
+
+    def test_reset_restores_the_signal_handlers(self):
+        """A new lifespan starts serving with no draining state and the server's handlers back."""
+        handler = signal.getsignal(signal.SIGINT)
+        self.coordinator.install_signal_handlers()
+        self.coordinator.begin("test")
+
+        self.coordinator.reset()
+
+        self.assertFalse(self.coordinator.draining)
+        self.assertIs(signal.getsignal(signal.SIGINT), handler)
+
+
+class TestDrainingMiddleware(unittest.TestCase):
+    """Tests for the refusal of new requests while draining"""
+
+    def request(self, coordinator: ShutdownCoordinator, path: str):
+        async def app(scope, receive, send):
+            await send({"type": "http.response.start", "status": 200, "headers": []})
+            await send({"type": "http.response.body", "body": b"ok"})
+
+        messages = []
+
+        async def send(message):
+            messages.append(message)
+
+        async def receive():
+            return {"type": "http.request"}
+
+        scope = {"type": "http", "method": "GET", "path": path}
+        asyncio.run(DrainingMiddleware(app, coordinator)(scope, receive, send))
+        return messages[0]["status"], dict(messages[0]["headers"])
+
+    def test_requests_are_refused_while_draining(self):
+        """New work gets 503 with Retry-After once draining, probes and metrics are still answered."""
+        coordinator = ShutdownCoordinator()
+        self.assertEqual(self.request(coordinator, "/submissions")[0], 200)
+
+        coordinator.begin("test")
+
+        status, headers = self.request(coordinator, "/submissions")
+        self.assertEqual(status, 503)
+        self.assertEqual(headers[b"retry-after"], b"1")
+        self.assertEqual(self.request(coordinator, "/health/liveness")[0], 200)
+        self.assertEqual(self.request(coordinator, "/metrics")[0], 200)
+
+
+if __name__ == "__main__":
+    unittest.main()
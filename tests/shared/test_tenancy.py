@@ -0,0 +1,177 @@
+"""
+Tests for the tenancy context of requests, of the work they hand over and of the keys it reaches
+"""
+
+import asyncio
+import unittest
+from types import SimpleNamespace
+from unittest.mock import patch
+from uuid import uuid4
+
+from pydantic import SecretStr
+
+from app.config.config import Settings
+from app.domains.corpus.corpus_archive import export_accessible, export_key
+from app.domains.fingerprints.fingerprint_models import FingerprintKey
+from app.domains.storage.exceptions import StoredObjectNotFoundException
+from app.domains.storage.submission_store import submission_prefix
+from app.domains.submissions.detection_integration_service import DetectionIntegrationService
+from app.shared.concurrency import JobScheduler
+from app.shared.exceptions import NotFoundException
+from app.shared.tenancy import (
+    UNRESTRICTED,
+    Tenancy,
+    TenancyMiddleware,
+    current_tenancy,
+    in_tenancy,
+    require_project_access,
+    tenancy_of,
+    tenancy_scope,
+    tenant_namespace,
+)
+
+
+def patched_settings(test: unittest.TestCase, **values) -> Settings:
+    settings = Settings(admin_api_token=SecretStr("admin-token"), **values)
+    for target in ("app.shared.tenancy.get_settings", "app.shared.security.get_settings"):
+        patcher = patch(target, return_value=settings)
+        patcher.start()
+        test.addCleanup(patcher.stop)
+    return settings
+
+
+class TestTenancyOfRequests(unittest.TestCase):
+    """Tests for the tenancy derived from the headers of a request"""
+
+    def test_disabled_tenancy_is_unrestricted(self):
+        patched_settings(self, tenancy_enabled=False)
+        self.assertEqual(tenancy_of({"x-tenant-id": "tenant-a"}), UNRESTRICTED)
+
+    def test_operator_tokens_are_unrestricted(self):
+        patched_settings(self, tenancy_enabled=True)
+        self.assertEqual(tenancy_of({"authorization": "Bearer admin-token", "x-tenant-id": "tenant-a"}), UNRESTRICTED)
+        self.assertEqual(tenancy_of({"authorization": "Bearer forged"}), Tenancy(None))
+
+    def test_tenant_header(self):
+        patched_settings(self, tenancy_enabled=True, tenant_header="X-Institution")
+        self.assertEqual(tenancy_of({"x-institution": " tenant-a "}), Tenancy("tenant-a"))
+        self.assertEqual(tenancy_of({"x-tenant-id": "tenant-a"}), Tenancy(None))
+        # Never reaching a project rather than one of the separators of the keys
+        self.assertEqual(tenancy_of({"x-institution": "tenant@a"}), Tenancy(None))
+        self.assertEqual(tenancy_of({"x-institution": "t" * 65}), Tenancy(None))
+
+    def test_middleware_runs_requests_in_their_tenancy(self):
+        patched_settings(self, tenancy_enabled=True)
+        seen = []
+
+        async def app(scope, receive, send):
+            seen.append(current_tenancy())
+
+        middleware = TenancyMiddleware(app)
+        asyncio.run(middleware({"type": "http", "headers": [(b"X-Tenant-Id", b"tenant-a")]}, None, None))
+        asyncio.run(middleware({"type": "lifespan"}, None, None))
+
+        self.assertEqual(seen, [Tenancy("tenant-a"), UNRESTRICTED])
+        self.assertEqual(current_tenancy(), UNRESTRICTED)
+
+
+class TestTenancyPropagation(unittest.TestCase):
+    """Tests for the tenancy of the work handed over to other threads"""
+
+    def test_scheduled_jobs_keep_the_tenancy_they_were_submitted_in(self):
+        scheduler = JobScheduler("test", 2)
+        try:
+            with tenancy_scope(Tenancy("tenant-a")):
+                inherited = scheduler.submit(current_tenancy).result(timeout=5)
+            explicit = scheduler.submit(in_tenancy(Tenancy("tenant-b"), current_tenancy)).result(timeout=5)
+        finally:
+            scheduler.shutdown(wait=True)
+
+        self.assertEqual(inherited, Tenancy("tenant-a"))
+        self.assertEqual(explicit, Tenancy("tenant-b"))
+        self.assertEqual(current_tenancy(), UNRESTRICTED)
+
+    def test_runs_queued_outside_of_requests_are_restricted_to_their_project(self):
+        project_uuid = uuid4()
+        submitted = []
+        service = DetectionIntegrationService.__new__(DetectionIntegrationService)
+        service.job_scheduler = SimpleNamespace(submit=lambda function, *args, **kwargs: submitted.append(function))
+        service._start_detection_run_threaded = lambda *args: current_tenancy()
+
+        with patch(
+            "app.domains.submissions.detection_integration_service.tenancy_of_project",
+            return_value=Tenancy("tenant-a", projects={project_uuid}),
+        ):
+            service._queue_detection_run(uuid4(), uuid4(), [], project_uuid, uuid4())
+        with tenancy_scope(Tenancy("tenant-b")):
+            service._queue_detection_run(uuid4(), uuid4(), [], project_uuid, uuid4())
+
+        self.assertEqual([function() for function in submitted], [Tenancy("tenant-a"), Tenancy("tenant-b")])
+
+    def test_pairs_are_only_compared_within_the_project_of_the_run(self):
+        project_uuid = uuid4()
+        submissions = {
+            "own": SimpleNamespace(project_uuid=project_uuid),
+            "other": SimpleNamespace(project_uuid=project_uuid),
+            "foreign": SimpleNamespace(project_uuid=uuid4()),
+        }
+        repository = SimpleNamespace(get_by_id=submissions.get)
+
+        pair = DetectionIntegrationService._pair_submissions(repository, "own", "other", project_uuid)
+        self.assertEqual(pair, (submissions["own"], submissions["other"]))
+        self.assertIsNone(DetectionIntegrationService._pair_submissions(repository, "own", "foreign", project_uuid))
+        self.assertIsNone(DetectionIntegrationService._pair_submissions(repository, "own", "missing", project_uuid))
+
+
+class TestTenantKeys(unittest.TestCase):
+    """Tests for the storage and cache keys reached by a tenant"""
+
+    def setUp(self):
+        self.own_project = uuid4()
+        self.other_project = uuid4()
+        tenants = {self.own_project: "tenant-a", self.other_project: "tenant-b"}
+        patcher = patch("app.shared.tenancy.project_tenant", side_effect=tenants.get)
+        self.project_tenant = patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def test_keys_of_other_tenants_are_not_found(self):
+        with tenancy_scope(Tenancy("tenant-a")):
+            self.assertTrue(submission_prefix(self.own_project, uuid4()).startswith(f"projects/{self.own_project}/"))
+            self.assertTrue(export_key(self.own_project, "export").startswith(f"exports/projects/{self.own_project}/"))
+            with self.assertRaises(StoredObjectNotFoundException):
+                submission_prefix(self.other_project, uuid4())
+            with self.assertRaises(StoredObjectNotFoundException):
+                export_key(uuid4(), "export")
+            with self.assertRaises(NotFoundException):
+                require_project_access(self.other_project)
+            with self.assertRaises(NotFoundException):
+                require_project_access("../projects")
+
+        self.assertTrue(submission_prefix(self.other_project, uuid4()).startswith("projects/"))
+
+    def test_projects_are_looked_up_once_per_tenancy(self):
+        with tenancy_scope(Tenancy("tenant-a")):
+            submission_prefix(self.own_project, uuid4())
+            submission_prefix(self.own_project, uuid4())
+        self.assertEqual(self.project_tenant.call_count, 1)
+
+    def test_imported_archives_are_restricted_to_the_exports_of_the_tenant(self):
+        with tenancy_scope(Tenancy("tenant-a")):
+            self.assertTrue(export_accessible(f"exports/projects/{self.own_project}/export.tar.gz"))
+            self.assertFalse(export_accessible(f"exports/projects/{self.other_project}/export.tar.gz"))
+            self.assertFalse(export_accessible("uploads/export.tar.gz"))
+        self.assertTrue(export_accessible("uploads/export.tar.gz"))
+
+    def test_cache_keys_are_namespaced_by_tenant(self):
+        key = FingerprintKey("abc", "python", "v1", "default", 5, 4)
+        with tenancy_scope(Tenancy("tenant-a")):
+            namespaced = FingerprintKey("abc", "python", "v1", "default", 5, 4, tenant=tenant_namespace())
+
+        self.assertEqual(key.to_cache_key(), "v1|python|default|5|4|abc")
+        self.assertNotEqual(namespaced.to_cache_key(), key.to_cache_key())
+        self.assertEqual(FingerprintKey.from_cache_key(namespaced.to_cache_key()), namespaced)
+        self.assertEqual(FingerprintKey.from_cache_key(key.to_cache_key()), key)
+
+
+if __name__ == "__main__":
+    unittest.main()
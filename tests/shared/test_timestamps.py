@@ -0,0 +1,116 @@
+"""
+Tests for the parsing, storage and serialization of timezone-aware timestamps
+"""
+
+import unittest
+from datetime import datetime, timedelta, timezone
+from types import SimpleNamespace
+from uuid import uuid4
+
+from pydantic import ValidationError
+
+from app.domains.submissions.dto.create_submission_dto import CreateSubmissionDto
+from app.domains.submissions.dto.submission_response_dto import SubmissionResponseDto
+from app.shared.timestamps import UtcDateTime, as_utc, parse_timestamp, to_rfc3339
+
+UTC = timezone.utc
+
+# 2024-03-31 01:15 UTC, just after Paris moved to summer time
+INSTANT = datetime(2024, 3, 31, 1, 15, tzinfo=UTC)
+
+
+def submission_data(**fields) -> dict:
+    return {
+        "link": "https://github.com/user/repository.git",
+        "project_uuid": uuid4(),
+        "group_uuid": uuid4(),
+        "project_step_uuid": uuid4(),
+        **fields,
+    }
+
+
+class TestParseTimestamp(unittest.TestCase):
+    """Tests for timestamps supplied with explicit offsets"""
+
+    def test_offsets_give_the_same_instant(self):
+        """Every offset notation of one instant parses to it in UTC."""
+        for value in [
+            "2024-03-31T01:15:00Z",
+            "2024-03-31T01:15:00+00:00",
+            "2024-03-31T03:15:00+02:00",
+            "2024-03-31T03:15:00+0200",
+            "2024-03-30T20:15:00-05:00",
+            "2024-03-31T06:45:00+05:30",
+            "2024-03-31 03:15:00.000+02:00",
+        ]:
+            parsed = parse_timestamp(value)
+            self.assertEqual(parsed, INSTANT, value)
+            self.assertEqual(parsed.utcoffset(), timedelta(0), value)
+
+    def test_git_dates_are_accepted(self):
+        """Commit dates in the RFC 2822, default and raw git formats keep their offset."""
+        for value in ["Sun, 31 Mar 2024 03:15:00 +0200", "Sun Mar 31 03:15:00 2024 +0200", "1711847700 +0200"]:
+            self.assertEqual(parse_timestamp(value), INSTANT, value)
+
+    def test_timestamps_without_offset_are_rejected(self):
+        """Naive times, unknown offsets and other formats raise ValueError."""
+        for value in ["2024-03-31T03:15:00", "Sun, 31 Mar 2024 03:15:00 -0000", "31/03/2024 03:15", ""]:
+            with self.assertRaises(ValueError, msg=value):
+                parse_timestamp(value)
+
+
+class TestTimestampFields(unittest.TestCase):
+    """Tests for the timestamps of requests, responses and database columns"""
+
+    def test_submission_timestamp_requires_an_offset(self):
+        """Submissions get their upload time in UTC, naive ones are refused."""
+        dto = CreateSubmissionDto(**submission_data(upload_date_time="2024-03-31T03:15:00+02:00"))
+        self.assertEqual(dto.upload_date_time, INSTANT)
+        self.assertEqual(dto.upload_date_time.tzinfo, UTC)
+        self.assertIsNone(CreateSubmissionDto(**submission_data()).upload_date_time)
+
+        for naive in ["2024-03-31T03:15:00", datetime(2024, 3, 31, 3, 15)]:
+            with self.assertRaises(ValidationError):
+                CreateSubmissionDto(**submission_data(upload_date_time=naive))
+
+    def test_responses_are_serialized_with_offset(self):
+        """Response timestamps are RFC 3339 in UTC, whatever their original timezone."""
+        paris = timezone(timedelta(hours=2))
+        response = SubmissionResponseDto(
+            **submission_data(
+                id=uuid4(),
+                link_type=None,
+                description=None,
+                submitted_by_uuid=None,
+                file_size_bytes=None,
+                file_count=None,
+                upload_date_time=INSTANT.astimezone(paris),
+                status="completed",
+                created_at=datetime(2024, 3, 31, 1, 15),
+                updated_at=None,
+                ip_address=None,
+                user_agent=None,
+            )
+        )
+
+        payload = response.model_dump(mode="json")
+        self.assertEqual(payload["upload_date_time"], "2024-03-31T01:15:00Z")
+        self.assertEqual(payload["created_at"], "2024-03-31T01:15:00Z")
+        self.assertEqual(to_rfc3339(INSTANT.astimezone(paris)), "2024-03-31T01:15:00Z")
+        self.assertIsNone(to_rfc3339(None))
+
+    def test_columns_store_utc(self):
+        """Columns write UTC, naive for SQLite, and read back aware UTC datetimes."""
+        column = UtcDateTime()
+        paris_time = INSTANT.astimezone(timezone(timedelta(hours=2)))
+
+        self.assertEqual(column.process_bind_param(paris_time, SimpleNamespace(name="postgresql")), INSTANT)
+        stored = column.process_bind_param(paris_time, SimpleNamespace(name="sqlite"))
+        self.assertEqual(stored, datetime(2024, 3, 31, 1, 15))
+        self.assertEqual(column.process_result_value(stored, SimpleNamespace(name="sqlite")), INSTANT)
+        self.assertIsNone(column.process_bind_param(None, SimpleNamespace(name="sqlite")))
+        self.assertEqual(as_utc(paris_time).tzinfo, UTC)
+
+
+if __name__ == "__main__":
+    unittest.main()
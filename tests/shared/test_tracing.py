@@ -0,0 +1,215 @@
+"""
+Tests for structured logs correlated by request and run IDs, across the worker threads of a run
+"""
+
+import asyncio
+import io
+import json
+import logging
+import threading
+import unittest
+from types import SimpleNamespace
+
+from fastapi.exceptions import RequestValidationError
+from starlette.exceptions import HTTPException as StarletteHTTPException
+
+from app.domains.detection.pairwise_comparison import ParallelPairwiseComparator
+from app.shared.concurrency import JobScheduler
+from app.shared.tracing import (
+    REQUEST_ID_HEADER,
+    CorrelationFilter,
+    JsonFormatter,
+    LogFilter,
+    RequestIdMiddleware,
+    current_fields,
+    install_error_handlers,
+    parse_log_filter,
+    span,
+)
+
+logger = logging.getLogger("app.tests.tracing")
+
+
+class CapturedLogs:
+    """JSON log lines of the records of a logger handled while in use"""
+
+    def __init__(self, name: str = logger.name):
+        self.logger = logging.getLogger(name)
+
+    def __enter__(self):
+        self.stream = io.StringIO()
+        self.handler = logging.StreamHandler(self.stream)
+        self.handler.setFormatter(JsonFormatter())
+        self.handler.addFilter(CorrelationFilter())
+        self.previous_level = self.logger.level
+        self.logger.addHandler(self.handler)
+        self.logger.setLevel(logging.DEBUG)
+        return self
+
+    def __exit__(self, *exc_info):
+        self.logger.removeHandler(self.handler)
+        self.logger.setLevel(self.previous_level)
+
+    @property
+    def entries(self):
+        return [json.loads(line) for line in self.stream.getvalue().splitlines()]
+
+
+def request(app, headers=()):
+    """Send a GET request through the request ID middleware, returning the response start message"""
+    sent = []
+
+    async def send(message):
+        sent.append(message)
+
+    scope = {"type": "http", "method": "GET", "path": "/runs", "headers": list(headers)}
+    asyncio.run(RequestIdMiddleware(app)(scope, None, send))
+    return sent[0]
+
+
+def response_headers(message):
+    return {name.decode(): value.decode() for name, value in message["headers"]}
+
+
+class TestCorrelation(unittest.TestCase):
+    """Tests for the correlation fields of the events of a request starting a run"""
+
+    def test_worker_events_carry_request_and_run_ids(self):
+        """Events logged by comparison workers and scheduled jobs carry the request ID and the run ID."""
+        scheduler = JobScheduler("test", 2)
+
+        def compare(pair):
+            logger.info(f"Compared {pair[0]} and {pair[1]}")
+            return 1.0
+
+        def detection_run():
+            with span(run_id="run-1"):
+                logger.info("Run started")
+                pairs = [(left, right) for left in range(4) for right in range(left + 1, 4)]
+                list(ParallelPairwiseComparator(workers=4, chunk_size=1).compare(pairs, compare))
+
+        async def app(scope, receive, send):
+            logger.info("Request received")
+            scheduler.submit(detection_run).result(timeout=5)
+            await send({"type": "http.response.start", "status": 202, "headers": []})
+
+        try:
+            with CapturedLogs() as logs:
+                start = request(app, [(b"x-request-id", b"client-42")])
+        finally:
+            scheduler.shutdown(wait=True)
+
+        self.assertEqual(response_headers(start)["x-request-id"], "client-42")
+        entries = logs.entries
+        self.assertEqual(len(entries), 8)
+        self.assertEqual({entry["request_id"] for entry in entries}, {"client-42"})
+        self.assertNotIn("run_id", entries[0])
+        comparisons = [entry for entry in entries if entry["message"].startswith("Compared")]
+        self.assertEqual(len(comparisons), 6)
+        self.assertEqual({entry["run_id"] for entry in comparisons}, {"run-1"})
+        self.assertTrue(all(entry["thread"].startswith("comparison") for entry in comparisons))
+        self.assertNotEqual(entries[1]["thread"], threading.current_thread().name)
+        self.assertEqual(entries[1]["run_id"], "run-1")
+        self.assertEqual(entries[0]["level"], "INFO")
+        self.assertEqual(entries[0]["logger"], "app.tests.tracing")
+        self.assertEqual(current_fields(), {})
+
+    def test_invalid_or_missing_request_ids_are_replaced(self):
+        """Requests without an acceptable ID get a generated one, replacing a header set by the application."""
+        seen = []
+
+        async def app(scope, receive, send):
+            seen.append(current_fields()["request_id"])
+            await send({"type": "http.response.start", "status": 200, "headers": [(b"x-request-id", b"stale")]})
+
+        request(app)
+        start = request(app, [(b"x-request-id", b"not valid\n")])
+
+        self.assertEqual(len(seen[0]), 32)
+        self.assertEqual(len(seen[1]), 32)
+        self.assertNotEqual(seen[0], seen[1])
+        self.assertEqual(start["headers"], [(b"x-request-id", seen[1].encode())])
+
+
+class ErrorHandlers:
+    """Application double keeping the exception handlers installed"""
+
+    def __init__(self):
+        self.handlers = {}
+
+    def add_exception_handler(self, exception, handler):
+        self.handlers[exception] = handler
+
+    def respond(self, exception, error):
+        request = SimpleNamespace(
+            scope={"request_id": "client-42"}, method="POST", url=SimpleNamespace(path="/submissions")
+        )
+        return asyncio.run(self.handlers[exception](request, error))
+
+
+class TestErrorResponses(unittest.TestCase):
+    """Tests for the request ID of error responses"""
+
+    def setUp(self):
+        self.app = ErrorHandlers()
+        install_error_handlers(self.app)
+
+    def test_errors_carry_the_request_id(self):
+        """HTTP, validation and unexpected errors return the request ID in their body and headers."""
+        responses = [
+            self.app.respond(StarletteHTTPException, StarletteHTTPException(404, "Submission not found")),
+            self.app.respond(RequestValidationError, RequestValidationError([{"loc": ["body"], "msg": "missing"}])),
+        ]
+        with CapturedLogs("app.shared.tracing") as logs:
+            responses.append(self.app.respond(Exception, RuntimeError("boom")))
+
+        self.assertEqual([response.status_code for response in responses], [404, 422, 500])
+        for response in responses:
+            self.assertEqual(json.loads(response.body)["request_id"], "client-42")
+            self.assertEqual(response.headers[REQUEST_ID_HEADER], "client-42")
+        self.assertEqual(json.loads(responses[0].body)["detail"], "Submission not found")
+        self.assertEqual(json.loads(responses[2].body)["detail"], "Internal server error")
+        self.assertEqual(logs.entries[0]["request_id"], "client-42")
+        self.assertIn("RuntimeError: boom", logs.entries[0]["exception"])
+
+
+class TestLogFilter(unittest.TestCase):
+    """Tests for the parsing and application of log filters"""
+
+    def test_parse(self):
+        """A filter is a default level followed by per logger levels, unknown levels are refused."""
+        self.assertEqual(
+            parse_log_filter(" warning, app.domains.detection=DEBUG ,uvicorn.access=error"),
+            (logging.WARNING, {"app.domains.detection": logging.DEBUG, "uvicorn.access": logging.ERROR}),
+        )
+        self.assertEqual(parse_log_filter(""), (None, {}))
+        with self.assertRaises(ValueError):
+            parse_log_filter("info,app=loud")
+        with self.assertRaises(ValueError):
+            parse_log_filter("=debug")
+
+    def test_apply_resets_loggers_no_longer_named(self):
+        """Applying a filter replaces the previous one, an invalid filter changes nothing."""
+        root = logging.getLogger()
+        previous_level = root.level
+        log_filter = LogFilter()
+        try:
+            log_filter.apply("warning,app.tests.first=debug,app.tests.second=error")
+            self.assertEqual(root.level, logging.WARNING)
+            self.assertEqual(logging.getLogger("app.tests.first").level, logging.DEBUG)
+
+            log_filter.apply("app.tests.second=info")
+            with self.assertRaises(ValueError):
+                log_filter.apply("app.tests.second=loud")
+
+            self.assertEqual(log_filter.spec, "app.tests.second=info")
+            self.assertEqual(log_filter.levels(), (logging.INFO, {"app.tests.second": logging.INFO}))
+            self.assertEqual(logging.getLogger("app.tests.first").level, logging.NOTSET)
+        finally:
+            log_filter.apply("")
+            root.setLevel(previous_level)
+        self.assertEqual(logging.getLogger("app.tests.second").level, logging.NOTSET)
+
+
+if __name__ == "__main__":
+    unittest.main()
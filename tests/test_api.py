@@ -54,4 +54,27 @@ def test_liveness_check(client: TestClient):
     response = client.get("/health/liveness")
     data = response.json()
     assert response.status_code == 200
-    assert data["status"] == "alive"
\ No newline at end of file
+    assert data["status"] == "alive"
+
+
+@pytest.mark.parametrize(
+    "method,path",
+    [
+        ("PUT", "/retention/projects/550e8400-e29b-41d4-a716-446655440000/policy"),
+        ("DELETE", "/retention/projects/550e8400-e29b-41d4-a716-446655440000/policy"),
+        ("POST", "/retention/purge?dry_run=false"),
+        ("PUT", "/retention/submissions/550e8400-e29b-41d4-a716-446655440000/legal-hold"),
+        ("PUT", "/retention/runs/550e8400-e29b-41d4-a716-446655440000/legal-hold"),
+    ],
+)
+def test_retention_changes_require_the_admin_scope(client: TestClient, method: str, path: str):
+    """Policies, legal holds and purges are refused without the admin token"""
+    response = client.request(method, path, json={"legal_hold": False, "reason": "test"})
+    assert response.status_code in (401, 403)
+
+
+@pytest.mark.parametrize("method", ["GET", "DELETE"])
+def test_fingerprint_cache_requires_the_admin_scope(client: TestClient, method: str):
+    """The cache can neither be inspected nor wiped without the admin token"""
+    response = client.request(method, "/admin/fingerprint-cache")
+    assert response.status_code in (401, 403)
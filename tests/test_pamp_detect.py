@@ -0,0 +1,148 @@
+"""
+Tests for the pamp-detect CLI: the local run of a directory of submissions, its reports and exit codes
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+import pamp_detect
+from app.domains.detection.directory_comparison import DirectoryComparator
+from app.domains.detection.similarity_detection_service import SimilarityDetectionService
+from app.domains.fingerprints.fingerprint_service import FingerprintService
+from tests.helpers import SAMPLES_DIRECTORY, TREE_SITTER_AVAILABLE, PythonFiles, SharedLinesVisualization, WordTokenizer
+
+ROOT = Path(__file__).parent.parent
+
+LOOP = "def main ( ) :\n    total = 0\n    for value in values :\n        total = total + value\n"
+SUBMISSIONS = {
+    "alice": {"main.py": LOOP, "README.md": "# Alice\n"},
+    "bob": {"src/main.py": LOOP},
+    "carol": {"app.py": "class App :\n    def run ( self ) :\n        return self . total / 3\n"},
+}
+
+
+class LocalRunTestCase(unittest.TestCase):
+    """Base class writing submission directories to a temporary directory"""
+
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        self.directory = Path(directory.name)
+
+    def write(self, submissions: dict) -> Path:
+        for name, files in submissions.items():
+            for path, content in files.items():
+                target = self.directory / name / path
+                target.parent.mkdir(parents=True, exist_ok=True)
+                target.write_text(content, encoding="utf-8")
+        return self.directory
+
+
+class TestLocalRun(LocalRunTestCase):
+    """Tests for the comparison of the submissions of a directory through the code of detection runs"""
+
+    def run_detection(self, threshold: float = 0.8, names=tuple(SUBMISSIONS)) -> pamp_detect.LocalRun:
+        comparator = DirectoryComparator(
+            PythonFiles(),
+            FingerprintService(WordTokenizer(), k=3, window=2),
+            SimilarityDetectionService(),
+            SharedLinesVisualization(),
+        )
+        submissions = pamp_detect.collect_submissions(self.write({name: SUBMISSIONS[name] for name in names}))
+        return pamp_detect.run_detection(comparator, submissions, threshold)
+
+    def test_every_pair_is_compared_and_flagged_at_the_threshold(self):
+        """Each pair of subdirectories is recorded like a run pair, a pair at the threshold fails the check."""
+        (self.directory / ".git").mkdir()
+        local_run = self.run_detection()
+
+        self.assertEqual(list(local_run.submissions), ["alice", "bob", "carol"])
+        self.assertEqual(local_run.run.total_pairs, 3)
+        self.assertEqual(local_run.run.completed_pairs, 3)
+        names = local_run.names
+        self.assertEqual(
+            [(names[str(p.submission_id)], names[str(p.compared_submission_id)]) for p in local_run.flagged],
+            [("alice", "bob")],
+        )
+        self.assertEqual(local_run.exit_code, pamp_detect.EXIT_FLAGGED)
+
+    def test_no_pair_at_the_threshold_passes(self):
+        """Submissions sharing less than the threshold exit with 0, their pairs still reported."""
+        local_run = self.run_detection(names=("alice", "carol"))
+
+        self.assertEqual(local_run.flagged, [])
+        self.assertEqual(len(local_run.pairs), 1)
+        self.assertEqual(local_run.exit_code, pamp_detect.EXIT_CLEAN)
+
+    def test_reports(self):
+        """The JSON report names submissions by directory, the HTML one renders the flagged pairs with their code."""
+        local_run = self.run_detection()
+
+        report = json.loads(pamp_detect.json_report(local_run))
+        self.assertEqual(report["flagged_pairs"], 1)
+        first = report["pairs"][0]
+        self.assertEqual((first["submission"], first["compared_submission"], first["flagged"]), ("alice", "bob", True))
+        self.assertEqual(first["fragments"][0]["file2_path"], "src/main.py")
+        self.assertFalse(any(pair["flagged"] for pair in report["pairs"][1:]))
+        self.assertEqual(report["parameters"]["language"], "auto")
+
+        html = pamp_detect.html_report(local_run)
+        self.assertIn("alice", html)
+        self.assertIn("total = total + value", html)
+        self.assertNotIn("carol</td>", html)
+
+    def test_invalid_arguments(self):
+        """Directories without two submissions and unknown report formats fail with exit code 2."""
+        self.write({"alice": SUBMISSIONS["alice"]})
+
+        self.assertEqual(pamp_detect.main(["run", "--dir", str(self.directory)]), pamp_detect.EXIT_ERROR)
+        self.assertEqual(
+            pamp_detect.main(["run", "--dir", str(self.directory), "--out", "report.txt"]), pamp_detect.EXIT_ERROR
+        )
+        with self.assertRaises(SystemExit) as context:
+            pamp_detect.main(["run", "--dir", str(self.directory), "--threshold", "2"])
+        self.assertEqual(context.exception.code, pamp_detect.EXIT_ERROR)
+
+
+@unittest.skipUnless(TREE_SITTER_AVAILABLE, "tree-sitter language pack not installed")
+class TestCommandLine(LocalRunTestCase):
+    """Tests running the CLI as a process over the language samples"""
+
+    def command(self, *args: str) -> subprocess.CompletedProcess:
+        return subprocess.run(
+            [sys.executable, str(ROOT / "pamp_detect.py"), *args], cwd=ROOT, capture_output=True, text=True
+        )
+
+    def test_run_gates_on_the_threshold(self):
+        """Copied samples are flagged with exit code 1, no file of a language left out of them exits with 0."""
+        for name in ("alice", "bob"):
+            shutil.copytree(SAMPLES_DIRECTORY, self.directory / name, ignore=shutil.ignore_patterns("*.md"))
+        out = self.directory / "report.json"
+
+        flagged = self.command("run", "--dir", str(self.directory), "--threshold", "0.9", "--out", str(out))
+        clean = self.command("run", "--dir", str(self.directory), "--language", "markdown")
+
+        self.assertEqual(flagged.returncode, 1, flagged.stderr)
+        report = json.loads(out.read_text(encoding="utf-8"))
+        self.assertEqual(report["flagged_pairs"], 1)
+        self.assertGreaterEqual(report["pairs"][0]["overall_similarity"], 0.9)
+        self.assertEqual(clean.returncode, 0, clean.stderr)
+        self.assertEqual(clean.stdout, "")
+
+    def test_tokenize(self):
+        """Tokens of a file are printed one JSON object per line."""
+        result = self.command("tokenize", str(SAMPLES_DIRECTORY / "sample.py"))
+
+        self.assertEqual(result.returncode, 0, result.stderr)
+        tokens = [json.loads(line) for line in result.stdout.splitlines()]
+        self.assertTrue(tokens)
+        self.assertIn("identifier", {token["type"] for token in tokens})
+
+
+if __name__ == "__main__":
+    unittest.main()